@@ -0,0 +1,76 @@
+#
+# QuantumArb 2.0 - Benchmarks: Regression Check
+#
+# File: benches/check_baselines.py
+#
+# Description:
+# Compares the latest criterion results (target/criterion/<id>/new, under
+# CARGO_TARGET_DIR if set) against the committed baselines in
+# benches/baselines.json, and fails if any hot-path benchmark is slower than
+# its baseline by more than the tolerance, or is missing from the run.
+#
+#   cargo bench --bench pre_trade_risk --bench order_book \
+#       --bench monte_carlo --bench graph_cycles
+#   python3 benches/check_baselines.py            # check
+#   python3 benches/check_baselines.py --update   # re-record the baselines
+#
+# Baselines are only comparable on the hardware they were recorded on; re-record
+# them (and commit the result) when the CI runner class changes, or when a
+# change is meant to move a benchmark.
+#
+# Dependencies: none (Python 3 standard library).
+#
+
+import argparse
+import json
+import os
+import pathlib
+import sys
+
+ROOT = pathlib.Path(__file__).resolve().parent.parent
+BASELINES = ROOT / "benches" / "baselines.json"
+CRITERION = pathlib.Path(os.environ.get("CARGO_TARGET_DIR", ROOT / "target")) / "criterion"
+
+
+def latest_mean_ns(benchmark_id):
+    """Mean time per iteration from the latest run, or None if it did not run."""
+    estimates = CRITERION / benchmark_id / "new" / "estimates.json"
+    if not estimates.exists():
+        return None
+    return json.loads(estimates.read_text())["mean"]["point_estimate"]
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Check hot-path benchmarks against their baselines.")
+    parser.add_argument("--update", action="store_true", help="record the latest results as the new baselines")
+    args = parser.parse_args()
+
+    baselines = json.loads(BASELINES.read_text())
+    tolerance = baselines["tolerance"]
+    failures = []
+    for benchmark_id, baseline in sorted(baselines["benchmarks"].items()):
+        mean_ns = latest_mean_ns(benchmark_id)
+        if mean_ns is None:
+            failures.append(f"{benchmark_id}: no result in {CRITERION}")
+            continue
+        if args.update:
+            baseline["mean_ns"] = round(mean_ns, 1)
+            print(f"{benchmark_id:40} {mean_ns:14.1f} ns (recorded)")
+            continue
+        change = mean_ns / baseline["mean_ns"] - 1.0
+        verdict = "REGRESSED" if change > tolerance else "ok"
+        print(f"{benchmark_id:40} {mean_ns:14.1f} ns  baseline {baseline['mean_ns']:14.1f} ns  {change:+7.1%}  {verdict}")
+        if change > tolerance:
+            failures.append(f"{benchmark_id}: {change:+.1%} against the baseline (tolerance {tolerance:.0%})")
+
+    if args.update:
+        BASELINES.write_text(json.dumps(baselines, indent=2) + "\n")
+    if failures:
+        print("\nBenchmark check failed:", file=sys.stderr)
+        for failure in failures:
+            print(f"  - {failure}", file=sys.stderr)
+        sys.exit(1)
+
+
+if __name__ == "__main__":
+    main()
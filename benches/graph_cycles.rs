@@ -0,0 +1,64 @@
+/*
+ * QuantumArb 2.0 - Benchmarks: Graph Cycle Detection
+ *
+ * File: benches/graph_cycles.rs
+ *
+ * Description:
+ * Times the graph engine's arbitrage search (graph_engine/cycles.rs) on
+ * fully connected currency graphs of 10 and 40 currencies:
+ *
+ *   no_arbitrage/N   consistent rates with a bid/ask haircut, so the
+ *                    Bellman-Ford search runs to completion and finds nothing
+ *   arbitrage/N      the same graph with one mispriced rate, found as a
+ *                    three-currency cycle
+ */
+
+#[path = "../src/core_services/graph_engine/cycles.rs"]
+mod cycles;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use cycles::RateGraph;
+
+/// What a conversion loses to the spread on each leg.
+const HAIRCUT: f64 = 0.999;
+
+/// Every pair quoted both ways, from each currency's USD value.
+fn rate_graph(currencies: usize) -> RateGraph {
+    let value = |i: usize| 1.0 + i as f64 * 0.37;
+    let mut graph = RateGraph::default();
+    for from in 0..currencies {
+        for to in 0..currencies {
+            if from != to {
+                graph.set_rate(&currency(from), &currency(to), value(from) / value(to) * HAIRCUT);
+            }
+        }
+    }
+    graph
+}
+
+fn currency(i: usize) -> String {
+    format!("C{:02}", i)
+}
+
+fn bench_graph_cycles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("graph_cycles");
+    for currencies in [10, 40] {
+        let consistent = rate_graph(currencies);
+        assert!(consistent.find_arbitrage("C00").is_none());
+        group.bench_with_input(BenchmarkId::new("no_arbitrage", currencies), &consistent, |b, graph| {
+            b.iter(|| graph.find_arbitrage(black_box("C00")))
+        });
+
+        // C01 -> C02 quoted 2% rich: C00 -> C01 -> C02 -> C00 pays after haircuts.
+        let mut mispriced = rate_graph(currencies);
+        mispriced.set_rate("C01", "C02", 1.37 / 1.74 * 1.02);
+        assert!(mispriced.find_arbitrage("C00").is_some());
+        group.bench_with_input(BenchmarkId::new("arbitrage", currencies), &mispriced, |b, graph| {
+            b.iter(|| graph.find_arbitrage(black_box("C00")))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_graph_cycles);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+/*
+ * QuantumArb 2.0 - Benchmarks: Monte Carlo Path Generation
+ *
+ * File: benches/monte_carlo.rs
+ *
+ * Description:
+ * Times one VaR run of the VaR calculator's Monte Carlo engine
+ * (`quantumarb-risk`): 10,000 one-day paths over the calculator's two-asset
+ * portfolio, then the 99% percentile of the losses.
+ * One benchmark per return distribution, since sampling dominates the cost,
+ * on the scalar engine and again on the SIMD kernel:
+ *
+ *   normal / student_t / empirical
+ *   simd_normal / simd_student_t / simd_empirical
+ *
+ * and large_book (simd_large_book): 100 normal assets, where drawing the
+ * returns dominates. Built for AVX2 (RUSTFLAGS="-C target-cpu=x86-64-v3")
+ * the SIMD kernel runs it in about half the scalar engine's time; on the
+ * default SSE2 target the two are close.
+ *
+ * Draws come from a fixed-seed `quantumarb-sim` stream, as in a seeded run.
+ */
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use quantumarb_risk::distributions::{DistributionKind, ReturnSampler};
+use quantumarb_risk::monte_carlo::{self, SimulatedAsset, VarEngine, VarSampling};
+use quantumarb_sim::Seed;
+use rand_distr::{Distribution, Normal};
+
+const NUM_PATHS: usize = 10_000;
+const CONFIDENCE_LEVEL: f64 = 0.99;
+const HISTORY_DAYS: usize = 500;
+const LARGE_BOOK_ASSETS: usize = 100;
+const ENGINES: [(VarEngine, &str); 2] = [(VarEngine::Scalar, ""), (VarEngine::Simd, "simd_")];
+
+fn bench_monte_carlo(c: &mut Criterion) {
+    let seed = Seed::fixed(42);
+    // (quantity, price, daily volatility), as in the calculator's portfolio.
+    let portfolio = [(10.0, 60000.0, 0.02), (50.0, 3000.0, 0.03)];
+    let mut history_rng = seed.stream("bench.return_history");
+    let histories: Vec<Vec<f64>> = portfolio
+        .iter()
+        .map(|(_, _, volatility)| {
+            let daily = Normal::new(0.0, *volatility).unwrap();
+            (0..HISTORY_DAYS).map(|_| daily.sample(&mut history_rng)).collect()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("monte_carlo");
+    let kinds = [
+        ("normal", DistributionKind::Normal),
+        ("student_t", DistributionKind::StudentT),
+        ("empirical", DistributionKind::Empirical),
+    ];
+    for (name, kind) in kinds {
+        let samplers: Vec<ReturnSampler> = portfolio
+            .iter()
+            .zip(&histories)
+            .map(|((_, _, volatility), history)| ReturnSampler::fit(kind, history, *volatility))
+            .collect();
+        let assets: Vec<SimulatedAsset> = portfolio
+            .iter()
+            .zip(&samplers)
+            .map(|((quantity, price, _), sampler)| SimulatedAsset { quantity: *quantity, price: *price, sampler })
+            .collect();
+        for (engine, prefix) in ENGINES {
+            let sampling = VarSampling::plain(engine);
+            let mut rng = seed.stream("bench.monte_carlo");
+            group.bench_function(format!("{}{}", prefix, name), |b| {
+                b.iter(|| {
+                    monte_carlo::simulate_var_with_rates(sampling, &assets, None, NUM_PATHS, CONFIDENCE_LEVEL, &mut rng)
+                })
+            });
+        }
+    }
+
+    // A large book, where drawing the returns outweighs selecting the VaR.
+    let samplers: Vec<ReturnSampler> = (0..LARGE_BOOK_ASSETS)
+        .map(|asset| ReturnSampler::fit(DistributionKind::Normal, &[], 0.01 + 0.0002 * asset as f64))
+        .collect();
+    let assets: Vec<SimulatedAsset> =
+        samplers.iter().map(|sampler| SimulatedAsset { quantity: 100.0, price: 50.0, sampler }).collect();
+    for (engine, prefix) in ENGINES {
+        let sampling = VarSampling::plain(engine);
+        let mut rng = seed.stream("bench.monte_carlo");
+        group.bench_function(format!("{}large_book", prefix), |b| {
+            b.iter(|| {
+                monte_carlo::simulate_var_with_rates(sampling, &assets, None, NUM_PATHS, CONFIDENCE_LEVEL, &mut rng)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_monte_carlo);
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+/*
+ * QuantumArb 2.0 - Benchmarks: Order Book Update
+ *
+ * File: benches/order_book.rs
+ *
+ * Description:
+ * Times what every top-of-book update costs before a strategy sees it:
+ * decoding the binary BboUpdate off the bus (`quantumarb-wire`) and running
+ * the data quality monitor's per-tick checks (data_quality_monitor/checks.rs)
+ * for crossed, stale and outlier quotes.
+ *
+ *   decode          the wire decode alone
+ *   quality_check   the checks alone, on an instrument with a full
+ *                   volatility window
+ *   update          decode and check, as the monitor does per message
+ *
+ * The feed is a smooth price wave with strictly increasing timestamps, so
+ * every tick is clean and takes the full path through the checks.
+ */
+
+// The monitor's module is compiled in for its checks; the rest is unused here.
+#![allow(dead_code)]
+
+#[path = "../src/core_services/data_quality_monitor/checks.rs"]
+mod checks;
+
+use checks::{QualityConfig, QualityMonitor};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use quantumarb_wire::BboUpdate;
+use std::time::{Duration, Instant};
+
+/// Ticks in one period of the price wave; the feed repeats it.
+const WAVE_TICKS: u64 = 1000;
+const TICK_SPACING_NS: u64 = 1_000_000;
+/// How long after its exchange timestamp each tick arrives.
+const WIRE_LATENCY_NS: u64 = 50_000;
+
+fn config() -> QualityConfig {
+    QualityConfig {
+        stale_after_ns: 500_000_000,
+        outlier_sigmas: 6.0,
+        heartbeat_timeout: Duration::from_secs(5),
+        recovery_ticks: 10,
+    }
+}
+
+/// The `n`th tick of a BTC quote oscillating +-$50 around $60,000.
+fn tick(n: u64) -> BboUpdate {
+    let phase = (n % WAVE_TICKS) as f64 / WAVE_TICKS as f64 * std::f64::consts::TAU;
+    let mid = 60000_00 + (phase.sin() * 50_00.0).round() as i64;
+    BboUpdate {
+        instrument_id: 1,
+        best_bid_price: (mid - 5) as u64,
+        best_bid_size: 10,
+        best_ask_price: (mid + 5) as u64,
+        best_ask_size: 12,
+        timestamp_ns: 1_700_000_000_000_000_000 + n * TICK_SPACING_NS,
+        venue_id: 1,
+    }
+}
+
+/// A monitor that has already seen a full window of ticks. The first ticks
+/// may trip the outlier check while the volatility estimate builds up; by
+/// the end of warm-up every tick is clean.
+fn warm_monitor() -> (QualityMonitor, u64) {
+    let mut monitor = QualityMonitor::new(config());
+    let now = Instant::now();
+    for n in 0..WAVE_TICKS {
+        let bbo = tick(n);
+        monitor.on_tick(&bbo, bbo.timestamp_ns + WIRE_LATENCY_NS, now);
+    }
+    for n in WAVE_TICKS..2 * WAVE_TICKS {
+        let bbo = tick(n);
+        let alert = monitor.on_tick(&bbo, bbo.timestamp_ns + WIRE_LATENCY_NS, now);
+        assert!(alert.is_none(), "warm-up tick {} raised {:?}", n, alert);
+    }
+    (monitor, 2 * WAVE_TICKS)
+}
+
+fn bench_order_book(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_book");
+
+    let payload = quantumarb_wire::encode(&tick(0));
+    group.bench_function("decode", |b| {
+        b.iter(|| quantumarb_wire::decode::<BboUpdate>(black_box(&payload)).unwrap())
+    });
+
+    let (mut monitor, mut n) = warm_monitor();
+    let now = Instant::now();
+    group.bench_function("quality_check", |b| {
+        b.iter_batched(
+            || {
+                n += 1;
+                tick(n)
+            },
+            |bbo| monitor.on_tick(&bbo, bbo.timestamp_ns + WIRE_LATENCY_NS, now),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+
+    let (mut monitor, mut n) = warm_monitor();
+    group.bench_function("update", |b| {
+        b.iter_batched(
+            || {
+                n += 1;
+                quantumarb_wire::encode(&tick(n))
+            },
+            |payload| {
+                let bbo: BboUpdate = quantumarb_wire::decode(&payload).unwrap();
+                monitor.on_tick(&bbo, bbo.timestamp_ns + WIRE_LATENCY_NS, now)
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_order_book);
+criterion_main!(benches);
@@ -0,0 +1,120 @@
+/*
+ * QuantumArb 2.0 - Benchmarks: Pre-Trade Risk Check
+ *
+ * File: benches/pre_trade_risk.rs
+ *
+ * Description:
+ * Times the risk gateway's order checks (`quantumarb-risk`) with limits
+ * and exposures already loaded, as they are on the order path: instrument
+ * tick and lot size, dynamic order size, concentration caps and counterparty
+ * credit. Redis and the network are not part of the measurement.
+ *
+ *   approved                 an order that passes every check
+ *   rejected_concentration   rejected at the concentration caps, the most
+ *                            expensive rejection
+ *   rejected_instrument      rejected by the first check
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use quantumarb_money::{Money, Price};
+use quantumarb_refdata::ReferenceData;
+use quantumarb_risk::checks::{self, OrderLimits};
+use quantumarb_risk::concentration::{ConcentrationLimits, Exposures};
+use quantumarb_risk::counterparty::CounterpartyLimits;
+use quantumarb_types::{CounterpartyExposure, PortfolioSnapshot, Position, SettlementModel};
+use quantumarb_wire::{HopStamps, OrderPriority, OrderRequest, OrderSide, TradingMode};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// $1.05M gross: 300k BTC at VENUE_A, 300k ETH at VENUE_B, 450k ESZ25 at CME.
+fn exposures() -> Exposures {
+    let position = |symbol: &str, quantity: i64, price: f64, multiplier: f64, venue: &str| Position {
+        symbol: symbol.to_string(),
+        quantity,
+        current_market_price: Price::from_f64(price),
+        multiplier: Money::from_f64(multiplier),
+        venue_quantities: HashMap::from([(venue.to_string(), quantity)]),
+        ..Position::default()
+    };
+    let positions = [
+        position("BTC", 5, 60000.0, 1.0, "VENUE_A"),
+        position("ETH", 100, 3000.0, 1.0, "VENUE_B"),
+        position("ESZ25", 2, 4500.0, 50.0, "CME"),
+    ];
+    let snapshot = PortfolioSnapshot {
+        positions: positions.into_iter().map(|p| (p.symbol.clone(), p)).collect(),
+        ..PortfolioSnapshot::default()
+    };
+    Exposures::from_snapshot(&snapshot)
+}
+
+fn counterparty_exposures() -> HashMap<String, CounterpartyExposure> {
+    let exposure = |counterparty: &str, settlement_model, total_exposure| CounterpartyExposure {
+        counterparty: counterparty.to_string(),
+        settlement_model,
+        position_exposure: Money::from_f64(total_exposure),
+        unsettled_exposure: Money::ZERO,
+        total_exposure: Money::from_f64(total_exposure),
+    };
+    [
+        exposure("VENUE_A", SettlementModel::NonDvp { settlement_days: 1 }, 300_000.0),
+        exposure("VENUE_B", SettlementModel::NonDvp { settlement_days: 1 }, 300_000.0),
+        exposure("CME", SettlementModel::Dvp, 450_000.0),
+    ]
+    .into_iter()
+    .map(|e| (e.counterparty.clone(), e))
+    .collect()
+}
+
+fn order(instrument_id: u32, price: u64, size: u32, venue_id: u32) -> OrderRequest {
+    OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id: 101,
+        instrument_id,
+        side: OrderSide::Buy,
+        price,
+        size,
+        stamps: HopStamps::default(),
+        venue_id,
+        mode: TradingMode::Live,
+        priority: OrderPriority::Opportunistic,
+        strategy_id: String::new(),
+    }
+}
+
+fn bench_pre_trade_risk(c: &mut Criterion) {
+    let instruments = ReferenceData::seeded();
+    let concentration_limits = ConcentrationLimits::default();
+    let exposures = exposures();
+    let limit = Money::from_f64(5_000_000.0);
+    let counterparty_limits: CounterpartyLimits =
+        HashMap::from([("VENUE_A".to_string(), limit), ("VENUE_B".to_string(), limit)]);
+    let counterparty_exposures = counterparty_exposures();
+    let limits = OrderLimits {
+        instruments: &instruments,
+        max_order_size: 100,
+        concentration: &concentration_limits,
+        exposures: Some(&exposures),
+        counterparty: &counterparty_limits,
+        counterparty_exposures: &counterparty_exposures,
+    };
+
+    let approved = order(2, 3000_00, 1, 1);
+    let rejected_concentration = order(1, 60000_00, 2, 1);
+    let rejected_instrument = order(99, 100_00, 1, 1);
+    assert!(checks::check_order(&limits, &approved).is_ok());
+    assert!(checks::check_order(&limits, &rejected_concentration).is_err());
+
+    let mut group = c.benchmark_group("pre_trade_risk");
+    group.bench_function("approved", |b| b.iter(|| checks::check_order(&limits, black_box(&approved))));
+    group.bench_function("rejected_concentration", |b| {
+        b.iter(|| checks::check_order(&limits, black_box(&rejected_concentration)))
+    });
+    group.bench_function("rejected_instrument", |b| {
+        b.iter(|| checks::check_order(&limits, black_box(&rejected_instrument)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_pre_trade_risk);
+criterion_main!(benches);
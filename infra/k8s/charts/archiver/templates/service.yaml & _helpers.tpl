@@ -0,0 +1,53 @@
+# File: infra/k8s/charts/archiver/templates/service.yaml
+apiVersion: v1
+kind: Service
+metadata:
+  name: {{ include "archiver.fullname" . }}
+  labels:
+    {{- include "archiver.labels" . | nindent 4 }}
+spec:
+  type: {{ .Values.service.type }}
+  ports:
+    - port: {{ .Values.service.port }}
+      targetPort: {{ .Values.service.targetPort }}
+      protocol: TCP
+      name: http
+  selector:
+    {{- include "archiver.selectorLabels" . | nindent 4 }}
+---
+# File: infra/k8s/charts/archiver/templates/_helpers.tpl
+{{- define "archiver.name" -}}
+{{- default .Chart.Name .Values.nameOverride | trunc 63 | trimSuffix "-" }}
+{{- end }}
+
+{{- define "archiver.fullname" -}}
+{{- if .Values.fullnameOverride }}
+{{- .Values.fullnameOverride | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- $name := default .Chart.Name .Values.nameOverride }}
+{{- if contains $name .Release.Name }}
+{{- .Release.Name | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- printf "%s-%s" .Release.Name $name | trunc 63 | trimSuffix "-" }}
+{{- end }}
+{{- end }}
+{{- end }}
+
+{{- define "archiver.labels" -}}
+helm.sh/chart: {{ include "archiver.name" . }}-{{ .Chart.Version | replace "+" "_" }}
+{{ include "archiver.selectorLabels" . }}
+app.kubernetes.io/managed-by: {{ .Release.Service }}
+{{- end }}
+
+{{- define "archiver.selectorLabels" -}}
+app.kubernetes.io/name: {{ include "archiver.name" . }}
+app.kubernetes.io/instance: {{ .Release.Name }}
+{{- end }}
+
+{{- define "archiver.serviceAccountName" -}}
+{{- if .Values.serviceAccount.create }}
+{{- default (include "archiver.fullname" .) .Values.serviceAccount.name }}
+{{- else }}
+{{- default "default" .Values.serviceAccount.name }}
+{{- end }}
+{{- end }}
@@ -0,0 +1,53 @@
+# File: infra/k8s/charts/data-quality-monitor/templates/service.yaml
+apiVersion: v1
+kind: Service
+metadata:
+  name: {{ include "data-quality-monitor.fullname" . }}
+  labels:
+    {{- include "data-quality-monitor.labels" . | nindent 4 }}
+spec:
+  type: {{ .Values.service.type }}
+  ports:
+    - port: {{ .Values.service.port }}
+      targetPort: {{ .Values.service.targetPort }}
+      protocol: TCP
+      name: http
+  selector:
+    {{- include "data-quality-monitor.selectorLabels" . | nindent 4 }}
+---
+# File: infra/k8s/charts/data-quality-monitor/templates/_helpers.tpl
+{{- define "data-quality-monitor.name" -}}
+{{- default .Chart.Name .Values.nameOverride | trunc 63 | trimSuffix "-" }}
+{{- end }}
+
+{{- define "data-quality-monitor.fullname" -}}
+{{- if .Values.fullnameOverride }}
+{{- .Values.fullnameOverride | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- $name := default .Chart.Name .Values.nameOverride }}
+{{- if contains $name .Release.Name }}
+{{- .Release.Name | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- printf "%s-%s" .Release.Name $name | trunc 63 | trimSuffix "-" }}
+{{- end }}
+{{- end }}
+{{- end }}
+
+{{- define "data-quality-monitor.labels" -}}
+helm.sh/chart: {{ include "data-quality-monitor.name" . }}-{{ .Chart.Version | replace "+" "_" }}
+{{ include "data-quality-monitor.selectorLabels" . }}
+app.kubernetes.io/managed-by: {{ .Release.Service }}
+{{- end }}
+
+{{- define "data-quality-monitor.selectorLabels" -}}
+app.kubernetes.io/name: {{ include "data-quality-monitor.name" . }}
+app.kubernetes.io/instance: {{ .Release.Name }}
+{{- end }}
+
+{{- define "data-quality-monitor.serviceAccountName" -}}
+{{- if .Values.serviceAccount.create }}
+{{- default (include "data-quality-monitor.fullname" .) .Values.serviceAccount.name }}
+{{- else }}
+{{- default "default" .Values.serviceAccount.name }}
+{{- end }}
+{{- end }}
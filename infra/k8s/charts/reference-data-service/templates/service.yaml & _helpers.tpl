@@ -0,0 +1,53 @@
+# File: infra/k8s/charts/reference-data-service/templates/service.yaml
+apiVersion: v1
+kind: Service
+metadata:
+  name: {{ include "reference-data-service.fullname" . }}
+  labels:
+    {{- include "reference-data-service.labels" . | nindent 4 }}
+spec:
+  type: {{ .Values.service.type }}
+  ports:
+    - port: {{ .Values.service.port }}
+      targetPort: {{ .Values.service.targetPort }}
+      protocol: TCP
+      name: http
+  selector:
+    {{- include "reference-data-service.selectorLabels" . | nindent 4 }}
+---
+# File: infra/k8s/charts/reference-data-service/templates/_helpers.tpl
+{{- define "reference-data-service.name" -}}
+{{- default .Chart.Name .Values.nameOverride | trunc 63 | trimSuffix "-" }}
+{{- end }}
+
+{{- define "reference-data-service.fullname" -}}
+{{- if .Values.fullnameOverride }}
+{{- .Values.fullnameOverride | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- $name := default .Chart.Name .Values.nameOverride }}
+{{- if contains $name .Release.Name }}
+{{- .Release.Name | trunc 63 | trimSuffix "-" }}
+{{- else }}
+{{- printf "%s-%s" .Release.Name $name | trunc 63 | trimSuffix "-" }}
+{{- end }}
+{{- end }}
+{{- end }}
+
+{{- define "reference-data-service.labels" -}}
+helm.sh/chart: {{ include "reference-data-service.name" . }}-{{ .Chart.Version | replace "+" "_" }}
+{{ include "reference-data-service.selectorLabels" . }}
+app.kubernetes.io/managed-by: {{ .Release.Service }}
+{{- end }}
+
+{{- define "reference-data-service.selectorLabels" -}}
+app.kubernetes.io/name: {{ include "reference-data-service.name" . }}
+app.kubernetes.io/instance: {{ .Release.Name }}
+{{- end }}
+
+{{- define "reference-data-service.serviceAccountName" -}}
+{{- if .Values.serviceAccount.create }}
+{{- default (include "reference-data-service.fullname" .) .Values.serviceAccount.name }}
+{{- else }}
+{{- default "default" .Values.serviceAccount.name }}
+{{- end }}
+{{- end }}
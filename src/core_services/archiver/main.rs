@@ -0,0 +1,349 @@
+/*
+ * QuantumArb 2.0 - Core Services: Archiver
+ *
+ * File: src/core_services/archiver/main.rs
+ *
+ * Description:
+ * This microservice keeps a durable copy of the platform's bus traffic for
+ * research and backtesting. It subscribes to the market data, order,
+ * execution report and alternative data topics and writes what it sees to
+ * S3-compatible object storage as zstd-compressed Parquet files, partitioned
+ * by dataset, date and hour (layout and schema live in `quantumarb-archive`,
+ * which the market replay service uses to read the files back).
+ *
+ * Its primary role is to:
+ * 1. Subscribe to the archived topics and buffer messages per partition.
+ * 2. Write a partition's buffer out as a new file when it reaches
+ *    QA_ARCHIVE_MAX_ROWS rows, when traffic moves on to the next hour (or
+ *    day), and every QA_ARCHIVE_FLUSH_SECS seconds.
+ * 3. Keep a buffer whose upload failed and retry it on the next flush, so an
+ *    object store outage delays the archive rather than losing data.
+ *
+ * Payloads are stored exactly as published, bus envelope included, so a
+ * replay republishes the original message ids; the archiver only decodes
+ * them to fill the instrument_id column. Execution reports don't carry an
+ * instrument, so it is taken from the order request seen for the same order.
+ *
+ * Progress is served on GET /archive/status; GET /openapi.json serves the
+ * OpenAPI document, browsable on GET /docs.
+ */
+
+use chrono::{DateTime, Utc};
+use object_store::ObjectStore;
+use quantumarb_archive::{ArchiveRecord, Partitioning};
+use quantumarb_bus::{topics, BusMessage};
+use quantumarb_money::Money;
+use quantumarb_openapi::{ApiDoc, ApiSchema};
+use quantumarb_wire::{
+    utc_now_ns, BboUpdate, Encoding, ExecutionReport, HopStamps, Liquidity, OrderPriority, OrderRequest, OrderSide,
+    OrderStatus, TradingMode,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+use warp::Filter;
+
+// --- Data Structures ---
+
+/// Archiving progress, served on GET /archive/status.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+struct ArchiveStatus {
+    messages_received: u64,
+    rows_buffered: usize,
+    open_partitions: usize,
+    files_written: u64,
+    rows_written: u64,
+    bytes_written: u64,
+    failed_uploads: u64,
+    last_file: Option<String>,
+    last_flush_utc: Option<DateTime<Utc>>,
+}
+
+type SharedStatus = Arc<Mutex<ArchiveStatus>>;
+
+/// Buffers messages per partition and writes them to the store.
+struct Archiver {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    partitioning: Partitioning,
+    max_rows: usize,
+    /// Partition path -> rows not yet written.
+    buffers: HashMap<String, Vec<ArchiveRecord>>,
+    /// Open orders -> instrument, for tagging their execution reports.
+    order_instruments: HashMap<Uuid, u32>,
+    status: SharedStatus,
+}
+
+const DEFAULT_MAX_ROWS: usize = 100_000;
+const DEFAULT_FLUSH_SECS: u64 = 60;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Archiver ---");
+
+    let store = match quantumarb_archive::open_store() {
+        Ok(store) => store,
+        Err(e) => {
+            println!("Could not open the archive store: {}", e);
+            return;
+        }
+    };
+    let status: SharedStatus = Arc::new(Mutex::new(ArchiveStatus::default()));
+    let archiver = Archiver {
+        store,
+        prefix: quantumarb_archive::prefix_from_env(),
+        partitioning: Partitioning::from_env(),
+        max_rows: env_or("QA_ARCHIVE_MAX_ROWS", DEFAULT_MAX_ROWS),
+        buffers: HashMap::new(),
+        order_instruments: HashMap::new(),
+        status: status.clone(),
+    };
+    println!("Archiving to '{}' with {:?} partitions.", archiver.prefix, archiver.partitioning);
+
+    let flush_secs = env_or("QA_ARCHIVE_FLUSH_SECS", DEFAULT_FLUSH_SECS);
+    tokio::spawn(async move {
+        run_archiver(archiver, flush_secs).await;
+    });
+
+    // --- API Endpoint for archiving progress ---
+    let get_status = warp::path!("archive" / "status")
+        .and(warp::get())
+        .and(with_state(status))
+        .and_then(handler_get_status);
+
+    println!("API server running at http://127.0.0.1:3037/archive/status");
+    warp::serve(get_status.or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3037)).await;
+}
+
+/// A positive number from the environment, or `default`.
+fn env_or<T: std::str::FromStr + PartialOrd + Default>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v| *v > T::default()).unwrap_or(default)
+}
+
+/// The OpenAPI document of the archiving endpoint.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Archiver", "Progress of the bus traffic archive.")
+        .get::<ArchiveStatus>("/archive/status", "Archiving progress")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /archive/status.
+async fn handler_get_status(status: SharedStatus) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = status.lock().unwrap().clone();
+    Ok(warp::reply::json(&status))
+}
+
+/// Receives bus traffic and flushes every partition on a timer.
+async fn run_archiver(mut archiver: Archiver, flush_secs: u64) {
+    let encoding = Encoding::from_env();
+    // In a real system these are NATS subscriptions to "market_data.>",
+    // topics::ORDER_REQUESTS, topics::EXECUTION_REPORTS and topics::ALT_DATA_NORMALIZED.
+    let mut bus = time::interval(Duration::from_secs(1));
+    let mut flush_timer = time::interval(Duration::from_secs(flush_secs));
+    flush_timer.tick().await; // The first tick completes immediately.
+
+    loop {
+        tokio::select! {
+            _ = bus.tick() => {
+                for message in simulated_bus_traffic(encoding) {
+                    archiver.receive(message, Utc::now()).await;
+                }
+            }
+            _ = flush_timer.tick() => {
+                let partitions: Vec<String> = archiver.buffers.keys().cloned().collect();
+                for partition in partitions {
+                    archiver.flush(&partition).await;
+                }
+            }
+        }
+    }
+}
+
+impl Archiver {
+    /// Buffers one message, writing out its partition if it is full and any
+    /// earlier partition of the same dataset once traffic has moved past it.
+    async fn receive(&mut self, message: BusMessage, received_utc: DateTime<Utc>) {
+        self.status.lock().unwrap().messages_received += 1;
+        let Some(dataset) = quantumarb_archive::dataset_for_topic(&message.topic) else {
+            return;
+        };
+        let partition = quantumarb_archive::partition_path(&self.prefix, dataset, received_utc, self.partitioning);
+
+        if !self.buffers.contains_key(&partition) {
+            let dataset_prefix = format!("{}/{}/", self.prefix, dataset);
+            let finished: Vec<String> =
+                self.buffers.keys().filter(|p| p.starts_with(&dataset_prefix)).cloned().collect();
+            for previous in finished {
+                self.flush(&previous).await;
+            }
+        }
+
+        let record = ArchiveRecord {
+            instrument_id: self.instrument_of(&message),
+            event_time_ns: received_utc.timestamp_nanos_opt().unwrap_or_default(),
+            content_type: content_type_of(message.body()).to_string(),
+            topic: message.topic,
+            payload: message.payload,
+        };
+        let buffer = self.buffers.entry(partition.clone()).or_default();
+        buffer.push(record);
+        let full = buffer.len() >= self.max_rows;
+        self.update_buffer_status();
+
+        if full {
+            self.flush(&partition).await;
+        }
+    }
+
+    /// The instrument a message refers to, if any.
+    fn instrument_of(&mut self, message: &BusMessage) -> Option<u32> {
+        if message.topic.starts_with(topics::MARKET_DATA_PREFIX) {
+            return Encoding::decode_any::<BboUpdate>(message.body()).ok().map(|bbo| bbo.instrument_id);
+        }
+        match message.topic.as_str() {
+            topics::ORDER_REQUESTS => {
+                let order = Encoding::decode_any::<OrderRequest>(message.body()).ok()?;
+                self.order_instruments.insert(order.order_id, order.instrument_id);
+                Some(order.instrument_id)
+            }
+            topics::EXECUTION_REPORTS => {
+                let report = Encoding::decode_any::<ExecutionReport>(message.body()).ok()?;
+                match report.status {
+                    OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::RejectedByExchange => {
+                        self.order_instruments.remove(&report.internal_order_id)
+                    }
+                    _ => self.order_instruments.get(&report.internal_order_id).copied(),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes a partition's buffer out as one file. On failure the rows stay
+    /// buffered and are retried with the next flush.
+    async fn flush(&mut self, partition: &str) {
+        let Some(rows) = self.buffers.remove(partition) else {
+            return;
+        };
+        if rows.is_empty() {
+            return;
+        }
+
+        let result = match quantumarb_archive::encode_parquet(&rows) {
+            Ok(bytes) => {
+                let size = bytes.len();
+                let file_id = Uuid::new_v4().simple().to_string();
+                quantumarb_archive::put_file(&*self.store, partition, &file_id, bytes)
+                    .await
+                    .map(|path| (path, size))
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok((path, size)) => {
+                println!("  -> Archived {} row(s) to '{}' ({} bytes).", rows.len(), path, size);
+                let mut status = self.status.lock().unwrap();
+                status.files_written += 1;
+                status.rows_written += rows.len() as u64;
+                status.bytes_written += size as u64;
+                status.last_file = Some(path.to_string());
+                status.last_flush_utc = Some(Utc::now());
+            }
+            Err(e) => {
+                println!("  -> Failed to archive {} row(s) for '{}', will retry: {}", rows.len(), partition, e);
+                self.status.lock().unwrap().failed_uploads += 1;
+                self.buffers.entry(partition.to_string()).or_default().splice(0..0, rows);
+            }
+        }
+        self.update_buffer_status();
+    }
+
+    fn update_buffer_status(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.rows_buffered = self.buffers.values().map(Vec::len).sum();
+        status.open_partitions = self.buffers.len();
+    }
+}
+
+/// Bus payloads carry no content type; JSON is recognised by its leading '{'
+/// exactly as `Encoding::decode_any` does.
+fn content_type_of(payload: &[u8]) -> &'static str {
+    if payload.first() == Some(&b'{') {
+        Encoding::Json.content_type()
+    } else {
+        Encoding::Binary.content_type()
+    }
+}
+
+/// One second of simulated bus traffic on the archived topics.
+fn simulated_bus_traffic(encoding: Encoding) -> Vec<BusMessage> {
+    let now_ns = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    let bbo = |instrument_id, bid, ask| BboUpdate {
+        instrument_id,
+        best_bid_price: bid,
+        best_ask_price: ask,
+        best_bid_size: 10,
+        best_ask_size: 12,
+        timestamp_ns: now_ns,
+        venue_id: 0,
+    };
+    let order = OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id: 101,
+        instrument_id: 1,
+        side: OrderSide::Buy,
+        price: 60000_15,
+        size: 2,
+        stamps: HopStamps::default(),
+        venue_id: 1,
+        mode: TradingMode::Sandbox,
+        priority: OrderPriority::Opportunistic,
+        strategy_id: String::new(),
+    };
+    let report = ExecutionReport {
+        exchange_order_id: format!("EXCH-{}", Uuid::new_v4().simple()),
+        exec_id: Uuid::new_v4().simple().to_string(),
+        internal_order_id: order.order_id,
+        status: OrderStatus::Filled,
+        filled_size: order.size,
+        filled_price: order.price,
+        reject: None,
+        stamps: HopStamps::default(),
+        mode: TradingMode::Sandbox,
+        cumulative_size: order.size,
+        leaves_size: 0,
+        liquidity: Some(Liquidity::Taker),
+        fee: Money::ZERO,
+        transact_time_ns: utc_now_ns(),
+        exec_ref_id: String::new(),
+    };
+    let alt_data = serde_json::json!({
+        "event_id": Uuid::new_v4().to_string(),
+        "source_type": "news",
+        "source_name": "FinancialWire",
+        "content": "Tech Giant 'Innovate Inc.' Announces Breakthrough in Chip Technology",
+        "metadata": { "sentiment_score": "0.75", "related_symbols": "INVT,CHIP,SEMI" },
+        "timestamp_utc": Utc::now().to_rfc3339(),
+    });
+    let message = |topic: &str, payload: Vec<u8>| BusMessage { seq: 0, topic: topic.to_string(), payload };
+
+    vec![
+        message(&topics::market_data(1), encoding.encode(&bbo(1, 60000_05, 60000_15))),
+        message(&topics::market_data(2), encoding.encode(&bbo(2, 3000_10, 3000_22))),
+        message(topics::ORDER_REQUESTS, encoding.encode(&order)),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report)),
+        message(topics::ALT_DATA_NORMALIZED, alt_data.to_string().into_bytes()),
+    ]
+}
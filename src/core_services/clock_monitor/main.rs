@@ -0,0 +1,189 @@
+/*
+ * QuantumArb 2.0 - Core Services: Clock Monitor
+ *
+ * File: src/core_services/clock_monitor/main.rs
+ *
+ * Description:
+ * This microservice watches the clocks the platform's timestamps come from.
+ * Latencies are measured between stamps taken on different hosts, so a host
+ * whose clock is off by 500µs makes every hop through it 500µs longer or
+ * shorter than it was; the clock monitor says when that can be happening.
+ *
+ * Its primary role is to:
+ * 1. Poll every host's clock source each QA_CLOCK_POLL_SECS (see
+ *    sources.rs) for its offset from its reference.
+ * 2. Track each host's drift and the fleet's skew, and judge them against
+ *    the thresholds (see skew.rs).
+ * 3. Publish every poll's picture on 'clock.skew', and each change of level
+ *    on 'alerts.clock_skew'.
+ * 4. Notify on the routes configured in QA_NOTIFY_CONFIG_PATH when a host or
+ *    the fleet becomes CRITICAL (see `quantumarb-notify`).
+ * 5. Answer other clock monitors' probes on QA_CLOCK_PROBE_BIND.
+ *
+ * API:
+ *   GET /clocks          every host's offset, drift and level, and the skew
+ *   GET /clocks/alerts   the changes of level, newest first
+ * GET /openapi.json serves the OpenAPI document, browsable on GET /docs.
+ *
+ * Configuration (environment):
+ *   QA_CLOCK_HOSTS                 "host=source;..." (sources in sources.rs);
+ *                                  by default four synthetic hosts
+ *   QA_CLOCK_POLL_SECS=1           time between polls
+ *   QA_CLOCK_PROBE_BIND=0.0.0.0:3148
+ *                                  where probes are answered
+ *
+ * With --seed N (or QA_SEED) the synthetic clocks are seeded, so every run
+ * wanders the same way.
+ */
+
+mod skew;
+mod sources;
+
+use chrono::Utc;
+use quantumarb_bus::topics;
+use quantumarb_notify::{EventClass, Notifier};
+use quantumarb_openapi::ApiDoc;
+use quantumarb_sim::Seed;
+use skew::{ClockAlert, ClockLevel, ClockMonitor, ClockStatus, SkewConfig};
+use sources::ClockSource;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+const DEFAULT_HOSTS: &str = "exchange-gateway=synthetic;strategy-engine=synthetic;\
+                             market-data=synthetic;risk-gateway=synthetic";
+const DEFAULT_PROBE_BIND: &str = "0.0.0.0:3148";
+
+type SharedMonitor = Arc<Mutex<ClockMonitor>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Clock Monitor ---");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let config = SkewConfig::from_env();
+    println!("Thresholds: {:?}", config);
+    let poll_secs = std::env::var("QA_CLOCK_POLL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &u64| *v > 0)
+        .unwrap_or(1);
+
+    // Each host's clock source.
+    let mut monitor = ClockMonitor::new(config);
+    let mut hosts: Vec<(String, Box<dyn ClockSource>)> = Vec::new();
+    let spec = std::env::var("QA_CLOCK_HOSTS").unwrap_or_else(|_| DEFAULT_HOSTS.to_string());
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((host, source)) = entry.split_once('=') else {
+            println!("Ignoring QA_CLOCK_HOSTS entry '{}': expected host=source", entry);
+            continue;
+        };
+        let rng = seed.stream(&format!("clock_monitor.{}", host));
+        match sources::open_source(source, rng) {
+            Ok(source) => {
+                println!("Watching {}'s clock with {}", host, source.describe());
+                monitor.add_host(host, source.describe(), source.kind());
+                hosts.push((host.to_string(), source));
+            }
+            Err(e) => println!("Cannot watch {}'s clock: {}", host, e),
+        }
+    }
+    let state: SharedMonitor = Arc::new(Mutex::new(monitor));
+
+    let probe_bind = std::env::var("QA_CLOCK_PROBE_BIND").unwrap_or_else(|_| DEFAULT_PROBE_BIND.to_string());
+    match sources::serve_probes(&probe_bind) {
+        Ok(()) => println!("Answering clock probes on {}", probe_bind),
+        Err(e) => println!("Cannot answer clock probes on {}: {}", probe_bind, e),
+    }
+
+    // Poll on a thread of its own: sources may block while they read.
+    let polling_state = state.clone();
+    let notifier = Notifier::from_env("clock-monitor");
+    tokio::task::spawn_blocking(move || {
+        poll_clocks(polling_state, hosts, Duration::from_secs(poll_secs), notifier);
+    });
+
+    // --- API Endpoints ---
+    let get_clocks = warp::path!("clocks")
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_get_clocks);
+    let get_alerts = warp::path!("clocks" / "alerts")
+        .and(warp::get())
+        .and(with_state(state))
+        .and_then(handler_get_alerts);
+
+    println!("API server running at http://127.0.0.1:3048 (/clocks, /clocks/alerts)");
+    let routes = get_clocks.or(get_alerts).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3048)).await;
+}
+
+/// The OpenAPI document of the clock endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Clock Monitor", "Host clocks' offset and drift, and the skew between them.")
+        .get::<ClockStatus>("/clocks", "Every host's clock and the fleet's skew")
+        .get::<Vec<ClockAlert>>("/clocks/alerts", "Changes of level, newest first")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /clocks.
+async fn handler_get_clocks(state: SharedMonitor) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = state.lock().unwrap().status(Utc::now());
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /clocks/alerts.
+async fn handler_get_alerts(state: SharedMonitor) -> Result<impl warp::Reply, warp::Rejection> {
+    let alerts = state.lock().unwrap().alerts();
+    Ok(warp::reply::json(&alerts))
+}
+
+/// Polls every host's clock, then publishes the picture and any change of
+/// level. Runs on a blocking thread of the runtime, so it may notify.
+fn poll_clocks(
+    state: SharedMonitor,
+    mut hosts: Vec<(String, Box<dyn ClockSource>)>,
+    every: Duration,
+    notifier: Notifier,
+) {
+    let mut next_poll = Instant::now();
+    loop {
+        next_poll += every;
+        std::thread::sleep(next_poll.saturating_duration_since(Instant::now()));
+
+        // Read before taking the lock, so the API is never kept waiting on a source.
+        let polled: Vec<_> = hosts.iter_mut().map(|(host, source)| (host.clone(), source.poll())).collect();
+
+        let now = Utc::now();
+        let mut monitor = state.lock().unwrap();
+        for (host, reading) in polled {
+            monitor.record(&host, reading, now);
+        }
+        let alerts = monitor.evaluate(now);
+        let status = monitor.status(now);
+        drop(monitor);
+
+        quantumarb_bus::publish_json(topics::CLOCK_SKEW, &status);
+        for alert in alerts {
+            println!("  -> Clock {}: {:?} -> {:?} ({})", alert.scope, alert.previous, alert.level, alert.reason);
+            quantumarb_bus::publish_json(topics::CLOCK_SKEW_ALERTS, &alert);
+            if alert.level == ClockLevel::Critical {
+                notifier.notify(
+                    notifier
+                        .event(EventClass::ClockSkew)
+                        .field("scope", &alert.scope)
+                        .field("level", "CRITICAL")
+                        .field("reason", &alert.reason),
+                );
+            }
+        }
+    }
+}
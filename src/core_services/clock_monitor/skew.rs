@@ -0,0 +1,371 @@
+/*
+ * QuantumArb 2.0 - Core Services: Clock Skew Tracking
+ *
+ * File: src/core_services/clock_monitor/skew.rs
+ *
+ * Description:
+ * Keeps each host's recent clock readings and judges them. A host's drift
+ * is the least-squares slope of its offsets over the window, in ns per
+ * second (ppb): what its clock is gaining on the reference now, whatever
+ * its servo reports. The fleet skew is the spread between the most ahead
+ * and the most behind of the hosts with a fresh, synced reading, which is
+ * the error a latency measured between two of them can carry.
+ *
+ * Each host, and the fleet, is at one level:
+ *
+ *   CRITICAL   the offset (the skew, for the fleet) is at or over
+ *              `critical_ns`; or the host is not synced to its reference,
+ *              or has given no reading for `stale_after`
+ *   WARNING    the offset (skew) is at or over `warn_ns`, or the host
+ *              drifts by `drift_warn_ppb` or more
+ *   OK         otherwise
+ *
+ * Every change of level, recoveries included, is an alert; the monitor
+ * publishes it and notifies on CRITICAL (see main.rs).
+ *
+ * Configuration (environment):
+ *   QA_CLOCK_WARN_US=100          offset or skew that warns
+ *   QA_CLOCK_CRITICAL_US=1000     offset or skew that is critical
+ *   QA_CLOCK_DRIFT_WARN_PPB=1000  drift that warns
+ *   QA_CLOCK_STALE_SECS=10        time without a reading that is critical
+ *   QA_CLOCK_WINDOW=60            readings kept per host for the drift
+ */
+
+use chrono::{DateTime, Duration, Utc};
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::sources::{ClockReading, SourceKind};
+
+/// Alerts kept for GET /clocks/alerts.
+const ALERT_RETENTION: usize = 1_000;
+/// The scope of the fleet's alerts.
+pub const FLEET: &str = "fleet";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SkewConfig {
+    pub warn_ns: i64,
+    pub critical_ns: i64,
+    pub drift_warn_ppb: f64,
+    pub stale_after: Duration,
+    pub window: usize,
+}
+
+impl SkewConfig {
+    pub fn from_env() -> SkewConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        SkewConfig {
+            warn_ns: var("QA_CLOCK_WARN_US", 100i64) * 1_000,
+            critical_ns: var("QA_CLOCK_CRITICAL_US", 1_000i64) * 1_000,
+            drift_warn_ppb: var("QA_CLOCK_DRIFT_WARN_PPB", 1_000.0),
+            stale_after: Duration::seconds(var("QA_CLOCK_STALE_SECS", 10i64).max(1)),
+            window: var("QA_CLOCK_WINDOW", 60usize).max(3),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ClockLevel {
+    Ok,
+    Warning,
+    Critical,
+}
+
+/// One host on GET /clocks.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct HostClockStatus {
+    pub host: String,
+    pub source: String,
+    pub source_kind: SourceKind,
+    pub last_reading: Option<ClockReading>,
+    pub last_reading_utc: Option<DateTime<Utc>>,
+    /// Why the last poll gave no reading, if it did not.
+    pub last_error: Option<String>,
+    /// Offsets' slope over the window; None with fewer than three readings.
+    pub drift_ppb: Option<f64>,
+    pub level: ClockLevel,
+    pub reason: String,
+}
+
+/// Body of GET /clocks, also published on 'clock.skew'.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ClockStatus {
+    pub hosts: Vec<HostClockStatus>,
+    /// Spread of the fresh, synced offsets; None with fewer than two hosts.
+    pub fleet_skew_ns: Option<i64>,
+    pub fleet_level: ClockLevel,
+    pub warn_ns: i64,
+    pub critical_ns: i64,
+    pub timestamp_utc: DateTime<Utc>,
+}
+
+/// A change of level of a host or of the fleet, on 'alerts.clock_skew'.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ClockAlert {
+    /// The host, or "fleet".
+    pub scope: String,
+    pub level: ClockLevel,
+    pub previous: ClockLevel,
+    pub reason: String,
+    pub timestamp_utc: DateTime<Utc>,
+}
+
+struct HostClock {
+    source: String,
+    source_kind: SourceKind,
+    readings: VecDeque<(DateTime<Utc>, ClockReading)>,
+    last_error: Option<String>,
+    level: ClockLevel,
+}
+
+/// Every host's readings and levels, and the alerts raised.
+pub struct ClockMonitor {
+    config: SkewConfig,
+    hosts: BTreeMap<String, HostClock>,
+    fleet_level: ClockLevel,
+    alerts: VecDeque<ClockAlert>,
+}
+
+impl ClockMonitor {
+    pub fn new(config: SkewConfig) -> ClockMonitor {
+        ClockMonitor { config, hosts: BTreeMap::new(), fleet_level: ClockLevel::Ok, alerts: VecDeque::new() }
+    }
+
+    pub fn add_host(&mut self, host: &str, source: String, source_kind: SourceKind) {
+        let clock = HostClock {
+            source,
+            source_kind,
+            readings: VecDeque::new(),
+            last_error: None,
+            level: ClockLevel::Ok,
+        };
+        self.hosts.insert(host.to_string(), clock);
+    }
+
+    /// Records the outcome of polling `host`.
+    pub fn record(&mut self, host: &str, polled: Result<ClockReading, String>, now: DateTime<Utc>) {
+        let Some(clock) = self.hosts.get_mut(host) else {
+            return;
+        };
+        match polled {
+            Ok(reading) => {
+                if clock.readings.len() == self.config.window {
+                    clock.readings.pop_front();
+                }
+                clock.readings.push_back((now, reading));
+                clock.last_error = None;
+            }
+            Err(e) => clock.last_error = Some(e),
+        }
+    }
+
+    /// Judges every host and the fleet, returning the alerts for the levels
+    /// that changed.
+    pub fn evaluate(&mut self, now: DateTime<Utc>) -> Vec<ClockAlert> {
+        let mut raised = Vec::new();
+        for (host, clock) in self.hosts.iter_mut() {
+            let (level, reason) = host_level(&self.config, clock, now);
+            if level != clock.level {
+                raised.push(ClockAlert {
+                    scope: host.clone(),
+                    level,
+                    previous: clock.level,
+                    reason,
+                    timestamp_utc: now,
+                });
+                clock.level = level;
+            }
+        }
+        let (level, reason) = self.fleet(now);
+        if level != self.fleet_level {
+            raised.push(ClockAlert {
+                scope: FLEET.to_string(),
+                level,
+                previous: self.fleet_level,
+                reason,
+                timestamp_utc: now,
+            });
+            self.fleet_level = level;
+        }
+        for alert in &raised {
+            if self.alerts.len() == ALERT_RETENTION {
+                self.alerts.pop_front();
+            }
+            self.alerts.push_back(alert.clone());
+        }
+        raised
+    }
+
+    /// The fleet's level and why.
+    fn fleet(&self, now: DateTime<Utc>) -> (ClockLevel, String) {
+        let Some(skew_ns) = self.fleet_skew_ns(now) else {
+            return (ClockLevel::Ok, "fewer than two hosts with a synced reading".to_string());
+        };
+        let level = threshold_level(&self.config, skew_ns);
+        (level, format!("hosts' clocks are {}µs apart", skew_ns as f64 / 1_000.0))
+    }
+
+    fn fleet_skew_ns(&self, now: DateTime<Utc>) -> Option<i64> {
+        let offsets: Vec<i64> = self
+            .hosts
+            .values()
+            .filter_map(|clock| clock.readings.back())
+            .filter(|(at, reading)| reading.synced && now - *at < self.config.stale_after)
+            .map(|(_, reading)| reading.offset_ns)
+            .collect();
+        (offsets.len() >= 2).then(|| offsets.iter().max().unwrap() - offsets.iter().min().unwrap())
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> ClockStatus {
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|(host, clock)| {
+                let (level, reason) = host_level(&self.config, clock, now);
+                HostClockStatus {
+                    host: host.clone(),
+                    source: clock.source.clone(),
+                    source_kind: clock.source_kind,
+                    last_reading: clock.readings.back().map(|(_, reading)| reading.clone()),
+                    last_reading_utc: clock.readings.back().map(|(at, _)| *at),
+                    last_error: clock.last_error.clone(),
+                    drift_ppb: drift_ppb(&clock.readings),
+                    level,
+                    reason,
+                }
+            })
+            .collect();
+        ClockStatus {
+            hosts,
+            fleet_skew_ns: self.fleet_skew_ns(now),
+            fleet_level: self.fleet(now).0,
+            warn_ns: self.config.warn_ns,
+            critical_ns: self.config.critical_ns,
+            timestamp_utc: now,
+        }
+    }
+
+    /// Alerts raised, newest first.
+    pub fn alerts(&self) -> Vec<ClockAlert> {
+        self.alerts.iter().rev().cloned().collect()
+    }
+}
+
+fn threshold_level(config: &SkewConfig, magnitude_ns: i64) -> ClockLevel {
+    if magnitude_ns >= config.critical_ns {
+        ClockLevel::Critical
+    } else if magnitude_ns >= config.warn_ns {
+        ClockLevel::Warning
+    } else {
+        ClockLevel::Ok
+    }
+}
+
+/// A host's level and why.
+fn host_level(config: &SkewConfig, clock: &HostClock, now: DateTime<Utc>) -> (ClockLevel, String) {
+    let Some((at, reading)) = clock.readings.back().filter(|(at, _)| now - *at < config.stale_after) else {
+        let since = clock
+            .readings
+            .back()
+            .map_or("ever".to_string(), |(at, _)| format!("{}s", (now - *at).num_seconds()));
+        let error = clock.last_error.as_deref().unwrap_or("no reading");
+        return (ClockLevel::Critical, format!("no clock reading for {}: {}", since, error));
+    };
+    if !reading.synced {
+        return (ClockLevel::Critical, format!("not synced to its reference ({})", reading.detail));
+    }
+    let offset = format!("offset {}µs at {}", reading.offset_ns as f64 / 1_000.0, at.format("%H:%M:%S"));
+    match (threshold_level(config, reading.offset_ns.abs()), drift_ppb(&clock.readings)) {
+        (ClockLevel::Ok, Some(drift)) if drift.abs() >= config.drift_warn_ppb => {
+            (ClockLevel::Warning, format!("drifting {:.0}ppb, {}", drift, offset))
+        }
+        (level, _) => (level, offset),
+    }
+}
+
+/// Least-squares slope of the offsets against time, in ns per second.
+fn drift_ppb(readings: &VecDeque<(DateTime<Utc>, ClockReading)>) -> Option<f64> {
+    if readings.len() < 3 {
+        return None;
+    }
+    let start = readings.front()?.0;
+    let points: Vec<(f64, f64)> = readings
+        .iter()
+        .map(|(at, reading)| ((*at - start).num_milliseconds() as f64 / 1_000.0, reading.offset_ns as f64))
+        .collect();
+    let n = points.len() as f64;
+    let (mean_t, mean_o) = points.iter().fold((0.0, 0.0), |(t, o), (pt, po)| (t + pt / n, o + po / n));
+    let covariance: f64 = points.iter().map(|(t, o)| (t - mean_t) * (o - mean_o)).sum();
+    let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(offset_ns: i64, synced: bool) -> ClockReading {
+        ClockReading { offset_ns, frequency_ppb: None, synced, detail: "test".to_string() }
+    }
+
+    fn config() -> SkewConfig {
+        SkewConfig {
+            warn_ns: 100_000,
+            critical_ns: 1_000_000,
+            drift_warn_ppb: 1_000.0,
+            stale_after: Duration::seconds(10),
+            window: 60,
+        }
+    }
+
+    #[test]
+    fn hosts_and_the_fleet_change_level_on_offset_drift_sync_and_staleness() {
+        let mut monitor = ClockMonitor::new(config());
+        for host in ["gw-1", "risk-1", "md-1"] {
+            monitor.add_host(host, "test".to_string(), SourceKind::Synthetic);
+        }
+        let start = Utc::now();
+        let at = |secs: i64| start + Duration::seconds(secs);
+
+        // md-1 gains 5µs a second: a 5000ppb drift, still within its offset
+        // thresholds but warned of.
+        for secs in 0..5 {
+            monitor.record("gw-1", Ok(reading(10_000, true)), at(secs));
+            monitor.record("risk-1", Ok(reading(-20_000, true)), at(secs));
+            monitor.record("md-1", Ok(reading(secs * 5_000, true)), at(secs));
+        }
+        let alerts = monitor.evaluate(at(4));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].scope.as_str(), alerts[0].level), ("md-1", ClockLevel::Warning));
+        let status = monitor.status(at(4));
+        assert!((status.hosts[1].drift_ppb.unwrap() - 5_000.0).abs() < 1e-6);
+        assert_eq!(status.fleet_skew_ns, Some(40_000));
+
+        // risk-1 is stepped 1.2ms behind: critical itself, and the fleet too.
+        monitor.record("risk-1", Ok(reading(-1_200_000, true)), at(5));
+        let alerts = monitor.evaluate(at(5));
+        let levels: Vec<(&str, ClockLevel)> = alerts.iter().map(|a| (a.scope.as_str(), a.level)).collect();
+        assert_eq!(levels, vec![("risk-1", ClockLevel::Critical), (FLEET, ClockLevel::Critical)]);
+
+        // It recovers but loses sync; gw-1 goes silent past the stale limit;
+        // md-1 is back on time and its drift slows.
+        monitor.record("risk-1", Ok(reading(0, false)), at(15));
+        monitor.record("gw-1", Err("chronyc: not found".to_string()), at(15));
+        monitor.record("md-1", Ok(reading(0, true)), at(15));
+        let alerts = monitor.evaluate(at(15));
+        let levels: Vec<(&str, ClockLevel)> = alerts.iter().map(|a| (a.scope.as_str(), a.level)).collect();
+        let expected = vec![("gw-1", ClockLevel::Critical), ("md-1", ClockLevel::Ok), (FLEET, ClockLevel::Ok)];
+        assert_eq!(levels, expected);
+        assert_eq!(alerts[0].reason, "no clock reading for 11s: chronyc: not found");
+        assert_eq!(monitor.status(at(15)).hosts[2].reason, "not synced to its reference (test)");
+        assert_eq!(monitor.alerts().len(), 6);
+    }
+}
@@ -0,0 +1,379 @@
+/*
+ * QuantumArb 2.0 - Core Services: Clock Readings
+ *
+ * File: src/core_services/clock_monitor/sources.rs
+ *
+ * Description:
+ * Where a host's clock readings come from. Each monitored host has one
+ * `ClockSource`, named in QA_CLOCK_HOSTS:
+ *
+ *   chrony             `chronyc tracking` on this host
+ *   chrony:FILE        the output of `chronyc tracking` that an agent on
+ *                      the host keeps writing to FILE
+ *   ptp4l:FILE         the last offset summary in a ptp4l (or phc2sys) log
+ *   peer:HOST:PORT     a timestamp exchange with the clock monitor's probe
+ *                      responder on another host, as NTP does it
+ *   synthetic          a simulated disciplined clock (the default hosts)
+ *
+ * A reading is the host clock's offset from its reference, positive when
+ * the host is ahead: chrony's system time offset from NTP time, ptp4l's
+ * offset from the grandmaster, or for a peer its offset from this host's
+ * clock. With the four timestamps of an exchange (t1 sent here, t2 received
+ * there, t3 sent there, t4 received here):
+ *
+ *   offset = ((t2 - t1) + (t3 - t4)) / 2      delay = (t4 - t1) - (t3 - t2)
+ *
+ * A probe is "QACK" | t1, answered with "QACK" | t1 | t2 | t3 (i64 UTC
+ * nanoseconds, big endian). Every clock monitor answers probes on
+ * QA_CLOCK_PROBE_BIND, so monitors can probe each other.
+ *
+ * Sources are polled on a blocking thread and may block it briefly: chronyc
+ * runs as a process and a peer waits up to QA_CLOCK_PROBE_TIMEOUT_MS=200
+ * for its answer. A ptp4l log that has not grown a new summary since the
+ * last poll gives no reading.
+ */
+
+use chrono::Utc;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_sim::SimRng;
+use rand::Rng;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+const PROBE_MAGIC: &[u8; 4] = b"QACK";
+/// Bytes read from the end of a ptp4l log for its last summary.
+const LOG_TAIL_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SourceKind {
+    Chrony,
+    Ptp,
+    Peer,
+    Synthetic,
+}
+
+/// One reading of a host's clock.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct ClockReading {
+    /// Host clock minus its reference, in nanoseconds.
+    pub offset_ns: i64,
+    /// The frequency error the host's servo reports, positive when fast.
+    pub frequency_ppb: Option<f64>,
+    /// Whether the host's servo is locked to its reference.
+    pub synced: bool,
+    /// Stratum and reference, servo state or round trip delay.
+    pub detail: String,
+}
+
+pub trait ClockSource: Send {
+    fn kind(&self) -> SourceKind;
+    /// Where the readings come from, for GET /clocks and the logs.
+    fn describe(&self) -> String;
+    fn poll(&mut self) -> Result<ClockReading, String>;
+}
+
+/// The source a QA_CLOCK_HOSTS entry names.
+pub fn open_source(spec: &str, rng: SimRng) -> Result<Box<dyn ClockSource>, String> {
+    let (kind, argument) = spec.split_once(':').unwrap_or((spec, ""));
+    let source: Box<dyn ClockSource> = match kind {
+        "synthetic" => Box::new(SyntheticClock::new(rng)),
+        "chrony" if argument.is_empty() => Box::new(ChronySource { file: None }),
+        "chrony" => Box::new(ChronySource { file: Some(argument.to_string()) }),
+        "ptp4l" => Box::new(Ptp4lSource { path: argument.to_string(), last_line: None }),
+        "peer" => Box::new(PeerSource::connect(argument).map_err(|e| e.to_string())?),
+        other => return Err(format!("unknown source '{}'", other)),
+    };
+    Ok(source)
+}
+
+fn utc_now_ns() -> i64 {
+    Utc::now().timestamp_nanos_opt().unwrap_or(0)
+}
+
+// --- chrony ---
+
+pub struct ChronySource {
+    /// None to run chronyc here.
+    file: Option<String>,
+}
+
+impl ClockSource for ChronySource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Chrony
+    }
+
+    fn describe(&self) -> String {
+        match &self.file {
+            Some(file) => format!("chrony tracking from {}", file),
+            None => "chronyc tracking".to_string(),
+        }
+    }
+
+    fn poll(&mut self) -> Result<ClockReading, String> {
+        let text = match &self.file {
+            Some(file) => std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?,
+            None => {
+                let output = std::process::Command::new("chronyc")
+                    .arg("tracking")
+                    .output()
+                    .map_err(|e| format!("chronyc: {}", e))?;
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+        };
+        parse_chrony_tracking(&text)
+    }
+}
+
+/// Reads `chronyc tracking` output: the system time offset ("fast" or
+/// "slow" of NTP time), the frequency error and the leap status.
+pub fn parse_chrony_tracking(text: &str) -> Result<ClockReading, String> {
+    let field = |name: &str| {
+        text.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim())
+    };
+    let signed = |value: &str, positive: &str, negative: &str| -> Option<f64> {
+        let mut words = value.split_whitespace();
+        let magnitude: f64 = words.next()?.parse().ok()?;
+        let direction = words.find(|w| *w == positive || *w == negative)?;
+        Some(if direction == positive { magnitude } else { -magnitude })
+    };
+    let system_time = field("System time").ok_or("no 'System time' in chrony tracking")?;
+    let offset_secs = signed(system_time, "fast", "slow").ok_or(format!("unreadable system time '{}'", system_time))?;
+    let frequency_ppm = field("Frequency").and_then(|value| signed(value, "fast", "slow"));
+    let leap = field("Leap status").unwrap_or("Unknown");
+    Ok(ClockReading {
+        offset_ns: (offset_secs * 1e9).round() as i64,
+        frequency_ppb: frequency_ppm.map(|ppm| ppm * 1_000.0),
+        synced: leap != "Not synchronised" && leap != "Unknown",
+        detail: format!(
+            "stratum {}, reference {}, leap {}",
+            field("Stratum").unwrap_or("?"),
+            field("Reference ID").unwrap_or("?"),
+            leap
+        ),
+    })
+}
+
+// --- ptp4l ---
+
+pub struct Ptp4lSource {
+    path: String,
+    last_line: Option<String>,
+}
+
+impl ClockSource for Ptp4lSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Ptp
+    }
+
+    fn describe(&self) -> String {
+        format!("ptp4l log {}", self.path)
+    }
+
+    fn poll(&mut self) -> Result<ClockReading, String> {
+        let mut file = File::open(&self.path).map_err(|e| format!("{}: {}", self.path, e))?;
+        let length = file.metadata().map_err(|e| e.to_string())?.len();
+        file.seek(SeekFrom::Start(length.saturating_sub(LOG_TAIL_BYTES))).map_err(|e| e.to_string())?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).map_err(|e| e.to_string())?;
+        let tail = String::from_utf8_lossy(&tail);
+        let (line, reading) = tail
+            .lines()
+            .rev()
+            .find_map(|line| Some((line, parse_ptp4l_summary(line)?)))
+            .ok_or(format!("no offset summary in {}", self.path))?;
+        if self.last_line.as_deref() == Some(line) {
+            return Err(format!("no new offset summary in {}", self.path));
+        }
+        self.last_line = Some(line.to_string());
+        Ok(reading)
+    }
+}
+
+/// Reads a ptp4l or phc2sys summary line such as
+/// "ptp4l[5374.018]: master offset -12 s2 freq +1234 path delay 789". The
+/// servo is locked in state s2.
+pub fn parse_ptp4l_summary(line: &str) -> Option<ClockReading> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let at = words.iter().position(|w| *w == "offset")?;
+    let offset_ns: i64 = words.get(at + 1)?.parse().ok()?;
+    let state = words.get(at + 2).filter(|w| w.starts_with('s'))?;
+    let value_after = |name: &str| {
+        let at = words.iter().position(|w| *w == name)?;
+        words.get(at + 1)?.trim_start_matches('+').parse::<f64>().ok()
+    };
+    let delay = words.iter().position(|w| *w == "delay").and_then(|at| words.get(at + 1));
+    Some(ClockReading {
+        offset_ns,
+        frequency_ppb: value_after("freq"),
+        synced: *state == "s2",
+        detail: format!("servo {}, path delay {}ns", state, delay.unwrap_or(&"?")),
+    })
+}
+
+// --- Peer probes ---
+
+pub struct PeerSource {
+    socket: UdpSocket,
+    target: String,
+    timeout: Duration,
+}
+
+impl PeerSource {
+    fn connect(target: &str) -> std::io::Result<PeerSource> {
+        let timeout_ms = std::env::var("QA_CLOCK_PROBE_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(200);
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+        Ok(PeerSource { socket, target: target.to_string(), timeout: Duration::from_millis(timeout_ms) })
+    }
+}
+
+impl ClockSource for PeerSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Peer
+    }
+
+    fn describe(&self) -> String {
+        format!("peer probes to {}", self.target)
+    }
+
+    fn poll(&mut self) -> Result<ClockReading, String> {
+        let t1 = utc_now_ns();
+        let mut probe = PROBE_MAGIC.to_vec();
+        probe.extend(t1.to_be_bytes());
+        self.socket.send(&probe).map_err(|e| format!("probe to {}: {}", self.target, e))?;
+        // Late answers to earlier probes are skipped until ours comes back.
+        let started = Instant::now();
+        let mut buffer = [0u8; 64];
+        loop {
+            let remaining = self.timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() || self.socket.set_read_timeout(Some(remaining)).is_err() {
+                return Err(format!("no answer from {}", self.target));
+            }
+            let len = self.socket.recv(&mut buffer).map_err(|_| format!("no answer from {}", self.target))?;
+            let t4 = utc_now_ns();
+            let stamp = |at: usize| i64::from_be_bytes(buffer[at..at + 8].try_into().unwrap());
+            if len != 28 || &buffer[..4] != PROBE_MAGIC || stamp(4) != t1 {
+                continue;
+            }
+            let (offset_ns, delay_ns) = peer_offset(t1, stamp(12), stamp(20), t4);
+            return Ok(ClockReading {
+                offset_ns,
+                frequency_ppb: None,
+                synced: true,
+                detail: format!("round trip {}ns", delay_ns),
+            });
+        }
+    }
+}
+
+/// The peer's offset from this clock and the network delay of an exchange.
+pub fn peer_offset(t1: i64, t2: i64, t3: i64, t4: i64) -> (i64, i64) {
+    (((t2 - t1) + (t3 - t4)) / 2, (t4 - t1) - (t3 - t2))
+}
+
+/// Answers other monitors' probes on `bind`, on a thread of its own.
+pub fn serve_probes(bind: &str) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(bind)?;
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 64];
+        loop {
+            let Ok((len, from)) = socket.recv_from(&mut buffer) else {
+                continue;
+            };
+            let t2 = utc_now_ns();
+            if len != 12 || &buffer[..4] != PROBE_MAGIC {
+                continue;
+            }
+            let mut answer = buffer[..12].to_vec();
+            answer.extend(t2.to_be_bytes());
+            answer.extend(utc_now_ns().to_be_bytes());
+            let _ = socket.send_to(&answer, from);
+        }
+    });
+    Ok(())
+}
+
+// --- Synthetic ---
+
+/// A clock its servo keeps near its reference, wandering by a few
+/// microseconds and now and then stepped away, as after a lost reference.
+pub struct SyntheticClock {
+    rng: SimRng,
+    offset_ns: i64,
+    frequency_ppb: f64,
+}
+
+impl SyntheticClock {
+    fn new(mut rng: SimRng) -> SyntheticClock {
+        let offset_ns = rng.gen_range(-20_000..=20_000);
+        let frequency_ppb = rng.gen_range(-50.0..=50.0);
+        SyntheticClock { rng, offset_ns, frequency_ppb }
+    }
+}
+
+impl ClockSource for SyntheticClock {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Synthetic
+    }
+
+    fn describe(&self) -> String {
+        "synthetic".to_string()
+    }
+
+    fn poll(&mut self) -> Result<ClockReading, String> {
+        self.offset_ns += self.frequency_ppb as i64 + self.rng.gen_range(-2_000..=2_000);
+        self.offset_ns -= self.offset_ns / 10;
+        if self.rng.gen_bool(0.005) {
+            self.offset_ns += self.rng.gen_range(-800_000..=800_000);
+        }
+        Ok(ClockReading {
+            offset_ns: self.offset_ns,
+            frequency_ppb: Some(self.frequency_ppb),
+            synced: true,
+            detail: "simulated".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrony_ptp4l_and_peer_readings_are_host_minus_reference() {
+        let tracking = "Reference ID    : A9FEA97B (169.254.169.123)\n\
+                        Stratum         : 4\n\
+                        System time     : 0.000012345 seconds slow of NTP time\n\
+                        Last offset     : +0.000000812 seconds\n\
+                        Frequency       : 12.345 ppm fast\n\
+                        Leap status     : Normal\n";
+        let reading = parse_chrony_tracking(tracking).unwrap();
+        assert_eq!(reading.offset_ns, -12_345);
+        assert_eq!(reading.frequency_ppb, Some(12_345.0));
+        assert!(reading.synced);
+        assert_eq!(reading.detail, "stratum 4, reference A9FEA97B (169.254.169.123), leap Normal");
+        let unsynced = tracking.replace("Normal", "Not synchronised");
+        assert!(!parse_chrony_tracking(&unsynced).unwrap().synced);
+
+        let line = "ptp4l[5374.018]: master offset        -12 s2 freq  +1234 path delay       789";
+        let reading = parse_ptp4l_summary(line).unwrap();
+        assert_eq!((reading.offset_ns, reading.frequency_ppb, reading.synced), (-12, Some(1234.0), true));
+        let stepping = "phc2sys[5375.020]: CLOCK_REALTIME phc offset   48211 s1 freq  -310 delay    512";
+        assert!(!parse_ptp4l_summary(stepping).unwrap().synced);
+        assert!(parse_ptp4l_summary("ptp4l[5376.001]: selected best master clock 001122.fffe.334455").is_none());
+
+        // The peer is 50µs ahead over a 30µs each-way path.
+        assert_eq!(peer_offset(1_000_000, 1_080_000, 1_090_000, 1_070_000), (50_000, 60_000));
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * QuantumArb 2.0 - Core Services: Alternative Data Anomaly Scoring
+ *
+ * File: src/core_services/data_bus_connector/anomaly.rs
+ *
+ * Description:
+ * Flags unusual alternative data per symbol with streaming statistics, so
+ * the strategy engine can stand aside while the news flow is abnormal:
+ *
+ *   NEWS_BURST       the news count of the current bucket is `volume_z`
+ *                    standard deviations above the symbol's EWMA baseline of
+ *                    bucket counts (checked as events arrive, so a burst is
+ *                    flagged before its bucket closes)
+ *   SENTIMENT_SHIFT  a fast EWMA of sentiment has moved `shift_z` standard
+ *                    deviations away from the slow baseline mean
+ *
+ * Baselines are exponentially weighted mean/variance estimates, updated
+ * after scoring so an anomaly does not mask itself. Nothing is flagged
+ * until a symbol has `warmup` buckets (bursts) or events (shifts) of
+ * history. A sentiment shift re-arms once the score falls back below half
+ * the threshold; a burst is flagged at most once per bucket.
+ *
+ * Anomalies are published on 'alerts.alt_data' with a hold period the
+ * strategy engine applies as a trade filter. They go out through a
+ * spill-to-disk queue (QA_ANOMALY_SPILL_PATH), so a slow bus never stalls
+ * the news loop and no alert is lost.
+ *
+ * Configuration (environment):
+ *   QA_ANOMALY_BUCKET_SECS=60        news volume bucket
+ *   QA_ANOMALY_VOLUME_Z=4.0          burst threshold
+ *   QA_ANOMALY_SHIFT_Z=3.0           sentiment shift threshold
+ *   QA_ANOMALY_WARMUP=30             buckets / events before scoring
+ *   QA_ANOMALY_HOLD_SECS=300         how long consumers should filter trades
+ *   QA_ANOMALY_SPILL_PATH=alt_data_alerts.spill.jsonl
+ *                                    overflow file of the alert queue
+ */
+
+use quantumarb_types::{AltDataAnomaly, AnomalyKind};
+use std::collections::HashMap;
+
+/// EWMA weights: baselines average over roughly 60 buckets / 50 events, the
+/// fast sentiment over roughly 5 events.
+const VOLUME_BASELINE_ALPHA: f64 = 2.0 / 61.0;
+const SENTIMENT_BASELINE_ALPHA: f64 = 2.0 / 51.0;
+const SENTIMENT_FAST_ALPHA: f64 = 2.0 / 6.0;
+/// Floors on the baseline standard deviations, so a perfectly regular
+/// stream does not turn every small change into an anomaly. News counts get
+/// at least the Poisson deviation of their mean as well.
+const MIN_VOLUME_STD: f64 = 1.0;
+const MIN_SENTIMENT_STD: f64 = 0.05;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    pub bucket_ms: i64,
+    pub volume_z: f64,
+    pub shift_z: f64,
+    pub warmup: u64,
+    pub hold_secs: u64,
+    pub spill_path: String,
+}
+
+impl AnomalyConfig {
+    pub fn from_env() -> AnomalyConfig {
+        let number = |name: &str, default: f64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &f64| *v > 0.0).unwrap_or(default)
+        };
+        AnomalyConfig {
+            bucket_ms: number("QA_ANOMALY_BUCKET_SECS", 60.0) as i64 * 1000,
+            volume_z: number("QA_ANOMALY_VOLUME_Z", 4.0),
+            shift_z: number("QA_ANOMALY_SHIFT_Z", 3.0),
+            warmup: number("QA_ANOMALY_WARMUP", 30.0) as u64,
+            hold_secs: number("QA_ANOMALY_HOLD_SECS", 300.0) as u64,
+            spill_path: std::env::var("QA_ANOMALY_SPILL_PATH")
+                .unwrap_or_else(|_| "alt_data_alerts.spill.jsonl".to_string()),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// Exponentially weighted mean and variance.
+#[derive(Debug, Clone, Default)]
+struct Ewma {
+    mean: f64,
+    variance: f64,
+    samples: u64,
+}
+
+impl Ewma {
+    fn update(&mut self, value: f64, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = value;
+        } else {
+            let delta = value - self.mean;
+            self.mean += alpha * delta;
+            self.variance = (1.0 - alpha) * (self.variance + alpha * delta * delta);
+        }
+        self.samples += 1;
+    }
+
+    fn z(&self, value: f64, min_std: f64) -> f64 {
+        (value - self.mean) / self.variance.sqrt().max(min_std)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct SymbolStats {
+    bucket_start_ms: i64,
+    bucket_count: u32,
+    burst_flagged: bool,
+    volume: Ewma,
+    sentiment_baseline: Ewma,
+    sentiment_fast: Option<f64>,
+    shift_flagged: bool,
+}
+
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    symbols: HashMap<String, SymbolStats>,
+}
+
+// --- Scoring ---
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> AnomalyDetector {
+        AnomalyDetector { config, symbols: HashMap::new() }
+    }
+
+    /// Scores one news event for each of its symbols.
+    pub fn on_news(&mut self, symbols: &[String], sentiment: f64, at_ms: i64) -> Vec<AltDataAnomaly> {
+        let config = &self.config;
+        let mut anomalies = Vec::new();
+        for symbol in symbols {
+            let stats = self.symbols.entry(symbol.clone()).or_insert_with(|| SymbolStats {
+                bucket_start_ms: at_ms - at_ms % config.bucket_ms,
+                ..SymbolStats::default()
+            });
+            close_buckets(stats, config, at_ms);
+
+            stats.bucket_count += 1;
+            if stats.volume.samples >= config.warmup && !stats.burst_flagged {
+                let min_std = MIN_VOLUME_STD.max(stats.volume.mean.sqrt());
+                let z = stats.volume.z(stats.bucket_count as f64, min_std);
+                if z >= config.volume_z {
+                    stats.burst_flagged = true;
+                    anomalies.push(anomaly(
+                        symbol,
+                        AnomalyKind::NewsBurst,
+                        z,
+                        format!("{} news events this bucket vs {:.1} typical.", stats.bucket_count, stats.volume.mean),
+                        config,
+                    ));
+                }
+            }
+
+            let fast = match stats.sentiment_fast {
+                None => sentiment,
+                Some(fast) => fast + SENTIMENT_FAST_ALPHA * (sentiment - fast),
+            };
+            stats.sentiment_fast = Some(fast);
+            if stats.sentiment_baseline.samples >= config.warmup {
+                let z = stats.sentiment_baseline.z(fast, MIN_SENTIMENT_STD);
+                if z.abs() >= config.shift_z && !stats.shift_flagged {
+                    stats.shift_flagged = true;
+                    anomalies.push(anomaly(
+                        symbol,
+                        AnomalyKind::SentimentShift,
+                        z,
+                        format!("Recent sentiment {:.2} vs baseline {:.2}.", fast, stats.sentiment_baseline.mean),
+                        config,
+                    ));
+                } else if z.abs() < config.shift_z / 2.0 {
+                    stats.shift_flagged = false;
+                }
+            }
+            stats.sentiment_baseline.update(sentiment, SENTIMENT_BASELINE_ALPHA);
+        }
+        anomalies
+    }
+
+    /// Closes finished buckets for every symbol (quiet buckets count as zero).
+    pub fn on_timer(&mut self, now_ms: i64) {
+        for stats in self.symbols.values_mut() {
+            close_buckets(stats, &self.config, now_ms);
+        }
+    }
+}
+
+/// Folds every bucket that ended before `now_ms` into the volume baseline.
+fn close_buckets(stats: &mut SymbolStats, config: &AnomalyConfig, now_ms: i64) {
+    // Cap the catch-up after a long silence; the baseline has decayed by then.
+    let mut remaining = 1000;
+    while now_ms >= stats.bucket_start_ms + config.bucket_ms && remaining > 0 {
+        stats.volume.update(stats.bucket_count as f64, VOLUME_BASELINE_ALPHA);
+        stats.bucket_start_ms += config.bucket_ms;
+        stats.bucket_count = 0;
+        stats.burst_flagged = false;
+        remaining -= 1;
+    }
+    if remaining == 0 {
+        stats.bucket_start_ms = now_ms - now_ms % config.bucket_ms;
+    }
+}
+
+fn anomaly(symbol: &str, kind: AnomalyKind, score: f64, detail: String, config: &AnomalyConfig) -> AltDataAnomaly {
+    AltDataAnomaly {
+        symbol: symbol.to_string(),
+        kind,
+        score,
+        detail,
+        hold_secs: config.hold_secs,
+        timestamp_utc: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> AnomalyDetector {
+        AnomalyDetector::new(AnomalyConfig {
+            bucket_ms: 1_000,
+            volume_z: 4.0,
+            shift_z: 3.0,
+            warmup: 5,
+            hold_secs: 300,
+            spill_path: String::new(),
+        })
+    }
+
+    fn symbols() -> Vec<String> {
+        vec!["BTC".to_string()]
+    }
+
+    /// Sends `count` events at `at_ms`; returns the index and kind of each anomaly.
+    fn news(detector: &mut AnomalyDetector, count: usize, sentiment: f64, at_ms: i64) -> Vec<(usize, AnomalyKind)> {
+        (0..count)
+            .flat_map(|i| detector.on_news(&symbols(), sentiment, at_ms).into_iter().map(move |a| (i, a.kind)))
+            .collect()
+    }
+
+    #[test]
+    fn a_news_burst_is_flagged_once_per_bucket_after_warmup() {
+        let mut cold = detector();
+        assert!(news(&mut cold, 20, 0.0, 0).is_empty(), "no baseline yet");
+
+        let mut detector = detector();
+        for bucket in 0..10 {
+            assert!(news(&mut detector, 2, 0.0, bucket * 1_000).is_empty());
+        }
+        // Baseline 2 a bucket with the Poisson floor of sqrt(2): the 8th event is 4 deviations out.
+        assert_eq!(news(&mut detector, 20, 0.0, 10_000), [(7, AnomalyKind::NewsBurst)]);
+        assert_eq!(news(&mut detector, 20, 0.0, 11_000).len(), 1, "the next bucket can burst again");
+    }
+
+    #[test]
+    fn quiet_buckets_count_as_zero() {
+        let mut detector = detector();
+        news(&mut detector, 1, 0.0, 0);
+        detector.on_timer(6_000);
+        let anomalies = detector.on_news(&symbols(), 0.0, 6_000);
+        assert!(anomalies.is_empty());
+        // Six buckets of 1, 0, 0, 0, 0, 0 put the baseline under one event.
+        assert_eq!(news(&mut detector, 4, 0.0, 6_500), [(3, AnomalyKind::NewsBurst)]);
+    }
+
+    #[test]
+    fn a_sentiment_shift_is_flagged_once_and_re_arms_when_it_subsides() {
+        let mut detector = detector();
+        assert!(news(&mut detector, 10, 0.0, 0).is_empty());
+        let shift = detector.on_news(&symbols(), 1.0, 0);
+        assert_eq!(shift.len(), 1);
+        assert_eq!((shift[0].kind, shift[0].hold_secs), (AnomalyKind::SentimentShift, 300));
+        assert!(shift[0].score >= 3.0, "{}", shift[0].score);
+        assert!(news(&mut detector, 4, 1.0, 0).is_empty(), "still the same shift");
+
+        // Once the baseline has settled back, the opposite move is a new shift.
+        assert!(news(&mut detector, 200, 0.0, 0).is_empty());
+        let reversal = detector.on_news(&symbols(), -1.0, 0);
+        assert_eq!(reversal.len(), 1);
+        assert!(reversal[0].score <= -3.0, "{}", reversal[0].score);
+    }
+}
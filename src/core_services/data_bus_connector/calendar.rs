@@ -0,0 +1,257 @@
+/*
+ * QuantumArb 2.0 - Core Services: Economic Calendar Adapter
+ *
+ * File: src/core_services/data_bus_connector/calendar.rs
+ *
+ * Description:
+ * Polls an economic calendar vendor for scheduled releases, keeps the ones
+ * that move the markets traded (FOMC rate decisions, nonfarm payrolls and
+ * CPI) and publishes each upcoming one on 'calendar.event_windows' as a
+ * `quantumarb_types::EventWindow`: the release time and the window around
+ * it in which prices are treated as event risk. The strategy engine pauses
+ * or widens its thresholds inside the windows, and the risk gateway
+ * tightens its limits.
+ *
+ * The vendor answers with the week's releases:
+ *
+ *   [{"id": "US-CPI-2026-10", "title": "CPI m/m", "country": "US",
+ *     "date": "2026-10-15T12:30:00Z", "impact": "High"}, ...]
+ *
+ * Every window in the lookahead, and any still open, is published again at
+ * each poll, so a consumer that starts late or a release that moves catches
+ * up; consumers key the windows on event_id.
+ *
+ * Configuration (environment):
+ *   QA_CALENDAR_API_URL               calendar endpoint
+ *   QA_CALENDAR_POLL_SECS=300         polling interval
+ *   QA_CALENDAR_LOOKAHEAD_HOURS=24    how far ahead windows are published
+ *   QA_EVENT_WINDOWS=FOMC=15/60,NFP=5/30,CPI=5/30
+ *                                     minutes before/after each kind of
+ *                                     release; kinds not listed are dropped
+ *   QA_EVENT_SYMBOLS=                 symbols a kind moves (CPI=ESZ25|BTC,...);
+ *                                     every symbol when not listed
+ *
+ * The vendor responses are simulated.
+ */
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use quantumarb_bus::topics;
+use quantumarb_types::{EconomicEventKind, EventWindow};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::time;
+
+const DEFAULT_API_URL: &str = "https://api.fictional-calendar.com/v1/releases";
+const DEFAULT_EVENT_WINDOWS: &str = "FOMC=15/60,NFP=5/30,CPI=5/30";
+
+// --- Configuration ---
+
+/// Minutes either side of a release its window covers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSpan {
+    pub before_minutes: i64,
+    pub after_minutes: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarConfig {
+    pub api_url: String,
+    pub poll_interval: time::Duration,
+    pub lookahead: Duration,
+    pub windows: HashMap<EconomicEventKind, WindowSpan>,
+    pub symbols: HashMap<EconomicEventKind, Vec<String>>,
+}
+
+impl CalendarConfig {
+    pub fn from_env() -> CalendarConfig {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let number = |name: &str, default: u64| {
+            var(name).and_then(|v| v.parse().ok()).filter(|v: &u64| *v > 0).unwrap_or(default)
+        };
+        let windows = var("QA_EVENT_WINDOWS").and_then(|spec| match parse_windows(&spec) {
+            Ok(windows) => Some(windows),
+            Err(e) => {
+                println!("Ignoring QA_EVENT_WINDOWS: {}", e);
+                None
+            }
+        });
+        CalendarConfig {
+            api_url: var("QA_CALENDAR_API_URL").unwrap_or_else(|| DEFAULT_API_URL.to_string()),
+            poll_interval: time::Duration::from_secs(number("QA_CALENDAR_POLL_SECS", 300)),
+            lookahead: Duration::hours(number("QA_CALENDAR_LOOKAHEAD_HOURS", 24) as i64),
+            windows: windows.unwrap_or_else(|| parse_windows(DEFAULT_EVENT_WINDOWS).expect("default windows parse")),
+            symbols: var("QA_EVENT_SYMBOLS").map(|spec| parse_symbols(&spec)).unwrap_or_default(),
+        }
+    }
+}
+
+fn parse_kind(name: &str) -> Option<EconomicEventKind> {
+    match name.trim().to_uppercase().as_str() {
+        "FOMC" => Some(EconomicEventKind::Fomc),
+        "NFP" => Some(EconomicEventKind::Nfp),
+        "CPI" => Some(EconomicEventKind::Cpi),
+        _ => None,
+    }
+}
+
+/// Parses "FOMC=15/60,CPI=5/30".
+fn parse_windows(spec: &str) -> Result<HashMap<EconomicEventKind, WindowSpan>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (kind, span) = entry.split_once('=').ok_or_else(|| format!("'{}' has no window", entry))?;
+            let kind = parse_kind(kind).ok_or_else(|| format!("unknown release kind '{}'", kind))?;
+            let (before_minutes, after_minutes) = span
+                .split_once('/')
+                .and_then(|(before, after)| Some((before.trim().parse().ok()?, after.trim().parse().ok()?)))
+                .filter(|(before, after): &(i64, i64)| *before >= 0 && *after >= 0)
+                .ok_or_else(|| format!("bad window '{}'", span))?;
+            Ok((kind, WindowSpan { before_minutes, after_minutes }))
+        })
+        .collect()
+}
+
+/// Parses "CPI=ESZ25|BTC,NFP=ESZ25", skipping unknown kinds.
+fn parse_symbols(spec: &str) -> HashMap<EconomicEventKind, Vec<String>> {
+    spec.split(',')
+        .filter_map(|entry| {
+            let (kind, symbols) = entry.split_once('=')?;
+            let symbols = symbols.split('|').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+            Some((parse_kind(kind)?, symbols))
+        })
+        .collect()
+}
+
+// --- Vendor Format ---
+
+/// One release from the calendar vendor.
+#[derive(Debug, Deserialize)]
+struct RawRelease {
+    id: String,
+    title: String,
+    country: String,
+    date: String,
+}
+
+/// The kind of release a vendor title names; None for releases not traded on.
+fn classify(raw: &RawRelease) -> Option<EconomicEventKind> {
+    if raw.country != "US" {
+        return None;
+    }
+    let title = raw.title.to_lowercase();
+    if title.contains("fomc") || title.contains("federal funds rate") {
+        Some(EconomicEventKind::Fomc)
+    } else if title.contains("nonfarm") || title.contains("non-farm") {
+        Some(EconomicEventKind::Nfp)
+    } else if title.starts_with("cpi") || title.contains("consumer price index") {
+        Some(EconomicEventKind::Cpi)
+    } else {
+        None
+    }
+}
+
+/// Transforms a vendor release into its event window; Ok(None) for a
+/// release with no window configured.
+fn normalize_release(config: &CalendarConfig, raw: &RawRelease) -> Result<Option<EventWindow>, String> {
+    let Some((kind, span)) = classify(raw).and_then(|kind| Some((kind, *config.windows.get(&kind)?))) else {
+        return Ok(None);
+    };
+    let scheduled_utc = DateTime::parse_from_rfc3339(&raw.date)
+        .map_err(|e| format!("bad date '{}': {}", raw.date, e))?
+        .with_timezone(&Utc);
+    Ok(Some(EventWindow {
+        event_id: raw.id.clone(),
+        kind,
+        name: raw.title.clone(),
+        scheduled_utc,
+        starts_utc: scheduled_utc - Duration::minutes(span.before_minutes),
+        ends_utc: scheduled_utc + Duration::minutes(span.after_minutes),
+        symbols: config.symbols.get(&kind).cloned().unwrap_or_default(),
+    }))
+}
+
+// --- Main Application Logic ---
+
+/// Polls the calendar and publishes the windows that are open or start
+/// within the lookahead.
+pub async fn run_calendar_adapter(config: CalendarConfig) {
+    println!("Polling '{}' for economic releases ({:?} windows)", config.api_url, config.windows);
+    let mut interval = time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        // In a real system:
+        // let body = http_client.get(&config.api_url).send().await?.text().await?;
+        let body = get_simulated_releases(now);
+        let releases = match serde_json::from_str::<Vec<RawRelease>>(&body) {
+            Ok(releases) => releases,
+            Err(e) => {
+                println!("\nUndecodable economic calendar: {}", e);
+                continue;
+            }
+        };
+        for raw in &releases {
+            match normalize_release(&config, raw) {
+                Ok(Some(window)) if window.ends_utc > now && window.starts_utc <= now + config.lookahead => {
+                    quantumarb_bus::publish_json(topics::EVENT_WINDOWS, &window);
+                }
+                Ok(_) => {}
+                Err(e) => println!("\nDropping economic release {}: {}", raw.id, e),
+            }
+        }
+    }
+}
+
+/// Simulates the vendor's releases: a CPI print and a minor release in the
+/// coming hours, payrolls tomorrow and an FOMC decision the day after.
+fn get_simulated_releases(now: DateTime<Utc>) -> String {
+    let hour = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    let release = |id: &str, title: &str, at: DateTime<Utc>, impact: &str| {
+        serde_json::json!({"id": id, "title": title, "country": "US", "date": at.to_rfc3339(), "impact": impact})
+    };
+    serde_json::json!([
+        release("US-CPI", "CPI m/m", hour + Duration::minutes(90), "High"),
+        release("US-WHOLESALE", "Wholesale Inventories m/m", hour + Duration::minutes(150), "Low"),
+        release("US-NFP", "Non-Farm Employment Change", hour + Duration::hours(24), "High"),
+        release("US-FOMC", "FOMC Statement", hour + Duration::hours(48), "High"),
+    ])
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(id: &str, title: &str, country: &str, date: &str) -> RawRelease {
+        RawRelease { id: id.into(), title: title.into(), country: country.into(), date: date.into() }
+    }
+
+    #[test]
+    fn releases_are_classified_and_given_their_configured_windows() {
+        let config = CalendarConfig {
+            api_url: DEFAULT_API_URL.to_string(),
+            poll_interval: time::Duration::from_secs(300),
+            lookahead: Duration::hours(24),
+            windows: parse_windows("FOMC=15/60, CPI=5/30").unwrap(),
+            symbols: parse_symbols("CPI=ESZ25|BTC,GDP=ESZ25"),
+        };
+        assert!(parse_windows("CPI=5").is_err());
+        assert!(parse_windows("GDP=5/5").is_err());
+
+        let cpi = normalize_release(&config, &raw("US-CPI-10", "CPI m/m", "US", "2026-10-15T12:30:00Z")).unwrap();
+        let cpi = cpi.expect("CPI has a window");
+        assert_eq!(cpi.kind, EconomicEventKind::Cpi);
+        assert_eq!(cpi.starts_utc.to_rfc3339(), "2026-10-15T12:25:00+00:00");
+        assert_eq!(cpi.ends_utc.to_rfc3339(), "2026-10-15T13:00:00+00:00");
+        assert_eq!(cpi.symbols, vec!["ESZ25", "BTC"]);
+        assert!(cpi.covers("BTC", cpi.scheduled_utc) && !cpi.covers("ETH", cpi.scheduled_utc));
+
+        let fomc = raw("US-FOMC", "Federal Funds Rate", "US", "2026-10-28T18:00:00Z");
+        assert_eq!(normalize_release(&config, &fomc).unwrap().unwrap().symbols, Vec::<String>::new());
+        // Payrolls have no window configured; other countries' CPI is not traded on.
+        assert_eq!(normalize_release(&config, &raw("US-NFP", "Non-Farm Employment Change", "US", "x")), Ok(None));
+        assert_eq!(normalize_release(&config, &raw("EU-CPI", "CPI y/y", "EU", "2026-10-17T09:00:00Z")), Ok(None));
+        assert!(normalize_release(&config, &raw("US-CPI-11", "CPI m/m", "US", "mid-November")).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+/*
+ * QuantumArb 2.0 - Core Services: Feature Store Writer
+ *
+ * File: src/core_services/data_bus_connector/feature_store.rs
+ *
+ * Description:
+ * Materializes the `quantumarb-features` snapshots from the connector's
+ * normalized news events and the top of book it follows on
+ * 'market_data.instrument.*', with its book signals. Inputs arrive on two bounded queues: news on a
+ * blocking one (no event is lost) and quotes on a drop-oldest one (under
+ * load only the freshest quotes matter). Every interval the
+ * writer snapshots each symbol it has seen and stores the snapshots in Redis
+ * (latest + history, read live by the strategy engine) and, when configured,
+ * in Postgres (read by the training pipeline).
+ *
+ * Snapshot timestamps are aligned to the interval, so every replica of the
+ * connector writes the same (symbol, timestamp) keys and the stores keep one
+ * snapshot per symbol per interval.
+ *
+ * A store that cannot be reached is logged and skipped, so features keep
+ * being computed and the other store keeps being written.
+ *
+ * Configuration (environment):
+ *   QA_FEATURE_REDIS_URL=redis://127.0.0.1/   live store
+ *   QA_FEATURE_PG_URL=                        offline store (unset: disabled)
+ *   QA_FEATURE_INTERVAL_SECS=60               snapshot interval (one bar)
+ *   QA_FEATURE_RETENTION_HOURS=24             Redis history kept per symbol
+ */
+
+use quantumarb_features::{FeatureBuilder, FeatureSnapshot};
+use quantumarb_queues::Receiver;
+use quantumarb_signals::BookSignals;
+use std::collections::BTreeMap;
+use tokio::time::{self, Duration};
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct FeatureStoreConfig {
+    pub redis_url: String,
+    pub postgres_url: Option<String>,
+    pub interval: Duration,
+    pub retention_ms: i64,
+}
+
+impl FeatureStoreConfig {
+    pub fn from_env() -> FeatureStoreConfig {
+        let number = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &u64| *v > 0).unwrap_or(default)
+        };
+        FeatureStoreConfig {
+            redis_url: std::env::var("QA_FEATURE_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string()),
+            postgres_url: std::env::var("QA_FEATURE_PG_URL").ok().filter(|v| !v.is_empty()),
+            interval: Duration::from_secs(number("QA_FEATURE_INTERVAL_SECS", 60)),
+            retention_ms: number("QA_FEATURE_RETENTION_HOURS", 24) as i64 * 3_600_000,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// An event the writer folds into the features.
+#[derive(Debug, Clone)]
+pub enum FeatureInput {
+    News { symbols: Vec<String>, sentiment: f64, at_ms: i64 },
+    /// Top of book in dollars.
+    Quote { symbol: String, bid: f64, ask: f64 },
+    Signals(BookSignals),
+}
+
+struct FeatureWriter {
+    config: FeatureStoreConfig,
+    builders: BTreeMap<String, FeatureBuilder>,
+    redis: Option<redis::aio::MultiplexedConnection>,
+    postgres: Option<tokio_postgres::Client>,
+}
+
+// --- Main Application Logic ---
+
+/// Runs the writer until either input queue closes.
+pub async fn run_feature_store(
+    config: FeatureStoreConfig,
+    mut news: Receiver<FeatureInput>,
+    mut quotes: Receiver<FeatureInput>,
+) {
+    let mut writer = FeatureWriter::connect(config).await;
+    let mut interval = time::interval(writer.config.interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            input = news.recv() => match input {
+                Some(input) => writer.apply(input),
+                None => break,
+            },
+            input = quotes.recv() => match input {
+                Some(input) => writer.apply(input),
+                None => break,
+            },
+            _ = interval.tick() => {
+                let interval_ms = writer.config.interval.as_millis() as i64;
+                let now_ms = chrono::Utc::now().timestamp_millis();
+                writer.flush(now_ms - now_ms % interval_ms).await;
+            }
+        }
+    }
+}
+
+impl FeatureWriter {
+    async fn connect(config: FeatureStoreConfig) -> FeatureWriter {
+        let redis = match redis::Client::open(config.redis_url.as_str()) {
+            Ok(client) => match client.get_multiplexed_async_connection().await {
+                Ok(con) => Some(con),
+                Err(e) => {
+                    let url = &config.redis_url;
+                    println!("Feature store: Redis unavailable at {} ({}); live features not stored.", url, e);
+                    None
+                }
+            },
+            Err(e) => {
+                println!("Feature store: invalid Redis URL {} ({}).", config.redis_url, e);
+                None
+            }
+        };
+        let postgres = match &config.postgres_url {
+            Some(url) => connect_postgres(url).await,
+            None => None,
+        };
+        println!(
+            "Feature store: snapshots every {:?} to Redis {}{}.",
+            config.interval,
+            if redis.is_some() { "on" } else { "off" },
+            if postgres.is_some() { " and Postgres" } else { "" }
+        );
+        FeatureWriter { config, builders: BTreeMap::new(), redis, postgres }
+    }
+
+    fn apply(&mut self, input: FeatureInput) {
+        match input {
+            FeatureInput::News { symbols, sentiment, at_ms } => {
+                for symbol in symbols {
+                    self.builders.entry(symbol).or_default().on_news(sentiment, at_ms);
+                }
+            }
+            FeatureInput::Quote { symbol, bid, ask } => self.builders.entry(symbol).or_default().on_quote(bid, ask),
+            FeatureInput::Signals(signals) => {
+                self.builders.entry(signals.symbol.clone()).or_default().on_signals(signals)
+            }
+        }
+    }
+
+    /// Snapshots every symbol and writes the snapshots to the stores.
+    async fn flush(&mut self, now_ms: i64) {
+        let snapshots: Vec<FeatureSnapshot> =
+            self.builders.iter_mut().map(|(symbol, builder)| builder.snapshot(symbol, now_ms)).collect();
+        for snapshot in &snapshots {
+            println!("  -> Features {}: {}", snapshot.symbol, serde_json::to_string(snapshot).unwrap());
+            if let Some(con) = self.redis.as_mut() {
+                if let Err(e) = quantumarb_features::write_redis(con, snapshot, self.config.retention_ms).await {
+                    println!("  -> Failed to store {} features in Redis: {}", snapshot.symbol, e);
+                }
+            }
+            if let Some(client) = &self.postgres {
+                if let Err(e) = quantumarb_features::write_postgres(client, snapshot).await {
+                    println!("  -> Failed to store {} features in Postgres: {}", snapshot.symbol, e);
+                }
+            }
+        }
+    }
+}
+
+/// Connects to Postgres and creates the feature table if needed.
+async fn connect_postgres(url: &str) -> Option<tokio_postgres::Client> {
+    let (client, connection) = match tokio_postgres::connect(url, tokio_postgres::NoTls).await {
+        Ok(pair) => pair,
+        Err(e) => {
+            println!("Feature store: Postgres unavailable ({}); offline features not stored.", e);
+            return None;
+        }
+    };
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            println!("Feature store: Postgres connection closed: {}", e);
+        }
+    });
+    match client.batch_execute(quantumarb_features::POSTGRES_SCHEMA).await {
+        Ok(()) => Some(client),
+        Err(e) => {
+            println!("Feature store: failed to create the feature table: {}", e);
+            None
+        }
+    }
+}
@@ -16,17 +16,72 @@
  *
  * This POC simulates a connection to a fictional news sentiment WebSocket feed.
  *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * serde = { version = "1.0", features = ["derive"] }
- * serde_json = "1.0"
- * uuid = { version = "1", features = ["v4"] }
+ * A second adapter normalizes corporate actions (splits, cash dividends and
+ * symbol changes) from a reference data vendor, plus any listed in the
+ * QA_CORPORATE_ACTIONS_PATH file at start-up, and publishes them on
+ * 'reference.corporate_actions' for the portfolio manager. Vendor tickers are
+ * mapped to instrument ids with the `quantumarb-refdata` definitions
+ * (QA_REFDATA_PATH).
+ *
+ * The connector also feeds the feature store (`feature_store.rs`): rolling
+ * sentiment, spread and volatility features per symbol, built from the news
+ * events and the top of book on 'market_data.instrument.*', and written to
+ * Redis for the strategy engine and Postgres for model training.
+ *
+ * News events are also scored for per-symbol bursts of volume and sentiment
+ * regime shifts (`anomaly.rs`); anomalies go out on 'alerts.alt_data' for the
+ * strategy engine to use as trade filters.
+ *
+ * From the same top of book it computes each instrument's imbalance,
+ * microprice and momentum (`quantumarb-signals`), publishes them on
+ * 'signals.instrument.<id>' and adds them to the features.
+ *
+ * The stages are joined by bounded `quantumarb-queues`: news into the
+ * feature store blocks when full, quotes drop the oldest, and anomaly alerts
+ * spill to disk rather than stall the news loop or be lost.
+ *
+ * The news sentiment is aggregated per symbol over 1m/5m/1h windows
+ * (`sentiment.rs`), published on 'alt_data.sentiment' and served on
+ * http://127.0.0.1:3041/sentiment.
+ *
+ * A weather adapter (`weather.rs`) polls the precipitation forecast along
+ * the microwave link's path and publishes it on 'alt_data.weather' for the
+ * latency oracle.
+ *
+ * An economic calendar adapter (`calendar.rs`) publishes the windows around
+ * upcoming FOMC, payrolls and CPI releases on 'calendar.event_windows', for
+ * the strategy engine and the risk gateway to trade more cautiously in.
+ *
+ * The simulated quotes and forecasts are drawn from seeded streams when the
+ * connector is started with --seed N (or QA_SEED), so feature runs can be
+ * repeated.
  */
 
+mod anomaly;
+mod calendar;
+mod feature_store;
+mod sentiment;
+mod weather;
+
+use anomaly::{AnomalyConfig, AnomalyDetector};
+use calendar::CalendarConfig;
+use quantumarb_bus::topics;
+use quantumarb_corporate_actions::{CorporateAction, CorporateActionKind};
+use feature_store::{FeatureInput, FeatureStoreConfig};
+use sentiment::{SentimentBoard, SentimentConfig};
+use quantumarb_money::Price;
+use quantumarb_queues::{Receiver, Sender};
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_signals::{SignalBuilder, TopOfBook};
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_types::AltDataAnomaly;
+use quantumarb_wire::BboUpdate;
+use rand_distr::{Distribution, Normal, Uniform};
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use tokio::time::{self, Duration};
 use uuid::Uuid;
+use weather::WeatherConfig;
 
 // --- Data Structures ---
 
@@ -52,6 +107,27 @@ struct NormalizedAltDataEvent {
     timestamp_utc: String,
 }
 
+/// Represents a raw corporate-action record from a fictional reference data vendor.
+#[derive(Debug, Deserialize)]
+struct RawCorporateActionMessage {
+    reference: String,
+    ticker: String,
+    event: String, // "SPLIT", "DIVIDEND" or "TICKER_CHANGE"
+    ex_date: String,
+    ratio: Option<String>, // "new:old", e.g. "4:1"
+    gross_amount: Option<f64>,
+    currency: Option<String>,
+    pay_date: Option<String>,
+    new_ticker: Option<String>,
+}
+
+/// Capacities of the feature store's input queues. The news loop waits for
+/// space; quotes replace the oldest queued quotes (counted in the log).
+const FEATURE_NEWS_CAPACITY: usize = 10_000;
+const FEATURE_QUOTE_CAPACITY: usize = 10_000;
+/// Anomaly alerts held in memory before they spill to disk.
+const ANOMALY_QUEUE_CAPACITY: usize = 1_000;
+
 // --- Main Application Logic ---
 
 #[tokio::main]
@@ -61,22 +137,84 @@ async fn main() {
     // In a real system, we would establish a persistent WebSocket connection here.
     // For this POC, we'll just simulate receiving messages in a loop.
     println!("Simulating connection to 'ws://api.fictional-news.com/v1/stream'...");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+
+    tokio::spawn(async move {
+        run_corporate_actions_adapter().await;
+    });
+
+    let (features, news_inputs) = quantumarb_queues::blocking("feature_news", FEATURE_NEWS_CAPACITY);
+    let (quote_features, quote_inputs) = quantumarb_queues::drop_oldest("feature_quotes", FEATURE_QUOTE_CAPACITY);
+    tokio::spawn(async move {
+        feature_store::run_feature_store(FeatureStoreConfig::from_env(), news_inputs, quote_inputs).await;
+    });
+    let quote_rng = seed.stream("data_bus_connector.quotes");
+    tokio::spawn(async move {
+        run_market_data_adapter(quote_features, quote_rng).await;
+    });
 
+    let weather_rng = seed.stream("data_bus_connector.weather");
+    tokio::spawn(async move {
+        weather::run_weather_adapter(WeatherConfig::from_env(), weather_rng).await;
+    });
+
+    tokio::spawn(async move {
+        calendar::run_calendar_adapter(CalendarConfig::from_env()).await;
+    });
+
+    let sentiment_board = SentimentBoard::default();
+    let publisher_board = sentiment_board.clone();
+    tokio::spawn(async move {
+        sentiment::run_publisher(SentimentConfig::from_env(), publisher_board).await;
+    });
+    let api_board = sentiment_board.clone();
+    tokio::spawn(async move {
+        sentiment::run_api(api_board).await;
+    });
+
+    let anomaly_config = AnomalyConfig::from_env();
+    let (anomaly_alerts, pending_alerts) =
+        quantumarb_queues::spill_to_disk("alt_data_alerts", ANOMALY_QUEUE_CAPACITY, &anomaly_config.spill_path);
+    tokio::spawn(async move {
+        publish_anomalies(pending_alerts).await;
+    });
+
+    let mut anomalies = AnomalyDetector::new(anomaly_config);
     let mut interval = time::interval(Duration::from_secs(5));
+    let mut tick: u64 = 0;
     loop {
         interval.tick().await;
+        tick += 1;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        anomalies.on_timer(now_ms);
 
-        // 1. Simulate receiving a raw message from the external source.
-        let raw_message_json = get_simulated_news_message();
-        let raw_message: RawNewsMessage = serde_json::from_str(&raw_message_json).unwrap();
-        println!("\nReceived Raw Message: {:?}", raw_message);
+        // 1. Simulate receiving raw messages from the external source.
+        let raw_messages = std::iter::once(get_simulated_news_message()).chain(get_simulated_news_burst(tick));
+        for raw_message_json in raw_messages {
+            let raw_message: RawNewsMessage = serde_json::from_str(&raw_message_json).unwrap();
+            println!("\nReceived Raw Message: {:?}", raw_message);
+            let sentiment = raw_message.sentiment_score as f64;
 
-        // 2. Normalize the raw message into our internal format.
-        let normalized_event = normalize_news_message(raw_message);
-        println!("  -> Normalized Event: {:?}", normalized_event);
+            // 2. Feed the sentiment into the aggregation windows and the feature store.
+            sentiment_board.record(&raw_message.related_symbols, sentiment, now_ms);
+            let news = FeatureInput::News { symbols: raw_message.related_symbols.clone(), sentiment, at_ms: now_ms };
+            if features.send(news).await.is_err() {
+                println!("  -> Feature store stopped; news event not folded into features.");
+            }
 
-        // 3. Publish the normalized event to the internal message bus.
-        publish_to_internal_bus(&normalized_event);
+            // 3. Score the event for news bursts and sentiment shifts.
+            for anomaly in anomalies.on_news(&raw_message.related_symbols, sentiment, now_ms) {
+                let _ = anomaly_alerts.send(anomaly).await;
+            }
+
+            // 4. Normalize the raw message into our internal format.
+            let normalized_event = normalize_news_message(raw_message);
+            println!("  -> Normalized Event: {:?}", normalized_event);
+
+            // 5. Publish the normalized event to the internal message bus.
+            publish_to_internal_bus(&normalized_event);
+        }
     }
 }
 
@@ -92,6 +230,22 @@ fn get_simulated_news_message() -> String {
     .to_string()
 }
 
+/// Simulates a burst of negative INVT headlines for two minutes of every
+/// hour (on top of the regular feed), to exercise the anomaly scoring.
+fn get_simulated_news_burst(tick: u64) -> Vec<String> {
+    // Ticks are 5 seconds apart: 720 per hour, the burst covers 24 of them.
+    if !(600..624).contains(&(tick % 720)) {
+        return Vec::new();
+    }
+    let message = r#"{
+        "source": "MarketWatchWire",
+        "headline": "Innovate Inc. Halts Chip Shipments Pending Regulatory Review",
+        "sentiment_score": -0.8,
+        "related_symbols": ["INVT"]
+    }"#;
+    vec![message.to_string(); 3]
+}
+
 /// Transforms a source-specific message into our standard internal format.
 fn normalize_news_message(raw: RawNewsMessage) -> NormalizedAltDataEvent {
     let mut metadata = std::collections::HashMap::new();
@@ -110,8 +264,191 @@ fn normalize_news_message(raw: RawNewsMessage) -> NormalizedAltDataEvent {
 
 /// Simulates publishing the event to an internal message bus like NATS or Kafka.
 fn publish_to_internal_bus(event: &NormalizedAltDataEvent) {
-    let event_json = serde_json::to_string_pretty(event).unwrap();
-    println!("  -> Publishing to topic 'alt_data.normalized':\n{}", event_json);
+    quantumarb_bus::publish_json(topics::ALT_DATA_NORMALIZED, event);
+}
+
+/// Simulates publishing the queued alt-data anomalies to the internal message bus.
+async fn publish_anomalies(mut pending: Receiver<AltDataAnomaly>) {
+    while let Some(anomaly) = pending.recv().await {
+        // With a real bus client, a failed publish is retried before the next alert.
+        quantumarb_bus::publish_json(topics::ALT_DATA_ALERTS, &anomaly);
+    }
+}
+
+/// Follows the top of book of every known instrument for the feature store,
+/// and publishes each instrument's book signals.
+async fn run_market_data_adapter(features: Sender<FeatureInput>, rng: SimRng) {
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
     // In a real system:
-    // nats_client.publish("alt_data.normalized", event_json.as_bytes()).await.unwrap();
+    // let mut subscriber = nats_client.subscribe("market_data.instrument.*").await.unwrap();
+    // while let Some(message) = subscriber.next().await {
+    //     let bbo: BboUpdate = Encoding::decode_any(&message.payload)?; ...
+    // }
+    let mut feed = SimulatedQuotes::new(&instruments, rng);
+    let mut signals: HashMap<u32, SignalBuilder> = HashMap::new();
+    let mut interval = time::interval(Duration::from_secs(1));
+    // Drops are logged at 1, 2, 4, 8, ... so a sustained overload does not flood the log.
+    let mut next_drop_report: u64 = 1;
+    loop {
+        interval.tick().await;
+        for bbo in feed.next_quotes() {
+            let Some(definition) = instruments.get(bbo.instrument_id) else {
+                continue;
+            };
+            let book = TopOfBook {
+                bid: definition.price(bbo.best_bid_price).to_f64(),
+                bid_size: bbo.best_bid_size as f64,
+                ask: definition.price(bbo.best_ask_price).to_f64(),
+                ask_size: bbo.best_ask_size as f64,
+            };
+            let builder = signals.entry(bbo.instrument_id).or_insert_with(SignalBuilder::from_env);
+            let book_signals = builder.on_book(bbo.instrument_id, &definition.symbol, book, bbo.timestamp_ns);
+            if let Some(book_signals) = &book_signals {
+                quantumarb_bus::publish_json(&topics::signals(bbo.instrument_id), book_signals);
+            }
+            let quote = FeatureInput::Quote { symbol: definition.symbol.clone(), bid: book.bid, ask: book.ask };
+            let inputs = std::iter::once(quote).chain(book_signals.map(FeatureInput::Signals));
+            for input in inputs {
+                if features.send(input).await.is_err() {
+                    return; // The feature store has stopped.
+                }
+            }
+        }
+        let dropped = features.stats().dropped;
+        if dropped >= next_drop_report {
+            println!("  -> Feature store busy; {} oldest quotes dropped so far.", dropped);
+            next_drop_report = dropped * 2;
+        }
+    }
+}
+
+/// A random walk around a starting mid for each instrument, quoted a tick
+/// either side with random sizes.
+struct SimulatedQuotes {
+    /// (instrument, mid in points)
+    instruments: Vec<(InstrumentDefinition, f64)>,
+    noise: Normal<f64>,
+    sizes: Uniform<u32>,
+    rng: SimRng,
+}
+
+impl SimulatedQuotes {
+    fn new(instruments: &ReferenceData, rng: SimRng) -> Self {
+        let start = |symbol: &str| match symbol {
+            "BTC" => 60000.0,
+            "ETH" => 3000.0,
+            "ESZ25" => 4500.0,
+            _ => 150.0,
+        };
+        SimulatedQuotes {
+            instruments: instruments
+                .all()
+                .into_iter()
+                .map(|d| {
+                    let mid = start(&d.symbol);
+                    (d, mid)
+                })
+                .collect(),
+            noise: Normal::new(0.0, 0.0002).unwrap(),
+            sizes: Uniform::new_inclusive(1, 20),
+            rng,
+        }
+    }
+
+    fn next_quotes(&mut self) -> Vec<BboUpdate> {
+        let timestamp_ns = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+        self.instruments
+            .iter_mut()
+            .map(|(definition, mid)| {
+                *mid *= 1.0 + self.noise.sample(&mut self.rng);
+                let mid_price = Price::from_f64(*mid);
+                BboUpdate {
+                    instrument_id: definition.instrument_id,
+                    best_bid_price: definition.wire_price(mid_price - definition.tick_size),
+                    best_bid_size: self.sizes.sample(&mut self.rng),
+                    best_ask_price: definition.wire_price(mid_price + definition.tick_size),
+                    best_ask_size: self.sizes.sample(&mut self.rng),
+                    timestamp_ns,
+                    venue_id: 0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Publishes the corporate actions from QA_CORPORATE_ACTIONS_PATH, then
+/// polls the (simulated) reference data vendor for new ones.
+async fn run_corporate_actions_adapter() {
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
+    for action in quantumarb_corporate_actions::load_from_env() {
+        publish_corporate_action(&action);
+    }
+
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let raw_message: RawCorporateActionMessage = serde_json::from_str(&get_simulated_corporate_action()).unwrap();
+        match normalize_corporate_action(&raw_message, &instruments) {
+            Ok(action) => publish_corporate_action(&action),
+            Err(e) => println!("\nDropping corporate action {}: {}", raw_message.reference, e),
+        }
+    }
+}
+
+/// Simulates a record from the vendor's corporate actions endpoint.
+fn get_simulated_corporate_action() -> String {
+    r#"{
+        "reference": "CA-INVT-2025-0611",
+        "ticker": "INVT",
+        "event": "SPLIT",
+        "ex_date": "2025-06-11",
+        "ratio": "4:1"
+    }"#
+    .to_string()
+}
+
+/// Transforms a vendor record into the shared `CorporateAction` event.
+fn normalize_corporate_action(raw: &RawCorporateActionMessage, instruments: &ReferenceData) -> Result<CorporateAction, String> {
+    let parse_date = |field: &str, value: Option<&String>| {
+        let value = value.ok_or_else(|| format!("missing {}", field))?;
+        chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("bad {} '{}': {}", field, value, e))
+    };
+    let instrument_id = instruments
+        .by_symbol(&raw.ticker)
+        .map(|d| d.instrument_id)
+        .ok_or_else(|| format!("unknown ticker {}", raw.ticker))?;
+
+    let kind = match raw.event.as_str() {
+        "SPLIT" => {
+            let ratio = raw.ratio.as_deref().ok_or("missing ratio")?;
+            let (new_shares, old_shares) = ratio
+                .split_once(':')
+                .and_then(|(n, o)| Some((n.trim().parse().ok()?, o.trim().parse().ok()?)))
+                .filter(|(n, o): &(u32, u32)| *n > 0 && *o > 0)
+                .ok_or_else(|| format!("bad ratio '{}'", ratio))?;
+            CorporateActionKind::Split { new_shares, old_shares }
+        }
+        "DIVIDEND" => CorporateActionKind::CashDividend {
+            amount_per_share: raw.gross_amount.ok_or("missing gross_amount")?,
+            currency: raw.currency.clone().unwrap_or_else(|| "USD".to_string()),
+            pay_date: parse_date("pay_date", raw.pay_date.as_ref())?,
+        },
+        "TICKER_CHANGE" => CorporateActionKind::SymbolChange {
+            new_symbol: raw.new_ticker.clone().ok_or("missing new_ticker")?,
+        },
+        other => return Err(format!("unsupported event type {}", other)),
+    };
+
+    Ok(CorporateAction {
+        action_id: raw.reference.clone(),
+        instrument_id,
+        symbol: raw.ticker.clone(),
+        ex_date: parse_date("ex_date", Some(&raw.ex_date))?,
+        kind,
+    })
+}
+
+/// Simulates publishing a corporate action to the internal message bus.
+fn publish_corporate_action(action: &CorporateAction) {
+    quantumarb_bus::publish_json(topics::CORPORATE_ACTIONS, action);
 }
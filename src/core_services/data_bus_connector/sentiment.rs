@@ -0,0 +1,196 @@
+/*
+ * QuantumArb 2.0 - Core Services: Sentiment Aggregation
+ *
+ * File: src/core_services/data_bus_connector/sentiment.rs
+ *
+ * Description:
+ * Turns the point-in-time news events into per-symbol sentiment snapshots
+ * over the 1m, 5m and 1h windows of `quantumarb_features::SentimentWindows`
+ * (event count, mean and decay-weighted score per window), the same
+ * aggregation the feature store uses for the models' news_sentiment input.
+ *
+ * Every interval a snapshot of each symbol with news in the last hour is
+ * published on 'alt_data.sentiment'. The latest aggregation is also served
+ * over HTTP:
+ *
+ *   GET /sentiment            every symbol with news in the last hour
+ *   GET /sentiment/{symbol}   one symbol (all-zero counts without news)
+ *   GET /openapi.json         OpenAPI document of the above
+ *   GET /docs                 Swagger UI for the OpenAPI document
+ *
+ * Configuration (environment):
+ *   QA_SENTIMENT_PUBLISH_SECS=10   snapshot publishing interval
+ */
+
+use quantumarb_features::{SentimentWindow, SentimentWindows};
+use quantumarb_openapi::{ApiDoc, ApiSchema};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration};
+use warp::Filter;
+
+pub const SENTIMENT_TOPIC: &str = "alt_data.sentiment";
+const API_PORT: u16 = 3041;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SentimentConfig {
+    pub publish_interval: Duration,
+}
+
+impl SentimentConfig {
+    pub fn from_env() -> SentimentConfig {
+        let secs = std::env::var("QA_SENTIMENT_PUBLISH_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(10);
+        SentimentConfig { publish_interval: Duration::from_secs(secs) }
+    }
+}
+
+// --- Data Structures ---
+
+/// Published on 'alt_data.sentiment' and served on GET /sentiment.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct SentimentSnapshot {
+    pub symbol: String,
+    /// One entry per window, shortest first.
+    pub windows: Vec<SentimentWindow>,
+    pub timestamp_utc: String,
+}
+
+/// The sentiment windows of every symbol, shared by the news loop, the
+/// publisher and the API.
+#[derive(Clone, Default)]
+pub struct SentimentBoard(Arc<Mutex<BTreeMap<String, SentimentWindows>>>);
+
+impl SentimentBoard {
+    pub fn record(&self, symbols: &[String], sentiment: f64, at_ms: i64) {
+        let mut board = self.0.lock().unwrap();
+        for symbol in symbols {
+            board.entry(symbol.clone()).or_default().record(sentiment, at_ms);
+        }
+    }
+
+    /// Snapshots every symbol with news in the last hour, forgetting the rest.
+    pub fn snapshots(&self) -> Vec<SentimentSnapshot> {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let mut board = self.0.lock().unwrap();
+        let snapshots: Vec<SentimentSnapshot> = board
+            .iter_mut()
+            .map(|(symbol, windows)| snapshot(symbol, windows, now_ms))
+            .filter(|s| s.windows.iter().any(|w| w.count > 0))
+            .collect();
+        board.retain(|_, windows| !windows.is_empty());
+        snapshots
+    }
+
+    pub fn snapshot(&self, symbol: &str) -> SentimentSnapshot {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let mut board = self.0.lock().unwrap();
+        match board.get_mut(symbol) {
+            Some(windows) => snapshot(symbol, windows, now_ms),
+            None => snapshot(symbol, &mut SentimentWindows::default(), now_ms),
+        }
+    }
+}
+
+fn snapshot(symbol: &str, windows: &mut SentimentWindows, now_ms: i64) -> SentimentSnapshot {
+    SentimentSnapshot {
+        symbol: symbol.to_string(),
+        windows: windows.windows(now_ms),
+        timestamp_utc: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+// --- Main Application Logic ---
+
+/// Publishes every active symbol's snapshot once per interval.
+pub async fn run_publisher(config: SentimentConfig, board: SentimentBoard) {
+    let mut interval = time::interval(config.publish_interval);
+    loop {
+        interval.tick().await;
+        for snapshot in board.snapshots() {
+            quantumarb_bus::publish_json(SENTIMENT_TOPIC, &snapshot);
+        }
+    }
+}
+
+/// Serves the latest aggregation.
+pub async fn run_api(board: SentimentBoard) {
+    let list = warp::path!("sentiment").and(warp::get()).and(with_state(board.clone())).and_then(handler_list);
+    let get = warp::path!("sentiment" / String).and(warp::get()).and(with_state(board)).and_then(handler_get);
+
+    println!("Sentiment API running at http://127.0.0.1:{}/sentiment", API_PORT);
+    warp::serve(list.or(get).or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], API_PORT)).await;
+}
+
+/// The OpenAPI document of the sentiment endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Sentiment", "News sentiment per symbol over 1m, 5m and 1h windows.")
+        .get::<Vec<SentimentSnapshot>>("/sentiment", "Every symbol with news in the last hour")
+        .get::<SentimentSnapshot>("/sentiment/{symbol}", "One symbol")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /sentiment.
+async fn handler_list(board: SentimentBoard) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&board.snapshots()))
+}
+
+/// Handler for GET /sentiment/{symbol}.
+async fn handler_get(symbol: String, board: SentimentBoard) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&board.snapshot(&symbol)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes_ago(minutes: i64) -> i64 {
+        chrono::Utc::now().timestamp_millis() - minutes * 60_000
+    }
+
+    fn counts(snapshot: &SentimentSnapshot) -> Vec<(&str, u32)> {
+        snapshot.windows.iter().map(|w| (w.window.as_str(), w.count)).collect()
+    }
+
+    #[test]
+    fn news_counts_in_every_window_it_falls_in() {
+        let board = SentimentBoard::default();
+        board.record(&["BTC".to_string(), "ETH".to_string()], 0.8, minutes_ago(3));
+        board.record(&["BTC".to_string()], -0.4, minutes_ago(0));
+
+        let btc = board.snapshot("BTC");
+        assert_eq!(counts(&btc), [("1m", 1), ("5m", 2), ("1h", 2)]);
+        assert_eq!(btc.windows[0].mean, Some(-0.4));
+        assert!((btc.windows[1].mean.unwrap() - 0.2).abs() < 1e-9);
+        // The newer, bearish event weighs more than the older one.
+        assert!(btc.windows[1].decay_weighted.unwrap() < 0.2);
+        assert_eq!(counts(&board.snapshot("ETH")), [("1m", 0), ("5m", 1), ("1h", 1)]);
+    }
+
+    #[test]
+    fn symbols_without_news_in_the_last_hour_are_dropped() {
+        let board = SentimentBoard::default();
+        board.record(&["BTC".to_string()], 0.5, minutes_ago(10));
+        board.record(&["ETH".to_string()], 0.5, minutes_ago(61));
+
+        let symbols: Vec<String> = board.snapshots().into_iter().map(|s| s.symbol).collect();
+        assert_eq!(symbols, ["BTC"]);
+        assert_eq!(board.0.lock().unwrap().len(), 1);
+
+        let eth = board.snapshot("ETH");
+        assert_eq!(counts(&eth), [("1m", 0), ("5m", 0), ("1h", 0)]);
+        assert!(eth.windows.iter().all(|w| w.mean.is_none() && w.decay_weighted.is_none()));
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * QuantumArb 2.0 - Core Services: Weather Adapter
+ *
+ * File: src/core_services/data_bus_connector/weather.rs
+ *
+ * Description:
+ * Microwave links fade in rain. This adapter polls a weather API for the
+ * hourly precipitation forecast at waypoints along the microwave link's
+ * path, normalizes the vendor's column-oriented response into a
+ * `quantumarb_types::LinkWeatherForecast` and publishes it on
+ * 'alt_data.weather', where the latency oracle reads it to downgrade the
+ * microwave route before the rain arrives.
+ *
+ * The vendor answers one request per waypoint with parallel hourly arrays:
+ *
+ *   {"latitude": 41.50, "longitude": -81.69,
+ *    "hourly": {"time": ["2025-07-31T12:00", ...],   (UTC)
+ *               "precipitation": [0.0, ...],          (mm in the hour)
+ *               "precipitation_probability": [5, ...]}} (percent)
+ *
+ * Configuration (environment):
+ *   QA_WEATHER_API_URL          forecast endpoint, queried with
+ *                               ?latitude=..&longitude=..&hourly=...
+ *   QA_WEATHER_POLL_SECS=600    polling interval
+ *   QA_MW_LINK_NAME=CHI-NJ      link the forecast is published for
+ *   QA_MW_LINK_WAYPOINTS        "name:lat,lon;name:lat,lon;...", the default
+ *                               follows the Chicago - New Jersey route
+ *
+ * The vendor responses are simulated, from a seeded stream so a run with
+ * --seed N (or QA_SEED) sees the same storms.
+ */
+
+use chrono::{DateTime, NaiveDateTime, Timelike, Utc};
+use quantumarb_bus::topics;
+use quantumarb_sim::SimRng;
+use quantumarb_types::{LinkWeatherForecast, PrecipitationHour, WaypointForecast};
+use rand::Rng;
+use serde::Deserialize;
+use tokio::time::{self, Duration};
+
+const DEFAULT_API_URL: &str = "https://api.fictional-weather.com/v1/forecast";
+const DEFAULT_WAYPOINTS: &str = "Aurora IL:41.76,-88.32;Fort Wayne IN:41.08,-85.14;Cleveland OH:41.50,-81.69;\
+                                 Bradford PA:41.96,-78.64;Carteret NJ:40.58,-74.23";
+/// Hours of forecast requested per waypoint.
+const FORECAST_HOURS: usize = 6;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct Waypoint {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WeatherConfig {
+    pub api_url: String,
+    pub poll_interval: Duration,
+    pub link: String,
+    pub waypoints: Vec<Waypoint>,
+}
+
+impl WeatherConfig {
+    pub fn from_env() -> WeatherConfig {
+        let waypoints = std::env::var("QA_MW_LINK_WAYPOINTS").ok().and_then(|spec| match parse_waypoints(&spec) {
+            Ok(waypoints) => Some(waypoints),
+            Err(e) => {
+                println!("Ignoring QA_MW_LINK_WAYPOINTS: {}", e);
+                None
+            }
+        });
+        let poll_secs = std::env::var("QA_WEATHER_POLL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(600);
+        WeatherConfig {
+            api_url: std::env::var("QA_WEATHER_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            poll_interval: Duration::from_secs(poll_secs),
+            link: std::env::var("QA_MW_LINK_NAME").unwrap_or_else(|_| "CHI-NJ".to_string()),
+            waypoints: waypoints
+                .unwrap_or_else(|| parse_waypoints(DEFAULT_WAYPOINTS).expect("default waypoints parse")),
+        }
+    }
+}
+
+/// Parses "name:lat,lon;name:lat,lon;...".
+fn parse_waypoints(spec: &str) -> Result<Vec<Waypoint>, String> {
+    let waypoints = spec
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, coordinates) = entry.rsplit_once(':').ok_or_else(|| format!("'{}' has no coordinates", entry))?;
+            let (latitude, longitude) = coordinates
+                .split_once(',')
+                .and_then(|(lat, lon)| Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?)))
+                .ok_or_else(|| format!("bad coordinates '{}'", coordinates))?;
+            Ok(Waypoint { name: name.trim().to_string(), latitude, longitude })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if waypoints.is_empty() {
+        return Err("no waypoints".to_string());
+    }
+    Ok(waypoints)
+}
+
+// --- Vendor Format ---
+
+/// One waypoint's response from the weather API.
+#[derive(Debug, Deserialize)]
+struct RawForecast {
+    hourly: RawHourly,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawHourly {
+    time: Vec<String>,
+    precipitation: Vec<f64>,
+    precipitation_probability: Vec<f64>,
+}
+
+/// Transforms a vendor response into the waypoint's shared forecast.
+fn normalize_forecast(waypoint: &Waypoint, raw: RawForecast) -> Result<WaypointForecast, String> {
+    let hourly = raw.hourly;
+    if hourly.precipitation.len() != hourly.time.len() || hourly.precipitation_probability.len() != hourly.time.len() {
+        return Err(format!(
+            "hourly arrays differ in length ({} times, {} amounts, {} probabilities)",
+            hourly.time.len(),
+            hourly.precipitation.len(),
+            hourly.precipitation_probability.len()
+        ));
+    }
+    let hours = hourly
+        .time
+        .iter()
+        .zip(hourly.precipitation)
+        .zip(hourly.precipitation_probability)
+        .map(|((time, precipitation), probability)| {
+            let starts = NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+                .map_err(|e| format!("bad time '{}': {}", time, e))?;
+            Ok(PrecipitationHour {
+                starts_utc: starts.and_utc(),
+                precipitation_mm_per_hr: precipitation.max(0.0),
+                probability: (probability / 100.0).clamp(0.0, 1.0),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(WaypointForecast {
+        name: waypoint.name.clone(),
+        latitude: waypoint.latitude,
+        longitude: waypoint.longitude,
+        hours,
+    })
+}
+
+// --- Main Application Logic ---
+
+/// Polls the forecast for every waypoint and publishes the link's forecast.
+/// A waypoint whose response cannot be normalized is left out of that poll.
+pub async fn run_weather_adapter(config: WeatherConfig, mut rng: SimRng) {
+    println!("Polling '{}' for {} waypoints along link {}", config.api_url, config.waypoints.len(), config.link);
+    let mut interval = time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let mut waypoints = Vec::with_capacity(config.waypoints.len());
+        for waypoint in &config.waypoints {
+            // In a real system:
+            // let body = http_client.get(&config.api_url).query(&[("latitude", ..), ("longitude", ..),
+            //     ("hourly", "precipitation,precipitation_probability")]).send().await?.text().await?;
+            let body = get_simulated_forecast(waypoint, now, &mut rng);
+            let forecast = serde_json::from_str::<RawForecast>(&body)
+                .map_err(|e| e.to_string())
+                .and_then(|raw| normalize_forecast(waypoint, raw));
+            match forecast {
+                Ok(forecast) => waypoints.push(forecast),
+                Err(e) => println!("\nDropping the weather forecast for {}: {}", waypoint.name, e),
+            }
+        }
+        if waypoints.is_empty() {
+            continue;
+        }
+        let forecast = LinkWeatherForecast { link: config.link.clone(), waypoints, issued_utc: now };
+        quantumarb_bus::publish_json(topics::WEATHER_FORECASTS, &forecast);
+    }
+}
+
+/// Simulates the weather API's answer for one waypoint: mostly dry, with
+/// the odd storm cell passing over in the next few hours.
+fn get_simulated_forecast(waypoint: &Waypoint, now: DateTime<Utc>, rng: &mut SimRng) -> String {
+    let first_hour = now.with_minute(0).and_then(|t| t.with_second(0)).and_then(|t| t.with_nanosecond(0));
+    let first_hour = first_hour.unwrap_or(now);
+    let storm_hour = rng.gen_bool(0.15).then(|| rng.gen_range(0..3));
+    let mut time = Vec::with_capacity(FORECAST_HOURS);
+    let mut precipitation = Vec::with_capacity(FORECAST_HOURS);
+    let mut probability = Vec::with_capacity(FORECAST_HOURS);
+    for hour in 0..FORECAST_HOURS {
+        let starts = first_hour + chrono::Duration::hours(hour as i64);
+        time.push(starts.format("%Y-%m-%dT%H:%M").to_string());
+        if Some(hour) == storm_hour {
+            precipitation.push((rng.gen_range(4.0..15.0_f64) * 10.0).round() / 10.0);
+            probability.push(rng.gen_range(60..=95) as f64);
+        } else {
+            precipitation.push(0.0);
+            probability.push(rng.gen_range(0..=10) as f64);
+        }
+    }
+    serde_json::json!({
+        "latitude": waypoint.latitude,
+        "longitude": waypoint.longitude,
+        "hourly": {
+            "time": time,
+            "precipitation": precipitation,
+            "precipitation_probability": probability,
+        }
+    })
+    .to_string()
+}
+
@@ -0,0 +1,330 @@
+/*
+ * QuantumArb 2.0 - Core Services: Market Data Quality Checks
+ *
+ * File: src/core_services/data_quality_monitor/checks.rs
+ *
+ * Description:
+ * Per-instrument checks on the normalized top-of-book feed:
+ *
+ *   CrossedQuote      best bid at or above best ask
+ *   StaleTick         exchange timestamp older than QA_DQ_STALE_MS on
+ *                     arrival, or older than the previous tick
+ *   OutlierPrice      log return of the mid beyond QA_DQ_OUTLIER_SIGMAS
+ *                     standard deviations of the recent returns
+ *   MissingHeartbeat  no tick for QA_DQ_HEARTBEAT_SECS
+ *
+ * An instrument becomes suspect on its first failed check and is cleared
+ * after QA_DQ_RECOVERY_TICKS consecutive clean ticks. A Suspect alert is
+ * raised when an instrument becomes suspect (or fails a different check
+ * while suspect) and a Cleared alert when it recovers, so consumers can keep
+ * their own suspect set from the alerts alone. The alert and its issues are
+ * `quantumarb-types` definitions, shared with the strategy engine.
+ *
+ * Crossed and stale ticks are left out of the return statistics; so are
+ * outlier returns, to stop one bad print from widening the threshold that is
+ * meant to catch the next.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::{DataQualityAlert, Issue, QualityStatus};
+use quantumarb_wire::BboUpdate;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// --- Configuration ---
+
+/// Returns needed before the outlier check is applied.
+const MIN_RETURN_SAMPLES: usize = 30;
+/// Returns kept per instrument for the volatility estimate.
+const RETURN_WINDOW: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    pub stale_after_ns: u64,
+    pub outlier_sigmas: f64,
+    pub heartbeat_timeout: Duration,
+    pub recovery_ticks: u32,
+}
+
+impl QualityConfig {
+    pub fn from_env() -> QualityConfig {
+        let number = |name: &str, default: f64| {
+            std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok()).filter(|v| *v > 0.0).unwrap_or(default)
+        };
+        QualityConfig {
+            stale_after_ns: (number("QA_DQ_STALE_MS", 500.0) * 1_000_000.0) as u64,
+            outlier_sigmas: number("QA_DQ_OUTLIER_SIGMAS", 6.0),
+            heartbeat_timeout: Duration::from_secs_f64(number("QA_DQ_HEARTBEAT_SECS", 5.0)),
+            recovery_ticks: number("QA_DQ_RECOVERY_TICKS", 10.0) as u32,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// Per-instrument state served on GET /data-quality.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct InstrumentReport {
+    pub instrument_id: u32,
+    pub ticks: u64,
+    pub suspect: Option<Issue>,
+    pub issue_counts: BTreeMap<Issue, u64>,
+    pub last_tick_age_ms: Option<u64>,
+    /// Standard deviation of recent mid log returns.
+    pub return_volatility: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct InstrumentQuality {
+    ticks: u64,
+    last_received: Option<Instant>,
+    last_exchange_ns: u64,
+    last_mid: Option<f64>,
+    returns: VecDeque<f64>,
+    suspect: Option<Issue>,
+    clean_ticks: u32,
+    issue_counts: BTreeMap<Issue, u64>,
+}
+
+impl InstrumentQuality {
+    fn return_volatility(&self) -> Option<f64> {
+        if self.returns.len() < MIN_RETURN_SAMPLES {
+            return None;
+        }
+        let n = self.returns.len() as f64;
+        let mean = self.returns.iter().sum::<f64>() / n;
+        let variance = self.returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Some(variance.sqrt())
+    }
+
+    /// Records a failed check; returns an alert if this changes the instrument's state.
+    fn fail(&mut self, instrument_id: u32, issue: Issue, detail: String) -> Option<DataQualityAlert> {
+        *self.issue_counts.entry(issue).or_insert(0) += 1;
+        self.clean_ticks = 0;
+        if self.suspect == Some(issue) {
+            return None;
+        }
+        self.suspect = Some(issue);
+        Some(alert(instrument_id, QualityStatus::Suspect, issue, detail))
+    }
+}
+
+/// Runs the checks for every instrument seen on the feed.
+pub struct QualityMonitor {
+    config: QualityConfig,
+    instruments: HashMap<u32, InstrumentQuality>,
+}
+
+// --- Checks ---
+
+impl QualityMonitor {
+    pub fn new(config: QualityConfig) -> Self {
+        QualityMonitor { config, instruments: HashMap::new() }
+    }
+
+    /// Checks one tick. `received_ns` is the arrival time on the same clock
+    /// as the exchange timestamp (nanoseconds since the Unix epoch).
+    pub fn on_tick(&mut self, bbo: &BboUpdate, received_ns: u64, now: Instant) -> Option<DataQualityAlert> {
+        let config = &self.config;
+        let state = self.instruments.entry(bbo.instrument_id).or_default();
+        state.ticks += 1;
+        state.last_received = Some(now);
+
+        if bbo.best_bid_price >= bbo.best_ask_price {
+            let detail = format!("Bid {} at or above ask {}.", bbo.best_bid_price, bbo.best_ask_price);
+            return state.fail(bbo.instrument_id, Issue::CrossedQuote, detail);
+        }
+
+        let lag_ns = received_ns.saturating_sub(bbo.timestamp_ns);
+        if lag_ns > config.stale_after_ns || bbo.timestamp_ns < state.last_exchange_ns {
+            let detail = if lag_ns > config.stale_after_ns {
+                format!("Tick arrived {}ms after its exchange timestamp.", lag_ns / 1_000_000)
+            } else {
+                format!("Tick is {}ms older than the previous one.", (state.last_exchange_ns - bbo.timestamp_ns) / 1_000_000)
+            };
+            return state.fail(bbo.instrument_id, Issue::StaleTick, detail);
+        }
+        state.last_exchange_ns = bbo.timestamp_ns;
+
+        let mid = (bbo.best_bid_price + bbo.best_ask_price) as f64 / 2.0;
+        let previous_mid = state.last_mid.replace(mid);
+        if let Some(previous_mid) = previous_mid {
+            let log_return = (mid / previous_mid).ln();
+            match state.return_volatility() {
+                Some(sigma) if sigma > 0.0 && log_return.abs() > config.outlier_sigmas * sigma => {
+                    let detail = format!(
+                        "Mid moved {:.1} bps ({:.1} sigma) from {} to {}.",
+                        log_return * 10_000.0,
+                        log_return.abs() / sigma,
+                        previous_mid,
+                        mid
+                    );
+                    return state.fail(bbo.instrument_id, Issue::OutlierPrice, detail);
+                }
+                _ => {
+                    state.returns.push_back(log_return);
+                    if state.returns.len() > RETURN_WINDOW {
+                        state.returns.pop_front();
+                    }
+                }
+            }
+        }
+
+        // A clean tick.
+        let issue = state.suspect?;
+        state.clean_ticks += 1;
+        if state.clean_ticks < config.recovery_ticks {
+            return None;
+        }
+        state.suspect = None;
+        state.clean_ticks = 0;
+        let detail = format!("{} consecutive clean ticks.", config.recovery_ticks);
+        Some(alert(bbo.instrument_id, QualityStatus::Cleared, issue, detail))
+    }
+
+    /// Flags every instrument that has gone quiet for longer than the heartbeat timeout.
+    pub fn check_heartbeats(&mut self, now: Instant) -> Vec<DataQualityAlert> {
+        let timeout = self.config.heartbeat_timeout;
+        let mut alerts = Vec::new();
+        for (instrument_id, state) in self.instruments.iter_mut() {
+            let Some(last_received) = state.last_received else { continue };
+            let silence = now.duration_since(last_received);
+            if silence <= timeout || state.suspect == Some(Issue::MissingHeartbeat) {
+                continue;
+            }
+            let detail = format!("No tick for {:.1}s.", silence.as_secs_f64());
+            alerts.extend(state.fail(*instrument_id, Issue::MissingHeartbeat, detail));
+        }
+        alerts
+    }
+
+    /// Whether any instrument has ticked within the heartbeat timeout.
+    pub fn feed_live(&self, now: Instant) -> bool {
+        self.instruments
+            .values()
+            .filter_map(|state| state.last_received)
+            .any(|last_received| now.duration_since(last_received) <= self.config.heartbeat_timeout)
+    }
+
+    pub fn report(&self, now: Instant) -> Vec<InstrumentReport> {
+        let mut reports: Vec<InstrumentReport> = self
+            .instruments
+            .iter()
+            .map(|(instrument_id, state)| InstrumentReport {
+                instrument_id: *instrument_id,
+                ticks: state.ticks,
+                suspect: state.suspect,
+                issue_counts: state.issue_counts.clone(),
+                last_tick_age_ms: state.last_received.map(|t| now.duration_since(t).as_millis() as u64),
+                return_volatility: state.return_volatility(),
+            })
+            .collect();
+        reports.sort_by_key(|r| r.instrument_id);
+        reports
+    }
+}
+
+fn alert(instrument_id: u32, status: QualityStatus, issue: Issue, detail: String) -> DataQualityAlert {
+    DataQualityAlert { instrument_id, status, issue, detail, timestamp_utc: chrono::Utc::now().to_rfc3339() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn monitor() -> QualityMonitor {
+        QualityMonitor::new(QualityConfig {
+            stale_after_ns: 500 * MS,
+            outlier_sigmas: 6.0,
+            heartbeat_timeout: Duration::from_secs(5),
+            recovery_ticks: 3,
+        })
+    }
+
+    fn bbo(bid: u64, ask: u64, timestamp_ns: u64) -> BboUpdate {
+        BboUpdate {
+            instrument_id: 1,
+            best_bid_price: bid,
+            best_bid_size: 1,
+            best_ask_price: ask,
+            best_ask_size: 1,
+            timestamp_ns,
+            venue_id: 1,
+        }
+    }
+
+    /// Feeds `count` clean ticks, one a millisecond from `from_ns`, with the
+    /// mid alternating by a cent; returns the alerts raised.
+    fn clean_ticks(monitor: &mut QualityMonitor, from_ns: u64, count: u64) -> Vec<DataQualityAlert> {
+        (0..count)
+            .filter_map(|n| {
+                let (bid, ask) = if n % 2 == 0 { (60_000_00, 60_001_00) } else { (60_000_01, 60_001_01) };
+                let ts = from_ns + n * MS;
+                monitor.on_tick(&bbo(bid, ask, ts), ts + MS, Instant::now())
+            })
+            .collect()
+    }
+
+    fn status(alert: &DataQualityAlert) -> (QualityStatus, Issue) {
+        (alert.status, alert.issue)
+    }
+
+    #[test]
+    fn a_crossed_quote_makes_the_instrument_suspect_until_it_recovers() {
+        let mut monitor = monitor();
+        assert!(clean_ticks(&mut monitor, 0, 5).is_empty());
+        let crossed = monitor.on_tick(&bbo(60_001_00, 60_001_00, 10 * MS), 11 * MS, Instant::now()).unwrap();
+        assert_eq!(status(&crossed), (QualityStatus::Suspect, Issue::CrossedQuote));
+        let again = monitor.on_tick(&bbo(60_002_00, 60_001_00, 11 * MS), 12 * MS, Instant::now());
+        assert!(again.is_none(), "already suspect for it");
+
+        let alerts = clean_ticks(&mut monitor, 20 * MS, 3);
+        assert_eq!(alerts.iter().map(status).collect::<Vec<_>>(), [(QualityStatus::Cleared, Issue::CrossedQuote)]);
+        let report = &monitor.report(Instant::now())[0];
+        assert_eq!((report.suspect, report.issue_counts[&Issue::CrossedQuote]), (None, 2));
+    }
+
+    #[test]
+    fn late_or_out_of_order_ticks_are_stale() {
+        let mut monitor = monitor();
+        let late = monitor.on_tick(&bbo(60_000_00, 60_001_00, 0), 600 * MS, Instant::now()).unwrap();
+        assert_eq!(status(&late), (QualityStatus::Suspect, Issue::StaleTick));
+        assert!(late.detail.contains("600ms"));
+
+        let mut monitor = self::monitor();
+        clean_ticks(&mut monitor, 100 * MS, 1);
+        let older = monitor.on_tick(&bbo(60_000_00, 60_001_00, 50 * MS), 101 * MS, Instant::now()).unwrap();
+        assert_eq!(status(&older), (QualityStatus::Suspect, Issue::StaleTick));
+        assert!(older.detail.contains("50ms older"), "{}", older.detail);
+    }
+
+    #[test]
+    fn a_jump_beyond_the_return_volatility_is_an_outlier() {
+        let mut monitor = monitor();
+        assert!(clean_ticks(&mut monitor, 0, MIN_RETURN_SAMPLES as u64 + 1).is_empty());
+        let volatility = monitor.report(Instant::now())[0].return_volatility.unwrap();
+
+        let ts = 100 * MS;
+        let jump = monitor.on_tick(&bbo(60_600_00, 60_601_00, ts), ts + MS, Instant::now()).unwrap();
+        assert_eq!(status(&jump), (QualityStatus::Suspect, Issue::OutlierPrice));
+        assert_eq!(monitor.report(Instant::now())[0].return_volatility, Some(volatility), "left out of the returns");
+    }
+
+    #[test]
+    fn silence_beyond_the_heartbeat_timeout_is_flagged_once() {
+        let mut monitor = monitor();
+        let start = Instant::now();
+        monitor.on_tick(&bbo(60_000_00, 60_001_00, 0), MS, start);
+        assert!(monitor.check_heartbeats(start + Duration::from_secs(4)).is_empty());
+        assert!(monitor.feed_live(start + Duration::from_secs(4)));
+
+        let later = start + Duration::from_secs(6);
+        let alerts = monitor.check_heartbeats(later);
+        assert_eq!(alerts.iter().map(status).collect::<Vec<_>>(), [(QualityStatus::Suspect, Issue::MissingHeartbeat)]);
+        assert!(monitor.check_heartbeats(later + Duration::from_secs(1)).is_empty());
+        assert!(!monitor.feed_live(later));
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * QuantumArb 2.0 - Core Services: Data Quality Monitor
+ *
+ * File: src/core_services/data_quality_monitor/main.rs
+ *
+ * Description:
+ * This microservice watches the normalized market data feeds for bad data
+ * before strategies trade on it. Every tick on 'market_data.instrument.*' is
+ * checked for crossed quotes, stale timestamps and outlier prices, and every
+ * instrument for missing heartbeats (see checks.rs for the rules).
+ *
+ * Its primary role is to:
+ * 1. Subscribe to the top-of-book feed and run the checks per instrument.
+ * 2. Publish a Suspect alert on 'alerts.data_quality' when an instrument
+ *    fails a check, and a Cleared alert once its data is clean again. The
+ *    strategy engine pauses trading on instruments it has a Suspect alert for.
+ * 3. Serve each instrument's state and the recent alerts on GET /data-quality
+ *    (the OpenAPI document on GET /openapi.json, browsable on GET /docs).
+ * 4. Publish a "market_data" heartbeat on 'heartbeats' every second while the
+ *    feed is ticking, for the risk gateway's watchdog.
+ * 5. Notify on the routes configured in QA_NOTIFY_CONFIG_PATH when the whole
+ *    feed goes silent (see `quantumarb-notify`).
+ *
+ * With --seed N (or QA_SEED) the simulated feed's price noise is seeded, so
+ * every run walks the same prices.
+ */
+
+mod checks;
+
+use checks::{InstrumentReport, QualityConfig, QualityMonitor};
+use quantumarb_bus::topics;
+use quantumarb_notify::{EventClass, Notifier};
+use quantumarb_openapi::{ApiDoc, ApiSchema};
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_types::{DataQualityAlert, Heartbeat};
+use quantumarb_wire::{BboUpdate, Encoding};
+use rand_distr::{Distribution, Normal};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::time::{self, Duration};
+use warp::Filter;
+
+// --- Data Structures ---
+
+/// Alerts kept for GET /data-quality.
+const RECENT_ALERTS: usize = 100;
+
+struct MonitorState {
+    monitor: QualityMonitor,
+    recent_alerts: VecDeque<DataQualityAlert>,
+}
+
+type SharedMonitor = Arc<Mutex<MonitorState>>;
+
+#[derive(Serialize, ApiSchema)]
+struct DataQualityReport {
+    instruments: Vec<InstrumentReport>,
+    recent_alerts: Vec<DataQualityAlert>,
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Data Quality Monitor ---");
+
+    let config = QualityConfig::from_env();
+    println!("Checks: {:?}", config);
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let state: SharedMonitor = Arc::new(Mutex::new(MonitorState {
+        monitor: QualityMonitor::new(config),
+        recent_alerts: VecDeque::new(),
+    }));
+
+    // Task 1: check every tick on the feed.
+    let feed_state = state.clone();
+    let feed_rng = seed.stream("data_quality_monitor.feed");
+    tokio::spawn(async move {
+        consume_market_data(feed_state, feed_rng).await;
+    });
+
+    // Task 2: look for instruments that have gone quiet.
+    let heartbeat_state = state.clone();
+    let notifier = Notifier::from_env("data-quality-monitor");
+    tokio::spawn(async move {
+        check_heartbeats(heartbeat_state, notifier).await;
+    });
+
+    // --- API Endpoint for the current data quality picture ---
+    let report = warp::path!("data-quality")
+        .and(warp::get())
+        .and(with_state(state))
+        .and_then(handler_get_report);
+
+    println!("API server running at http://127.0.0.1:3038/data-quality");
+    warp::serve(report.or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3038)).await;
+}
+
+/// The OpenAPI document of the data quality endpoint.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Data Quality Monitor", "The market data feeds' quality checks.")
+        .get::<DataQualityReport>("/data-quality", "Each instrument's state and the recent alerts")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /data-quality.
+async fn handler_get_report(state: SharedMonitor) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = state.lock().unwrap();
+    let report = DataQualityReport {
+        instruments: state.monitor.report(Instant::now()),
+        recent_alerts: state.recent_alerts.iter().cloned().collect(),
+    };
+    Ok(warp::reply::json(&report))
+}
+
+/// Consumes the top-of-book feed and checks each tick.
+async fn consume_market_data(state: SharedMonitor, rng: SimRng) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe("market_data.instrument.*").await.unwrap();
+    let mut feed = SimulatedFeed::new(rng);
+    let mut interval = time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        for payload in feed.next_ticks() {
+            let bbo = match Encoding::decode_any::<BboUpdate>(&payload) {
+                Ok(bbo) => bbo,
+                Err(e) => {
+                    println!("  -> Undecodable market data message: {}", e);
+                    continue;
+                }
+            };
+            let mut state = state.lock().unwrap();
+            if let Some(alert) = state.monitor.on_tick(&bbo, epoch_ns(), Instant::now()) {
+                publish_alert(&mut state, alert);
+            }
+        }
+    }
+}
+
+/// Once a second, flags instruments whose feed has gone quiet, and vouches
+/// for the feed with a heartbeat while any instrument is still ticking.
+async fn check_heartbeats(state: SharedMonitor, notifier: Notifier) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut heartbeat = Heartbeat::new("market_data");
+    let mut was_live = false;
+    loop {
+        interval.tick().await;
+        let mut state = state.lock().unwrap();
+        let now = Instant::now();
+        for alert in state.monitor.check_heartbeats(now) {
+            publish_alert(&mut state, alert);
+        }
+        let live = state.monitor.feed_live(now);
+        if live {
+            let heartbeat_json = serde_json::to_string(heartbeat.beat()).unwrap();
+            if !was_live {
+                println!("  -> Feed is live; publishing to topic '{}': {}", topics::HEARTBEATS, heartbeat_json);
+            }
+            // In a real system:
+            // nats_client.publish(topics::HEARTBEATS, heartbeat_json.into()).await.unwrap();
+        } else if was_live {
+            println!("  -> Feed is silent; market data heartbeats stopped.");
+            let detail = "No instrument has ticked within the heartbeat timeout; market data heartbeats stopped.";
+            notifier.notify(notifier.event(EventClass::FeedDown).field("detail", detail));
+        }
+        was_live = live;
+    }
+}
+
+fn publish_alert(state: &mut MonitorState, alert: DataQualityAlert) {
+    quantumarb_bus::publish_json(topics::DATA_QUALITY_ALERTS, &alert);
+    state.recent_alerts.push_back(alert);
+    if state.recent_alerts.len() > RECENT_ALERTS {
+        state.recent_alerts.pop_front();
+    }
+}
+
+fn epoch_ns() -> u64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64
+}
+
+/// A random-walk feed for three instruments with a fault injected into each
+/// 30-second cycle: a crossed BTC quote, an ETH price spike, a burst of late
+/// BTC ticks, and an 8-second gap in the ESZ25 feed.
+struct SimulatedFeed {
+    tick: u64,
+    /// (instrument_id, mid in hundredths, half spread in hundredths)
+    instruments: Vec<(u32, f64, f64)>,
+    noise: Normal<f64>,
+    rng: SimRng,
+    encoding: Encoding,
+}
+
+impl SimulatedFeed {
+    fn new(rng: SimRng) -> Self {
+        SimulatedFeed {
+            tick: 0,
+            instruments: vec![(1, 60000_00.0, 5.0), (2, 3000_00.0, 6.0), (3, 4500_00.0, 12.5)],
+            noise: Normal::new(0.0, 0.0001).unwrap(),
+            rng,
+            encoding: Encoding::from_env(),
+        }
+    }
+
+    fn next_ticks(&mut self) -> Vec<Vec<u8>> {
+        self.tick += 1;
+        let phase = self.tick % 300;
+        let now_ns = epoch_ns();
+        let mut payloads = Vec::new();
+        for (instrument_id, mid, half_spread) in self.instruments.iter_mut() {
+            if *instrument_id == 3 && (200..280).contains(&phase) {
+                continue; // Feed gap.
+            }
+            *mid *= 1.0 + self.noise.sample(&mut self.rng);
+            let (mut bid, mut ask) = (*mid - *half_spread, *mid + *half_spread);
+            let mut timestamp_ns = now_ns;
+            match (*instrument_id, phase) {
+                (1, 50) => std::mem::swap(&mut bid, &mut ask),
+                (2, 100) => (bid, ask) = (bid * 1.05, ask * 1.05),
+                (1, 150..=152) => timestamp_ns -= 2_000_000_000,
+                _ => {}
+            }
+            let bbo = BboUpdate {
+                instrument_id: *instrument_id,
+                best_bid_price: bid.round() as u64,
+                best_bid_size: 10,
+                best_ask_price: ask.round() as u64,
+                best_ask_size: 10,
+                timestamp_ns,
+                venue_id: 0,
+            };
+            payloads.push(self.encoding.encode(&bbo));
+        }
+        payloads
+    }
+}
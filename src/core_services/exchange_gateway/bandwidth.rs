@@ -0,0 +1,273 @@
+/*
+ * QuantumArb 2.0 - Core Services: Path Bandwidth Accounting
+ *
+ * File: src/core_services/exchange_gateway/bandwidth.rs
+ *
+ * Description:
+ * Holds each metered network path to its message capacity with a token
+ * bucket: `msgs_per_sec` tokens flow in every second up to `burst`, and
+ * every message sent on the path takes one. The router asks the accountant
+ * before choosing a path (see `routing.rs`):
+ *
+ *   opportunistic  needs a token above the reserve (`reserve` x `burst`),
+ *                  which is kept for the higher classes
+ *   arbitrage      needs a token
+ *   hedge          always goes; sent without a token, it overdraws the
+ *                  bucket and the debt is refilled before anyone else sends
+ *
+ * An order the path's budget turns away spills to the next path its policy
+ * ranks, fiber in the end, and is counted as spilled for its class.
+ *
+ * Utilization is the messages sent in the last second over the capacity;
+ * GET /routing shows it per path with its peak, the tokens left and the
+ * orders spilled and overdrawn. A path without a configured capacity is
+ * unmetered: every order may use it and only its traffic is counted.
+ *
+ * Configuration (environment):
+ *   QA_MICROWAVE_MSGS_PER_SEC=500      microwave capacity
+ *   QA_MICROWAVE_BURST=50              messages the microwave bucket holds
+ *   QA_MICROWAVE_RESERVE=0.2           fraction of the burst opportunistic
+ *                                      orders may not use
+ *   QA_FIBER_MSGS_PER_SEC, QA_FIBER_BURST, QA_FIBER_RESERVE
+ *                                      the same for fiber, unmetered unless
+ *                                      QA_FIBER_MSGS_PER_SEC is set
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::OrderPriority;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::venue::NetworkPath;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    pub msgs_per_sec: f64,
+    pub burst: f64,
+    /// Fraction of the burst kept for arbitrage and hedge orders.
+    pub reserve: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthConfig {
+    /// Metered paths; the others are unmetered.
+    pub paths: HashMap<NetworkPath, BucketConfig>,
+}
+
+impl BandwidthConfig {
+    pub fn from_env() -> BandwidthConfig {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+        let bucket = |prefix: &str, default_rate: Option<f64>| {
+            let msgs_per_sec = var::<f64>(&format!("{}_MSGS_PER_SEC", prefix)).or(default_rate)?.max(1.0);
+            Some(BucketConfig {
+                msgs_per_sec,
+                burst: var(&format!("{}_BURST", prefix)).unwrap_or(msgs_per_sec / 10.0).max(1.0),
+                reserve: var(&format!("{}_RESERVE", prefix)).unwrap_or(0.2f64).clamp(0.0, 1.0),
+            })
+        };
+        let paths = [
+            (NetworkPath::Microwave, bucket("QA_MICROWAVE", Some(500.0))),
+            (NetworkPath::Fiber, bucket("QA_FIBER", None)),
+        ];
+        BandwidthConfig {
+            paths: paths.into_iter().filter_map(|(path, bucket)| Some((path, bucket?))).collect(),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// One path's bandwidth on GET /routing.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct PathBandwidth {
+    pub path: NetworkPath,
+    /// None for an unmetered path.
+    pub msgs_per_sec: Option<f64>,
+    pub burst: Option<f64>,
+    /// Tokens kept for arbitrage and hedge orders.
+    pub reserved: Option<f64>,
+    /// Negative while hedges' overdraft is being refilled.
+    pub tokens: Option<f64>,
+    pub sent_last_sec: usize,
+    /// `sent_last_sec` over the capacity.
+    pub utilization: Option<f64>,
+    pub peak_utilization: Option<f64>,
+    pub spilled_opportunistic: u64,
+    pub spilled_arbitrage: u64,
+    /// Messages sent with no token left: hedges, and orders no path had
+    /// room for.
+    pub overdrawn: u64,
+}
+
+#[derive(Debug, Clone)]
+struct PathBucket {
+    config: Option<BucketConfig>,
+    tokens: f64,
+    refilled: Instant,
+    /// When each message of the last second was sent.
+    sent: VecDeque<Instant>,
+    peak_sent: usize,
+    spilled_opportunistic: u64,
+    spilled_arbitrage: u64,
+    overdrawn: u64,
+}
+
+impl PathBucket {
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let Some(config) = self.config else {
+            return 0.0;
+        };
+        let refill = now.saturating_duration_since(self.refilled).as_secs_f64() * config.msgs_per_sec;
+        (self.tokens + refill).min(config.burst)
+    }
+
+    fn sent_last_sec(&self, now: Instant) -> usize {
+        self.sent.iter().filter(|at| now.saturating_duration_since(**at) < Duration::from_secs(1)).count()
+    }
+}
+
+/// Every path's bucket and traffic.
+pub struct BandwidthAccountant {
+    paths: HashMap<NetworkPath, PathBucket>,
+}
+
+impl BandwidthAccountant {
+    pub fn new(config: BandwidthConfig, now: Instant) -> BandwidthAccountant {
+        let paths = [NetworkPath::Microwave, NetworkPath::Fiber]
+            .into_iter()
+            .map(|path| {
+                let config = config.paths.get(&path).copied();
+                let bucket = PathBucket {
+                    config,
+                    tokens: config.map_or(0.0, |c| c.burst),
+                    refilled: now,
+                    sent: VecDeque::new(),
+                    peak_sent: 0,
+                    spilled_opportunistic: 0,
+                    spilled_arbitrage: 0,
+                    overdrawn: 0,
+                };
+                (path, bucket)
+            })
+            .collect();
+        BandwidthAccountant { paths }
+    }
+
+    /// Whether an order in `priority` may take `path` now. A refusal counts
+    /// the order as spilled from the path.
+    pub fn admits(&mut self, path: NetworkPath, priority: OrderPriority, now: Instant) -> bool {
+        let Some(bucket) = self.paths.get_mut(&path) else {
+            return true;
+        };
+        let Some(config) = bucket.config else {
+            return true;
+        };
+        let tokens = bucket.tokens_at(now);
+        let admitted = match priority {
+            OrderPriority::Hedge => true,
+            OrderPriority::Arbitrage => tokens >= 1.0,
+            OrderPriority::Opportunistic => tokens >= 1.0 + config.reserve * config.burst,
+        };
+        match (admitted, priority) {
+            (false, OrderPriority::Opportunistic) => bucket.spilled_opportunistic += 1,
+            (false, _) => bucket.spilled_arbitrage += 1,
+            (true, _) => {}
+        }
+        admitted
+    }
+
+    /// Charges a message sent on `path`.
+    pub fn take(&mut self, path: NetworkPath, now: Instant) {
+        let Some(bucket) = self.paths.get_mut(&path) else {
+            return;
+        };
+        while bucket.sent.front().is_some_and(|at| now.saturating_duration_since(*at) >= Duration::from_secs(1)) {
+            bucket.sent.pop_front();
+        }
+        bucket.sent.push_back(now);
+        bucket.peak_sent = bucket.peak_sent.max(bucket.sent.len());
+        if bucket.config.is_some() {
+            let tokens = bucket.tokens_at(now);
+            if tokens < 1.0 {
+                bucket.overdrawn += 1;
+            }
+            bucket.tokens = tokens - 1.0;
+            bucket.refilled = now;
+        }
+    }
+
+    pub fn status(&self, now: Instant) -> Vec<PathBandwidth> {
+        [NetworkPath::Microwave, NetworkPath::Fiber]
+            .iter()
+            .filter_map(|path| {
+                let bucket = self.paths.get(path)?;
+                let config = bucket.config;
+                let sent_last_sec = bucket.sent_last_sec(now);
+                Some(PathBandwidth {
+                    path: *path,
+                    msgs_per_sec: config.map(|c| c.msgs_per_sec),
+                    burst: config.map(|c| c.burst),
+                    reserved: config.map(|c| c.reserve * c.burst),
+                    tokens: config.map(|_| bucket.tokens_at(now)),
+                    sent_last_sec,
+                    utilization: config.map(|c| sent_last_sec as f64 / c.msgs_per_sec),
+                    peak_utilization: config.map(|c| bucket.peak_sent as f64 / c.msgs_per_sec),
+                    spilled_opportunistic: bucket.spilled_opportunistic,
+                    spilled_arbitrage: bucket.spilled_arbitrage,
+                    overdrawn: bucket.overdrawn,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_reserve_is_kept_for_arbitrage_and_hedges_overdraw() {
+        let bucket = BucketConfig { msgs_per_sec: 10.0, burst: 5.0, reserve: 0.4 };
+        let config = BandwidthConfig { paths: HashMap::from([(NetworkPath::Microwave, bucket)]) };
+        let start = Instant::now();
+        let mut bandwidth = BandwidthAccountant::new(config, start);
+        let microwave = NetworkPath::Microwave;
+
+        // Opportunistic orders may use 3 of the 5 tokens, arbitrage the rest.
+        let mut sent = 0;
+        while bandwidth.admits(microwave, OrderPriority::Opportunistic, start) {
+            bandwidth.take(microwave, start);
+            sent += 1;
+        }
+        assert_eq!(sent, 3);
+        for _ in 0..2 {
+            assert!(bandwidth.admits(microwave, OrderPriority::Arbitrage, start));
+            bandwidth.take(microwave, start);
+        }
+        assert!(!bandwidth.admits(microwave, OrderPriority::Arbitrage, start));
+
+        // A hedge still goes, and its debt is refilled first: 0.3s brings
+        // the bucket back to 2 tokens, not 3.
+        assert!(bandwidth.admits(microwave, OrderPriority::Hedge, start));
+        bandwidth.take(microwave, start);
+        let later = start + Duration::from_millis(300);
+        assert!(bandwidth.admits(microwave, OrderPriority::Arbitrage, later));
+        assert!(!bandwidth.admits(microwave, OrderPriority::Opportunistic, later));
+
+        // Fiber is unmetered.
+        assert!(bandwidth.admits(NetworkPath::Fiber, OrderPriority::Opportunistic, start));
+
+        let status = bandwidth.status(later);
+        let microwave = &status[0];
+        assert_eq!(microwave.sent_last_sec, 6);
+        assert_eq!(microwave.utilization, Some(0.6));
+        assert_eq!((microwave.spilled_opportunistic, microwave.spilled_arbitrage, microwave.overdrawn), (2, 1, 1));
+        assert!((microwave.tokens.unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(status[1].msgs_per_sec, None);
+    }
+}
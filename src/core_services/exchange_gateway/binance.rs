@@ -0,0 +1,575 @@
+/*
+ * QuantumArb 2.0 - Core Services: Binance Spot Adapter
+ *
+ * File: src/core_services/exchange_gateway/binance.rs
+ *
+ * Description:
+ * The live adapter for Binance spot, selected with QA_LIVE_VENUE=binance,
+ * for a small live pilot on a crypto venue:
+ *
+ *   orders   REST: POST /api/v3/order (LIMIT, GTC) to send, DELETE
+ *            /api/v3/order to cancel and PUT /api/v3/order/amend/keepPriority
+ *            to reduce the quantity. Every request is signed: the query
+ *            string's HMAC-SHA256 under the API secret, in hex, goes in
+ *            `signature` and the API key in the X-MBX-APIKEY header.
+ *   fills    the user-data WebSocket stream, on a listen key from POST
+ *            /api/v3/userDataStream kept alive every 30 minutes. Its
+ *            executionReport events become execution reports, which the
+ *            gateway collects through `streamed_reports`.
+ *
+ * Orders go out with their internal id as newClientOrderId, which the stream
+ * echoes back, under the instrument's BINANCE symbol in the venue symbology
+ * (BTCUSDT for BTC), with prices at the instrument's price scale.
+ *
+ * QA_BINANCE_TESTNET (default true) points the adapter at the spot testnet;
+ * set it to false only for the pilot itself. QA_BINANCE_REST_URL and
+ * QA_BINANCE_WS_URL override the endpoints. The key pair is read from
+ * QA_BINANCE_API_KEY and QA_BINANCE_API_SECRET.
+ *
+ * REST calls run on their own tasks, so `send` never waits on the network; a
+ * REST error comes back as a reject on the stream of reports. A cancel or
+ * amendment is reported by `cancel` or `amend` itself, and not again from
+ * the stream. The session supervisor's heartbeat is the user-data stream: it
+ * is answered while the stream is connected, and a logon reconnects it. The
+ * stream does not replay what was missed, so a resend returns nothing;
+ * orders affected by an outage are reconciled through the open order book.
+ */
+
+use crate::venue::{self, amended_report, report_without_fill, InboundOrder, NetworkPath, VenueAdapter};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_fees::Liquidity;
+use quantumarb_money::{Money, Price};
+use quantumarb_refdata::symbology::{self, Symbology};
+use quantumarb_refdata::{ReferenceData, DEFAULT_PRICE_DECIMALS};
+use quantumarb_wire::{ExecutionReport, OrderSide, OrderStatus, TradingMode};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
+use tokio::time::{self, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+/// The venue's name in the symbology and on GET /venues.
+const VENUE: &str = "BINANCE";
+const REST_URL: &str = "https://api.binance.com";
+const WS_URL: &str = "wss://stream.binance.com:9443/ws";
+const TESTNET_REST_URL: &str = "https://testnet.binance.vision";
+const TESTNET_WS_URL: &str = "wss://testnet.binance.vision/ws";
+/// How long a signed request stays valid at the venue.
+const RECV_WINDOW_MS: u64 = 5_000;
+/// Listen keys expire after an hour without a keepalive.
+const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// --- Configuration ---
+
+#[derive(Clone)]
+pub struct BinanceConfig {
+    pub rest_url: String,
+    pub ws_url: String,
+    pub testnet: bool,
+    api_key: String,
+    api_secret: String,
+}
+
+impl BinanceConfig {
+    pub fn from_env() -> BinanceConfig {
+        let testnet = std::env::var("QA_BINANCE_TESTNET").map(|v| v != "false").unwrap_or(true);
+        let (rest_url, ws_url) = if testnet { (TESTNET_REST_URL, TESTNET_WS_URL) } else { (REST_URL, WS_URL) };
+        let secret = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{} must be set for Binance", name));
+        BinanceConfig {
+            rest_url: std::env::var("QA_BINANCE_REST_URL").unwrap_or_else(|_| rest_url.to_string()),
+            ws_url: std::env::var("QA_BINANCE_WS_URL").unwrap_or_else(|_| ws_url.to_string()),
+            testnet,
+            api_key: secret("QA_BINANCE_API_KEY"),
+            api_secret: secret("QA_BINANCE_API_SECRET"),
+        }
+    }
+}
+
+// --- Signing ---
+
+/// Hex HMAC-SHA256 of `payload` under `secret`.
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// The query string for `params` with the receive window and timestamp
+/// appended, and its signature last.
+pub fn signed_query(params: &[(&str, String)], secret: &str, timestamp_ms: u64) -> String {
+    let mut query: Vec<String> = params.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+    query.push(format!("recvWindow={}", RECV_WINDOW_MS));
+    query.push(format!("timestamp={}", timestamp_ms));
+    let query = query.join("&");
+    let signature = sign(secret, &query);
+    format!("{}&signature={}", query, signature)
+}
+
+/// Translates a REST error (`code`, `msg`) into the shared reject codes.
+pub fn map_api_error(code: i64, msg: &str) -> Rejection {
+    let reject_code = match code {
+        -1121 => RejectCode::VenueUnknownInstrument,
+        -1013 | -1111 if msg.contains("PRICE") => RejectCode::VenueInvalidPrice,
+        -1013 | -1111 => RejectCode::VenueInvalidQuantity,
+        -2010 if msg.contains("Duplicate") => RejectCode::VenueDuplicateOrder,
+        -2010 if msg.contains("closed") => RejectCode::VenueMarketClosed,
+        _ => RejectCode::VenueOther,
+    };
+    Rejection::new(reject_code, format!("Venue reject ({}): {}", code, msg))
+}
+
+// --- User-Data Stream ---
+
+/// The fields of an executionReport event the gateway books from.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecutionEvent {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Client order id; for a cancel, the cancel request's own.
+    #[serde(rename = "c")]
+    pub client_order_id: String,
+    /// The canceled order's client order id; empty otherwise.
+    #[serde(rename = "C", default)]
+    pub original_client_order_id: String,
+    /// NEW, TRADE, CANCELED, EXPIRED, REJECTED, REPLACED, TRADE_PREVENTION.
+    #[serde(rename = "x")]
+    pub execution_type: String,
+    #[serde(rename = "X")]
+    pub order_status: String,
+    #[serde(rename = "r")]
+    pub reject_reason: String,
+    #[serde(rename = "i")]
+    pub order_id: u64,
+    #[serde(rename = "q")]
+    pub quantity: String,
+    #[serde(rename = "l")]
+    pub last_quantity: String,
+    #[serde(rename = "z")]
+    pub cumulative_quantity: String,
+    #[serde(rename = "L")]
+    pub last_price: String,
+    #[serde(rename = "n", default)]
+    pub commission: Option<String>,
+    #[serde(rename = "N", default)]
+    pub commission_asset: Option<String>,
+    #[serde(rename = "T")]
+    pub transaction_time_ms: u64,
+    #[serde(rename = "t")]
+    pub trade_id: i64,
+    #[serde(rename = "m")]
+    pub is_maker: bool,
+}
+
+impl ExecutionEvent {
+    /// The internal id of the order the event is about.
+    pub fn internal_order_id(&self) -> Option<Uuid> {
+        let id = if self.original_client_order_id.is_empty() {
+            &self.client_order_id
+        } else {
+            &self.original_client_order_id
+        };
+        Uuid::parse_str(id).ok()
+    }
+}
+
+/// The execution report for an event on `order`, whose prices are at
+/// `price_decimals`; None for events the gateway already has a report for
+/// (NEW, REPLACED). Commission is booked as the fee only when it is charged
+/// in the symbol's quote asset; one paid in another asset (a BNB discount)
+/// is left to fee reconciliation.
+pub fn report_from_event(event: &ExecutionEvent, order: &InboundOrder, price_decimals: u8) -> Option<ExecutionReport> {
+    let quantity = |value: &str| value.parse::<f64>().map(|q| q.round() as u32).unwrap_or(0);
+    let (size, cumulative_size) = (quantity(&event.quantity), quantity(&event.cumulative_quantity));
+    let (status, exec_id) = match event.execution_type.as_str() {
+        "TRADE" if event.order_status == "FILLED" => (OrderStatus::Filled, format!("T{}", event.trade_id)),
+        "TRADE" => (OrderStatus::PartiallyFilled, format!("T{}", event.trade_id)),
+        "CANCELED" | "EXPIRED" | "TRADE_PREVENTION" => (OrderStatus::Canceled, format!("{}-CANCELED", event.order_id)),
+        "REJECTED" => (OrderStatus::RejectedByExchange, format!("{}-REJECTED", event.order_id)),
+        _ => return None,
+    };
+    let mut report = report_without_fill(order, status, TradingMode::Live);
+    report.exchange_order_id = event.order_id.to_string();
+    report.exec_id = exec_id;
+    report.cumulative_size = cumulative_size;
+    report.leaves_size = match status {
+        OrderStatus::PartiallyFilled => size.saturating_sub(cumulative_size),
+        _ => 0,
+    };
+    report.transact_time_ns = event.transaction_time_ms * 1_000_000;
+    if status == OrderStatus::RejectedByExchange {
+        let reason = format!("Venue reject: {}", event.reject_reason);
+        report.reject = Some(Rejection::new(RejectCode::VenueOther, reason));
+    }
+    if event.execution_type == "TRADE" {
+        report.filled_size = quantity(&event.last_quantity);
+        let price = event.last_price.parse::<f64>().map(Price::from_f64).unwrap_or(Price::ZERO);
+        report.filled_price = price.to_wire(price_decimals).unwrap_or(0);
+        report.liquidity = Some(if event.is_maker { Liquidity::Maker } else { Liquidity::Taker });
+        let quote_asset = event.symbol.strip_prefix(order.instrument_symbol.as_str());
+        if event.commission_asset.as_deref().is_some_and(|asset| Some(asset) == quote_asset) {
+            let commission = event.commission.as_deref().and_then(|n| n.parse::<f64>().ok()).unwrap_or(0.0);
+            report.fee = Money::from_f64(commission);
+        }
+    }
+    Some(report)
+}
+
+// --- Adapter ---
+
+/// A REST call for the request task.
+enum Request {
+    New { order: InboundOrder, symbol: String, price: Price },
+    Cancel { order: InboundOrder, symbol: String },
+    Amend { order: InboundOrder, symbol: String, size: u32 },
+}
+
+/// What the adapter and its tasks share.
+struct Shared {
+    config: BinanceConfig,
+    client: reqwest::Client,
+    instruments: ReferenceData,
+    /// Orders sent and not yet done, by internal id, for reading their events.
+    orders: Mutex<HashMap<Uuid, InboundOrder>>,
+    /// Orders whose cancel we sent, and reported, ourselves.
+    canceled: Mutex<HashSet<Uuid>>,
+    streamed: Mutex<Vec<ExecutionReport>>,
+    connected: AtomicBool,
+    reconnect: Notify,
+}
+
+pub struct BinanceVenue {
+    shared: Arc<Shared>,
+    symbology: Symbology,
+    requests: mpsc::UnboundedSender<Request>,
+    /// Stands in for a sequence number on the stream, which has none.
+    seq: AtomicU64,
+}
+
+impl BinanceVenue {
+    pub fn connect() -> BinanceVenue {
+        assert_eq!(
+            TradingMode::from_env(),
+            TradingMode::Live,
+            "refusing to load a live venue adapter outside live mode"
+        );
+        let config = BinanceConfig::from_env();
+        println!("Connecting to Binance spot at {} (testnet: {})...", config.rest_url, config.testnet);
+        let shared = Arc::new(Shared {
+            config,
+            client: reqwest::Client::new(),
+            instruments: ReferenceData::new(quantumarb_refdata::load_from_env()),
+            orders: Mutex::new(HashMap::new()),
+            canceled: Mutex::new(HashSet::new()),
+            streamed: Mutex::new(Vec::new()),
+            connected: AtomicBool::new(false),
+            reconnect: Notify::new(),
+        });
+        let (requests, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_requests(shared.clone(), receiver));
+        tokio::spawn(follow_user_stream(shared.clone()));
+        BinanceVenue {
+            shared,
+            symbology: Symbology::new(symbology::load_mappings_from_env()),
+            requests,
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    fn symbol(&self, order: &InboundOrder) -> String {
+        venue::venue_symbol(order, VENUE, &self.shared.instruments, &self.symbology)
+    }
+
+    fn request(&self, request: Request) {
+        // The receiver lives as long as the runtime.
+        let _ = self.requests.send(request);
+    }
+}
+
+impl VenueAdapter for BinanceVenue {
+    fn name(&self) -> &'static str {
+        "Binance spot"
+    }
+
+    fn send(&self, order: &InboundOrder, path: NetworkPath) {
+        let symbol = self.symbol(order);
+        let price = match Price::from_wire(order.price, self.shared.price_decimals(order)) {
+            Ok(price) => price,
+            Err(e) => {
+                println!("  -> Not sending {}: {}", order.internal_order_id, e);
+                let mut report = report_without_fill(order, OrderStatus::RejectedByExchange, TradingMode::Live);
+                report.reject = Some(Rejection::new(RejectCode::VenueInvalidPrice, e.to_string()));
+                self.shared.streamed.lock().unwrap().push(report);
+                return;
+            }
+        };
+        println!("  -> [LIVE] Sending {} order via [{:?}] path: Symbol {}, Size {}", VENUE, path, symbol, order.size);
+        self.shared.orders.lock().unwrap().insert(order.internal_order_id, order.clone());
+        self.request(Request::New { order: order.clone(), symbol, price });
+    }
+
+    fn execution_reports(&self, order: &InboundOrder) -> Vec<ExecutionReport> {
+        // Fills arrive later on the user-data stream; until then the order
+        // is working.
+        let mut report = report_without_fill(order, OrderStatus::SentToExchange, TradingMode::Live);
+        report.exec_id = String::new();
+        report.leaves_size = order.size;
+        vec![report]
+    }
+
+    fn streamed_reports(&self) -> Vec<ExecutionReport> {
+        std::mem::take(&mut *self.shared.streamed.lock().unwrap())
+    }
+
+    fn cancel(&self, order: &InboundOrder) -> ExecutionReport {
+        println!("  -> [LIVE] Canceling {} on {}", order.internal_order_id, VENUE);
+        self.shared.canceled.lock().unwrap().insert(order.internal_order_id);
+        self.request(Request::Cancel { order: order.clone(), symbol: self.symbol(order) });
+        report_without_fill(order, OrderStatus::Canceled, TradingMode::Live)
+    }
+
+    fn amend(&self, order: &InboundOrder, size: u32, cumulative_size: u32) -> ExecutionReport {
+        println!("  -> [LIVE] Amending {} on {}: quantity {}", order.internal_order_id, VENUE, size);
+        self.request(Request::Amend { order: order.clone(), symbol: self.symbol(order), size });
+        amended_report(order, size, cumulative_size, TradingMode::Live)
+    }
+
+    fn sessions(&self) -> Vec<(&'static str, Vec<String>)> {
+        vec![(VENUE, vec!["BINANCE-USER-STREAM".to_string()])]
+    }
+
+    fn heartbeat(&self, session: &str) -> Option<u64> {
+        let _ = session;
+        self.shared.connected.load(Ordering::Relaxed).then(|| self.seq.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn logon(&self, session: &str) -> Result<u64, String> {
+        println!("  -> [LIVE] Reconnecting {}", session);
+        self.shared.reconnect.notify_one();
+        Ok(self.seq.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn resend(&self, session: &str, from: u64, to: u64) -> Vec<ExecutionReport> {
+        println!("  -> [LIVE] {} cannot replay {}..={}; reconcile its open orders instead.", session, from, to);
+        Vec::new()
+    }
+}
+
+impl Shared {
+    fn price_decimals(&self, order: &InboundOrder) -> u8 {
+        let definition = self.instruments.by_symbol(&order.instrument_symbol);
+        definition.map_or(DEFAULT_PRICE_DECIMALS, |d| d.price_decimals)
+    }
+
+    /// Sends a signed request; Ok carries the response body.
+    async fn signed(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<serde_json::Value, Rejection> {
+        let query = signed_query(params, &self.config.api_secret, chrono::Utc::now().timestamp_millis() as u64);
+        let url = format!("{}{}?{}", self.config.rest_url, path, query);
+        self.call(self.client.request(method, url)).await
+    }
+
+    async fn call(&self, request: reqwest::RequestBuilder) -> Result<serde_json::Value, Rejection> {
+        let unreachable =
+            |e: reqwest::Error| Rejection::new(RejectCode::VenueOther, format!("{} unreachable: {}", VENUE, e));
+        let response = request.header("X-MBX-APIKEY", &self.config.api_key).send().await.map_err(unreachable)?;
+        let status = response.status();
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        if status.is_success() {
+            return Ok(body);
+        }
+        Err(map_api_error(body["code"].as_i64().unwrap_or(0), body["msg"].as_str().unwrap_or("")))
+    }
+
+    async fn execute(&self, request: Request) {
+        match request {
+            Request::New { order, symbol, price } => {
+                let side = match order.side {
+                    OrderSide::Buy => "BUY",
+                    OrderSide::Sell => "SELL",
+                };
+                let params = [
+                    ("symbol", symbol),
+                    ("side", side.to_string()),
+                    ("type", "LIMIT".to_string()),
+                    ("timeInForce", "GTC".to_string()),
+                    ("quantity", order.size.to_string()),
+                    ("price", price.to_string()),
+                    ("newClientOrderId", order.internal_order_id.simple().to_string()),
+                ];
+                if let Err(rejection) = self.signed(reqwest::Method::POST, "/api/v3/order", &params).await {
+                    println!("  -> {} refused {}: {}", VENUE, order.internal_order_id, rejection);
+                    self.orders.lock().unwrap().remove(&order.internal_order_id);
+                    let mut report = report_without_fill(&order, OrderStatus::RejectedByExchange, TradingMode::Live);
+                    report.reject = Some(rejection);
+                    self.streamed.lock().unwrap().push(report);
+                }
+            }
+            Request::Cancel { order, symbol } => {
+                let params = [("symbol", symbol), ("origClientOrderId", order.internal_order_id.simple().to_string())];
+                if let Err(rejection) = self.signed(reqwest::Method::DELETE, "/api/v3/order", &params).await {
+                    // Usually the order is already done; its report is on the stream.
+                    println!("  -> {} refused the cancel of {}: {}", VENUE, order.internal_order_id, rejection);
+                }
+            }
+            Request::Amend { order, symbol, size } => {
+                let params = [
+                    ("symbol", symbol),
+                    ("origClientOrderId", order.internal_order_id.simple().to_string()),
+                    ("newQty", size.to_string()),
+                ];
+                let path = "/api/v3/order/amend/keepPriority";
+                if let Err(rejection) = self.signed(reqwest::Method::PUT, path, &params).await {
+                    println!("  -> {} refused the amendment of {}: {}", VENUE, order.internal_order_id, rejection);
+                }
+            }
+        }
+    }
+
+    /// Turns a stream message into a report, if it is one the gateway needs.
+    fn on_message(&self, text: &str) {
+        let Ok(event) = serde_json::from_str::<ExecutionEvent>(text) else {
+            return; // Balance and account updates.
+        };
+        let Some(internal_order_id) = event.internal_order_id().filter(|_| event.event == "executionReport") else {
+            return; // Orders placed outside the gateway.
+        };
+        let mut orders = self.orders.lock().unwrap();
+        let Some(order) = orders.get(&internal_order_id).cloned() else {
+            return;
+        };
+        let Some(report) = report_from_event(&event, &order, self.price_decimals(&order)) else {
+            return;
+        };
+        if !matches!(report.status, OrderStatus::PartiallyFilled) {
+            orders.remove(&internal_order_id);
+        }
+        let ours = self.canceled.lock().unwrap().remove(&internal_order_id);
+        if report.status == OrderStatus::Canceled && ours {
+            return;
+        }
+        self.streamed.lock().unwrap().push(report);
+    }
+
+    /// Follows the user-data stream on one listen key until it drops or a
+    /// reconnect is asked for.
+    async fn stream_once(&self) -> Result<(), String> {
+        let url = format!("{}/api/v3/userDataStream", self.config.rest_url);
+        let body = self.call(self.client.post(&url)).await.map_err(|r| r.message)?;
+        let listen_key = body["listenKey"].as_str().ok_or("no listen key in the response")?.to_string();
+        let stream_url = format!("{}/{}", self.config.ws_url, listen_key);
+        let (mut stream, _) = tokio_tungstenite::connect_async(stream_url).await.map_err(|e| e.to_string())?;
+        self.connected.store(true, Ordering::Relaxed);
+        println!("{} user-data stream connected.", VENUE);
+
+        let mut keepalive = time::interval(LISTEN_KEY_KEEPALIVE);
+        keepalive.tick().await;
+        loop {
+            tokio::select! {
+                message = stream.next() => match message {
+                    // Pings are answered by the socket itself.
+                    Some(Ok(Message::Text(text))) => self.on_message(&text),
+                    Some(Ok(Message::Close(_))) | None => return Err("closed by the venue".to_string()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.to_string()),
+                },
+                _ = keepalive.tick() => {
+                    let request = self.client.put(&url).query(&[("listenKey", listen_key.as_str())]);
+                    if let Err(rejection) = self.call(request).await {
+                        println!("  -> Listen key keepalive failed: {}", rejection);
+                    }
+                }
+                _ = self.reconnect.notified() => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Executes the adapter's REST calls, each on a task of its own.
+async fn run_requests(shared: Arc<Shared>, mut receiver: mpsc::UnboundedReceiver<Request>) {
+    while let Some(request) = receiver.recv().await {
+        let shared = shared.clone();
+        tokio::spawn(async move { shared.execute(request).await });
+    }
+}
+
+/// Keeps the user-data stream connected.
+async fn follow_user_stream(shared: Arc<Shared>) {
+    loop {
+        let result = shared.stream_once().await;
+        shared.connected.store(false, Ordering::Relaxed);
+        if let Err(e) = result {
+            println!("{} user-data stream lost: {}", VENUE, e);
+            time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_wire::{HopStamps, OrderPriority};
+
+    #[test]
+    fn requests_are_signed_and_stream_fills_become_reports() {
+        // The worked example in the Binance API documentation.
+        let secret = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let params = [
+            ("symbol", "LTCBTC".to_string()),
+            ("side", "BUY".to_string()),
+            ("type", "LIMIT".to_string()),
+            ("timeInForce", "GTC".to_string()),
+            ("quantity", "1".to_string()),
+            ("price", "0.1".to_string()),
+        ];
+        let query = signed_query(&params, secret, 1_499_827_319_559);
+        assert_eq!(
+            query,
+            "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000\
+             &timestamp=1499827319559&signature=c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+
+        let order = InboundOrder {
+            internal_order_id: Uuid::new_v4(),
+            instrument_symbol: "BTC".to_string(),
+            price: 60_000_00,
+            size: 3,
+            side: OrderSide::Buy,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Live,
+            strategy: None,
+            priority: OrderPriority::Opportunistic,
+        };
+        let event = format!(
+            r#"{{"e":"executionReport","E":1,"s":"BTCUSDT","c":"{}","S":"BUY","o":"LIMIT","q":"3.00000000",
+                "p":"60000.00","x":"TRADE","X":"PARTIALLY_FILLED","r":"NONE","i":4293153,"l":"1.00000000",
+                "z":"1.00000000","L":"59999.50","n":"0.06","N":"USDT","T":1499405658657,"t":7,"m":true}}"#,
+            order.internal_order_id.simple()
+        );
+        let event: ExecutionEvent = serde_json::from_str(&event).unwrap();
+        assert_eq!(event.internal_order_id(), Some(order.internal_order_id));
+        let fill = report_from_event(&event, &order, 2).unwrap();
+        assert_eq!((fill.status, fill.filled_size, fill.filled_price), (OrderStatus::PartiallyFilled, 1, 59_999_50));
+        assert_eq!((fill.cumulative_size, fill.leaves_size, fill.exec_id.as_str()), (1, 2, "T7"));
+        assert_eq!((fill.liquidity, fill.fee), (Some(Liquidity::Maker), Money::from_f64(0.06)));
+
+        let rejected = ExecutionEvent { execution_type: "REJECTED".to_string(), ..event.clone() };
+        let reject = report_from_event(&rejected, &order, 2).unwrap();
+        assert_eq!(reject.status, OrderStatus::RejectedByExchange);
+        assert_eq!(reject.reject.unwrap().code, RejectCode::VenueOther);
+        assert!(report_from_event(&ExecutionEvent { execution_type: "NEW".to_string(), ..event }, &order, 2).is_none());
+        assert_eq!(map_api_error(-1013, "Filter failure: PRICE_FILTER").code, RejectCode::VenueInvalidPrice);
+    }
+}
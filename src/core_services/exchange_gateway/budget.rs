@@ -0,0 +1,294 @@
+/*
+ * QuantumArb 2.0 - Core Services: Send-Time Budget
+ *
+ * File: src/core_services/exchange_gateway/budget.rs
+ *
+ * Description:
+ * Holds the gateway to a latency SLO on its own processing time: from an
+ * order arriving at the gateway to its write to the venue (for a package,
+ * to the first leg's write). The p99 of the orders sent in the last
+ * `window` is checked against the budget:
+ *
+ *   BREACHED    the p99 went over the budget; an alert is published on
+ *               'alerts.send_budget'
+ *   SHEDDING    with shedding enabled, orders from the lowest-priority
+ *               strategies are rejected (SYSTEM_OVERLOADED) before they are
+ *               processed. While the p99 stays over, every `escalate_after`
+ *               the next priority tier is shed too; the highest tier,
+ *               orders carrying no strategy and hedge orders are never shed.
+ *   RECOVERED   the p99 is back within the budget. Shedding is lifted one
+ *               tier per `escalate_after`, then the breach clears.
+ *
+ * A window with fewer than `min_samples` orders is too thin for a p99 and
+ * counts as within the budget, which is also how a fully shed tier stops
+ * holding the breach open.
+ *
+ * Configuration (environment):
+ *   QA_SEND_BUDGET_P99_US=100          the budget
+ *   QA_SEND_BUDGET_WINDOW_SECS=10      rolling window for the p99
+ *   QA_SEND_BUDGET_MIN_SAMPLES=50      orders needed for a p99
+ *   QA_SEND_BUDGET_SHED=false          shed load while over the budget
+ *   QA_SEND_BUDGET_ESCALATE_SECS=5     time between shedding steps
+ *   QA_STRATEGY_PRIORITIES             "market_making:3,stat_arb:2,momentum:1";
+ *                                      higher is kept longer
+ *   QA_STRATEGY_DEFAULT_PRIORITY=1     priority of unlisted strategies
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::OrderPriority;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+pub const SEND_BUDGET_ALERTS_TOPIC: &str = "alerts.send_budget";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SendBudgetConfig {
+    pub p99_budget_ns: u64,
+    pub window: Duration,
+    pub min_samples: usize,
+    pub shed: bool,
+    pub escalate_after: Duration,
+    pub priorities: HashMap<String, u8>,
+    pub default_priority: u8,
+}
+
+impl SendBudgetConfig {
+    pub fn from_env() -> SendBudgetConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let priorities = std::env::var("QA_STRATEGY_PRIORITIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (strategy, priority) = entry.split_once(':')?;
+                Some((strategy.trim().to_string(), priority.trim().parse().ok()?))
+            })
+            .collect();
+        SendBudgetConfig {
+            p99_budget_ns: var("QA_SEND_BUDGET_P99_US", 100u64) * 1_000,
+            window: Duration::from_secs(var("QA_SEND_BUDGET_WINDOW_SECS", 10u64).max(1)),
+            min_samples: var("QA_SEND_BUDGET_MIN_SAMPLES", 50usize).max(1),
+            shed: var("QA_SEND_BUDGET_SHED", false),
+            escalate_after: Duration::from_secs(var("QA_SEND_BUDGET_ESCALATE_SECS", 5u64)),
+            priorities,
+            default_priority: var("QA_STRATEGY_DEFAULT_PRIORITY", 1u8),
+        }
+    }
+
+    pub fn priority(&self, strategy: &str) -> u8 {
+        self.priorities.get(strategy).copied().unwrap_or(self.default_priority)
+    }
+
+    /// The distinct priorities, lowest first.
+    fn tiers(&self) -> Vec<u8> {
+        let mut tiers: Vec<u8> = self.priorities.values().copied().chain([self.default_priority]).collect();
+        tiers.sort_unstable();
+        tiers.dedup();
+        tiers
+    }
+}
+
+// --- Monitor ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BudgetEvent {
+    Breached,
+    Shedding,
+    Recovered,
+}
+
+/// Published on 'alerts.send_budget' at every change of state.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BudgetAlert {
+    pub event: BudgetEvent,
+    pub p99_us: f64,
+    pub budget_us: f64,
+    pub samples: usize,
+    /// Orders from strategies below this priority are being shed.
+    pub shed_below_priority: Option<u8>,
+    pub timestamp_utc: String,
+}
+
+/// Body of GET /latency/budget.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BudgetStatus {
+    pub p99_us: Option<f64>,
+    pub budget_us: f64,
+    pub samples: usize,
+    pub breached: bool,
+    pub shed_below_priority: Option<u8>,
+    pub orders_shed: u64,
+}
+
+pub struct SendBudget {
+    config: SendBudgetConfig,
+    tiers: Vec<u8>,
+    /// When each order in the window was sent, and its processing time.
+    samples: VecDeque<(Instant, u64)>,
+    breached: bool,
+    /// Number of priority tiers shed, lowest first.
+    shed_tiers: usize,
+    last_step: Option<Instant>,
+    orders_shed: u64,
+}
+
+impl SendBudget {
+    pub fn new(config: SendBudgetConfig) -> SendBudget {
+        SendBudget {
+            tiers: config.tiers(),
+            config,
+            samples: VecDeque::new(),
+            breached: false,
+            shed_tiers: 0,
+            last_step: None,
+            orders_shed: 0,
+        }
+    }
+
+    /// Records an order written to the venue `processing_ns` after it arrived.
+    pub fn record(&mut self, processing_ns: u64, now: Instant) {
+        self.samples.push_back((now, processing_ns));
+    }
+
+    /// Rejects an order its strategy's priority is being shed at.
+    pub fn admit(&mut self, strategy: Option<&str>, order_priority: OrderPriority) -> Result<(), Rejection> {
+        let (Some(strategy), Some(threshold)) = (strategy, self.shed_below_priority()) else {
+            return Ok(());
+        };
+        if order_priority == OrderPriority::Hedge {
+            return Ok(());
+        }
+        let priority = self.config.priority(strategy);
+        if priority >= threshold {
+            return Ok(());
+        }
+        self.orders_shed += 1;
+        Err(Rejection::new(
+            RejectCode::SystemOverloaded,
+            format!(
+                "Gateway send time is over its {}µs p99 budget; orders from {} (priority {}) are shed",
+                self.config.p99_budget_ns / 1_000,
+                strategy,
+                priority
+            ),
+        ))
+    }
+
+    fn shed_below_priority(&self) -> Option<u8> {
+        (self.shed_tiers > 0).then(|| self.tiers[self.shed_tiers])
+    }
+
+    /// The p99 over the window, once it holds enough orders.
+    fn p99_ns(&mut self, now: Instant) -> Option<u64> {
+        while self.samples.front().is_some_and(|(at, _)| now.duration_since(*at) > self.config.window) {
+            self.samples.pop_front();
+        }
+        if self.samples.len() < self.config.min_samples {
+            return None;
+        }
+        let mut sorted: Vec<u64> = self.samples.iter().map(|(_, ns)| *ns).collect();
+        sorted.sort_unstable();
+        Some(sorted[(sorted.len() * 99).div_ceil(100) - 1])
+    }
+
+    /// Checks the window against the budget and steps shedding up or down.
+    /// Returns the alert to publish if the state changed.
+    pub fn evaluate(&mut self, now: Instant) -> Option<BudgetAlert> {
+        let p99 = self.p99_ns(now);
+        let over = p99.is_some_and(|ns| ns > self.config.p99_budget_ns);
+        let step_due = self.last_step.is_none_or(|at| now.duration_since(at) >= self.config.escalate_after);
+        let event = if over && !self.breached {
+            self.breached = true;
+            if self.config.shed && self.tiers.len() > 1 {
+                self.shed_tiers = 1;
+            }
+            BudgetEvent::Breached
+        } else if over && step_due && self.shed_tiers > 0 && self.shed_tiers + 1 < self.tiers.len() {
+            self.shed_tiers += 1;
+            BudgetEvent::Shedding
+        } else if !over && self.breached && step_due && self.shed_tiers > 0 {
+            self.shed_tiers -= 1;
+            BudgetEvent::Shedding
+        } else if !over && self.breached && self.shed_tiers == 0 {
+            self.breached = false;
+            BudgetEvent::Recovered
+        } else {
+            return None;
+        };
+        self.last_step = Some(now);
+        Some(BudgetAlert {
+            event,
+            p99_us: p99.unwrap_or(0) as f64 / 1_000.0,
+            budget_us: self.config.p99_budget_ns as f64 / 1_000.0,
+            samples: self.samples.len(),
+            shed_below_priority: self.shed_below_priority(),
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    pub fn status(&mut self, now: Instant) -> BudgetStatus {
+        BudgetStatus {
+            p99_us: self.p99_ns(now).map(|ns| ns as f64 / 1_000.0),
+            budget_us: self.config.p99_budget_ns as f64 / 1_000.0,
+            samples: self.samples.len(),
+            breached: self.breached,
+            shed_below_priority: self.shed_below_priority(),
+            orders_shed: self.orders_shed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SendBudgetConfig {
+        SendBudgetConfig {
+            p99_budget_ns: 100_000,
+            window: Duration::from_secs(10),
+            min_samples: 10,
+            shed: true,
+            escalate_after: Duration::from_secs(5),
+            priorities: [("market_making", 3), ("stat_arb", 2)].map(|(s, p)| (s.to_string(), p)).into(),
+            default_priority: 1,
+        }
+    }
+
+    #[test]
+    fn sheds_the_lowest_priorities_first_and_lifts_one_tier_at_a_time() {
+        let mut budget = SendBudget::new(config());
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        for _ in 0..20 {
+            budget.record(150_000, start);
+        }
+        assert_eq!(budget.evaluate(at(0)).unwrap().event, BudgetEvent::Breached);
+        assert!(budget.admit(Some("momentum"), OrderPriority::Opportunistic).is_err());
+        assert!(budget.admit(Some("stat_arb"), OrderPriority::Arbitrage).is_ok());
+        assert!(budget.evaluate(at(1)).is_none());
+
+        // Still over: the next tier goes, but never the top one.
+        for _ in 0..20 {
+            budget.record(150_000, at(5));
+        }
+        assert_eq!(budget.evaluate(at(5)).unwrap().shed_below_priority, Some(3));
+        let shed = budget.admit(Some("stat_arb"), OrderPriority::Arbitrage).unwrap_err();
+        assert_eq!(shed.code, RejectCode::SystemOverloaded);
+        assert!(budget.admit(Some("market_making"), OrderPriority::Opportunistic).is_ok());
+        assert!(budget.admit(None, OrderPriority::Opportunistic).is_ok());
+        assert!(budget.admit(Some("momentum"), OrderPriority::Hedge).is_ok());
+        assert!(budget.evaluate(at(10)).is_none());
+
+        // The slow orders age out of the window: back down a tier at a time.
+        assert_eq!(budget.evaluate(at(16)).unwrap().shed_below_priority, Some(2));
+        assert_eq!(budget.evaluate(at(21)).unwrap().shed_below_priority, None);
+        assert_eq!(budget.evaluate(at(21)).unwrap().event, BudgetEvent::Recovered);
+        assert_eq!(budget.status(at(21)).orders_shed, 2);
+    }
+}
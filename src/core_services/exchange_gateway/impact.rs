@@ -0,0 +1,178 @@
+/*
+ * QuantumArb 2.0 - Core Services: Paper Market Impact
+ *
+ * File: src/core_services/exchange_gateway/impact.rs
+ *
+ * Description:
+ * Without it the paper simulator fills any size at the order's price, as if
+ * liquidity were infinite, which flatters every backtest of a strategy that
+ * trades size. Here an order moves the price against itself as it fills, by
+ * a function of its participation rate (its quantity over the market volume
+ * it competes with):
+ *
+ *   linear        impact = coefficient x participation
+ *   sqrt          impact = coefficient x sqrt(participation)
+ *
+ * The coefficient is the impact, in basis points, of trading the whole of
+ * that volume. The order's price is taken as the price on arrival; each fill
+ * is priced at the impact of everything filled so far, so later fills of a
+ * large order are worse than its first.
+ *
+ * Part of the impact stays in the price once the order is done and decays
+ * with a half-life, so the next order in the same instrument starts from a
+ * price the last one moved: a buy after a large buy pays more, a sell after
+ * it gets more.
+ *
+ * Configuration (environment):
+ *   QA_PAPER_IMPACT_MODEL=sqrt              none, linear or sqrt
+ *   QA_PAPER_IMPACT_BPS=50                  the coefficient
+ *   QA_PAPER_MARKET_VOLUMES="ESZ25:2000"    market volume per symbol
+ *   QA_PAPER_MARKET_VOLUME_DEFAULT=1000     for symbols not listed
+ *   QA_PAPER_IMPACT_PERMANENT=0.5           share of the impact left in the
+ *                                           price after the order
+ *   QA_PAPER_IMPACT_HALF_LIFE_SECS=60       half-life of what is left
+ */
+
+use quantumarb_wire::OrderSide;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImpactModel {
+    None,
+    Linear,
+    SquareRoot,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImpactConfig {
+    pub model: ImpactModel,
+    pub coefficient_bps: f64,
+    pub volumes: HashMap<String, u64>,
+    pub default_volume: u64,
+    pub permanent_share: f64,
+    pub half_life: Duration,
+}
+
+impl ImpactConfig {
+    pub fn from_env() -> ImpactConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let model = match std::env::var("QA_PAPER_IMPACT_MODEL").as_deref() {
+            Ok("none") => ImpactModel::None,
+            Ok("linear") => ImpactModel::Linear,
+            _ => ImpactModel::SquareRoot,
+        };
+        let volumes = std::env::var("QA_PAPER_MARKET_VOLUMES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (symbol, volume) = entry.split_once(':')?;
+                Some((symbol.trim().to_string(), volume.trim().parse().ok().filter(|v| *v > 0)?))
+            })
+            .collect();
+        ImpactConfig {
+            model,
+            coefficient_bps: var("QA_PAPER_IMPACT_BPS", 50.0),
+            volumes,
+            default_volume: var("QA_PAPER_MARKET_VOLUME_DEFAULT", 1000u64).max(1),
+            permanent_share: var("QA_PAPER_IMPACT_PERMANENT", 0.5f64).clamp(0.0, 1.0),
+            half_life: Duration::from_secs(var("QA_PAPER_IMPACT_HALF_LIFE_SECS", 60u64).max(1)),
+        }
+    }
+
+    fn volume(&self, symbol: &str) -> u64 {
+        self.volumes.get(symbol).copied().unwrap_or(self.default_volume)
+    }
+}
+
+// --- Model ---
+
+pub struct MarketImpact {
+    config: ImpactConfig,
+    /// The impact left in each symbol's price, in signed basis points, as
+    /// of when it was last set.
+    residual: HashMap<String, (f64, Instant)>,
+}
+
+impl MarketImpact {
+    pub fn new(config: ImpactConfig) -> MarketImpact {
+        MarketImpact { config, residual: HashMap::new() }
+    }
+
+    /// Impact in basis points of trading `quantity` of `symbol`.
+    pub fn impact_bps(&self, symbol: &str, quantity: u32) -> f64 {
+        let participation = quantity as f64 / self.config.volume(symbol) as f64;
+        match self.config.model {
+            ImpactModel::None => 0.0,
+            ImpactModel::Linear => self.config.coefficient_bps * participation,
+            ImpactModel::SquareRoot => self.config.coefficient_bps * participation.sqrt(),
+        }
+    }
+
+    fn residual_bps(&self, symbol: &str, now: Instant) -> f64 {
+        self.residual.get(symbol).map_or(0.0, |(bps, at)| {
+            let half_lives = now.duration_since(*at).as_secs_f64() / self.config.half_life.as_secs_f64();
+            bps * 0.5f64.powf(half_lives)
+        })
+    }
+
+    /// Prices the fills of one order, `fills` being their sizes in order:
+    /// returns, for each, how far from the arrival price it fills, in signed
+    /// basis points (up for a buy, down for a sell). Leaves the permanent
+    /// part of the order's impact in the symbol's price.
+    pub fn fill(&mut self, symbol: &str, side: OrderSide, fills: &[u32], now: Instant) -> Vec<f64> {
+        let sign = match side {
+            OrderSide::Buy => 1.0,
+            OrderSide::Sell => -1.0,
+        };
+        let residual = self.residual_bps(symbol, now);
+        let mut filled = 0;
+        let offsets = fills
+            .iter()
+            .map(|size| {
+                filled += size;
+                residual + sign * self.impact_bps(symbol, filled)
+            })
+            .collect();
+        if filled > 0 && self.config.model != ImpactModel::None {
+            let left = residual + sign * self.config.permanent_share * self.impact_bps(symbol, filled);
+            self.residual.insert(symbol.to_string(), (left, now));
+        }
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_orders_fill_worse_and_leave_the_price_moved() {
+        let config = ImpactConfig {
+            model: ImpactModel::SquareRoot,
+            coefficient_bps: 100.0,
+            volumes: [("ESZ25".to_string(), 400)].into(),
+            default_volume: 1000,
+            permanent_share: 0.5,
+            half_life: Duration::from_secs(60),
+        };
+        let mut impact = MarketImpact::new(config.clone());
+        let start = Instant::now();
+
+        // 100 of 400 is a quarter of the volume: sqrt gives half the coefficient.
+        assert_eq!(impact.fill("ESZ25", OrderSide::Buy, &[36, 64], start), [30.0, 50.0]);
+        // Half of the 50bp stays, and a minute later half of that.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(impact.fill("ESZ25", OrderSide::Sell, &[4], later), [12.5 - 10.0]);
+        assert_eq!(impact.fill("BTC", OrderSide::Sell, &[10], later), [-10.0]);
+
+        let linear = MarketImpact::new(ImpactConfig { model: ImpactModel::Linear, ..config.clone() });
+        assert_eq!(linear.impact_bps("ESZ25", 100), 25.0);
+        let mut none = MarketImpact::new(ImpactConfig { model: ImpactModel::None, ..config });
+        assert_eq!(none.fill("ESZ25", OrderSide::Buy, &[400], start), [0.0]);
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * QuantumArb 2.0 - Core Services: Order Journal
+ *
+ * File: src/core_services/exchange_gateway/journal.rs
+ *
+ * Description:
+ * Event-sources the order book through a transactional outbox (see
+ * `quantumarb-outbox`). Every change to the book is journaled as an
+ * `OrderEvent`, in the same commit as the execution report it publishes:
+ *
+ *   Sent       an order went to the venue and is tracked
+ *   Report     the sequencer released a venue report to the book; the report
+ *              is the commit's message on 'execution_reports', keyed by
+ *              exchange order id and ExecID
+ *   Restored   PUT /orders/open replaced the open orders
+ *   Snapshot   the whole book, written when the journal is compacted
+ *
+ * Reports the gateway refuses itself (mode mismatch, load shedding) and
+ * package summaries change no order; they are committed as messages alone,
+ * so they too are published exactly once.
+ *
+ * On start the journal is replayed into the book: a restarted gateway knows
+ * its open orders, their fills and the ExecIDs already seen, and reports
+ * committed before a crash but never published go out first. A publisher
+ * task drains the outbox and compacts the journal once it grows past
+ * QA_EXEC_JOURNAL_COMPACT_LINES. GET /orders/outbox shows its counters.
+ *
+ * Configuration (environment):
+ *   QA_EXEC_JOURNAL_PATH=exchange_gateway.journal.jsonl
+ *   QA_EXEC_OUTBOX_DRAIN_MS=50          time between publisher runs
+ *   QA_EXEC_JOURNAL_COMPACT_LINES=10000 journal length that triggers compaction
+ */
+
+use crate::legging::PackageReport;
+use crate::lifecycle::{BookSnapshot, OpenOrder, OrderBook};
+use crate::venue::InboundOrder;
+use quantumarb_bus::topics;
+use quantumarb_outbox::{Message, Outbox, OutboxStats};
+use quantumarb_wire::{Encoding, ExecutionReport};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub path: String,
+    pub drain_interval: Duration,
+    pub compact_lines: usize,
+}
+
+impl JournalConfig {
+    pub fn from_env() -> JournalConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        JournalConfig {
+            path: var("QA_EXEC_JOURNAL_PATH", "exchange_gateway.journal.jsonl".to_string()),
+            drain_interval: Duration::from_millis(var("QA_EXEC_OUTBOX_DRAIN_MS", 50u64).max(1)),
+            compact_lines: var("QA_EXEC_JOURNAL_COMPACT_LINES", 10_000usize).max(2),
+        }
+    }
+}
+
+// --- Events ---
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEvent {
+    Sent(InboundOrder),
+    Report(ExecutionReport),
+    Restored(Vec<OpenOrder>),
+    Snapshot(BookSnapshot),
+}
+
+// --- Journal ---
+
+/// The order journal. Commits are made while holding the order book's lock,
+/// so the journal's order is the book's.
+#[derive(Clone)]
+pub struct Journal {
+    outbox: Arc<Mutex<Outbox<OrderEvent>>>,
+    encoding: Encoding,
+    config: JournalConfig,
+}
+
+impl Journal {
+    /// Opens the journal and replays it into `book`.
+    pub fn open(config: JournalConfig, encoding: Encoding, book: &mut OrderBook) -> std::io::Result<Journal> {
+        let (outbox, events) = Outbox::open(&config.path)?;
+        let replayed = events.len();
+        for event in events {
+            match event {
+                OrderEvent::Sent(order) => book.track(&order),
+                OrderEvent::Report(report) => book.replay(&report),
+                OrderEvent::Restored(orders) => book.restore(orders),
+                OrderEvent::Snapshot(snapshot) => book.load_snapshot(snapshot),
+            }
+        }
+        let stats = outbox.stats();
+        println!(
+            "Order journal {}: {} events replayed, {} open orders, {} reports to publish",
+            stats.path,
+            replayed,
+            book.len(),
+            stats.pending_messages
+        );
+        Ok(Journal { outbox: Arc::new(Mutex::new(outbox)), encoding, config })
+    }
+
+    pub fn sent(&self, order: &InboundOrder) {
+        self.commit(vec![OrderEvent::Sent(order.clone())], Vec::new());
+    }
+
+    /// A report the book has applied (or flagged), with its publication.
+    pub fn report(&self, report: &ExecutionReport) {
+        let message = self.report_message(report);
+        self.commit(vec![OrderEvent::Report(report.clone())], vec![message]);
+    }
+
+    /// A report for an order the gateway refused without sending it.
+    pub fn refused(&self, report: &ExecutionReport) {
+        let message = self.report_message(report);
+        self.commit(Vec::new(), vec![message]);
+    }
+
+    pub fn package(&self, report: &PackageReport) {
+        let message = Message::json(topics::PACKAGE_REPORTS, format!("package:{}", report.package_id), report);
+        self.commit(Vec::new(), vec![message]);
+    }
+
+    pub fn restored(&self, orders: &[OpenOrder]) {
+        self.commit(vec![OrderEvent::Restored(orders.to_vec())], Vec::new());
+    }
+
+    pub fn stats(&self) -> OutboxStats {
+        self.outbox.lock().unwrap().stats()
+    }
+
+    pub fn drain_interval(&self) -> Duration {
+        self.config.drain_interval
+    }
+
+    /// Publishes what is pending, then compacts the journal if it is due.
+    pub fn drain(&self, book: &Mutex<OrderBook>) {
+        let compact = {
+            let mut outbox = self.outbox.lock().unwrap();
+            if let Err(e) = quantumarb_outbox::drain(&mut outbox) {
+                println!("Order journal: failed to record published reports ({}); they will be resent.", e);
+            }
+            outbox.journal_lines() >= self.config.compact_lines
+        };
+        if compact {
+            let book = book.lock().unwrap();
+            let mut outbox = self.outbox.lock().unwrap();
+            match outbox.compact(vec![OrderEvent::Snapshot(book.snapshot())]) {
+                Ok(()) => println!("\nOrder journal compacted: {} open orders.", book.len()),
+                Err(e) => println!("\nOrder journal compaction failed: {}", e),
+            }
+        }
+    }
+
+    fn report_message(&self, report: &ExecutionReport) -> Message {
+        let key = if report.exec_id.is_empty() {
+            format!("{}:{:?}:{}", report.internal_order_id, report.status, report.cumulative_size)
+        } else {
+            format!("{}:{}", report.exchange_order_id, report.exec_id)
+        };
+        Message::new(topics::EXECUTION_REPORTS, key, self.encoding.encode(report))
+    }
+
+    /// A commit that cannot be written is fatal: the book would be ahead of
+    /// its journal.
+    fn commit(&self, events: Vec<OrderEvent>, messages: Vec<Message>) {
+        self.outbox.lock().unwrap().commit(events, messages).expect("Failed to write the order journal");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_money::Money;
+    use quantumarb_wire::{HopStamps, OrderPriority, OrderSide, OrderStatus, TradingMode};
+    use std::io::Write;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    fn config(name: &str) -> JournalConfig {
+        let path = std::env::temp_dir().join(format!("qa-exec-journal-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().into_owned();
+        JournalConfig { path, drain_interval: Duration::from_millis(50), compact_lines: 100 }
+    }
+
+    fn order(size: u32) -> InboundOrder {
+        InboundOrder {
+            internal_order_id: Uuid::new_v4(),
+            instrument_symbol: "ESZ25".to_string(),
+            price: 4500_25,
+            size,
+            side: OrderSide::Buy,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            strategy: None,
+            priority: OrderPriority::Opportunistic,
+        }
+    }
+
+    fn report(
+        order: &InboundOrder,
+        exec_id: &str,
+        status: OrderStatus,
+        filled_size: u32,
+        cumulative_size: u32,
+    ) -> ExecutionReport {
+        ExecutionReport {
+            exchange_order_id: format!("EXCH-{}", order.size),
+            exec_id: exec_id.to_string(),
+            internal_order_id: order.internal_order_id,
+            status,
+            filled_size,
+            filled_price: order.price,
+            reject: None,
+            stamps: order.stamps,
+            mode: TradingMode::Sandbox,
+            cumulative_size,
+            leaves_size: order.size.saturating_sub(cumulative_size),
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }
+    }
+
+    fn reopen(config: &JournalConfig) -> std::io::Result<(Journal, OrderBook)> {
+        let mut book = OrderBook::default();
+        let journal = Journal::open(config.clone(), Encoding::Json, &mut book)?;
+        Ok((journal, book))
+    }
+
+    /// Journals a report and applies it, as the report loop does.
+    fn apply(journal: &Journal, book: &mut OrderBook, report: &ExecutionReport) {
+        journal.report(report);
+        book.apply(report).unwrap();
+    }
+
+    fn pending_keys(journal: &Journal) -> Vec<String> {
+        journal.outbox.lock().unwrap().pending().map(|message| message.key.clone()).collect()
+    }
+
+    fn append(config: &JournalConfig, bytes: &[u8]) {
+        std::fs::OpenOptions::new().append(true).open(&config.path).unwrap().write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn replay_rebuilds_the_open_orders_and_the_reports_seen() {
+        let config = config("replay");
+        let (journal, mut book) = reopen(&config).unwrap();
+        let (working, done, refused) = (order(10), order(5), order(3));
+        journal.sent(&working);
+        book.track(&working);
+        let partial = report(&working, "E-1", OrderStatus::PartiallyFilled, 4, 4);
+        apply(&journal, &mut book, &partial);
+        journal.sent(&done);
+        book.track(&done);
+        apply(&journal, &mut book, &report(&done, "E-2", OrderStatus::Filled, 5, 5));
+        journal.refused(&report(&refused, "", OrderStatus::RejectedByExchange, 0, 0));
+        drop(journal);
+
+        let (journal, mut replayed) = reopen(&config).unwrap();
+        assert_eq!(replayed.len(), 1);
+        let open = replayed.get(&working.internal_order_id).unwrap();
+        assert_eq!((open.status, open.cumulative_size), (OrderStatus::PartiallyFilled, 4));
+        assert!(replayed.get(&done.internal_order_id).is_none());
+
+        // Nothing was published before the restart: every report goes out, under its dedup key.
+        let refusal_key = format!("{}:RejectedByExchange:0", refused.internal_order_id);
+        assert_eq!(pending_keys(&journal), ["EXCH-10:E-1".to_string(), "EXCH-5:E-2".to_string(), refusal_key]);
+        assert_eq!(journal.stats().last_commit, 5);
+
+        // A report the venue resends after the restart is a duplicate.
+        assert!(replayed.receive(partial, Instant::now()).is_empty());
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn a_restore_replaces_the_open_orders() {
+        let config = config("restore");
+        let (journal, _) = reopen(&config).unwrap();
+        let (sent, restored) = (order(10), order(7));
+        journal.sent(&sent);
+        journal.restored(&[OpenOrder::new(restored.clone())]);
+        drop(journal);
+
+        let (_, replayed) = reopen(&config).unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert!(replayed.get(&restored.internal_order_id).is_some());
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn a_torn_last_commit_is_discarded_with_its_report() {
+        let config = config("torn");
+        let (journal, mut book) = reopen(&config).unwrap();
+        let sent = order(10);
+        journal.sent(&sent);
+        book.track(&sent);
+        apply(&journal, &mut book, &report(&sent, "E-1", OrderStatus::PartiallyFilled, 4, 4));
+        drop(journal);
+        append(&config, br#"{"commit":{"seq":3,"events":[{"report":{"exchange_order_id":"EXCH-10""#);
+
+        let (journal, mut replayed) = reopen(&config).unwrap();
+        assert_eq!(replayed.get(&sent.internal_order_id).unwrap().cumulative_size, 4);
+        assert_eq!(pending_keys(&journal), ["EXCH-10:E-1"]);
+        assert_eq!(journal.stats().last_commit, 2);
+        apply(&journal, &mut replayed, &report(&sent, "E-2", OrderStatus::Filled, 6, 10));
+        drop(journal);
+
+        let (journal, again) = reopen(&config).unwrap();
+        assert_eq!(again.len(), 0);
+        assert_eq!(pending_keys(&journal), ["EXCH-10:E-1", "EXCH-10:E-2"]);
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn a_corrupt_record_before_the_end_fails_the_open() {
+        let config = config("corrupt");
+        let (journal, _) = reopen(&config).unwrap();
+        journal.sent(&order(10));
+        append(&config, b"not a commit\n");
+        journal.sent(&order(5));
+        drop(journal);
+
+        let error = reopen(&config).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("corrupt outbox journal"), "{}", error);
+        let _ = std::fs::remove_file(&config.path);
+    }
+}
@@ -0,0 +1,551 @@
+/*
+ * QuantumArb 2.0 - Core Services: Multi-Leg Execution
+ *
+ * File: src/core_services/exchange_gateway/legging.rs
+ *
+ * Description:
+ * Executes multi-leg packages approved by the risk gateway. Between the first
+ * leg filling and the last one, the package is "legged": it holds an
+ * unhedged position that the arbitrage was never meant to carry. The
+ * controls below bound that legging risk:
+ *
+ *   sequence      sequential sends each leg only once the previous one has
+ *                 filled, in the order the strategy listed them (least
+ *                 liquid first), so a failure early on leaves little to undo;
+ *                 simultaneous sends every leg at once for speed.
+ *   leg timeout   how long a package may keep retrying legs the venue
+ *                 rejected. A leg still working once its reports are in
+ *                 (acknowledged, or filled only in part) is canceled at
+ *                 once, so nothing stays live behind a failed package.
+ *   leg retries   extra attempts at a leg the venue rejected, before the
+ *                 package is abandoned.
+ *   auto-hedge    when a package is abandoned, every leg that filled, in
+ *                 full or in part, is unwound with an opposite order for the
+ *                 quantity filled at the leg's average fill price, so the
+ *                 book returns to flat. Without it, the residual position is
+ *                 reported for an operator to handle.
+ *
+ * Each package produces one PackageReport alongside the execution reports of
+ * every order sent for it (legs, retries and hedges).
+ *
+ * Configuration (environment):
+ *   QA_LEG_SEQUENCE=sequential       sequential | simultaneous
+ *   QA_LEG_TIMEOUT_MS=250            leg timeout
+ *   QA_LEG_RETRIES=1                 extra attempts per rejected leg
+ *   QA_LEG_AUTO_HEDGE=true           unwind filled legs of a failed package
+ */
+
+use crate::venue::{InboundOrder, VenueAdapter};
+use quantumarb_wire::{ExecutionReport, Hop, OrderPriority, OrderSide, OrderStatus, TradingMode};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LegSequence {
+    Sequential,
+    Simultaneous,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeggingConfig {
+    pub sequence: LegSequence,
+    pub leg_timeout: Duration,
+    pub leg_retries: u32,
+    pub auto_hedge: bool,
+}
+
+impl LeggingConfig {
+    pub fn from_env() -> LeggingConfig {
+        let sequence = match std::env::var("QA_LEG_SEQUENCE").as_deref() {
+            Ok("simultaneous") => LegSequence::Simultaneous,
+            _ => LegSequence::Sequential,
+        };
+        let timeout_ms = std::env::var("QA_LEG_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(250);
+        LeggingConfig {
+            sequence,
+            leg_timeout: Duration::from_millis(timeout_ms),
+            leg_retries: std::env::var("QA_LEG_RETRIES").ok().and_then(|v| v.parse().ok()).unwrap_or(1),
+            auto_hedge: std::env::var("QA_LEG_AUTO_HEDGE").map_or(true, |v| v != "false"),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// A package approved by the risk gateway. Each leg is a complete order
+/// (size already multiplied by its ratio).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundPackage {
+    pub package_id: Uuid,
+    pub legs: Vec<InboundOrder>,
+    #[serde(default)]
+    pub mode: TradingMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PackageStatus {
+    /// Every leg filled.
+    Completed,
+    /// A leg failed and every filled leg was unwound.
+    Hedged,
+    /// A leg failed before any leg filled; nothing to unwind.
+    Failed,
+    /// A leg failed and part of the package is still open (auto-hedge off or
+    /// a hedge failed). Needs an operator.
+    Exposed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegOutcome {
+    /// 1-based, as in the risk gateway's rejections.
+    pub leg: usize,
+    /// The order id of the leg's last attempt.
+    pub internal_order_id: Uuid,
+    pub instrument_symbol: String,
+    pub side: OrderSide,
+    pub size: u32,
+    /// 0 if the leg was never sent.
+    pub attempts: u32,
+    /// Whether the leg filled in full.
+    pub filled: bool,
+    /// Quantity filled by the leg's last attempt.
+    pub filled_size: u32,
+    /// Volume-weighted average price of the last attempt's fills.
+    pub filled_price: u64,
+}
+
+/// Published on 'execution_reports.packages' once a package is done.
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageReport {
+    pub package_id: Uuid,
+    pub status: PackageStatus,
+    pub legs: Vec<LegOutcome>,
+    /// Order ids of the hedges sent to unwind filled legs.
+    pub hedges: Vec<Uuid>,
+    pub detail: String,
+    pub elapsed_us: u64,
+}
+
+/// Everything that happened to a package: each order sent with the venue's
+/// report for it, and the package summary.
+pub struct Execution {
+    pub orders: Vec<(InboundOrder, ExecutionReport)>,
+    pub report: PackageReport,
+}
+
+// --- Execution ---
+
+/// Works a package to completion, or unwinds it. `send` writes one order to
+/// the venue.
+pub fn execute(
+    package: &InboundPackage,
+    venue: &dyn VenueAdapter,
+    config: &LeggingConfig,
+    send: &dyn Fn(&InboundOrder),
+) -> Execution {
+    let started = Instant::now();
+    let mut orders = Vec::new();
+    let mut outcomes: Vec<LegOutcome> = package
+        .legs
+        .iter()
+        .enumerate()
+        .map(|(index, leg)| LegOutcome {
+            leg: index + 1,
+            internal_order_id: leg.internal_order_id,
+            instrument_symbol: leg.instrument_symbol.clone(),
+            side: leg.side,
+            size: leg.size,
+            attempts: 0,
+            filled: false,
+            filled_size: 0,
+            filled_price: 0,
+        })
+        .collect();
+
+    let mut failed_leg = None;
+    match config.sequence {
+        LegSequence::Sequential => {
+            for (index, leg) in package.legs.iter().enumerate() {
+                let order = submit(leg.clone(), send);
+                if !work_leg(order, venue, config, started, send, &mut orders, &mut outcomes[index]) {
+                    failed_leg = Some(index);
+                    break;
+                }
+            }
+        }
+        LegSequence::Simultaneous => {
+            let sent: Vec<InboundOrder> = package.legs.iter().map(|leg| submit(leg.clone(), send)).collect();
+            for (index, order) in sent.into_iter().enumerate() {
+                if !work_leg(order, venue, config, started, send, &mut orders, &mut outcomes[index]) {
+                    failed_leg = failed_leg.or(Some(index));
+                }
+            }
+        }
+    }
+
+    let mut hedges = Vec::new();
+    let (status, detail) = match failed_leg {
+        None => (PackageStatus::Completed, "All legs filled".to_string()),
+        Some(index) if !outcomes.iter().any(|outcome| outcome.filled_size > 0) => {
+            (PackageStatus::Failed, format!("Leg {} failed before any leg filled", index + 1))
+        }
+        Some(index) if !config.auto_hedge => (
+            PackageStatus::Exposed,
+            format!("Leg {} failed; auto-hedge is off, filled legs left open", index + 1),
+        ),
+        Some(index) => {
+            let mut all_hedged = true;
+            for outcome in outcomes.iter().filter(|outcome| outcome.filled_size > 0) {
+                let leg = &package.legs[outcome.leg - 1];
+                let hedge = InboundOrder {
+                    internal_order_id: Uuid::new_v4(),
+                    side: opposite(leg.side),
+                    price: outcome.filled_price,
+                    size: outcome.filled_size,
+                    priority: OrderPriority::Hedge,
+                    ..leg.clone()
+                };
+                let hedge = submit(hedge, send);
+                let mut final_status = OrderStatus::SentToExchange;
+                for mut report in venue.execution_reports(&hedge) {
+                    report.stamps.stamp(Hop::ExecutionReceive);
+                    final_status = report.status;
+                    orders.push((hedge.clone(), report));
+                }
+                all_hedged &= final_status == OrderStatus::Filled;
+                hedges.push(hedge.internal_order_id);
+            }
+            if all_hedged {
+                (PackageStatus::Hedged, format!("Leg {} failed; {} filled legs unwound", index + 1, hedges.len()))
+            } else {
+                (PackageStatus::Exposed, format!("Leg {} failed and a hedge did not fill", index + 1))
+            }
+        }
+    };
+
+    Execution {
+        orders,
+        report: PackageReport {
+            package_id: package.package_id,
+            status,
+            legs: outcomes,
+            hedges,
+            detail,
+            elapsed_us: started.elapsed().as_micros() as u64,
+        },
+    }
+}
+
+/// Stamps the gateway send hop and writes the order to the venue.
+fn submit(mut order: InboundOrder, send: &dyn Fn(&InboundOrder)) -> InboundOrder {
+    order.stamps.stamp(Hop::GatewaySend);
+    send(&order);
+    order
+}
+
+/// Waits for a sent leg to fill, retrying venue rejections while retries and
+/// the leg timeout allow. Returns whether the leg filled in full; a leg still
+/// working at the venue, filled in part or not at all, is canceled.
+fn work_leg(
+    mut order: InboundOrder,
+    venue: &dyn VenueAdapter,
+    config: &LeggingConfig,
+    started: Instant,
+    send: &dyn Fn(&InboundOrder),
+    orders: &mut Vec<(InboundOrder, ExecutionReport)>,
+    outcome: &mut LegOutcome,
+) -> bool {
+    loop {
+        outcome.attempts += 1;
+        outcome.internal_order_id = order.internal_order_id;
+        outcome.filled_size = 0;
+        let mut notional = 0u128;
+        let mut status = OrderStatus::SentToExchange;
+        for mut report in venue.execution_reports(&order) {
+            report.stamps.stamp(Hop::ExecutionReceive);
+            status = report.status;
+            record_fill(&report, outcome, &mut notional);
+            orders.push((order.clone(), report));
+        }
+        outcome.filled = status == OrderStatus::Filled;
+        match status {
+            OrderStatus::Filled => return true,
+            OrderStatus::RejectedByExchange
+                if outcome.attempts <= config.leg_retries && started.elapsed() < config.leg_timeout =>
+            {
+                println!(
+                    "  -> Leg {} rejected; retrying ({} of {})",
+                    outcome.leg, outcome.attempts, config.leg_retries
+                );
+                order = submit(InboundOrder { internal_order_id: Uuid::new_v4(), ..order }, send);
+            }
+            OrderStatus::RejectedByExchange | OrderStatus::Canceled => return false,
+            _ => {
+                println!("  -> Leg {} filled {} of {}; canceling the rest", outcome.leg, outcome.filled_size, order.size);
+                let mut report = venue.cancel(&order);
+                report.stamps.stamp(Hop::ExecutionReceive);
+                // A fill can beat the cancel to the venue.
+                record_fill(&report, outcome, &mut notional);
+                outcome.filled = report.status == OrderStatus::Filled;
+                orders.push((order, report));
+                return outcome.filled;
+            }
+        }
+    }
+}
+
+/// Adds a report's fill to the leg, keeping `filled_price` at the average
+/// price of its fills; `notional` is the running sum of price x quantity.
+fn record_fill(report: &ExecutionReport, outcome: &mut LegOutcome, notional: &mut u128) {
+    if report.filled_size == 0 {
+        return;
+    }
+    outcome.filled_size += report.filled_size;
+    *notional += report.filled_price as u128 * report.filled_size as u128;
+    let filled = outcome.filled_size as u128;
+    outcome.filled_price = ((*notional + filled / 2) / filled) as u64;
+}
+
+fn opposite(side: OrderSide) -> OrderSide {
+    match side {
+        OrderSide::Buy => OrderSide::Sell,
+        OrderSide::Sell => OrderSide::Buy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::venue::NetworkPath;
+    use quantumarb_money::Money;
+    use quantumarb_wire::HopStamps;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    /// (status, filled size, fill price) of one execution report.
+    type Script = Vec<(OrderStatus, u32, u64)>;
+
+    /// Plays back scripted reports: each order sent for a symbol takes the
+    /// next script for it. With none left an order is only acknowledged, as
+    /// with the live adapter.
+    #[derive(Default)]
+    struct ScriptedVenue {
+        scripts: Mutex<HashMap<String, VecDeque<Script>>>,
+        canceled: Mutex<Vec<Uuid>>,
+    }
+
+    impl ScriptedVenue {
+        fn script(self, symbol: &str, reports: Script) -> Self {
+            self.scripts.lock().unwrap().entry(symbol.to_string()).or_default().push_back(reports);
+            self
+        }
+    }
+
+    fn report(order: &InboundOrder, status: OrderStatus, filled_size: u32, filled_price: u64) -> ExecutionReport {
+        ExecutionReport {
+            exchange_order_id: "EXCH-1".to_string(),
+            exec_id: String::new(),
+            internal_order_id: order.internal_order_id,
+            status,
+            filled_size,
+            filled_price,
+            reject: None,
+            stamps: order.stamps,
+            mode: TradingMode::Sandbox,
+            cumulative_size: 0,
+            leaves_size: 0,
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }
+    }
+
+    impl VenueAdapter for ScriptedVenue {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn send(&self, _order: &InboundOrder, _path: NetworkPath) {}
+
+        fn execution_reports(&self, order: &InboundOrder) -> Vec<ExecutionReport> {
+            let script = self.scripts.lock().unwrap().get_mut(&order.instrument_symbol).and_then(VecDeque::pop_front);
+            let script = script.unwrap_or_else(|| vec![(OrderStatus::SentToExchange, 0, 0)]);
+            script.into_iter().map(|(status, size, price)| report(order, status, size, price)).collect()
+        }
+
+        fn cancel(&self, order: &InboundOrder) -> ExecutionReport {
+            self.canceled.lock().unwrap().push(order.internal_order_id);
+            report(order, OrderStatus::Canceled, 0, 0)
+        }
+
+        fn amend(&self, _order: &InboundOrder, _size: u32, _cumulative_size: u32) -> ExecutionReport {
+            unreachable!("legging never amends")
+        }
+
+        fn sessions(&self) -> Vec<(&'static str, Vec<String>)> {
+            Vec::new()
+        }
+
+        fn heartbeat(&self, _session: &str) -> Option<u64> {
+            None
+        }
+
+        fn logon(&self, _session: &str) -> Result<u64, String> {
+            Err("scripted".to_string())
+        }
+
+        fn resend(&self, _session: &str, _from: u64, _to: u64) -> Vec<ExecutionReport> {
+            Vec::new()
+        }
+    }
+
+    fn leg(symbol: &str, side: OrderSide, size: u32) -> InboundOrder {
+        InboundOrder {
+            internal_order_id: Uuid::new_v4(),
+            instrument_symbol: symbol.to_string(),
+            price: 100_00,
+            size,
+            side,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            strategy: None,
+            priority: OrderPriority::Opportunistic,
+        }
+    }
+
+    /// Buys 2 BTC, sells 2 ETH and buys 2 INVT.
+    fn package() -> InboundPackage {
+        InboundPackage {
+            package_id: Uuid::new_v4(),
+            legs: vec![leg("BTC", OrderSide::Buy, 2), leg("ETH", OrderSide::Sell, 2), leg("INVT", OrderSide::Buy, 2)],
+            mode: TradingMode::Sandbox,
+        }
+    }
+
+    fn config(sequence: LegSequence) -> LeggingConfig {
+        LeggingConfig { sequence, leg_timeout: Duration::from_secs(60), leg_retries: 1, auto_hedge: true }
+    }
+
+    /// Executes `package` and returns it with the orders sent, in order.
+    fn run(package: &InboundPackage, venue: &ScriptedVenue, config: &LeggingConfig) -> (Execution, Vec<InboundOrder>) {
+        let sent = RefCell::new(Vec::new());
+        let execution = execute(package, venue, config, &|order| sent.borrow_mut().push(order.clone()));
+        (execution, sent.into_inner())
+    }
+
+    fn filled(price: u64) -> Script {
+        vec![(OrderStatus::Filled, 2, price)]
+    }
+
+    #[test]
+    fn a_package_completes_when_every_leg_fills() {
+        let venue =
+            ScriptedVenue::default().script("BTC", filled(100)).script("ETH", filled(200)).script("INVT", filled(300));
+        let (execution, sent) = run(&package(), &venue, &config(LegSequence::Sequential));
+        assert_eq!(execution.report.status, PackageStatus::Completed);
+        assert_eq!(sent.len(), 3);
+        assert!(execution.report.legs.iter().all(|outcome| outcome.filled && outcome.attempts == 1));
+        assert_eq!(execution.orders.len(), 3);
+    }
+
+    #[test]
+    fn a_sequential_failure_cancels_the_working_leg_and_unwinds_at_the_average_price() {
+        let partials = vec![(OrderStatus::PartiallyFilled, 1, 100), (OrderStatus::Filled, 1, 103)];
+        let venue = ScriptedVenue::default().script("BTC", partials).script("BTC", filled(101));
+        let package = package();
+        let (execution, sent) = run(&package, &venue, &config(LegSequence::Sequential));
+
+        // ETH is only acknowledged, so it is canceled and INVT never sent.
+        let report = &execution.report;
+        assert_eq!(report.status, PackageStatus::Hedged, "{}", report.detail);
+        assert_eq!(*venue.canceled.lock().unwrap(), [package.legs[1].internal_order_id]);
+        assert_eq!(report.legs[2].attempts, 0);
+        assert_eq!((report.legs[0].filled_size, report.legs[0].filled_price), (2, 102));
+
+        let hedge = sent.last().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(report.hedges, [hedge.internal_order_id]);
+        assert_eq!((hedge.side, hedge.size, hedge.price), (OrderSide::Sell, 2, 102));
+        assert_eq!((hedge.instrument_symbol.as_str(), hedge.priority), ("BTC", OrderPriority::Hedge));
+    }
+
+    #[test]
+    fn a_first_leg_out_of_retries_fails_the_package_with_nothing_to_unwind() {
+        let rejected = vec![(OrderStatus::RejectedByExchange, 0, 0)];
+        let venue = ScriptedVenue::default().script("BTC", rejected.clone()).script("BTC", rejected);
+        let (execution, sent) = run(&package(), &venue, &config(LegSequence::Sequential));
+        assert_eq!(execution.report.status, PackageStatus::Failed);
+        assert_eq!(execution.report.detail, "Leg 1 failed before any leg filled");
+        assert_eq!((sent.len(), execution.report.legs[0].attempts), (2, 2));
+        assert!(venue.canceled.lock().unwrap().is_empty(), "a rejected order is not working");
+    }
+
+    #[test]
+    fn a_rejected_leg_is_retried_under_a_new_order_id() {
+        let venue = ScriptedVenue::default()
+            .script("BTC", vec![(OrderStatus::RejectedByExchange, 0, 0)])
+            .script("BTC", filled(100))
+            .script("ETH", filled(200))
+            .script("INVT", filled(300));
+        let package = package();
+        let (execution, sent) = run(&package, &venue, &config(LegSequence::Sequential));
+        assert_eq!(execution.report.status, PackageStatus::Completed);
+        let first = &execution.report.legs[0];
+        assert_eq!(first.attempts, 2);
+        assert_ne!(first.internal_order_id, package.legs[0].internal_order_id);
+        assert_eq!(sent[1].internal_order_id, first.internal_order_id);
+
+        // With the leg timeout spent, a reject is final.
+        let venue = ScriptedVenue::default().script("BTC", vec![(OrderStatus::RejectedByExchange, 0, 0)]);
+        let config = LeggingConfig { leg_timeout: Duration::ZERO, ..config(LegSequence::Sequential) };
+        let (execution, _) = run(&package, &venue, &config);
+        assert_eq!((execution.report.status, execution.report.legs[0].attempts), (PackageStatus::Failed, 1));
+    }
+
+    #[test]
+    fn a_simultaneous_failure_cancels_every_working_leg_and_unwinds_the_fills() {
+        let venue = ScriptedVenue::default()
+            .script("BTC", filled(100))
+            .script("ETH", vec![(OrderStatus::PartiallyFilled, 1, 200)])
+            .script("BTC", filled(100))
+            .script("ETH", filled(200));
+        let package = package();
+        let (execution, sent) = run(&package, &venue, &config(LegSequence::Simultaneous));
+
+        let report = &execution.report;
+        assert_eq!(report.status, PackageStatus::Hedged, "{}", report.detail);
+        assert_eq!(report.detail, "Leg 2 failed; 2 filled legs unwound");
+        let canceled = venue.canceled.lock().unwrap().clone();
+        assert_eq!(canceled, [package.legs[1].internal_order_id, package.legs[2].internal_order_id]);
+        let hedges: Vec<_> =
+            sent[3..].iter().map(|hedge| (hedge.instrument_symbol.as_str(), hedge.side, hedge.size)).collect();
+        assert_eq!(hedges, [("BTC", OrderSide::Sell, 2), ("ETH", OrderSide::Buy, 1)]);
+    }
+
+    #[test]
+    fn without_auto_hedge_the_filled_legs_are_left_exposed() {
+        let venue = ScriptedVenue::default().script("BTC", filled(100));
+        let config = LeggingConfig { auto_hedge: false, ..config(LegSequence::Simultaneous) };
+        let (execution, sent) = run(&package(), &venue, &config);
+        assert_eq!(execution.report.status, PackageStatus::Exposed);
+        assert!(execution.report.hedges.is_empty());
+        assert_eq!(sent.len(), 3, "no hedges sent");
+        assert_eq!(venue.canceled.lock().unwrap().len(), 2, "working legs are still canceled");
+    }
+
+    #[test]
+    fn a_hedge_that_does_not_fill_leaves_the_package_exposed() {
+        // The BTC hedge is only acknowledged.
+        let venue = ScriptedVenue::default().script("BTC", filled(100));
+        let (execution, sent) = run(&package(), &venue, &config(LegSequence::Sequential));
+        assert_eq!(execution.report.status, PackageStatus::Exposed);
+        assert_eq!(execution.report.detail, "Leg 2 failed and a hedge did not fill");
+        assert_eq!(execution.report.hedges, [sent.last().unwrap().internal_order_id]);
+    }
+}
@@ -0,0 +1,489 @@
+/*
+ * QuantumArb 2.0 - Core Services: Order Lifecycle
+ *
+ * File: src/core_services/exchange_gateway/lifecycle.rs
+ *
+ * Description:
+ * The gateway's book of open orders and the state machine each one follows
+ * through the venue's execution reports:
+ *
+ *   New / SentToExchange -> PartiallyFilled -> Filled
+ *                        \                  \-> Canceled (ours or unsolicited)
+ *                         \-> RejectedByExchange
+ *
+ * An amendment (Replaced) changes the order's quantity without changing its
+ * state. A bust (TradeBusted) takes the busted fill off the cumulative
+ * quantity and a correction (TradeCorrected) sets it to what the venue says,
+ * moving an open order back to PartiallyFilled or SentToExchange as needed;
+ * one for an order already Filled or Canceled leaves it closed. Cumulative
+ * quantity is tracked from the fills themselves and checked against what the
+ * venue says (CumQty, and Filled only once nothing is left).
+ *
+ * A report that cannot follow from the order's state is an impossible
+ * sequence: a fill beyond the order's quantity, a reject after a fill, a fill
+ * on an order that is already done, a cumulative quantity that disagrees with
+ * the fills seen. It is not applied. Instead it is recorded as a
+ * `LifecycleViolation` (GET /orders/violations) and published on
+ * 'alerts.order_lifecycle' for an operator to reconcile with the venue.
+ *
+ * Reports from the venue reach the book through its sequencer (see
+ * `sequencer.rs`), which drops duplicates and puts reports that arrive out
+ * of order back in order first.
+ */
+
+use crate::sequencer::{Sequencer, SequencerConfig, SequencerStats};
+use crate::venue::InboundOrder;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::{ExecutionReport, OrderStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use uuid::Uuid;
+
+pub const VIOLATIONS_TOPIC: &str = "alerts.order_lifecycle";
+
+/// Closed orders remembered, so a late report for one is recognised.
+const CLOSED_RETENTION: usize = 10_000;
+/// Violations kept for GET /orders/violations.
+const VIOLATION_RETENTION: usize = 1_000;
+
+// --- Data Structures ---
+
+/// An order working at the venue, as exported on GET /orders/open.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct OpenOrder {
+    /// The order as sent; `size` follows any amendment.
+    #[serde(flatten)]
+    pub order: InboundOrder,
+    #[serde(default = "sent_to_exchange")]
+    pub status: OrderStatus,
+    /// Quantity filled so far.
+    #[serde(default)]
+    pub cumulative_size: u32,
+}
+
+fn sent_to_exchange() -> OrderStatus {
+    OrderStatus::SentToExchange
+}
+
+/// An execution report that could not follow from the order's state.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct LifecycleViolation {
+    pub internal_order_id: Uuid,
+    pub exchange_order_id: String,
+    /// The order's state when the report arrived (None: not a known order).
+    pub state: Option<OrderStatus>,
+    pub report_status: OrderStatus,
+    pub detail: String,
+    pub timestamp_utc: String,
+}
+
+impl OpenOrder {
+    pub fn new(order: InboundOrder) -> OpenOrder {
+        OpenOrder { order, status: OrderStatus::New, cumulative_size: 0 }
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.status, OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::RejectedByExchange)
+    }
+
+    /// Moves the order on by the venue's next report. On error the order is
+    /// left as it was and the error says why the report is impossible.
+    pub fn apply(&mut self, report: &ExecutionReport) -> Result<(), String> {
+        match report.status {
+            OrderStatus::New | OrderStatus::SentToExchange => {
+                if self.status != OrderStatus::New && self.status != OrderStatus::SentToExchange {
+                    return Err(format!("{:?} order reported as {:?} again", self.status, report.status));
+                }
+                self.status = report.status;
+            }
+            OrderStatus::PartiallyFilled | OrderStatus::Filled => {
+                if report.filled_size == 0 {
+                    return Err("fill report without a fill quantity".to_string());
+                }
+                let cumulative = self.cumulative_size + report.filled_size;
+                if cumulative > self.order.size {
+                    return Err(format!("fills total {} on an order for {}", cumulative, self.order.size));
+                }
+                // Producers before the fill detail report no CumQty (0).
+                if report.cumulative_size != 0 && report.cumulative_size != cumulative {
+                    return Err(format!(
+                        "venue reports {} filled, but the fills seen total {}",
+                        report.cumulative_size, cumulative
+                    ));
+                }
+                let complete = cumulative == self.order.size;
+                if report.status == OrderStatus::Filled && !complete {
+                    return Err(format!("Filled with {} of {} still open", self.order.size - cumulative, self.order.size));
+                }
+                if report.status == OrderStatus::PartiallyFilled && complete {
+                    return Err(format!("PartiallyFilled, but all {} are filled", self.order.size));
+                }
+                self.cumulative_size = cumulative;
+                self.status = report.status;
+            }
+            OrderStatus::Replaced => {
+                if report.leaves_size == 0 {
+                    return Err("amended to nothing left working".to_string());
+                }
+                self.order.size = self.cumulative_size + report.leaves_size;
+            }
+            OrderStatus::Canceled => {
+                if report.cumulative_size != 0 && report.cumulative_size != self.cumulative_size {
+                    return Err(format!(
+                        "canceled with {} filled, but the fills seen total {}",
+                        report.cumulative_size, self.cumulative_size
+                    ));
+                }
+                self.status = OrderStatus::Canceled;
+            }
+            OrderStatus::RejectedByExchange => {
+                if self.cumulative_size > 0 {
+                    return Err(format!("rejected after {} filled", self.cumulative_size));
+                }
+                self.status = OrderStatus::RejectedByExchange;
+            }
+            OrderStatus::TradeBusted => {
+                let Some(cumulative) = self.cumulative_size.checked_sub(report.filled_size) else {
+                    return Err(format!("busts {} of {} filled", report.filled_size, self.cumulative_size));
+                };
+                if report.cumulative_size != 0 && report.cumulative_size != cumulative {
+                    return Err(format!(
+                        "venue reports {} filled after the bust, but the fills seen leave {}",
+                        report.cumulative_size, cumulative
+                    ));
+                }
+                self.reopen(cumulative);
+            }
+            OrderStatus::TradeCorrected => {
+                if report.cumulative_size > self.order.size {
+                    let (corrected, size) = (report.cumulative_size, self.order.size);
+                    return Err(format!("corrected to {} filled on an order for {}", corrected, size));
+                }
+                self.reopen(report.cumulative_size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the quantity filled after a bust or correction; the order is
+    /// Filled only if all of it still is.
+    fn reopen(&mut self, cumulative: u32) {
+        self.cumulative_size = cumulative;
+        self.status = if cumulative == self.order.size {
+            OrderStatus::Filled
+        } else if cumulative > 0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::SentToExchange
+        };
+    }
+}
+
+// --- Order Book ---
+
+/// Open orders, the fate of recently closed ones, and the violations seen.
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    open: HashMap<Uuid, OpenOrder>,
+    closed: HashMap<Uuid, OrderStatus>,
+    closed_order: VecDeque<Uuid>,
+    violations: VecDeque<LifecycleViolation>,
+    sequencer: Sequencer,
+}
+
+/// What the order journal keeps of the book when it is compacted: the open
+/// and recently closed orders, and the report keys seen, so a report the
+/// venue resends after a restart is still recognised as a duplicate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct BookSnapshot {
+    pub open: Vec<OpenOrder>,
+    /// Oldest first.
+    pub closed: Vec<(Uuid, OrderStatus)>,
+    pub seen: Vec<(String, String)>,
+}
+
+/// A report released by the sequencer and what applying it did: the order
+/// as the report left it, or None for a bust or correction of a fill on an
+/// order that is already closed.
+pub type Applied = (ExecutionReport, Result<Option<OpenOrder>, LifecycleViolation>);
+
+impl OrderBook {
+    pub fn new(sequencing: SequencerConfig) -> OrderBook {
+        OrderBook { sequencer: Sequencer::new(sequencing), ..OrderBook::default() }
+    }
+
+    /// Starts tracking an order sent to the venue. Tracking an order twice
+    /// (a package leg and its reports) keeps its progress.
+    pub fn track(&mut self, order: &InboundOrder) {
+        self.open.entry(order.internal_order_id).or_insert_with(|| OpenOrder::new(order.clone()));
+    }
+
+    pub fn get(&self, internal_order_id: &Uuid) -> Option<&OpenOrder> {
+        self.open.get(internal_order_id)
+    }
+
+    pub fn open_orders(&self) -> impl Iterator<Item = &OpenOrder> {
+        self.open.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Replaces the open orders with a restored set. Reports held for the
+    /// orders replaced are dropped.
+    pub fn restore(&mut self, orders: Vec<OpenOrder>) {
+        self.open = orders.into_iter().map(|order| (order.order.internal_order_id, order)).collect();
+        self.sequencer.clear_held();
+    }
+
+    pub fn snapshot(&self) -> BookSnapshot {
+        BookSnapshot {
+            open: self.open.values().cloned().collect(),
+            closed: self.closed_order.iter().map(|id| (*id, self.closed[id])).collect(),
+            seen: self.sequencer.seen(),
+        }
+    }
+
+    /// Rebuilds the book from a journaled snapshot.
+    pub fn load_snapshot(&mut self, snapshot: BookSnapshot) {
+        self.restore(snapshot.open);
+        self.closed.clear();
+        self.closed_order.clear();
+        for (id, status) in snapshot.closed {
+            self.remember_closed(id, status);
+        }
+        for (exchange_order_id, exec_id) in snapshot.seen {
+            self.sequencer.mark_seen(exchange_order_id, exec_id);
+        }
+    }
+
+    /// Applies a report replayed from the order journal. It went through the
+    /// sequencer before it was journaled, so it is only remembered as seen.
+    pub fn replay(&mut self, report: &ExecutionReport) {
+        self.sequencer.mark_seen(report.exchange_order_id.clone(), report.exec_id.clone());
+        let _ = self.apply_report(report);
+    }
+
+    pub fn sequencing(&self) -> SequencerStats {
+        self.sequencer.stats()
+    }
+
+    /// Takes a report from the venue through the sequencer and applies what
+    /// it releases, in order: nothing for a duplicate or a report held for a
+    /// gap, and any held reports the report lets through after it.
+    pub fn receive(&mut self, report: ExecutionReport, now: Instant) -> Vec<Applied> {
+        let cumulative = self.cumulative(&report.internal_order_id);
+        let released = self.sequencer.admit(report, cumulative, now);
+        self.apply_released(released)
+    }
+
+    /// Applies the reports held longer than the sequencer's hold time.
+    pub fn release_expired(&mut self, now: Instant) -> Vec<Applied> {
+        let released = self.sequencer.expire(now);
+        self.apply_released(released)
+    }
+
+    fn apply_released(&mut self, released: Vec<ExecutionReport>) -> Vec<Applied> {
+        let mut applied = Vec::with_capacity(released.len());
+        for report in released {
+            let id = report.internal_order_id;
+            let mut next = Some(report);
+            while let Some(report) = next {
+                let result = self.apply_report(&report);
+                next = self.sequencer.next_ready(&id, self.cumulative(&id));
+                applied.push((report, result));
+            }
+        }
+        applied
+    }
+
+    fn cumulative(&self, internal_order_id: &Uuid) -> Option<u32> {
+        self.open.get(internal_order_id).map(|order| order.cumulative_size)
+    }
+
+    pub fn violations(&self) -> Vec<LifecycleViolation> {
+        self.violations.iter().cloned().collect()
+    }
+
+    /// Applies a report as `apply` does, except that a bust or correction of
+    /// a fill on a Filled or Canceled order is accepted without reopening the
+    /// order (Ok(None)): the venue has nothing left working for it, and the
+    /// fill is put right downstream.
+    pub fn apply_report(&mut self, report: &ExecutionReport) -> Result<Option<OpenOrder>, LifecycleViolation> {
+        let id = report.internal_order_id;
+        if report.status.is_trade_correction() && !self.open.contains_key(&id) {
+            if let Some(OrderStatus::Filled | OrderStatus::Canceled) = self.closed.get(&id) {
+                return Ok(None);
+            }
+        }
+        self.apply(report).map(Some)
+    }
+
+    /// Applies a report to its order, closing the order once it is done.
+    /// Returns the order as the report left it.
+    pub fn apply(&mut self, report: &ExecutionReport) -> Result<OpenOrder, LifecycleViolation> {
+        let id = report.internal_order_id;
+        let Some(order) = self.open.get_mut(&id) else {
+            let state = self.closed.get(&id).copied();
+            let detail = match state {
+                Some(status) => format!("{:?} report for an order already {:?}", report.status, status),
+                None => format!("{:?} report for an order the gateway never sent", report.status),
+            };
+            return Err(self.flag(report, state, detail));
+        };
+        let state = order.status;
+        if let Err(detail) = order.apply(report) {
+            return Err(self.flag(report, Some(state), detail));
+        }
+        if !order.is_done() {
+            return Ok(order.clone());
+        }
+        let order = self.open.remove(&id).expect("applied to an open order");
+        self.remember_closed(id, order.status);
+        Ok(order)
+    }
+
+    fn remember_closed(&mut self, id: Uuid, status: OrderStatus) {
+        if self.closed_order.len() == CLOSED_RETENTION {
+            if let Some(oldest) = self.closed_order.pop_front() {
+                self.closed.remove(&oldest);
+            }
+        }
+        self.closed.insert(id, status);
+        self.closed_order.push_back(id);
+    }
+
+    fn flag(&mut self, report: &ExecutionReport, state: Option<OrderStatus>, detail: String) -> LifecycleViolation {
+        let violation = LifecycleViolation {
+            internal_order_id: report.internal_order_id,
+            exchange_order_id: report.exchange_order_id.clone(),
+            state,
+            report_status: report.status,
+            detail,
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+        };
+        if self.violations.len() == VIOLATION_RETENTION {
+            self.violations.pop_front();
+        }
+        self.violations.push_back(violation.clone());
+        violation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_money::Money;
+    use quantumarb_wire::{HopStamps, OrderPriority, OrderSide, TradingMode};
+
+    fn order(size: u32) -> InboundOrder {
+        InboundOrder {
+            internal_order_id: Uuid::new_v4(),
+            instrument_symbol: "ESZ25".to_string(),
+            price: 4500_25,
+            size,
+            side: OrderSide::Buy,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            strategy: None,
+            priority: OrderPriority::Opportunistic,
+        }
+    }
+
+    fn report(order: &InboundOrder, status: OrderStatus, filled_size: u32, cumulative_size: u32) -> ExecutionReport {
+        ExecutionReport {
+            exchange_order_id: "EXCH-1".to_string(),
+            exec_id: String::new(),
+            internal_order_id: order.internal_order_id,
+            status,
+            filled_size,
+            filled_price: order.price,
+            reject: None,
+            stamps: order.stamps,
+            mode: TradingMode::Sandbox,
+            cumulative_size,
+            leaves_size: order.size.saturating_sub(cumulative_size),
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn partial_fills_accumulate_until_filled() {
+        let mut book = OrderBook::default();
+        let sent = order(10);
+        book.track(&sent);
+        let open = book.apply(&report(&sent, OrderStatus::PartiallyFilled, 4, 4)).unwrap();
+        assert_eq!((open.status, open.cumulative_size), (OrderStatus::PartiallyFilled, 4));
+        let done = book.apply(&report(&sent, OrderStatus::Filled, 6, 10)).unwrap();
+        assert_eq!((done.status, done.cumulative_size), (OrderStatus::Filled, 10));
+        assert_eq!(book.len(), 0);
+    }
+
+    #[test]
+    fn amendments_change_the_quantity_left_to_fill() {
+        let mut book = OrderBook::default();
+        let sent = order(10);
+        book.track(&sent);
+        book.apply(&report(&sent, OrderStatus::PartiallyFilled, 4, 4)).unwrap();
+        let mut amend = report(&sent, OrderStatus::Replaced, 0, 4);
+        amend.leaves_size = 2;
+        assert_eq!(book.apply(&amend).unwrap().order.size, 6);
+        assert_eq!(book.apply(&report(&sent, OrderStatus::Filled, 2, 6)).unwrap().status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn unsolicited_cancels_close_a_partly_filled_order() {
+        let mut book = OrderBook::default();
+        let sent = order(10);
+        book.track(&sent);
+        book.apply(&report(&sent, OrderStatus::PartiallyFilled, 4, 4)).unwrap();
+        let canceled = book.apply(&report(&sent, OrderStatus::Canceled, 0, 4)).unwrap();
+        assert_eq!((canceled.status, canceled.cumulative_size), (OrderStatus::Canceled, 4));
+    }
+
+    #[test]
+    fn busts_and_corrections_restate_the_quantity_filled() {
+        let mut book = OrderBook::default();
+        let sent = order(10);
+        book.track(&sent);
+        book.apply(&report(&sent, OrderStatus::PartiallyFilled, 4, 4)).unwrap();
+        book.apply(&report(&sent, OrderStatus::PartiallyFilled, 3, 7)).unwrap();
+
+        let busted = book.apply_report(&report(&sent, OrderStatus::TradeBusted, 3, 4)).unwrap().unwrap();
+        assert_eq!((busted.status, busted.cumulative_size), (OrderStatus::PartiallyFilled, 4));
+        let corrected = book.apply_report(&report(&sent, OrderStatus::TradeCorrected, 2, 2)).unwrap().unwrap();
+        assert_eq!(corrected.cumulative_size, 2);
+        assert!(book.apply_report(&report(&sent, OrderStatus::TradeBusted, 3, 0)).is_err(), "busts more than filled");
+
+        // Once the order is done, a bust leaves it closed.
+        book.apply(&report(&sent, OrderStatus::Filled, 8, 10)).unwrap();
+        assert!(book.apply_report(&report(&sent, OrderStatus::TradeBusted, 8, 2)).unwrap().is_none());
+        assert_eq!(book.violations().len(), 1);
+    }
+
+    #[test]
+    fn flags_impossible_sequences_without_applying_them() {
+        let mut book = OrderBook::default();
+        let sent = order(10);
+        book.track(&sent);
+        book.apply(&report(&sent, OrderStatus::PartiallyFilled, 4, 4)).unwrap();
+
+        // Overfill, a CumQty that disagrees, a reject after a fill, Filled too early.
+        assert!(book.apply(&report(&sent, OrderStatus::Filled, 7, 11)).is_err());
+        assert!(book.apply(&report(&sent, OrderStatus::PartiallyFilled, 2, 5)).is_err());
+        assert!(book.apply(&report(&sent, OrderStatus::RejectedByExchange, 0, 0)).is_err());
+        assert!(book.apply(&report(&sent, OrderStatus::Filled, 2, 6)).is_err());
+        assert_eq!(book.get(&sent.internal_order_id).unwrap().cumulative_size, 4);
+
+        // A fill after the order is done.
+        book.apply(&report(&sent, OrderStatus::Filled, 6, 10)).unwrap();
+        let late = book.apply(&report(&sent, OrderStatus::PartiallyFilled, 1, 11)).unwrap_err();
+        assert_eq!(late.state, Some(OrderStatus::Filled));
+        assert_eq!(book.violations().len(), 5);
+    }
+}
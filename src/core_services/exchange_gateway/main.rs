@@ -4,181 +4,1046 @@
  * File: src/core_services/exchange_gateway/main.rs
  *
  * Description:
- * This is the final, fully integrated version of the Exchange Gateway. It now
- * queries the Latency Oracle before sending an order to dynamically select the
- * fastest available network path (e.g., Microwave or Fiber).
+ * This is the final, fully integrated version of the Exchange Gateway. It
+ * routes each order over a network path (Microwave or Fiber) chosen by a
+ * routing policy from the Latency Oracle's path scores, the cost of a
+ * message on each path and the order's priority class (see `routing.rs`),
+ * within each path's bandwidth budget (see `bandwidth.rs`).
  *
  * This completes the core tick-to-trade path, incorporating dynamic routing
  * for ultra-low-latency performance.
  *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * serde = { version = "1.0", features = ["derive"] }
- * serde_json = "1.0"
- * uuid = { version = "1", features = ["v4"] }
- * reqwest = "0.12"
+ * Venue rejections are translated from the exchange's native reason (FIX
+ * OrdRejReason, tag 103) into the shared `quantumarb-errors` reject codes
+ * before the execution report is published.
+ *
+ * The venue connection follows the platform trading mode (QA_TRADING_MODE):
+ * sandbox, the default, routes every order to the paper-trading simulator;
+ * live connects the real venue adapters, which only exist in builds with
+ * the `live-venues` feature (see `venue.rs`): CME Globex, or Binance spot
+ * with QA_LIVE_VENUE=binance (on its testnet unless QA_BINANCE_TESTNET=false,
+ * see `binance.rs`). Orders stamped with the other mode are rejected without
+ * reaching any venue. Reports a venue streams on its own, such as Binance
+ * fills, are applied as they arrive.
+ *
+ * Execution reports carry the fill detail downstream consumers book from:
+ * last and cumulative quantity, the quantity still working, the liquidity
+ * indicator, the venue's fee and its transact time.
+ *
+ * Execution reports are published in the binary `quantumarb-wire` encoding;
+ * the JSON form is still logged for debugging (QA_BUS_ENCODING=json switches
+ * the bus payload to JSON as well).
+ *
+ * With QA_RUNTIME_MODE=low-latency the final write of each order to the venue
+ * happens on a dedicated core-pinned thread busy-polling a bounded queue
+ * (see `quantumarb-hotpath`), keeping it clear of tokio scheduling jitter.
+ *
+ * As the last hop of the order path, the gateway stamps gateway send and
+ * execution receive into each message's `HopStamps` and aggregates the full
+ * tick-to-trade breakdown, served on GET /latency (port 3036).
+ *
+ * Multi-leg packages approved by the risk gateway are worked leg by leg with
+ * legging-risk controls (sequence, leg timeout, retries) and, if a leg
+ * cannot be filled, the legs that did fill are hedged back out (see
+ * `legging.rs`). A summary of each package is published on
+ * 'execution_reports.packages'.
+ *
+ * The risk gateway's heartbeat watchdog protects strategies that have gone
+ * silent through POST /orders/cancel (cancel the open orders in a set of
+ * symbols) and POST /orders/flatten (send offsetting orders for their
+ * positions). These emergency orders go straight to the venue as hedges, on
+ * the fastest path the router knows of.
+ *
+ * Every order follows the lifecycle state machine in `lifecycle.rs` through
+ * its execution reports: partial fills accumulate until the order is filled,
+ * amendments (POST /orders/amend) change its quantity, and venue rejects and
+ * unsolicited cancels close it. Reports that cannot follow from the order's
+ * state are flagged (GET /orders/violations, 'alerts.order_lifecycle').
+ *
+ * Reports reach the order book through a sequencer (see `sequencer.rs`):
+ * duplicates, recognised by exchange order id and ExecID, are dropped before
+ * they are applied or published, and a report that arrives ahead of the
+ * fills before it is held briefly for them. GET /orders/sequencing counts
+ * what it dropped and reordered.
+ *
+ * The open order book, with each order's state and filled quantity, is
+ * exported on GET /orders/open for the risk gateway's platform snapshots and
+ * replaced on PUT /orders/open when one is restored. A restore only rebuilds
+ * the gateway's view: orders that are no longer working at the venue close
+ * out with their next execution report.
+ *
+ * The venue sessions are supervised (see `supervisor.rs`): heartbeats on
+ * each venue's active session, reconnects that resynchronize sequence
+ * numbers, and failover to a backup session. Every change of a venue's state
+ * is published on 'venues.connectivity', which the risk gateway uses to stop
+ * approving orders for a venue that is down. GET /venues shows the sessions.
+ *
+ * The gateway holds itself to a send-time budget (see `budget.rs`): the
+ * rolling p99 of its processing time per order, from receipt to the write
+ * to the venue, is checked against QA_SEND_BUDGET_P99_US. Going over raises
+ * an alert on 'alerts.send_budget' and, with QA_SEND_BUDGET_SHED=true, sheds
+ * the orders of the lowest-priority strategies first. GET /latency/budget
+ * shows where it stands. Hedge orders are never shed.
+ *
+ * In the low-latency runtime mode, orders queued for the submit thread are
+ * written highest priority class first (hedge, arbitrage, opportunistic), so
+ * a risk-reducing hedge does not wait behind a burst of opportunistic orders.
+ *
+ * Order state is event-sourced (see `journal.rs`): every order sent and every
+ * report applied is committed to a local journal together with the bus
+ * message it causes, and a publisher drains that outbox with dedup keys. A
+ * restarted gateway replays the journal into its order book and publishes
+ * what a crash left unsent, so consumers never see a phantom or missing
+ * fill (GET /orders/outbox).
+ *
+ * Every request to a venue is paced against the venue's API rate limit (see
+ * `throttle.rs`), counted by the gateway over a sliding window: a request
+ * over the budget waits for it, cancels ahead of orders and orders ahead of
+ * queries, and part of each window is kept for cancels alone. GET
+ * /venues/rate-limits shows each venue's remaining budget.
+ *
+ * The paper venue prices fills with a market-impact model (see `impact.rs`):
+ * an order moves the price against itself by a linear or square-root
+ * function of its participation rate, and part of the move carries over to
+ * the next orders in the instrument.
+ *
+ * --seed N (or QA_SEED) makes sandbox runs repeatable: the simulated inbound
+ * flow and the paper venue's fills and rejections are drawn from seeded
+ * streams (see `quantumarb-sim`).
+ *
+ * The path scores are polled from the Latency Oracle in the background, so
+ * orders never wait on it. GET /routing shows the paths chosen per strategy
+ * and what they cost.
+ *
+ * With desks configured (QA_DESKS_PATH, see `quantumarb-tenancy`) the API
+ * takes a bearer token: a desk's token reads its own strategies' orders on
+ * GET /orders/open, and every other endpoint, including cancel, amend,
+ * flatten and the open order book restore, takes a firm token.
+ *
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
  */
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+#[cfg(feature = "live-venues")]
+mod binance;
+mod bandwidth;
+mod budget;
+mod impact;
+mod journal;
+mod legging;
+mod lifecycle;
+mod routing;
+mod sequencer;
+mod supervisor;
+mod throttle;
+mod venue;
+
+use budget::{SendBudget, SendBudgetConfig, SEND_BUDGET_ALERTS_TOPIC};
+use quantumarb_bus::topics;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_hotpath::{spawn_pinned, HotQueue, RuntimeMode};
+use quantumarb_latency::LatencyRecorder;
+use quantumarb_openapi::{ApiDoc, ApiSchema, Value};
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_tenancy::{Desks, Scope};
+use quantumarb_types::{CancelRequest, FlattenRequest};
+use rand::Rng;
+use journal::{Journal, JournalConfig};
+use legging::{InboundPackage, LeggingConfig, PackageReport};
+use lifecycle::{Applied, LifecycleViolation, OpenOrder, OrderBook};
+use quantumarb_wire::{
+    monotonic_ns, Encoding, ExecutionReport, Hop, HopStamps, OrderPriority, OrderQueue, OrderSide, OrderStatus,
+    TradingMode,
+};
+use routing::{Router, RoutingConfig};
+use serde::Deserialize;
+use sequencer::SequencerConfig;
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use supervisor::{Action, Probe, Supervisor, SupervisorConfig};
+use throttle::{RateLimitConfig, RateLimits, RequestKind, SharedRateLimits};
 use tokio::time::{self, Duration};
 use uuid::Uuid;
+use venue::{InboundOrder, NetworkPath, VenueAdapter};
+use warp::Filter;
 
 // --- Data Structures ---
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct InboundOrder {
+const LATENCY_ORACLE_URL: &str = "http://latency-oracle.default.svc.cluster.local/paths";
+
+type SharedLatency = Arc<Mutex<LatencyRecorder>>;
+type SharedOrderBook = Arc<Mutex<OrderBook>>;
+type SharedSupervisor = Arc<Mutex<Supervisor>>;
+type SharedBudget = Arc<Mutex<SendBudget>>;
+type SharedRouter = Arc<Mutex<Router>>;
+
+/// Body of POST /orders/amend: the order's new total quantity, filled part
+/// included.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct AmendRequest {
     internal_order_id: Uuid,
-    instrument_symbol: String,
-    price: u64,
     size: u32,
-    side: OrderSide,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-enum OrderStatus {
-    New,
-    SentToExchange,
-    PartiallyFilled,
-    Filled,
-    Canceled,
-    RejectedByExchange,
+/// What the emergency cancel and flatten endpoints need.
+#[derive(Clone)]
+struct EmergencyContext {
+    venue: Arc<dyn VenueAdapter>,
+    open_orders: SharedOrderBook,
+    journal: Journal,
+    mode: TradingMode,
+    rate_limits: SharedRateLimits,
+    router: SharedRouter,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-enum OrderSide {
-    Buy,
-    Sell,
+/// Where the final write of an order to the venue happens.
+enum WireSender {
+    /// Inline, on the tokio task that received the order.
+    Inline(Arc<dyn VenueAdapter>, SharedBudget),
+    /// On a core-pinned thread busy-polling this queue, highest priority first.
+    Pinned(HotQueue<(InboundOrder, NetworkPath, Option<u64>)>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ExecutionReport {
-    exchange_order_id: String,
-    internal_order_id: Uuid,
-    status: OrderStatus,
-    filled_size: u32,
-    filled_price: u64,
-}
+impl WireSender {
+    fn from_mode(mode: RuntimeMode, venue: Arc<dyn VenueAdapter>, budget: SharedBudget) -> WireSender {
+        let config = match mode {
+            RuntimeMode::Standard => return WireSender::Inline(venue, budget),
+            RuntimeMode::LowLatency(config) => config,
+        };
+        println!("Runtime mode: low-latency (cores {:?}, queue capacity {})", config.cores, config.queue_capacity);
+        let queue: HotQueue<(InboundOrder, NetworkPath, Option<u64>)> = HotQueue::new(config.queue_capacity);
+        let submit_queue = queue.clone();
+        spawn_pinned("order-submit", config.core(0), move || {
+            // Everything that has arrived, up to the queue capacity, is taken
+            // off the queue before each write, so the write goes to the
+            // highest class waiting.
+            let mut pending = OrderQueue::default();
+            loop {
+                while pending.len() < config.queue_capacity {
+                    let Some(item) = submit_queue.pop() else { break };
+                    pending.push(item.0.priority, item);
+                }
+                match pending.pop() {
+                    Some((order, path, received_ns)) => {
+                        venue.send(&order, path);
+                        record_send_time(&budget, received_ns);
+                    }
+                    None => std::hint::spin_loop(),
+                }
+            }
+        });
+        WireSender::Pinned(queue)
+    }
 
-// --- NEW: Structures for Latency Oracle ---
-#[derive(Debug, Deserialize, Copy, Clone)]
-enum NetworkPath {
-    Microwave,
-    Fiber,
+    /// Writes the order to the venue. With `received_ns`, the monotonic time
+    /// the gateway received it, the send time counts toward the budget.
+    fn send(&self, order: &InboundOrder, path: NetworkPath, received_ns: Option<u64>) {
+        match self {
+            WireSender::Inline(venue, budget) => {
+                venue.send(order, path);
+                record_send_time(budget, received_ns);
+            }
+            WireSender::Pinned(queue) => {
+                // Orders are never dropped: spin until the submit thread makes room.
+                let mut item = (order.clone(), path, received_ns);
+                while let Err(rejected) = queue.push(item) {
+                    item = rejected;
+                    std::hint::spin_loop();
+                }
+            }
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct OracleResponse {
-    path: NetworkPath,
-    latency_us: u32,
+fn record_send_time(budget: &SharedBudget, received_ns: Option<u64>) {
+    if let Some(received_ns) = received_ns {
+        budget.lock().unwrap().record(monotonic_ns().saturating_sub(received_ns), Instant::now());
+    }
 }
 
-const LATENCY_ORACLE_URL: &str = "http://latency-oracle.default.svc.cluster.local/fastest-path";
-
-
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Exchange Gateway (Oracle Integrated) ---");
 
-    let mut open_orders: HashMap<Uuid, InboundOrder> = HashMap::new();
+    let trading_mode = TradingMode::from_env();
+    let seed = Seed::from_args();
+    let venue = venue::connect(trading_mode, &seed);
+    println!("Trading mode: {} (venue: {})", trading_mode, venue.name());
+    println!("Simulation: {}", seed.describe());
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+
+    let sequencing = SequencerConfig::from_env();
+    println!("Execution report sequencing: {:?}", sequencing);
+    let hold = sequencing.hold;
+    let bus_encoding = Encoding::from_env();
+    let mut book = OrderBook::new(sequencing);
+    let journal =
+        Journal::open(JournalConfig::from_env(), bus_encoding, &mut book).expect("Failed to open the order journal");
+    let open_orders: SharedOrderBook = Arc::new(Mutex::new(book));
     let http_client = reqwest::Client::new();
+    let budget_config = SendBudgetConfig::from_env();
+    println!("Send-time budget: {:?}", budget_config);
+    let budget: SharedBudget = Arc::new(Mutex::new(SendBudget::new(budget_config)));
+    let wire_sender = WireSender::from_mode(RuntimeMode::from_env(), venue.clone(), budget.clone());
+    let latency: SharedLatency = Arc::new(Mutex::new(LatencyRecorder::new()));
+    let legging_config = LeggingConfig::from_env();
+    println!("Multi-leg execution: {:?}", legging_config);
+    let rate_limit_config = RateLimitConfig::from_env();
+    println!("Venue rate limits: {:?}", rate_limit_config);
+    let rate_limits: SharedRateLimits = Arc::new(Mutex::new(RateLimits::new(rate_limit_config)));
+    let routing_config = RoutingConfig::from_env();
+    println!("Path routing: {:?}", routing_config);
+    let router: SharedRouter = Arc::new(Mutex::new(Router::new(routing_config)));
+
+    // Spawn the publisher that drains the order journal's outbox
+    let (journal_clone, open_orders_clone) = (journal.clone(), open_orders.clone());
+    tokio::spawn(async move {
+        publish_from_outbox(journal_clone, open_orders_clone).await;
+    });
+
+    // Spawn the task that releases reports held too long for a gap
+    let (journal_clone, open_orders_clone) = (journal.clone(), open_orders.clone());
+    tokio::spawn(async move {
+        release_held_reports(open_orders_clone, hold, journal_clone).await;
+    });
+
+    // Spawn the task that applies reports the venue streams on its own
+    let (venue_clone, open_orders_clone, journal_clone) = (venue.clone(), open_orders.clone(), journal.clone());
+    tokio::spawn(async move {
+        apply_streamed_reports(venue_clone, open_orders_clone, journal_clone).await;
+    });
+
+    // Spawn the venue session supervisor
+    let supervisor_config = SupervisorConfig::from_env();
+    println!("Venue sessions: {:?}", supervisor_config);
+    let supervisor: SharedSupervisor = Arc::new(Mutex::new(Supervisor::new(supervisor_config, venue.sessions())));
+    let (supervisor_clone, venue_clone, open_orders_clone) = (supervisor.clone(), venue.clone(), open_orders.clone());
+    let (journal_clone, rate_limits_clone) = (journal.clone(), rate_limits.clone());
+    tokio::spawn(async move {
+        supervise_venues(supervisor_clone, venue_clone, open_orders_clone, journal_clone, rate_limits_clone).await;
+    });
 
-    println!("Simulating connection to 'CME Group' exchange...");
+    // Spawn the poller of the latency oracle's path scores
+    let router_clone = router.clone();
+    tokio::spawn(async move {
+        poll_path_scores(http_client, router_clone).await;
+    });
 
+    // Spawn the send-time budget monitor
+    let budget_clone = budget.clone();
+    tokio::spawn(async move {
+        monitor_send_budget(budget_clone).await;
+    });
+
+    // A desk reads its own strategies' open orders; the rest of the API is firm-wide.
+    let firm = quantumarb_tenancy::firm_only(desks.clone());
+
+    // --- API Endpoints: GET /latency -> hop-by-hop tick-to-trade histograms,
+    // GET /latency/budget -> the send-time budget ---
+    let budget_route = warp::path!("latency" / "budget")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(budget.clone()))
+        .and_then(handler_get_budget);
+    let latency_route = warp::path("latency")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(latency.clone()))
+        .and_then(handler_get_latency);
+
+    // --- API Endpoints: GET/PUT /orders/open -> export/restore the open order book ---
+    let get_open_orders = warp::path!("orders" / "open")
+        .and(warp::get())
+        .and(with_state(open_orders.clone()))
+        .and(quantumarb_tenancy::scope(desks.clone()))
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_open_orders);
+    let put_open_orders = warp::path!("orders" / "open")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(open_orders.clone()))
+        .and(with_state(journal.clone()))
+        .and_then(handler_put_open_orders);
+    let get_outbox = warp::path!("orders" / "outbox")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(journal.clone()))
+        .and_then(handler_get_outbox);
+    let get_violations = warp::path!("orders" / "violations")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(open_orders.clone()))
+        .and_then(handler_get_violations);
+    let get_sequencing = warp::path!("orders" / "sequencing")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(open_orders.clone()))
+        .and_then(handler_get_sequencing);
+    let get_venues = warp::path!("venues")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(supervisor))
+        .and_then(handler_get_venues);
+    let get_rate_limits = warp::path!("venues" / "rate-limits")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(rate_limits.clone()))
+        .and_then(handler_get_rate_limits);
+    let get_routing = warp::path!("routing")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(router.clone()))
+        .and_then(handler_get_routing);
+
+    // --- API Endpoints: POST /orders/cancel and /orders/flatten -> watchdog actions ---
+    let emergency = EmergencyContext {
+        venue: venue.clone(),
+        open_orders: open_orders.clone(),
+        journal: journal.clone(),
+        mode: trading_mode,
+        rate_limits: rate_limits.clone(),
+        router: router.clone(),
+    };
+    let cancel_orders = warp::path!("orders" / "cancel")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(emergency.clone()))
+        .and_then(handler_cancel_orders);
+    let amend_order = warp::path!("orders" / "amend")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(emergency.clone()))
+        .and_then(handler_amend_order);
+    let flatten_orders = warp::path!("orders" / "flatten")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(emergency))
+        .and_then(handler_flatten_orders);
+
+    println!(
+        "API server running at http://127.0.0.1:3036/latency[/budget], /venues[/rate-limits], /routing and /orders/{{open,violations,sequencing,outbox,cancel,amend,flatten}}"
+    );
+    let routes = budget_route
+        .or(latency_route)
+        .or(get_venues)
+        .or(get_rate_limits)
+        .or(get_routing)
+        .or(get_open_orders)
+        .or(put_open_orders)
+        .or(get_violations)
+        .or(get_sequencing)
+        .or(get_outbox)
+        .or(cancel_orders)
+        .or(amend_order)
+        .or(flatten_orders)
+        .or(quantumarb_openapi::routes(api_doc()))
+        .recover(quantumarb_tenancy::recover);
+    tokio::spawn(warp::serve(routes).run(([127, 0, 0, 1], 3036)));
+
+    let mut rng = seed.stream("exchange_gateway.orders");
     let mut interval = time::interval(Duration::from_secs(4));
+    let mut tick: u64 = 0;
     loop {
         interval.tick().await;
+        tick += 1;
 
-        let inbound_order = generate_simulated_inbound_order();
+        if tick.is_multiple_of(3) {
+            let package = generate_simulated_inbound_package(trading_mode, &mut rng);
+            let received_ns = monotonic_ns();
+            println!("\nReceived Inbound Package: ID {} ({} legs)", package.package_id, package.legs.len());
+            if package.mode != trading_mode {
+                for leg in &package.legs {
+                    publish_report_to_internal_bus(&mode_mismatch_report(leg, trading_mode), &journal);
+                }
+                continue;
+            }
+            let strategy = package.legs.first().and_then(|leg| leg.strategy.as_deref());
+            let priority = package.legs.first().map_or(OrderPriority::default(), |leg| leg.priority);
+            if let Err(rejection) = budget.lock().unwrap().admit(strategy, priority) {
+                println!("  -> Shed: {}", rejection.message);
+                for leg in &package.legs {
+                    let report = rejected_report(leg, rejection.clone(), trading_mode);
+                    publish_report_to_internal_bus(&report, &journal);
+                }
+                continue;
+            }
+
+            // The legs are worked without a pause, so they take their rate
+            // budget up front.
+            for leg in &package.legs {
+                throttle::acquire(&rate_limits, venue.order_venue(leg), RequestKind::Order).await;
+            }
+            // The package's send time runs to its first leg's write.
+            let first_send = Cell::new(Some(received_ns));
+            let sent = RefCell::new(Vec::new());
+            let send = |order: &InboundOrder| {
+                sent.borrow_mut().push(venue.order_venue(order));
+                let path = router.lock().unwrap().route(order.strategy.as_deref(), order.priority, Instant::now());
+                wire_sender.send(order, path, first_send.take())
+            };
+            let execution = legging::execute(&package, venue.as_ref(), &legging_config, &send);
+            charge_package_requests(&rate_limits, venue.as_ref(), &package, &execution, sent.into_inner());
+            let mut seen = HashSet::new();
+            for (order, report) in execution.orders {
+                println!("  -> Received Execution Report: Status {:?}", report.status);
+                let mut book = open_orders.lock().unwrap();
+                // Tick-to-trade runs to each order's first report.
+                if seen.insert(order.internal_order_id) {
+                    book.track(&order);
+                    journal.sent(&order);
+                    latency.lock().unwrap().record(&report.stamps);
+                }
+                receive_execution_report(&mut book, report, &journal);
+            }
+            publish_package_report(&execution.report, &journal);
+            continue;
+        }
+
+        let mut inbound_order = generate_simulated_inbound_order(trading_mode, &mut rng);
+        let received_ns = monotonic_ns();
         let order_id = inbound_order.internal_order_id;
         println!("\nReceived Inbound Order: ID {}", order_id);
 
-        // NEW: Query the latency oracle to get the fastest path
-        let fastest_path = get_fastest_path(&http_client).await.unwrap_or(NetworkPath::Fiber); // Default to Fiber on error
+        // A sandbox order must never reach a live venue, nor the reverse.
+        if inbound_order.mode != trading_mode {
+            let report = mode_mismatch_report(&inbound_order, trading_mode);
+            println!("  -> Refused: {}", report.reject.as_ref().unwrap());
+            publish_report_to_internal_bus(&report, &journal);
+            continue;
+        }
+        let admitted = budget.lock().unwrap().admit(inbound_order.strategy.as_deref(), inbound_order.priority);
+        if let Err(rejection) = admitted {
+            println!("  -> Shed: {}", rejection.message);
+            publish_report_to_internal_bus(&rejected_report(&inbound_order, rejection, trading_mode), &journal);
+            continue;
+        }
+
+        // Send the order to the "exchange" once the venue's rate limit
+        // allows, via the path its routing policy chooses
+        throttle::acquire(&rate_limits, venue.order_venue(&inbound_order), RequestKind::Order).await;
+        let (strategy, priority) = (inbound_order.strategy.as_deref(), inbound_order.priority);
+        let path = router.lock().unwrap().route(strategy, priority, Instant::now());
+        inbound_order.stamps.stamp(Hop::GatewaySend);
+        wire_sender.send(&inbound_order, path, Some(received_ns));
+        let exec_reports = venue.execution_reports(&inbound_order);
+        let mut book = open_orders.lock().unwrap();
+        book.track(&inbound_order);
+        journal.sent(&inbound_order);
+
+        for (index, mut exec_report) in exec_reports.into_iter().enumerate() {
+            exec_report.stamps.stamp(Hop::ExecutionReceive);
+            println!("  -> Received Execution Report: Status {:?}", exec_report.status);
+            if index == 0 {
+                latency.lock().unwrap().record(&exec_report.stamps);
+            }
+            receive_execution_report(&mut book, exec_report, &journal);
+        }
+    }
+}
+
+/// The OpenAPI document of the gateway's operator endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new(
+        "Exchange Gateway",
+        "Latency, venue health, rate limits and the open order book of the exchange gateway.",
+    )
+    .errors::<ErrorBody>()
+    .get::<quantumarb_latency::LatencySummary>("/latency", "Hop-by-hop tick-to-trade histograms")
+    .get::<budget::BudgetStatus>("/latency/budget", "The send-time budget")
+    .get::<Vec<supervisor::VenueStatus>>("/venues", "Venue connectivity")
+    .get::<Vec<throttle::VenueRateStatus>>("/venues/rate-limits", "Each venue's remaining request budget")
+    .get::<routing::RoutingStatus>("/routing", "Path scores, costs and the paths each strategy's orders took")
+    .get::<Vec<OpenOrder>>("/orders/open", "Export the open order book")
+    .put::<Vec<OpenOrder>, Value>("/orders/open", "Restore the open order book")
+    .get::<Vec<LifecycleViolation>>("/orders/violations", "Execution reports that broke the order lifecycle")
+    .get::<sequencer::SequencerStats>("/orders/sequencing", "Execution report sequencing statistics")
+    .get::<quantumarb_outbox::OutboxStats>("/orders/outbox", "Outbox statistics")
+    .post::<CancelRequest, Value>("/orders/cancel", "Cancel open orders")
+    .post::<AmendRequest, Option<OpenOrder>>("/orders/amend", "Change an open order's quantity")
+    .post::<FlattenRequest, Value>("/orders/flatten", "Send closing orders")
+}
 
-        // Send the order to the "exchange" via the selected path
-        send_order_to_exchange(&inbound_order, fastest_path);
-        open_orders.insert(order_id, inbound_order);
+/// Warp filter to inject the shared state into the handler.
+fn with_state<T: Clone + Send>(state: T) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
 
-        let exec_report = generate_simulated_execution_report(order_id);
-        println!("  -> Received Execution Report: Status {:?}", exec_report.status);
+/// Handler for GET /latency.
+async fn handler_get_latency(latency: SharedLatency) -> Result<impl warp::Reply, warp::Rejection> {
+    let summary = latency.lock().unwrap().summary();
+    Ok(warp::reply::json(&summary))
+}
+
+/// Handler for GET /latency/budget.
+async fn handler_get_budget(budget: SharedBudget) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = budget.lock().unwrap().status(Instant::now());
+    Ok(warp::reply::json(&status))
+}
 
-        process_execution_report(&mut open_orders, &exec_report);
-        publish_report_to_internal_bus(&exec_report);
+/// Handler for GET /orders/open (unordered); a desk's own strategies', for a
+/// desk caller.
+async fn handler_get_open_orders(
+    open_orders: SharedOrderBook,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let orders: Vec<OpenOrder> = open_orders
+        .lock()
+        .unwrap()
+        .open_orders()
+        .filter(|open| scope.sees_desk(open.order.strategy.as_deref().and_then(|s| desks.desk_of_strategy(s))))
+        .cloned()
+        .collect();
+    Ok(warp::reply::json(&orders))
+}
+
+/// Handler for PUT /orders/open: replaces the open order book. Orders saved
+/// without their state restore as sent and unfilled.
+async fn handler_put_open_orders(
+    orders: Vec<OpenOrder>,
+    open_orders: SharedOrderBook,
+    journal: Journal,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut book = open_orders.lock().unwrap();
+    println!("\nRestoring open order book: {} orders replace {}.", orders.len(), book.len());
+    journal.restored(&orders);
+    book.restore(orders);
+    Ok(warp::reply::json(&json!({ "open_orders": book.len() })))
+}
+
+/// Handler for GET /orders/outbox: the order journal and what is left to publish.
+async fn handler_get_outbox(journal: Journal) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&journal.stats()))
+}
+
+/// Handler for GET /orders/violations: impossible report sequences, oldest first.
+async fn handler_get_violations(open_orders: SharedOrderBook) -> Result<impl warp::Reply, warp::Rejection> {
+    let violations = open_orders.lock().unwrap().violations();
+    Ok(warp::reply::json(&violations))
+}
+
+/// Handler for GET /orders/sequencing: duplicates dropped and reports reordered.
+async fn handler_get_sequencing(open_orders: SharedOrderBook) -> Result<impl warp::Reply, warp::Rejection> {
+    let stats = open_orders.lock().unwrap().sequencing();
+    Ok(warp::reply::json(&stats))
+}
+
+/// Handler for GET /venues: each venue's state and the session in use.
+async fn handler_get_venues(supervisor: SharedSupervisor) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = supervisor.lock().unwrap().status();
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /venues/rate-limits: each venue's remaining request budget.
+async fn handler_get_rate_limits(rate_limits: SharedRateLimits) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = rate_limits.lock().unwrap().status(Instant::now());
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /routing: the paths orders took and what they cost.
+async fn handler_get_routing(router: SharedRouter) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = router.lock().unwrap().status(Instant::now());
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for POST /orders/cancel: cancels the open orders in the given symbols.
+async fn handler_cancel_orders(
+    request: CancelRequest,
+    ctx: EmergencyContext,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    println!("\nCANCEL {:?} ({})", request.symbols, request.reason);
+    let targets: Vec<InboundOrder> = ctx
+        .open_orders
+        .lock()
+        .unwrap()
+        .open_orders()
+        .map(|open| &open.order)
+        .filter(|order| request.symbols.is_empty() || request.symbols.contains(&order.instrument_symbol))
+        .cloned()
+        .collect();
+    for order in &targets {
+        throttle::acquire(&ctx.rate_limits, ctx.venue.order_venue(order), RequestKind::Cancel).await;
+        let report = ctx.venue.cancel(order);
+        receive_execution_report(&mut ctx.open_orders.lock().unwrap(), report, &ctx.journal);
+    }
+    Ok(warp::reply::json(&json!({ "canceled": targets.len() })))
+}
+
+/// Handler for POST /orders/amend: changes a working order's quantity. The
+/// new quantity must leave something to fill.
+async fn handler_amend_order(
+    request: AmendRequest,
+    ctx: EmergencyContext,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let open = ctx.open_orders.lock().unwrap().get(&request.internal_order_id).cloned();
+    let Some(open) = open else {
+        let rejection = Rejection::new(
+            RejectCode::SystemConflict,
+            format!("Order {} is not open.", request.internal_order_id),
+        );
+        return Ok(error_reply(rejection));
+    };
+    if request.size <= open.cumulative_size {
+        let rejection = Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            format!("{} of order {} has already filled; cancel it instead.", open.cumulative_size, request.internal_order_id),
+        );
+        return Ok(error_reply(rejection));
+    }
+    println!("\nAMEND {} from {} to {}", request.internal_order_id, open.order.size, request.size);
+    throttle::acquire(&ctx.rate_limits, ctx.venue.order_venue(&open.order), RequestKind::Order).await;
+    let report = ctx.venue.amend(&open.order, request.size, open.cumulative_size);
+    let mut book = ctx.open_orders.lock().unwrap();
+    receive_execution_report(&mut book, report, &ctx.journal);
+    let amended = book.get(&request.internal_order_id);
+    Ok(warp::reply::with_status(warp::reply::json(&amended), warp::http::StatusCode::OK))
+}
+
+/// Builds a standard `{ "error": { code, category, message } }` response.
+fn error_reply(rejection: Rejection) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+/// Handler for POST /orders/flatten: sends the offsetting orders.
+async fn handler_flatten_orders(
+    request: FlattenRequest,
+    ctx: EmergencyContext,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    println!("\nFLATTEN {} position(s) ({})", request.orders.len(), request.reason);
+    let mut filled = 0;
+    for flatten in &request.orders {
+        let mut order = InboundOrder {
+            internal_order_id: Uuid::new_v4(),
+            instrument_symbol: flatten.instrument_symbol.clone(),
+            price: flatten.price,
+            size: flatten.size,
+            side: flatten.side,
+            stamps: HopStamps::default(),
+            mode: ctx.mode,
+            strategy: None,
+            priority: OrderPriority::Hedge,
+        };
+        throttle::acquire(&ctx.rate_limits, ctx.venue.order_venue(&order), RequestKind::Order).await;
+        let path = ctx.router.lock().unwrap().route(None, order.priority, Instant::now());
+        order.stamps.stamp(Hop::GatewaySend);
+        ctx.venue.send(&order, path);
+        let reports = ctx.venue.execution_reports(&order);
+        let mut book = ctx.open_orders.lock().unwrap();
+        book.track(&order);
+        ctx.journal.sent(&order);
+        for mut report in reports {
+            report.stamps.stamp(Hop::ExecutionReceive);
+            let applied = receive_execution_report(&mut book, report, &ctx.journal);
+            filled += applied.iter().filter(|report| report.status == OrderStatus::Filled).count();
+        }
+    }
+    Ok(warp::reply::json(&json!({ "sent": request.orders.len(), "filled": filled })))
+}
+
+/// Every heartbeat interval, probes each venue's active session and acts on
+/// the outcome: replays resent execution reports and publishes state changes.
+async fn supervise_venues(
+    supervisor: SharedSupervisor,
+    venue: Arc<dyn VenueAdapter>,
+    open_orders: SharedOrderBook,
+    journal: Journal,
+    rate_limits: SharedRateLimits,
+) {
+    let mut interval = time::interval(supervisor.lock().unwrap().heartbeat_interval());
+    loop {
+        interval.tick().await;
+        let probes = supervisor.lock().unwrap().probes();
+        for probe in probes {
+            let (name, actions) = match probe {
+                Probe::Heartbeat { venue: name, session } => {
+                    throttle::acquire(&rate_limits, &name, RequestKind::Query).await;
+                    let answer = venue.heartbeat(&session);
+                    let actions = supervisor.lock().unwrap().on_heartbeat(&name, answer);
+                    (name, actions)
+                }
+                Probe::Logon { venue: name, session } => {
+                    throttle::acquire(&rate_limits, &name, RequestKind::Query).await;
+                    let answer = venue.logon(&session);
+                    let actions = supervisor.lock().unwrap().on_logon(&name, answer);
+                    (name, actions)
+                }
+            };
+            for action in actions {
+                match action {
+                    Action::Resend { session, from, to } => {
+                        println!("\nResynchronizing {}: messages {}..={} were missed.", session, from, to);
+                        throttle::acquire(&rate_limits, &name, RequestKind::Query).await;
+                        let reports = venue.resend(&session, from, to);
+                        let mut book = open_orders.lock().unwrap();
+                        for report in reports {
+                            receive_execution_report(&mut book, report, &journal);
+                        }
+                    }
+                    Action::Publish(event) => {
+                        println!("\nVENUE {} {:?} on {}: {}", event.venue, event.state, event.session, event.detail);
+                        quantumarb_bus::publish_json(topics::VENUE_CONNECTIVITY, &event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Every hold interval, applies and publishes the reports the sequencer
+/// gave up holding for a gap.
+async fn release_held_reports(open_orders: SharedOrderBook, hold: Duration, journal: Journal) {
+    let mut interval = time::interval(hold);
+    loop {
+        interval.tick().await;
+        let mut book = open_orders.lock().unwrap();
+        let applied = book.release_expired(Instant::now());
+        for (report, _) in &applied {
+            println!("\nGave up waiting for the reports before {} on order {}.", report.exec_id, report.internal_order_id);
+        }
+        publish_applied(applied, &journal);
+    }
+}
+
+/// Applies the reports a venue delivers apart from its answers to our
+/// requests (fills on a user-data stream).
+async fn apply_streamed_reports(venue: Arc<dyn VenueAdapter>, open_orders: SharedOrderBook, journal: Journal) {
+    let mut interval = time::interval(Duration::from_millis(50));
+    loop {
+        interval.tick().await;
+        for mut report in venue.streamed_reports() {
+            report.stamps.stamp(Hop::ExecutionReceive);
+            println!("\nStreamed Execution Report for {}: Status {:?}", report.internal_order_id, report.status);
+            receive_execution_report(&mut open_orders.lock().unwrap(), report, &journal);
+        }
+    }
+}
+
+/// Counts the requests a package made beyond the budget its legs took up
+/// front: retries, hedges and cancels of partly filled legs.
+fn charge_package_requests(
+    rate_limits: &SharedRateLimits,
+    venue: &dyn VenueAdapter,
+    package: &InboundPackage,
+    execution: &legging::Execution,
+    mut sent: Vec<&'static str>,
+) {
+    for leg in &package.legs {
+        let paid = venue.order_venue(leg);
+        if let Some(index) = sent.iter().position(|venue| *venue == paid) {
+            sent.swap_remove(index);
+        }
+    }
+    let canceled = execution.report.legs.iter().filter(|outcome| !outcome.filled && outcome.filled_size > 0);
+    let now = Instant::now();
+    let mut limits = rate_limits.lock().unwrap();
+    for venue_name in sent {
+        limits.record(venue_name, now);
+    }
+    for outcome in canceled {
+        limits.record(venue.order_venue(&package.legs[outcome.leg - 1]), now);
+    }
+}
+
+/// Publishes what the order journal has committed, in commit order.
+async fn publish_from_outbox(journal: Journal, open_orders: SharedOrderBook) {
+    let mut interval = time::interval(journal.drain_interval());
+    loop {
+        interval.tick().await;
+        journal.drain(&open_orders);
     }
 }
 
-/// NEW: Function to get the fastest path from the Latency Oracle.
-async fn get_fastest_path(client: &reqwest::Client) -> Option<NetworkPath> {
-    println!("  -> Querying Latency Oracle for fastest path...");
-    match client.get(LATENCY_ORACLE_URL).send().await {
-        Ok(response) => match response.json::<OracleResponse>().await {
-            Ok(oracle_response) => {
-                println!("  -> Oracle recommends: {:?} ({}µs)", oracle_response.path, oracle_response.latency_us);
-                Some(oracle_response.path)
+/// Every second, reads the path scores from the latency oracle for the
+/// router. An unreachable oracle leaves the last scores to lapse.
+async fn poll_path_scores(client: reqwest::Client, router: SharedRouter) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut reachable = true;
+    loop {
+        interval.tick().await;
+        let paths = match client.get(LATENCY_ORACLE_URL).send().await {
+            Ok(response) => response.json::<Vec<routing::OraclePath>>().await.ok(),
+            Err(_) => None,
+        };
+        match paths {
+            Some(paths) => {
+                reachable = true;
+                router.lock().unwrap().update_scores(routing::scores(paths), Instant::now());
             }
-            Err(_) => {
-                println!("  -> Error parsing Oracle response.");
-                None
+            None if reachable => {
+                reachable = false;
+                println!("\nLatency Oracle unreachable; orders go by fiber once its scores lapse.");
             }
-        },
-        Err(_) => {
-            println!("  -> Failed to connect to Latency Oracle.");
-            None
+            None => {}
         }
     }
 }
 
-/// Simulates a new order arriving from the internal system.
-fn generate_simulated_inbound_order() -> InboundOrder {
+const SIMULATED_STRATEGIES: [&str; 3] = ["market_making", "stat_arb", "momentum"];
+
+/// Simulates a new order arriving from the internal system, with the upstream
+/// hops stamped as the strategy engine and risk gateway would have.
+fn generate_simulated_inbound_order(mode: TradingMode, rng: &mut SimRng) -> InboundOrder {
+    let now = monotonic_ns();
+    let mut stamps = HopStamps::default();
+    stamps.set(Hop::MarketDataReceive, now - 9_000 - rng.gen_range(0..4_000));
+    stamps.set(Hop::StrategyDecision, now - 6_000 - rng.gen_range(0..2_000));
+    stamps.set(Hop::RiskDecision, now - 2_000 - rng.gen_range(0..1_000));
+    let strategy = SIMULATED_STRATEGIES[rng.gen_range(0..SIMULATED_STRATEGIES.len())];
+    let priority = match strategy {
+        _ if rng.gen_bool(0.1) => OrderPriority::Hedge,
+        "stat_arb" => OrderPriority::Arbitrage,
+        _ => OrderPriority::Opportunistic,
+    };
     InboundOrder {
-        internal_order_id: Uuid::new_v4(),
+        internal_order_id: quantumarb_sim::uuid(rng),
         instrument_symbol: "ESZ25".to_string(),
         price: 4500_25,
         size: 10,
         side: OrderSide::Buy,
+        stamps,
+        mode,
+        strategy: Some(strategy.to_string()),
+        priority,
     }
 }
 
-/// Simulates sending the order, now with path selection.
-fn send_order_to_exchange(order: &InboundOrder, path: NetworkPath) {
-    println!(
-        "  -> Sending order via [{:?}] path: Symbol {}, Size {}",
-        path, order.instrument_symbol, order.size
+/// Simulates an approved calendar spread: buy the front ES contract, sell the
+/// next one.
+fn generate_simulated_inbound_package(mode: TradingMode, rng: &mut SimRng) -> InboundPackage {
+    let front = generate_simulated_inbound_order(mode, rng);
+    let back = InboundOrder {
+        internal_order_id: quantumarb_sim::uuid(rng),
+        instrument_symbol: "ESH26".to_string(),
+        price: 4538_50,
+        side: OrderSide::Sell,
+        ..front.clone()
+    };
+    InboundPackage { package_id: quantumarb_sim::uuid(rng), legs: vec![front, back], mode }
+}
+
+/// The report for an order stamped with the other trading mode; it never
+/// reaches a venue.
+fn mode_mismatch_report(order: &InboundOrder, mode: TradingMode) -> ExecutionReport {
+    let rejection = Rejection::new(
+        RejectCode::SystemModeMismatch,
+        format!("{} order sent to a {} exchange gateway", order.mode, mode),
     );
+    rejected_report(order, rejection, mode)
+}
+
+/// The report for an order the gateway refused without sending it.
+fn rejected_report(order: &InboundOrder, rejection: Rejection, mode: TradingMode) -> ExecutionReport {
+    let mut report = venue::report_without_fill(order, OrderStatus::RejectedByExchange, mode);
+    report.reject = Some(rejection);
+    report
 }
 
-/// Simulates an execution report coming back from the exchange.
-fn generate_simulated_execution_report(internal_id: Uuid) -> ExecutionReport {
-    ExecutionReport {
-        exchange_order_id: format!("EXCH-{}", Uuid::new_v4().to_simple()),
-        internal_order_id: internal_id,
-        status: OrderStatus::Filled,
-        filled_size: 10,
-        filled_price: 4500_25,
+/// Every second, checks the send-time budget and publishes any change of state.
+async fn monitor_send_budget(budget: SharedBudget) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let alert = budget.lock().unwrap().evaluate(Instant::now());
+        if let Some(alert) = alert {
+            println!(
+                "\nSend-time budget {:?}: p99 {:.1}µs against {:.1}µs",
+                alert.event, alert.p99_us, alert.budget_us
+            );
+            quantumarb_bus::publish_json(SEND_BUDGET_ALERTS_TOPIC, &alert);
+        }
     }
 }
 
-/// Updates the local state based on the execution report.
-fn process_execution_report(
-    open_orders: &mut HashMap<Uuid, InboundOrder>,
-    report: &ExecutionReport,
-) {
-    if report.status == OrderStatus::Filled || report.status == OrderStatus::Canceled {
-        if open_orders.remove(&report.internal_order_id).is_some() {
-            println!("  -> Order {} is now closed.", report.internal_order_id);
+/// Passes the venue's report to the order book, which drops duplicates and
+/// holds reports that arrive ahead of a gap, then publishes the reports it
+/// applied. Returns them.
+fn receive_execution_report(book: &mut OrderBook, report: ExecutionReport, journal: &Journal) -> Vec<ExecutionReport> {
+    let exec_id = report.exec_id.clone();
+    let applied = book.receive(report, Instant::now());
+    if applied.is_empty() {
+        println!("  -> Report {} is a duplicate or is held for the reports before it.", exec_id);
+    }
+    publish_applied(applied, journal)
+}
+
+/// Logs the reports the order book applied and journals them, in order, for
+/// publishing.
+fn publish_applied(applied: Vec<Applied>, journal: &Journal) -> Vec<ExecutionReport> {
+    applied
+        .into_iter()
+        .map(|(report, result)| {
+            log_execution_report(&report, result);
+            log_report_json(&report);
+            journal.report(&report);
+            report
+        })
+        .collect()
+}
+
+/// Logs how the execution report moved its order on, and publishes the
+/// violation if it could not follow from the order's state.
+fn log_execution_report(report: &ExecutionReport, result: Result<Option<OpenOrder>, LifecycleViolation>) {
+    if let Some(reject) = &report.reject {
+        println!("  -> Order {} rejected by venue: {}", report.internal_order_id, reject);
+    }
+    match result {
+        Ok(None) => println!(
+            "  -> Fill {} on closed order {}: {:?}.",
+            report.exec_ref_id, report.internal_order_id, report.status
+        ),
+        Ok(Some(open)) => match (report.status, open.status) {
+            (OrderStatus::Replaced, _) => {
+                println!("  -> Order {} amended to {}.", report.internal_order_id, open.order.size)
+            }
+            (OrderStatus::TradeBusted | OrderStatus::TradeCorrected, _) => println!(
+                "  -> Fill {} on order {} {}; {} of {} now filled.",
+                report.exec_ref_id,
+                report.internal_order_id,
+                if report.status == OrderStatus::TradeBusted { "busted" } else { "corrected" },
+                open.cumulative_size,
+                open.order.size
+            ),
+            (_, OrderStatus::PartiallyFilled) => println!(
+                "  -> Order {} filled {} of {}.",
+                report.internal_order_id, open.cumulative_size, open.order.size
+            ),
+            (_, OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::RejectedByExchange) => {
+                println!("  -> Order {} is now closed.", report.internal_order_id)
+            }
+            _ => {}
+        },
+        Err(violation) => {
+            println!("  -> IMPOSSIBLE SEQUENCE on order {}: {}", violation.internal_order_id, violation.detail);
+            quantumarb_bus::publish_json(lifecycle::VIOLATIONS_TOPIC, &violation);
         }
     }
 }
 
-/// Publishes the execution report to an internal topic for other services.
-fn publish_report_to_internal_bus(report: &ExecutionReport) {
+/// Publishes a report for an order the gateway refused, through the journal.
+fn publish_report_to_internal_bus(report: &ExecutionReport, journal: &Journal) {
+    log_report_json(report);
+    journal.refused(report);
+}
+
+fn log_report_json(report: &ExecutionReport) {
     let report_json = serde_json::to_string_pretty(report).unwrap();
-    println!(
-        "  -> Publishing to topic 'execution_reports':\n{}",
-        report_json
-    );
+    println!("  -> Execution report for topic 'execution_reports':\n{}", report_json);
+}
+
+/// Publishes a package summary for the portfolio manager and operators,
+/// through the journal.
+fn publish_package_report(report: &PackageReport, journal: &Journal) {
+    println!("  -> Package {} {:?}: {}", report.package_id, report.status, report.detail);
+    journal.package(report);
 }
@@ -0,0 +1,385 @@
+/*
+ * QuantumArb 2.0 - Core Services: Path Routing Policy
+ *
+ * File: src/core_services/exchange_gateway/routing.rs
+ *
+ * Description:
+ * Chooses the network path of each order. The fastest path is not always
+ * the best one: microwave bandwidth is scarce and every message on it costs
+ * more than on fiber, while most orders gain little from the microseconds it
+ * saves. Each path is weighed on the latency oracle's score (microseconds,
+ * with jitter, loss and forecast rain priced in; see the oracle's
+ * `scoring.rs`) against its cost per message, under the policy of the
+ * order's strategy:
+ *
+ *   fastest       the best score, whatever it costs
+ *   cheapest      the lowest cost per message, the score breaking ties
+ *   weighted:V    the lowest cost + V x class weight x score, V being what a
+ *                 microsecond is worth to the strategy, in USD per message
+ *
+ * The class weight scales V by the order's priority class: 1 for
+ * opportunistic orders, QA_ROUTING_ARBITRAGE_WEIGHT for arbitrage legs.
+ * Hedge orders always take the fastest path.
+ *
+ * A path is only taken if its bandwidth budget admits the order (see
+ * `bandwidth.rs`); otherwise the order spills to the next path its policy
+ * ranks. A path the oracle has no score for (not answering probes) is not
+ * chosen, and with no scores at all, or scores older than
+ * QA_ROUTING_STALE_SECS, orders go by fiber.
+ *
+ * The scores are polled from the oracle's GET /paths in the background, so
+ * no order waits on it. GET /routing shows the scores, the costs, the
+ * microwave bandwidth in use and the paths each strategy's orders took.
+ *
+ * Configuration (environment):
+ *   QA_PATH_COST_MICROWAVE=0.02        USD per message on microwave
+ *   QA_PATH_COST_FIBER=0.0005          USD per message on fiber
+ *   QA_ROUTING_POLICIES                "market_making=fastest;stat_arb=weighted:0.0002;momentum=cheapest"
+ *   QA_ROUTING_DEFAULT_POLICY=weighted:0.0001
+ *                                      policy of unlisted strategies and of
+ *                                      orders carrying none
+ *   QA_ROUTING_ARBITRAGE_WEIGHT=4.0    class weight of arbitrage legs
+ *   QA_ROUTING_STALE_SECS=5            age at which the oracle's scores lapse
+ *   QA_MICROWAVE_*, QA_FIBER_*         path bandwidth, see `bandwidth.rs`
+ */
+
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::OrderPriority;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::bandwidth::{BandwidthAccountant, BandwidthConfig, PathBandwidth};
+use crate::venue::NetworkPath;
+
+const PATHS: [NetworkPath; 2] = [NetworkPath::Microwave, NetworkPath::Fiber];
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoutingPolicy {
+    Fastest,
+    Cheapest,
+    /// USD per message a microsecond of score is worth.
+    Weighted(f64),
+}
+
+impl RoutingPolicy {
+    /// Parses "fastest", "cheapest" or "weighted:0.0002".
+    pub fn parse(spec: &str) -> Option<RoutingPolicy> {
+        match spec.trim().split_once(':') {
+            None if spec.trim() == "fastest" => Some(RoutingPolicy::Fastest),
+            None if spec.trim() == "cheapest" => Some(RoutingPolicy::Cheapest),
+            Some(("weighted", value)) => {
+                value.trim().parse().ok().filter(|v: &f64| *v >= 0.0).map(RoutingPolicy::Weighted)
+            }
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            RoutingPolicy::Fastest => "fastest".to_string(),
+            RoutingPolicy::Cheapest => "cheapest".to_string(),
+            RoutingPolicy::Weighted(value) => format!("weighted:{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutingConfig {
+    pub microwave_cost: Money,
+    pub fiber_cost: Money,
+    pub bandwidth: BandwidthConfig,
+    pub policies: HashMap<String, RoutingPolicy>,
+    pub default_policy: RoutingPolicy,
+    pub arbitrage_weight: f64,
+    pub stale_after: Duration,
+}
+
+impl RoutingConfig {
+    pub fn from_env() -> RoutingConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let policies = std::env::var("QA_ROUTING_POLICIES")
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|entry| {
+                let (strategy, policy) = entry.split_once('=')?;
+                Some((strategy.trim().to_string(), RoutingPolicy::parse(policy)?))
+            })
+            .collect();
+        let default_policy = std::env::var("QA_ROUTING_DEFAULT_POLICY")
+            .ok()
+            .and_then(|spec| RoutingPolicy::parse(&spec))
+            .unwrap_or(RoutingPolicy::Weighted(0.0001));
+        RoutingConfig {
+            microwave_cost: Money::from_f64(var("QA_PATH_COST_MICROWAVE", 0.02)),
+            fiber_cost: Money::from_f64(var("QA_PATH_COST_FIBER", 0.0005)),
+            bandwidth: BandwidthConfig::from_env(),
+            policies,
+            default_policy,
+            arbitrage_weight: var("QA_ROUTING_ARBITRAGE_WEIGHT", 4.0),
+            stale_after: Duration::from_secs(var("QA_ROUTING_STALE_SECS", 5u64).max(1)),
+        }
+    }
+
+    pub fn policy(&self, strategy: Option<&str>) -> RoutingPolicy {
+        strategy.and_then(|strategy| self.policies.get(strategy)).copied().unwrap_or(self.default_policy)
+    }
+
+    fn cost(&self, path: NetworkPath) -> Money {
+        match path {
+            NetworkPath::Microwave => self.microwave_cost,
+            NetworkPath::Fiber => self.fiber_cost,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// One path on GET /routing.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct PathRouting {
+    pub path: NetworkPath,
+    /// The oracle's score, None if it has none or it has lapsed.
+    pub score_us: Option<f64>,
+    pub cost_per_message: Money,
+    pub messages: u64,
+    pub spend: Money,
+}
+
+/// The paths one strategy's orders took.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct StrategyRouting {
+    /// Empty for orders carrying no strategy.
+    pub strategy: String,
+    pub policy: String,
+    pub microwave: u64,
+    pub fiber: u64,
+}
+
+/// Body of GET /routing.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct RoutingStatus {
+    pub paths: Vec<PathRouting>,
+    pub bandwidth: Vec<PathBandwidth>,
+    pub strategies: Vec<StrategyRouting>,
+}
+
+/// Chooses each order's path and counts what went where.
+pub struct Router {
+    config: RoutingConfig,
+    /// The oracle's last scores and when they were read.
+    scores: HashMap<NetworkPath, f64>,
+    scored_at: Option<Instant>,
+    bandwidth: BandwidthAccountant,
+    /// Messages and spend per path.
+    totals: HashMap<NetworkPath, (u64, Money)>,
+    /// Microwave and fiber messages per strategy.
+    strategies: BTreeMap<String, (u64, u64)>,
+}
+
+impl Router {
+    pub fn new(config: RoutingConfig) -> Router {
+        Router {
+            bandwidth: BandwidthAccountant::new(config.bandwidth.clone(), Instant::now()),
+            config,
+            scores: HashMap::new(),
+            scored_at: None,
+            totals: HashMap::new(),
+            strategies: BTreeMap::new(),
+        }
+    }
+
+    /// Takes the oracle's scores; a path left out has none.
+    pub fn update_scores(&mut self, scores: HashMap<NetworkPath, f64>, now: Instant) {
+        self.scores = scores;
+        self.scored_at = Some(now);
+    }
+
+    fn live_scores(&self, now: Instant) -> Option<&HashMap<NetworkPath, f64>> {
+        let scored_at = self.scored_at?;
+        (now.duration_since(scored_at) <= self.config.stale_after).then_some(&self.scores)
+    }
+
+    /// The path for one message of an order from `strategy` in `priority`:
+    /// the first its policy ranks that has the bandwidth, charged to it.
+    pub fn route(&mut self, strategy: Option<&str>, priority: OrderPriority, now: Instant) -> NetworkPath {
+        let (policy, weight) = match priority {
+            OrderPriority::Hedge => (RoutingPolicy::Fastest, 1.0),
+            OrderPriority::Arbitrage => (self.config.policy(strategy), self.config.arbitrage_weight),
+            OrderPriority::Opportunistic => (self.config.policy(strategy), 1.0),
+        };
+        let mut ranked: Vec<(NetworkPath, (f64, f64))> = self
+            .live_scores(now)
+            .into_iter()
+            .flat_map(|scores| scores.iter())
+            .filter(|(_, score)| score.is_finite())
+            .map(|(path, score)| {
+                let cost = self.config.cost(*path).to_f64();
+                let key = match policy {
+                    RoutingPolicy::Fastest => (*score, cost),
+                    RoutingPolicy::Cheapest => (cost, *score),
+                    RoutingPolicy::Weighted(value_per_us) => (cost + value_per_us * weight * score, *score),
+                };
+                (*path, key)
+            })
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        let path = ranked
+            .into_iter()
+            .map(|(path, _)| path)
+            .find(|path| self.bandwidth.admits(*path, priority, now))
+            .unwrap_or(NetworkPath::Fiber);
+
+        self.bandwidth.take(path, now);
+        let total = self.totals.entry(path).or_insert((0, Money::ZERO));
+        total.0 += 1;
+        total.1 += self.config.cost(path);
+        let counts = self.strategies.entry(strategy.unwrap_or_default().to_string()).or_default();
+        match path {
+            NetworkPath::Microwave => counts.0 += 1,
+            NetworkPath::Fiber => counts.1 += 1,
+        }
+        path
+    }
+
+    pub fn status(&self, now: Instant) -> RoutingStatus {
+        let scores = self.live_scores(now);
+        let paths = PATHS
+            .iter()
+            .map(|path| {
+                let (messages, spend) = self.totals.get(path).copied().unwrap_or((0, Money::ZERO));
+                PathRouting {
+                    path: *path,
+                    score_us: scores.and_then(|scores| scores.get(path)).copied().filter(|s| s.is_finite()),
+                    cost_per_message: self.config.cost(*path),
+                    messages,
+                    spend,
+                }
+            })
+            .collect();
+        let strategies = self
+            .strategies
+            .iter()
+            .map(|(strategy, (microwave, fiber))| StrategyRouting {
+                strategy: strategy.clone(),
+                policy: self.config.policy(Some(strategy.as_str()).filter(|s| !s.is_empty())).describe(),
+                microwave: *microwave,
+                fiber: *fiber,
+            })
+            .collect();
+        RoutingStatus { paths, bandwidth: self.bandwidth.status(now), strategies }
+    }
+}
+
+// --- Oracle Scores ---
+
+/// The part of the oracle's GET /paths entries the router reads.
+#[derive(Debug, Deserialize)]
+pub struct OraclePath {
+    path: NetworkPath,
+    stats: Option<OracleStats>,
+    rain: Option<OracleRain>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OracleStats {
+    /// Null when every probe in the window was lost.
+    score: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OracleRain {
+    penalty_us: f64,
+}
+
+/// Each path's score, rain penalty included, as the oracle routes on it.
+pub fn scores(paths: Vec<OraclePath>) -> HashMap<NetworkPath, f64> {
+    paths
+        .into_iter()
+        .filter_map(|p| {
+            let score = p.stats?.score?;
+            Some((p.path, score + p.rain.map_or(0.0, |rain| rain.penalty_us)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bandwidth::BucketConfig;
+
+    fn config() -> RoutingConfig {
+        RoutingConfig {
+            microwave_cost: Money::from_f64(0.02),
+            fiber_cost: Money::from_f64(0.0005),
+            bandwidth: BandwidthConfig {
+                paths: HashMap::from([(
+                    NetworkPath::Microwave,
+                    BucketConfig { msgs_per_sec: 2.0, burst: 2.0, reserve: 0.0 },
+                )]),
+            },
+            policies: [
+                ("market_making", RoutingPolicy::Fastest),
+                ("momentum", RoutingPolicy::Cheapest),
+                ("stat_arb", RoutingPolicy::Weighted(0.0002)),
+            ]
+            .map(|(s, p)| (s.to_string(), p))
+            .into(),
+            default_policy: RoutingPolicy::Weighted(0.0001),
+            arbitrage_weight: 4.0,
+            stale_after: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn paths_are_chosen_on_policy_class_and_microwave_bandwidth() {
+        let mut router = Router::new(config());
+        let now = Instant::now();
+        // No scores yet: fiber.
+        assert_eq!(router.route(Some("market_making"), OrderPriority::Opportunistic, now), NetworkPath::Fiber);
+
+        // Microwave 60µs faster for 0.0195 more: worth it at 0.0002 x 4 per
+        // µs (0.048), not at 0.0002 (0.012).
+        let paths: Vec<OraclePath> = serde_json::from_str(
+            r#"[{"path":"Microwave","stats":{"score":40.0},"rain":null},
+                {"path":"Fiber","stats":{"score":100.0},"rain":null}]"#,
+        )
+        .unwrap();
+        router.update_scores(scores(paths), now);
+        assert_eq!(router.route(Some("stat_arb"), OrderPriority::Opportunistic, now), NetworkPath::Fiber);
+        assert_eq!(router.route(Some("stat_arb"), OrderPriority::Arbitrage, now), NetworkPath::Microwave);
+        assert_eq!(router.route(Some("momentum"), OrderPriority::Hedge, now), NetworkPath::Microwave);
+        assert_eq!(router.route(Some("momentum"), OrderPriority::Arbitrage, now), NetworkPath::Fiber);
+
+        // The microwave budget is spent for now, but not for hedges.
+        assert_eq!(router.route(Some("market_making"), OrderPriority::Opportunistic, now), NetworkPath::Fiber);
+        assert_eq!(router.route(None, OrderPriority::Hedge, now), NetworkPath::Microwave);
+        let later = now + Duration::from_secs(1);
+        assert_eq!(router.route(Some("market_making"), OrderPriority::Opportunistic, later), NetworkPath::Microwave);
+
+        // Forecast rain makes microwave the slower path.
+        let paths: Vec<OraclePath> = serde_json::from_str(
+            r#"[{"path":"Microwave","stats":{"score":40.0},"rain":{"penalty_us":200.0}},
+                {"path":"Fiber","stats":{"score":100.0},"rain":null}]"#,
+        )
+        .unwrap();
+        router.update_scores(scores(paths), later);
+        assert_eq!(router.route(None, OrderPriority::Hedge, later), NetworkPath::Fiber);
+
+        // Lapsed scores route by fiber.
+        let stale = later + Duration::from_secs(6);
+        assert_eq!(router.route(Some("market_making"), OrderPriority::Hedge, stale), NetworkPath::Fiber);
+
+        let status = router.status(later);
+        assert_eq!((status.paths[0].messages, status.paths[1].messages), (4, 6));
+        assert_eq!(status.paths[0].spend, Money::from_f64(0.08));
+        let stat_arb = status.strategies.iter().find(|s| s.strategy == "stat_arb").unwrap();
+        assert_eq!((status.bandwidth[0].spilled_opportunistic, status.bandwidth[0].overdrawn), (1, 1));
+        assert_eq!((stat_arb.policy.as_str(), stat_arb.microwave, stat_arb.fiber), ("weighted:0.0002", 1, 1));
+    }
+}
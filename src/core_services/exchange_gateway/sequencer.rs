@@ -0,0 +1,285 @@
+/*
+ * QuantumArb 2.0 - Core Services: Execution Report Sequencer
+ *
+ * File: src/core_services/exchange_gateway/sequencer.rs
+ *
+ * Description:
+ * Stands between the venue and the order book. After a reconnect a venue may
+ * resend reports the gateway already has (PossDupFlag=Y), and reports on
+ * different sessions or resend paths can arrive out of order. Applied as
+ * they come, a duplicate fill would be counted twice and a late partial fill
+ * would be flagged as an impossible sequence.
+ *
+ * Duplicates are recognised by exchange_order_id + exec_id (FIX ExecID) and
+ * dropped; the last DEDUP_RETENTION keys are remembered. Reports from
+ * producers without an exec_id are never treated as duplicates.
+ *
+ * Order is restored from the quantities the reports carry: a fill follows
+ * directly on the order's fills so far when its CumQty less its LastQty is
+ * what has already filled, and a cancel when its CumQty is. A report that is
+ * ahead of that is held in a small per-order buffer until the reports before
+ * it arrive. Held reports are released anyway, in CumQty order, once they
+ * have waited the hold time or the order has more than the window held; the
+ * lifecycle then flags whatever is still missing.
+ *
+ * Configuration (environment):
+ *   QA_EXEC_REORDER_HOLD_MS=250    longest a report is held for a gap
+ *   QA_EXEC_REORDER_WINDOW=8       reports held per order before giving up
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::{ExecutionReport, OrderStatus};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Execution ids remembered for duplicate detection.
+const DEDUP_RETENTION: usize = 100_000;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SequencerConfig {
+    pub hold: Duration,
+    pub window: usize,
+}
+
+impl Default for SequencerConfig {
+    fn default() -> Self {
+        SequencerConfig { hold: Duration::from_millis(250), window: 8 }
+    }
+}
+
+impl SequencerConfig {
+    pub fn from_env() -> SequencerConfig {
+        let var = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        let defaults = SequencerConfig::default();
+        SequencerConfig {
+            hold: Duration::from_millis(var("QA_EXEC_REORDER_HOLD_MS", defaults.hold.as_millis() as u64)),
+            window: var("QA_EXEC_REORDER_WINDOW", defaults.window as u64).max(1) as usize,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// Counters served on GET /orders/sequencing.
+#[derive(Debug, Clone, Copy, Default, Serialize, ApiSchema)]
+pub struct SequencerStats {
+    pub duplicates_dropped: u64,
+    /// Reports held for a gap that was then filled.
+    pub reordered: u64,
+    /// Reports released before the gap ahead of them was filled.
+    pub released_with_gap: u64,
+    pub held: usize,
+}
+
+struct Held {
+    report: ExecutionReport,
+    since: Instant,
+}
+
+// --- Sequencer ---
+
+#[derive(Default)]
+pub struct Sequencer {
+    config: SequencerConfig,
+    seen: HashSet<(String, String)>,
+    seen_order: VecDeque<(String, String)>,
+    held: HashMap<Uuid, Vec<Held>>,
+    stats: SequencerStats,
+}
+
+impl std::fmt::Debug for Sequencer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sequencer").field("config", &self.config).field("stats", &self.stats()).finish()
+    }
+}
+
+impl Sequencer {
+    pub fn new(config: SequencerConfig) -> Sequencer {
+        Sequencer { config, ..Sequencer::default() }
+    }
+
+    /// Takes a report from the venue. `cumulative` is what the order has
+    /// filled so far (None: not an open order). Returns the reports to apply
+    /// now, in order: the report itself if it follows on, nothing if it is a
+    /// duplicate or is held, or the order's held reports if the window is full.
+    pub fn admit(&mut self, report: ExecutionReport, cumulative: Option<u32>, now: Instant) -> Vec<ExecutionReport> {
+        if self.is_duplicate(&report) {
+            self.stats.duplicates_dropped += 1;
+            return Vec::new();
+        }
+        let Some(cumulative) = cumulative else { return vec![report] };
+        if !is_ahead(&report, cumulative) {
+            return vec![report];
+        }
+        let id = report.internal_order_id;
+        let held = self.held.entry(id).or_default();
+        held.push(Held { report, since: now });
+        if held.len() <= self.config.window {
+            return Vec::new();
+        }
+        self.release(&id)
+    }
+
+    /// A held report that follows directly on `cumulative`, once the report
+    /// before it has been applied.
+    pub fn next_ready(&mut self, internal_order_id: &Uuid, cumulative: Option<u32>) -> Option<ExecutionReport> {
+        let cumulative = cumulative?;
+        let held = self.held.get_mut(internal_order_id)?;
+        let index = held.iter().position(|h| !is_ahead(&h.report, cumulative))?;
+        let report = held.remove(index).report;
+        if held.is_empty() {
+            self.held.remove(internal_order_id);
+        }
+        self.stats.reordered += 1;
+        Some(report)
+    }
+
+    /// Releases the reports of every order with one held longer than the
+    /// hold time, each order's in CumQty order.
+    pub fn expire(&mut self, now: Instant) -> Vec<ExecutionReport> {
+        let hold = self.config.hold;
+        let expired: Vec<Uuid> = self
+            .held
+            .iter()
+            .filter(|(_, held)| held.iter().any(|h| now.duration_since(h.since) >= hold))
+            .map(|(id, _)| *id)
+            .collect();
+        expired.iter().flat_map(|id| self.release(id)).collect()
+    }
+
+    /// Forgets the held reports, e.g. when the order book is restored.
+    pub fn clear_held(&mut self) {
+        self.held.clear();
+    }
+
+    /// Remembers a report key as seen, for reports replayed from the order
+    /// journal after a restart.
+    pub fn mark_seen(&mut self, exchange_order_id: String, exec_id: String) {
+        if !exec_id.is_empty() {
+            self.remember((exchange_order_id, exec_id));
+        }
+    }
+
+    /// The keys remembered for duplicate detection, oldest first.
+    pub fn seen(&self) -> Vec<(String, String)> {
+        self.seen_order.iter().cloned().collect()
+    }
+
+    pub fn stats(&self) -> SequencerStats {
+        SequencerStats { held: self.held.values().map(Vec::len).sum(), ..self.stats }
+    }
+
+    fn release(&mut self, internal_order_id: &Uuid) -> Vec<ExecutionReport> {
+        let mut held = self.held.remove(internal_order_id).unwrap_or_default();
+        held.sort_by_key(|h| h.report.cumulative_size);
+        self.stats.released_with_gap += held.len() as u64;
+        held.into_iter().map(|h| h.report).collect()
+    }
+
+    fn is_duplicate(&mut self, report: &ExecutionReport) -> bool {
+        if report.exec_id.is_empty() {
+            return false;
+        }
+        !self.remember((report.exchange_order_id.clone(), report.exec_id.clone()))
+    }
+
+    /// Adds the key to those seen; false if it was already there.
+    fn remember(&mut self, key: (String, String)) -> bool {
+        if !self.seen.insert(key.clone()) {
+            return false;
+        }
+        if self.seen_order.len() == DEDUP_RETENTION {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen_order.push_back(key);
+        true
+    }
+}
+
+/// Whether the report belongs after fills the order has not had yet.
+/// Producers before the fill detail report no CumQty (0) and are never held.
+fn is_ahead(report: &ExecutionReport, cumulative: u32) -> bool {
+    match report.status {
+        OrderStatus::PartiallyFilled | OrderStatus::Filled => {
+            report.cumulative_size != 0 && report.cumulative_size.saturating_sub(report.filled_size) > cumulative
+        }
+        OrderStatus::Canceled => report.cumulative_size > cumulative,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_money::Money;
+    use quantumarb_wire::TradingMode;
+
+    fn fill(order: Uuid, exec_id: &str, filled_size: u32, cumulative_size: u32) -> ExecutionReport {
+        ExecutionReport {
+            exchange_order_id: "EXCH-1".to_string(),
+            exec_id: exec_id.to_string(),
+            internal_order_id: order,
+            status: if cumulative_size == 10 { OrderStatus::Filled } else { OrderStatus::PartiallyFilled },
+            filled_size,
+            filled_price: 4500_25,
+            reject: None,
+            stamps: Default::default(),
+            mode: TradingMode::Sandbox,
+            cumulative_size,
+            leaves_size: 10 - cumulative_size,
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn drops_reports_already_seen() {
+        let mut sequencer = Sequencer::default();
+        let order = Uuid::new_v4();
+        let now = Instant::now();
+        assert_eq!(sequencer.admit(fill(order, "E1", 4, 4), Some(0), now).len(), 1);
+        assert!(sequencer.admit(fill(order, "E1", 4, 4), Some(4), now).is_empty());
+        // No exec id: cannot tell, so it goes through.
+        assert_eq!(sequencer.admit(fill(order, "", 4, 4), Some(4), now).len(), 1);
+        assert_eq!(sequencer.stats().duplicates_dropped, 1);
+    }
+
+    #[test]
+    fn holds_a_fill_until_the_one_before_it_arrives() {
+        let mut sequencer = Sequencer::default();
+        let order = Uuid::new_v4();
+        let now = Instant::now();
+        assert!(sequencer.admit(fill(order, "E2", 6, 10), Some(0), now).is_empty());
+        let first = sequencer.admit(fill(order, "E1", 4, 4), Some(0), now);
+        assert_eq!(first[0].exec_id, "E1");
+        assert!(sequencer.next_ready(&order, Some(0)).is_none());
+        assert_eq!(sequencer.next_ready(&order, Some(4)).unwrap().exec_id, "E2");
+        assert_eq!(sequencer.stats().held, 0);
+    }
+
+    #[test]
+    fn releases_held_reports_after_the_hold_time_or_past_the_window() {
+        let config = SequencerConfig { hold: Duration::from_millis(100), window: 2 };
+        let mut sequencer = Sequencer::new(config);
+        let (late, crowded) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        sequencer.admit(fill(late, "L3", 3, 10), Some(0), now);
+        sequencer.admit(fill(late, "L2", 3, 7), Some(0), now);
+        assert!(sequencer.expire(now + Duration::from_millis(50)).is_empty());
+        let expired = sequencer.expire(now + Duration::from_millis(100));
+        assert_eq!(expired.iter().map(|r| r.exec_id.as_str()).collect::<Vec<_>>(), vec!["L2", "L3"]);
+
+        sequencer.admit(fill(crowded, "C2", 1, 2), Some(0), now);
+        sequencer.admit(fill(crowded, "C3", 1, 3), Some(0), now);
+        assert_eq!(sequencer.admit(fill(crowded, "C4", 1, 4), Some(0), now).len(), 3);
+        assert_eq!(sequencer.stats().released_with_gap, 5);
+    }
+}
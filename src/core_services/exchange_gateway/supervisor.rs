@@ -0,0 +1,351 @@
+/*
+ * QuantumArb 2.0 - Core Services: Venue Session Supervisor
+ *
+ * File: src/core_services/exchange_gateway/supervisor.rs
+ *
+ * Description:
+ * Keeps the gateway's venue sessions alive. Each venue has one or more
+ * sessions, primary first. Every heartbeat interval the venue's active
+ * session is sent a heartbeat (FIX TestRequest), and the venue's answer
+ * carries its message sequence number. A venue is in one of four states,
+ * published on 'venues.connectivity' whenever it, or the session in use,
+ * changes:
+ *
+ *   Up            connected on the primary session
+ *   Reconnecting  the session stopped answering heartbeats; logging on again
+ *   FailedOver    connected on a backup session after the one before it
+ *                 would not log on
+ *   Down          no session would log on. Logons continue, cycling through
+ *                 the sessions, until one succeeds
+ *
+ * Venues start Reconnecting until their first logon. The risk gateway stops
+ * approving orders for a venue that is Reconnecting or Down, so strategies
+ * route instruments listed elsewhere to another venue in the meantime.
+ *
+ * Sequence numbers are tracked per session. When the venue's number jumps
+ * past the one expected, in a heartbeat answer or on logon, the messages
+ * missed in between are requested again (FIX ResendRequest). Any execution
+ * reports among them are applied before the venue is reported routable.
+ *
+ * A venue that failed over stays on its backup session until that one fails
+ * too; the supervisor then moves on to the next session, wrapping round to
+ * the primary.
+ *
+ * Configuration (environment):
+ *   QA_VENUE_HEARTBEAT_MS=1000       heartbeat interval
+ *   QA_VENUE_MISSED_HEARTBEATS=3     unanswered heartbeats before reconnecting
+ *   QA_VENUE_LOGON_ATTEMPTS=3        failed logons on a session before failing over
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::{ConnectivityState, VenueConnectivity};
+use serde::Serialize;
+use std::time::Duration;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    pub heartbeat_interval: Duration,
+    pub missed_heartbeats: u32,
+    pub logon_attempts: u32,
+}
+
+impl SupervisorConfig {
+    pub fn from_env() -> SupervisorConfig {
+        let var = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        SupervisorConfig {
+            heartbeat_interval: Duration::from_millis(var("QA_VENUE_HEARTBEAT_MS", 1_000)),
+            missed_heartbeats: var("QA_VENUE_MISSED_HEARTBEATS", 3).max(1) as u32,
+            logon_attempts: var("QA_VENUE_LOGON_ATTEMPTS", 3).max(1) as u32,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// What the supervisor asks of a venue session this interval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    Heartbeat { venue: String, session: String },
+    Logon { venue: String, session: String },
+}
+
+/// What the gateway must do after a heartbeat answer or a logon.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Request the venue's messages `from..=to` on `session` again.
+    Resend { session: String, from: u64, to: u64 },
+    /// Publish the venue's new state on 'venues.connectivity'.
+    Publish(VenueConnectivity),
+}
+
+/// One line of GET /venues.
+#[derive(Debug, Serialize, ApiSchema)]
+pub struct VenueStatus {
+    pub venue: String,
+    pub state: ConnectivityState,
+    pub session: String,
+    pub sessions: Vec<String>,
+    pub missed_heartbeats: u32,
+    /// Sequence number expected next on the active session (0: none seen yet).
+    pub expected_seq: u64,
+}
+
+struct Session {
+    name: String,
+    /// The venue's next sequence number; 0 until the first one is seen.
+    expected_seq: u64,
+}
+
+impl Session {
+    /// Takes a sequence number from the venue. Returns the range of messages
+    /// skipped before it, if any. Numbers already seen are ignored.
+    fn advance(&mut self, seq: u64) -> Option<(u64, u64)> {
+        let gap = (self.expected_seq != 0 && seq > self.expected_seq).then(|| (self.expected_seq, seq - 1));
+        if seq >= self.expected_seq {
+            self.expected_seq = seq + 1;
+        }
+        gap
+    }
+}
+
+struct SupervisedVenue {
+    venue: String,
+    sessions: Vec<Session>,
+    active: usize,
+    state: ConnectivityState,
+    missed: u32,
+    failed_logons: u32,
+    /// Sessions given up on since the venue was last connected.
+    sessions_tried: usize,
+}
+
+impl SupervisedVenue {
+    fn session(&mut self) -> &mut Session {
+        &mut self.sessions[self.active]
+    }
+
+    fn resend(&mut self, seq: u64) -> Option<Action> {
+        let session = self.session();
+        let (from, to) = session.advance(seq)?;
+        Some(Action::Resend { session: session.name.clone(), from, to })
+    }
+
+    fn publish(&self, detail: String) -> Action {
+        Action::Publish(VenueConnectivity {
+            venue: self.venue.clone(),
+            state: self.state,
+            session: self.sessions[self.active].name.clone(),
+            detail,
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+// --- Supervisor ---
+
+pub struct Supervisor {
+    config: SupervisorConfig,
+    venues: Vec<SupervisedVenue>,
+}
+
+impl Supervisor {
+    /// Supervises each venue's sessions, given primary first. Venues without
+    /// a session are left out.
+    pub fn new(config: SupervisorConfig, venues: Vec<(&str, Vec<String>)>) -> Supervisor {
+        let venues = venues
+            .into_iter()
+            .filter(|(_, sessions)| !sessions.is_empty())
+            .map(|(venue, sessions)| SupervisedVenue {
+                venue: venue.to_string(),
+                sessions: sessions.into_iter().map(|name| Session { name, expected_seq: 0 }).collect(),
+                active: 0,
+                state: ConnectivityState::Reconnecting,
+                missed: 0,
+                failed_logons: 0,
+                sessions_tried: 0,
+            })
+            .collect();
+        Supervisor { config, venues }
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.config.heartbeat_interval
+    }
+
+    /// This interval's probe of every venue: a heartbeat on a connected
+    /// session, a logon on any other.
+    pub fn probes(&self) -> Vec<Probe> {
+        self.venues
+            .iter()
+            .map(|v| {
+                let (venue, session) = (v.venue.clone(), v.sessions[v.active].name.clone());
+                if v.state.is_routable() {
+                    Probe::Heartbeat { venue, session }
+                } else {
+                    Probe::Logon { venue, session }
+                }
+            })
+            .collect()
+    }
+
+    /// Takes the venue's answer to a heartbeat: its sequence number, or None
+    /// if it did not answer within the interval.
+    pub fn on_heartbeat(&mut self, venue: &str, answer: Option<u64>) -> Vec<Action> {
+        let missed_heartbeats = self.config.missed_heartbeats;
+        let Some(v) = self.venue_mut(venue) else { return Vec::new() };
+        if !v.state.is_routable() {
+            return Vec::new();
+        }
+        let Some(seq) = answer else {
+            v.missed += 1;
+            if v.missed < missed_heartbeats {
+                return Vec::new();
+            }
+            v.state = ConnectivityState::Reconnecting;
+            v.failed_logons = 0;
+            v.sessions_tried = 0;
+            let detail = format!("{} heartbeats unanswered on {}", v.missed, v.sessions[v.active].name);
+            return vec![v.publish(detail)];
+        };
+        v.missed = 0;
+        v.resend(seq).into_iter().collect()
+    }
+
+    /// Takes the outcome of a logon: the sequence number of the venue's
+    /// Logon, or why it failed.
+    pub fn on_logon(&mut self, venue: &str, answer: Result<u64, String>) -> Vec<Action> {
+        let logon_attempts = self.config.logon_attempts;
+        let Some(v) = self.venue_mut(venue) else { return Vec::new() };
+        if v.state.is_routable() {
+            return Vec::new();
+        }
+        match answer {
+            Ok(seq) => {
+                // Catch up on what was missed before reporting the venue routable.
+                let mut actions: Vec<Action> = v.resend(seq).into_iter().collect();
+                v.state = if v.active == 0 { ConnectivityState::Up } else { ConnectivityState::FailedOver };
+                v.missed = 0;
+                v.failed_logons = 0;
+                v.sessions_tried = 0;
+                actions.push(v.publish(format!("logged on to {}", v.sessions[v.active].name)));
+                actions
+            }
+            Err(reason) => {
+                v.failed_logons += 1;
+                if v.failed_logons < logon_attempts {
+                    return Vec::new();
+                }
+                let failed = v.sessions[v.active].name.clone();
+                v.failed_logons = 0;
+                v.sessions_tried += 1;
+                v.active = (v.active + 1) % v.sessions.len();
+                if v.sessions_tried < v.sessions.len() {
+                    let detail = format!("{} would not log on ({}); failing over to {}", failed, reason, v.sessions[v.active].name);
+                    return vec![v.publish(detail)];
+                }
+                if v.state == ConnectivityState::Down {
+                    return Vec::new();
+                }
+                v.state = ConnectivityState::Down;
+                vec![v.publish(format!("no session would log on; {} last failed ({})", failed, reason))]
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<VenueStatus> {
+        self.venues
+            .iter()
+            .map(|v| VenueStatus {
+                venue: v.venue.clone(),
+                state: v.state,
+                session: v.sessions[v.active].name.clone(),
+                sessions: v.sessions.iter().map(|s| s.name.clone()).collect(),
+                missed_heartbeats: v.missed,
+                expected_seq: v.sessions[v.active].expected_seq,
+            })
+            .collect()
+    }
+
+    fn venue_mut(&mut self, venue: &str) -> Option<&mut SupervisedVenue> {
+        self.venues.iter_mut().find(|v| v.venue == venue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supervisor() -> Supervisor {
+        let config =
+            SupervisorConfig { heartbeat_interval: Duration::from_secs(1), missed_heartbeats: 2, logon_attempts: 2 };
+        Supervisor::new(config, vec![("CME", vec!["CME-PRIMARY".to_string(), "CME-BACKUP".to_string()])])
+    }
+
+    fn published(actions: &[Action]) -> Vec<(ConnectivityState, String)> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Publish(event) => Some((event.state, event.session.clone())),
+                Action::Resend { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missed_heartbeats_reconnect_and_fail_over_to_the_backup() {
+        let mut supervisor = supervisor();
+        let up = supervisor.on_logon("CME", Ok(1));
+        assert_eq!(published(&up), vec![(ConnectivityState::Up, "CME-PRIMARY".to_string())]);
+        assert!(supervisor.on_heartbeat("CME", Some(2)).is_empty());
+
+        assert!(supervisor.on_heartbeat("CME", None).is_empty());
+        let lost = supervisor.on_heartbeat("CME", None);
+        assert_eq!(published(&lost), vec![(ConnectivityState::Reconnecting, "CME-PRIMARY".to_string())]);
+        assert!(matches!(&supervisor.probes()[0], Probe::Logon { session, .. } if session == "CME-PRIMARY"));
+
+        assert!(supervisor.on_logon("CME", Err("timed out".to_string())).is_empty());
+        let failing_over = supervisor.on_logon("CME", Err("timed out".to_string()));
+        assert_eq!(published(&failing_over), vec![(ConnectivityState::Reconnecting, "CME-BACKUP".to_string())]);
+        let backup = supervisor.on_logon("CME", Ok(40));
+        assert_eq!(published(&backup), vec![(ConnectivityState::FailedOver, "CME-BACKUP".to_string())]);
+        assert!(matches!(&supervisor.probes()[0], Probe::Heartbeat { session, .. } if session == "CME-BACKUP"));
+    }
+
+    #[test]
+    fn down_once_every_session_fails_and_up_again_on_the_primary() {
+        let mut supervisor = supervisor();
+        let timed_out = || Err("timed out".to_string());
+        let mut events = Vec::new();
+        for _ in 0..8 {
+            events.extend(published(&supervisor.on_logon("CME", timed_out())));
+        }
+        // Primary, then backup, then Down once, however long it lasts.
+        assert_eq!(
+            events,
+            vec![
+                (ConnectivityState::Reconnecting, "CME-BACKUP".to_string()),
+                (ConnectivityState::Down, "CME-PRIMARY".to_string()),
+            ]
+        );
+        let up = supervisor.on_logon("CME", Ok(1));
+        assert_eq!(published(&up), vec![(ConnectivityState::Up, "CME-PRIMARY".to_string())]);
+    }
+
+    #[test]
+    fn sequence_gaps_are_resent_before_the_venue_is_routable() {
+        let mut supervisor = supervisor();
+        supervisor.on_logon("CME", Ok(1));
+        let gap = supervisor.on_heartbeat("CME", Some(5));
+        assert!(matches!(&gap[..], [Action::Resend { from: 2, to: 4, .. }]));
+        // A number already seen is not a gap.
+        assert!(supervisor.on_heartbeat("CME", Some(5)).is_empty());
+
+        supervisor.on_heartbeat("CME", None);
+        supervisor.on_heartbeat("CME", None);
+        let back = supervisor.on_logon("CME", Ok(9));
+        assert!(matches!(&back[0], Action::Resend { from: 6, to: 8, .. }));
+        assert!(matches!(&back[1], Action::Publish(event) if event.state == ConnectivityState::Up));
+    }
+}
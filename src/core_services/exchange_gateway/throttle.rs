@@ -0,0 +1,330 @@
+/*
+ * QuantumArb 2.0 - Core Services: Venue Rate Limits
+ *
+ * File: src/core_services/exchange_gateway/throttle.rs
+ *
+ * Description:
+ * Venues cap how many API requests a client may make in a window, and a
+ * crypto venue bans the key for a while when the cap is broken. The gateway
+ * keeps each venue's count itself, over a sliding window, and paces its own
+ * requests to stay under it:
+ *
+ *   cancel   order cancels, which may use the whole window
+ *   order    new orders and amendments
+ *   query    heartbeats, logons and resend requests
+ *
+ * Orders and queries leave `cancel_reserve_pct` of the window unused, so a
+ * burst of new orders can never leave the gateway unable to pull its quotes.
+ * A request over the budget waits for it; while a cancel is waiting no order
+ * or query is let through, and while an order is waiting no query is.
+ *
+ * GET /venues/rate-limits shows each venue's remaining budget and what is
+ * waiting for it.
+ *
+ * Configuration (environment):
+ *   QA_VENUE_RATE_LIMITS="BINANCE:50/10"   per venue, requests per window in
+ *                                          seconds
+ *   QA_VENUE_RATE_LIMIT_DEFAULT=100/1      for venues not listed
+ *   QA_VENUE_CANCEL_RESERVE_PCT=20         share of the window kept for cancels
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How soon a request held back behind a higher class looks again.
+const PRIORITY_RETRY: Duration = Duration::from_millis(1);
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    /// Parses "requests/seconds", e.g. "50/10".
+    fn parse(text: &str) -> Option<RateLimit> {
+        let (requests, secs) = text.trim().split_once('/')?;
+        let requests: u32 = requests.trim().parse().ok().filter(|r| *r > 0)?;
+        let secs: u64 = secs.trim().parse().ok().filter(|s| *s > 0)?;
+        Some(RateLimit { requests, window: Duration::from_secs(secs) })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub limits: HashMap<String, RateLimit>,
+    pub default_limit: RateLimit,
+    pub cancel_reserve_pct: u32,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> RateLimitConfig {
+        let limits = std::env::var("QA_VENUE_RATE_LIMITS")
+            .unwrap_or_else(|_| "BINANCE:50/10".to_string())
+            .split(',')
+            .filter_map(|entry| {
+                let (venue, limit) = entry.split_once(':')?;
+                Some((venue.trim().to_string(), RateLimit::parse(limit)?))
+            })
+            .collect();
+        let default_limit = std::env::var("QA_VENUE_RATE_LIMIT_DEFAULT")
+            .ok()
+            .and_then(|v| RateLimit::parse(&v))
+            .unwrap_or(RateLimit { requests: 100, window: Duration::from_secs(1) });
+        let cancel_reserve_pct = std::env::var("QA_VENUE_CANCEL_RESERVE_PCT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20u32)
+            .min(100);
+        RateLimitConfig { limits, default_limit, cancel_reserve_pct }
+    }
+
+    fn limit(&self, venue: &str) -> RateLimit {
+        self.limits.get(venue).copied().unwrap_or(self.default_limit)
+    }
+}
+
+// --- Scheduler ---
+
+/// The classes of request, highest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Cancel,
+    Order,
+    Query,
+}
+
+impl RequestKind {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// One venue's entry in GET /venues/rate-limits.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct VenueRateStatus {
+    pub venue: String,
+    pub limit: u32,
+    pub window_secs: u64,
+    pub used: u32,
+    /// Requests left in the window, cancels included.
+    pub remaining: u32,
+    /// Requests left for orders and queries once the cancel reserve is kept.
+    pub remaining_for_orders: u32,
+    pub queued_cancels: usize,
+    pub queued_orders: usize,
+    pub queued_queries: usize,
+    /// Requests made since the gateway started.
+    pub sent: u64,
+    /// Of those, the ones that had to wait for budget.
+    pub throttled: u64,
+}
+
+struct VenueRate {
+    limit: RateLimit,
+    cancel_reserve: u32,
+    /// When each request in the window was made.
+    made: VecDeque<Instant>,
+    /// Requests waiting for budget, by `RequestKind`.
+    waiting: [usize; 3],
+    sent: u64,
+    throttled: u64,
+}
+
+impl VenueRate {
+    fn new(limit: RateLimit, cancel_reserve_pct: u32) -> VenueRate {
+        // Orders always keep at least one request of the window.
+        let cancel_reserve = (limit.requests * cancel_reserve_pct / 100).min(limit.requests - 1);
+        VenueRate { limit, cancel_reserve, made: VecDeque::new(), waiting: [0; 3], sent: 0, throttled: 0 }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self.made.front().is_some_and(|at| now.duration_since(*at) >= self.limit.window) {
+            self.made.pop_front();
+        }
+    }
+
+    /// Requests of `kind` the window may hold.
+    fn headroom(&self, kind: RequestKind) -> u32 {
+        match kind {
+            RequestKind::Cancel => self.limit.requests,
+            RequestKind::Order | RequestKind::Query => self.limit.requests - self.cancel_reserve,
+        }
+    }
+
+    fn used(&self) -> u32 {
+        self.made.len() as u32
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.made.push_back(now);
+        self.sent += 1;
+    }
+}
+
+pub struct RateLimits {
+    config: RateLimitConfig,
+    venues: HashMap<String, VenueRate>,
+}
+
+pub type SharedRateLimits = Arc<Mutex<RateLimits>>;
+
+impl RateLimits {
+    pub fn new(config: RateLimitConfig) -> RateLimits {
+        RateLimits { config, venues: HashMap::new() }
+    }
+
+    fn venue(&mut self, venue: &str, now: Instant) -> &mut VenueRate {
+        let config = &self.config;
+        let rate = self
+            .venues
+            .entry(venue.to_string())
+            .or_insert_with(|| VenueRate::new(config.limit(venue), config.cancel_reserve_pct));
+        rate.prune(now);
+        rate
+    }
+
+    /// Takes budget for a request of `kind` to `venue` if it has some and no
+    /// higher class is waiting; otherwise returns how long to wait before
+    /// asking again. `queued` says the request is already counted as waiting.
+    pub fn try_take(&mut self, venue: &str, kind: RequestKind, queued: bool, now: Instant) -> Result<(), Duration> {
+        let rate = self.venue(venue, now);
+        let behind = rate.waiting[..kind.index()].iter().sum::<usize>() > 0;
+        let (used, headroom) = (rate.used(), rate.headroom(kind));
+        if used < headroom && !behind {
+            rate.record(now);
+            if queued {
+                rate.waiting[kind.index()] -= 1;
+            }
+            return Ok(());
+        }
+        if !queued {
+            rate.waiting[kind.index()] += 1;
+            rate.throttled += 1;
+        }
+        if used < headroom {
+            return Err(PRIORITY_RETRY);
+        }
+        // Wait for enough of the window to age out to bring it under headroom.
+        let frees_up = rate.made[(used - headroom) as usize] + rate.limit.window;
+        Err(frees_up.saturating_duration_since(now).max(PRIORITY_RETRY))
+    }
+
+    /// Counts a request made without waiting for budget.
+    pub fn record(&mut self, venue: &str, now: Instant) {
+        self.venue(venue, now).record(now);
+    }
+
+    /// Stops counting a request as waiting (it was given up on).
+    fn leave(&mut self, venue: &str, kind: RequestKind) {
+        if let Some(rate) = self.venues.get_mut(venue) {
+            rate.waiting[kind.index()] = rate.waiting[kind.index()].saturating_sub(1);
+        }
+    }
+
+    /// Every venue's budget, by venue name.
+    pub fn status(&mut self, now: Instant) -> Vec<VenueRateStatus> {
+        let mut status: Vec<VenueRateStatus> = self
+            .venues
+            .iter_mut()
+            .map(|(venue, rate)| {
+                rate.prune(now);
+                let used = rate.used();
+                VenueRateStatus {
+                    venue: venue.clone(),
+                    limit: rate.limit.requests,
+                    window_secs: rate.limit.window.as_secs(),
+                    used,
+                    remaining: rate.limit.requests.saturating_sub(used),
+                    remaining_for_orders: rate.headroom(RequestKind::Order).saturating_sub(used),
+                    queued_cancels: rate.waiting[RequestKind::Cancel.index()],
+                    queued_orders: rate.waiting[RequestKind::Order.index()],
+                    queued_queries: rate.waiting[RequestKind::Query.index()],
+                    sent: rate.sent,
+                    throttled: rate.throttled,
+                }
+            })
+            .collect();
+        status.sort_by(|a, b| a.venue.cmp(&b.venue));
+        status
+    }
+}
+
+/// A request counted as waiting until it takes its budget or is dropped.
+struct Waiting<'a> {
+    limits: &'a SharedRateLimits,
+    venue: &'a str,
+    kind: RequestKind,
+    queued: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.queued {
+            self.limits.lock().unwrap().leave(self.venue, self.kind);
+        }
+    }
+}
+
+/// Waits until `venue` has budget for a request of `kind`, then takes it.
+pub async fn acquire(limits: &SharedRateLimits, venue: &str, kind: RequestKind) {
+    let first = limits.lock().unwrap().try_take(venue, kind, false, Instant::now());
+    let Err(mut wait) = first else {
+        return;
+    };
+    let mut waiting = Waiting { limits, venue, kind, queued: true };
+    loop {
+        tokio::time::sleep(wait).await;
+        let taken = limits.lock().unwrap().try_take(venue, kind, true, Instant::now());
+        match taken {
+            // Taking the budget already stopped counting it as waiting.
+            Ok(()) => {
+                waiting.queued = false;
+                return;
+            }
+            Err(next) => wait = next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_leave_the_cancel_reserve_and_wait_behind_queued_cancels() {
+        let config = RateLimitConfig {
+            limits: [("BINANCE".to_string(), RateLimit::parse("10/10").unwrap())].into(),
+            default_limit: RateLimit { requests: 100, window: Duration::from_secs(1) },
+            cancel_reserve_pct: 20,
+        };
+        let mut limits = RateLimits::new(config);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        for i in 0..8 {
+            assert!(limits.try_take("BINANCE", RequestKind::Order, false, at(i)).is_ok());
+        }
+        // The last two requests of the window are kept for cancels.
+        assert_eq!(limits.try_take("BINANCE", RequestKind::Order, false, at(8)), Err(Duration::from_secs(2)));
+        assert!(limits.try_take("BINANCE", RequestKind::Cancel, false, at(8)).is_ok());
+        assert!(limits.try_take("BINANCE", RequestKind::Cancel, false, at(8)).is_ok());
+        assert!(limits.try_take("BINANCE", RequestKind::Cancel, false, at(8)).is_err());
+
+        // The first order ages out, but the waiting cancel goes first.
+        assert!(limits.try_take("BINANCE", RequestKind::Order, true, at(10)).is_err());
+        assert!(limits.try_take("BINANCE", RequestKind::Cancel, true, at(10)).is_ok());
+        let status = &limits.status(at(10))[0];
+        assert_eq!((status.used, status.remaining, status.remaining_for_orders), (10, 0, 0));
+        assert_eq!((status.queued_cancels, status.queued_orders, status.throttled), (0, 1, 2));
+
+        assert!(limits.try_take("BINANCE", RequestKind::Order, true, at(13)).is_ok());
+        assert!(limits.try_take("CME", RequestKind::Query, false, at(13)).is_ok(), "venues are counted apart");
+        assert_eq!(limits.status(at(13))[0].queued_orders, 0);
+    }
+}
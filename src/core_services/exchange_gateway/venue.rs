@@ -0,0 +1,571 @@
+/*
+ * QuantumArb 2.0 - Core Services: Venue Adapters
+ *
+ * File: src/core_services/exchange_gateway/venue.rs
+ *
+ * Description:
+ * The connection behind the gateway's order path, selected by the trading
+ * mode:
+ *
+ *   PaperVenue   the paper-trading simulator. Orders fill at their price
+ *                moved against them by their market impact (see
+ *                `impact.rs`), in one to three slices: the first takes
+ *                liquidity, later ones rest and make it. About one order in
+ *                five leaves its last slice working and one in twenty has the
+ *                rest canceled by the venue (an unsolicited cancel); roughly
+ *                one in ten is rejected outright with a FIX OrdRejReason.
+ *                Fills are charged fees from the `quantumarb-fees` schedule
+ *                for venue PAPER (the default schedule unless
+ *                QA_FEE_SCHEDULES_PATH defines one). About one order in
+ *                twenty has a report delivered twice, and as many have two
+ *                reports arrive out of order. About one filled order in
+ *                fifty has its last fill busted, or corrected to the
+ *                order's price, after the fact. It also stands in
+ *                for the sessions to VENUE_A, VENUE_B and CME, a primary
+ *                and a backup each, which now and then stop answering for
+ *                a few seconds or skip sequence numbers. Used in sandbox
+ *                mode; with a seed (--seed N or QA_SEED) it fills and
+ *                rejects the same orders on every run.
+ *   CmeVenue     the FIX sessions to CME Globex, primary and backup. Used in
+ *                live mode.
+ *   BinanceVenue Binance spot over signed REST and the user-data stream
+ *                (see `binance.rs`), for a crypto pilot. Used in live mode
+ *                with QA_LIVE_VENUE=binance.
+ *
+ * Live adapters are only compiled with the `live-venues` cargo feature;
+ * sandbox builds leave it off, so they contain no code that can reach a real
+ * venue and refuse to start in live mode. A live adapter also checks
+ * QA_TRADING_MODE itself when it connects and refuses to load in a sandbox
+ * process, whatever the caller asked for.
+ *
+ * Orders carry the canonical instrument symbol; each adapter sends the
+ * venue's own symbol for it, from the venue symbology
+ * (`quantumarb_refdata::symbology`, loaded from QA_SYMBOLOGY_PATH or the
+ * built-in set) as of the time of sending. The paper venue uses the
+ * symbology of the instrument's primary listing venue. An instrument with
+ * no mapping goes out under its canonical symbol, for the venue to reject
+ * if it does not know it.
+ *
+ * Venue rejections are translated from the exchange's native reason (FIX
+ * OrdRejReason, tag 103) into the shared `quantumarb-errors` reject codes.
+ *
+ * Each adapter lists its sessions and answers the heartbeats, logons and
+ * resend requests of the session supervisor (see `supervisor.rs`).
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_fees::{FeeEngine, Liquidity};
+use quantumarb_money::{Money, Price};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::symbology::{self, Symbology};
+use quantumarb_refdata::{ReferenceData, DEFAULT_PRICE_DECIMALS};
+use quantumarb_sim::{Seed, SimRng};
+use rand::Rng;
+use crate::impact::{ImpactConfig, MarketImpact};
+use quantumarb_wire::{utc_now_ns, ExecutionReport, HopStamps, OrderPriority, OrderSide, OrderStatus, TradingMode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use uuid::Uuid;
+
+// --- Data Structures ---
+
+/// An order from the risk gateway, as held by the exchange gateway.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct InboundOrder {
+    pub internal_order_id: Uuid,
+    pub instrument_symbol: String,
+    pub price: u64,
+    pub size: u32,
+    pub side: OrderSide,
+    #[serde(default)]
+    pub stamps: HopStamps,
+    #[serde(default)]
+    pub mode: TradingMode,
+    /// The strategy that sent the order, for load shedding; None for the
+    /// gateway's own orders (flattens).
+    #[serde(default)]
+    pub strategy: Option<String>,
+    #[serde(default)]
+    pub priority: OrderPriority,
+}
+
+/// Network path to the venue, as chosen by the router (see `routing.rs`).
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, ApiSchema)]
+pub enum NetworkPath {
+    Microwave,
+    Fiber,
+}
+
+/// A venue connection.
+pub trait VenueAdapter: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Writes the order to the venue over `path`.
+    fn send(&self, order: &InboundOrder, path: NetworkPath);
+    /// The venue's reports for an order it was sent, in the order they
+    /// arrived: fills, partial or full, and any reject or unsolicited cancel.
+    fn execution_reports(&self, order: &InboundOrder) -> Vec<ExecutionReport>;
+    /// Reports that arrived on their own since they were last taken, for
+    /// orders sent earlier (fills on a venue's stream). Adapters that return
+    /// every report from `execution_reports` have none.
+    fn streamed_reports(&self) -> Vec<ExecutionReport> {
+        Vec::new()
+    }
+    /// Cancels a working order.
+    fn cancel(&self, order: &InboundOrder) -> ExecutionReport;
+    /// Amends a working order to `size` in total, `cumulative_size` of which
+    /// has already filled.
+    fn amend(&self, order: &InboundOrder, size: u32, cumulative_size: u32) -> ExecutionReport;
+    /// The venues this connection reaches, each with its sessions, primary
+    /// first.
+    fn sessions(&self) -> Vec<(&'static str, Vec<String>)>;
+    /// The venue among `sessions` an order goes to, whose rate limit its
+    /// requests count against.
+    fn order_venue(&self, order: &InboundOrder) -> &'static str {
+        let _ = order;
+        self.sessions().first().map_or("", |(venue, _)| *venue)
+    }
+    /// Sends a heartbeat (FIX TestRequest) on `session`. The answer carries
+    /// the venue's sequence number; None if the venue did not answer.
+    fn heartbeat(&self, session: &str) -> Option<u64>;
+    /// Logs on to `session` and sends orders on it from now on. Ok carries the
+    /// sequence number of the venue's Logon.
+    fn logon(&self, session: &str) -> Result<u64, String>;
+    /// Asks the venue to resend its messages `from..=to` on `session` (FIX
+    /// ResendRequest). Returns the execution reports among them.
+    fn resend(&self, session: &str, from: u64, to: u64) -> Vec<ExecutionReport>;
+}
+
+/// Connects the adapter for `mode`. `seed` drives the paper venue.
+pub fn connect(mode: TradingMode, seed: &Seed) -> Arc<dyn VenueAdapter> {
+    match mode {
+        TradingMode::Sandbox => Arc::new(PaperVenue {
+            rng: Mutex::new(seed.stream("exchange_gateway.paper_venue")),
+            fees: Mutex::new(FeeEngine::from_env()),
+            impact: Mutex::new(MarketImpact::new(ImpactConfig::from_env())),
+            instruments: ReferenceData::new(quantumarb_refdata::load_from_env()),
+            symbology: Symbology::new(symbology::load_mappings_from_env()),
+            links: Mutex::new(PaperLinks {
+                rng: seed.stream("exchange_gateway.paper_sessions"),
+                sessions: HashMap::new(),
+            }),
+        }),
+        TradingMode::Live => connect_live(),
+    }
+}
+
+#[cfg(feature = "live-venues")]
+fn connect_live() -> Arc<dyn VenueAdapter> {
+    match std::env::var("QA_LIVE_VENUE").as_deref() {
+        Ok("binance") => Arc::new(crate::binance::BinanceVenue::connect()),
+        _ => Arc::new(CmeVenue::connect()),
+    }
+}
+
+#[cfg(not(feature = "live-venues"))]
+fn connect_live() -> Arc<dyn VenueAdapter> {
+    panic!("QA_TRADING_MODE=live, but this is a sandbox build: live venue adapters need the `live-venues` feature.");
+}
+
+// --- Paper Trading ---
+
+/// The paper-trading simulator.
+pub struct PaperVenue {
+    rng: Mutex<SimRng>,
+    fees: Mutex<FeeEngine>,
+    impact: Mutex<MarketImpact>,
+    /// Instrument definitions, for the price scale fees are charged at and
+    /// the venue an order is listed on.
+    instruments: ReferenceData,
+    symbology: Symbology,
+    links: Mutex<PaperLinks>,
+}
+
+/// The simulated venue sessions, on their own random stream so outages do
+/// not change which orders fill.
+struct PaperLinks {
+    rng: SimRng,
+    sessions: HashMap<String, PaperSession>,
+}
+
+#[derive(Default)]
+struct PaperSession {
+    seq: u64,
+    /// Heartbeats and logons left unanswered in the current outage.
+    silent_for: u32,
+}
+
+/// Venues the paper simulator stands in for.
+const PAPER_VENUES: [&str; 3] = ["VENUE_A", "VENUE_B", "CME"];
+
+impl PaperVenue {
+    /// Fee schedule name the simulator charges under.
+    const FEE_VENUE: &'static str = "PAPER";
+
+    /// The fee for filling `size` of `order` at the wire price `filled_price`.
+    fn charge(&self, order: &InboundOrder, size: u32, filled_price: u64, liquidity: Liquidity) -> Money {
+        let price = match self.instruments.by_symbol(&order.instrument_symbol) {
+            Some(definition) => definition.price(filled_price),
+            None => Price::from_wire(filled_price, DEFAULT_PRICE_DECIMALS).unwrap_or(Price::MAX),
+        };
+        let quantity = match order.side {
+            OrderSide::Buy => size as i64,
+            OrderSide::Sell => -(size as i64),
+        };
+        let fees = self.fees.lock().unwrap().apply(Self::FEE_VENUE, liquidity, quantity, price.to_f64());
+        Money::from_f64(fees.total)
+    }
+
+    /// The wire price `offset_bps` from `order`'s price, on the tick grid.
+    fn moved_price(&self, order: &InboundOrder, offset_bps: f64) -> u64 {
+        let factor = 1.0 + offset_bps / 10_000.0;
+        match self.instruments.by_symbol(&order.instrument_symbol) {
+            Some(definition) => definition.wire_price(Price::from_f64(definition.price(order.price).to_f64() * factor)),
+            None => (order.price as f64 * factor).round() as u64,
+        }
+    }
+}
+
+impl VenueAdapter for PaperVenue {
+    fn name(&self) -> &'static str {
+        "Paper trading simulator"
+    }
+
+    fn send(&self, order: &InboundOrder, path: NetworkPath) {
+        let venue = self.instruments.by_symbol(&order.instrument_symbol).map(|d| d.venue.as_str());
+        let symbol = venue_symbol(order, venue.unwrap_or_default(), &self.instruments, &self.symbology);
+        println!(
+            "  -> [PAPER] Sending order via [{:?}] path: Symbol {} ({}), Size {}",
+            path, symbol, order.instrument_symbol, order.size
+        );
+    }
+
+    fn execution_reports(&self, order: &InboundOrder) -> Vec<ExecutionReport> {
+        let mut rng = self.rng.lock().unwrap();
+        let exchange_order_id = format!("PAPER-{}", quantumarb_sim::uuid(&mut rng).simple());
+        if rng.gen_bool(0.1) {
+            let venue_reason = [1, 2, 6, 99][rng.gen_range(0..4)];
+            let mut report = report_without_fill(order, OrderStatus::RejectedByExchange, TradingMode::Sandbox);
+            report.exchange_order_id = exchange_order_id;
+            report.reject = Some(map_venue_reject(venue_reason));
+            return vec![report];
+        }
+
+        // Cut the order into slices; maybe hold back or cancel the last one.
+        let slices = rng.gen_range(1..=3).min(order.size.max(1));
+        let mut sizes: Vec<u32> = (0..slices).map(|i| order.size / slices + u32::from(i < order.size % slices)).collect();
+        let ending = match rng.gen_range(0..20) {
+            0 if slices > 1 => Some(OrderStatus::Canceled),
+            1..=4 if slices > 1 => Some(OrderStatus::PartiallyFilled),
+            _ => None,
+        };
+        if ending.is_some() {
+            sizes.pop();
+        }
+        drop(rng);
+        let offsets = self.impact.lock().unwrap().fill(&order.instrument_symbol, order.side, &sizes, Instant::now());
+
+        let mut reports = Vec::with_capacity(sizes.len() + 1);
+        let mut cumulative = 0;
+        for (index, (size, offset_bps)) in sizes.into_iter().zip(offsets).enumerate() {
+            cumulative += size;
+            let filled_price = self.moved_price(order, offset_bps);
+            let liquidity = if index == 0 { Liquidity::Taker } else { Liquidity::Maker };
+            let leaves = order.size - cumulative;
+            reports.push(ExecutionReport {
+                exchange_order_id: exchange_order_id.clone(),
+                exec_id: format!("{}-{}", exchange_order_id, index + 1),
+                internal_order_id: order.internal_order_id,
+                status: if leaves == 0 { OrderStatus::Filled } else { OrderStatus::PartiallyFilled },
+                filled_size: size,
+                filled_price,
+                reject: None,
+                stamps: order.stamps,
+                mode: TradingMode::Sandbox,
+                cumulative_size: cumulative,
+                leaves_size: leaves,
+                liquidity: Some(liquidity),
+                fee: self.charge(order, size, filled_price, liquidity),
+                transact_time_ns: utc_now_ns(),
+                exec_ref_id: String::new(),
+            });
+        }
+        if ending == Some(OrderStatus::Canceled) {
+            let mut report = report_without_fill(order, OrderStatus::Canceled, TradingMode::Sandbox);
+            report.exec_id = format!("{}-{}", exchange_order_id, reports.len() + 1);
+            report.exchange_order_id = exchange_order_id;
+            report.cumulative_size = cumulative;
+            reports.push(report);
+        }
+
+        // As after a reconnect, now and then a report arrives twice or two
+        // arrive the wrong way round.
+        let mut rng = self.rng.lock().unwrap();
+        if reports.len() > 1 && rng.gen_bool(0.05) {
+            let index = rng.gen_range(1..reports.len());
+            reports.swap(index - 1, index);
+        }
+        if rng.gen_bool(0.05) {
+            let index = rng.gen_range(0..reports.len());
+            reports.insert(index + 1, reports[index].clone());
+        }
+
+        // Now and then the venue busts the last fill, or corrects its price
+        // to the order's, after the order's other reports.
+        let last_fill = reports.iter().rev().find(|report| report.filled_size > 0).cloned();
+        if let Some(fill) = last_fill.filter(|_| rng.gen_bool(0.02)) {
+            let bust = rng.gen_bool(0.5);
+            drop(rng);
+            let closed = ending != Some(OrderStatus::PartiallyFilled);
+            let mut report = fill.clone();
+            report.exec_id = format!("{}-{}", fill.exchange_order_id, reports.len() + 1);
+            report.exec_ref_id = fill.exec_id.clone();
+            report.transact_time_ns = utc_now_ns();
+            if bust {
+                report.status = OrderStatus::TradeBusted;
+                report.cumulative_size = cumulative - fill.filled_size;
+            } else {
+                report.status = OrderStatus::TradeCorrected;
+                report.filled_price = order.price;
+                let liquidity = fill.liquidity.unwrap_or(Liquidity::Taker);
+                report.fee = self.charge(order, fill.filled_size, order.price, liquidity);
+            }
+            report.leaves_size = if closed { 0 } else { order.size - report.cumulative_size };
+            reports.push(report);
+        }
+        reports
+    }
+
+    fn cancel(&self, order: &InboundOrder) -> ExecutionReport {
+        println!("  -> [PAPER] Canceling order {}", order.internal_order_id);
+        report_without_fill(order, OrderStatus::Canceled, TradingMode::Sandbox)
+    }
+
+    fn amend(&self, order: &InboundOrder, size: u32, cumulative_size: u32) -> ExecutionReport {
+        println!("  -> [PAPER] Amending order {} to {}", order.internal_order_id, size);
+        amended_report(order, size, cumulative_size, TradingMode::Sandbox)
+    }
+
+    fn sessions(&self) -> Vec<(&'static str, Vec<String>)> {
+        PAPER_VENUES.iter().map(|venue| (*venue, vec![format!("{}-PRIMARY", venue), format!("{}-BACKUP", venue)])).collect()
+    }
+
+    fn order_venue(&self, order: &InboundOrder) -> &'static str {
+        let listed = self.instruments.by_symbol(&order.instrument_symbol).map(|d| d.venue.as_str());
+        PAPER_VENUES.into_iter().find(|venue| Some(*venue) == listed).unwrap_or(PAPER_VENUES[0])
+    }
+
+    fn heartbeat(&self, session: &str) -> Option<u64> {
+        let mut links = self.links.lock().unwrap();
+        let PaperLinks { rng, sessions } = &mut *links;
+        let link = sessions.entry(session.to_string()).or_default();
+        if link.silent_for > 0 {
+            link.silent_for -= 1;
+            return None;
+        }
+        // About one outage, of 5 to 30 intervals, every 500 heartbeats.
+        if rng.gen_bool(0.002) {
+            link.silent_for = rng.gen_range(5..30);
+            return None;
+        }
+        // Now and then a few of the venue's messages never arrive.
+        if rng.gen_bool(0.01) {
+            link.seq += rng.gen_range(1..4);
+        }
+        link.seq += 1;
+        Some(link.seq)
+    }
+
+    fn logon(&self, session: &str) -> Result<u64, String> {
+        let mut links = self.links.lock().unwrap();
+        let PaperLinks { rng, sessions } = &mut *links;
+        let link = sessions.entry(session.to_string()).or_default();
+        if link.silent_for > 0 {
+            link.silent_for -= 1;
+            return Err("logon timed out".to_string());
+        }
+        // The venue kept sending while the session was away.
+        link.seq += 1 + rng.gen_range(0..3);
+        println!("  -> [PAPER] Logged on to {} (MsgSeqNum {})", session, link.seq);
+        Ok(link.seq)
+    }
+
+    fn resend(&self, session: &str, from: u64, to: u64) -> Vec<ExecutionReport> {
+        // The simulator keeps no message store: it fills the gap with a
+        // SequenceReset-GapFill and there are no reports to replay.
+        println!("  -> [PAPER] ResendRequest on {} for {}..={}: gap filled", session, from, to);
+        Vec::new()
+    }
+}
+
+// --- Live Venues ---
+
+/// The FIX sessions to CME Globex.
+#[cfg(feature = "live-venues")]
+pub struct CmeVenue {
+    /// The session orders are sent on.
+    active_session: Mutex<String>,
+    /// Stands in for the venue's MsgSeqNum until there is a real session.
+    seq: std::sync::atomic::AtomicU64,
+    instruments: ReferenceData,
+    symbology: Symbology,
+}
+
+#[cfg(feature = "live-venues")]
+impl CmeVenue {
+    fn connect() -> CmeVenue {
+        assert_eq!(
+            TradingMode::from_env(),
+            TradingMode::Live,
+            "refusing to load a live venue adapter outside live mode"
+        );
+        println!("Connecting FIX session to CME Globex...");
+        // In a real system:
+        // let session = fix::Session::logon(CME_SESSION_CONFIG).await.unwrap();
+        CmeVenue {
+            active_session: Mutex::new(Self::SESSIONS[0].to_string()),
+            seq: Default::default(),
+            instruments: ReferenceData::new(quantumarb_refdata::load_from_env()),
+            symbology: Symbology::new(symbology::load_mappings_from_env()),
+        }
+    }
+
+    /// Primary and backup market segment gateway sessions.
+    const SESSIONS: [&'static str; 2] = ["CME-PRIMARY", "CME-BACKUP"];
+}
+
+#[cfg(feature = "live-venues")]
+impl VenueAdapter for CmeVenue {
+    fn name(&self) -> &'static str {
+        "CME Globex"
+    }
+
+    fn send(&self, order: &InboundOrder, path: NetworkPath) {
+        println!(
+            "  -> [LIVE] Sending NewOrderSingle on {} via [{:?}] path: Symbol {}, Size {}",
+            self.active_session.lock().unwrap(),
+            path,
+            venue_symbol(order, "CME", &self.instruments, &self.symbology),
+            order.size
+        );
+        // In a real system:
+        // sessions[active_session].send(fix::new_order_single(order)).unwrap();
+    }
+
+    fn execution_reports(&self, order: &InboundOrder) -> Vec<ExecutionReport> {
+        // In a real system fills arrive later as ExecutionReport (35=8)
+        // messages on the session; until then the order is working.
+        vec![ExecutionReport {
+            exchange_order_id: String::new(),
+            exec_id: String::new(),
+            internal_order_id: order.internal_order_id,
+            status: OrderStatus::SentToExchange,
+            filled_size: 0,
+            filled_price: 0,
+            reject: None,
+            stamps: order.stamps,
+            mode: TradingMode::Live,
+            cumulative_size: 0,
+            leaves_size: order.size,
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }]
+    }
+
+    fn cancel(&self, order: &InboundOrder) -> ExecutionReport {
+        println!("  -> [LIVE] Sending OrderCancelRequest for {}", order.internal_order_id);
+        // In a real system the cancel is confirmed by a later ExecutionReport:
+        // session.send(fix::order_cancel_request(order)).unwrap();
+        report_without_fill(order, OrderStatus::Canceled, TradingMode::Live)
+    }
+
+    fn amend(&self, order: &InboundOrder, size: u32, cumulative_size: u32) -> ExecutionReport {
+        println!("  -> [LIVE] Sending OrderCancelReplaceRequest for {}: quantity {}", order.internal_order_id, size);
+        // In a real system the amendment is confirmed by a later ExecutionReport:
+        // session.send(fix::order_cancel_replace_request(order, size)).unwrap();
+        amended_report(order, size, cumulative_size, TradingMode::Live)
+    }
+
+    fn sessions(&self) -> Vec<(&'static str, Vec<String>)> {
+        vec![("CME", Self::SESSIONS.iter().map(|session| session.to_string()).collect())]
+    }
+
+    fn heartbeat(&self, session: &str) -> Option<u64> {
+        // In a real system the answer is the venue's Heartbeat (35=0) echoing
+        // our TestReqID, or nothing within the interval:
+        // sessions[session].send(fix::test_request()).unwrap();
+        // sessions[session].await_heartbeat(interval).await.map(|heartbeat| heartbeat.msg_seq_num)
+        let _ = session;
+        Some(self.seq.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1)
+    }
+
+    fn logon(&self, session: &str) -> Result<u64, String> {
+        println!("  -> [LIVE] Sending Logon on {}", session);
+        // In a real system:
+        // let logon = sessions[session].logon().await.map_err(|e| e.to_string())?;
+        *self.active_session.lock().unwrap() = session.to_string();
+        Ok(self.seq.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1)
+    }
+
+    fn resend(&self, session: &str, from: u64, to: u64) -> Vec<ExecutionReport> {
+        println!("  -> [LIVE] Sending ResendRequest on {} for {}..={}", session, from, to);
+        // In a real system the resent ExecutionReports (PossDupFlag=Y) arrive
+        // on the session before any new message:
+        // sessions[session].send(fix::resend_request(from, to)).unwrap();
+        Vec::new()
+    }
+}
+
+/// How `venue` names the order's instrument now: its symbol in the
+/// symbology, or the canonical symbol where there is no mapping.
+pub fn venue_symbol(order: &InboundOrder, venue: &str, instruments: &ReferenceData, symbology: &Symbology) -> String {
+    instruments
+        .by_symbol(&order.instrument_symbol)
+        .and_then(|definition| symbology.to_venue(definition.instrument_id, venue, chrono::Utc::now()))
+        .map_or_else(|| order.instrument_symbol.clone(), |mapping| mapping.venue_symbol.clone())
+}
+
+/// A report that carries no fill (a cancel, a reject or an amendment).
+pub fn report_without_fill(order: &InboundOrder, status: OrderStatus, mode: TradingMode) -> ExecutionReport {
+    ExecutionReport {
+        exchange_order_id: String::new(),
+        exec_id: Uuid::new_v4().simple().to_string(),
+        internal_order_id: order.internal_order_id,
+        status,
+        filled_size: 0,
+        filled_price: 0,
+        reject: None,
+        stamps: order.stamps,
+        mode,
+        cumulative_size: 0,
+        leaves_size: 0,
+        liquidity: None,
+        fee: Money::ZERO,
+        transact_time_ns: utc_now_ns(),
+        exec_ref_id: String::new(),
+    }
+}
+
+/// The venue's acknowledgement of an amendment to `size`.
+pub fn amended_report(order: &InboundOrder, size: u32, cumulative_size: u32, mode: TradingMode) -> ExecutionReport {
+    let mut report = report_without_fill(order, OrderStatus::Replaced, mode);
+    report.cumulative_size = cumulative_size;
+    report.leaves_size = size.saturating_sub(cumulative_size);
+    report
+}
+
+/// Translates a FIX OrdRejReason (tag 103) into the shared reject taxonomy.
+pub fn map_venue_reject(ord_rej_reason: u32) -> Rejection {
+    let (code, text) = match ord_rej_reason {
+        1 => (RejectCode::VenueUnknownInstrument, "Unknown symbol"),
+        2 => (RejectCode::VenueMarketClosed, "Exchange closed"),
+        3 => (RejectCode::VenueInvalidQuantity, "Order exceeds limit"),
+        6 => (RejectCode::VenueDuplicateOrder, "Duplicate order"),
+        16 => (RejectCode::VenueInvalidPrice, "Price exceeds current price band"),
+        _ => (RejectCode::VenueOther, "Other"),
+    };
+    Rejection::new(code, format!("Venue reject (OrdRejReason={}): {}", ord_rej_reason, text))
+}
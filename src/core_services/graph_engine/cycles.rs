@@ -0,0 +1,68 @@
+/*
+ * QuantumArb 2.0 - Core Services: Arbitrage Cycle Detection
+ *
+ * File: src/core_services/graph_engine/cycles.rs
+ *
+ * Description:
+ * The exchange rate graph and the search for arbitrage cycles in it. Each
+ * quoted rate is an edge weighted by -ln(rate), so a cycle whose rates
+ * multiply to more than 1 - a round trip that ends with more than it started
+ * with - has a negative total weight. Bellman-Ford finds such a cycle among
+ * the currencies reachable from the starting one.
+ *
+ * benches/graph_cycles.rs times the search on larger graphs.
+ */
+
+use petgraph::algo::find_negative_cycle;
+use petgraph::graph::{DiGraph, NodeIndex};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageOpportunity {
+    /// Currencies in trading order, ending where the cycle started.
+    pub path: Vec<String>,
+    /// Amount ended with per unit started with.
+    pub profit_ratio: f64,
+}
+
+/// Exchange rates between currencies.
+#[derive(Default)]
+pub struct RateGraph {
+    graph: DiGraph<String, f64>,
+    nodes: HashMap<String, NodeIndex>,
+}
+
+impl RateGraph {
+    /// Sets the rate for converting one unit of `from` into `to`.
+    pub fn set_rate(&mut self, from: &str, to: &str, rate: f64) {
+        let (from, to) = (self.node(from), self.node(to));
+        self.graph.update_edge(from, to, -rate.ln());
+    }
+
+    fn node(&mut self, currency: &str) -> NodeIndex {
+        if let Some(index) = self.nodes.get(currency) {
+            return *index;
+        }
+        let index = self.graph.add_node(currency.to_string());
+        self.nodes.insert(currency.to_string(), index);
+        index
+    }
+
+    /// Finds an arbitrage cycle reachable from `start`, if there is one.
+    pub fn find_arbitrage(&self, start: &str) -> Option<ArbitrageOpportunity> {
+        let start = *self.nodes.get(start)?;
+        let cycle = find_negative_cycle(&self.graph, start)?;
+        let weight: f64 = cycle
+            .iter()
+            .zip(cycle.iter().cycle().skip(1))
+            .map(|(from, to)| {
+                let edge = self.graph.find_edge(*from, *to).expect("cycle follows graph edges");
+                self.graph[edge]
+            })
+            .sum();
+        let mut path: Vec<String> = cycle.iter().map(|index| self.graph[*index].clone()).collect();
+        path.push(path[0].clone());
+        Some(ArbitrageOpportunity { path, profit_ratio: (-weight).exp() })
+    }
+}
@@ -10,23 +10,23 @@
  * visible to simpler systems.
  *
  * This POC implements a detector for triangular arbitrage in FX markets by
- * searching for negative cycles in the graph of log-transformed exchange rates.
+ * searching for negative cycles in the graph of log-transformed exchange rates
+ * (see cycles.rs).
+ *
+ * Crypto quotes arrive under each venue's own symbol ("BTC-USD" on VENUE_A,
+ * "BTCUSD" on VENUE_B). The venue symbology (`quantumarb_refdata::symbology`,
+ * from QA_SYMBOLOGY_PATH or the built-in set) maps them to the canonical
+ * instrument, whose symbol and currency are the graph's nodes, so every
+ * venue's quote prices the same edge and the best one is kept. A quote on a
+ * symbol with no mapping is left out.
  */
 
-use petgraph::graph::{Graph, NodeIndex};
-use petgraph::algo::bellman_ford;
-use serde::Serialize;
-use std::collections::HashMap;
-use tokio::time::{self, Duration};
-use warp::Filter;
-
-// --- Data Structures ---
+mod cycles;
 
-#[derive(Debug, Clone, Serialize)]
-struct ArbitrageOpportunity {
-    path: Vec<String>,
-    profit_ratio: f64,
-}
+use cycles::RateGraph;
+use quantumarb_refdata::symbology::{self, Symbology};
+use quantumarb_refdata::ReferenceData;
+use std::collections::HashMap;
 
 // --- Main Application Logic ---
 
@@ -35,41 +35,46 @@ async fn main() {
     println!("--- Starting QuantumArb 2.0 Cross-Asset Graph Engine ---");
 
     // This would be updated in real-time from market data feeds
-    let mut exchange_rates = HashMap::new();
-    exchange_rates.insert(("USD", "EUR"), 0.92);
-    exchange_rates.insert(("EUR", "JPY"), 165.25);
+    let mut rates = RateGraph::default();
+    rates.set_rate("USD", "EUR", 0.92);
+    rates.set_rate("EUR", "JPY", 165.25);
     // This rate creates an arbitrage opportunity: 1/151.95 = 0.00658
-    exchange_rates.insert(("JPY", "USD"), 0.00665); 
-
-    // Build the graph
-    let mut graph = Graph::<&str, f64>::new();
-    let mut node_map = HashMap::new();
+    rates.set_rate("JPY", "USD", 0.00665);
 
-    for (from, to) in exchange_rates.keys() {
-        node_map.entry(from).or_insert_with(|| graph.add_node(from));
-        node_map.entry(to).or_insert_with(|| graph.add_node(to));
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
+    let symbology = Symbology::new(symbology::load_mappings_from_env());
+    let now = chrono::Utc::now();
+    // (venue, venue symbol, bid, ask)
+    let venue_quotes = [
+        ("VENUE_A", "BTC-USD", 60_000.0, 60_001.0),
+        ("VENUE_B", "BTCUSD", 60_000.5, 60_002.0),
+        ("VENUE_A", "ETH-USD", 3_000.0, 3_000.2),
+        ("VENUE_B", "ETH/USD", 2_999.9, 3_000.1),
+        ("VENUE_B", "SOLUSD", 150.0, 150.1),
+    ];
+    let mut best: HashMap<(String, String), f64> = HashMap::new();
+    for (venue, venue_symbol, bid, ask) in venue_quotes {
+        let definition = symbology.canonical_id(venue, venue_symbol, now).and_then(|id| instruments.get(id));
+        let Some(definition) = definition else {
+            println!("No instrument for {} on {}; quote left out.", venue_symbol, venue);
+            continue;
+        };
+        let (asset, currency) = (definition.symbol.clone(), definition.currency.clone());
+        for (pair, rate) in [((asset.clone(), currency.clone()), bid), ((currency, asset), 1.0 / ask)] {
+            let held = best.entry(pair).or_insert(rate);
+            *held = held.max(rate);
+        }
     }
-
-    for ((from, to), rate) in &exchange_rates {
-        let from_node = node_map[from];
-        let to_node = node_map[to];
-        // Use the negative logarithm of the rate as the edge weight
-        graph.add_edge(from_node, to_node, -rate.log(std::f64::consts::E));
+    for ((from, to), rate) in &best {
+        rates.set_rate(from, to, *rate);
     }
 
     // Use Bellman-Ford algorithm to detect negative cycles
     println!("Searching for arbitrage opportunities (negative cycles)...");
-    let start_node = node_map["USD"];
-    match bellman_ford(&graph, start_node) {
-        Ok(_) => println!("No arbitrage opportunities found."),
-        Err(e) => {
+    match rates.find_arbitrage("USD") {
+        None => println!("No arbitrage opportunities found."),
+        Some(opportunity) => {
             println!("ARBITRAGE DETECTED!");
-            // The error from bellman_ford in petgraph contains the cycle
-            // A real implementation would parse this to show the path.
-            let opportunity = ArbitrageOpportunity {
-                path: vec!["USD".to_string(), "EUR".to_string(), "JPY".to_string(), "USD".to_string()],
-                profit_ratio: 1.015, // Mock profit
-            };
             println!("  -> Path: {}", opportunity.path.join(" -> "));
             println!("  -> Profit: {:.2}%", (opportunity.profit_ratio - 1.0) * 100.0);
         }
@@ -0,0 +1,389 @@
+/*
+ * QuantumArb 2.0 - Core Services: Net Exposure Hedging
+ *
+ * File: src/core_services/hedging_engine/hedging.rs
+ *
+ * Description:
+ * Nets the book's delta per underlying and per currency and decides when
+ * and with what to hedge it.
+ *
+ * Exposures, in currency (see `net_exposures`):
+ *
+ *   underlying  spot positions count their market value towards their own
+ *               symbol; a rule's `members` (futures on the underlying, say)
+ *               count theirs towards the rule's underlying; options count
+ *               their delta notional towards the underlying named in their
+ *               reference data, priced as in the risk gateway's greeks check
+ *   currency    the market value of spot positions in a currency other than
+ *               the base currency. Futures and options, margined or paid for
+ *               in premium, are left out
+ *
+ * Each exposure with a rule is held to bands around zero:
+ *
+ *   |delta| <= band               nothing to do
+ *   band < |delta| <= hard band   hedged once it has stayed outside the band
+ *                                 for min_interval_secs, so that exposure
+ *                                 which reverts by itself is not paid for
+ *   |delta| > hard band           hedged at once
+ *
+ * where the hard band is HARD_BAND_MULTIPLE x band. A hedge brings the
+ * exposure back to `hedge_to` x band on its own side (half the band by
+ * default) rather than to zero: the next small move does not breach the
+ * band again, and the round trips a full hedge invites are not paid for.
+ * After a hedge, the rule waits min_interval_secs before its next soft
+ * hedge.
+ *
+ * A rule lists its hedge instruments (spot or futures) with their all-in
+ * cost in basis points of notional; the cheapest one with a price is used.
+ * One unit of an instrument hedges price x multiplier of an underlying,
+ * and multiplier units of a currency. Hedges are whole lots, rounded
+ * towards zero so a hedge never overshoots.
+ */
+
+use chrono::{DateTime, Duration, Utc};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{AssetClass, ReferenceData};
+use quantumarb_risk::greeks::OptionMarket;
+use quantumarb_types::Position;
+use quantumarb_wire::OrderSide;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Multiple of a rule's band past which exposure is hedged at once.
+pub const HARD_BAND_MULTIPLE: f64 = 2.0;
+
+// --- Configuration ---
+
+/// What an exposure is to: an underlying or a currency.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Exposure {
+    Underlying(String),
+    Currency(String),
+}
+
+impl std::fmt::Display for Exposure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Exposure::Underlying(symbol) => write!(f, "{}", symbol),
+            Exposure::Currency(currency) => write!(f, "{} currency", currency),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct HedgeInstrument {
+    pub symbol: String,
+    /// All-in cost of trading it (spread, fees, impact), in basis points of
+    /// notional.
+    pub cost_bps: f64,
+    #[serde(default = "default_venue_id")]
+    pub venue_id: u32,
+}
+
+fn default_venue_id() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct HedgeRule {
+    pub exposure: Exposure,
+    /// Instruments whose market value counts towards the underlying.
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// Net exposure tolerated without a hedge, in currency.
+    pub band: f64,
+    /// Where a hedge leaves the exposure, as a fraction of the band.
+    #[serde(default = "default_hedge_to")]
+    pub hedge_to: f64,
+    pub min_interval_secs: u64,
+    pub instruments: Vec<HedgeInstrument>,
+}
+
+fn default_hedge_to() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct HedgeConfig {
+    pub base_currency: String,
+    pub rules: Vec<HedgeRule>,
+}
+
+impl HedgeConfig {
+    /// Loads the rules from QA_HEDGE_CONFIG_PATH (JSON), or the built-in set:
+    /// BTC hedged with spot, ES with the December future.
+    pub fn from_env() -> HedgeConfig {
+        if let Ok(path) = std::env::var("QA_HEDGE_CONFIG_PATH") {
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()));
+            match loaded {
+                Ok(config) => return config,
+                Err(e) => println!("Failed to read hedge rules from {} ({}); using the built-in set.", path, e),
+            }
+        }
+        let instrument =
+            |symbol: &str, cost_bps, venue_id| HedgeInstrument { symbol: symbol.to_string(), cost_bps, venue_id };
+        HedgeConfig {
+            base_currency: "USD".to_string(),
+            rules: vec![
+                HedgeRule {
+                    exposure: Exposure::Underlying("BTC".to_string()),
+                    members: Vec::new(),
+                    band: 250_000.0,
+                    hedge_to: default_hedge_to(),
+                    min_interval_secs: 60,
+                    instruments: vec![instrument("BTC", 6.0, 1)],
+                },
+                HedgeRule {
+                    exposure: Exposure::Underlying("ES".to_string()),
+                    members: vec!["ESZ25".to_string()],
+                    band: 500_000.0,
+                    hedge_to: default_hedge_to(),
+                    min_interval_secs: 300,
+                    instruments: vec![instrument("ESZ25", 1.5, 3)],
+                },
+            ],
+        }
+    }
+}
+
+// --- Exposures ---
+
+/// One exposure netted across the book.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct NetExposure {
+    pub exposure: Exposure,
+    pub delta: f64,
+    /// Delta by contributing symbol.
+    pub contributions: HashMap<String, f64>,
+}
+
+/// Nets the book's delta per underlying and currency, largest first. A
+/// position with no price, or an option whose underlying has none, is left
+/// out.
+pub fn net_exposures(
+    config: &HedgeConfig,
+    positions: &[Position],
+    instruments: &ReferenceData,
+    options: &OptionMarket,
+    now: DateTime<Utc>,
+) -> Vec<NetExposure> {
+    let member_of: HashMap<&str, &Exposure> = config
+        .rules
+        .iter()
+        .flat_map(|rule| rule.members.iter().map(move |member| (member.as_str(), &rule.exposure)))
+        .collect();
+    let mut exposures: HashMap<Exposure, NetExposure> = HashMap::new();
+    let mut add = |exposure: Exposure, symbol: &str, delta: f64| {
+        let net = exposures.entry(exposure.clone()).or_insert_with(|| NetExposure {
+            exposure,
+            delta: 0.0,
+            contributions: HashMap::new(),
+        });
+        net.delta += delta;
+        *net.contributions.entry(symbol.to_string()).or_default() += delta;
+    };
+    for position in positions.iter().filter(|p| p.quantity != 0) {
+        let definition = instruments.by_symbol(&position.symbol);
+        if let Some(terms) = definition.and_then(|d| d.option.as_ref()) {
+            let exposure = definition.map(|d| options.exposure(d, position.quantity, now));
+            if let Some(Ok(Some(greeks))) = exposure {
+                add(Exposure::Underlying(terms.underlying.clone()), &position.symbol, greeks.delta_notional.to_f64());
+            }
+            continue;
+        }
+        if position.current_market_price.is_zero() {
+            continue;
+        }
+        let value = position.quantity as f64 * position.current_market_price.to_f64() * position.multiplier.to_f64();
+        let underlying = match member_of.get(position.symbol.as_str()) {
+            Some(exposure) => (*exposure).clone(),
+            None => Exposure::Underlying(position.symbol.clone()),
+        };
+        add(underlying, &position.symbol, value);
+        let spot = definition.is_some_and(|d| !matches!(d.asset_class, AssetClass::Future | AssetClass::Option));
+        let currency = definition.map(|d| d.currency.as_str()).filter(|c| *c != config.base_currency);
+        if let (true, Some(currency)) = (spot, currency) {
+            add(Exposure::Currency(currency.to_string()), &position.symbol, value);
+        }
+    }
+    let mut exposures: Vec<NetExposure> = exposures.into_values().collect();
+    exposures.sort_by(|a, b| b.delta.abs().total_cmp(&a.delta.abs()).then_with(|| a.exposure.cmp(&b.exposure)));
+    exposures
+}
+
+// --- Decisions ---
+
+/// An order that would bring an exposure back inside its band.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct HedgeOrder {
+    pub symbol: String,
+    pub instrument_id: u32,
+    pub venue_id: u32,
+    pub side: OrderSide,
+    pub quantity: u32,
+    pub price: f64,
+    pub delta_before: f64,
+    pub delta_after: f64,
+    pub estimated_cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+#[serde(tag = "decision", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HedgeDecision {
+    WithinBand,
+    /// Outside the band, but not for long enough.
+    Waiting { until: DateTime<Utc> },
+    /// None of the rule's instruments has a price.
+    NoPrice,
+    /// The hedge rounds to less than a lot.
+    TooSmall,
+    Hedge(HedgeOrder),
+}
+
+/// When each exposure left its band and was last hedged.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HedgeTimers {
+    outside_since: HashMap<Exposure, DateTime<Utc>>,
+    last_hedged: HashMap<Exposure, DateTime<Utc>>,
+}
+
+impl HedgeTimers {
+    /// Decides what to do about `delta` under `rule` now, given instrument
+    /// prices by symbol. A hedge is taken to be sent.
+    pub fn decide(
+        &mut self,
+        rule: &HedgeRule,
+        delta: f64,
+        prices: &HashMap<String, f64>,
+        instruments: &ReferenceData,
+        now: DateTime<Utc>,
+    ) -> HedgeDecision {
+        let exposure = &rule.exposure;
+        if delta.abs() <= rule.band {
+            self.outside_since.remove(exposure);
+            return HedgeDecision::WithinBand;
+        }
+        let since = *self.outside_since.entry(exposure.clone()).or_insert(now);
+        if delta.abs() <= rule.band * HARD_BAND_MULTIPLE {
+            let interval = Duration::seconds(rule.min_interval_secs as i64);
+            let last_hedged = self.last_hedged.get(exposure).copied();
+            let until = (since + interval).max(last_hedged.map_or(now, |at| at + interval));
+            if until > now {
+                return HedgeDecision::Waiting { until };
+            }
+        }
+        let cheapest = rule
+            .instruments
+            .iter()
+            .filter_map(|hedge| {
+                let definition = instruments.by_symbol(&hedge.symbol)?;
+                let price = prices.get(&hedge.symbol).copied().filter(|p| *p > 0.0)?;
+                Some((hedge, definition, price))
+            })
+            .min_by(|a, b| a.0.cost_bps.total_cmp(&b.0.cost_bps));
+        let Some((hedge, definition, price)) = cheapest else {
+            return HedgeDecision::NoPrice;
+        };
+        let multiplier = definition.multiplier.to_f64();
+        let unit_delta = match exposure {
+            Exposure::Underlying(_) => price * multiplier,
+            Exposure::Currency(_) => multiplier,
+        };
+        let target = delta.signum() * rule.band * rule.hedge_to;
+        let lot = definition.lot_size.max(1) as f64;
+        let lots = ((delta - target).abs() / unit_delta / lot).floor();
+        if lots < 1.0 {
+            return HedgeDecision::TooSmall;
+        }
+        let quantity = lots * lot;
+        let hedged = quantity * unit_delta * delta.signum();
+        self.outside_since.remove(exposure);
+        self.last_hedged.insert(exposure.clone(), now);
+        HedgeDecision::Hedge(HedgeOrder {
+            symbol: hedge.symbol.clone(),
+            instrument_id: definition.instrument_id,
+            venue_id: hedge.venue_id,
+            side: if delta > 0.0 { OrderSide::Sell } else { OrderSide::Buy },
+            quantity: quantity as u32,
+            price,
+            delta_before: delta,
+            delta_after: delta - hedged,
+            estimated_cost: quantity * price * multiplier * hedge.cost_bps / 10_000.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_money::{Money, Price};
+    use quantumarb_risk::pricing::ImpliedVols;
+
+    fn position(symbol: &str, quantity: i64, price: f64, multiplier: f64) -> Position {
+        Position {
+            symbol: symbol.to_string(),
+            quantity,
+            cost_basis: Money::default(),
+            average_entry_price: Price::from_f64(price),
+            current_market_price: Price::from_f64(price),
+            unrealized_pnl: Money::default(),
+            multiplier: Money::from_f64(multiplier),
+            venue_quantities: HashMap::new(),
+            counterparty_quantities: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn exposures_net_across_instruments_and_are_hedged_outside_their_bands() {
+        let config = HedgeConfig::from_env();
+        let instruments = ReferenceData::seeded();
+        let vols = ImpliedVols { by_underlying: HashMap::new(), default: 0.6 };
+        let spots = HashMap::from([("BTC".to_string(), 60_000.0)]);
+        let options = OptionMarket { spots, vols, curve: None };
+        let now = DateTime::from_timestamp(1_767_225_600, 0).unwrap();
+        let book = [
+            position("BTC", 10, 60_000.0, 1.0),
+            position("BTC-26DEC26-70000-C", 20, 9_000.0, 1.0),
+            position("ESZ25", -3, 6_000.0, 50.0),
+            position("INVT", 1_000, 150.0, 1.0),
+        ];
+
+        let exposures = net_exposures(&config, &book, &instruments, &options, now);
+        let btc = exposures.iter().find(|e| e.exposure == Exposure::Underlying("BTC".to_string())).unwrap();
+        let call_delta = btc.contributions["BTC-26DEC26-70000-C"];
+        assert!(call_delta > 0.0 && call_delta < 20.0 * 60_000.0, "{:?}", btc);
+        assert!((btc.delta - (600_000.0 + call_delta)).abs() < 1e-6);
+        let find = |underlying: &str| exposures.iter().find(|e| e.exposure == Exposure::Underlying(underlying.into()));
+        let es = find("ES").unwrap();
+        assert_eq!(es.delta, -900_000.0);
+        assert_eq!(find("INVT").unwrap().delta, 150_000.0);
+        assert!(exposures.iter().all(|e| !matches!(e.exposure, Exposure::Currency(_))));
+
+        // BTC is past its hard band: sold back to half the band at once.
+        let prices = HashMap::from([("BTC".to_string(), 60_000.0), ("ESZ25".to_string(), 6_000.0)]);
+        let mut timers = HedgeTimers::default();
+        let HedgeDecision::Hedge(order) = timers.decide(&config.rules[0], btc.delta, &prices, &instruments, now) else {
+            panic!("BTC not hedged");
+        };
+        assert_eq!((order.side, order.symbol.as_str()), (OrderSide::Sell, "BTC"));
+        assert_eq!(order.quantity as f64, ((btc.delta - 125_000.0) / 60_000.0).floor());
+        assert!(order.delta_after >= 125_000.0 && order.delta_after < 185_000.0, "{:?}", order);
+
+        // ES is short past its band but inside the hard band: bought once it stays out.
+        let es_rule = &config.rules[1];
+        let waiting = timers.decide(es_rule, es.delta, &prices, &instruments, now);
+        assert_eq!(waiting, HedgeDecision::Waiting { until: now + Duration::seconds(300) });
+        let later = now + Duration::seconds(300);
+        let HedgeDecision::Hedge(order) = timers.decide(es_rule, es.delta, &prices, &instruments, later) else {
+            panic!("ES not hedged");
+        };
+        assert_eq!((order.side, order.quantity), (OrderSide::Buy, 2));
+        assert_eq!(order.estimated_cost, 2.0 * 300_000.0 * 1.5 / 10_000.0);
+        assert_eq!(timers.decide(es_rule, -400_000.0, &prices, &instruments, later), HedgeDecision::WithinBand);
+        let unpriced = timers.decide(es_rule, -2_000_000.0, &HashMap::new(), &instruments, later);
+        assert_eq!(unpriced, HedgeDecision::NoPrice);
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * QuantumArb 2.0 - Core Services: Hedging Engine
+ *
+ * File: src/core_services/hedging_engine/main.rs
+ *
+ * Description:
+ * This microservice keeps the book's net exposure per underlying and per
+ * currency inside configured bands by hedging it automatically. Every
+ * QA_HEDGE_INTERVAL_SECS it:
+ *
+ * 1. Takes the current positions from the portfolio manager (GET
+ *    /portfolio) and nets their delta per underlying and currency: spot at
+ *    market value, futures towards the underlying their rule names, options
+ *    at their Black-Scholes delta (QA_OPTION_VOLS, as in the risk gateway).
+ * 2. Holds each exposure with a rule to its bands, and picks the cheapest of
+ *    the rule's hedge instruments, spot or futures (see `hedging.rs`).
+ * 3. Sends each hedge to the risk gateway as an order on 'orders.requests'
+ *    with the HEDGE priority class, so it is checked ahead of other flow and
+ *    is not charged to a capital allocation. The order id is the bus dedup
+ *    key.
+ *
+ * Instruments are priced at their marks in the portfolio snapshot; with
+ * QA_FEATURE_REDIS_URL set, ones the book does not hold are priced at their
+ * mid in the feature store.
+ *
+ * The exposures, the latest decision per rule and the hedges sent are
+ * served on GET /hedging. GET /openapi.json serves the OpenAPI document,
+ * browsable on GET /docs.
+ *
+ * Configuration (environment):
+ *   QA_PORTFOLIO_MANAGER_URL=     portfolio manager base URL
+ *   QA_HEDGE_CONFIG_PATH=         JSON hedge rules (unset: the built-in set)
+ *   QA_HEDGE_ACCOUNT_ID=101       account the hedges are booked to
+ *   QA_HEDGE_INTERVAL_SECS=10     time between checks
+ *   QA_FEATURE_REDIS_URL=         feature store, for unheld instruments
+ */
+
+mod hedging;
+
+use chrono::Utc;
+use hedging::{Exposure, HedgeConfig, HedgeDecision, HedgeOrder, HedgeTimers, NetExposure};
+use quantumarb_bus::topics;
+use quantumarb_money::Price;
+use quantumarb_openapi::{ApiDoc, ApiSchema};
+use quantumarb_refdata::ReferenceData;
+use quantumarb_risk::greeks::OptionMarket;
+use quantumarb_risk::pricing::ImpliedVols;
+use quantumarb_types::{PortfolioSnapshot, Position};
+use quantumarb_wire::{Encoding, HopStamps, OrderPriority, OrderRequest, TradingMode};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+use warp::Filter;
+
+// --- Configuration ---
+
+const DEFAULT_PORTFOLIO_MANAGER_URL: &str = "http://portfolio-manager.default.svc.cluster.local";
+const STRATEGY_NAME: &str = "auto_hedger";
+/// Hedges kept for GET /hedging.
+const RECENT_HEDGES: usize = 100;
+
+// --- Data Structures ---
+
+/// Response body of GET /hedging.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct HedgingStatus {
+    config: HedgeConfig,
+    account_id: u32,
+    exposures: Vec<NetExposure>,
+    decisions: Vec<(Exposure, HedgeDecision)>,
+    /// Newest last.
+    recent_hedges: VecDeque<HedgeOrder>,
+    hedges_sent: u64,
+    estimated_cost: f64,
+    last_run_utc: Option<String>,
+}
+
+type SharedStatus = Arc<RwLock<HedgingStatus>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Hedging Engine ---");
+
+    let config = HedgeConfig::from_env();
+    for rule in &config.rules {
+        let instruments: Vec<&str> = rule.instruments.iter().map(|i| i.symbol.as_str()).collect();
+        println!("Hedging {} outside +-{:.0} with {}", rule.exposure, rule.band, instruments.join(", "));
+    }
+    let account_id = std::env::var("QA_HEDGE_ACCOUNT_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(101);
+    let status: SharedStatus = Arc::new(RwLock::new(HedgingStatus {
+        config,
+        account_id,
+        exposures: Vec::new(),
+        decisions: Vec::new(),
+        recent_hedges: VecDeque::new(),
+        hedges_sent: 0,
+        estimated_cost: 0.0,
+        last_run_utc: None,
+    }));
+
+    let hedger_status = status.clone();
+    tokio::spawn(async move {
+        run_hedger(hedger_status).await;
+    });
+
+    let get = warp::path!("hedging").and(warp::get()).and(with_state(status)).and_then(handler_get_status);
+
+    println!("API server running at http://127.0.0.1:3045/hedging");
+    warp::serve(get.or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3045)).await;
+}
+
+/// The OpenAPI document of the hedging endpoint.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Hedging Engine", "Net exposures and the hedges taken to keep them within their bands.")
+        .get::<HedgingStatus>("/hedging", "Exposures, decisions and recent hedges")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /hedging.
+async fn handler_get_status(status: SharedStatus) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&*status.read().unwrap()))
+}
+
+/// Nets the book's exposures every interval and hedges those outside their
+/// bands.
+async fn run_hedger(status: SharedStatus) {
+    let http_client = quantumarb_tenancy::firm_client();
+    let portfolio_manager =
+        std::env::var("QA_PORTFOLIO_MANAGER_URL").unwrap_or_else(|_| DEFAULT_PORTFOLIO_MANAGER_URL.to_string());
+    let interval_secs = std::env::var("QA_HEDGE_INTERVAL_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(10);
+    let (config, account_id) = {
+        let status = status.read().unwrap();
+        (status.config.clone(), status.account_id)
+    };
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
+    let vols = ImpliedVols::from_env();
+    let mut feature_store = connect_feature_store().await;
+    let (encoding, mode) = (Encoding::from_env(), TradingMode::from_env());
+    let mut timers = HedgeTimers::default();
+    let mut interval = time::interval(Duration::from_secs(u64::max(interval_secs, 1)));
+    loop {
+        interval.tick().await;
+        println!("\nChecking net exposures...");
+
+        let url = format!("{}/portfolio", portfolio_manager.trim_end_matches('/'));
+        let snapshot = match http_client.get(&url).send().await {
+            Ok(response) => response.json::<PortfolioSnapshot>().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                println!("  -> No portfolio snapshot ({}); skipping this check.", e);
+                continue;
+            }
+        };
+        let positions: Vec<Position> = snapshot.positions.into_values().collect();
+        let mut prices: HashMap<String, f64> = positions
+            .iter()
+            .filter(|p| !p.current_market_price.is_zero())
+            .map(|p| (p.symbol.clone(), p.current_market_price.to_f64()))
+            .collect();
+        if let Some(store) = feature_store.as_mut() {
+            let mut unpriced: Vec<String> = config
+                .rules
+                .iter()
+                .flat_map(|rule| rule.instruments.iter().map(|i| i.symbol.clone()))
+                .chain(instruments.all().into_iter().filter_map(|d| d.option.map(|terms| terms.underlying)))
+                .filter(|symbol| !prices.contains_key(symbol))
+                .collect();
+            unpriced.sort();
+            unpriced.dedup();
+            for symbol in unpriced {
+                let latest = quantumarb_features::read_latest(store, &symbol).await;
+                if let Ok(Some(mid)) = latest.map(|features| features.and_then(|f| f.mid)) {
+                    prices.insert(symbol, mid);
+                }
+            }
+        }
+
+        let now = Utc::now();
+        let options = OptionMarket { spots: prices.clone(), vols: vols.clone(), curve: None };
+        let exposures = hedging::net_exposures(&config, &positions, &instruments, &options, now);
+        for exposure in &exposures {
+            println!("  -> {}: net {:+.0}", exposure.exposure, exposure.delta);
+        }
+        let mut decisions = Vec::new();
+        let mut hedges = Vec::new();
+        for rule in &config.rules {
+            let delta = exposures.iter().find(|e| e.exposure == rule.exposure).map_or(0.0, |e| e.delta);
+            let decision = timers.decide(rule, delta, &prices, &instruments, now);
+            match &decision {
+                HedgeDecision::Hedge(order) => {
+                    send_hedge(order, account_id, &instruments, encoding, mode);
+                    hedges.push(order.clone());
+                }
+                HedgeDecision::Waiting { until } => {
+                    println!("  -> {} outside its band; hedging at {} if it stays out.", rule.exposure, until)
+                }
+                HedgeDecision::NoPrice => {
+                    println!("  -> {} outside its band, but no hedge instrument has a price.", rule.exposure)
+                }
+                HedgeDecision::TooSmall | HedgeDecision::WithinBand => {}
+            }
+            decisions.push((rule.exposure.clone(), decision));
+        }
+
+        let mut status = status.write().unwrap();
+        status.exposures = exposures;
+        status.decisions = decisions;
+        for hedge in hedges {
+            status.hedges_sent += 1;
+            status.estimated_cost += hedge.estimated_cost;
+            if status.recent_hedges.len() == RECENT_HEDGES {
+                status.recent_hedges.pop_front();
+            }
+            status.recent_hedges.push_back(hedge);
+        }
+        status.last_run_utc = Some(now.to_rfc3339());
+    }
+}
+
+/// Connects to QA_FEATURE_REDIS_URL, if set.
+async fn connect_feature_store() -> Option<redis::aio::MultiplexedConnection> {
+    let url = std::env::var("QA_FEATURE_REDIS_URL").ok().filter(|v| !v.is_empty())?;
+    let connection = match redis::Client::open(url.as_str()) {
+        Ok(client) => client.get_multiplexed_async_connection().await,
+        Err(e) => Err(e),
+    };
+    connection.map_err(|e| println!("Feature store unavailable at {} ({}); pricing at marks only.", url, e)).ok()
+}
+
+/// Sends a hedge to the risk gateway.
+fn send_hedge(hedge: &HedgeOrder, account_id: u32, instruments: &ReferenceData, encoding: Encoding, mode: TradingMode) {
+    let Some(definition) = instruments.get(hedge.instrument_id) else {
+        return;
+    };
+    let order = OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id,
+        instrument_id: hedge.instrument_id,
+        side: hedge.side,
+        price: definition.wire_price(Price::from_f64(hedge.price)),
+        size: hedge.quantity,
+        stamps: HopStamps::default(),
+        venue_id: hedge.venue_id,
+        mode,
+        priority: OrderPriority::Hedge,
+        strategy_id: STRATEGY_NAME.to_string(),
+    };
+    println!(
+        "  -> Hedging: {:?} {} {} (net {:+.0} -> {:+.0}, est. cost {:.2})",
+        hedge.side, hedge.quantity, hedge.symbol, hedge.delta_before, hedge.delta_after, hedge.estimated_cost
+    );
+    quantumarb_bus::publish_deduplicated(topics::ORDER_REQUESTS, &encoding.encode(&order), &order.order_id.to_string());
+}
@@ -8,71 +8,253 @@
  * (e.g., a primary microwave link and a backup fiber link) to a specific
  * destination, like an exchange's matching engine.
  *
- * It exposes a simple API endpoint that other services, primarily the
- * exchange_gateway, can query to get the fastest currently available path
- * for sending an order.
+ * Each path's probes come from its own measurement source: synthetic,
+ * software-timed UDP echoes, or PTP-synchronized hardware timestamps from a
+ * capture file or the NIC's telemetry (see `measurement.rs`). Sources are
+ * polled every second, and the route is chosen on a score over
+ * its recent probes (latency percentiles, jitter and packet loss) with
+ * hysteresis, so the exchange gateway does not flap between routes on every
+ * probe (see `scoring.rs`). Rain forecast along the microwave link, from
+ * the data bus connector's 'alt_data.weather' feed, raises the microwave
+ * score ahead of the fade (see `weather.rs`). The API, on port 3030:
+ * - GET /fastest-path: the selected route, for the exchange_gateway.
+ * - GET /paths: every path's source, latest latency, window statistics and
+ * any rain penalty.
+ * - GET /paths/history?window=15m&points=300: per-second path scores and
+ * the selected route, downsampled, with every route switch in the window.
+ * - GET /openapi.json: the API's OpenAPI document, browsable on GET /docs.
  *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * warp = "0.3"
- * serde = { version = "1.0", features = ["derive"] }
- * rand = "0.8"
+ * Configuration (environment):
+ *   QA_PATH_HISTORY_SECS=3600   seconds of history kept (one point a second)
+ *   QA_ROUTE_*                  scoring and hysteresis, see `scoring.rs`
+ *   QA_WEATHER_*                rain fade prediction, see `weather.rs`
+ *   QA_LATENCY_SOURCE_MICROWAVE, QA_LATENCY_SOURCE_FIBER
+ *                               each path's source, see `measurement.rs`
+ *   QA_LATENCY_STALE_SECS=5     seconds without a probe from a source before
+ *                               each further second counts as a lost probe
+ *
+ * The synthetic jitter and packet loss are drawn from seeded streams when
+ * the oracle is started with --seed N (or QA_SEED), so path choices repeat
+ * across runs.
  */
 
-use serde::Serialize;
+mod measurement;
+mod scoring;
+mod weather;
+
+use chrono::{DateTime, Utc};
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_types::LinkWeatherForecast;
+use measurement::{MeasurementSource, SourceKind};
+use quantumarb_sim::Seed;
+use scoring::{PathStats, ProbeWindow, RouteSelector, RouteSwitch, ScoringConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use tokio::time::{self, Duration};
 use warp::Filter;
+use weather::{RainOutlook, WeatherConfig};
+
+const DEFAULT_HISTORY_SECS: usize = 3600;
+const DEFAULT_HISTORY_WINDOW_SECS: i64 = 900;
+const DEFAULT_HISTORY_POINTS: usize = 300;
+/// Route switches kept, however old.
+const MAX_SWITCHES: usize = 1000;
 
 // --- Data Structures ---
 
-#[derive(Debug, Clone, Serialize, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
 enum NetworkPath {
     Microwave,
     Fiber,
 }
 
-/// Represents the state of the network paths.
-/// In a real system, this would be updated by a background process
-/// that sends and receives custom ICMP or UDP packets to measure RTT.
-#[derive(Debug, Clone, Serialize)]
+/// Represents the state of one network path.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
 struct PathState {
     path: NetworkPath,
-    latency_us: u32, // Latency in microseconds
+    source: String,
+    source_kind: SourceKind,
+    latency_us: Option<u32>, // Last answered probe, in microseconds
+    stats: Option<PathStats>,
+    /// Forecast rain raising the path's score (microwave only).
+    rain: Option<RainOutlook>,
+    #[serde(skip)]
+    probes: ProbeWindow,
+}
+
+impl PathState {
+    fn new(path: NetworkPath, source: &dyn MeasurementSource) -> PathState {
+        PathState {
+            path,
+            source: source.describe(),
+            source_kind: source.kind(),
+            latency_us: None,
+            stats: None,
+            rain: None,
+            probes: ProbeWindow::default(),
+        }
+    }
+
+    /// The score routes are chosen on: the window score plus any rain penalty.
+    fn route_score(&self) -> Option<f64> {
+        Some(self.stats?.score + self.rain.as_ref().map_or(0.0, |r| r.penalty_us))
+    }
+}
+
+/// Body of a GET /fastest-path response. `latency_us` is the route's median
+/// over the scoring window.
+#[derive(Debug, Serialize, ApiSchema)]
+struct RouteResponse {
+    path: NetworkPath,
+    latency_us: u32,
+    score: f64,
+}
+
+/// One path at one point of the history.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct PathPoint {
+    path: NetworkPath,
+    /// The second's last probe; None when it got no answer, or there was none.
+    latency_us: Option<u32>,
+    /// The route score, rain penalty included.
+    score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct HistoryPoint {
+    timestamp_utc: DateTime<Utc>,
+    selected: Option<NetworkPath>,
+    paths: Vec<PathPoint>,
+}
+
+/// Body of a GET /paths/history response.
+#[derive(Debug, Serialize, ApiSchema)]
+struct PathHistory {
+    window_secs: i64,
+    points: Vec<HistoryPoint>,
+    switches: Vec<RouteSwitch>,
+}
+
+/// Query parameters for GET /paths/history.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct HistoryQuery {
+    window: Option<String>,
+    points: Option<usize>,
+}
+
+/// The paths, the selected route and their history, shared by the API and
+/// the monitoring loop.
+struct OracleState {
+    paths: Vec<PathState>,
+    selector: RouteSelector,
+    history: VecDeque<HistoryPoint>,
+    history_capacity: usize,
+    switches: VecDeque<RouteSwitch>,
+    /// The latest forecast along the microwave link.
+    weather: Option<LinkWeatherForecast>,
+}
+
+impl OracleState {
+    fn record(&mut self, point: HistoryPoint, switch: Option<RouteSwitch>) {
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(point);
+        if let Some(switch) = switch {
+            if self.switches.len() == MAX_SWITCHES {
+                self.switches.pop_front();
+            }
+            self.switches.push_back(switch);
+        }
+    }
 }
 
-/// The shared state that the API and the monitoring loop will use.
 /// We use Arc<Mutex> to allow safe concurrent access.
-type SharedState = Arc<Mutex<Vec<PathState>>>;
+type SharedState = Arc<Mutex<OracleState>>;
 
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Latency Oracle ---");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let config = ScoringConfig::from_env();
+    let history_capacity = std::env::var("QA_PATH_HISTORY_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(DEFAULT_HISTORY_SECS);
 
-    // Initialize the shared state with some default values.
-    let state = Arc::new(Mutex::new(vec![
-        PathState { path: NetworkPath::Microwave, latency_us: 4010 }, // ~4.01ms
-        PathState { path: NetworkPath::Fiber, latency_us: 4550 },     // ~4.55ms
-    ]));
+    // Each path's measurement source, and its state.
+    let sources: Vec<(NetworkPath, Box<dyn MeasurementSource>)> = [NetworkPath::Microwave, NetworkPath::Fiber]
+        .into_iter()
+        .map(|path| {
+            let rng = seed.stream(&format!("latency_oracle.jitter.{:?}", path).to_lowercase());
+            (path, measurement::source_from_env(path, rng))
+        })
+        .collect();
+    for (path, source) in &sources {
+        println!("Measuring {:?} with {}", path, source.describe());
+    }
+    let state = Arc::new(Mutex::new(OracleState {
+        paths: sources.iter().map(|(path, source)| PathState::new(*path, source.as_ref())).collect(),
+        selector: RouteSelector::default(),
+        history: VecDeque::with_capacity(history_capacity),
+        history_capacity,
+        switches: VecDeque::new(),
+        weather: None,
+    }));
 
-    // Spawn a background task to simulate latency monitoring.
-    let monitoring_state = state.clone();
+    // Spawn a background task to follow the weather along the microwave link.
+    let weather_state = state.clone();
     tokio::spawn(async move {
-        monitor_network_paths(monitoring_state).await;
+        listen_for_weather_forecasts(weather_state).await;
+    });
+
+    // Monitor on a thread of its own: sources may block while they measure.
+    let monitoring_state = state.clone();
+    let sources = sources.into_iter().map(|(_, source)| source).collect();
+    tokio::task::spawn_blocking(move || {
+        monitor_network_paths(monitoring_state, sources, config, WeatherConfig::from_env());
     });
 
     // --- API Endpoint Definition ---
-    // GET /fastest-path -> returns the path with the lowest latency.
+    // GET /fastest-path -> returns the selected route.
     let get_fastest_path = warp::path("fastest-path")
         .and(warp::get())
-        .and(with_state(state))
+        .and(with_state(state.clone()))
         .and_then(handler_get_fastest_path);
+    // GET /paths -> every path's latest statistics.
+    let get_paths = warp::path!("paths")
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_get_paths);
+    // GET /paths/history -> path scores and route switches over a window.
+    let get_history = warp::path!("paths" / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .and(with_state(state))
+        .and_then(handler_get_history);
+
+    println!("API server running at http://127.0.0.1:3030 (/fastest-path, /paths, /paths/history)");
+    let routes = get_fastest_path.or(get_paths).or(get_history).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+}
 
-    println!("API server running at http://127.0.0.1:3030/fastest-path");
-    warp::serve(get_fastest_path).run(([127, 0, 0, 1], 3030)).await;
+/// The OpenAPI document of the path endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Latency Oracle", "Latency of the microwave and fiber paths, and the route to take.")
+        .errors::<ErrorBody>()
+        .get::<RouteResponse>("/fastest-path", "The selected route")
+        .get::<Vec<PathState>>("/paths", "Every path's latency, window statistics and rain penalty")
+        .operation(
+            Operation::get("/paths/history", "Path scores and route switches over time")
+                .query::<HistoryQuery>()
+                .response::<PathHistory>(),
+        )
 }
 
 /// Warp filter to inject the shared state into the handler.
@@ -80,39 +262,176 @@ fn with_state(state: SharedState) -> impl Filter<Extract = (SharedState,), Error
     warp::any().map(move || state.clone())
 }
 
+fn error_reply(code: RejectCode, message: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    let rejection = Rejection::new(code, message);
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
 /// The handler function for the /fastest-path endpoint.
 async fn handler_get_fastest_path(state: SharedState) -> Result<impl warp::Reply, warp::Rejection> {
-    let paths = state.lock().unwrap();
-    
-    // Find the path with the minimum latency.
-    let fastest_path = paths.iter().min_by_key(|p| p.latency_us).unwrap();
+    let state = state.lock().unwrap();
+    let selected = state.selector.current().and_then(|current| {
+        let path = state.paths.iter().find(|p| p.path == current)?;
+        Some(RouteResponse { path: current, latency_us: path.stats?.p50_us, score: path.route_score()? })
+    });
+    let Some(route) = selected else {
+        return Ok(error_reply(RejectCode::SystemStateUnavailable, "No path is answering probes."));
+    };
+
+    println!("  -> API Request: Selected path is {:?} with {}µs median latency.", route.path, route.latency_us);
+    Ok(warp::reply::with_status(warp::reply::json(&route), warp::http::StatusCode::OK))
+}
 
-    println!("  -> API Request: Fastest path is {:?} with {}µs latency.", fastest_path.path, fastest_path.latency_us);
-    Ok(warp::reply::json(fastest_path))
+/// Handler for GET /paths.
+async fn handler_get_paths(state: SharedState) -> Result<impl warp::Reply, warp::Rejection> {
+    let paths = state.lock().unwrap().paths.clone();
+    Ok(warp::reply::json(&paths))
 }
 
-/// Background task to simulate continuous monitoring of network paths.
-async fn monitor_network_paths(state: SharedState) {
-    let mut interval = time::interval(Duration::from_secs(1));
+/// Handler for GET /paths/history?window=15m&points=300.
+async fn handler_get_history(query: HistoryQuery, state: SharedState) -> Result<impl warp::Reply, warp::Rejection> {
+    let window_secs = match query.window.as_deref().map(parse_window) {
+        None => DEFAULT_HISTORY_WINDOW_SECS,
+        Some(Some(secs)) => secs,
+        Some(None) => {
+            return Ok(error_reply(RejectCode::SystemInvalidRequest, "window must look like 90s, 30m, 24h or 7d."));
+        }
+    };
+    let max_points = query.points.unwrap_or(DEFAULT_HISTORY_POINTS).max(1);
+
+    let since = Utc::now() - chrono::Duration::seconds(window_secs);
+    let state = state.lock().unwrap();
+    let in_window: Vec<&HistoryPoint> = state.history.iter().filter(|p| p.timestamp_utc >= since).collect();
+    // Every n-th point; the switches below are complete, so no route change is lost.
+    let stride = in_window.len().div_ceil(max_points).max(1);
+    let points = in_window.into_iter().step_by(stride).cloned().collect();
+    let switches = state
+        .switches
+        .iter()
+        .filter(|s| DateTime::parse_from_rfc3339(&s.timestamp_utc).is_ok_and(|t| t >= since))
+        .cloned()
+        .collect();
+
+    let response = PathHistory { window_secs, points, switches };
+    Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK))
+}
+
+/// Parses a window such as "90s", "30m", "24h" or "7d" into seconds.
+fn parse_window(window: &str) -> Option<i64> {
+    let (value, unit) = window.split_at(window.char_indices().last()?.0);
+    let value: i64 = value.parse().ok().filter(|v| *v > 0)?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    Some(value * multiplier)
+}
+
+/// Polls every path's source once a second, rescores the paths and
+/// re-evaluates the route. `sources` are in the order of the state's paths.
+fn monitor_network_paths(
+    state: SharedState,
+    mut sources: Vec<Box<dyn MeasurementSource>>,
+    config: ScoringConfig,
+    weather_config: WeatherConfig,
+) {
+    let stale_polls: u32 = std::env::var("QA_LATENCY_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(5);
+    let mut empty_polls = vec![0u32; sources.len()];
+    let mut next_poll = std::time::Instant::now();
     loop {
-        interval.tick().await;
+        next_poll += Duration::from_secs(1);
+        std::thread::sleep(next_poll.saturating_duration_since(std::time::Instant::now()));
 
-        let mut paths = state.lock().unwrap();
+        // Measure before taking the lock, so the API is never kept waiting on a probe.
+        let mut measured: Vec<Vec<Option<u32>>> = sources.iter_mut().map(|source| source.poll()).collect();
+        for (probes, empty) in measured.iter_mut().zip(empty_polls.iter_mut()) {
+            *empty = if probes.is_empty() { *empty + 1 } else { 0 };
+            if *empty > stale_polls {
+                probes.push(None);
+            }
+        }
+
+        let mut state = state.lock().unwrap();
         println!("\nMonitoring network paths...");
 
-        for path_state in paths.iter_mut() {
-            // Simulate random fluctuations in latency.
-            // Microwave is generally faster but more susceptible to jitter (e.g., from weather).
-            let jitter_us = match path_state.path {
-                NetworkPath::Microwave => rand::random::<i32>() % 100 - 50, // -50µs to +50µs
-                NetworkPath::Fiber => rand::random::<i32>() % 20 - 10,       // -10µs to +10µs
-            };
-            
-            // Apply the jitter, ensuring latency doesn't go below a baseline.
-            let new_latency = (path_state.latency_us as i32 + jitter_us).max(4000);
-            path_state.latency_us = new_latency as u32;
-
-            println!("  -> Path: {:?}, New Latency: {}µs", path_state.path, path_state.latency_us);
+        let now = Utc::now();
+        let rain = state.weather.as_ref().and_then(|forecast| weather::rain_outlook(forecast, now, &weather_config));
+        let mut point = HistoryPoint { timestamp_utc: now, selected: None, paths: Vec::new() };
+        for (path_state, probes) in state.paths.iter_mut().zip(measured) {
+            for probe in &probes {
+                path_state.probes.push(*probe, config.window);
+            }
+            if let Some(latency) = probes.iter().rev().flatten().next() {
+                path_state.latency_us = Some(*latency);
+            }
+            path_state.stats = path_state.probes.stats(&config);
+            if path_state.path == NetworkPath::Microwave {
+                if rain.is_some() && path_state.rain.is_none() {
+                    println!("  -> Rain forecast on the microwave link: {:?}", rain);
+                }
+                path_state.rain = rain.clone();
+            }
+
+            let lost = probes.iter().filter(|p| p.is_none()).count();
+            match path_state.latency_us {
+                Some(latency) if lost == 0 => println!("  -> Path: {:?}, New Latency: {}µs", path_state.path, latency),
+                _ if probes.is_empty() => println!("  -> Path: {:?}, no new probes", path_state.path),
+                _ => println!("  -> Path: {:?}, {} of {} probes lost", path_state.path, lost, probes.len()),
+            }
+            point.paths.push(PathPoint {
+                path: path_state.path,
+                latency_us: probes.last().copied().flatten(),
+                score: path_state.route_score().filter(|s| s.is_finite()),
+            });
+        }
+
+        let scores: Vec<(NetworkPath, f64)> =
+            state.paths.iter().filter_map(|p| Some((p.path, p.route_score()?))).collect();
+        let switch = state.selector.evaluate(&scores, &config);
+        if let Some(switch) = &switch {
+            println!("  -> Route switched from {:?} to {:?} (score {:.0})", switch.from, switch.to, switch.to_score);
+        }
+        point.selected = state.selector.current();
+        state.record(point, switch);
+    }
+}
+
+/// Simulates the subscription to 'alt_data.weather'.
+async fn listen_for_weather_forecasts(state: SharedState) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::WEATHER_FORECASTS).await.unwrap();
+    let mut interval = time::interval(Duration::from_secs(600));
+    let mut cycle: u64 = 0;
+    loop {
+        interval.tick().await;
+        cycle += 1;
+        // Every third forecast has a storm cell over Cleveland in twenty minutes.
+        let now = Utc::now();
+        let (rain, probability) = if cycle.is_multiple_of(3) { (8.4, 0.8) } else { (0.0, 0.05) };
+        let payload = format!(
+            r#"{{"link":"CHI-NJ","issued_utc":"{}","waypoints":[
+                {{"name":"Cleveland OH","latitude":41.50,"longitude":-81.69,"hours":[
+                    {{"starts_utc":"{}","precipitation_mm_per_hr":{},"probability":{}}}]}}]}}"#,
+            now.to_rfc3339(),
+            (now + chrono::Duration::minutes(20)).to_rfc3339(),
+            rain,
+            probability,
+        );
+        match serde_json::from_str::<LinkWeatherForecast>(&payload) {
+            Ok(forecast) => {
+                println!("\nReceived the weather forecast for link {}", forecast.link);
+                state.lock().unwrap().weather = Some(forecast);
+            }
+            Err(e) => println!("\nDropping a malformed weather forecast: {}", e),
         }
     }
 }
@@ -0,0 +1,475 @@
+/*
+ * QuantumArb 2.0 - Core Services: Latency Measurement Sources
+ *
+ * File: src/core_services/latency_oracle/measurement.rs
+ *
+ * Description:
+ * Where a path's probes come from. Each path has one `MeasurementSource`,
+ * chosen with QA_LATENCY_SOURCE_MICROWAVE and QA_LATENCY_SOURCE_FIBER, so
+ * synthetic, software and hardware measurements can be mixed:
+ *
+ *   synthetic                 the simulated random walk (the default)
+ *   software:HOST:PORT[@BIND] round trip of a UDP echo (RFC 862) to the far
+ *                             end, sent from BIND (the path's local
+ *                             interface address); latency is half the RTT
+ *   pcap:FILE                 hardware timestamps from a capture file that
+ *                             a PTP-synchronized capture card keeps
+ *                             appending to (nanosecond pcap, Ethernet)
+ *   telemetry:BIND            hardware timestamps pushed by the NIC's
+ *                             telemetry agent as UDP datagrams to BIND
+ *
+ * Hardware sources measure one way. The sender's NIC stamps each probe on
+ * egress with its PTP-disciplined clock (one-step, as for PTP Sync), and the
+ * receiving NIC stamps it on arrival, so latency = rx - tx with no host
+ * scheduling in it. A capture record is a UDP datagram whose payload is the
+ * probe, stamped on arrival by the capture card:
+ *
+ *   "QAPB" | sequence (u64, big endian) | tx_hw_ns (u64, big endian)
+ *
+ * and a telemetry datagram is the JSON {"seq": .., "tx_hw_ns": .., "rx_hw_ns": ..}.
+ * Sequence gaps count as lost probes. A probe stamped as arriving before it
+ * left means the two clocks have lost sync; it is dropped, not scored.
+ *
+ * Sources are polled from the monitoring thread, once a second, and may
+ * block it briefly (the software source waits up to
+ * QA_SOFTWARE_PROBE_TIMEOUT_MS=50 for its echo).
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_sim::SimRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use crate::NetworkPath;
+
+/// Most lost probes a single sequence gap is counted as, so a restarted
+/// sender does not flood the window.
+const MAX_GAP: u64 = 1_000;
+const PROBE_MAGIC: &[u8; 4] = b"QAPB";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SourceKind {
+    Synthetic,
+    Software,
+    Hardware,
+}
+
+/// A path's probes; None is a probe that got no answer.
+pub trait MeasurementSource: Send {
+    fn kind(&self) -> SourceKind;
+    /// How the source measures, for GET /paths and the logs.
+    fn describe(&self) -> String;
+    /// The probes completed since the last poll, oldest first. May be empty
+    /// when a hardware source has nothing new.
+    fn poll(&mut self) -> Vec<Option<u32>>;
+}
+
+/// Opens the source configured for `path`, falling back to the synthetic
+/// one (with a message) if the setting cannot be used.
+pub fn source_from_env(path: NetworkPath, rng: SimRng) -> Box<dyn MeasurementSource> {
+    let (variable, synthetic) = match path {
+        NetworkPath::Microwave => ("QA_LATENCY_SOURCE_MICROWAVE", SyntheticSource::microwave(rng)),
+        NetworkPath::Fiber => ("QA_LATENCY_SOURCE_FIBER", SyntheticSource::fiber(rng)),
+    };
+    let spec = std::env::var(variable).unwrap_or_else(|_| "synthetic".to_string());
+    match open_source(&spec) {
+        Ok(Some(source)) => source,
+        Ok(None) => Box::new(synthetic),
+        Err(e) => {
+            println!("Cannot use {}={}: {}. Measuring {:?} synthetically.", variable, spec, e, path);
+            Box::new(synthetic)
+        }
+    }
+}
+
+/// The source a setting names; None for "synthetic".
+fn open_source(spec: &str) -> Result<Option<Box<dyn MeasurementSource>>, String> {
+    let (kind, argument) = spec.split_once(':').unwrap_or((spec, ""));
+    let source: Box<dyn MeasurementSource> = match kind {
+        "synthetic" => return Ok(None),
+        "software" => {
+            let (target, bind) = argument.split_once('@').unwrap_or((argument, "0.0.0.0:0"));
+            Box::new(SoftwareEchoSource::connect(target, bind).map_err(|e| e.to_string())?)
+        }
+        "pcap" => Box::new(PcapSource::open(argument).map_err(|e| e.to_string())?),
+        "telemetry" => Box::new(TelemetrySource::bind(argument).map_err(|e| e.to_string())?),
+        other => return Err(format!("unknown source '{}'", other)),
+    };
+    Ok(Some(source))
+}
+
+// --- Synthetic ---
+
+/// A random walk around the path's base latency, floored at 4000µs.
+pub struct SyntheticSource {
+    rng: SimRng,
+    latency_us: u32,
+    jitter_us: i32,
+    loss_probability: f64,
+}
+
+impl SyntheticSource {
+    /// Microwave is generally faster but more susceptible to jitter and
+    /// fades (e.g., from weather).
+    fn microwave(rng: SimRng) -> SyntheticSource {
+        SyntheticSource { rng, latency_us: 4010, jitter_us: 50, loss_probability: 0.02 } // ~4.01ms
+    }
+
+    fn fiber(rng: SimRng) -> SyntheticSource {
+        SyntheticSource { rng, latency_us: 4550, jitter_us: 10, loss_probability: 0.001 } // ~4.55ms
+    }
+}
+
+impl MeasurementSource for SyntheticSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Synthetic
+    }
+
+    fn describe(&self) -> String {
+        format!("synthetic (±{}µs, {}% loss)", self.jitter_us, self.loss_probability * 100.0)
+    }
+
+    fn poll(&mut self) -> Vec<Option<u32>> {
+        let jitter_us = self.rng.gen_range(-self.jitter_us..=self.jitter_us);
+        self.latency_us = (self.latency_us as i32 + jitter_us).max(4000) as u32;
+        vec![(!self.rng.gen_bool(self.loss_probability)).then_some(self.latency_us)]
+    }
+}
+
+// --- Software ---
+
+/// One UDP echo per poll, timed by the host clock.
+pub struct SoftwareEchoSource {
+    socket: UdpSocket,
+    target: String,
+    sequence: u64,
+    timeout: Duration,
+}
+
+impl SoftwareEchoSource {
+    fn connect(target: &str, bind: &str) -> std::io::Result<SoftwareEchoSource> {
+        let timeout_ms = std::env::var("QA_SOFTWARE_PROBE_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(50);
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(target)?;
+        Ok(SoftwareEchoSource {
+            socket,
+            target: target.to_string(),
+            sequence: 0,
+            timeout: Duration::from_millis(timeout_ms),
+        })
+    }
+}
+
+impl MeasurementSource for SoftwareEchoSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Software
+    }
+
+    fn describe(&self) -> String {
+        format!("software UDP echo to {}", self.target)
+    }
+
+    fn poll(&mut self) -> Vec<Option<u32>> {
+        self.sequence += 1;
+        let sent = Instant::now();
+        if self.socket.send(&self.sequence.to_be_bytes()).is_err() {
+            return vec![None];
+        }
+        // Late echoes of earlier probes are skipped until ours comes back.
+        let mut buffer = [0u8; 64];
+        loop {
+            let remaining = self.timeout.saturating_sub(sent.elapsed());
+            if remaining.is_zero() || self.socket.set_read_timeout(Some(remaining)).is_err() {
+                return vec![None];
+            }
+            match self.socket.recv(&mut buffer) {
+                Ok(8) if buffer[..8] == self.sequence.to_be_bytes() => {
+                    return vec![Some((sent.elapsed().as_micros() / 2) as u32)];
+                }
+                Ok(_) => continue,
+                Err(_) => return vec![None],
+            }
+        }
+    }
+}
+
+// --- Hardware ---
+
+/// Turns hardware-stamped probes into latencies, counting sequence gaps as
+/// losses.
+#[derive(Debug, Default)]
+struct HardwareStamps {
+    next_sequence: Option<u64>,
+}
+
+impl HardwareStamps {
+    fn push(&mut self, sequence: u64, tx_hw_ns: u64, rx_hw_ns: u64, probes: &mut Vec<Option<u32>>) {
+        if let Some(expected) = self.next_sequence {
+            if sequence < expected {
+                return; // a duplicate or reordered probe, already counted
+            }
+            probes.extend(std::iter::repeat_n(None, (sequence - expected).min(MAX_GAP) as usize));
+        }
+        self.next_sequence = Some(sequence + 1);
+        if let Some(latency_ns) = rx_hw_ns.checked_sub(tx_hw_ns) {
+            probes.push(Some(((latency_ns + 500) / 1_000) as u32));
+        }
+    }
+}
+
+/// Follows a capture file as the capture card appends to it.
+pub struct PcapSource {
+    file: File,
+    path: String,
+    /// Bytes read but not yet a whole record.
+    pending: Vec<u8>,
+    format: Option<PcapFormat>,
+    stamps: HardwareStamps,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PcapFormat {
+    big_endian: bool,
+    /// Record timestamps are in nanoseconds rather than microseconds.
+    nanos: bool,
+}
+
+impl PcapSource {
+    fn open(path: &str) -> std::io::Result<PcapSource> {
+        Ok(PcapSource {
+            file: File::open(path)?,
+            path: path.to_string(),
+            pending: Vec::new(),
+            format: None,
+            stamps: HardwareStamps::default(),
+        })
+    }
+}
+
+impl MeasurementSource for PcapSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Hardware
+    }
+
+    fn describe(&self) -> String {
+        format!("hardware timestamps from capture {}", self.path)
+    }
+
+    fn poll(&mut self) -> Vec<Option<u32>> {
+        let position = self.file.stream_position().unwrap_or(0);
+        if let Err(e) = self.file.read_to_end(&mut self.pending) {
+            println!("  -> Failed to read capture {}: {}", self.path, e);
+            let _ = self.file.seek(SeekFrom::Start(position));
+        }
+        let mut probes = Vec::new();
+        match parse_pcap(&mut self.pending, &mut self.format, &mut self.stamps, &mut probes) {
+            Ok(()) => probes,
+            Err(e) => {
+                println!("  -> Capture {} is unusable: {}", self.path, e);
+                self.pending.clear();
+                probes
+            }
+        }
+    }
+}
+
+/// Parses the whole records at the front of `buffer`, leaving a trailing
+/// partial record there for the next read.
+fn parse_pcap(
+    buffer: &mut Vec<u8>,
+    format: &mut Option<PcapFormat>,
+    stamps: &mut HardwareStamps,
+    probes: &mut Vec<Option<u32>>,
+) -> Result<(), String> {
+    let mut offset = 0;
+    let format = match format {
+        Some(format) => *format,
+        None => {
+            if buffer.len() < 24 {
+                return Ok(());
+            }
+            let magic = [buffer[0], buffer[1], buffer[2], buffer[3]];
+            let parsed = match magic {
+                [0xd4, 0xc3, 0xb2, 0xa1] => PcapFormat { big_endian: false, nanos: false },
+                [0x4d, 0x3c, 0xb2, 0xa1] => PcapFormat { big_endian: false, nanos: true },
+                [0xa1, 0xb2, 0xc3, 0xd4] => PcapFormat { big_endian: true, nanos: false },
+                [0xa1, 0xb2, 0x3c, 0x4d] => PcapFormat { big_endian: true, nanos: true },
+                _ => return Err("not a pcap file".to_string()),
+            };
+            let link_type = read_u32(&buffer[20..24], parsed.big_endian);
+            if link_type != 1 {
+                return Err(format!("link type {} is not Ethernet", link_type));
+            }
+            *format = Some(parsed);
+            offset = 24;
+            parsed
+        }
+    };
+    while buffer.len() - offset >= 16 {
+        let header = &buffer[offset..offset + 16];
+        let seconds = read_u32(&header[0..4], format.big_endian) as u64;
+        let fraction = read_u32(&header[4..8], format.big_endian) as u64;
+        let captured = read_u32(&header[8..12], format.big_endian) as usize;
+        if buffer.len() - offset - 16 < captured {
+            break;
+        }
+        let rx_hw_ns = seconds * 1_000_000_000 + if format.nanos { fraction } else { fraction * 1_000 };
+        if let Some((sequence, tx_hw_ns)) = probe_in_frame(&buffer[offset + 16..offset + 16 + captured]) {
+            stamps.push(sequence, tx_hw_ns, rx_hw_ns, probes);
+        }
+        offset += 16 + captured;
+    }
+    buffer.drain(..offset);
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// The probe carried by an Ethernet frame (optionally VLAN-tagged) holding
+/// IPv4/UDP; None for any other traffic on the capture.
+fn probe_in_frame(frame: &[u8]) -> Option<(u64, u64)> {
+    let mut ethertype_at = 12;
+    if frame.get(12..14)? == [0x81, 0x00] {
+        ethertype_at += 4;
+    }
+    if frame.get(ethertype_at..ethertype_at + 2)? != [0x08, 0x00] {
+        return None;
+    }
+    let ip = frame.get(ethertype_at + 2..)?;
+    let header_len = (*ip.first()? & 0x0f) as usize * 4;
+    if *ip.get(9)? != 17 {
+        return None;
+    }
+    let payload = ip.get(header_len + 8..)?;
+    if payload.get(..4)? != PROBE_MAGIC {
+        return None;
+    }
+    let sequence = u64::from_be_bytes(payload.get(4..12)?.try_into().ok()?);
+    let tx_hw_ns = u64::from_be_bytes(payload.get(12..20)?.try_into().ok()?);
+    Some((sequence, tx_hw_ns))
+}
+
+/// A probe as reported by the NIC telemetry agent.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct TelemetryStamp {
+    seq: u64,
+    tx_hw_ns: u64,
+    rx_hw_ns: u64,
+}
+
+/// Receives the NIC telemetry agent's datagrams.
+pub struct TelemetrySource {
+    socket: UdpSocket,
+    bind: String,
+    stamps: HardwareStamps,
+}
+
+impl TelemetrySource {
+    fn bind(bind: &str) -> std::io::Result<TelemetrySource> {
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        Ok(TelemetrySource { socket, bind: bind.to_string(), stamps: HardwareStamps::default() })
+    }
+}
+
+impl MeasurementSource for TelemetrySource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Hardware
+    }
+
+    fn describe(&self) -> String {
+        format!("hardware timestamps from NIC telemetry on {}", self.bind)
+    }
+
+    fn poll(&mut self) -> Vec<Option<u32>> {
+        let mut probes = Vec::new();
+        let mut buffer = [0u8; 1024];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(len) => match serde_json::from_slice::<TelemetryStamp>(&buffer[..len]) {
+                    Ok(stamp) => self.stamps.push(stamp.seq, stamp.tx_hw_ns, stamp.rx_hw_ns, &mut probes),
+                    Err(e) => println!("  -> Dropping a malformed telemetry datagram: {}", e),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return probes,
+                Err(e) => {
+                    println!("  -> Telemetry socket {} failed: {}", self.bind, e);
+                    return probes;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ethernet/IPv4/UDP frame carrying a probe.
+    fn probe_frame(sequence: u64, tx_hw_ns: u64) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend([0x08, 0x00]);
+        let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 17];
+        ip.resize(20, 0);
+        frame.extend(ip);
+        frame.extend([0u8; 8]);
+        frame.extend(PROBE_MAGIC);
+        frame.extend(sequence.to_be_bytes());
+        frame.extend(tx_hw_ns.to_be_bytes());
+        frame
+    }
+
+    fn record(rx_hw_ns: u64, frame: &[u8]) -> Vec<u8> {
+        let mut record = Vec::new();
+        record.extend(((rx_hw_ns / 1_000_000_000) as u32).to_le_bytes());
+        record.extend(((rx_hw_ns % 1_000_000_000) as u32).to_le_bytes());
+        record.extend((frame.len() as u32).to_le_bytes());
+        record.extend((frame.len() as u32).to_le_bytes());
+        record.extend(frame);
+        record
+    }
+
+    #[test]
+    fn capture_records_become_one_way_latencies_with_gaps_as_losses() {
+        let mut capture = vec![0x4d, 0x3c, 0xb2, 0xa1, 2, 0, 4, 0];
+        capture.resize(16, 0);
+        capture.extend(65_535u32.to_le_bytes());
+        capture.extend(1u32.to_le_bytes());
+        let sent = 1_753_963_200_000_000_000;
+        capture.extend(record(sent + 4_012_400, &probe_frame(7, sent)));
+        // Probe 8 never arrived; probe 9 was stamped 4,021.6µs after it left.
+        capture.extend(record(sent + 2_000_000_000 + 4_021_600, &probe_frame(9, sent + 2_000_000_000)));
+        // Clocks out of sync: dropped, not scored.
+        capture.extend(record(sent + 2_500_000_000, &probe_frame(10, sent + 3_000_000_000)));
+        let partial = record(sent + 4_000_000_000, &probe_frame(11, sent + 3_995_000_000));
+
+        let (mut format, mut stamps, mut probes) = (None, HardwareStamps::default(), Vec::new());
+        let mut buffer = capture.clone();
+        buffer.extend(&partial[..20]);
+        parse_pcap(&mut buffer, &mut format, &mut stamps, &mut probes).unwrap();
+        assert_eq!(probes, vec![Some(4_012), None, Some(4_022)]);
+        assert_eq!(buffer, partial[..20]);
+
+        // The rest of the record arrives with the next read.
+        buffer.extend(&partial[20..]);
+        probes.clear();
+        parse_pcap(&mut buffer, &mut format, &mut stamps, &mut probes).unwrap();
+        assert_eq!(probes, vec![Some(5_000)]);
+        assert!(buffer.is_empty());
+    }
+}
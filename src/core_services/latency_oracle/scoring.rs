@@ -0,0 +1,255 @@
+/*
+ * QuantumArb 2.0 - Core Services: Route Scoring
+ *
+ * File: src/core_services/latency_oracle/scoring.rs
+ *
+ * Description:
+ * Scores each network path from its recent probes rather than the last one,
+ * and picks the route with hysteresis so the exchange gateway does not flap
+ * between two paths whose latencies cross on every probe.
+ *
+ * A path's score is in microseconds, lower is better:
+ *
+ *   score = p50 + tail_weight x (p99 - p50) + jitter_weight x jitter
+ *           + loss_penalty_us x loss_rate
+ *
+ * over the last `window` probes, where jitter is the mean absolute change
+ * between consecutive answered probes and a probe that got no answer counts
+ * toward the loss rate only.
+ *
+ * The selected route changes only when another path has scored better by
+ * more than `switch_margin` (a fraction of the current route's score) on
+ * `confirmations` consecutive evaluations. A current route that loses every
+ * probe in the window is abandoned at once.
+ *
+ * Configuration (environment):
+ *   QA_ROUTE_WINDOW=60                 probes scored per path
+ *   QA_ROUTE_TAIL_WEIGHT=0.5           weight of the p99 - p50 spread
+ *   QA_ROUTE_JITTER_WEIGHT=1.0         weight of the jitter
+ *   QA_ROUTE_LOSS_PENALTY_US=5000      score added at 100% packet loss
+ *   QA_ROUTE_SWITCH_MARGIN=0.05        improvement needed to switch
+ *   QA_ROUTE_SWITCH_CONFIRMATIONS=3    consecutive evaluations to switch
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::NetworkPath;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct ScoringConfig {
+    pub window: usize,
+    pub tail_weight: f64,
+    pub jitter_weight: f64,
+    pub loss_penalty_us: f64,
+    pub switch_margin: f64,
+    pub confirmations: u32,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            window: 60,
+            tail_weight: 0.5,
+            jitter_weight: 1.0,
+            loss_penalty_us: 5_000.0,
+            switch_margin: 0.05,
+            confirmations: 3,
+        }
+    }
+}
+
+impl ScoringConfig {
+    pub fn from_env() -> ScoringConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let defaults = ScoringConfig::default();
+        ScoringConfig {
+            window: var("QA_ROUTE_WINDOW", defaults.window).max(2),
+            tail_weight: var("QA_ROUTE_TAIL_WEIGHT", defaults.tail_weight),
+            jitter_weight: var("QA_ROUTE_JITTER_WEIGHT", defaults.jitter_weight),
+            loss_penalty_us: var("QA_ROUTE_LOSS_PENALTY_US", defaults.loss_penalty_us),
+            switch_margin: var("QA_ROUTE_SWITCH_MARGIN", defaults.switch_margin),
+            confirmations: var("QA_ROUTE_SWITCH_CONFIRMATIONS", defaults.confirmations).max(1),
+        }
+    }
+}
+
+// --- Path Statistics ---
+
+/// A path's statistics over its probe window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct PathStats {
+    pub probes: usize,
+    pub p50_us: u32,
+    pub p99_us: u32,
+    pub jitter_us: f64,
+    pub loss_rate: f64,
+    /// Infinite (null in JSON) when every probe in the window was lost.
+    pub score: f64,
+}
+
+/// The last `window` probes of one path; None is a probe that got no answer.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeWindow {
+    samples: VecDeque<Option<u32>>,
+}
+
+impl ProbeWindow {
+    pub fn push(&mut self, latency_us: Option<u32>, window: usize) {
+        if self.samples.len() == window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency_us);
+    }
+
+    /// The window's statistics; None before the first probe.
+    pub fn stats(&self, config: &ScoringConfig) -> Option<PathStats> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let answered: Vec<u32> = self.samples.iter().flatten().copied().collect();
+        let loss_rate = (self.samples.len() - answered.len()) as f64 / self.samples.len() as f64;
+        let mut sorted = answered.clone();
+        sorted.sort_unstable();
+        let percentile = |p: f64| -> u32 {
+            match sorted.len() {
+                0 => 0,
+                n => sorted[((p * n as f64).ceil() as usize).clamp(1, n) - 1],
+            }
+        };
+        let (p50_us, p99_us) = (percentile(0.50), percentile(0.99));
+        let jitter_us = if answered.len() < 2 {
+            0.0
+        } else {
+            let changes: u64 = answered.windows(2).map(|w| w[0].abs_diff(w[1]) as u64).sum();
+            changes as f64 / (answered.len() - 1) as f64
+        };
+        let score = if answered.is_empty() {
+            f64::INFINITY
+        } else {
+            p50_us as f64
+                + config.tail_weight * (p99_us - p50_us) as f64
+                + config.jitter_weight * jitter_us
+                + config.loss_penalty_us * loss_rate
+        };
+        Some(PathStats { probes: self.samples.len(), p50_us, p99_us, jitter_us, loss_rate, score })
+    }
+}
+
+// --- Route Selection ---
+
+/// A change of the selected route.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct RouteSwitch {
+    pub from: Option<NetworkPath>,
+    pub to: NetworkPath,
+    /// None when there was no route, or it had stopped answering.
+    pub from_score: Option<f64>,
+    pub to_score: f64,
+    pub timestamp_utc: String,
+}
+
+/// The selected route and the path, if any, that is beating it.
+#[derive(Debug, Clone, Default)]
+pub struct RouteSelector {
+    current: Option<NetworkPath>,
+    challenger: Option<(NetworkPath, u32)>,
+}
+
+impl RouteSelector {
+    pub fn current(&self) -> Option<NetworkPath> {
+        self.current
+    }
+
+    /// Re-evaluates the route against the latest scores. Returns the switch
+    /// if the route changed.
+    pub fn evaluate(&mut self, scores: &[(NetworkPath, f64)], config: &ScoringConfig) -> Option<RouteSwitch> {
+        let (best, best_score) = scores
+            .iter()
+            .copied()
+            .filter(|(_, score)| score.is_finite())
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        let current_score = self.current.and_then(|c| scores.iter().find(|(p, _)| *p == c)).map(|(_, s)| *s);
+        let switch_now = match current_score {
+            // Nothing selected yet, or the current route is gone: take the best.
+            None => true,
+            Some(score) if !score.is_finite() => true,
+            Some(_) if Some(best) == self.current => {
+                self.challenger = None;
+                false
+            }
+            Some(score) if best_score < score * (1.0 - config.switch_margin) => {
+                let confirmed = match self.challenger {
+                    Some((path, count)) if path == best => count + 1,
+                    _ => 1,
+                };
+                self.challenger = Some((best, confirmed));
+                confirmed >= config.confirmations
+            }
+            Some(_) => {
+                self.challenger = None;
+                false
+            }
+        };
+        if !switch_now {
+            return None;
+        }
+        let from = self.current.replace(best);
+        self.challenger = None;
+        Some(RouteSwitch {
+            from,
+            to: best,
+            from_score: current_score.filter(|s| s.is_finite()),
+            to_score: best_score,
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_penalize_tail_jitter_and_loss() {
+        let config = ScoringConfig::default();
+        let mut steady = ProbeWindow::default();
+        let mut lossy = ProbeWindow::default();
+        let probes = [None, Some(4_000), Some(4_100), Some(4_000), Some(4_100)];
+        for probe in probes.iter().chain(&probes) {
+            steady.push(Some(4_500), config.window);
+            lossy.push(*probe, config.window);
+        }
+        let steady = steady.stats(&config).unwrap();
+        let lossy = lossy.stats(&config).unwrap();
+        assert_eq!(steady.score, 4_500.0);
+        assert_eq!((lossy.p50_us, lossy.p99_us, lossy.loss_rate), (4_000, 4_100, 0.2));
+        // 4000 + 0.5 x 100 + 100 jitter + 20% of 5000 lost.
+        assert_eq!(lossy.score, 5_150.0);
+        assert!(lossy.score > steady.score);
+    }
+
+    #[test]
+    fn switches_only_after_a_confirmed_margin() {
+        let config = ScoringConfig::default();
+        let mut selector = RouteSelector::default();
+        use NetworkPath::*;
+        assert_eq!(selector.evaluate(&[(Microwave, 4_100.0), (Fiber, 4_550.0)], &config).unwrap().to, Microwave);
+        // Fiber edges ahead, inside the margin: no flap.
+        assert!(selector.evaluate(&[(Microwave, 4_560.0), (Fiber, 4_550.0)], &config).is_none());
+        // Clearly ahead, but it has to stay ahead.
+        let worse = [(Microwave, 5_000.0), (Fiber, 4_550.0)];
+        assert!(selector.evaluate(&worse, &config).is_none());
+        assert!(selector.evaluate(&[(Microwave, 4_100.0), (Fiber, 4_550.0)], &config).is_none());
+        assert!(selector.evaluate(&worse, &config).is_none());
+        assert!(selector.evaluate(&worse, &config).is_none());
+        assert_eq!(selector.evaluate(&worse, &config).unwrap().to, Fiber);
+        // A route that stops answering is left at once.
+        assert_eq!(selector.evaluate(&[(Microwave, 4_100.0), (Fiber, f64::INFINITY)], &config).unwrap().to, Microwave);
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * QuantumArb 2.0 - Core Services: Rain Fade Prediction
+ *
+ * File: src/core_services/latency_oracle/weather.rs
+ *
+ * Description:
+ * Rain along a microwave link attenuates the signal until the radios drop
+ * to a lower modulation or lose the hop altogether, and the probes only
+ * show it once it has happened. The precipitation forecast the data bus
+ * connector publishes on 'alt_data.weather' lets the oracle act before then:
+ * when rain is forecast at any waypoint of the link within the lookahead,
+ * the microwave route's score is raised by a penalty
+ *
+ *   penalty = min(max_penalty_us, penalty_us_per_mm x rate x probability)
+ *
+ * for the worst such hour, so the route moves to fiber through the usual
+ * hysteresis before the fade starts. Hours below the rain threshold are
+ * ignored, and so is a forecast older than the maximum age.
+ *
+ * Configuration (environment):
+ *   QA_WEATHER_LOOKAHEAD_MINS=30       how far ahead rain counts
+ *   QA_WEATHER_RAIN_THRESHOLD_MM=2.0   lightest rain (mm/h) that counts
+ *   QA_WEATHER_PENALTY_US_PER_MM=100   score added per expected mm/h
+ *   QA_WEATHER_MAX_PENALTY_US=2000     cap on the penalty
+ *   QA_WEATHER_MAX_AGE_MINS=180        forecasts older than this are ignored
+ */
+
+use chrono::{DateTime, Duration, Utc};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::LinkWeatherForecast;
+use serde::Serialize;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct WeatherConfig {
+    pub lookahead: Duration,
+    pub rain_threshold_mm_per_hr: f64,
+    pub penalty_us_per_mm: f64,
+    pub max_penalty_us: f64,
+    pub max_forecast_age: Duration,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            lookahead: Duration::minutes(30),
+            rain_threshold_mm_per_hr: 2.0,
+            penalty_us_per_mm: 100.0,
+            max_penalty_us: 2_000.0,
+            max_forecast_age: Duration::minutes(180),
+        }
+    }
+}
+
+impl WeatherConfig {
+    pub fn from_env() -> WeatherConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let defaults = WeatherConfig::default();
+        WeatherConfig {
+            lookahead: Duration::minutes(var("QA_WEATHER_LOOKAHEAD_MINS", 30)),
+            rain_threshold_mm_per_hr: var("QA_WEATHER_RAIN_THRESHOLD_MM", defaults.rain_threshold_mm_per_hr),
+            penalty_us_per_mm: var("QA_WEATHER_PENALTY_US_PER_MM", defaults.penalty_us_per_mm),
+            max_penalty_us: var("QA_WEATHER_MAX_PENALTY_US", defaults.max_penalty_us),
+            max_forecast_age: Duration::minutes(var("QA_WEATHER_MAX_AGE_MINS", 180)),
+        }
+    }
+}
+
+// --- Prediction ---
+
+/// The rain behind a microwave penalty, served with the path on GET /paths.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct RainOutlook {
+    pub penalty_us: f64,
+    pub waypoint: String,
+    pub starts_utc: DateTime<Utc>,
+    pub precipitation_mm_per_hr: f64,
+    pub probability: f64,
+    pub forecast_issued_utc: DateTime<Utc>,
+}
+
+/// The worst rain forecast along the link between `now` and the lookahead,
+/// or None when none is forecast or the forecast is too old.
+pub fn rain_outlook(forecast: &LinkWeatherForecast, now: DateTime<Utc>, config: &WeatherConfig) -> Option<RainOutlook> {
+    if now - forecast.issued_utc > config.max_forecast_age {
+        return None;
+    }
+    let horizon = now + config.lookahead;
+    forecast
+        .waypoints
+        .iter()
+        .flat_map(|waypoint| waypoint.hours.iter().map(move |hour| (waypoint, hour)))
+        .filter(|(_, hour)| hour.starts_utc < horizon && hour.starts_utc + Duration::hours(1) > now)
+        .filter(|(_, hour)| hour.precipitation_mm_per_hr >= config.rain_threshold_mm_per_hr)
+        .map(|(waypoint, hour)| RainOutlook {
+            penalty_us: (config.penalty_us_per_mm * hour.precipitation_mm_per_hr * hour.probability)
+                .min(config.max_penalty_us),
+            waypoint: waypoint.name.clone(),
+            starts_utc: hour.starts_utc,
+            precipitation_mm_per_hr: hour.precipitation_mm_per_hr,
+            probability: hour.probability,
+            forecast_issued_utc: forecast.issued_utc,
+        })
+        .max_by(|a, b| a.penalty_us.total_cmp(&b.penalty_us))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_types::{PrecipitationHour, WaypointForecast};
+
+    #[test]
+    fn penalizes_the_worst_rain_inside_the_lookahead() {
+        let config = WeatherConfig::default();
+        let now: DateTime<Utc> = "2025-07-31T12:40:00Z".parse().unwrap();
+        let hour = |starts: &str, precipitation_mm_per_hr, probability| PrecipitationHour {
+            starts_utc: format!("2025-07-31T{}:00Z", starts).parse().unwrap(),
+            precipitation_mm_per_hr,
+            probability,
+        };
+        let waypoint = |name: &str, hours| WaypointForecast {
+            name: name.to_string(),
+            latitude: 0.0,
+            longitude: 0.0,
+            hours,
+        };
+        let forecast = LinkWeatherForecast {
+            link: "CHI-NJ".to_string(),
+            waypoints: vec![
+                // Drizzle now, a downpour after the lookahead.
+                waypoint("Aurora IL", vec![hour("12:00", 1.5, 0.9), hour("14:00", 30.0, 1.0)]),
+                waypoint("Cleveland OH", vec![hour("12:00", 0.0, 0.1), hour("13:00", 8.0, 0.75)]),
+                waypoint("Carteret NJ", vec![hour("13:00", 4.0, 0.9)]),
+            ],
+            issued_utc: "2025-07-31T12:00:00Z".parse().unwrap(),
+        };
+        let outlook = rain_outlook(&forecast, now, &config).unwrap();
+        assert_eq!((outlook.waypoint.as_str(), outlook.penalty_us), ("Cleveland OH", 600.0));
+        // Four hours on, the forecast is too old to act on.
+        assert!(rain_outlook(&forecast, now + Duration::hours(4), &config).is_none());
+    }
+}
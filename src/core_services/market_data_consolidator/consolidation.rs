@@ -0,0 +1,196 @@
+/*
+ * QuantumArb 2.0 - Core Services: Consolidated Book
+ *
+ * File: src/core_services/market_data_consolidator/consolidation.rs
+ *
+ * Description:
+ * Keeps each venue's latest top of book per instrument and merges them into
+ * a `ConsolidatedBbo`: the highest bid and lowest ask any venue quotes, the
+ * size quoted at each across venues, and the venue quoting the most of it.
+ *
+ * A venue's quote drops out once it is older than QA_CONSOLIDATOR_STALE_MS,
+ * so a venue whose feed has gone quiet cannot hold the consolidated price
+ * at a level nobody trades at any more. A side quoted with a zero price or
+ * size does not count for that side. The consolidated book may be locked
+ * or crossed across venues; that is the opportunity the strategy engine
+ * looks for, so it is published as it is.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::{BboUpdate, ConsolidatedBbo};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct ConsolidatorConfig {
+    pub stale_after_ns: u64,
+}
+
+impl ConsolidatorConfig {
+    pub fn from_env() -> ConsolidatorConfig {
+        let stale_ms = std::env::var("QA_CONSOLIDATOR_STALE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(2_000);
+        ConsolidatorConfig { stale_after_ns: stale_ms * 1_000_000 }
+    }
+}
+
+// --- Data Structures ---
+
+/// An instrument's consolidated BBO and the venue quotes behind it, for
+/// GET /nbbo.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct InstrumentBook {
+    pub consolidated: ConsolidatedBbo,
+    pub venues: Vec<BboUpdate>,
+}
+
+/// Every instrument's venue quotes and last consolidated BBO.
+#[derive(Debug)]
+pub struct Consolidator {
+    config: ConsolidatorConfig,
+    /// Latest quote by instrument, then venue.
+    quotes: HashMap<u32, BTreeMap<u32, BboUpdate>>,
+    published: HashMap<u32, ConsolidatedBbo>,
+}
+
+impl Consolidator {
+    pub fn new(config: ConsolidatorConfig) -> Consolidator {
+        Consolidator { config, quotes: HashMap::new(), published: HashMap::new() }
+    }
+
+    /// Folds in a venue's top of book. Returns the instrument's consolidated
+    /// BBO if it changed.
+    pub fn on_bbo(&mut self, bbo: BboUpdate, now_ns: u64) -> Option<ConsolidatedBbo> {
+        let venues = self.quotes.entry(bbo.instrument_id).or_default();
+        if venues.get(&bbo.venue_id).is_some_and(|held| held.timestamp_ns > bbo.timestamp_ns) {
+            return None; // Out of order: the venue has quoted since.
+        }
+        venues.insert(bbo.venue_id, bbo);
+        self.refresh(bbo.instrument_id, now_ns)
+    }
+
+    /// Drops the quotes gone stale. Returns the consolidated BBOs that
+    /// changed as a result.
+    pub fn expire(&mut self, now_ns: u64) -> Vec<ConsolidatedBbo> {
+        let mut instruments: Vec<u32> = self.quotes.keys().copied().collect();
+        instruments.sort();
+        instruments.into_iter().filter_map(|instrument_id| self.refresh(instrument_id, now_ns)).collect()
+    }
+
+    pub fn books(&self) -> Vec<InstrumentBook> {
+        let mut books: Vec<InstrumentBook> =
+            self.published.keys().filter_map(|instrument_id| self.book(*instrument_id)).collect();
+        books.sort_by_key(|book| book.consolidated.instrument_id);
+        books
+    }
+
+    pub fn book(&self, instrument_id: u32) -> Option<InstrumentBook> {
+        let consolidated = *self.published.get(&instrument_id)?;
+        let venues = self.quotes.get(&instrument_id).map(|venues| venues.values().copied().collect());
+        Some(InstrumentBook { consolidated, venues: venues.unwrap_or_default() })
+    }
+
+    fn refresh(&mut self, instrument_id: u32, now_ns: u64) -> Option<ConsolidatedBbo> {
+        let venues = self.quotes.get_mut(&instrument_id)?;
+        let stale_after_ns = self.config.stale_after_ns;
+        venues.retain(|_, quote| now_ns.saturating_sub(quote.timestamp_ns) <= stale_after_ns);
+        let consolidated = consolidate(instrument_id, venues);
+        let unchanged = self.published.get(&instrument_id).is_some_and(|last| {
+            ConsolidatedBbo { timestamp_ns: last.timestamp_ns, ..consolidated } == *last
+        });
+        self.published.insert(instrument_id, consolidated);
+        (!unchanged).then_some(consolidated)
+    }
+}
+
+/// Merges the venues' quotes for one instrument.
+pub fn consolidate(instrument_id: u32, venues: &BTreeMap<u32, BboUpdate>) -> ConsolidatedBbo {
+    let bids: Vec<(u32, u64, u32)> = venues
+        .values()
+        .filter(|q| q.best_bid_price > 0 && q.best_bid_size > 0)
+        .map(|q| (q.venue_id, q.best_bid_price, q.best_bid_size))
+        .collect();
+    let asks: Vec<(u32, u64, u32)> = venues
+        .values()
+        .filter(|q| q.best_ask_price > 0 && q.best_ask_size > 0)
+        .map(|q| (q.venue_id, q.best_ask_price, q.best_ask_size))
+        .collect();
+    let (bid_price, bid_size, bid_venue) = best_side(&bids, bids.iter().map(|(_, price, _)| *price).max());
+    let (ask_price, ask_size, ask_venue) = best_side(&asks, asks.iter().map(|(_, price, _)| *price).min());
+    ConsolidatedBbo {
+        instrument_id,
+        best_bid_price: bid_price,
+        best_bid_size: bid_size,
+        best_bid_venue_id: bid_venue,
+        best_ask_price: ask_price,
+        best_ask_size: ask_size,
+        best_ask_venue_id: ask_venue,
+        venue_count: venues.len().min(u8::MAX as usize) as u8,
+        timestamp_ns: venues.values().map(|q| q.timestamp_ns).max().unwrap_or(0),
+    }
+}
+
+/// (price, total size at it, venue quoting the most) for a side's best
+/// price; zeros without one. Quotes are in venue id order, so the first
+/// venue wins a tie.
+fn best_side(quotes: &[(u32, u64, u32)], best: Option<u64>) -> (u64, u32, u32) {
+    let Some(best) = best else {
+        return (0, 0, 0);
+    };
+    let (mut total, mut venue, mut most) = (0u32, 0u32, 0u32);
+    for (venue_id, _, size) in quotes.iter().filter(|(_, price, _)| *price == best) {
+        total = total.saturating_add(*size);
+        if *size > most {
+            (venue, most) = (*venue_id, *size);
+        }
+    }
+    (best, total, venue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(venue_id: u32, bid: (u64, u32), ask: (u64, u32), timestamp_ns: u64) -> BboUpdate {
+        BboUpdate {
+            instrument_id: 1,
+            best_bid_price: bid.0,
+            best_bid_size: bid.1,
+            best_ask_price: ask.0,
+            best_ask_size: ask.1,
+            timestamp_ns,
+            venue_id,
+        }
+    }
+
+    #[test]
+    fn merges_venues_into_the_best_bid_and_offer_until_quotes_go_stale() {
+        let second = 1_000_000_000;
+        let mut consolidator = Consolidator::new(ConsolidatorConfig { stale_after_ns: 2 * second });
+
+        consolidator.on_bbo(quote(1, (60_000_00, 5), (60_002_00, 4), 10 * second), 10 * second).unwrap();
+        let nbbo = consolidator.on_bbo(quote(2, (60_000_00, 8), (60_001_00, 3), 10 * second), 10 * second).unwrap();
+        // Both venues bid the best price; the ask is venue 2's alone.
+        assert_eq!((nbbo.best_bid_price, nbbo.best_bid_size, nbbo.best_bid_venue_id), (60_000_00, 13, 2));
+        assert_eq!((nbbo.best_ask_price, nbbo.best_ask_size, nbbo.best_ask_venue_id), (60_001_00, 3, 2));
+        assert_eq!(nbbo.venue_count, 2);
+
+        // A requote that leaves the consolidated book as it was is not news,
+        // and an out-of-order quote is ignored.
+        assert!(consolidator.on_bbo(quote(1, (60_000_00, 5), (60_002_00, 9), 11 * second), 11 * second).is_none());
+        assert!(consolidator.on_bbo(quote(1, (60_005_00, 1), (60_006_00, 1), 10 * second), 11 * second).is_none());
+
+        // Venue 2 goes quiet: its quotes drop out, venue 1's is the book.
+        assert!(consolidator.expire(12 * second).is_empty());
+        let expired = consolidator.expire(12 * second + 1);
+        assert_eq!(expired.len(), 1);
+        assert_eq!((expired[0].best_bid_size, expired[0].best_bid_venue_id), (5, 1));
+        assert_eq!((expired[0].best_ask_price, expired[0].best_ask_venue_id), (60_002_00, 1));
+        assert_eq!(consolidator.book(1).unwrap().venues.len(), 1);
+    }
+}
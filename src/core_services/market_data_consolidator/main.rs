@@ -0,0 +1,248 @@
+/*
+ * QuantumArb 2.0 - Core Services: Market Data Consolidator
+ *
+ * File: src/core_services/market_data_consolidator/main.rs
+ *
+ * Description:
+ * This microservice builds the consolidated best bid and offer (an NBBO)
+ * for instruments quoted on more than one venue, so consumers see the true
+ * cross-market price instead of comparing venue feeds themselves.
+ *
+ * Its primary role is to:
+ * 1. Follow every venue's top of book as its feed arrives, under the venue's
+ *    own symbol, and map it to the canonical instrument id through the
+ *    venue symbology (`quantumarb_refdata::symbology`, loaded from
+ *    QA_SYMBOLOGY_PATH or the built-in set). A quote for a symbol with no
+ *    mapping is dropped.
+ * 2. Merge the venues' quotes per instrument (see `consolidation.rs`) and
+ *    publish a `ConsolidatedBbo` on 'market_data.consolidated.instrument.<id>'
+ *    whenever the consolidated price, size or attribution changes,
+ *    including when a venue's quote goes stale.
+ * 3. Serve the consolidated books with the venue quotes behind them:
+ *    - GET /nbbo                    -> every instrument
+ *    - GET /nbbo/{instrument_id}    -> one instrument
+ *    - GET /openapi.json            -> the API's OpenAPI document (Swagger UI
+ *                                      on GET /docs)
+ *
+ * Events go on the bus in the binary `quantumarb-wire` encoding by default;
+ * set QA_BUS_ENCODING=json to publish JSON instead when debugging.
+ *
+ * This POC simulates BTC and ETH quoted on VENUE_A (as BTC-USD and ETH-USD)
+ * and VENUE_B (as BTCUSD and ETHUSD), with VENUE_B's feed dropping out for
+ * a few seconds every minute. With --seed N (or QA_SEED) the simulated
+ * quotes are seeded.
+ */
+
+mod consolidation;
+
+use consolidation::{Consolidator, ConsolidatorConfig, InstrumentBook};
+use quantumarb_bus::topics;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_refdata::symbology::{self, Symbology};
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_wire::{utc_now_ns, BboUpdate, ConsolidatedBbo, Encoding};
+use rand_distr::{Distribution, Normal, Uniform};
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration};
+use warp::http::StatusCode;
+use warp::Filter;
+
+// --- Data Structures ---
+
+type SharedConsolidator = Arc<Mutex<Consolidator>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Market Data Consolidator ---");
+
+    let config = ConsolidatorConfig::from_env();
+    println!("Venue quotes go stale after {}ms.", config.stale_after_ns / 1_000_000);
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let state: SharedConsolidator = Arc::new(Mutex::new(Consolidator::new(config)));
+
+    // Task 1: merge every venue quote as it arrives.
+    let symbology = Symbology::new(symbology::load_mappings_from_env());
+    let (feed_state, feed_rng) = (state.clone(), seed.stream("market_data_consolidator.feed"));
+    tokio::spawn(async move {
+        consume_venue_quotes(feed_state, symbology, feed_rng).await;
+    });
+
+    // Task 2: drop quotes from venues that have gone quiet.
+    let expiry_state = state.clone();
+    tokio::spawn(async move {
+        expire_stale_quotes(expiry_state).await;
+    });
+
+    // --- API Endpoints for the consolidated books ---
+    let list = warp::path!("nbbo")
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_list_books);
+    let get = warp::path!("nbbo" / u32)
+        .and(warp::get())
+        .and(with_state(state))
+        .and_then(handler_get_book);
+
+    println!("API server running at http://127.0.0.1:3042/nbbo");
+    warp::serve(list.or(get).or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3042)).await;
+}
+
+/// The OpenAPI document of the NBBO endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Market Data Consolidator", "Consolidated best bid and offer across venues.")
+        .errors::<ErrorBody>()
+        .get::<Vec<InstrumentBook>>("/nbbo", "Every instrument's consolidated book")
+        .operation(
+            Operation::get("/nbbo/{instrument_id}", "One instrument's consolidated book")
+                .path_param::<u32>("instrument_id")
+                .response::<InstrumentBook>(),
+        )
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /nbbo.
+async fn handler_list_books(state: SharedConsolidator) -> Result<impl warp::Reply, warp::Rejection> {
+    let books = state.lock().unwrap().books();
+    Ok(warp::reply::json(&books))
+}
+
+/// Handler for GET /nbbo/{instrument_id}.
+async fn handler_get_book(instrument_id: u32, state: SharedConsolidator) -> Result<impl warp::Reply, warp::Rejection> {
+    let book = state.lock().unwrap().book(instrument_id);
+    Ok(match book {
+        Some(book) => warp::reply::with_status(warp::reply::json(&book), StatusCode::OK),
+        None => {
+            let message = format!("No venue quotes instrument {}", instrument_id);
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+            warp::reply::with_status(warp::reply::json(&body), StatusCode::NOT_FOUND)
+        }
+    })
+}
+
+/// Consumes the venues' top-of-book feeds and publishes every change to a
+/// consolidated book.
+async fn consume_venue_quotes(state: SharedConsolidator, symbology: Symbology, rng: SimRng) {
+    // In a real system each venue's feed handler delivers its quotes, and
+    // mappings added on 'reference.symbology' are applied as they arrive.
+    let encoding = Encoding::from_env();
+    let mut feed = SimulatedVenues::new(rng);
+    let mut interval = time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let now = chrono::Utc::now();
+        for quote in feed.next_quotes() {
+            let Some(instrument_id) = symbology.canonical_id(quote.venue, &quote.venue_symbol, now) else {
+                println!("  -> No instrument for {} on {}; quote dropped.", quote.venue_symbol, quote.venue);
+                continue;
+            };
+            let bbo = BboUpdate { instrument_id, ..quote.bbo };
+            let changed = state.lock().unwrap().on_bbo(bbo, utc_now_ns());
+            if let Some(consolidated) = changed {
+                publish_consolidated(&consolidated, encoding);
+            }
+        }
+    }
+}
+
+/// Once a second, drops stale venue quotes and publishes the books they
+/// change.
+async fn expire_stale_quotes(state: SharedConsolidator) {
+    let encoding = Encoding::from_env();
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let changed = state.lock().unwrap().expire(utc_now_ns());
+        for consolidated in &changed {
+            publish_consolidated(consolidated, encoding);
+        }
+    }
+}
+
+/// Simulates publishing a consolidated book to the internal message bus.
+fn publish_consolidated(consolidated: &ConsolidatedBbo, encoding: Encoding) {
+    let topic = topics::consolidated_market_data(consolidated.instrument_id);
+    let payload = encoding.encode(consolidated);
+    println!(
+        "  -> Publishing to topic '{}' ({} bytes, {}): {} x {} (venue {}) / {} x {} (venue {})",
+        topic,
+        payload.len(),
+        encoding.content_type(),
+        consolidated.best_bid_size,
+        consolidated.best_bid_price,
+        consolidated.best_bid_venue_id,
+        consolidated.best_ask_size,
+        consolidated.best_ask_price,
+        consolidated.best_ask_venue_id
+    );
+    // In a real system:
+    // nats_client.publish(&topic, payload.into()).await.unwrap();
+}
+
+/// A venue's top of book under its own symbol; `bbo` has no instrument id
+/// until the symbol is mapped.
+struct VenueQuote {
+    venue: &'static str,
+    venue_symbol: String,
+    bbo: BboUpdate,
+}
+
+/// A random walk for each instrument, quoted by each venue a few cents
+/// either side of it with random sizes. VENUE_B stops quoting for 5 of
+/// every 120 ticks.
+struct SimulatedVenues {
+    tick: u64,
+    /// (mid in hundredths, symbol on VENUE_A, symbol on VENUE_B)
+    instruments: Vec<(f64, &'static str, &'static str)>,
+    noise: Normal<f64>,
+    offsets: Uniform<i64>,
+    sizes: Uniform<u32>,
+    rng: SimRng,
+}
+
+impl SimulatedVenues {
+    fn new(rng: SimRng) -> Self {
+        SimulatedVenues {
+            tick: 0,
+            instruments: vec![(60000_00.0, "BTC-USD", "BTCUSD"), (3000_00.0, "ETH-USD", "ETHUSD")],
+            noise: Normal::new(0.0, 0.0001).unwrap(),
+            offsets: Uniform::new_inclusive(1, 4),
+            sizes: Uniform::new_inclusive(1, 20),
+            rng,
+        }
+    }
+
+    fn next_quotes(&mut self) -> Vec<VenueQuote> {
+        self.tick += 1;
+        let timestamp_ns = utc_now_ns();
+        let mut quotes = Vec::new();
+        for (mid, venue_a_symbol, venue_b_symbol) in self.instruments.iter_mut() {
+            *mid *= 1.0 + self.noise.sample(&mut self.rng);
+            for (venue_id, venue, venue_symbol) in [(1, "VENUE_A", *venue_a_symbol), (2, "VENUE_B", *venue_b_symbol)] {
+                if venue_id == 2 && self.tick % 120 >= 115 {
+                    continue; // VENUE_B feed gap.
+                }
+                let bbo = BboUpdate {
+                    instrument_id: 0,
+                    best_bid_price: (mid.round() as i64 - self.offsets.sample(&mut self.rng)) as u64,
+                    best_bid_size: self.sizes.sample(&mut self.rng),
+                    best_ask_price: (mid.round() as i64 + self.offsets.sample(&mut self.rng)) as u64,
+                    best_ask_size: self.sizes.sample(&mut self.rng),
+                    timestamp_ns,
+                    venue_id,
+                };
+                quotes.push(VenueQuote { venue, venue_symbol: venue_symbol.to_string(), bbo });
+            }
+        }
+        quotes
+    }
+}
@@ -13,98 +13,577 @@
  * 2. Publish this data in the same format as a live feed (e.g., BboUpdate).
  * 3. Control the speed of the replay to simulate real-time or accelerated time.
  *
+ * Replays run as sessions controlled over a small HTTP API, so operators can
+ * start, stop and monitor them without restarting the service:
+ * - POST /replay/start  { "speed": 10.0, "adjust_corporate_actions": true }
+ *                                          -> starts a new session
+ * - POST /replay/stop                      -> stops the running session
+ * - GET  /replay/status                    -> progress of the current session
+ *
+ * A start request with an "archive" section replays bus history written by
+ * the archiver instead of the built-in sample data:
+ *
+ *   "archive": { "start_utc": "2025-06-09T00:00:00Z", "end_utc": "2025-06-12T00:00:00Z",
+ *                "instruments": [1, 2], "topics": ["market_data.>", "alt_data.normalized"] }
+ *
+ * Only partitions inside the date range are listed, and files are streamed
+ * from the store a chunk at a time while the replay runs, so a multi-day
+ * replay needs neither a local download nor the whole range in memory.
+ * Archived messages are republished on their original topics, byte for
+ * byte, and /replay/status reports how many of the selected files and bytes
+ * have been read and how far through the range the replay clock is.
+ *
+ * Events go on the bus in the binary `quantumarb-wire` encoding by default;
+ * set QA_BUS_ENCODING=json to publish JSON instead when debugging.
+ *
+ * The sample data is also published conflated, on
+ * 'market_data.conflated.instrument.<id>', for consumers that want each
+ * instrument's latest state at most QA_CONFLATION_MAX_HZ times a second
+ * (see `quantumarb_bus::conflation`). An archive replay republishes the
+ * conflated topics as they were archived.
+ *
+ * A start request with a "synthetic" section replays a generated market
+ * instead (see `synthetic.rs`): GBM, Heston-like stochastic volatility,
+ * jump-diffusion or regime-switching paths for the listed instruments.
+ *
+ *   "synthetic": { "model": { "model": "jump_diffusion", "drift": 0.0, "volatility": 0.6,
+ *                             "jump_intensity": 12.0, "jump_mean": -0.05, "jump_std": 0.03 },
+ *                  "instruments": [{ "instrument_id": 1, "start_price": 60000.0 }],
+ *                  "events": 36000, "interval_ms": 100, "seed": 42 }
+ *
+ * The path is drawn from the request's seed, else the service's --seed N
+ * (or QA_SEED), else a fresh one; /replay/status shows the seed used, so any
+ * session can be generated again exactly.
+ *
+ * History is back-adjusted for splits listed in QA_CORPORATE_ACTIONS_PATH
+ * (prices divided and sizes multiplied by every later split), so a backtest
+ * running across a split sees a continuous price series rather than a jump.
+ *
  * This allows the entire platform to be tested against historical scenarios.
  *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * serde = { version = "1.0", features = ["derive"] }
- * serde_json = "1.0"
- * chrono = "0.4"
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
  */
 
+mod synthetic;
+
+use chrono::{DateTime, Utc};
+use quantumarb_archive::{ArchiveQuery, ArchiveReader, ArchiveRecord, ReadProgress};
+use quantumarb_bus::{topics, BusMessage, Conflator};
+use quantumarb_corporate_actions::CorporateAction;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_sim::Seed;
+use quantumarb_wire::{utc_now_ns, BboUpdate, Encoding};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::{self, Duration, Instant};
+use synthetic::SyntheticRequest;
+use uuid::Uuid;
+use warp::Filter;
 
 // --- Data Structures ---
 
-/// Using the same BBO update structure as the strategy engine for compatibility.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct BboUpdate {
-    instrument_id: u32,
-    best_bid_price: u64,
-    best_bid_size: u32,
-    best_ask_price: u64,
-    best_ask_size: u32,
-    // Add a timestamp to the data model for replaying
-    timestamp_ns: u64,
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, ApiSchema)]
+enum ReplayStatus {
+    Idle,
+    Running,
+    Completed,
+    Stopped,
+    Failed,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, ApiSchema)]
+enum ReplaySource {
+    Sample,
+    Archive,
+    Synthetic,
+}
+
+/// How far an archive replay has got through the data it selected.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct ArchiveProgress {
+    files_total: usize,
+    files_read: usize,
+    bytes_total: u64,
+    bytes_read: u64,
+    /// Share of the selected bytes read so far.
+    percent_read: f64,
+    /// Event time of the last message published.
+    replay_time_utc: Option<DateTime<Utc>>,
+}
+
+/// Progress of the current (or most recent) replay session.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct ReplaySession {
+    session_id: Option<Uuid>,
+    status: ReplayStatus,
+    source: ReplaySource,
+    speed: f64,
+    events_published: usize,
+    /// Known up front for the sample data only; archive replays report
+    /// `archive` progress instead.
+    total_events: Option<usize>,
+    /// Events whose prices and sizes were back-adjusted for later splits.
+    adjusted_events: usize,
+    started_utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<ArchiveProgress>,
+    /// Seed a synthetic session was generated from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Body of a POST /replay/start request.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct StartReplayRequest {
+    speed: Option<f64>,
+    /// Back-adjust history for splits (default true).
+    adjust_corporate_actions: Option<bool>,
+    /// Replay archived bus history instead of the sample data.
+    archive: Option<ArchiveReplayRequest>,
+    /// Replay a generated market instead of the sample data.
+    synthetic: Option<SyntheticRequest>,
+}
+
+/// Which archived history to replay.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct ArchiveReplayRequest {
+    start_utc: DateTime<Utc>,
+    end_utc: DateTime<Utc>,
+    /// Instruments to replay (default all).
+    #[serde(default)]
+    instruments: Vec<u32>,
+    /// Topics to replay, with NATS-style ">" wildcards (default "market_data.>").
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+/// Session state shared between the API and the replay task. The join handle
+/// lets /replay/stop abort a session mid-stream.
+struct ReplayController {
+    session: ReplaySession,
+    task: Option<JoinHandle<()>>,
+    /// The service's seed, used by synthetic sessions that bring none.
+    seed: Seed,
+}
+
+type SharedController = Arc<Mutex<ReplayController>>;
+
+/// Records fetched from the archive per read while an archive replay runs.
+const ARCHIVE_CHUNK_RECORDS: usize = 10_000;
+
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Market Replay Service ---");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+
+    let controller = Arc::new(Mutex::new(ReplayController {
+        session: ReplaySession {
+            session_id: None,
+            status: ReplayStatus::Idle,
+            source: ReplaySource::Sample,
+            speed: 1.0,
+            events_published: 0,
+            total_events: None,
+            adjusted_events: 0,
+            started_utc: None,
+            archive: None,
+            seed: None,
+            error: None,
+        },
+        task: None,
+        seed,
+    }));
+
+    // --- API Endpoints to control replay sessions ---
+    let start = warp::path!("replay" / "start")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(controller.clone()))
+        .and_then(handler_start_replay);
+    let stop = warp::path!("replay" / "stop")
+        .and(warp::post())
+        .and(with_state(controller.clone()))
+        .and_then(handler_stop_replay);
+    let status = warp::path!("replay" / "status")
+        .and(warp::get())
+        .and(with_state(controller))
+        .and_then(handler_replay_status);
+
+    println!("API server running at http://127.0.0.1:3035/replay");
+    warp::serve(start.or(stop).or(status).or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3035)).await;
+}
+
+/// The OpenAPI document of the replay endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Market Replay Service", "Replays sample, archived or synthetic market data onto the bus.")
+        .errors::<ErrorBody>()
+        .post::<StartReplayRequest, ReplaySession>("/replay/start", "Start a replay session")
+        .operation(Operation::post("/replay/stop", "Stop the running session").response::<ReplaySession>())
+        .get::<ReplaySession>("/replay/status", "The current (or most recent) session")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
 
-    // 1. Load historical data from a source.
-    let historical_data = load_mock_historical_data();
-    println!("Loaded {} historical market data events.", historical_data.len());
+/// Handler for POST /replay/start. Only one session may run at a time.
+async fn handler_start_replay(
+    request: StartReplayRequest,
+    controller: SharedController,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut ctrl = controller.lock().unwrap();
+    if ctrl.session.status == ReplayStatus::Running {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemConflict, "A replay session is already running.")));
+    }
+
+    let speed = request.speed.filter(|s| *s > 0.0).unwrap_or(1.0);
+    let actions = if request.adjust_corporate_actions.unwrap_or(true) {
+        quantumarb_corporate_actions::load_from_env()
+    } else {
+        Vec::new()
+    };
+    let mut session = ReplaySession {
+        session_id: Some(Uuid::new_v4()),
+        status: ReplayStatus::Running,
+        source: ReplaySource::Sample,
+        speed,
+        events_published: 0,
+        total_events: None,
+        adjusted_events: 0,
+        started_utc: Some(Utc::now().to_rfc3339()),
+        archive: None,
+        seed: None,
+        error: None,
+    };
+    let task_controller = controller.clone();
+
+    if request.archive.is_some() && request.synthetic.is_some() {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            "A replay takes either an archive or a synthetic section, not both.",
+        )));
+    }
+
+    let task = match (request.archive, request.synthetic) {
+        // 1a. Stream the requested range from the archive.
+        (Some(archive), _) => {
+            if archive.end_utc <= archive.start_utc {
+                return Ok(error_reply(Rejection::new(
+                    RejectCode::SystemInvalidRequest,
+                    "The archive end_utc must be after start_utc.",
+                )));
+            }
+            let store = match quantumarb_archive::open_store() {
+                Ok(store) => store,
+                Err(e) => {
+                    return Ok(error_reply(Rejection::new(
+                        RejectCode::SystemStateUnavailable,
+                        format!("The archive store is unavailable: {}", e),
+                    )))
+                }
+            };
+            let query = ArchiveQuery {
+                start: archive.start_utc,
+                end: archive.end_utc,
+                instrument_ids: archive.instruments,
+                topics: archive.topics,
+            };
+            println!("Replaying archived bus traffic from {} to {}.", query.start, query.end);
+            session.source = ReplaySource::Archive;
+            tokio::spawn(async move {
+                replay_archive(store, query, speed, actions, task_controller).await;
+            })
+        }
+        // 1b. Generate a synthetic market.
+        (None, Some(synthetic)) => {
+            if let Err(e) = synthetic.validate() {
+                return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, e)));
+            }
+            let seed = synthetic.seed.or(ctrl.seed.value()).unwrap_or_else(rand::random);
+            let mut rng = Seed::fixed(seed).stream("market_replay.synthetic");
+            let generated = synthetic::generate(&synthetic, utc_now_ns(), &mut rng);
+            println!("Generated {} synthetic market data events (seed {}).", generated.len(), seed);
+            session.source = ReplaySource::Synthetic;
+            session.total_events = Some(generated.len());
+            session.seed = Some(seed);
+            tokio::spawn(async move {
+                replay_market_data(generated, speed, task_controller).await;
+            })
+        }
+        // 1c. Load the built-in sample data.
+        (None, None) => {
+            let mut historical_data = load_mock_historical_data();
+            println!("Loaded {} historical market data events.", historical_data.len());
+            session.total_events = Some(historical_data.len());
+            session.adjusted_events = back_adjust(&mut historical_data, &actions);
+            tokio::spawn(async move {
+                replay_market_data(historical_data, speed, task_controller).await;
+            })
+        }
+    };
+
+    // 2. The replay loop is running; hand back the new session.
+    ctrl.session = session;
+    ctrl.task = Some(task);
+    Ok(warp::reply::with_status(warp::reply::json(&ctrl.session), warp::http::StatusCode::OK))
+}
 
-    // 2. Start the replay loop.
-    replay_market_data(historical_data).await;
+fn error_reply(rejection: Rejection) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+/// Handler for POST /replay/stop.
+async fn handler_stop_replay(controller: SharedController) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut ctrl = controller.lock().unwrap();
+    if let Some(task) = ctrl.task.take() {
+        task.abort();
+    }
+    if ctrl.session.status == ReplayStatus::Running {
+        ctrl.session.status = ReplayStatus::Stopped;
+        println!("\n--- Market Replay Stopped by operator ---");
+    }
+    Ok(warp::reply::json(&ctrl.session))
+}
+
+/// Handler for GET /replay/status.
+async fn handler_replay_status(controller: SharedController) -> Result<impl warp::Reply, warp::Rejection> {
+    let session = controller.lock().unwrap().session.clone();
+    Ok(warp::reply::json(&session))
 }
 
 /// Loads a mock dataset representing a few seconds of market activity.
 fn load_mock_historical_data() -> Vec<BboUpdate> {
     vec![
-        BboUpdate { instrument_id: 1, best_bid_price: 60000_05, best_ask_price: 60000_15, best_bid_size: 10, best_ask_size: 12, timestamp_ns: 1000000000 }, // Time 1.0s
-        BboUpdate { instrument_id: 2, best_bid_price: 60035_10, best_ask_price: 60035_22, best_bid_size: 5, best_ask_size: 8, timestamp_ns: 1000500000 },  // Time 1.0005s
-        BboUpdate { instrument_id: 1, best_bid_price: 60000_04, best_ask_price: 60000_14, best_bid_size: 15, best_ask_size: 10, timestamp_ns: 1001000000 }, // Time 1.001s
-        BboUpdate { instrument_id: 1, best_bid_price: 60000_06, best_ask_price: 60000_16, best_bid_size: 8, best_ask_size: 11, timestamp_ns: 2000000000 },  // Time 2.0s
-        BboUpdate { instrument_id: 2, best_bid_price: 60035_09, best_ask_price: 60035_21, best_bid_size: 7, best_ask_size: 9, timestamp_ns: 2000800000 },  // Time 2.0008s
+        BboUpdate { instrument_id: 1, best_bid_price: 60000_05, best_ask_price: 60000_15, best_bid_size: 10, best_ask_size: 12, timestamp_ns: 1000000000, venue_id: 1 }, // Time 1.0s
+        BboUpdate { instrument_id: 2, best_bid_price: 60035_10, best_ask_price: 60035_22, best_bid_size: 5, best_ask_size: 8, timestamp_ns: 1000500000, venue_id: 2 },  // Time 1.0005s
+        BboUpdate { instrument_id: 1, best_bid_price: 60000_04, best_ask_price: 60000_14, best_bid_size: 15, best_ask_size: 10, timestamp_ns: 1001000000, venue_id: 1 }, // Time 1.001s
+        BboUpdate { instrument_id: 1, best_bid_price: 60000_06, best_ask_price: 60000_16, best_bid_size: 8, best_ask_size: 11, timestamp_ns: 2000000000, venue_id: 1 },  // Time 2.0s
+        BboUpdate { instrument_id: 2, best_bid_price: 60035_09, best_ask_price: 60035_21, best_bid_size: 7, best_ask_size: 9, timestamp_ns: 2000800000, venue_id: 2 },  // Time 2.0008s
     ]
 }
 
-/// The core replay logic.
-async fn replay_market_data(data: Vec<BboUpdate>) {
+/// Back-adjusts prices and sizes for every split going ex after each event.
+/// Returns the number of events changed.
+fn back_adjust(data: &mut [BboUpdate], actions: &[CorporateAction]) -> usize {
+    let adjusted = data.iter_mut().map(|event| adjust_event(event, actions)).filter(|changed| *changed).count();
+    if adjusted > 0 {
+        println!("Back-adjusted {} event(s) for corporate actions.", adjusted);
+    }
+    adjusted
+}
+
+/// Back-adjusts one event; returns whether anything changed.
+fn adjust_event(event: &mut BboUpdate, actions: &[CorporateAction]) -> bool {
+    let (price_factor, size_factor) =
+        quantumarb_corporate_actions::back_adjustment(actions, event.instrument_id, event.timestamp_ns);
+    if price_factor == 1.0 {
+        return false;
+    }
+    event.best_bid_price = (event.best_bid_price as f64 * price_factor).round() as u64;
+    event.best_ask_price = (event.best_ask_price as f64 * price_factor).round() as u64;
+    event.best_bid_size = (event.best_bid_size as f64 * size_factor).round() as u32;
+    event.best_ask_size = (event.best_ask_size as f64 * size_factor).round() as u32;
+    true
+}
+
+/// The core replay logic. `speed` scales the original inter-event gaps
+/// (2.0 replays twice as fast as real time).
+async fn replay_market_data(data: Vec<BboUpdate>, speed: f64, controller: SharedController) {
     if data.is_empty() {
         println!("No data to replay.");
+        let mut ctrl = controller.lock().unwrap();
+        ctrl.session.status = ReplayStatus::Completed;
+        ctrl.task = None;
         return;
     }
 
-    println!("\n--- Starting Market Replay in 3 seconds... ---");
+    let encoding = Encoding::from_env();
+    println!("\n--- Starting Market Replay at {}x in 3 seconds... ---", speed);
     time::sleep(Duration::from_secs(3)).await;
 
     let start_time = Instant::now();
     let first_event_timestamp = data[0].timestamp_ns;
+    let mut conflator = Conflator::from_env();
 
-    for event in data {
+    for (seq, event) in data.into_iter().enumerate() {
         // Calculate how long to wait before publishing the next event to simulate real-time.
-        let elapsed_time_ns = event.timestamp_ns - first_event_timestamp;
+        let elapsed_time_ns = ((event.timestamp_ns - first_event_timestamp) as f64 / speed) as u64;
         let target_instant = start_time + Duration::from_nanos(elapsed_time_ns);
-        
+
+        // Held conflated updates that come due meanwhile go out on time.
+        while let Some(due) = conflator.next_due().map(Instant::from_std).filter(|due| *due < target_instant) {
+            time::sleep_until(due).await;
+            conflator.due(due.into_std()).iter().for_each(publish_conflated);
+        }
         let now = Instant::now();
         if target_instant > now {
             time::sleep_until(target_instant).await;
         }
 
         // Publish the event to the internal message bus.
-        publish_to_internal_bus(&event);
+        publish_to_internal_bus(&event, encoding);
+        let (topic, payload) = (topics::market_data(event.instrument_id), encoding.encode(&event));
+        let message = BusMessage { seq: seq as u64, topic, payload };
+        if let Some(message) = conflator.offer(message, Instant::now().into_std()) {
+            publish_conflated(&message);
+        }
+        controller.lock().unwrap().session.events_published += 1;
     }
+    conflator.flush().iter().for_each(publish_conflated);
+    println!("  -> Conflated topics coalesced {} updates.", conflator.coalesced());
 
+    let mut ctrl = controller.lock().unwrap();
+    ctrl.session.status = ReplayStatus::Completed;
+    ctrl.task = None;
     println!("\n--- Market Replay Complete ---");
 }
 
 /// Simulates publishing the event to an internal message bus like NATS.
-fn publish_to_internal_bus(event: &BboUpdate) {
+fn publish_to_internal_bus(event: &BboUpdate, encoding: Encoding) {
     let topic = format!("market_data.instrument.{}", event.instrument_id);
-    let event_json = serde_json::to_string(event).unwrap();
+    let payload = encoding.encode(event);
     println!(
-        "[{:.3}s] Publishing to topic '{}': Price={}",
+        "[{:.3}s] Publishing to topic '{}' ({} bytes, {}): Price={}",
         Instant::now().elapsed().as_secs_f32(),
         topic,
+        payload.len(),
+        encoding.content_type(),
         event.best_bid_price
     );
     // In a real system:
-    // nats_client.publish(&topic, event_json.as_bytes()).await.unwrap();
+    // nats_client.publish(&topic, payload.into()).await.unwrap();
+}
+
+/// Publishes an update on the conflated variant of its topic.
+fn publish_conflated(message: &BusMessage) {
+    let Some(topic) = topics::conflated(&message.topic) else {
+        return;
+    };
+    println!("  -> Publishing to topic '{}' ({} bytes)", topic, message.payload.len());
+    // In a real system:
+    // nats_client.publish(&topic, message.payload.clone().into()).await.unwrap();
+}
+
+/// Replays archived bus traffic, reading the next chunk from the store only
+/// once the previous one has been published. Timing follows the archived
+/// event times, scaled by `speed` as for the sample data.
+async fn replay_archive(
+    store: Arc<dyn object_store::ObjectStore>,
+    query: ArchiveQuery,
+    speed: f64,
+    actions: Vec<CorporateAction>,
+    controller: SharedController,
+) {
+    let prefix = quantumarb_archive::prefix_from_env();
+    let mut reader = match ArchiveReader::open(store, &prefix, query).await {
+        Ok(reader) => reader,
+        Err(e) => return fail_session(&controller, e.to_string()),
+    };
+    let selected = reader.progress();
+    println!("Selected {} archive file(s), {} bytes.", selected.files_total, selected.bytes_total);
+    record_archive_progress(&controller, selected, None);
+
+    println!("\n--- Starting Archive Replay at {}x in 3 seconds... ---", speed);
+    time::sleep(Duration::from_secs(3)).await;
+
+    // (wall clock start, event time of the first record)
+    let mut clock: Option<(Instant, i64)> = None;
+    loop {
+        let chunk = match reader.next_chunk(ARCHIVE_CHUNK_RECORDS).await {
+            Ok(chunk) if chunk.is_empty() => break,
+            Ok(chunk) => chunk,
+            Err(e) => return fail_session(&controller, e.to_string()),
+        };
+        let mut replay_time = None;
+        for mut record in chunk {
+            let (start_time, first_event_ns) = *clock.get_or_insert((Instant::now(), record.event_time_ns));
+            let elapsed_time_ns = ((record.event_time_ns - first_event_ns).max(0) as f64 / speed) as u64;
+            time::sleep_until(start_time + Duration::from_nanos(elapsed_time_ns)).await;
+
+            let adjusted = adjust_archived(&mut record, &actions);
+            publish_archived(&record);
+            replay_time = Some(DateTime::from_timestamp_nanos(record.event_time_ns));
+
+            let mut ctrl = controller.lock().unwrap();
+            ctrl.session.events_published += 1;
+            if adjusted {
+                ctrl.session.adjusted_events += 1;
+            }
+        }
+        record_archive_progress(&controller, reader.progress(), replay_time);
+    }
+
+    let mut ctrl = controller.lock().unwrap();
+    ctrl.session.status = ReplayStatus::Completed;
+    ctrl.task = None;
+    println!("\n--- Archive Replay Complete ---");
+}
+
+/// Back-adjusts an archived market data message for splits, re-encoding it
+/// in the encoding it was archived in.
+fn adjust_archived(record: &mut ArchiveRecord, actions: &[CorporateAction]) -> bool {
+    if actions.is_empty() || !record.topic.starts_with("market_data.") {
+        return false;
+    }
+    let Ok(mut event) = Encoding::decode_any::<BboUpdate>(&record.payload) else {
+        return false;
+    };
+    if !adjust_event(&mut event, actions) {
+        return false;
+    }
+    let encoding =
+        if record.content_type == Encoding::Json.content_type() { Encoding::Json } else { Encoding::Binary };
+    record.payload = encoding.encode(&event);
+    true
+}
+
+fn record_archive_progress(controller: &SharedController, progress: ReadProgress, replay_time: Option<DateTime<Utc>>) {
+    let mut ctrl = controller.lock().unwrap();
+    let previous_time = ctrl.session.archive.as_ref().and_then(|a| a.replay_time_utc);
+    ctrl.session.archive = Some(ArchiveProgress {
+        files_total: progress.files_total,
+        files_read: progress.files_read,
+        bytes_total: progress.bytes_total,
+        bytes_read: progress.bytes_read,
+        percent_read: if progress.bytes_total > 0 {
+            progress.bytes_read as f64 / progress.bytes_total as f64 * 100.0
+        } else {
+            100.0
+        },
+        replay_time_utc: replay_time.or(previous_time),
+    });
+}
+
+fn fail_session(controller: &SharedController, error: String) {
+    println!("\n--- Archive Replay Failed: {} ---", error);
+    let mut ctrl = controller.lock().unwrap();
+    ctrl.session.status = ReplayStatus::Failed;
+    ctrl.session.error = Some(error);
+    ctrl.task = None;
+}
+
+/// Republishes an archived message on its original topic, unchanged.
+fn publish_archived(record: &ArchiveRecord) {
+    println!(
+        "[{}] Publishing to topic '{}' ({} bytes, {})",
+        DateTime::from_timestamp_nanos(record.event_time_ns).format("%Y-%m-%d %H:%M:%S%.3f"),
+        record.topic,
+        record.payload.len(),
+        record.content_type
+    );
+    // In a real system:
+    // nats_client.publish(&record.topic, record.payload.clone().into()).await.unwrap();
 }
@@ -0,0 +1,291 @@
+/*
+ * QuantumArb 2.0 - Core Services: Synthetic Markets
+ *
+ * File: src/core_services/market_replay_service/synthetic.rs
+ *
+ * Description:
+ * Generates market data for markets that never happened, so a strategy can
+ * be stressed against paths history does not contain. Each instrument's mid
+ * follows one of four models, with parameters annualised:
+ *
+ *   gbm               geometric Brownian motion: constant drift and volatility
+ *   heston            the variance mean-reverts and has its own volatility,
+ *                     correlated with the price (full-truncation Euler)
+ *   jump_diffusion    GBM plus Poisson jumps with normal log sizes (Merton);
+ *                     the drift is compensated, so jumps add risk, not return
+ *   regime_switching  GBM whose drift and volatility switch between regimes,
+ *                     each lasting an exponential time of the given mean
+ *
+ * The mid is quoted either side at a fixed spread, with random sizes, as a
+ * BBO update every `interval_ms` of simulated time. All draws come from one
+ * stream keyed by the session's seed, so the same request and seed always
+ * give the same prices and sizes.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_sim::SimRng;
+use quantumarb_wire::BboUpdate;
+use rand::Rng;
+use rand_distr::{Distribution, Exp, Normal, Poisson, StandardNormal};
+use serde::Deserialize;
+
+/// Upper bound on the events one session may generate.
+pub const MAX_EVENTS: usize = 1_000_000;
+
+const MILLIS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0 * 1000.0;
+
+// --- Request ---
+
+/// The price process, tagged by "model".
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum MarketModel {
+    Gbm {
+        drift: f64,
+        volatility: f64,
+    },
+    Heston {
+        drift: f64,
+        /// Variance at the start of the path.
+        initial_variance: f64,
+        /// Speed at which the variance returns to its long-run level.
+        mean_reversion: f64,
+        long_run_variance: f64,
+        vol_of_vol: f64,
+        /// Between the price and variance shocks; negative gives the leverage effect.
+        correlation: f64,
+    },
+    JumpDiffusion {
+        drift: f64,
+        volatility: f64,
+        /// Expected jumps a year.
+        jump_intensity: f64,
+        /// Mean and standard deviation of a jump's log size.
+        jump_mean: f64,
+        jump_std: f64,
+    },
+    RegimeSwitching {
+        regimes: Vec<Regime>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct Regime {
+    pub drift: f64,
+    pub volatility: f64,
+    /// Mean time spent in the regime before switching to another.
+    pub mean_duration_secs: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct SyntheticInstrument {
+    pub instrument_id: u32,
+    /// Starting mid, in price units.
+    pub start_price: f64,
+    #[serde(default = "default_spread_bps")]
+    pub spread_bps: f64,
+}
+
+fn default_spread_bps() -> f64 {
+    2.0
+}
+
+/// The "synthetic" section of a POST /replay/start request.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct SyntheticRequest {
+    pub model: MarketModel,
+    pub instruments: Vec<SyntheticInstrument>,
+    /// Updates per instrument.
+    pub events: usize,
+    /// Simulated time between updates (default 100ms).
+    pub interval_ms: Option<u64>,
+    /// Draws with this seed instead of the service's.
+    pub seed: Option<u64>,
+    /// Venue id stamped on the updates (default 1).
+    pub venue_id: Option<u32>,
+}
+
+impl SyntheticRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.instruments.is_empty() || self.events == 0 {
+            return Err("A synthetic replay needs at least one instrument and one event.".to_string());
+        }
+        if self.instruments.len().saturating_mul(self.events) > MAX_EVENTS {
+            return Err(format!("A synthetic replay may generate at most {} events.", MAX_EVENTS));
+        }
+        if self.interval_ms == Some(0) {
+            return Err("interval_ms must be positive.".to_string());
+        }
+        if let Some(bad) = self.instruments.iter().find(|i| !(i.start_price > 0.0 && i.spread_bps >= 0.0)) {
+            return Err(format!("Instrument {} needs a positive start_price and spread_bps.", bad.instrument_id));
+        }
+        let valid = match &self.model {
+            MarketModel::Gbm { volatility, .. } => *volatility >= 0.0,
+            MarketModel::Heston {
+                initial_variance, mean_reversion, long_run_variance, vol_of_vol, correlation, ..
+            } => {
+                [initial_variance, mean_reversion, long_run_variance, vol_of_vol].iter().all(|v| **v >= 0.0)
+                    && (-1.0..=1.0).contains(correlation)
+            }
+            MarketModel::JumpDiffusion { volatility, jump_intensity, jump_std, .. } => {
+                *volatility >= 0.0 && *jump_intensity >= 0.0 && *jump_std >= 0.0
+            }
+            MarketModel::RegimeSwitching { regimes } => {
+                !regimes.is_empty() && regimes.iter().all(|r| r.volatility >= 0.0 && r.mean_duration_secs > 0.0)
+            }
+        };
+        if !valid {
+            return Err("The model's volatilities, variances, intensities and durations must not be negative, \
+                        and its correlation must lie in [-1, 1]."
+                .to_string());
+        }
+        Ok(())
+    }
+}
+
+// --- Generation ---
+
+/// The state one instrument's path carries from step to step.
+struct Path {
+    log_price: f64,
+    variance: f64,
+    regime: usize,
+}
+
+/// Generates `request.events` updates per instrument, interleaved in time
+/// order, the first at `start_ns`. Call `validate` first.
+pub fn generate(request: &SyntheticRequest, start_ns: u64, rng: &mut SimRng) -> Vec<BboUpdate> {
+    let interval_ms = request.interval_ms.unwrap_or(100);
+    let dt = interval_ms as f64 / MILLIS_PER_YEAR;
+    let mut paths: Vec<Path> = request
+        .instruments
+        .iter()
+        .map(|instrument| Path {
+            log_price: instrument.start_price.ln(),
+            variance: match request.model {
+                MarketModel::Heston { initial_variance, .. } => initial_variance,
+                _ => 0.0,
+            },
+            regime: 0,
+        })
+        .collect();
+
+    let mut updates = Vec::with_capacity(request.instruments.len() * request.events);
+    for step in 0..request.events {
+        let timestamp_ns = start_ns + step as u64 * interval_ms * 1_000_000;
+        for (instrument, path) in request.instruments.iter().zip(paths.iter_mut()) {
+            if step > 0 {
+                advance(&request.model, path, interval_ms, dt, rng);
+            }
+            updates.push(quote(instrument, path.log_price.exp(), timestamp_ns, request.venue_id.unwrap_or(1), rng));
+        }
+    }
+    updates
+}
+
+/// Moves one path on by a step of `dt` years.
+fn advance(model: &MarketModel, path: &mut Path, interval_ms: u64, dt: f64, rng: &mut SimRng) {
+    let z: f64 = StandardNormal.sample(rng);
+    match model {
+        MarketModel::Gbm { drift, volatility } => {
+            path.log_price += (drift - volatility * volatility / 2.0) * dt + volatility * dt.sqrt() * z;
+        }
+        MarketModel::Heston { drift, mean_reversion, long_run_variance, vol_of_vol, correlation, .. } => {
+            // Full truncation: a variance driven below zero is used as zero.
+            let variance = path.variance.max(0.0);
+            let independent: f64 = StandardNormal.sample(rng);
+            let z_variance = correlation * z + (1.0 - correlation * correlation).sqrt() * independent;
+            path.log_price += (drift - variance / 2.0) * dt + (variance * dt).sqrt() * z;
+            path.variance += mean_reversion * (long_run_variance - variance) * dt
+                + vol_of_vol * (variance * dt).sqrt() * z_variance;
+        }
+        MarketModel::JumpDiffusion { drift, volatility, jump_intensity, jump_mean, jump_std } => {
+            let compensator = jump_intensity * ((jump_mean + jump_std * jump_std / 2.0).exp() - 1.0);
+            path.log_price += (drift - compensator - volatility * volatility / 2.0) * dt + volatility * dt.sqrt() * z;
+            if *jump_intensity > 0.0 {
+                let jumps = Poisson::new(jump_intensity * dt).map_or(0.0, |p| p.sample(rng));
+                let size = Normal::new(*jump_mean, *jump_std).expect("validated jump_std");
+                path.log_price += (0..jumps as u64).map(|_| size.sample(rng)).sum::<f64>();
+            }
+        }
+        MarketModel::RegimeSwitching { regimes } => {
+            let regime = &regimes[path.regime];
+            path.log_price +=
+                (regime.drift - regime.volatility * regime.volatility / 2.0) * dt + regime.volatility * dt.sqrt() * z;
+            // Leaving within the step has probability 1 - exp(-step / mean duration).
+            let leaves = Exp::new(1.0 / regime.mean_duration_secs).expect("validated duration").sample(rng);
+            if regimes.len() > 1 && leaves < interval_ms as f64 / 1000.0 {
+                let next = rng.gen_range(0..regimes.len() - 1);
+                path.regime = if next >= path.regime { next + 1 } else { next };
+            }
+        }
+    }
+}
+
+/// Quotes `mid` at the instrument's spread, in wire hundredths, with the
+/// ask at least one hundredth above the bid.
+fn quote(instrument: &SyntheticInstrument, mid: f64, timestamp_ns: u64, venue_id: u32, rng: &mut SimRng) -> BboUpdate {
+    let half_spread = mid * instrument.spread_bps / 20_000.0;
+    let best_bid_price = ((mid - half_spread) * 100.0).floor().max(1.0) as u64;
+    let best_ask_price = (((mid + half_spread) * 100.0).ceil() as u64).max(best_bid_price + 1);
+    BboUpdate {
+        instrument_id: instrument.instrument_id,
+        best_bid_price,
+        best_ask_price,
+        best_bid_size: rng.gen_range(1..=20),
+        best_ask_size: rng.gen_range(1..=20),
+        timestamp_ns,
+        venue_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_sim::Seed;
+
+    fn request(model: MarketModel) -> SyntheticRequest {
+        SyntheticRequest {
+            model,
+            instruments: vec![
+                SyntheticInstrument { instrument_id: 1, start_price: 60_000.0, spread_bps: 2.0 },
+                SyntheticInstrument { instrument_id: 2, start_price: 3_000.0, spread_bps: 2.0 },
+            ],
+            events: 500,
+            interval_ms: Some(60_000),
+            seed: None,
+            venue_id: None,
+        }
+    }
+
+    #[test]
+    fn paths_repeat_with_the_seed_and_follow_the_model() {
+        let heston = request(MarketModel::Heston {
+            drift: 0.0,
+            initial_variance: 0.64,
+            mean_reversion: 2.0,
+            long_run_variance: 0.64,
+            vol_of_vol: 1.0,
+            correlation: -0.7,
+        });
+        assert!(heston.validate().is_ok());
+        let run = |seed| generate(&heston, 1_000, &mut Seed::fixed(seed).stream("market_replay.synthetic"));
+        let path = run(7);
+        assert_eq!(path, run(7));
+        assert_ne!(path, run(8));
+        assert_eq!(path.len(), 1_000);
+        assert_eq!((path[0].instrument_id, path[1].instrument_id, path[2].timestamp_ns), (1, 2, 60_000_001_000));
+        assert!(path.iter().all(|u| u.best_bid_price < u.best_ask_price));
+
+        // Without volatility a GBM path is its drift.
+        let mut rng = Seed::fixed(1).stream("test");
+        let flat = generate(&request(MarketModel::Gbm { drift: 0.0, volatility: 0.0 }), 0, &mut rng);
+        let btc = flat.iter().filter(|u| u.instrument_id == 1);
+        assert!(btc.map(|u| (u.best_bid_price, u.best_ask_price)).all(|(bid, ask)| {
+            bid.abs_diff(59_994_00) <= 1 && ask.abs_diff(60_006_00) <= 1
+        }));
+
+        let no_regimes = request(MarketModel::RegimeSwitching { regimes: vec![] });
+        assert!(no_regimes.validate().is_err());
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * QuantumArb 2.0 - Core Services: Mock Exchange
+ *
+ * File: src/core_services/mock_exchange/main.rs
+ *
+ * Description:
+ * This microservice is a stand-in venue, so integration tests and demos of
+ * the whole platform can run end to end without reaching a real exchange.
+ * It lists every instrument in the reference data and matches orders with
+ * price-time priority (see `matching.rs`).
+ *
+ * Its primary role is to:
+ * 1. Take orders and cancels over HTTP (port 3043) and answer with the
+ *    venue's execution reports for them: the ack, any fills, and the cancel
+ *    of whatever an IOC or market order could not fill.
+ *    - POST /orders                   -> submit an order
+ *    - DELETE /orders/{order_id}      -> cancel what is left of it
+ *    - GET /orders/{order_id}         -> the order as the exchange sees it
+ *    - GET /book/{instrument_id}      -> the book, 10 levels a side
+ *    - GET /openapi.json, GET /docs   -> the API's OpenAPI document, Swagger UI
+ * 2. Publish every execution report, the resting orders' fills included, on
+ *    'mock_exchange.execution_reports' (a drop copy, as a venue would send).
+ * 3. Publish market data like a venue feed: the top of book on
+ *    'market_data.instrument.<id>' whenever it changes (stamped with
+ *    QA_MOCK_EXCHANGE_VENUE_ID, default 1), and every trade on
+ *    'mock_exchange.trades.instrument.<id>'.
+ *
+ * Unless QA_MOCK_EXCHANGE_FLOW=false, simulated traders keep BTC and ETH
+ * books alive: they rest orders either side of a random-walk mid, cancel
+ * their oldest ones, and now and then cross the spread. With --seed N (or
+ * QA_SEED) the flow is seeded.
+ *
+ * Events go on the bus in the binary `quantumarb-wire` encoding by default;
+ * set QA_BUS_ENCODING=json to publish JSON instead when debugging.
+ */
+
+mod matching;
+
+use matching::{BookDepth, MatchingEngine, NewOrder, OrderState, Outcome, TimeInForce};
+use quantumarb_bus::topics;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_refdata::ReferenceData;
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_wire::{utc_now_ns, Encoding, ExecutionReport, OrderSide};
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::Filter;
+
+/// Drop copy of every execution report the exchange sends.
+const EXECUTION_REPORTS_TOPIC: &str = "mock_exchange.execution_reports";
+const TRADES_PREFIX: &str = "mock_exchange.trades.instrument.";
+/// Levels a side on GET /book.
+const BOOK_DEPTH: usize = 10;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone)]
+struct ExchangeConfig {
+    venue_id: u32,
+    simulated_flow: bool,
+}
+
+impl ExchangeConfig {
+    fn from_env() -> ExchangeConfig {
+        ExchangeConfig {
+            venue_id: std::env::var("QA_MOCK_EXCHANGE_VENUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(1),
+            simulated_flow: std::env::var("QA_MOCK_EXCHANGE_FLOW").map(|v| v != "false").unwrap_or(true),
+        }
+    }
+}
+
+/// The engine and where its output goes.
+#[derive(Clone)]
+struct Exchange {
+    engine: Arc<Mutex<MatchingEngine>>,
+    venue_id: u32,
+    encoding: Encoding,
+}
+
+impl Exchange {
+    /// Publishes what an order or cancel did: its reports, its trades and,
+    /// if the top of the book moved, the new one.
+    fn publish(&self, instrument_id: u32, outcome: &Outcome) {
+        for report in &outcome.reports {
+            let payload = self.encoding.encode(report);
+            println!(
+                "  -> Publishing to topic '{}' ({} bytes): {} {:?}, {} filled, {} left",
+                EXECUTION_REPORTS_TOPIC,
+                payload.len(),
+                report.internal_order_id,
+                report.status,
+                report.cumulative_size,
+                report.leaves_size
+            );
+        }
+        for trade in &outcome.trades {
+            quantumarb_bus::publish_json(&format!("{}{}", TRADES_PREFIX, instrument_id), trade);
+        }
+        if outcome.top_changed {
+            let bbo = self.engine.lock().unwrap().bbo(instrument_id, self.venue_id, utc_now_ns());
+            let payload = self.encoding.encode(&bbo);
+            println!(
+                "  -> Publishing to topic '{}' ({} bytes): {} x {} / {} x {}",
+                topics::market_data(instrument_id),
+                payload.len(),
+                bbo.best_bid_size,
+                bbo.best_bid_price,
+                bbo.best_ask_size,
+                bbo.best_ask_price
+            );
+        }
+        // In a real system:
+        // nats_client.publish(&topic, payload.into()).await.unwrap();
+    }
+
+    fn submit(&self, order: NewOrder) -> Result<Outcome, Rejection> {
+        let instrument_id = order.instrument_id;
+        let outcome = self.engine.lock().unwrap().submit(order, utc_now_ns())?;
+        self.publish(instrument_id, &outcome);
+        Ok(outcome)
+    }
+
+    fn cancel(&self, order_id: &Uuid) -> Result<Outcome, Rejection> {
+        let mut engine = self.engine.lock().unwrap();
+        let outcome = engine.cancel(order_id, utc_now_ns())?;
+        let instrument_id = engine.order(order_id).map_or(0, |order| order.instrument_id);
+        drop(engine);
+        self.publish(instrument_id, &outcome);
+        Ok(outcome)
+    }
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Mock Exchange ---");
+
+    let config = ExchangeConfig::from_env();
+    println!("Mock exchange: {:?}", config);
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
+    let exchange = Exchange {
+        engine: Arc::new(Mutex::new(MatchingEngine::new(instruments))),
+        venue_id: config.venue_id,
+        encoding: Encoding::from_env(),
+    };
+
+    // Task 1: simulated traders keeping the books alive.
+    if config.simulated_flow {
+        let (flow_exchange, flow_rng) = (exchange.clone(), seed.stream("mock_exchange.flow"));
+        tokio::spawn(async move {
+            simulate_flow(flow_exchange, flow_rng).await;
+        });
+    }
+
+    // --- API Endpoints for orders and the book ---
+    let submit = warp::path!("orders")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(exchange.clone()))
+        .and_then(handler_submit_order);
+    let cancel = warp::path!("orders" / Uuid)
+        .and(warp::delete())
+        .and(with_state(exchange.clone()))
+        .and_then(handler_cancel_order);
+    let get_order = warp::path!("orders" / Uuid)
+        .and(warp::get())
+        .and(with_state(exchange.clone()))
+        .and_then(handler_get_order);
+    let get_book = warp::path!("book" / u32)
+        .and(warp::get())
+        .and(with_state(exchange))
+        .and_then(handler_get_book);
+
+    println!("API server running at http://127.0.0.1:3043/orders and /book");
+    let routes = submit.or(cancel).or(get_order).or(get_book).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3043)).await;
+}
+
+/// The OpenAPI document of the order and book endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Mock Exchange", "A stand-in venue matching orders with price-time priority.")
+        .errors::<ErrorBody>()
+        .post::<NewOrder, Vec<ExecutionReport>>("/orders", "Submit an order; answers its execution reports")
+        .operation(
+            Operation::delete("/orders/{order_id}", "Cancel what is left of an order")
+                .path_param::<Uuid>("order_id")
+                .response::<ExecutionReport>(),
+        )
+        .operation(
+            Operation::get("/orders/{order_id}", "The order as the exchange sees it")
+                .path_param::<Uuid>("order_id")
+                .response::<OrderState>(),
+        )
+        .operation(
+            Operation::get("/book/{instrument_id}", "The book, 10 levels a side")
+                .path_param::<u32>("instrument_id")
+                .response::<BookDepth>(),
+        )
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Builds a standard `{ "error": { code, category, message } }` response.
+fn error_reply(rejection: Rejection) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = StatusCode::from_u16(rejection.code.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+/// Handler for POST /orders: the order's own reports, in order.
+async fn handler_submit_order(order: NewOrder, exchange: Exchange) -> Result<impl warp::Reply, warp::Rejection> {
+    let order_id = order.order_id;
+    Ok(match exchange.submit(order) {
+        Ok(outcome) => {
+            let reports: Vec<_> = outcome.reports.iter().filter(|r| r.internal_order_id == order_id).collect();
+            warp::reply::with_status(warp::reply::json(&reports), StatusCode::OK)
+        }
+        Err(rejection) => error_reply(rejection),
+    })
+}
+
+/// Handler for DELETE /orders/{order_id}.
+async fn handler_cancel_order(order_id: Uuid, exchange: Exchange) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(match exchange.cancel(&order_id) {
+        Ok(outcome) => warp::reply::with_status(warp::reply::json(&outcome.reports[0]), StatusCode::OK),
+        Err(rejection) => error_reply(rejection),
+    })
+}
+
+/// Handler for GET /orders/{order_id}.
+async fn handler_get_order(order_id: Uuid, exchange: Exchange) -> Result<impl warp::Reply, warp::Rejection> {
+    let order = exchange.engine.lock().unwrap().order(&order_id).cloned();
+    Ok(match order {
+        Some(order) => warp::reply::with_status(warp::reply::json(&order), StatusCode::OK),
+        None => {
+            let message = format!("Order {} is unknown", order_id);
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+            warp::reply::with_status(warp::reply::json(&body), StatusCode::NOT_FOUND)
+        }
+    })
+}
+
+/// Handler for GET /book/{instrument_id}.
+async fn handler_get_book(instrument_id: u32, exchange: Exchange) -> Result<impl warp::Reply, warp::Rejection> {
+    let depth = exchange.engine.lock().unwrap().depth(instrument_id, BOOK_DEPTH);
+    Ok(match depth {
+        Some(depth) => warp::reply::with_status(warp::reply::json(&depth), StatusCode::OK),
+        None => {
+            let message = format!("Instrument {} is not listed", instrument_id);
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+            warp::reply::with_status(warp::reply::json(&body), StatusCode::NOT_FOUND)
+        }
+    })
+}
+
+/// Simulated traders: every 250ms each book gets a resting order a few
+/// cents from a random-walk mid, the oldest resting orders are canceled once
+/// a trader has 40 working, and one tick in five an IOC crosses the spread.
+async fn simulate_flow(exchange: Exchange, mut rng: SimRng) {
+    // (instrument id, mid in hundredths)
+    let mut mids: Vec<(u32, u64)> = vec![(1, 60_000_00), (2, 3_000_00)];
+    let mut working: HashMap<u32, VecDeque<Uuid>> = HashMap::new();
+    let mut interval = time::interval(Duration::from_millis(250));
+    loop {
+        interval.tick().await;
+        for (instrument_id, mid) in mids.iter_mut() {
+            *mid = mid.saturating_add_signed(rng.gen_range(-5..=5));
+            let side = if rng.gen_bool(0.5) { OrderSide::Buy } else { OrderSide::Sell };
+            let offset = rng.gen_range(1..=20);
+            let price = match side {
+                OrderSide::Buy => *mid - offset,
+                OrderSide::Sell => *mid + offset,
+            };
+            let resting = NewOrder {
+                order_id: quantumarb_sim::uuid(&mut rng),
+                instrument_id: *instrument_id,
+                side,
+                price,
+                size: rng.gen_range(1..=5),
+                time_in_force: TimeInForce::Gtc,
+            };
+            let queue = working.entry(*instrument_id).or_default();
+            if exchange.submit(resting.clone()).is_ok() {
+                queue.push_back(resting.order_id);
+            }
+            if queue.len() > 40 {
+                // Filled orders are already gone; the cancel is refused.
+                let _ = exchange.cancel(&queue.pop_front().unwrap());
+            }
+            if rng.gen_bool(0.2) {
+                let taker = NewOrder {
+                    order_id: quantumarb_sim::uuid(&mut rng),
+                    price: 0,
+                    size: rng.gen_range(1..=3),
+                    time_in_force: TimeInForce::Ioc,
+                    side: match side {
+                        OrderSide::Buy => OrderSide::Sell,
+                        OrderSide::Sell => OrderSide::Buy,
+                    },
+                    ..resting
+                };
+                let _ = exchange.submit(taker);
+            }
+        }
+    }
+}
@@ -0,0 +1,447 @@
+/*
+ * QuantumArb 2.0 - Core Services: Matching Engine
+ *
+ * File: src/core_services/mock_exchange/matching.rs
+ *
+ * Description:
+ * A central limit order book per instrument with price-time priority: an
+ * incoming order trades against the best opposite price first and, within a
+ * price, against the order that rested there first. Every trade is at the
+ * resting order's price.
+ *
+ * Each order gets the reports a venue would send for it:
+ *
+ *   NEW (ack)         the order was accepted; carries the exchange order id
+ *   PARTIALLY_FILLED  a fill leaving some of the order working
+ *   FILLED            the fill that completed it
+ *   CANCELED          canceled on request, or the unfilled rest of an IOC or
+ *                     market order
+ *
+ * Fills carry the liquidity indicator (MAKER for the resting order, TAKER
+ * for the incoming one); the engine charges no fees. Orders are checked
+ * against the instrument's reference data (tick and lot size) and rejected,
+ * with no ack, if they do not fit.
+ *
+ * A market order is an order with price 0: it takes whatever the opposite
+ * side holds and cancels the rest.
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::ReferenceData;
+use quantumarb_wire::{BboUpdate, ExecutionReport, HopStamps, Liquidity, OrderSide, OrderStatus, TradingMode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimeInForce {
+    /// Rests in the book until filled or canceled.
+    #[default]
+    Gtc,
+    /// Trades what it can on arrival; the rest is canceled.
+    Ioc,
+}
+
+/// Body of POST /orders.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct NewOrder {
+    /// The client's id for the order; must be unique.
+    pub order_id: Uuid,
+    pub instrument_id: u32,
+    pub side: OrderSide,
+    /// Limit price at the instrument's scale; 0 for a market order.
+    #[serde(default)]
+    pub price: u64,
+    pub size: u32,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+}
+
+/// An accepted order as the exchange sees it (GET /orders/{id}).
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct OrderState {
+    pub order_id: Uuid,
+    pub exchange_order_id: String,
+    pub instrument_id: u32,
+    pub side: OrderSide,
+    pub price: u64,
+    pub size: u32,
+    pub filled_size: u32,
+    pub status: OrderStatus,
+}
+
+impl OrderState {
+    fn leaves(&self) -> u32 {
+        match self.status {
+            OrderStatus::New | OrderStatus::PartiallyFilled => self.size - self.filled_size,
+            _ => 0,
+        }
+    }
+}
+
+/// A trade print, published as market data.
+#[derive(Debug, Clone, Serialize)]
+pub struct Trade {
+    pub instrument_id: u32,
+    pub price: u64,
+    pub size: u32,
+    /// The side of the incoming order.
+    pub aggressor: OrderSide,
+    pub timestamp_ns: u64,
+}
+
+/// One price level of GET /book/{instrument_id}.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Level {
+    pub price: u64,
+    pub size: u32,
+    pub orders: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BookDepth {
+    pub instrument_id: u32,
+    /// Best first.
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+/// What an order or cancel did to the book.
+#[derive(Debug, Clone, Default)]
+pub struct Outcome {
+    /// Every report it caused, the incoming order's and the resting orders',
+    /// in the order they happened.
+    pub reports: Vec<ExecutionReport>,
+    pub trades: Vec<Trade>,
+    /// Whether the top of the book moved.
+    pub top_changed: bool,
+}
+
+// --- Order Book ---
+
+/// An order resting at a price, with what is left of it.
+#[derive(Debug, Clone)]
+struct Resting {
+    order_id: Uuid,
+    leaves: u32,
+}
+
+/// (price, size) at the best price of one side, if it has orders.
+type Top = Option<(u64, u32)>;
+
+#[derive(Debug, Default)]
+struct Book {
+    bids: BTreeMap<u64, VecDeque<Resting>>,
+    asks: BTreeMap<u64, VecDeque<Resting>>,
+}
+
+impl Book {
+    /// (price, size) at the best bid and the best ask.
+    fn top(&self) -> (Top, Top) {
+        let level = |(price, orders): (&u64, &VecDeque<Resting>)| (*price, orders.iter().map(|o| o.leaves).sum());
+        (self.bids.iter().next_back().map(level), self.asks.iter().next().map(level))
+    }
+
+    fn side(&mut self, side: OrderSide) -> &mut BTreeMap<u64, VecDeque<Resting>> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    /// The best price an order on `side` can trade against, if it crosses
+    /// `limit` (0 = any price).
+    fn best_opposite(&self, side: OrderSide, limit: u64) -> Option<u64> {
+        match side {
+            OrderSide::Buy => self.asks.keys().next().copied().filter(|ask| limit == 0 || *ask <= limit),
+            OrderSide::Sell => self.bids.keys().next_back().copied().filter(|bid| limit == 0 || *bid >= limit),
+        }
+    }
+}
+
+fn levels<'a>(side: impl Iterator<Item = (&'a u64, &'a VecDeque<Resting>)>, depth: usize) -> Vec<Level> {
+    side.take(depth)
+        .map(|(price, orders)| Level {
+            price: *price,
+            size: orders.iter().map(|o| o.leaves).sum(),
+            orders: orders.len(),
+        })
+        .collect()
+}
+
+// --- Matching Engine ---
+
+pub struct MatchingEngine {
+    instruments: ReferenceData,
+    books: HashMap<u32, Book>,
+    orders: HashMap<Uuid, OrderState>,
+    next_exchange_order_id: u64,
+    next_exec_id: u64,
+}
+
+impl MatchingEngine {
+    pub fn new(instruments: ReferenceData) -> Self {
+        MatchingEngine {
+            instruments,
+            books: HashMap::new(),
+            orders: HashMap::new(),
+            next_exchange_order_id: 0,
+            next_exec_id: 0,
+        }
+    }
+
+    pub fn order(&self, order_id: &Uuid) -> Option<&OrderState> {
+        self.orders.get(order_id)
+    }
+
+    /// Checks the order, acknowledges it and matches it against the book.
+    /// A rejected order leaves no trace.
+    pub fn submit(&mut self, order: NewOrder, now_ns: u64) -> Result<Outcome, Rejection> {
+        self.check(&order)?;
+        self.next_exchange_order_id += 1;
+        let state = OrderState {
+            order_id: order.order_id,
+            exchange_order_id: format!("MOCK-{}", self.next_exchange_order_id),
+            instrument_id: order.instrument_id,
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            filled_size: 0,
+            status: OrderStatus::New,
+        };
+        self.orders.insert(order.order_id, state);
+        let mut outcome = Outcome::default();
+        let ack = self.report(&order.order_id, OrderStatus::New, now_ns);
+        outcome.reports.push(ack);
+
+        let top = self.books.entry(order.instrument_id).or_default().top();
+        let mut leaves = order.size;
+        while leaves > 0 {
+            let book = self.books.get_mut(&order.instrument_id).expect("book exists");
+            let Some(price) = book.best_opposite(order.side, order.price) else {
+                break;
+            };
+            let level = book.side(opposite(order.side)).get_mut(&price).expect("best level exists");
+            let resting = level.front_mut().expect("levels are never left empty");
+            let size = leaves.min(resting.leaves);
+            resting.leaves -= size;
+            leaves -= size;
+            let maker = resting.order_id;
+            if resting.leaves == 0 {
+                level.pop_front();
+                if level.is_empty() {
+                    book.side(opposite(order.side)).remove(&price);
+                }
+            }
+            outcome.trades.push(Trade {
+                instrument_id: order.instrument_id,
+                price,
+                size,
+                aggressor: order.side,
+                timestamp_ns: now_ns,
+            });
+            // Fill the resting order first: its fill happened as the
+            // incoming order arrived.
+            for (order_id, liquidity) in [(maker, Liquidity::Maker), (order.order_id, Liquidity::Taker)] {
+                let fill = self.fill(&order_id, price, size, liquidity, now_ns);
+                outcome.reports.push(fill);
+            }
+        }
+
+        if leaves > 0 {
+            if order.price == 0 || order.time_in_force == TimeInForce::Ioc {
+                self.orders.get_mut(&order.order_id).expect("order exists").status = OrderStatus::Canceled;
+                outcome.reports.push(self.report(&order.order_id, OrderStatus::Canceled, now_ns));
+            } else {
+                let resting = Resting { order_id: order.order_id, leaves };
+                let book = self.books.get_mut(&order.instrument_id).expect("book exists");
+                book.side(order.side).entry(order.price).or_default().push_back(resting);
+            }
+        }
+        outcome.top_changed = self.books[&order.instrument_id].top() != top;
+        Ok(outcome)
+    }
+
+    /// Cancels what is left of a working order.
+    pub fn cancel(&mut self, order_id: &Uuid, now_ns: u64) -> Result<Outcome, Rejection> {
+        let Some(state) = self.orders.get_mut(order_id) else {
+            return Err(Rejection::new(RejectCode::VenueOther, format!("Unknown order {}", order_id)));
+        };
+        if state.leaves() == 0 {
+            let message = format!("Order {} is already {:?}", order_id, state.status);
+            return Err(Rejection::new(RejectCode::VenueOther, message));
+        }
+        let (instrument_id, side, price) = (state.instrument_id, state.side, state.price);
+        // Reported with what was left, before it goes.
+        let mut report = self.report(order_id, OrderStatus::Canceled, now_ns);
+        report.leaves_size = 0;
+        self.orders.get_mut(order_id).expect("order exists").status = OrderStatus::Canceled;
+
+        let book = self.books.get_mut(&instrument_id).expect("a working order has a book");
+        let top = book.top();
+        let levels = book.side(side);
+        if let Some(level) = levels.get_mut(&price) {
+            level.retain(|resting| resting.order_id != *order_id);
+            if level.is_empty() {
+                levels.remove(&price);
+            }
+        }
+        let top_changed = book.top() != top;
+        Ok(Outcome { reports: vec![report], trades: Vec::new(), top_changed })
+    }
+
+    /// The top of an instrument's book as market data; a side with no orders
+    /// has price and size 0.
+    pub fn bbo(&self, instrument_id: u32, venue_id: u32, now_ns: u64) -> BboUpdate {
+        let (bid, ask) = self.books.get(&instrument_id).map(Book::top).unwrap_or_default();
+        let (best_bid_price, best_bid_size) = bid.unwrap_or_default();
+        let (best_ask_price, best_ask_size) = ask.unwrap_or_default();
+        BboUpdate {
+            instrument_id,
+            best_bid_price,
+            best_bid_size,
+            best_ask_price,
+            best_ask_size,
+            timestamp_ns: now_ns,
+            venue_id,
+        }
+    }
+
+    /// The best `depth` levels of each side; None for an instrument that is
+    /// not listed.
+    pub fn depth(&self, instrument_id: u32, depth: usize) -> Option<BookDepth> {
+        self.instruments.get(instrument_id)?;
+        let empty = Book::default();
+        let book = self.books.get(&instrument_id).unwrap_or(&empty);
+        Some(BookDepth {
+            instrument_id,
+            bids: levels(book.bids.iter().rev(), depth),
+            asks: levels(book.asks.iter(), depth),
+        })
+    }
+
+    fn check(&self, order: &NewOrder) -> Result<(), Rejection> {
+        if self.orders.contains_key(&order.order_id) {
+            let message = format!("Order id {} has already been used", order.order_id);
+            return Err(Rejection::new(RejectCode::VenueDuplicateOrder, message));
+        }
+        let Some(definition) = self.instruments.get(order.instrument_id) else {
+            let message = format!("Instrument {} is not listed", order.instrument_id);
+            return Err(Rejection::new(RejectCode::VenueUnknownInstrument, message));
+        };
+        if order.size == 0 {
+            return Err(Rejection::new(RejectCode::VenueInvalidQuantity, "Size must be positive"));
+        }
+        definition.check_size(order.size).map_err(|e| Rejection::new(RejectCode::VenueInvalidQuantity, e))?;
+        if order.price > 0 {
+            let price = definition.price(order.price);
+            definition.check_price(price).map_err(|e| Rejection::new(RejectCode::VenueInvalidPrice, e))?;
+        }
+        Ok(())
+    }
+
+    /// Books a fill on an order and reports it.
+    fn fill(&mut self, order_id: &Uuid, price: u64, size: u32, liquidity: Liquidity, now_ns: u64) -> ExecutionReport {
+        let state = self.orders.get_mut(order_id).expect("filled orders exist");
+        state.filled_size += size;
+        state.status = match state.filled_size == state.size {
+            true => OrderStatus::Filled,
+            false => OrderStatus::PartiallyFilled,
+        };
+        let status = state.status;
+        let mut report = self.report(order_id, status, now_ns);
+        report.filled_size = size;
+        report.filled_price = price;
+        report.liquidity = Some(liquidity);
+        report
+    }
+
+    /// A report of the order as it stands.
+    fn report(&mut self, order_id: &Uuid, status: OrderStatus, now_ns: u64) -> ExecutionReport {
+        self.next_exec_id += 1;
+        let state = &self.orders[order_id];
+        ExecutionReport {
+            exchange_order_id: state.exchange_order_id.clone(),
+            exec_id: format!("E{}", self.next_exec_id),
+            internal_order_id: *order_id,
+            status,
+            filled_size: 0,
+            filled_price: 0,
+            reject: None,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            cumulative_size: state.filled_size,
+            leaves_size: state.leaves(),
+            liquidity: None,
+            fee: Money::ZERO,
+            transact_time_ns: now_ns,
+            exec_ref_id: String::new(),
+        }
+    }
+}
+
+fn opposite(side: OrderSide) -> OrderSide {
+    match side {
+        OrderSide::Buy => OrderSide::Sell,
+        OrderSide::Sell => OrderSide::Buy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(side: OrderSide, price: u64, size: u32) -> NewOrder {
+        NewOrder { order_id: Uuid::new_v4(), instrument_id: 1, side, price, size, time_in_force: TimeInForce::Gtc }
+    }
+
+    #[test]
+    fn matches_best_price_then_earliest_order_and_reports_partial_fills() {
+        let mut engine = MatchingEngine::new(ReferenceData::seeded());
+        let first = order(OrderSide::Sell, 60_001_00, 2);
+        let second = order(OrderSide::Sell, 60_001_00, 3);
+        let better = order(OrderSide::Sell, 60_000_50, 1);
+        for resting in [&first, &second, &better] {
+            let acked = engine.submit(resting.clone(), 1).unwrap();
+            assert_eq!(acked.reports[0].status, OrderStatus::New);
+        }
+        assert_eq!(engine.bbo(1, 1, 1).best_ask_price, 60_000_50);
+
+        // Takes the better price, then the first order at the next one, then
+        // part of the second.
+        let buy = order(OrderSide::Buy, 60_001_00, 4);
+        let outcome = engine.submit(buy.clone(), 2).unwrap();
+        let prints: Vec<(u64, u32)> = outcome.trades.iter().map(|t| (t.price, t.size)).collect();
+        assert_eq!(prints, [(60_000_50, 1), (60_001_00, 2), (60_001_00, 1)]);
+        let taker = outcome.reports.last().unwrap();
+        assert_eq!((taker.status, taker.cumulative_size, taker.leaves_size), (OrderStatus::Filled, 4, 0));
+        assert_eq!(taker.liquidity, Some(Liquidity::Taker));
+        let rest = engine.order(&second.order_id).unwrap();
+        assert_eq!((rest.status, rest.filled_size), (OrderStatus::PartiallyFilled, 1));
+        let bbo = engine.bbo(1, 1, 2);
+        assert_eq!((bbo.best_ask_price, bbo.best_ask_size, bbo.best_bid_size), (60_001_00, 2, 0));
+
+        // An IOC that cannot fill in full cancels the rest, and a canceled
+        // order leaves the book.
+        let ioc = NewOrder { time_in_force: TimeInForce::Ioc, ..order(OrderSide::Buy, 60_001_00, 5) };
+        let statuses: Vec<OrderStatus> = engine.submit(ioc, 3).unwrap().reports.iter().map(|r| r.status).collect();
+        use OrderStatus::{Canceled, Filled, New, PartiallyFilled};
+        assert_eq!(statuses, [New, Filled, PartiallyFilled, Canceled], "the resting order's fill comes first");
+        let bid = order(OrderSide::Buy, 59_000_00, 1);
+        engine.submit(bid.clone(), 4).unwrap();
+        assert!(engine.cancel(&bid.order_id, 5).unwrap().top_changed);
+        assert!(engine.cancel(&bid.order_id, 6).is_err());
+        assert!(engine.depth(1, 5).unwrap().bids.is_empty());
+
+        // ESZ25 trades in quarter points.
+        let off_tick = NewOrder { instrument_id: 3, ..order(OrderSide::Buy, 4500_10, 1) };
+        let off_tick = engine.submit(off_tick, 7).unwrap_err();
+        assert_eq!(off_tick.code, RejectCode::VenueInvalidPrice);
+        assert_eq!(engine.submit(buy, 8).unwrap_err().code, RejectCode::VenueDuplicateOrder);
+    }
+}
@@ -0,0 +1,153 @@
+/*
+ * QuantumArb 2.0 - Core Services: Account P&L
+ *
+ * File: src/core_services/portfolio_manager/accounts.rs
+ *
+ * Description:
+ * Books every fill tagged with an account_id a third time, into that
+ * account's positions, so the risk gateway can hold each account to its
+ * daily loss limit. An account's daily P&L is its realized plus unrealized
+ * P&L less what it stood at when the session (the trading day closed by
+ * close_daily_pnl) began; fees are left out, as for the firm's daily P&L.
+ * Each account also reports the maintenance margin its positions require,
+ * at the rates of the firm's margin monitor (see margin.rs).
+ *
+ * The accounts are served on GET /portfolio/accounts and published on
+ * 'portfolio.account_pnl' every few seconds. Untagged fills belong to no
+ * account, and corporate actions are applied to the firm book only.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::{AccountPnl, Position};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::margin;
+use crate::pnl::PositionLot;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+struct AccountLedger {
+    positions: HashMap<String, Position>,
+    realized_pnl: Money,
+    /// Realized + unrealized P&L when the session began.
+    session_start_pnl: Money,
+}
+
+/// Every account's positions and session P&L.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct AccountBook {
+    accounts: HashMap<u32, AccountLedger>,
+    session_start_utc: DateTime<Utc>,
+}
+
+impl AccountBook {
+    pub fn new(session_start_utc: DateTime<Utc>) -> AccountBook {
+        AccountBook { accounts: HashMap::new(), session_start_utc }
+    }
+
+    /// Books a fill into its account. `quantity` is signed, positive for buys.
+    pub fn book(&mut self, account_id: Option<u32>, symbol: &str, quantity: i64, price: Price, multiplier: Money) {
+        let Some(account_id) = account_id else {
+            return;
+        };
+        let ledger = self.accounts.entry(account_id).or_default();
+        let position = ledger.positions.entry(symbol.to_string()).or_insert(Position {
+            symbol: symbol.to_string(),
+            current_market_price: price,
+            multiplier,
+            ..Default::default()
+        });
+        let mut lot = position.lot();
+        let effect = lot.fill(Quantity(quantity), price);
+        ledger.realized_pnl += effect.realized_pnl.at_multiplier(position.multiplier);
+        position.set_lot(lot);
+        position.mark();
+    }
+
+    /// Marks every account's position in `symbol`.
+    pub fn mark(&mut self, symbol: &str, price: Price) {
+        self.for_each_position(symbol, |position| position.current_market_price = price);
+    }
+
+    pub fn set_multiplier(&mut self, symbol: &str, multiplier: Money) {
+        self.for_each_position(symbol, |position| position.multiplier = multiplier);
+    }
+
+    /// Starts a new session: each account's daily P&L starts again from zero.
+    pub fn close_session(&mut self, now: DateTime<Utc>) {
+        for ledger in self.accounts.values_mut() {
+            ledger.session_start_pnl = ledger.total_pnl();
+        }
+        self.session_start_utc = now;
+    }
+
+    /// Every account's session P&L, by account id.
+    pub fn report(&self) -> Vec<AccountPnl> {
+        let mut report: Vec<AccountPnl> = self
+            .accounts
+            .iter()
+            .map(|(account_id, ledger)| {
+                let unrealized_pnl = ledger.unrealized_pnl();
+                AccountPnl {
+                    account_id: *account_id,
+                    session_start_utc: self.session_start_utc,
+                    realized_pnl: ledger.realized_pnl,
+                    unrealized_pnl,
+                    daily_pnl: ledger.realized_pnl + unrealized_pnl - ledger.session_start_pnl,
+                    positions: ledger.positions.values().map(|p| (p.symbol.clone(), p.quantity)).collect(),
+                    margin_requirement: Money::from_f64(margin::requirement(&ledger.positions)),
+                    timestamp_utc: Utc::now(),
+                }
+            })
+            .collect();
+        report.sort_by_key(|account| account.account_id);
+        report
+    }
+
+    fn for_each_position(&mut self, symbol: &str, mut update: impl FnMut(&mut Position)) {
+        for ledger in self.accounts.values_mut() {
+            if let Some(position) = ledger.positions.get_mut(symbol) {
+                update(position);
+                position.mark();
+            }
+        }
+    }
+}
+
+impl AccountLedger {
+    fn unrealized_pnl(&self) -> Money {
+        self.positions.values().map(|p| p.unrealized_pnl).sum()
+    }
+
+    fn total_pnl(&self) -> Money {
+        self.realized_pnl + self.unrealized_pnl()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daily_pnl_starts_again_from_zero_each_session() {
+        let start = Utc::now();
+        let mut book = AccountBook::new(start);
+        let one = Money::from_f64(1.0);
+        book.book(Some(101), "BTC", 2, Price::from_f64(60_000.0), one);
+        book.book(None, "BTC", 5, Price::from_f64(60_000.0), one);
+        book.mark("BTC", Price::from_f64(59_000.0));
+        let account = &book.report()[0];
+        assert_eq!((account.account_id, account.daily_pnl), (101, Money::from_f64(-2_000.0)));
+        assert_eq!(account.positions["BTC"], 2);
+
+        book.close_session(start + chrono::Duration::days(1));
+        book.book(Some(101), "BTC", -1, Price::from_f64(58_500.0), one);
+        book.mark("BTC", Price::from_f64(58_500.0));
+        // Down 500 on each of the two units since the close at 59,000.
+        assert_eq!(book.report()[0].daily_pnl, Money::from_f64(-1_000.0));
+    }
+}
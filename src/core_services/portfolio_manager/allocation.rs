@@ -0,0 +1,385 @@
+/*
+ * QuantumArb 2.0 - Core Services: Fill Allocation
+ *
+ * File: src/core_services/portfolio_manager/allocation.rs
+ *
+ * Description:
+ * Splits the fills of a parent (block) account across its sub-accounts
+ * after the trade. The parent's fills are booked to the parent's own account
+ * and gathered into a block per order, until the block is allocated: when
+ * the order's last fill arrives, or at the close of the trading day for
+ * blocks still open (partly filled and canceled orders, and fills that name
+ * no order, pooled per symbol and side).
+ *
+ * Every sub-account receives its share at the block's average price, so all
+ * of them hold the block at the same price whatever fills made it up. Shares
+ * are whole units, split by
+ *
+ *   ratios        the weights of the parent's allocation scheme
+ *   instruction   quantities given for the order ahead of its fills (POST
+ *                 /portfolio/allocations/instructions), used as weights if
+ *                 the order filled more or less than instructed
+ *
+ * Rounding each share down leaves a residual of at most one unit per
+ * sub-account. It goes to the scheme's residual account if it names one,
+ * otherwise one unit each to the sub-accounts with the largest remainders
+ * (ties to the larger weight, then the lower account id).
+ *
+ * Allocation moves the block out of the parent account and into the
+ * sub-accounts' positions (see accounts.rs), whose P&L and margin
+ * requirement follow it; the firm book and the strategy sub-portfolios do
+ * not change. The block's fees are split pro rata by quantity in the
+ * report, and the rounding of the average price stays with the parent. A
+ * bust or correction of a fill in a block not yet allocated adjusts the
+ * block; one after allocation gathers into a block of its own.
+ *
+ * Each allocation is reported on GET /portfolio/allocations and published
+ * on 'portfolio.allocations'; blocks not yet allocated are listed on GET
+ * /portfolio/allocations/open.
+ *
+ * Configuration (environment):
+ *   QA_ALLOCATION_SCHEMES=101=201:50*|202:30|203:20;105=301:1|302:1
+ *                         parent=sub:weight|...; '*' marks the residual
+ *                         account; no parent accounts when unset
+ */
+
+use crate::{Fill, Portfolio};
+use chrono::{DateTime, Utc};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+pub const ALLOCATIONS_TOPIC: &str = "portfolio.allocations";
+/// Allocation reports kept for GET /portfolio/allocations.
+const REPORT_RETENTION: usize = 1_000;
+
+// --- Configuration ---
+
+/// How a parent account's fills are split.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct AllocationScheme {
+    pub parent_account: u32,
+    /// Weight per sub-account.
+    pub weights: BTreeMap<u32, u64>,
+    /// Takes the units left over by rounding.
+    pub residual_account: Option<u32>,
+}
+
+/// Parses "101=201:50*|202:30|203:20;105=301:1|302:1".
+pub fn parse_schemes(spec: &str) -> Result<BTreeMap<u32, AllocationScheme>, String> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (parent, subs) = entry.split_once('=').ok_or_else(|| format!("'{}' has no sub-accounts", entry))?;
+            let parent_account = parent.trim().parse().map_err(|_| format!("bad parent account '{}'", parent))?;
+            let mut scheme = AllocationScheme { parent_account, weights: BTreeMap::new(), residual_account: None };
+            for sub in subs.split('|') {
+                let (account, weight) = sub.split_once(':').ok_or_else(|| format!("'{}' has no weight", sub))?;
+                let account: u32 = account.trim().parse().map_err(|_| format!("bad sub-account '{}'", account))?;
+                let (weight, residual) = match weight.trim().strip_suffix('*') {
+                    Some(weight) => (weight, true),
+                    None => (weight.trim(), false),
+                };
+                let weight = weight.parse().ok().filter(|w| *w > 0).ok_or_else(|| format!("bad weight in '{}'", sub))?;
+                if account == parent_account || scheme.weights.insert(account, weight).is_some() {
+                    return Err(format!("account {} is listed twice for parent {}", account, parent_account));
+                }
+                if residual && scheme.residual_account.replace(account).is_some() {
+                    return Err(format!("parent {} has two residual accounts", parent_account));
+                }
+            }
+            Ok((parent_account, scheme))
+        })
+        .collect()
+}
+
+pub fn schemes_from_env() -> BTreeMap<u32, AllocationScheme> {
+    let spec = std::env::var("QA_ALLOCATION_SCHEMES").unwrap_or_default();
+    parse_schemes(&spec).unwrap_or_else(|e| {
+        println!("Ignoring QA_ALLOCATION_SCHEMES: {}", e);
+        BTreeMap::new()
+    })
+}
+
+// --- Data Structures ---
+
+/// Body of a POST /portfolio/allocations/instructions request.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct AllocationInstruction {
+    pub order_id: Uuid,
+    /// Units per sub-account.
+    pub quantities: BTreeMap<u32, u64>,
+}
+
+/// A parent account's fills awaiting allocation.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct Block {
+    pub block_id: String,
+    pub order_id: Option<Uuid>,
+    pub parent_account: u32,
+    pub symbol: String,
+    /// Signed, positive for buys.
+    pub quantity: i64,
+    /// Sum of price x quantity of the fills.
+    pub cost: Money,
+    pub fees: Money,
+    pub fills: u32,
+    pub opened_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum AllocationMethod {
+    Ratios,
+    Instruction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct AllocationLine {
+    pub account_id: u32,
+    /// Signed, positive for buys.
+    pub quantity: i64,
+    pub fees: Money,
+}
+
+/// What an allocation gave each sub-account, all at `average_price`.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct AllocationReport {
+    pub block_id: String,
+    pub order_id: Option<Uuid>,
+    pub parent_account: u32,
+    pub symbol: String,
+    pub quantity: i64,
+    pub average_price: Price,
+    pub fees: Money,
+    pub method: AllocationMethod,
+    pub lines: Vec<AllocationLine>,
+    /// Units handed out after rounding down, included in `lines`.
+    pub residual: u64,
+    pub allocated_utc: DateTime<Utc>,
+}
+
+/// Open blocks, pending instructions and recent allocations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct AllocationBook {
+    blocks: BTreeMap<String, Block>,
+    instructions: HashMap<Uuid, BTreeMap<u32, u64>>,
+    reports: VecDeque<AllocationReport>,
+}
+
+impl AllocationBook {
+    /// Gathers a parent account's fill into its block. The block's id, or
+    /// None if the fill is not for a parent account.
+    pub fn hold(
+        &mut self,
+        schemes: &BTreeMap<u32, AllocationScheme>,
+        fill: &Fill,
+        fee_total: Money,
+        now: DateTime<Utc>,
+    ) -> Option<String> {
+        let parent_account = fill.account_id.filter(|account| schemes.contains_key(account))?;
+        let block_id = match fill.order_id {
+            Some(order_id) => order_id.to_string(),
+            None => {
+                let side = if fill.quantity >= 0 { "Buy" } else { "Sell" };
+                format!("{}:{}:{}", parent_account, fill.symbol, side)
+            }
+        };
+        let block = self.blocks.entry(block_id.clone()).or_insert_with(|| Block {
+            block_id: block_id.clone(),
+            order_id: fill.order_id,
+            parent_account,
+            symbol: fill.symbol.clone(),
+            quantity: 0,
+            cost: Money::ZERO,
+            fees: Money::ZERO,
+            fills: 0,
+            opened_utc: now,
+        });
+        block.quantity += fill.quantity;
+        block.cost += fill.price * Quantity(fill.quantity);
+        block.fees += fee_total;
+        block.fills += 1;
+        Some(block_id)
+    }
+
+    /// Sets the quantities to allocate an order's fills by. Errs if the
+    /// order was already allocated or the instruction allocates nothing.
+    pub fn instruct(&mut self, instruction: AllocationInstruction) -> Result<(), String> {
+        if instruction.quantities.values().all(|quantity| *quantity == 0) {
+            return Err("The instruction allocates nothing.".to_string());
+        }
+        if self.reports.iter().any(|report| report.order_id == Some(instruction.order_id)) {
+            return Err(format!("Order {} is already allocated.", instruction.order_id));
+        }
+        self.instructions.insert(instruction.order_id, instruction.quantities);
+        Ok(())
+    }
+
+    pub fn is_open(&self, block_id: &str) -> bool {
+        self.blocks.contains_key(block_id)
+    }
+
+    pub fn open_blocks(&self) -> Vec<Block> {
+        self.blocks.values().cloned().collect()
+    }
+
+    /// Allocations, newest first.
+    pub fn reports(&self) -> Vec<AllocationReport> {
+        self.reports.iter().rev().cloned().collect()
+    }
+}
+
+// --- Allocation ---
+
+/// Allocates a block: takes it out of the parent account and books each
+/// sub-account's share at the block's average price. Errs, leaving the
+/// block open, if the parent has no scheme and the order no instruction.
+pub fn allocate(p: &mut Portfolio, block_id: &str, now: DateTime<Utc>) -> Result<AllocationReport, String> {
+    let block = p.allocations.blocks.get(block_id).ok_or_else(|| format!("No open block {}.", block_id))?;
+    let instruction = block.order_id.and_then(|order_id| p.allocations.instructions.get(&order_id));
+    let scheme = p.allocation_schemes.get(&block.parent_account);
+    let (method, weights, residual_account) = match (instruction, scheme) {
+        (Some(quantities), _) => (AllocationMethod::Instruction, quantities, None),
+        (None, Some(scheme)) => (AllocationMethod::Ratios, &scheme.weights, scheme.residual_account),
+        (None, None) => return Err(format!("Parent account {} has no allocation scheme.", block.parent_account)),
+    };
+    let total = block.quantity.unsigned_abs();
+    let (shares, residual) = split(total, weights, residual_account);
+    let average_price = block.cost.per_unit(Quantity(block.quantity));
+
+    let mut lines: Vec<AllocationLine> = shares
+        .into_iter()
+        .filter(|(_, share)| *share > 0)
+        .map(|(account_id, share)| AllocationLine {
+            account_id,
+            quantity: share as i64 * block.quantity.signum(),
+            fees: block.fees.pro_rata(Quantity(share as i64), Quantity(total as i64)),
+        })
+        .collect();
+    let unassigned = block.fees - lines.iter().map(|line| line.fees).sum::<Money>();
+    if let Some(largest) = lines.iter_mut().max_by_key(|line| line.quantity.abs()) {
+        largest.fees += unassigned;
+    }
+
+    let block = p.allocations.blocks.remove(block_id).expect("block looked up above");
+    if let Some(order_id) = block.order_id {
+        p.allocations.instructions.remove(&order_id);
+    }
+    let multiplier = p.positions.get(&block.symbol).map_or(Money::from_f64(1.0), |position| position.multiplier);
+    p.accounts.book(Some(block.parent_account), &block.symbol, -block.quantity, average_price, multiplier);
+    for line in &lines {
+        p.accounts.book(Some(line.account_id), &block.symbol, line.quantity, average_price, multiplier);
+    }
+    let report = AllocationReport {
+        block_id: block.block_id,
+        order_id: block.order_id,
+        parent_account: block.parent_account,
+        symbol: block.symbol,
+        quantity: block.quantity,
+        average_price,
+        fees: block.fees,
+        method,
+        lines,
+        residual,
+        allocated_utc: now,
+    };
+    if p.allocations.reports.len() == REPORT_RETENTION {
+        p.allocations.reports.pop_front();
+    }
+    p.allocations.reports.push_back(report.clone());
+    Ok(report)
+}
+
+/// Splits `total` units by `weights` into whole shares. Returns the shares
+/// and the residual units handed out after rounding down.
+fn split(total: u64, weights: &BTreeMap<u32, u64>, residual_account: Option<u32>) -> (BTreeMap<u32, u64>, u64) {
+    let sum: u128 = weights.values().map(|weight| *weight as u128).sum();
+    let mut shares = BTreeMap::new();
+    let mut remainders = Vec::with_capacity(weights.len());
+    for (account, weight) in weights {
+        let exact = total as u128 * *weight as u128;
+        shares.insert(*account, (exact / sum) as u64);
+        remainders.push((exact % sum, *weight, *account));
+    }
+    let residual = total - shares.values().sum::<u64>();
+    match residual_account {
+        Some(account) => *shares.entry(account).or_default() += residual,
+        None => {
+            // Largest remainder first, then the larger weight, then the lower account id.
+            remainders.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+            for (_, _, account) in remainders.into_iter().take(residual as usize) {
+                *shares.entry(account).or_default() += 1;
+            }
+        }
+    }
+    (shares, residual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book_fill;
+    use quantumarb_fees::Liquidity;
+
+    fn fill(account_id: u32, order_id: u128, quantity: i64, price: f64) -> Fill {
+        Fill {
+            symbol: "BTC".to_string(),
+            quantity,
+            price: Price::from_f64(price),
+            venue: "VENUE_A".to_string(),
+            counterparty: "VENUE_A".to_string(),
+            liquidity: Liquidity::Taker,
+            fee: Some(Money::from_f64(1.0)),
+            transact_time_utc: None,
+            strategy_id: None,
+            exec_id: None,
+            account_id: Some(account_id),
+            amends: None,
+            order_id: Some(Uuid::from_u128(order_id)),
+            last_fill: false,
+        }
+    }
+
+    fn positions(p: &Portfolio) -> Vec<(u32, i64)> {
+        p.accounts.report().iter().map(|account| (account.account_id, account.positions["BTC"])).collect()
+    }
+
+    #[test]
+    fn blocks_split_by_ratio_or_instruction_at_the_average_price() {
+        assert!(parse_schemes("101=201:1*|202:1*").is_err());
+        assert!(parse_schemes("101=101:1").is_err());
+        let mut p = Portfolio::new(Utc::now());
+        p.allocation_schemes = parse_schemes("101=201:50|202:30|203:20; 105=301:1*|302:1").unwrap();
+        let now = Utc::now();
+        for f in [fill(101, 1, 3, 60_000.0), fill(101, 1, 4, 60_070.0)] {
+            book_fill(&mut p, f, Money::from_f64(1.0), now, now);
+        }
+
+        // 7 units at 50/30/20: 3.5, 2.1 and 1.4 round down to 3, 2 and 1; 201
+        // has the largest remainder and takes the residual unit.
+        let report = allocate(&mut p, &Uuid::from_u128(1).to_string(), now).unwrap();
+        assert_eq!(report.average_price, Price::from_f64(60_040.0));
+        assert_eq!(report.residual, 1);
+        let split: Vec<_> = report.lines.iter().map(|line| (line.account_id, line.quantity)).collect();
+        assert_eq!(split, vec![(201, 4), (202, 2), (203, 1)]);
+        assert_eq!(report.lines.iter().map(|line| line.fees).sum::<Money>(), Money::from_f64(2.0));
+        assert_eq!(positions(&p), vec![(101, 0), (201, 4), (202, 2), (203, 1)]);
+        assert_eq!(p.positions["BTC"].quantity, 7, "the firm book is unchanged");
+
+        // An instruction for 6/4 on an order that filled 5 (a sale), with the
+        // residual account of the scheme ignored.
+        let instruction =
+            AllocationInstruction { order_id: Uuid::from_u128(2), quantities: BTreeMap::from([(301, 6), (302, 4)]) };
+        p.allocations.instruct(instruction).unwrap();
+        book_fill(&mut p, fill(105, 2, -5, 60_100.0), Money::from_f64(1.0), now, now);
+        let report = allocate(&mut p, &Uuid::from_u128(2).to_string(), now).unwrap();
+        assert_eq!(report.method, AllocationMethod::Instruction);
+        assert_eq!(report.lines.iter().map(|line| line.quantity).collect::<Vec<_>>(), vec![-3, -2]);
+        assert!(allocate(&mut p, &Uuid::from_u128(2).to_string(), now).is_err());
+        let again = AllocationInstruction { order_id: Uuid::from_u128(2), quantities: BTreeMap::from([(301, 1)]) };
+        assert!(p.allocations.instruct(again).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * QuantumArb 2.0 - Core Services: Cash Ledger
+ *
+ * File: src/core_services/portfolio_manager/cash.rs
+ *
+ * Description:
+ * Tracks cash per currency alongside positions. Every fill posts a cash
+ * movement of -(notional) - fees in the instrument's currency, the notional
+ * being price x quantity x contract multiplier: buys debit, sells credit. Movements settle T+N by asset class; until then they
+ * are pending and do not count towards the available balance.
+ *
+ *   available = settled cash that can be used now
+ *   pending   = net of movements awaiting settlement
+ *   projected = available + pending
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_money::{Money, Price};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of journal entries kept for GET /cash.
+const JOURNAL_RETENTION: usize = 1000;
+
+// --- Reference Data ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AssetClass {
+    Crypto,
+    Future,
+    Equity,
+}
+
+impl AssetClass {
+    /// Settlement lag in business days (T+N).
+    pub fn settlement_days(self) -> i64 {
+        match self {
+            AssetClass::Crypto => 0,
+            AssetClass::Future => 1,
+            AssetClass::Equity => 2,
+        }
+    }
+}
+
+/// Static instrument reference data until a reference data service exists.
+pub fn instrument_terms(symbol: &str) -> (AssetClass, &'static str) {
+    match symbol {
+        "BTC" | "ETH" => (AssetClass::Crypto, "USD"),
+        "ESZ25" => (AssetClass::Future, "USD"),
+        _ => (AssetClass::Equity, "USD"),
+    }
+}
+
+/// Adds `days` business days (weekends skipped) to `from`.
+pub fn add_business_days(from: DateTime<Utc>, days: i64) -> DateTime<Utc> {
+    use chrono::{Datelike, Weekday};
+    let mut date = from;
+    let mut remaining = days;
+    while remaining > 0 {
+        date += chrono::Duration::days(1);
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            remaining -= 1;
+        }
+    }
+    date
+}
+
+// --- Ledger ---
+
+/// One posted cash movement.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct LedgerEntry {
+    pub currency: String,
+    pub amount: Money,
+    pub description: String,
+    pub trade_utc: DateTime<Utc>,
+    pub settles_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CurrencyBalance {
+    pub currency: String,
+    pub available: Money,
+    pub pending: Money,
+    pub projected: Money,
+}
+
+/// Body of a GET /cash response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CashReport {
+    pub balances: Vec<CurrencyBalance>,
+    pub pending_movements: Vec<LedgerEntry>,
+    pub recent_entries: Vec<LedgerEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct CashLedger {
+    available: BTreeMap<String, Money>,
+    pending: Vec<LedgerEntry>,
+    journal: VecDeque<LedgerEntry>,
+}
+
+impl CashLedger {
+    /// A ledger holding an opening settled balance.
+    pub fn with_opening_balance(currency: &str, amount: Money) -> CashLedger {
+        let mut ledger = CashLedger::default();
+        ledger.available.insert(currency.to_string(), amount);
+        ledger
+    }
+
+    /// Posts the cash leg of a fill (quantity and notional are signed:
+    /// positive for buys).
+    pub fn post_fill(
+        &mut self,
+        symbol: &str,
+        quantity: i64,
+        price: Price,
+        notional: Money,
+        fees: Money,
+        trade_utc: DateTime<Utc>,
+    ) {
+        let (asset_class, currency) = instrument_terms(symbol);
+        let entry = LedgerEntry {
+            currency: currency.to_string(),
+            amount: -notional - fees,
+            description: format!("{} {} {} @ {:.2} (fees {:.2})", if quantity > 0 { "Buy" } else { "Sell" }, quantity.abs(), symbol, price, fees),
+            trade_utc,
+            settles_utc: add_business_days(trade_utc, asset_class.settlement_days()),
+        };
+        self.post(entry);
+    }
+
+    /// Posts a non-trade movement (dividend, cash in lieu) settling at `settles_utc`.
+    pub fn post_movement(&mut self, currency: &str, amount: Money, description: String, trade_utc: DateTime<Utc>, settles_utc: DateTime<Utc>) {
+        self.post(LedgerEntry { currency: currency.to_string(), amount, description, trade_utc, settles_utc });
+    }
+
+    /// Posts a movement; it is pending until `settles_utc`.
+    fn post(&mut self, entry: LedgerEntry) {
+        if entry.settles_utc <= entry.trade_utc {
+            *self.available.entry(entry.currency.clone()).or_default() += entry.amount;
+        } else {
+            self.pending.push(entry.clone());
+        }
+        if self.journal.len() == JOURNAL_RETENTION {
+            self.journal.pop_front();
+        }
+        self.journal.push_back(entry);
+    }
+
+    /// Moves every pending movement due by `now` into the available balance.
+    pub fn settle_due(&mut self, now: DateTime<Utc>) {
+        let (due, still_pending): (Vec<LedgerEntry>, Vec<LedgerEntry>) =
+            self.pending.drain(..).partition(|e| e.settles_utc <= now);
+        self.pending = still_pending;
+        for entry in due {
+            *self.available.entry(entry.currency.clone()).or_default() += entry.amount;
+            println!("  -> Settled {:.2} {}: {}", entry.amount, entry.currency, entry.description);
+        }
+    }
+
+    pub fn report(&self) -> CashReport {
+        let mut balances: BTreeMap<String, CurrencyBalance> = self
+            .available
+            .iter()
+            .map(|(currency, available)| {
+                let balance = CurrencyBalance {
+                    currency: currency.clone(),
+                    available: *available,
+                    pending: Money::ZERO,
+                    projected: *available,
+                };
+                (currency.clone(), balance)
+            })
+            .collect();
+        for entry in &self.pending {
+            let balance = balances.entry(entry.currency.clone()).or_insert_with(|| CurrencyBalance {
+                currency: entry.currency.clone(),
+                available: Money::ZERO,
+                pending: Money::ZERO,
+                projected: Money::ZERO,
+            });
+            balance.pending += entry.amount;
+            balance.projected += entry.amount;
+        }
+        CashReport {
+            balances: balances.into_values().collect(),
+            pending_movements: self.pending.clone(),
+            recent_entries: self.journal.iter().rev().take(50).cloned().collect(),
+        }
+    }
+}
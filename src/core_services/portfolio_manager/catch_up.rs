@@ -0,0 +1,489 @@
+/*
+ * QuantumArb 2.0 - Core Services: Fill Catch-Up
+ *
+ * File: src/core_services/portfolio_manager/catch_up.rs
+ *
+ * Description:
+ * Books the fills published while the portfolio manager was down before it
+ * books live ones (see `quantumarb_bus::replay`). Every fill booked, or
+ * skipped as already booked, is acknowledged to the checkpoint with the time
+ * it reached the bus, after the journal holds it.
+ *
+ * On start, with a checkpoint and the archive as the source, the live fill
+ * subscription is held back while the history since the checkpoint is read:
+ * the order requests and execution reports the archiver wrote, a report
+ * becoming a fill of the order it names (its instrument, side, account,
+ * strategy and venue). History is read up to the start, after waiting
+ * QA_ARCHIVE_FLUSH_SECS for the archiver to flush what it saw just before;
+ * then the held live fills follow. A fill in both is booked once, and the
+ * book's ExecID keys (fills.rs) catch what the catch-up cannot. At most
+ * MAX_HELD_FILLS are held; past that the live subscription is left unread,
+ * so its blocking queue pushes back on the bus until history is read. If the
+ * archive cannot be read, or none is configured (QA_ARCHIVE_LOCAL_DIR or
+ * QA_ARCHIVE_BUCKET), the error is kept and the manager goes live.
+ *
+ * With Kafka as the source the fill subscription's consumer group resumes
+ * from its committed offsets, so fills go live at once. Progress is served
+ * on GET /portfolio/catch-up.
+ *
+ * Configuration (environment): QA_REPLAY_SOURCE, QA_REPLAY_DIR and
+ * QA_REPLAY_GRACE_SECS (see `quantumarb_bus::replay`), the archive's
+ * QA_ARCHIVE_* variables, and QA_ARCHIVE_FLUSH_SECS=60.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_archive::{ArchiveQuery, ArchiveReader};
+use quantumarb_bus::replay::{CatchUp, CheckpointStore, Phase, ReplayConfig, ReplaySource};
+use quantumarb_bus::topics;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_queues::{Receiver, Sender};
+use quantumarb_risk::concentration::venue_name;
+use quantumarb_wire::{Encoding, ExecutionReport, OrderRequest};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+use crate::{Fill, SharedPortfolio};
+
+/// The consumer name of the portfolio manager's checkpoint.
+const CONSUMER: &str = "portfolio_manager";
+/// Archived records read at a time.
+const HISTORY_CHUNK_RECORDS: usize = 10_000;
+/// Live fills held back while history is read.
+const MAX_HELD_FILLS: u64 = if cfg!(test) { 4 } else { 50_000 };
+
+// --- Status ---
+
+/// Progress of the catch-up, served on GET /portfolio/catch-up.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CatchUpStatus {
+    /// archive or kafka.
+    pub source: String,
+    /// catching_up or live.
+    pub phase: String,
+    pub resumed_from_utc: Option<DateTime<Utc>>,
+    pub replayed: u64,
+    /// Live fills held back while history was read.
+    pub held: u64,
+    pub duplicates: u64,
+    /// Archived reports whose order request was not in the history read.
+    pub unmatched_reports: u64,
+    pub error: Option<String>,
+}
+
+pub type SharedCatchUpStatus = Arc<Mutex<CatchUpStatus>>;
+
+fn phase_name(phase: Phase) -> String {
+    match phase {
+        Phase::CatchingUp => "catching_up",
+        Phase::Live => "live",
+    }
+    .to_string()
+}
+
+// --- Checkpoint ---
+
+/// The checkpoint and the bus times of fills queued but not yet booked.
+pub struct FillCheckpoint {
+    store: CheckpointStore,
+    received_ns: HashMap<String, i64>,
+}
+
+pub type SharedCheckpoint = Arc<Mutex<FillCheckpoint>>;
+
+impl FillCheckpoint {
+    pub fn open(config: &ReplayConfig) -> std::io::Result<FillCheckpoint> {
+        Ok(FillCheckpoint { store: CheckpointStore::open(config, CONSUMER)?, received_ns: HashMap::new() })
+    }
+
+    /// Notes when a fill reached the bus, for its acknowledgement.
+    fn received(&mut self, fill: &Fill, time_ns: i64) {
+        if let Some(exec_id) = &fill.exec_id {
+            self.received_ns.insert(exec_id.clone(), time_ns);
+        }
+    }
+
+    /// Acknowledges a fill the journal holds (or the book already did).
+    pub fn booked(&mut self, fill: &Fill, now: DateTime<Utc>) {
+        let now_ns = now.timestamp_nanos_opt().unwrap_or_default();
+        let time_ns = fill.exec_id.as_ref().and_then(|id| self.received_ns.remove(id)).unwrap_or(now_ns);
+        if let Err(e) = self.store.ack(fill.exec_id.as_deref(), time_ns) {
+            println!("  -> Failed to write the fill checkpoint: {}", e);
+        }
+    }
+}
+
+// --- History ---
+
+/// Where the fills published since the checkpoint are read from.
+pub trait FillHistory: Send + 'static {
+    /// Why history cannot be read, or None if it can.
+    fn unavailable(&self) -> Option<String>;
+    /// Sends the fills from `resume_from` to now, with the time each reached
+    /// the bus; dropping `history` ends them.
+    fn read(
+        self,
+        resume_from: DateTime<Utc>,
+        history: mpsc::Sender<(Fill, i64)>,
+        status: SharedCatchUpStatus,
+    ) -> impl Future<Output = ()> + Send;
+}
+
+/// The order requests and execution reports the archiver wrote.
+pub struct ArchiveHistory {
+    pub portfolio: SharedPortfolio,
+}
+
+impl FillHistory for ArchiveHistory {
+    fn unavailable(&self) -> Option<String> {
+        let archived = ["QA_ARCHIVE_LOCAL_DIR", "QA_ARCHIVE_BUCKET"].iter().any(|name| std::env::var(name).is_ok());
+        (!archived).then(|| "no archive configured (QA_ARCHIVE_LOCAL_DIR or QA_ARCHIVE_BUCKET)".to_string())
+    }
+
+    async fn read(self, resume_from: DateTime<Utc>, history: mpsc::Sender<(Fill, i64)>, status: SharedCatchUpStatus) {
+        read_history(resume_from, self.portfolio, history, status).await;
+    }
+}
+
+// --- Catching up ---
+
+/// Passes live fills on to the book, after the fills published since the
+/// checkpoint when there are any to read.
+pub async fn run(
+    config: ReplayConfig,
+    checkpoint: SharedCheckpoint,
+    status: SharedCatchUpStatus,
+    source: impl FillHistory,
+    mut live: Receiver<Fill>,
+    fills: Sender<Fill>,
+) {
+    let (resume_from_ns, mut catch_up) = {
+        let checkpoint = checkpoint.lock().unwrap();
+        (checkpoint.store.resume_from_ns(), CatchUp::new(checkpoint.store.checkpoint()))
+    };
+    let resume_from = resume_from_ns.map(DateTime::from_timestamp_nanos);
+    status.lock().unwrap().resumed_from_utc = resume_from;
+
+    let unavailable = source.unavailable();
+    if let (ReplaySource::Archive, Some(_), Some(reason)) = (config.source, resume_from, &unavailable) {
+        println!("Cannot catch up on fills ({}); they go live at once.", reason);
+        status.lock().unwrap().error = unavailable;
+    } else if let (ReplaySource::Archive, Some(resume_from)) = (config.source, resume_from) {
+        println!("Catching up on fills since {} before going live.", resume_from);
+        let (history_sender, mut history) = mpsc::channel(1024);
+        let history_status = status.clone();
+        tokio::spawn(async move {
+            source.read(resume_from, history_sender, history_status).await;
+        });
+        loop {
+            let holding = catch_up.stats().held < MAX_HELD_FILLS;
+            tokio::select! {
+                replayed = history.recv() => {
+                    let Some((fill, time_ns)) = replayed else {
+                        break;
+                    };
+                    let exec_id = fill.exec_id.clone();
+                    if let Some(replayed) = catch_up.replayed(exec_id.as_deref(), (fill, time_ns)) {
+                        if !forward(&fills, &checkpoint, replayed).await {
+                            return;
+                        }
+                    }
+                }
+                held = live.recv(), if holding => {
+                    let Some(fill) = held else {
+                        return;
+                    };
+                    let exec_id = fill.exec_id.clone();
+                    let _ = catch_up.live(exec_id.as_deref(), (fill, now_ns()));
+                    if catch_up.stats().held == MAX_HELD_FILLS {
+                        println!("  -> Holding {} live fills; the rest wait until history is read.", MAX_HELD_FILLS);
+                    }
+                }
+            }
+            record(&status, &catch_up);
+        }
+    }
+    let held = catch_up.go_live();
+    record(&status, &catch_up);
+    let stats = catch_up.stats();
+    println!(
+        "Fills are live: {} replayed, {} held meanwhile, {} duplicates skipped.",
+        stats.replayed, stats.held, stats.duplicates
+    );
+    for held in held {
+        if !forward(&fills, &checkpoint, held).await {
+            return;
+        }
+    }
+    while let Some(fill) = live.recv().await {
+        let exec_id = fill.exec_id.clone();
+        let Some(live) = catch_up.live(exec_id.as_deref(), (fill, now_ns())) else {
+            record(&status, &catch_up);
+            continue;
+        };
+        if !forward(&fills, &checkpoint, live).await {
+            return;
+        }
+    }
+}
+
+/// Queues a fill for booking, noting when it reached the bus; false once
+/// the book has stopped.
+async fn forward(fills: &Sender<Fill>, checkpoint: &SharedCheckpoint, (fill, time_ns): (Fill, i64)) -> bool {
+    checkpoint.lock().unwrap().received(&fill, time_ns);
+    fills.send(fill).await.is_ok()
+}
+
+fn now_ns() -> i64 {
+    Utc::now().timestamp_nanos_opt().unwrap_or_default()
+}
+
+fn record<T>(status: &SharedCatchUpStatus, catch_up: &CatchUp<T>) {
+    let stats = catch_up.stats();
+    let mut status = status.lock().unwrap();
+    status.phase = phase_name(catch_up.phase());
+    status.replayed = stats.replayed;
+    status.held = stats.held;
+    status.duplicates = stats.duplicates;
+}
+
+/// Sends the archived fills from `resume_from` to now, with the time each
+/// reached the bus; the channel closes when they are all sent.
+async fn read_history(
+    resume_from: DateTime<Utc>,
+    portfolio: SharedPortfolio,
+    history: mpsc::Sender<(Fill, i64)>,
+    status: SharedCatchUpStatus,
+) {
+    let end = Utc::now();
+    let flush_secs = std::env::var("QA_ARCHIVE_FLUSH_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(60);
+    tokio::time::sleep(Duration::from_secs(flush_secs)).await;
+
+    let query = ArchiveQuery {
+        start: resume_from,
+        end,
+        instrument_ids: Vec::new(),
+        topics: vec![topics::ORDER_REQUESTS.to_string(), topics::EXECUTION_REPORTS.to_string()],
+    };
+    let opened = match quantumarb_archive::open_store() {
+        Ok(store) => ArchiveReader::open(store, &quantumarb_archive::prefix_from_env(), query).await,
+        Err(e) => Err(e.into()),
+    };
+    let mut reader = match opened {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("  -> Could not open the archive to catch up; going live: {}", e);
+            status.lock().unwrap().error = Some(e.to_string());
+            return;
+        }
+    };
+    let mut orders: HashMap<Uuid, OrderRequest> = HashMap::new();
+    loop {
+        let chunk = match reader.next_chunk(HISTORY_CHUNK_RECORDS).await {
+            Ok(chunk) if chunk.is_empty() => return,
+            Ok(chunk) => chunk,
+            Err(e) => {
+                println!("  -> Catch-up stopped reading the archive; going live: {}", e);
+                status.lock().unwrap().error = Some(e.to_string());
+                return;
+            }
+        };
+        for record in chunk {
+            let body = quantumarb_bus::envelope::payload_of(&record.payload);
+            if record.topic == topics::ORDER_REQUESTS {
+                if let Ok(order) = Encoding::decode_any::<OrderRequest>(body) {
+                    orders.insert(order.order_id, order);
+                }
+                continue;
+            }
+            let Ok(report) = Encoding::decode_any::<ExecutionReport>(body) else {
+                continue;
+            };
+            let Some(fill) = fill_of(&report, orders.get(&report.internal_order_id), &portfolio) else {
+                if report.filled_size > 0 {
+                    status.lock().unwrap().unmatched_reports += 1;
+                }
+                continue;
+            };
+            if history.send((fill, record.event_time_ns)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The fill an archived report makes, given the order it names.
+fn fill_of(report: &ExecutionReport, order: Option<&OrderRequest>, portfolio: &SharedPortfolio) -> Option<Fill> {
+    let order = order?;
+    let definition = portfolio.lock().unwrap().instruments.get(order.instrument_id)?.clone();
+    let venue = venue_name(order.venue_id).unwrap_or("UNROUTED");
+    Fill::from_report(report, &definition, order.side, venue, &order.strategy_id, order.account_id)
+}
+
+/// The status before catching up starts.
+pub fn initial_status(config: &ReplayConfig) -> CatchUpStatus {
+    CatchUpStatus {
+        source: match config.source {
+            ReplaySource::Archive => "archive",
+            ReplaySource::Kafka => "kafka",
+        }
+        .to_string(),
+        phase: phase_name(Phase::CatchingUp),
+        resumed_from_utc: None,
+        replayed: 0,
+        held: 0,
+        duplicates: 0,
+        unmatched_reports: 0,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_bus::replay::CheckpointStore;
+    use quantumarb_fees::Liquidity;
+    use quantumarb_money::Price;
+
+    const T0: i64 = 1_750_000_000_000_000_000;
+    const SECOND: i64 = 1_000_000_000;
+
+    /// History from memory, sent once the catch-up holds `hold_first` live fills.
+    struct ScriptedHistory {
+        unavailable: Option<&'static str>,
+        fills: Vec<(Fill, i64)>,
+        hold_first: u64,
+    }
+
+    impl FillHistory for ScriptedHistory {
+        fn unavailable(&self) -> Option<String> {
+            self.unavailable.map(str::to_string)
+        }
+
+        async fn read(self, _: DateTime<Utc>, history: mpsc::Sender<(Fill, i64)>, status: SharedCatchUpStatus) {
+            loop {
+                let held = status.lock().unwrap().held;
+                if held >= self.hold_first {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            for fill in self.fills {
+                if history.send(fill).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn fill(exec_id: &str) -> Fill {
+        Fill {
+            symbol: "BTC".to_string(),
+            quantity: 1,
+            price: Price::from_f64(60_000.0),
+            venue: "VENUE_A".to_string(),
+            counterparty: "VENUE_A".to_string(),
+            liquidity: Liquidity::Taker,
+            fee: None,
+            transact_time_utc: None,
+            strategy_id: None,
+            exec_id: Some(exec_id.to_string()),
+            account_id: Some(101),
+            amends: None,
+            order_id: None,
+            last_fill: false,
+        }
+    }
+
+    /// A checkpoint in a fresh directory that acknowledged `x-0` at T0.
+    fn checkpointed(name: &str) -> (ReplayConfig, SharedCheckpoint, SharedCatchUpStatus) {
+        let dir = std::env::temp_dir().join(format!("qa-pm-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ReplayConfig { source: ReplaySource::Archive, dir, grace: std::time::Duration::from_secs(5) };
+        CheckpointStore::open(&config, CONSUMER).unwrap().ack(Some("x-0"), T0).unwrap();
+        let checkpoint = Arc::new(Mutex::new(FillCheckpoint::open(&config).unwrap()));
+        let status = Arc::new(Mutex::new(initial_status(&config)));
+        (config, checkpoint, status)
+    }
+
+    async fn live_queue(exec_ids: &[&str]) -> Receiver<Fill> {
+        let (sender, receiver) = quantumarb_queues::blocking("test_live_fills", 16);
+        for exec_id in exec_ids {
+            sender.send(fill(exec_id)).await.unwrap();
+        }
+        receiver
+    }
+
+    async fn drain(mut booked: Receiver<Fill>) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        while let Some(fill) = booked.recv().await {
+            fills.push(fill);
+        }
+        fills
+    }
+
+    fn exec_ids(fills: &[Fill]) -> Vec<&str> {
+        fills.iter().map(|f| f.exec_id.as_deref().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn history_is_booked_before_held_live_fills_and_overlapping_fills_once() {
+        let (config, checkpoint, status) = checkpointed("catch-up");
+        let dir = config.dir.clone();
+        // x-2 is live while history still has it; x-3 arrives after the held fills are full.
+        let live = live_queue(&["x-2", "x-5", "x-6", "x-7", "x-8", "x-3", "x-9"]).await;
+        let history = ScriptedHistory {
+            unavailable: None,
+            fills: (0..5).map(|k| (fill(&format!("x-{}", k)), T0 + k * SECOND)).collect(),
+            hold_first: MAX_HELD_FILLS,
+        };
+        let (fills, booked) = quantumarb_queues::blocking("test_fills", 16);
+        let started_ns = now_ns();
+        run(config.clone(), checkpoint.clone(), status.clone(), history, live, fills).await;
+
+        let booked = drain(booked).await;
+        assert_eq!(exec_ids(&booked), ["x-1", "x-2", "x-3", "x-4", "x-5", "x-6", "x-7", "x-8", "x-9"]);
+        let status = status.lock().unwrap().clone();
+        assert_eq!(status.phase, "live");
+        assert_eq!(status.resumed_from_utc, Some(DateTime::from_timestamp_nanos(T0 - 5 * SECOND)));
+        assert_eq!((status.replayed, status.held, status.duplicates), (4, MAX_HELD_FILLS, 3));
+
+        // Replayed fills are acknowledged at the time they reached the bus,
+        // live ones at the time they were received, not when booked.
+        let later = Utc::now() + chrono::Duration::days(1);
+        let acked_ns = |checkpoint: &SharedCheckpoint| checkpoint.lock().unwrap().store.checkpoint().acked_ns.unwrap();
+        for fill in &booked[..4] {
+            checkpoint.lock().unwrap().booked(fill, later);
+        }
+        assert_eq!(acked_ns(&checkpoint), T0 + 4 * SECOND);
+        for fill in &booked[4..] {
+            checkpoint.lock().unwrap().booked(fill, later);
+        }
+        assert!((started_ns..=now_ns()).contains(&acked_ns(&checkpoint)));
+
+        let reopened = CheckpointStore::open(&config, CONSUMER).unwrap();
+        let recent: Vec<&str> = reopened.checkpoint().recent.iter().map(String::as_str).collect();
+        assert_eq!(recent, ["x-0", "x-1", "x-2", "x-3", "x-4", "x-5", "x-6", "x-7", "x-8", "x-9"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn fills_go_live_at_once_when_history_cannot_be_read() {
+        let (config, checkpoint, status) = checkpointed("catch-up-unavailable");
+        let live = live_queue(&["x-0", "x-1", "x-1", "x-2"]).await;
+        let history =
+            ScriptedHistory { unavailable: Some("no archive"), fills: vec![(fill("x-9"), T0)], hold_first: 0 };
+        let (fills, booked) = quantumarb_queues::blocking("test_fills", 16);
+        run(config.clone(), checkpoint, status.clone(), history, live, fills).await;
+
+        assert_eq!(exec_ids(&drain(booked).await), ["x-1", "x-2"]);
+        let status = status.lock().unwrap().clone();
+        assert_eq!((status.phase.as_str(), status.error.as_deref()), ("live", Some("no archive")));
+        assert_eq!((status.replayed, status.held, status.duplicates), (0, 0, 2));
+        let _ = std::fs::remove_dir_all(config.dir);
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * QuantumArb 2.0 - Core Services: Corporate Actions
+ *
+ * File: src/core_services/portfolio_manager/corporate_actions.rs
+ *
+ * Description:
+ * Applies corporate actions from 'reference.corporate_actions' to positions
+ * on their ex-date:
+ *
+ * - Split: quantities (including the per-venue and per-counterparty
+ *   breakdowns) scale by new/old shares, prices by old/new, and the cost
+ *   basis is unchanged. A fractional share left over is paid out as cash in
+ *   lieu at the adjusted market price, realizing against its share of the
+ *   cost basis.
+ * - Cash dividend: quantity x amount per share is credited (debited for a
+ *   short) to realized P&L and posted to the cash ledger, settling on the
+ *   pay date.
+ * - Symbol change: the position moves to the new ticker.
+ *
+ * Actions are keyed by `action_id` and applied at most once, also across
+ * restarts: the ids of applied actions are journaled with the book.
+ */
+
+use chrono::{DateTime, NaiveTime, Utc};
+use quantumarb_corporate_actions::{CorporateAction, CorporateActionKind};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::pnl::PositionLot;
+use crate::{cash, Portfolio};
+
+/// An action that has been applied, with what it did to the portfolio.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct AppliedCorporateAction {
+    pub action: CorporateAction,
+    pub applied_utc: DateTime<Utc>,
+    pub effect: String,
+}
+
+/// Actions received but not yet effective, and the audit trail of applied ones.
+#[derive(Debug, Default)]
+pub struct CorporateActionBook {
+    pending: Vec<CorporateAction>,
+    applied: Vec<AppliedCorporateAction>,
+    seen: HashSet<String>,
+}
+
+impl CorporateActionBook {
+    /// Queues an action; duplicates of one already seen are ignored.
+    pub fn receive(&mut self, action: CorporateAction) {
+        if self.seen.insert(action.action_id.clone()) {
+            println!("\nReceived corporate action {} ({} ex {})", action.action_id, action.symbol, action.ex_date);
+            self.pending.push(action);
+        }
+    }
+
+    /// Marks actions applied before a restart, so they are not applied again.
+    pub fn already_applied(&mut self, action_ids: Vec<String>) {
+        self.seen.extend(action_ids);
+    }
+
+    /// Applies every pending action whose ex-date has arrived, oldest ex-date
+    /// first. Returns the ids of the actions applied.
+    pub fn apply_due(&mut self, portfolio: &mut Portfolio, now: DateTime<Utc>) -> Vec<String> {
+        let today = now.date_naive();
+        let (mut due, pending): (Vec<CorporateAction>, Vec<CorporateAction>) =
+            self.pending.drain(..).partition(|a| a.ex_date <= today);
+        self.pending = pending;
+        due.sort_by_key(|a| a.ex_date);
+
+        let mut applied = Vec::with_capacity(due.len());
+        for action in due {
+            let effect = apply(portfolio, &action, now);
+            println!("  -> Applied corporate action {}: {}", action.action_id, effect);
+            applied.push(action.action_id.clone());
+            self.applied.push(AppliedCorporateAction { action, applied_utc: now, effect });
+        }
+        applied
+    }
+
+    pub fn applied(&self) -> &[AppliedCorporateAction] {
+        &self.applied
+    }
+}
+
+/// Applies one action and describes the adjustment made.
+fn apply(p: &mut Portfolio, action: &CorporateAction, now: DateTime<Utc>) -> String {
+    let Some(position) = p.positions.get_mut(&action.symbol) else {
+        return format!("no position in {}", action.symbol);
+    };
+
+    match &action.kind {
+        CorporateActionKind::Split { new_shares, old_shares } => {
+            let exact_quantity = position.quantity as f64 * action.quantity_factor();
+            let new_quantity = exact_quantity.trunc() as i64;
+            let old_quantity = position.quantity;
+
+            position.quantity = new_quantity;
+            position.current_market_price = position.current_market_price.scaled(action.price_factor());
+            scale_quantities(&mut position.venue_quantities, action.quantity_factor(), new_quantity);
+            scale_quantities(&mut position.counterparty_quantities, action.quantity_factor(), new_quantity);
+
+            let mut effect = format!("{}-for-{} split, {} -> {} {}", new_shares, old_shares, old_quantity, new_quantity, action.symbol);
+            let fraction = exact_quantity - new_quantity as f64;
+            if fraction != 0.0 {
+                let cash_in_lieu = Money::from_f64(fraction * position.current_market_price.to_f64());
+                let released = position.cost_basis.scaled(fraction / exact_quantity);
+                let (_, currency) = cash::instrument_terms(&action.symbol);
+                let description = format!("Cash in lieu of {:.4} {} ({})", fraction, action.symbol, action.action_id);
+                p.cash.post_movement(currency, cash_in_lieu, description, now, now);
+                p.realized_pnl += cash_in_lieu - released;
+                position.cost_basis -= released;
+                effect.push_str(&format!(", cash in lieu {:.2} {}", cash_in_lieu, currency));
+            }
+            position.average_entry_price = position.cost_basis.per_unit(Quantity(new_quantity));
+            position.mark();
+            effect
+        }
+        CorporateActionKind::CashDividend { amount_per_share, currency, pay_date } => {
+            let amount = Money::from_f64(*amount_per_share) * Quantity(position.quantity);
+            let settles_utc = pay_date.and_time(NaiveTime::MIN).and_utc();
+            let description = format!("Dividend {} x {} {} ({})", position.quantity, amount_per_share, action.symbol, action.action_id);
+            p.cash.post_movement(currency, amount, description, now, settles_utc);
+            p.realized_pnl += amount;
+            format!("dividend of {:.2} {} payable {}", amount, currency, pay_date)
+        }
+        CorporateActionKind::SymbolChange { new_symbol } => {
+            if p.positions.contains_key(new_symbol) {
+                return format!("not applied: a position in {} already exists", new_symbol);
+            }
+            let mut position = p.positions.remove(&action.symbol).unwrap();
+            position.symbol = new_symbol.clone();
+            p.positions.insert(new_symbol.clone(), position);
+            format!("{} renamed to {}", action.symbol, new_symbol)
+        }
+    }
+}
+
+/// Scales a quantity breakdown by `factor`, putting any rounding residual on
+/// the largest entry so the breakdown still sums to `total`.
+fn scale_quantities(quantities: &mut HashMap<String, i64>, factor: f64, total: i64) {
+    for quantity in quantities.values_mut() {
+        *quantity = (*quantity as f64 * factor).trunc() as i64;
+    }
+    let residual = total - quantities.values().sum::<i64>();
+    if let Some(largest) = quantities.values_mut().max_by_key(|q| q.abs()) {
+        *largest += residual;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OPENING_CASH_USD;
+    use chrono::{NaiveDate, TimeZone};
+    use quantumarb_money::Price;
+    use quantumarb_types::Position;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, day, 14, 30, 0).unwrap()
+    }
+
+    fn action(id: &str, symbol: &str, ex_day: u32, kind: CorporateActionKind) -> CorporateAction {
+        CorporateAction {
+            action_id: id.to_string(),
+            instrument_id: 7,
+            symbol: symbol.to_string(),
+            ex_date: date(ex_day),
+            kind,
+        }
+    }
+
+    fn split(id: &str, ex_day: u32, new_shares: u32, old_shares: u32) -> CorporateAction {
+        action(id, "ACME", ex_day, CorporateActionKind::Split { new_shares, old_shares })
+    }
+
+    fn position(symbol: &str, quantity: i64, entry: f64, mark: f64) -> Position {
+        let mut position = Position {
+            symbol: symbol.to_string(),
+            quantity,
+            cost_basis: Money::from_f64(entry * quantity as f64),
+            average_entry_price: Price::from_f64(entry),
+            current_market_price: Price::from_f64(mark),
+            multiplier: Money::from_f64(1.0),
+            venue_quantities: HashMap::from([("VENUE_A".to_string(), quantity)]),
+            counterparty_quantities: HashMap::from([("VENUE_A".to_string(), quantity)]),
+            ..Default::default()
+        };
+        position.mark();
+        position
+    }
+
+    fn portfolio_holding(positions: Vec<Position>) -> Portfolio {
+        let mut p = Portfolio::new(at(1));
+        for position in positions {
+            p.positions.insert(position.symbol.clone(), position);
+        }
+        p
+    }
+
+    fn usd(p: &Portfolio) -> (Money, Money) {
+        let report = p.cash.report();
+        let balance = report.balances.iter().find(|b| b.currency == "USD").unwrap();
+        (balance.available, balance.pending)
+    }
+
+    #[test]
+    fn forward_split_scales_quantity_and_price_and_keeps_the_cost_basis() {
+        let mut p = portfolio_holding(vec![position("ACME", 100, 120.0, 150.0)]);
+        let mut book = CorporateActionBook::default();
+        book.receive(split("CA-1", 2, 4, 1));
+        book.apply_due(&mut p, at(2));
+
+        let acme = &p.positions["ACME"];
+        assert_eq!(acme.quantity, 400);
+        assert_eq!(acme.current_market_price, Price::from_f64(37.5));
+        assert_eq!(acme.cost_basis, Money::from_f64(12_000.0));
+        assert_eq!(acme.average_entry_price, Price::from_f64(30.0));
+        assert_eq!(acme.unrealized_pnl, Money::from_f64(3_000.0));
+        assert_eq!(acme.venue_quantities["VENUE_A"], 400);
+        assert_eq!(acme.counterparty_quantities["VENUE_A"], 400);
+        assert_eq!(p.realized_pnl, Money::ZERO);
+        assert_eq!(usd(&p), (OPENING_CASH_USD, Money::ZERO));
+    }
+
+    #[test]
+    fn fractional_share_is_paid_as_cash_in_lieu_against_its_share_of_the_basis() {
+        let mut acme = position("ACME", 101, 120.0, 150.0);
+        acme.venue_quantities =
+            HashMap::from([("VENUE_A".to_string(), 33), ("VENUE_B".to_string(), 33), ("VENUE_C".to_string(), 35)]);
+        let mut p = portfolio_holding(vec![acme]);
+        let mut book = CorporateActionBook::default();
+        book.receive(split("CA-1", 2, 3, 2));
+        book.apply_due(&mut p, at(2));
+
+        // 151.5 new shares: 151 are kept and half a share is paid at the adjusted 100.00.
+        let acme = &p.positions["ACME"];
+        assert_eq!(acme.quantity, 151);
+        assert_eq!(acme.current_market_price, Price::from_f64(100.0));
+        assert_eq!(usd(&p), (OPENING_CASH_USD + Money::from_f64(50.0), Money::ZERO));
+
+        // The half share carried 12,120 / 303 = 40.00 of the basis.
+        assert_eq!(acme.cost_basis, Money::from_f64(12_080.0));
+        assert_eq!(acme.average_entry_price, Price::from_f64(80.0));
+        assert_eq!(p.realized_pnl, Money::from_f64(10.0));
+
+        // Each venue truncates to 49 / 49 / 52; the missing share goes to the largest.
+        assert_eq!(acme.venue_quantities["VENUE_A"], 49);
+        assert_eq!(acme.venue_quantities["VENUE_B"], 49);
+        assert_eq!(acme.venue_quantities["VENUE_C"], 53);
+        assert_eq!(acme.venue_quantities.values().sum::<i64>(), 151);
+    }
+
+    #[test]
+    fn reverse_split_truncates_to_whole_shares() {
+        let mut p = portfolio_holding(vec![position("ACME", 25, 10.0, 12.0)]);
+        let mut book = CorporateActionBook::default();
+        book.receive(split("CA-1", 2, 1, 10));
+        book.apply_due(&mut p, at(2));
+
+        let acme = &p.positions["ACME"];
+        assert_eq!(acme.quantity, 2);
+        assert_eq!(acme.current_market_price, Price::from_f64(120.0));
+        assert_eq!(acme.cost_basis, Money::from_f64(200.0));
+        assert_eq!(acme.average_entry_price, Price::from_f64(100.0));
+        assert_eq!(acme.counterparty_quantities["VENUE_A"], 2);
+        assert_eq!(p.realized_pnl, Money::from_f64(10.0));
+        assert_eq!(usd(&p), (OPENING_CASH_USD + Money::from_f64(60.0), Money::ZERO));
+    }
+
+    #[test]
+    fn dividends_credit_longs_and_debit_shorts_settling_on_the_pay_date() {
+        let mut p = portfolio_holding(vec![position("ACME", 100, 50.0, 50.0), position("BOLT", -40, 20.0, 20.0)]);
+        let dividend = || CorporateActionKind::CashDividend {
+            amount_per_share: 0.25,
+            currency: "USD".to_string(),
+            pay_date: date(20),
+        };
+        let mut book = CorporateActionBook::default();
+        book.receive(action("CA-1", "ACME", 2, dividend()));
+        book.receive(action("CA-2", "BOLT", 2, dividend()));
+        book.apply_due(&mut p, at(2));
+
+        assert_eq!(p.positions["ACME"].quantity, 100);
+        assert_eq!(p.realized_pnl, Money::from_f64(25.0 - 10.0));
+        assert_eq!(usd(&p), (OPENING_CASH_USD, Money::from_f64(15.0)));
+
+        p.cash.settle_due(at(19));
+        assert_eq!(usd(&p), (OPENING_CASH_USD, Money::from_f64(15.0)));
+        p.cash.settle_due(at(20));
+        assert_eq!(usd(&p), (OPENING_CASH_USD + Money::from_f64(15.0), Money::ZERO));
+    }
+
+    #[test]
+    fn symbol_change_moves_the_position_unless_the_new_ticker_is_held() {
+        let rename = |id: &str, from: &str, to: &str| {
+            action(id, from, 2, CorporateActionKind::SymbolChange { new_symbol: to.to_string() })
+        };
+        let mut p = portfolio_holding(vec![position("ACME", 100, 50.0, 50.0), position("BOLT", 10, 20.0, 20.0)]);
+        let mut book = CorporateActionBook::default();
+        book.receive(rename("CA-1", "ACME", "ACMX"));
+        book.receive(rename("CA-2", "BOLT", "ACMX"));
+        book.apply_due(&mut p, at(2));
+
+        assert!(!p.positions.contains_key("ACME"));
+        assert_eq!(p.positions["ACMX"].symbol, "ACMX");
+        assert_eq!(p.positions["ACMX"].quantity, 100);
+        assert_eq!(p.positions["BOLT"].quantity, 10);
+        assert!(book.applied()[1].effect.starts_with("not applied"));
+    }
+
+    #[test]
+    fn actions_apply_once_on_their_ex_date_oldest_first() {
+        let mut p = portfolio_holding(vec![position("ACME", 100, 50.0, 50.0)]);
+        let mut book = CorporateActionBook::default();
+        book.already_applied(vec!["CA-0".to_string()]);
+        book.receive(split("CA-0", 1, 2, 1));
+        // Received out of order: the split must still apply before the rename.
+        book.receive(action("CA-2", "ACME", 3, CorporateActionKind::SymbolChange { new_symbol: "ACMX".to_string() }));
+        book.receive(split("CA-1", 2, 2, 1));
+        book.receive(split("CA-1", 2, 2, 1));
+        book.receive(split("CA-3", 9, 2, 1));
+
+        assert!(book.apply_due(&mut p, at(1)).is_empty());
+        assert_eq!(book.apply_due(&mut p, at(3)), vec!["CA-1".to_string(), "CA-2".to_string()]);
+        assert_eq!(p.positions["ACMX"].quantity, 200);
+        assert!(book.apply_due(&mut p, at(8)).is_empty());
+
+        // The later split is still pending and finds no position under the old ticker.
+        assert_eq!(book.apply_due(&mut p, at(9)), vec!["CA-3".to_string()]);
+        assert_eq!(book.applied()[2].effect, "no position in ACME");
+        assert_eq!(p.positions["ACMX"].quantity, 200);
+    }
+}
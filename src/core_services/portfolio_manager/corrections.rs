@@ -0,0 +1,230 @@
+/*
+ * QuantumArb 2.0 - Core Services: Trade Busts and Corrections
+ *
+ * File: src/core_services/portfolio_manager/corrections.rs
+ *
+ * Description:
+ * Puts right a fill the venue busted (TradeBusted) or corrected
+ * (TradeCorrected) after it was booked. The report arrives as a fill that
+ * names the fill it amends: "{exchange order id}:{ExecRefID}".
+ *
+ * The book keeps its recent fills in a trade log, as they were booked (fee
+ * and trade time included). A bust books the logged fill's reversal: the
+ * opposite quantity at the original price, with its fee refunded, for the
+ * same venue, strategy and account and on the same trade date, so
+ * positions, cash, fees and the sub-portfolios return to what they would
+ * have been without the fill. A correction books the reversal and then the
+ * corrected fill, at the quantity and price of the report and its fee (the
+ * original's if the venue sent none). The corrected fill then stands in the
+ * log under the correction's own key, which a later bust or correction
+ * names.
+ *
+ * Total P&L reverses exactly. The realized/unrealized split follows the
+ * reversal trade: reversing part of a position that has since traded
+ * realizes against its average cost instead of restoring the old lots.
+ *
+ * Every bust and correction is kept in an audit trail of original vs
+ * corrected records (GET /portfolio/corrections). One naming a fill no longer
+ * in the log (older than TRADE_RETENTION fills) books nothing and is kept
+ * with no original, for an operator to reconcile. Busts and corrections are
+ * keyed like fills, so a redelivered one applies once; the log and the trail
+ * are part of the restorable book.
+ */
+
+use crate::{book_fill, Fill, Portfolio};
+use chrono::{DateTime, Utc};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Fills kept for busts and corrections to find; older ones are forgotten first.
+const TRADE_RETENTION: usize = 10_000;
+/// Busts and corrections kept for GET /portfolio/corrections.
+const CORRECTION_RETENTION: usize = 1_000;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum AmendmentKind {
+    Bust,
+    Correction,
+}
+
+/// The fill a bust or correction amends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct Amendment {
+    /// The amended fill's "{exchange order id}:{ExecID}".
+    pub original: String,
+    pub kind: AmendmentKind,
+}
+
+/// A fill as it was booked.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct BookedTrade {
+    pub key: String,
+    pub fill: Fill,
+    pub fee_total: Money,
+    pub traded_utc: DateTime<Utc>,
+}
+
+/// One bust or correction, with the fill it amended and what replaced it.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct CorrectionRecord {
+    /// The bust's or correction's own key.
+    pub key: String,
+    pub kind: AmendmentKind,
+    pub original_key: String,
+    /// None if the original was not in the trade log; nothing was booked.
+    pub original: Option<BookedTrade>,
+    /// The corrected fill; None for a bust.
+    pub corrected: Option<BookedTrade>,
+    pub applied_utc: DateTime<Utc>,
+}
+
+/// The recent fills and the busts and corrections applied, oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct TradeLog {
+    trades: VecDeque<BookedTrade>,
+    corrections: VecDeque<CorrectionRecord>,
+}
+
+impl TradeLog {
+    pub fn record(&mut self, trade: BookedTrade) {
+        if self.trades.len() == TRADE_RETENTION {
+            self.trades.pop_front();
+        }
+        self.trades.push_back(trade);
+    }
+
+    fn take(&mut self, key: &str) -> Option<BookedTrade> {
+        let index = self.trades.iter().position(|trade| trade.key == key)?;
+        self.trades.remove(index)
+    }
+
+    fn audit(&mut self, record: CorrectionRecord) {
+        if self.corrections.len() == CORRECTION_RETENTION {
+            self.corrections.pop_front();
+        }
+        self.corrections.push_back(record);
+    }
+
+    /// Busts and corrections applied, newest first.
+    pub fn corrections(&self) -> Vec<CorrectionRecord> {
+        self.corrections.iter().rev().cloned().collect()
+    }
+}
+
+// --- Booking ---
+
+/// Applies a bust or correction `fill` to the book. A key already booked
+/// changes nothing.
+pub fn amend(p: &mut Portfolio, fill: Fill, amendment: Amendment, traded_utc: DateTime<Utc>, now: DateTime<Utc>) {
+    let key = fill.exec_id.clone().unwrap_or_default();
+    if !key.is_empty() && !p.booked_fills.insert(&key) {
+        return;
+    }
+    let original = p.trade_log.take(&amendment.original);
+    let mut corrected = None;
+    if let Some(original) = &original {
+        let reversal = Fill {
+            quantity: -original.fill.quantity,
+            exec_id: None,
+            amends: None,
+            ..original.fill.clone()
+        };
+        book_fill(p, reversal, -original.fee_total, original.traded_utc, now);
+
+        if amendment.kind == AmendmentKind::Correction && fill.quantity != 0 {
+            let fee_total = fill.fee.unwrap_or(original.fee_total);
+            let replacement = Fill { exec_id: None, amends: None, ..fill };
+            book_fill(p, replacement.clone(), fee_total, traded_utc, now);
+            let trade = BookedTrade { key: key.clone(), fill: replacement, fee_total, traded_utc };
+            p.trade_log.record(trade.clone());
+            corrected = Some(trade);
+        }
+    }
+    p.trade_log.audit(CorrectionRecord {
+        key,
+        kind: amendment.kind,
+        original_key: amendment.original,
+        original,
+        corrected,
+        applied_utc: now,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book_fill, PortfolioState};
+    use quantumarb_fees::Liquidity;
+    use quantumarb_money::Price;
+
+    fn fill(key: &str, quantity: i64, price: f64, amends: Option<(&str, AmendmentKind)>) -> Fill {
+        Fill {
+            symbol: "BTC".to_string(),
+            quantity,
+            price: Price::from_f64(price),
+            venue: "VENUE_A".to_string(),
+            counterparty: "VENUE_A".to_string(),
+            liquidity: Liquidity::Taker,
+            fee: Some(Money::from_f64(2.0)),
+            transact_time_utc: None,
+            strategy_id: Some("sor_arbitrage".to_string()),
+            exec_id: Some(key.to_string()),
+            account_id: Some(101),
+            amends: amends.map(|(original, kind)| Amendment { original: original.to_string(), kind }),
+            order_id: None,
+            last_fill: false,
+        }
+    }
+
+    fn book(p: &mut Portfolio, fill: Fill) {
+        let now = Utc::now();
+        book_fill(p, fill.clone(), fill.fee.unwrap(), now, now);
+    }
+
+    /// The BTC quantity, its cost less the P&L realized (which fixes total
+    /// P&L at any mark) and the fees paid.
+    fn held(p: &Portfolio) -> (i64, Money, Money) {
+        (p.positions["BTC"].quantity, p.positions["BTC"].cost_basis - p.realized_pnl, p.total_fees)
+    }
+
+    #[test]
+    fn busts_reverse_the_fill_and_corrections_replace_it() {
+        let mut p = Portfolio::new(Utc::now());
+        book(&mut p, fill("X1:E1", 3, 60_000.0, None));
+        let before = held(&p);
+        book(&mut p, fill("X1:E2", 2, 60_100.0, None));
+
+        // Busting E2, twice, leaves the book as it was before it.
+        let bust = fill("X1:E3", 2, 60_100.0, Some(("X1:E2", AmendmentKind::Bust)));
+        book(&mut p, bust.clone());
+        book(&mut p, bust);
+        assert_eq!(held(&p), before);
+        assert_eq!(p.positions["BTC"].venue_quantities["VENUE_A"], 3);
+
+        // E1 corrected to 3 @ 59,900: the position is as if it had filled there.
+        book(&mut p, fill("X1:E4", 3, 59_900.0, Some(("X1:E1", AmendmentKind::Correction))));
+        let mut clean = Portfolio::new(Utc::now());
+        book(&mut clean, fill("X1:E4", 3, 59_900.0, None));
+        assert_eq!(held(&p), held(&clean));
+
+        // A bust of a fill the log no longer holds is audited, not booked.
+        book(&mut p, fill("X9:E1", 1, 60_000.0, Some(("X9:E0", AmendmentKind::Bust))));
+        let trail = p.trade_log.corrections();
+        assert_eq!(trail.len(), 3);
+        assert!(trail[0].original.is_none());
+        assert_eq!(trail[1].corrected.as_ref().unwrap().fill.price, Price::from_f64(59_900.0));
+        assert_eq!(trail[2].original.as_ref().unwrap().key, "X1:E2");
+
+        // The log and the trail are restored with the book.
+        let state: PortfolioState = serde_json::from_str(&serde_json::to_string(&p.state()).unwrap()).unwrap();
+        let mut restored = Portfolio::new(Utc::now());
+        restored.restore(state);
+        book(&mut restored, fill("X1:E5", 0, 0.0, Some(("X1:E4", AmendmentKind::Bust))));
+        assert_eq!(restored.positions["BTC"].quantity, 0);
+        assert_eq!(restored.trade_log.corrections().len(), 4);
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * QuantumArb 2.0 - Core Services: Counterparty Exposure
+ *
+ * File: src/core_services/portfolio_manager/counterparty.rs
+ *
+ * Description:
+ * Aggregates the firm's exposure to each counterparty (the venue, broker or
+ * clearing house standing behind a fill). Exposure has two parts:
+ *
+ * - Position exposure: the market value of holdings kept at the counterparty,
+ *   which is at risk if it fails (e.g. assets custodied on an exchange).
+ * - Unsettled exposure: for venues that do not settle delivery-versus-payment
+ *   (DVP), the notional of trades that have executed but not yet settled.
+ *   One leg has already moved, so the full notional is at risk until
+ *   settlement. DVP venues carry no unsettled exposure.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use quantumarb_types::{CounterpartyExposure, Position, SettlementModel};
+
+/// Static counterparty reference data until a reference data service exists.
+pub fn settlement_model(counterparty: &str) -> SettlementModel {
+    match counterparty {
+        "VENUE_A" => SettlementModel::NonDvp { settlement_days: 1 },
+        "VENUE_B" | "CME" => SettlementModel::Dvp,
+        // Unknown counterparties are treated as the riskier case.
+        _ => SettlementModel::NonDvp { settlement_days: 2 },
+    }
+}
+
+/// A fill on a non-DVP venue that has not settled yet.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct UnsettledTrade {
+    pub counterparty: String,
+    pub notional: Money,
+    pub settles_utc: DateTime<Utc>,
+}
+
+impl UnsettledTrade {
+    /// Returns the unsettled leg for a fill, or None for DVP counterparties.
+    pub fn for_fill(counterparty: &str, notional: Money, traded_utc: DateTime<Utc>) -> Option<UnsettledTrade> {
+        match settlement_model(counterparty) {
+            SettlementModel::Dvp => None,
+            SettlementModel::NonDvp { settlement_days } => Some(UnsettledTrade {
+                counterparty: counterparty.to_string(),
+                notional: notional.abs(),
+                settles_utc: traded_utc + chrono::Duration::days(settlement_days as i64),
+            }),
+        }
+    }
+}
+
+/// Builds the per-counterparty view, dropping trades that have settled.
+pub fn aggregate(
+    positions: &HashMap<String, Position>,
+    unsettled: &mut Vec<UnsettledTrade>,
+    now: DateTime<Utc>,
+) -> Vec<CounterpartyExposure> {
+    unsettled.retain(|t| t.settles_utc > now);
+
+    let mut by_counterparty: HashMap<String, (Money, Money)> = HashMap::new();
+    for position in positions.values() {
+        for (counterparty, quantity) in &position.counterparty_quantities {
+            let entry = by_counterparty.entry(counterparty.clone()).or_default();
+            entry.0 += Money::notional(position.current_market_price, Quantity(*quantity), position.multiplier).abs();
+        }
+    }
+    for trade in unsettled.iter() {
+        by_counterparty.entry(trade.counterparty.clone()).or_default().1 += trade.notional;
+    }
+
+    let mut exposures: Vec<CounterpartyExposure> = by_counterparty
+        .into_iter()
+        .map(|(counterparty, (position_exposure, unsettled_exposure))| CounterpartyExposure {
+            settlement_model: settlement_model(&counterparty),
+            counterparty,
+            position_exposure,
+            unsettled_exposure,
+            total_exposure: position_exposure + unsettled_exposure,
+        })
+        .collect();
+    exposures.sort_by_key(|e| std::cmp::Reverse(e.total_exposure));
+    exposures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use quantumarb_money::Price;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 2, 12, 0, 0).unwrap()
+    }
+
+    fn position(symbol: &str, mark: f64, multiplier: f64, counterparties: &[(&str, i64)]) -> (String, Position) {
+        let counterparty_quantities: HashMap<String, i64> =
+            counterparties.iter().map(|(counterparty, quantity)| (counterparty.to_string(), *quantity)).collect();
+        let position = Position {
+            symbol: symbol.to_string(),
+            quantity: counterparty_quantities.values().sum(),
+            current_market_price: Price::from_f64(mark),
+            multiplier: Money::from_f64(multiplier),
+            counterparty_quantities,
+            ..Default::default()
+        };
+        (symbol.to_string(), position)
+    }
+
+    #[test]
+    fn only_non_dvp_fills_leave_an_unsettled_leg() {
+        assert!(UnsettledTrade::for_fill("VENUE_B", Money::from_f64(1_000.0), now()).is_none());
+        assert!(UnsettledTrade::for_fill("CME", Money::from_f64(1_000.0), now()).is_none());
+
+        let sale = UnsettledTrade::for_fill("VENUE_A", Money::from_f64(-1_000.0), now()).unwrap();
+        assert_eq!(sale.notional, Money::from_f64(1_000.0));
+        assert_eq!(sale.settles_utc, now() + chrono::Duration::days(1));
+        let unknown = UnsettledTrade::for_fill("OTC_DESK", Money::from_f64(1_000.0), now()).unwrap();
+        assert_eq!(unknown.settles_utc, now() + chrono::Duration::days(2));
+    }
+
+    #[test]
+    fn exposure_sums_gross_position_value_and_unsettled_notional_per_counterparty() {
+        let positions = HashMap::from([
+            position("BTC", 60_000.0, 1.0, &[("VENUE_A", 2), ("VENUE_B", -1)]),
+            position("ESZ25", 4_500.0, 50.0, &[("CME", -3)]),
+            position("ETH", 3_000.0, 1.0, &[("VENUE_A", -10)]),
+        ]);
+        let mut unsettled = vec![
+            UnsettledTrade::for_fill("VENUE_A", Money::from_f64(5_000.0), now()).unwrap(),
+            UnsettledTrade::for_fill("OTC_DESK", Money::from_f64(7_000.0), now()).unwrap(),
+        ];
+
+        let exposures = aggregate(&positions, &mut unsettled, now());
+        let summary: Vec<(&str, f64, f64, f64)> = exposures
+            .iter()
+            .map(|e| {
+                let (position, unsettled) = (e.position_exposure.to_f64(), e.unsettled_exposure.to_f64());
+                (e.counterparty.as_str(), position, unsettled, e.total_exposure.to_f64())
+            })
+            .collect();
+        // Largest total first; shorts count at their gross value.
+        assert_eq!(
+            summary,
+            [
+                ("CME", 675_000.0, 0.0, 675_000.0),
+                ("VENUE_A", 150_000.0, 5_000.0, 155_000.0),
+                ("VENUE_B", 60_000.0, 0.0, 60_000.0),
+                ("OTC_DESK", 0.0, 7_000.0, 7_000.0),
+            ]
+        );
+        assert_eq!(exposures[0].settlement_model, SettlementModel::Dvp);
+        assert_eq!(exposures[1].settlement_model, SettlementModel::NonDvp { settlement_days: 1 });
+    }
+
+    #[test]
+    fn settled_trades_drop_out_of_the_exposure() {
+        let mut unsettled = vec![
+            UnsettledTrade::for_fill("VENUE_A", Money::from_f64(5_000.0), now()).unwrap(),
+            UnsettledTrade::for_fill("OTC_DESK", Money::from_f64(7_000.0), now()).unwrap(),
+        ];
+
+        let exposures = aggregate(&HashMap::new(), &mut unsettled, now() + chrono::Duration::days(1));
+        assert_eq!(unsettled.len(), 1);
+        assert_eq!(exposures.len(), 1);
+        assert_eq!(exposures[0].counterparty, "OTC_DESK");
+        assert_eq!(exposures[0].total_exposure, Money::from_f64(7_000.0));
+    }
+}
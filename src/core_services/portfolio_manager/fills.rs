@@ -0,0 +1,180 @@
+/*
+ * QuantumArb 2.0 - Core Services: Booked Fills
+ *
+ * File: src/core_services/portfolio_manager/fills.rs
+ *
+ * Description:
+ * Makes booking a fill idempotent. Fills from execution reports are keyed
+ * on the report's (exchange order id, ExecID), carried as the fill's
+ * "{exchange order id}:{ExecID}" exec_id; the book keeps the keys of the
+ * last BOOKED_RETENTION fills it booked, and `book_fill` ignores a fill
+ * whose key it has seen. A report the bus delivers twice, a duplicate in
+ * the journal, or a fill posted again after a restore therefore counts
+ * once, in whatever order the reports arrive.
+ *
+ * The keys are part of the restorable book (GET/PUT /portfolio/state) and
+ * of every journal snapshot, so they survive restarts and restores with the
+ * positions they produced. Fills without an ExecID (hypothetical and
+ * hand-posted ones) are always booked.
+ */
+
+use quantumarb_openapi::{ApiSchema, Components, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashSet, VecDeque};
+
+/// Keys kept; older ones are forgotten first.
+const BOOKED_RETENTION: usize = 100_000;
+
+// --- Data Structures ---
+
+/// Keys of the fills in the book, oldest first. Serialized as the list of
+/// keys.
+#[derive(Debug, Clone, Default)]
+pub struct BookedFills {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl BookedFills {
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    /// Records a booked fill's key. False if it was booked already.
+    pub fn insert(&mut self, key: &str) -> bool {
+        if !self.keys.insert(key.to_string()) {
+            return false;
+        }
+        if self.order.len() == BOOKED_RETENTION {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.to_string());
+        true
+    }
+}
+
+impl Serialize for BookedFills {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.order.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BookedFills {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut booked = BookedFills::default();
+        for key in Vec::<String>::deserialize(deserializer)? {
+            booked.insert(&key);
+        }
+        Ok(booked)
+    }
+}
+
+impl ApiSchema for BookedFills {
+    fn schema(components: &mut Components) -> Value {
+        Vec::<String>::schema(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::{BookedFill, Journal, JournalConfig};
+    use crate::{book_fill, Fill, Portfolio, PortfolioState};
+    use chrono::Utc;
+    use quantumarb_fees::Liquidity;
+    use quantumarb_money::Money;
+    use quantumarb_refdata::ReferenceData;
+    use quantumarb_wire::{ExecutionReport, HopStamps, OrderSide, OrderStatus, TradingMode};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn fill(exchange_order_id: &str, exec_id: &str, side: OrderSide, size: u32, price: u64) -> Fill {
+        let report = ExecutionReport {
+            exchange_order_id: exchange_order_id.to_string(),
+            exec_id: exec_id.to_string(),
+            internal_order_id: Uuid::nil(),
+            status: OrderStatus::PartiallyFilled,
+            filled_size: size,
+            filled_price: price,
+            reject: None,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            cumulative_size: size,
+            leaves_size: 0,
+            liquidity: Some(Liquidity::Maker),
+            fee: Money::from_f64(1.0),
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        };
+        let btc = ReferenceData::seeded().by_symbol("BTC").unwrap().clone();
+        Fill::from_report(&report, &btc, side, "VENUE_A", "sor_arbitrage", 101).unwrap()
+    }
+
+    fn book(p: &mut Portfolio, stream: &[Fill]) {
+        let now = Utc::now();
+        for fill in stream {
+            book_fill(p, fill.clone(), fill.fee.unwrap_or(Money::ZERO), now, now);
+        }
+    }
+
+    /// What the fills booked, independent of the order they came in.
+    fn booked(p: &Portfolio) -> (i64, Money, Money, Money, Vec<Money>, usize) {
+        let btc = &p.positions["BTC"];
+        let cash = p.cash.report().balances.iter().map(|balance| balance.projected).collect();
+        (btc.quantity, btc.cost_basis, p.realized_pnl, p.total_fees, cash, p.booked_fills.order.len())
+    }
+
+    #[test]
+    fn duplicated_and_reordered_report_streams_book_each_fill_once() {
+        let (a1, a2) = (fill("X1", "E1", OrderSide::Buy, 3, 60_000_00), fill("X1", "E2", OrderSide::Buy, 2, 60_010_00));
+        // The same ExecID on another order is another fill.
+        let b1 = fill("X2", "E1", OrderSide::Sell, 1, 60_020_00);
+
+        let mut clean = Portfolio::new(Utc::now());
+        book(&mut clean, &[a1.clone(), a2.clone(), b1.clone()]);
+        assert_eq!(booked(&clean).0, 4);
+        assert_eq!(booked(&clean).3, Money::from_f64(3.0));
+
+        let mut redelivered = Portfolio::new(Utc::now());
+        book(&mut redelivered, &[a2.clone(), a1.clone(), a2.clone(), b1.clone(), a1.clone(), b1.clone(), a2]);
+        assert_eq!(booked(&redelivered), booked(&clean));
+        assert_eq!(redelivered.positions["BTC"].venue_quantities["VENUE_A"], 4);
+
+        // Fills without an ExecID are never taken for duplicates.
+        let mut untagged = a1.clone();
+        untagged.exec_id = None;
+        book(&mut redelivered, &[untagged.clone(), untagged]);
+        assert_eq!(redelivered.positions["BTC"].quantity, 10);
+    }
+
+    #[test]
+    fn booked_fills_survive_restores_and_journal_replay() {
+        let a1 = fill("X1", "E1", OrderSide::Buy, 3, 60_000_00);
+        let mut live = Portfolio::new(Utc::now());
+        book(&mut live, std::slice::from_ref(&a1));
+
+        let saved = serde_json::to_string(&live.state()).unwrap();
+        let mut restored = Portfolio::new(Utc::now());
+        restored.restore(serde_json::from_str::<PortfolioState>(&saved).unwrap());
+        book(&mut restored, std::slice::from_ref(&a1));
+        assert_eq!(booked(&restored), booked(&live));
+
+        // A journal holding the same fill twice replays it once.
+        let path = std::env::temp_dir().join(format!("pm-fills-{}.jsonl", Uuid::new_v4()));
+        let config = JournalConfig {
+            path: path.to_string_lossy().into_owned(),
+            drain_interval: Duration::from_millis(50),
+            compact_lines: 10_000,
+        };
+        let journal = Journal::open(config.clone(), &mut Portfolio::new(Utc::now())).unwrap();
+        let now = Utc::now();
+        let twice = BookedFill { fill: a1.clone(), fee_total: Money::from_f64(1.0), traded_utc: now, booked_utc: now };
+        journal.booked(&twice);
+        journal.booked(&twice);
+        let mut replayed = Portfolio::new(Utc::now());
+        Journal::open(config, &mut replayed).unwrap();
+        assert_eq!(booked(&replayed), booked(&live));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * QuantumArb 2.0 - Core Services: FX Exposure Monitor
+ *
+ * File: src/core_services/portfolio_manager/fx.rs
+ *
+ * Description:
+ * Tracks the book's unhedged exposure to each currency other than the base
+ * currency and converts foreign cash back to base at scheduled fixes.
+ *
+ *   exposure = projected cash in the currency
+ *            + market value of positions denominated in it (unrealized P&L
+ *              for futures, whose notional is not paid)
+ *
+ * The currency of a position is the one its reference data names. Each
+ * exposure is valued in base at the configured rate and checked against its
+ * limit; a currency going over its limit raises an alert.
+ *
+ * At each fix time (by default 16:00 UTC, the WM/Reuters fix in winter) and
+ * with auto-convert enabled, the projected cash balance in every foreign
+ * currency is converted at the fix rate: the ledger is debited in the
+ * currency and credited in base, both legs settling spot (T+2). Position
+ * exposure is left to the hedging engine's currency rules.
+ *
+ * Configuration (environment):
+ *   QA_FX_BASE_CURRENCY=USD                    currency the book is run in
+ *   QA_FX_RATES=EUR=1.08,GBP=1.27,JPY=0.0067   base units per unit of currency
+ *   QA_FX_ALERT_LIMIT=250000                   exposure limit per currency, in base
+ *   QA_FX_ALERT_LIMITS=                        per-currency overrides (EUR=500000,...)
+ *   QA_FX_FIX_TIMES=16:00                      UTC times of scheduled conversions
+ *   QA_FX_AUTO_CONVERT=false                   convert foreign cash at each fix
+ */
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{AssetClass, ReferenceData};
+use quantumarb_types::Position;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::cash::{add_business_days, CashLedger, CashReport};
+
+/// FX spot settles T+2.
+const SPOT_SETTLEMENT_DAYS: i64 = 2;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FxConfig {
+    pub base_currency: String,
+    /// Units of base currency per unit of each currency.
+    pub rates: BTreeMap<String, f64>,
+    pub default_limit: f64,
+    pub limits: BTreeMap<String, f64>,
+    pub fix_times: Vec<NaiveTime>,
+    pub auto_convert: bool,
+}
+
+impl FxConfig {
+    pub fn from_env() -> FxConfig {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let fix_times = var("QA_FX_FIX_TIMES")
+            .unwrap_or_else(|| "16:00".to_string())
+            .split(',')
+            .filter_map(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok())
+            .collect();
+        FxConfig {
+            base_currency: var("QA_FX_BASE_CURRENCY").unwrap_or_else(|| "USD".to_string()),
+            rates: parse_pairs(&var("QA_FX_RATES").unwrap_or_else(|| "EUR=1.08,GBP=1.27,JPY=0.0067".to_string())),
+            default_limit: var("QA_FX_ALERT_LIMIT").and_then(|v| v.parse().ok()).unwrap_or(250_000.0),
+            limits: var("QA_FX_ALERT_LIMITS").map(|v| parse_pairs(&v)).unwrap_or_default(),
+            fix_times,
+            auto_convert: std::env::var("QA_FX_AUTO_CONVERT").as_deref() == Ok("true"),
+        }
+    }
+
+    fn limit(&self, currency: &str) -> f64 {
+        self.limits.get(currency).copied().unwrap_or(self.default_limit)
+    }
+
+    /// The latest fix in (after, upto], if any.
+    pub fn fix_between(&self, after: DateTime<Utc>, upto: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = (upto.date_naive() - after.date_naive()).num_days();
+        (0..=days)
+            .flat_map(|d| {
+                let date = after.date_naive() + Duration::days(d);
+                self.fix_times.iter().map(move |t| date.and_time(*t).and_utc())
+            })
+            .filter(|fix| *fix > after && *fix <= upto)
+            .max()
+    }
+
+    /// The first fix after `now`.
+    pub fn next_fix(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.fix_times
+            .iter()
+            .flat_map(|t| (0..2).map(move |d| (now.date_naive() + Duration::days(d)).and_time(*t).and_utc()))
+            .filter(|fix| *fix > now)
+            .min()
+    }
+}
+
+/// Parses "EUR=1.08,GBP=1.27".
+fn parse_pairs(value: &str) -> BTreeMap<String, f64> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (currency, number) = pair.split_once('=')?;
+            Some((currency.trim().to_uppercase(), number.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FxExposure {
+    pub currency: String,
+    /// Projected cash, in the currency.
+    pub cash: f64,
+    /// Positions denominated in the currency, in the currency.
+    pub positions: f64,
+    pub net: f64,
+    /// Base units per unit; None when no rate is configured.
+    pub rate: Option<f64>,
+    pub net_base: Option<f64>,
+    pub limit: f64,
+    pub over_limit: bool,
+}
+
+/// One scheduled conversion of foreign cash to base.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FxConversion {
+    pub currency: String,
+    /// Amount of the currency sold (negative: bought to cover a deficit).
+    pub sold: Money,
+    pub rate: f64,
+    pub bought: Money,
+    pub fix_utc: DateTime<Utc>,
+    pub settles_utc: DateTime<Utc>,
+}
+
+/// Body of a GET /portfolio/fx response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FxReport {
+    pub base_currency: String,
+    pub exposures: Vec<FxExposure>,
+    pub auto_convert: bool,
+    pub next_fix_utc: Option<DateTime<Utc>>,
+    /// Newest last.
+    pub recent_conversions: Vec<FxConversion>,
+    pub timestamp_utc: String,
+}
+
+// --- Exposure and Conversion ---
+
+/// The book's exposure to every currency other than base.
+pub fn exposures(
+    config: &FxConfig,
+    positions: &HashMap<String, Position>,
+    instruments: &ReferenceData,
+    cash: &CashReport,
+) -> Vec<FxExposure> {
+    let mut by_currency: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+    for balance in cash.balances.iter().filter(|b| b.currency != config.base_currency) {
+        by_currency.entry(balance.currency.clone()).or_default().0 += balance.projected.to_f64();
+    }
+    for position in positions.values().filter(|p| p.quantity != 0) {
+        let Some(definition) = instruments.by_symbol(&position.symbol) else {
+            continue;
+        };
+        if definition.currency == config.base_currency {
+            continue;
+        }
+        let value = if definition.asset_class == AssetClass::Future {
+            position.unrealized_pnl.to_f64()
+        } else {
+            position.quantity as f64 * position.multiplier.to_f64() * position.current_market_price.to_f64()
+        };
+        by_currency.entry(definition.currency.clone()).or_default().1 += value;
+    }
+
+    by_currency
+        .into_iter()
+        .map(|(currency, (cash, positions))| {
+            let net = cash + positions;
+            let rate = config.rates.get(&currency).copied();
+            let net_base = rate.map(|r| net * r);
+            let limit = config.limit(&currency);
+            // Without a rate the exposure cannot be valued; flag it rather than miss it.
+            let over_limit = net_base.map_or(net != 0.0, |v| v.abs() > limit);
+            FxExposure { currency, cash, positions, net, rate, net_base, limit, over_limit }
+        })
+        .collect()
+}
+
+/// Converts the projected cash in every foreign currency with a rate to base
+/// at the fix, posting both legs to the ledger.
+pub fn convert_cash(config: &FxConfig, ledger: &mut CashLedger, fix_utc: DateTime<Utc>) -> Vec<FxConversion> {
+    let settles_utc = add_business_days(fix_utc, SPOT_SETTLEMENT_DAYS);
+    let balances = ledger.report().balances;
+    let mut conversions = Vec::new();
+    for balance in balances.iter().filter(|b| b.currency != config.base_currency && !b.projected.is_zero()) {
+        let Some(&rate) = config.rates.get(&balance.currency) else {
+            continue;
+        };
+        let sold = balance.projected;
+        let bought = sold.scaled(rate);
+        let description = format!(
+            "FX conversion {:.2} {} -> {:.2} {} @ {}",
+            sold, balance.currency, bought, config.base_currency, rate
+        );
+        ledger.post_movement(&balance.currency, -sold, description.clone(), fix_utc, settles_utc);
+        ledger.post_movement(&config.base_currency, bought, description, fix_utc, settles_utc);
+        conversions.push(FxConversion {
+            currency: balance.currency.clone(),
+            sold,
+            rate,
+            bought,
+            fix_utc,
+            settles_utc,
+        });
+    }
+    conversions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_foreign_cash_at_the_fix_and_flags_exposure_over_limit() {
+        let mut config = FxConfig::from_env();
+        config.rates = parse_pairs("EUR=1.1");
+        config.default_limit = 100_000.0;
+        config.fix_times = vec![NaiveTime::from_hms_opt(16, 0, 0).unwrap()];
+
+        // Monday 2025-03-03, 15:59 to 16:01 UTC.
+        let before = "2025-03-03T15:59:00Z".parse::<DateTime<Utc>>().unwrap();
+        let after = before + Duration::minutes(2);
+        let fix = config.fix_between(before, after).unwrap();
+        assert_eq!(fix, "2025-03-03T16:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(config.fix_between(after, after + Duration::minutes(1)), None);
+        assert_eq!(config.next_fix(after), Some(fix + Duration::days(1)));
+
+        let mut ledger = CashLedger::with_opening_balance("USD", Money::from_f64(1_000_000.0));
+        ledger.post_movement("EUR", Money::from_f64(200_000.0), "Dividend".to_string(), before, before);
+        ledger.settle_due(before);
+        let instruments = ReferenceData::seeded();
+        let exposed = exposures(&config, &HashMap::new(), &instruments, &ledger.report());
+        assert_eq!(exposed.len(), 1);
+        assert!((exposed[0].net_base.unwrap() - 220_000.0).abs() < 1e-6);
+        assert!(exposed[0].over_limit);
+
+        let conversions = convert_cash(&config, &mut ledger, fix);
+        assert_eq!(conversions.len(), 1);
+        assert_eq!(conversions[0].bought, Money::from_f64(220_000.0));
+        // Wednesday: two business days after Monday.
+        assert_eq!(conversions[0].settles_utc, fix + Duration::days(2));
+        let exposed = exposures(&config, &HashMap::new(), &instruments, &ledger.report());
+        assert!(exposed[0].net == 0.0 && !exposed[0].over_limit);
+        let usd = ledger.report().balances.into_iter().find(|b| b.currency == "USD").unwrap();
+        assert_eq!(usd.projected, Money::from_f64(1_220_000.0));
+    }
+}
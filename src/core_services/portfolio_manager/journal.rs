@@ -0,0 +1,377 @@
+/*
+ * QuantumArb 2.0 - Core Services: Portfolio Journal
+ *
+ * File: src/core_services/portfolio_manager/journal.rs
+ *
+ * Description:
+ * Event-sources the book through a transactional outbox (see
+ * `quantumarb-outbox`), so a restarted portfolio manager holds exactly the
+ * fills it had booked and publishes exactly the messages it had decided to:
+ *
+ *   Booked     a fill, with the fee and times it was booked at; replayed
+ *              through the same booking as a live fill
+ *   Allocated  a parent account's block split across its sub-accounts
+ *              (see allocation.rs), committed with its report for
+ *              'portfolio.allocations'; replayed through the same allocation
+ *   Snapshot   the whole book, with the fills and corporate actions already
+ *              applied; written by PUT /portfolio/state, after corporate
+ *              actions and multiplier changes, and when the journal is
+ *              compacted
+ *
+ * Margin alerts, the margin call's reduce instruction and flattening orders
+ * change no position; they are committed as messages alone, each under its
+ * own dedup key, and published by the outbox task.
+ *
+ * Fills from execution reports carry the report's exchange order id and
+ * ExecID, and the book, snapshots included, keeps those it has booked (see
+ * fills.rs); replaying a fill journaled twice books it once.
+ *
+ * Configuration (environment):
+ *   QA_PM_JOURNAL_PATH=portfolio_manager.journal.jsonl
+ *   QA_PM_OUTBOX_DRAIN_MS=50            time between publisher runs
+ *   QA_PM_JOURNAL_COMPACT_LINES=10000   journal length that triggers compaction
+ */
+
+use crate::allocation::{self, AllocationReport};
+use crate::{book_fill, Fill, Portfolio, PortfolioState};
+use chrono::{DateTime, Utc};
+use quantumarb_money::Money;
+use quantumarb_outbox::{Message, Outbox, OutboxStats};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub path: String,
+    pub drain_interval: Duration,
+    pub compact_lines: usize,
+}
+
+impl JournalConfig {
+    pub fn from_env() -> JournalConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        JournalConfig {
+            path: var("QA_PM_JOURNAL_PATH", "portfolio_manager.journal.jsonl".to_string()),
+            drain_interval: Duration::from_millis(var("QA_PM_OUTBOX_DRAIN_MS", 50u64).max(1)),
+            compact_lines: var("QA_PM_JOURNAL_COMPACT_LINES", 10_000usize).max(2),
+        }
+    }
+}
+
+// --- Events ---
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookedFill {
+    pub fill: Fill,
+    pub fee_total: Money,
+    pub traded_utc: DateTime<Utc>,
+    pub booked_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Allocated {
+    pub block_id: String,
+    pub allocated_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub state: PortfolioState,
+    /// Booked fill keys, from journals written before the state kept them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub booked: Vec<String>,
+    #[serde(default)]
+    pub corporate_actions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PortfolioEvent {
+    Booked(Box<BookedFill>),
+    Allocated(Allocated),
+    Snapshot(Box<BookSnapshot>),
+}
+
+// --- Journal ---
+
+struct Inner {
+    outbox: Outbox<PortfolioEvent>,
+    corporate_actions: Vec<String>,
+}
+
+impl Inner {
+    fn snapshot(&self, p: &Portfolio) -> PortfolioEvent {
+        PortfolioEvent::Snapshot(Box::new(BookSnapshot {
+            state: p.state(),
+            booked: Vec::new(),
+            corporate_actions: self.corporate_actions.clone(),
+        }))
+    }
+
+    /// A commit that cannot be written is fatal: the book would be ahead of
+    /// its journal.
+    fn commit(&mut self, events: Vec<PortfolioEvent>, messages: Vec<Message>) {
+        self.outbox.commit(events, messages).expect("Failed to write the portfolio journal");
+    }
+}
+
+/// The portfolio journal. Events are committed while holding the portfolio's
+/// lock, so the journal's order is the book's.
+#[derive(Clone)]
+pub struct Journal {
+    inner: Arc<Mutex<Inner>>,
+    config: JournalConfig,
+}
+
+impl Journal {
+    /// Opens the journal and replays it into `p`.
+    pub fn open(config: JournalConfig, p: &mut Portfolio) -> std::io::Result<Journal> {
+        let (outbox, events) = Outbox::open(&config.path)?;
+        let mut inner = Inner { outbox, corporate_actions: Vec::new() };
+        let replayed = events.len();
+        for event in events {
+            match event {
+                PortfolioEvent::Booked(booked) => {
+                    book_fill(p, booked.fill, booked.fee_total, booked.traded_utc, booked.booked_utc);
+                }
+                PortfolioEvent::Allocated(allocated) => {
+                    let _ = allocation::allocate(p, &allocated.block_id, allocated.allocated_utc);
+                }
+                PortfolioEvent::Snapshot(snapshot) => {
+                    p.restore(snapshot.state);
+                    for exec_id in &snapshot.booked {
+                        p.booked_fills.insert(exec_id);
+                    }
+                    inner.corporate_actions = snapshot.corporate_actions;
+                }
+            }
+        }
+        let stats = inner.outbox.stats();
+        println!(
+            "Portfolio journal {}: {} events replayed, {} positions, {} messages to publish",
+            stats.path,
+            replayed,
+            p.positions.len(),
+            stats.pending_messages
+        );
+        Ok(Journal { inner: Arc::new(Mutex::new(inner)), config })
+    }
+
+    pub fn booked(&self, booked: &BookedFill) {
+        self.inner.lock().unwrap().commit(vec![PortfolioEvent::Booked(Box::new(booked.clone()))], Vec::new());
+    }
+
+    /// Allocations made, with their reports to publish.
+    pub fn allocated(&self, reports: &[AllocationReport]) {
+        let events = reports
+            .iter()
+            .map(|report| {
+                PortfolioEvent::Allocated(Allocated {
+                    block_id: report.block_id.clone(),
+                    allocated_utc: report.allocated_utc,
+                })
+            })
+            .collect();
+        let messages = reports
+            .iter()
+            .map(|report| {
+                let allocated_ns = report.allocated_utc.timestamp_nanos_opt().unwrap_or(0);
+                let key = format!("allocation:{}:{}", report.block_id, allocated_ns);
+                Message::json(allocation::ALLOCATIONS_TOPIC, key, report)
+            })
+            .collect();
+        self.inner.lock().unwrap().commit(events, messages);
+    }
+
+    /// Records a change made to the book outside fills, as a snapshot.
+    pub fn changed(&self, p: &Portfolio) {
+        let mut inner = self.inner.lock().unwrap();
+        let snapshot = inner.snapshot(p);
+        inner.commit(vec![snapshot], Vec::new());
+    }
+
+    /// Corporate actions applied to the book.
+    pub fn corporate_actions(&self, p: &Portfolio, action_ids: Vec<String>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.corporate_actions.extend(action_ids);
+        let snapshot = inner.snapshot(p);
+        inner.commit(vec![snapshot], Vec::new());
+    }
+
+    /// Corporate actions already applied, not to be applied again.
+    pub fn corporate_actions_applied(&self) -> Vec<String> {
+        self.inner.lock().unwrap().corporate_actions.clone()
+    }
+
+    /// Messages that change no state.
+    pub fn publish(&self, messages: Vec<Message>) {
+        self.inner.lock().unwrap().commit(Vec::new(), messages);
+    }
+
+    pub fn stats(&self) -> OutboxStats {
+        self.inner.lock().unwrap().outbox.stats()
+    }
+
+    pub fn drain_interval(&self) -> Duration {
+        self.config.drain_interval
+    }
+
+    /// Publishes what is pending, then compacts the journal if it is due.
+    pub fn drain(&self, portfolio: &Mutex<Portfolio>) {
+        let compact = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Err(e) = quantumarb_outbox::drain(&mut inner.outbox) {
+                println!("Portfolio journal: failed to record published messages ({}); they will be resent.", e);
+            }
+            inner.outbox.journal_lines() >= self.config.compact_lines
+        };
+        if compact {
+            let p = portfolio.lock().unwrap();
+            let mut inner = self.inner.lock().unwrap();
+            let snapshot = inner.snapshot(&p);
+            match inner.outbox.compact(vec![snapshot]) {
+                Ok(()) => println!("\nPortfolio journal compacted: {} positions.", p.positions.len()),
+                Err(e) => println!("\nPortfolio journal compaction failed: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_fees::Liquidity;
+    use quantumarb_money::Price;
+    use std::io::Write;
+
+    fn config(name: &str) -> JournalConfig {
+        let path = std::env::temp_dir().join(format!("qa-pm-journal-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().into_owned();
+        JournalConfig { path, drain_interval: Duration::from_millis(50), compact_lines: 100 }
+    }
+
+    fn fill(exec_id: &str, quantity: i64, price: f64) -> Fill {
+        Fill {
+            symbol: "BTC".to_string(),
+            quantity,
+            price: Price::from_f64(price),
+            venue: "VENUE_A".to_string(),
+            counterparty: "VENUE_A".to_string(),
+            liquidity: Liquidity::Taker,
+            fee: Some(Money::from_f64(2.0)),
+            transact_time_utc: None,
+            strategy_id: Some("sor_arbitrage".to_string()),
+            exec_id: Some(exec_id.to_string()),
+            account_id: Some(101),
+            amends: None,
+            order_id: None,
+            last_fill: false,
+        }
+    }
+
+    /// Journals a fill and books it, as the fill listener does.
+    fn book(journal: &Journal, p: &mut Portfolio, fill: Fill) {
+        let now = Utc::now();
+        let booked = BookedFill { fee_total: fill.fee.unwrap(), fill, traded_utc: now, booked_utc: now };
+        journal.booked(&booked);
+        book_fill(p, booked.fill, booked.fee_total, booked.traded_utc, booked.booked_utc);
+    }
+
+    /// The BTC position and the book's P&L and fees.
+    fn summary(p: &Portfolio) -> (i64, Money, Money, Money) {
+        let btc = p.positions.get("BTC").cloned().unwrap_or_default();
+        (btc.quantity, btc.cost_basis, p.realized_pnl, p.total_fees)
+    }
+
+    fn reopen(config: &JournalConfig) -> std::io::Result<(Journal, Portfolio)> {
+        let mut p = Portfolio::new(Utc::now());
+        let journal = Journal::open(config.clone(), &mut p)?;
+        Ok((journal, p))
+    }
+
+    fn append(config: &JournalConfig, bytes: &[u8]) {
+        std::fs::OpenOptions::new().append(true).open(&config.path).unwrap().write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn replay_rebuilds_the_book_booking_a_fill_journaled_twice_once() {
+        let config = config("replay");
+        let (journal, mut p) = reopen(&config).unwrap();
+        book(&journal, &mut p, fill("E-1", 2, 60_000.0));
+        book(&journal, &mut p, fill("E-2", -1, 61_000.0));
+        book(&journal, &mut p, fill("E-2", -1, 61_000.0));
+        assert_eq!(summary(&p), (1, Money::from_f64(60_000.0), Money::from_f64(1_000.0), Money::from_f64(4.0)));
+        drop(journal);
+
+        let (journal, replayed) = reopen(&config).unwrap();
+        assert_eq!(summary(&replayed), summary(&p));
+        assert!(replayed.booked_fills.contains("E-1") && replayed.booked_fills.contains("E-2"));
+        assert_eq!((journal.stats().last_commit, journal.stats().pending_messages), (3, 0));
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn snapshots_restore_the_book_and_the_corporate_actions_applied() {
+        let config = JournalConfig { compact_lines: 3, ..config("snapshot") };
+        let (journal, mut p) = reopen(&config).unwrap();
+        book(&journal, &mut p, fill("E-1", 2, 60_000.0));
+        journal.corporate_actions(&p, vec!["CA-1".to_string()]);
+        book(&journal, &mut p, fill("E-2", 1, 62_000.0));
+
+        // The third line triggers compaction to a snapshot of the book.
+        let portfolio = Mutex::new(p);
+        journal.drain(&portfolio);
+        assert_eq!(journal.stats().compactions, 1);
+        assert!(journal.stats().journal_lines < 3);
+        drop(journal);
+
+        let p = portfolio.into_inner().unwrap();
+        let (journal, replayed) = reopen(&config).unwrap();
+        assert_eq!(summary(&replayed), summary(&p));
+        assert_eq!(summary(&replayed).0, 3);
+        assert_eq!(journal.corporate_actions_applied(), ["CA-1"]);
+        assert!(replayed.booked_fills.contains("E-2"));
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn a_torn_last_commit_is_discarded_and_the_journal_continues() {
+        let config = config("torn");
+        let (journal, mut p) = reopen(&config).unwrap();
+        book(&journal, &mut p, fill("E-1", 2, 60_000.0));
+        drop(journal);
+        append(&config, br#"{"commit":{"seq":2,"events":[{"booked":{"fill":{"symbol":"BTC""#);
+
+        let (journal, mut replayed) = reopen(&config).unwrap();
+        assert_eq!(summary(&replayed), summary(&p));
+        assert_eq!(journal.stats().last_commit, 1);
+        book(&journal, &mut replayed, fill("E-2", 1, 62_000.0));
+        drop(journal);
+
+        let (journal, again) = reopen(&config).unwrap();
+        assert_eq!(summary(&again), summary(&replayed));
+        assert_eq!(journal.stats().last_commit, 2);
+        let _ = std::fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn a_corrupt_record_before_the_end_fails_the_open() {
+        let config = config("corrupt");
+        let (journal, mut p) = reopen(&config).unwrap();
+        book(&journal, &mut p, fill("E-1", 2, 60_000.0));
+        append(&config, b"{\"commit\":{\"seq\":2,\"events\":[{\"booked\"\n");
+        book(&journal, &mut p, fill("E-2", 1, 62_000.0));
+        drop(journal);
+
+        let error = reopen(&config).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("corrupt outbox journal"), "{}", error);
+        let _ = std::fs::remove_file(&config.path);
+    }
+}
@@ -13,155 +13,1534 @@
  * 2. Subscribe to market data to get real-time prices for P&L calculation.
  * 3. Maintain a state of all positions (e.g., quantity, average entry price).
  * 4. Calculate and expose Realized and Unrealized P&L via an API.
+ * 5. Generate flattening orders on operator request (POST /portfolio/flatten).
+ * 6. Close out the P&L of each trading day (GET /portfolio/pnl/daily), used by
+ * the VaR calculator to backtest its forecasts. QA_PNL_PERIOD_SECS shortens
+ * the "day" for simulations.
+ * 7. Aggregate exposure per counterparty, including unsettled trades on
+ * non-DVP venues (GET /portfolio/counterparties), for the risk gateway's
+ * counterparty credit limits.
+ * 8. Keep a cash ledger per currency: each fill debits or credits its
+ * notional plus fees, settling T+N by asset class (GET /cash shows
+ * available vs pending balances).
+ * 9. Charge venue fees on every fill: the fee the venue reported on the
+ * execution report, or else one priced from the `quantumarb-fees` schedules
+ * (maker/taker tiers, per-contract and regulatory fees); the snapshot reports
+ * total fees and P&L net of them. Cash and settlement run from the venue's
+ * transact time when the report carries one.
+ * 10. Apply corporate actions (splits, cash dividends, symbol changes) to
+ * positions on their ex-date (GET /portfolio/corporate-actions lists what
+ * was applied).
+ * 11. Monitor intraday margin every minute, projecting usage under a +-2 sigma
+ * adverse move; alerts on 'alerts.margin' and, if enabled, instructs the
+ * strategy engine to reduce positions (GET /portfolio/margin).
+ * 12. Scale P&L, market value and exposures by each instrument's contract
+ * multiplier from the reference data service (50 per point for ESZ25).
+ * 13. Evaluate trades before they are submitted (POST /portfolio/what-if):
+ * hypothetical fills or target positions are booked on a copy of the book,
+ * and the response compares exposure, margin usage and concentration before
+ * and after, with the VaR calculator's incremental VaR (see what_if.rs).
+ * 14. Keep a virtual sub-portfolio per strategy, booked from the strategy_id
+ * tag on fills, next to the firm-level book (GET /portfolio/strategy/{id});
+ * GET /portfolio/strategies shows where strategies hold opposite positions
+ * that net out at the firm level (see strategies.rs).
+ * 15. Keep each account's session P&L, booked from the account_id tag on
+ * fills, for the risk gateway's daily loss limits: served on GET
+ * /portfolio/accounts and published on 'portfolio.account_pnl' every
+ * ACCOUNT_PNL_INTERVAL_SECS (see accounts.rs).
+ * 16. Monitor unhedged FX exposure from foreign-denominated positions and
+ * cash every minute, alerting on 'alerts.fx' when a currency goes over its
+ * limit, and, if enabled, convert foreign cash to base in the cash ledger at
+ * scheduled fixes (GET /portfolio/fx, see fx.rs).
+ * 17. Stream position and P&L changes to dashboards over a WebSocket (GET
+ * /portfolio/stream): each fill and mark pushes only the fields that changed,
+ * for the symbols each client subscribed to (see stream.rs).
+ * 18. Reverse fills the venue busts and rebook the ones it corrects, through
+ * positions, cash, fees and the sub-portfolios, keeping an audit trail of
+ * original vs corrected fills (GET /portfolio/corrections, see
+ * corrections.rs).
+ * 19. Allocate the fills of parent accounts across their sub-accounts at the
+ * average price, by ratios or per-order instructions, into the accounts'
+ * positions and margin, reporting each allocation (GET
+ * /portfolio/allocations, see allocation.rs).
+ * 20. Catch up on the fills published while it was down before booking live
+ * ones: the order flow since its last acknowledged fill is read from the
+ * archive (or resumed from the Kafka consumer group's offsets) and booked
+ * first, with the live fills that arrive meanwhile held back until it is
+ * done (GET /portfolio/catch-up, see catch_up.rs).
+ *
+ * Fills are booked with the position arithmetic in pnl.rs: partial closes
+ * realize against the position's cost basis, and a fill through zero closes
+ * the old side before opening the new one at the fill price. Its invariants
+ * are checked by property tests over random fill sequences
+ * (`cargo test -p portfolio-manager`).
+ *
+ * Prices, P&L, fees and cash are `quantumarb-money` fixed-point amounts;
+ * they serialize as plain numbers, so the API bodies are unchanged.
+ *
+ * Fills and market data pass through bounded `quantumarb-queues` (GET
+ * /portfolio/queues): fills block the bus subscription when full and prices
+ * drop the oldest.
+ *
+ * The book is event-sourced through a transactional outbox (see journal.rs):
+ * every booked fill is journaled before it is applied, and margin alerts,
+ * reduce instructions and flattening orders are committed to the same
+ * journal and published from it with dedup keys. On start the journal is
+ * replayed, so a restart neither loses a fill nor books one twice, and
+ * messages committed before a crash are published once (GET
+ * /portfolio/outbox). Booking is idempotent on the execution report's
+ * (exchange order id, ExecID): the book keeps the keys of the fills it
+ * holds, with its state and snapshots, and a redelivered report counts once
+ * in whatever order reports arrive (see fills.rs).
+ *
+ * With QA_PM_FEEDS=http the simulated fill and market data subscriptions are
+ * not started; fills and prices are instead posted to POST /portfolio/fills
+ * and POST /portfolio/prices (used by the integration harness in
+ * tests/integration). Both endpoints answer 404 otherwise. The simulated
+ * market data is drawn from a seeded stream with --seed N (or QA_SEED).
+ *
+ * With desks configured (QA_DESKS_PATH, see `quantumarb-tenancy`), a desk's
+ * token reads only its own strategies (GET /portfolio/strategies and
+ * /portfolio/strategy/{id}) and accounts (GET /portfolio/accounts); the rest
+ * of the API is the firm's book and answers firm tokens only. Account P&L is
+ * published on the account's desk topic ('desk.<desk>.portfolio.account_pnl').
+ *
+ * GET /portfolio/state exports the book (positions, P&L, fees, cash ledger,
+ * unsettled trades) for the risk gateway's platform snapshots; PUT restores
+ * it, replacing the live book.
+ *
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
  */
 
-use serde::Serialize;
-use std::collections::HashMap;
+mod accounts;
+mod allocation;
+mod cash;
+mod catch_up;
+mod corporate_actions;
+mod corrections;
+mod counterparty;
+mod fills;
+mod fx;
+mod journal;
+mod margin;
+mod pnl;
+mod strategies;
+mod stream;
+mod what_if;
+
+use accounts::AccountBook;
+use allocation::{AllocationBook, AllocationInstruction, AllocationReport, AllocationScheme, Block};
+use cash::CashLedger;
+use catch_up::{CatchUpStatus, FillCheckpoint, SharedCatchUpStatus, SharedCheckpoint};
+use chrono::{DateTime, Datelike, Utc};
+use corporate_actions::CorporateActionBook;
+use corrections::{Amendment, AmendmentKind, BookedTrade, CorrectionRecord, TradeLog};
+use counterparty::UnsettledTrade;
+use fills::BookedFills;
+use journal::{BookedFill, Journal, JournalConfig};
+use fx::{FxConfig, FxConversion, FxReport};
+use margin::{MarginConfig, MarginLevel, MarginReport};
+use pnl::PositionLot;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_fees::{FeeEngine, Liquidity};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_bus::replay::ReplayConfig;
+use quantumarb_outbox::{Message, OutboxStats};
+use quantumarb_queues::{QueueStats, Receiver, Sender};
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_risk::concentration::Exposures;
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_tenancy::{Desks, Scope};
+use quantumarb_types::{AccountPnl, CounterpartyExposure, DailyPnl, PortfolioSnapshot, Position, StrategyInstruction};
+use quantumarb_wire::{utc_now_ns, ExecutionReport, HopStamps, OrderSide, OrderStatus, TradingMode};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
+use strategies::StrategyBook;
+use stream::PnlStream;
 use tokio::time::{self, Duration};
+use uuid::Uuid;
 use warp::Filter;
+use what_if::{Comparison, IncrementalVaR, VaRChange, WhatIfReport, WhatIfRequest};
 
 // --- Data Structures ---
 
-#[derive(Debug, Clone, Serialize)]
-struct Position {
-    symbol: String,
-    quantity: i64,
-    average_entry_price: f64,
-    current_market_price: f64,
-    unrealized_pnl: f64,
-}
-
-#[derive(Debug, Clone, Serialize)]
-struct PortfolioSnapshot {
+/// The live book. GET /portfolio serves its `PortfolioSnapshot`.
+#[derive(Debug, Clone)]
+struct Portfolio {
     positions: HashMap<String, Position>,
-    realized_pnl: f64,
-    total_unrealized_pnl: f64,
-    total_portfolio_value: f64,
+    realized_pnl: Money,
+    total_unrealized_pnl: Money,
+    total_portfolio_value: Money,
+    total_fees: Money,
+    /// Realized + unrealized P&L, less fees.
+    net_pnl: Money,
     timestamp_utc: String,
+    /// Non-DVP fills awaiting settlement (reported via /portfolio/counterparties).
+    unsettled_trades: Vec<UnsettledTrade>,
+    /// Cash per currency (reported via /cash).
+    cash: CashLedger,
+    /// Instrument definitions, for contract multipliers.
+    instruments: ReferenceData,
+    /// Per-strategy sub-portfolios (reported via /portfolio/strategies).
+    strategies: StrategyBook,
+    /// Per-account session P&L (reported via /portfolio/accounts).
+    accounts: AccountBook,
+    /// Keys of the fills booked, so none is booked twice (see fills.rs).
+    booked_fills: BookedFills,
+    /// Recent fills and the busts and corrections applied (see corrections.rs).
+    trade_log: TradeLog,
+    /// Parent accounts' schemes, from QA_ALLOCATION_SCHEMES (see allocation.rs).
+    allocation_schemes: BTreeMap<u32, AllocationScheme>,
+    /// Parent accounts' fills awaiting allocation, and the allocations made.
+    allocations: AllocationBook,
+}
+
+impl Portfolio {
+    /// An empty book holding the opening cash.
+    fn new(now: DateTime<Utc>) -> Portfolio {
+        Portfolio {
+            positions: HashMap::new(),
+            realized_pnl: Money::ZERO,
+            total_unrealized_pnl: Money::ZERO,
+            total_portfolio_value: Money::ZERO,
+            total_fees: Money::ZERO,
+            net_pnl: Money::ZERO,
+            timestamp_utc: now.to_rfc3339(),
+            unsettled_trades: Vec::new(),
+            cash: CashLedger::with_opening_balance("USD", OPENING_CASH_USD),
+            instruments: ReferenceData::seeded(),
+            strategies: StrategyBook::default(),
+            accounts: AccountBook::new(now),
+            booked_fills: BookedFills::default(),
+            trade_log: TradeLog::default(),
+            allocation_schemes: allocation::schemes_from_env(),
+            allocations: AllocationBook::default(),
+        }
+    }
+
+    fn snapshot(&self) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            positions: self.positions.clone(),
+            realized_pnl: self.realized_pnl,
+            total_unrealized_pnl: self.total_unrealized_pnl,
+            total_portfolio_value: self.total_portfolio_value,
+            total_fees: self.total_fees,
+            net_pnl: self.net_pnl,
+            timestamp_utc: self.timestamp_utc.clone(),
+        }
+    }
+
+    /// The restorable part of the book.
+    fn state(&self) -> PortfolioState {
+        PortfolioState {
+            positions: self.positions.clone(),
+            realized_pnl: self.realized_pnl,
+            total_fees: self.total_fees,
+            unsettled_trades: self.unsettled_trades.clone(),
+            cash: self.cash.clone(),
+            strategies: self.strategies.clone(),
+            accounts: self.accounts.clone(),
+            booked_fills: self.booked_fills.clone(),
+            trade_log: self.trade_log.clone(),
+            allocations: self.allocations.clone(),
+            captured_utc: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Replaces the book with a restored one. Unrealized P&L follows on the
+    /// next mark to market.
+    fn restore(&mut self, book: PortfolioState) {
+        self.positions = book.positions;
+        for position in self.positions.values_mut() {
+            // Books saved before the cost basis was kept: rebuild it from the average.
+            if position.cost_basis.is_zero() && position.quantity != 0 {
+                position.cost_basis = position.average_entry_price * Quantity(position.quantity);
+            }
+        }
+        self.realized_pnl = book.realized_pnl;
+        self.total_fees = book.total_fees;
+        self.unsettled_trades = book.unsettled_trades;
+        self.cash = book.cash;
+        self.strategies = book.strategies;
+        self.accounts = book.accounts;
+        self.booked_fills = book.booked_fills;
+        self.trade_log = book.trade_log;
+        self.allocations = book.allocations;
+        refresh_totals(self);
+    }
 }
 
 // Represents a fill from an execution report
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
 struct Fill {
     symbol: String,
     quantity: i64, // Positive for buy, negative for sell
-    price: f64,
+    price: Price,
+    venue: String,
+    counterparty: String,
+    liquidity: Liquidity,
+    /// The fee the venue charged; priced from the fee schedules when absent.
+    #[serde(default)]
+    fee: Option<Money>,
+    /// When the venue executed the fill; the fill's arrival when absent.
+    #[serde(default)]
+    transact_time_utc: Option<DateTime<Utc>>,
+    /// The strategy whose order filled, for its sub-portfolio.
+    #[serde(default)]
+    strategy_id: Option<String>,
+    /// The report's "{exchange order id}:{ExecID}", so a redelivered fill is
+    /// booked once.
+    #[serde(default)]
+    exec_id: Option<String>,
+    /// The account whose order filled, for its daily P&L.
+    #[serde(default)]
+    account_id: Option<u32>,
+    /// The fill this one busts or corrects (see corrections.rs).
+    #[serde(default)]
+    amends: Option<Amendment>,
+    /// The order filled, whose fills a parent account allocates as a block.
+    #[serde(default)]
+    order_id: Option<Uuid>,
+    /// The order's last fill, which allocates its block (see allocation.rs).
+    #[serde(default)]
+    last_fill: bool,
+}
+
+impl Fill {
+    /// The fill an execution report describes, for an order in `definition`'s
+    /// instrument. Reports carry only the order id, so the side, venue,
+    /// strategy and account come from the order. A bust or correction report
+    /// gives a fill that amends the one it names. None if the report fills
+    /// nothing.
+    fn from_report(
+        report: &ExecutionReport,
+        definition: &InstrumentDefinition,
+        side: OrderSide,
+        venue: &str,
+        strategy_id: &str,
+        account_id: u32,
+    ) -> Option<Fill> {
+        let amends = match report.status {
+            OrderStatus::TradeBusted => Some(AmendmentKind::Bust),
+            OrderStatus::TradeCorrected => Some(AmendmentKind::Correction),
+            _ => None,
+        };
+        if report.filled_size == 0 && amends.is_none() {
+            return None;
+        }
+        let size = report.filled_size as i64;
+        Some(Fill {
+            symbol: definition.symbol.clone(),
+            quantity: match side {
+                OrderSide::Buy => size,
+                OrderSide::Sell => -size,
+            },
+            price: definition.price(report.filled_price),
+            venue: venue.to_string(),
+            counterparty: venue.to_string(),
+            liquidity: report.liquidity.unwrap_or(Liquidity::Taker),
+            // Reports from before the fill detail carry neither a liquidity
+            // indicator nor a fee; those are priced from the schedules.
+            fee: report.liquidity.map(|_| report.fee),
+            transact_time_utc: (report.transact_time_ns > 0)
+                .then(|| DateTime::from_timestamp_nanos(report.transact_time_ns as i64)),
+            strategy_id: Some(strategy_id.to_string()),
+            exec_id: (!report.exec_id.is_empty()).then(|| format!("{}:{}", report.exchange_order_id, report.exec_id)),
+            account_id: Some(account_id),
+            amends: amends.map(|kind| Amendment {
+                original: format!("{}:{}", report.exchange_order_id, report.exec_ref_id),
+                kind,
+            }),
+            order_id: Some(report.internal_order_id),
+            last_fill: report.status == OrderStatus::Filled,
+        })
+    }
+}
+
+/// The restorable part of the book, for GET/PUT /portfolio/state.
+#[derive(Debug, Serialize, Deserialize, ApiSchema)]
+struct PortfolioState {
+    positions: HashMap<String, Position>,
+    realized_pnl: Money,
+    total_fees: Money,
+    unsettled_trades: Vec<UnsettledTrade>,
+    cash: CashLedger,
+    /// Absent from states saved before the sub-portfolios were kept.
+    #[serde(default)]
+    strategies: StrategyBook,
+    #[serde(default)]
+    accounts: AccountBook,
+    /// Keys of the fills in the book; absent from older states.
+    #[serde(default)]
+    booked_fills: BookedFills,
+    #[serde(default)]
+    trade_log: TradeLog,
+    #[serde(default)]
+    allocations: AllocationBook,
+    captured_utc: String,
+}
+
+/// A market price for marking positions.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct PriceUpdate {
+    symbol: String,
+    price: Price,
 }
 
-type SharedPortfolio = Arc<Mutex<PortfolioSnapshot>>;
+/// Producer ends of the event queues, kept for their counters.
+#[derive(Clone)]
+struct Queues {
+    fills: Sender<Fill>,
+    prices: Sender<PriceUpdate>,
+}
+
+/// Body of a POST /portfolio/flatten request. No symbol flattens everything.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct FlattenRequest {
+    symbol: Option<String>,
+}
+
+/// An order that takes a position back to zero.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct FlattenOrder {
+    symbol: String,
+    side: String, // "Buy" or "Sell"
+    quantity: u64,
+    reference_price: Price,
+}
+
+type SharedPortfolio = Arc<Mutex<Portfolio>>;
+type SharedDailyPnl = Arc<Mutex<Vec<DailyPnl>>>;
+type SharedCorporateActions = Arc<Mutex<CorporateActionBook>>;
+type SharedMargin = Arc<Mutex<Option<MarginReport>>>;
+type SharedFx = Arc<Mutex<Option<FxReport>>>;
+
+const OPENING_CASH_USD: Money = Money::from_micros(1_000_000_000_000);
+const INSTRUMENTS_URL: &str = "http://reference-data-service.default.svc.cluster.local/instruments";
+const INCREMENTAL_VAR_URL: &str = "http://var-calculator.default.svc.cluster.local/var/incremental";
+const DEFAULT_PNL_PERIOD_SECS: u64 = 86400;
+/// Roughly a year of trading days, enough for a Basel backtest window.
+const DAILY_PNL_RETENTION: usize = 500;
+const FILL_QUEUE_CAPACITY: usize = 10_000;
+const PRICE_QUEUE_CAPACITY: usize = 10_000;
+/// Where POST /portfolio/flatten publishes its closing orders.
+const FLATTEN_TOPIC: &str = "orders.flatten";
+const MARGIN_ALERT_TOPIC: &str = "alerts.margin";
+const FX_ALERT_TOPIC: &str = "alerts.fx";
+/// FX conversions kept for GET /portfolio/fx.
+const RECENT_FX_CONVERSIONS: usize = 100;
+/// Strategy the simulated fills are tagged with.
+const SIM_STRATEGY: &str = "sor_arbitrage";
+/// Account the simulated fills are booked to.
+const SIM_ACCOUNT: u32 = 101;
+const ACCOUNT_PNL_INTERVAL_SECS: u64 = 2;
 
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Portfolio Manager ---");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+
+    // Initialize the portfolio state and replay the journal into it
+    let mut book = Portfolio::new(chrono::Utc::now());
+    let journal = Journal::open(JournalConfig::from_env(), &mut book).expect("Failed to open the portfolio journal");
+    let portfolio = Arc::new(Mutex::new(book));
 
-    // Initialize the shared portfolio state
-    let portfolio = Arc::new(Mutex::new(PortfolioSnapshot {
-        positions: HashMap::new(),
-        realized_pnl: 0.0,
-        total_unrealized_pnl: 0.0,
-        total_portfolio_value: 0.0,
-        timestamp_utc: chrono::Utc::now().to_rfc3339(),
-    }));
+    // Event queues between the bus subscriptions and the portfolio
+    let (fills, fill_queue) = quantumarb_queues::blocking("portfolio_fills", FILL_QUEUE_CAPACITY);
+    let (prices, price_queue) = quantumarb_queues::drop_oldest("portfolio_prices", PRICE_QUEUE_CAPACITY);
+    let queues = Queues { fills, prices };
+
+    // The fill checkpoint the bus subscription resumes from
+    let replay = ReplayConfig::from_env();
+    let checkpoint: SharedCheckpoint =
+        Arc::new(Mutex::new(FillCheckpoint::open(&replay).expect("Failed to open the fill checkpoint")));
+    let catch_up_status: SharedCatchUpStatus = Arc::new(Mutex::new(catch_up::initial_status(&replay)));
 
     // Spawn background tasks
+    let http_feeds = std::env::var("QA_PM_FEEDS").as_deref() == Ok("http");
+    if http_feeds {
+        println!("Fills and prices are taken from POST /portfolio/fills and /portfolio/prices.");
+    } else {
+        let (live_fills, live_fill_queue) = quantumarb_queues::blocking("portfolio_live_fills", FILL_QUEUE_CAPACITY);
+        let (fill_sender, price_sender) = (queues.fills.clone(), queues.prices.clone());
+        let rng = seed.stream("portfolio_manager.market_data");
+        let (checkpoint_clone_1, status_clone, portfolio_clone_10) =
+            (checkpoint.clone(), catch_up_status.clone(), portfolio.clone());
+        tokio::spawn(async move {
+            let history = catch_up::ArchiveHistory { portfolio: portfolio_clone_10 };
+            catch_up::run(replay, checkpoint_clone_1, status_clone, history, live_fill_queue, fill_sender).await;
+        });
+        tokio::spawn(async move {
+            listen_for_fills(live_fills).await;
+        });
+        tokio::spawn(async move {
+            listen_for_market_data(price_sender, rng).await;
+        });
+    }
+    let pnl_stream = PnlStream::new();
     let portfolio_clone_1 = portfolio.clone();
+    let journal_clone_1 = journal.clone();
+    let stream_clone_1 = pnl_stream.clone();
+    let checkpoint_clone_2 = checkpoint.clone();
     tokio::spawn(async move {
-        listen_for_fills(portfolio_clone_1).await;
+        apply_fills(fill_queue, portfolio_clone_1, journal_clone_1, stream_clone_1, checkpoint_clone_2).await;
     });
 
     let portfolio_clone_2 = portfolio.clone();
+    let stream_clone_2 = pnl_stream.clone();
+    tokio::spawn(async move {
+        mark_to_market(price_queue, portfolio_clone_2, stream_clone_2).await;
+    });
+
+    let portfolio_clone_7 = portfolio.clone();
+    let journal_clone_2 = journal.clone();
+    tokio::spawn(async move {
+        publish_from_outbox(journal_clone_2, portfolio_clone_7).await;
+    });
+
+    let daily_pnl: SharedDailyPnl = Arc::new(Mutex::new(Vec::new()));
+    let portfolio_clone_3 = portfolio.clone();
+    let daily_pnl_clone = daily_pnl.clone();
+    let journal_clone_6 = journal.clone();
+    tokio::spawn(async move {
+        close_daily_pnl(portfolio_clone_3, daily_pnl_clone, journal_clone_6).await;
+    });
+
+    let (portfolio_clone_8, desks_clone) = (portfolio.clone(), desks.clone());
+    tokio::spawn(async move {
+        publish_account_pnl(portfolio_clone_8, desks_clone).await;
+    });
+
+    let corporate_actions: SharedCorporateActions = Arc::new(Mutex::new(CorporateActionBook::default()));
+    let portfolio_clone_4 = portfolio.clone();
+    let corporate_actions_clone = corporate_actions.clone();
+    let journal_clone_3 = journal.clone();
+    let stream_clone_3 = pnl_stream.clone();
+    tokio::spawn(async move {
+        process_corporate_actions(portfolio_clone_4, corporate_actions_clone, journal_clone_3, stream_clone_3).await;
+    });
+
+    let portfolio_clone_6 = portfolio.clone();
+    let journal_clone_4 = journal.clone();
+    tokio::spawn(async move {
+        follow_reference_data(portfolio_clone_6, journal_clone_4).await;
+    });
+
+    let margin: SharedMargin = Arc::new(Mutex::new(None));
+    let portfolio_clone_5 = portfolio.clone();
+    let margin_clone = margin.clone();
+    let journal_clone_5 = journal.clone();
+    tokio::spawn(async move {
+        monitor_margin(portfolio_clone_5, margin_clone, journal_clone_5).await;
+    });
+
+    let fx: SharedFx = Arc::new(Mutex::new(None));
+    let portfolio_clone_9 = portfolio.clone();
+    let fx_clone = fx.clone();
+    let journal_clone_7 = journal.clone();
     tokio::spawn(async move {
-        mark_to_market(portfolio_clone_2).await;
+        monitor_fx(portfolio_clone_9, fx_clone, journal_clone_7).await;
     });
 
+    // A desk reaches its own strategies and accounts; the rest is the firm's book.
+    let firm = quantumarb_tenancy::firm_only(desks.clone());
+    let scope = quantumarb_tenancy::scope(desks.clone());
+
     // --- API Endpoint to get the latest portfolio snapshot ---
-    let get_portfolio = warp::path("portfolio")
+    let get_portfolio = warp::path!("portfolio")
+        .and(firm.clone())
         .and(warp::get())
-        .and(with_state(portfolio))
+        .and(with_state(portfolio.clone()))
         .and_then(handler_get_portfolio);
-    
+
+    let flatten = warp::path!("portfolio" / "flatten")
+        .and(firm.clone())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(portfolio.clone()))
+        .and(with_state(journal.clone()))
+        .and_then(handler_flatten);
+
+    let get_state = warp::path!("portfolio" / "state")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_state);
+
+    let put_state = warp::path!("portfolio" / "state")
+        .and(firm.clone())
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_state(portfolio.clone()))
+        .and(with_state(journal.clone()))
+        .and(with_state(pnl_stream.clone()))
+        .and_then(handler_put_state);
+
+    let stream_pnl = warp::path!("portfolio" / "stream")
+        .and(firm.clone())
+        .and(warp::ws())
+        .and(with_state(pnl_stream))
+        .and(with_state(portfolio.clone()))
+        .map(|ws: warp::ws::Ws, pnl_stream: PnlStream, portfolio: SharedPortfolio| {
+            ws.on_upgrade(move |socket| stream::serve(socket, pnl_stream, portfolio))
+        });
+
+    let what_if = warp::path!("portfolio" / "what-if")
+        .and(firm.clone())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_what_if);
+
+    let get_strategies = warp::path!("portfolio" / "strategies")
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_strategies);
+
+    let get_strategy = warp::path!("portfolio" / "strategy" / String)
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_strategy);
+
+    let get_accounts = warp::path!("portfolio" / "accounts")
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_accounts);
+
+    let get_counterparties = warp::path!("portfolio" / "counterparties")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_counterparties);
+
+    let get_cash = warp::path!("cash")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_cash);
+
+    let get_daily_pnl = warp::path!("portfolio" / "pnl" / "daily")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(daily_pnl))
+        .and_then(handler_get_daily_pnl);
+
+    let get_corporate_actions = warp::path!("portfolio" / "corporate-actions")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(corporate_actions))
+        .and_then(handler_get_corporate_actions);
+
+    let get_corrections = warp::path!("portfolio" / "corrections")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_corrections);
+
+    let get_allocations = warp::path!("portfolio" / "allocations")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_allocations);
+
+    let get_open_allocations = warp::path!("portfolio" / "allocations" / "open")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(portfolio.clone()))
+        .and_then(handler_get_open_allocations);
+
+    let post_allocation_instruction = warp::path!("portfolio" / "allocations" / "instructions")
+        .and(firm.clone())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(portfolio))
+        .and(with_state(journal.clone()))
+        .and_then(handler_post_allocation_instruction);
+
+    let get_margin = warp::path!("portfolio" / "margin")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(margin))
+        .and_then(handler_get_margin);
+
+    let get_fx = warp::path!("portfolio" / "fx")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(fx))
+        .and_then(handler_get_fx);
+
+    let post_fill = warp::path!("portfolio" / "fills")
+        .and(firm.clone())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(queues.clone()))
+        .and(with_state(http_feeds))
+        .and_then(handler_post_fill);
+
+    let post_price = warp::path!("portfolio" / "prices")
+        .and(firm.clone())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(queues.clone()))
+        .and(with_state(http_feeds))
+        .and_then(handler_post_price);
+
+    let get_queues = warp::path!("portfolio" / "queues")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(queues))
+        .and_then(handler_get_queues);
+
+    let get_outbox = warp::path!("portfolio" / "outbox")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(journal))
+        .and_then(handler_get_outbox);
+
+    let get_catch_up = warp::path!("portfolio" / "catch-up")
+        .and(firm.clone())
+        .and(warp::get())
+        .and(with_state(catch_up_status))
+        .and_then(handler_get_catch_up);
+
     println!("API server running at http://127.0.0.1:3032/portfolio");
-    warp::serve(get_portfolio).run(([127, 0, 0, 1], 3032)).await;
+    warp::serve(get_portfolio.or(flatten).or(get_daily_pnl).or(get_counterparties).or(get_cash).or(get_corporate_actions).or(get_corrections).or(get_allocations).or(get_open_allocations).or(post_allocation_instruction).or(get_margin).or(get_fx).or(stream_pnl).or(get_queues).or(get_outbox).or(get_catch_up).or(get_state).or(put_state).or(what_if).or(get_strategies).or(get_strategy).or(get_accounts).or(post_fill).or(post_price).or(quantumarb_openapi::routes(api_doc())).recover(quantumarb_tenancy::recover)).run(([127, 0, 0, 1], 3032)).await;
+}
+
+/// The portfolio API's OpenAPI document.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Portfolio Manager", "Positions, P&L, cash, margin and exposures of the firm's book.")
+        .errors::<ErrorBody>()
+        .get::<PortfolioSnapshot>("/portfolio", "The latest portfolio snapshot")
+        .post::<FlattenRequest, Vec<FlattenOrder>>("/portfolio/flatten", "Publish closing orders for open positions")
+        .get::<PortfolioState>("/portfolio/state", "Export the book")
+        .put::<PortfolioState, PortfolioSnapshot>("/portfolio/state", "Restore the book, replacing the live one")
+        .operation(Operation::get("/portfolio/stream", "WebSocket of position and P&L deltas").status(101))
+        .post::<WhatIfRequest, WhatIfReport>("/portfolio/what-if", "Evaluate trades before they are submitted")
+        .get::<strategies::StrategiesReport>("/portfolio/strategies", "Strategies and where they offset")
+        .get::<strategies::SubPortfolio>("/portfolio/strategy/{id}", "One strategy's sub-portfolio")
+        .get::<Vec<AccountPnl>>("/portfolio/accounts", "P&L per account")
+        .get::<Vec<CounterpartyExposure>>("/portfolio/counterparties", "Unsettled exposure per counterparty")
+        .get::<cash::CashReport>("/cash", "Cash balances and pending settlements")
+        .get::<Vec<DailyPnl>>("/portfolio/pnl/daily", "Closed daily P&L")
+        .get::<Vec<corporate_actions::AppliedCorporateAction>>("/portfolio/corporate-actions", "Applied actions")
+        .get::<Vec<CorrectionRecord>>("/portfolio/corrections", "Busts and corrections, original vs corrected")
+        .get::<Vec<AllocationReport>>("/portfolio/allocations", "Allocations of parent accounts' fills")
+        .get::<Vec<Block>>("/portfolio/allocations/open", "Blocks awaiting allocation")
+        .operation(
+            Operation::post("/portfolio/allocations/instructions", "Allocate an order's fills by quantity")
+                .body::<AllocationInstruction>()
+                .status(204),
+        )
+        .get::<Option<MarginReport>>("/portfolio/margin", "The latest margin evaluation")
+        .get::<Option<FxReport>>("/portfolio/fx", "FX exposure and recent conversions")
+        .operation(Operation::post("/portfolio/fills", "Queue a fill (HTTP feeds)").body::<Fill>().status(202))
+        .operation(Operation::post("/portfolio/prices", "Queue a price (HTTP feeds)").body::<PriceUpdate>().status(202))
+        .get::<Vec<QueueStats>>("/portfolio/queues", "Fill and price queue statistics")
+        .get::<OutboxStats>("/portfolio/outbox", "Outbox statistics")
+        .get::<CatchUpStatus>("/portfolio/catch-up", "Progress catching up on fills missed while down")
 }
 
 /// Warp filter to inject state into the handler.
-fn with_state(
-    state: SharedPortfolio,
-) -> impl Filter<Extract = (SharedPortfolio,), Error = std::convert::Infallible> + Clone {
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || state.clone())
 }
 
 /// Handler for the /portfolio API endpoint.
 async fn handler_get_portfolio(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
-    let portfolio_snapshot = state.lock().unwrap().clone();
-    Ok(warp::reply::json(&portfolio_snapshot))
+    let snapshot = state.lock().unwrap().snapshot();
+    Ok(warp::reply::json(&snapshot))
 }
 
-/// Simulates listening for execution reports (fills) from the message bus.
-async fn listen_for_fills(portfolio: SharedPortfolio) {
-    let mut interval = time::interval(Duration::from_secs(5));
+/// Handler for the /portfolio/flatten API endpoint. Builds one closing order
+/// per open position and publishes them, through the outbox, for the exchange
+/// gateway to execute.
+async fn handler_flatten(
+    request: FlattenRequest,
+    state: SharedPortfolio,
+    journal: Journal,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let p = state.lock().unwrap();
+    let orders: Vec<FlattenOrder> = p
+        .positions
+        .values()
+        .filter(|pos| pos.quantity != 0)
+        .filter(|pos| request.symbol.as_ref().is_none_or(|s| *s == pos.symbol))
+        .map(|pos| FlattenOrder {
+            symbol: pos.symbol.clone(),
+            side: if pos.quantity > 0 { "Sell" } else { "Buy" }.to_string(),
+            quantity: pos.quantity.unsigned_abs(),
+            reference_price: pos.current_market_price,
+        })
+        .collect();
+
+    println!("\nFlatten requested: {} closing order(s).", orders.len());
+    let request_id = Uuid::new_v4();
+    let messages = orders
+        .iter()
+        .map(|order| Message::json(FLATTEN_TOPIC, format!("flatten:{}:{}", request_id, order.symbol), order))
+        .collect();
+    journal.publish(messages);
+    Ok(warp::reply::json(&orders))
+}
+
+/// Handler for GET /portfolio/state: the book, for platform snapshots.
+async fn handler_get_state(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let book = state.lock().unwrap().state();
+    Ok(warp::reply::json(&book))
+}
+
+/// Handler for PUT /portfolio/state: replaces the book with a restored one.
+/// Unrealized P&L follows on the next mark to market.
+async fn handler_put_state(
+    book: PortfolioState,
+    state: SharedPortfolio,
+    journal: Journal,
+    pnl_stream: PnlStream,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut p = state.lock().unwrap();
+    println!("\nRestoring portfolio state captured at {} ({} positions).", book.captured_utc, book.positions.len());
+    p.restore(book);
+    journal.changed(&p);
+    pnl_stream.publish_all(&p);
+    Ok(warp::reply::json(&p.snapshot()))
+}
+
+/// Handler for POST /portfolio/what-if: books the hypothetical fills or
+/// targets on a copy of the book and compares it with the live one.
+async fn handler_what_if(request: WhatIfRequest, state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let now = chrono::Utc::now();
+    let mut before = state.lock().unwrap().clone();
+    before.cash.settle_due(now);
+    let fills = match what_if::resolve(&request, &before.positions) {
+        Ok(fills) => fills,
+        Err(message) => {
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+            return Ok(warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::BAD_REQUEST));
+        }
+    };
+
+    let mut after = before.clone();
+    let mut realized_pnl = Money::ZERO;
+    for fill in &fills {
+        let fill = Fill {
+            symbol: fill.symbol.clone(),
+            quantity: fill.quantity,
+            price: fill.price,
+            venue: fill.venue.clone(),
+            counterparty: fill.venue.clone(),
+            liquidity: Liquidity::Taker,
+            fee: Some(Money::ZERO),
+            transact_time_utc: Some(now),
+            strategy_id: None,
+            exec_id: None,
+            account_id: None,
+            amends: None,
+            order_id: None,
+            last_fill: false,
+        };
+        if let Some((realized, _)) = book_fill(&mut after, fill, Money::ZERO, now, now) {
+            realized_pnl += realized;
+        }
+    }
+
+    let config = MarginConfig::from_env();
+    let concentration = |p: &Portfolio| {
+        let snapshot = PortfolioSnapshot { positions: p.positions.clone(), ..Default::default() };
+        Exposures::from_snapshot(&snapshot).metrics()
+    };
+    let changes = what_if::var_changes(&fills, &after.positions);
+    let var = if changes.is_empty() { None } else { fetch_incremental_var(&changes).await };
+    let report = WhatIfReport {
+        realized_pnl,
+        exposure: Comparison {
+            before: what_if::exposure(&before.positions),
+            after: what_if::exposure(&after.positions),
+        },
+        margin: Comparison {
+            before: margin::evaluate(&config, &before.positions, &before.cash.report()),
+            after: margin::evaluate(&config, &after.positions, &after.cash.report()),
+        },
+        concentration: Comparison { before: concentration(&before), after: concentration(&after) },
+        var,
+        fills,
+        timestamp_utc: now.to_rfc3339(),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&report), warp::http::StatusCode::OK))
+}
+
+/// Asks the VaR calculator for the incremental VaR of a change. None if it
+/// cannot be reached.
+async fn fetch_incremental_var(changes: &[VaRChange]) -> Option<IncrementalVaR> {
+    let body = serde_json::json!({ "changes": changes });
+    match reqwest::Client::new().post(INCREMENTAL_VAR_URL).json(&body).send().await {
+        Ok(response) => match response.json::<IncrementalVaR>().await {
+            Ok(var) => Some(var),
+            Err(_) => {
+                println!("  -> Error parsing the incremental VaR response.");
+                None
+            }
+        },
+        Err(_) => {
+            println!("  -> Failed to reach the VaR calculator for an incremental VaR.");
+            None
+        }
+    }
+}
+
+/// Handler for GET /portfolio/strategies: every strategy's totals and where
+/// their positions net out.
+async fn handler_get_strategies(
+    state: SharedPortfolio,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let report = state.lock().unwrap().strategies.report(|id| scope.sees_strategy(&desks, id));
+    Ok(warp::reply::json(&report))
+}
+
+/// Handler for GET /portfolio/strategy/{id}: one strategy's sub-portfolio;
+/// another desk's is not found.
+async fn handler_get_strategy(
+    strategy_id: String,
+    state: SharedPortfolio,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !scope.sees_strategy(&desks, &strategy_id) {
+        return Err(warp::reject::not_found());
+    }
+    match state.lock().unwrap().strategies.get(&strategy_id) {
+        Some(sub) => Ok(warp::reply::json(sub)),
+        None => Err(warp::reject::not_found()),
+    }
+}
+
+/// Handler for GET /portfolio/accounts: each account's session P&L.
+async fn handler_get_accounts(
+    state: SharedPortfolio,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut report = state.lock().unwrap().accounts.report();
+    report.retain(|account| scope.sees_account(&desks, account.account_id));
+    Ok(warp::reply::json(&report))
+}
+
+/// Handler for the /portfolio/counterparties API endpoint. Largest exposure first.
+async fn handler_get_counterparties(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut guard = state.lock().unwrap();
+    let p = &mut *guard;
+    let exposures: Vec<CounterpartyExposure> =
+        counterparty::aggregate(&p.positions, &mut p.unsettled_trades, chrono::Utc::now());
+    Ok(warp::reply::json(&exposures))
+}
+
+/// Handler for the /cash API endpoint.
+async fn handler_get_cash(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut p = state.lock().unwrap();
+    p.cash.settle_due(chrono::Utc::now());
+    Ok(warp::reply::json(&p.cash.report()))
+}
+
+/// Handler for the /portfolio/pnl/daily API endpoint. Oldest day first.
+async fn handler_get_daily_pnl(state: SharedDailyPnl) -> Result<impl warp::Reply, warp::Rejection> {
+    let history = state.lock().unwrap().clone();
+    Ok(warp::reply::json(&history))
+}
+
+/// Handler for GET /portfolio/corrections: the busts and corrections
+/// applied, newest first.
+async fn handler_get_corrections(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let corrections = state.lock().unwrap().trade_log.corrections();
+    Ok(warp::reply::json(&corrections))
+}
+
+/// Handler for GET /portfolio/allocations: allocations made, newest first.
+async fn handler_get_allocations(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let reports = state.lock().unwrap().allocations.reports();
+    Ok(warp::reply::json(&reports))
+}
+
+/// Handler for GET /portfolio/allocations/open: blocks awaiting allocation.
+async fn handler_get_open_allocations(state: SharedPortfolio) -> Result<impl warp::Reply, warp::Rejection> {
+    let blocks = state.lock().unwrap().allocations.open_blocks();
+    Ok(warp::reply::json(&blocks))
+}
+
+/// Handler for POST /portfolio/allocations/instructions: quantities to
+/// allocate an order's fills by, in place of its parent's ratios.
+async fn handler_post_allocation_instruction(
+    instruction: AllocationInstruction,
+    state: SharedPortfolio,
+    journal: Journal,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let mut p = state.lock().unwrap();
+    let order_id = instruction.order_id;
+    if let Err(message) = p.allocations.instruct(instruction) {
+        let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+        return Ok(Box::new(warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::CONFLICT)));
+    }
+    journal.changed(&p);
+    println!("\nAllocation instruction recorded for order {}.", order_id);
+    Ok(Box::new(warp::http::StatusCode::NO_CONTENT))
+}
+
+/// Handler for the /portfolio/corporate-actions API endpoint. Oldest first.
+async fn handler_get_corporate_actions(state: SharedCorporateActions) -> Result<impl warp::Reply, warp::Rejection> {
+    let applied = state.lock().unwrap().applied().to_vec();
+    Ok(warp::reply::json(&applied))
+}
+
+/// Handler for the /portfolio/margin API endpoint: the latest margin check.
+async fn handler_get_margin(state: SharedMargin) -> Result<impl warp::Reply, warp::Rejection> {
+    let report = state.lock().unwrap().clone();
+    Ok(warp::reply::json(&report))
+}
+
+/// Handler for the /portfolio/fx API endpoint: the latest FX exposure check.
+async fn handler_get_fx(state: SharedFx) -> Result<impl warp::Reply, warp::Rejection> {
+    let report = state.lock().unwrap().clone();
+    Ok(warp::reply::json(&report))
+}
+
+/// Handler for the /portfolio/queues API endpoint.
+async fn handler_get_queues(queues: Queues) -> Result<impl warp::Reply, warp::Rejection> {
+    let stats: Vec<QueueStats> = vec![queues.fills.stats(), queues.prices.stats()];
+    Ok(warp::reply::json(&stats))
+}
+
+/// Handler for GET /portfolio/outbox: the journal's publishing counters.
+async fn handler_get_outbox(journal: Journal) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&journal.stats()))
+}
+
+/// Handler for GET /portfolio/catch-up: progress booking the fills missed
+/// while the manager was down.
+async fn handler_get_catch_up(status: SharedCatchUpStatus) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = status.lock().unwrap().clone();
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for POST /portfolio/fills: queues a fill as if it had arrived on
+/// the bus. Answers 404 unless QA_PM_FEEDS=http.
+async fn handler_post_fill(fill: Fill, queues: Queues, enabled: bool) -> Result<impl warp::Reply, warp::Rejection> {
+    if !enabled {
+        return Err(warp::reject::not_found());
+    }
+    println!("\nReceived Fill: {} {} @ {:.2} on {}", fill.quantity, fill.symbol, fill.price, fill.venue);
+    match queues.fills.send(fill).await {
+        Ok(()) => Ok(warp::http::StatusCode::ACCEPTED),
+        Err(_) => Ok(warp::http::StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+/// Handler for POST /portfolio/prices: queues a price for marking positions.
+/// Answers 404 unless QA_PM_FEEDS=http.
+async fn handler_post_price(
+    update: PriceUpdate,
+    queues: Queues,
+    enabled: bool,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !enabled {
+        return Err(warp::reject::not_found());
+    }
+    match queues.prices.send(update).await {
+        Ok(()) => Ok(warp::http::StatusCode::ACCEPTED),
+        Err(_) => Ok(warp::http::StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+/// Re-evaluates margin usage every minute. Alerts are raised when the level
+/// changes; a margin call with auto-reduce enabled also instructs the strategy
+/// engine to cut positions.
+async fn monitor_margin(portfolio: SharedPortfolio, latest: SharedMargin, journal: Journal) {
+    let config = MarginConfig::from_env();
+    let mut previous_level = MarginLevel::Ok;
+    let mut interval = time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
-        // Simulate receiving a new fill
-        let fill = Fill { symbol: "BTC".to_string(), quantity: 2, price: 60100.50 };
-        println!("\nReceived Fill: Buy 2 BTC @ 60100.50");
+        let (report, orders) = {
+            let mut p = portfolio.lock().unwrap();
+            p.cash.settle_due(chrono::Utc::now());
+            let report = margin::evaluate(&config, &p.positions, &p.cash.report());
+            let orders = quantumarb_types::reduction_orders(&p.positions, report.required_reduction);
+            (report, orders)
+        };
+
+        if report.level != previous_level {
+            println!("\nMargin level {:?} -> {:?}", previous_level, report.level);
+            journal.publish(vec![Message::json(MARGIN_ALERT_TOPIC, format!("margin:{}", Uuid::new_v4()), &report)]);
+            previous_level = report.level;
+        }
+
+        if report.level == MarginLevel::MarginCall && config.auto_reduce && !orders.is_empty() {
+            let reason = if report.projected.equity_exhausted {
+                format!("Projected equity exhausted under a {}σ move", report.shock_sigmas)
+            } else {
+                format!(
+                    "Projected margin usage {:.0}% under a {}σ move",
+                    report.projected.usage * 100.0,
+                    report.shock_sigmas
+                )
+            };
+            let instruction = StrategyInstruction::ReducePositions {
+                reason,
+                fraction: report.required_reduction,
+                orders,
+            };
+            let key = format!("reduce:{}", Uuid::new_v4());
+            journal.publish(vec![Message::json(quantumarb_bus::topics::STRATEGY_INSTRUCTIONS, key, &instruction)]);
+        }
 
+        *latest.lock().unwrap() = Some(report);
+    }
+}
+
+/// Re-evaluates FX exposure every minute. Alerts are raised when a currency
+/// goes over its limit; with auto-convert enabled, foreign cash is converted
+/// to base at each fix that has passed since the previous check.
+async fn monitor_fx(portfolio: SharedPortfolio, latest: SharedFx, journal: Journal) {
+    let config = FxConfig::from_env();
+    let mut over_limit: Vec<String> = Vec::new();
+    let mut recent: VecDeque<FxConversion> = VecDeque::new();
+    let mut last_check = chrono::Utc::now();
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let now = chrono::Utc::now();
+        let (exposures, conversions) = {
+            let mut p = portfolio.lock().unwrap();
+            p.cash.settle_due(now);
+            let fix = config.fix_between(last_check, now).filter(|_| config.auto_convert);
+            let conversions = fix.map(|fix| fx::convert_cash(&config, &mut p.cash, fix)).unwrap_or_default();
+            if !conversions.is_empty() {
+                journal.changed(&p);
+            }
+            (fx::exposures(&config, &p.positions, &p.instruments, &p.cash.report()), conversions)
+        };
+        last_check = now;
+
+        for conversion in conversions {
+            println!(
+                "\nFX conversion at the {} fix: {:.2} {} -> {:.2} {}",
+                conversion.fix_utc, conversion.sold, conversion.currency, conversion.bought, config.base_currency
+            );
+            if recent.len() == RECENT_FX_CONVERSIONS {
+                recent.pop_front();
+            }
+            recent.push_back(conversion);
+        }
+
+        let report = FxReport {
+            base_currency: config.base_currency.clone(),
+            exposures,
+            auto_convert: config.auto_convert,
+            next_fix_utc: config.next_fix(now),
+            recent_conversions: recent.iter().cloned().collect(),
+            timestamp_utc: now.to_rfc3339(),
+        };
+        for exposure in report.exposures.iter().filter(|e| e.over_limit && !over_limit.contains(&e.currency)) {
+            println!("\nFX exposure to {} over its limit: {:.0}", exposure.currency, exposure.net);
+            journal.publish(vec![Message::json(FX_ALERT_TOPIC, format!("fx:{}", Uuid::new_v4()), exposure)]);
+        }
+        over_limit = report.exposures.iter().filter(|e| e.over_limit).map(|e| e.currency.clone()).collect();
+
+        *latest.lock().unwrap() = Some(report);
+    }
+}
+
+/// Simulates the subscription to 'reference.corporate_actions' (seeded from
+/// QA_CORPORATE_ACTIONS_PATH) and applies actions as their ex-date arrives.
+async fn process_corporate_actions(
+    portfolio: SharedPortfolio,
+    book: SharedCorporateActions,
+    journal: Journal,
+    pnl_stream: PnlStream,
+) {
+    book.lock().unwrap().already_applied(journal.corporate_actions_applied());
+    for action in quantumarb_corporate_actions::load_from_env() {
+        book.lock().unwrap().receive(action);
+    }
+
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
         let mut p = portfolio.lock().unwrap();
-        let position = p.positions.entry(fill.symbol.clone()).or_insert(Position {
-            symbol: fill.symbol.clone(),
-            quantity: 0,
-            average_entry_price: 0.0,
-            current_market_price: fill.price,
-            unrealized_pnl: 0.0,
-        });
+        let applied = book.lock().unwrap().apply_due(&mut p, chrono::Utc::now());
+        if !applied.is_empty() {
+            journal.corporate_actions(&p, applied);
+            pnl_stream.publish_all(&p);
+        }
+    }
+}
+
+/// Closes out each trading day: the day's P&L is the change in total
+/// (realized + unrealized) P&L since the previous close. Fees are left out,
+/// as for the "actual" P&L of a regulatory VaR backtest. Each close also
+/// starts the accounts' next session.
+async fn close_daily_pnl(portfolio: SharedPortfolio, daily_pnl: SharedDailyPnl, journal: Journal) {
+    let period_secs = std::env::var("QA_PNL_PERIOD_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|p| *p > 0)
+        .unwrap_or(DEFAULT_PNL_PERIOD_SECS);
+    let mut interval = time::interval(Duration::from_secs(period_secs));
+    interval.tick().await; // The first tick completes immediately.
+
+    let total_pnl = |p: &Portfolio| p.realized_pnl + p.total_unrealized_pnl;
+    let mut period_start = chrono::Utc::now();
+    let mut previous_total = total_pnl(&portfolio.lock().unwrap());
+    loop {
+        interval.tick().await;
+        let period_end = chrono::Utc::now();
+        let total = {
+            let mut p = portfolio.lock().unwrap();
+            let open = p.allocations.open_blocks().into_iter().map(|block| block.block_id).collect();
+            allocate_blocks(&mut p, &journal, open, period_end);
+            p.accounts.close_session(period_end);
+            journal.changed(&p);
+            total_pnl(&p)
+        };
+        let day = DailyPnl {
+            period_start_utc: period_start,
+            period_end_utc: period_end,
+            pnl: total - previous_total,
+        };
+        println!("\nClosed trading day: P&L ${:.2}", day.pnl);
 
-        // Update position based on the fill
-        let old_quantity = position.quantity;
-        let new_quantity = old_quantity + fill.quantity;
-
-        // If position is closed or reduced, calculate realized P&L
-        if old_quantity.signum() != new_quantity.signum() && new_quantity != 0 {
-            let closed_quantity = std::cmp::min(old_quantity.abs(), fill.quantity.abs());
-            let realized = (fill.price - position.average_entry_price) * closed_quantity as f64 * old_quantity.signum() as f64;
-            p.realized_pnl += realized;
-            println!("  -> Realized P&L: ${:.2}", realized);
-        }
-        
-        // Update average entry price
-        if new_quantity != 0 {
-            position.average_entry_price = ((position.average_entry_price * old_quantity as f64) + (fill.price * fill.quantity as f64)) / new_quantity as f64;
+        let mut history = daily_pnl.lock().unwrap();
+        if history.len() == DAILY_PNL_RETENTION {
+            history.remove(0);
+        }
+        history.push(day);
+        period_start = period_end;
+        previous_total = total;
+    }
+}
+
+/// Simulates listening for execution reports (fills) from the message bus;
+/// every tenth report busts the fill before it. Awaiting `send` holds the
+/// subscription while the fill queue is full.
+async fn listen_for_fills(fills: Sender<Fill>) {
+    let instruments = ReferenceData::seeded();
+    let btc = instruments.by_symbol("BTC").expect("BTC is a built-in instrument").clone();
+    let mut interval = time::interval(Duration::from_secs(5));
+    let mut last_report: Option<ExecutionReport> = None;
+    for tick in 1u64.. {
+        interval.tick().await;
+        if let Some(mut bust) = last_report.take().filter(|_| tick % 10 == 0) {
+            bust.status = OrderStatus::TradeBusted;
+            bust.exec_ref_id = std::mem::replace(&mut bust.exec_id, Uuid::new_v4().simple().to_string());
+            let Some(fill) = Fill::from_report(&bust, &btc, OrderSide::Buy, "VENUE_A", SIM_STRATEGY, SIM_ACCOUNT) else {
+                continue;
+            };
+            println!("\nReceived Bust of fill {}", bust.exec_ref_id);
+            if fills.send(fill).await.is_err() {
+                return;
+            }
+            continue;
+        }
+        // Simulate receiving a new fill
+        let report = ExecutionReport {
+            exchange_order_id: "SIM-FILL".to_string(),
+            exec_id: Uuid::new_v4().simple().to_string(),
+            internal_order_id: Uuid::new_v4(),
+            status: OrderStatus::Filled,
+            filled_size: 2,
+            filled_price: 60100_50,
+            reject: None,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            cumulative_size: 2,
+            leaves_size: 0,
+            liquidity: Some(Liquidity::Taker),
+            fee: Money::from_f64(240.40),
+            transact_time_ns: utc_now_ns(),
+            exec_ref_id: String::new(),
+        };
+        let Some(fill) = Fill::from_report(&report, &btc, OrderSide::Buy, "VENUE_A", SIM_STRATEGY, SIM_ACCOUNT) else {
+            continue;
+        };
+        last_report = Some(report);
+        println!("\nReceived Fill: Buy 2 BTC @ 60100.50 on {}", fill.venue);
+        if fills.send(fill).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Applies queued fills to positions, fees and cash, journaling each one
+/// first. A fill already booked (the bus redelivered it) is skipped before it
+/// reaches the journal or the fee schedules' volume. Each fill is then
+/// acknowledged to the checkpoint.
+async fn apply_fills(
+    mut fills: Receiver<Fill>,
+    portfolio: SharedPortfolio,
+    journal: Journal,
+    pnl_stream: PnlStream,
+    checkpoint: SharedCheckpoint,
+) {
+    let mut fee_engine = FeeEngine::from_env();
+    let mut fee_month = chrono::Utc::now().month();
+    while let Some(fill) = fills.recv().await {
+        let booked = |key: &&str| portfolio.lock().unwrap().booked_fills.contains(key);
+        if let Some(exec_id) = fill.exec_id.as_deref().filter(booked) {
+            println!("  -> Fill {} already booked; skipped.", exec_id);
+            checkpoint.lock().unwrap().booked(&fill, chrono::Utc::now());
+            continue;
+        }
+        let now = chrono::Utc::now();
+        if now.month() != fee_month {
+            fee_engine.reset_monthly_volume();
+            fee_month = now.month();
+        }
+        let fee_total = if let Some(amendment) = &fill.amends {
+            // A bust or correction adds no volume; its fee comes with it or
+            // from the fill it amends (see corrections.rs).
+            println!("  -> {:?} of fill {}", amendment.kind, amendment.original);
+            fill.fee.unwrap_or(Money::ZERO)
         } else {
-            position.average_entry_price = 0.0; // Position is flat
+            // Volume accrues whoever priced the fee, so the schedule's tiers stay current.
+            let fees = fee_engine.apply(&fill.venue, fill.liquidity, fill.quantity, fill.price.to_f64());
+            match fill.fee {
+                Some(reported) => {
+                    println!("  -> Fees: ${:.2} ({:?}, reported by the venue)", reported, fees.liquidity);
+                    reported
+                }
+                None => {
+                    let priced = Money::from_f64(fees.total);
+                    println!("  -> Fees: ${:.2} ({:?}, tier {})", priced, fees.liquidity, fees.tier);
+                    priced
+                }
+            }
+        };
+        let traded_utc = fill.transact_time_utc.unwrap_or(now);
+
+        let mut p = portfolio.lock().unwrap();
+        journal.booked(&BookedFill { fill: fill.clone(), fee_total, traded_utc, booked_utc: now });
+        checkpoint.lock().unwrap().booked(&fill, now);
+        let symbol = fill.symbol.clone();
+        let completed_order = fill.order_id.filter(|_| fill.last_fill);
+        if let Some((realized, closed_quantity)) = book_fill(&mut p, fill, fee_total, traded_utc, now) {
+            println!("  -> Realized P&L: ${:.2} on {} closed", realized, closed_quantity);
         }
-        position.quantity = new_quantity;
+        if let Some(order_id) = completed_order {
+            allocate_blocks(&mut p, &journal, vec![order_id.to_string()], now);
+        }
+        pnl_stream.publish(&p, &symbol);
     }
 }
 
-/// Simulates receiving market data and marking positions to market.
-async fn mark_to_market(portfolio: SharedPortfolio) {
+/// Allocates the parent accounts' blocks among `block_ids` and journals the
+/// allocations, with their reports for 'portfolio.allocations'.
+fn allocate_blocks(p: &mut Portfolio, journal: &Journal, block_ids: Vec<String>, now: DateTime<Utc>) {
+    let mut reports = Vec::new();
+    for block_id in block_ids {
+        if !p.allocations.is_open(&block_id) {
+            continue;
+        }
+        match allocation::allocate(p, &block_id, now) {
+            Ok(report) => {
+                println!(
+                    "  -> Allocated {} {} @ {:.2} from account {} across {} accounts",
+                    report.quantity,
+                    report.symbol,
+                    report.average_price,
+                    report.parent_account,
+                    report.lines.len()
+                );
+                reports.push(report);
+            }
+            Err(e) => println!("  -> Block {} not allocated: {}", block_id, e),
+        }
+    }
+    if !reports.is_empty() {
+        journal.allocated(&reports);
+    }
+}
+
+/// Books a fill into positions, counterparty exposure and cash. Returns the
+/// realized P&L and quantity if it closes part of a position. A fill whose
+/// key is already in the book changes nothing (see fills.rs); a bust or
+/// correction is applied to the fill it amends (see corrections.rs).
+fn book_fill(
+    p: &mut Portfolio,
+    fill: Fill,
+    fee_total: Money,
+    traded_utc: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<(Money, Quantity)> {
+    if let Some(amendment) = fill.amends.clone() {
+        corrections::amend(p, fill, amendment, traded_utc, now);
+        return None;
+    }
+    if fill.exec_id.as_deref().is_some_and(|key| !p.booked_fills.insert(key)) {
+        return None;
+    }
+    if let Some(key) = fill.exec_id.clone() {
+        p.trade_log.record(BookedTrade { key, fill: fill.clone(), fee_total, traded_utc });
+    }
+    p.allocations.hold(&p.allocation_schemes, &fill, fee_total, now);
+    let multiplier = p.instruments.by_symbol(&fill.symbol).map_or(Money::from_f64(1.0), |d| d.multiplier);
+    let position = p.positions.entry(fill.symbol.clone()).or_insert(Position {
+        symbol: fill.symbol.clone(),
+        current_market_price: fill.price,
+        multiplier,
+        ..Default::default()
+    });
+
+    // Update position based on the fill (see pnl.rs)
+    let mut lot = position.lot();
+    let effect = lot.fill(Quantity(fill.quantity), fill.price);
+    let realized = effect.realized_pnl.at_multiplier(position.multiplier);
+    position.set_lot(lot);
+    position.mark();
+    *position.venue_quantities.entry(fill.venue).or_insert(0) += fill.quantity;
+    *position.counterparty_quantities.entry(fill.counterparty.clone()).or_insert(0) += fill.quantity;
+
+    let notional = Money::notional(fill.price, Quantity(fill.quantity), position.multiplier);
+    if let Some(trade) = UnsettledTrade::for_fill(&fill.counterparty, notional, traded_utc) {
+        p.unsettled_trades.push(trade);
+    }
+    p.cash.settle_due(now);
+    p.cash.post_fill(&fill.symbol, fill.quantity, fill.price, notional, fee_total, traded_utc);
+    p.total_fees += fee_total;
+    let multiplier = position.multiplier;
+    p.strategies.book(fill.strategy_id.as_deref(), &fill.symbol, fill.quantity, fill.price, multiplier, fee_total);
+    p.accounts.book(fill.account_id, &fill.symbol, fill.quantity, fill.price, multiplier);
+    let closed = (!effect.closed_quantity.is_zero()).then_some((realized, effect.closed_quantity));
+    if closed.is_some() {
+        p.realized_pnl += realized;
+    }
+    refresh_totals(p);
+    closed
+}
+
+/// Simulates receiving market data from the message bus.
+async fn listen_for_market_data(prices: Sender<PriceUpdate>, mut rng: SimRng) {
     let mut interval = time::interval(Duration::from_secs(1));
     loop {
         interval.tick().await;
-        let mut p = portfolio.lock().unwrap();
-        if p.positions.is_empty() { continue; }
+        // Simulate new market price for BTC
+        let price = Price::from_f64(60100.50 + rng.gen_range(-10.0..10.0)).round_to(Price::from_f64(0.01));
+        if prices.send(PriceUpdate { symbol: "BTC".to_string(), price }).await.is_err() {
+            return;
+        }
+    }
+}
 
-        let mut total_unrealized = 0.0;
-        let mut total_value = 0.0;
+/// Marks positions to market at each queued price.
+async fn mark_to_market(mut prices: Receiver<PriceUpdate>, portfolio: SharedPortfolio, pnl_stream: PnlStream) {
+    while let Some(update) = prices.recv().await {
+        let mut guard = portfolio.lock().unwrap();
+        let p = &mut *guard;
+        if let Some(position) = p.positions.get_mut(&update.symbol) {
+            position.current_market_price = update.price;
+            position.mark();
+        }
+        p.strategies.mark(&update.symbol, update.price);
+        p.accounts.mark(&update.symbol, update.price);
+        refresh_totals(p);
+        pnl_stream.publish(p, &update.symbol);
+    }
+}
 
-        // Simulate new market price for BTC
-        let new_btc_price = 60100.50 + (rand::random::<f64>() * 20.0 - 10.0);
-        
-        if let Some(position) = p.positions.get_mut("BTC") {
-            position.current_market_price = new_btc_price;
-            position.unrealized_pnl = (position.current_market_price - position.average_entry_price) * position.quantity as f64;
-            total_unrealized += position.unrealized_pnl;
-            total_value += position.quantity as f64 * position.current_market_price;
-        }
-        
-        p.total_unrealized_pnl = total_unrealized;
-        p.total_portfolio_value = total_value;
-        p.timestamp_utc = chrono::Utc::now().to_rfc3339();
+/// Recomputes the portfolio totals from the positions.
+fn refresh_totals(p: &mut Portfolio) {
+    p.total_unrealized_pnl = p.positions.values().map(|pos| pos.unrealized_pnl).sum();
+    p.total_portfolio_value = p
+        .positions
+        .values()
+        .map(|pos| Money::notional(pos.current_market_price, Quantity(pos.quantity), pos.multiplier))
+        .sum();
+    p.net_pnl = p.realized_pnl + p.total_unrealized_pnl - p.total_fees;
+    p.timestamp_utc = chrono::Utc::now().to_rfc3339();
+}
+
+/// Publishes each account's session P&L for the risk gateway's daily loss
+/// limits, on its desk's topic.
+async fn publish_account_pnl(portfolio: SharedPortfolio, desks: Arc<Desks>) {
+    let mut interval = time::interval(Duration::from_secs(ACCOUNT_PNL_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let report = portfolio.lock().unwrap().accounts.report();
+        for account in &report {
+            let desk = desks.desk_of_account(account.account_id);
+            let topic = quantumarb_bus::topics::for_desk(desk, quantumarb_bus::topics::ACCOUNT_PNL);
+            quantumarb_bus::publish_json(&topic, account);
+        }
+    }
+}
+
+/// Publishes the messages committed to the journal, and compacts it.
+async fn publish_from_outbox(journal: Journal, portfolio: SharedPortfolio) {
+    let mut interval = time::interval(journal.drain_interval());
+    loop {
+        interval.tick().await;
+        journal.drain(&portfolio);
+    }
+}
+
+/// Loads instrument definitions from the reference data service and applies
+/// changed multipliers to open positions.
+async fn follow_reference_data(portfolio: SharedPortfolio, journal: Journal) {
+    // In a real system, changes arrive on the bus between the full reloads:
+    // let mut updates = nats_client.subscribe(quantumarb_refdata::UPDATES_TOPIC).await.unwrap();
+    let http_client = reqwest::Client::new();
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let definitions = match http_client.get(INSTRUMENTS_URL).send().await {
+            Ok(response) => match response.json::<Vec<InstrumentDefinition>>().await {
+                Ok(definitions) => definitions,
+                Err(_) => {
+                    println!("  -> Error parsing instrument definitions.");
+                    continue;
+                }
+            },
+            Err(_) => {
+                println!("  -> Failed to fetch instrument definitions; using the last known set.");
+                continue;
+            }
+        };
+
+        let mut guard = portfolio.lock().unwrap();
+        let p = &mut *guard;
+        let mut changed = false;
+        for definition in definitions {
+            if !p.instruments.apply(definition.clone()) {
+                continue;
+            }
+            if let Some(position) = p.positions.get_mut(&definition.symbol) {
+                if position.multiplier != definition.multiplier {
+                    println!("  -> {} multiplier changed to {}.", definition.symbol, definition.multiplier);
+                    position.multiplier = definition.multiplier;
+                    position.mark();
+                    p.strategies.set_multiplier(&definition.symbol, definition.multiplier);
+                    p.accounts.set_multiplier(&definition.symbol, definition.multiplier);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            journal.changed(p);
+        }
     }
 }
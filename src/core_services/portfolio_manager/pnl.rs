@@ -0,0 +1,201 @@
+/*
+ * QuantumArb 2.0 - Core Services: Position P&L
+ *
+ * File: src/core_services/portfolio_manager/pnl.rs
+ *
+ * Description:
+ * How a fill changes a position's quantity, cost basis and realized P&L. A
+ * fill either opens/adds to a position or reduces it; a fill large enough to
+ * go through zero is both, split at zero:
+ *
+ *   adding    the fill's price x quantity is added to the cost basis;
+ *             nothing is realized.
+ *   reducing  the closed quantity's share of the cost basis is released, and
+ *             the difference from its value at the fill price is realized.
+ *   flipping  the old side closes as above, and the remainder opens a new
+ *             position at the fill price.
+ *
+ * The cost basis is the exact entry value of the open quantity (signed like
+ * the quantity); the average entry price is derived from it for display.
+ * Amounts are `quantumarb-money` per-point values; callers apply the contract
+ * multiplier. Because every amount is fixed-point and cost only ever moves
+ * between the basis and realized P&L, realized + unrealized P&L equals the
+ * net cash paid and received for the fills plus the marked value of the
+ * position exactly, with no fees.
+ */
+
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_types::Position;
+
+/// A position's signed quantity and what it cost to enter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Lot {
+    pub quantity: Quantity,
+    /// Entry value of the open quantity, per point; zero when flat.
+    pub cost_basis: Money,
+}
+
+/// What a fill closed out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillEffect {
+    /// Quantity of the existing position that the fill closed.
+    pub closed_quantity: Quantity,
+    pub realized_pnl: Money,
+}
+
+impl Lot {
+    /// Applies a fill of signed `quantity` (positive for buys) at `price`.
+    pub fn fill(&mut self, quantity: Quantity, price: Price) -> FillEffect {
+        let closing = !self.quantity.is_zero() && !quantity.is_zero() && self.quantity.signum() != quantity.signum();
+        if !closing {
+            self.quantity = self.quantity + quantity;
+            self.cost_basis += price * quantity;
+            return FillEffect { closed_quantity: Quantity::ZERO, realized_pnl: Money::ZERO };
+        }
+
+        let closed_quantity = self.quantity.abs().min(quantity.abs());
+        // Signed like the position being closed.
+        let closed = Quantity(closed_quantity.0 * self.quantity.signum());
+        let closed_cost = self.cost_basis.pro_rata(closed, self.quantity);
+        let realized_pnl = price * closed - closed_cost;
+        self.cost_basis -= closed_cost;
+        self.quantity = self.quantity + quantity;
+        // Whatever the fill did not close opens the other side.
+        let opened = quantity + closed;
+        if !opened.is_zero() {
+            self.cost_basis += price * opened;
+        }
+        FillEffect { closed_quantity, realized_pnl }
+    }
+
+    /// Average price the open quantity was entered at; zero when flat.
+    pub fn average_entry_price(&self) -> Price {
+        self.cost_basis.per_unit(self.quantity)
+    }
+
+    /// Unrealized P&L of the position marked at `mark`.
+    pub fn unrealized_pnl(&self, mark: Price) -> Money {
+        mark * self.quantity - self.cost_basis
+    }
+}
+
+/// A portfolio position read and written as a `Lot`.
+pub trait PositionLot {
+    fn lot(&self) -> Lot;
+    fn set_lot(&mut self, lot: Lot);
+    /// Recomputes unrealized P&L at the current mark, in currency.
+    fn mark(&mut self);
+}
+
+impl PositionLot for Position {
+    fn lot(&self) -> Lot {
+        Lot { quantity: Quantity(self.quantity), cost_basis: self.cost_basis }
+    }
+
+    fn set_lot(&mut self, lot: Lot) {
+        self.quantity = lot.quantity.0;
+        self.cost_basis = lot.cost_basis;
+        self.average_entry_price = lot.average_entry_price();
+    }
+
+    fn mark(&mut self) {
+        self.unrealized_pnl = self.lot().unrealized_pnl(self.current_market_price).at_multiplier(self.multiplier);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Prices in cents, as the venues quote them.
+    fn price() -> impl Strategy<Value = Price> {
+        (1u64..=10_000_000).prop_map(|cents| Price::from_wire(cents, 2).unwrap())
+    }
+
+    fn fill() -> impl Strategy<Value = (Quantity, Price)> {
+        ((-500i64..=500).prop_filter("a fill trades something", |q| *q != 0).prop_map(Quantity), price())
+    }
+
+    fn fills() -> impl Strategy<Value = Vec<(Quantity, Price)>> {
+        prop::collection::vec(fill(), 1..60)
+    }
+
+    proptest! {
+        #[test]
+        fn pnl_equals_cash_flow_plus_mark_value(fills in fills(), mark in price()) {
+            let mut lot = Lot::default();
+            let mut realized = Money::ZERO;
+            let mut cash_flow = Money::ZERO;
+            for (quantity, price) in &fills {
+                realized += lot.fill(*quantity, *price).realized_pnl;
+                cash_flow -= *price * *quantity;
+            }
+            let quantity: i64 = fills.iter().map(|(q, _)| q.0).sum();
+            prop_assert_eq!(lot.quantity, Quantity(quantity));
+            prop_assert_eq!(realized + lot.unrealized_pnl(mark), cash_flow + mark * lot.quantity);
+        }
+
+        #[test]
+        fn flat_positions_have_no_cost_or_unrealized_pnl(fills in fills(), mark in price()) {
+            let mut lot = Lot::default();
+            for (quantity, price) in fills {
+                lot.fill(quantity, price);
+                if lot.quantity.is_zero() {
+                    prop_assert_eq!(lot.cost_basis, Money::ZERO);
+                    prop_assert_eq!(lot.average_entry_price(), Price::ZERO);
+                    prop_assert_eq!(lot.unrealized_pnl(mark), Money::ZERO);
+                }
+            }
+        }
+
+        #[test]
+        fn adding_realizes_nothing_and_averages_within_the_fill_prices(fills in fills()) {
+            let mut lot = Lot::default();
+            let low = fills.iter().map(|(_, p)| *p).min().unwrap();
+            let high = fills.iter().map(|(_, p)| *p).max().unwrap();
+            for (quantity, price) in fills {
+                let adding = lot.quantity.is_zero() || lot.quantity.signum() == quantity.signum();
+                let effect = lot.fill(quantity, price);
+                if adding {
+                    prop_assert_eq!(effect, FillEffect { closed_quantity: Quantity::ZERO, realized_pnl: Money::ZERO });
+                }
+                if !lot.quantity.is_zero() {
+                    prop_assert!(lot.average_entry_price() >= low && lot.average_entry_price() <= high);
+                }
+            }
+        }
+
+        #[test]
+        fn partial_close_keeps_the_entry_price(open in fill(), fraction in 0.01f64..0.99, exit in price()) {
+            let (quantity, entry) = open;
+            let close = Quantity(-((quantity.0 as f64 * fraction).trunc() as i64));
+            prop_assume!(!close.is_zero());
+            let mut lot = Lot::default();
+            lot.fill(quantity, entry);
+            let effect = lot.fill(close, exit);
+            prop_assert_eq!(lot.quantity, quantity + close);
+            prop_assert_eq!(lot.average_entry_price(), entry);
+            prop_assert_eq!(effect.closed_quantity, close.abs());
+            // A long gains when it sells higher; a short gains when it buys lower.
+            prop_assert_eq!(effect.realized_pnl, (exit - entry) * -close);
+        }
+
+        #[test]
+        fn flip_through_zero_closes_the_old_side_and_opens_at_the_fill_price(
+            open in fill(),
+            extra in 1i64..=500,
+            exit in price(),
+        ) {
+            let (quantity, entry) = open;
+            let flip = Quantity(-(quantity.0 + quantity.signum() * extra));
+            let mut lot = Lot::default();
+            lot.fill(quantity, entry);
+            let effect = lot.fill(flip, exit);
+            prop_assert_eq!(lot.quantity, Quantity(-quantity.signum() * extra));
+            prop_assert_eq!(lot.average_entry_price(), exit);
+            prop_assert_eq!(effect.closed_quantity, quantity.abs());
+            prop_assert_eq!(effect.realized_pnl, (exit - entry) * quantity);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+/*
+ * QuantumArb 2.0 - Core Services: Strategy Sub-Portfolios
+ *
+ * File: src/core_services/portfolio_manager/strategies.rs
+ *
+ * Description:
+ * Books every fill a second time, into a virtual sub-portfolio for the
+ * strategy named by the fill's strategy_id tag (UNATTRIBUTED when untagged),
+ * so each strategy has its own positions, P&L and fees. The firm-level book
+ * in main.rs is unchanged and remains the one risk and margin run on.
+ *
+ * Sub-portfolios use the same position arithmetic as the firm (pnl.rs). The
+ * firm's position in a symbol is the sum of the strategies' positions, and
+ * total (realized + unrealized) P&L sums too; realized P&L alone does not,
+ * since a strategy closing against another's open position realizes P&L the
+ * firm, which is still open, does not.
+ *
+ * Netting: when strategies hold opposite positions in a symbol, the
+ * offsetting quantity nets out at the firm level and carries no market risk.
+ * GET /portfolio/strategies reports it per symbol alongside each strategy's
+ * summary; GET /portfolio/strategy/{id} serves one sub-portfolio. A desk
+ * sees its own strategies and the netting between them only.
+ *
+ * Corporate actions are applied to the firm book only.
+ */
+
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::pnl::PositionLot;
+
+/// Sub-portfolio of fills that carry no strategy_id.
+pub const UNATTRIBUTED: &str = "UNATTRIBUTED";
+
+// --- Data Structures ---
+
+/// One strategy's positions and P&L.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct SubPortfolio {
+    pub strategy_id: String,
+    pub positions: HashMap<String, Position>,
+    pub realized_pnl: Money,
+    pub total_unrealized_pnl: Money,
+    pub total_fees: Money,
+    /// Realized + unrealized P&L, less fees.
+    pub net_pnl: Money,
+}
+
+/// A strategy's totals, for GET /portfolio/strategies.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct StrategySummary {
+    pub strategy_id: String,
+    pub open_positions: usize,
+    pub gross_exposure: Money,
+    pub net_pnl: Money,
+}
+
+/// Where strategies hold opposite positions in one symbol.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct SymbolNetting {
+    pub symbol: String,
+    pub by_strategy: HashMap<String, i64>,
+    /// Sum of the long strategies' positions.
+    pub long_quantity: i64,
+    /// Sum of the short strategies' positions, as a positive quantity.
+    pub short_quantity: i64,
+    /// The firm's position: long less short.
+    pub net_quantity: i64,
+    /// Quantity that offsets between strategies.
+    pub netted_quantity: i64,
+    pub netted_notional: Money,
+}
+
+/// Body of a GET /portfolio/strategies response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct StrategiesReport {
+    pub strategies: Vec<StrategySummary>,
+    pub netting: Vec<SymbolNetting>,
+}
+
+/// Every strategy's sub-portfolio.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct StrategyBook {
+    sub_portfolios: HashMap<String, SubPortfolio>,
+}
+
+impl StrategyBook {
+    /// Books a fill into its strategy's sub-portfolio. `quantity` is signed,
+    /// positive for buys.
+    pub fn book(
+        &mut self,
+        strategy_id: Option<&str>,
+        symbol: &str,
+        quantity: i64,
+        price: Price,
+        multiplier: Money,
+        fee: Money,
+    ) {
+        let strategy_id = strategy_id.unwrap_or(UNATTRIBUTED);
+        let sub = self.sub_portfolios.entry(strategy_id.to_string()).or_insert_with(|| SubPortfolio {
+            strategy_id: strategy_id.to_string(),
+            ..Default::default()
+        });
+        let position = sub.positions.entry(symbol.to_string()).or_insert(Position {
+            symbol: symbol.to_string(),
+            current_market_price: price,
+            multiplier,
+            ..Default::default()
+        });
+        let mut lot = position.lot();
+        let effect = lot.fill(Quantity(quantity), price);
+        sub.realized_pnl += effect.realized_pnl.at_multiplier(position.multiplier);
+        position.set_lot(lot);
+        position.mark();
+        sub.total_fees += fee;
+        sub.refresh_totals();
+    }
+
+    /// Marks every strategy's position in `symbol`.
+    pub fn mark(&mut self, symbol: &str, price: Price) {
+        self.for_each_position(symbol, |position| position.current_market_price = price);
+    }
+
+    pub fn set_multiplier(&mut self, symbol: &str, multiplier: Money) {
+        self.for_each_position(symbol, |position| position.multiplier = multiplier);
+    }
+
+    pub fn get(&self, strategy_id: &str) -> Option<&SubPortfolio> {
+        self.sub_portfolios.get(strategy_id)
+    }
+
+    /// The totals of each strategy `visible` passes and the netting between
+    /// them, by id and symbol.
+    pub fn report(&self, visible: impl Fn(&str) -> bool) -> StrategiesReport {
+        let mut strategies: Vec<StrategySummary> = self
+            .sub_portfolios
+            .values()
+            .filter(|sub| visible(&sub.strategy_id))
+            .map(|sub| StrategySummary {
+                strategy_id: sub.strategy_id.clone(),
+                open_positions: sub.positions.values().filter(|p| p.quantity != 0).count(),
+                gross_exposure: sub.positions.values().map(|p| notional(p, p.quantity).abs()).sum(),
+                net_pnl: sub.net_pnl,
+            })
+            .collect();
+        strategies.sort_by(|a, b| a.strategy_id.cmp(&b.strategy_id));
+        StrategiesReport { strategies, netting: self.netting(&visible) }
+    }
+
+    /// Symbols in which some of the strategies `visible` passes are long and
+    /// others short.
+    pub fn netting(&self, visible: &impl Fn(&str) -> bool) -> Vec<SymbolNetting> {
+        let mut by_symbol: HashMap<&str, Vec<(&str, &Position)>> = HashMap::new();
+        for sub in self.sub_portfolios.values().filter(|sub| visible(&sub.strategy_id)) {
+            for position in sub.positions.values().filter(|p| p.quantity != 0) {
+                by_symbol.entry(position.symbol.as_str()).or_default().push((sub.strategy_id.as_str(), position));
+            }
+        }
+        let mut netting: Vec<SymbolNetting> = by_symbol
+            .into_iter()
+            .filter_map(|(symbol, held)| {
+                let long_quantity: i64 = held.iter().map(|(_, p)| p.quantity.max(0)).sum();
+                let short_quantity: i64 = held.iter().map(|(_, p)| (-p.quantity).max(0)).sum();
+                let netted_quantity = long_quantity.min(short_quantity);
+                if netted_quantity == 0 {
+                    return None;
+                }
+                Some(SymbolNetting {
+                    symbol: symbol.to_string(),
+                    by_strategy: held.iter().map(|(id, p)| (id.to_string(), p.quantity)).collect(),
+                    long_quantity,
+                    short_quantity,
+                    net_quantity: long_quantity - short_quantity,
+                    netted_quantity,
+                    netted_notional: notional(held[0].1, netted_quantity),
+                })
+            })
+            .collect();
+        netting.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        netting
+    }
+
+    fn for_each_position(&mut self, symbol: &str, mut update: impl FnMut(&mut Position)) {
+        for sub in self.sub_portfolios.values_mut() {
+            if let Some(position) = sub.positions.get_mut(symbol) {
+                update(position);
+                position.mark();
+                sub.refresh_totals();
+            }
+        }
+    }
+}
+
+impl SubPortfolio {
+    fn refresh_totals(&mut self) {
+        self.total_unrealized_pnl = self.positions.values().map(|p| p.unrealized_pnl).sum();
+        self.net_pnl = self.realized_pnl + self.total_unrealized_pnl - self.total_fees;
+    }
+}
+
+fn notional(position: &Position, quantity: i64) -> Money {
+    Money::notional(position.current_market_price, Quantity(quantity), position.multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opposite_strategies_net_at_the_firm_and_their_pnl_sums_to_the_firms() {
+        let mut book = StrategyBook::default();
+        let (one, price) = (Money::from_f64(1.0), |p: f64| Price::from_f64(p));
+        book.book(Some("momentum"), "BTC", 3, price(60_000.0), one, Money::ZERO);
+        book.book(Some("mean_reversion"), "BTC", -2, price(60_100.0), one, Money::ZERO);
+        book.book(None, "ETH", 5, price(3_000.0), one, Money::from_f64(1.5));
+        book.mark("BTC", price(60_050.0));
+
+        let netting = book.netting(&|_: &str| true);
+        assert_eq!(netting.len(), 1);
+        let btc = &netting[0];
+        assert_eq!((btc.long_quantity, btc.short_quantity, btc.net_quantity, btc.netted_quantity), (3, 2, 1, 2));
+        assert_eq!(btc.netted_notional, Money::from_f64(120_100.0));
+
+        // The firm: long 1 BTC bought for 180,000 - 120,200 net, marked at 60,050.
+        let total: Money = book.sub_portfolios.values().map(|s| s.realized_pnl + s.total_unrealized_pnl).sum();
+        assert_eq!(total, Money::from_f64(60_050.0 - (180_000.0 - 120_200.0)));
+        assert_eq!(book.get(UNATTRIBUTED).unwrap().net_pnl, Money::from_f64(-1.5));
+
+        // A desk running momentum alone sees no netting against the other desk's strategy.
+        let desk = book.report(|id| id == "momentum");
+        assert_eq!(desk.strategies.len(), 1);
+        assert!(desk.netting.is_empty());
+    }
+}
@@ -0,0 +1,393 @@
+/*
+ * QuantumArb 2.0 - Core Services: Streaming P&L
+ *
+ * File: src/core_services/portfolio_manager/stream.rs
+ *
+ * Description:
+ * Pushes position and P&L changes to dashboards over a WebSocket (GET
+ * /portfolio/stream) instead of having them poll GET /portfolio. Every fill
+ * and every mark publishes an update holding only what changed: the fields
+ * of the positions that moved and, when they moved, the book totals.
+ *
+ * A client chooses its symbols by sending
+ *
+ *   {"subscribe": ["BTC", "ETH"]}      or      {"unsubscribe": ["ETH"]}
+ *
+ * ("*" subscribes to every symbol). Each subscription is answered with a
+ * snapshot of the newly subscribed positions; from then on the client gets
+ * an update whenever one of its positions changes, and every change to the
+ * totals. Updates carry a stream-wide sequence number, so a filtered client
+ * sees gaps; a client that falls more than STREAM_BUFFER updates behind is
+ * sent a fresh snapshot of everything it subscribes to.
+ */
+
+use futures::{SinkExt, StreamExt};
+use quantumarb_money::{Money, Price};
+use quantumarb_types::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use warp::ws::{Message, WebSocket};
+
+use crate::{Portfolio, SharedPortfolio};
+
+/// Updates a slow client may fall behind before it is resynchronized.
+const STREAM_BUFFER: usize = 1024;
+
+// --- Messages ---
+
+/// The fields of a position that changed; all of them in a snapshot. A
+/// closed or removed position is sent with a zero quantity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionDelta {
+    pub symbol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_entry_price: Option<Price>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_market_price: Option<Price>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unrealized_pnl: Option<Money>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Totals {
+    pub realized_pnl: Money,
+    pub total_unrealized_pnl: Money,
+    pub total_portfolio_value: Money,
+    pub total_fees: Money,
+    pub net_pnl: Money,
+}
+
+/// A message sent to a client.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Snapshot { sequence: u64, positions: Vec<PositionDelta>, totals: Totals },
+    Update {
+        sequence: u64,
+        positions: Vec<PositionDelta>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        totals: Option<Totals>,
+    },
+    Error { message: String },
+}
+
+/// A message received from a client.
+#[derive(Debug, Clone, Deserialize)]
+struct ClientRequest {
+    #[serde(default)]
+    subscribe: Vec<String>,
+    #[serde(default)]
+    unsubscribe: Vec<String>,
+}
+
+/// One published change to the book, before filtering per client.
+#[derive(Debug, Clone)]
+struct Update {
+    sequence: u64,
+    positions: Vec<PositionDelta>,
+    totals: Option<Totals>,
+}
+
+// --- Publishing ---
+
+/// What clients were last told, to publish only the differences.
+#[derive(Debug, Default)]
+struct Published {
+    sequence: u64,
+    positions: HashMap<String, PositionDelta>,
+    totals: Option<Totals>,
+}
+
+impl Published {
+    /// Records the current state of `symbols` (every position when None) and
+    /// the totals; returns what changed, if anything did.
+    fn changes(
+        &mut self,
+        positions: &HashMap<String, Position>,
+        totals: Totals,
+        symbols: Option<&[&str]>,
+    ) -> Option<Update> {
+        let symbols: BTreeSet<String> = match symbols {
+            Some(symbols) => symbols.iter().map(|s| s.to_string()).collect(),
+            None => positions.keys().chain(self.positions.keys()).cloned().collect(),
+        };
+        let mut deltas = Vec::new();
+        for symbol in &symbols {
+            let before = match self.positions.get(symbol) {
+                Some(before) => before.clone(),
+                // Neither held nor ever sent: nothing to say.
+                None if !positions.contains_key(symbol) => continue,
+                None => unchanged(symbol),
+            };
+            let current = positions.get(symbol).map_or_else(|| closed(symbol), full);
+            let delta = PositionDelta {
+                symbol: symbol.clone(),
+                quantity: changed(current.quantity, before.quantity),
+                average_entry_price: changed(current.average_entry_price, before.average_entry_price),
+                current_market_price: changed(current.current_market_price, before.current_market_price),
+                unrealized_pnl: changed(current.unrealized_pnl, before.unrealized_pnl),
+            };
+            if !delta.is_empty() {
+                deltas.push(delta);
+            }
+            if positions.contains_key(symbol) {
+                self.positions.insert(symbol.clone(), current);
+            } else {
+                self.positions.remove(symbol);
+            }
+        }
+        let totals_changed = self.totals != Some(totals);
+        self.totals = Some(totals);
+        if deltas.is_empty() && !totals_changed {
+            return None;
+        }
+        self.sequence += 1;
+        Some(Update { sequence: self.sequence, positions: deltas, totals: totals_changed.then_some(totals) })
+    }
+}
+
+impl PositionDelta {
+    fn is_empty(&self) -> bool {
+        self.quantity.is_none()
+            && self.average_entry_price.is_none()
+            && self.current_market_price.is_none()
+            && self.unrealized_pnl.is_none()
+    }
+}
+
+/// `current` unless it equals what was sent before.
+fn changed<T: PartialEq + Copy>(current: Option<T>, before: Option<T>) -> Option<T> {
+    current.filter(|value| before != Some(*value))
+}
+
+fn full(position: &Position) -> PositionDelta {
+    PositionDelta {
+        symbol: position.symbol.clone(),
+        quantity: Some(position.quantity),
+        average_entry_price: Some(position.average_entry_price),
+        current_market_price: Some(position.current_market_price),
+        unrealized_pnl: Some(position.unrealized_pnl),
+    }
+}
+
+fn closed(symbol: &str) -> PositionDelta {
+    PositionDelta { quantity: Some(0), unrealized_pnl: Some(Money::ZERO), ..unchanged(symbol) }
+}
+
+fn unchanged(symbol: &str) -> PositionDelta {
+    PositionDelta {
+        symbol: symbol.to_string(),
+        quantity: None,
+        average_entry_price: None,
+        current_market_price: None,
+        unrealized_pnl: None,
+    }
+}
+
+fn totals(p: &Portfolio) -> Totals {
+    Totals {
+        realized_pnl: p.realized_pnl,
+        total_unrealized_pnl: p.total_unrealized_pnl,
+        total_portfolio_value: p.total_portfolio_value,
+        total_fees: p.total_fees,
+        net_pnl: p.net_pnl,
+    }
+}
+
+/// Publishes book changes to the WebSocket clients. Cloned into every task
+/// that changes positions.
+#[derive(Clone)]
+pub struct PnlStream {
+    published: Arc<Mutex<Published>>,
+    updates: broadcast::Sender<Arc<Update>>,
+}
+
+impl PnlStream {
+    pub fn new() -> PnlStream {
+        let (updates, _) = broadcast::channel(STREAM_BUFFER);
+        PnlStream { published: Arc::new(Mutex::new(Published::default())), updates }
+    }
+
+    /// Publishes what changed in `symbol`'s position and in the totals. Call
+    /// it with the book still locked, so updates go out in booking order.
+    pub fn publish(&self, p: &Portfolio, symbol: &str) {
+        self.send(p, Some(&[symbol]));
+    }
+
+    /// Publishes what changed anywhere in the book (after a restore or a
+    /// corporate action).
+    pub fn publish_all(&self, p: &Portfolio) {
+        self.send(p, None);
+    }
+
+    fn send(&self, p: &Portfolio, symbols: Option<&[&str]>) {
+        let update = self.published.lock().unwrap().changes(&p.positions, totals(p), symbols);
+        if let Some(update) = update {
+            // No receivers just means no client is connected.
+            let _ = self.updates.send(Arc::new(update));
+        }
+    }
+
+    /// A snapshot of `subscription`'s positions and the totals, as of the
+    /// last published update.
+    fn snapshot(&self, portfolio: &SharedPortfolio, subscription: &Subscription) -> ServerMessage {
+        let p = portfolio.lock().unwrap();
+        let sequence = self.published.lock().unwrap().sequence;
+        let mut positions: Vec<PositionDelta> =
+            p.positions.values().filter(|position| subscription.wants(&position.symbol)).map(full).collect();
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        ServerMessage::Snapshot { sequence, positions, totals: totals(&p) }
+    }
+}
+
+/// The symbols one client follows.
+#[derive(Debug, Clone, Default)]
+struct Subscription {
+    all: bool,
+    symbols: BTreeSet<String>,
+}
+
+impl Subscription {
+    fn wants(&self, symbol: &str) -> bool {
+        self.all || self.symbols.contains(symbol)
+    }
+
+    /// Applies a request; returns the symbols newly subscribed to.
+    fn apply(&mut self, request: ClientRequest) -> Subscription {
+        let mut added = Subscription::default();
+        for symbol in request.subscribe {
+            if symbol == "*" {
+                added.all = !self.all;
+                self.all = true;
+            } else if !self.wants(&symbol) {
+                self.symbols.insert(symbol.clone());
+                added.symbols.insert(symbol);
+            }
+        }
+        for symbol in request.unsubscribe {
+            if symbol == "*" {
+                self.all = false;
+                self.symbols.clear();
+            } else {
+                self.symbols.remove(&symbol);
+            }
+        }
+        added
+    }
+
+    /// The part of `update` this client is sent, if any.
+    fn filter(&self, update: &Update) -> Option<ServerMessage> {
+        let positions: Vec<PositionDelta> =
+            update.positions.iter().filter(|delta| self.wants(&delta.symbol)).cloned().collect();
+        if positions.is_empty() && update.totals.is_none() {
+            return None;
+        }
+        Some(ServerMessage::Update { sequence: update.sequence, positions, totals: update.totals })
+    }
+}
+
+// --- Clients ---
+
+/// Serves one WebSocket client until it disconnects.
+pub async fn serve(socket: WebSocket, stream: PnlStream, portfolio: SharedPortfolio) {
+    let (mut sink, mut requests) = socket.split();
+    let mut updates = stream.updates.subscribe();
+    let mut subscription = Subscription::default();
+    loop {
+        let reply = tokio::select! {
+            request = requests.next() => match request {
+                Some(Ok(message)) if message.is_text() => {
+                    match serde_json::from_slice::<ClientRequest>(message.as_bytes()) {
+                        Ok(request) => {
+                            let added = subscription.apply(request);
+                            (added.all || !added.symbols.is_empty()).then(|| stream.snapshot(&portfolio, &added))
+                        }
+                        Err(e) => Some(ServerMessage::Error { message: format!("invalid request: {}", e) }),
+                    }
+                }
+                Some(Ok(message)) if message.is_close() => break,
+                Some(Ok(_)) => None,
+                Some(Err(_)) | None => break,
+            },
+            update = updates.recv() => match update {
+                Ok(update) => subscription.filter(&update),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    println!("  -> P&L stream client {} updates behind; resending its snapshot.", missed);
+                    Some(stream.snapshot(&portfolio, &subscription))
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if let Some(reply) = reply {
+            let text = serde_json::to_string(&reply).unwrap();
+            if sink.send(Message::text(text)).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zero_totals() -> Totals {
+        Totals {
+            realized_pnl: Money::ZERO,
+            total_unrealized_pnl: Money::ZERO,
+            total_portfolio_value: Money::ZERO,
+            total_fees: Money::ZERO,
+            net_pnl: Money::ZERO,
+        }
+    }
+
+    fn position(symbol: &str, quantity: i64, mark: f64, unrealized: f64) -> Position {
+        Position {
+            symbol: symbol.to_string(),
+            quantity,
+            average_entry_price: Price::from_f64(100.0),
+            current_market_price: Price::from_f64(mark),
+            unrealized_pnl: Money::from_f64(unrealized),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn publishes_only_changed_fields_to_subscribed_clients() {
+        let mut published = Published::default();
+        let mut totals = zero_totals();
+        let mut book = HashMap::from([("BTC".to_string(), position("BTC", 2, 100.0, 0.0))]);
+        let first = published.changes(&book, totals, Some(&["BTC"])).unwrap();
+        assert_eq!(first.positions, vec![full(&book["BTC"])]);
+
+        // A mark moves the price and the P&L only.
+        book.insert("BTC".to_string(), position("BTC", 2, 101.0, 2.0));
+        totals.total_unrealized_pnl = Money::from_f64(2.0);
+        let mark = published.changes(&book, totals, Some(&["BTC"])).unwrap();
+        let expected = PositionDelta {
+            current_market_price: Some(Price::from_f64(101.0)),
+            unrealized_pnl: Some(Money::from_f64(2.0)),
+            ..unchanged("BTC")
+        };
+        assert_eq!((mark.sequence, mark.positions.clone(), mark.totals), (2, vec![expected], Some(totals)));
+        assert!(published.changes(&book, totals, Some(&["BTC"])).is_none());
+        assert!(published.changes(&book, totals, Some(&["ETH"])).is_none());
+
+        // Clients see their symbols' deltas; the totals go to everyone.
+        let mut eth_only = Subscription::default();
+        eth_only.apply(ClientRequest { subscribe: vec!["ETH".to_string()], unsubscribe: Vec::new() });
+        match eth_only.filter(&mark) {
+            Some(ServerMessage::Update { positions, totals: Some(_), .. }) => assert!(positions.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // A position that leaves the book is sent as closed.
+        book.clear();
+        let removed = published.changes(&book, totals, None).unwrap();
+        assert_eq!(removed.positions, vec![closed("BTC")]);
+    }
+}
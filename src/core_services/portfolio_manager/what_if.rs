@@ -0,0 +1,234 @@
+/*
+ * QuantumArb 2.0 - Core Services: What-If Analysis
+ *
+ * File: src/core_services/portfolio_manager/what_if.rs
+ *
+ * Description:
+ * Lets a trader see what a trade would do to the book before submitting it.
+ * POST /portfolio/what-if takes hypothetical fills, target positions, or
+ * both, books them on a copy of the live portfolio and reports, before and
+ * after:
+ *
+ *   exposure       gross, net, long and short notional, and per symbol
+ *   margin         current and projected usage (see margin.rs)
+ *   concentration  symbol, sector and venue shares of gross exposure
+ *   VaR            the VaR calculator's incremental VaR for the change
+ *
+ * Fills are booked first, then each target trades its symbol to the target
+ * quantity. Either is priced at the mark unless it gives a price; a symbol
+ * the book does not hold needs one. Hypothetical fills carry no fees and
+ * their cash leg is posted like a real fill's, so margin sees the cash spent.
+ * The live book is never changed.
+ *
+ * VaR is None when the VaR calculator cannot be reached; the rest of the
+ * report does not depend on it.
+ */
+
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_risk::concentration::ConcentrationMetrics;
+use quantumarb_types::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::margin::MarginReport;
+
+/// Venue hypothetical fills are booked to when they name none.
+pub const UNSPECIFIED_VENUE: &str = "UNSPECIFIED";
+
+// --- Data Structures ---
+
+/// Body of a POST /portfolio/what-if request.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct WhatIfRequest {
+    #[serde(default)]
+    pub fills: Vec<HypotheticalFill>,
+    #[serde(default)]
+    pub targets: Vec<TargetPosition>,
+}
+
+/// A fill to book as if it had happened. Quantity is signed: positive buys.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct HypotheticalFill {
+    pub symbol: String,
+    pub quantity: i64,
+    #[serde(default)]
+    pub price: Option<Price>,
+    #[serde(default)]
+    pub venue: Option<String>,
+}
+
+/// A position to trade to, whatever the book holds now.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct TargetPosition {
+    pub symbol: String,
+    pub quantity: i64,
+    #[serde(default)]
+    pub price: Option<Price>,
+    #[serde(default)]
+    pub venue: Option<String>,
+}
+
+/// A hypothetical fill as booked: priced and assigned to a venue.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BookedFill {
+    pub symbol: String,
+    pub quantity: i64,
+    pub price: Price,
+    pub venue: String,
+}
+
+/// Notional exposure of a book, with the instruments' multipliers.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ExposureSummary {
+    pub gross: Money,
+    pub net: Money,
+    pub long: Money,
+    pub short: Money,
+    pub by_symbol: HashMap<String, Money>,
+}
+
+/// The same measure of the live book and of the book after the trade.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Comparison<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// The VaR calculator's POST /var/incremental response.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct IncrementalVaR {
+    pub confidence_level: f64,
+    pub var_before: f64,
+    pub var_after: f64,
+    pub incremental_var: f64,
+}
+
+/// A change in one symbol's quantity, for POST /var/incremental. The price
+/// is the value of one unit (mark x multiplier).
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct VaRChange {
+    pub symbol: String,
+    pub quantity: i64,
+    pub price: f64,
+}
+
+/// Body of a POST /portfolio/what-if response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct WhatIfReport {
+    pub fills: Vec<BookedFill>,
+    /// P&L the fills would realize on positions they close.
+    pub realized_pnl: Money,
+    pub exposure: Comparison<ExposureSummary>,
+    pub margin: Comparison<MarginReport>,
+    pub concentration: Comparison<ConcentrationMetrics>,
+    pub var: Option<IncrementalVaR>,
+    pub timestamp_utc: String,
+}
+
+// --- Analysis ---
+
+/// The fills that carry out the request against `positions`: the request's
+/// fills, then one per target for the difference. Errs naming a symbol that
+/// needs a price.
+pub fn resolve(request: &WhatIfRequest, positions: &HashMap<String, Position>) -> Result<Vec<BookedFill>, String> {
+    let mut held: HashMap<&str, i64> = positions.iter().map(|(s, p)| (s.as_str(), p.quantity)).collect();
+    let mut booked = Vec::new();
+    let requested = request.fills.iter().map(|f| (f.symbol.as_str(), f.quantity, false, f.price, f.venue.as_ref()));
+    let targets = request.targets.iter().map(|t| (t.symbol.as_str(), t.quantity, true, t.price, t.venue.as_ref()));
+    for (symbol, quantity, is_target, price, venue) in requested.chain(targets) {
+        let current = held.get(symbol).copied().unwrap_or(0);
+        let quantity = if is_target { quantity - current } else { quantity };
+        if quantity == 0 {
+            continue;
+        }
+        let price = match price.or_else(|| positions.get(symbol).map(|p| p.current_market_price)) {
+            Some(price) if price > Price::ZERO => price,
+            _ => return Err(format!("{} is not held: its fill needs a price.", symbol)),
+        };
+        held.insert(symbol, current + quantity);
+        booked.push(BookedFill {
+            symbol: symbol.to_string(),
+            quantity,
+            price,
+            venue: venue.cloned().unwrap_or_else(|| UNSPECIFIED_VENUE.to_string()),
+        });
+    }
+    Ok(booked)
+}
+
+pub fn exposure(positions: &HashMap<String, Position>) -> ExposureSummary {
+    let by_symbol: HashMap<String, Money> = positions
+        .values()
+        .filter(|p| p.quantity != 0)
+        .map(|p| (p.symbol.clone(), Money::notional(p.current_market_price, Quantity(p.quantity), p.multiplier)))
+        .collect();
+    let long: Money = by_symbol.values().filter(|v| **v > Money::ZERO).copied().sum();
+    let short: Money = by_symbol.values().filter(|v| **v < Money::ZERO).copied().sum();
+    ExposureSummary { gross: long - short, net: long + short, long, short, by_symbol }
+}
+
+/// Net quantity change per symbol, valued at the book-after's marks.
+pub fn var_changes(fills: &[BookedFill], after: &HashMap<String, Position>) -> Vec<VaRChange> {
+    let mut net: HashMap<&str, i64> = HashMap::new();
+    for fill in fills {
+        *net.entry(fill.symbol.as_str()).or_insert(0) += fill.quantity;
+    }
+    let mut changes: Vec<VaRChange> = net
+        .into_iter()
+        .filter(|(_, quantity)| *quantity != 0)
+        .filter_map(|(symbol, quantity)| {
+            let position = after.get(symbol)?;
+            let price = position.current_market_price.to_f64() * position.multiplier.to_f64();
+            Some(VaRChange { symbol: symbol.to_string(), quantity, price })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(symbol: &str, quantity: i64, mark: f64, multiplier: f64) -> (String, Position) {
+        let position = Position {
+            symbol: symbol.to_string(),
+            quantity,
+            current_market_price: Price::from_f64(mark),
+            multiplier: Money::from_f64(multiplier),
+            ..Default::default()
+        };
+        (symbol.to_string(), position)
+    }
+
+    #[test]
+    fn targets_trade_the_difference_after_the_fills() {
+        let positions: HashMap<String, Position> = [held("BTC", 2, 60_000.0, 1.0)].into_iter().collect();
+        let request = WhatIfRequest {
+            fills: vec![HypotheticalFill { symbol: "BTC".to_string(), quantity: 1, price: None, venue: None }],
+            targets: vec![TargetPosition { symbol: "BTC".to_string(), quantity: -1, price: None, venue: None }],
+        };
+        let fills = resolve(&request, &positions).unwrap();
+        assert_eq!(fills.iter().map(|f| f.quantity).collect::<Vec<_>>(), vec![1, -4]);
+        assert_eq!(fills[0].price, Price::from_f64(60_000.0));
+        assert_eq!(fills[1].venue, UNSPECIFIED_VENUE);
+
+        let unpriced = WhatIfRequest {
+            fills: Vec::new(),
+            targets: vec![TargetPosition { symbol: "ETH".to_string(), quantity: 5, price: None, venue: None }],
+        };
+        assert!(resolve(&unpriced, &positions).is_err());
+    }
+
+    #[test]
+    fn exposure_nets_longs_against_shorts_at_the_multiplier() {
+        let positions: HashMap<String, Position> =
+            [held("BTC", 1, 60_000.0, 1.0), held("ESZ25", -2, 5_000.0, 50.0)].into_iter().collect();
+        let summary = exposure(&positions);
+        assert_eq!(summary.long, Money::from_f64(60_000.0));
+        assert_eq!(summary.short, Money::from_f64(-500_000.0));
+        assert_eq!(summary.gross, Money::from_f64(560_000.0));
+        assert_eq!(summary.net, Money::from_f64(-440_000.0));
+    }
+}
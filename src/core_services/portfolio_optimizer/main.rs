@@ -0,0 +1,351 @@
+/*
+ * QuantumArb 2.0 - Core Services: Portfolio Optimizer
+ *
+ * File: src/core_services/portfolio_optimizer/main.rs
+ *
+ * Description:
+ * This microservice suggests how the book should be weighted. Every
+ * QA_OPTIMIZER_INTERVAL_SECS it:
+ *
+ * 1. Takes the current positions from the portfolio manager (GET
+ *    /portfolio), valued at their marks and contract multipliers.
+ * 2. Estimates the covariance of the held symbols from their daily returns
+ *    (the VaR calculator's return store, QA_RETURN_HISTORY_PATH, or a
+ *    simulated history seeded with --seed N or QA_SEED).
+ * 3. Takes each symbol's expected return from the champion ML model's
+ *    up-probability (see `signals.rs`).
+ * 4. Computes target weights by mean-variance or risk-parity optimization
+ *    within the per-symbol, gross and net limits (see `optimizer.rs`).
+ * 5. Publishes a `StrategyInstruction::Rebalance` on 'strategy.instructions'
+ *    with the target weights and the whole-unit orders that would reach
+ *    them, leaving out trades smaller than QA_OPTIMIZER_MIN_TRADE_WEIGHT of
+ *    capital. Nothing is published while the book is on target.
+ *
+ * Weights are fractions of capital: QA_OPTIMIZER_CAPITAL, or else the
+ * book's gross market value, so that a fully invested book keeps its size.
+ *
+ * The latest suggestion, with each symbol's expected return, volatility
+ * and share of the target book's risk, is served on GET /optimizer. GET
+ * /openapi.json serves the OpenAPI document, browsable on GET /docs.
+ *
+ * Configuration (environment):
+ *   QA_PORTFOLIO_MANAGER_URL=            portfolio manager base URL
+ *   QA_OPTIMIZER_METHOD=mean-variance    or risk-parity
+ *   QA_OPTIMIZER_RISK_AVERSION=50        mean-variance, on daily returns
+ *   QA_OPTIMIZER_MAX_WEIGHT=0.5          largest absolute weight per symbol
+ *   QA_OPTIMIZER_MAX_GROSS=1             largest sum of absolute weights
+ *   QA_OPTIMIZER_MAX_NET=1               largest absolute sum of weights
+ *   QA_OPTIMIZER_CAPITAL=                capital the weights are of
+ *   QA_OPTIMIZER_MIN_TRADE_WEIGHT=0.01   smallest weight change traded
+ *   QA_OPTIMIZER_INTERVAL_SECS=300       time between runs
+ */
+
+mod optimizer;
+mod signals;
+
+use optimizer::{Constraints, Covariance, Method};
+use quantumarb_bus::topics;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, ApiSchema};
+use quantumarb_risk::distributions;
+use quantumarb_sim::{Seed, SimRng};
+use quantumarb_types::{PortfolioSnapshot, ReduceOrder, StrategyInstruction, TargetWeight};
+use serde::Serialize;
+use signals::{ModelSignals, ModelView};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::time::{self, Duration};
+use warp::http::StatusCode;
+use warp::Filter;
+
+// --- Configuration ---
+
+const DEFAULT_PORTFOLIO_MANAGER_URL: &str = "http://portfolio-manager.default.svc.cluster.local";
+/// Daily volatility assumed for symbols without a return history.
+const DEFAULT_DAILY_VOLATILITY: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, Serialize, ApiSchema)]
+struct OptimizerConfig {
+    method: Method,
+    risk_aversion: f64,
+    constraints: Constraints,
+    capital: Option<f64>,
+    min_trade_weight: f64,
+    interval_secs: u64,
+}
+
+impl OptimizerConfig {
+    fn from_env() -> OptimizerConfig {
+        let number = |name: &str, default: f64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &f64| *v > 0.0).unwrap_or(default)
+        };
+        let method = std::env::var("QA_OPTIMIZER_METHOD").ok();
+        let method = method.as_deref().and_then(Method::parse).unwrap_or(Method::MeanVariance);
+        OptimizerConfig {
+            method,
+            risk_aversion: number("QA_OPTIMIZER_RISK_AVERSION", 50.0),
+            constraints: Constraints {
+                max_weight: number("QA_OPTIMIZER_MAX_WEIGHT", 0.5),
+                max_gross: number("QA_OPTIMIZER_MAX_GROSS", 1.0),
+                max_net: number("QA_OPTIMIZER_MAX_NET", 1.0),
+                long_only: method == Method::RiskParity,
+            },
+            capital: std::env::var("QA_OPTIMIZER_CAPITAL").ok().and_then(|v| v.parse().ok()).filter(|v| *v > 0.0),
+            min_trade_weight: number("QA_OPTIMIZER_MIN_TRADE_WEIGHT", 0.01),
+            interval_secs: number("QA_OPTIMIZER_INTERVAL_SECS", 300.0) as u64,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// One held symbol: its value per unit and its place in the suggestion.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct AssetTarget {
+    symbol: String,
+    quantity: i64,
+    /// Price times contract multiplier.
+    unit_value: f64,
+    expected_return: f64,
+    volatility: f64,
+    model: ModelView,
+    current_weight: f64,
+    target_weight: f64,
+    target_quantity: i64,
+    /// Share of the target book's variance.
+    risk_contribution: f64,
+}
+
+/// Response body of GET /optimizer.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct RebalanceSuggestion {
+    config: OptimizerConfig,
+    capital: f64,
+    /// Daily expected return and volatility of the target book, as
+    /// fractions of capital.
+    expected_return: f64,
+    volatility: f64,
+    assets: Vec<AssetTarget>,
+    orders: Vec<ReduceOrder>,
+    timestamp_utc: String,
+}
+
+type SharedSuggestion = Arc<RwLock<Option<RebalanceSuggestion>>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Portfolio Optimizer ---");
+
+    let config = OptimizerConfig::from_env();
+    println!(
+        "Method: {} (max weight {:.0}%, gross {:.0}%, net {:.0}%)",
+        config.method.as_str(),
+        config.constraints.max_weight * 100.0,
+        config.constraints.max_gross * 100.0,
+        config.constraints.max_net * 100.0
+    );
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+    let state: SharedSuggestion = Arc::new(RwLock::new(None));
+
+    let (run_state, history_rng) = (state.clone(), seed.stream("portfolio_optimizer.return_history"));
+    tokio::spawn(async move {
+        run_optimizer(config, run_state, history_rng).await;
+    });
+
+    let get = warp::path!("optimizer").and(warp::get()).and(with_state(state)).and_then(handler_get_suggestion);
+
+    println!("API server running at http://127.0.0.1:3044/optimizer");
+    warp::serve(get.or(quantumarb_openapi::routes(api_doc()))).run(([127, 0, 0, 1], 3044)).await;
+}
+
+/// The OpenAPI document of the optimizer endpoint.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Portfolio Optimizer", "Suggested target weights for the book.")
+        .errors::<ErrorBody>()
+        .get::<RebalanceSuggestion>("/optimizer", "The latest rebalance suggestion")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /optimizer.
+async fn handler_get_suggestion(state: SharedSuggestion) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(match state.read().unwrap().as_ref() {
+        Some(suggestion) => warp::reply::with_status(warp::reply::json(suggestion), StatusCode::OK),
+        None => {
+            let rejection = Rejection::new(RejectCode::SystemInvalidRequest, "No optimization has run yet");
+            warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), StatusCode::NOT_FOUND)
+        }
+    })
+}
+
+/// Optimizes the book every interval and publishes the suggestion when it
+/// would trade.
+async fn run_optimizer(config: OptimizerConfig, state: SharedSuggestion, mut rng: SimRng) {
+    let http_client = quantumarb_tenancy::firm_client();
+    let mut models = ModelSignals::from_env().await;
+    println!("Expected returns: {}", models.describe());
+    let portfolio_manager =
+        std::env::var("QA_PORTFOLIO_MANAGER_URL").unwrap_or_else(|_| DEFAULT_PORTFOLIO_MANAGER_URL.to_string());
+    let mut return_history: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut interval = time::interval(Duration::from_secs(config.interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        println!("\nOptimizing the book ({})...", config.method.as_str());
+
+        let url = format!("{}/portfolio", portfolio_manager.trim_end_matches('/'));
+        let snapshot = match http_client.get(&url).send().await {
+            Ok(response) => response.json::<PortfolioSnapshot>().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                println!("  -> No portfolio snapshot ({}); skipping this run.", e);
+                continue;
+            }
+        };
+
+        let mut assets = held_assets(&snapshot);
+        if assets.is_empty() {
+            println!("  -> No open positions to optimize.");
+            continue;
+        }
+        let missing: Vec<(String, f64)> = assets
+            .iter()
+            .filter(|asset| !return_history.contains_key(&asset.symbol))
+            .map(|asset| (asset.symbol.clone(), DEFAULT_DAILY_VOLATILITY))
+            .collect();
+        if !missing.is_empty() {
+            return_history.extend(distributions::load_return_history(&missing, &mut rng));
+        }
+        let histories: Vec<&[f64]> =
+            assets.iter().map(|a| return_history.get(&a.symbol).map_or(&[][..], |h| h.as_slice())).collect();
+        let covariance = Covariance::estimate(&histories, DEFAULT_DAILY_VOLATILITY);
+        for (i, asset) in assets.iter_mut().enumerate() {
+            asset.volatility = covariance.volatility(i);
+            asset.model = models.view(&asset.symbol).await;
+            asset.expected_return =
+                optimizer::expected_return(asset.model.probability_up, asset.model.signal_up, asset.volatility);
+        }
+
+        let suggestion = suggest(&config, assets, &covariance);
+        for asset in &suggestion.assets {
+            println!(
+                "  -> {}: {:+.1}% -> {:+.1}% (expected {:+.2}%/day, risk share {:.0}%)",
+                asset.symbol,
+                asset.current_weight * 100.0,
+                asset.target_weight * 100.0,
+                asset.expected_return * 100.0,
+                asset.risk_contribution * 100.0
+            );
+        }
+        if suggestion.orders.is_empty() {
+            println!("  -> Book is on target.");
+        } else {
+            publish_suggestion(&suggestion);
+        }
+        *state.write().unwrap() = Some(suggestion);
+    }
+}
+
+/// The snapshot's open positions with a value, not yet optimized.
+fn held_assets(snapshot: &PortfolioSnapshot) -> Vec<AssetTarget> {
+    let mut assets: Vec<AssetTarget> = snapshot
+        .positions
+        .values()
+        .filter(|p| p.quantity != 0)
+        .map(|p| AssetTarget {
+            symbol: p.symbol.clone(),
+            quantity: p.quantity,
+            unit_value: p.current_market_price.to_f64() * p.multiplier.to_f64(),
+            expected_return: 0.0,
+            volatility: 0.0,
+            model: ModelView::default(),
+            current_weight: 0.0,
+            target_weight: 0.0,
+            target_quantity: p.quantity,
+            risk_contribution: 0.0,
+        })
+        .filter(|a| a.unit_value > 0.0)
+        .collect();
+    assets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    assets
+}
+
+/// Optimizes `assets` and works out the orders that reach the targets.
+fn suggest(config: &OptimizerConfig, mut assets: Vec<AssetTarget>, covariance: &Covariance) -> RebalanceSuggestion {
+    let gross: f64 = assets.iter().map(|a| (a.quantity as f64 * a.unit_value).abs()).sum();
+    let capital = config.capital.unwrap_or(gross);
+    let expected_returns: Vec<f64> = assets.iter().map(|a| a.expected_return).collect();
+    let weights = match config.method {
+        Method::MeanVariance => {
+            optimizer::mean_variance(&expected_returns, covariance, config.risk_aversion, &config.constraints)
+        }
+        Method::RiskParity => optimizer::risk_parity(covariance, &config.constraints),
+    };
+    let contributions = optimizer::risk_contributions(&weights, covariance);
+
+    let mut orders = Vec::new();
+    for ((asset, weight), contribution) in assets.iter_mut().zip(&weights).zip(contributions) {
+        asset.current_weight = asset.quantity as f64 * asset.unit_value / capital;
+        asset.target_weight = *weight;
+        asset.risk_contribution = contribution;
+        if (asset.target_weight - asset.current_weight).abs() < config.min_trade_weight {
+            continue;
+        }
+        // Whole units, rounded towards zero so no limit is overshot.
+        asset.target_quantity = (weight * capital / asset.unit_value).trunc() as i64;
+        let change = asset.target_quantity - asset.quantity;
+        if change != 0 {
+            let side = if change > 0 { "Buy" } else { "Sell" };
+            let quantity = change.unsigned_abs();
+            orders.push(ReduceOrder { symbol: asset.symbol.clone(), side: side.to_string(), quantity });
+        }
+    }
+    RebalanceSuggestion {
+        config: *config,
+        capital,
+        expected_return: expected_returns.iter().zip(&weights).map(|(r, w)| r * w).sum(),
+        volatility: covariance.variance(&weights).sqrt(),
+        assets,
+        orders,
+        timestamp_utc: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// Simulates publishing the suggestion to the strategy engine.
+fn publish_suggestion(suggestion: &RebalanceSuggestion) {
+    let instruction = StrategyInstruction::Rebalance {
+        reason: format!(
+            "{} targets on {:.2} of capital: expected {:+.3}%/day at {:.2}%/day volatility",
+            suggestion.config.method.as_str(),
+            suggestion.capital,
+            suggestion.expected_return * 100.0,
+            suggestion.volatility * 100.0
+        ),
+        targets: suggestion
+            .assets
+            .iter()
+            .map(|a| TargetWeight {
+                symbol: a.symbol.clone(),
+                current_weight: a.current_weight,
+                target_weight: a.target_weight,
+            })
+            .collect(),
+        orders: suggestion.orders.clone(),
+    };
+    println!(
+        "  -> Publishing {} rebalance order(s) to topic '{}'.",
+        suggestion.orders.len(),
+        topics::STRATEGY_INSTRUCTIONS
+    );
+    quantumarb_bus::publish_json(topics::STRATEGY_INSTRUCTIONS, &instruction);
+}
@@ -0,0 +1,338 @@
+/*
+ * QuantumArb 2.0 - Core Services: Portfolio Construction
+ *
+ * File: src/core_services/portfolio_optimizer/optimizer.rs
+ *
+ * Description:
+ * Target weights for a book, as fractions of its capital (negative for a
+ * short), by one of two methods:
+ *
+ *   mean-variance  maximizes w'mu - (risk_aversion / 2) w'Sigma w by
+ *                  projected gradient ascent: a step along the gradient,
+ *                  then back onto the constraints
+ *   risk-parity    long-only weights with equal contributions to the
+ *                  book's variance, by cyclical coordinate descent on
+ *                  y'Sigma y / 2 - sum(ln y) / n, normalized to the gross
+ *                  limit; expected returns play no part
+ *
+ * The constraints are a cap on each symbol's absolute weight, on the gross
+ * (sum of absolute weights) and on the net (absolute sum of weights). A
+ * point is projected onto all three at once with Dykstra's algorithm, which
+ * converges to the nearest point of the intersection, not just some point
+ * in it. A risk-parity book the caps cut into is no longer exactly parity.
+ *
+ * The covariance is estimated from daily returns over the window all the
+ * symbols share, shrunk a little towards its diagonal so that it stays
+ * well-conditioned on short histories.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+
+/// Weight of the diagonal in the shrunk covariance.
+const SHRINKAGE: f64 = 0.1;
+/// Returns a symbol needs before its covariances are estimated rather
+/// than assumed zero.
+const MIN_OBSERVATIONS: usize = 20;
+const MAX_ITERATIONS: usize = 10_000;
+const PROJECTION_ITERATIONS: usize = 500;
+const TOLERANCE: f64 = 1e-12;
+/// Up-probability taken from a model that gives a signal but no probability.
+const SIGNAL_ONLY_PROBABILITY: f64 = 0.55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Method {
+    MeanVariance,
+    RiskParity,
+}
+
+impl Method {
+    pub fn parse(value: &str) -> Option<Method> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mean-variance" | "mv" => Some(Method::MeanVariance),
+            "risk-parity" | "rp" => Some(Method::RiskParity),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::MeanVariance => "mean-variance",
+            Method::RiskParity => "risk-parity",
+        }
+    }
+}
+
+/// Limits on the target weights, as fractions of capital.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct Constraints {
+    pub max_weight: f64,
+    pub max_gross: f64,
+    pub max_net: f64,
+    pub long_only: bool,
+}
+
+impl Constraints {
+    /// Moves `weights` to the nearest point that meets every limit.
+    pub fn project(&self, weights: &mut [f64]) {
+        let n = weights.len();
+        let mut increments = [vec![0.0; n], vec![0.0; n], vec![0.0; n]];
+        for _ in 0..PROJECTION_ITERATIONS {
+            let previous = weights.to_vec();
+            for (set, increment) in increments.iter_mut().enumerate() {
+                let mut point: Vec<f64> = weights.iter().zip(increment.iter()).map(|(w, i)| w + i).collect();
+                let before = point.clone();
+                match set {
+                    0 => self.project_box(&mut point),
+                    1 => project_l1_ball(&mut point, self.max_gross),
+                    _ => project_net(&mut point, self.max_net),
+                }
+                increment.iter_mut().zip(before.iter().zip(&point)).for_each(|(i, (b, p))| *i = b - p);
+                weights.copy_from_slice(&point);
+            }
+            if max_change(&previous, weights) < TOLERANCE {
+                break;
+            }
+        }
+    }
+
+    fn project_box(&self, weights: &mut [f64]) {
+        let lower = if self.long_only { 0.0 } else { -self.max_weight };
+        weights.iter_mut().for_each(|w| *w = w.clamp(lower, self.max_weight));
+    }
+}
+
+/// The nearest point with absolute weights summing to at most `radius`.
+fn project_l1_ball(weights: &mut [f64], radius: f64) {
+    if weights.iter().map(|w| w.abs()).sum::<f64>() <= radius {
+        return;
+    }
+    let mut sizes: Vec<f64> = weights.iter().map(|w| w.abs()).collect();
+    sizes.sort_by(|a, b| b.total_cmp(a));
+    // Shrink every size by the threshold that brings their sum to the radius.
+    let (mut total, mut threshold) = (0.0, 0.0);
+    for (k, size) in sizes.iter().enumerate() {
+        total += size;
+        let candidate = (total - radius) / (k + 1) as f64;
+        if *size > candidate {
+            threshold = candidate;
+        }
+    }
+    weights.iter_mut().for_each(|w| *w = w.signum() * (w.abs() - threshold).max(0.0));
+}
+
+/// The nearest point whose weights sum to within `limit` of zero.
+fn project_net(weights: &mut [f64], limit: f64) {
+    let net: f64 = weights.iter().sum();
+    let excess = net - net.clamp(-limit, limit);
+    let shift = excess / weights.len() as f64;
+    weights.iter_mut().for_each(|w| *w -= shift);
+}
+
+fn max_change(before: &[f64], after: &[f64]) -> f64 {
+    before.iter().zip(after).map(|(b, a)| (b - a).abs()).fold(0.0, f64::max)
+}
+
+/// A symmetric covariance matrix of daily returns, row-major.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Covariance {
+    n: usize,
+    values: Vec<f64>,
+}
+
+impl Covariance {
+    #[cfg(test)]
+    fn from_rows(rows: &[Vec<f64>]) -> Covariance {
+        Covariance { n: rows.len(), values: rows.concat() }
+    }
+
+    /// Estimates the covariance of symbols from their daily returns, oldest
+    /// first, over the latest window they all have. A symbol with fewer than
+    /// MIN_OBSERVATIONS returns gets `default_volatility` and no covariance.
+    pub fn estimate(histories: &[&[f64]], default_volatility: f64) -> Covariance {
+        let n = histories.len();
+        let estimated: Vec<usize> = (0..n).filter(|&i| histories[i].len() >= MIN_OBSERVATIONS).collect();
+        let window = estimated.iter().map(|&i| histories[i].len()).min().unwrap_or(0);
+        let recent = |i: usize| &histories[i][histories[i].len() - window..];
+        let means: Vec<f64> = (0..n)
+            .map(|i| if estimated.contains(&i) { recent(i).iter().sum::<f64>() / window as f64 } else { 0.0 })
+            .collect();
+        let mut values = vec![0.0; n * n];
+        for i in 0..n {
+            values[i * n + i] = default_volatility * default_volatility;
+        }
+        for (a, &i) in estimated.iter().enumerate() {
+            for &j in &estimated[a..] {
+                let sum: f64 = recent(i).iter().zip(recent(j)).map(|(x, y)| (x - means[i]) * (y - means[j])).sum();
+                let shrink = if i == j { 1.0 } else { 1.0 - SHRINKAGE };
+                let value = sum / (window - 1) as f64 * shrink;
+                values[i * n + j] = value;
+                values[j * n + i] = value;
+            }
+        }
+        Covariance { n, values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.values[i * self.n + j]
+    }
+
+    pub fn volatility(&self, i: usize) -> f64 {
+        self.get(i, i).sqrt()
+    }
+
+    /// Sigma w.
+    pub fn times(&self, weights: &[f64]) -> Vec<f64> {
+        self.values.chunks(self.n).map(|row| row.iter().zip(weights).map(|(c, w)| c * w).sum()).collect()
+    }
+
+    /// w' Sigma w.
+    pub fn variance(&self, weights: &[f64]) -> f64 {
+        self.times(weights).iter().zip(weights).map(|(s, w)| s * w).sum()
+    }
+
+    /// An upper bound on the largest eigenvalue: the largest absolute row
+    /// sum.
+    fn spectral_bound(&self) -> f64 {
+        self.values.chunks(self.n).map(|row| row.iter().map(|c| c.abs()).sum::<f64>()).fold(0.0, f64::max)
+    }
+}
+
+/// The daily return implied by a model's up-probability for a symbol moving
+/// one `volatility` a day: p - (1 - p) of it. `signal_up` stands in for a
+/// probability the model did not give.
+pub fn expected_return(probability_up: Option<f64>, signal_up: Option<bool>, volatility: f64) -> f64 {
+    let probability = match (probability_up, signal_up) {
+        (Some(p), _) => p.clamp(0.0, 1.0),
+        (None, Some(true)) => SIGNAL_ONLY_PROBABILITY,
+        (None, Some(false)) => 1.0 - SIGNAL_ONLY_PROBABILITY,
+        (None, None) => 0.5,
+    };
+    (2.0 * probability - 1.0) * volatility
+}
+
+/// Mean-variance target weights.
+pub fn mean_variance(
+    expected_returns: &[f64],
+    covariance: &Covariance,
+    risk_aversion: f64,
+    constraints: &Constraints,
+) -> Vec<f64> {
+    let n = covariance.len();
+    let mut weights = vec![0.0; n];
+    let bound = risk_aversion * covariance.spectral_bound();
+    if n == 0 || bound <= 0.0 {
+        return weights;
+    }
+    let step = 1.0 / bound;
+    for _ in 0..MAX_ITERATIONS {
+        let previous = weights.clone();
+        let risk = covariance.times(&weights);
+        for i in 0..n {
+            weights[i] += step * (expected_returns[i] - risk_aversion * risk[i]);
+        }
+        constraints.project(&mut weights);
+        if max_change(&previous, &weights) < TOLERANCE {
+            break;
+        }
+    }
+    weights
+}
+
+/// Risk-parity target weights, long only, summing to the tighter of the
+/// gross and net limits before the caps apply.
+pub fn risk_parity(covariance: &Covariance, constraints: &Constraints) -> Vec<f64> {
+    let n = covariance.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let budget = 1.0 / n as f64;
+    let mut y: Vec<f64> = (0..n).map(|i| 1.0 / covariance.volatility(i).max(f64::MIN_POSITIVE)).collect();
+    for _ in 0..MAX_ITERATIONS {
+        let previous = y.clone();
+        for i in 0..n {
+            let variance = covariance.get(i, i);
+            let others: f64 = (0..n).filter(|&j| j != i).map(|j| covariance.get(i, j) * y[j]).sum();
+            y[i] = (-others + (others * others + 4.0 * variance * budget).sqrt()) / (2.0 * variance);
+        }
+        if max_change(&previous, &y) < TOLERANCE * y.iter().fold(0.0, |m: f64, v| m.max(*v)) {
+            break;
+        }
+    }
+    let total: f64 = y.iter().sum();
+    let invested = constraints.max_gross.min(constraints.max_net);
+    let mut weights: Vec<f64> = y.iter().map(|v| v / total * invested).collect();
+    constraints.project(&mut weights);
+    weights
+}
+
+/// Each weight's share of the book's variance, w_i (Sigma w)_i / w'Sigma w;
+/// they sum to one.
+pub fn risk_contributions(weights: &[f64], covariance: &Covariance) -> Vec<f64> {
+    let variance = covariance.variance(weights);
+    if variance <= 0.0 {
+        return vec![0.0; weights.len()];
+    }
+    covariance.times(weights).iter().zip(weights).map(|(s, w)| s * w / variance).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_within(weights: &[f64], constraints: &Constraints) {
+        let slack = 1e-9;
+        assert!(weights.iter().all(|w| w.abs() <= constraints.max_weight + slack), "{:?}", weights);
+        assert!(weights.iter().map(|w| w.abs()).sum::<f64>() <= constraints.max_gross + slack, "{:?}", weights);
+        assert!(weights.iter().sum::<f64>().abs() <= constraints.max_net + slack, "{:?}", weights);
+    }
+
+    #[test]
+    fn weights_follow_the_signals_within_the_limits_and_parity_balances_risk() {
+        // BTC 2%, ETH 3% and SOL 5% a day; BTC and ETH 0.6 correlated.
+        let covariance = Covariance::from_rows(&[
+            vec![0.0004, 0.00036, 0.0],
+            vec![0.00036, 0.0009, 0.0],
+            vec![0.0, 0.0, 0.0025],
+        ]);
+        let loose = Constraints { max_weight: 10.0, max_gross: 10.0, max_net: 10.0, long_only: false };
+        let signals = [0.004, -0.006, 0.0];
+        // Unconstrained, w = Sigma^-1 mu / risk_aversion.
+        let weights = mean_variance(&signals, &covariance, 20.0, &loose);
+        let determinant = 0.0004 * 0.0009 - 0.00036 * 0.00036;
+        let btc = (0.0009 * 0.004 + 0.00036 * 0.006) / determinant / 20.0;
+        assert!((weights[0] - btc).abs() < 1e-6 && weights[1] < 0.0 && weights[2].abs() < 1e-9, "{:?}", weights);
+
+        let tight = Constraints { max_weight: 0.4, max_gross: 0.6, max_net: 0.1, long_only: false };
+        let weights = mean_variance(&signals, &covariance, 20.0, &tight);
+        assert_within(&weights, &tight);
+        assert!(weights[0] > 0.0 && weights[1] < 0.0, "{:?}", weights);
+        assert!((weights.iter().map(|w| w.abs()).sum::<f64>() - 0.6).abs() < 1e-6, "{:?}", weights);
+
+        let budget = Constraints { max_weight: 1.0, max_gross: 1.0, max_net: 1.0, long_only: true };
+        let weights = risk_parity(&covariance, &budget);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9, "{:?}", weights);
+        for contribution in risk_contributions(&weights, &covariance) {
+            assert!((contribution - 1.0 / 3.0).abs() < 1e-6, "{:?}", weights);
+        }
+        let capped = Constraints { max_weight: 0.3, ..budget };
+        let weights = risk_parity(&covariance, &capped);
+        assert_within(&weights, &capped);
+        assert!(weights.iter().all(|w| *w >= 0.0), "{:?}", weights);
+
+        // Two years of returns and one symbol with too few to estimate.
+        let btc: Vec<f64> = (0..504).map(|t| if t % 2 == 0 { 0.02 } else { -0.02 }).collect();
+        let eth: Vec<f64> = btc.iter().map(|r| 1.5 * r).collect();
+        let estimated = Covariance::estimate(&[&btc, &eth, &[0.01; 5]], 0.05);
+        assert!((estimated.volatility(0) - 0.02).abs() < 1e-4 && (estimated.volatility(1) - 0.03).abs() < 1e-4);
+        // 1.5 x the BTC variance of 0.0004, over n - 1.
+        assert!((estimated.get(0, 1) / 0.0006 - (1.0 - SHRINKAGE)).abs() < 5e-3);
+        assert!((estimated.volatility(2) - 0.05).abs() < 1e-12 && estimated.get(0, 2) == 0.0);
+        assert!((expected_return(Some(0.6), None, 0.02) - 0.004).abs() < 1e-12);
+    }
+}
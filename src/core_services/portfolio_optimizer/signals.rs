@@ -0,0 +1,121 @@
+/*
+ * QuantumArb 2.0 - Core Services: Model Signals for Portfolio Construction
+ *
+ * File: src/core_services/portfolio_optimizer/signals.rs
+ *
+ * Description:
+ * The expected returns behind a mean-variance book come from the champion
+ * ML model the strategy engine trades on: the inference server's POST
+ * /predict is asked about each symbol with the symbol's latest snapshot in
+ * the feature store, the same inputs the strategy engine sends, and its
+ * up-probability becomes an expected return (`optimizer::expected_return`).
+ *
+ * A symbol without features, or a model that does not answer, is neutral:
+ * no expected return, so the optimizer only weighs its risk. With no model
+ * or feature store configured every symbol is neutral and mean-variance
+ * reduces to minimum variance.
+ *
+ * Configuration (environment):
+ *   QA_MODEL_CHAMPION_URL=            inference server /predict endpoint
+ *   QA_FEATURE_REDIS_URL=             feature store
+ *   QA_MODEL_TIMEOUT_MS=50            per-request timeout
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Request body of the inference server's POST /predict.
+#[derive(Debug, Clone, Serialize)]
+struct PredictionFeatures {
+    news_sentiment: f64,
+    mavg_spread: f64,
+}
+
+/// Response body of the inference server's POST /predict.
+#[derive(Debug, Clone, Deserialize)]
+struct PredictionResponse {
+    signal: String,
+    #[serde(default)]
+    probability: Option<f64>,
+}
+
+/// What the model says about one symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, ApiSchema)]
+pub struct ModelView {
+    pub signal_up: Option<bool>,
+    pub probability_up: Option<f64>,
+}
+
+pub struct ModelSignals {
+    client: reqwest::Client,
+    champion_url: Option<String>,
+    store: Option<redis::aio::MultiplexedConnection>,
+}
+
+impl ModelSignals {
+    pub async fn from_env() -> ModelSignals {
+        let url = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let timeout = std::env::var("QA_MODEL_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(50);
+        let client = reqwest::Client::builder().timeout(Duration::from_millis(timeout)).build().unwrap();
+        let store = match url("QA_FEATURE_REDIS_URL") {
+            Some(store_url) => {
+                let connection = match redis::Client::open(store_url.as_str()) {
+                    Ok(client) => client.get_multiplexed_async_connection().await,
+                    Err(e) => Err(e),
+                };
+                connection.map_err(|e| println!("Feature store unavailable at {} ({}).", store_url, e)).ok()
+            }
+            None => None,
+        };
+        ModelSignals { client, champion_url: url("QA_MODEL_CHAMPION_URL"), store }
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.champion_url, &self.store) {
+            (Some(url), Some(_)) => format!("champion model at {}", url),
+            (Some(_), None) => "no feature store; every symbol neutral".to_string(),
+            (None, _) => "no champion model; every symbol neutral".to_string(),
+        }
+    }
+
+    /// The champion's view of `symbol`; neutral when it cannot be asked.
+    pub async fn view(&mut self, symbol: &str) -> ModelView {
+        match self.predict(symbol).await {
+            Ok(view) => view,
+            Err(e) => {
+                println!("  -> No model signal for {} ({}); neutral.", symbol, e);
+                ModelView::default()
+            }
+        }
+    }
+
+    async fn predict(&mut self, symbol: &str) -> Result<ModelView, String> {
+        let (Some(url), Some(store)) = (&self.champion_url, &mut self.store) else {
+            return Ok(ModelView::default());
+        };
+        let snapshot = quantumarb_features::read_latest(store, symbol)
+            .await
+            .map_err(|e| format!("feature store read failed: {}", e))?
+            .ok_or_else(|| format!("no features stored for {}", symbol))?;
+        let features = PredictionFeatures {
+            news_sentiment: snapshot.sentiment_5m.unwrap_or(0.0),
+            mavg_spread: snapshot.mavg_spread.ok_or_else(|| format!("not enough price history for {}", symbol))?,
+        };
+        let response = self.client.post(url).json(&features).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        let prediction = response.json::<PredictionResponse>().await.map_err(|e| e.to_string())?;
+        let signal_up = match prediction.signal.as_str() {
+            "BUY" => Some(true),
+            "SELL" => Some(false),
+            _ => None,
+        };
+        Ok(ModelView { signal_up, probability_up: prediction.probability })
+    }
+}
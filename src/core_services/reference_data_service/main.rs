@@ -0,0 +1,248 @@
+/*
+ * QuantumArb 2.0 - Core Services: Reference Data Service
+ *
+ * File: src/core_services/reference_data_service/main.rs
+ *
+ * Description:
+ * This microservice owns the instrument definitions (symbol, venue, tick
+ * size, lot size, multiplier, currency, asset class) behind the bare
+ * instrument ids carried on the bus. Definitions are loaded at startup from
+ * QA_REFDATA_PATH (or the built-in set in `quantumarb-refdata`).
+ *
+ * Its primary role is to:
+ * 1. Serve definitions over HTTP:
+ *    - GET /instruments                    -> every definition
+ *    - GET /instruments/{id}               -> one definition by id
+ *    - GET /instruments/symbol/{symbol}    -> one definition by symbol
+ *    - PUT /instruments/{id}               -> add or change a definition
+ * 2. Publish every changed definition on 'reference.instruments', and the
+ *    full set at startup, so consumers (the risk gateway's tick-size checks,
+ *    the portfolio manager's P&L multipliers) stay current without polling.
+ *
+ * Each change bumps the definition's version. Consumers load the full set
+ * from GET /instruments when they start and then apply bus updates.
+ *
+ * It also owns the symbology: each venue's symbols, ids and aliases for an
+ * instrument, with the period each is valid for (see
+ * `quantumarb_refdata::symbology`), loaded from QA_SYMBOLOGY_PATH or the
+ * built-in set:
+ *    - GET  /symbology                   -> every venue mapping
+ *    - GET  /symbology/{venue}/{name}    -> the mapping a venue symbol, venue
+ *                                           id or alias names now
+ *    - POST /symbology                   -> add a mapping
+ * Added mappings are published on 'reference.symbology'.
+ *
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
+ */
+
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_refdata::symbology::{self, Symbology, VenueMapping};
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use std::sync::{Arc, Mutex};
+use warp::http::StatusCode;
+use warp::Filter;
+
+// --- Data Structures ---
+
+type SharedReferenceData = Arc<Mutex<ReferenceData>>;
+type SharedSymbology = Arc<Mutex<Symbology>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Reference Data Service ---");
+
+    let data = ReferenceData::new(quantumarb_refdata::load_from_env());
+    let definitions = data.all();
+    println!("Loaded {} instrument definitions.", definitions.len());
+    for definition in &definitions {
+        publish_update(definition);
+    }
+    let state: SharedReferenceData = Arc::new(Mutex::new(data));
+    let symbology = Symbology::new(symbology::load_mappings_from_env());
+    println!("Loaded {} venue mappings.", symbology.mappings().len());
+    let symbology: SharedSymbology = Arc::new(Mutex::new(symbology));
+
+    // --- API Endpoints for instrument lookup and maintenance ---
+    let list = warp::path!("instruments")
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_list_instruments);
+    let get_by_id = warp::path!("instruments" / u32)
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_get_instrument);
+    let get_by_symbol = warp::path!("instruments" / "symbol" / String)
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(handler_get_instrument_by_symbol);
+    let put = warp::path!("instruments" / u32)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_state(state.clone()))
+        .and_then(handler_put_instrument);
+
+    // --- API Endpoints for venue symbology ---
+    let list_mappings = warp::path!("symbology")
+        .and(warp::get())
+        .and(with_state(symbology.clone()))
+        .and_then(handler_list_mappings);
+    let resolve = warp::path!("symbology" / String / String)
+        .and(warp::get())
+        .and(with_state(symbology.clone()))
+        .and_then(handler_resolve_mapping);
+    let add_mapping = warp::path!("symbology")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(state))
+        .and(with_state(symbology))
+        .and_then(handler_add_mapping);
+
+    println!("API server running at http://127.0.0.1:3039/instruments");
+    let instruments = list.or(get_by_symbol).or(get_by_id).or(put);
+    let routes = instruments.or(list_mappings).or(resolve).or(add_mapping).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3039)).await;
+}
+
+/// The OpenAPI document of the instrument and symbology endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Reference Data Service", "Instrument definitions and venue symbology.")
+        .errors::<ErrorBody>()
+        .get::<Vec<InstrumentDefinition>>("/instruments", "Every definition")
+        .operation(
+            Operation::get("/instruments/{id}", "One definition by id")
+                .path_param::<u32>("id")
+                .response::<InstrumentDefinition>(),
+        )
+        .get::<InstrumentDefinition>("/instruments/symbol/{symbol}", "One definition by symbol")
+        .operation(
+            Operation::put("/instruments/{id}", "Add or change a definition")
+                .path_param::<u32>("id")
+                .body::<InstrumentDefinition>()
+                .response::<InstrumentDefinition>(),
+        )
+        .get::<Vec<VenueMapping>>("/symbology", "Every venue mapping")
+        .get::<VenueMapping>("/symbology/{venue}/{name}", "The mapping a venue symbol, venue id or alias names now")
+        .operation(
+            Operation::post("/symbology", "Add a mapping")
+                .body::<VenueMapping>()
+                .response::<VenueMapping>()
+                .status(201),
+        )
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+fn error_reply(rejection: Rejection, status: StatusCode) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+fn definition_reply(definition: Option<&InstrumentDefinition>, key: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    match definition {
+        Some(definition) => warp::reply::with_status(warp::reply::json(definition), StatusCode::OK),
+        None => error_reply(
+            Rejection::new(RejectCode::SystemInvalidRequest, format!("Unknown instrument {}", key)),
+            StatusCode::NOT_FOUND,
+        ),
+    }
+}
+
+/// Handler for GET /instruments.
+async fn handler_list_instruments(state: SharedReferenceData) -> Result<impl warp::Reply, warp::Rejection> {
+    let definitions = state.lock().unwrap().all();
+    Ok(warp::reply::json(&definitions))
+}
+
+/// Handler for GET /instruments/{id}.
+async fn handler_get_instrument(instrument_id: u32, state: SharedReferenceData) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = state.lock().unwrap();
+    Ok(definition_reply(data.get(instrument_id), instrument_id.to_string()))
+}
+
+/// Handler for GET /instruments/symbol/{symbol}.
+async fn handler_get_instrument_by_symbol(symbol: String, state: SharedReferenceData) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = state.lock().unwrap();
+    Ok(definition_reply(data.by_symbol(&symbol), symbol))
+}
+
+/// Handler for PUT /instruments/{id}. The path id wins over the body's, and
+/// the version is assigned here.
+async fn handler_put_instrument(
+    instrument_id: u32,
+    mut definition: InstrumentDefinition,
+    state: SharedReferenceData,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    definition.instrument_id = instrument_id;
+    if let Err(reason) = definition.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason), StatusCode::BAD_REQUEST));
+    }
+
+    let mut data = state.lock().unwrap();
+    if let Some(other) = data.by_symbol(&definition.symbol).filter(|d| d.instrument_id != instrument_id) {
+        let message = format!("Symbol {} already belongs to instrument {}", definition.symbol, other.instrument_id);
+        return Ok(error_reply(Rejection::new(RejectCode::SystemConflict, message), StatusCode::CONFLICT));
+    }
+    definition.version = data.get(instrument_id).map(|d| d.version).unwrap_or(0) + 1;
+    data.apply(definition.clone());
+    println!("Instrument {} ({}) updated to version {}.", instrument_id, definition.symbol, definition.version);
+    publish_update(&definition);
+
+    Ok(warp::reply::with_status(warp::reply::json(&definition), StatusCode::OK))
+}
+
+/// Handler for GET /symbology.
+async fn handler_list_mappings(symbology: SharedSymbology) -> Result<impl warp::Reply, warp::Rejection> {
+    let symbology = symbology.lock().unwrap();
+    Ok(warp::reply::json(&symbology.mappings()))
+}
+
+/// Handler for GET /symbology/{venue}/{name}.
+async fn handler_resolve_mapping(
+    venue: String,
+    name: String,
+    symbology: SharedSymbology,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let symbology = symbology.lock().unwrap();
+    Ok(match symbology.resolve(&venue, &name, chrono::Utc::now()) {
+        Some(mapping) => warp::reply::with_status(warp::reply::json(mapping), StatusCode::OK),
+        None => error_reply(
+            Rejection::new(RejectCode::SystemInvalidRequest, format!("{} has no instrument named {}", venue, name)),
+            StatusCode::NOT_FOUND,
+        ),
+    })
+}
+
+/// Handler for POST /symbology. The instrument must be defined.
+async fn handler_add_mapping(
+    mapping: VenueMapping,
+    state: SharedReferenceData,
+    symbology: SharedSymbology,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if state.lock().unwrap().get(mapping.instrument_id).is_none() {
+        let message = format!("Unknown instrument {}", mapping.instrument_id);
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, message), StatusCode::BAD_REQUEST));
+    }
+    if let Err(reason) = mapping.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason), StatusCode::BAD_REQUEST));
+    }
+    if let Err(reason) = symbology.lock().unwrap().add(mapping.clone()) {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemConflict, reason), StatusCode::CONFLICT));
+    }
+    println!("{} {} now maps to instrument {}.", mapping.venue, mapping.venue_symbol, mapping.instrument_id);
+    quantumarb_bus::publish_json(symbology::SYMBOLOGY_TOPIC, &mapping);
+
+    Ok(warp::reply::with_status(warp::reply::json(&mapping), StatusCode::CREATED))
+}
+
+/// Simulates publishing a definition to the internal message bus.
+fn publish_update(definition: &InstrumentDefinition) {
+    quantumarb_bus::publish_json(quantumarb_refdata::UPDATES_TOPIC, definition);
+}
@@ -0,0 +1,159 @@
+/*
+ * QuantumArb 2.0 - Core Services: Status Gateway
+ *
+ * File: src/core_services/status_gateway/main.rs
+ *
+ * Description:
+ * This microservice is the one address dashboards need: it aggregates the
+ * platform's state from the services behind it (VaR, portfolio, alerts,
+ * latency paths, open orders, feed health and clocks) into a single
+ * response, so a dashboard does not have to know every internal port (see
+ * `status.rs`).
+ *
+ * - GET /status: every section, with its state (OK, STALE, UNAVAILABLE), the
+ *   service's body and when it was fetched, and `healthy` when all are OK.
+ * - GET /status/{section}: one section.
+ *
+ * Responses are cached for QA_STATUS_CACHE_MS, so polling dashboards do not
+ * multiply the load on the services behind the gateway.
+ *
+ * Every request must carry one of the configured bearer tokens
+ * (Authorization: Bearer <token>); others are answered 401 with
+ * SYSTEM_UNAUTHORIZED. With no tokens configured every request is refused.
+ * The API's OpenAPI document (GET /openapi.json, browsable on GET /docs)
+ * needs no token.
+ *
+ * Configuration (environment):
+ *   QA_STATUS_TOKENS=           comma-separated bearer tokens
+ *   QA_STATUS_CACHE_MS=1000     how long an aggregated status is served
+ *   QA_STATUS_TIMEOUT_MS=500    per-service request timeout
+ *   QA_*_URL                    service base URLs, see `status.rs`
+ */
+
+mod status;
+
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::ApiDoc;
+use status::{PlatformStatus, Section, StatusCache, SOURCES};
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::Filter;
+
+// --- Authentication ---
+
+/// The bearer tokens the gateway accepts.
+#[derive(Clone)]
+struct Tokens(Arc<Vec<String>>);
+
+impl Tokens {
+    fn from_env() -> Tokens {
+        let tokens = std::env::var("QA_STATUS_TOKENS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        Tokens(Arc::new(tokens))
+    }
+
+    /// Whether an Authorization header carries an accepted token.
+    fn accepts(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = authorization.and_then(|h| h.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        self.0.iter().any(|accepted| constant_time_eq(accepted.as_bytes(), token.trim().as_bytes()))
+    }
+}
+
+/// Compares without returning early, so response times do not reveal how
+/// much of a token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Status Gateway ---");
+
+    let tokens = Tokens::from_env();
+    if tokens.0.is_empty() {
+        println!("No QA_STATUS_TOKENS configured; every request will be refused.");
+    }
+    let cache = Arc::new(StatusCache::from_env());
+    let sections: Vec<&str> = SOURCES.iter().map(|s| s.section).collect();
+    println!("Aggregating {} (cached {:?}).", sections.join(", "), cache.ttl());
+
+    let get_status = warp::path!("status")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_state(tokens.clone()))
+        .and(with_state(cache.clone()))
+        .and_then(handler_get_status);
+
+    let get_section = warp::path!("status" / String)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_state(tokens))
+        .and(with_state(cache))
+        .and_then(handler_get_section);
+
+    println!("API server running at http://127.0.0.1:3046/status");
+    let routes = get_status.or(get_section).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3046)).await;
+}
+
+/// The OpenAPI document of the status endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Status Gateway", "The platform's state in one response. Requires Authorization: Bearer <token>.")
+        .errors::<ErrorBody>()
+        .get::<PlatformStatus>("/status", "Every section of the platform status")
+        .get::<Section>("/status/{section}", "One section")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+fn error(status: StatusCode, code: RejectCode, message: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(Rejection::new(code, message))), status)
+}
+
+fn unauthorized() -> warp::reply::WithStatus<warp::reply::Json> {
+    error(StatusCode::UNAUTHORIZED, RejectCode::SystemUnauthorized, "a valid bearer token is required".to_string())
+}
+
+/// Handler for GET /status.
+async fn handler_get_status(
+    authorization: Option<String>,
+    tokens: Tokens,
+    cache: Arc<StatusCache>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !tokens.accepts(authorization.as_deref()) {
+        return Ok(unauthorized());
+    }
+    let status = cache.status().await;
+    Ok(warp::reply::with_status(warp::reply::json(&status), StatusCode::OK))
+}
+
+/// Handler for GET /status/{section}.
+async fn handler_get_section(
+    name: String,
+    authorization: Option<String>,
+    tokens: Tokens,
+    cache: Arc<StatusCache>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !tokens.accepts(authorization.as_deref()) {
+        return Ok(unauthorized());
+    }
+    if !SOURCES.iter().any(|s| s.section == name) {
+        let message = format!("no status section '{}'", name);
+        return Ok(error(StatusCode::NOT_FOUND, RejectCode::SystemInvalidRequest, message));
+    }
+    let status = cache.status().await;
+    Ok(warp::reply::with_status(warp::reply::json(&status.sections[&name]), StatusCode::OK))
+}
@@ -0,0 +1,254 @@
+/*
+ * QuantumArb 2.0 - Core Services: Platform Status Aggregation
+ *
+ * File: src/core_services/status_gateway/status.rs
+ *
+ * Description:
+ * Collects one section of platform state from each service behind the
+ * status gateway:
+ *
+ *   var           var calculator          GET /var
+ *   portfolio     portfolio manager       GET /portfolio
+ *   alerts        trade surveillance      GET /alerts
+ *   latency_paths latency oracle          GET /paths
+ *   open_orders   exchange gateway        GET /orders/open
+ *   feed_health   data quality monitor    GET /data-quality
+ *   clocks        clock monitor           GET /clocks
+ *
+ * The sections are fetched in parallel, and the result is cached for
+ * QA_STATUS_CACHE_MS: however many dashboards poll, each service is asked at
+ * most once per period, and concurrent requests for an expired cache wait
+ * for a single refresh. A section whose service does not answer keeps its
+ * last good body, marked STALE with the error and the time it was fetched;
+ * one that never answered is UNAVAILABLE.
+ *
+ * Each service's base URL is its in-cluster address unless the variable
+ * named in `SOURCES` overrides it.
+ *
+ * Configuration (environment):
+ *   QA_STATUS_CACHE_MS=1000      how long an aggregated status is served
+ *   QA_STATUS_TIMEOUT_MS=500     per-service request timeout
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// --- Sources ---
+
+/// A service the gateway reads one section from.
+pub struct Source {
+    pub section: &'static str,
+    env: &'static str,
+    default: &'static str,
+    path: &'static str,
+}
+
+impl Source {
+    fn url(&self) -> String {
+        let base = std::env::var(self.env).unwrap_or_else(|_| self.default.to_string());
+        format!("{}{}", base.trim_end_matches('/'), self.path)
+    }
+}
+
+pub const SOURCES: [Source; 7] = [
+    Source {
+        section: "var",
+        env: "QA_VAR_CALCULATOR_URL",
+        default: "http://var-calculator.default.svc.cluster.local",
+        path: "/var",
+    },
+    Source {
+        section: "portfolio",
+        env: "QA_PORTFOLIO_MANAGER_URL",
+        default: "http://portfolio-manager.default.svc.cluster.local",
+        path: "/portfolio",
+    },
+    Source {
+        section: "alerts",
+        env: "QA_SURVEILLANCE_URL",
+        default: "http://trade-surveillance-service.default.svc.cluster.local",
+        path: "/alerts",
+    },
+    Source {
+        section: "latency_paths",
+        env: "QA_LATENCY_ORACLE_URL",
+        default: "http://latency-oracle.default.svc.cluster.local",
+        path: "/paths",
+    },
+    Source {
+        section: "open_orders",
+        env: "QA_EXCHANGE_GATEWAY_URL",
+        default: "http://exchange-gateway.default.svc.cluster.local",
+        path: "/orders/open",
+    },
+    Source {
+        section: "feed_health",
+        env: "QA_DATA_QUALITY_URL",
+        default: "http://data-quality-monitor.default.svc.cluster.local",
+        path: "/data-quality",
+    },
+    Source {
+        section: "clocks",
+        env: "QA_CLOCK_MONITOR_URL",
+        default: "http://clock-monitor.default.svc.cluster.local",
+        path: "/clocks",
+    },
+];
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SectionState {
+    Ok,
+    /// The service did not answer; the body is the last one it returned.
+    Stale,
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Section {
+    pub state: SectionState,
+    pub url: String,
+    /// The service's response body, as it returned it.
+    pub data: Option<Value>,
+    pub error: Option<String>,
+    pub fetched_utc: Option<DateTime<Utc>>,
+    pub latency_ms: Option<u64>,
+}
+
+/// Body of a GET /status response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct PlatformStatus {
+    /// Every section is OK.
+    pub healthy: bool,
+    pub sections: BTreeMap<String, Section>,
+    pub refreshed_utc: DateTime<Utc>,
+}
+
+// --- Aggregation ---
+
+pub struct StatusCache {
+    client: reqwest::Client,
+    ttl: Duration,
+    latest: Mutex<Option<(Instant, PlatformStatus)>>,
+}
+
+impl StatusCache {
+    pub fn from_env() -> StatusCache {
+        let millis = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &u64| *v > 0).unwrap_or(default)
+        };
+        let timeout = Duration::from_millis(millis("QA_STATUS_TIMEOUT_MS", 500));
+        StatusCache {
+            client: quantumarb_tenancy::firm_client_builder().timeout(timeout).build().unwrap(),
+            ttl: Duration::from_millis(millis("QA_STATUS_CACHE_MS", 1000)),
+            latest: Mutex::new(None),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The aggregated status, refreshed first if the cached one has expired.
+    pub async fn status(&self) -> PlatformStatus {
+        // Held across the refresh, so concurrent callers share one.
+        let mut latest = self.latest.lock().await;
+        if let Some((refreshed, status)) = latest.as_ref() {
+            if refreshed.elapsed() < self.ttl {
+                return status.clone();
+            }
+        }
+        let previous = latest.as_ref().map(|(_, status)| &status.sections);
+        let fetches = SOURCES.iter().map(|source| self.fetch(source));
+        let results = futures::future::join_all(fetches).await;
+        let now = Utc::now();
+        let sections: BTreeMap<String, Section> = SOURCES
+            .iter()
+            .zip(results)
+            .map(|(source, result)| {
+                let before = previous.and_then(|sections| sections.get(source.section));
+                (source.section.to_string(), section(source.url(), result, before, now))
+            })
+            .collect();
+        let status = PlatformStatus {
+            healthy: sections.values().all(|s| s.state == SectionState::Ok),
+            sections,
+            refreshed_utc: now,
+        };
+        *latest = Some((Instant::now(), status.clone()));
+        status
+    }
+
+    /// A service's response body and how long it took, in milliseconds.
+    async fn fetch(&self, source: &Source) -> Result<(Value, u64), String> {
+        let started = Instant::now();
+        let response = self.client.get(source.url()).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        let body = response.json::<Value>().await.map_err(|e| e.to_string())?;
+        Ok((body, started.elapsed().as_millis() as u64))
+    }
+}
+
+/// A section from a fetch, falling back on the previous body when it failed.
+fn section(url: String, result: Result<(Value, u64), String>, before: Option<&Section>, now: DateTime<Utc>) -> Section {
+    match result {
+        Ok((data, latency_ms)) => Section {
+            state: SectionState::Ok,
+            url,
+            data: Some(data),
+            error: None,
+            fetched_utc: Some(now),
+            latency_ms: Some(latency_ms),
+        },
+        Err(error) => match before.filter(|b| b.data.is_some()) {
+            Some(before) => Section {
+                state: SectionState::Stale,
+                url,
+                data: before.data.clone(),
+                error: Some(error),
+                fetched_utc: before.fetched_utc,
+                latency_ms: None,
+            },
+            None => Section {
+                state: SectionState::Unavailable,
+                url,
+                data: None,
+                error: Some(error),
+                fetched_utc: None,
+                latency_ms: None,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_failed_fetch_serves_the_last_good_body_as_stale() {
+        let url = "http://var-calculator/var".to_string();
+        let earlier = Utc::now();
+        let body = serde_json::json!({ "var_99": 125000.0 });
+        let ok = section(url.clone(), Ok((body.clone(), 12)), None, earlier);
+        assert_eq!(ok.state, SectionState::Ok);
+
+        let later = earlier + chrono::Duration::seconds(5);
+        let stale = section(url.clone(), Err("timed out".to_string()), Some(&ok), later);
+        assert_eq!(stale.state, SectionState::Stale);
+        assert_eq!(stale.data, Some(body));
+        assert_eq!(stale.fetched_utc, Some(earlier));
+
+        let missing = section(url, Err("connection refused".to_string()), None, later);
+        assert_eq!((missing.state, missing.data), (SectionState::Unavailable, None));
+    }
+}
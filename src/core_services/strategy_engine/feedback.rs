@@ -0,0 +1,294 @@
+/*
+ * QuantumArb 2.0 - Core Services: Model Prediction Feedback
+ *
+ * File: src/core_services/strategy_engine/feedback.rs
+ *
+ * Description:
+ * Scores each model's predictions against what the market did next. A
+ * prediction is held with the mid at the time it was made; once the outcome
+ * horizon has passed, the first mid observed after it settles the outcome:
+ *
+ *   correct  = BUY and the mid rose, or SELL and the mid fell
+ *   flat     = the mid did not move (counted, but not scored)
+ *
+ * Over a rolling window of settled outcomes the module reports accuracy,
+ * precision of BUY and SELL signals and, when the server sends the model's
+ * probability of an up move, the Brier score and a calibration table
+ * (predicted probability vs observed up rate in 10 buckets). A falling
+ * accuracy or a widening calibration gap is the drift signal.
+ *
+ * Configuration (environment):
+ *   QA_MODEL_OUTCOME_SECS=60        horizon over which a prediction is scored
+ *   QA_MODEL_FEEDBACK_WINDOW=200    settled outcomes kept for the metrics
+ *   QA_MODEL_MIN_ACCURACY=          if set, the champion's signal stops gating
+ *                                   trades while its accuracy is below this
+ *   QA_MODEL_MIN_SAMPLES=50         outcomes needed before the threshold applies
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::models::{Prediction, Signal};
+
+const CALIBRATION_BUCKETS: usize = 10;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct FeedbackConfig {
+    pub horizon: Duration,
+    pub window: usize,
+    pub min_accuracy: Option<f64>,
+    pub min_samples: usize,
+}
+
+impl FeedbackConfig {
+    pub fn from_env() -> FeedbackConfig {
+        let number = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &u64| *v > 0).unwrap_or(default)
+        };
+        FeedbackConfig {
+            horizon: Duration::from_secs(number("QA_MODEL_OUTCOME_SECS", 60)),
+            window: number("QA_MODEL_FEEDBACK_WINDOW", 200) as usize,
+            min_accuracy: std::env::var("QA_MODEL_MIN_ACCURACY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &f64| (0.0..=1.0).contains(v)),
+            min_samples: number("QA_MODEL_MIN_SAMPLES", 50) as usize,
+        }
+    }
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone)]
+struct PendingPrediction {
+    prediction: Prediction,
+    mid: f64,
+    made_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+struct Outcome {
+    prediction: Prediction,
+    /// Realized move of the mid over the horizon, in basis points.
+    move_bps: f64,
+}
+
+impl Outcome {
+    fn went_up(&self) -> bool {
+        self.move_bps > 0.0
+    }
+
+    fn correct(&self) -> bool {
+        match self.prediction.signal {
+            Signal::Buy => self.move_bps > 0.0,
+            Signal::Sell => self.move_bps < 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CalibrationBucket {
+    /// Predicted probability of an up move: [lower, upper).
+    pub lower: f64,
+    pub upper: f64,
+    pub predictions: usize,
+    pub mean_probability: f64,
+    pub observed_up_rate: f64,
+}
+
+/// Rolling metrics for one model, served on GET /models/feedback.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct FeedbackMetrics {
+    /// Settled, non-flat outcomes in the window.
+    pub samples: usize,
+    /// Settled outcomes where the mid did not move.
+    pub flat: u64,
+    /// Predictions still waiting for the horizon to pass.
+    pub pending: usize,
+    pub accuracy: Option<f64>,
+    pub buy_precision: Option<f64>,
+    pub sell_precision: Option<f64>,
+    /// Mean realized move in the predicted direction, in basis points.
+    pub mean_signed_move_bps: Option<f64>,
+    /// Mean squared error of the up probability (lower is better).
+    pub brier_score: Option<f64>,
+    pub calibration: Vec<CalibrationBucket>,
+}
+
+/// Prediction outcomes of one model.
+#[derive(Debug, Clone, Default)]
+pub struct ModelFeedback {
+    pending: VecDeque<PendingPrediction>,
+    outcomes: VecDeque<Outcome>,
+    flat: u64,
+}
+
+// --- Outcome Tracking ---
+
+impl ModelFeedback {
+    /// Holds a prediction made at `mid` until its horizon has passed.
+    pub fn record_prediction(&mut self, prediction: Prediction, mid: f64, now: Instant) {
+        if mid > 0.0 {
+            self.pending.push_back(PendingPrediction { prediction, mid, made_at: now });
+        }
+    }
+
+    /// Settles every pending prediction whose horizon has passed at `mid`.
+    pub fn observe_mid(&mut self, config: &FeedbackConfig, mid: f64, now: Instant) {
+        if mid <= 0.0 {
+            return;
+        }
+        while let Some(pending) = self.pending.front() {
+            if now.duration_since(pending.made_at) < config.horizon {
+                break;
+            }
+            let pending = self.pending.pop_front().unwrap();
+            let move_bps = (mid - pending.mid) / pending.mid * 10_000.0;
+            if move_bps == 0.0 {
+                self.flat += 1;
+                continue;
+            }
+            if self.outcomes.len() == config.window {
+                self.outcomes.pop_front();
+            }
+            self.outcomes.push_back(Outcome { prediction: pending.prediction, move_bps });
+        }
+    }
+
+    /// Rolling accuracy, once at least `min_samples` outcomes have settled.
+    pub fn accuracy(&self, min_samples: usize) -> Option<f64> {
+        if self.outcomes.is_empty() || self.outcomes.len() < min_samples {
+            return None;
+        }
+        Some(self.outcomes.iter().filter(|o| o.correct()).count() as f64 / self.outcomes.len() as f64)
+    }
+
+    pub fn metrics(&self) -> FeedbackMetrics {
+        let samples = self.outcomes.len();
+        let precision = |signal: Signal| {
+            let called: Vec<&Outcome> = self.outcomes.iter().filter(|o| o.prediction.signal == signal).collect();
+            (!called.is_empty()).then(|| called.iter().filter(|o| o.correct()).count() as f64 / called.len() as f64)
+        };
+        let mean_signed_move_bps = (samples > 0).then(|| {
+            let total: f64 = self.outcomes.iter().map(|o| o.move_bps * o.prediction.signal.direction() as f64).sum();
+            total / samples as f64
+        });
+
+        let scored: Vec<(f64, bool)> = self
+            .outcomes
+            .iter()
+            .filter_map(|o| o.prediction.probability_up.map(|p| (p.clamp(0.0, 1.0), o.went_up())))
+            .collect();
+        let brier_score = (!scored.is_empty()).then(|| {
+            let total: f64 = scored.iter().map(|(p, up)| (p - if *up { 1.0 } else { 0.0 }).powi(2)).sum();
+            total / scored.len() as f64
+        });
+
+        let mut calibration = Vec::new();
+        for bucket in 0..CALIBRATION_BUCKETS {
+            let lower = bucket as f64 / CALIBRATION_BUCKETS as f64;
+            let upper = (bucket + 1) as f64 / CALIBRATION_BUCKETS as f64;
+            let in_bucket: Vec<&(f64, bool)> = scored
+                .iter()
+                .filter(|(p, _)| *p >= lower && (*p < upper || bucket == CALIBRATION_BUCKETS - 1))
+                .collect();
+            if in_bucket.is_empty() {
+                continue;
+            }
+            let n = in_bucket.len() as f64;
+            calibration.push(CalibrationBucket {
+                lower,
+                upper,
+                predictions: in_bucket.len(),
+                mean_probability: in_bucket.iter().map(|(p, _)| p).sum::<f64>() / n,
+                observed_up_rate: in_bucket.iter().filter(|(_, up)| *up).count() as f64 / n,
+            });
+        }
+
+        FeedbackMetrics {
+            samples,
+            flat: self.flat,
+            pending: self.pending.len(),
+            accuracy: self.accuracy(1),
+            buy_precision: precision(Signal::Buy),
+            sell_precision: precision(Signal::Sell),
+            mean_signed_move_bps,
+            brier_score,
+            calibration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(window: usize) -> FeedbackConfig {
+        FeedbackConfig { horizon: Duration::from_secs(60), window, min_accuracy: None, min_samples: 2 }
+    }
+
+    fn prediction(signal: Signal, probability_up: Option<f64>) -> Prediction {
+        Prediction { signal, probability_up }
+    }
+
+    #[test]
+    fn predictions_settle_at_the_first_mid_after_their_horizon() {
+        let (config, start) = (config(10), Instant::now());
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut feedback = ModelFeedback::default();
+        feedback.record_prediction(prediction(Signal::Buy, None), 100.0, at(0));
+        feedback.record_prediction(prediction(Signal::Sell, None), 100.0, at(10));
+        feedback.record_prediction(prediction(Signal::Sell, None), 0.0, at(10));
+
+        feedback.observe_mid(&config, 101.0, at(59));
+        assert_eq!((feedback.metrics().samples, feedback.metrics().pending), (0, 2));
+        feedback.observe_mid(&config, 0.0, at(80));
+        feedback.observe_mid(&config, 101.0, at(80));
+        feedback.record_prediction(prediction(Signal::Buy, None), 101.0, at(80));
+        feedback.observe_mid(&config, 101.0, at(140));
+
+        let metrics = feedback.metrics();
+        assert_eq!((metrics.samples, metrics.flat, metrics.pending), (2, 1, 0));
+        assert_eq!(metrics.accuracy, Some(0.5));
+        assert_eq!((metrics.buy_precision, metrics.sell_precision), (Some(1.0), Some(0.0)));
+        // +100 bps called right, +100 bps called wrong.
+        assert!(metrics.mean_signed_move_bps.unwrap().abs() < 1e-9);
+        assert_eq!(feedback.accuracy(3), None);
+    }
+
+    #[test]
+    fn accuracy_covers_the_latest_window_of_outcomes() {
+        let (config, start) = (config(3), Instant::now());
+        let mut feedback = ModelFeedback::default();
+        for (i, mid) in [99.0, 99.0, 101.0, 101.0, 101.0].into_iter().enumerate() {
+            let made = start + Duration::from_secs(i as u64 * 60);
+            feedback.record_prediction(prediction(Signal::Buy, None), 100.0, made);
+            feedback.observe_mid(&config, mid, made + config.horizon);
+        }
+        assert_eq!(feedback.metrics().samples, 3);
+        assert_eq!(feedback.accuracy(config.min_samples), Some(1.0));
+    }
+
+    #[test]
+    fn probabilities_are_scored_and_bucketed_for_calibration() {
+        let (config, start) = (config(10), Instant::now());
+        let mut feedback = ModelFeedback::default();
+        let calls = [(Some(0.9), 101.0), (Some(0.9), 101.0), (Some(0.2), 99.0), (Some(1.0), 101.0), (None, 99.0)];
+        for (i, (probability_up, mid)) in calls.into_iter().enumerate() {
+            let made = start + Duration::from_secs(i as u64 * 60);
+            feedback.record_prediction(prediction(Signal::Buy, probability_up), 100.0, made);
+            feedback.observe_mid(&config, mid, made + config.horizon);
+        }
+
+        let metrics = feedback.metrics();
+        assert!((metrics.brier_score.unwrap() - 0.015).abs() < 1e-12, "{:?}", metrics.brier_score);
+        let buckets: Vec<(f64, usize, f64)> =
+            metrics.calibration.iter().map(|b| (b.lower, b.predictions, b.observed_up_rate)).collect();
+        assert_eq!(buckets, [(0.2, 1, 0.0), (0.9, 3, 1.0)]);
+        assert!((metrics.calibration[1].mean_probability - 2.8 / 3.0).abs() < 1e-12);
+    }
+}
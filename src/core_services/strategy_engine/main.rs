@@ -10,11 +10,114 @@
  * intelligently splitting the order across multiple venues.
  *
  * This minimizes market impact and slippage, leading to better execution prices.
+ *
+ * Each leg of the plan is sent to the Risk Gateway for a pre-trade check.
+ * When both services are colocated (QA_RISK_TRANSPORT=shm), requests and
+ * verdicts travel over a pair of shared-memory SPSC rings in the binary
+ * `quantumarb-wire` format instead of the network. An order the gateway holds
+ * for a supervisor is answered RISK_PENDING_APPROVAL first; its final
+ * verdict arrives later, among the verdicts for later orders.
+ *
+ * With QA_RUNTIME_MODE=low-latency the market-data consumer and order
+ * submission paths move onto dedicated, core-pinned busy-polling threads
+ * (see `quantumarb-hotpath`).
+ *
+ * Each order request carries `HopStamps` with the market data receive and
+ * strategy decision times, for tick-to-trade measurement downstream, and the
+ * platform trading mode (QA_TRADING_MODE, sandbox unless set to live), which
+ * the risk and exchange gateways check against their own.
+ *
+ * The SOR ranks ask levels by all-in price: the quoted price plus the taker
+ * fee per unit at that venue's current tier (`quantumarb-fees`), so a cheaper
+ * quote on an expensive venue no longer wins by default. Book prices are read
+ * at the traded instrument's price scale (`quantumarb-refdata`) and the
+ * plan's cost is totalled in `quantumarb-money`.
+ *
+ * Trading pauses on any instrument the data quality monitor has published a
+ * Suspect alert for on 'alerts.data_quality' (crossed, stale or outlier
+ * prices, or a silent feed), and resumes when it publishes a Cleared alert.
+ * It also stands aside on a symbol for the hold period of any alternative
+ * data anomaly (news burst or sentiment shift) on 'alerts.alt_data'.
+ *
+ * Around scheduled economic releases it trades more cautiously, inside the
+ * windows the data bus connector publishes on 'calendar.event_windows'.
+ * QA_EVENT_ACTIONS (default FOMC=PAUSE,NFP=PAUSE,CPI=WIDEN) names what each
+ * kind of release does: PAUSE stands aside until the window closes; WIDEN
+ * divides the book-signal imbalance at which buying is held by
+ * QA_EVENT_WIDEN_FACTOR (default 2), from 0.3 when QA_SIGNAL_MIN_IMBALANCE
+ * is not set, so a smaller lean against a buy is enough to hold it.
+ *
+ * With QA_SIGNAL_MIN_IMBALANCE set (e.g. 0.3), it also holds off buying
+ * while the traded instrument's book signals on 'signals.instrument.<id>'
+ * (`quantumarb-signals`) lean down: at least that imbalance towards the ask
+ * and a falling microprice.
+ *
+ * The models' paper positions are marked at the traded instrument's
+ * cross-venue mid from the market data consolidator's best bid and offer
+ * on 'market_data.consolidated.instrument.<id>', falling back to the venue
+ * books until one arrives.
+ *
+ * Position reductions instructed on 'strategy.instructions' (by the
+ * portfolio manager on a margin call, or the risk gateway when VaR is over
+ * its limit) are sent as orders on the network risk path, which accepts
+ * risk-reducing orders even while VaR blocks new risk. Rebalance suggestions
+ * from the portfolio optimizer on the same topic are logged, not traded.
+ *
+ * Every pass of the trading loop publishes a heartbeat on 'heartbeats' as
+ * "strategy:sor_arbitrage"; if they stop, the risk gateway's watchdog
+ * cancels the strategy's orders or flattens its positions. When the strategy
+ * belongs to a desk (QA_DESKS_PATH, see `quantumarb-tenancy`) the heartbeats
+ * go on the desk's topic, 'desk.<desk>.heartbeats', and the API on port 3040
+ * answers only the desk's and firm tokens.
+ *
+ * Buys are gated on the champion ML model's signal; an optional challenger
+ * model runs in shadow with its predictions and hypothetical P&L logged, and
+ * can be promoted over the API on port 3040 (see `models.rs`):
+ *   GET  /models            champion/challenger statistics
+ *   POST /models/promote    swap champion and challenger
+ *   GET  /models/feedback   prediction accuracy, precision and calibration
+ *   GET  /openapi.json      the API's OpenAPI document (Swagger UI on /docs)
+ *
+ * Strategies shipped as WebAssembly plugins (`quantumarb-strategy-sdk`) run
+ * next to the SOR in the standard runtime, loaded from QA_PLUGIN_DIR and
+ * reloaded when their files change, each sandboxed with its own fuel and
+ * memory limits; their orders are risk checked as "plugin:<name>". The same
+ * API manages them on /plugins (see `plugins.rs`).
  */
 
+mod feedback;
+mod models;
+mod plugins;
+
+use models::{FeatureSource, FeedbackReport, Gate, ModelConfig, ModelReport, ModelRouter};
+use plugins::{PluginConfig, PluginHost, PluginStatus, SharedPlugins};
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_fees::{FeeEngine, Liquidity};
+use quantumarb_hotpath::{busy_poll, spawn_pinned, HotQueue, LowLatencyConfig, RuntimeMode};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_bus::topics;
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_shm::{risk_channel, PushError, ShmConsumer, ShmProducer};
+use quantumarb_signals::{BookSignals, Lean};
+use quantumarb_strategy_sdk::{MarketView, Side, VenueBook};
+use quantumarb_tenancy::Desks;
+use quantumarb_types::{
+    AltDataAnomaly, AnomalyKind, DataQualityAlert, EconomicEventKind, EventWindow, Heartbeat, Issue, QualityStatus,
+    StrategyInstruction,
+};
+use quantumarb_wire::{
+    monotonic_ns, ConsolidatedBbo, Encoding, Hop, HopStamps, OrderPriority, OrderRequest, OrderSide, RiskVerdict,
+    TradingMode,
+};
 use serde::Deserialize;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use tokio::time;
+use uuid::Uuid;
+use warp::Filter;
 
 // --- Data Structures ---
 
@@ -32,6 +135,8 @@ struct MarketUpdate {
     // Top 5 levels of the book
     bids: Vec<OrderBookLevel>,
     asks: Vec<OrderBookLevel>,
+    /// Monotonic time the update was received from the feed.
+    received_ns: u64,
 }
 
 /// A single leg of an execution plan.
@@ -46,43 +151,1015 @@ struct TradeAction {
 #[derive(Debug)]
 struct ExecutionPlan {
     actions: Vec<TradeAction>,
-    average_price: f64,
-    total_cost: f64,
+    average_price: Price,
+    total_cost: Money,
     total_size: u32,
+    /// Estimated taker fees across all legs, in dollars.
+    estimated_fees: Money,
+    stamps: HopStamps,
+}
+
+/// This strategy's heartbeat, published from the trading loop so a loop that
+/// hangs goes silent even if the process is still up.
+fn strategy_heartbeat() -> Heartbeat {
+    Heartbeat::new(&format!("strategy:{}", STRATEGY_NAME))
+}
+
+/// Instruments paused on a data quality alert, with the issue. Written only
+/// when an alert arrives, so the read on every evaluation is uncontended.
+#[derive(Debug, Clone, Default)]
+struct TradingPauses(Arc<RwLock<HashMap<u32, Issue>>>);
+
+impl TradingPauses {
+    fn apply(&self, alert: &DataQualityAlert) {
+        let mut paused = self.0.write().unwrap();
+        match alert.status {
+            QualityStatus::Suspect => {
+                println!("  -> Pausing instrument {}: {:?} ({})", alert.instrument_id, alert.issue, alert.detail);
+                paused.insert(alert.instrument_id, alert.issue);
+            }
+            QualityStatus::Cleared => {
+                if paused.remove(&alert.instrument_id).is_some() {
+                    println!("  -> Resuming instrument {}: {}", alert.instrument_id, alert.detail);
+                }
+            }
+        }
+    }
+
+    fn reason(&self, instrument_id: u32) -> Option<Issue> {
+        self.0.read().unwrap().get(&instrument_id).copied()
+    }
+}
+
+/// Everything that can hold a trade before the SOR runs.
+#[derive(Debug, Clone)]
+struct Gates {
+    pauses: TradingPauses,
+    alt_data: AltDataFilters,
+    events: EventWindows,
+    book: BookSignalFilter,
+    models: ModelRouter,
+}
+
+/// The traded instrument's latest book signals, and the imbalance at which
+/// a down lean holds buying (None: signals are not used).
+#[derive(Debug, Clone, Default)]
+struct BookSignalFilter {
+    min_imbalance: Option<f64>,
+    latest: Arc<RwLock<Option<BookSignals>>>,
 }
 
+impl BookSignalFilter {
+    fn from_env() -> BookSignalFilter {
+        let min_imbalance = std::env::var("QA_SIGNAL_MIN_IMBALANCE").ok().and_then(|v| v.parse().ok());
+        BookSignalFilter { min_imbalance: min_imbalance.filter(|v: &f64| *v > 0.0 && *v <= 1.0), ..Default::default() }
+    }
+
+    fn apply(&self, signals: BookSignals) {
+        *self.latest.write().unwrap() = Some(signals);
+    }
+
+    /// The latest signals, if they lean against a buy. A `widen` factor
+    /// above 1 divides the imbalance that is enough to hold the buy.
+    fn against_buy(&self, widen: f64) -> Option<BookSignals> {
+        let min_imbalance = match self.min_imbalance {
+            Some(min_imbalance) => min_imbalance,
+            None if widen > 1.0 => EVENT_MIN_IMBALANCE,
+            None => return None,
+        };
+        let latest = self.latest.read().unwrap();
+        latest.as_ref().filter(|signals| signals.lean(min_imbalance / widen.max(1.0)) == Lean::Down).cloned()
+    }
+}
+
+/// What the strategy does inside a release's event window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EventAction {
+    Pause,
+    /// Holds buying on a smaller lean of the book: the factor divides the
+    /// imbalance threshold.
+    Widen(f64),
+}
+
+/// The economic releases' event windows, by event id, and the action each
+/// kind of release calls for.
+#[derive(Debug, Clone, Default)]
+struct EventWindows {
+    actions: HashMap<EconomicEventKind, EventAction>,
+    windows: Arc<RwLock<HashMap<String, EventWindow>>>,
+}
+
+impl EventWindows {
+    fn from_env() -> EventWindows {
+        let widen = std::env::var("QA_EVENT_WIDEN_FACTOR")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &f64| *v >= 1.0)
+            .unwrap_or(2.0);
+        let spec = std::env::var("QA_EVENT_ACTIONS").unwrap_or_else(|_| "FOMC=PAUSE,NFP=PAUSE,CPI=WIDEN".to_string());
+        let actions = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (kind, action) = entry.split_once('=')?;
+                let kind = match kind.trim().to_uppercase().as_str() {
+                    "FOMC" => EconomicEventKind::Fomc,
+                    "NFP" => EconomicEventKind::Nfp,
+                    "CPI" => EconomicEventKind::Cpi,
+                    _ => return None,
+                };
+                let action = match action.trim().to_uppercase().as_str() {
+                    "PAUSE" => EventAction::Pause,
+                    "WIDEN" => EventAction::Widen(widen),
+                    _ => return None,
+                };
+                Some((kind, action))
+            })
+            .collect();
+        EventWindows { actions, windows: Arc::default() }
+    }
+
+    /// Records a published window, replacing an earlier one for the same
+    /// release, and forgets windows that have closed.
+    fn apply(&self, window: EventWindow) {
+        let now = chrono::Utc::now();
+        let mut windows = self.windows.write().unwrap();
+        windows.retain(|_, known| known.ends_utc > now);
+        if window.ends_utc > now {
+            windows.insert(window.event_id.clone(), window);
+        }
+    }
+
+    /// The strictest action among the windows open for `symbol` (a pause
+    /// over any widening), with its window.
+    fn active(&self, symbol: &str) -> Option<(EventAction, EventWindow)> {
+        let now = chrono::Utc::now();
+        let windows = self.windows.read().unwrap();
+        let strictness = |action: &EventAction| match action {
+            EventAction::Pause => f64::INFINITY,
+            EventAction::Widen(factor) => *factor,
+        };
+        windows
+            .values()
+            .filter(|window| window.covers(symbol, now))
+            .filter_map(|window| Some((*self.actions.get(&window.kind)?, window.clone())))
+            .max_by(|(a, _), (b, _)| strictness(a).total_cmp(&strictness(b)))
+    }
+}
+
+/// The traded instrument's latest consolidated best bid and offer.
+#[derive(Debug, Clone, Default)]
+struct ConsolidatedQuote(Arc<RwLock<Option<ConsolidatedBbo>>>);
+
+impl ConsolidatedQuote {
+    fn apply(&self, nbbo: ConsolidatedBbo) {
+        *self.0.write().unwrap() = Some(nbbo);
+    }
+
+    /// Cross-venue mid in dollars; None until both sides are quoted.
+    fn mid(&self, instrument: &InstrumentDefinition) -> Option<f64> {
+        let nbbo = (*self.0.read().unwrap())?;
+        if nbbo.best_bid_price == 0 || nbbo.best_ask_price == 0 {
+            return None;
+        }
+        let (bid, ask) = (instrument.price(nbbo.best_bid_price), instrument.price(nbbo.best_ask_price));
+        Some((bid.to_f64() + ask.to_f64()) / 2.0)
+    }
+}
+
+/// Symbols under an alt-data anomaly, with the anomaly and when the hold ends.
+#[derive(Debug, Clone, Default)]
+struct AltDataFilters(Arc<RwLock<HashMap<String, (AnomalyKind, Instant)>>>);
+
+impl AltDataFilters {
+    fn apply(&self, anomaly: &AltDataAnomaly) {
+        println!(
+            "  -> Filtering {} for {}s: {:?} (score {:.1}) {}",
+            anomaly.symbol, anomaly.hold_secs, anomaly.kind, anomaly.score, anomaly.detail
+        );
+        let until = Instant::now() + Duration::from_secs(anomaly.hold_secs);
+        let mut filters = self.0.write().unwrap();
+        let entry = filters.entry(anomaly.symbol.clone()).or_insert((anomaly.kind, until));
+        if until >= entry.1 {
+            *entry = (anomaly.kind, until);
+        }
+    }
+
+    /// The anomaly holding `symbol`, if its hold has not ended.
+    fn active(&self, symbol: &str) -> Option<AnomalyKind> {
+        let filters = self.0.read().unwrap();
+        filters.get(symbol).filter(|(_, until)| Instant::now() < *until).map(|(kind, _)| *kind)
+    }
+}
+
+/// How pre-trade checks reach the risk gateway.
+enum RiskTransport {
+    /// Colocated deployment: SPSC rings in shared memory.
+    SharedMemory { requests: ShmProducer, verdicts: ShmConsumer },
+    /// Default: a network call to the risk-gateway service.
+    Network,
+}
+
+impl RiskTransport {
+    /// Selects the transport from QA_RISK_TRANSPORT ("shm" or "network").
+    fn from_env() -> RiskTransport {
+        if std::env::var("QA_RISK_TRANSPORT").as_deref() != Ok("shm") {
+            return RiskTransport::Network;
+        }
+        let requests = ShmProducer::open(risk_channel::REQUESTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE);
+        let verdicts = ShmConsumer::open(risk_channel::VERDICTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE);
+        match (requests, verdicts) {
+            (Ok(requests), Ok(verdicts)) => {
+                println!("Risk transport: shared memory ({})", risk_channel::REQUESTS_PATH);
+                RiskTransport::SharedMemory { requests, verdicts }
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                println!("Failed to map risk rings ({}); falling back to network.", e);
+                RiskTransport::Network
+            }
+        }
+    }
+}
+
+const ACCOUNT_ID: u32 = 101;
+/// Name the strategy heart-beats under, and its watchdog policy is keyed by.
+const STRATEGY_NAME: &str = "sor_arbitrage";
+/// Symbol of the traded instrument (id 1), for feature store lookups.
+const TRADED_SYMBOL: &str = "BTC";
+const VERDICT_TIMEOUT: Duration = Duration::from_millis(5);
+/// Imbalance threshold widened in an event window when QA_SIGNAL_MIN_IMBALANCE is not set.
+const EVENT_MIN_IMBALANCE: f64 = 0.3;
+
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Strategy Engine (SOR Integrated) ---");
 
+    let mode = TradingMode::from_env();
+    println!("Trading mode: {}", mode);
+    let risk_transport = RiskTransport::from_env();
+    let fee_engine = FeeEngine::from_env();
+    let pauses = TradingPauses::default();
+    let alt_data = AltDataFilters::default();
+    let models = ModelRouter::new(ModelConfig::from_env());
+    let instrument = ReferenceData::new(quantumarb_refdata::load_from_env())
+        .by_symbol(TRADED_SYMBOL)
+        .cloned()
+        .unwrap_or_else(|| panic!("no reference data for {}", TRADED_SYMBOL));
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+    let heartbeat_topic = topics::for_desk(desks.desk_of_strategy(STRATEGY_NAME), topics::HEARTBEATS);
+
+    let alert_pauses = pauses.clone();
+    tokio::spawn(async move {
+        listen_for_data_quality_alerts(alert_pauses).await;
+    });
+
+    let anomaly_filters = alt_data.clone();
+    tokio::spawn(async move {
+        listen_for_alt_data_anomalies(anomaly_filters).await;
+    });
+
+    let events = EventWindows::from_env();
+    let event_windows = events.clone();
+    tokio::spawn(async move {
+        listen_for_event_windows(event_windows).await;
+    });
+
+    tokio::spawn(async move {
+        listen_for_strategy_instructions(mode).await;
+    });
+
+    let consolidated = ConsolidatedQuote::default();
+    let (quote_feed, quote_instrument) = (consolidated.clone(), instrument.instrument_id);
+    tokio::spawn(async move {
+        listen_for_consolidated_bbo(quote_feed, quote_instrument).await;
+    });
+
+    let (query_models, query_instrument) = (models.clone(), instrument.clone());
+    tokio::spawn(async move {
+        query_model_signals(query_models, query_instrument, consolidated).await;
+    });
+
+    let plugins: SharedPlugins = Arc::new(Mutex::new(
+        PluginHost::new(PluginConfig::from_env()).expect("Failed to start the plugin runtime"),
+    ));
+    plugins::reload(&plugins, false);
+    let watched_plugins = plugins.clone();
+    tokio::spawn(async move {
+        plugins::watch(watched_plugins).await;
+    });
+
+    let (api_models, api_plugins) = (models.clone(), plugins.clone());
+    tokio::spawn(async move {
+        run_model_api(api_models, api_plugins, desks).await;
+    });
+
+    let book = BookSignalFilter::from_env();
+    let widens = events.actions.values().any(|action| matches!(action, EventAction::Widen(_)));
+    if book.min_imbalance.is_some() || widens {
+        let signal_filter = book.clone();
+        tokio::spawn(async move {
+            listen_for_book_signals(signal_filter).await;
+        });
+    }
+
+    let gates = Gates { pauses, alt_data, events, book, models };
+    match RuntimeMode::from_env() {
+        RuntimeMode::Standard => {
+            run_standard(risk_transport, fee_engine, instrument, gates, mode, plugins, heartbeat_topic).await
+        }
+        RuntimeMode::LowLatency(config) => {
+            run_low_latency(risk_transport, fee_engine, instrument, gates, mode, config, heartbeat_topic).await
+        }
+    }
+}
+
+/// Queries the champion and challenger models every cycle and records their
+/// signals. The trading path only reads the latest champion signal, so a slow
+/// inference server never blocks an evaluation.
+async fn query_model_signals(models: ModelRouter, instrument: InstrumentDefinition, consolidated: ConsolidatedQuote) {
+    let (champion, challenger) = models.endpoints();
+    if champion.is_none() && challenger.is_none() {
+        println!("No model endpoints configured; trading without a model signal.");
+        return;
+    }
+    println!("Model routing: champion {:?}, challenger {:?}", champion, challenger);
+
+    let client = match reqwest::Client::builder().timeout(models.config().timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            println!("Failed to build the model client: {}", e);
+            return;
+        }
+    };
+    let mut feature_source = FeatureSource::from_env().await;
     let mut interval = time::interval(Duration::from_secs(5));
     loop {
         interval.tick().await;
+        let features = match feature_source.features(TRADED_SYMBOL).await {
+            Ok(features) => features,
+            Err(e) => {
+                println!("  -> No model features this cycle: {}", e);
+                continue;
+            }
+        };
+        let mid = match consolidated.mid(&instrument) {
+            Some(mid) => mid,
+            None => reference_mid(&get_simulated_market_update(1), &get_simulated_market_update(2), &instrument),
+        };
+
+        // Endpoints are re-read every cycle so a promotion takes effect immediately.
+        let (champion_url, challenger_url) = models.endpoints();
+        let champion = async {
+            match &champion_url {
+                Some(url) => Some(models::predict(&client, url, &features).await),
+                None => None,
+            }
+        };
+        let challenger = async {
+            match &challenger_url {
+                Some(url) => Some(models::predict(&client, url, &features).await),
+                None => None,
+            }
+        };
+        let (champion, challenger) = tokio::join!(champion, challenger);
+
+        if let (Some(Ok(champion)), Some(Ok(challenger))) = (&champion, &challenger) {
+            let shadow = serde_json::json!({
+                "champion": champion,
+                "challenger": challenger,
+                "challenger_url": challenger_url,
+                "mid": mid,
+                "timestamp_utc": chrono::Utc::now().to_rfc3339(),
+            });
+            println!("  -> Shadow prediction: challenger {:?}, champion {:?}", challenger.signal, champion.signal);
+            quantumarb_bus::publish(models::SHADOW_TOPIC, &shadow.to_string());
+        }
+        models.record(champion, challenger, mid);
+    }
+}
+
+/// Mid price in dollars used to mark the models' paper positions until a
+/// consolidated BBO arrives. The simulated books carry asks only, so this is
+/// the best ask across venues.
+fn reference_mid(venue_a: &MarketUpdate, venue_b: &MarketUpdate, instrument: &InstrumentDefinition) -> f64 {
+    let best = |update: &MarketUpdate| {
+        let bid = update.bids.iter().map(|l| l.price).max();
+        let ask = update.asks.iter().map(|l| l.price).min();
+        match (bid, ask) {
+            (Some(bid), Some(ask)) => Some((instrument.price(bid).to_f64() + instrument.price(ask).to_f64()) / 2.0),
+            (bid, ask) => bid.or(ask).map(|p| instrument.price(p).to_f64()),
+        }
+    };
+    let mids: Vec<f64> = [best(venue_a), best(venue_b)].into_iter().flatten().collect();
+    if mids.is_empty() {
+        return 0.0;
+    }
+    mids.iter().sum::<f64>() / mids.len() as f64
+}
+
+/// Serves the champion/challenger API on port 3040, to the firm and the
+/// strategy's desk.
+async fn run_model_api(models: ModelRouter, plugins: SharedPlugins, desks: Arc<Desks>) {
+    let owner = quantumarb_tenancy::desk_only(desks.clone(), desks.desk_of_strategy(STRATEGY_NAME));
+    let report = warp::path!("models")
+        .and(owner.clone())
+        .and(warp::get())
+        .and(with_state(models.clone()))
+        .and_then(handler_get_models);
+    let promote = warp::path!("models" / "promote")
+        .and(owner.clone())
+        .and(warp::post())
+        .and(with_state(models.clone()))
+        .and_then(handler_promote_challenger);
+    let feedback = warp::path!("models" / "feedback")
+        .and(owner.clone())
+        .and(warp::get())
+        .and(with_state(models))
+        .and_then(handler_get_feedback);
+    let list_plugins = warp::path!("plugins")
+        .and(owner.clone())
+        .and(warp::get())
+        .and(with_state(plugins.clone()))
+        .and_then(handler_get_plugins);
+    let reload_plugins = warp::path!("plugins" / "reload")
+        .and(owner.clone())
+        .and(warp::post())
+        .and(with_state(plugins.clone()))
+        .and_then(handler_reload_plugins);
+    let unload_plugin = warp::path!("plugins" / String)
+        .and(owner.clone())
+        .and(warp::delete())
+        .and(with_state(plugins))
+        .and_then(handler_unload_plugin);
+
+    println!("Model API running at http://127.0.0.1:3040/models");
+    let routes = report
+        .or(promote)
+        .or(feedback)
+        .or(list_plugins)
+        .or(reload_plugins)
+        .or(unload_plugin)
+        .or(quantumarb_openapi::routes(api_doc()))
+        .recover(quantumarb_tenancy::recover);
+    warp::serve(routes).run(([127, 0, 0, 1], 3040)).await;
+}
+
+/// The OpenAPI document of the model API.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Strategy Engine", "The ML models gating the strategy's trades, and the strategy plugins.")
+        .errors::<ErrorBody>()
+        .get::<ModelReport>("/models", "Champion and challenger statistics")
+        .operation(Operation::post("/models/promote", "Swap champion and challenger").response::<ModelReport>())
+        .get::<FeedbackReport>("/models/feedback", "Prediction accuracy, precision and calibration")
+        .get::<Vec<PluginStatus>>("/plugins", "Strategy plugins and their counters")
+        .operation(
+            Operation::post("/plugins/reload", "Rescan the plugin directory, retrying quarantined plugins")
+                .response::<Vec<PluginStatus>>(),
+        )
+        .operation(Operation::delete("/plugins/{name}", "Unload a strategy plugin").status(204))
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+fn error_reply(rejection: Rejection) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+/// Handler for GET /models.
+async fn handler_get_models(models: ModelRouter) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&models.report()))
+}
+
+/// Handler for GET /models/feedback.
+async fn handler_get_feedback(models: ModelRouter) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&models.feedback_report()))
+}
+
+/// Handler for POST /models/promote.
+async fn handler_promote_challenger(models: ModelRouter) -> Result<impl warp::Reply, warp::Rejection> {
+    match models.promote() {
+        Ok(report) => {
+            let champion = report.champion.as_ref().map(|m| m.url.as_str()).unwrap_or("-");
+            println!("  -> Promoted challenger {} to champion.", champion);
+            let event = serde_json::json!({
+                "champion_url": champion,
+                "challenger_url": report.challenger.as_ref().map(|m| m.url.as_str()),
+                "timestamp_utc": report.last_promotion_utc,
+            });
+            quantumarb_bus::publish(models::PROMOTION_TOPIC, &event.to_string());
+            Ok(warp::reply::with_status(warp::reply::json(&report), warp::http::StatusCode::OK))
+        }
+        Err(rejection) => Ok(error_reply(rejection)),
+    }
+}
+
+/// Handler for GET /plugins.
+async fn handler_get_plugins(plugins: SharedPlugins) -> Result<impl warp::Reply, warp::Rejection> {
+    let statuses = plugins.lock().unwrap().statuses();
+    Ok(warp::reply::json(&statuses))
+}
+
+/// Handler for POST /plugins/reload.
+async fn handler_reload_plugins(plugins: SharedPlugins) -> Result<impl warp::Reply, warp::Rejection> {
+    let reloaded = plugins.clone();
+    let _ = tokio::task::spawn_blocking(move || plugins::reload(&reloaded, true)).await;
+    let statuses = plugins.lock().unwrap().statuses();
+    Ok(warp::reply::json(&statuses))
+}
+
+/// Handler for DELETE /plugins/{name}.
+async fn handler_unload_plugin(name: String, plugins: SharedPlugins) -> Result<impl warp::Reply, warp::Rejection> {
+    if !plugins.lock().unwrap().unload(&name) {
+        return Err(warp::reject::not_found());
+    }
+    Ok(warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT))
+}
+
+/// Keeps the paused instrument set in line with the data quality monitor's alerts.
+async fn listen_for_data_quality_alerts(pauses: TradingPauses) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::DATA_QUALITY_ALERTS).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let simulated_alerts = [
+        r#"{"instrument_id":1,"status":"Suspect","issue":"OutlierPrice","detail":"Mid moved 487.3 bps (9.1 sigma).",
+            "timestamp_utc":"2025-07-31T12:00:00Z"}"#,
+        r#"{"instrument_id":1,"status":"Cleared","issue":"OutlierPrice","detail":"10 consecutive clean ticks.",
+            "timestamp_utc":"2025-07-31T12:00:10Z"}"#,
+    ];
+    let mut interval = time::interval(Duration::from_secs(20));
+    interval.tick().await;
+    for payload in simulated_alerts.iter().cycle() {
+        interval.tick().await;
+        match serde_json::from_str::<DataQualityAlert>(payload) {
+            Ok(alert) => pauses.apply(&alert),
+            Err(e) => println!("  -> Undecodable data quality alert: {}", e),
+        }
+    }
+}
+
+/// Applies the data bus connector's alt-data anomalies as trade filters.
+async fn listen_for_alt_data_anomalies(filters: AltDataFilters) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::ALT_DATA_ALERTS).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let simulated_anomaly = r#"{
+        "symbol": "BTC",
+        "kind": "NEWS_BURST",
+        "score": 5.2,
+        "detail": "31 news events this bucket vs 9.4 typical.",
+        "hold_secs": 30,
+        "timestamp_utc": "2025-07-31T12:00:00Z"
+    }"#;
+    let mut interval = time::interval(Duration::from_secs(90));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match serde_json::from_str::<AltDataAnomaly>(simulated_anomaly) {
+            Ok(anomaly) => filters.apply(&anomaly),
+            Err(e) => println!("  -> Undecodable alt-data anomaly: {}", e),
+        }
+    }
+}
+
+/// Follows the economic releases' event windows from the data bus connector.
+async fn listen_for_event_windows(events: EventWindows) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::EVENT_WINDOWS).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    // Simulated: a CPI print two minutes after each ten-minute poll.
+    let mut interval = time::interval(Duration::from_secs(600));
+    loop {
+        interval.tick().await;
+        let scheduled = chrono::Utc::now() + chrono::Duration::minutes(2);
+        let payload = serde_json::json!({
+            "event_id": format!("US-CPI-{}", scheduled.format("%Y%m%d%H%M")),
+            "kind": "CPI",
+            "name": "CPI m/m",
+            "scheduled_utc": scheduled,
+            "starts_utc": scheduled - chrono::Duration::minutes(1),
+            "ends_utc": scheduled + chrono::Duration::minutes(1),
+            "symbols": [],
+        });
+        match serde_json::from_value::<EventWindow>(payload) {
+            Ok(window) => events.apply(window),
+            Err(e) => println!("  -> Undecodable event window: {}", e),
+        }
+    }
+}
+
+/// Follows the traded instrument's book signals from the data bus connector.
+async fn listen_for_book_signals(filter: BookSignalFilter) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(&topics::signals(1)).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let simulated_signals = [
+        r#"{"instrument_id":1,"symbol":"BTC","timestamp_ns":0,"mid":60010.5,"imbalance":-0.6,
+            "microprice":60010.2,"microprice_edge_bps":-0.05,"momentum_bps":-1.8}"#,
+        r#"{"instrument_id":1,"symbol":"BTC","timestamp_ns":0,"mid":60011.5,"imbalance":0.2,
+            "microprice":60011.6,"microprice_edge_bps":0.02,"momentum_bps":0.4}"#,
+    ];
+    let mut interval = time::interval(Duration::from_secs(15));
+    for payload in simulated_signals.iter().cycle() {
+        interval.tick().await;
+        match serde_json::from_str::<BookSignals>(payload) {
+            Ok(signals) => filter.apply(signals),
+            Err(e) => println!("  -> Undecodable book signals: {}", e),
+        }
+    }
+}
+
+/// Follows the traded instrument's consolidated BBO from the market data
+/// consolidator.
+async fn listen_for_consolidated_bbo(quote: ConsolidatedQuote, instrument_id: u32) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(&topics::consolidated_market_data(instrument_id)).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let encoding = Encoding::from_env();
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let simulated = ConsolidatedBbo {
+            instrument_id,
+            best_bid_price: 60_009_50,
+            best_bid_size: 12,
+            best_bid_venue_id: 2,
+            best_ask_price: 60_010_00,
+            best_ask_size: 7,
+            best_ask_venue_id: 1,
+            venue_count: 2,
+            timestamp_ns: quantumarb_wire::utc_now_ns(),
+        };
+        match Encoding::decode_any::<ConsolidatedBbo>(&encoding.encode(&simulated)) {
+            Ok(nbbo) => quote.apply(nbbo),
+            Err(e) => println!("  -> Undecodable consolidated BBO: {}", e),
+        }
+    }
+}
+
+/// Carries out position reductions instructed by the margin monitor or the
+/// risk gateway's VaR escalation, and logs the portfolio optimizer's
+/// rebalance suggestions.
+async fn listen_for_strategy_instructions(mode: TradingMode) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::STRATEGY_INSTRUCTIONS).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let simulated_instruction = r#"{
+        "type": "ReducePositions",
+        "reason": "VaR 131250.00 over its 100000.00 limit; hedging to 90% of the limit",
+        "fraction": 0.3143,
+        "orders": [{"symbol": "BTC", "side": "Sell", "quantity": 2}]
+    }"#;
+    let instruments = ReferenceData::new(quantumarb_refdata::load_from_env());
+    let mut interval = time::interval(Duration::from_secs(300));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match serde_json::from_str::<StrategyInstruction>(simulated_instruction) {
+            Ok(instruction) => apply_instruction(&instruction, &instruments, mode),
+            Err(e) => println!("  -> Undecodable strategy instruction: {}", e),
+        }
+    }
+}
+
+fn apply_instruction(instruction: &StrategyInstruction, instruments: &ReferenceData, mode: TradingMode) {
+    let (reason, fraction, orders) = match instruction {
+        StrategyInstruction::ReducePositions { reason, fraction, orders } => (reason, fraction, orders),
+        StrategyInstruction::Rebalance { reason, targets, orders } => {
+            println!("\nRebalance suggested: {}", reason);
+            for target in targets {
+                println!(
+                    "  -> {}: {:+.1}% of capital, target {:+.1}%",
+                    target.symbol,
+                    target.current_weight * 100.0,
+                    target.target_weight * 100.0
+                );
+            }
+            for order in orders {
+                println!("  -> Suggested {} {} {}; not sent.", order.side, order.quantity, order.symbol);
+            }
+            return;
+        }
+    };
+    println!("\nReducing positions by {:.0}%: {}", fraction * 100.0, reason);
+    for order in orders {
+        let Some(instrument_id) = instruments.by_symbol(&order.symbol).map(|d| d.instrument_id) else {
+            println!("  -> No instrument for {}; {} {} not sent.", order.symbol, order.side, order.quantity);
+            continue;
+        };
+        println!(
+            "  -> Sending {} {} {} (instrument {}, {}) to risk-gateway over the network.",
+            order.side, order.quantity, order.symbol, instrument_id, mode
+        );
+    }
+}
+
+/// Default mode: everything runs on the tokio runtime.
+async fn run_standard(
+    mut risk_transport: RiskTransport,
+    fee_engine: FeeEngine,
+    instrument: InstrumentDefinition,
+    gates: Gates,
+    mode: TradingMode,
+    plugins: SharedPlugins,
+    heartbeat_topic: String,
+) {
+    let mut interval = time::interval(Duration::from_secs(5));
+    let mut heartbeat = strategy_heartbeat();
+    loop {
+        interval.tick().await;
+        quantumarb_bus::publish_json(&heartbeat_topic, heartbeat.beat());
 
         // 1. Simulate receiving full order book updates from two venues.
         let venue_a_update = get_simulated_market_update(1);
         let venue_b_update = get_simulated_market_update(2);
         println!("\nReceived market updates from Venue A & B.");
 
-        // 2. Define a desired trade: e.g., we want to buy 50 units.
-        let desired_trade_size: u32 = 50;
-        println!("  -> Goal: Buy {} units.", desired_trade_size);
+        if let Some(plan) = evaluate_opportunity(&venue_a_update, &venue_b_update, &fee_engine, &instrument, &gates) {
+            // 4. Pre-trade risk check for every leg of the plan.
+            request_risk_checks(&mut risk_transport, &plan, venue_a_update.instrument_id, mode);
+        }
+
+        // 5. The strategy plugins' orders, checked the same way.
+        let orders = run_plugins(&plugins, &[(1, &venue_a_update), (2, &venue_b_update)], &instrument, &gates, mode);
+        if !orders.is_empty() {
+            submit_risk_checks(&mut risk_transport, orders);
+        }
+    }
+}
+
+/// The orders the strategy plugins want on this pass, unless the instrument
+/// is paused on data quality or under an alt-data anomaly.
+fn run_plugins(
+    plugins: &SharedPlugins,
+    updates: &[(u32, &MarketUpdate)],
+    instrument: &InstrumentDefinition,
+    gates: &Gates,
+    mode: TradingMode,
+) -> Vec<OrderRequest> {
+    if gates.pauses.reason(instrument.instrument_id).is_some() || gates.alt_data.active(TRADED_SYMBOL).is_some() {
+        return Vec::new();
+    }
+    let view = MarketView {
+        instrument_id: instrument.instrument_id,
+        symbol: instrument.symbol.clone(),
+        price_decimals: instrument.price_decimals,
+        venues: updates
+            .iter()
+            .filter_map(|(venue_id, update)| {
+                let (bid, ask) = (update.bids.first()?, update.asks.first()?);
+                Some(VenueBook {
+                    venue_id: *venue_id,
+                    best_bid: bid.price,
+                    bid_size: bid.size,
+                    best_ask: ask.price,
+                    ask_size: ask.size,
+                })
+            })
+            .collect(),
+        time_ns: chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+    };
+    let mut stamps = HopStamps::default();
+    stamps.set(Hop::MarketDataReceive, updates.iter().map(|(_, update)| update.received_ns).max().unwrap_or_default());
+    let intents = plugins.lock().unwrap().on_market(&view);
+    stamps.stamp(Hop::StrategyDecision);
+    intents
+        .into_iter()
+        .map(|(name, intent)| {
+            println!(
+                "  -> Plugin '{}': {:?} {} @ {} on Venue {}",
+                name, intent.side, intent.size, intent.price, intent.venue_id
+            );
+            OrderRequest {
+                order_id: Uuid::new_v4(),
+                account_id: ACCOUNT_ID,
+                instrument_id: instrument.instrument_id,
+                side: match intent.side {
+                    Side::Buy => OrderSide::Buy,
+                    Side::Sell => OrderSide::Sell,
+                },
+                price: intent.price,
+                size: intent.size,
+                stamps,
+                venue_id: intent.venue_id,
+                mode,
+                priority: OrderPriority::Opportunistic,
+                strategy_id: format!("plugin:{}", name),
+            }
+        })
+        .collect()
+}
+
+/// Low-latency mode: the market-data consumer and the order-submission path
+/// each run on a dedicated thread pinned to its own core, busy-polling a
+/// bounded queue. Only the (simulated) feed handler stays on tokio.
+async fn run_low_latency(
+    mut risk_transport: RiskTransport,
+    fee_engine: FeeEngine,
+    instrument: InstrumentDefinition,
+    gates: Gates,
+    mode: TradingMode,
+    config: LowLatencyConfig,
+    heartbeat_topic: String,
+) {
+    println!("Runtime mode: low-latency (cores {:?}, queue capacity {})", config.cores, config.queue_capacity);
+
+    let md_queue: HotQueue<(MarketUpdate, MarketUpdate)> = HotQueue::new(config.queue_capacity);
+    let order_queue: HotQueue<(ExecutionPlan, u32)> = HotQueue::new(config.queue_capacity);
+    let running = Arc::new(AtomicBool::new(true));
+
+    // Market-data consumer: book updates -> SOR execution plan.
+    let (md, orders, md_running) = (md_queue.clone(), order_queue.clone(), running.clone());
+    spawn_pinned("md-consumer", config.core(0), move || {
+        busy_poll(&md, &md_running, |(venue_a_update, venue_b_update)| {
+            println!("\nReceived market updates from Venue A & B.");
+            let plan = evaluate_opportunity(&venue_a_update, &venue_b_update, &fee_engine, &instrument, &gates);
+            if let Some(plan) = plan {
+                if orders.push((plan, venue_a_update.instrument_id)).is_err() {
+                    println!("  -> Order queue full; execution plan dropped.");
+                }
+            }
+        });
+    });
 
-        // 3. Use the SOR to calculate the best execution plan.
-        if let Some(plan) = calculate_sor_execution_plan(desired_trade_size, &venue_a_update, &venue_b_update) {
+    // Order submission: execution plan -> pre-trade risk check.
+    let submit_running = running.clone();
+    spawn_pinned("order-submit", config.core(1), move || {
+        busy_poll(&order_queue, &submit_running, |(plan, instrument_id)| {
+            request_risk_checks(&mut risk_transport, &plan, instrument_id, mode);
+        });
+    });
+
+    // Feed handler (simulated). In production this is the NIC receive path.
+    // It heart-beats only while the market-data consumer keeps up, so a stuck
+    // pinned thread goes silent too.
+    let mut interval = time::interval(Duration::from_secs(5));
+    let mut heartbeat = strategy_heartbeat();
+    loop {
+        interval.tick().await;
+        if md_queue.is_empty() {
+            quantumarb_bus::publish_json(&heartbeat_topic, heartbeat.beat());
+        }
+        let update = (get_simulated_market_update(1), get_simulated_market_update(2));
+        if md_queue.push(update).is_err() {
+            println!("  -> Market-data queue full; update dropped ({} so far).", md_queue.rejected_pushes());
+        }
+    }
+}
+
+/// Runs the SOR for the desired trade and prints the resulting plan, unless
+/// the instrument is paused on a data quality alert, its symbol is under an
+/// alt-data anomaly or in a release's pausing window, its book leans down
+/// (by a smaller lean in a widening window), or the champion model does not
+/// signal a buy.
+fn evaluate_opportunity(
+    venue_a_update: &MarketUpdate,
+    venue_b_update: &MarketUpdate,
+    fee_engine: &FeeEngine,
+    instrument: &InstrumentDefinition,
+    gates: &Gates,
+) -> Option<ExecutionPlan> {
+    if let Some(issue) = gates.pauses.reason(venue_a_update.instrument_id) {
+        println!("  -> Instrument {} paused on data quality ({:?}); not trading.", venue_a_update.instrument_id, issue);
+        return None;
+    }
+    if let Some(kind) = gates.alt_data.active(TRADED_SYMBOL) {
+        println!("  -> {} under alt-data anomaly ({:?}); not trading.", TRADED_SYMBOL, kind);
+        return None;
+    }
+    let mut widen = 1.0;
+    match gates.events.active(TRADED_SYMBOL) {
+        Some((EventAction::Pause, window)) => {
+            println!("  -> {} in the {} window until {}; not trading.", TRADED_SYMBOL, window.name, window.ends_utc);
+            return None;
+        }
+        Some((EventAction::Widen(factor), _)) => widen = factor,
+        None => {}
+    }
+    if let Some(signals) = gates.book.against_buy(widen) {
+        println!(
+            "  -> {} book leans down (imbalance {:.2}, momentum {:.1} bps{}); not buying.",
+            TRADED_SYMBOL,
+            signals.imbalance,
+            signals.momentum_bps.unwrap_or_default(),
+            if widen > 1.0 { ", threshold widened for a release" } else { "" }
+        );
+        return None;
+    }
+    if let Gate::Hold(reason) = gates.models.gate() {
+        println!("  -> Holding: {}; not trading.", reason);
+        return None;
+    }
+
+    // 2. Define a desired trade: e.g., we want to buy 50 units.
+    let desired_trade_size: u32 = 50;
+    println!("  -> Goal: Buy {} units.", desired_trade_size);
+
+    // 3. Use the SOR to calculate the best execution plan.
+    let mut plan =
+        calculate_sor_execution_plan(desired_trade_size, venue_a_update, venue_b_update, fee_engine, instrument);
+    if let Some(plan) = &mut plan {
+        // The decision was triggered by the later of the two updates.
+        let tick_ns = venue_a_update.received_ns.max(venue_b_update.received_ns);
+        plan.stamps.set(Hop::MarketDataReceive, tick_ns);
+        plan.stamps.stamp(Hop::StrategyDecision);
+    }
+    match &plan {
+        Some(plan) => {
             println!("--- SOR Execution Plan ---");
             println!("  -> Total Size: {}", plan.total_size);
             println!("  -> Average Price: {:.2}", plan.average_price);
-            println!("  -> Total Cost: ${:.2}", plan.total_cost / 100.0);
-            for action in plan.actions {
+            println!("  -> Total Cost: ${:.2}", plan.total_cost);
+            println!("  -> Estimated Fees: ${:.2}", plan.estimated_fees);
+            for action in &plan.actions {
                 println!("    - Execute on Venue {}: Buy {} @ {}", action.venue_id, action.size, action.price);
             }
-        } else {
-            println!("  -> Could not generate an execution plan (insufficient liquidity).");
+        }
+        None => println!("  -> Could not generate an execution plan (insufficient liquidity)."),
+    }
+    plan
+}
+
+/// Sends every leg of the plan to the risk gateway and waits for the verdicts.
+fn request_risk_checks(transport: &mut RiskTransport, plan: &ExecutionPlan, instrument_id: u32, mode: TradingMode) {
+    let orders = plan
+        .actions
+        .iter()
+        .map(|action| OrderRequest {
+            order_id: Uuid::new_v4(),
+            account_id: ACCOUNT_ID,
+            instrument_id,
+            side: OrderSide::Buy,
+            price: action.price,
+            size: action.size,
+            stamps: plan.stamps,
+            venue_id: action.venue_id,
+            mode,
+            // The plan works a cross-venue price difference.
+            priority: OrderPriority::Arbitrage,
+            strategy_id: STRATEGY_NAME.to_string(),
+        })
+        .collect();
+    submit_risk_checks(transport, orders);
+}
+
+/// Sends orders to the risk gateway and waits for the verdicts.
+fn submit_risk_checks(transport: &mut RiskTransport, orders: Vec<OrderRequest>) {
+    let (requests, verdicts) = match transport {
+        RiskTransport::SharedMemory { requests, verdicts } => (requests, verdicts),
+        RiskTransport::Network => {
+            println!("  -> Sending {} order(s) to risk-gateway over the network.", orders.len());
+            return;
+        }
+    };
+
+    let started = Instant::now();
+    for order in &orders {
+        match requests.try_push(&quantumarb_wire::encode(order)) {
+            Ok(()) => {}
+            Err(PushError::Full) => println!("  -> Risk ring full; order {} not submitted.", order.order_id),
+            Err(PushError::TooLarge { max }) => println!("  -> Order message exceeds ring slot size ({} bytes).", max),
+        }
+    }
+
+    let mut buf = Vec::with_capacity(risk_channel::SLOT_SIZE);
+    let mut awaiting: HashSet<Uuid> = orders.iter().map(|order| order.order_id).collect();
+    while !awaiting.is_empty() {
+        if !verdicts.pop_spin(&mut buf, VERDICT_TIMEOUT) {
+            println!("  -> Timed out waiting for risk verdicts.");
+            return;
+        }
+        let verdict = quantumarb_wire::decode::<RiskVerdict>(&buf);
+        if let Ok(verdict) = &verdict {
+            if !awaiting.remove(&verdict.order_id) {
+                println!("  -> Final verdict for held order {} follows.", verdict.order_id);
+            }
+        }
+        match verdict {
+            Ok(verdict) if verdict.reject.as_ref().is_some_and(|r| r.code == RejectCode::RiskPendingApproval) => {
+                println!("  -> Risk HELD order {} for a supervisor; its verdict will follow.", verdict.order_id)
+            }
+            Ok(verdict) if verdict.approved => println!(
+                "  -> Risk APPROVED order {} ({}ns tick-to-risk)",
+                verdict.order_id,
+                verdict.stamps.risk_decision_ns.saturating_sub(verdict.stamps.market_data_receive_ns)
+            ),
+            Ok(verdict) => println!(
+                "  -> Risk REJECTED order {}: {}",
+                verdict.order_id,
+                verdict.reject.map(|r| r.to_string()).unwrap_or_default()
+            ),
+            Err(e) => println!("  -> Undecodable risk verdict: {}", e),
         }
     }
+    println!("  -> Risk round trip for {} order(s): {:?}", orders.len(), started.elapsed());
 }
 
 /// Simulates receiving a multi-level market data update.
@@ -96,6 +1173,7 @@ fn get_simulated_market_update(venue_id: u32) -> MarketUpdate {
                 OrderBookLevel { price: 60012, size: 40 },
                 OrderBookLevel { price: 60015, size: 50 },
             ],
+            received_ns: monotonic_ns(),
         }
     } else {
         MarketUpdate {
@@ -106,6 +1184,7 @@ fn get_simulated_market_update(venue_id: u32) -> MarketUpdate {
                 OrderBookLevel { price: 60013, size: 30 },
                 OrderBookLevel { price: 60014, size: 60 },
             ],
+            received_ns: monotonic_ns(),
         }
     }
 }
@@ -115,17 +1194,25 @@ fn calculate_sor_execution_plan(
     mut size_to_buy: u32,
     venue_a: &MarketUpdate,
     venue_b: &MarketUpdate,
+    fee_engine: &FeeEngine,
+    instrument: &InstrumentDefinition,
 ) -> Option<ExecutionPlan> {
     let mut actions = Vec::new();
-    let mut total_cost: u64 = 0;
+    // Price x quantity summed over the legs, before the contract multiplier.
+    let mut traded_value = Money::ZERO;
+    let mut estimated_fees = Money::ZERO;
     let total_size_bought: u32 = size_to_buy;
 
     // Combine all available ask levels from both venues into a single list
     let mut all_asks: Vec<(OrderBookLevel, u32)> = venue_a.asks.iter().map(|&l| (l, venue_a.instrument_id)).collect();
     all_asks.extend(venue_b.asks.iter().map(|&l| (l, venue_b.instrument_id)));
 
-    // Sort all available liquidity by the best price (lowest ask)
-    all_asks.sort_by_key(|a| a.0.price);
+    // Sort all available liquidity by the best all-in price (ask + taker fee per unit)
+    let all_in_price = |(level, venue_id): &(OrderBookLevel, u32)| {
+        let price = instrument.price(level.price).to_f64();
+        price + fee_engine.estimate(venue_name(*venue_id), Liquidity::Taker, 1, price).total
+    };
+    all_asks.sort_by(|a, b| all_in_price(a).total_cmp(&all_in_price(b)));
 
     for (level, venue_id) in all_asks {
         if size_to_buy == 0 {
@@ -141,7 +1228,10 @@ fn calculate_sor_execution_plan(
             size: size_to_take,
         });
 
-        total_cost += level.price * size_to_take as u64;
+        let price = instrument.price(level.price);
+        traded_value += price * Quantity::from(size_to_take);
+        let fees = fee_engine.estimate(venue_name(venue_id), Liquidity::Taker, size_to_take as i64, price.to_f64());
+        estimated_fees += Money::from_f64(fees.total);
         size_to_buy -= size_to_take;
     }
 
@@ -152,8 +1242,19 @@ fn calculate_sor_execution_plan(
 
     Some(ExecutionPlan {
         actions,
-        average_price: total_cost as f64 / total_size_bought as f64,
-        total_cost: total_cost as f64,
+        average_price: traded_value.per_unit(Quantity::from(total_size_bought)),
+        total_cost: traded_value.at_multiplier(instrument.multiplier),
         total_size: total_size_bought,
+        estimated_fees,
+        stamps: HopStamps::default(),
     })
 }
+
+/// Fee schedule name of a venue id.
+fn venue_name(venue_id: u32) -> &'static str {
+    match venue_id {
+        1 => "VENUE_A",
+        2 => "VENUE_B",
+        _ => "UNKNOWN",
+    }
+}
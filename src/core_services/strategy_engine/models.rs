@@ -0,0 +1,433 @@
+/*
+ * QuantumArb 2.0 - Core Services: Champion / Challenger Model Routing
+ *
+ * File: src/core_services/strategy_engine/models.rs
+ *
+ * Description:
+ * Trials a new model against the live one without letting it trade. Two
+ * inference servers are queried with the same features on every cycle:
+ *
+ *   champion    its signal gates trading (the SOR only buys on BUY)
+ *   challenger  shadow only: its predictions are logged and published
+ *
+ * Each model also keeps a paper position - long or short `paper_size` units
+ * from the mid at its latest prediction - marked at the mid of the next one,
+ * so the two models' hypothetical P&L can be compared on GET /models.
+ * POST /models/promote swaps the two endpoints (their statistics move with
+ * them) without a redeploy.
+ *
+ * A stale champion signal (no successful prediction within `max_signal_age`)
+ * holds trading. With no champion endpoint configured the engine trades
+ * without a model signal, as before.
+ *
+ * Every prediction is also scored against the subsequent price move
+ * (`feedback.rs`; GET /models/feedback). With a minimum accuracy configured,
+ * the champion's signal stops gating trades while its rolling accuracy is
+ * below it, and gates them again once it recovers.
+ *
+ * Model inputs are the symbol's latest snapshot in the feature store written
+ * by the data bus connector (`quantumarb-features`), so live predictions see
+ * the same features as training. Without a store they are fixed values.
+ *
+ * Configuration (environment):
+ *   QA_FEATURE_REDIS_URL=             feature store (unset: fixed features)
+ *   QA_MODEL_CHAMPION_URL=            inference server /predict endpoint
+ *   QA_MODEL_CHALLENGER_URL=          optional shadow model endpoint
+ *   QA_MODEL_TIMEOUT_MS=50            per-request timeout
+ *   QA_MODEL_MAX_SIGNAL_AGE_SECS=30   champion signal older than this holds trading
+ *   QA_MODEL_PAPER_SIZE=50            units behind each model's paper position
+ */
+
+use crate::feedback::{FeedbackConfig, FeedbackMetrics, ModelFeedback};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+pub const SHADOW_TOPIC: &str = "models.shadow_predictions";
+pub const PROMOTION_TOPIC: &str = "models.promotions";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct ModelConfig {
+    pub champion_url: Option<String>,
+    pub challenger_url: Option<String>,
+    pub timeout: Duration,
+    pub max_signal_age: Duration,
+    pub paper_size: u32,
+    pub feedback: FeedbackConfig,
+}
+
+impl ModelConfig {
+    pub fn from_env() -> ModelConfig {
+        let url = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let number = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|v: &u64| *v > 0).unwrap_or(default)
+        };
+        ModelConfig {
+            champion_url: url("QA_MODEL_CHAMPION_URL"),
+            challenger_url: url("QA_MODEL_CHALLENGER_URL"),
+            timeout: Duration::from_millis(number("QA_MODEL_TIMEOUT_MS", 50)),
+            max_signal_age: Duration::from_secs(number("QA_MODEL_MAX_SIGNAL_AGE_SECS", 30)),
+            paper_size: number("QA_MODEL_PAPER_SIZE", 50) as u32,
+            feedback: FeedbackConfig::from_env(),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Signal {
+    Buy,
+    Sell,
+}
+
+impl Signal {
+    pub fn direction(self) -> i64 {
+        match self {
+            Signal::Buy => 1,
+            Signal::Sell => -1,
+        }
+    }
+}
+
+/// One model answer: the signal and, when the server provides it, the
+/// model's probability that the price moves up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Prediction {
+    pub signal: Signal,
+    pub probability_up: Option<f64>,
+}
+
+/// Request body of the inference server's POST /predict.
+#[derive(Debug, Clone, Serialize)]
+pub struct PredictionFeatures {
+    pub news_sentiment: f64,
+    pub mavg_spread: f64,
+}
+
+/// Where model inputs come from.
+pub enum FeatureSource {
+    /// Latest snapshots in the Redis feature store.
+    Store(redis::aio::MultiplexedConnection),
+    /// Fixed values, for running without a feature store.
+    Fixed,
+}
+
+impl FeatureSource {
+    /// Connects to QA_FEATURE_REDIS_URL, if set.
+    pub async fn from_env() -> FeatureSource {
+        let Some(url) = std::env::var("QA_FEATURE_REDIS_URL").ok().filter(|v| !v.is_empty()) else {
+            return FeatureSource::Fixed;
+        };
+        let connection = match redis::Client::open(url.as_str()) {
+            Ok(client) => client.get_multiplexed_async_connection().await,
+            Err(e) => Err(e),
+        };
+        match connection {
+            Ok(con) => FeatureSource::Store(con),
+            Err(e) => {
+                println!("Feature store unavailable at {} ({}); using fixed model features.", url, e);
+                FeatureSource::Fixed
+            }
+        }
+    }
+
+    /// The model inputs for `symbol`: news_sentiment is the decay-weighted 5m
+    /// sentiment window, neutral without news in the window.
+    pub async fn features(&mut self, symbol: &str) -> Result<PredictionFeatures, String> {
+        let con = match self {
+            FeatureSource::Store(con) => con,
+            FeatureSource::Fixed => return Ok(PredictionFeatures { news_sentiment: 0.2, mavg_spread: 1.5 }),
+        };
+        let snapshot = quantumarb_features::read_latest(con, symbol)
+            .await
+            .map_err(|e| format!("feature store read failed: {}", e))?
+            .ok_or_else(|| format!("no features stored for {}", symbol))?;
+        Ok(PredictionFeatures {
+            news_sentiment: snapshot.sentiment_5m.unwrap_or(0.0),
+            mavg_spread: snapshot.mavg_spread.ok_or_else(|| format!("not enough price history for {}", symbol))?,
+        })
+    }
+}
+
+/// Response body of the inference server's POST /predict.
+#[derive(Debug, Clone, Deserialize)]
+struct PredictionResponse {
+    signal: Signal,
+    #[serde(default)]
+    probability: Option<f64>,
+}
+
+/// One model endpoint and its running statistics.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ModelStats {
+    pub url: String,
+    pub predictions: u64,
+    pub failures: u64,
+    pub last_signal: Option<Signal>,
+    pub last_prediction_utc: Option<String>,
+    /// Signed paper position in units (positive long).
+    pub paper_position: i64,
+    /// P&L of the paper position, in dollars, marked at the latest prediction.
+    pub hypothetical_pnl: f64,
+    #[serde(skip)]
+    entry_mid: Option<f64>,
+    #[serde(skip)]
+    last_success: Option<Instant>,
+    #[serde(skip)]
+    feedback: ModelFeedback,
+}
+
+impl ModelStats {
+    fn new(url: String) -> ModelStats {
+        ModelStats {
+            url,
+            predictions: 0,
+            failures: 0,
+            last_signal: None,
+            last_prediction_utc: None,
+            paper_position: 0,
+            hypothetical_pnl: 0.0,
+            entry_mid: None,
+            last_success: None,
+            feedback: ModelFeedback::default(),
+        }
+    }
+
+    /// Marks the paper position to `mid` and re-opens it in the signal's direction.
+    fn record(&mut self, prediction: Prediction, mid: f64, paper_size: u32, now: Instant) {
+        let signal = prediction.signal;
+        self.feedback.record_prediction(prediction, mid, now);
+        if let Some(entry) = self.entry_mid {
+            self.hypothetical_pnl += self.paper_position as f64 * (mid - entry);
+        }
+        self.paper_position = signal.direction() * paper_size as i64;
+        self.entry_mid = Some(mid);
+        self.predictions += 1;
+        self.last_signal = Some(signal);
+        self.last_prediction_utc = Some(chrono::Utc::now().to_rfc3339());
+        self.last_success = Some(now);
+    }
+}
+
+/// Body of a GET /models (and POST /models/promote) response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ModelReport {
+    pub champion: Option<ModelStats>,
+    pub challenger: Option<ModelStats>,
+    /// Cycles in which both models produced a prediction.
+    pub compared: u64,
+    /// Fraction of compared cycles in which the two signals agreed.
+    pub agreement_rate: Option<f64>,
+    pub promotions: u64,
+    pub last_promotion_utc: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ModelFeedbackReport {
+    pub url: String,
+    pub metrics: FeedbackMetrics,
+}
+
+/// Body of a GET /models/feedback response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FeedbackReport {
+    pub champion: Option<ModelFeedbackReport>,
+    pub challenger: Option<ModelFeedbackReport>,
+    pub min_accuracy: Option<f64>,
+    /// Whether the champion's signal currently gates trades.
+    pub ml_filter_enabled: bool,
+    /// Why the filter is disabled, when it is.
+    pub ml_filter_disabled_reason: Option<String>,
+}
+
+/// Whether the champion's latest signal allows a buy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gate {
+    Trade,
+    Hold(String),
+}
+
+#[derive(Debug)]
+struct RouterState {
+    champion: Option<ModelStats>,
+    challenger: Option<ModelStats>,
+    compared: u64,
+    agreed: u64,
+    promotions: u64,
+    last_promotion_utc: Option<String>,
+    /// Set while the champion's accuracy is below the configured minimum.
+    filter_disabled: Option<String>,
+}
+
+impl RouterState {
+    /// Disables the ML filter while the champion's accuracy is below the
+    /// minimum, and re-enables it once it recovers (or the model changes).
+    fn refresh_filter(&mut self, config: &FeedbackConfig) {
+        let Some(min_accuracy) = config.min_accuracy else {
+            return;
+        };
+        let accuracy = self.champion.as_ref().and_then(|m| m.feedback.accuracy(config.min_samples));
+        let disabled = match accuracy {
+            Some(accuracy) if accuracy < min_accuracy => Some(format!(
+                "champion accuracy {:.1}% below minimum {:.1}%",
+                accuracy * 100.0,
+                min_accuracy * 100.0
+            )),
+            _ => None,
+        };
+        match (&self.filter_disabled, &disabled) {
+            (None, Some(reason)) => println!("  -> ML filter disabled: {}.", reason),
+            (Some(_), None) => println!("  -> ML filter re-enabled."),
+            _ => {}
+        }
+        self.filter_disabled = disabled;
+    }
+}
+
+/// Champion/challenger state shared between the query task, the trading path
+/// and the API. The trading path only takes the read lock.
+#[derive(Debug, Clone)]
+pub struct ModelRouter {
+    config: ModelConfig,
+    state: Arc<RwLock<RouterState>>,
+}
+
+// --- Routing ---
+
+impl ModelRouter {
+    pub fn new(config: ModelConfig) -> ModelRouter {
+        let state = RouterState {
+            champion: config.champion_url.clone().map(ModelStats::new),
+            challenger: config.challenger_url.clone().map(ModelStats::new),
+            compared: 0,
+            agreed: 0,
+            promotions: 0,
+            last_promotion_utc: None,
+            filter_disabled: None,
+        };
+        ModelRouter { config, state: Arc::new(RwLock::new(state)) }
+    }
+
+    pub fn config(&self) -> &ModelConfig {
+        &self.config
+    }
+
+    /// Current (champion, challenger) endpoints.
+    pub fn endpoints(&self) -> (Option<String>, Option<String>) {
+        let state = self.state.read().unwrap();
+        (state.champion.as_ref().map(|m| m.url.clone()), state.challenger.as_ref().map(|m| m.url.clone()))
+    }
+
+    /// Decides whether a buy may go ahead on the champion's latest signal.
+    pub fn gate(&self) -> Gate {
+        let state = self.state.read().unwrap();
+        let Some(champion) = &state.champion else {
+            return Gate::Trade;
+        };
+        if state.filter_disabled.is_some() {
+            return Gate::Trade;
+        }
+        let fresh = champion.last_success.is_some_and(|at| at.elapsed() <= self.config.max_signal_age);
+        match champion.last_signal {
+            _ if !fresh => Gate::Hold("no recent champion signal".to_string()),
+            Some(Signal::Buy) => Gate::Trade,
+            Some(Signal::Sell) => Gate::Hold("champion signals SELL".to_string()),
+            None => Gate::Hold("no champion signal".to_string()),
+        }
+    }
+
+    /// Records one cycle's predictions (None when the endpoint is not
+    /// configured) at the given mid, and settles earlier predictions whose
+    /// outcome horizon has passed.
+    pub fn record(
+        &self,
+        champion: Option<Result<Prediction, String>>,
+        challenger: Option<Result<Prediction, String>>,
+        mid: f64,
+    ) {
+        let mut guard = self.state.write().unwrap();
+        let state = &mut *guard;
+        let paper_size = self.config.paper_size;
+        let now = Instant::now();
+        for (slot, result) in [(&mut state.champion, champion.as_ref()), (&mut state.challenger, challenger.as_ref())] {
+            if let Some(model) = slot.as_mut() {
+                model.feedback.observe_mid(&self.config.feedback, mid, now);
+            }
+            if let (Some(model), Some(result)) = (slot.as_mut(), result) {
+                match result {
+                    Ok(prediction) => model.record(*prediction, mid, paper_size, now),
+                    Err(e) => {
+                        model.failures += 1;
+                        println!("  -> Model {} failed: {}", model.url, e);
+                    }
+                }
+            }
+        }
+        if let (Some(Ok(champion)), Some(Ok(challenger))) = (champion, challenger) {
+            state.compared += 1;
+            state.agreed += (champion.signal == challenger.signal) as u64;
+        }
+        state.refresh_filter(&self.config.feedback);
+    }
+
+    /// Makes the challenger the champion and vice versa.
+    pub fn promote(&self) -> Result<ModelReport, Rejection> {
+        let mut state = self.state.write().unwrap();
+        if state.challenger.is_none() {
+            return Err(Rejection::new(RejectCode::SystemConflict, "No challenger model is configured."));
+        }
+        let previous = state.champion.take();
+        state.champion = state.challenger.take();
+        state.challenger = previous;
+        state.promotions += 1;
+        state.last_promotion_utc = Some(chrono::Utc::now().to_rfc3339());
+        state.refresh_filter(&self.config.feedback);
+        drop(state);
+        Ok(self.report())
+    }
+
+    pub fn report(&self) -> ModelReport {
+        let state = self.state.read().unwrap();
+        ModelReport {
+            champion: state.champion.clone(),
+            challenger: state.challenger.clone(),
+            compared: state.compared,
+            agreement_rate: (state.compared > 0).then(|| state.agreed as f64 / state.compared as f64),
+            promotions: state.promotions,
+            last_promotion_utc: state.last_promotion_utc.clone(),
+        }
+    }
+
+    pub fn feedback_report(&self) -> FeedbackReport {
+        let state = self.state.read().unwrap();
+        let report = |model: &Option<ModelStats>| {
+            model.as_ref().map(|m| ModelFeedbackReport { url: m.url.clone(), metrics: m.feedback.metrics() })
+        };
+        FeedbackReport {
+            champion: report(&state.champion),
+            challenger: report(&state.challenger),
+            min_accuracy: self.config.feedback.min_accuracy,
+            ml_filter_enabled: state.champion.is_some() && state.filter_disabled.is_none(),
+            ml_filter_disabled_reason: state.filter_disabled.clone(),
+        }
+    }
+}
+
+/// Asks one inference server for a prediction.
+pub async fn predict(client: &reqwest::Client, url: &str, features: &PredictionFeatures) -> Result<Prediction, String> {
+    let response = client.post(url).json(features).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    response
+        .json::<PredictionResponse>()
+        .await
+        .map(|r| Prediction { signal: r.signal, probability_up: r.probability })
+        .map_err(|e| e.to_string())
+}
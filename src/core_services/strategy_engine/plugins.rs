@@ -0,0 +1,469 @@
+/*
+ * QuantumArb 2.0 - Core Services: Strategy Plugins
+ *
+ * File: src/core_services/strategy_engine/plugins.rs
+ *
+ * Description:
+ * Runs strategies shipped as WebAssembly plugins next to the built-in SOR
+ * strategy, so a quant can deploy one without rebuilding the engine. A
+ * plugin is written against `quantumarb-strategy-sdk`, which documents the
+ * ABI, and dropped into QA_PLUGIN_DIR as <name>.wasm (or <name>.wat, the
+ * text format), with its configuration, if it takes one, in <name>.json.
+ *
+ * Hot loading: the directory is rescanned every QA_PLUGIN_RELOAD_SECS. A new
+ * module is loaded, a changed module or configuration reloads its plugin
+ * from scratch, and a removed module unloads it. Modules are compiled
+ * outside the lock the trading loop takes, so a reload never stalls a pass.
+ * DELETE /plugins/{name} unloads a plugin until its files change again.
+ *
+ * Sandboxing: a plugin imports nothing but `qa.log`, so it has no files,
+ * network or clock; every call runs on QA_PLUGIN_FUEL units of fuel (about
+ * one per instruction) and its memory is capped at QA_PLUGIN_MEMORY_MB. A
+ * plugin that runs out of fuel, traps or answers garbage is restarted from
+ * its module and configuration, and quarantined after QA_PLUGIN_MAX_FAULTS
+ * faults in a row, until its files change or it is reloaded.
+ *
+ * Each pass of the standard runtime hands every running plugin the venue
+ * books as a `MarketView`; the orders it returns are risk checked like the
+ * built-in strategy's, tagged with strategy id "plugin:<name>". Plugins
+ * stand aside while the instrument is paused on data quality or under an
+ * alt-data anomaly; the low-latency runtime runs the built-in strategy only.
+ *
+ * API (port 3040, with the model API):
+ *   GET    /plugins          every plugin's state and counters
+ *   POST   /plugins/reload   rescan the directory now, retrying quarantined
+ *                            plugins
+ *   DELETE /plugins/{name}   unload a plugin
+ *
+ * Configuration (environment):
+ *   QA_PLUGIN_DIR=plugins
+ *   QA_PLUGIN_RELOAD_SECS=5
+ *   QA_PLUGIN_FUEL=50000000
+ *   QA_PLUGIN_MEMORY_MB=64
+ *   QA_PLUGIN_MAX_FAULTS=3
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_strategy_sdk::{MarketView, OrderIntent, ABI_VERSION};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use wasmtime::{Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+/// The largest order list a plugin may answer with.
+const MAX_OUTPUT_BYTES: u32 = 1 << 20;
+/// Longest log line kept from a plugin.
+const MAX_LOG_BYTES: usize = 1024;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct PluginConfig {
+    pub dir: PathBuf,
+    pub reload_interval: Duration,
+    pub fuel: u64,
+    pub memory_bytes: usize,
+    pub max_faults: u32,
+}
+
+impl PluginConfig {
+    pub fn from_env() -> PluginConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        PluginConfig {
+            dir: PathBuf::from(var("QA_PLUGIN_DIR", "plugins".to_string())),
+            reload_interval: Duration::from_secs(var("QA_PLUGIN_RELOAD_SECS", 5u64).max(1)),
+            fuel: var("QA_PLUGIN_FUEL", 50_000_000),
+            memory_bytes: var("QA_PLUGIN_MEMORY_MB", 64usize) << 20,
+            max_faults: var("QA_PLUGIN_MAX_FAULTS", 3u32).max(1),
+        }
+    }
+}
+
+// --- Status ---
+
+/// A plugin's state and counters, served on GET /plugins.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct PluginStatus {
+    pub name: String,
+    /// running, quarantined, failed (would not load) or unloaded.
+    pub state: String,
+    pub loaded_utc: DateTime<Utc>,
+    pub calls: u64,
+    pub orders: u64,
+    pub faults: u64,
+    pub restarts: u64,
+    /// Fuel the last call used.
+    pub last_fuel: u64,
+    pub last_error: Option<String>,
+}
+
+// --- Instances ---
+
+/// What a plugin's store holds.
+struct HostState {
+    name: String,
+    limits: StoreLimits,
+}
+
+/// An instantiated plugin.
+struct Instance {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: TypedFunc<(i32, i32), ()>,
+    on_market: TypedFunc<(i32, i32), i64>,
+}
+
+impl Instance {
+    /// Copies `input` into the plugin's memory and returns where.
+    fn write(&mut self, input: &[u8]) -> wasmtime::Result<(i32, i32)> {
+        let len = i32::try_from(input.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, input)?;
+        Ok((ptr, len))
+    }
+
+    fn on_market(&mut self, view: &[u8], fuel: u64) -> wasmtime::Result<(Vec<OrderIntent>, u64)> {
+        self.store.set_fuel(fuel)?;
+        let (ptr, len) = self.write(view)?;
+        let packed = self.on_market.call(&mut self.store, (ptr, len))?;
+        let mut orders = Vec::new();
+        if packed != 0 {
+            let (out_ptr, out_len) = quantumarb_strategy_sdk::unpack(packed);
+            if out_len > MAX_OUTPUT_BYTES {
+                let error = format!("answered {} bytes, more than {}", out_len, MAX_OUTPUT_BYTES);
+                return Err(wasmtime::Error::msg(error));
+            }
+            let mut output = vec![0; out_len as usize];
+            self.memory.read(&self.store, out_ptr as usize, &mut output)?;
+            self.free.call(&mut self.store, (out_ptr as i32, out_len as i32))?;
+            orders = serde_json::from_slice(&output)?;
+        }
+        Ok((orders, fuel - self.store.get_fuel()?))
+    }
+}
+
+/// A plugin's module and configuration, and its instance while it runs.
+struct Plugin {
+    module: Module,
+    config: Vec<u8>,
+    /// Latest modification time of its files, to notice changes.
+    modified: Option<SystemTime>,
+    instance: Option<Instance>,
+    consecutive_faults: u32,
+    status: PluginStatus,
+}
+
+// --- Host ---
+
+/// The loaded plugins.
+pub struct PluginHost {
+    engine: Engine,
+    linker: Linker<HostState>,
+    config: PluginConfig,
+    plugins: BTreeMap<String, Plugin>,
+}
+
+pub type SharedPlugins = Arc<Mutex<PluginHost>>;
+
+impl PluginHost {
+    pub fn new(config: PluginConfig) -> wasmtime::Result<PluginHost> {
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap("qa", "log", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let Some(memory) = caller.get_export("memory").and_then(|export| export.into_memory()) else {
+                return;
+            };
+            let start = ptr as u32 as usize;
+            let end = start.saturating_add((len.max(0) as usize).min(MAX_LOG_BYTES));
+            if let Some(bytes) = memory.data(&caller).get(start..end) {
+                println!("  [plugin {}] {}", caller.data().name, String::from_utf8_lossy(bytes));
+            }
+        })?;
+        Ok(PluginHost { engine, linker, config, plugins: BTreeMap::new() })
+    }
+
+    pub fn statuses(&self) -> Vec<PluginStatus> {
+        self.plugins.values().map(|plugin| plugin.status.clone()).collect()
+    }
+
+    /// Installs a compiled plugin, replacing any of the same name, and
+    /// starts it.
+    fn install(&mut self, name: &str, module: Module, config: Vec<u8>, modified: Option<SystemTime>) {
+        let status = PluginStatus {
+            name: name.to_string(),
+            state: "running".to_string(),
+            loaded_utc: Utc::now(),
+            calls: 0,
+            orders: 0,
+            faults: 0,
+            restarts: 0,
+            last_fuel: 0,
+            last_error: None,
+        };
+        let mut plugin = Plugin { module, config, modified, instance: None, consecutive_faults: 0, status };
+        match self.instantiate(name, &plugin) {
+            Ok(instance) => {
+                println!("Loaded strategy plugin '{}'.", name);
+                plugin.instance = Some(instance);
+            }
+            Err(e) => {
+                println!("Strategy plugin '{}' failed to load: {:#}", name, e);
+                plugin.status.state = "failed".to_string();
+                plugin.status.last_error = Some(format!("{:#}", e));
+            }
+        }
+        self.plugins.insert(name.to_string(), plugin);
+    }
+
+    /// A fresh instance of a plugin, initialized with its configuration.
+    fn instantiate(&self, name: &str, plugin: &Plugin) -> wasmtime::Result<Instance> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.config.memory_bytes).instances(1).build();
+        let mut store = Store::new(&self.engine, HostState { name: name.to_string(), limits });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.config.fuel)?;
+        let instance = self.linker.instantiate(&mut store, &plugin.module)?;
+        let version = instance.get_typed_func::<(), i32>(&mut store, "qa_abi_version")?.call(&mut store, ())?;
+        if version != ABI_VERSION {
+            let error = format!("built for plugin ABI {}, the engine runs {}", version, ABI_VERSION);
+            return Err(wasmtime::Error::msg(error));
+        }
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("exports no memory"))?;
+        let mut loaded = Instance {
+            memory,
+            alloc: instance.get_typed_func(&mut store, "qa_alloc")?,
+            free: instance.get_typed_func(&mut store, "qa_free")?,
+            on_market: instance.get_typed_func(&mut store, "qa_on_market")?,
+            store,
+        };
+        let init = instance.get_typed_func::<(i32, i32), i32>(&mut loaded.store, "qa_init")?;
+        let config = if plugin.config.is_empty() { b"{}".as_slice() } else { &plugin.config };
+        let (ptr, len) = loaded.write(config)?;
+        match init.call(&mut loaded.store, (ptr, len))? {
+            0 => Ok(loaded),
+            code => Err(wasmtime::Error::msg(format!("refused its configuration (code {})", code))),
+        }
+    }
+
+    /// Unloads a plugin; false if there is none by that name.
+    pub fn unload(&mut self, name: &str) -> bool {
+        let Some(plugin) = self.plugins.get_mut(name) else {
+            return false;
+        };
+        plugin.instance = None;
+        plugin.status.state = "unloaded".to_string();
+        println!("Unloaded strategy plugin '{}'.", name);
+        true
+    }
+
+    /// Runs every running plugin on `view`; returns the orders each wants.
+    pub fn on_market(&mut self, view: &MarketView) -> Vec<(String, OrderIntent)> {
+        let Ok(input) = serde_json::to_vec(view) else {
+            return Vec::new();
+        };
+        let mut orders = Vec::new();
+        let names: Vec<String> = self.plugins.keys().cloned().collect();
+        for name in names {
+            let Some(plugin) = self.plugins.get_mut(&name) else {
+                continue;
+            };
+            let Some(instance) = plugin.instance.as_mut() else {
+                continue;
+            };
+            plugin.status.calls += 1;
+            match instance.on_market(&input, self.config.fuel) {
+                Ok((intents, fuel)) => {
+                    plugin.consecutive_faults = 0;
+                    plugin.status.last_fuel = fuel;
+                    plugin.status.orders += intents.len() as u64;
+                    orders.extend(intents.into_iter().map(|intent| (name.clone(), intent)));
+                }
+                Err(e) => self.fault(&name, format!("{:#}", e)),
+            }
+        }
+        orders
+    }
+
+    /// Restarts a plugin that faulted, or quarantines it.
+    fn fault(&mut self, name: &str, error: String) {
+        let Some(mut plugin) = self.plugins.remove(name) else {
+            return;
+        };
+        plugin.instance = None;
+        plugin.consecutive_faults += 1;
+        plugin.status.faults += 1;
+        plugin.status.last_fuel = self.config.fuel;
+        println!("  -> Strategy plugin '{}' faulted: {}", name, error);
+        plugin.status.last_error = Some(error);
+        if plugin.consecutive_faults >= self.config.max_faults {
+            let faults = plugin.consecutive_faults;
+            println!("  -> Strategy plugin '{}' quarantined after {} faults in a row.", name, faults);
+            plugin.status.state = "quarantined".to_string();
+        } else {
+            match self.instantiate(name, &plugin) {
+                Ok(instance) => {
+                    plugin.instance = Some(instance);
+                    plugin.status.restarts += 1;
+                }
+                Err(e) => {
+                    plugin.status.state = "failed".to_string();
+                    plugin.status.last_error = Some(format!("{:#}", e));
+                }
+            }
+        }
+        self.plugins.insert(name.to_string(), plugin);
+    }
+}
+
+// --- Reloading ---
+
+/// A plugin's files in the plugin directory.
+struct PluginFiles {
+    module: PathBuf,
+    config: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+/// The plugins in `dir`, by name.
+fn scan_dir(dir: &Path) -> BTreeMap<String, PluginFiles> {
+    let mut found = BTreeMap::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return found;
+    };
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_module = matches!(path.extension().and_then(|e| e.to_str()), Some("wasm" | "wat"));
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()).filter(|_| is_module) else {
+            continue;
+        };
+        let config = Some(path.with_extension("json")).filter(|config| config.is_file());
+        let modified = modified(&path).max(config.as_deref().and_then(modified));
+        found.insert(name.to_string(), PluginFiles { module: path.clone(), config, modified });
+    }
+    found
+}
+
+/// Brings the loaded plugins in line with the plugin directory. With
+/// `retry`, quarantined and unloaded plugins are loaded again too.
+pub fn reload(plugins: &SharedPlugins, retry: bool) {
+    let (dir, engine) = {
+        let host = plugins.lock().unwrap();
+        (host.config.dir.clone(), host.engine.clone())
+    };
+    let found = scan_dir(&dir);
+    let changed: Vec<(String, PluginFiles)> = {
+        let mut host = plugins.lock().unwrap();
+        let removed: Vec<String> = host.plugins.keys().filter(|name| !found.contains_key(*name)).cloned().collect();
+        for name in removed {
+            host.plugins.remove(&name);
+            println!("Strategy plugin '{}' removed; unloaded.", name);
+        }
+        found
+            .into_iter()
+            .filter(|(name, files)| match host.plugins.get(name) {
+                Some(plugin) => plugin.modified != files.modified || (retry && plugin.instance.is_none()),
+                None => true,
+            })
+            .collect()
+    };
+    for (name, files) in changed {
+        // Compiled without the lock, so the trading loop keeps running.
+        let module = Module::from_file(&engine, &files.module);
+        let config = files.config.as_ref().map(std::fs::read).transpose();
+        let mut host = plugins.lock().unwrap();
+        match (module, config) {
+            (Ok(module), Ok(config)) => host.install(&name, module, config.unwrap_or_default(), files.modified),
+            (Err(e), _) => println!("Strategy plugin '{}' does not compile: {:#}", name, e),
+            (_, Err(e)) => println!("Strategy plugin '{}' configuration unreadable: {}", name, e),
+        }
+    }
+}
+
+/// Rescans the plugin directory periodically.
+pub async fn watch(plugins: SharedPlugins) {
+    let interval = plugins.lock().unwrap().config.reload_interval;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let plugins = plugins.clone();
+        let _ = tokio::task::spawn_blocking(move || reload(&plugins, false)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_strategy_sdk::{Side, VenueBook};
+
+    const QUOTER: &str = r#"(module
+        (import "qa" "log" (func $log (param i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 16) "[{\"venue_id\":1,\"side\":\"buy\",\"price\":6010050,\"size\":2}]")
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "qa_abi_version") (result i32) i32.const 1)
+        (func (export "qa_alloc") (param $len i32) (result i32) (local $ptr i32)
+            global.get $next local.set $ptr
+            global.get $next local.get $len i32.add global.set $next
+            local.get $ptr)
+        (func (export "qa_free") (param i32 i32))
+        (func (export "qa_init") (param $ptr i32) (param $len i32) (result i32)
+            (call $log (local.get $ptr) (local.get $len))
+            i32.const 0)
+        (func (export "qa_on_market") (param i32 i32) (result i64)
+            (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 54))))"#;
+
+    #[test]
+    fn plugins_trade_within_their_fuel_and_memory_and_are_quarantined_when_they_fault() {
+        let config = PluginConfig {
+            dir: PathBuf::from("unused"),
+            reload_interval: Duration::from_secs(5),
+            fuel: 100_000,
+            memory_bytes: 1 << 20,
+            max_faults: 2,
+        };
+        let mut host = PluginHost::new(config).unwrap();
+        let compile = |host: &PluginHost, source: &str| Module::new(&host.engine, source).unwrap();
+        let spinner = QUOTER.replace("(i64.or (i64.shl", "(loop $spin (br $spin)) (i64.or (i64.shl");
+        let greedy = QUOTER.replace("(memory (export \"memory\") 1)", "(memory (export \"memory\") 17)");
+        for (name, source) in [("quoter", QUOTER), ("spinner", spinner.as_str()), ("greedy", greedy.as_str())] {
+            let module = compile(&host, source);
+            host.install(name, module, br#"{"edge":5}"#.to_vec(), None);
+        }
+
+        let view = MarketView {
+            instrument_id: 1,
+            symbol: "BTC".to_string(),
+            price_decimals: 2,
+            venues: vec![VenueBook { venue_id: 1, best_bid: 6010000, bid_size: 5, best_ask: 6010050, ask_size: 4 }],
+            time_ns: 0,
+        };
+        let order = OrderIntent { venue_id: 1, side: Side::Buy, price: 6010050, size: 2 };
+        assert_eq!(host.on_market(&view), [("quoter".to_string(), order)]);
+        assert_eq!(host.on_market(&view), [("quoter".to_string(), order)]);
+
+        let states: Vec<(String, String, u64, u64)> = host
+            .statuses()
+            .into_iter()
+            .map(|status| (status.name, status.state, status.faults, status.restarts))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("greedy".to_string(), "failed".to_string(), 0, 0),
+                ("quoter".to_string(), "running".to_string(), 0, 0),
+                ("spinner".to_string(), "quarantined".to_string(), 2, 1),
+            ]
+        );
+        assert!(host.unload("quoter"));
+        assert!(host.on_market(&view).is_empty());
+    }
+}
@@ -0,0 +1,748 @@
+/*
+ * QuantumArb 2.0 - Core Services: Blotter Book
+ *
+ * File: src/core_services/trade_blotter/blotter.rs
+ *
+ * Description:
+ * Folds the order flow into one row per order and one per execution report:
+ *
+ *   orders.requests     OrderRequest       -> the order, status New
+ *   audit.events        RiskDecisionAudit  -> the risk verdict; Approved,
+ *                                             RiskRejected or Held
+ *   execution_reports   ExecutionReport    -> an execution row; the order
+ *                                             goes Working, PartiallyFilled,
+ *                                             Filled, Canceled or VenueRejected
+ *
+ * A risk decision carries the request it was made on, so it opens the order
+ * when it arrives first; a package decision opens every leg, each under its
+ * leg order id and tagged with the package and leg number. An execution
+ * report for an order not seen yet is parked until the order arrives (up to
+ * MAX_PARKED_ORDERS orders). A report whose (exchange order id, ExecID) was
+ * already booked is ignored, so redeliveries count once.
+ *
+ * A Filled, Canceled or rejected order keeps its status; later fills still
+ * add to its filled quantity. A risk decision on an order the venue has
+ * already answered is recorded without changing its status.
+ *
+ * A bust (TradeBusted) or correction (TradeCorrected) gets an execution row
+ * of its own, naming the fill it amends (`amends`); the fill's row names it
+ * back (`amended_by`), so the original and corrected records stay side by
+ * side. The order's filled quantity, average price and fees drop the fill's
+ * and, for a correction, take the corrected one's instead. One naming a fill
+ * the blotter never saw changes no order.
+ *
+ * Every row gets a sequence number in the order the blotter first saw it.
+ * Queries return rows newest first, a page at a time: the next page starts
+ * below the last sequence returned (`next_cursor`).
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_errors::RejectCode;
+use quantumarb_money::{Money, Price};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{ReferenceData, DEFAULT_PRICE_DECIMALS};
+use quantumarb_risk::concentration::venue_name;
+use quantumarb_types::{CheckedRequest, RiskDecisionAudit, RiskOutcome};
+use quantumarb_wire::{ExecutionReport, Liquidity, OrderPriority, OrderRequest, OrderSide, OrderStatus, TradingMode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// Orders execution reports may be parked for before their order arrives.
+const MAX_PARKED_ORDERS: usize = 10_000;
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1_000;
+
+// --- Events ---
+
+/// One message the blotter books, as journaled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BlotterEvent {
+    Order(OrderRequest),
+    Decision(RiskDecisionAudit),
+    Report(ExecutionReport),
+}
+
+// --- Rows ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum BlotterStatus {
+    /// Seen on its way to the venue, with no risk verdict or venue answer yet.
+    New,
+    Approved,
+    RiskRejected,
+    /// Parked for a supervisor.
+    Held,
+    /// Acknowledged by the venue and unfilled.
+    Working,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    VenueRejected,
+}
+
+impl BlotterStatus {
+    fn is_final(self) -> bool {
+        matches!(self, Self::Filled | Self::Canceled | Self::RiskRejected | Self::VenueRejected)
+    }
+
+    /// Whether a venue has answered the order.
+    fn at_venue(self) -> bool {
+        matches!(self, Self::Working | Self::PartiallyFilled | Self::Filled | Self::Canceled | Self::VenueRejected)
+    }
+}
+
+/// Where the order was sent and as what.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct OrderRoute {
+    /// 0 when the order was not routed.
+    pub venue_id: u32,
+    pub venue: Option<String>,
+    pub priority: OrderPriority,
+    /// The package the order is a leg of.
+    pub package_id: Option<Uuid>,
+    /// Leg number within the package, from 1.
+    pub leg: Option<u32>,
+}
+
+/// The risk gateway's verdict on the order (on its package, for a leg).
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct RiskVerdict {
+    pub outcome: RiskOutcome,
+    pub code: Option<RejectCode>,
+    pub reason: Option<String>,
+    pub checked_by: String,
+    pub decided_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct BlotterOrder {
+    pub sequence: u64,
+    pub order_id: Uuid,
+    pub received_utc: DateTime<Utc>,
+    pub updated_utc: DateTime<Utc>,
+    pub account_id: u32,
+    /// Empty for orders no strategy sent.
+    pub strategy_id: String,
+    pub instrument_id: u32,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: u32,
+    pub mode: TradingMode,
+    pub route: OrderRoute,
+    pub risk: Option<RiskVerdict>,
+    pub status: BlotterStatus,
+    pub exchange_order_id: Option<String>,
+    pub filled_size: u32,
+    pub average_price: Option<f64>,
+    pub fees: Money,
+    /// Why the venue rejected the order.
+    pub reject_reason: Option<String>,
+    #[serde(skip)]
+    filled_notional: f64,
+}
+
+/// One execution report, with the context of its order.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct BlotterExecution {
+    pub sequence: u64,
+    pub received_utc: DateTime<Utc>,
+    /// When the venue executed it, if the venue said.
+    pub transact_utc: Option<DateTime<Utc>>,
+    pub order_id: Uuid,
+    pub exchange_order_id: String,
+    pub exec_id: String,
+    pub status: OrderStatus,
+    pub account_id: u32,
+    pub strategy_id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub venue: Option<String>,
+    /// 0 for a report that is not a fill.
+    pub filled_size: u32,
+    pub filled_price: Option<f64>,
+    pub cumulative_size: u32,
+    pub leaves_size: u32,
+    pub liquidity: Option<Liquidity>,
+    pub fee: Money,
+    /// The ExecID of the fill a bust or correction amends.
+    pub amends: Option<String>,
+    /// The ExecID of the bust or correction that amended this fill.
+    pub amended_by: Option<String>,
+}
+
+// --- Queries ---
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ApiSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    /// Every matching row, unpaged.
+    Csv,
+}
+
+/// Query of GET /blotter/orders and /blotter/executions. Rows are matched on
+/// the time the blotter received them, in [from_utc, to_utc); `status`
+/// applies to orders only.
+#[derive(Debug, Clone, Default, Deserialize, ApiSchema)]
+pub struct BlotterQuery {
+    pub from_utc: Option<DateTime<Utc>>,
+    pub to_utc: Option<DateTime<Utc>>,
+    pub symbol: Option<String>,
+    pub strategy: Option<String>,
+    pub account: Option<u32>,
+    pub status: Option<BlotterStatus>,
+    /// Rows per page (default 100, at most 1000).
+    pub limit: Option<usize>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<u64>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+impl BlotterQuery {
+    fn matches(&self, received_utc: DateTime<Utc>, symbol: &str, strategy_id: &str, account_id: u32) -> bool {
+        self.from_utc.is_none_or(|from| received_utc >= from)
+            && self.to_utc.is_none_or(|to| received_utc < to)
+            && self.symbol.as_deref().is_none_or(|s| s == symbol)
+            && self.strategy.as_deref().is_none_or(|s| s == strategy_id)
+            && self.account.is_none_or(|a| a == account_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    /// Pass as `cursor` for the next page; None on the last.
+    pub next_cursor: Option<u64>,
+}
+
+/// Body of a GET /blotter/orders/{order_id} response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct OrderDetail {
+    pub order: BlotterOrder,
+    /// Oldest first.
+    pub executions: Vec<BlotterExecution>,
+}
+
+/// Counters served with the blotter's status.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct BlotterStats {
+    pub orders: usize,
+    pub executions: usize,
+    pub duplicate_reports: u64,
+    /// Reports waiting for their order.
+    pub parked_reports: usize,
+    /// Reports dropped because too many orders had reports parked.
+    pub dropped_reports: u64,
+}
+
+// --- Blotter ---
+
+pub struct Blotter {
+    next_sequence: u64,
+    orders: BTreeMap<u64, BlotterOrder>,
+    by_order_id: HashMap<Uuid, u64>,
+    executions: BTreeMap<u64, BlotterExecution>,
+    by_order_executions: HashMap<Uuid, Vec<u64>>,
+    /// Sequence of the execution booked under each (exchange order id, ExecID).
+    by_exec: HashMap<(String, String), u64>,
+    parked: HashMap<Uuid, Vec<(DateTime<Utc>, ExecutionReport)>>,
+    stats: BlotterStats,
+    instruments: ReferenceData,
+}
+
+impl Blotter {
+    pub fn new(instruments: ReferenceData) -> Blotter {
+        Blotter {
+            next_sequence: 1,
+            orders: BTreeMap::new(),
+            by_order_id: HashMap::new(),
+            executions: BTreeMap::new(),
+            by_order_executions: HashMap::new(),
+            by_exec: HashMap::new(),
+            parked: HashMap::new(),
+            stats: BlotterStats::default(),
+            instruments,
+        }
+    }
+
+    /// Books one event received at `received_utc`.
+    pub fn apply(&mut self, event: BlotterEvent, received_utc: DateTime<Utc>) {
+        match event {
+            BlotterEvent::Order(order) => self.on_order(&order, None, received_utc),
+            BlotterEvent::Decision(decision) => self.on_decision(decision, received_utc),
+            BlotterEvent::Report(report) => self.on_report(report, received_utc),
+        }
+    }
+
+    fn on_order(&mut self, order: &OrderRequest, leg_of: Option<(Uuid, u32)>, received_utc: DateTime<Utc>) {
+        if self.by_order_id.contains_key(&order.order_id) {
+            return;
+        }
+        let sequence = self.sequence();
+        let (symbol, price) = self.instrument(order.instrument_id, order.price);
+        let row = BlotterOrder {
+            sequence,
+            order_id: order.order_id,
+            received_utc,
+            updated_utc: received_utc,
+            account_id: order.account_id,
+            strategy_id: order.strategy_id.clone(),
+            instrument_id: order.instrument_id,
+            symbol,
+            side: order.side,
+            price,
+            size: order.size,
+            mode: order.mode,
+            route: OrderRoute {
+                venue_id: order.venue_id,
+                venue: venue_name(order.venue_id).map(str::to_string),
+                priority: order.priority,
+                package_id: leg_of.map(|(package_id, _)| package_id),
+                leg: leg_of.map(|(_, leg)| leg),
+            },
+            risk: None,
+            status: BlotterStatus::New,
+            exchange_order_id: None,
+            filled_size: 0,
+            average_price: None,
+            fees: Money::ZERO,
+            reject_reason: None,
+            filled_notional: 0.0,
+        };
+        self.orders.insert(sequence, row);
+        self.by_order_id.insert(order.order_id, sequence);
+        self.stats.orders = self.orders.len();
+        for (received_utc, report) in self.parked.remove(&order.order_id).unwrap_or_default() {
+            self.on_report(report, received_utc);
+        }
+        self.stats.parked_reports = self.parked.values().map(Vec::len).sum();
+    }
+
+    fn on_decision(&mut self, decision: RiskDecisionAudit, decided_utc: DateTime<Utc>) {
+        let orders: Vec<OrderRequest> = match &decision.request {
+            CheckedRequest::Order(order) => {
+                self.on_order(order, None, decided_utc);
+                vec![order.clone()]
+            }
+            CheckedRequest::Package(package) => (0..package.legs.len())
+                .map(|index| {
+                    let leg = package.leg_order(index);
+                    self.on_order(&leg, Some((package.package_id, index as u32 + 1)), decided_utc);
+                    leg
+                })
+                .collect(),
+        };
+        for order in orders {
+            let Some(row) = self.by_order_id.get(&order.order_id).and_then(|seq| self.orders.get_mut(seq)) else {
+                continue;
+            };
+            row.risk = Some(RiskVerdict {
+                outcome: decision.outcome,
+                code: decision.code,
+                reason: decision.reason.clone(),
+                checked_by: decision.checked_by.clone(),
+                decided_utc,
+            });
+            if !row.status.at_venue() {
+                row.status = match decision.outcome {
+                    RiskOutcome::Approved => BlotterStatus::Approved,
+                    RiskOutcome::Rejected => BlotterStatus::RiskRejected,
+                    RiskOutcome::Held => BlotterStatus::Held,
+                };
+            }
+            row.updated_utc = decided_utc;
+        }
+    }
+
+    fn on_report(&mut self, report: ExecutionReport, received_utc: DateTime<Utc>) {
+        let key = (report.exchange_order_id.clone(), report.exec_id.clone());
+        if !report.exec_id.is_empty() && self.by_exec.contains_key(&key) {
+            self.stats.duplicate_reports += 1;
+            return;
+        }
+        let Some(&order_sequence) = self.by_order_id.get(&report.internal_order_id) else {
+            self.park(report, received_utc);
+            return;
+        };
+        let sequence = self.sequence();
+        if !report.exec_id.is_empty() {
+            self.by_exec.insert(key, sequence);
+        }
+        let order = self.orders.get_mut(&order_sequence).expect("indexed orders exist");
+        let filled_price = (report.filled_size > 0).then(|| match self.instruments.get(order.instrument_id) {
+            Some(definition) => definition.price(report.filled_price).to_f64(),
+            None => Price::from_wire(report.filled_price, DEFAULT_PRICE_DECIMALS).unwrap_or(Price::MAX).to_f64(),
+        });
+
+        let amended = report.status.is_trade_correction().then(|| {
+            let original = (report.exchange_order_id.clone(), report.exec_ref_id.clone());
+            self.by_exec.get(&original).and_then(|sequence| self.executions.get_mut(sequence))
+        });
+        let counts = match amended {
+            Some(Some(original)) => {
+                order.filled_notional -= original.filled_price.unwrap_or(0.0) * original.filled_size as f64;
+                order.filled_size = order.filled_size.saturating_sub(original.filled_size);
+                order.fees -= original.fee;
+                original.amended_by = Some(report.exec_id.clone());
+                report.status == OrderStatus::TradeCorrected
+            }
+            Some(None) => false,
+            None => true,
+        };
+        if counts {
+            if let Some(price) = filled_price {
+                order.filled_notional += price * report.filled_size as f64;
+                order.filled_size += report.filled_size;
+            }
+            order.fees += report.fee;
+        }
+        order.average_price = (order.filled_size > 0).then(|| order.filled_notional / order.filled_size as f64);
+        order.exchange_order_id = Some(report.exchange_order_id.clone());
+        if let Some(reject) = &report.reject {
+            order.reject_reason = Some(reject.message.clone());
+        }
+        if !order.status.is_final() {
+            order.status = match report.status {
+                OrderStatus::Filled => BlotterStatus::Filled,
+                OrderStatus::Canceled => BlotterStatus::Canceled,
+                OrderStatus::RejectedByExchange => BlotterStatus::VenueRejected,
+                _ if order.filled_size > 0 => BlotterStatus::PartiallyFilled,
+                _ => BlotterStatus::Working,
+            };
+        }
+        order.updated_utc = received_utc;
+
+        let execution = BlotterExecution {
+            sequence,
+            received_utc,
+            transact_utc: (report.transact_time_ns > 0)
+                .then(|| DateTime::from_timestamp_nanos(report.transact_time_ns as i64)),
+            order_id: report.internal_order_id,
+            exchange_order_id: report.exchange_order_id,
+            exec_id: report.exec_id,
+            status: report.status,
+            account_id: order.account_id,
+            strategy_id: order.strategy_id.clone(),
+            symbol: order.symbol.clone(),
+            side: order.side,
+            venue: order.route.venue.clone(),
+            filled_size: report.filled_size,
+            filled_price,
+            cumulative_size: report.cumulative_size,
+            leaves_size: report.leaves_size,
+            liquidity: report.liquidity,
+            fee: report.fee,
+            amends: report.status.is_trade_correction().then_some(report.exec_ref_id),
+            amended_by: None,
+        };
+        self.by_order_executions.entry(execution.order_id).or_default().push(sequence);
+        self.executions.insert(sequence, execution);
+        self.stats.executions = self.executions.len();
+    }
+
+    fn park(&mut self, report: ExecutionReport, received_utc: DateTime<Utc>) {
+        if !self.parked.contains_key(&report.internal_order_id) && self.parked.len() >= MAX_PARKED_ORDERS {
+            self.stats.dropped_reports += 1;
+            return;
+        }
+        self.parked.entry(report.internal_order_id).or_default().push((received_utc, report));
+        self.stats.parked_reports += 1;
+    }
+
+    fn sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+
+    fn instrument(&self, instrument_id: u32, wire_price: u64) -> (String, f64) {
+        match self.instruments.get(instrument_id) {
+            Some(definition) => (definition.symbol.clone(), definition.price(wire_price).to_f64()),
+            None => (
+                format!("INSTRUMENT-{}", instrument_id),
+                Price::from_wire(wire_price, DEFAULT_PRICE_DECIMALS).unwrap_or(Price::MAX).to_f64(),
+            ),
+        }
+    }
+
+    pub fn stats(&self) -> BlotterStats {
+        self.stats.clone()
+    }
+
+    pub fn order(&self, order_id: Uuid) -> Option<OrderDetail> {
+        let order = self.orders.get(self.by_order_id.get(&order_id)?)?.clone();
+        let executions = self.by_order_executions.get(&order_id).map_or_else(Vec::new, |sequences| {
+            sequences.iter().filter_map(|sequence| self.executions.get(sequence)).cloned().collect()
+        });
+        Some(OrderDetail { order, executions })
+    }
+
+    /// The orders matching `query` whose account and strategy `visible`
+    /// passes.
+    pub fn orders(&self, query: &BlotterQuery, visible: impl Fn(u32, &str) -> bool) -> Page<BlotterOrder> {
+        let rows = self.orders.range(..query.cursor.unwrap_or(u64::MAX)).rev().map(|(_, order)| order).filter(|o| {
+            query.matches(o.received_utc, &o.symbol, &o.strategy_id, o.account_id)
+                && query.status.is_none_or(|status| status == o.status)
+                && visible(o.account_id, &o.strategy_id)
+        });
+        page(rows, query, |order| order.sequence)
+    }
+
+    /// The execution reports matching `query` whose account and strategy
+    /// `visible` passes.
+    pub fn executions(&self, query: &BlotterQuery, visible: impl Fn(u32, &str) -> bool) -> Page<BlotterExecution> {
+        let rows = self
+            .executions
+            .range(..query.cursor.unwrap_or(u64::MAX))
+            .rev()
+            .map(|(_, execution)| execution)
+            .filter(|e| query.matches(e.received_utc, &e.symbol, &e.strategy_id, e.account_id))
+            .filter(|e| visible(e.account_id, &e.strategy_id));
+        page(rows, query, |execution| execution.sequence)
+    }
+}
+
+/// The first page of `rows`, or every row for a CSV export.
+fn page<'a, T: Clone + 'a>(
+    rows: impl Iterator<Item = &'a T>,
+    query: &BlotterQuery,
+    sequence: impl Fn(&T) -> u64,
+) -> Page<T> {
+    if query.format == ExportFormat::Csv {
+        return Page { rows: rows.cloned().collect(), next_cursor: None };
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let mut rows: Vec<T> = rows.take(limit + 1).cloned().collect();
+    let more = rows.len() > limit;
+    rows.truncate(limit);
+    let next_cursor = if more { rows.last().map(sequence) } else { None };
+    Page { rows, next_cursor }
+}
+
+// --- CSV Export ---
+
+pub fn orders_csv(orders: &[BlotterOrder]) -> String {
+    let header = "sequence,order_id,received_utc,updated_utc,account_id,strategy_id,symbol,side,price,size,mode,\
+                  venue,priority,package_id,leg,risk_outcome,risk_code,risk_reason,status,exchange_order_id,\
+                  filled_size,average_price,fees,reject_reason";
+    let lines = orders.iter().map(|o| {
+        let risk = o.risk.as_ref();
+        csv_line(&[
+            o.sequence.to_string(),
+            o.order_id.to_string(),
+            o.received_utc.to_rfc3339(),
+            o.updated_utc.to_rfc3339(),
+            o.account_id.to_string(),
+            o.strategy_id.clone(),
+            o.symbol.clone(),
+            format!("{:?}", o.side),
+            o.price.to_string(),
+            o.size.to_string(),
+            format!("{:?}", o.mode),
+            o.route.venue.clone().unwrap_or_default(),
+            format!("{:?}", o.route.priority),
+            o.route.package_id.map(|id| id.to_string()).unwrap_or_default(),
+            o.route.leg.map(|leg| leg.to_string()).unwrap_or_default(),
+            risk.map(|r| format!("{:?}", r.outcome)).unwrap_or_default(),
+            risk.and_then(|r| r.code).map(|code| code.as_str().to_string()).unwrap_or_default(),
+            risk.and_then(|r| r.reason.clone()).unwrap_or_default(),
+            format!("{:?}", o.status),
+            o.exchange_order_id.clone().unwrap_or_default(),
+            o.filled_size.to_string(),
+            o.average_price.map(|p| p.to_string()).unwrap_or_default(),
+            o.fees.to_string(),
+            o.reject_reason.clone().unwrap_or_default(),
+        ])
+    });
+    std::iter::once(header.to_string()).chain(lines).collect::<Vec<_>>().join("\n") + "\n"
+}
+
+pub fn executions_csv(executions: &[BlotterExecution]) -> String {
+    let header = "sequence,received_utc,transact_utc,order_id,exchange_order_id,exec_id,status,account_id,\
+                  strategy_id,symbol,side,venue,filled_size,filled_price,cumulative_size,leaves_size,liquidity,fee,\
+                  amends,amended_by";
+    let lines = executions.iter().map(|e| {
+        csv_line(&[
+            e.sequence.to_string(),
+            e.received_utc.to_rfc3339(),
+            e.transact_utc.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            e.order_id.to_string(),
+            e.exchange_order_id.clone(),
+            e.exec_id.clone(),
+            format!("{:?}", e.status),
+            e.account_id.to_string(),
+            e.strategy_id.clone(),
+            e.symbol.clone(),
+            format!("{:?}", e.side),
+            e.venue.clone().unwrap_or_default(),
+            e.filled_size.to_string(),
+            e.filled_price.map(|p| p.to_string()).unwrap_or_default(),
+            e.cumulative_size.to_string(),
+            e.leaves_size.to_string(),
+            e.liquidity.map(|l| format!("{:?}", l)).unwrap_or_default(),
+            e.fee.to_string(),
+            e.amends.clone().unwrap_or_default(),
+            e.amended_by.clone().unwrap_or_default(),
+        ])
+    });
+    std::iter::once(header.to_string()).chain(lines).collect::<Vec<_>>().join("\n") + "\n"
+}
+
+/// Joins fields into a CSV line, quoting those that need it (RFC 4180).
+fn csv_line(fields: &[String]) -> String {
+    let quoted = fields.iter().map(|field| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.clone()
+        }
+    });
+    quoted.collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use quantumarb_types::RiskDecisionAudit;
+    use quantumarb_wire::{HopStamps, MultiLegOrder, OrderLeg};
+
+    fn all(_: u32, _: &str) -> bool {
+        true
+    }
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 16, 14, 0, second).unwrap()
+    }
+
+    fn order(id: u128, strategy_id: &str, instrument_id: u32) -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::from_u128(id),
+            account_id: 101,
+            instrument_id,
+            side: OrderSide::Buy,
+            price: 60_000_00,
+            size: 10,
+            stamps: HopStamps::default(),
+            venue_id: 1,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Arbitrage,
+            strategy_id: strategy_id.to_string(),
+        }
+    }
+
+    fn report(order_id: Uuid, exec_id: &str, status: OrderStatus, filled_size: u32, price: u64) -> ExecutionReport {
+        ExecutionReport {
+            exchange_order_id: "X1".to_string(),
+            exec_id: exec_id.to_string(),
+            internal_order_id: order_id,
+            status,
+            filled_size,
+            filled_price: price,
+            reject: None,
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            cumulative_size: 0,
+            leaves_size: 0,
+            liquidity: None,
+            fee: Money::from_f64(0.5),
+            transact_time_ns: 0,
+            exec_ref_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn orders_collect_their_decisions_and_fills_and_page_newest_first() {
+        let mut blotter = Blotter::new(ReferenceData::seeded());
+        let a = order(1, "sor_arbitrage", 1);
+        // The fill arrives before the order and waits for it; its redelivery counts once.
+        let fill = report(a.order_id, "E1", OrderStatus::PartiallyFilled, 4, 60_000_00);
+        blotter.apply(BlotterEvent::Report(fill.clone()), at(1));
+        assert_eq!(blotter.stats().parked_reports, 1);
+        blotter.apply(BlotterEvent::Order(a.clone()), at(0));
+        blotter.apply(BlotterEvent::Report(fill), at(2));
+        blotter.apply(BlotterEvent::Report(report(a.order_id, "E2", OrderStatus::Filled, 6, 60_010_00)), at(3));
+        let detail = blotter.order(a.order_id).unwrap();
+        assert_eq!((detail.order.status, detail.order.filled_size), (BlotterStatus::Filled, 10));
+        assert!((detail.order.average_price.unwrap() - 60_006.0).abs() < 1e-9);
+        assert_eq!(detail.order.fees, Money::from_f64(1.0));
+        assert_eq!((detail.order.symbol.as_str(), detail.order.route.venue.as_deref()), ("BTC", Some("VENUE_A")));
+        assert_eq!(detail.executions.len(), 2);
+        assert_eq!(blotter.stats().duplicate_reports, 1);
+
+        // A rejected package opens its legs, tagged with the package.
+        let package = MultiLegOrder {
+            package_id: Uuid::from_u128(100),
+            account_id: 101,
+            quantity: 2,
+            legs: vec![
+                OrderLeg { instrument_id: 1, side: OrderSide::Buy, ratio: 1, price: 60_000_00, venue_id: 1 },
+                OrderLeg { instrument_id: 2, side: OrderSide::Sell, ratio: 3, price: 3_000_00, venue_id: 2 },
+            ],
+            stamps: HopStamps::default(),
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Arbitrage,
+            strategy_id: "spread".to_string(),
+        };
+        let decision = RiskDecisionAudit {
+            outcome: RiskOutcome::Rejected,
+            code: Some(RejectCode::RiskExposureLimit),
+            reason: Some("too big".to_string()),
+            checked_by: "risk-gateway-1".to_string(),
+            request: CheckedRequest::Package(package),
+        };
+        blotter.apply(BlotterEvent::Decision(decision), at(4));
+        let leg = blotter.order(Uuid::from_u128(102)).unwrap().order;
+        assert_eq!((leg.status, leg.size, leg.route.leg), (BlotterStatus::RiskRejected, 6, Some(2)));
+        assert_eq!(leg.route.package_id, Some(Uuid::from_u128(100)));
+
+        // Newest first, a page at a time, filtered.
+        let query = BlotterQuery { limit: Some(2), ..BlotterQuery::default() };
+        let first = blotter.orders(&query, all);
+        assert_eq!(first.rows.iter().map(|o| o.order_id.as_u128()).collect::<Vec<_>>(), vec![102, 101]);
+        let next = blotter.orders(&BlotterQuery { cursor: first.next_cursor, ..query.clone() }, all);
+        assert_eq!((next.rows.len(), next.rows[0].order_id.as_u128(), next.next_cursor), (1, 1, None));
+        let spread = BlotterQuery { strategy: Some("spread".into()), symbol: Some("BTC".into()), ..query.clone() };
+        assert_eq!(blotter.orders(&spread, all).rows.len(), 1);
+        let window = BlotterQuery { from_utc: Some(at(1)), to_utc: Some(at(3)), ..query.clone() };
+        assert_eq!(blotter.executions(&window, all).rows.len(), 1, "the fill received at 14:00:02 only");
+        let filled = BlotterQuery { status: Some(BlotterStatus::Filled), ..query };
+        assert_eq!(blotter.orders(&filled, all).rows.len(), 1);
+
+        let export = BlotterQuery { format: ExportFormat::Csv, ..Default::default() };
+        let csv = orders_csv(&blotter.orders(&export, all).rows);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains("RISK_EXPOSURE_LIMIT,too big,RiskRejected"));
+        assert_eq!(csv_line(&["a,b".into(), "say \"hi\"".into()]), "\"a,b\",\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn busts_and_corrections_restate_the_order_next_to_the_original_fill() {
+        let mut blotter = Blotter::new(ReferenceData::seeded());
+        let a = order(1, "sor_arbitrage", 1);
+        blotter.apply(BlotterEvent::Order(a.clone()), at(0));
+        let e1 = report(a.order_id, "E1", OrderStatus::PartiallyFilled, 4, 60_000_00);
+        blotter.apply(BlotterEvent::Report(e1), at(1));
+        blotter.apply(BlotterEvent::Report(report(a.order_id, "E2", OrderStatus::Filled, 6, 60_010_00)), at(2));
+
+        let mut bust = report(a.order_id, "E3", OrderStatus::TradeBusted, 6, 60_010_00);
+        bust.exec_ref_id = "E2".to_string();
+        blotter.apply(BlotterEvent::Report(bust), at(3));
+        let mut correction = report(a.order_id, "E4", OrderStatus::TradeCorrected, 4, 59_990_00);
+        correction.exec_ref_id = "E1".to_string();
+        blotter.apply(BlotterEvent::Report(correction), at(4));
+
+        let detail = blotter.order(a.order_id).unwrap();
+        assert_eq!((detail.order.status, detail.order.filled_size), (BlotterStatus::Filled, 4));
+        assert!((detail.order.average_price.unwrap() - 59_990.0).abs() < 1e-9);
+        assert_eq!(detail.order.fees, Money::from_f64(0.5));
+        let links: Vec<_> = detail.executions.iter().map(|e| (e.amends.as_deref(), e.amended_by.as_deref())).collect();
+        assert_eq!(links, vec![(None, Some("E4")), (None, Some("E3")), (Some("E2"), None), (Some("E1"), None)]);
+        let csv = executions_csv(&detail.executions);
+        assert!(csv.lines().nth(4).unwrap().ends_with(",E1,"));
+    }
+}
@@ -0,0 +1,381 @@
+/*
+ * QuantumArb 2.0 - Core Services: Trade Blotter
+ *
+ * File: src/core_services/trade_blotter/main.rs
+ *
+ * Description:
+ * The blotter traders and middle office read the day's trading from. It
+ * keeps every order and every execution report with its full context: the
+ * account and strategy that sent it, the instrument, the venue and route
+ * (priority, and the package and leg for package legs), the risk gateway's
+ * verdict, and the fills, average price and fees (see `blotter.rs`).
+ *
+ * - Orders come from 'orders.requests', risk verdicts from the risk
+ * gateway's RISK_* events on 'audit.events', and venue answers from
+ * 'execution_reports'.
+ * - Every message booked is appended to a JSONL journal (QA_BLOTTER_PATH,
+ * default trade_blotter.jsonl) with the time it was received, and the
+ * journal is replayed at startup, so a restarted blotter shows what it
+ * showed before.
+ * - An API on port 3047:
+ *   GET /blotter/orders        orders, newest first; filter by from_utc,
+ *                              to_utc, symbol, strategy, account and status,
+ *                              page with limit and cursor
+ *   GET /blotter/orders/{id}   one order with its executions
+ *   GET /blotter/executions    execution reports, filtered and paged alike
+ *   GET /blotter/stats         row counts and duplicate or parked reports
+ *   format=csv on either list exports every matching row as CSV.
+ * - With desks configured (QA_DESKS_PATH, see `quantumarb-tenancy`) the API
+ * takes a bearer token: a desk's token sees the orders and executions of
+ * its own accounts and strategies, and /blotter/stats takes a firm token.
+ * - GET /openapi.json serves the API's OpenAPI document, and GET /docs
+ * browses it in Swagger UI.
+ *
+ * The bus subscription is simulated until the services hold a real bus
+ * connection.
+ */
+
+mod blotter;
+
+use blotter::{Blotter, BlotterEvent, BlotterExecution, BlotterOrder, BlotterQuery, BlotterStats, ExportFormat};
+use blotter::{OrderDetail, Page};
+use chrono::{DateTime, Utc};
+use quantumarb_bus::{topics, BusMessage};
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_refdata::ReferenceData;
+use quantumarb_tenancy::{Desks, Scope};
+use quantumarb_types::{AuditEvent, CheckedRequest, RiskDecisionAudit, RiskOutcome};
+use quantumarb_wire::{
+    utc_now_ns, Encoding, ExecutionReport, HopStamps, Liquidity, OrderPriority, OrderRequest, OrderSide, OrderStatus,
+    TradingMode,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::Filter;
+
+// --- Data Structures ---
+
+/// One journal line: a message and when the blotter received it.
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    received_utc: DateTime<Utc>,
+    event: BlotterEvent,
+}
+
+/// The blotter and the journal it is written through.
+struct BlotterState {
+    blotter: Blotter,
+    journal: File,
+}
+
+impl BlotterState {
+    /// Journals and books one message.
+    fn book(&mut self, event: BlotterEvent) {
+        let entry = JournalEntry { received_utc: Utc::now(), event };
+        match serde_json::to_string(&entry) {
+            Ok(line) => {
+                if let Err(e) = writeln!(self.journal, "{}", line) {
+                    println!("  -> Failed to journal a blotter event: {}", e);
+                }
+            }
+            Err(e) => println!("  -> Unserializable blotter event: {}", e),
+        }
+        self.blotter.apply(entry.event, entry.received_utc);
+    }
+}
+
+type SharedBlotter = Arc<Mutex<BlotterState>>;
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Trade Blotter Service ---");
+    let path = std::env::var("QA_BLOTTER_PATH").unwrap_or_else(|_| "trade_blotter.jsonl".to_string());
+    let mut blotter = Blotter::new(ReferenceData::new(quantumarb_refdata::load_from_env()));
+    let replayed = replay_journal(&path, &mut blotter).expect("Failed to read the blotter journal");
+    let journal = OpenOptions::new().create(true).append(true).open(&path).expect("Failed to open the blotter journal");
+    let stats = blotter.stats();
+    println!("Replayed {} events from '{}': {} orders, {} executions", replayed, path, stats.orders, stats.executions);
+    let state: SharedBlotter = Arc::new(Mutex::new(BlotterState { blotter, journal }));
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+
+    let api_state = state.clone();
+    tokio::spawn(async move {
+        run_api(api_state, desks).await;
+    });
+
+    subscribe_to_order_flow(state).await;
+}
+
+/// Books every event in the journal at `path`; a missing journal is an empty
+/// one. Lines that do not parse (a write cut short by a crash) are skipped.
+fn replay_journal(path: &str, blotter: &mut Blotter) -> std::io::Result<usize> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut replayed = 0;
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str::<JournalEntry>(&line?) {
+            Ok(entry) => {
+                blotter.apply(entry.event, entry.received_utc);
+                replayed += 1;
+            }
+            Err(e) => println!("  -> Skipping an unreadable journal line: {}", e),
+        }
+    }
+    Ok(replayed)
+}
+
+/// The blotter event a bus message carries, if any: orders, execution
+/// reports and the risk gateway's decisions.
+fn event_of(message: &BusMessage) -> Option<BlotterEvent> {
+    let decoded = match message.topic.as_str() {
+        topics::ORDER_REQUESTS => Encoding::decode_any::<OrderRequest>(message.body()).map(BlotterEvent::Order),
+        topics::EXECUTION_REPORTS => Encoding::decode_any::<ExecutionReport>(message.body()).map(BlotterEvent::Report),
+        topics::AUDIT_EVENTS => {
+            let event = serde_json::from_slice::<AuditEvent>(message.body()).ok()?;
+            if event.service_name != "risk-gateway" || !event.event_type.starts_with("RISK_") {
+                return None;
+            }
+            return match serde_json::from_str::<RiskDecisionAudit>(&event.payload) {
+                Ok(decision) => Some(BlotterEvent::Decision(decision)),
+                Err(e) => {
+                    println!("  -> Unreadable risk decision {}: {}", event.event_id, e);
+                    None
+                }
+            };
+        }
+        _ => return None,
+    };
+    decoded.map_err(|e| println!("  -> Undecodable message on '{}': {}", message.topic, e)).ok()
+}
+
+/// Simulates the bus subscription to the order, execution report and audit
+/// topics.
+async fn subscribe_to_order_flow(state: SharedBlotter) {
+    // In a real system:
+    // let mut orders = nats_client.subscribe(topics::ORDER_REQUESTS).await.unwrap();
+    // ...and likewise for the execution report and audit topics.
+    let encoding = Encoding::from_env();
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        for message in simulated_order_flow(encoding) {
+            if let Some(event) = event_of(&message) {
+                state.lock().unwrap().book(event);
+            }
+        }
+    }
+}
+
+/// One round of order flow: a BTC buy approved and filled in two parts, an
+/// ETH sell rejected for size, and an ETH buy acknowledged and canceled.
+fn simulated_order_flow(encoding: Encoding) -> Vec<BusMessage> {
+    let order = |instrument_id, side, price, size, strategy_id: &str| OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id: 101,
+        instrument_id,
+        side,
+        price,
+        size,
+        stamps: HopStamps::default(),
+        venue_id: 1,
+        mode: TradingMode::Sandbox,
+        priority: OrderPriority::Opportunistic,
+        strategy_id: strategy_id.to_string(),
+    };
+    let exchange_order_id = format!("EXCH-{}", Uuid::new_v4().simple());
+    let report = |order: &OrderRequest, status, filled_size, cumulative_size| ExecutionReport {
+        exchange_order_id: exchange_order_id.clone(),
+        exec_id: Uuid::new_v4().simple().to_string(),
+        internal_order_id: order.order_id,
+        status,
+        filled_size,
+        filled_price: if filled_size > 0 { order.price } else { 0 },
+        reject: None,
+        stamps: HopStamps::default(),
+        mode: TradingMode::Sandbox,
+        cumulative_size,
+        leaves_size: order.size - cumulative_size,
+        liquidity: (filled_size > 0).then_some(Liquidity::Taker),
+        fee: Money::from_f64(filled_size as f64 * 0.05),
+        transact_time_ns: utc_now_ns(),
+        exec_ref_id: String::new(),
+    };
+    let decision = |order: &OrderRequest, outcome: RiskOutcome, code, reason: Option<&str>| {
+        let audit = RiskDecisionAudit {
+            outcome,
+            code,
+            reason: reason.map(str::to_string),
+            checked_by: "risk-gateway-instance-1".to_string(),
+            request: CheckedRequest::Order(order.clone()),
+        };
+        let event = AuditEvent {
+            event_id: Uuid::new_v4(),
+            service_name: "risk-gateway".to_string(),
+            event_type: outcome.event_type().to_string(),
+            timestamp: Utc::now(),
+            payload: serde_json::to_string(&audit).unwrap(),
+        };
+        message(topics::AUDIT_EVENTS, serde_json::to_vec(&event).unwrap())
+    };
+    fn message(topic: &str, payload: Vec<u8>) -> BusMessage {
+        BusMessage { seq: 0, topic: topic.to_string(), payload }
+    }
+
+    let filled = order(1, OrderSide::Buy, 60150_00, 40, "sor_arbitrage");
+    let rejected = order(2, OrderSide::Sell, 3010_00, 900, "stat_arb");
+    let canceled = order(2, OrderSide::Buy, 2990_00, 20, "stat_arb");
+    vec![
+        decision(&filled, RiskOutcome::Approved, None, None),
+        message(topics::ORDER_REQUESTS, encoding.encode(&filled)),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&filled, OrderStatus::SentToExchange, 0, 0))),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&filled, OrderStatus::PartiallyFilled, 15, 15))),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&filled, OrderStatus::Filled, 25, 40))),
+        decision(&rejected, RiskOutcome::Rejected, Some(RejectCode::RiskOrderSizeLimit), Some("Size over the limit")),
+        decision(&canceled, RiskOutcome::Approved, None, None),
+        message(topics::ORDER_REQUESTS, encoding.encode(&canceled)),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&canceled, OrderStatus::SentToExchange, 0, 0))),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&canceled, OrderStatus::Canceled, 0, 0))),
+    ]
+}
+
+// --- API ---
+
+async fn run_api(state: SharedBlotter, desks: Arc<Desks>) {
+    let orders = warp::path!("blotter" / "orders")
+        .and(warp::get())
+        .and(warp::query::<BlotterQuery>())
+        .and(with_state(state.clone()))
+        .and(quantumarb_tenancy::scope(desks.clone()))
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_orders);
+    let order = warp::path!("blotter" / "orders" / Uuid)
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and(quantumarb_tenancy::scope(desks.clone()))
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_order);
+    let executions = warp::path!("blotter" / "executions")
+        .and(warp::get())
+        .and(warp::query::<BlotterQuery>())
+        .and(with_state(state.clone()))
+        .and(quantumarb_tenancy::scope(desks.clone()))
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_executions);
+    let stats = warp::path!("blotter" / "stats")
+        .and(warp::get())
+        .and(quantumarb_tenancy::firm_only(desks))
+        .and(with_state(state))
+        .and_then(handler_get_stats);
+    println!("Blotter API running at http://127.0.0.1:3047 (/blotter/orders, /blotter/executions, /blotter/stats)");
+    let routes = orders
+        .or(order)
+        .or(executions)
+        .or(stats)
+        .or(quantumarb_openapi::routes(api_doc()))
+        .recover(quantumarb_tenancy::recover);
+    warp::serve(routes).run(([127, 0, 0, 1], 3047)).await;
+}
+
+/// The OpenAPI document of the blotter endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Trade Blotter", "Every order and execution with its strategy, route and risk verdict.")
+        .errors::<ErrorBody>()
+        .operation(
+            Operation::get("/blotter/orders", "Orders, newest first (format=csv exports every match)")
+                .query::<BlotterQuery>()
+                .response::<Page<BlotterOrder>>(),
+        )
+        .operation(
+            Operation::get("/blotter/orders/{order_id}", "One order with its executions")
+                .path_param::<Uuid>("order_id")
+                .response::<OrderDetail>(),
+        )
+        .operation(
+            Operation::get("/blotter/executions", "Execution reports, newest first (format=csv exports every match)")
+                .query::<BlotterQuery>()
+                .response::<Page<BlotterExecution>>(),
+        )
+        .get::<BlotterStats>("/blotter/stats", "Row counts and duplicate or parked reports")
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(state: T) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// A CSV download named `filename`.
+fn csv_reply(body: String, filename: &str) -> Box<dyn warp::Reply> {
+    let reply = warp::reply::with_header(body, "content-type", "text/csv; charset=utf-8");
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+    Box::new(warp::reply::with_header(reply, "content-disposition", disposition))
+}
+
+/// Whether `scope` sees the rows of `account_id` and `strategy_id`.
+fn visible_to<'a>(scope: &'a Scope, desks: &'a Desks) -> impl Fn(u32, &str) -> bool + 'a {
+    move |account_id, strategy_id| scope.sees_account(desks, account_id) || scope.sees_strategy(desks, strategy_id)
+}
+
+/// Handler for GET /blotter/orders.
+async fn handler_get_orders(
+    query: BlotterQuery,
+    state: SharedBlotter,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let page = state.lock().unwrap().blotter.orders(&query, visible_to(&scope, &desks));
+    Ok(match query.format {
+        ExportFormat::Csv => csv_reply(blotter::orders_csv(&page.rows), "orders.csv"),
+        ExportFormat::Json => Box::new(warp::reply::json(&page)),
+    })
+}
+
+/// Handler for GET /blotter/orders/{order_id}. Another desk's order is as
+/// unknown as a missing one.
+async fn handler_get_order(
+    order_id: Uuid,
+    state: SharedBlotter,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let visible = visible_to(&scope, &desks);
+    let detail = state.lock().unwrap().blotter.order(order_id);
+    match detail.filter(|detail| visible(detail.order.account_id, &detail.order.strategy_id)) {
+        Some(detail) => Ok(warp::reply::with_status(warp::reply::json(&detail), StatusCode::OK)),
+        None => {
+            let rejection = Rejection::new(RejectCode::SystemInvalidRequest, format!("Unknown order {}", order_id));
+            Ok(warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), StatusCode::NOT_FOUND))
+        }
+    }
+}
+
+/// Handler for GET /blotter/executions.
+async fn handler_get_executions(
+    query: BlotterQuery,
+    state: SharedBlotter,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let page = state.lock().unwrap().blotter.executions(&query, visible_to(&scope, &desks));
+    Ok(match query.format {
+        ExportFormat::Csv => csv_reply(blotter::executions_csv(&page.rows), "executions.csv"),
+        ExportFormat::Json => Box::new(warp::reply::json(&page)),
+    })
+}
+
+/// Handler for GET /blotter/stats.
+async fn handler_get_stats(state: SharedBlotter) -> Result<impl warp::Reply, warp::Rejection> {
+    let stats = state.lock().unwrap().blotter.stats();
+    Ok(warp::reply::json(&stats))
+}
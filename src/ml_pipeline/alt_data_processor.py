@@ -4,10 +4,12 @@
 # File: src/ml_pipeline/alt_data_processor.py
 #
 # Description:
-# This script is responsible for consuming the normalized alternative data events
-# from the internal message bus (published by the data-bus-connector). It then
-# processes this data to create features that can be used by downstream machine
-# learning models.
+# This script is responsible for consuming the per-symbol sentiment snapshots
+# from the internal message bus (published by the data-bus-connector on
+# 'alt_data.sentiment', aggregated over 1m/5m/1h windows). It then turns them
+# into features that can be used by downstream machine learning models: the
+# news_sentiment feature is the decay-weighted 5m window, as in the feature
+# store the strategy engine reads.
 #
 # In a real SageMaker pipeline, this would be a continuously running process,
 # perhaps using a streaming framework like Spark Streaming or Flink, and would
@@ -34,16 +36,16 @@ def process_event_stream():
     Simulates a long-running process that consumes events from a message bus.
     """
     print("--- Starting Alternative Data Processor ---")
-    print("Listening for normalized events on topic 'alt_data.normalized'...")
+    print("Listening for sentiment snapshots on topic 'alt_data.sentiment'...")
 
     while True:
-        # 1. Simulate receiving a normalized event from the message bus.
-        event_json = get_simulated_normalized_event()
-        event = json.loads(event_json)
-        print(f"\nConsumed Event: ID {event['event_id'][:8]}...")
+        # 1. Simulate receiving a sentiment snapshot from the message bus.
+        snapshot_json = get_simulated_sentiment_snapshot()
+        snapshot = json.loads(snapshot_json)
+        print(f"\nConsumed Snapshot: {snapshot['symbol']} at {snapshot['timestamp_utc']}")
 
-        # 2. Process the event to generate or update features.
-        update_features_from_event(event)
+        # 2. Process the snapshot to generate or update features.
+        update_features_from_snapshot(snapshot)
 
         # 3. Display the current state of the feature store.
         print("--- Current Feature Store State ---")
@@ -53,48 +55,38 @@ def process_event_stream():
         time.sleep(5)
 
 
-def get_simulated_normalized_event():
+def get_simulated_sentiment_snapshot():
     """
     Simulates receiving a JSON message from the internal message bus.
-    This is the output format of the data_bus_connector service.
+    This is the 'alt_data.sentiment' output format of the data_bus_connector service.
     """
     return json.dumps({
-        "event_id": "a1b2c3d4-e5f6-7890-1234-567890abcdef",
-        "source_type": "news",
-        "source_name": "FinancialWire",
-        "content": "Tech Giant 'Innovate Inc.' Announces Breakthrough in Chip Technology",
-        "metadata": {
-            "sentiment_score": "0.75",
-            "related_symbols": "INVT,CHIP,SEMI"
-        },
+        "symbol": "INVT",
+        "windows": [
+            {"window": "1m", "count": 1, "mean": 0.75, "decay_weighted": 0.75},
+            {"window": "5m", "count": 5, "mean": 0.31, "decay_weighted": 0.42},
+            {"window": "1h", "count": 48, "mean": 0.12, "decay_weighted": 0.27}
+        ],
         "timestamp_utc": "2025-07-31T12:00:00Z"
     })
 
 
-def update_features_from_event(event):
+def update_features_from_snapshot(snapshot):
     """
-    Parses a normalized event and updates the feature store.
+    Maps a sentiment snapshot onto the symbol's features.
     """
-    metadata = event.get("metadata", {})
-    if "related_symbols" not in metadata:
+    windows = {w["window"]: w for w in snapshot.get("windows", [])}
+    if "5m" not in windows or "1h" not in windows:
         return
 
-    symbols = metadata["related_symbols"].split(',')
-    sentiment_score = float(metadata.get("sentiment_score", 0.0))
+    symbol = snapshot["symbol"]
+    print(f"  -> Processing sentiment for symbol: {symbol}")
 
-    print(f"  -> Processing event for symbols: {symbols}")
-
-    for symbol in symbols:
-        if symbol not in FEATURE_STORE:
-            FEATURE_STORE[symbol] = {
-                'latest_news_sentiment': 0.0,
-                'news_event_count': 0
-            }
-
-        # Update the features for the given symbol
-        # In a real system, you might use a rolling average or a more complex calculation.
-        FEATURE_STORE[symbol]['latest_news_sentiment'] = sentiment_score
-        FEATURE_STORE[symbol]['news_event_count'] += 1
+    # No news in the window means neutral sentiment.
+    FEATURE_STORE[symbol] = {
+        'news_sentiment': windows["5m"]["decay_weighted"] or 0.0,
+        'news_event_count': windows["1h"]["count"],
+    }
 
 
 if __name__ == "__main__":
@@ -10,6 +10,10 @@
 # The server loads the trained model at startup and exposes a `/predict`
 # endpoint that accepts real-time feature data and returns a trading signal.
 #
+# The model file can be set with QA_MODEL_FILE, so a challenger model runs as
+# a second deployment of the same server (the strategy engine queries both;
+# see strategy_engine/models.rs).
+#
 # Dependencies:
 # pip install fastapi uvicorn python-multipart xgboost pandas
 #
@@ -32,7 +36,7 @@ app = FastAPI(
 )
 
 # Load the trained XGBoost model from the file created by train_model.py
-MODEL_FILE = 'xgb_price_predictor.json'
+MODEL_FILE = os.environ.get('QA_MODEL_FILE', 'xgb_price_predictor.json')
 model = xgb.XGBClassifier()
 
 # Check if model file exists before loading
@@ -53,6 +57,8 @@ class PredictionFeatures(BaseModel):
 class PredictionResponse(BaseModel):
     prediction: int # 0 for price down, 1 for price up
     signal: str
+    probability: float # model probability that the price moves up
+    model: str # model file that produced the prediction
 
 # --- API Endpoints ---
 
@@ -76,11 +82,13 @@ def predict(features: PredictionFeatures):
     # Make a prediction using the loaded model
     prediction_raw = model.predict(input_df)[0]
     prediction_int = int(prediction_raw)
+    # Probability of the "up" class, used downstream for calibration metrics.
+    probability_up = float(model.predict_proba(input_df)[0][1])
 
     # Convert the numerical prediction to a human-readable signal
     signal_str = "BUY" if prediction_int == 1 else "SELL"
     
     print(f"Received features: {features.dict()}. Prediction: {signal_str}")
 
-    return PredictionResponse(prediction=prediction_int, signal=signal_str)
+    return PredictionResponse(prediction=prediction_int, signal=signal_str, probability=probability_up, model=MODEL_FILE)
 
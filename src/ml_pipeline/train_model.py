@@ -12,10 +12,17 @@
 # performance and accuracy. The goal is to predict a simple binary outcome: will
 # the price go up in the next time period?
 #
+# With QA_FEATURE_PG_URL set, the training set is read from the feature
+# store's `feature_snapshots` table (written by the data-bus-connector, see
+# src/shared/features/lib.rs) for QA_FEATURE_SYMBOL, so the model is trained on
+# exactly the features the strategy engine sends at inference time. Otherwise
+# mock data is generated.
+#
 # Dependencies:
-# pip install pandas scikit-learn xgboost
+# pip install pandas scikit-learn xgboost psycopg2-binary
 #
 
+import os
 import pandas as pd
 import numpy as np
 import xgboost as xgb
@@ -42,12 +49,34 @@ def generate_mock_data(rows=1000):
     })
     return df
 
+def load_feature_store_data(dsn, symbol):
+    """
+    Loads a symbol's feature snapshots from the feature store, mapped to the
+    model's inputs the same way the strategy engine maps them live.
+    """
+    import psycopg2
+    with psycopg2.connect(dsn) as conn:
+        df = pd.read_sql(
+            "SELECT ts, sentiment_5m, mavg_spread, mid FROM feature_snapshots "
+            "WHERE symbol = %(symbol)s AND mid IS NOT NULL ORDER BY ts",
+            conn,
+            params={'symbol': symbol},
+        )
+    print(f"Loaded {len(df)} feature snapshots for {symbol} from the feature store.")
+    df = df.rename(columns={'ts': 'timestamp', 'mid': 'close'})
+    # The decay-weighted 5m sentiment window; no news means neutral sentiment,
+    # as at inference time.
+    df['news_sentiment'] = df['sentiment_5m'].fillna(0.0)
+    return df[['timestamp', 'close', 'news_sentiment', 'mavg_spread']]
+
 def create_features_and_target(df):
     """
     Engineers features and creates the target variable for prediction.
     """
     # Features (X): What we use to predict.
-    df['mavg_spread'] = df['short_mavg'] - df['long_mavg']
+    # Feature store snapshots already carry the spread.
+    if 'mavg_spread' not in df:
+        df['mavg_spread'] = df['short_mavg'] - df['long_mavg']
     features = df[['news_sentiment', 'mavg_spread']]
     
     # Target (y): What we want to predict.
@@ -79,7 +108,11 @@ def train_xgboost_model(X_train, y_train):
 
 if __name__ == "__main__":
     # 1. Generate and prepare data
-    data = generate_mock_data()
+    feature_store_dsn = os.environ.get('QA_FEATURE_PG_URL')
+    if feature_store_dsn:
+        data = load_feature_store_data(feature_store_dsn, os.environ.get('QA_FEATURE_SYMBOL', 'BTC'))
+    else:
+        data = generate_mock_data()
     X, y = create_features_and_target(data)
     
     # 2. Split data into training and testing sets
@@ -0,0 +1,251 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Approval Queue
+ *
+ * File: src/risk_compliance/risk_gateway/approvals.rs
+ *
+ * Description:
+ * Some rejections are better put to a supervisor than made final. A single
+ * order that fails a check whose code is in the approval policy's
+ * `hold_codes` (by default RISK_STRESS_APPROVAL_REQUIRED, the stress check's
+ * REQUIRE_APPROVAL action) is parked here instead, and the strategy gets an
+ * interim verdict with RISK_PENDING_APPROVAL. A supervisor approves or
+ * rejects it (POST /approvals/{order_id}); one not decided within
+ * `timeout_secs` is rejected automatically.
+ *
+ * The instance that parked the order sends the strategy its final verdict,
+ * on the same transport as the interim one. An approved order is checked
+ * again with the codes the supervisor approved waived, so it still meets
+ * every other limit when it is released; if it now fails another check in
+ * `hold_codes` it is parked again. Only the codes in HOLDABLE can be held:
+ * the others (a kill switch, an unknown instrument, a wrong shard, ...) are
+ * not a supervisor's to waive. Packages are never held.
+ *
+ * The queue and the policy live in Redis, so a supervisor can decide
+ * through any gateway instance. Every change to the queue is a versioned
+ * write (see `store::set_if_version`), so instances holding, deciding and
+ * settling orders at once do not lose each other's changes.
+ */
+
+use chrono::{DateTime, Duration, Utc};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::OrderRequest;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The codes an order can be held for.
+pub const HOLDABLE: [RejectCode; 6] = [
+    RejectCode::RiskOrderSizeLimit,
+    RejectCode::RiskDailyLossLimit,
+    RejectCode::RiskGreeksLimit,
+    RejectCode::RiskStressLimit,
+    RejectCode::RiskStressApprovalRequired,
+    RejectCode::RiskCapitalAllocation,
+];
+/// How long past its deadline an order parked by another instance is kept
+/// before it is dropped as orphaned (that instance is gone).
+const ORPHAN_GRACE_SECS: i64 = 300;
+
+// --- Data Structures ---
+
+/// Which rejections are held, and for how long; stored in Redis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ApprovalPolicy {
+    pub hold_codes: Vec<RejectCode>,
+    pub timeout_secs: u64,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> ApprovalPolicy {
+        ApprovalPolicy { hold_codes: vec![RejectCode::RiskStressApprovalRequired], timeout_secs: 300 }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn validate(&self) -> Result<(), Rejection> {
+        if let Some(code) = self.hold_codes.iter().find(|code| !HOLDABLE.contains(code)) {
+            return Err(Rejection::new(RejectCode::SystemInvalidRequest, format!("{} cannot be held", code)));
+        }
+        if self.timeout_secs == 0 {
+            return Err(Rejection::new(RejectCode::SystemInvalidRequest, "Timeout must be positive"));
+        }
+        Ok(())
+    }
+
+    pub fn holds(&self, code: RejectCode) -> bool {
+        self.hold_codes.contains(&code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "state", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HoldState {
+    Pending,
+    Approved { supervisor: String, decided_utc: DateTime<Utc> },
+    Rejected { supervisor: String, note: String, decided_utc: DateTime<Utc> },
+}
+
+/// An order waiting for, or settled by, a supervisor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct HeldOrder {
+    pub order: OrderRequest,
+    /// The gateway instance that parked the order and sends its verdict.
+    pub instance: String,
+    /// The check it was held for.
+    pub rejection: Rejection,
+    /// Codes a supervisor already approved, for an order parked again.
+    pub waived: Vec<RejectCode>,
+    pub held_utc: DateTime<Utc>,
+    pub deadline_utc: DateTime<Utc>,
+    pub state: HoldState,
+}
+
+/// Body of a POST /approvals/{order_id} request.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct Decision {
+    pub approve: bool,
+    pub supervisor: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// What became of a held order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Check it again with these codes waived.
+    Approved { waived: Vec<RejectCode> },
+    /// Rejected by a supervisor, or for want of a decision.
+    Rejected(Rejection),
+}
+
+/// Every held order; stored in Redis.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct ApprovalQueue {
+    pub orders: Vec<HeldOrder>,
+    /// Bumped by every write; see `update_approval_queue`.
+    #[serde(default)]
+    pub version: u64,
+}
+
+impl ApprovalQueue {
+    pub fn hold(
+        &mut self,
+        order: &OrderRequest,
+        rejection: Rejection,
+        waived: Vec<RejectCode>,
+        instance: &str,
+        policy: &ApprovalPolicy,
+        now: DateTime<Utc>,
+    ) {
+        self.orders.retain(|held| held.order.order_id != order.order_id);
+        self.orders.push(HeldOrder {
+            order: order.clone(),
+            instance: instance.to_string(),
+            rejection,
+            waived,
+            held_utc: now,
+            deadline_utc: now + Duration::seconds(policy.timeout_secs as i64),
+            state: HoldState::Pending,
+        });
+    }
+
+    /// Records a supervisor's decision on a pending order.
+    pub fn decide(&mut self, order_id: Uuid, decision: Decision, now: DateTime<Utc>) -> Result<HeldOrder, Rejection> {
+        let held = self.orders.iter_mut().find(|held| held.order.order_id == order_id && held.deadline_utc > now);
+        let Some(held) = held.filter(|held| held.state == HoldState::Pending) else {
+            return Err(Rejection::new(
+                RejectCode::SystemInvalidRequest,
+                format!("No order {} is waiting for approval", order_id),
+            ));
+        };
+        let supervisor = decision.supervisor;
+        held.state = if decision.approve {
+            HoldState::Approved { supervisor, decided_utc: now }
+        } else {
+            HoldState::Rejected { supervisor, note: decision.note, decided_utc: now }
+        };
+        Ok(held.clone())
+    }
+
+    /// Takes the orders `instance` parked that are decided or out of time,
+    /// and drops orphaned ones.
+    pub fn take_settled(&mut self, instance: &str, now: DateTime<Utc>) -> Vec<(OrderRequest, Outcome)> {
+        let orphaned = now - Duration::seconds(ORPHAN_GRACE_SECS);
+        self.orders.retain(|held| held.instance == instance || held.deadline_utc > orphaned);
+        let mut settled = Vec::new();
+        self.orders.retain(|held| {
+            if held.instance != instance {
+                return true;
+            }
+            let (code, reason) = (held.rejection.code, &held.rejection.message);
+            let outcome = match &held.state {
+                HoldState::Approved { .. } => {
+                    Outcome::Approved { waived: [held.waived.as_slice(), &[code]].concat() }
+                }
+                HoldState::Rejected { supervisor, note, .. } => {
+                    let message = format!("Rejected by {}: {} ({})", supervisor, note, reason);
+                    Outcome::Rejected(Rejection::new(code, message))
+                }
+                HoldState::Pending if held.deadline_utc <= now => {
+                    Outcome::Rejected(Rejection::new(code, format!("Not approved in time: {}", reason)))
+                }
+                HoldState::Pending => return true,
+            };
+            settled.push((held.order.clone(), outcome));
+            false
+        });
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_wire::{HopStamps, OrderPriority, OrderSide, TradingMode};
+
+    fn order() -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::new_v4(),
+            account_id: 101,
+            instrument_id: 1,
+            side: OrderSide::Buy,
+            price: 60_000_00,
+            size: 10,
+            stamps: HopStamps::default(),
+            venue_id: 1,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn held_orders_settle_by_decision_or_timeout_at_their_own_instance() {
+        let (now, policy) = (Utc::now(), ApprovalPolicy::default());
+        let stressed = Rejection::new(RejectCode::RiskStressApprovalRequired, "stressed loss past the limit");
+        let mut queue = ApprovalQueue::default();
+        let (approved, rejected, lapsed) = (order(), order(), order());
+        for held in [&approved, &rejected, &lapsed] {
+            queue.hold(held, stressed.clone(), Vec::new(), "rg-1", &policy, now);
+        }
+        let decision = |approve| Decision { approve, supervisor: "desk-head".to_string(), note: "too big".to_string() };
+        queue.decide(approved.order_id, decision(true), now).unwrap();
+        queue.decide(rejected.order_id, decision(false), now).unwrap();
+        assert!(queue.decide(approved.order_id, decision(false), now).is_err(), "already decided");
+
+        // Another instance leaves them alone; this one gets the decided two.
+        assert!(queue.take_settled("rg-2", now).is_empty());
+        let settled = queue.take_settled("rg-1", now);
+        assert_eq!(settled.len(), 2);
+        let waived = vec![RejectCode::RiskStressApprovalRequired];
+        assert!(settled.contains(&(approved.clone(), Outcome::Approved { waived })));
+        assert!(matches!(&settled[1].1, Outcome::Rejected(r) if r.message.starts_with("Rejected by desk-head")));
+
+        let deadline = now + Duration::seconds(policy.timeout_secs as i64);
+        assert!(queue.decide(lapsed.order_id, decision(true), deadline).is_err(), "out of time");
+        let settled = queue.take_settled("rg-1", deadline);
+        assert!(matches!(&settled[..], [(o, Outcome::Rejected(r))] if *o == lapsed && r.code == stressed.code));
+        assert!(queue.orders.is_empty());
+        assert!(ApprovalPolicy { hold_codes: vec![RejectCode::RiskKillSwitchEngaged], ..policy }.validate().is_err());
+    }
+}
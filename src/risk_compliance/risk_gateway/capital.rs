@@ -0,0 +1,303 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Capital Allocation
+ *
+ * File: src/risk_compliance/risk_gateway/capital.rs
+ *
+ * Description:
+ * Gives each strategy a notional budget (PUT /capital/{strategy_id}) and
+ * rejects, with RISK_CAPITAL_ALLOCATION, an order or package that would take
+ * the strategy's consumption past it. A strategy consumes:
+ *
+ *   positions     the gross exposure of its sub-portfolio at the portfolio
+ *                 manager (GET /portfolio/strategies)
+ *   open orders   the unfilled notional of its orders working at the
+ *                 exchange gateway (GET /orders/open)
+ *   pending       what this instance approved since the last refresh began,
+ *                 which neither service can show yet
+ *
+ * Both services are polled every couple of seconds; an approval is dropped
+ * from `pending` once a refresh that started after it has completed. A
+ * package consumes its net notional, as against the exposure limit.
+ *
+ * Budgets live in Redis, so every gateway instance enforces the same ones,
+ * and can be changed intraday: PUT sets a strategy's budget, and POST
+ * /capital/reallocate moves part of one strategy's budget to another in one
+ * write. A budget cut below what the strategy already consumes stops its new
+ * orders until positions or orders come off. Orders that name no strategy,
+ * strategies without a budget and hedge orders are not held back.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::ReferenceData;
+use quantumarb_wire::OrderPriority;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+// --- Data Structures ---
+
+/// One strategy's budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct Allocation {
+    pub budget: Money,
+    pub updated_utc: DateTime<Utc>,
+}
+
+/// Every strategy's budget, by strategy id; stored in Redis.
+pub type CapitalAllocations = BTreeMap<String, Allocation>;
+
+/// Body of a PUT /capital/{strategy_id} request.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct AllocationUpdate {
+    pub budget: Money,
+}
+
+/// Body of a POST /capital/reallocate request.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct Reallocation {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+}
+
+/// A strategy's budget and what it consumes, for GET /capital.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct StrategyCapital {
+    pub strategy_id: String,
+    pub budget: Option<Money>,
+    pub positions: Money,
+    pub open_orders: Money,
+    pub pending: Money,
+    pub used: Money,
+    /// Budget less used; None without a budget.
+    pub available: Option<Money>,
+}
+
+/// Body of GET /capital.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CapitalStatus {
+    /// When positions and open orders were last read; None before the first time.
+    pub refreshed_utc: Option<DateTime<Utc>>,
+    pub strategies: Vec<StrategyCapital>,
+}
+
+/// The part of GET /portfolio/strategies the gateway reads.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct StrategiesReport {
+    pub strategies: Vec<StrategyExposure>,
+}
+
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct StrategyExposure {
+    pub strategy_id: String,
+    pub gross_exposure: Money,
+}
+
+/// The part of an exchange gateway open order (GET /orders/open) the
+/// gateway reads.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct WorkingOrder {
+    pub instrument_symbol: String,
+    pub price: u64,
+    pub size: u32,
+    #[serde(default)]
+    pub cumulative_size: u32,
+    #[serde(default)]
+    pub strategy: Option<String>,
+}
+
+/// What each strategy consumes, as this instance last saw it.
+#[derive(Debug, Default)]
+pub struct CapitalUsage {
+    positions: HashMap<String, Money>,
+    open_orders: HashMap<String, Money>,
+    pending: Vec<(Instant, String, Money)>,
+    refreshed: Option<DateTime<Utc>>,
+}
+
+impl CapitalUsage {
+    /// Replaces positions and open orders with a refresh that began at
+    /// `started`; approvals made before then are now in one or the other.
+    pub fn refreshed(
+        &mut self,
+        started: Instant,
+        positions: HashMap<String, Money>,
+        open_orders: HashMap<String, Money>,
+    ) {
+        self.positions = positions;
+        self.open_orders = open_orders;
+        self.pending.retain(|(approved, _, _)| *approved >= started);
+        self.refreshed = Some(Utc::now());
+    }
+
+    /// Charges an approval to its strategy until the next refresh.
+    pub fn reserve(&mut self, strategy_id: &str, notional: Money) {
+        self.pending.push((Instant::now(), strategy_id.to_string(), notional));
+    }
+
+    pub fn used(&self, strategy_id: &str) -> Money {
+        self.positions_of(strategy_id) + self.open_orders_of(strategy_id) + self.pending_of(strategy_id)
+    }
+
+    /// Every strategy with a budget or some consumption.
+    pub fn report(&self, allocations: &CapitalAllocations) -> CapitalStatus {
+        let mut strategies: Vec<&String> = allocations.keys().collect();
+        strategies.extend(self.positions.keys().chain(self.open_orders.keys()));
+        strategies.extend(self.pending.iter().map(|(_, strategy_id, _)| strategy_id));
+        strategies.sort();
+        strategies.dedup();
+        let strategies = strategies
+            .into_iter()
+            .map(|strategy_id| {
+                let budget = allocations.get(strategy_id).map(|allocation| allocation.budget);
+                let used = self.used(strategy_id);
+                StrategyCapital {
+                    strategy_id: strategy_id.clone(),
+                    budget,
+                    positions: self.positions_of(strategy_id),
+                    open_orders: self.open_orders_of(strategy_id),
+                    pending: self.pending_of(strategy_id),
+                    used,
+                    available: budget.map(|budget| budget - used),
+                }
+            })
+            .collect();
+        CapitalStatus { refreshed_utc: self.refreshed, strategies }
+    }
+
+    fn positions_of(&self, strategy_id: &str) -> Money {
+        self.positions.get(strategy_id).copied().unwrap_or(Money::ZERO)
+    }
+
+    fn open_orders_of(&self, strategy_id: &str) -> Money {
+        self.open_orders.get(strategy_id).copied().unwrap_or(Money::ZERO)
+    }
+
+    fn pending_of(&self, strategy_id: &str) -> Money {
+        self.pending.iter().filter(|(_, s, _)| s == strategy_id).map(|(_, _, notional)| *notional).sum()
+    }
+}
+
+/// Each strategy's gross position exposure.
+pub fn position_notional(report: StrategiesReport) -> HashMap<String, Money> {
+    report.strategies.into_iter().map(|s| (s.strategy_id, s.gross_exposure)).collect()
+}
+
+/// Each strategy's unfilled order notional. Orders on instruments the
+/// gateway does not know are left out, as the venue will refuse them.
+pub fn open_order_notional(orders: &[WorkingOrder], instruments: &ReferenceData) -> HashMap<String, Money> {
+    let mut notional = HashMap::new();
+    for order in orders {
+        let (Some(strategy_id), Some(instrument)) = (&order.strategy, instruments.by_symbol(&order.instrument_symbol))
+        else {
+            continue;
+        };
+        let unfilled = Quantity::from(order.size.saturating_sub(order.cumulative_size));
+        *notional.entry(strategy_id.clone()).or_insert(Money::ZERO) +=
+            instrument.notional(instrument.price(order.price), unfilled).abs();
+    }
+    notional
+}
+
+// --- Reallocation ---
+
+/// Moves `amount` of one strategy's budget to another.
+pub fn reallocate(allocations: &mut CapitalAllocations, request: &Reallocation) -> Result<(), Rejection> {
+    let invalid = |reason: String| Err(Rejection::new(RejectCode::SystemInvalidRequest, reason));
+    if request.amount <= Money::ZERO {
+        return invalid("Reallocated amount must be positive".to_string());
+    }
+    if request.from == request.to {
+        return invalid("Cannot reallocate a strategy's budget to itself".to_string());
+    }
+    let Some(from) = allocations.get(&request.from) else {
+        return invalid(format!("Strategy {} has no allocation", request.from));
+    };
+    if from.budget < request.amount {
+        return invalid(format!("Strategy {} has only {:.2} allocated", request.from, from.budget));
+    }
+    let now = Utc::now();
+    let to_budget = allocations.get(&request.to).map_or(Money::ZERO, |allocation| allocation.budget);
+    allocations.insert(request.from.clone(), Allocation { budget: from.budget - request.amount, updated_utc: now });
+    allocations.insert(request.to.clone(), Allocation { budget: to_budget + request.amount, updated_utc: now });
+    Ok(())
+}
+
+// --- Pre-Trade Check ---
+
+/// Rejects an order or package of `notional` that would take its strategy
+/// past its budget, scaled by `factor` (below 1 while Redis is down).
+pub fn check(
+    allocations: &CapitalAllocations,
+    usage: &CapitalUsage,
+    strategy_id: &str,
+    priority: OrderPriority,
+    notional: Money,
+    factor: f64,
+) -> Result<(), Rejection> {
+    if strategy_id.is_empty() || priority == OrderPriority::Hedge {
+        return Ok(());
+    }
+    let Some(allocation) = allocations.get(strategy_id) else {
+        return Ok(());
+    };
+    let budget = allocation.budget.scaled(factor);
+    let used = usage.used(strategy_id);
+    if used + notional > budget {
+        return Err(Rejection::new(
+            RejectCode::RiskCapitalAllocation,
+            format!(
+                "Strategy {} would use {:.2} on top of {:.2}, past its allocation of {:.2}",
+                strategy_id, notional, used, budget
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approvals_count_until_a_later_refresh_and_budgets_can_move() {
+        let mut allocations = CapitalAllocations::new();
+        let budget = Allocation { budget: Money::from_f64(100_000.0), updated_utc: Utc::now() };
+        allocations.insert("stat_arb".to_string(), budget);
+        let mut usage = CapitalUsage::default();
+        usage.refreshed(
+            Instant::now(),
+            HashMap::from([("stat_arb".to_string(), Money::from_f64(60_000.0))]),
+            HashMap::from([("stat_arb".to_string(), Money::from_f64(20_000.0))]),
+        );
+        let order = |usage: &CapitalUsage, notional: f64, priority| {
+            check(&allocations, usage, "stat_arb", priority, Money::from_f64(notional), 1.0)
+        };
+        assert!(order(&usage, 20_000.0, OrderPriority::Arbitrage).is_ok());
+        assert!(order(&usage, 20_000.01, OrderPriority::Arbitrage).is_err());
+        assert!(order(&usage, 50_000.0, OrderPriority::Hedge).is_ok());
+        assert!(check(&allocations, &usage, "momentum", OrderPriority::Arbitrage, Money::from_f64(1e9), 1.0).is_ok());
+
+        let started = Instant::now();
+        usage.reserve("stat_arb", Money::from_f64(15_000.0));
+        assert!(order(&usage, 5_000.01, OrderPriority::Arbitrage).is_err());
+        // A refresh that began before the approval does not show it yet.
+        usage.refreshed(started, HashMap::new(), HashMap::new());
+        assert_eq!(usage.used("stat_arb"), Money::from_f64(15_000.0));
+        usage.refreshed(Instant::now(), HashMap::new(), HashMap::new());
+        assert_eq!(usage.used("stat_arb"), Money::ZERO);
+
+        let move_budget = |amount: f64| Reallocation {
+            from: "stat_arb".to_string(),
+            to: "momentum".to_string(),
+            amount: Money::from_f64(amount),
+        };
+        reallocate(&mut allocations, &move_budget(40_000.0)).unwrap();
+        assert_eq!(allocations["stat_arb"].budget, Money::from_f64(60_000.0));
+        assert_eq!(allocations["momentum"].budget, Money::from_f64(40_000.0));
+        assert!(reallocate(&mut allocations, &move_budget(60_000.01)).is_err());
+    }
+}
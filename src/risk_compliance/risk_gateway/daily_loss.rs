@@ -0,0 +1,186 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Daily Loss Limits
+ *
+ * File: src/risk_compliance/risk_gateway/daily_loss.rs
+ *
+ * Description:
+ * Holds each account to a maximum daily loss. The portfolio manager
+ * publishes every account's session P&L (realized + unrealized since the
+ * trading day began) on 'portfolio.account_pnl'. When an account's loss
+ * reaches its limit (`max_daily_loss`, set through PUT /limits/{account_id})
+ * the account is locked out: orders that would add to one of its positions
+ * are rejected with RISK_DAILY_LOSS_LIMIT, while orders that reduce one
+ * still pass. With no P&L from the portfolio manager yet, no order can be
+ * shown to reduce a position, so all are rejected.
+ *
+ * The lockout is kept on the account in Redis, so every gateway instance
+ * enforces it, and is raised by the instance that owns the account. It
+ * lasts until the portfolio manager starts the next session or an operator
+ * releases it (POST /daily-loss/{account_id}/reset); a released account is
+ * not locked out again before the next session. Locking out is notified as
+ * DAILY_LOSS_LOCKOUT.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_risk::concentration;
+use quantumarb_types::AccountPnl;
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+
+// --- Data Structures ---
+
+/// An account locked out by its daily loss limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct DailyLossLockout {
+    pub daily_pnl: Money,
+    pub limit: Money,
+    /// The session the loss was made in; the lockout ends with it.
+    pub session_start_utc: DateTime<Utc>,
+    pub locked_utc: DateTime<Utc>,
+}
+
+/// Body of GET /daily-loss/{account_id}.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct DailyLossStatus {
+    pub account_id: u32,
+    pub max_daily_loss: Option<Money>,
+    pub lockout: Option<DailyLossLockout>,
+    /// The last P&L the portfolio manager published for the account.
+    pub pnl: Option<AccountPnl>,
+}
+
+/// What the latest P&L means for an account's lockout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    Lock(DailyLossLockout),
+    /// A new session has begun.
+    Release,
+    Unchanged,
+}
+
+// --- Evaluation ---
+
+/// Compares an account's session P&L with its limit. `released_session` is
+/// the session in which an operator last released the account.
+pub fn evaluate(
+    max_daily_loss: Option<Money>,
+    lockout: Option<&DailyLossLockout>,
+    released_session: Option<DateTime<Utc>>,
+    pnl: &AccountPnl,
+) -> Transition {
+    if let Some(lockout) = lockout {
+        return if pnl.session_start_utc > lockout.session_start_utc {
+            Transition::Release
+        } else {
+            Transition::Unchanged
+        };
+    }
+    let Some(limit) = max_daily_loss else {
+        return Transition::Unchanged;
+    };
+    if pnl.daily_pnl > -limit || released_session == Some(pnl.session_start_utc) {
+        return Transition::Unchanged;
+    }
+    Transition::Lock(DailyLossLockout {
+        daily_pnl: pnl.daily_pnl,
+        limit,
+        session_start_utc: pnl.session_start_utc,
+        locked_utc: Utc::now(),
+    })
+}
+
+// --- Pre-Trade Check ---
+
+/// While the account is locked out, rejects an order unless it reduces the
+/// account's position in the order's symbol.
+pub fn check_order(
+    lockout: Option<&DailyLossLockout>,
+    pnl: Option<&AccountPnl>,
+    order: &OrderRequest,
+) -> Result<(), Rejection> {
+    let Some(lockout) = lockout else {
+        return Ok(());
+    };
+    let reduces = match (pnl, concentration::instrument_symbol(order.instrument_id)) {
+        (Some(pnl), Some(symbol)) => {
+            let current = pnl.positions.get(symbol).copied().unwrap_or(0);
+            let after = match order.side {
+                OrderSide::Buy => current + order.size as i64,
+                OrderSide::Sell => current - order.size as i64,
+            };
+            after.abs() < current.abs()
+        }
+        _ => false,
+    };
+    if reduces {
+        return Ok(());
+    }
+    Err(Rejection::new(
+        RejectCode::RiskDailyLossLimit,
+        format!(
+            "Account {} lost {} today, past its daily loss limit of {}; only position-reducing orders are accepted",
+            order.account_id, lockout.daily_pnl, lockout.limit
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_wire::{HopStamps, OrderPriority, TradingMode};
+    use std::collections::HashMap;
+
+    fn pnl(daily_pnl: f64, session_start_utc: DateTime<Utc>) -> AccountPnl {
+        AccountPnl {
+            account_id: 101,
+            session_start_utc,
+            realized_pnl: Money::ZERO,
+            unrealized_pnl: Money::from_f64(daily_pnl),
+            daily_pnl: Money::from_f64(daily_pnl),
+            positions: HashMap::from([("BTC".to_string(), 3)]),
+            margin_requirement: Money::ZERO,
+            timestamp_utc: Utc::now(),
+        }
+    }
+
+    fn order(side: OrderSide, size: u32) -> OrderRequest {
+        OrderRequest {
+            order_id: uuid::Uuid::nil(),
+            account_id: 101,
+            instrument_id: 1,
+            side,
+            price: 60_000_00,
+            size,
+            stamps: HopStamps::default(),
+            venue_id: 1,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn a_locked_out_account_can_only_reduce_until_the_next_session() {
+        let (today, tomorrow) = (Utc::now(), Utc::now() + chrono::Duration::days(1));
+        let limit = Some(Money::from_f64(25_000.0));
+        assert_eq!(evaluate(limit, None, None, &pnl(-24_999.0, today)), Transition::Unchanged);
+        let Transition::Lock(lockout) = evaluate(limit, None, None, &pnl(-25_000.0, today)) else {
+            panic!("a loss at the limit locks the account out");
+        };
+
+        let current = pnl(-25_000.0, today);
+        assert!(check_order(Some(&lockout), Some(&current), &order(OrderSide::Buy, 1)).is_err());
+        assert!(check_order(Some(&lockout), Some(&current), &order(OrderSide::Sell, 2)).is_ok());
+        // Selling through zero leaves a larger short than the long it closes.
+        assert!(check_order(Some(&lockout), Some(&current), &order(OrderSide::Sell, 7)).is_err());
+        assert!(check_order(Some(&lockout), None, &order(OrderSide::Sell, 2)).is_err());
+
+        assert_eq!(evaluate(limit, Some(&lockout), None, &current), Transition::Unchanged);
+        assert_eq!(evaluate(limit, Some(&lockout), None, &pnl(-25_000.0, tomorrow)), Transition::Release);
+        // Released by an operator: not locked out again in the same session.
+        assert_eq!(evaluate(limit, None, Some(today), &current), Transition::Unchanged);
+    }
+}
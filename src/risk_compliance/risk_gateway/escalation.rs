@@ -0,0 +1,245 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: VaR Escalation Policy
+ *
+ * File: src/risk_compliance/risk_gateway/escalation.rs
+ *
+ * Description:
+ * Escalates on the portfolio's VaR against an absolute limit, on top of the
+ * limit tightening driven by the VaR-to-portfolio ratio. The policy is a
+ * ladder of steps, each taken when VaR reaches a share of the limit, and
+ * each naming the actions that apply while VaR is at or above it:
+ *
+ *   NOTIFY                  a VaR breach notification when the step is reached
+ *   BLOCK_RISK_INCREASING   reject orders that would add to a symbol's
+ *                           exposure; orders that reduce it still pass
+ *   AUTO_HEDGE              instruct the strategy engine, on
+ *                           'strategy.instructions', to cut every position by
+ *                           the fraction that brings VaR back to the hedge
+ *                           target, when the step is reached
+ *
+ * The default ladder notifies at 80% of the limit, also blocks at 100% and
+ * also hedges at 125%, to 90% of the limit. Notifications and hedges fire
+ * once on the way up; a hedge is not repeated until VaR has dropped below
+ * its step and risen through it again, so fills have time to bring VaR down.
+ * Blocking lifts as soon as VaR falls below its step.
+ *
+ * The policy is kept in Redis and set through the admin API
+ * (/var-escalation); the limit defaults to QA_VAR_LIMIT (100,000).
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::InstrumentDefinition;
+use quantumarb_risk::concentration::{self, Exposures};
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_VAR_LIMIT: f64 = 100_000.0;
+
+// --- Policy ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EscalationAction {
+    Notify,
+    BlockRiskIncreasing,
+    AutoHedge,
+}
+
+/// One rung of the ladder.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct EscalationStep {
+    /// Share of the VaR limit at which the step is reached (1.0 = the limit).
+    pub at_pct_of_limit: f64,
+    pub actions: Vec<EscalationAction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct EscalationPolicy {
+    /// Absolute 99% VaR limit, in dollars.
+    pub var_limit: f64,
+    /// Steps in ascending order of `at_pct_of_limit`.
+    pub steps: Vec<EscalationStep>,
+    /// VaR an auto-hedge aims for, as a share of the limit.
+    pub hedge_target_pct_of_limit: f64,
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        use EscalationAction::*;
+        let var_limit = std::env::var("QA_VAR_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &f64| *v > 0.0)
+            .unwrap_or(DEFAULT_VAR_LIMIT);
+        let step = |at_pct_of_limit, actions: &[EscalationAction]| EscalationStep {
+            at_pct_of_limit,
+            actions: actions.to_vec(),
+        };
+        EscalationPolicy {
+            var_limit,
+            steps: vec![
+                step(0.8, &[Notify]),
+                step(1.0, &[Notify, BlockRiskIncreasing]),
+                step(1.25, &[Notify, BlockRiskIncreasing, AutoHedge]),
+            ],
+            hedge_target_pct_of_limit: 0.9,
+        }
+    }
+}
+
+impl EscalationPolicy {
+    /// Errs with the reason the policy cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.var_limit <= 0.0 {
+            return Err("var_limit must be positive.".to_string());
+        }
+        if self.hedge_target_pct_of_limit <= 0.0 {
+            return Err("hedge_target_pct_of_limit must be positive.".to_string());
+        }
+        if self.steps.iter().any(|s| s.at_pct_of_limit <= 0.0) {
+            return Err("Every step's at_pct_of_limit must be positive.".to_string());
+        }
+        if self.steps.windows(2).any(|w| w[0].at_pct_of_limit >= w[1].at_pct_of_limit) {
+            return Err("Steps must be in ascending order of at_pct_of_limit.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Index of the highest step `var_amount` has reached.
+    pub fn step_for(&self, var_amount: f64) -> Option<usize> {
+        self.steps.iter().rposition(|s| var_amount >= s.at_pct_of_limit * self.var_limit)
+    }
+
+    /// Fraction of every position to cut to bring VaR down to the hedge
+    /// target, taking VaR as proportional to position size.
+    pub fn hedge_fraction(&self, var_amount: f64) -> f64 {
+        if var_amount <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - self.hedge_target_pct_of_limit * self.var_limit / var_amount).clamp(0.0, 1.0)
+    }
+}
+
+// --- Escalation State ---
+
+/// Where VaR stands on the ladder, for GET /var-escalation and the
+/// pre-trade check.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct EscalationStatus {
+    /// Index of the step reached; None below the first.
+    pub step: Option<usize>,
+    pub var_amount: f64,
+    pub var_limit: f64,
+    pub blocking_risk_increasing: bool,
+    pub updated_utc: String,
+}
+
+impl EscalationStatus {
+    /// Moves to the step `var_amount` has reached under `policy`. Returns
+    /// the actions to carry out now: those of each step passed on the way up
+    /// (notifying once, for the highest), nothing on the way down.
+    pub fn update(&mut self, policy: &EscalationPolicy, var_amount: f64) -> Vec<EscalationAction> {
+        let previous = self.step;
+        let step = policy.step_for(var_amount);
+        self.step = step;
+        self.var_amount = var_amount;
+        self.var_limit = policy.var_limit;
+        self.blocking_risk_increasing =
+            step.is_some_and(|i| policy.steps[i].actions.contains(&EscalationAction::BlockRiskIncreasing));
+        self.updated_utc = chrono::Utc::now().to_rfc3339();
+
+        let Some(reached) = step.filter(|s| previous.is_none_or(|p| *s > p)) else {
+            return Vec::new();
+        };
+        let first_new = previous.map_or(0, |p| p + 1);
+        let mut actions: Vec<EscalationAction> = Vec::new();
+        for passed in &policy.steps[first_new..=reached] {
+            for action in &passed.actions {
+                if !actions.contains(action) {
+                    actions.push(*action);
+                }
+            }
+        }
+        actions.retain(|a| *a != EscalationAction::BlockRiskIncreasing);
+        actions
+    }
+}
+
+// --- Pre-Trade Check ---
+
+/// While blocking, rejects an order unless it reduces the absolute exposure
+/// of its symbol. With no portfolio snapshot yet, no order can be shown to
+/// reduce risk, so all are rejected.
+pub fn check_order(
+    status: &EscalationStatus,
+    exposures: Option<&Exposures>,
+    instrument: Option<&InstrumentDefinition>,
+    order: &OrderRequest,
+) -> Result<(), Rejection> {
+    if !status.blocking_risk_increasing {
+        return Ok(());
+    }
+    let reduces = match (exposures, instrument, concentration::instrument_symbol(order.instrument_id)) {
+        (Some(exposures), Some(instrument), Some(symbol)) => {
+            let quantity = match order.side {
+                OrderSide::Buy => Quantity::from(order.size),
+                OrderSide::Sell => -Quantity::from(order.size),
+            };
+            let current = exposures.symbol(symbol);
+            let after = current + instrument.notional(instrument.price(order.price), quantity);
+            after.abs() < current.abs()
+        }
+        _ => false,
+    };
+    if reduces {
+        return Ok(());
+    }
+    Err(Rejection::new(
+        RejectCode::RiskVarLimitBreached,
+        format!(
+            "VaR {} is over {:.0}% of its {} limit; only risk-reducing orders are accepted",
+            Money::from_f64(status.var_amount),
+            status.var_amount / status.var_limit * 100.0,
+            Money::from_f64(status.var_limit),
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EscalationAction::*;
+
+    fn policy() -> EscalationPolicy {
+        EscalationPolicy { var_limit: 100_000.0, ..EscalationPolicy::default() }
+    }
+
+    #[test]
+    fn acts_on_the_way_up_and_only_blocks_on_the_way_down() {
+        let policy = policy();
+        let mut status = EscalationStatus::default();
+        assert!(status.update(&policy, 50_000.0).is_empty());
+        assert_eq!(status.update(&policy, 85_000.0), vec![Notify]);
+        assert!(!status.blocking_risk_increasing);
+        // Straight past the limit to the hedge step: one notification, one hedge.
+        assert_eq!(status.update(&policy, 130_000.0), vec![Notify, AutoHedge]);
+        assert!(status.blocking_risk_increasing);
+        assert!(status.update(&policy, 110_000.0).is_empty());
+        assert!(status.blocking_risk_increasing);
+        assert_eq!(status.update(&policy, 128_000.0), vec![Notify, AutoHedge]);
+        assert!(status.update(&policy, 90_000.0).is_empty());
+        assert!(!status.blocking_risk_increasing);
+    }
+
+    #[test]
+    fn hedges_back_to_the_target_and_rejects_unordered_steps() {
+        let mut policy = policy();
+        assert!((policy.hedge_fraction(150_000.0) - 0.4).abs() < 1e-12);
+        assert_eq!(policy.hedge_fraction(80_000.0), 0.0);
+        assert!(policy.validate().is_ok());
+        policy.steps.swap(0, 2);
+        assert!(policy.validate().is_err());
+    }
+}
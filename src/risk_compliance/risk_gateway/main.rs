@@ -12,157 +12,3121 @@
  * - A background task periodically fetches the 99% VaR from the var-calculator.
  * - Based on the VaR, it adjusts the 'max_order_size' and 'max_exposure' limits
  * for the account. If VaR is high, limits are tightened; if VaR is low, they
- * are loosened.
+ * are loosened. Limits are also tightened pre-emptively when the VaR history
+ * shows a steep rise over the last hour. The rules are a declarative policy
+ * with conditions on the VaR ratio, its trend, volatility, drawdown and time
+ * of day, which can be previewed on given inputs before it is set (admin
+ * API: /limit-policy; see `policy.rs`).
  * - This creates a closed-loop, adaptive risk management system.
+ * - An admin API (port 3034) lets operators inspect and set account limits
+ * and engage or release the firm-wide kill switch. Both live in Redis so
+ * every gateway replica sees the same values.
+ * - Rejections carry a machine-readable code from `quantumarb-errors`.
+ * - Colocated strategy engines can submit orders over shared-memory rings
+ * (QA_RISK_TRANSPORT=shm) instead of the network; verdicts go back the same way.
+ * - Concentration caps per symbol, sector and venue are checked against the
+ * portfolio manager's live snapshot (admin API: /concentration-limits).
+ * Like the snapshot, the caps are refreshed every two seconds rather than
+ * read for each order.
+ * - Per-counterparty credit limits cover positions held at a venue plus
+ * unsettled trades on non-DVP venues (admin API: /counterparty-limits).
+ * - Orders for unknown instruments, or off the instrument's tick or lot size,
+ * are rejected using definitions from the reference data service.
+ * - Coordinated platform snapshots (admin API: /snapshots) archive the risk
+ * state, the portfolio manager's book and the exchange gateway's open orders
+ * together, and restore them for disaster recovery or environment cloning.
+ * - The gateway runs in the platform's trading mode (QA_TRADING_MODE, sandbox
+ * unless set to live) and rejects orders stamped with the other mode. In
+ * sandbox the order size and counterparty credit limits are multiplied by
+ * QA_SANDBOX_LIMIT_MULTIPLIER (default 10) so paper strategies are not
+ * throttled by production-sized limits (admin API: /mode).
+ * - Multi-leg orders (MultiLegOrder, e.g. a cross-venue arbitrage) are
+ * checked as a package: every leg against the single-order limits and the
+ * package's net notional against the account's exposure limit. One verdict,
+ * keyed by the package id, covers all legs.
+ * - A heartbeat watchdog cancels the open orders of a strategy that stops
+ * heart-beating, or flattens its positions, through the exchange gateway,
+ * per the strategy's policy (admin API: /flatten-policies, /watchdog). A
+ * silent market data feed trips every strategy's policy.
+ * - Orders routed to a venue the exchange gateway reports as reconnecting or
+ * down (on 'venues.connectivity') are rejected until it is back up, on its
+ * primary session or a backup (admin API: /venues).
+ * - Engaging the kill switch and a VaR breach are notified on the routes
+ * configured in QA_NOTIFY_CONFIG_PATH (webhook, Slack, email; see
+ * `quantumarb-notify`).
+ * - VaR is also held against an absolute limit (QA_VAR_LIMIT) by an
+ * escalation policy: steps at shares of the limit notify, block orders that
+ * add to exposure, and have the strategy engine cut positions to bring VaR
+ * back down (admin API: /var-escalation; see `escalation.rs`).
+ * - Redis can be a single instance, a Sentinel-monitored master or a
+ * cluster, with reconnects that follow a failover. While Redis is down the
+ * pre-trade checks run in a degraded mode on the last state they read, with
+ * conservative limits (admin API: /redis; see `store.rs`).
+ * - Redis and the services the gateway calls default to their in-cluster
+ * addresses; QA_REDIS_URL, QA_VAR_CALCULATOR_URL, QA_PORTFOLIO_MANAGER_URL,
+ * QA_REFERENCE_DATA_URL and QA_EXCHANGE_GATEWAY_URL override them, e.g. for
+ * the integration harness (tests/integration).
+ * - The simulated order flow is drawn from a seeded stream: --seed N (or
+ * QA_SEED) replays the same orders and packages (`quantumarb-sim`).
+ * - Requests on the shared-memory ring are checked by priority class (hedge,
+ * then arbitrage, then opportunistic; arrival order within a class), so a
+ * risk-reducing hedge is not held up by a burst of opportunistic orders.
+ * - Accounts can be sharded across several gateway instances
+ * (QA_RISK_SHARDING): a consistent-hash ring assigns each account an owner,
+ * a coordinator instance republishes the ring as instances join and leave,
+ * and orders for another instance's accounts are rejected naming the owner.
+ * Account state is written with optimistic concurrency, and an approved
+ * package reserves its net notional in the account's exposure (admin API:
+ * /shards; see `shards.rs`).
+ * - Each account can be held to a maximum daily loss (max_daily_loss on
+ * /limits): when the session P&L the portfolio manager publishes reaches
+ * it, the account is locked out of orders that add to its positions until
+ * the next session or an operator releases it (admin API: /daily-loss; see
+ * `daily_loss.rs`).
+ * - Each strategy can be given a notional budget: orders and packages are
+ * charged against it for the strategy's positions, its working orders and
+ * recent approvals, and rejected past it. Budgets can be moved between
+ * strategies intraday (admin API: /capital; see `capital.rs`).
+ * - Option orders are priced (Black-Scholes, off the underlying's mid in the
+ * feature store, QA_OPTION_VOLS and the yield curve) and held to each
+ * account's vega and gamma limits (max_vega and max_gamma on /limits); their
+ * delta-adjusted notional is what they are charged to a capital allocation
+ * (admin API: /greeks; see `quantumarb_risk::greeks`).
+ * - Large orders are stressed against the scenarios of their asset class
+ * (e.g. the underlying down 10%): one that would take the account's worst
+ * scenario loss past its stress limit (max_stress_loss on /limits) is
+ * rejected, or held for a supervisor (admin API: /stress; see
+ * `quantumarb_risk::stress`).
+ * - Orders that fail a check the approval policy names (by default the
+ * stress check's REQUIRE_APPROVAL) are parked instead of rejected: the
+ * strategy gets an interim RISK_PENDING_APPROVAL verdict, a supervisor
+ * approves or rejects the order, and the final verdict follows on the same
+ * ring; undecided orders are rejected at a timeout (admin API: /approvals;
+ * see `approvals.rs`).
+ * - Every decision is recorded on 'audit.events' (kept in the WORM log) with
+ * the request it was made on, so a recorded request stream can be replayed
+ * against a new build of the gateway and the decisions compared (see the
+ * risk-replay tool, src/tools/risk_replay).
+ * - Limits tighten at the times of day prices gap: by default order size and
+ * exposure limits and symbol concentration caps are halved in the first and
+ * last 5 minutes of the session and around the economic releases the data
+ * bus connector publishes on 'calendar.event_windows'. A scheduler opens and
+ * closes the windows; symbols can keep their own session (admin API:
+ * /limit-schedule; see `time_limits.rs`).
+ * - With desks configured (QA_DESKS_PATH, see `quantumarb-tenancy`) the admin
+ * API takes a bearer token. A desk's token reads the limits, daily loss and
+ * greeks of its own accounts, the capital, flatten policies and watchdog
+ * sources of its own strategies and the held orders of its own accounts, and
+ * changes only those; another desk's account or strategy answers as if it
+ * did not exist. Every other endpoint is firm-wide and takes a firm token.
+ * - The order, package, concentration and counterparty checks themselves live
+ * in the `quantumarb-risk` library; this service loads their inputs and
+ * serves the verdicts.
+ *
+ * GET /openapi.json serves the admin API's OpenAPI document and GET /docs
+ * browses it in Swagger UI.
  */
 
-use redis::AsyncCommands;
-use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use tokio::time::{self, Duration};
-use uuid::Uuid;
+// The admin API's chain of warp filters outgrows the default limit.
+#![recursion_limit = "256"]
+
+mod approvals;
+mod capital;
+mod daily_loss;
+mod escalation;
+mod policy;
+mod shards;
+mod snapshot;
+mod store;
+mod time_limits;
+mod watchdog;
+
+use approvals::{ApprovalPolicy, ApprovalQueue, Decision, Outcome};
+use capital::{AllocationUpdate, CapitalAllocations, CapitalUsage, Reallocation, StrategiesReport, WorkingOrder};
+use daily_loss::{DailyLossLockout, DailyLossStatus, Transition};
+use escalation::{EscalationAction, EscalationPolicy, EscalationStatus};
+use policy::{Evaluation, LimitPolicy, PolicyInputs, PolicyStatus};
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_features::FeatureSnapshot;
+use quantumarb_money::{Money, Quantity};
+use quantumarb_notify::{EventClass, Notifier};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_risk::checks::{self, OrderLimits};
+use quantumarb_risk::concentration::{self, ConcentrationLimits, ConcentrationLimitsUpdate, Exposures};
+use quantumarb_risk::counterparty::{CounterpartyLimitUpdate, CounterpartyLimits};
+use quantumarb_risk::curves::{self, YieldCurve};
+use quantumarb_risk::greeks::{self, GreekExposure, GreeksLimits, OptionMarket};
+use quantumarb_risk::package;
+use quantumarb_risk::pricing::ImpliedVols;
+use quantumarb_risk::stress::{self, StressConfig, StressMarket};
+use quantumarb_shm::{risk_channel, PushError, ShmConsumer, ShmProducer};
+use quantumarb_sim::Seed;
+use quantumarb_tenancy::{Desks, Scope};
+use quantumarb_types::{
+    AccountPnl, AuditEvent, CancelRequest, CheckedRequest, CounterpartyExposure, EventWindow, FlattenRequest, Heartbeat,
+    PortfolioSnapshot, RiskDecisionAudit, RiskOutcome, StrategyInstruction, VaRHistory, VaRResult, VenueConnectivity,
+};
+use quantumarb_wire::{
+    Hop, HopStamps, MultiLegOrder, OrderLeg, OrderPriority, OrderQueue, OrderRequest, OrderSide, RiskVerdict,
+    TradingMode, WireMessage,
+};
+use rand::Rng;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use shards::{ShardConfig, Shards};
+use snapshot::{CreateSnapshotRequest, PlatformSnapshot, RestoreReport, SnapshotConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use store::{RedisStore, StoreConfig};
+use time_limits::{LimitSchedule, ScheduleStatus};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::{self, Duration};
+use uuid::Uuid;
+use warp::Filter;
+use watchdog::{FlattenPolicies, StrategyPolicy, Watchdog, WatchdogConfig};
+
+// --- Data Structures ---
+
+#[derive(Debug, Serialize, Deserialize, ApiSchema)]
+struct AccountState {
+    account_id: u32,
+    base_max_exposure: Money, // The baseline limit
+    current_max_exposure: Money, // The dynamically adjusted limit
+    base_max_order_size: u32,
+    current_max_order_size: u32,
+    current_exposure: Money,
+    /// Bumped by every write; see `update_account`.
+    #[serde(default)]
+    version: u64,
+    /// Session loss at which the account is locked out; no limit when absent.
+    #[serde(default)]
+    max_daily_loss: Option<Money>,
+    #[serde(default)]
+    daily_loss_lockout: Option<DailyLossLockout>,
+    /// The session in which an operator last released a lockout.
+    #[serde(default)]
+    daily_loss_released_session: Option<chrono::DateTime<chrono::Utc>>,
+    /// Net option vega (per volatility point) the account may hold.
+    #[serde(default)]
+    max_vega: Option<Money>,
+    /// Net option dollar gamma (per 1% move) the account may hold.
+    #[serde(default)]
+    max_gamma: Option<Money>,
+    /// Loss the account may face in the worst stress scenario.
+    #[serde(default)]
+    max_stress_loss: Option<Money>,
+}
+
+#[derive(Debug, PartialEq)]
+enum RiskDecision {
+    Approved,
+    Rejected(Rejection),
+    /// Parked for a supervisor (see `approvals.rs`); the verdict follows.
+    Held(Rejection),
+}
+
+impl RiskDecision {
+    /// The verdict sent back to the strategy.
+    fn verdict(self, order_id: Uuid, stamps: HopStamps) -> RiskVerdict {
+        let reject = match self {
+            RiskDecision::Approved => return RiskVerdict { order_id, approved: true, reject: None, stamps },
+            RiskDecision::Rejected(rejection) => rejection,
+            RiskDecision::Held(rejection) => {
+                Rejection::new(RejectCode::RiskPendingApproval, format!("Held for approval: {}", rejection))
+            }
+        };
+        RiskVerdict { order_id, approved: false, reject: Some(reject), stamps }
+    }
+}
+
+/// Firm-wide kill switch. While engaged, every order is rejected.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+struct KillSwitchState {
+    engaged: bool,
+    reason: String,
+    updated_utc: String,
+}
+
+/// Body of a PUT /limits/{account_id} request. Omitted fields are left unchanged.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct LimitUpdate {
+    max_order_size: Option<u32>,
+    max_exposure: Option<Money>,
+    max_daily_loss: Option<Money>,
+    max_vega: Option<Money>,
+    max_gamma: Option<Money>,
+    max_stress_loss: Option<Money>,
+}
+
+/// Body of a GET /greeks/{account_id} response.
+#[derive(Debug, Serialize, ApiSchema)]
+struct GreeksStatus {
+    account_id: u32,
+    limits: GreeksLimits,
+    /// Net greeks of the account's option positions.
+    exposure: GreekExposure,
+    /// Underlying prices the options were priced off.
+    spots: HashMap<String, f64>,
+    vols: ImpliedVols,
+}
+
+/// Body of a POST /kill-switch request.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct KillSwitchRequest {
+    engaged: bool,
+    reason: Option<String>,
+}
+
+/// Services the gateway calls.
+const VAR_CALCULATOR: Upstream =
+    Upstream { env: "QA_VAR_CALCULATOR_URL", default: "http://var-calculator.default.svc.cluster.local" };
+const PORTFOLIO_MANAGER: Upstream =
+    Upstream { env: "QA_PORTFOLIO_MANAGER_URL", default: "http://portfolio-manager.default.svc.cluster.local" };
+const REFERENCE_DATA: Upstream =
+    Upstream { env: "QA_REFERENCE_DATA_URL", default: "http://reference-data-service.default.svc.cluster.local" };
+const EXCHANGE_GATEWAY: Upstream =
+    Upstream { env: "QA_EXCHANGE_GATEWAY_URL", default: "http://exchange-gateway.default.svc.cluster.local" };
+const KILL_SWITCH_KEY: &str = "kill_switch";
+const CONCENTRATION_LIMITS_KEY: &str = "concentration_limits";
+const COUNTERPARTY_LIMITS_KEY: &str = "counterparty_limits";
+const FLATTEN_POLICIES_KEY: &str = "flatten_policies";
+const VAR_ESCALATION_KEY: &str = "var_escalation_policy";
+const CAPITAL_ALLOCATIONS_KEY: &str = "capital_allocations";
+const STRESS_CONFIG_KEY: &str = "stress_config";
+const APPROVAL_POLICY_KEY: &str = "approval_policy";
+const APPROVAL_QUEUE_KEY: &str = "approval_queue";
+const LIMIT_SCHEDULE_KEY: &str = "limit_schedule";
+const LIMIT_POLICY_KEY: &str = "limit_policy";
+/// How long a release stays on the calendar after its scheduled time.
+const CALENDAR_RETENTION: chrono::TimeDelta = chrono::TimeDelta::hours(24);
+/// Redis keys captured in a platform snapshot (patterns are expanded with KEYS).
+const SNAPSHOT_KEY_PATTERNS: [&str; 11] = [
+    "account:*",
+    CONCENTRATION_LIMITS_KEY,
+    COUNTERPARTY_LIMITS_KEY,
+    FLATTEN_POLICIES_KEY,
+    VAR_ESCALATION_KEY,
+    CAPITAL_ALLOCATIONS_KEY,
+    STRESS_CONFIG_KEY,
+    APPROVAL_POLICY_KEY,
+    LIMIT_SCHEDULE_KEY,
+    LIMIT_POLICY_KEY,
+    KILL_SWITCH_KEY,
+];
+
+type SharedConnection = Arc<tokio::sync::Mutex<RedisStore>>;
+/// Latest exposures from the portfolio manager; None until the first snapshot.
+type SharedExposures = Arc<RwLock<Option<Exposures>>>;
+/// The concentration caps, refreshed from Redis in the background rather
+/// than read for every order.
+type SharedConcentrationLimits = Arc<RwLock<ConcentrationLimits>>;
+/// Last price of each symbol in the portfolio manager's book, in points.
+type SharedMarks = Arc<RwLock<HashMap<String, f64>>>;
+/// Latest per-counterparty exposure from the portfolio manager.
+type SharedCounterpartyExposures = Arc<RwLock<HashMap<String, CounterpartyExposure>>>;
+/// Instrument definitions; the built-in set until the reference data service answers.
+type SharedInstruments = Arc<RwLock<ReferenceData>>;
+type SharedWatchdog = Arc<std::sync::Mutex<Watchdog>>;
+/// Latest connectivity event per venue, from the exchange gateway.
+type SharedVenues = Arc<RwLock<HashMap<String, VenueConnectivity>>>;
+/// Where VaR stands on the escalation ladder, updated with every VaR fetch.
+type SharedEscalation = Arc<RwLock<EscalationStatus>>;
+/// The limit policy's last evaluation, updated with every VaR fetch.
+type SharedPolicyStatus = Arc<RwLock<PolicyStatus>>;
+/// Which instance owns which accounts.
+type SharedShards = Arc<RwLock<Shards>>;
+/// Latest session P&L per account, from the portfolio manager.
+type SharedAccountPnl = Arc<RwLock<HashMap<u32, AccountPnl>>>;
+/// What each strategy consumes of its capital allocation.
+type SharedCapital = Arc<RwLock<CapitalUsage>>;
+/// Underlying prices, volatilities and rates option orders are priced off.
+type SharedOptionMarket = Arc<RwLock<OptionMarket>>;
+/// The intraday limit windows open now, as the scheduler last found them.
+type SharedTimeLimits = Arc<RwLock<ScheduleStatus>>;
+/// Economic releases by event id, from the data bus connector's calendar.
+type SharedCalendar = Arc<RwLock<HashMap<String, EventWindow>>>;
+
+/// Attempts at an account write before giving up on concurrent writers.
+const ACCOUNT_WRITE_ATTEMPTS: usize = 5;
+/// Daily loss limit of the account set up at start.
+const DEFAULT_MAX_DAILY_LOSS: f64 = 25_000.0;
+/// Option vega and gamma limits of the account set up at start.
+const DEFAULT_MAX_VEGA: f64 = 5_000.0;
+const DEFAULT_MAX_GAMMA: f64 = 50_000.0;
+/// Worst scenario loss allowed the account set up at start.
+const DEFAULT_MAX_STRESS_LOSS: f64 = 1_000_000.0;
+/// Strategies the simulated order flow is charged to.
+const SIM_ORDER_STRATEGY: &str = "momentum";
+const SIM_PACKAGE_STRATEGY: &str = "stat_arb";
+
+/// A service the gateway calls: its default in-cluster base URL and the
+/// variable that overrides it.
+struct Upstream {
+    env: &'static str,
+    default: &'static str,
+}
+
+impl Upstream {
+    fn url(&self, path: &str) -> String {
+        let base = std::env::var(self.env).unwrap_or_else(|_| self.default.to_string());
+        format!("{}{}", base.trim_end_matches('/'), path)
+    }
+}
+
+/// The trading mode and how far limits are relaxed in it.
+#[derive(Debug, Clone, Copy, Serialize, ApiSchema)]
+struct ModeConfig {
+    mode: TradingMode,
+    /// Factor applied to order size and counterparty credit limits; always 1 when live.
+    limit_multiplier: f64,
+}
+
+impl ModeConfig {
+    fn from_env() -> ModeConfig {
+        let mode = TradingMode::from_env();
+        let limit_multiplier = match mode {
+            TradingMode::Live => 1.0,
+            TradingMode::Sandbox => std::env::var("QA_SANDBOX_LIMIT_MULTIPLIER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &f64| *v >= 1.0)
+                .unwrap_or(10.0),
+        };
+        ModeConfig { mode, limit_multiplier }
+    }
+}
+
+/// Everything a pre-trade check reads.
+#[derive(Clone)]
+struct RiskContext {
+    con: SharedConnection,
+    exposures: SharedExposures,
+    concentration_limits: SharedConcentrationLimits,
+    marks: SharedMarks,
+    counterparty_exposures: SharedCounterpartyExposures,
+    instruments: SharedInstruments,
+    venues: SharedVenues,
+    escalation: SharedEscalation,
+    shards: SharedShards,
+    account_pnl: SharedAccountPnl,
+    capital: SharedCapital,
+    options: SharedOptionMarket,
+    time_limits: SharedTimeLimits,
+    mode: ModeConfig,
+}
+
+/// Everything the snapshot endpoints need. `busy` serializes snapshots and
+/// restores, which both hold the kill switch.
+#[derive(Clone)]
+struct SnapshotContext {
+    con: SharedConnection,
+    config: SnapshotConfig,
+    http_client: reqwest::Client,
+    busy: Arc<tokio::sync::Mutex<()>>,
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    println!("--- Starting QuantumArb 2.0 Dynamic Risk Gateway ---");
+    let mode = ModeConfig::from_env();
+    println!("Trading mode: {} (limit multiplier {})", mode.mode, mode.limit_multiplier);
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
+
+    let store_config = StoreConfig::from_env();
+    println!("Redis: {}", store_config.topology.describe());
+    let mut store = RedisStore::new(store_config).expect("Invalid Redis configuration");
+    redis::cmd("PING").query_async::<_, ()>(&mut store).await.expect("Failed to connect to Redis");
+    let con: SharedConnection = Arc::new(tokio::sync::Mutex::new(store));
+
+    setup_initial_account_state(con.clone()).await;
+    let shard_config = ShardConfig::from_env();
+    if shard_config.enabled {
+        println!("Sharding: instance {} at {}", shard_config.instance.id, shard_config.instance.url);
+    }
+    let shards: SharedShards = Arc::new(RwLock::new(Shards::new(shard_config.clone())));
+    if shard_config.enabled {
+        let (shards_clone, con_clone) = (shards.clone(), con.clone());
+        tokio::spawn(async move {
+            maintain_shard_membership(shards_clone, con_clone, shard_config).await;
+        });
+    }
+    let notifier = Notifier::from_env("risk-gateway");
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+
+    // Spawn the background task that tracks live portfolio exposures
+    let ctx = RiskContext {
+        con: con.clone(),
+        exposures: Arc::new(RwLock::new(None)),
+        concentration_limits: Arc::new(RwLock::new(ConcentrationLimits::default())),
+        marks: Arc::new(RwLock::new(HashMap::new())),
+        counterparty_exposures: Arc::new(RwLock::new(HashMap::new())),
+        instruments: Arc::new(RwLock::new(ReferenceData::seeded())),
+        venues: Arc::new(RwLock::new(HashMap::new())),
+        escalation: Arc::new(RwLock::new(EscalationStatus::default())),
+        shards: shards.clone(),
+        account_pnl: Arc::new(RwLock::new(HashMap::new())),
+        capital: Arc::new(RwLock::new(CapitalUsage::default())),
+        options: Arc::new(RwLock::new(OptionMarket {
+            spots: HashMap::new(),
+            vols: ImpliedVols::from_env(),
+            curve: YieldCurve::bootstrap(&curves::load_quotes_from_env()).ok(),
+        })),
+        time_limits: Arc::new(RwLock::new(ScheduleStatus::default())),
+        mode,
+    };
+
+    // Spawn the background task to adjust limits and escalate based on VaR
+    let policy_status: SharedPolicyStatus = Arc::new(RwLock::new(PolicyStatus::default()));
+    let (ctx_clone, notifier_clone, policy_clone) = (ctx.clone(), notifier.clone(), policy_status.clone());
+    tokio::spawn(async move {
+        adjust_limits_from_var(ctx_clone, notifier_clone, policy_clone).await;
+    });
+
+    let (exposures_clone, marks_clone) = (ctx.exposures.clone(), ctx.marks.clone());
+    let counterparty_clone = ctx.counterparty_exposures.clone();
+    tokio::spawn(async move {
+        refresh_portfolio_exposures(exposures_clone, marks_clone, counterparty_clone).await;
+    });
+    let (con_clone, limits_clone) = (con.clone(), ctx.concentration_limits.clone());
+    tokio::spawn(async move {
+        refresh_concentration_limits(con_clone, limits_clone).await;
+    });
+
+    // Spawn the background task that holds accounts to their daily loss limits
+    let (ctx_clone, notifier_clone) = (ctx.clone(), notifier.clone());
+    tokio::spawn(async move {
+        enforce_daily_loss_limits(ctx_clone, notifier_clone).await;
+    });
+
+    // Spawn the background task that tracks what each strategy's capital is used by
+    let ctx_clone = ctx.clone();
+    tokio::spawn(async move {
+        track_capital_usage(ctx_clone).await;
+    });
+
+    // Spawn the background task that follows the prices options are priced off
+    let ctx_clone = ctx.clone();
+    tokio::spawn(async move {
+        refresh_option_underlyings(ctx_clone).await;
+    });
+
+    // Spawn the scheduler that opens and closes the intraday limit windows,
+    // and its economic calendar feed
+    let calendar: SharedCalendar = Arc::new(RwLock::new(HashMap::new()));
+    let (con_clone, time_limits_clone, calendar_clone) = (con.clone(), ctx.time_limits.clone(), calendar.clone());
+    tokio::spawn(async move {
+        run_limit_scheduler(con_clone, time_limits_clone, calendar_clone).await;
+    });
+    tokio::spawn(async move {
+        listen_for_event_windows(calendar).await;
+    });
+
+    // Spawn the background task that keeps instrument definitions current
+    let instruments_clone = ctx.instruments.clone();
+    tokio::spawn(async move {
+        follow_reference_data(instruments_clone).await;
+    });
+
+    // Spawn the background task that follows venue connectivity
+    let venues_clone = ctx.venues.clone();
+    tokio::spawn(async move {
+        listen_for_venue_connectivity(venues_clone).await;
+    });
+
+    // Spawn the heartbeat watchdog and its feed
+    let watchdog: SharedWatchdog = Arc::new(std::sync::Mutex::new(Watchdog::new(WatchdogConfig::from_env())));
+    let (heartbeat_watchdog, trip_watchdog, con_clone) = (watchdog.clone(), watchdog.clone(), con.clone());
+    let instruments_clone = ctx.instruments.clone();
+    tokio::spawn(async move {
+        listen_for_heartbeats(heartbeat_watchdog).await;
+    });
+    tokio::spawn(async move {
+        run_watchdog(trip_watchdog, con_clone, instruments_clone).await;
+    });
+
+    // Spawn the background task that settles held orders; their verdicts go
+    // out on the shared-memory ring, if it is served
+    let (settled_tx, settled_rx) = tokio::sync::mpsc::unbounded_channel();
+    let ctx_clone = ctx.clone();
+    tokio::spawn(async move {
+        settle_held_orders(ctx_clone, settled_tx).await;
+    });
+
+    if std::env::var("QA_RISK_TRANSPORT").as_deref() == Ok("shm") {
+        let ctx_clone = ctx.clone();
+        tokio::spawn(async move {
+            serve_shm_requests(ctx_clone, settled_rx).await;
+        });
+    }
+
+    // --- Admin API for operators (limits and kill switch) ---
+    // Desk callers reach only their desk's accounts and strategies; the rest is firm-wide.
+    let firm = quantumarb_tenancy::firm_only(desks.clone());
+    let scope = quantumarb_tenancy::scope(desks.clone());
+    let get_limits = warp::path!("limits" / u32)
+        .and(warp::get())
+        .and(with_state(con.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_limits);
+    let set_limits = warp::path!("limits" / u32)
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_limits);
+    let get_kill_switch = warp::path("kill-switch")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_kill_switch);
+    let set_kill_switch = warp::path("kill-switch")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(with_state(notifier))
+        .and_then(handler_set_kill_switch);
+    let get_concentration = warp::path("concentration-limits")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_concentration_limits);
+    let set_concentration = warp::path("concentration-limits")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.concentration_limits.clone()))
+        .and_then(handler_set_concentration_limits);
+    let get_counterparty_limits = warp::path!("counterparty-limits")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_counterparty_limits);
+    let set_counterparty_limit = warp::path!("counterparty-limits" / String)
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_counterparty_limit);
+    let get_flatten_policies = warp::path!("flatten-policies")
+        .and(warp::get())
+        .and(with_state(con.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_flatten_policies);
+    let set_flatten_policy = warp::path!("flatten-policies" / String)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_set_flatten_policy);
+    let get_watchdog = warp::path!("watchdog")
+        .and(warp::get())
+        .and(with_state(watchdog))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_watchdog);
+    let get_venues = warp::path!("venues")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(ctx.venues.clone()))
+        .and_then(handler_get_venues);
+    let get_var_escalation = warp::path!("var-escalation")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.escalation.clone()))
+        .and_then(handler_get_var_escalation);
+    let set_var_escalation = warp::path!("var-escalation")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_var_escalation);
+    let get_limit_schedule = warp::path!("limit-schedule")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.time_limits.clone()))
+        .and_then(handler_get_limit_schedule);
+    let set_limit_schedule = warp::path!("limit-schedule")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_limit_schedule);
+    let get_limit_policy = warp::path!("limit-policy")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and(with_state(policy_status.clone()))
+        .and_then(handler_get_limit_policy);
+    let set_limit_policy = warp::path!("limit-policy")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_limit_policy);
+    let preview_limit_policy = warp::path!("limit-policy" / "preview")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(with_state(policy_status))
+        .and_then(handler_preview_limit_policy);
+    let get_mode = warp::path!("mode").and(warp::get()).and(firm.clone()).map(move || warp::reply::json(&mode));
+    let get_redis = warp::path!("redis")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_redis);
+    let get_shards = warp::path!("shards")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(shards.clone()))
+        .and_then(handler_get_shards);
+    let route_account = warp::path!("shards" / "route" / u32)
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(shards))
+        .and_then(handler_route_account);
+    let get_daily_loss = warp::path!("daily-loss" / u32)
+        .and(warp::get())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.account_pnl.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_daily_loss);
+    let reset_daily_loss = warp::path!("daily-loss" / u32 / "reset")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.account_pnl.clone()))
+        .and_then(handler_reset_daily_loss);
+    let get_capital = warp::path!("capital")
+        .and(warp::get())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.capital.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_capital);
+    let set_capital = warp::path!("capital" / String)
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.capital.clone()))
+        .and_then(handler_set_capital);
+    let reallocate_capital = warp::path!("capital" / "reallocate")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(with_state(ctx.capital.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_reallocate_capital);
+    let get_stress_config = warp::path!("stress" / "config")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_stress_config);
+    let set_stress_config = warp::path!("stress" / "config")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_stress_config);
+    let get_approvals = warp::path!("approvals")
+        .and(warp::get())
+        .and(with_state(con.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_approvals);
+    let get_approval_policy = warp::path!("approvals" / "policy")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(con.clone()))
+        .and_then(handler_get_approval_policy);
+    let set_approval_policy = warp::path!("approvals" / "policy")
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and_then(handler_set_approval_policy);
+    let decide_held_order = warp::path!("approvals" / Uuid)
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(con.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_decide_held_order);
+    let get_greeks = warp::path!("greeks" / u32)
+        .and(warp::get())
+        .and(with_state(ctx.clone()))
+        .and(scope.clone())
+        .and(with_state(desks.clone()))
+        .and_then(handler_get_greeks);
+    let snapshots = SnapshotContext {
+        con: con.clone(),
+        config: SnapshotConfig::from_env(),
+        http_client: quantumarb_tenancy::firm_client(),
+        busy: Arc::new(tokio::sync::Mutex::new(())),
+    };
+    let list_snapshots = warp::path!("snapshots")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(snapshots.clone()))
+        .and_then(handler_list_snapshots);
+    let create_snapshot = warp::path!("snapshots")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(snapshots.clone()))
+        .and_then(handler_create_snapshot);
+    let get_snapshot = warp::path!("snapshots" / String)
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(snapshots.clone()))
+        .and_then(handler_get_snapshot);
+    let put_snapshot = warp::path!("snapshots" / String)
+        .and(warp::put())
+        .and(firm.clone())
+        .and(warp::body::json())
+        .and(with_state(snapshots.clone()))
+        .and_then(handler_put_snapshot);
+    let restore_snapshot = warp::path!("snapshots" / String / "restore")
+        .and(warp::post())
+        .and(firm.clone())
+        .and(with_state(snapshots))
+        .and_then(handler_restore_snapshot);
+    let routes = get_limits
+        .or(set_limits)
+        .or(get_kill_switch)
+        .or(set_kill_switch)
+        .or(get_concentration)
+        .or(set_concentration)
+        .or(get_counterparty_limits)
+        .or(set_counterparty_limit)
+        .or(list_snapshots)
+        .or(create_snapshot)
+        .or(get_snapshot)
+        .or(put_snapshot)
+        .or(restore_snapshot)
+        .or(get_flatten_policies)
+        .or(set_flatten_policy)
+        .or(get_watchdog)
+        .or(get_venues)
+        .or(get_var_escalation)
+        .or(set_var_escalation)
+        .or(get_limit_schedule)
+        .or(set_limit_schedule)
+        .or(get_limit_policy)
+        .or(set_limit_policy)
+        .or(preview_limit_policy)
+        .or(get_mode)
+        .or(get_redis)
+        .or(get_shards)
+        .or(route_account)
+        .or(get_daily_loss)
+        .or(reset_daily_loss)
+        .or(get_capital)
+        .or(reallocate_capital)
+        .or(set_capital)
+        .or(get_greeks)
+        .or(get_stress_config)
+        .or(set_stress_config)
+        .or(get_approvals)
+        .or(get_approval_policy)
+        .or(set_approval_policy)
+        .or(decide_held_order)
+        .or(quantumarb_openapi::routes(api_doc()))
+        .recover(quantumarb_tenancy::recover);
+
+    println!(
+        "Admin API running at http://127.0.0.1:3034 \
+         (/limits, /kill-switch, /concentration-limits, /counterparty-limits, /snapshots, /flatten-policies, \
+         /watchdog, /venues, /var-escalation, /limit-schedule, /limit-policy, /mode, /redis, /shards, \
+         /daily-loss, /capital, /greeks, /stress, /approvals)"
+    );
+    tokio::spawn(warp::serve(routes).run(([127, 0, 0, 1], 3034)));
+
+    // This part would listen for incoming order requests
+    let mut rng = seed.stream("risk_gateway.orders");
+    let mut interval = time::interval(Duration::from_secs(2));
+    let mut tick: u64 = 0;
+    loop {
+        interval.tick().await;
+        tick += 1;
+        if tick.is_multiple_of(5) {
+            // Every fifth request is a cross-venue BTC arbitrage package.
+            let quantity = rng.gen_range(1..=150);
+            let leg = |side, price, venue_id| OrderLeg { instrument_id: 1, side, ratio: 1, price, venue_id };
+            let package = MultiLegOrder {
+                package_id: quantumarb_sim::uuid(&mut rng),
+                account_id: 101,
+                quantity,
+                legs: vec![leg(OrderSide::Buy, 60150_00, 1), leg(OrderSide::Sell, 60162_50, 2)],
+                stamps: HopStamps::default(),
+                mode: ctx.mode.mode,
+                priority: OrderPriority::Arbitrage,
+                strategy_id: SIM_PACKAGE_STRATEGY.to_string(),
+            };
+            println!("\nReceived Multi-Leg Order: {} legs, Quantity {}", package.legs.len(), quantity);
+            let decision = check_package(&ctx, &package).await;
+            println!("  -> Risk Decision: {:?}", decision);
+            audit_decision(&ctx, CheckedRequest::Package(package), &decision);
+            continue;
+        }
+        let order_request = OrderRequest {
+            order_id: quantumarb_sim::uuid(&mut rng),
+            account_id: 101,
+            instrument_id: 1,
+            side: OrderSide::Buy,
+            price: 60150_00,
+            size: rng.gen_range(1..=150),
+            stamps: HopStamps::default(),
+            venue_id: 1,
+            mode: ctx.mode.mode,
+            priority: if rng.gen_bool(0.2) { OrderPriority::Hedge } else { OrderPriority::Opportunistic },
+            strategy_id: SIM_ORDER_STRATEGY.to_string(),
+        };
+        println!("\nReceived Order Request: Size {} ({})", order_request.size, order_request.priority);
+        let decision = check_pre_trade_risk(&ctx, &order_request).await;
+        println!("  -> Risk Decision: {:?}", decision);
+        audit_decision(&ctx, CheckedRequest::Order(order_request), &decision);
+    }
+}
+
+/// The admin API's OpenAPI document.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Risk Gateway", "Limits, kill switch, approvals and snapshots of the pre-trade risk gateway.")
+        .errors::<ErrorBody>()
+        .operation(
+            Operation::get("/limits/{account_id}", "An account's limits and state")
+                .path_param::<u32>("account_id")
+                .response::<AccountState>(),
+        )
+        .operation(
+            Operation::put("/limits/{account_id}", "Update an account's baseline limits")
+                .path_param::<u32>("account_id")
+                .body::<LimitUpdate>()
+                .response::<AccountState>(),
+        )
+        .get::<KillSwitchState>("/kill-switch", "The kill switch")
+        .post::<KillSwitchRequest, KillSwitchState>("/kill-switch", "Engage or release the kill switch")
+        .get::<ConcentrationLimits>("/concentration-limits", "The concentration caps")
+        .put::<ConcentrationLimitsUpdate, ConcentrationLimits>("/concentration-limits", "Update the concentration caps")
+        .get::<CounterpartyLimits>("/counterparty-limits", "Counterparty exposure limits")
+        .put::<CounterpartyLimitUpdate, CounterpartyLimits>(
+            "/counterparty-limits/{counterparty}",
+            "Set a counterparty's limit",
+        )
+        .get::<FlattenPolicies>("/flatten-policies", "The watchdog's flatten policy per strategy")
+        .put::<StrategyPolicy, FlattenPolicies>("/flatten-policies/{strategy}", "Set a strategy's flatten policy")
+        .get::<Vec<watchdog::SourceStatus>>("/watchdog", "Watched sources and their last heartbeat")
+        .get::<Vec<VenueConnectivity>>("/venues", "The last connectivity event of every venue")
+        .get::<VaREscalationView>("/var-escalation", "The VaR escalation policy and where VaR stands on it")
+        .put::<EscalationPolicy, EscalationPolicy>("/var-escalation", "Set the VaR escalation policy")
+        .get::<LimitScheduleView>("/limit-schedule", "The intraday limit schedule and the windows open now")
+        .put::<LimitSchedule, LimitSchedule>("/limit-schedule", "Set the intraday limit schedule")
+        .get::<LimitPolicyView>("/limit-policy", "The limit policy and its last evaluation")
+        .put::<LimitPolicy, LimitPolicy>("/limit-policy", "Set the limit policy")
+        .post::<PolicyPreviewRequest, PolicyPreview>("/limit-policy/preview", "Preview a limit policy's limits")
+        .get::<ModeConfig>("/mode", "The trading mode")
+        .get::<store::StoreStatus>("/redis", "The Redis topology and whether checks run degraded")
+        .get::<shards::ShardStatus>("/shards", "This instance and the shard map")
+        .operation(
+            Operation::get("/shards/route/{account_id}", "The instance that checks an account's orders")
+                .path_param::<u32>("account_id")
+                .response::<serde_json::Value>(),
+        )
+        .operation(
+            Operation::get("/daily-loss/{account_id}", "An account's daily loss and lockout")
+                .path_param::<u32>("account_id")
+                .response::<DailyLossStatus>(),
+        )
+        .operation(
+            Operation::post("/daily-loss/{account_id}/reset", "Release an account's daily loss lockout")
+                .path_param::<u32>("account_id")
+                .response::<DailyLossStatus>(),
+        )
+        .get::<capital::CapitalStatus>("/capital", "Capital allocated to and used by each strategy")
+        .put::<AllocationUpdate, capital::CapitalStatus>("/capital/{strategy_id}", "Set a strategy's budget")
+        .post::<Reallocation, capital::CapitalStatus>("/capital/reallocate", "Move budget between strategies")
+        .get::<StressConfig>("/stress/config", "The stress scenarios and loss limit")
+        .put::<StressConfig, StressConfig>("/stress/config", "Set the stress scenarios and loss limit")
+        .get::<ApprovalQueue>("/approvals", "Every held order and its state")
+        .get::<ApprovalPolicy>("/approvals/policy", "The checks whose failures are held for approval")
+        .put::<ApprovalPolicy, ApprovalPolicy>("/approvals/policy", "Set the approval policy")
+        .operation(
+            Operation::post("/approvals/{order_id}", "Approve or reject a held order")
+                .path_param::<Uuid>("order_id")
+                .body::<Decision>()
+                .response::<approvals::HeldOrder>(),
+        )
+        .operation(
+            Operation::get("/greeks/{account_id}", "An account's option greeks and limits")
+                .path_param::<u32>("account_id")
+                .response::<GreeksStatus>(),
+        )
+        .get::<Vec<snapshot::SnapshotSummary>>("/snapshots", "Every snapshot archive, newest first")
+        .post::<CreateSnapshotRequest, snapshot::SnapshotSummary>("/snapshots", "Capture a platform snapshot")
+        .get::<PlatformSnapshot>("/snapshots/{snapshot_id}", "A snapshot archive")
+        .put::<PlatformSnapshot, snapshot::SnapshotSummary>("/snapshots/{snapshot_id}", "Import a snapshot archive")
+        .operation(
+            Operation::post("/snapshots/{snapshot_id}/restore", "Restore the platform from a snapshot")
+                .response::<RestoreReport>(),
+        )
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(
+    state: T,
+) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Builds a standard `{ "error": { code, category, message } }` response.
+fn error_reply(rejection: Rejection) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
+}
+
+/// Handler for GET /limits/{account_id}.
+async fn handler_get_limits(
+    account_id: u32,
+    con_arc: SharedConnection,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !scope.sees_account(&desks, account_id) {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    }
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(format!("account:{}", account_id)).await {
+        Ok(state_json) => {
+            let state: AccountState = serde_json::from_str(&state_json).unwrap();
+            Ok(warp::reply::with_status(warp::reply::json(&state), warp::http::StatusCode::OK))
+        }
+        Err(_) => Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found"))),
+    }
+}
+
+/// Handler for PUT /limits/{account_id}. Updates the baseline limits; the
+/// current limits follow immediately and are re-derived on the next VaR cycle.
+async fn handler_set_limits(
+    account_id: u32,
+    update: LimitUpdate,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = update_account(&con_arc, account_id, |state| {
+        if let Some(max_order_size) = update.max_order_size {
+            state.base_max_order_size = max_order_size;
+            state.current_max_order_size = max_order_size;
+        }
+        if let Some(max_exposure) = update.max_exposure {
+            state.base_max_exposure = max_exposure;
+            state.current_max_exposure = max_exposure;
+        }
+        if let Some(max_daily_loss) = update.max_daily_loss {
+            state.max_daily_loss = Some(max_daily_loss);
+        }
+        if let Some(max_vega) = update.max_vega {
+            state.max_vega = Some(max_vega);
+        }
+        if let Some(max_gamma) = update.max_gamma {
+            state.max_gamma = Some(max_gamma);
+        }
+        if let Some(max_stress_loss) = update.max_stress_loss {
+            state.max_stress_loss = Some(max_stress_loss);
+        }
+        Ok(())
+    })
+    .await;
+    let state = match result {
+        Ok(state) => state,
+        Err(rejection) => return Ok(error_reply(rejection)),
+    };
+    println!("  -> ADMIN: Limits for account {} set to size {} / exposure {:.2}", account_id, state.base_max_order_size, state.base_max_exposure);
+    Ok(warp::reply::with_status(warp::reply::json(&state), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /daily-loss/{account_id}: the limit, any lockout and the
+/// latest session P&L.
+async fn handler_get_daily_loss(
+    account_id: u32,
+    con_arc: SharedConnection,
+    account_pnl: SharedAccountPnl,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !scope.sees_account(&desks, account_id) {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    }
+    let state_json = con_arc.lock().await.get::<_, Option<String>>(format!("account:{}", account_id)).await;
+    let Ok(Some(state_json)) = state_json else {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    };
+    let state: AccountState = serde_json::from_str(&state_json).unwrap();
+    let status = DailyLossStatus {
+        account_id,
+        max_daily_loss: state.max_daily_loss,
+        lockout: state.daily_loss_lockout,
+        pnl: account_pnl.read().unwrap().get(&account_id).cloned(),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK))
+}
+
+/// Handler for POST /daily-loss/{account_id}/reset: releases a lockout for
+/// the rest of the session.
+async fn handler_reset_daily_loss(
+    account_id: u32,
+    con_arc: SharedConnection,
+    account_pnl: SharedAccountPnl,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let session = account_pnl.read().unwrap().get(&account_id).map(|pnl| pnl.session_start_utc);
+    let result = update_account(&con_arc, account_id, |state| {
+        if let Some(lockout) = state.daily_loss_lockout.take() {
+            state.daily_loss_released_session = Some(lockout.session_start_utc);
+        } else if session.is_some() {
+            state.daily_loss_released_session = session;
+        }
+        Ok(())
+    })
+    .await;
+    let state = match result {
+        Ok(state) => state,
+        Err(rejection) => return Ok(error_reply(rejection)),
+    };
+    println!("  -> ADMIN: Daily loss lockout of account {} released for the session.", account_id);
+    let status = DailyLossStatus {
+        account_id,
+        max_daily_loss: state.max_daily_loss,
+        lockout: None,
+        pnl: account_pnl.read().unwrap().get(&account_id).cloned(),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /capital: every strategy's budget and what it uses (a
+/// desk's own strategies, for a desk caller).
+async fn handler_get_capital(
+    con_arc: SharedConnection,
+    capital: SharedCapital,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let allocations = load_capital_allocations(&con_arc).await;
+    let mut status = capital.read().unwrap().report(&allocations);
+    status.strategies.retain(|strategy| scope.sees_strategy(&desks, &strategy.strategy_id));
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for PUT /capital/{strategy_id}: sets the strategy's budget.
+async fn handler_set_capital(
+    strategy_id: String,
+    update: AllocationUpdate,
+    con_arc: SharedConnection,
+    capital: SharedCapital,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if update.budget < Money::ZERO {
+        let rejection = Rejection::new(RejectCode::SystemInvalidRequest, "Budget must not be negative");
+        return Ok(error_reply(rejection));
+    }
+    let allocations = {
+        let mut con = con_arc.lock().await;
+        let mut allocations = read_capital_allocations(&mut con).await;
+        let allocation = capital::Allocation { budget: update.budget, updated_utc: chrono::Utc::now() };
+        allocations.insert(strategy_id.clone(), allocation);
+        let _: () = con.set(CAPITAL_ALLOCATIONS_KEY, serde_json::to_string(&allocations).unwrap()).await.unwrap();
+        allocations
+    };
+    println!("  -> ADMIN: Capital allocation of strategy {} set to {:.2}", strategy_id, update.budget);
+    let status = capital.read().unwrap().report(&allocations);
+    Ok(warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK))
+}
+
+/// Handler for POST /capital/reallocate: moves budget between strategies;
+/// a desk moves it only between its own.
+async fn handler_reallocate_capital(
+    request: Reallocation,
+    con_arc: SharedConnection,
+    capital: SharedCapital,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Some(strategy) = [&request.from, &request.to].into_iter().find(|s| !scope.sees_strategy(&desks, s)) {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            format!("No strategy '{}' on this desk", strategy),
+        )));
+    }
+    let allocations = {
+        let mut con = con_arc.lock().await;
+        let mut allocations = read_capital_allocations(&mut con).await;
+        if let Err(rejection) = capital::reallocate(&mut allocations, &request) {
+            return Ok(error_reply(rejection));
+        }
+        let _: () = con.set(CAPITAL_ALLOCATIONS_KEY, serde_json::to_string(&allocations).unwrap()).await.unwrap();
+        allocations
+    };
+    println!("  -> ADMIN: Reallocated {:.2} of capital from {} to {}", request.amount, request.from, request.to);
+    let mut status = capital.read().unwrap().report(&allocations);
+    status.strategies.retain(|strategy| scope.sees_strategy(&desks, &strategy.strategy_id));
+    Ok(warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK))
+}
+
+/// Reads the capital allocations from Redis. A missing key means no budgets.
+async fn load_capital_allocations(con_arc: &SharedConnection) -> CapitalAllocations {
+    read_capital_allocations(&mut *con_arc.lock().await).await
+}
+
+async fn read_capital_allocations(con: &mut RedisStore) -> CapitalAllocations {
+    match con.get::<_, Option<String>>(CAPITAL_ALLOCATIONS_KEY).await {
+        Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
+        _ => CapitalAllocations::new(),
+    }
+}
+
+/// Handler for GET /stress/config.
+async fn handler_get_stress_config(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let config = match con_arc.lock().await.get::<_, Option<String>>(STRESS_CONFIG_KEY).await {
+        Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
+        _ => StressConfig::default(),
+    };
+    Ok(warp::reply::json(&config))
+}
+
+/// Handler for PUT /stress/config: replaces the stress settings and scenarios.
+async fn handler_set_stress_config(
+    config: StressConfig,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let implausible = config.scenarios.values().flatten().find(|scenario| scenario.price_move <= -1.0);
+    if config.min_order_notional < Money::ZERO || implausible.is_some() {
+        let rejection = Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            "Minimum order notional must not be negative, and no price may fall 100% or more",
+        );
+        return Ok(error_reply(rejection));
+    }
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(STRESS_CONFIG_KEY, serde_json::to_string(&config).unwrap()).await.unwrap();
+    println!("  -> ADMIN: Stress config set ({} asset classes, {:?})", config.scenarios.len(), config.action);
+    Ok(warp::reply::with_status(warp::reply::json(&config), warp::http::StatusCode::OK))
+}
+
+async fn read_approval_policy(con: &mut RedisStore) -> ApprovalPolicy {
+    match con.get::<_, Option<String>>(APPROVAL_POLICY_KEY).await {
+        Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
+        _ => ApprovalPolicy::default(),
+    }
+}
+
+/// The approval queue and whether it is stored yet. A queue that cannot be
+/// read is an error, never an empty queue a write would then overwrite.
+async fn read_approval_queue(con: &mut RedisStore) -> Result<(ApprovalQueue, bool), Rejection> {
+    let unavailable = |e: String| Rejection::new(RejectCode::SystemStateUnavailable, e);
+    match con.get::<_, Option<String>>(APPROVAL_QUEUE_KEY).await.map_err(|e| unavailable(e.to_string()))? {
+        Some(json) => serde_json::from_str(&json)
+            .map(|queue| (queue, true))
+            .map_err(|e| unavailable(format!("Unreadable approval queue: {}", e))),
+        None => Ok((ApprovalQueue::default(), false)),
+    }
+}
+
+/// Reads the approval queue, applies `change` and writes it back if no other
+/// instance wrote it in between; otherwise `change` is applied again to the
+/// newer queue. `change` may reject, and then nothing is written.
+async fn update_approval_queue<T, F>(con: &mut RedisStore, mut change: F) -> Result<T, Rejection>
+where
+    F: FnMut(&mut ApprovalQueue) -> Result<T, Rejection>,
+{
+    let unavailable = |e: redis::RedisError| Rejection::new(RejectCode::SystemStateUnavailable, e.to_string());
+    for _ in 0..ACCOUNT_WRITE_ATTEMPTS {
+        let (mut queue, stored) = read_approval_queue(con).await?;
+        let read_version = queue.version;
+        let changed = change(&mut queue)?;
+        queue.version = read_version + 1;
+        let json = serde_json::to_string(&queue).unwrap();
+        let written = match stored {
+            true => con.set_if_version(APPROVAL_QUEUE_KEY, read_version, &json).await.map_err(unavailable)?,
+            false => con.set_nx(APPROVAL_QUEUE_KEY, &json).await.map_err(unavailable)?,
+        };
+        if written {
+            return Ok(changed);
+        }
+    }
+    Err(Rejection::new(
+        RejectCode::SystemConflict,
+        format!("The approval queue kept changing under {} write attempts", ACCOUNT_WRITE_ATTEMPTS),
+    ))
+}
+
+/// Handler for GET /approvals: every held order (of a desk's accounts, for
+/// a desk caller) and its state.
+async fn handler_get_approvals(
+    con_arc: SharedConnection,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut queue = match read_approval_queue(&mut *con_arc.lock().await).await {
+        Ok((queue, _)) => queue,
+        Err(rejection) => return Ok(error_reply(rejection)),
+    };
+    queue.orders.retain(|held| scope.sees_account(&desks, held.order.account_id));
+    Ok(warp::reply::with_status(warp::reply::json(&queue), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /approvals/policy.
+async fn handler_get_approval_policy(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let policy = read_approval_policy(&mut *con_arc.lock().await).await;
+    Ok(warp::reply::json(&policy))
+}
+
+/// Handler for PUT /approvals/policy: which rejections are held, and for how long.
+async fn handler_set_approval_policy(
+    policy: ApprovalPolicy,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(rejection) = policy.validate() {
+        return Ok(error_reply(rejection));
+    }
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(APPROVAL_POLICY_KEY, serde_json::to_string(&policy).unwrap()).await.unwrap();
+    println!("  -> ADMIN: Approval policy set: hold {:?} for {}s", policy.hold_codes, policy.timeout_secs);
+    Ok(warp::reply::with_status(warp::reply::json(&policy), warp::http::StatusCode::OK))
+}
+
+/// Handler for POST /approvals/{order_id}: a supervisor approves or rejects
+/// a held order. The instance that holds it sends the verdict.
+async fn handler_decide_held_order(
+    order_id: Uuid,
+    decision: Decision,
+    con_arc: SharedConnection,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let (supervisor, approve) = (decision.supervisor.clone(), decision.approve);
+    let mut con = con_arc.lock().await;
+    let decided = update_approval_queue(&mut con, |queue| {
+        // Another desk's order is answered as if it were not held at all.
+        let held = queue.orders.iter().find(|held| held.order.order_id == order_id);
+        if held.is_some_and(|held| !scope.sees_account(&desks, held.order.account_id)) {
+            return Err(Rejection::new(
+                RejectCode::SystemInvalidRequest,
+                format!("No order {} is waiting for approval", order_id),
+            ));
+        }
+        queue.decide(order_id, decision.clone(), chrono::Utc::now())
+    })
+    .await;
+    let held = match decided {
+        Ok(held) => held,
+        Err(rejection) => return Ok(error_reply(rejection)),
+    };
+    let verb = if approve { "approved" } else { "rejected" };
+    println!("  -> ADMIN: {} {} held order {} ({})", supervisor, verb, order_id, held.rejection.code);
+    Ok(warp::reply::with_status(warp::reply::json(&held), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /greeks/{account_id}: the account's option greeks and limits.
+async fn handler_get_greeks(
+    account_id: u32,
+    ctx: RiskContext,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !scope.sees_account(&desks, account_id) {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    }
+    let state_json = ctx.con.lock().await.get::<_, Option<String>>(format!("account:{}", account_id)).await;
+    let Ok(Some(state_json)) = state_json else {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    };
+    let state: AccountState = serde_json::from_str(&state_json).unwrap();
+    let market = ctx.options.read().unwrap().clone();
+    let exposure = match ctx.account_pnl.read().unwrap().get(&account_id) {
+        Some(pnl) => market.book_exposure(&ctx.instruments.read().unwrap(), &pnl.positions, chrono::Utc::now()),
+        None => GreekExposure::default(),
+    };
+    let status = GreeksStatus {
+        account_id,
+        limits: GreeksLimits { max_vega: state.max_vega, max_gamma: state.max_gamma },
+        exposure,
+        spots: market.spots,
+        vols: market.vols,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /shards: this instance, whether it coordinates, and the map.
+async fn handler_get_shards(shards: SharedShards) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = shards.read().unwrap().status();
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /shards/route/{account_id}: the instance that checks the
+/// account's orders.
+async fn handler_route_account(account_id: u32, shards: SharedShards) -> Result<impl warp::Reply, warp::Rejection> {
+    let shards = shards.read().unwrap();
+    match shards.owner(account_id) {
+        Ok(owner) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "account_id": account_id, "owner": owner })),
+            warp::http::StatusCode::OK,
+        )),
+        Err(rejection) => Ok(error_reply(rejection)),
+    }
+}
+
+/// Handler for GET /redis: the topology and whether the checks run degraded.
+async fn handler_get_redis(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = con_arc.lock().await.status();
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /kill-switch.
+async fn handler_get_kill_switch(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = load_kill_switch(&con_arc).await;
+    Ok(warp::reply::json(&state))
+}
+
+/// Handler for POST /kill-switch.
+async fn handler_set_kill_switch(
+    request: KillSwitchRequest,
+    con_arc: SharedConnection,
+    notifier: Notifier,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = KillSwitchState {
+        engaged: request.engaged,
+        reason: request.reason.unwrap_or_default(),
+        updated_utc: chrono::Utc::now().to_rfc3339(),
+    };
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(KILL_SWITCH_KEY, serde_json::to_string(&state).unwrap()).await.unwrap();
+    if state.engaged {
+        println!("  -> ADMIN: KILL SWITCH ENGAGED ({})", state.reason);
+        notifier.notify(notifier.event(EventClass::KillSwitchEngaged).field("reason", &state.reason));
+    } else {
+        println!("  -> ADMIN: Kill switch released.");
+    }
+    Ok(warp::reply::json(&state))
+}
+
+/// Reads the kill switch from Redis. A missing key means trading is allowed.
+async fn load_kill_switch(con_arc: &SharedConnection) -> KillSwitchState {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(KILL_SWITCH_KEY).await {
+        Ok(state_json) => serde_json::from_str(&state_json).unwrap(),
+        Err(_) => KillSwitchState {
+            engaged: false,
+            reason: String::new(),
+            updated_utc: chrono::Utc::now().to_rfc3339(),
+        },
+    }
+}
+
+/// Writes the kill switch to Redis.
+async fn store_kill_switch(con_arc: &SharedConnection, state: &KillSwitchState) {
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(KILL_SWITCH_KEY, serde_json::to_string(state).unwrap()).await.unwrap();
+}
+
+/// 404 for an unknown snapshot id.
+fn snapshot_not_found(snapshot_id: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    let rejection = Rejection::new(RejectCode::SystemInvalidRequest, format!("Unknown snapshot {}", snapshot_id));
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), warp::http::StatusCode::NOT_FOUND)
+}
+
+/// Handler for GET /snapshots: every archive, newest first.
+async fn handler_list_snapshots(ctx: SnapshotContext) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&snapshot::list(&ctx.config)))
+}
+
+/// Handler for POST /snapshots. Halts trading with the kill switch while the
+/// sections are captured, then puts the switch back as it was. Nothing is
+/// archived unless every section was captured.
+async fn handler_create_snapshot(
+    request: CreateSnapshotRequest,
+    ctx: SnapshotContext,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let _busy = ctx.busy.lock().await;
+    let snapshot_id = snapshot::new_snapshot_id();
+    if snapshot::exists(&ctx.config, &snapshot_id) {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemConflict,
+            format!("Snapshot {} already exists; retry in a second.", snapshot_id),
+        )));
+    }
+
+    let prior_kill_switch: Option<String> = ctx.con.lock().await.get(KILL_SWITCH_KEY).await.unwrap_or(None);
+    let halt_reason = format!("Platform snapshot {}", snapshot_id);
+    println!("  -> ADMIN: Halting trading for platform snapshot {}...", snapshot_id);
+    let halt = KillSwitchState { engaged: true, reason: halt_reason.clone(), updated_utc: chrono::Utc::now().to_rfc3339() };
+    store_kill_switch(&ctx.con, &halt).await;
+    time::sleep(ctx.config.quiesce).await;
+
+    let captured = capture_platform(&ctx, &snapshot_id, request.label, prior_kill_switch.clone()).await;
+
+    // Put the kill switch back, unless an operator changed it in the meantime.
+    if load_kill_switch(&ctx.con).await.reason == halt_reason {
+        let mut con = ctx.con.lock().await;
+        let _: () = match prior_kill_switch {
+            Some(raw) => con.set(KILL_SWITCH_KEY, raw).await.unwrap(),
+            None => con.del(KILL_SWITCH_KEY).await.unwrap(),
+        };
+    }
+
+    let saved = captured.and_then(|platform| snapshot::save(&ctx.config, &platform).map(|()| platform));
+    match saved {
+        Ok(platform) => {
+            println!("  -> ADMIN: Platform snapshot {} archived.", snapshot_id);
+            Ok(warp::reply::with_status(warp::reply::json(&platform.summary()), warp::http::StatusCode::OK))
+        }
+        Err(e) => {
+            println!("  -> ADMIN: Platform snapshot {} failed: {}", snapshot_id, e);
+            Ok(error_reply(Rejection::new(
+                RejectCode::SystemStateUnavailable,
+                format!("Snapshot failed, nothing archived: {}", e),
+            )))
+        }
+    }
+}
+
+/// Reads the three sections of a snapshot while trading is halted.
+async fn capture_platform(
+    ctx: &SnapshotContext,
+    snapshot_id: &str,
+    label: Option<String>,
+    prior_kill_switch: Option<String>,
+) -> Result<PlatformSnapshot, String> {
+    let mut risk = {
+        let mut con = ctx.con.lock().await;
+        snapshot::capture_risk(&mut *con, &SNAPSHOT_KEY_PATTERNS).await?
+    };
+    // Redis now holds the snapshot's own halt; archive the switch as it was.
+    match prior_kill_switch {
+        Some(raw) => risk.redis_keys.insert(KILL_SWITCH_KEY.to_string(), snapshot::redis_value(raw)),
+        None => risk.redis_keys.remove(KILL_SWITCH_KEY),
+    };
+    let portfolio = snapshot::fetch_section(&ctx.http_client, &PORTFOLIO_MANAGER.url("/portfolio/state")).await?;
+    let open_orders = snapshot::fetch_section(&ctx.http_client, &EXCHANGE_GATEWAY.url("/orders/open")).await?;
+    Ok(PlatformSnapshot {
+        format_version: snapshot::FORMAT_VERSION,
+        snapshot_id: snapshot_id.to_string(),
+        label: label.unwrap_or_default(),
+        created_utc: chrono::Utc::now().to_rfc3339(),
+        risk,
+        portfolio,
+        open_orders,
+    })
+}
+
+/// Handler for GET /snapshots/{id}: the whole archive, e.g. to copy it to
+/// another environment.
+async fn handler_get_snapshot(snapshot_id: String, ctx: SnapshotContext) -> Result<impl warp::Reply, warp::Rejection> {
+    if !snapshot::is_valid_id(&snapshot_id) {
+        return Ok(snapshot_not_found(&snapshot_id));
+    }
+    match snapshot::load(&ctx.config, &snapshot_id) {
+        Ok(Some(platform)) => Ok(warp::reply::with_status(warp::reply::json(&platform), warp::http::StatusCode::OK)),
+        Ok(None) => Ok(snapshot_not_found(&snapshot_id)),
+        Err(e) => Ok(error_reply(Rejection::new(RejectCode::SystemStateUnavailable, e))),
+    }
+}
+
+/// Handler for PUT /snapshots/{id}: imports an archive taken elsewhere.
+/// Existing archives are never overwritten.
+async fn handler_put_snapshot(
+    snapshot_id: String,
+    platform: PlatformSnapshot,
+    ctx: SnapshotContext,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let invalid = |message: String| Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, message)));
+    if !snapshot::is_valid_id(&snapshot_id) {
+        return invalid("Snapshot ids are letters, digits, '-' and '_' (at most 64).".to_string());
+    }
+    if platform.snapshot_id != snapshot_id {
+        return invalid(format!("The archive is snapshot {}, not {}.", platform.snapshot_id, snapshot_id));
+    }
+    if platform.format_version != snapshot::FORMAT_VERSION {
+        return invalid(format!(
+            "Archive format {} is not supported (expected {}).",
+            platform.format_version,
+            snapshot::FORMAT_VERSION
+        ));
+    }
+    let _busy = ctx.busy.lock().await;
+    if snapshot::exists(&ctx.config, &snapshot_id) {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemConflict,
+            format!("Snapshot {} already exists.", snapshot_id),
+        )));
+    }
+    match snapshot::save(&ctx.config, &platform) {
+        Ok(()) => {
+            println!("  -> ADMIN: Imported platform snapshot {}.", snapshot_id);
+            Ok(warp::reply::with_status(warp::reply::json(&platform.summary()), warp::http::StatusCode::OK))
+        }
+        Err(e) => Ok(error_reply(Rejection::new(RejectCode::SystemStateUnavailable, e))),
+    }
+}
+
+/// Handler for POST /snapshots/{id}/restore. Engages the kill switch, writes
+/// every section back and leaves trading halted for an operator to review
+/// the restored state and release. Sections are restored independently and
+/// reported one by one.
+async fn handler_restore_snapshot(snapshot_id: String, ctx: SnapshotContext) -> Result<impl warp::Reply, warp::Rejection> {
+    if !snapshot::is_valid_id(&snapshot_id) {
+        return Ok(snapshot_not_found(&snapshot_id));
+    }
+    let _busy = ctx.busy.lock().await;
+    let platform = match snapshot::load(&ctx.config, &snapshot_id) {
+        Ok(Some(platform)) => platform,
+        Ok(None) => return Ok(snapshot_not_found(&snapshot_id)),
+        Err(e) => return Ok(error_reply(Rejection::new(RejectCode::SystemStateUnavailable, e))),
+    };
+    if platform.format_version != snapshot::FORMAT_VERSION {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            format!(
+                "Archive format {} is not supported (expected {}).",
+                platform.format_version,
+                snapshot::FORMAT_VERSION
+            ),
+        )));
+    }
+
+    println!("  -> ADMIN: Restoring platform snapshot {} ({}).", snapshot_id, platform.created_utc);
+    let halt = KillSwitchState {
+        engaged: true,
+        reason: format!("Restored snapshot {}; review and release", snapshot_id),
+        updated_utc: chrono::Utc::now().to_rfc3339(),
+    };
+    store_kill_switch(&ctx.con, &halt).await;
+    time::sleep(ctx.config.quiesce).await;
+
+    let risk = {
+        let mut con = ctx.con.lock().await;
+        snapshot::restore_risk(&mut *con, &platform.risk, &SNAPSHOT_KEY_PATTERNS, KILL_SWITCH_KEY)
+            .await
+            .map(|written| format!("{} keys written", written))
+    };
+    let portfolio_url = PORTFOLIO_MANAGER.url("/portfolio/state");
+    let portfolio = snapshot::put_section(&ctx.http_client, &portfolio_url, &platform.portfolio)
+        .await
+        .map(|()| "book replaced".to_string());
+    let open_orders_url = EXCHANGE_GATEWAY.url("/orders/open");
+    let open_orders = snapshot::put_section(&ctx.http_client, &open_orders_url, &platform.open_orders)
+        .await
+        .map(|()| format!("{} open orders", platform.summary().open_orders));
+
+    let report = RestoreReport {
+        snapshot_id,
+        sections: vec![
+            snapshot::section_result("risk", risk),
+            snapshot::section_result("portfolio", portfolio),
+            snapshot::section_result("open_orders", open_orders),
+        ],
+        kill_switch_engaged: true,
+        restored_utc: chrono::Utc::now().to_rfc3339(),
+    };
+    for section in &report.sections {
+        println!("  -> ADMIN: Restore {}: {} ({})", section.section, if section.restored { "ok" } else { "FAILED" }, section.detail);
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&report), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /concentration-limits.
+async fn handler_get_concentration_limits(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let limits = load_concentration_limits(&con_arc).await;
+    Ok(warp::reply::json(&limits))
+}
+
+/// Handler for PUT /concentration-limits.
+async fn handler_set_concentration_limits(
+    update: ConcentrationLimitsUpdate,
+    con_arc: SharedConnection,
+    checked: SharedConcentrationLimits,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut limits = load_concentration_limits(&con_arc).await;
+    limits.apply(update);
+    let caps = [limits.max_symbol_pct, limits.max_sector_pct, limits.max_venue_pct];
+    if caps.iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            "Concentration caps are fractions between 0 and 1.",
+        )));
+    }
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(CONCENTRATION_LIMITS_KEY, serde_json::to_string(&limits).unwrap()).await.unwrap();
+    *checked.write().unwrap() = limits.clone();
+    println!("  -> ADMIN: Concentration limits set to {:?}", limits);
+    Ok(warp::reply::with_status(warp::reply::json(&limits), warp::http::StatusCode::OK))
+}
+
+/// Reads the concentration caps from Redis, falling back to the defaults.
+async fn load_concentration_limits(con_arc: &SharedConnection) -> ConcentrationLimits {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(CONCENTRATION_LIMITS_KEY).await {
+        Ok(limits_json) => serde_json::from_str(&limits_json).unwrap_or_default(),
+        Err(_) => ConcentrationLimits::default(),
+    }
+}
+
+/// Background task that keeps the concentration caps the pre-trade checks
+/// use in step with Redis. While Redis is down, or holds a value that does
+/// not parse, the last caps read stay in force.
+async fn refresh_concentration_limits(con_arc: SharedConnection, limits: SharedConcentrationLimits) {
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let stored = con_arc.lock().await.get::<_, Option<String>>(CONCENTRATION_LIMITS_KEY).await;
+        match stored.map(|json| json.map(|json| serde_json::from_str::<ConcentrationLimits>(&json))) {
+            Ok(Some(Ok(read))) => *limits.write().unwrap() = read,
+            Ok(None) => *limits.write().unwrap() = ConcentrationLimits::default(),
+            Ok(Some(Err(e))) => println!("  -> Unreadable concentration limits ({}); keeping the last ones.", e),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Handler for GET /counterparty-limits.
+async fn handler_get_counterparty_limits(con_arc: SharedConnection) -> Result<impl warp::Reply, warp::Rejection> {
+    let limits = load_counterparty_limits(&con_arc).await;
+    Ok(warp::reply::json(&limits))
+}
+
+/// Handler for PUT /counterparty-limits/{counterparty}.
+async fn handler_set_counterparty_limit(
+    counterparty: String,
+    update: CounterpartyLimitUpdate,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if update.max_exposure < Money::ZERO {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, "max_exposure must not be negative.")));
+    }
+    let mut limits = load_counterparty_limits(&con_arc).await;
+    limits.insert(counterparty.clone(), update.max_exposure);
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(COUNTERPARTY_LIMITS_KEY, serde_json::to_string(&limits).unwrap()).await.unwrap();
+    println!("  -> ADMIN: Credit limit for {} set to {:.2}", counterparty, update.max_exposure);
+    Ok(warp::reply::with_status(warp::reply::json(&limits), warp::http::StatusCode::OK))
+}
+
+/// Reads the per-counterparty credit limits from Redis.
+async fn load_counterparty_limits(con_arc: &SharedConnection) -> CounterpartyLimits {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(COUNTERPARTY_LIMITS_KEY).await {
+        Ok(limits_json) => serde_json::from_str(&limits_json).unwrap_or_default(),
+        Err(_) => CounterpartyLimits::new(),
+    }
+}
+
+/// Background task that keeps the portfolio and counterparty exposures used
+/// by the concentration and credit checks up to date.
+async fn refresh_portfolio_exposures(
+    exposures: SharedExposures,
+    marks: SharedMarks,
+    counterparty_exposures: SharedCounterpartyExposures,
+) {
+    let http_client = quantumarb_tenancy::firm_client();
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        match http_client.get(PORTFOLIO_MANAGER.url("/portfolio")).send().await {
+            Ok(response) => match response.json::<PortfolioSnapshot>().await {
+                Ok(snapshot) => {
+                    *exposures.write().unwrap() = Some(Exposures::from_snapshot(&snapshot));
+                    *marks.write().unwrap() = snapshot
+                        .positions
+                        .values()
+                        .map(|position| (position.symbol.clone(), position.current_market_price.to_f64()))
+                        .collect();
+                }
+                Err(_) => println!("  -> Error parsing portfolio snapshot."),
+            },
+            Err(_) => println!("  -> Failed to fetch portfolio snapshot; concentration checks use the last one."),
+        }
+        if let Ok(response) = http_client.get(PORTFOLIO_MANAGER.url("/portfolio/counterparties")).send().await {
+            if let Ok(rows) = response.json::<Vec<CounterpartyExposure>>().await {
+                *counterparty_exposures.write().unwrap() =
+                    rows.into_iter().map(|row| (row.counterparty.clone(), row)).collect();
+            }
+        }
+    }
+}
+
+/// Background task that follows each account's session P&L and locks out,
+/// or releases, the accounts this instance owns.
+async fn enforce_daily_loss_limits(ctx: RiskContext, notifier: Notifier) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(quantumarb_bus::topics::ACCOUNT_PNL).await.unwrap();
+    // and, for the accounts of desks, topics::all_desks(topics::ACCOUNT_PNL).
+    let http_client = quantumarb_tenancy::firm_client();
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let report = match http_client.get(PORTFOLIO_MANAGER.url("/portfolio/accounts")).send().await {
+            Ok(response) => match response.json::<Vec<AccountPnl>>().await {
+                Ok(report) => report,
+                Err(_) => {
+                    println!("  -> Error parsing account P&L.");
+                    continue;
+                }
+            },
+            Err(_) => {
+                println!("  -> Failed to fetch account P&L; lockouts stand as they are.");
+                continue;
+            }
+        };
+        *ctx.account_pnl.write().unwrap() = report.iter().map(|pnl| (pnl.account_id, pnl.clone())).collect();
+
+        for pnl in report {
+            if ctx.shards.read().unwrap().check_owner(pnl.account_id).is_err() {
+                continue;
+            }
+            let state_json = ctx.con.lock().await.get::<_, Option<String>>(format!("account:{}", pnl.account_id)).await;
+            let Ok(Some(state_json)) = state_json else {
+                continue;
+            };
+            let state: AccountState = serde_json::from_str(&state_json).unwrap();
+            let transition = daily_loss::evaluate(
+                state.max_daily_loss,
+                state.daily_loss_lockout.as_ref(),
+                state.daily_loss_released_session,
+                &pnl,
+            );
+            let result = match &transition {
+                Transition::Unchanged => continue,
+                Transition::Lock(lockout) => {
+                    update_account(&ctx.con, pnl.account_id, |state| {
+                        state.daily_loss_lockout = Some(lockout.clone());
+                        Ok(())
+                    })
+                    .await
+                }
+                Transition::Release => {
+                    update_account(&ctx.con, pnl.account_id, |state| {
+                        state.daily_loss_lockout = None;
+                        Ok(())
+                    })
+                    .await
+                }
+            };
+            match (transition, result) {
+                (_, Err(rejection)) => {
+                    println!("  -> Daily loss lockout of account {} not updated: {}", pnl.account_id, rejection)
+                }
+                (Transition::Lock(lockout), Ok(_)) => {
+                    println!(
+                        "\nDAILY LOSS LIMIT: account {} lost {:.2} (limit {:.2}); blocking risk-increasing orders.",
+                        pnl.account_id, lockout.daily_pnl, lockout.limit
+                    );
+                    let lockout_notice = notifier
+                        .event(EventClass::DailyLossLockout)
+                        .field("account_id", pnl.account_id)
+                        .field("daily_pnl", format!("{:.2}", lockout.daily_pnl))
+                        .field("limit", format!("{:.2}", lockout.limit))
+                        .field("until", "the next session or an operator reset");
+                    notifier.notify(lockout_notice);
+                }
+                (_, Ok(_)) => println!("\nNew session: daily loss lockout of account {} lifted.", pnl.account_id),
+            }
+        }
+    }
+}
+
+/// Background task that reads what each strategy's positions and working
+/// orders use of its capital allocation.
+async fn track_capital_usage(ctx: RiskContext) {
+    let http_client = quantumarb_tenancy::firm_client();
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let started = std::time::Instant::now();
+        let positions = match http_client.get(PORTFOLIO_MANAGER.url("/portfolio/strategies")).send().await {
+            Ok(response) => response.json::<StrategiesReport>().await.ok(),
+            Err(_) => None,
+        };
+        let open_orders = match http_client.get(EXCHANGE_GATEWAY.url("/orders/open")).send().await {
+            Ok(response) => response.json::<Vec<WorkingOrder>>().await.ok(),
+            Err(_) => None,
+        };
+        let (Some(positions), Some(open_orders)) = (positions, open_orders) else {
+            println!("  -> Failed to read strategy positions or open orders; capital usage stands as it is.");
+            continue;
+        };
+        let open_orders = capital::open_order_notional(&open_orders, &ctx.instruments.read().unwrap());
+        ctx.capital.write().unwrap().refreshed(started, capital::position_notional(positions), open_orders);
+    }
+}
+
+/// Background task that reads the mid of every option underlying from the
+/// feature store.
+async fn refresh_option_underlyings(ctx: RiskContext) {
+    let mut interval = time::interval(Duration::from_secs(2));
+    loop {
+        interval.tick().await;
+        let mut underlyings: Vec<String> = ctx
+            .instruments
+            .read()
+            .unwrap()
+            .all()
+            .into_iter()
+            .filter_map(|definition| definition.option.map(|terms| terms.underlying))
+            .collect();
+        underlyings.sort();
+        underlyings.dedup();
+        for underlying in underlyings {
+            let key = quantumarb_features::latest_key(&underlying);
+            let latest = ctx.con.lock().await.get::<_, Option<String>>(key).await;
+            let mid = match latest {
+                Ok(Some(json)) => serde_json::from_str::<FeatureSnapshot>(&json).ok().and_then(|features| features.mid),
+                _ => None,
+            };
+            if let Some(mid) = mid {
+                ctx.options.write().unwrap().spots.insert(underlying, mid);
+            }
+        }
+    }
+}
+
+/// Handler for GET /flatten-policies.
+async fn handler_get_flatten_policies(
+    con_arc: SharedConnection,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut policies = load_flatten_policies(&con_arc).await;
+    policies.retain(|strategy, _| scope.sees_strategy(&desks, strategy));
+    Ok(warp::reply::json(&policies))
+}
+
+/// Handler for PUT /flatten-policies/{strategy}.
+async fn handler_set_flatten_policy(
+    strategy: String,
+    policy: StrategyPolicy,
+    con_arc: SharedConnection,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !scope.sees_strategy(&desks, &strategy) {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            format!("No strategy '{}' on this desk", strategy),
+        )));
+    }
+    if policy.symbols.is_empty() {
+        return Ok(error_reply(Rejection::new(
+            RejectCode::SystemInvalidRequest,
+            "A flatten policy needs the symbols the strategy trades.",
+        )));
+    }
+    let mut policies = load_flatten_policies(&con_arc).await;
+    println!("  -> ADMIN: Flatten policy for {} set to {:?} on {:?}", strategy, policy.action, policy.symbols);
+    policies.insert(strategy, policy);
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(FLATTEN_POLICIES_KEY, serde_json::to_string(&policies).unwrap()).await.unwrap();
+    policies.retain(|strategy, _| scope.sees_strategy(&desks, strategy));
+    Ok(warp::reply::with_status(warp::reply::json(&policies), warp::http::StatusCode::OK))
+}
+
+/// Reads the flatten policies from Redis, falling back to the defaults.
+async fn load_flatten_policies(con_arc: &SharedConnection) -> FlattenPolicies {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(FLATTEN_POLICIES_KEY).await {
+        Ok(policies_json) => serde_json::from_str(&policies_json).unwrap_or_else(|_| watchdog::default_policies()),
+        Err(_) => watchdog::default_policies(),
+    }
+}
+
+/// Body of a GET /var-escalation response.
+#[derive(Debug, Serialize, ApiSchema)]
+struct VaREscalationView {
+    policy: EscalationPolicy,
+    status: EscalationStatus,
+}
+
+/// Handler for GET /var-escalation: the policy and where VaR stands on it.
+async fn handler_get_var_escalation(
+    con_arc: SharedConnection,
+    escalation: SharedEscalation,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let policy = load_var_escalation_policy(&con_arc).await;
+    let status = escalation.read().unwrap().clone();
+    Ok(warp::reply::json(&VaREscalationView { policy, status }))
+}
+
+/// Handler for PUT /var-escalation. Takes effect at the next VaR fetch.
+async fn handler_set_var_escalation(
+    policy: EscalationPolicy,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reason) = policy.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason)));
+    }
+    println!("  -> ADMIN: VaR escalation policy set: limit {:.2}, {} step(s)", policy.var_limit, policy.steps.len());
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(VAR_ESCALATION_KEY, serde_json::to_string(&policy).unwrap()).await.unwrap();
+    Ok(warp::reply::with_status(warp::reply::json(&policy), warp::http::StatusCode::OK))
+}
+
+/// Reads the VaR escalation policy from Redis, falling back to the default.
+async fn load_var_escalation_policy(con_arc: &SharedConnection) -> EscalationPolicy {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(VAR_ESCALATION_KEY).await {
+        Ok(policy_json) => serde_json::from_str(&policy_json).unwrap_or_default(),
+        Err(_) => EscalationPolicy::default(),
+    }
+}
+
+/// Body of a GET /limit-schedule response.
+#[derive(Serialize, ApiSchema)]
+struct LimitScheduleView {
+    schedule: LimitSchedule,
+    status: ScheduleStatus,
+}
+
+/// Handler for GET /limit-schedule: the schedule and the windows open now.
+async fn handler_get_limit_schedule(
+    con_arc: SharedConnection,
+    time_limits: SharedTimeLimits,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let schedule = load_limit_schedule(&con_arc).await.unwrap_or_default();
+    let status = time_limits.read().unwrap().clone();
+    Ok(warp::reply::json(&LimitScheduleView { schedule, status }))
+}
+
+/// Handler for PUT /limit-schedule. Takes effect at the scheduler's next run.
+async fn handler_set_limit_schedule(
+    schedule: LimitSchedule,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reason) = schedule.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason)));
+    }
+    println!("  -> ADMIN: Limit schedule set: {} symbol profile(s)", schedule.symbols.len());
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(LIMIT_SCHEDULE_KEY, serde_json::to_string(&schedule).unwrap()).await.unwrap();
+    Ok(warp::reply::with_status(warp::reply::json(&schedule), warp::http::StatusCode::OK))
+}
 
-// --- Data Structures ---
+/// Reads the limit schedule from Redis: the default when none is set, None
+/// when Redis cannot be read.
+async fn load_limit_schedule(con_arc: &SharedConnection) -> Option<LimitSchedule> {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, Option<String>>(LIMIT_SCHEDULE_KEY).await {
+        Ok(schedule_json) => Some(schedule_json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()),
+        Err(_) => None,
+    }
+}
 
-#[derive(Debug, Clone)]
-struct OrderRequest {
-    order_id: Uuid,
-    account_id: u32,
-    price: u64,
-    size: u32,
+/// Body of a GET /limit-policy response.
+#[derive(Serialize, ApiSchema)]
+struct LimitPolicyView {
+    policy: LimitPolicy,
+    status: PolicyStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AccountState {
+/// Body of a POST /limit-policy/preview request. Without a policy the one
+/// set is previewed; without inputs, those of the last VaR fetch.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct PolicyPreviewRequest {
+    #[serde(default)]
+    policy: Option<LimitPolicy>,
+    #[serde(default)]
+    inputs: Option<PolicyInputs>,
+    /// The account whose limits are shown; 101 if omitted.
+    #[serde(default)]
+    account_id: Option<u32>,
+}
+
+/// Body of a POST /limit-policy/preview response: the limits the policy
+/// would set, which are not applied.
+#[derive(Debug, Serialize, ApiSchema)]
+struct PolicyPreview {
+    inputs: PolicyInputs,
+    evaluation: Evaluation,
     account_id: u32,
-    base_max_exposure: f64, // The baseline limit
-    current_max_exposure: f64, // The dynamically adjusted limit
-    base_max_order_size: u32,
-    current_max_order_size: u32,
-    current_exposure: f64,
+    max_order_size: u32,
+    max_exposure: Money,
 }
 
-#[derive(Debug, PartialEq)]
-enum RiskDecision {
-    Approved,
-    Rejected(String),
+/// Handler for GET /limit-policy: the policy and its last evaluation.
+async fn handler_get_limit_policy(
+    con_arc: SharedConnection,
+    policy_status: SharedPolicyStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let policy = load_limit_policy(&con_arc).await;
+    let status = policy_status.read().unwrap().clone();
+    Ok(warp::reply::json(&LimitPolicyView { policy, status }))
 }
 
-// Structure for the VaR service response
-#[derive(Debug, Deserialize)]
-struct VaRResult {
-    var_amount: f64,
-    portfolio_value: f64,
+/// Handler for PUT /limit-policy. Takes effect at the next VaR fetch.
+async fn handler_set_limit_policy(
+    policy: LimitPolicy,
+    con_arc: SharedConnection,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reason) = policy.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason)));
+    }
+    println!("  -> ADMIN: Limit policy set: {} rule(s)", policy.rules.len());
+    let mut con = con_arc.lock().await;
+    let _: () = con.set(LIMIT_POLICY_KEY, serde_json::to_string(&policy).unwrap()).await.unwrap();
+    Ok(warp::reply::with_status(warp::reply::json(&policy), warp::http::StatusCode::OK))
 }
 
-const REDIS_URL: &str = "redis://127.0.0.1/";
-const VAR_CALCULATOR_URL: &str = "http://var-calculator.default.svc.cluster.local/var";
+/// Handler for POST /limit-policy/preview: evaluates a policy without
+/// changing any limit.
+async fn handler_preview_limit_policy(
+    request: PolicyPreviewRequest,
+    con_arc: SharedConnection,
+    policy_status: SharedPolicyStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let policy = match request.policy {
+        Some(policy) => policy,
+        None => load_limit_policy(&con_arc).await,
+    };
+    if let Err(reason) = policy.validate() {
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason)));
+    }
+    let Some(inputs) = request.inputs.or_else(|| policy_status.read().unwrap().inputs.clone()) else {
+        let reason = "No VaR has been fetched yet; give the inputs to preview on.";
+        return Ok(error_reply(Rejection::new(RejectCode::SystemInvalidRequest, reason)));
+    };
+    let account_id = request.account_id.unwrap_or(101);
+    let state = {
+        let mut con = con_arc.lock().await;
+        con.get::<_, String>(format!("account:{}", account_id)).await
+    };
+    let Ok(state_json) = state else {
+        return Ok(error_reply(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found")));
+    };
+    let state: AccountState = serde_json::from_str(&state_json).unwrap();
+    let evaluation = policy.evaluate(&inputs);
+    let preview = PolicyPreview {
+        max_order_size: (state.base_max_order_size as f32 * evaluation.factor as f32) as u32,
+        max_exposure: state.base_max_exposure.scaled(evaluation.factor),
+        inputs,
+        evaluation,
+        account_id,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&preview), warp::http::StatusCode::OK))
+}
 
-// --- Main Application Logic ---
+/// Reads the limit policy from Redis, falling back to the default.
+async fn load_limit_policy(con_arc: &SharedConnection) -> LimitPolicy {
+    let mut con = con_arc.lock().await;
+    match con.get::<_, String>(LIMIT_POLICY_KEY).await {
+        Ok(policy_json) => serde_json::from_str(&policy_json).unwrap_or_default(),
+        Err(_) => LimitPolicy::default(),
+    }
+}
 
-#[tokio::main]
-async fn main() {
-    println!("--- Starting QuantumArb 2.0 Dynamic Risk Gateway ---");
+/// The scheduler behind the intraday limit windows: every second, works out
+/// which windows of the schedule are open, from the releases on the economic
+/// calendar. While Redis is down it keeps the last schedule it read.
+async fn run_limit_scheduler(con_arc: SharedConnection, time_limits: SharedTimeLimits, calendar: SharedCalendar) {
+    let mut schedule = LimitSchedule::default();
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if let Some(latest) = load_limit_schedule(&con_arc).await {
+            schedule = latest;
+        }
+        let now = chrono::Utc::now();
+        let releases: Vec<EventWindow> = {
+            let mut calendar = calendar.write().unwrap();
+            calendar.retain(|_, release| release.scheduled_utc + CALENDAR_RETENTION > now);
+            let mut releases: Vec<EventWindow> = calendar.values().cloned().collect();
+            releases.sort_by_key(|release| release.scheduled_utc);
+            releases
+        };
 
-    let client = redis::Client::open(REDIS_URL).expect("Invalid Redis URL");
-    let con = Arc::new(tokio::sync::Mutex::new(
-        client.get_async_connection().await.expect("Failed to connect to Redis"),
-    ));
+        let status = time_limits::evaluate(&schedule, &releases, now);
+        let mut current = time_limits.write().unwrap();
+        for window in status.open_windows.iter().filter(|w| !current.open_windows.contains(w)) {
+            let symbols = if window.symbols.is_empty() { "all symbols".to_string() } else { window.symbols.join(", ") };
+            println!(
+                "\nLIMIT WINDOW '{}' open until {}: limits x{} for {}.",
+                window.reason, window.until_utc, window.factor, symbols
+            );
+        }
+        for window in current.open_windows.iter().filter(|w| !status.open_windows.contains(w)) {
+            println!("\nLIMIT WINDOW '{}' closed.", window.reason);
+        }
+        *current = status;
+    }
+}
 
-    setup_initial_account_state(con.clone()).await;
+/// Follows the economic releases the data bus connector publishes; a release
+/// published again (e.g. rescheduled) replaces the earlier copy.
+async fn listen_for_event_windows(calendar: SharedCalendar) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe(topics::EVENT_WINDOWS).await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    // Simulated: a CPI print seven minutes after each half-hourly poll.
+    let mut interval = time::interval(Duration::from_secs(1800));
+    loop {
+        interval.tick().await;
+        let scheduled = chrono::Utc::now() + chrono::Duration::minutes(7);
+        let payload = serde_json::json!({
+            "event_id": format!("US-CPI-{}", scheduled.format("%Y%m%d%H%M")),
+            "kind": "CPI",
+            "name": "CPI m/m",
+            "scheduled_utc": scheduled,
+            "starts_utc": scheduled - chrono::Duration::minutes(5),
+            "ends_utc": scheduled + chrono::Duration::minutes(30),
+            "symbols": [],
+        });
+        match serde_json::from_value::<EventWindow>(payload) {
+            Ok(release) => {
+                calendar.write().unwrap().insert(release.event_id.clone(), release);
+            }
+            Err(e) => println!("  -> Undecodable event window: {}", e),
+        }
+    }
+}
 
-    // Spawn the background task to adjust limits based on VaR
-    let con_clone = con.clone();
-    tokio::spawn(async move {
-        adjust_limits_from_var(con_clone).await;
+/// Handler for GET /watchdog: every watched source and when it last
+/// heart-beat. A desk caller sees its own strategies and the market data feed.
+async fn handler_get_watchdog(
+    watchdog: SharedWatchdog,
+    scope: Scope,
+    desks: Arc<Desks>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut status = watchdog.lock().unwrap().status(std::time::Instant::now());
+    status.retain(|source| match watchdog::source_strategy(&source.source) {
+        Some(strategy) => scope.sees_strategy(&desks, strategy),
+        None => true,
     });
+    Ok(warp::reply::json(&status))
+}
 
-    // This part would listen for incoming order requests
-    let mut interval = time::interval(Duration::from_secs(2));
+/// Feeds heartbeats into the watchdog.
+async fn listen_for_heartbeats(watchdog: SharedWatchdog) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe("heartbeats").await.unwrap();
+    // and, for the strategies of desks, topics::all_desks(topics::HEARTBEATS).
+    // while let Some(message) = subscriber.next().await { ... }
+    // Simulated: the feed beats every second; the strategy every five seconds,
+    // except for a 30-second outage in every two minutes.
+    let strategy_source = watchdog::strategy_source("sor_arbitrage");
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut tick: u64 = 0;
     loop {
         interval.tick().await;
-        let order_request = OrderRequest { order_id: Uuid::new_v4(), account_id: 101, price: 60150_00, size: (rand::random::<u32>() % 150) + 1 };
-        println!("\nReceived Order Request: Size {}", order_request.size);
-        let decision = check_pre_trade_risk(con.clone(), &order_request).await;
-        println!("  -> Risk Decision: {:?}", decision);
+        tick += 1;
+        let mut sources = vec![watchdog::MARKET_DATA_SOURCE];
+        if tick.is_multiple_of(5) && !(60..90).contains(&(tick % 120)) {
+            sources.push(&strategy_source);
+        }
+        for source in sources {
+            let payload = serde_json::json!({
+                "source": source,
+                "sequence": tick,
+                "sent_utc": chrono::Utc::now().to_rfc3339(),
+            });
+            match serde_json::from_value::<Heartbeat>(payload) {
+                Ok(heartbeat) => {
+                    if watchdog.lock().unwrap().on_heartbeat(&heartbeat, std::time::Instant::now()) {
+                        println!("\nWATCHDOG: {} is heart-beating again (#{}).", heartbeat.source, heartbeat.sequence);
+                    }
+                }
+                Err(e) => println!("  -> Undecodable heartbeat: {}", e),
+            }
+        }
+    }
+}
+
+/// Handler for GET /venues: the last connectivity event of every venue.
+async fn handler_get_venues(venues: SharedVenues) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut events: Vec<VenueConnectivity> = venues.read().unwrap().values().cloned().collect();
+    events.sort_by(|a, b| a.venue.cmp(&b.venue));
+    Ok(warp::reply::json(&events))
+}
+
+/// Keeps the latest connectivity state of each venue.
+async fn listen_for_venue_connectivity(venues: SharedVenues) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe("venues.connectivity").await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    // Simulated: VENUE_B loses its primary session for 20 seconds in every
+    // three minutes, and its backup will not log on either.
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut tick: u64 = 0;
+    loop {
+        interval.tick().await;
+        tick += 1;
+        let (state, session, detail) = match tick % 180 {
+            1 => ("UP", "VENUE_B-PRIMARY", "logged on to VENUE_B-PRIMARY"),
+            120 => ("RECONNECTING", "VENUE_B-PRIMARY", "3 heartbeats unanswered on VENUE_B-PRIMARY"),
+            126 => ("DOWN", "VENUE_B-PRIMARY", "no session would log on; VENUE_B-BACKUP last failed (logon timed out)"),
+            140 => ("UP", "VENUE_B-PRIMARY", "logged on to VENUE_B-PRIMARY"),
+            _ => continue,
+        };
+        let payload = serde_json::json!({
+            "venue": "VENUE_B",
+            "state": state,
+            "session": session,
+            "detail": detail,
+            "timestamp_utc": chrono::Utc::now().to_rfc3339(),
+        });
+        match serde_json::from_value::<VenueConnectivity>(payload) {
+            Ok(event) => {
+                if !event.state.is_routable() {
+                    println!("\nVENUE {} is {:?} ({}): blocking orders routed to it.", event.venue, event.state, event.detail);
+                }
+                venues.write().unwrap().insert(event.venue.clone(), event);
+            }
+            Err(e) => println!("  -> Undecodable connectivity event: {}", e),
+        }
+    }
+}
+
+/// Rejects an order routed to a venue that is reconnecting or down. A venue
+/// never reported on is assumed up.
+fn check_venue_connectivity(venues: &HashMap<String, VenueConnectivity>, order: &OrderRequest) -> Result<(), Rejection> {
+    let Some(event) = concentration::venue_name(order.venue_id).and_then(|venue| venues.get(venue)) else {
+        return Ok(());
+    };
+    if event.state.is_routable() {
+        return Ok(());
+    }
+    Err(Rejection::new(
+        RejectCode::RiskVenueUnavailable,
+        format!("Venue {} is {:?} since {}: {}", event.venue, event.state, event.timestamp_utc, event.detail),
+    ))
+}
+
+/// Once a second, acts on every source that has stopped heart-beating.
+async fn run_watchdog(watchdog: SharedWatchdog, con_arc: SharedConnection, instruments: SharedInstruments) {
+    let http_client = quantumarb_tenancy::firm_client();
+    // Watch every strategy with a policy, and the feed, from start-up.
+    let policies = load_flatten_policies(&con_arc).await;
+    {
+        let now = std::time::Instant::now();
+        let mut watchdog = watchdog.lock().unwrap();
+        watchdog.watch(watchdog::MARKET_DATA_SOURCE, now);
+        for strategy in policies.keys() {
+            watchdog.watch(&watchdog::strategy_source(strategy), now);
+        }
+    }
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let trips = watchdog.lock().unwrap().check(std::time::Instant::now());
+        if trips.is_empty() {
+            continue;
+        }
+        let policies = load_flatten_policies(&con_arc).await;
+        for trip in trips {
+            println!("\nWATCHDOG: No heartbeat from {} for {}ms.", trip.source, trip.silent_for_ms);
+            for (strategy, policy) in watchdog::affected(&trip.source, &policies) {
+                let reason = format!("Watchdog: {} silent for {}ms", trip.source, trip.silent_for_ms);
+                protect_strategy(&http_client, strategy, policy, reason, &instruments).await;
+            }
+        }
+    }
+}
+
+/// Cancels a strategy's open orders and, under a FLATTEN policy, closes its
+/// positions, through the exchange gateway.
+async fn protect_strategy(
+    http_client: &reqwest::Client,
+    strategy: &str,
+    policy: &StrategyPolicy,
+    reason: String,
+    instruments: &SharedInstruments,
+) {
+    println!("  -> {:?} for strategy {} on {:?}", policy.action, strategy, policy.symbols);
+    let cancel = CancelRequest { symbols: policy.symbols.clone(), reason: reason.clone() };
+    match http_client.post(EXCHANGE_GATEWAY.url("/orders/cancel")).json(&cancel).send().await {
+        Ok(response) if response.status().is_success() => println!("  -> Open orders canceled."),
+        Ok(response) => println!("  -> Cancel request failed: HTTP {}", response.status()),
+        Err(e) => println!("  -> Failed to reach the exchange gateway to cancel: {}", e),
+    }
+    if policy.action != watchdog::FlattenAction::Flatten {
+        return;
+    }
+
+    let snapshot = match http_client.get(PORTFOLIO_MANAGER.url("/portfolio")).send().await {
+        Ok(response) => response.json::<PortfolioSnapshot>().await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let orders = match snapshot {
+        Ok(snapshot) => watchdog::flattening_orders(&snapshot, &policy.symbols, &instruments.read().unwrap()),
+        Err(e) => {
+            println!("  -> Cannot flatten {}: no portfolio snapshot ({}).", strategy, e);
+            return;
+        }
+    };
+    if orders.is_empty() {
+        println!("  -> No open positions to flatten.");
+        return;
+    }
+    let flatten = FlattenRequest { orders, reason };
+    match http_client.post(EXCHANGE_GATEWAY.url("/orders/flatten")).json(&flatten).send().await {
+        Ok(response) if response.status().is_success() => {
+            println!("  -> {} flattening order(s) sent.", flatten.orders.len())
+        }
+        Ok(response) => println!("  -> Flatten request failed: HTTP {}", response.status()),
+        Err(e) => println!("  -> Failed to reach the exchange gateway to flatten: {}", e),
+    }
+}
+
+/// Loads every instrument definition from the reference data service, then
+/// keeps them current. Definitions already held with a newer version win.
+async fn follow_reference_data(instruments: SharedInstruments) {
+    // In a real system, changes arrive on the bus between the full reloads:
+    // let mut updates = nats_client.subscribe(quantumarb_refdata::UPDATES_TOPIC).await.unwrap();
+    let http_client = reqwest::Client::new();
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let response = match http_client.get(REFERENCE_DATA.url("/instruments")).send().await {
+            Ok(response) => response,
+            Err(_) => {
+                println!("  -> Failed to fetch instrument definitions; using the last known set.");
+                continue;
+            }
+        };
+        match response.json::<Vec<InstrumentDefinition>>().await {
+            Ok(definitions) => {
+                let mut data = instruments.write().unwrap();
+                for definition in definitions {
+                    data.apply(definition);
+                }
+            }
+            Err(_) => println!("  -> Error parsing instrument definitions."),
+        }
+    }
+}
+
+/// A request taken off the shared-memory ring, waiting for its check.
+enum RingRequest {
+    Order(OrderRequest),
+    Package(MultiLegOrder),
+}
+
+impl RingRequest {
+    /// Single orders and packages share the ring; dispatch on the template.
+    fn decode(buf: &[u8]) -> Result<RingRequest, quantumarb_wire::DecodeError> {
+        let template_id = quantumarb_wire::peek_header(buf).map(|header| header.template_id);
+        if template_id == Ok(MultiLegOrder::TEMPLATE_ID) {
+            quantumarb_wire::decode::<MultiLegOrder>(buf).map(RingRequest::Package)
+        } else {
+            quantumarb_wire::decode::<OrderRequest>(buf).map(RingRequest::Order)
+        }
+    }
+
+    fn priority(&self) -> OrderPriority {
+        match self {
+            RingRequest::Order(order) => order.priority,
+            RingRequest::Package(package) => package.priority,
+        }
+    }
+}
+
+/// Serves pre-trade checks from colocated strategy engines over the shared-
+/// memory rings. The loop busy-polls (yielding to the runtime between polls)
+/// because an idle sleep would add far more latency than the check itself.
+async fn serve_shm_requests(ctx: RiskContext, mut settled: UnboundedReceiver<RiskVerdict>) {
+    let mut requests = ShmConsumer::open(risk_channel::REQUESTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE)
+        .expect("Failed to map risk request ring");
+    let mut verdicts = ShmProducer::open(risk_channel::VERDICTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE)
+        .expect("Failed to map risk verdict ring");
+    println!("Serving pre-trade checks over shared memory ({})", risk_channel::REQUESTS_PATH);
+
+    let mut buf = Vec::with_capacity(risk_channel::SLOT_SIZE);
+    let mut pending: OrderQueue<RingRequest> = OrderQueue::default();
+    loop {
+        // A held order's verdict goes out as soon as it is settled.
+        if let Ok(verdict) = settled.try_recv() {
+            push_verdict(&mut verdicts, verdict).await;
+            continue;
+        }
+        // Take everything waiting on the ring before checking the next
+        // request, so a hedge queued behind a burst goes first.
+        while pending.len() < risk_channel::CAPACITY as usize && requests.try_pop(&mut buf) {
+            match RingRequest::decode(&buf) {
+                Ok(request) => pending.push(request.priority(), request),
+                Err(e) => println!("  -> Dropping undecodable order on risk ring: {}", e),
+            }
+        }
+        let Some(request) = pending.pop() else {
+            tokio::task::yield_now().await;
+            continue;
+        };
+        let (stamps, decision, request) = match request {
+            RingRequest::Package(package) => {
+                (package.stamps, check_package(&ctx, &package).await, CheckedRequest::Package(package))
+            }
+            RingRequest::Order(order) => {
+                (order.stamps, check_pre_trade_risk(&ctx, &order).await, CheckedRequest::Order(order))
+            }
+        };
+
+        let order_id = request.id();
+        audit_decision(&ctx, request, &decision);
+        let mut verdict = decision.verdict(order_id, stamps);
+        verdict.stamps.stamp(Hop::RiskDecision);
+        push_verdict(&mut verdicts, verdict).await;
+    }
+}
+
+async fn push_verdict(ring: &mut ShmProducer, mut verdict: RiskVerdict) {
+    let mut payload = quantumarb_wire::encode(&verdict);
+    loop {
+        match ring.try_push(&payload) {
+            Ok(()) => return,
+            // The strategy engine drains verdicts promptly; spin briefly if it lags.
+            Err(PushError::Full) => tokio::task::yield_now().await,
+            // A long rejection message: cut it, on a char boundary, until the verdict fits a slot.
+            Err(PushError::TooLarge { max }) => {
+                let Some(reject) = verdict.reject.as_mut().filter(|reject| !reject.message.is_empty()) else {
+                    println!("  -> Verdict for order {} does not fit a ring slot; dropped", verdict.order_id);
+                    return;
+                };
+                let mut cut = reject.message.len().saturating_sub(payload.len() - max);
+                while !reject.message.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                println!(
+                    "  -> Verdict for order {} over {} bytes; rejection message cut from {} to {} bytes",
+                    verdict.order_id,
+                    max,
+                    reject.message.len(),
+                    cut
+                );
+                reject.message.truncate(cut);
+                payload = quantumarb_wire::encode(&verdict);
+            }
+        }
+    }
+}
+
+/// Background task that settles the orders this instance held once a
+/// supervisor decides them or their time runs out: an approved order is
+/// checked again with what was approved waived.
+async fn settle_held_orders(ctx: RiskContext, verdicts: UnboundedSender<RiskVerdict>) {
+    let instance = ctx.shards.read().unwrap().instance().id.clone();
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let settled = {
+            let mut con = ctx.con.lock().await;
+            // Only write the queue when there is something to settle.
+            let settled = match read_approval_queue(&mut con).await {
+                Ok((queue, _)) if queue.clone().take_settled(&instance, chrono::Utc::now()).is_empty() => continue,
+                Ok(_) => update_approval_queue(&mut con, |queue| Ok(queue.take_settled(&instance, chrono::Utc::now())))
+                    .await,
+                Err(rejection) => Err(rejection),
+            };
+            match settled {
+                Ok(settled) => settled,
+                Err(rejection) => {
+                    println!("  -> Failed to update the approval queue: {}", rejection.message);
+                    continue;
+                }
+            }
+        };
+        for (order, outcome) in settled {
+            let decision = match outcome {
+                Outcome::Approved { waived } => {
+                    let decision = run_pre_trade_checks(&ctx, &order, &waived).await;
+                    hold_for_approval(&ctx, &order, decision, waived).await
+                }
+                Outcome::Rejected(rejection) => RiskDecision::Rejected(rejection),
+            };
+            println!("  -> Held order {} settled: {:?}", order.order_id, decision);
+            audit_decision(&ctx, CheckedRequest::Order(order.clone()), &decision);
+            let mut verdict = decision.verdict(order.order_id, order.stamps);
+            verdict.stamps.stamp(Hop::RiskDecision);
+            // Without the shared-memory transport nobody listens for it.
+            let _ = verdicts.send(verdict);
+        }
     }
 }
 
+/// Records a decision on 'audit.events', with the request it was made on.
+fn audit_decision(ctx: &RiskContext, request: CheckedRequest, decision: &RiskDecision) {
+    let (outcome, rejection) = match decision {
+        RiskDecision::Approved => (RiskOutcome::Approved, None),
+        RiskDecision::Rejected(rejection) => (RiskOutcome::Rejected, Some(rejection)),
+        RiskDecision::Held(rejection) => (RiskOutcome::Held, Some(rejection)),
+    };
+    let audit = RiskDecisionAudit {
+        outcome,
+        code: rejection.map(|r| r.code),
+        reason: rejection.map(|r| r.message.clone()),
+        checked_by: ctx.shards.read().unwrap().instance().id.clone(),
+        request,
+    };
+    let event = AuditEvent {
+        event_id: Uuid::new_v4(),
+        service_name: "risk-gateway".to_string(),
+        event_type: outcome.event_type().to_string(),
+        timestamp: chrono::Utc::now(),
+        payload: serde_json::to_string(&audit).unwrap(),
+    };
+    quantumarb_bus::publish_json(quantumarb_bus::topics::AUDIT_EVENTS, &event);
+}
+
 /// Sets up an initial account state in Redis.
-async fn setup_initial_account_state(con_arc: Arc<tokio::sync::Mutex<redis::aio::Connection>>) {
+async fn setup_initial_account_state(con_arc: SharedConnection) {
     let mut con = con_arc.lock().await;
     let key = "account:101";
     if !con.exists::<_, bool>(key).await.unwrap_or(false) {
         let state = AccountState {
             account_id: 101,
-            base_max_exposure: 100000.0,
-            current_max_exposure: 100000.0,
+            base_max_exposure: Money::from_f64(100000.0),
+            current_max_exposure: Money::from_f64(100000.0),
             base_max_order_size: 100,
             current_max_order_size: 100,
-            current_exposure: 50000.0,
+            current_exposure: Money::from_f64(50000.0),
+            version: 0,
+            max_daily_loss: Some(Money::from_f64(DEFAULT_MAX_DAILY_LOSS)),
+            daily_loss_lockout: None,
+            daily_loss_released_session: None,
+            max_vega: Some(Money::from_f64(DEFAULT_MAX_VEGA)),
+            max_gamma: Some(Money::from_f64(DEFAULT_MAX_GAMMA)),
+            max_stress_loss: Some(Money::from_f64(DEFAULT_MAX_STRESS_LOSS)),
         };
         let _: () = con.set(key, serde_json::to_string(&state).unwrap()).await.unwrap();
         println!("Initialized account 101 in Redis.");
     }
 }
 
-/// Background task that fetches VaR and adjusts risk limits.
-async fn adjust_limits_from_var(con_arc: Arc<tokio::sync::Mutex<redis::aio::Connection>>) {
-    let http_client = reqwest::Client::new();
+/// Background task that fetches VaR and adjusts risk limits by the limit policy.
+async fn adjust_limits_from_var(ctx: RiskContext, notifier: Notifier, policy_status: SharedPolicyStatus) {
+    let http_client = quantumarb_tenancy::firm_client();
+    // The notifying rule that applied at the last fetch, so each is notified once.
+    let mut notified: Option<String> = None;
     let mut interval = time::interval(Duration::from_secs(15));
     loop {
         interval.tick().await;
         println!("\nAdjusting limits based on VaR...");
         
         // Fetch latest VaR
-        if let Ok(response) = http_client.get(VAR_CALCULATOR_URL).send().await {
+        if let Ok(response) = http_client.get(VAR_CALCULATOR.url("/var")).send().await {
             if let Ok(var_result) = response.json::<VaRResult>().await {
-                let mut con = con_arc.lock().await;
-                let key = "account:101";
-                if let Ok(state_json) = con.get::<_, String>(key).await {
-                    let mut state: AccountState = serde_json::from_str(&state_json).unwrap();
-
-                    // Dynamic Adjustment Logic:
-                    // If VaR is more than 5% of the portfolio value, tighten limits by 25%.
-                    // Otherwise, use baseline limits.
-                    let var_ratio = var_result.var_amount / var_result.portfolio_value;
-                    if var_ratio > 0.05 {
-                        println!("  -> High VaR detected ({:.2}%). Tightening limits.", var_ratio * 100.0);
-                        state.current_max_order_size = (state.base_max_order_size as f32 * 0.75) as u32;
-                        state.current_max_exposure = state.base_max_exposure * 0.75;
-                    } else {
-                        println!("  -> VaR is normal ({:.2}%). Using baseline limits.", var_ratio * 100.0);
-                        state.current_max_order_size = state.base_max_order_size;
-                        state.current_max_exposure = state.base_max_exposure;
+                // A figure that more paths would still move is noise; keep
+                // the limits set from the last converged one.
+                if !var_result.converged {
+                    println!(
+                        "  -> VaR ${:.2} has not converged. Keeping current limits.",
+                        var_result.var_amount
+                    );
+                    continue;
+                }
+                escalate_on_var(&ctx.con, &http_client, &notifier, &ctx.escalation, &var_result).await;
+                let history = fetch_var_history(&http_client).await;
+                let policy = load_limit_policy(&ctx.con).await;
+                let daily_pnl = ctx.account_pnl.read().unwrap().get(&101).map(|pnl| pnl.daily_pnl);
+
+                // Dynamic Adjustment Logic: the first rule of the limit policy
+                // that holds sets the factor on the baseline limits; with none,
+                // the baseline limits apply.
+                let mut inputs = PolicyInputs::from_var(&var_result, history.as_ref(), chrono::Utc::now().time());
+                let mut evaluation = None;
+                let adjusted = update_account(&ctx.con, 101, |state| {
+                    inputs.drawdown = policy::drawdown(daily_pnl, state.max_daily_loss);
+                    let result = policy.evaluate(&inputs);
+                    state.current_max_order_size = (state.base_max_order_size as f32 * result.factor as f32) as u32;
+                    state.current_max_exposure = state.base_max_exposure.scaled(result.factor);
+                    evaluation = Some(result);
+                    Ok(())
+                })
+                .await;
+                if let Err(rejection) = adjusted {
+                    println!("  -> Failed to adjust limits: {}", rejection);
+                }
+                if let Some(evaluation) = &evaluation {
+                    match &evaluation.rule {
+                        Some(rule) => println!(
+                            "  -> VaR is {:.2}% of the portfolio. Rule '{}' applies: limits at {:.0}% of baseline.",
+                            inputs.var_ratio * 100.0,
+                            rule,
+                            evaluation.factor * 100.0
+                        ),
+                        None => println!(
+                            "  -> VaR is {:.2}% of the portfolio. Using baseline limits.",
+                            inputs.var_ratio * 100.0
+                        ),
                     }
-                    
-                    let _: () = con.set(key, serde_json::to_string(&state).unwrap()).await.unwrap();
+                    let notifying = evaluation.rule.clone().filter(|_| evaluation.notify);
+                    if notifying.is_some() && notifying != notified {
+                        notify_policy_breach(&notifier, &var_result, evaluation);
+                    }
+                    notified = notifying;
                 }
+                *policy_status.write().unwrap() =
+                    PolicyStatus { inputs: Some(inputs), evaluation, updated_utc: chrono::Utc::now().to_rfc3339() };
             }
         }
     }
 }
 
-/// Core risk check logic, now using the dynamically adjusted limits.
-async fn check_pre_trade_risk(
-    con_arc: Arc<tokio::sync::Mutex<redis::aio::Connection>>,
+/// Notifies a VaR breach for a notifying rule of the limit policy.
+fn notify_policy_breach(notifier: &Notifier, var_result: &VaRResult, evaluation: &Evaluation) {
+    let breach = notifier
+        .event(EventClass::VarBreach)
+        .field("var_amount", format!("{:.2}", var_result.var_amount))
+        .field("limit", format!("limit policy rule '{}'", evaluation.rule.as_deref().unwrap_or_default()))
+        .field("portfolio_value", format!("{:.2}", var_result.portfolio_value))
+        .field(
+            "action",
+            format!("order size and exposure limits tightened to {:.0}% of baseline", evaluation.factor * 100.0),
+        );
+    notifier.notify(breach);
+}
+
+/// Moves the escalation ladder to the latest VaR and carries out the actions
+/// of any step reached. Blocking is applied by the pre-trade check.
+async fn escalate_on_var(
+    con_arc: &SharedConnection,
+    http_client: &reqwest::Client,
+    notifier: &Notifier,
+    escalation: &SharedEscalation,
+    var_result: &VaRResult,
+) {
+    let policy = load_var_escalation_policy(con_arc).await;
+    let var_amount = var_result.var_amount;
+    let (actions, status) = {
+        let mut status = escalation.write().unwrap();
+        (status.update(&policy, var_amount), status.clone())
+    };
+    let Some(step) = status.step.filter(|_| !actions.is_empty()) else {
+        return;
+    };
+    let at_pct = policy.steps[step].at_pct_of_limit * 100.0;
+    println!("  -> VaR {:.2} reached {:.0}% of its {:.2} limit: {:?}", var_amount, at_pct, policy.var_limit, actions);
+
+    let mut hedge_fraction = None;
+    if actions.contains(&EscalationAction::AutoHedge) {
+        hedge_fraction = request_var_hedge(http_client, &policy, var_amount).await;
+    }
+    if actions.contains(&EscalationAction::Notify) {
+        let mut taken = Vec::new();
+        if status.blocking_risk_increasing {
+            taken.push("risk-increasing orders blocked".to_string());
+        }
+        if let Some(fraction) = hedge_fraction {
+            taken.push(format!("strategy engine instructed to cut positions by {:.0}%", fraction * 100.0));
+        }
+        let breach = notifier
+            .event(EventClass::VarBreach)
+            .field("var_amount", format!("{:.2}", var_amount))
+            .field("limit", format!("{:.2}", policy.var_limit))
+            .field("step", format!("{:.0}% of limit", at_pct))
+            .field("action", if taken.is_empty() { "none".to_string() } else { taken.join("; ") });
+        notifier.notify(breach);
+    }
+}
+
+/// Instructs the strategy engine to cut every position by the fraction that
+/// brings VaR back to the policy's hedge target. Returns the fraction, or
+/// None if the book could not be read or holds nothing to cut.
+async fn request_var_hedge(http_client: &reqwest::Client, policy: &EscalationPolicy, var_amount: f64) -> Option<f64> {
+    let fraction = policy.hedge_fraction(var_amount);
+    let snapshot = match http_client.get(PORTFOLIO_MANAGER.url("/portfolio")).send().await {
+        Ok(response) => response.json::<PortfolioSnapshot>().await.ok(),
+        Err(_) => None,
+    };
+    let Some(snapshot) = snapshot else {
+        println!("  -> Failed to fetch the portfolio to hedge VaR.");
+        return None;
+    };
+    let orders = quantumarb_types::reduction_orders(&snapshot.positions, fraction);
+    if orders.is_empty() {
+        return None;
+    }
+    let instruction = StrategyInstruction::ReducePositions {
+        reason: format!(
+            "VaR {:.2} over its {:.2} limit; hedging to {:.0}% of the limit",
+            var_amount,
+            policy.var_limit,
+            policy.hedge_target_pct_of_limit * 100.0
+        ),
+        fraction,
+        orders,
+    };
+    quantumarb_bus::publish_json(quantumarb_bus::topics::STRATEGY_INSTRUCTIONS, &instruction);
+    Some(fraction)
+}
+
+/// The last hour of VaR history, the limit policy's trend and volatility
+/// inputs. None if the VaR calculator does not answer.
+async fn fetch_var_history(http_client: &reqwest::Client) -> Option<VaRHistory> {
+    let url = VAR_CALCULATOR.url("/var/history?window=1h&points=12");
+    http_client.get(url).send().await.ok()?.json::<VaRHistory>().await.ok()
+}
+
+/// The Redis-held inputs of a pre-trade check.
+struct RiskState {
+    kill_switch: Option<KillSwitchState>,
+    account: Option<AccountState>,
+    counterparty_limits: CounterpartyLimits,
+    capital_allocations: CapitalAllocations,
+    stress_config: StressConfig,
+    /// Multiplier on every limit: 1, or the degraded factor if any input is
+    /// the local copy kept while Redis is down.
+    degraded_factor: f64,
+}
+
+/// Reads the risk state an order is checked against. Fails only if Redis is
+/// down and the local copy is missing or too old.
+async fn read_risk_state(con_arc: &SharedConnection, account_id: u32) -> Result<RiskState, Rejection> {
+    let mut store = con_arc.lock().await;
+    let kill_switch = store.read_for_check(KILL_SWITCH_KEY).await?;
+    let account = store.read_for_check(&format!("account:{}", account_id)).await?;
+    let counterparty = store.read_for_check(COUNTERPARTY_LIMITS_KEY).await?;
+    let capital = store.read_for_check(CAPITAL_ALLOCATIONS_KEY).await?;
+    let stress = store.read_for_check(STRESS_CONFIG_KEY).await?;
+    let degraded =
+        [&kill_switch, &account, &counterparty, &capital, &stress].iter().any(|read| read.cached);
+    Ok(RiskState {
+        kill_switch: kill_switch.value.map(|json| serde_json::from_str(&json).unwrap()),
+        account: account.value.map(|json| serde_json::from_str(&json).unwrap()),
+        counterparty_limits: counterparty.value.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+        capital_allocations: capital.value.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+        stress_config: stress.value.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+        degraded_factor: if degraded { store.degraded_limit_factor() } else { 1.0 },
+    })
+}
+
+/// Checks an order; one that fails a check the approval policy names is
+/// held for a supervisor instead of rejected.
+async fn check_pre_trade_risk(ctx: &RiskContext, order: &OrderRequest) -> RiskDecision {
+    let decision = run_pre_trade_checks(ctx, order, &[]).await;
+    hold_for_approval(ctx, order, decision, Vec::new()).await
+}
+
+/// Parks an order rejected for a code the approval policy holds. Without
+/// Redis nothing can be parked, so the rejection stands.
+async fn hold_for_approval(
+    ctx: &RiskContext,
     order: &OrderRequest,
+    decision: RiskDecision,
+    waived: Vec<RejectCode>,
 ) -> RiskDecision {
-    let mut con = con_arc.lock().await;
-    let key = format!("account:{}", order.account_id);
-    let state_json: String = match con.get(&key).await {
-        Ok(val) => val,
-        Err(_) => return RiskDecision::Rejected("Account not found".to_string()),
+    let RiskDecision::Rejected(rejection) = decision else {
+        return decision;
     };
-    let state: AccountState = serde_json::from_str(&state_json).unwrap();
+    let instance = ctx.shards.read().unwrap().instance().id.clone();
+    let mut con = ctx.con.lock().await;
+    let policy = read_approval_policy(&mut con).await;
+    if !policy.holds(rejection.code) {
+        return RiskDecision::Rejected(rejection);
+    }
+    let held = update_approval_queue(&mut con, |queue| {
+        queue.hold(order, rejection.clone(), waived.clone(), &instance, &policy, chrono::Utc::now());
+        Ok(())
+    })
+    .await;
+    match held {
+        Ok(()) => RiskDecision::Held(rejection),
+        Err(_) => RiskDecision::Rejected(rejection),
+    }
+}
+
+/// Core risk check logic, now using the dynamically adjusted limits. Checks
+/// failing with a code in `waived` were approved by a supervisor and pass.
+async fn run_pre_trade_checks(
+    ctx: &RiskContext,
+    order: &OrderRequest,
+    waived: &[RejectCode],
+) -> RiskDecision {
+    let waive = |result: Result<(), Rejection>| {
+        result.or_else(|rejection| if waived.contains(&rejection.code) { Ok(()) } else { Err(rejection) })
+    };
+    if order.mode != ctx.mode.mode {
+        return RiskDecision::Rejected(Rejection::new(
+            RejectCode::SystemModeMismatch,
+            format!("{} order sent to a {} risk gateway", order.mode, ctx.mode.mode),
+        ));
+    }
+    if let Err(rejection) = ctx.shards.read().unwrap().check_owner(order.account_id) {
+        return RiskDecision::Rejected(rejection);
+    }
 
-    // Check against the CURRENT (dynamically adjusted) limits
-    if order.size > state.current_max_order_size {
-        return RiskDecision::Rejected(format!(
-            "Order size {} exceeds current dynamic limit {}",
-            order.size, state.current_max_order_size
+    let state = match read_risk_state(&ctx.con, order.account_id).await {
+        Ok(state) => state,
+        Err(rejection) => return RiskDecision::Rejected(rejection),
+    };
+    if let Some(kill_switch) = state.kill_switch.filter(|k| k.engaged) {
+        return RiskDecision::Rejected(Rejection::new(
+            RejectCode::RiskKillSwitchEngaged,
+            format!("Kill switch engaged: {}", kill_switch.reason),
         ));
     }
-    // ... other checks ...
+    if let Err(rejection) = check_venue_connectivity(&ctx.venues.read().unwrap(), order) {
+        return RiskDecision::Rejected(rejection);
+    }
+    {
+        let exposures = ctx.exposures.read().unwrap();
+        let instruments = ctx.instruments.read().unwrap();
+        let status = ctx.escalation.read().unwrap();
+        let instrument = instruments.get(order.instrument_id);
+        if let Err(rejection) = escalation::check_order(&status, exposures.as_ref(), instrument, order) {
+            return RiskDecision::Rejected(rejection);
+        }
+    }
+
+    let Some(account) = state.account else {
+        return RiskDecision::Rejected(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found"));
+    };
+    {
+        let account_pnl = ctx.account_pnl.read().unwrap();
+        let pnl = account_pnl.get(&order.account_id);
+        if let Err(rejection) = waive(daily_loss::check_order(account.daily_loss_lockout.as_ref(), pnl, order)) {
+            return RiskDecision::Rejected(rejection);
+        }
+    }
+    // Price option orders and hold the account to its vega and gamma limits;
+    // an unknown instrument is rejected by the order checks below.
+    let option_greeks = {
+        let instruments = ctx.instruments.read().unwrap();
+        let account_pnl = ctx.account_pnl.read().unwrap();
+        let no_positions = HashMap::new();
+        let positions = account_pnl.get(&order.account_id).map_or(&no_positions, |pnl| &pnl.positions);
+        let limits = if waived.contains(&RejectCode::RiskGreeksLimit) {
+            GreeksLimits::default()
+        } else {
+            GreeksLimits {
+                max_vega: account.max_vega.map(|limit| limit.scaled(state.degraded_factor)),
+                max_gamma: account.max_gamma.map(|limit| limit.scaled(state.degraded_factor)),
+            }
+        };
+        let market = ctx.options.read().unwrap();
+        let now = chrono::Utc::now();
+        let checked = instruments.get(order.instrument_id).map(|instrument| {
+            greeks::check_order(&limits, &market, instrument, &instruments, positions, order, now)
+        });
+        match checked.transpose() {
+            Ok(option_greeks) => option_greeks.flatten(),
+            Err(rejection) => return RiskDecision::Rejected(rejection),
+        }
+    };
+    // Capital and stress are measured at the order's notional; an option's
+    // is delta-adjusted
+    let notional = match (option_greeks, ctx.instruments.read().unwrap().get(order.instrument_id)) {
+        (Some(option_greeks), _) => option_greeks.delta_notional.abs(),
+        (None, Some(instrument)) => {
+            instrument.notional(instrument.price(order.price), Quantity::from(order.size)).abs()
+        }
+        (None, None) => Money::ZERO,
+    };
+    // Stress large orders against their asset class's scenarios
+    let stress_check = {
+        let instruments = ctx.instruments.read().unwrap();
+        let account_pnl = ctx.account_pnl.read().unwrap();
+        let no_positions = HashMap::new();
+        let positions = account_pnl.get(&order.account_id).map_or(&no_positions, |pnl| &pnl.positions);
+        let (marks, options) = (ctx.marks.read().unwrap(), ctx.options.read().unwrap());
+        let now = chrono::Utc::now();
+        let market = StressMarket { instruments: &instruments, marks: &marks, options: &options, now };
+        let max_loss = account.max_stress_loss.map(|limit| limit.scaled(state.degraded_factor));
+        instruments.get(order.instrument_id).map_or(Ok(()), |instrument| {
+            stress::check_order(&state.stress_config, max_loss, &market, positions, instrument, order, notional)
+        })
+    };
+    if let Err(rejection) = waive(stress_check) {
+        return RiskDecision::Rejected(rejection);
+    }
+    // Tightened while an intraday window is open for the order's symbol
+    let time_factor = {
+        let instruments = ctx.instruments.read().unwrap();
+        let symbol = instruments.get(order.instrument_id).map_or("", |instrument| instrument.symbol.as_str());
+        ctx.time_limits.read().unwrap().factor(symbol)
+    };
+    let mut concentration_limits = ctx.concentration_limits.read().unwrap().clone();
+    for cap in [
+        &mut concentration_limits.max_symbol_pct,
+        &mut concentration_limits.max_sector_pct,
+        &mut concentration_limits.max_venue_pct,
+    ] {
+        *cap *= state.degraded_factor;
+    }
+    concentration_limits.max_symbol_pct *= time_factor;
+    let mut counterparty_limits = state.counterparty_limits;
+    let credit_factor = ctx.mode.limit_multiplier * state.degraded_factor;
+    counterparty_limits.values_mut().for_each(|limit| *limit = limit.scaled(credit_factor));
+
+    // Check against the CURRENT (dynamically adjusted) limits, relaxed in
+    // sandbox and tightened while Redis is down and in intraday windows
+    let instruments = ctx.instruments.read().unwrap();
+    let exposures = ctx.exposures.read().unwrap();
+    let counterparty_exposures = ctx.counterparty_exposures.read().unwrap();
+    let size_factor = ctx.mode.limit_multiplier * state.degraded_factor * time_factor;
+    let limits = OrderLimits {
+        instruments: &instruments,
+        max_order_size: if waived.contains(&RejectCode::RiskOrderSizeLimit) {
+            u32::MAX
+        } else {
+            (account.current_max_order_size as f64 * size_factor) as u32
+        },
+        concentration: &concentration_limits,
+        exposures: exposures.as_ref(),
+        counterparty: &counterparty_limits,
+        counterparty_exposures: &counterparty_exposures,
+    };
+    if let Err(rejection) = checks::check_order(&limits, order) {
+        return RiskDecision::Rejected(rejection);
+    }
+
+    // Charge the order to its strategy's capital allocation
+    let (strategy_id, allocations) = (&order.strategy_id, &state.capital_allocations);
+    let mut capital = ctx.capital.write().unwrap();
+    let factor = state.degraded_factor;
+    let charged = capital::check(allocations, &capital, strategy_id, order.priority, notional, factor);
+    if let Err(rejection) = waive(charged) {
+        return RiskDecision::Rejected(rejection);
+    }
+    if !strategy_id.is_empty() {
+        capital.reserve(strategy_id, notional);
+    }
     RiskDecision::Approved
 }
+
+/// Checks a multi-leg order as a package: each leg against the single-order
+/// checks, then the package's net notional against the exposure limit.
+async fn check_package(ctx: &RiskContext, package: &MultiLegOrder) -> RiskDecision {
+    if let Err(rejection) = package::validate(package) {
+        return RiskDecision::Rejected(rejection);
+    }
+    for index in 0..package.legs.len() {
+        // The package is charged to its strategy's capital as a whole, below.
+        let mut leg = package.leg_order(index);
+        leg.strategy_id.clear();
+        if let RiskDecision::Rejected(rejection) = run_pre_trade_checks(ctx, &leg, &[]).await {
+            return RiskDecision::Rejected(package::leg_rejection(index, rejection));
+        }
+    }
+
+    let state = match read_risk_state(&ctx.con, package.account_id).await {
+        Ok(state) => state,
+        Err(rejection) => return RiskDecision::Rejected(rejection),
+    };
+    let Some(account) = state.account else {
+        return RiskDecision::Rejected(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found"));
+    };
+
+    let instruments = ctx.instruments.read().unwrap().clone();
+    let net = package::net_notional(package, &instruments).abs();
+    // The exposure limit is tightened by the strictest window open for a leg
+    let time_factor = {
+        let time_limits = ctx.time_limits.read().unwrap();
+        let symbols = package.legs.iter().filter_map(|leg| instruments.get(leg.instrument_id)).map(|i| &i.symbol);
+        symbols.map(|symbol| time_limits.factor(symbol)).fold(1.0, f64::min)
+    };
+    let exposure_factor = ctx.mode.limit_multiplier * time_factor;
+    let (strategy_id, allocations) = (&package.strategy_id, &state.capital_allocations);
+    let capital_check = {
+        let capital = ctx.capital.read().unwrap();
+        capital::check(allocations, &capital, strategy_id, package.priority, net, state.degraded_factor)
+    };
+    if let Err(rejection) = capital_check {
+        return RiskDecision::Rejected(rejection);
+    }
+    let reserve_capital = || {
+        if !strategy_id.is_empty() {
+            ctx.capital.write().unwrap().reserve(strategy_id, net);
+        }
+    };
+    if state.degraded_factor < 1.0 {
+        // Redis is down: check against the local copy; nothing can be reserved.
+        let max_exposure = account.current_max_exposure.scaled(exposure_factor * state.degraded_factor);
+        return match package::check_net_exposure(package, &instruments, account.current_exposure, max_exposure) {
+            Ok(()) => {
+                reserve_capital();
+                RiskDecision::Approved
+            }
+            Err(rejection) => RiskDecision::Rejected(rejection),
+        };
+    }
+    // Check and reserve in one versioned write, so two instances serving the
+    // account during a rebalance cannot both spend the same headroom.
+    let reserved = update_account(&ctx.con, package.account_id, |account| {
+        let max_exposure = account.current_max_exposure.scaled(exposure_factor);
+        package::check_net_exposure(package, &instruments, account.current_exposure, max_exposure)?;
+        account.current_exposure += net;
+        Ok(())
+    })
+    .await;
+    match reserved {
+        Ok(_) => {
+            reserve_capital();
+            RiskDecision::Approved
+        }
+        Err(rejection) => RiskDecision::Rejected(rejection),
+    }
+}
+
+/// Reads an account, applies `change` and writes it back if no other writer
+/// (another gateway instance, during a rebalance) got in between; otherwise
+/// `change` is applied again to the newer state. `change` may reject.
+async fn update_account<F>(
+    con_arc: &SharedConnection,
+    account_id: u32,
+    mut change: F,
+) -> Result<AccountState, Rejection>
+where
+    F: FnMut(&mut AccountState) -> Result<(), Rejection>,
+{
+    let key = format!("account:{}", account_id);
+    let unavailable = |e: redis::RedisError| Rejection::new(RejectCode::SystemStateUnavailable, e.to_string());
+    let mut con = con_arc.lock().await;
+    for _ in 0..ACCOUNT_WRITE_ATTEMPTS {
+        let Some(state_json) = con.get::<_, Option<String>>(&key).await.map_err(unavailable)? else {
+            return Err(Rejection::new(RejectCode::RiskAccountNotFound, "Account not found"));
+        };
+        let mut state: AccountState = serde_json::from_str(&state_json).unwrap();
+        let read_version = state.version;
+        change(&mut state)?;
+        state.version = read_version + 1;
+        let json = serde_json::to_string(&state).unwrap();
+        if con.set_if_version(&key, read_version, &json).await.map_err(unavailable)? {
+            return Ok(state);
+        }
+    }
+    Err(Rejection::new(
+        RejectCode::SystemConflict,
+        format!("Account {} kept changing under {} write attempts", account_id, ACCOUNT_WRITE_ATTEMPTS),
+    ))
+}
+
+/// Keeps this instance in the shard membership and its view of the map
+/// current; as coordinator, republishes the map when instances come or go.
+async fn maintain_shard_membership(shards: SharedShards, con_arc: SharedConnection, config: ShardConfig) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let round = {
+            let mut store = con_arc.lock().await;
+            shards::membership_round(&mut *store, &config).await
+        };
+        match round {
+            Ok(round) => {
+                if round.published {
+                    if let Some(map) = &round.map {
+                        println!("\nPublished shard map epoch {} as coordinator.", map.epoch);
+                        quantumarb_bus::publish_json(shards::SHARD_MAP_TOPIC, map);
+                    }
+                }
+                shards.write().unwrap().apply(round);
+            }
+            Err(e) => println!("Shard membership round failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Limit Policy
+ *
+ * File: src/risk_compliance/risk_gateway/policy.rs
+ *
+ * Description:
+ * The rules by which the gateway tightens an account's order size and
+ * exposure limits from their baseline after every VaR fetch. A policy is an
+ * ordered list of rules, each a condition and the factor applied to the
+ * baseline limits while it holds; the first rule whose condition holds sets
+ * the factor, and with none the baseline limits apply. Conditions are a
+ * small tree, written in JSON:
+ *
+ *   {"var_ratio": {"above": 0.05}}      VaR as a share of the portfolio value
+ *   {"var_trend": {"above": 0.25}}      VaR growth over the last hour
+ *   {"volatility": {"above": 0.01}}     standard deviation of the portfolio
+ *                                       value's returns between the points of
+ *                                       the last hour's VaR history
+ *   {"drawdown": {"above": 0.5}}        the account's session loss as a share
+ *                                       of its max_daily_loss
+ *   {"time_of_day": {"from_utc": "13:30:00", "to_utc": "14:00:00"}}
+ *   {"all": [..]}, {"any": [..]}, {"not": {..}}
+ *
+ * A range gives `above`, `below` or both, exclusive. An input the gateway
+ * does not have (no VaR history, no P&L from the portfolio manager, no daily
+ * loss limit) is in no range. A time window whose end is before its start
+ * runs through midnight.
+ *
+ * Rules only tighten: factors are in (0, 1]; raise the baseline on /limits
+ * to loosen. A rule marked `notify` is notified as a VaR breach when it
+ * starts to apply.
+ *
+ * The default policy is the gateway's original logic: limits at 75% while
+ * VaR is over 5% of the portfolio value (notified), otherwise at 90% while
+ * VaR is up more than 25% over the last hour.
+ *
+ * The policy is kept in Redis and set through the admin API (/limit-policy).
+ * POST /limit-policy/preview evaluates the policy set, or a candidate, on
+ * the latest inputs or given ones, and shows the limits that would result
+ * without changing any.
+ */
+
+use chrono::NaiveTime;
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::{VaRHistory, VaRResult};
+use serde::{Deserialize, Serialize};
+
+// --- Policy ---
+
+/// Bounds on an input; None is unbounded on that side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct Range {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f64>,
+}
+
+impl Range {
+    fn contains(&self, value: Option<f64>) -> bool {
+        value.is_some_and(|v| self.above.is_none_or(|a| v > a) && self.below.is_none_or(|b| v < b))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    VarRatio(Range),
+    VarTrend(Range),
+    Volatility(Range),
+    Drawdown(Range),
+    TimeOfDay { from_utc: NaiveTime, to_utc: NaiveTime },
+}
+
+impl Condition {
+    pub fn holds(&self, inputs: &PolicyInputs) -> bool {
+        match self {
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds(inputs)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds(inputs)),
+            Condition::Not(condition) => !condition.holds(inputs),
+            Condition::VarRatio(range) => range.contains(Some(inputs.var_ratio)),
+            Condition::VarTrend(range) => range.contains(inputs.var_trend),
+            Condition::Volatility(range) => range.contains(inputs.volatility),
+            Condition::Drawdown(range) => range.contains(inputs.drawdown),
+            Condition::TimeOfDay { from_utc, to_utc } if from_utc <= to_utc => {
+                *from_utc <= inputs.time_utc && inputs.time_utc < *to_utc
+            }
+            Condition::TimeOfDay { from_utc, to_utc } => *from_utc <= inputs.time_utc || inputs.time_utc < *to_utc,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                if conditions.is_empty() {
+                    return Err("'all' and 'any' need at least one condition.".to_string());
+                }
+                conditions.iter().try_for_each(Condition::validate)
+            }
+            Condition::Not(condition) => condition.validate(),
+            Condition::VarRatio(range)
+            | Condition::VarTrend(range)
+            | Condition::Volatility(range)
+            | Condition::Drawdown(range) => match (range.above, range.below) {
+                (None, None) => Err("A range needs 'above', 'below' or both.".to_string()),
+                (Some(above), Some(below)) if above >= below => {
+                    Err(format!("The range above {} and below {} is empty.", above, below))
+                }
+                _ => Ok(()),
+            },
+            Condition::TimeOfDay { from_utc, to_utc } if from_utc == to_utc => {
+                Err(format!("The time window from {} to {} is empty.", from_utc, to_utc))
+            }
+            Condition::TimeOfDay { .. } => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct PolicyRule {
+    pub name: String,
+    pub when: Condition,
+    /// Factor on the baseline order size and exposure limits while the rule applies.
+    pub factor: f64,
+    /// Notify a VaR breach when the rule starts to apply.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct LimitPolicy {
+    /// Rules in order of precedence: the first that holds applies.
+    pub rules: Vec<PolicyRule>,
+}
+
+impl Default for LimitPolicy {
+    fn default() -> Self {
+        let above = |bound| Range { above: Some(bound), below: None };
+        LimitPolicy {
+            rules: vec![
+                PolicyRule {
+                    name: "high_var".to_string(),
+                    when: Condition::VarRatio(above(0.05)),
+                    factor: 0.75,
+                    notify: true,
+                },
+                PolicyRule {
+                    name: "var_rising".to_string(),
+                    when: Condition::VarTrend(above(0.25)),
+                    factor: 0.9,
+                    notify: false,
+                },
+            ],
+        }
+    }
+}
+
+impl LimitPolicy {
+    /// Errs with the reason the policy cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.name.is_empty() {
+                return Err("Every rule needs a name.".to_string());
+            }
+            if self.rules[..i].iter().any(|r| r.name == rule.name) {
+                return Err(format!("Rule '{}' is named twice.", rule.name));
+            }
+            if !(rule.factor > 0.0 && rule.factor <= 1.0) {
+                return Err(format!("Rule '{}': factor must be above 0 and at most 1.", rule.name));
+            }
+            rule.when.validate().map_err(|reason| format!("Rule '{}': {}", rule.name, reason))?;
+        }
+        Ok(())
+    }
+
+    /// The rule that applies to `inputs`, and every rule that holds.
+    pub fn evaluate(&self, inputs: &PolicyInputs) -> Evaluation {
+        let holding: Vec<&PolicyRule> = self.rules.iter().filter(|r| r.when.holds(inputs)).collect();
+        Evaluation {
+            rule: holding.first().map(|r| r.name.clone()),
+            factor: holding.first().map_or(1.0, |r| r.factor),
+            notify: holding.first().is_some_and(|r| r.notify),
+            holding: holding.iter().map(|r| r.name.clone()).collect(),
+        }
+    }
+}
+
+// --- Evaluation ---
+
+/// What the policy's conditions are evaluated on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct PolicyInputs {
+    pub var_ratio: f64,
+    #[serde(default)]
+    pub var_trend: Option<f64>,
+    #[serde(default)]
+    pub volatility: Option<f64>,
+    #[serde(default)]
+    pub drawdown: Option<f64>,
+    pub time_utc: NaiveTime,
+}
+
+impl PolicyInputs {
+    /// The inputs of a VaR fetch and the last hour's history, without the
+    /// account's drawdown.
+    pub fn from_var(var_result: &VaRResult, history: Option<&VaRHistory>, time_utc: NaiveTime) -> PolicyInputs {
+        PolicyInputs {
+            var_ratio: var_result.var_amount / var_result.portfolio_value,
+            var_trend: history.and_then(var_trend),
+            volatility: history.and_then(volatility),
+            drawdown: None,
+            time_utc,
+        }
+    }
+}
+
+/// VaR growth from the first to the last point of `history`.
+fn var_trend(history: &VaRHistory) -> Option<f64> {
+    let first = history.points.first()?.var_amount;
+    let last = history.points.last()?.var_amount;
+    if history.points.len() < 2 || first <= 0.0 {
+        return None;
+    }
+    Some(last / first - 1.0)
+}
+
+/// Sample standard deviation of the portfolio value's returns between the
+/// points of `history`.
+fn volatility(history: &VaRHistory) -> Option<f64> {
+    let returns: Vec<f64> = history
+        .points
+        .windows(2)
+        .filter(|w| w[0].portfolio_value > 0.0)
+        .map(|w| w[1].portfolio_value / w[0].portfolio_value - 1.0)
+        .collect();
+    if returns.len() < 2 {
+        return None;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+/// The session loss as a share of the daily loss limit; 0 while in profit.
+pub fn drawdown(daily_pnl: Option<Money>, max_daily_loss: Option<Money>) -> Option<f64> {
+    let limit = max_daily_loss.filter(|l| *l > Money::ZERO)?;
+    Some((-daily_pnl?).max(Money::ZERO).ratio(limit))
+}
+
+/// The outcome of evaluating a policy.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct Evaluation {
+    /// The rule that applies; None for the baseline limits.
+    pub rule: Option<String>,
+    pub factor: f64,
+    pub notify: bool,
+    /// Every rule whose condition holds, in order.
+    pub holding: Vec<String>,
+}
+
+/// The last evaluation after a VaR fetch, for GET /limit-policy and as the
+/// default inputs of a preview.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct PolicyStatus {
+    pub inputs: Option<PolicyInputs>,
+    pub evaluation: Option<Evaluation>,
+    pub updated_utc: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(var_ratio: f64, var_trend: Option<f64>) -> PolicyInputs {
+        PolicyInputs {
+            var_ratio,
+            var_trend,
+            volatility: None,
+            drawdown: None,
+            time_utc: NaiveTime::from_hms_opt(15, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn the_default_policy_reproduces_the_original_tightening() {
+        let policy = LimitPolicy::default();
+        assert!(policy.validate().is_ok());
+        let high = policy.evaluate(&inputs(0.06, Some(0.5)));
+        assert_eq!((high.rule.as_deref(), high.factor, high.notify), (Some("high_var"), 0.75, true));
+        assert_eq!(high.holding, ["high_var", "var_rising"]);
+        assert_eq!(policy.evaluate(&inputs(0.03, Some(0.3))).factor, 0.9);
+        let normal = policy.evaluate(&inputs(0.03, None));
+        assert_eq!((normal.rule, normal.factor), (None, 1.0));
+    }
+
+    #[test]
+    fn conditions_combine_and_windows_run_through_midnight() {
+        let json = r#"{"rules": [{"name": "overnight_drawdown", "factor": 0.5, "when": {"all": [
+            {"time_of_day": {"from_utc": "22:00:00", "to_utc": "02:00:00"}},
+            {"not": {"drawdown": {"below": 0.5}}}
+        ]}}]}"#;
+        let policy: LimitPolicy = serde_json::from_str(json).unwrap();
+        assert!(policy.validate().is_ok());
+        let mut late = inputs(0.01, None);
+        late.time_utc = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
+        // Without a daily loss limit there is no drawdown, and `not` of no range holds.
+        assert_eq!(policy.evaluate(&late).factor, 0.5);
+        late.drawdown = drawdown(Some(Money::from_f64(-5_000.0)), Some(Money::from_f64(25_000.0)));
+        assert_eq!(policy.evaluate(&late).factor, 1.0);
+        late.drawdown = drawdown(Some(Money::from_f64(-20_000.0)), Some(Money::from_f64(25_000.0)));
+        assert_eq!(policy.evaluate(&late).factor, 0.5);
+        assert_eq!(policy.evaluate(&inputs(0.01, None)).factor, 1.0);
+
+        let mut loosening = policy.clone();
+        loosening.rules[0].factor = 1.5;
+        assert!(loosening.validate().is_err());
+        let empty: LimitPolicy =
+            serde_json::from_str(r#"{"rules": [{"name": "x", "factor": 0.5, "when": {"var_ratio": {}}}]}"#).unwrap();
+        assert!(empty.validate().is_err());
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Account Shards
+ *
+ * File: src/risk_compliance/risk_gateway/shards.rs
+ *
+ * Description:
+ * Lets several gateway instances share the firm's flow, each checking the
+ * orders of the accounts the consistent-hash ring
+ * (`quantumarb_risk::sharding`) gives it.
+ *
+ * Membership lives in Redis, in the sorted set 'risk_gateway:instances' of
+ * instances scored by when they expire. Every instance re-adds itself each
+ * second with an expiry a TTL ahead (Redis TIME), so an instance that stops
+ * drops out of the set when the TTL runs out; the coordinator trims expired
+ * members and reads the rest by score. One instance is the
+ * coordinator: it holds the lease 'risk_gateway:coordinator', renewed each
+ * round, and whenever the live set differs from the published map it writes
+ * 'risk_gateway:shard_map' with the next epoch and announces it on
+ * 'risk.shard_map'. If the coordinator stops, its lease expires and another
+ * instance takes over.
+ *
+ * Every instance reloads the map each round. An order for an account owned
+ * by another instance is rejected with SYSTEM_WRONG_SHARD naming the owner,
+ * so the router can resend it; GET /shards/route/{account_id} answers the
+ * same question up front. With sharding on, orders are refused
+ * (SYSTEM_NOT_READY) until the first map arrives; with it off the instance
+ * owns every account.
+ *
+ * During a rebalance an account can briefly be checked by both its old and
+ * its new owner, so writes to account state are optimistic: each names the
+ * version it read and fails if another write got there first (see
+ * `store::set_if_version`).
+ *
+ * Configuration (environment):
+ *   QA_RISK_SHARDING=false                       enable account sharding
+ *   QA_RISK_INSTANCE_ID=$HOSTNAME                this instance's id
+ *   QA_RISK_ADVERTISE_URL=http://127.0.0.1:3034  where routers reach it
+ *   QA_RISK_SHARD_VNODES=64                      ring points per instance
+ *   QA_RISK_SHARD_TTL_MS=5000                    membership and lease expiry
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_risk::sharding::{GatewayInstance, HashRing, ShardMap, DEFAULT_VNODES};
+use redis::aio::ConnectionLike;
+use redis::{AsyncCommands, RedisResult};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const SHARD_MAP_TOPIC: &str = "risk.shard_map";
+/// Sorted set of instance JSON, scored by expiry in Redis-clock milliseconds.
+const INSTANCES_KEY: &str = "risk_gateway:instances";
+const COORDINATOR_KEY: &str = "risk_gateway:coordinator";
+const SHARD_MAP_KEY: &str = "risk_gateway:shard_map";
+
+/// Takes the lease if it is free, renews it if we hold it. 1 if held.
+const LEASE_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    return 1
+end
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return 1
+end
+return 0
+";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct ShardConfig {
+    pub enabled: bool,
+    pub instance: GatewayInstance,
+    pub vnodes: u32,
+    pub ttl: Duration,
+}
+
+impl ShardConfig {
+    pub fn from_env() -> ShardConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let id = std::env::var("QA_RISK_INSTANCE_ID")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| format!("risk-gateway-{}", std::process::id()));
+        ShardConfig {
+            enabled: var("QA_RISK_SHARDING", false),
+            instance: GatewayInstance {
+                id,
+                url: std::env::var("QA_RISK_ADVERTISE_URL").unwrap_or_else(|_| "http://127.0.0.1:3034".to_string()),
+            },
+            vnodes: var("QA_RISK_SHARD_VNODES", DEFAULT_VNODES).max(1),
+            ttl: Duration::from_millis(var("QA_RISK_SHARD_TTL_MS", 5_000u64).max(1_000)),
+        }
+    }
+}
+
+// --- Ownership ---
+
+/// Body of GET /shards.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ShardStatus {
+    pub enabled: bool,
+    pub instance: GatewayInstance,
+    pub coordinator: bool,
+    pub map: Option<ShardMap>,
+}
+
+/// This instance's view of the shard map.
+pub struct Shards {
+    config: ShardConfig,
+    map: Option<ShardMap>,
+    ring: Option<HashRing>,
+    coordinator: bool,
+}
+
+impl Shards {
+    pub fn new(config: ShardConfig) -> Shards {
+        Shards { config, map: None, ring: None, coordinator: false }
+    }
+
+    pub fn instance(&self) -> &GatewayInstance {
+        &self.config.instance
+    }
+
+    /// The instance that owns the account; this one when sharding is off.
+    pub fn owner(&self, account_id: u32) -> Result<&GatewayInstance, Rejection> {
+        if !self.config.enabled {
+            return Ok(&self.config.instance);
+        }
+        self.ring.as_ref().and_then(|ring| ring.owner(account_id)).ok_or_else(|| {
+            Rejection::new(RejectCode::SystemNotReady, "No shard map yet; account ownership is unknown")
+        })
+    }
+
+    /// Rejects orders for accounts another instance owns.
+    pub fn check_owner(&self, account_id: u32) -> Result<(), Rejection> {
+        let owner = self.owner(account_id)?;
+        if owner.id == self.config.instance.id {
+            return Ok(());
+        }
+        Err(Rejection::new(
+            RejectCode::SystemWrongShard,
+            format!("Account {} is served by risk gateway {} ({})", account_id, owner.id, owner.url),
+        ))
+    }
+
+    /// Takes the result of a membership round.
+    pub fn apply(&mut self, round: MembershipRound) {
+        self.coordinator = round.coordinator;
+        let Some(map) = round.map else {
+            return;
+        };
+        if self.map.as_ref().is_some_and(|current| current.epoch >= map.epoch) {
+            return;
+        }
+        let owns = map.instances.iter().any(|i| i.id == self.config.instance.id);
+        println!(
+            "\nShard map epoch {}: {} instance(s){}",
+            map.epoch,
+            map.instances.len(),
+            if owns { "" } else { "; this instance holds no accounts yet" }
+        );
+        self.ring = Some(map.ring());
+        self.map = Some(map);
+    }
+
+    pub fn status(&self) -> ShardStatus {
+        ShardStatus {
+            enabled: self.config.enabled,
+            instance: self.config.instance.clone(),
+            coordinator: self.coordinator,
+            map: self.map.clone(),
+        }
+    }
+}
+
+// --- Membership ---
+
+/// What one membership round found.
+pub struct MembershipRound {
+    pub coordinator: bool,
+    /// The map in Redis after the round.
+    pub map: Option<ShardMap>,
+    /// Set when this instance, as coordinator, wrote a new map.
+    pub published: bool,
+}
+
+/// One round: refresh this instance's membership, take or renew the
+/// coordinator lease, and as coordinator write a new map if the live set has
+/// changed.
+pub async fn membership_round<C: ConnectionLike + Send>(
+    con: &mut C,
+    config: &ShardConfig,
+) -> RedisResult<MembershipRound> {
+    let ttl_ms = config.ttl.as_millis() as u64;
+    let now_ms = server_time_ms(con).await?;
+    let me = serde_json::to_string(&config.instance).expect("instance serializes");
+    let _: () = con.zadd(INSTANCES_KEY, me, (now_ms + ttl_ms) as f64).await?;
+    let held: i32 = redis::Script::new(LEASE_SCRIPT)
+        .key(COORDINATOR_KEY)
+        .arg(&config.instance.id)
+        .arg(ttl_ms)
+        .invoke_async(con)
+        .await?;
+    let current: Option<String> = con.get(SHARD_MAP_KEY).await?;
+    let mut map: Option<ShardMap> = current.and_then(|json| serde_json::from_str(&json).ok());
+    let coordinator = held == 1;
+    let mut published = false;
+    if coordinator {
+        let _: () = con.zrembyscore(INSTANCES_KEY, "-inf", now_ms as f64).await?;
+        let members: Vec<(String, f64)> =
+            con.zrangebyscore_withscores(INSTANCES_KEY, format!("({}", now_ms), "+inf").await?;
+        if let Some(next) = next_map(map.as_ref(), live_instances(members), config.vnodes) {
+            let _: () = con.set(SHARD_MAP_KEY, serde_json::to_string(&next).expect("map serializes")).await?;
+            map = Some(next);
+            published = true;
+        }
+    }
+    Ok(MembershipRound { coordinator, map, published })
+}
+
+/// Milliseconds since the epoch on the Redis server's clock, which every
+/// instance shares.
+async fn server_time_ms<C: ConnectionLike + Send>(con: &mut C) -> RedisResult<u64> {
+    let (secs, micros): (u64, u64) = redis::cmd("TIME").query_async(con).await?;
+    Ok(secs * 1_000 + micros / 1_000)
+}
+
+/// The instances among sorted-set members (instance JSON, expiry), one per
+/// id, sorted by id. An instance that changed its URL is briefly listed
+/// twice; the entry refreshed last wins.
+fn live_instances(members: Vec<(String, f64)>) -> Vec<GatewayInstance> {
+    let mut latest: HashMap<String, (GatewayInstance, f64)> = HashMap::new();
+    for (json, expiry) in members {
+        let Ok(instance) = serde_json::from_str::<GatewayInstance>(&json) else {
+            continue;
+        };
+        if latest.get(&instance.id).is_none_or(|(_, seen)| expiry > *seen) {
+            latest.insert(instance.id.clone(), (instance, expiry));
+        }
+    }
+    let mut instances: Vec<GatewayInstance> = latest.into_values().map(|(instance, _)| instance).collect();
+    instances.sort_by(|a, b| a.id.cmp(&b.id));
+    instances
+}
+
+/// The map to publish when the live set or the ring size differs from the
+/// current map; None to keep it. An empty set is never published.
+fn next_map(current: Option<&ShardMap>, live: Vec<GatewayInstance>, vnodes: u32) -> Option<ShardMap> {
+    let changed = current.is_none_or(|m| m.instances != live || m.vnodes != vnodes);
+    if !changed || live.is_empty() {
+        return None;
+    }
+    Some(ShardMap {
+        epoch: current.map_or(1, |m| m.epoch + 1),
+        vnodes,
+        instances: live,
+        updated_utc: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(id: &str) -> GatewayInstance {
+        GatewayInstance { id: id.to_string(), url: format!("http://{}:3034", id) }
+    }
+
+    fn config(id: &str, enabled: bool) -> ShardConfig {
+        ShardConfig { enabled, instance: instance(id), vnodes: DEFAULT_VNODES, ttl: Duration::from_secs(5) }
+    }
+
+    fn map(epoch: u64, ids: &[&str]) -> ShardMap {
+        ShardMap {
+            epoch,
+            vnodes: DEFAULT_VNODES,
+            instances: ids.iter().map(|id| instance(id)).collect(),
+            updated_utc: String::new(),
+        }
+    }
+
+    fn round(map: Option<ShardMap>) -> MembershipRound {
+        MembershipRound { coordinator: false, map, published: false }
+    }
+
+    #[test]
+    fn only_newer_epochs_replace_the_map() {
+        let mut shards = Shards::new(config("gw-a", true));
+        shards.apply(round(Some(map(2, &["gw-a", "gw-b"]))));
+        shards.apply(round(Some(map(1, &["gw-b"]))));
+        shards.apply(round(Some(map(2, &["gw-b"]))));
+        assert_eq!(shards.status().map.unwrap().instances.len(), 2, "older or equal epochs are ignored");
+
+        shards.apply(MembershipRound { coordinator: true, ..round(None) });
+        let status = shards.status();
+        assert!(status.coordinator);
+        assert_eq!(status.map.unwrap().epoch, 2, "a round without a map keeps the current one");
+
+        shards.apply(round(Some(map(3, &["gw-b"]))));
+        assert_eq!(shards.status().map.unwrap().epoch, 3);
+        assert_eq!(shards.owner(101).unwrap().id, "gw-b");
+    }
+
+    #[test]
+    fn orders_for_accounts_owned_elsewhere_are_redirected() {
+        let mut shards = Shards::new(config("gw-a", true));
+        assert_eq!(shards.check_owner(101).unwrap_err().code, RejectCode::SystemNotReady);
+
+        shards.apply(round(Some(map(1, &["gw-a", "gw-b"]))));
+        let owned_here = (0..100).find(|account| shards.owner(*account).unwrap().id == "gw-a").unwrap();
+        let owned_elsewhere = (0..100).find(|account| shards.owner(*account).unwrap().id == "gw-b").unwrap();
+        assert_eq!(shards.check_owner(owned_here), Ok(()));
+        let error = shards.check_owner(owned_elsewhere).unwrap_err();
+        assert_eq!(error.code, RejectCode::SystemWrongShard);
+        assert!(error.message.ends_with("served by risk gateway gw-b (http://gw-b:3034)"), "{}", error.message);
+
+        let unsharded = Shards::new(config("gw-a", false));
+        assert_eq!(unsharded.check_owner(owned_elsewhere), Ok(()), "without sharding every account is local");
+    }
+
+    #[test]
+    fn a_new_map_is_published_only_when_membership_changes() {
+        let first = next_map(None, vec![instance("gw-a")], DEFAULT_VNODES).unwrap();
+        assert_eq!(first.epoch, 1);
+        assert!(next_map(Some(&first), vec![instance("gw-a")], DEFAULT_VNODES).is_none());
+        assert!(next_map(None, Vec::new(), DEFAULT_VNODES).is_none(), "an empty set is never published");
+        assert!(next_map(Some(&first), Vec::new(), DEFAULT_VNODES).is_none());
+
+        let joined = next_map(Some(&first), vec![instance("gw-a"), instance("gw-b")], DEFAULT_VNODES).unwrap();
+        assert_eq!((joined.epoch, joined.instances.len()), (2, 2));
+        let resized = next_map(Some(&joined), joined.instances.clone(), 8).unwrap();
+        assert_eq!((resized.epoch, resized.vnodes), (3, 8));
+    }
+
+    #[test]
+    fn live_instances_are_deduplicated_by_id_and_sorted() {
+        let json = |instance: &GatewayInstance| serde_json::to_string(instance).unwrap();
+        let moved = GatewayInstance { url: "http://gw-a:4000".to_string(), ..instance("gw-a") };
+        let members = vec![
+            (json(&instance("gw-b")), 2_000.0),
+            (json(&moved), 3_000.0),
+            ("not an instance".to_string(), 3_000.0),
+            (json(&instance("gw-a")), 1_000.0),
+        ];
+        assert_eq!(live_instances(members), [moved, instance("gw-b")]);
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Platform Snapshots
+ *
+ * File: src/risk_compliance/risk_gateway/snapshot.rs
+ *
+ * Description:
+ * Versioned archives of the platform's trading state, for disaster recovery
+ * and for cloning an environment (e.g. production into staging). A snapshot
+ * holds three sections:
+ *
+ *   risk          the risk gateway's Redis keys: account limits and
+ *                 exposures, concentration and counterparty limits, and the
+ *                 kill switch as it was before the snapshot
+ *   portfolio     the portfolio manager's book (GET /portfolio/state)
+ *   open_orders   the exchange gateway's open orders (GET /orders/open)
+ *
+ * The admin API coordinates the capture: it engages the kill switch, waits
+ * for in-flight orders and fills to settle, reads the three sections and
+ * puts the kill switch back, so the sections agree with each other.
+ * Restoring writes every section back and leaves the kill switch engaged
+ * until an operator has reviewed the restored state.
+ *
+ * Archives are JSON files named `{snapshot_id}.json` in QA_SNAPSHOT_DIR;
+ * `format_version` is bumped whenever a section changes shape, and archives
+ * of another version are refused.
+ *
+ * Configuration (environment):
+ *   QA_SNAPSHOT_DIR=snapshots        archive directory
+ *   QA_SNAPSHOT_QUIESCE_MS=500       wait between halting and capturing
+ */
+
+use quantumarb_openapi::ApiSchema;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::time::Duration;
+
+/// Version of the archive layout written by this build.
+pub const FORMAT_VERSION: u32 = 1;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    pub dir: PathBuf,
+    pub quiesce: Duration,
+}
+
+impl SnapshotConfig {
+    pub fn from_env() -> SnapshotConfig {
+        let quiesce_ms = std::env::var("QA_SNAPSHOT_QUIESCE_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(500);
+        SnapshotConfig {
+            dir: PathBuf::from(std::env::var("QA_SNAPSHOT_DIR").unwrap_or_else(|_| "snapshots".to_string())),
+            quiesce: Duration::from_millis(quiesce_ms),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+/// A complete archive, as stored on disk and served on GET /snapshots/{id}.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct PlatformSnapshot {
+    pub format_version: u32,
+    pub snapshot_id: String,
+    pub label: String,
+    pub created_utc: String,
+    pub risk: RiskSection,
+    /// The portfolio manager's PortfolioState, passed through as-is.
+    pub portfolio: Value,
+    /// The exchange gateway's open orders, passed through as-is.
+    pub open_orders: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct RiskSection {
+    /// Redis key -> its JSON value.
+    pub redis_keys: BTreeMap<String, Value>,
+}
+
+/// One line of GET /snapshots.
+#[derive(Debug, Serialize, ApiSchema)]
+pub struct SnapshotSummary {
+    pub snapshot_id: String,
+    pub label: String,
+    pub created_utc: String,
+    pub format_version: u32,
+    pub accounts: usize,
+    pub open_orders: usize,
+}
+
+/// Body of a POST /snapshots request.
+#[derive(Debug, Default, Deserialize, ApiSchema)]
+pub struct CreateSnapshotRequest {
+    pub label: Option<String>,
+}
+
+/// Outcome of restoring one section.
+#[derive(Debug, Serialize, ApiSchema)]
+pub struct SectionResult {
+    pub section: String,
+    pub restored: bool,
+    pub detail: String,
+}
+
+/// Response of POST /snapshots/{id}/restore.
+#[derive(Debug, Serialize, ApiSchema)]
+pub struct RestoreReport {
+    pub snapshot_id: String,
+    pub sections: Vec<SectionResult>,
+    /// Trading stays halted until an operator releases the kill switch.
+    pub kill_switch_engaged: bool,
+    pub restored_utc: String,
+}
+
+impl PlatformSnapshot {
+    pub fn summary(&self) -> SnapshotSummary {
+        SnapshotSummary {
+            snapshot_id: self.snapshot_id.clone(),
+            label: self.label.clone(),
+            created_utc: self.created_utc.clone(),
+            format_version: self.format_version,
+            accounts: self.risk.redis_keys.keys().filter(|k| k.starts_with("account:")).count(),
+            open_orders: self.open_orders.as_array().map_or(0, |orders| orders.len()),
+        }
+    }
+}
+
+/// A new snapshot id, e.g. "snap-20250102T030405Z".
+pub fn new_snapshot_id() -> String {
+    format!("snap-{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Snapshot ids become file names, so only letters, digits, '-' and '_'.
+pub fn is_valid_id(snapshot_id: &str) -> bool {
+    !snapshot_id.is_empty()
+        && snapshot_id.len() <= 64
+        && snapshot_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// --- Archive Storage ---
+
+fn archive_path(config: &SnapshotConfig, snapshot_id: &str) -> PathBuf {
+    config.dir.join(format!("{}.json", snapshot_id))
+}
+
+pub fn exists(config: &SnapshotConfig, snapshot_id: &str) -> bool {
+    archive_path(config, snapshot_id).exists()
+}
+
+pub fn save(config: &SnapshotConfig, snapshot: &PlatformSnapshot) -> Result<(), String> {
+    std::fs::create_dir_all(&config.dir).map_err(|e| format!("cannot create {}: {}", config.dir.display(), e))?;
+    let path = archive_path(config, &snapshot.snapshot_id);
+    // Write then rename, so a crash never leaves a half-written archive behind.
+    let partial = path.with_extension("json.partial");
+    let json = serde_json::to_vec_pretty(snapshot).map_err(|e| e.to_string())?;
+    std::fs::write(&partial, json).map_err(|e| format!("cannot write {}: {}", partial.display(), e))?;
+    std::fs::rename(&partial, &path).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// Loads an archive; Ok(None) if there is none with that id.
+pub fn load(config: &SnapshotConfig, snapshot_id: &str) -> Result<Option<PlatformSnapshot>, String> {
+    let path = archive_path(config, snapshot_id);
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    serde_json::from_slice(&bytes).map(Some).map_err(|e| format!("{} is not a snapshot archive: {}", path.display(), e))
+}
+
+/// Every readable archive, newest first.
+pub fn list(config: &SnapshotConfig) -> Vec<SnapshotSummary> {
+    let Ok(entries) = std::fs::read_dir(&config.dir) else {
+        return Vec::new();
+    };
+    let mut summaries: Vec<SnapshotSummary> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let snapshot_id = name.strip_suffix(".json")?;
+            load(config, snapshot_id).ok().flatten().map(|snapshot| snapshot.summary())
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.created_utc.cmp(&a.created_utc));
+    summaries
+}
+
+// --- Capture and Restore ---
+
+/// A Redis value as stored in the archive; values that are not JSON are kept
+/// as strings.
+pub fn redis_value(raw: String) -> Value {
+    serde_json::from_str(&raw).unwrap_or(Value::String(raw))
+}
+
+/// Reads every key matching `patterns` (expanded with KEYS).
+pub async fn capture_risk(con: &mut impl AsyncCommands, patterns: &[&str]) -> Result<RiskSection, String> {
+    let mut redis_keys = BTreeMap::new();
+    for pattern in patterns {
+        let keys: Vec<String> = con.keys(pattern).await.map_err(|e| format!("KEYS {}: {}", pattern, e))?;
+        for key in keys {
+            let raw: Option<String> = con.get(&key).await.map_err(|e| format!("GET {}: {}", key, e))?;
+            if let Some(raw) = raw {
+                redis_keys.insert(key, redis_value(raw));
+            }
+        }
+    }
+    Ok(RiskSection { redis_keys })
+}
+
+/// Replaces the keys matching `patterns` with the archived ones; keys the
+/// archive does not have are deleted. `held_key` (the kill switch, which the
+/// caller holds during a restore) is left alone.
+pub async fn restore_risk(
+    con: &mut impl AsyncCommands,
+    risk: &RiskSection,
+    patterns: &[&str],
+    held_key: &str,
+) -> Result<usize, String> {
+    for pattern in patterns {
+        let keys: Vec<String> = con.keys(pattern).await.map_err(|e| format!("KEYS {}: {}", pattern, e))?;
+        for key in keys.iter().filter(|k| *k != held_key && !risk.redis_keys.contains_key(*k)) {
+            let _: () = con.del(key).await.map_err(|e| format!("DEL {}: {}", key, e))?;
+        }
+    }
+    let mut written = 0;
+    for (key, value) in risk.redis_keys.iter().filter(|(k, _)| *k != held_key) {
+        let raw = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        let _: () = con.set(key, raw).await.map_err(|e| format!("SET {}: {}", key, e))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// GETs a section from another service.
+pub async fn fetch_section(client: &reqwest::Client, url: &str) -> Result<Value, String> {
+    let response = client.get(url).send().await.map_err(|e| format!("{}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{}: HTTP {}", url, response.status()));
+    }
+    response.json().await.map_err(|e| format!("{}: {}", url, e))
+}
+
+/// PUTs a section back to its service.
+pub async fn put_section(client: &reqwest::Client, url: &str, section: &Value) -> Result<(), String> {
+    let response = client.put(url).json(section).send().await.map_err(|e| format!("{}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{}: HTTP {}", url, response.status()));
+    }
+    Ok(())
+}
+
+/// Turns the outcome of restoring a section into its report line.
+pub fn section_result(section: &str, outcome: Result<String, String>) -> SectionResult {
+    let (restored, detail) = match outcome {
+        Ok(detail) => (true, detail),
+        Err(e) => (false, e),
+    };
+    SectionResult { section: section.to_string(), restored, detail }
+}
@@ -0,0 +1,406 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Redis Topologies and Degraded Mode
+ *
+ * File: src/risk_compliance/risk_gateway/store.rs
+ *
+ * Description:
+ * Every pre-trade check reads the risk state (account limits and exposure,
+ * concentration and counterparty limits, the kill switch) from Redis, so a
+ * single Redis instance is a single point of failure for all of them. The
+ * gateway connects to one of three topologies:
+ *
+ *   single     one instance (QA_REDIS_URL)
+ *   sentinel   a master watched by Redis Sentinel (QA_REDIS_SENTINELS and
+ *              QA_REDIS_SENTINEL_MASTER). After a failover the Sentinels
+ *              are asked for the new master and the gateway reconnects to it
+ *   cluster    a Redis Cluster (QA_REDIS_CLUSTER_NODES). The cluster client
+ *              follows slot moves and promoted replicas itself
+ *
+ * A command that fails on a broken connection (I/O error, timeout, or a
+ * READONLY reply from a master demoted under it) drops the connection; the
+ * command is retried once on a fresh one. While reconnecting keeps failing
+ * Redis is DOWN: commands fail at once instead of each waiting out a
+ * connect timeout, and a reconnect is attempted at most every
+ * QA_REDIS_RETRY_MS.
+ *
+ * Degraded mode: the store keeps the last value the pre-trade checks read
+ * for each key. While Redis is down the checks run against that copy with
+ * every limit multiplied by QA_REDIS_DEGRADED_LIMIT_FACTOR. A copy older
+ * than QA_REDIS_DEGRADED_MAX_AGE_SECS, or a key never read, is not used and
+ * the order is rejected (SYSTEM_STATE_UNAVAILABLE). Admin writes fail while
+ * Redis is down; they are not queued.
+ *
+ * Versioned writes: with several gateway instances (see `shards.rs`) two
+ * of them can update the same JSON value at once. `set_if_version` writes a
+ * value only if the stored one still carries the `version` field it was
+ * read at, checked and written in one Lua script; the caller re-reads and
+ * retries when it was not.
+ *
+ * Configuration (environment):
+ *   QA_REDIS_URL=redis://127.0.0.1/       single instance
+ *   QA_REDIS_SENTINELS                    "redis://host:26379,..."; selects sentinel
+ *   QA_REDIS_SENTINEL_MASTER=mymaster     master name the Sentinels monitor
+ *   QA_REDIS_CLUSTER_NODES                "redis://host:6379,..."; selects cluster
+ *   QA_REDIS_TIMEOUT_MS=250               connect and response timeout
+ *   QA_REDIS_RETRY_MS=1000                time between reconnect attempts
+ *   QA_REDIS_DEGRADED_LIMIT_FACTOR=0.5    limit multiplier while degraded
+ *   QA_REDIS_DEGRADED_MAX_AGE_SECS=300    oldest copy used while degraded
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_openapi::ApiSchema;
+use redis::aio::ConnectionLike;
+use redis::cluster::ClusterClientBuilder;
+use redis::sentinel::Sentinel;
+use redis::{Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// SETs KEYS[1] to ARGV[2] if its JSON `version` (0 when absent) is ARGV[1].
+const SET_IF_VERSION_SCRIPT: &str = r"
+local current = redis.call('GET', KEYS[1])
+if not current then
+    return 0
+end
+if (cjson.decode(current)['version'] or 0) ~= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2])
+return 1
+";
+
+/// Default Redis; QA_REDIS_URL overrides it.
+const REDIS_URL: &str = "redis://127.0.0.1/";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedisTopology {
+    Single(String),
+    Sentinel { sentinels: Vec<String>, master: String },
+    Cluster(Vec<String>),
+}
+
+impl RedisTopology {
+    /// Sentinel if QA_REDIS_SENTINELS is set, else cluster if
+    /// QA_REDIS_CLUSTER_NODES is, else the single QA_REDIS_URL.
+    pub fn from_env() -> RedisTopology {
+        let list = |name: &str| -> Vec<String> {
+            let value = std::env::var(name).unwrap_or_default();
+            value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+        };
+        let sentinels = list("QA_REDIS_SENTINELS");
+        if !sentinels.is_empty() {
+            let master = std::env::var("QA_REDIS_SENTINEL_MASTER").unwrap_or_else(|_| "mymaster".to_string());
+            return RedisTopology::Sentinel { sentinels, master };
+        }
+        let nodes = list("QA_REDIS_CLUSTER_NODES");
+        if !nodes.is_empty() {
+            return RedisTopology::Cluster(nodes);
+        }
+        RedisTopology::Single(std::env::var("QA_REDIS_URL").unwrap_or_else(|_| REDIS_URL.to_string()))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RedisTopology::Single(url) => format!("single ({})", url),
+            RedisTopology::Sentinel { sentinels, master } => {
+                format!("sentinel (master '{}' via {})", master, sentinels.join(", "))
+            }
+            RedisTopology::Cluster(nodes) => format!("cluster ({})", nodes.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoreConfig {
+    pub topology: RedisTopology,
+    pub timeout: Duration,
+    pub retry_interval: Duration,
+    pub degraded_limit_factor: f64,
+    pub degraded_max_age: Duration,
+}
+
+impl StoreConfig {
+    pub fn from_env() -> StoreConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        StoreConfig {
+            topology: RedisTopology::from_env(),
+            timeout: Duration::from_millis(var("QA_REDIS_TIMEOUT_MS", 250u64).max(1)),
+            retry_interval: Duration::from_millis(var("QA_REDIS_RETRY_MS", 1_000u64)),
+            degraded_limit_factor: var("QA_REDIS_DEGRADED_LIMIT_FACTOR", 0.5f64).clamp(0.0, 1.0),
+            degraded_max_age: Duration::from_secs(var("QA_REDIS_DEGRADED_MAX_AGE_SECS", 300u64)),
+        }
+    }
+}
+
+// --- Store ---
+
+enum Connector {
+    Single(redis::Client),
+    Sentinel { sentinel: Sentinel, master: String },
+    Cluster(redis::cluster::ClusterClient),
+}
+
+/// A value read for a pre-trade check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckRead {
+    pub value: Option<String>,
+    /// Served from the local copy because Redis is down.
+    pub cached: bool,
+}
+
+/// Body of GET /redis.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct StoreStatus {
+    pub topology: String,
+    pub connected: bool,
+    /// Set while Redis is down and the pre-trade checks run degraded.
+    pub down_since_utc: Option<String>,
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    pub degraded_limit_factor: f64,
+    pub degraded_max_age_secs: u64,
+    pub cached_keys: usize,
+}
+
+/// The gateway's Redis connection. It implements `ConnectionLike`, so every
+/// `AsyncCommands` call goes through the failover handling.
+pub struct RedisStore {
+    config: StoreConfig,
+    connector: Connector,
+    connection: Option<Box<dyn ConnectionLike + Send>>,
+    /// No reconnect is attempted before this.
+    next_attempt: Option<Instant>,
+    down_since: Option<chrono::DateTime<chrono::Utc>>,
+    reconnects: u64,
+    last_error: Option<String>,
+    /// The last value the pre-trade checks read per key, and when.
+    cache: HashMap<String, (Option<String>, Instant)>,
+}
+
+enum Request<'a> {
+    Command(&'a Cmd),
+    Pipeline(&'a Pipeline, usize, usize),
+}
+
+impl RedisStore {
+    pub fn new(config: StoreConfig) -> RedisResult<RedisStore> {
+        let connector = match &config.topology {
+            RedisTopology::Single(url) => Connector::Single(redis::Client::open(url.as_str())?),
+            RedisTopology::Sentinel { sentinels, master } => Connector::Sentinel {
+                sentinel: Sentinel::build(sentinels.iter().map(String::as_str).collect())?,
+                master: master.clone(),
+            },
+            RedisTopology::Cluster(nodes) => Connector::Cluster(
+                ClusterClientBuilder::new(nodes.iter().map(String::as_str).collect::<Vec<_>>())
+                    .connection_timeout(config.timeout)
+                    .response_timeout(config.timeout)
+                    .build()?,
+            ),
+        };
+        Ok(RedisStore {
+            config,
+            connector,
+            connection: None,
+            next_attempt: None,
+            down_since: None,
+            reconnects: 0,
+            last_error: None,
+            cache: HashMap::new(),
+        })
+    }
+
+    pub fn degraded_limit_factor(&self) -> f64 {
+        self.config.degraded_limit_factor
+    }
+
+    pub fn status(&self) -> StoreStatus {
+        StoreStatus {
+            topology: self.config.topology.describe(),
+            connected: self.connection.is_some(),
+            down_since_utc: self.down_since.map(|t| t.to_rfc3339()),
+            reconnects: self.reconnects,
+            last_error: self.last_error.clone(),
+            degraded_limit_factor: self.config.degraded_limit_factor,
+            degraded_max_age_secs: self.config.degraded_max_age.as_secs(),
+            cached_keys: self.cache.len(),
+        }
+    }
+
+    /// Writes `json` to `key` if the stored value is still at `read_version`.
+    /// False if the key is gone or another write got there first.
+    pub async fn set_if_version(&mut self, key: &str, read_version: u64, json: &str) -> RedisResult<bool> {
+        let written: i32 =
+            redis::Script::new(SET_IF_VERSION_SCRIPT).key(key).arg(read_version).arg(json).invoke_async(self).await?;
+        Ok(written == 1)
+    }
+
+    /// GETs `key` for a pre-trade check, from the local copy while Redis is
+    /// down. Errors other than a lost connection read as a missing key.
+    pub async fn read_for_check(&mut self, key: &str) -> Result<CheckRead, Rejection> {
+        match redis::cmd("GET").arg(key).query_async::<_, Option<String>>(self).await {
+            Ok(value) => {
+                self.cache.insert(key.to_string(), (value.clone(), Instant::now()));
+                Ok(CheckRead { value, cached: false })
+            }
+            Err(e) if is_connection_error(&e) => match self.cache.get(key) {
+                Some((value, read_at)) if read_at.elapsed() <= self.config.degraded_max_age => {
+                    Ok(CheckRead { value: value.clone(), cached: true })
+                }
+                _ => Err(Rejection::new(
+                    RejectCode::SystemStateUnavailable,
+                    format!("Redis is unreachable and there is no recent local copy of '{}'", key),
+                )),
+            },
+            Err(_) => Ok(CheckRead { value: None, cached: false }),
+        }
+    }
+
+    async fn connect(&mut self) -> RedisResult<Box<dyn ConnectionLike + Send>> {
+        let timeout = self.config.timeout;
+        let attempt = async {
+            let connection: Box<dyn ConnectionLike + Send> = match &mut self.connector {
+                Connector::Single(client) => {
+                    Box::new(client.get_multiplexed_async_connection_with_timeouts(timeout, timeout).await?)
+                }
+                Connector::Sentinel { sentinel, master } => {
+                    let client = sentinel.async_master_for(master, None).await?;
+                    Box::new(client.get_multiplexed_async_connection_with_timeouts(timeout, timeout).await?)
+                }
+                Connector::Cluster(client) => Box::new(client.get_async_connection().await?),
+            };
+            Ok(connection)
+        };
+        match tokio::time::timeout(timeout, attempt).await {
+            Ok(result) => result,
+            Err(_) => Err(RedisError::from((ErrorKind::IoError, "Redis connect timed out"))),
+        }
+    }
+
+    /// The live connection, reconnecting if it was dropped and the retry
+    /// interval has passed.
+    async fn connection(&mut self) -> RedisResult<&mut Box<dyn ConnectionLike + Send>> {
+        if self.connection.is_none() {
+            if self.next_attempt.is_some_and(|at| Instant::now() < at) {
+                return Err(RedisError::from((ErrorKind::IoError, "Redis is down")));
+            }
+            match self.connect().await {
+                Ok(connection) => {
+                    if let Some(since) = self.down_since.take() {
+                        println!("Redis reconnected ({}); down since {}.", self.config.topology.describe(), since);
+                    }
+                    self.reconnects += 1;
+                    self.next_attempt = None;
+                    self.connection = Some(connection);
+                }
+                Err(e) => {
+                    self.mark_down(&e);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(self.connection.as_mut().expect("connected above"))
+    }
+
+    fn mark_down(&mut self, error: &RedisError) {
+        self.connection = None;
+        self.next_attempt = Some(Instant::now() + self.config.retry_interval);
+        self.last_error = Some(error.to_string());
+        if self.down_since.is_none() {
+            println!("\nRedis DOWN ({}): {}. Pre-trade checks run degraded.", self.config.topology.describe(), error);
+            self.down_since = Some(chrono::Utc::now());
+        }
+    }
+
+    async fn send(&mut self, request: Request<'_>) -> RedisResult<Vec<Value>> {
+        let mut retried = false;
+        loop {
+            let connection = self.connection().await?;
+            let result = match request {
+                Request::Command(cmd) => connection.req_packed_command(cmd).await.map(|value| vec![value]),
+                Request::Pipeline(pipeline, offset, count) => {
+                    connection.req_packed_commands(pipeline, offset, count).await
+                }
+            };
+            match result {
+                Err(e) if is_connection_error(&e) => {
+                    // Drop the connection; the retry reconnects (to the new
+                    // master, under Sentinel) without waiting for the interval.
+                    self.connection = None;
+                    if retried {
+                        self.mark_down(&e);
+                        return Err(e);
+                    }
+                    self.last_error = Some(e.to_string());
+                    retried = true;
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+impl ConnectionLike for RedisStore {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let mut values = self.send(Request::Command(cmd)).await?;
+            Ok(values.pop().unwrap_or(Value::Nil))
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(self.send(Request::Pipeline(pipeline, offset, count)))
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+/// Whether the error means the connection (or the node behind it) is gone,
+/// rather than a bad command.
+fn is_connection_error(e: &RedisError) -> bool {
+    e.is_io_error()
+        || e.is_timeout()
+        || e.is_connection_dropped()
+        || e.is_connection_refusal()
+        || matches!(e.kind(), ErrorKind::ReadOnly | ErrorKind::MasterDown | ErrorKind::ClusterDown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_the_local_copy_while_down_until_it_is_too_old() {
+        // Nothing listens on port 1: every connect fails at once.
+        let config = StoreConfig {
+            topology: RedisTopology::Single("redis://127.0.0.1:1/".to_string()),
+            timeout: Duration::from_millis(100),
+            retry_interval: Duration::from_secs(60),
+            degraded_limit_factor: 0.5,
+            degraded_max_age: Duration::from_secs(300),
+        };
+        let mut store = RedisStore::new(config).unwrap();
+        let rejection = store.read_for_check("account:101").await.unwrap_err();
+        assert_eq!(rejection.code, RejectCode::SystemStateUnavailable);
+        assert!(store.status().down_since_utc.is_some());
+
+        let value = Some("{\"account_id\":101}".to_string());
+        store.cache.insert("account:101".to_string(), (value.clone(), Instant::now()));
+        assert_eq!(store.read_for_check("account:101").await, Ok(CheckRead { value, cached: true }));
+
+        let stale = Instant::now() - Duration::from_secs(301);
+        store.cache.insert("account:101".to_string(), (None, stale));
+        assert!(store.read_for_check("account:101").await.is_err());
+    }
+}
@@ -0,0 +1,248 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Intraday Limit Schedule
+ *
+ * File: src/risk_compliance/risk_gateway/time_limits.rs
+ *
+ * Description:
+ * Tightens account and symbol limits at the times of day when prices are
+ * most likely to gap: just after the open, just before the close and around
+ * scheduled economic releases. A time profile names the session's open and
+ * close (UTC), how long each window lasts and the factor applied to limits
+ * inside it. The default halves limits in the first and last 5 minutes of
+ * the session and from 5 minutes before to 5 minutes after each release.
+ *
+ * Releases come from the data bus connector's economic calendar adapter, on
+ * 'calendar.event_windows', and may name the symbols they move; one naming
+ * none moves every symbol. The gateway draws its own windows around the
+ * release time. A symbol can keep its own profile, e.g. a future that
+ * trades different hours; it is then tightened only by its own windows.
+ *
+ * The gateway's scheduler works out the open windows every second. While
+ * any is open for an order's symbol, the order is checked against the
+ * account's order size and exposure limits and the symbol's concentration
+ * cap times the lowest factor among them; overlapping windows do not
+ * compound.
+ *
+ * The schedule is kept in Redis and set through the admin API
+ * (/limit-schedule).
+ */
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_types::EventWindow;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// --- Schedule ---
+
+/// When a symbol's limits are tightened, and by how much.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct TimeProfile {
+    pub open_utc: NaiveTime,
+    /// Before `open_utc` for a session that runs through midnight.
+    pub close_utc: NaiveTime,
+    pub after_open_minutes: u32,
+    pub before_close_minutes: u32,
+    /// Factor on limits in the open and close windows.
+    pub session_factor: f64,
+    pub before_event_minutes: u32,
+    pub after_event_minutes: u32,
+    /// Factor on limits around an economic release.
+    pub event_factor: f64,
+}
+
+impl Default for TimeProfile {
+    fn default() -> Self {
+        TimeProfile {
+            open_utc: NaiveTime::from_hms_opt(13, 30, 0).unwrap(),
+            close_utc: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+            after_open_minutes: 5,
+            before_close_minutes: 5,
+            session_factor: 0.5,
+            before_event_minutes: 5,
+            after_event_minutes: 5,
+            event_factor: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct LimitSchedule {
+    /// The profile of every symbol without one of its own.
+    pub profile: TimeProfile,
+    #[serde(default)]
+    pub symbols: BTreeMap<String, TimeProfile>,
+}
+
+impl LimitSchedule {
+    /// Errs with the reason the schedule cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        let profiles = std::iter::once(("default", &self.profile))
+            .chain(self.symbols.iter().map(|(symbol, profile)| (symbol.as_str(), profile)));
+        for (name, profile) in profiles {
+            let factors = [profile.session_factor, profile.event_factor];
+            if factors.iter().any(|factor| !(*factor > 0.0 && *factor <= 1.0)) {
+                return Err(format!("The {} profile's factors must be in (0, 1].", name));
+            }
+            if profile.open_utc == profile.close_utc {
+                return Err(format!("The {} profile's session opens and closes at the same time.", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// --- Windows ---
+
+/// A stretch of time in which limits are tightened.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct LimitWindow {
+    /// "open", "close" or the release's name.
+    pub reason: String,
+    pub from_utc: DateTime<Utc>,
+    pub until_utc: DateTime<Utc>,
+    pub factor: f64,
+    /// Symbols tightened; every symbol on the default profile when empty.
+    pub symbols: Vec<String>,
+}
+
+/// What the scheduler found at its last run.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct ScheduleStatus {
+    pub evaluated_utc: Option<DateTime<Utc>>,
+    pub open_windows: Vec<LimitWindow>,
+    /// Symbols on a profile of their own.
+    pub own_profiles: BTreeSet<String>,
+    /// Releases on the economic calendar.
+    pub calendar: Vec<EventWindow>,
+}
+
+impl ScheduleStatus {
+    /// Factor on the limits of orders in `symbol`: the lowest among the open
+    /// windows covering it, or 1.
+    pub fn factor(&self, symbol: &str) -> f64 {
+        let on_default = !self.own_profiles.contains(symbol);
+        self.open_windows
+            .iter()
+            .filter(|window| {
+                if window.symbols.is_empty() {
+                    on_default
+                } else {
+                    window.symbols.iter().any(|s| s == symbol)
+                }
+            })
+            .map(|window| window.factor)
+            .fold(1.0, f64::min)
+    }
+}
+
+/// The windows of `schedule` open at `now`, given the releases in `calendar`.
+pub fn evaluate(schedule: &LimitSchedule, calendar: &[EventWindow], now: DateTime<Utc>) -> ScheduleStatus {
+    let minutes = |m: u32| Duration::minutes(m as i64);
+    let profiles = std::iter::once((None, &schedule.profile))
+        .chain(schedule.symbols.iter().map(|(symbol, profile)| (Some(symbol), profile)));
+    let mut windows = Vec::new();
+    for (symbol, profile) in profiles {
+        let scope: Vec<String> = symbol.cloned().into_iter().collect();
+        let window = |reason: &str, from_utc, until_utc, factor, symbols: Vec<String>| LimitWindow {
+            reason: reason.to_string(),
+            from_utc,
+            until_utc,
+            factor,
+            symbols,
+        };
+        // Yesterday's session as well, for one that runs through midnight.
+        for date in [now.date_naive() - Duration::days(1), now.date_naive()] {
+            let open = date.and_time(profile.open_utc).and_utc();
+            let mut close = date.and_time(profile.close_utc).and_utc();
+            if close <= open {
+                close += Duration::days(1);
+            }
+            let after_open = open + minutes(profile.after_open_minutes);
+            let before_close = close - minutes(profile.before_close_minutes);
+            windows.push(window("open", open, after_open, profile.session_factor, scope.clone()));
+            windows.push(window("close", before_close, close, profile.session_factor, scope.clone()));
+        }
+        for event in calendar {
+            let symbols = match symbol {
+                Some(symbol) if event.symbols.is_empty() || event.symbols.contains(symbol) => scope.clone(),
+                Some(_) => continue,
+                None if event.symbols.is_empty() => Vec::new(),
+                None => {
+                    let on_default: Vec<String> =
+                        event.symbols.iter().filter(|s| !schedule.symbols.contains_key(*s)).cloned().collect();
+                    if on_default.is_empty() {
+                        continue;
+                    }
+                    on_default
+                }
+            };
+            let from = event.scheduled_utc - minutes(profile.before_event_minutes);
+            let until = event.scheduled_utc + minutes(profile.after_event_minutes);
+            windows.push(window(&event.name, from, until, profile.event_factor, symbols));
+        }
+    }
+    windows.retain(|window| window.from_utc <= now && now < window.until_utc && window.factor < 1.0);
+    ScheduleStatus {
+        evaluated_utc: Some(now),
+        open_windows: windows,
+        own_profiles: schedule.symbols.keys().cloned().collect(),
+        calendar: calendar.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use quantumarb_types::EconomicEventKind;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn limits_tighten_after_the_open_before_the_close_and_around_releases() {
+        let mut schedule = LimitSchedule::default();
+        // BTC trades around the clock: its session is midnight to midnight.
+        let btc = TimeProfile { open_utc: NaiveTime::MIN, close_utc: NaiveTime::MIN, ..TimeProfile::default() };
+        schedule.symbols.insert("BTC".to_string(), btc);
+        assert!(schedule.validate().is_err(), "a session must open and close at different times");
+        schedule.symbols.get_mut("BTC").unwrap().close_utc = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+        assert!(schedule.validate().is_ok());
+
+        let release = |kind, name: &str, scheduled_utc, symbols: &[&str]| EventWindow {
+            event_id: name.to_string(),
+            kind,
+            name: name.to_string(),
+            scheduled_utc,
+            starts_utc: scheduled_utc,
+            ends_utc: scheduled_utc,
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+        };
+        let payrolls = release(EconomicEventKind::Nfp, "Nonfarm payrolls", at(12, 30), &[]);
+        let cpi = release(EconomicEventKind::Cpi, "CPI", at(16, 0), &["ES"]);
+        let calendar = [payrolls, cpi];
+        let factor = |now, symbol| evaluate(&schedule, &calendar, now).factor(symbol);
+
+        assert_eq!(factor(at(13, 31), "ES"), 0.5, "the first 5 minutes of the session");
+        assert_eq!(factor(at(13, 35), "ES"), 1.0);
+        assert_eq!(factor(at(19, 56), "ES"), 0.5, "the last 5 minutes of the session");
+        assert_eq!(factor(at(13, 31), "BTC"), 1.0, "BTC keeps its own session");
+        assert_eq!(factor(at(0, 2), "BTC"), 0.5);
+        assert_eq!(factor(at(23, 57), "BTC"), 0.5);
+
+        // A release naming no symbols moves every one; CPI moves ES only.
+        assert_eq!(factor(at(12, 26), "ES"), 0.5);
+        assert_eq!(factor(at(12, 34), "BTC"), 0.5);
+        assert_eq!(factor(at(12, 35), "ES"), 1.0);
+        assert_eq!(factor(at(15, 57), "ES"), 0.5);
+        assert_eq!(factor(at(15, 57), "NQ"), 1.0);
+
+        // Overlapping windows do not compound.
+        schedule.profile.after_event_minutes = 70;
+        let status = evaluate(&schedule, &calendar, at(13, 32));
+        assert_eq!(status.open_windows.len(), 2);
+        assert_eq!(status.factor("ES"), 0.5);
+    }
+}
@@ -0,0 +1,333 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Heartbeat Watchdog
+ *
+ * File: src/risk_compliance/risk_gateway/watchdog.rs
+ *
+ * Description:
+ * Watches the heartbeats on 'heartbeats' and acts when one stops. Sources
+ * are the strategies ("strategy:<name>", sent from each trading loop) and
+ * the market data feed ("market_data", sent by the data quality monitor
+ * while ticks are arriving).
+ *
+ * A strategy that goes silent may have crashed with orders working and
+ * positions open; a silent feed means every strategy is trading blind. When
+ * a source trips, each affected strategy's policy decides what happens
+ * through the exchange gateway:
+ *
+ *   CANCEL_ONLY   cancel the open orders in the strategy's symbols
+ *   FLATTEN       cancel them, then send offsetting orders for the positions
+ *                 held in those symbols
+ *
+ * A silent strategy affects only itself; a silent feed affects all of them.
+ * Policies are kept in Redis and set through the admin API
+ * (/flatten-policies). A source trips once per outage and is re-armed by its
+ * next heartbeat; the watchdog never resumes trading on its own.
+ *
+ * Configuration (environment):
+ *   QA_WATCHDOG_STRATEGY_TIMEOUT_MS=15000      strategy silence before tripping
+ *   QA_WATCHDOG_MARKET_DATA_TIMEOUT_MS=5000    feed silence before tripping
+ */
+
+use quantumarb_money::Price;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{ReferenceData, DEFAULT_PRICE_DECIMALS};
+use quantumarb_types::{FlattenOrder, Heartbeat, PortfolioSnapshot};
+use quantumarb_wire::OrderSide;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+pub const MARKET_DATA_SOURCE: &str = "market_data";
+const STRATEGY_PREFIX: &str = "strategy:";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    pub strategy_timeout: Duration,
+    pub market_data_timeout: Duration,
+}
+
+impl WatchdogConfig {
+    pub fn from_env() -> WatchdogConfig {
+        let millis = |name: &str, default: u64| {
+            Duration::from_millis(std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default))
+        };
+        WatchdogConfig {
+            strategy_timeout: millis("QA_WATCHDOG_STRATEGY_TIMEOUT_MS", 15_000),
+            market_data_timeout: millis("QA_WATCHDOG_MARKET_DATA_TIMEOUT_MS", 5_000),
+        }
+    }
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FlattenAction {
+    CancelOnly,
+    Flatten,
+}
+
+/// What to do when a strategy's heartbeat, or the feed's, stops.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct StrategyPolicy {
+    pub action: FlattenAction,
+    /// Symbols the strategy trades; its orders and positions in them are
+    /// what the watchdog cancels or flattens.
+    pub symbols: Vec<String>,
+}
+
+/// Policies by strategy name.
+pub type FlattenPolicies = BTreeMap<String, StrategyPolicy>;
+
+pub fn default_policies() -> FlattenPolicies {
+    let sor = StrategyPolicy { action: FlattenAction::CancelOnly, symbols: vec!["BTC".to_string()] };
+    BTreeMap::from([("sor_arbitrage".to_string(), sor)])
+}
+
+/// A source that has just stopped heart-beating.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Trip {
+    pub source: String,
+    pub silent_for_ms: u64,
+}
+
+/// One line of GET /watchdog.
+#[derive(Debug, Serialize, ApiSchema)]
+pub struct SourceStatus {
+    pub source: String,
+    pub last_heartbeat_ms_ago: u64,
+    pub timeout_ms: u64,
+    pub tripped: bool,
+}
+
+// --- Watchdog ---
+
+pub struct Watchdog {
+    config: WatchdogConfig,
+    last_seen: HashMap<String, Instant>,
+    tripped: HashSet<String>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Watchdog {
+        Watchdog { config, last_seen: HashMap::new(), tripped: HashSet::new() }
+    }
+
+    /// Starts watching `source` as if it had just heart-beaten, so a source
+    /// that never comes up still trips.
+    pub fn watch(&mut self, source: &str, now: Instant) {
+        self.last_seen.entry(source.to_string()).or_insert(now);
+    }
+
+    /// Records a heartbeat. Returns true if the source had tripped and is
+    /// now back.
+    pub fn on_heartbeat(&mut self, heartbeat: &Heartbeat, now: Instant) -> bool {
+        self.last_seen.insert(heartbeat.source.clone(), now);
+        self.tripped.remove(&heartbeat.source)
+    }
+
+    /// Sources that have gone silent since the last check.
+    pub fn check(&mut self, now: Instant) -> Vec<Trip> {
+        let mut trips = Vec::new();
+        for (source, last_seen) in &self.last_seen {
+            let silence = now.duration_since(*last_seen);
+            if silence > self.timeout(source) && self.tripped.insert(source.clone()) {
+                trips.push(Trip { source: source.clone(), silent_for_ms: silence.as_millis() as u64 });
+            }
+        }
+        trips
+    }
+
+    pub fn status(&self, now: Instant) -> Vec<SourceStatus> {
+        let mut status: Vec<SourceStatus> = self
+            .last_seen
+            .iter()
+            .map(|(source, last_seen)| SourceStatus {
+                source: source.clone(),
+                last_heartbeat_ms_ago: now.duration_since(*last_seen).as_millis() as u64,
+                timeout_ms: self.timeout(source).as_millis() as u64,
+                tripped: self.tripped.contains(source),
+            })
+            .collect();
+        status.sort_by(|a, b| a.source.cmp(&b.source));
+        status
+    }
+
+    fn timeout(&self, source: &str) -> Duration {
+        if source == MARKET_DATA_SOURCE {
+            self.config.market_data_timeout
+        } else {
+            self.config.strategy_timeout
+        }
+    }
+}
+
+/// The heartbeat source name of a strategy.
+pub fn strategy_source(strategy: &str) -> String {
+    format!("{}{}", STRATEGY_PREFIX, strategy)
+}
+
+/// The strategy a heartbeat source is; None for the market data feed.
+pub fn source_strategy(source: &str) -> Option<&str> {
+    source.strip_prefix(STRATEGY_PREFIX)
+}
+
+/// The strategies a trip affects, with their policies: the strategy itself,
+/// or every strategy when the feed has gone silent.
+pub fn affected<'a>(source: &str, policies: &'a FlattenPolicies) -> Vec<(&'a String, &'a StrategyPolicy)> {
+    if source == MARKET_DATA_SOURCE {
+        return policies.iter().collect();
+    }
+    let strategy = source.strip_prefix(STRATEGY_PREFIX).unwrap_or(source);
+    policies.get_key_value(strategy).into_iter().collect()
+}
+
+/// Offsetting orders, at the last mark rounded to the tick, for the
+/// positions held in `symbols`.
+pub fn flattening_orders(
+    snapshot: &PortfolioSnapshot,
+    symbols: &[String],
+    instruments: &ReferenceData,
+) -> Vec<FlattenOrder> {
+    snapshot
+        .positions
+        .values()
+        .filter(|position| position.quantity != 0 && symbols.contains(&position.symbol))
+        .map(|position| {
+            let mark = position.current_market_price;
+            let price = match instruments.by_symbol(&position.symbol) {
+                Some(definition) => definition.wire_price(mark),
+                // Not in reference data: the default scale, to its nearest unit.
+                None => {
+                    let unit = Price::from_wire(1, DEFAULT_PRICE_DECIMALS).expect("the default price scale is valid");
+                    mark.round_to(unit).to_wire(DEFAULT_PRICE_DECIMALS).unwrap_or(0)
+                }
+            };
+            FlattenOrder {
+                instrument_symbol: position.symbol.clone(),
+                side: if position.quantity > 0 { OrderSide::Sell } else { OrderSide::Buy },
+                size: position.quantity.unsigned_abs().min(u32::MAX as u64) as u32,
+                price,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_types::Position;
+
+    fn watchdog() -> Watchdog {
+        Watchdog::new(WatchdogConfig {
+            strategy_timeout: Duration::from_millis(15_000),
+            market_data_timeout: Duration::from_millis(5_000),
+        })
+    }
+
+    fn sources(trips: &[Trip]) -> Vec<&str> {
+        let mut sources: Vec<&str> = trips.iter().map(|t| t.source.as_str()).collect();
+        sources.sort();
+        sources
+    }
+
+    #[test]
+    fn a_source_trips_once_its_silence_exceeds_its_timeout() {
+        let start = Instant::now();
+        let mut watchdog = watchdog();
+        watchdog.watch(MARKET_DATA_SOURCE, start);
+        watchdog.watch(&strategy_source("sor_arbitrage"), start);
+
+        assert!(watchdog.check(start + Duration::from_millis(5_000)).is_empty());
+        let trips = watchdog.check(start + Duration::from_millis(5_001));
+        assert_eq!(sources(&trips), [MARKET_DATA_SOURCE]);
+        assert_eq!(trips[0].silent_for_ms, 5_001);
+
+        assert!(watchdog.check(start + Duration::from_millis(15_000)).is_empty());
+        assert_eq!(sources(&watchdog.check(start + Duration::from_millis(15_001))), ["strategy:sor_arbitrage"]);
+        assert!(watchdog.check(start + Duration::from_secs(60)).is_empty(), "one trip per outage");
+    }
+
+    #[test]
+    fn the_next_heartbeat_re_arms_a_tripped_source() {
+        let start = Instant::now();
+        let mut watchdog = watchdog();
+        let source = strategy_source("sor_arbitrage");
+        watchdog.watch(&source, start);
+        assert!(!watchdog.on_heartbeat(&Heartbeat::new(&source), start + Duration::from_secs(10)));
+        assert!(watchdog.check(start + Duration::from_secs(20)).is_empty(), "the heartbeat restarted the clock");
+
+        assert_eq!(watchdog.check(start + Duration::from_secs(26)).len(), 1);
+        assert!(watchdog.status(start + Duration::from_secs(26))[0].tripped);
+        assert!(watchdog.on_heartbeat(&Heartbeat::new(&source), start + Duration::from_secs(30)));
+        assert!(!watchdog.on_heartbeat(&Heartbeat::new(&source), start + Duration::from_secs(31)));
+
+        let status = watchdog.status(start + Duration::from_secs(32));
+        assert_eq!((status[0].last_heartbeat_ms_ago, status[0].timeout_ms, status[0].tripped), (1_000, 15_000, false));
+        assert_eq!(watchdog.check(start + Duration::from_secs(47)).len(), 1, "a second outage trips again");
+    }
+
+    #[test]
+    fn watching_again_does_not_reset_the_clock() {
+        let start = Instant::now();
+        let mut watchdog = watchdog();
+        watchdog.watch(MARKET_DATA_SOURCE, start);
+        watchdog.watch(MARKET_DATA_SOURCE, start + Duration::from_secs(4));
+        assert_eq!(watchdog.check(start + Duration::from_secs(6)).len(), 1);
+    }
+
+    #[test]
+    fn a_silent_feed_affects_every_strategy_and_a_silent_strategy_only_itself() {
+        let mut policies = default_policies();
+        let basis = StrategyPolicy { action: FlattenAction::Flatten, symbols: vec!["ESZ25".to_string()] };
+        policies.insert("basis".to_string(), basis);
+
+        let names = |source: &str| -> Vec<String> {
+            affected(source, &policies).into_iter().map(|(name, _)| name.clone()).collect()
+        };
+        assert_eq!(names(MARKET_DATA_SOURCE), ["basis", "sor_arbitrage"]);
+        assert_eq!(names(&strategy_source("basis")), ["basis"]);
+        assert!(names(&strategy_source("unknown")).is_empty());
+        assert_eq!(source_strategy("strategy:basis"), Some("basis"));
+        assert_eq!(source_strategy(MARKET_DATA_SOURCE), None);
+    }
+
+    #[test]
+    fn flattening_offsets_the_positions_in_the_symbols_at_the_mark_on_the_tick() {
+        let position = |symbol: &str, quantity: i64, mark: f64| Position {
+            symbol: symbol.to_string(),
+            quantity,
+            current_market_price: Price::from_f64(mark),
+            ..Default::default()
+        };
+        let snapshot = PortfolioSnapshot {
+            positions: [
+                position("BTC", 2, 60_000.004),
+                position("ESZ25", -3, 4_500.3),
+                position("XYZ", 5, 12.346),
+                position("INVT", 0, 150.0),
+                position("ETH", 4, 3_000.0),
+            ]
+            .into_iter()
+            .map(|p| (p.symbol.clone(), p))
+            .collect(),
+            ..Default::default()
+        };
+        let symbols: Vec<String> = ["BTC", "ESZ25", "XYZ", "INVT"].iter().map(|s| s.to_string()).collect();
+
+        let mut orders = flattening_orders(&snapshot, &symbols, &ReferenceData::seeded());
+        orders.sort_by(|a, b| a.instrument_symbol.cmp(&b.instrument_symbol));
+        let orders: Vec<(&str, OrderSide, u32, u64)> =
+            orders.iter().map(|o| (o.instrument_symbol.as_str(), o.side, o.size, o.price)).collect();
+        assert_eq!(
+            orders,
+            [
+                ("BTC", OrderSide::Sell, 2, 6_000_000),
+                ("ESZ25", OrderSide::Buy, 3, 450_025),
+                ("XYZ", OrderSide::Sell, 5, 1_235),
+            ]
+        );
+    }
+}
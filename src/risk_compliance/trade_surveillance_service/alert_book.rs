@@ -0,0 +1,378 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Alert Scoring and Suppression
+ *
+ * File: src/risk_compliance/trade_surveillance_service/alert_book.rs
+ *
+ * Description:
+ * Turns rule detections into alerts. Each alert carries a severity score
+ * (0-100) blending three components, each scaled to 0..1:
+ *
+ *   size       largest order size involved, relative to SIZE_SCALE
+ *   frequency  detections folded into the alert, relative to FREQUENCY_SCALE
+ *   notional   total notional involved, on a log scale up to NOTIONAL_SCALE
+ *
+ * weighted by QA_ALERT_WEIGHTS (e.g. "size:0.3,frequency:0.3,notional:0.4").
+ *
+ * Detections of the same pattern for the same strategy (and related
+ * strategy) within QA_ALERT_SUPPRESSION_SECS of the last one are folded into
+ * the open alert instead of raising a new one, so a repeating pattern yields
+ * one alert whose occurrence count and severity escalate.
+ *
+ * At most QA_ALERT_MEMORY_LIMIT alerts (default 1000) are held in memory.
+ * Beyond that the alert seen least recently is spilled to the JSON-lines
+ * file QA_ALERT_SPILL_PATH (default surveillance_alerts.spill.jsonl) rather
+ * than dropped, and served from there on GET /alerts/spilled. A repeat of a
+ * spilled alert's pattern raises a new alert.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::Detection;
+
+const SIZE_SCALE: f64 = 10_000.0;
+const FREQUENCY_SCALE: f64 = 10.0;
+const NOTIONAL_SCALE: f64 = 10_000_000.0;
+const DEFAULT_SUPPRESSION_SECS: i64 = 600;
+const DEFAULT_MEMORY_LIMIT: usize = 1000;
+const DEFAULT_SPILL_PATH: &str = "surveillance_alerts.spill.jsonl";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct ScoringWeights {
+    pub size: f64,
+    pub frequency: f64,
+    pub notional: f64,
+}
+
+impl ScoringWeights {
+    /// Reads QA_ALERT_WEIGHTS; components not listed keep their default.
+    pub fn from_env() -> ScoringWeights {
+        let mut weights = ScoringWeights { size: 0.3, frequency: 0.3, notional: 0.4 };
+        let Ok(spec) = std::env::var("QA_ALERT_WEIGHTS") else {
+            return weights;
+        };
+        for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+            let parsed = entry
+                .split_once(':')
+                .and_then(|(name, value)| Some((name.trim(), value.trim().parse::<f64>().ok().filter(|v| *v >= 0.0)?)));
+            match parsed {
+                Some(("size", value)) => weights.size = value,
+                Some(("frequency", value)) => weights.frequency = value,
+                Some(("notional", value)) => weights.notional = value,
+                _ => println!("Ignoring invalid QA_ALERT_WEIGHTS entry '{}'", entry),
+            }
+        }
+        weights
+    }
+
+    fn score(&self, max_size: u32, occurrences: u32, total_notional: f64) -> f64 {
+        let size = (max_size as f64 / SIZE_SCALE).min(1.0);
+        let frequency = (occurrences as f64 / FREQUENCY_SCALE).min(1.0);
+        let notional = ((1.0 + total_notional.max(0.0)).log10() / NOTIONAL_SCALE.log10()).min(1.0);
+        let total_weight = self.size + self.frequency + self.notional;
+        if total_weight == 0.0 {
+            return 0.0;
+        }
+        100.0 * (self.size * size + self.frequency * frequency + self.notional * notional) / total_weight
+    }
+}
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    fn from_score(score: f64) -> Severity {
+        match score {
+            s if s >= 80.0 => Severity::Critical,
+            s if s >= 60.0 => Severity::High,
+            s if s >= 35.0 => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ComplianceAlert {
+    pub alert_id: String,
+    pub strategy_id: String,
+    /// The other strategy involved, for cross-strategy patterns.
+    pub related_strategy_id: Option<String>,
+    /// The desk of `strategy_id`; None when it belongs to no desk.
+    pub desk: Option<String>,
+    pub pattern_detected: String,
+    /// Description of the most recent detection.
+    pub description: String,
+    pub severity_score: f64,
+    pub severity: Severity,
+    pub occurrences: u32,
+    pub max_size: u32,
+    pub total_notional: f64,
+    pub first_seen_utc: DateTime<Utc>,
+    pub last_seen_utc: DateTime<Utc>,
+}
+
+type SuppressionKey = (String, String, Option<String>);
+
+pub struct AlertBook {
+    weights: ScoringWeights,
+    suppression: chrono::Duration,
+    alerts: Vec<ComplianceAlert>,
+    /// Index into `alerts` of the open alert for each pattern/strategy pair.
+    open: HashMap<SuppressionKey, usize>,
+    memory_limit: usize,
+    spill_path: String,
+}
+
+// --- Alert Book ---
+
+impl AlertBook {
+    pub fn from_env() -> AlertBook {
+        let suppression_secs = std::env::var("QA_ALERT_SUPPRESSION_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SUPPRESSION_SECS);
+        AlertBook {
+            weights: ScoringWeights::from_env(),
+            suppression: chrono::Duration::seconds(suppression_secs),
+            alerts: Vec::new(),
+            open: HashMap::new(),
+            memory_limit: std::env::var("QA_ALERT_MEMORY_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|l| *l > 0)
+                .unwrap_or(DEFAULT_MEMORY_LIMIT),
+            spill_path: std::env::var("QA_ALERT_SPILL_PATH").unwrap_or_else(|_| DEFAULT_SPILL_PATH.to_string()),
+        }
+    }
+
+    /// Records a detection: folds it into the open alert for the same pattern
+    /// and strategies if that was last seen within the suppression window,
+    /// otherwise raises a new alert. Returns the alert if it is new or its
+    /// severity went up, for notification.
+    pub fn raise(&mut self, detection: Detection, desk: Option<&str>, now: DateTime<Utc>) -> Option<ComplianceAlert> {
+        let key = (detection.pattern.to_string(), detection.strategy_id.clone(), detection.related_strategy_id.clone());
+        if let Some(&index) = self.open.get(&key) {
+            let alert = &mut self.alerts[index];
+            if now - alert.last_seen_utc <= self.suppression {
+                let previous = alert.severity;
+                alert.occurrences += 1;
+                alert.max_size = alert.max_size.max(detection.size);
+                alert.total_notional += detection.notional;
+                alert.description = detection.description;
+                alert.last_seen_utc = now;
+                alert.severity_score = self.weights.score(alert.max_size, alert.occurrences, alert.total_notional);
+                alert.severity = Severity::from_score(alert.severity_score);
+                if alert.severity > previous {
+                    println!(
+                        "  -> COMPLIANCE ALERT ESCALATED: {} ({}) {:?} -> {:?} after {} occurrences",
+                        alert.pattern_detected, alert.strategy_id, previous, alert.severity, alert.occurrences
+                    );
+                    return Some(alert.clone());
+                }
+                return None;
+            }
+        }
+
+        let severity_score = self.weights.score(detection.size, 1, detection.notional);
+        let alert = ComplianceAlert {
+            alert_id: format!("ALERT-{}", rand::random::<u32>()),
+            strategy_id: detection.strategy_id,
+            related_strategy_id: detection.related_strategy_id,
+            desk: desk.map(str::to_string),
+            pattern_detected: detection.pattern.to_string(),
+            description: detection.description,
+            severity_score,
+            severity: Severity::from_score(severity_score),
+            occurrences: 1,
+            max_size: detection.size,
+            total_notional: detection.notional,
+            first_seen_utc: now,
+            last_seen_utc: now,
+        };
+        println!("  -> COMPLIANCE ALERT: {} ({:?})", alert.pattern_detected, alert.severity);
+        self.alerts.push(alert.clone());
+        self.open.insert(key, self.alerts.len() - 1);
+        if self.alerts.len() > self.memory_limit {
+            self.spill_least_recent();
+        }
+        Some(alert)
+    }
+
+    /// Moves the alert seen least recently to the spill file. It stays in
+    /// memory if the file cannot be written, so nothing is lost.
+    fn spill_least_recent(&mut self) {
+        let Some(index) = (0..self.alerts.len()).min_by_key(|&i| self.alerts[i].last_seen_utc) else {
+            return;
+        };
+        let line = serde_json::to_string(&self.alerts[index]).expect("alerts always serialize");
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.spill_path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = written {
+            println!("  -> Failed to spill alert to {}: {}", self.spill_path, e);
+            return;
+        }
+        self.alerts.remove(index);
+        self.open.retain(|_, i| *i != index);
+        for i in self.open.values_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+    }
+
+    /// Alerts spilled to disk, oldest first.
+    pub fn spilled(&self) -> Vec<serde_json::Value> {
+        match std::fs::read_to_string(&self.spill_path) {
+            Ok(contents) => contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// All alerts, most severe first.
+    pub fn alerts(&self) -> Vec<ComplianceAlert> {
+        let mut alerts = self.alerts.clone();
+        alerts.sort_by(|a, b| b.severity_score.total_cmp(&a.severity_score));
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(memory_limit: usize, spill_path: &str) -> AlertBook {
+        AlertBook {
+            weights: ScoringWeights { size: 0.3, frequency: 0.3, notional: 0.4 },
+            suppression: chrono::Duration::seconds(600),
+            alerts: Vec::new(),
+            open: HashMap::new(),
+            memory_limit,
+            spill_path: spill_path.to_string(),
+        }
+    }
+
+    fn spill_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("quantumarb-alerts-{}-{}.jsonl", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    /// 1,000 @ 1,000: a 1,000,000 notional.
+    fn detection(pattern: &str, strategy_id: &str, related: Option<&str>) -> Detection {
+        Detection {
+            strategy_id: strategy_id.to_string(),
+            related_strategy_id: related.map(str::to_string),
+            pattern: pattern.to_string(),
+            description: format!("{} by {}", pattern, strategy_id),
+            size: 1_000,
+            notional: 1_000_000.0,
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_790_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn repeats_fold_into_the_open_alert_and_notify_only_on_escalation() {
+        let mut book = book(100, &spill_path("unused"));
+        let first = book.raise(detection("Wash Trade", "s1", None), Some("desk-a"), at(0)).unwrap();
+        assert_eq!((first.severity, first.occurrences, first.desk.as_deref()), (Severity::Medium, 1, Some("desk-a")));
+
+        // Frequency and notional grow with each repeat; the seventh makes it High.
+        let notified: Vec<u32> = (2..=8)
+            .filter_map(|n| book.raise(detection("Wash Trade", "s1", None), None, at(n * 10)))
+            .map(|alert| alert.occurrences)
+            .collect();
+        assert_eq!(notified, [7]);
+
+        let alerts = book.alerts();
+        assert_eq!(alerts.len(), 1);
+        let alert = &alerts[0];
+        assert_eq!((alert.alert_id.as_str(), alert.occurrences), (first.alert_id.as_str(), 8));
+        assert_eq!((alert.total_notional, alert.severity), (8_000_000.0, Severity::High));
+        assert_eq!((alert.first_seen_utc, alert.last_seen_utc), (at(0), at(80)));
+    }
+
+    #[test]
+    fn a_repeat_after_the_suppression_window_raises_a_new_alert() {
+        let mut book = book(100, &spill_path("unused"));
+        let first = book.raise(detection("Wash Trade", "s1", None), None, at(0)).unwrap();
+        assert!(book.raise(detection("Wash Trade", "s1", None), None, at(600)).is_none(), "last seen 600s ago");
+        let second = book.raise(detection("Wash Trade", "s1", None), None, at(1_201)).unwrap();
+        assert_ne!(second.alert_id, first.alert_id);
+        assert_eq!((second.occurrences, book.alerts().len()), (1, 2));
+    }
+
+    #[test]
+    fn patterns_and_strategy_pairs_are_kept_apart() {
+        let mut book = book(100, &spill_path("unused"));
+        assert!(book.raise(detection("Collusion", "s1", Some("s2")), None, at(0)).is_some());
+        assert!(book.raise(detection("Collusion", "s1", Some("s3")), None, at(1)).is_some());
+        assert!(book.raise(detection("Wash Trade", "s1", None), None, at(2)).is_some());
+        assert!(book.raise(detection("Collusion", "s1", Some("s2")), None, at(3)).is_none());
+        assert_eq!(book.alerts().len(), 3);
+    }
+
+    #[test]
+    fn the_least_recently_seen_alert_is_spilled_beyond_the_memory_limit() {
+        let path = spill_path("spill");
+        let mut book = book(2, &path);
+        book.raise(detection("Wash Trade", "s1", None), None, at(0));
+        book.raise(detection("Wash Trade", "s2", None), None, at(1));
+        book.raise(detection("Wash Trade", "s1", None), None, at(2));
+        book.raise(detection("Wash Trade", "s3", None), None, at(3));
+
+        let spilled = book.spilled();
+        assert_eq!(spilled.len(), 1);
+        assert_eq!(spilled[0]["strategy_id"], "s2");
+        let mut held: Vec<String> = book.alerts().into_iter().map(|alert| alert.strategy_id).collect();
+        held.sort();
+        assert_eq!(held, ["s1", "s3"]);
+
+        // The open index still points at the right alerts after the removal.
+        assert!(book.raise(detection("Wash Trade", "s3", None), None, at(4)).is_none());
+        let s3 = book.alerts().into_iter().find(|alert| alert.strategy_id == "s3").unwrap();
+        assert_eq!(s3.occurrences, 2);
+        // A repeat of the spilled alert starts afresh.
+        let s2 = book.raise(detection("Wash Trade", "s2", None), None, at(5)).unwrap();
+        assert_eq!(s2.occurrences, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn an_alert_that_cannot_be_spilled_stays_in_memory() {
+        let mut book = book(1, "/nonexistent-directory/alerts.jsonl");
+        book.raise(detection("Wash Trade", "s1", None), None, at(0));
+        book.raise(detection("Wash Trade", "s2", None), None, at(1));
+        assert_eq!(book.alerts().len(), 2);
+        assert!(book.spilled().is_empty());
+    }
+
+    #[test]
+    fn scores_map_onto_severities() {
+        let weights = ScoringWeights { size: 1.0, frequency: 0.0, notional: 0.0 };
+        assert_eq!(weights.score(5_000, 1, 0.0), 50.0);
+        assert_eq!(weights.score(50_000, 1, 0.0), 100.0, "each component is capped at 1");
+        assert_eq!(ScoringWeights { size: 0.0, frequency: 0.0, notional: 0.0 }.score(5_000, 3, 1e6), 0.0);
+        let severities: Vec<Severity> = [0.0, 34.9, 35.0, 60.0, 80.0].map(Severity::from_score).to_vec();
+        assert_eq!(severities, [Severity::Low, Severity::Low, Severity::Medium, Severity::High, Severity::Critical]);
+    }
+}
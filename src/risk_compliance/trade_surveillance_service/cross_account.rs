@@ -0,0 +1,199 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Cross-Strategy and Cross-Account Rules
+ *
+ * File: src/risk_compliance/trade_surveillance_service/cross_account.rs
+ *
+ * Description:
+ * Rules that correlate fills across strategies and accounts, run against the
+ * shared event window on every fill:
+ *
+ * - Wash trade: two different strategies in the same account fill on
+ *   opposite sides of the same instrument at (nearly) the same price and size
+ *   within a short interval. No change in beneficial ownership.
+ * - Collusion: a strategy repeatedly fills opposite another account's
+ *   strategy at off-market prices (away from the reference mid), which can
+ *   move P&L between accounts or paint the tape.
+ */
+
+use tokio::time::Duration;
+
+use crate::event_window::{EventKey, EventWindow};
+use crate::{Detection, OrderEvent, OrderEventType};
+
+/// Fills this close together are treated as the two sides of one trade.
+const MATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Prices within this many basis points are treated as the same price.
+const PRICE_TOLERANCE_BPS: f64 = 1.0;
+/// A fill this far from the reference mid is off-market.
+const OFF_MARKET_BPS: f64 = 50.0;
+/// Matched off-market pairs in the window before collusion is flagged.
+const COLLUSION_MIN_REPEATS: usize = 3;
+
+fn bps_apart(a: f64, b: f64) -> f64 {
+    if b == 0.0 { return f64::INFINITY; }
+    ((a - b) / b).abs() * 10_000.0
+}
+
+fn is_off_market(fill: &OrderEvent) -> bool {
+    bps_apart(fill.price, fill.reference_price) > OFF_MARKET_BPS
+}
+
+/// Whether `a` and `b` look like the two sides of the same trade.
+fn is_opposite_match(a: &OrderEvent, b: &OrderEvent) -> bool {
+    matches!(b.event_type, OrderEventType::Filled)
+        && a.strategy_id != b.strategy_id
+        && a.instrument == b.instrument
+        && a.side != b.side
+        && a.size == b.size
+        && bps_apart(a.price, b.price) <= PRICE_TOLERANCE_BPS
+        && a.timestamp.max(b.timestamp) - a.timestamp.min(b.timestamp) <= MATCH_INTERVAL
+}
+
+/// Flags `fill` if another strategy in the same account took the other side.
+pub fn detect_wash_trade(window: &EventWindow, fill: &OrderEvent) -> Option<Detection> {
+    let other = window
+        .query(EventKey::Account(&fill.account_id))
+        .into_iter()
+        .find(|e| is_opposite_match(fill, e))?;
+
+    Some(Detection {
+        strategy_id: fill.strategy_id.clone(),
+        related_strategy_id: Some(other.strategy_id.clone()),
+        pattern: "Potential Wash Trade".to_string(),
+        description: format!(
+            "Strategies {} and {} in account {} filled opposite each other: {} {} @ {:.2}.",
+            fill.strategy_id, other.strategy_id, fill.account_id, fill.size, fill.instrument, fill.price
+        ),
+        size: fill.size,
+        notional: fill.size as f64 * fill.price,
+    })
+}
+
+/// Flags `fill` if its strategy has repeatedly traded off-market opposite the
+/// same strategy in another account.
+pub fn detect_collusion(window: &EventWindow, fill: &OrderEvent) -> Option<Detection> {
+    if !is_off_market(fill) {
+        return None;
+    }
+    let instrument_events = window.query(EventKey::Instrument(&fill.instrument));
+    let counterparty = instrument_events
+        .iter()
+        .find(|e| e.account_id != fill.account_id && is_opposite_match(fill, e))?;
+
+    // Count this strategy's off-market fills in the instrument that matched the same counterparty.
+    let repeats = window
+        .query(EventKey::Strategy(&fill.strategy_id))
+        .into_iter()
+        .filter(|own| matches!(own.event_type, OrderEventType::Filled) && own.instrument == fill.instrument && is_off_market(own))
+        .filter(|own| {
+            instrument_events.iter().any(|e| {
+                e.strategy_id == counterparty.strategy_id && e.account_id == counterparty.account_id && is_opposite_match(own, e)
+            })
+        })
+        .count();
+    if repeats < COLLUSION_MIN_REPEATS {
+        return None;
+    }
+
+    Some(Detection {
+        strategy_id: fill.strategy_id.clone(),
+        related_strategy_id: Some(counterparty.strategy_id.clone()),
+        pattern: "Potential Cross-Account Collusion".to_string(),
+        description: format!(
+            "{} ({}) traded opposite {} ({}) {} times in {} at off-market prices; latest {:.2} vs mid {:.2} ({:.0} bps).",
+            fill.strategy_id,
+            fill.account_id,
+            counterparty.strategy_id,
+            counterparty.account_id,
+            repeats,
+            fill.instrument,
+            fill.price,
+            fill.reference_price,
+            bps_apart(fill.price, fill.reference_price)
+        ),
+        size: fill.size,
+        notional: fill.size as f64 * fill.price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Side;
+    use tokio::time::Instant;
+
+    fn fill(strategy_id: &str, account_id: &str, side: Side, price: f64, at: Instant) -> OrderEvent {
+        OrderEvent {
+            strategy_id: strategy_id.to_string(),
+            account_id: account_id.to_string(),
+            order_id: format!("{}-{:?}", strategy_id, side),
+            instrument: "BTC".to_string(),
+            side,
+            event_type: OrderEventType::Filled,
+            size: 2,
+            price,
+            reference_price: 60_000.0,
+            best_bid: None,
+            best_ask: None,
+            timestamp: at,
+        }
+    }
+
+    fn window(events: &[OrderEvent]) -> EventWindow {
+        let mut window = EventWindow::new(Duration::from_secs(300));
+        for event in events {
+            window.push(event.clone());
+        }
+        window
+    }
+
+    #[test]
+    fn opposite_matches_need_another_strategy_the_other_side_and_the_same_trade() {
+        let t0 = Instant::now();
+        let buy = fill("mm-1", "101", Side::Buy, 60_000.0, t0);
+        assert!(is_opposite_match(&buy, &fill("arb-1", "101", Side::Sell, 60_005.0, t0 + Duration::from_millis(900))));
+        assert!(!is_opposite_match(&buy, &fill("mm-1", "101", Side::Sell, 60_000.0, t0)), "same strategy");
+        assert!(!is_opposite_match(&buy, &fill("arb-1", "101", Side::Buy, 60_000.0, t0)), "same side");
+        assert!(!is_opposite_match(&buy, &fill("arb-1", "101", Side::Sell, 60_010.0, t0)), "over 1 bp apart");
+        assert!(!is_opposite_match(&buy, &fill("arb-1", "101", Side::Sell, 60_000.0, t0 + Duration::from_secs(2))));
+        let resting = OrderEvent { event_type: OrderEventType::New, ..fill("arb-1", "101", Side::Sell, 60_000.0, t0) };
+        assert!(!is_opposite_match(&buy, &resting), "not a fill");
+        let other_size = OrderEvent { size: 3, ..fill("arb-1", "101", Side::Sell, 60_000.0, t0) };
+        assert!(!is_opposite_match(&buy, &other_size));
+    }
+
+    #[test]
+    fn detects_a_wash_trade_between_strategies_of_one_account() {
+        let t0 = Instant::now();
+        let buy = fill("mm-1", "101", Side::Buy, 60_000.0, t0);
+        let sell = fill("arb-1", "101", Side::Sell, 60_000.0, t0 + Duration::from_millis(200));
+        let detection = detect_wash_trade(&window(&[buy.clone(), sell.clone()]), &sell).unwrap();
+        assert_eq!((detection.strategy_id.as_str(), detection.related_strategy_id.as_deref()), ("arb-1", Some("mm-1")));
+        assert_eq!((detection.size, detection.notional), (2, 120_000.0));
+
+        // The same pair across two accounts is not a wash trade.
+        let elsewhere = fill("arb-1", "202", Side::Sell, 60_000.0, t0 + Duration::from_millis(200));
+        assert!(detect_wash_trade(&window(&[buy, elsewhere.clone()]), &elsewhere).is_none());
+    }
+
+    #[test]
+    fn detects_repeated_off_market_fills_opposite_another_account() {
+        let t0 = Instant::now();
+        // 100 bps over the 60,000 mid, each time opposite taker-9 in account 202.
+        let pairs: Vec<_> = (0..3)
+            .map(|n| {
+                let at = t0 + Duration::from_secs(10 * n);
+                [fill("mm-1", "101", Side::Sell, 60_600.0, at), fill("taker-9", "202", Side::Buy, 60_600.0, at)]
+            })
+            .collect();
+        let events: Vec<_> = pairs.iter().flatten().cloned().collect();
+        let latest = &pairs[2][0];
+        let detection = detect_collusion(&window(&events), latest).unwrap();
+        assert_eq!(detection.related_strategy_id.as_deref(), Some("taker-9"));
+        assert!(detection.description.contains("3 times"), "{}", detection.description);
+
+        assert!(detect_collusion(&window(&events[2..]), latest).is_none(), "two repeats are not enough");
+        let on_market: Vec<_> = events.iter().map(|e| OrderEvent { price: 60_001.0, ..e.clone() }).collect();
+        assert!(detect_collusion(&window(&on_market), &on_market[4]).is_none());
+    }
+}
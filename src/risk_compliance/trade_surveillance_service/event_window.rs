@@ -0,0 +1,158 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Shared Surveillance Event Window
+ *
+ * File: src/risk_compliance/trade_surveillance_service/event_window.rs
+ *
+ * Description:
+ * One time-bounded window of order events shared by every rule, replacing
+ * the per-strategy histories. Events are stored once, in arrival order, with
+ * a sequence number; secondary indices map each strategy, account and
+ * instrument to the sequence numbers of its events, so rules can ask for
+ * "everything strategy X did" or "every fill in ETH" without scanning the
+ * whole window. Eviction is by age (and a hard cap on size) from the front,
+ * which keeps every index in sequence order.
+ */
+
+use std::collections::{HashMap, VecDeque};
+use tokio::time::{Duration, Instant};
+
+use crate::OrderEvent;
+
+/// Hard cap on the number of events held, whatever their age.
+const MAX_EVENTS: usize = 100_000;
+
+/// The keys an event is indexed by.
+#[derive(Debug, Clone, Copy)]
+pub enum EventKey<'a> {
+    Strategy(&'a str),
+    Account(&'a str),
+    Instrument(&'a str),
+}
+
+pub struct EventWindow {
+    span: Duration,
+    events: VecDeque<(u64, OrderEvent)>,
+    next_seq: u64,
+    by_strategy: HashMap<String, VecDeque<u64>>,
+    by_account: HashMap<String, VecDeque<u64>>,
+    by_instrument: HashMap<String, VecDeque<u64>>,
+}
+
+impl EventWindow {
+    pub fn new(span: Duration) -> EventWindow {
+        EventWindow {
+            span,
+            events: VecDeque::new(),
+            next_seq: 0,
+            by_strategy: HashMap::new(),
+            by_account: HashMap::new(),
+            by_instrument: HashMap::new(),
+        }
+    }
+
+    /// Adds an event, evicting any that have aged out of the window.
+    pub fn push(&mut self, event: OrderEvent) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.by_strategy.entry(event.strategy_id.clone()).or_default().push_back(seq);
+        self.by_account.entry(event.account_id.clone()).or_default().push_back(seq);
+        self.by_instrument.entry(event.instrument.clone()).or_default().push_back(seq);
+        let now = event.timestamp;
+        self.events.push_back((seq, event));
+        self.evict(now);
+    }
+
+    fn evict(&mut self, now: Instant) {
+        while let Some((seq, front)) = self.events.front() {
+            let expired = now.saturating_duration_since(front.timestamp) > self.span;
+            if !expired && self.events.len() <= MAX_EVENTS {
+                break;
+            }
+            let seq = *seq;
+            let keys = (front.strategy_id.clone(), front.account_id.clone(), front.instrument.clone());
+            remove_front(&mut self.by_strategy, &keys.0, seq);
+            remove_front(&mut self.by_account, &keys.1, seq);
+            remove_front(&mut self.by_instrument, &keys.2, seq);
+            self.events.pop_front();
+        }
+    }
+
+    /// Events for one key, oldest first.
+    pub fn query(&self, key: EventKey) -> Vec<&OrderEvent> {
+        let (index, name) = match key {
+            EventKey::Strategy(name) => (&self.by_strategy, name),
+            EventKey::Account(name) => (&self.by_account, name),
+            EventKey::Instrument(name) => (&self.by_instrument, name),
+        };
+        let Some(seqs) = index.get(name) else {
+            return Vec::new();
+        };
+        let first_seq = match self.events.front() {
+            Some((seq, _)) => *seq,
+            None => return Vec::new(),
+        };
+        // Sequence numbers are contiguous, so an event's position is its offset from the front.
+        seqs.iter().filter_map(|seq| self.events.get((seq - first_seq) as usize).map(|(_, e)| e)).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+}
+
+/// Drops `seq` from the front of `key`'s index (and the key once it is empty).
+fn remove_front(index: &mut HashMap<String, VecDeque<u64>>, key: &str, seq: u64) {
+    if let Some(seqs) = index.get_mut(key) {
+        if seqs.front() == Some(&seq) {
+            seqs.pop_front();
+        }
+        if seqs.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OrderEventType, Side};
+
+    fn event(strategy_id: &str, instrument: &str, at: Instant) -> OrderEvent {
+        OrderEvent {
+            strategy_id: strategy_id.to_string(),
+            account_id: "101".to_string(),
+            order_id: format!("{}-{}", strategy_id, instrument),
+            instrument: instrument.to_string(),
+            side: Side::Buy,
+            event_type: OrderEventType::New,
+            size: 1,
+            price: 100.0,
+            reference_price: 100.0,
+            best_bid: None,
+            best_ask: None,
+            timestamp: at,
+        }
+    }
+
+    #[test]
+    fn queries_by_key_in_arrival_order_and_evicts_by_age() {
+        let t0 = Instant::now();
+        let mut window = EventWindow::new(Duration::from_secs(60));
+        window.push(event("mm-1", "BTC", t0));
+        window.push(event("arb-1", "ETH", t0 + Duration::from_secs(30)));
+        window.push(event("mm-1", "ETH", t0 + Duration::from_secs(45)));
+        let instruments = |w: &EventWindow| {
+            w.query(EventKey::Strategy("mm-1")).iter().map(|e| e.instrument.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(instruments(&window), ["BTC", "ETH"]);
+        assert_eq!(window.query(EventKey::Instrument("ETH")).len(), 2);
+        assert_eq!(window.query(EventKey::Account("101")).len(), 3);
+
+        // The first event ages out; the indices follow.
+        window.push(event("arb-1", "BTC", t0 + Duration::from_secs(61)));
+        assert_eq!(window.len(), 3);
+        assert_eq!(instruments(&window), ["ETH"]);
+        assert_eq!(window.query(EventKey::Instrument("BTC"))[0].strategy_id, "arb-1");
+        assert!(window.query(EventKey::Strategy("unknown")).is_empty());
+    }
+}
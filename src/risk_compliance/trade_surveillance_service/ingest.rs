@@ -0,0 +1,187 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Order Event Ingestion
+ *
+ * File: src/risk_compliance/trade_surveillance_service/ingest.rs
+ *
+ * Description:
+ * Normalizes the platform's bus traffic into surveillance `OrderEvent`s:
+ *
+ *   orders.requests            OrderRequest    -> New
+ *   execution_reports          ExecutionReport -> Filled / Canceled
+ *   market_data.instrument.*   BboUpdate       -> top of book (no event)
+ *
+ * Execution reports only carry the order id, so the normalizer keeps each
+ * open OrderRequest to recover the account, instrument, side and price; an
+ * amendment (Replaced) updates the kept order's size.
+ * Every event is stamped with the latest top of book for its instrument.
+ * Wire prices are read at the instrument's price scale from the reference
+ * data (hundredths for an instrument it does not know).
+ * Payloads may be binary or JSON (`Encoding::decode_any`).
+ *
+ * Messages reach the rules through bounded queues. For orders and execution
+ * reports the subscriber awaits free space rather than dropping, so a slow
+ * rule pass pushes back on the bus consumer (JetStream flow control) instead
+ * of losing events. Market data only refreshes the books, so under load the
+ * oldest quotes are dropped instead.
+ */
+
+use quantumarb_bus::{topics, BusMessage};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_queues::QueueStats;
+use quantumarb_money::Price;
+use quantumarb_refdata::{ReferenceData, DEFAULT_PRICE_DECIMALS};
+use quantumarb_wire::{BboUpdate, Encoding, ExecutionReport, OrderRequest, OrderSide, OrderStatus};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::{OrderEvent, OrderEventType, Side};
+
+/// Counters served on GET /ingest/stats.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+pub struct IngestStats {
+    pub messages_received: u64,
+    pub events_normalized: u64,
+    pub undecodable: u64,
+    /// Execution reports for orders we never saw the request for.
+    pub unmatched_reports: u64,
+    /// The order and market data queues.
+    pub queues: Vec<QueueStats>,
+    /// Events currently held in the shared rule window.
+    pub window_events: usize,
+}
+
+pub struct Normalizer {
+    open_orders: HashMap<Uuid, OrderRequest>,
+    /// Latest (best bid, best ask) per instrument.
+    books: HashMap<u32, (f64, f64)>,
+    /// Instrument definitions, for symbols and price scales.
+    instruments: ReferenceData,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer {
+            open_orders: HashMap::new(),
+            books: HashMap::new(),
+            instruments: ReferenceData::new(quantumarb_refdata::load_from_env()),
+        }
+    }
+}
+
+impl Normalizer {
+    /// Turns one bus message into an order event, if it describes one.
+    pub fn normalize(&mut self, message: &BusMessage, stats: &mut IngestStats) -> Option<OrderEvent> {
+        stats.messages_received += 1;
+        let result = match message.topic.as_str() {
+            topics::ORDER_REQUESTS => Encoding::decode_any::<OrderRequest>(message.body()).map(|order| self.on_order(order)),
+            topics::EXECUTION_REPORTS => Encoding::decode_any::<ExecutionReport>(message.body()).map(|report| self.on_report(report, stats)),
+            topic if topic.starts_with(topics::MARKET_DATA_PREFIX) => {
+                Encoding::decode_any::<BboUpdate>(message.body()).map(|bbo| self.on_bbo(bbo))
+            }
+            _ => return None,
+        };
+        match result {
+            Ok(event) => {
+                stats.events_normalized += event.is_some() as u64;
+                event
+            }
+            Err(e) => {
+                stats.undecodable += 1;
+                println!("  -> Undecodable message on '{}': {}", message.topic, e);
+                None
+            }
+        }
+    }
+
+    fn on_order(&mut self, order: OrderRequest) -> Option<OrderEvent> {
+        let event = self.event(&order, OrderEventType::New, order.size, order.price);
+        self.open_orders.insert(order.order_id, order);
+        Some(event)
+    }
+
+    fn on_report(&mut self, report: ExecutionReport, stats: &mut IngestStats) -> Option<OrderEvent> {
+        if report.status.is_trade_correction() {
+            // The patterns look at fills as they printed; a later bust or
+            // correction does not undo the behaviour.
+            return None;
+        }
+        if report.status == OrderStatus::Replaced {
+            // An amended order is watched at its new size.
+            match self.open_orders.get_mut(&report.internal_order_id) {
+                Some(order) => order.size = report.cumulative_size + report.leaves_size,
+                None => stats.unmatched_reports += 1,
+            }
+            return None;
+        }
+        let Some(order) = self.open_orders.get(&report.internal_order_id) else {
+            stats.unmatched_reports += 1;
+            return None;
+        };
+        let event = match report.status {
+            OrderStatus::Filled | OrderStatus::PartiallyFilled => {
+                Some(self.event(order, OrderEventType::Filled, report.filled_size, report.filled_price))
+            }
+            OrderStatus::Canceled => Some(self.event(order, OrderEventType::Canceled, order.size, order.price)),
+            OrderStatus::RejectedByExchange
+            | OrderStatus::New
+            | OrderStatus::SentToExchange
+            | OrderStatus::Replaced
+            | OrderStatus::TradeBusted
+            | OrderStatus::TradeCorrected => None,
+        };
+        if matches!(report.status, OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::RejectedByExchange) {
+            self.open_orders.remove(&report.internal_order_id);
+        }
+        event
+    }
+
+    fn on_bbo(&mut self, bbo: BboUpdate) -> Option<OrderEvent> {
+        let price = |wire| self.price(bbo.instrument_id, wire);
+        let book = (price(bbo.best_bid_price), price(bbo.best_ask_price));
+        self.books.insert(bbo.instrument_id, book);
+        None
+    }
+
+    fn instrument_symbol(&self, instrument_id: u32) -> String {
+        match self.instruments.get(instrument_id) {
+            Some(definition) => definition.symbol.clone(),
+            None => format!("INSTRUMENT-{}", instrument_id),
+        }
+    }
+
+    fn price(&self, instrument_id: u32, wire: u64) -> f64 {
+        let price = match self.instruments.get(instrument_id) {
+            Some(definition) => definition.price(wire),
+            None => Price::from_wire(wire, DEFAULT_PRICE_DECIMALS).unwrap_or(Price::MAX),
+        };
+        price.to_f64()
+    }
+
+    fn event(&self, order: &OrderRequest, event_type: OrderEventType, size: u32, price: u64) -> OrderEvent {
+        let price = self.price(order.instrument_id, price);
+        let book = self.books.get(&order.instrument_id).copied();
+        OrderEvent {
+            strategy_id: if order.strategy_id.is_empty() {
+                format!("ACCOUNT-{}", order.account_id)
+            } else {
+                order.strategy_id.clone()
+            },
+            account_id: order.account_id.to_string(),
+            order_id: order.order_id.to_string(),
+            instrument: self.instrument_symbol(order.instrument_id),
+            side: match order.side {
+                OrderSide::Buy => Side::Buy,
+                OrderSide::Sell => Side::Sell,
+            },
+            event_type,
+            size,
+            price,
+            reference_price: book.map_or(price, |(bid, ask)| (bid + ask) / 2.0),
+            best_bid: book.map(|(bid, _)| bid),
+            best_ask: book.map(|(_, ask)| ask),
+            timestamp: Instant::now(),
+        }
+    }
+}
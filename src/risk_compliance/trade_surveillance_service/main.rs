@@ -11,14 +11,69 @@
  * It consumes a stream of all order-related events and applies rules to
  * identify patterns like spoofing, layering, or wash trading.
  *
- * This POC implements a simple rule to detect "layering": placing a large
- * order to create a false sense of liquidity, and then cancelling it shortly after.
+ * This POC implements a rule to detect spoofing/layering: placing a large
+ * order to create a false sense of liquidity, and then cancelling it before it
+ * can trade. Each order event carries the top of book at the time, so the rule
+ * (see `spoofing`) can tell orders resting away from the touch that are pulled
+ * as the market approaches, and orders opposite the strategy's own fills.
+ *
+ * Events are kept in a single shared window (see `event_window`), indexed by
+ * strategy, account and instrument, so rules can also correlate activity
+ * across strategies and accounts: wash trades between strategies of one
+ * account, and repeated off-market trading between accounts (see
+ * `cross_account`). QA_SURVEILLANCE_WINDOW_SECS sets the window (default 300).
+ *
+ * Detections go through the alert book (see `alert_book`), which scores
+ * their severity and folds repeats of the same pattern into one escalating
+ * alert rather than raising a new one each time. New alerts, and alerts whose
+ * severity escalates, are notified on the routes configured in
+ * QA_NOTIFY_CONFIG_PATH (see `quantumarb-notify`).
+ *
+ * Order events come from the order, execution report and market data topics
+ * on the bus, normalized into `OrderEvent`s (see `ingest`) and handed to the
+ * rules through two bounded `quantumarb-queues`: orders and execution
+ * reports on a blocking queue (QA_SURVEILLANCE_BUFFER, default 10000) and
+ * market data on a drop-oldest one (QA_SURVEILLANCE_MD_BUFFER, default
+ * 10000). The alert book keeps its most recent alerts in memory and spills
+ * older ones to disk.
+ *
+ * Compliance can add rules without a release: Rhai scripts in
+ * QA_SURVEILLANCE_RULES_DIR run on every event after the built-in rules,
+ * sandboxed and time-boxed, and are reloaded as the files change (see
+ * `rules`). GET /rules lists them; POST /rules/reload rescans the directory.
+ *
+ * Each alert carries the desk of its strategy (QA_DESKS_PATH, see
+ * `quantumarb-tenancy`): a desk's token reads its own alerts on GET /alerts,
+ * and the rest of the API answers firm tokens only.
+ *
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
  */
 
-use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+mod alert_book;
+mod cross_account;
+mod event_window;
+mod ingest;
+mod rules;
+mod spoofing;
+
+use alert_book::{AlertBook, ComplianceAlert};
+use event_window::EventWindow;
+use ingest::{IngestStats, Normalizer};
+use quantumarb_bus::{topics, BusMessage};
+use quantumarb_money::Money;
+use quantumarb_notify::{EventClass, Notifier};
+use quantumarb_openapi::{ApiDoc, Operation};
+use quantumarb_queues::{Receiver, Sender};
+use quantumarb_tenancy::{Desks, Scope};
+use rules::{RuleConfig, RuleHost, RuleStatus, SharedRules};
+use quantumarb_wire::{
+    utc_now_ns, BboUpdate, Encoding, ExecutionReport, HopStamps, Liquidity, OrderPriority, OrderRequest, OrderSide,
+    OrderStatus, TradingMode,
+};
 use std::sync::{Arc, Mutex};
 use tokio::time::{self, Duration, Instant};
+use uuid::Uuid;
 use warp::Filter;
 
 // --- Data Structures ---
@@ -30,27 +85,50 @@ enum OrderEventType {
     Filled,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Buy,
+    Sell,
+}
+
 #[derive(Debug, Clone)]
 struct OrderEvent {
     strategy_id: String,
+    account_id: String,
     order_id: String,
+    instrument: String,
+    side: Side,
     event_type: OrderEventType,
     size: u32,
+    /// Order price, or the execution price for fills.
+    price: f64,
+    /// Market mid when the event happened, to judge off-market prices.
+    reference_price: f64,
+    /// Top of book when the event happened, if known.
+    best_bid: Option<f64>,
+    best_ask: Option<f64>,
     timestamp: Instant,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct ComplianceAlert {
-    alert_id: String,
+/// A single rule hit, before scoring and suppression.
+#[derive(Debug, Clone)]
+struct Detection {
     strategy_id: String,
-    pattern_detected: String,
+    /// The other strategy involved, for cross-strategy patterns.
+    related_strategy_id: Option<String>,
+    pattern: String,
     description: String,
-    timestamp_utc: String,
+    size: u32,
+    notional: f64,
 }
 
-// State to track recent orders for each strategy
-type StrategyOrderHistory = Arc<Mutex<HashMap<String, VecDeque<OrderEvent>>>>;
-type GeneratedAlerts = Arc<Mutex<Vec<ComplianceAlert>>>;
+// One window of recent events shared by all rules
+type SharedEventWindow = Arc<Mutex<EventWindow>>;
+type GeneratedAlerts = Arc<Mutex<AlertBook>>;
+type SharedIngestStats = Arc<Mutex<IngestStats>>;
+
+const DEFAULT_WINDOW_SECS: u64 = 300;
+const DEFAULT_BUFFER_CAPACITY: usize = 10_000;
 
 // --- Main Application Logic ---
 
@@ -58,24 +136,95 @@ type GeneratedAlerts = Arc<Mutex<Vec<ComplianceAlert>>>;
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Trade Surveillance Service ---");
 
-    let order_history = Arc::new(Mutex::new(HashMap::new()));
-    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let window_secs = std::env::var("QA_SURVEILLANCE_WINDOW_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_WINDOW_SECS);
+    let event_window = Arc::new(Mutex::new(EventWindow::new(Duration::from_secs(window_secs))));
+    let alerts = Arc::new(Mutex::new(AlertBook::from_env()));
+
+    let capacity = |name: &str| {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|c| *c > 0).unwrap_or(DEFAULT_BUFFER_CAPACITY)
+    };
+    let stats = Arc::new(Mutex::new(IngestStats::default()));
+    let (orders, order_queue) = quantumarb_queues::blocking("surveillance_orders", capacity("QA_SURVEILLANCE_BUFFER"));
+    let (market_data, market_data_queue) =
+        quantumarb_queues::drop_oldest("surveillance_market_data", capacity("QA_SURVEILLANCE_MD_BUFFER"));
 
-    // Spawn background task to simulate receiving order events
-    let history_clone = order_history.clone();
+    // Spawn the bus subscription and the rule engine, joined by the bounded queues
+    tokio::spawn(async move {
+        subscribe_to_order_topics(orders, market_data).await;
+    });
+    let history_clone = event_window.clone();
     let alerts_clone = alerts.clone();
+    let stats_clone = stats.clone();
+    let notifier = Notifier::from_env("trade-surveillance");
+    let desks = Arc::new(Desks::from_env().expect("Invalid desk configuration"));
+    let desks_clone = desks.clone();
+    let rules = Arc::new(Mutex::new(RuleHost::new(RuleConfig::from_env(), event_window.clone())));
+    rules::reload(&rules, false);
+    tokio::spawn(rules::watch(rules.clone()));
+    let rules_clone = rules.clone();
     tokio::spawn(async move {
-        listen_for_order_events(history_clone, alerts_clone).await;
+        let (window, alerts, stats, desks) = (history_clone, alerts_clone, stats_clone, desks_clone);
+        process_order_events(order_queue, market_data_queue, window, alerts, stats, rules_clone, notifier, desks).await;
     });
 
     // --- API Endpoint to get the latest compliance alerts ---
-    let get_alerts = warp::path("alerts")
+    // A desk reads its own alerts; the rest of the API is compliance's.
+    let firm = quantumarb_tenancy::firm_only(desks.clone());
+    let get_alerts = warp::path!("alerts")
         .and(warp::get())
-        .and(with_state(alerts))
+        .and(with_state(alerts.clone()))
+        .and(quantumarb_tenancy::scope(desks.clone()))
         .and_then(handler_get_alerts);
 
+    let get_spilled_alerts = warp::path!("alerts" / "spilled")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(alerts))
+        .and_then(handler_get_spilled_alerts);
+
+    let get_ingest_stats = warp::path!("ingest" / "stats")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(stats))
+        .and_then(handler_get_ingest_stats);
+
+    let get_rules = warp::path!("rules")
+        .and(warp::get())
+        .and(firm.clone())
+        .and(with_state(rules.clone()))
+        .and_then(handler_get_rules);
+
+    let reload_rules = warp::path!("rules" / "reload")
+        .and(warp::post())
+        .and(firm)
+        .and(with_state(rules))
+        .and_then(handler_reload_rules);
+
     println!("API server running at http://127.0.0.1:3033/alerts");
-    warp::serve(get_alerts).run(([127, 0, 0, 1], 3033)).await;
+    let routes = get_alerts
+        .or(get_spilled_alerts)
+        .or(get_ingest_stats)
+        .or(get_rules)
+        .or(reload_rules)
+        .or(quantumarb_openapi::routes(api_doc()))
+        .recover(quantumarb_tenancy::recover);
+    warp::serve(routes).run(([127, 0, 0, 1], 3033)).await;
+}
+
+/// The OpenAPI document of the alert and ingest endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("Trade Surveillance Service", "Market abuse alerts raised on the platform's order flow.")
+        .get::<Vec<ComplianceAlert>>("/alerts", "The most recent alerts")
+        .get::<Vec<quantumarb_openapi::Value>>("/alerts/spilled", "Alerts moved to disk, oldest first")
+        .get::<IngestStats>("/ingest/stats", "Event ingest statistics")
+        .get::<Vec<RuleStatus>>("/rules", "Scripted rules and their counters")
+        .operation(
+            Operation::post("/rules/reload", "Rescan the rules directory, retrying quarantined rules")
+                .response::<Vec<RuleStatus>>(),
+        )
 }
 
 /// Warp filter to inject state into the handler.
@@ -85,75 +234,187 @@ fn with_state<T: Clone + Send>(
     warp::any().map(move || state.clone())
 }
 
-/// Handler for the /alerts API endpoint.
-async fn handler_get_alerts(state: GeneratedAlerts) -> Result<impl warp::Reply, warp::Rejection> {
-    let alerts_snapshot = state.lock().unwrap().clone();
+/// Handler for the /alerts API endpoint. Most severe first; a desk's own,
+/// for a desk caller.
+async fn handler_get_alerts(state: GeneratedAlerts, scope: Scope) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut alerts_snapshot = state.lock().unwrap().alerts();
+    alerts_snapshot.retain(|alert| scope.sees_desk(alert.desk.as_deref()));
     Ok(warp::reply::json(&alerts_snapshot))
 }
 
-/// Simulates listening for all order events from the message bus.
-async fn listen_for_order_events(history: StrategyOrderHistory, alerts: GeneratedAlerts) {
+/// Handler for the /alerts/spilled API endpoint: alerts moved to disk, oldest first.
+async fn handler_get_spilled_alerts(state: GeneratedAlerts) -> Result<impl warp::Reply, warp::Rejection> {
+    let spilled = state.lock().unwrap().spilled();
+    Ok(warp::reply::json(&spilled))
+}
+
+/// Handler for the /ingest/stats API endpoint.
+async fn handler_get_ingest_stats(state: SharedIngestStats) -> Result<impl warp::Reply, warp::Rejection> {
+    let stats = state.lock().unwrap().clone();
+    Ok(warp::reply::json(&stats))
+}
+
+/// Handler for the /rules API endpoint.
+async fn handler_get_rules(rules: SharedRules) -> Result<impl warp::Reply, warp::Rejection> {
+    let statuses = rules.lock().unwrap().statuses();
+    Ok(warp::reply::json(&statuses))
+}
+
+/// Handler for POST /rules/reload.
+async fn handler_reload_rules(rules: SharedRules) -> Result<impl warp::Reply, warp::Rejection> {
+    let reloaded = rules.clone();
+    let _ = tokio::task::spawn_blocking(move || rules::reload(&reloaded, true)).await;
+    let statuses = rules.lock().unwrap().statuses();
+    Ok(warp::reply::json(&statuses))
+}
+
+/// Simulates the bus subscription to the order, execution report and market
+/// data topics. Messages are numbered in arrival order and routed by topic:
+/// awaiting `send` on the order queue blocks the subscription while it is
+/// full, which is the backpressure path; market data never waits.
+async fn subscribe_to_order_topics(orders: Sender<BusMessage>, market_data: Sender<BusMessage>) {
+    // In a real system:
+    // let mut orders = nats_client.subscribe(topics::ORDER_REQUESTS).await.unwrap(); etc.
+    let encoding = Encoding::from_env();
     let mut interval = time::interval(Duration::from_secs(2));
+    let mut seq: u64 = 0;
     loop {
         interval.tick().await;
-
-        // Simulate a sequence of events indicative of layering
-        let events = vec![
-            OrderEvent { strategy_id: "NLP-NEWS-TRADER".to_string(), order_id: "A1".to_string(), event_type: OrderEventType::New, size: 5000, timestamp: Instant::now() },
-            OrderEvent { strategy_id: "NLP-NEWS-TRADER".to_string(), order_id: "A2".to_string(), event_type: OrderEventType::New, size: 10, timestamp: Instant::now() + Duration::from_millis(50) },
-            OrderEvent { strategy_id: "NLP-NEWS-TRADER".to_string(), order_id: "A2".to_string(), event_type: OrderEventType::Filled, size: 10, timestamp: Instant::now() + Duration::from_millis(100) },
-            OrderEvent { strategy_id: "NLP-NEWS-TRADER".to_string(), order_id: "A1".to_string(), event_type: OrderEventType::Canceled, size: 5000, timestamp: Instant::now() + Duration::from_millis(150) },
-        ];
-        
-        println!("\nReceived Batch of 4 Order Events...");
-        for event in events {
-            let mut history_lock = history.lock().unwrap();
-            let strategy_history = history_lock.entry(event.strategy_id.clone()).or_insert_with(VecDeque::new);
-            strategy_history.push_back(event.clone());
-
-            // Keep history to a reasonable size
-            if strategy_history.len() > 100 {
-                strategy_history.pop_front();
+        for mut message in simulated_bus_traffic(encoding) {
+            seq += 1;
+            message.seq = seq;
+            let queue = if message.topic.starts_with(topics::MARKET_DATA_PREFIX) { &market_data } else { &orders };
+            if queue.send(message).await.is_err() {
+                return; // The rule engine has stopped.
             }
-            
-            // Run detection logic
-            detect_layering_pattern(strategy_history, &alerts);
         }
     }
 }
 
-/// The core detection logic for a layering/spoofing pattern.
-fn detect_layering_pattern(history: &VecDeque<OrderEvent>, alerts: &GeneratedAlerts) {
-    // A very simple rule: find a large new order followed by a cancellation of that same order
-    // within a short time window (e.g., 200ms).
-    if history.len() < 2 { return; }
-
-    if let (Some(last_event), Some(first_event)) = (history.back(), history.front()) {
-        if last_event.order_id == first_event.order_id &&
-           matches!(first_event.event_type, OrderEventType::New) &&
-           matches!(last_event.event_type, OrderEventType::Canceled) &&
-           first_event.size > 1000 && // Was a large order
-           last_event.timestamp.duration_since(first_event.timestamp) < Duration::from_millis(200) {
-            
-            let description = format!(
-                "Strategy placed large order {} (size {}) and canceled it within 200ms.",
-                first_event.order_id, first_event.size
-            );
-            
-            let alert = ComplianceAlert {
-                alert_id: format!("ALERT-{}", rand::random::<u32>()),
-                strategy_id: first_event.strategy_id.clone(),
-                pattern_detected: "Potential Layering/Spoofing".to_string(),
-                description,
-                timestamp_utc: chrono::Utc::now().to_rfc3339(),
-            };
-            
-            println!("  -> COMPLIANCE ALERT: {}", alert.pattern_detected);
-            alerts.lock().unwrap().push(alert);
-            
-            // Clear history after detection to avoid re-alerting
-            // In a real system, you'd have more sophisticated state management.
-            // history.clear();
+/// One round of bus traffic: a large BTC bid placed behind the touch, a small
+/// sell filled while it rests, the bid pulled as the market drops towards it;
+/// and two accounts crossing ETH well above the mid.
+fn simulated_bus_traffic(encoding: Encoding) -> Vec<BusMessage> {
+    let order = |account_id, instrument_id, side, price, size| OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id,
+        instrument_id,
+        side,
+        price,
+        size,
+        stamps: HopStamps::default(),
+        venue_id: 1,
+        mode: TradingMode::Sandbox,
+        priority: OrderPriority::Opportunistic,
+        strategy_id: String::new(),
+    };
+    let report = |order: &OrderRequest, status, filled_size| ExecutionReport {
+        exchange_order_id: format!("EXCH-{}", Uuid::new_v4().simple()),
+        exec_id: Uuid::new_v4().simple().to_string(),
+        internal_order_id: order.order_id,
+        status,
+        filled_size,
+        filled_price: if filled_size > 0 { order.price } else { 0 },
+        reject: None,
+        stamps: HopStamps::default(),
+        mode: TradingMode::Sandbox,
+        cumulative_size: filled_size,
+        leaves_size: 0,
+        liquidity: (filled_size > 0).then_some(Liquidity::Taker),
+        fee: Money::ZERO,
+        transact_time_ns: utc_now_ns(),
+        exec_ref_id: String::new(),
+    };
+    let bbo = |instrument_id, bid, ask| BboUpdate {
+        instrument_id,
+        best_bid_price: bid,
+        best_ask_price: ask,
+        best_bid_size: 10,
+        best_ask_size: 10,
+        timestamp_ns: 0,
+        venue_id: 0,
+    };
+    let message = |topic: &str, payload: Vec<u8>| BusMessage { seq: 0, topic: topic.to_string(), payload };
+
+    let large = order(101, 1, OrderSide::Buy, 60000_00, 5000);
+    let small = order(101, 1, OrderSide::Sell, 60101_00, 10);
+    let cross_buy = order(101, 2, OrderSide::Buy, 3030_00, 50);
+    let cross_sell = order(205, 2, OrderSide::Sell, 3030_00, 50);
+    vec![
+        message(&topics::market_data(1), encoding.encode(&bbo(1, 60099_00, 60101_00))),
+        message(&topics::market_data(2), encoding.encode(&bbo(2, 2999_00, 3001_00))),
+        message(topics::ORDER_REQUESTS, encoding.encode(&large)),
+        message(topics::ORDER_REQUESTS, encoding.encode(&small)),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&small, OrderStatus::Filled, 10))),
+        message(&topics::market_data(1), encoding.encode(&bbo(1, 60020_00, 60022_00))),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&large, OrderStatus::Canceled, 0))),
+        message(topics::ORDER_REQUESTS, encoding.encode(&cross_buy)),
+        message(topics::ORDER_REQUESTS, encoding.encode(&cross_sell)),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&cross_buy, OrderStatus::Filled, 50))),
+        message(topics::EXECUTION_REPORTS, encoding.encode(&report(&cross_sell, OrderStatus::Filled, 50))),
+    ]
+}
+
+/// Drains the ingestion queues: normalizes each order message, adds the
+/// event to the shared window and runs the rules against it. Market data
+/// that arrived before an order message is applied first, so every event is
+/// stamped with the book as it stood when the event happened (less any
+/// quotes the market data queue dropped under load).
+#[allow(clippy::too_many_arguments)]
+async fn process_order_events(
+    mut orders: Receiver<BusMessage>,
+    mut market_data: Receiver<BusMessage>,
+    window: SharedEventWindow,
+    alerts: GeneratedAlerts,
+    stats: SharedIngestStats,
+    rules: SharedRules,
+    notifier: Notifier,
+    desks: Arc<Desks>,
+) {
+    let mut normalizer = Normalizer::default();
+    let mut next_quote: Option<BusMessage> = None;
+    while let Some(message) = orders.recv().await {
+        let event = {
+            let mut stats_lock = stats.lock().unwrap();
+            while let Some(quote) = next_quote.take().or_else(|| market_data.try_recv()) {
+                if quote.seq > message.seq {
+                    next_quote = Some(quote);
+                    break;
+                }
+                normalizer.normalize(&quote, &mut stats_lock);
+            }
+            stats_lock.queues = vec![orders.stats(), market_data.stats()];
+            normalizer.normalize(&message, &mut stats_lock)
+        };
+        let Some(event) = event else { continue };
+
+        let mut window_lock = window.lock().unwrap();
+        window_lock.push(event.clone());
+        stats.lock().unwrap().window_events = window_lock.len();
+
+        // Run detection logic
+        let mut detections = vec![spoofing::detect_spoofing(&window_lock, &event)];
+        if matches!(event.event_type, OrderEventType::Filled) {
+            detections.push(cross_account::detect_wash_trade(&window_lock, &event));
+            detections.push(cross_account::detect_collusion(&window_lock, &event));
+        }
+        // Scripted rules read the window through its lock themselves.
+        drop(window_lock);
+        detections.extend(rules.lock().unwrap().on_event(&event).into_iter().map(Some));
+        let mut alert_book = alerts.lock().unwrap();
+        for detection in detections.into_iter().flatten() {
+            let desk = desks.desk_of_strategy(&detection.strategy_id);
+            let Some(alert) = alert_book.raise(detection, desk, chrono::Utc::now()) else { continue };
+            let notification = notifier
+                .event(EventClass::ComplianceAlert)
+                .field("alert_id", &alert.alert_id)
+                .field("pattern", &alert.pattern_detected)
+                .field("severity", format!("{:?}", alert.severity))
+                .field("strategy_id", &alert.strategy_id)
+                .field("desk", alert.desk.as_deref().unwrap_or("-"))
+                .field("occurrences", alert.occurrences)
+                .field("description", &alert.description);
+            notifier.notify(notification);
         }
     }
 }
@@ -0,0 +1,521 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Scripted Surveillance Rules
+ *
+ * File: src/risk_compliance/trade_surveillance_service/rules.rs
+ *
+ * Description:
+ * Lets compliance write simple rules in Rhai, an embedded scripting
+ * language, instead of changing the service. Each rule is a file
+ * <name>.rhai in QA_SURVEILLANCE_RULES_DIR defining `on_event(event)`, which
+ * runs on every order event after the built-in rules:
+ *
+ *   // budget_ms: 5
+ *   fn on_event(event) {
+ *       if event.kind != "canceled" { return; }
+ *       let orders = strategy_events(event.strategy_id);
+ *       let placed = orders.filter(|e| e.kind == "new").len();
+ *       let canceled = orders.filter(|e| e.kind == "canceled").len();
+ *       if placed >= 20 && canceled * 10 >= placed * 9 {
+ *           alert("High Cancel Ratio", `${canceled} of ${placed} orders canceled`);
+ *       }
+ *   }
+ *
+ * An event is a map: strategy_id, account_id, order_id, instrument, side
+ * ("buy" or "sell"), kind ("new", "canceled" or "filled"), size, price,
+ * reference_price, best_bid and best_ask (() when unknown), and age_ms, how
+ * long before the current event it happened. The window of recent events is
+ * read with strategy_events(id), account_events(id) and
+ * instrument_events(symbol), oldest first, the current event included.
+ * alert(pattern, description) or alert(pattern, description, size, notional)
+ * raises a detection against the event's strategy; it goes through the alert
+ * book (scoring, suppression, notification) like the built-in rules'.
+ *
+ * Sandboxing: scripts have no file, network or module access (`import` and
+ * `eval` are off) and print only to the service's log. A call may run at
+ * most QA_SURVEILLANCE_RULE_MAX_OPERATIONS operations and for its time
+ * budget, QA_SURVEILLANCE_RULE_BUDGET_MS or the rule's own `// budget_ms: N`
+ * line; strings, arrays, maps and call depth are capped too. A call that
+ * errors or runs over is a fault, and its alerts are dropped; a rule is
+ * quarantined after QA_SURVEILLANCE_RULE_MAX_FAULTS faults in a row, until
+ * its file changes or the rules are reloaded.
+ *
+ * Hot reload: the directory is rescanned every
+ * QA_SURVEILLANCE_RULES_RELOAD_SECS. A new or changed file is compiled
+ * (outside the lock the event loop takes) and replaces its rule; a rule
+ * whose file was removed is dropped; a file that does not compile is
+ * reported and leaves no rule running under its name.
+ *
+ * API (port 3033):
+ *   GET  /rules          every rule's state and counters
+ *   POST /rules/reload   rescan the directory now, retrying quarantined rules
+ *
+ * Configuration (environment):
+ *   QA_SURVEILLANCE_RULES_DIR=surveillance_rules
+ *   QA_SURVEILLANCE_RULES_RELOAD_SECS=5
+ *   QA_SURVEILLANCE_RULE_BUDGET_MS=10
+ *   QA_SURVEILLANCE_RULE_MAX_OPERATIONS=1000000
+ *   QA_SURVEILLANCE_RULE_MAX_FAULTS=3
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, INT};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+use crate::event_window::EventKey;
+use crate::{Detection, OrderEvent, OrderEventType, SharedEventWindow, Side};
+
+/// The function a rule defines.
+const ENTRY_POINT: &str = "on_event";
+/// Detections one call may raise.
+const MAX_ALERTS_PER_CALL: usize = 16;
+/// Operations between checks of the time budget.
+const BUDGET_CHECK_OPERATIONS: u64 = 256;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct RuleConfig {
+    pub dir: PathBuf,
+    pub reload_interval: Duration,
+    pub budget: Duration,
+    pub max_operations: u64,
+    pub max_faults: u32,
+}
+
+impl RuleConfig {
+    pub fn from_env() -> RuleConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        RuleConfig {
+            dir: PathBuf::from(var("QA_SURVEILLANCE_RULES_DIR", "surveillance_rules".to_string())),
+            reload_interval: Duration::from_secs(var("QA_SURVEILLANCE_RULES_RELOAD_SECS", 5u64).max(1)),
+            budget: Duration::from_millis(var("QA_SURVEILLANCE_RULE_BUDGET_MS", 10)),
+            max_operations: var("QA_SURVEILLANCE_RULE_MAX_OPERATIONS", 1_000_000),
+            max_faults: var("QA_SURVEILLANCE_RULE_MAX_FAULTS", 3u32).max(1),
+        }
+    }
+}
+
+// --- Status ---
+
+/// A rule's state and counters, served on GET /rules.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct RuleStatus {
+    pub name: String,
+    /// running, quarantined or failed (would not compile).
+    pub state: String,
+    pub loaded_utc: DateTime<Utc>,
+    pub budget_ms: u64,
+    pub calls: u64,
+    pub alerts: u64,
+    pub faults: u64,
+    /// How long the last call ran, in microseconds.
+    pub last_micros: u64,
+    pub last_error: Option<String>,
+}
+
+// --- Script API ---
+
+/// What the script functions see during a call.
+struct CallContext {
+    rule: String,
+    event: Option<OrderEvent>,
+    detections: Vec<Detection>,
+}
+
+type SharedCallContext = Arc<Mutex<CallContext>>;
+
+fn event_map(event: &OrderEvent, now: Instant) -> Map {
+    let optional = |price: Option<f64>| price.map_or(Dynamic::UNIT, Dynamic::from_float);
+    let mut map = Map::new();
+    map.insert("strategy_id".into(), event.strategy_id.clone().into());
+    map.insert("account_id".into(), event.account_id.clone().into());
+    map.insert("order_id".into(), event.order_id.clone().into());
+    map.insert("instrument".into(), event.instrument.clone().into());
+    let side = match event.side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    };
+    map.insert("side".into(), side.into());
+    let kind = match event.event_type {
+        OrderEventType::New => "new",
+        OrderEventType::Canceled => "canceled",
+        OrderEventType::Filled => "filled",
+    };
+    map.insert("kind".into(), kind.into());
+    map.insert("size".into(), Dynamic::from_int(INT::from(event.size)));
+    map.insert("price".into(), Dynamic::from_float(event.price));
+    map.insert("reference_price".into(), Dynamic::from_float(event.reference_price));
+    map.insert("best_bid".into(), optional(event.best_bid));
+    map.insert("best_ask".into(), optional(event.best_ask));
+    let age_ms = now.saturating_duration_since(event.timestamp).as_millis();
+    map.insert("age_ms".into(), Dynamic::from_int(INT::try_from(age_ms).unwrap_or(INT::MAX)));
+    map
+}
+
+/// The sandboxed engine, with the event window and alert functions. A call
+/// is stopped once `started.elapsed()` passes the deadline it returns.
+fn build_engine(
+    config: &RuleConfig,
+    started: std::time::Instant,
+    window: SharedEventWindow,
+    context: SharedCallContext,
+) -> (Engine, Arc<AtomicU64>) {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(config.max_operations);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(1024);
+
+    // The deadline of the running call, in nanoseconds after `started`.
+    let deadline_ns = Arc::new(AtomicU64::new(u64::MAX));
+    let deadline = deadline_ns.clone();
+    engine.on_progress(move |operations| {
+        if operations % BUDGET_CHECK_OPERATIONS != 0 {
+            return None;
+        }
+        let elapsed_ns = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        (elapsed_ns > deadline.load(Ordering::Relaxed)).then(|| Dynamic::from("over its time budget"))
+    });
+
+    let print_context = context.clone();
+    engine.on_print(move |text| {
+        println!("  -> [rule {}] {}", print_context.lock().unwrap().rule, text);
+    });
+    let debug_context = context.clone();
+    engine.on_debug(move |text, _, _| {
+        println!("  -> [rule {}] {}", debug_context.lock().unwrap().rule, text);
+    });
+
+    let window_context = context.clone();
+    let events = move |key: EventKey<'_>| -> Array {
+        let Some(now) = window_context.lock().unwrap().event.as_ref().map(|event| event.timestamp) else {
+            return Array::new();
+        };
+        let window = window.lock().unwrap();
+        window.query(key).into_iter().map(|event| Dynamic::from_map(event_map(event, now))).collect()
+    };
+    let by_strategy = events.clone();
+    engine.register_fn("strategy_events", move |id: &str| by_strategy(EventKey::Strategy(id)));
+    let by_account = events.clone();
+    engine.register_fn("account_events", move |id: &str| by_account(EventKey::Account(id)));
+    engine.register_fn("instrument_events", move |symbol: &str| events(EventKey::Instrument(symbol)));
+
+    // Without a size and notional, the alert takes the event's.
+    let raise = move |pattern: &str, description: &str, amounts: Option<(INT, f64)>| {
+        let mut context = context.lock().unwrap();
+        let Some(event) = &context.event else {
+            return;
+        };
+        if context.detections.len() >= MAX_ALERTS_PER_CALL {
+            return;
+        }
+        let (size, notional) = match amounts {
+            Some((size, notional)) => (u32::try_from(size.max(0)).unwrap_or(u32::MAX), notional),
+            None => (event.size, event.size as f64 * event.price),
+        };
+        let detection = Detection {
+            strategy_id: event.strategy_id.clone(),
+            related_strategy_id: None,
+            pattern: pattern.to_string(),
+            description: description.to_string(),
+            size,
+            notional,
+        };
+        context.detections.push(detection);
+    };
+    let short = raise.clone();
+    engine.register_fn("alert", move |pattern: &str, description: &str| short(pattern, description, None));
+    engine.register_fn("alert", move |pattern: &str, description: &str, size: INT, notional: f64| {
+        raise(pattern, description, Some((size, notional)))
+    });
+    (engine, deadline_ns)
+}
+
+// --- Rules ---
+
+/// A compiled rule.
+struct Rule {
+    ast: Option<AST>,
+    modified: Option<SystemTime>,
+    budget: Duration,
+    consecutive_faults: u32,
+    status: RuleStatus,
+}
+
+/// The scripted rules and the engine they run in.
+pub struct RuleHost {
+    engine: Arc<Engine>,
+    config: RuleConfig,
+    context: SharedCallContext,
+    deadline_ns: Arc<AtomicU64>,
+    started: std::time::Instant,
+    rules: BTreeMap<String, Rule>,
+}
+
+pub type SharedRules = Arc<Mutex<RuleHost>>;
+
+impl RuleHost {
+    pub fn new(config: RuleConfig, window: SharedEventWindow) -> RuleHost {
+        let context = Arc::new(Mutex::new(CallContext { rule: String::new(), event: None, detections: Vec::new() }));
+        let started = std::time::Instant::now();
+        let (engine, deadline_ns) = build_engine(&config, started, window, context.clone());
+        RuleHost {
+            engine: Arc::new(engine),
+            config,
+            context,
+            deadline_ns,
+            started,
+            rules: BTreeMap::new(),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<RuleStatus> {
+        self.rules.values().map(|rule| rule.status.clone()).collect()
+    }
+
+    /// Installs a compiled rule, or records why it did not compile.
+    fn install(&mut self, name: &str, compiled: Result<(AST, Duration), String>, modified: Option<SystemTime>) {
+        let budget = compiled.as_ref().map_or(self.config.budget, |(_, budget)| *budget);
+        let mut status = RuleStatus {
+            name: name.to_string(),
+            state: "running".to_string(),
+            loaded_utc: Utc::now(),
+            budget_ms: u64::try_from(budget.as_millis()).unwrap_or(u64::MAX),
+            calls: 0,
+            alerts: 0,
+            faults: 0,
+            last_micros: 0,
+            last_error: None,
+        };
+        let ast = match compiled {
+            Ok((ast, _)) => {
+                println!("Loaded surveillance rule '{}'.", name);
+                Some(ast)
+            }
+            Err(e) => {
+                println!("Surveillance rule '{}' does not compile: {}", name, e);
+                status.state = "failed".to_string();
+                status.last_error = Some(e);
+                None
+            }
+        };
+        self.rules.insert(name.to_string(), Rule { ast, modified, budget, consecutive_faults: 0, status });
+    }
+
+    /// Runs every running rule on `event`; returns what they detected.
+    pub fn on_event(&mut self, event: &OrderEvent) -> Vec<Detection> {
+        let mut detections = Vec::new();
+        let input = Dynamic::from_map(event_map(event, event.timestamp));
+        for (name, rule) in self.rules.iter_mut().filter(|(_, rule)| rule.status.state == "running") {
+            let Some(ast) = &rule.ast else {
+                continue;
+            };
+            *self.context.lock().unwrap() =
+                CallContext { rule: name.clone(), event: Some(event.clone()), detections: Vec::new() };
+            let begun = self.started.elapsed();
+            let deadline = u64::try_from((begun + rule.budget).as_nanos()).unwrap_or(u64::MAX);
+            self.deadline_ns.store(deadline, Ordering::Relaxed);
+            let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), ast, ENTRY_POINT, (input.clone(),));
+            let elapsed = self.started.elapsed() - begun;
+            self.deadline_ns.store(u64::MAX, Ordering::Relaxed);
+            let raised = std::mem::take(&mut self.context.lock().unwrap().detections);
+
+            rule.status.calls += 1;
+            rule.status.last_micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+            match result {
+                Ok(_) => {
+                    rule.consecutive_faults = 0;
+                    rule.status.alerts += raised.len() as u64;
+                    detections.extend(raised);
+                }
+                Err(e) => {
+                    rule.consecutive_faults += 1;
+                    rule.status.faults += 1;
+                    println!("  -> Surveillance rule '{}' faulted: {}", name, e);
+                    rule.status.last_error = Some(e.to_string());
+                    if rule.consecutive_faults >= self.config.max_faults {
+                        let faults = rule.consecutive_faults;
+                        println!("  -> Surveillance rule '{}' quarantined after {} faults in a row.", name, faults);
+                        rule.status.state = "quarantined".to_string();
+                    }
+                }
+            }
+        }
+        self.context.lock().unwrap().event = None;
+        detections
+    }
+}
+
+/// Compiles a rule's source; returns it with its time budget.
+fn compile(engine: &Engine, source: &str, default_budget: Duration) -> Result<(AST, Duration), String> {
+    let ast = engine.compile(source).map_err(|e| e.to_string())?;
+    if !ast.iter_functions().any(|f| f.name == ENTRY_POINT && f.params.len() == 1) {
+        return Err(format!("defines no {}(event)", ENTRY_POINT));
+    }
+    let budget = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("//")?.trim().strip_prefix("budget_ms:"))
+        .find_map(|value| value.trim().parse().ok())
+        .map_or(default_budget, Duration::from_millis);
+    Ok((ast, budget))
+}
+
+// --- Reloading ---
+
+/// The rule files in `dir`, by name, with when each was last modified.
+fn scan_dir(dir: &Path) -> BTreeMap<String, (PathBuf, Option<SystemTime>)> {
+    let mut found = BTreeMap::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return found;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_rule = path.extension().and_then(|e| e.to_str()) == Some("rhai");
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()).filter(|_| is_rule) else {
+            continue;
+        };
+        let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        found.insert(name.to_string(), (path.clone(), modified));
+    }
+    found
+}
+
+/// Brings the loaded rules in line with the rules directory. With `retry`,
+/// quarantined rules are loaded again too.
+pub fn reload(rules: &SharedRules, retry: bool) {
+    let (dir, engine, budget) = {
+        let host = rules.lock().unwrap();
+        (host.config.dir.clone(), host.engine.clone(), host.config.budget)
+    };
+    let found = scan_dir(&dir);
+    let changed: Vec<(String, PathBuf, Option<SystemTime>)> = {
+        let mut host = rules.lock().unwrap();
+        let removed: Vec<String> = host.rules.keys().filter(|name| !found.contains_key(*name)).cloned().collect();
+        for name in removed {
+            host.rules.remove(&name);
+            println!("Surveillance rule '{}' removed.", name);
+        }
+        found
+            .into_iter()
+            .filter(|(name, (_, modified))| match host.rules.get(name) {
+                Some(rule) => rule.modified != *modified || (retry && rule.status.state == "quarantined"),
+                None => true,
+            })
+            .map(|(name, (path, modified))| (name, path, modified))
+            .collect()
+    };
+    for (name, path, modified) in changed {
+        // Compiled without the lock, so the event loop keeps running.
+        let compiled = std::fs::read_to_string(&path)
+            .map_err(|e| format!("unreadable: {}", e))
+            .and_then(|source| compile(&engine, &source, budget));
+        rules.lock().unwrap().install(&name, compiled, modified);
+    }
+}
+
+/// Rescans the rules directory periodically.
+pub async fn watch(rules: SharedRules) {
+    let interval = rules.lock().unwrap().config.reload_interval;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let rules = rules.clone();
+        let _ = tokio::task::spawn_blocking(move || reload(&rules, false)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_window::EventWindow;
+
+    const CANCEL_RATIO: &str = r#"
+        fn on_event(event) {
+            if event.kind != "canceled" { return; }
+            let orders = strategy_events(event.strategy_id);
+            let placed = orders.filter(|e| e.kind == "new").len();
+            let canceled = orders.filter(|e| e.kind == "canceled").len();
+            if placed >= 2 && canceled == placed {
+                alert("High Cancel Ratio", `${canceled} of ${placed} orders canceled in ${event.instrument}`);
+            }
+        }
+    "#;
+
+    fn event(order_id: &str, event_type: OrderEventType) -> OrderEvent {
+        OrderEvent {
+            strategy_id: "mm-1".to_string(),
+            account_id: "101".to_string(),
+            order_id: order_id.to_string(),
+            instrument: "BTC".to_string(),
+            side: Side::Buy,
+            event_type,
+            size: 5,
+            price: 60000.0,
+            reference_price: 60001.0,
+            best_bid: Some(60000.0),
+            best_ask: None,
+            timestamp: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn scripted_rules_read_the_window_raise_alerts_and_are_quarantined_when_they_run_over() {
+        let dir = std::env::temp_dir().join(format!("qa-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cancel_ratio.rhai"), CANCEL_RATIO).unwrap();
+        std::fs::write(dir.join("spinner.rhai"), "// budget_ms: 1\nfn on_event(event) { loop { } }").unwrap();
+        std::fs::write(dir.join("broken.rhai"), "fn on_event(event) { let x = ; }").unwrap();
+        std::fs::write(dir.join("reader.rhai"), r#"fn on_event(event) { import "secrets" as s; }"#).unwrap();
+
+        let config = RuleConfig {
+            dir: dir.clone(),
+            reload_interval: Duration::from_secs(5),
+            budget: Duration::from_millis(50),
+            max_operations: u64::MAX,
+            max_faults: 2,
+        };
+        let window = Arc::new(Mutex::new(EventWindow::new(Duration::from_secs(300))));
+        let rules = Arc::new(Mutex::new(RuleHost::new(config, window.clone())));
+        reload(&rules, false);
+
+        let mut detections = Vec::new();
+        for event in [
+            event("A", OrderEventType::New),
+            event("B", OrderEventType::New),
+            event("A", OrderEventType::Canceled),
+            event("B", OrderEventType::Canceled),
+        ] {
+            window.lock().unwrap().push(event.clone());
+            detections.extend(rules.lock().unwrap().on_event(&event));
+        }
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].pattern, "High Cancel Ratio");
+        assert_eq!(detections[0].description, "2 of 2 orders canceled in BTC");
+        assert_eq!((detections[0].size, detections[0].notional), (5, 300_000.0));
+
+        let statuses: BTreeMap<String, RuleStatus> =
+            rules.lock().unwrap().statuses().into_iter().map(|status| (status.name.clone(), status)).collect();
+        assert_eq!(statuses["broken"].state, "failed");
+        assert_eq!((statuses["cancel_ratio"].state.as_str(), statuses["cancel_ratio"].alerts), ("running", 1));
+        assert_eq!(statuses["spinner"].budget_ms, 1);
+        assert_eq!((statuses["spinner"].state.as_str(), statuses["spinner"].calls), ("quarantined", 2));
+        assert_eq!((statuses["reader"].state.as_str(), statuses["reader"].faults), ("quarantined", 2));
+
+        reload(&rules, true);
+        assert_eq!(rules.lock().unwrap().statuses()[3].state, "running");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Spoofing Detection with Book Context
+ *
+ * File: src/risk_compliance/trade_surveillance_service/spoofing.rs
+ *
+ * Description:
+ * Evaluates every cancellation of a large order against the book as it stood
+ * when the order was placed and when it was cancelled. Two signals:
+ *
+ * - Cancelled on approach: the order was placed away from the touch (behind
+ *   the best bid for a buy, the best ask for a sell) and cancelled once the
+ *   market had moved at least halfway towards it, i.e. just before it was at
+ *   risk of being filled.
+ * - Opposite-side execution: while the order rested, the same strategy
+ *   executed on the other side, the side the large order was advertising
+ *   against.
+ *
+ * Either signal raises a detection; both together are the classic spoof.
+ */
+
+use tokio::time::Duration;
+
+use crate::event_window::{EventKey, EventWindow};
+use crate::{Detection, OrderEvent, OrderEventType, Side};
+
+/// Orders at least this size are checked.
+const LARGE_ORDER_SIZE: u32 = 1000;
+/// Placed at least this far behind the touch counts as away from the market.
+const AWAY_FROM_TOUCH_BPS: f64 = 5.0;
+/// Cancelled once the distance to the touch has shrunk to this fraction.
+const APPROACH_FRACTION: f64 = 0.5;
+/// Orders resting longer than this are treated as genuine.
+const MAX_RESTING_TIME: Duration = Duration::from_secs(30);
+
+/// Basis points the order sits behind its own side's touch (negative if
+/// through it), or None without a book.
+fn distance_from_touch_bps(event: &OrderEvent) -> Option<f64> {
+    match event.side {
+        Side::Buy => event.best_bid.filter(|b| *b > 0.0).map(|bid| (bid - event.price) / bid * 10_000.0),
+        Side::Sell => event.best_ask.filter(|a| *a > 0.0).map(|ask| (event.price - ask) / ask * 10_000.0),
+    }
+}
+
+/// Runs the rule for a cancellation event.
+pub fn detect_spoofing(window: &EventWindow, cancel: &OrderEvent) -> Option<Detection> {
+    if !matches!(cancel.event_type, OrderEventType::Canceled) || cancel.size < LARGE_ORDER_SIZE {
+        return None;
+    }
+    let history = window.query(EventKey::Strategy(&cancel.strategy_id));
+    let placed = history
+        .iter()
+        .find(|e| e.order_id == cancel.order_id && matches!(e.event_type, OrderEventType::New))?;
+    let resting = cancel.timestamp.saturating_duration_since(placed.timestamp);
+    if resting > MAX_RESTING_TIME {
+        return None;
+    }
+
+    let mut signals = Vec::new();
+    if let (Some(at_placement), Some(at_cancel)) = (distance_from_touch_bps(placed), distance_from_touch_bps(cancel)) {
+        if at_placement >= AWAY_FROM_TOUCH_BPS && at_cancel <= at_placement * APPROACH_FRACTION {
+            signals.push(format!(
+                "placed {:.1} bps behind the touch and cancelled as the market came to {:.1} bps",
+                at_placement, at_cancel
+            ));
+        }
+    }
+
+    let opposite_fills: Vec<&&OrderEvent> = history
+        .iter()
+        .filter(|e| {
+            matches!(e.event_type, OrderEventType::Filled)
+                && e.instrument == cancel.instrument
+                && e.side != cancel.side
+                && e.timestamp >= placed.timestamp
+                && e.timestamp <= cancel.timestamp
+        })
+        .collect();
+    let opposite_size: u32 = opposite_fills.iter().map(|e| e.size).sum();
+    let opposite_notional: f64 = opposite_fills.iter().map(|e| e.size as f64 * e.price).sum();
+    if !opposite_fills.is_empty() {
+        signals.push(format!("{} executed on the opposite side while it rested", opposite_size));
+    }
+
+    if signals.is_empty() {
+        return None;
+    }
+    Some(Detection {
+        strategy_id: cancel.strategy_id.clone(),
+        related_strategy_id: None,
+        pattern: "Potential Spoofing".to_string(),
+        description: format!(
+            "Large {:?} order {} ({} {} @ {:.2}, rested {} ms): {}.",
+            cancel.side,
+            cancel.order_id,
+            cancel.size,
+            cancel.instrument,
+            placed.price,
+            resting.as_millis(),
+            signals.join("; ")
+        ),
+        size: cancel.size,
+        notional: cancel.size as f64 * placed.price + opposite_notional,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    /// An event on the spoofer's BTC order "S-1" (or a fill on another order).
+    struct Event {
+        order_id: &'static str,
+        side: Side,
+        event_type: OrderEventType,
+        size: u32,
+        price: f64,
+        /// (best bid, best ask) when the event happened.
+        book: Option<(f64, f64)>,
+        at_secs: u64,
+    }
+
+    impl Event {
+        fn new(side: Side, event_type: OrderEventType, size: u32, price: f64, at_secs: u64) -> Event {
+            Event { order_id: "S-1", side, event_type, size, price, book: None, at_secs }
+        }
+
+        fn book(self, bid: f64, ask: f64) -> Event {
+            Event { book: Some((bid, ask)), ..self }
+        }
+
+        fn build(&self, start: Instant) -> OrderEvent {
+            OrderEvent {
+                strategy_id: "spoofer".to_string(),
+                account_id: "ACC-1".to_string(),
+                order_id: self.order_id.to_string(),
+                instrument: "BTC".to_string(),
+                side: self.side,
+                event_type: self.event_type.clone(),
+                size: self.size,
+                price: self.price,
+                reference_price: self.price,
+                best_bid: self.book.map(|(bid, _)| bid),
+                best_ask: self.book.map(|(_, ask)| ask),
+                timestamp: start + Duration::from_secs(self.at_secs),
+            }
+        }
+    }
+
+    fn fill(side: Side, size: u32, at_secs: u64) -> Event {
+        Event { order_id: "F-1", ..Event::new(side, OrderEventType::Filled, size, 10_000.0, at_secs) }
+    }
+
+    /// Runs the rule on the last event, with every event before it in the window.
+    fn detect(events: &[Event]) -> Option<Detection> {
+        let start = Instant::now();
+        let mut window = EventWindow::new(Duration::from_secs(300));
+        for event in &events[..events.len() - 1] {
+            window.push(event.build(start));
+        }
+        detect_spoofing(&window, &events[events.len() - 1].build(start))
+    }
+
+    /// A 1,000 bid placed 5 bps behind a 10,000 best bid.
+    fn placed() -> Event {
+        Event::new(Side::Buy, OrderEventType::New, 1_000, 9_995.0, 0).book(10_000.0, 10_001.0)
+    }
+
+    fn cancel(bid: f64, at_secs: u64) -> Event {
+        Event::new(Side::Buy, OrderEventType::Canceled, 1_000, 9_995.0, at_secs).book(bid, bid + 1.0)
+    }
+
+    #[test]
+    fn a_large_order_cancelled_as_the_market_approaches_is_flagged() {
+        // Halfway is 2.5 bps; the bid came to 2.49 bps away.
+        let detection = detect(&[placed(), cancel(9_997.49, 5)]).expect("cancelled on approach");
+        assert_eq!(detection.pattern, "Potential Spoofing");
+        assert!(detection.description.contains("placed 5.0 bps behind the touch"), "{}", detection.description);
+        assert_eq!((detection.size, detection.notional), (1_000, 9_995_000.0));
+
+        assert!(detect(&[placed(), cancel(9_997.6, 5)]).is_none(), "not yet halfway");
+        let near_touch = Event { price: 9_996.0, ..placed() };
+        let near_cancel = Event { price: 9_996.0, ..cancel(9_996.0, 5) };
+        assert!(detect(&[near_touch, near_cancel]).is_none(), "placed 4 bps away");
+        let small = [Event { size: 999, ..placed() }, Event { size: 999, ..cancel(9_997.49, 5) }];
+        assert!(detect(&small).is_none(), "below the large order size");
+    }
+
+    #[test]
+    fn orders_resting_past_the_limit_are_genuine() {
+        let late = MAX_RESTING_TIME.as_secs() + 1;
+        assert!(detect(&[placed(), fill(Side::Sell, 10, 1), cancel(9_997.49, late)]).is_none());
+        assert!(detect(&[placed(), cancel(9_997.49, MAX_RESTING_TIME.as_secs())]).is_some());
+    }
+
+    #[test]
+    fn opposite_side_fills_count_only_while_the_order_rests() {
+        let placed_later = Event { at_secs: 1, ..placed() };
+        let events =
+            [fill(Side::Sell, 7, 0), placed_later, fill(Side::Sell, 3, 2), fill(Side::Buy, 5, 3), cancel(10_000.0, 4)];
+        let detection = detect(&events).expect("sold while the bid rested");
+        let description = &detection.description;
+        assert!(description.ends_with("3 executed on the opposite side while it rested."), "{}", description);
+        assert_eq!(detection.notional, 9_995_000.0 + 30_000.0);
+
+        let after_cancel = [placed(), cancel(10_000.0, 4), fill(Side::Sell, 3, 5)];
+        let start = Instant::now();
+        let mut window = EventWindow::new(Duration::from_secs(300));
+        for event in &after_cancel {
+            window.push(event.build(start));
+        }
+        assert!(detect_spoofing(&window, &after_cancel[1].build(start)).is_none(), "filled after the cancel");
+    }
+
+    #[test]
+    fn without_a_book_only_opposite_fills_can_flag_the_order() {
+        let unbooked = |event: Event| Event { book: None, ..event };
+        assert!(detect(&[unbooked(placed()), unbooked(cancel(9_997.49, 5))]).is_none());
+        let detection = detect(&[unbooked(placed()), fill(Side::Sell, 3, 2), unbooked(cancel(9_997.49, 5))]).unwrap();
+        assert!(!detection.description.contains("bps"), "{}", detection.description);
+        assert!(detect(&[cancel(9_997.49, 5)]).is_none(), "no placement in the window");
+    }
+}
@@ -0,0 +1,370 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: VaR History
+ *
+ * File: src/risk_compliance/var_calculator/history.rs
+ *
+ * Description:
+ * The calculator's record of past results: a ring buffer of the last
+ * QA_VAR_HISTORY_CAPACITY results (one every RESULT_INTERVAL_SECS), the
+ * JSON-lines file they are persisted to, the downsampling behind
+ * GET /var/history and the pairing of forecasts with closed trading days
+ * for the backtests.
+ *
+ * The history file (QA_VAR_HISTORY_PATH) is appended to by the calculation
+ * task alone, outside the store's lock. It is compacted to the ring's
+ * capacity when it is loaded and whenever it grows past twice that, so it
+ * stays bounded and quick to reload.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_risk::backtest::BacktestObservation;
+use quantumarb_types::{DailyPnl, VaRHistoryPoint, VaRResult};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+/// Seconds between results.
+pub const RESULT_INTERVAL_SECS: i64 = 15;
+// One result every 15s: 40,320 results is 7 days.
+const DEFAULT_HISTORY_CAPACITY: usize = 40_320;
+pub const DEFAULT_HISTORY_WINDOW_SECS: i64 = 24 * 3600;
+pub const DEFAULT_HISTORY_POINTS: usize = 500;
+
+// --- Store ---
+
+/// Latest result plus a bounded history, oldest first.
+pub struct VaRStore {
+    pub history: VecDeque<VaRResult>,
+    capacity: usize,
+}
+
+impl VaRStore {
+    pub fn new(history: VecDeque<VaRResult>, capacity: usize) -> VaRStore {
+        VaRStore { history, capacity }
+    }
+
+    pub fn latest(&self) -> Option<&VaRResult> {
+        self.history.back()
+    }
+
+    pub fn push(&mut self, result: VaRResult) {
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(result);
+    }
+
+    /// How far back a full history reaches, in seconds; the widest window
+    /// GET /var/history serves.
+    pub fn retention_secs(&self) -> i64 {
+        i64::try_from(self.capacity).unwrap_or(i64::MAX).saturating_mul(RESULT_INTERVAL_SECS)
+    }
+}
+
+/// Builds the store from the environment, reloading persisted results if
+/// configured. The history file, if any, is returned for the calculation task.
+pub fn load_var_store() -> (VaRStore, Option<HistoryFile>) {
+    let capacity = std::env::var("QA_VAR_HISTORY_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(DEFAULT_HISTORY_CAPACITY);
+    let Ok(path) = std::env::var("QA_VAR_HISTORY_PATH") else {
+        return (VaRStore::new(VecDeque::with_capacity(capacity), capacity), None);
+    };
+    match HistoryFile::open(&path, capacity) {
+        Ok((file, history)) => {
+            println!("Reloaded {} historical VaR results from {}", history.len(), path);
+            (VaRStore::new(history, capacity), Some(file))
+        }
+        Err(e) => {
+            println!("Failed to reload VaR history from {}: {}; starting empty", path, e);
+            (VaRStore::new(VecDeque::with_capacity(capacity), capacity), Some(HistoryFile { path, capacity, lines: 0 }))
+        }
+    }
+}
+
+// --- Persistence ---
+
+/// The JSON-lines file results are persisted to.
+pub struct HistoryFile {
+    path: String,
+    capacity: usize,
+    /// Lines in the file, counted since it was last compacted.
+    lines: usize,
+}
+
+impl HistoryFile {
+    /// Reads the results persisted at `path`, oldest first and at most
+    /// `capacity` of them, and compacts the file to those.
+    pub fn open(path: &str, capacity: usize) -> io::Result<(HistoryFile, VecDeque<VaRResult>)> {
+        let mut file = HistoryFile { path: path.to_string(), capacity, lines: 0 };
+        let (history, lines) = file.read_tail()?;
+        file.lines = lines;
+        if lines > history.len() {
+            file.rewrite(&history)?;
+        }
+        Ok((file, history))
+    }
+
+    pub fn append(&mut self, result: &VaRResult) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(result)?)?;
+        self.lines += 1;
+        if self.lines > 2 * self.capacity {
+            let (history, _) = self.read_tail()?;
+            self.rewrite(&history)?;
+        }
+        Ok(())
+    }
+
+    /// The last `capacity` results in the file and the number of lines read.
+    /// A missing file is empty; unreadable lines are skipped.
+    fn read_tail(&self) -> io::Result<(VecDeque<VaRResult>, usize)> {
+        let mut history = VecDeque::with_capacity(self.capacity);
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((history, 0)),
+            Err(e) => return Err(e),
+        };
+        let mut lines = 0;
+        for line in BufReader::new(file).lines() {
+            lines += 1;
+            if let Ok(result) = serde_json::from_str::<VaRResult>(&line?) {
+                if history.len() == self.capacity {
+                    history.pop_front();
+                }
+                history.push_back(result);
+            }
+        }
+        Ok((history, lines))
+    }
+
+    /// Replaces the file with `history`, through a temporary file so a crash
+    /// midway leaves the old one in place.
+    fn rewrite(&mut self, history: &VecDeque<VaRResult>) -> io::Result<()> {
+        let temporary = format!("{}.compacting", self.path);
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        for result in history {
+            writeln!(writer, "{}", serde_json::to_string(result)?)?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&temporary, &self.path)?;
+        self.lines = history.len();
+        Ok(())
+    }
+}
+
+// --- Queries ---
+
+/// Parses a window such as "90s", "30m", "24h" or "7d" into seconds. None if
+/// it is malformed or does not fit in an i64.
+pub fn parse_window(window: &str) -> Option<i64> {
+    let (value, unit) = window.split_at(window.char_indices().last()?.0);
+    let value: i64 = value.parse().ok().filter(|v| *v > 0)?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    value.checked_mul(multiplier)
+}
+
+/// Reduces the results to at most `max_points` by splitting the window into
+/// equal time buckets and keeping the worst VaR in each non-empty bucket.
+pub fn downsample(
+    results: &[&VaRResult],
+    since: DateTime<Utc>,
+    window_secs: i64,
+    max_points: usize,
+) -> Vec<VaRHistoryPoint> {
+    if results.len() <= max_points {
+        return results
+            .iter()
+            .map(|r| VaRHistoryPoint {
+                timestamp_utc: r.timestamp_utc,
+                var_amount: r.var_amount,
+                portfolio_value: r.portfolio_value,
+                samples: 1,
+            })
+            .collect();
+    }
+
+    let bucket_ms = (window_secs.saturating_mul(1000) / max_points as i64).max(1);
+    let mut points: Vec<VaRHistoryPoint> = Vec::with_capacity(max_points);
+    let mut current_bucket = -1;
+    for result in results {
+        let bucket = (result.timestamp_utc - since).num_milliseconds() / bucket_ms;
+        match points.last_mut() {
+            Some(point) if bucket == current_bucket => {
+                point.samples += 1;
+                if result.var_amount > point.var_amount {
+                    point.var_amount = result.var_amount;
+                    point.portfolio_value = result.portfolio_value;
+                }
+            }
+            _ => {
+                current_bucket = bucket;
+                points.push(VaRHistoryPoint {
+                    timestamp_utc: since + chrono::Duration::milliseconds(bucket * bucket_ms),
+                    var_amount: result.var_amount,
+                    portfolio_value: result.portfolio_value,
+                    samples: 1,
+                });
+            }
+        }
+    }
+    points
+}
+
+// --- Backtest Pairing ---
+
+/// Pairs each day closed after `last_evaluated` with the VaR forecast in
+/// force when the day started, and moves `last_evaluated` past them. Days
+/// without a forecast are skipped.
+pub fn pair_with_forecasts(
+    days: Vec<DailyPnl>,
+    history: &VecDeque<VaRResult>,
+    last_evaluated: &mut Option<DateTime<Utc>>,
+) -> Vec<BacktestObservation> {
+    let mut observations = Vec::new();
+    for day in days {
+        if last_evaluated.is_some_and(|t| day.period_end_utc <= t) {
+            continue;
+        }
+        *last_evaluated = Some(day.period_end_utc);
+        let Some(forecast) = history.iter().rev().find(|r| r.timestamp_utc <= day.period_start_utc) else {
+            println!("  -> No VaR forecast for the day starting {}; skipped in backtest.", day.period_start_utc);
+            continue;
+        };
+        observations.push(BacktestObservation::new(
+            day.period_start_utc,
+            day.period_end_utc,
+            forecast.confidence_level,
+            forecast.var_amount,
+            day.pnl.to_f64(),
+        ));
+    }
+    observations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_790_000_000 + secs, 0).unwrap()
+    }
+
+    fn result(secs: i64, var_amount: f64) -> VaRResult {
+        VaRResult {
+            confidence_level: 0.99,
+            var_amount,
+            standard_error: 0.0,
+            expected_shortfall: 0.0,
+            var_interval: None,
+            es_interval: None,
+            converged: true,
+            portfolio_value: var_amount * 10.0,
+            timestamp_utc: at(secs),
+        }
+    }
+
+    fn history_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("quantumarb-var-history-{}-{}.jsonl", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn line_count(path: &str) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn windows_parse_by_unit_and_reject_overflow() {
+        assert_eq!(parse_window("90s"), Some(90));
+        assert_eq!(parse_window("30m"), Some(1_800));
+        assert_eq!(parse_window("24h"), Some(86_400));
+        assert_eq!(parse_window("7d"), Some(604_800));
+        for malformed in ["", "7", "d", "7w", "0d", "-1h", "1.5h", "5é"] {
+            assert_eq!(parse_window(malformed), None, "{}", malformed);
+        }
+        assert_eq!(parse_window("200000000000000d"), None);
+        assert_eq!(parse_window(&format!("{}s", i64::MAX)), Some(i64::MAX));
+    }
+
+    #[test]
+    fn downsampling_keeps_the_worst_var_of_each_bucket() {
+        let results = [result(0, 5.0), result(10, 9.0), result(20, 7.0), result(61, 4.0), result(119, 6.0)];
+        let refs: Vec<&VaRResult> = results.iter().collect();
+        assert_eq!(downsample(&refs, at(0), 120, 5).len(), 5, "few enough to keep every result");
+
+        // Two 60s buckets.
+        let points = downsample(&refs, at(0), 120, 2);
+        let summary: Vec<_> =
+            points.iter().map(|p| (p.timestamp_utc, p.var_amount, p.portfolio_value, p.samples)).collect();
+        assert_eq!(summary, [(at(0), 9.0, 90.0, 3), (at(60), 6.0, 60.0, 2)]);
+
+        // A window too long to express in milliseconds still buckets.
+        assert_eq!(downsample(&refs, at(0), i64::MAX, 2).len(), 1);
+    }
+
+    #[test]
+    fn the_store_keeps_the_newest_results_up_to_its_retention() {
+        let mut store = VaRStore::new(VecDeque::new(), 3);
+        for secs in 0..5 {
+            store.push(result(secs, secs as f64));
+        }
+        assert_eq!(store.history.iter().map(|r| r.var_amount).collect::<Vec<_>>(), [2.0, 3.0, 4.0]);
+        assert_eq!(store.latest().unwrap().var_amount, 4.0);
+        assert_eq!(store.retention_secs(), 45);
+        assert_eq!(VaRStore::new(VecDeque::new(), usize::MAX).retention_secs(), i64::MAX);
+    }
+
+    #[test]
+    fn the_history_file_is_compacted_to_capacity() {
+        let path = history_path("compact");
+        let (mut file, history) = HistoryFile::open(&path, 3).unwrap();
+        assert!(history.is_empty(), "a missing file is empty");
+        for secs in 0..6 {
+            file.append(&result(secs, secs as f64)).unwrap();
+        }
+        assert_eq!(line_count(&path), 6, "up to twice the capacity");
+        file.append(&result(6, 6.0)).unwrap();
+        assert_eq!(line_count(&path), 3);
+
+        // A longer file, with a torn line, is compacted when it is loaded.
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"torn\n");
+        std::fs::write(&path, contents).unwrap();
+        let (_, history) = HistoryFile::open(&path, 2).unwrap();
+        assert_eq!(history.iter().map(|r| r.var_amount).collect::<Vec<_>>(), [5.0, 6.0]);
+        assert_eq!(line_count(&path), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn each_day_is_paired_once_with_the_forecast_in_force_at_its_start() {
+        use quantumarb_money::Money;
+        let history: VecDeque<VaRResult> = [result(100, 1_000.0), result(200, 2_000.0)].into();
+        let day = |start: i64, pnl: f64| DailyPnl {
+            period_start_utc: at(start),
+            period_end_utc: at(start + 86_400),
+            pnl: Money::from_f64(pnl),
+        };
+
+        let mut last_evaluated = None;
+        let days = vec![day(50, -500.0), day(150, -1_500.0), day(200, -1_500.0)];
+        let observations = pair_with_forecasts(days.clone(), &history, &mut last_evaluated);
+        let paired: Vec<_> = observations.iter().map(|o| (o.period_start_utc, o.predicted_var, o.exception)).collect();
+        // The first day started before any forecast.
+        assert_eq!(paired, [(at(150), 1_000.0, true), (at(200), 2_000.0, false)]);
+        assert_eq!(last_evaluated, Some(at(200 + 86_400)));
+
+        assert!(pair_with_forecasts(days, &history, &mut last_evaluated).is_empty(), "already evaluated");
+        assert_eq!(pair_with_forecasts(vec![day(300, 10.0)], &history, &mut last_evaluated).len(), 1);
+    }
+}
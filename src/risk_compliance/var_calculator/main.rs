@@ -17,24 +17,72 @@
  * the simulation results.
  * 4. Expose the calculated VaR via an API for consumption by risk dashboards
  * and the main risk gateway.
+ * 5. Keep a history of results in a ring buffer, served as a downsampled
+ * time series on GET /var/history?window=24h&points=500 (at most the
+ * history's retention). Set QA_VAR_HISTORY_PATH to also append each result
+ * to a JSON-lines file, which is reloaded on startup so the history survives
+ * restarts; see `history.rs`.
+ * 6. Backtest the model against the daily P&L closed by the portfolio
+ * manager (Kupiec POF and Basel traffic light), served on GET /var/backtest.
  *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * warp = "0.3"
- * serde = { version = "1.0", features = ["derive"] }
- * rand = "0.8"
- * rand_distr = "0.4"
+ * Daily returns are drawn per asset from a normal, Student-t or empirical
+ * (bootstrap) distribution fitted to the historical return store; select
+ * them with QA_VAR_DISTRIBUTIONS=BTC:student-t,ETH:empirical. The
+ * distributions, the Monte Carlo engine and the backtests live in
+ * `quantumarb-risk`; this service feeds them and serves the results. The
+ * paths are generated four at a time in SIMD lanes; set
+ * QA_VAR_ENGINE=scalar for the one-path-at-a-time engine. QA_VAR_SEQUENCE=sobol,
+ * QA_VAR_ANTITHETIC and QA_VAR_CONTROL_VARIATE trade the engine for
+ * quasi-random paths and variance reduction. Each result reports the
+ * standard error of its VaR, the expected shortfall, 95% bootstrap
+ * intervals for both, and whether the VaR has converged: a VaR on half the
+ * paths within QA_VAR_CONVERGENCE_TOLERANCE (default 5%) of the VaR on all
+ * of them. The risk gateway skips results that have not.
+ *
+ * Rates positions (bonds and interest rate futures) are valued off a yield
+ * curve bootstrapped from deposit and swap quotes (QA_YIELD_CURVE_PATH, or
+ * the built-in USD curve), rebuilt as quotes arrive on 'market_data.rates'.
+ * Each Monte Carlo path shocks the curve's level and slope (daily vols
+ * QA_CURVE_PARALLEL_VOL_BP, QA_CURVE_SLOPE_VOL_BP) rather than drawing a
+ * price return for them. GET /curve serves the curve, and
+ * GET /var/curve-scenarios the rates book's P&L under parallel, steepener
+ * and flattener stress moves.
+ *
+ * POST /var/incremental prices hypothetical changes in quantities (the
+ * portfolio manager's what-if API): it returns the VaR before and after the
+ * change, simulated on the same paths so the delta is the change's
+ * incremental VaR. Symbols not held are simulated with a normal distribution
+ * at DEFAULT_DAILY_VOLATILITY and need a price.
+ *
+ * Run with --seed N (or QA_SEED) for reproducible results: the same seed
+ * draws the same simulated return history and Monte Carlo paths, so runs
+ * on the same engine produce identical VaR figures (see `quantumarb-sim`).
+ *
+ * GET /openapi.json serves the API's OpenAPI document and GET /docs browses
+ * it in Swagger UI.
  */
 
-use rand::thread_rng;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_risk::backtest::{self, BacktestObservation, BacktestReport, BACKTEST_WINDOW};
+use quantumarb_risk::curves::{self, CurveFactors, CurvePoint, CurveQuote, RateInstrument, RatePosition, YieldCurve};
+use quantumarb_risk::diagnostics::{self, Interval};
+use quantumarb_risk::distributions::{self, DistributionKind, ReturnSampler};
+use quantumarb_risk::monte_carlo::{self, RatesBook, SimulatedAsset, VarSampling};
+use quantumarb_sim::{Seed, SimRng};
 use rand_distr::{Distribution, Normal};
-use serde::Serialize;
-use std::collections::HashMap;
+use quantumarb_types::{ConfidenceInterval, DailyPnl, VaRHistory, VaRResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use tokio::time::{self, Duration};
 use warp::Filter;
 
+mod history;
+
+use history::{HistoryFile, VaRStore};
+
 // --- Data Structures ---
 
 #[derive(Debug, Clone, Serialize)]
@@ -43,45 +91,217 @@ struct Position {
     quantity: i64,      // Can be negative for short positions
     current_price: f64,
     daily_return_volatility: f64, // Standard deviation of daily returns
+    distribution: DistributionKind,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct VaRResult {
+/// Query parameters for GET /var/history.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct HistoryQuery {
+    window: Option<String>,
+    points: Option<usize>,
+}
+
+/// The latest curve quotes and the curve bootstrapped from them.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+struct CurveState {
+    quotes: Vec<CurveQuote>,
+    #[serde(skip)]
+    curve: YieldCurve,
+    points: Vec<CurvePoint>,
+    updated_utc: DateTime<Utc>,
+}
+
+impl CurveState {
+    fn new(quotes: Vec<CurveQuote>, curve: YieldCurve) -> CurveState {
+        CurveState { points: curve.points(), quotes, curve, updated_utc: Utc::now() }
+    }
+}
+
+/// Body of a POST /var/incremental request.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct IncrementalVaRRequest {
+    changes: Vec<PositionChange>,
+}
+
+/// A change in one symbol's quantity.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct PositionChange {
+    symbol: String,
+    quantity: i64,
+    /// Value of one unit; required for symbols the portfolio does not hold.
+    price: Option<f64>,
+}
+
+/// Body of a POST /var/incremental response.
+#[derive(Debug, Serialize, ApiSchema)]
+struct IncrementalVaRResult {
     confidence_level: f64,
-    var_amount: f64, // The calculated Value at Risk
-    portfolio_value: f64,
-    timestamp_utc: String,
+    var_before: f64,
+    var_after: f64,
+    /// var_after - var_before: negative when the change reduces risk.
+    incremental_var: f64,
+    timestamp_utc: DateTime<Utc>,
+}
+
+/// What the periodic VaR run and POST /var/incremental simulate with.
+#[derive(Clone)]
+struct VaRModel {
+    portfolio: PortfolioState,
+    return_history: Arc<HashMap<String, Vec<f64>>>,
+    curve: SharedCurve,
+    rates: RatesPositions,
+    factors: CurveFactors,
+    /// The periodic run's engine and variance reduction; what-if changes
+    /// run plain on the scalar engine.
+    sampling: VarSampling,
+    /// Relative change between half and all of the paths that still counts
+    /// as converged.
+    convergence_tolerance: f64,
+    /// Draws for POST /var/incremental; the periodic run has its own stream.
+    rng: Arc<Mutex<SimRng>>,
+}
+
+/// One stress scenario of GET /var/curve-scenarios.
+#[derive(Debug, Serialize, ApiSchema)]
+struct CurveScenarioResult {
+    scenario: &'static str,
+    parallel_bp: f64,
+    slope_bp: f64,
+    pnl: f64,
+    by_position: HashMap<String, f64>,
 }
 
 type PortfolioState = Arc<Mutex<HashMap<String, Position>>>;
-type VaRHistory = Arc<Mutex<Option<VaRResult>>>;
+type SharedCurve = Arc<Mutex<CurveState>>;
+type RatesPositions = Arc<Vec<RatePosition>>;
+type SharedVaRStore = Arc<Mutex<VaRStore>>;
+type SharedBacktest = Arc<Mutex<VecDeque<BacktestObservation>>>;
+
+const PORTFOLIO_DAILY_PNL_URL: &str = "http://portfolio-manager.default.svc.cluster.local/portfolio/pnl/daily";
+
+const NUM_SIMULATIONS: usize = 10_000;
+const CONFIDENCE_LEVEL: f64 = 0.99;
+/// Daily volatility assumed for symbols without a return history.
+const DEFAULT_DAILY_VOLATILITY: f64 = 0.02;
 
 // --- Main Application Logic ---
 
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 Real-time VaR Calculator ---");
+    let seed = Seed::from_args();
+    println!("Simulation: {}", seed.describe());
 
     // Initialize the portfolio state
     let portfolio = Arc::new(Mutex::new(load_initial_portfolio()));
-    // Store the latest VaR result
-    let latest_var = Arc::new(Mutex::new(None));
+    // Historical daily returns used to fit each asset's distribution
+    let mut symbols: Vec<(String, f64)> = portfolio
+        .lock()
+        .unwrap()
+        .values()
+        .map(|p| (p.symbol.clone(), p.daily_return_volatility))
+        .collect();
+    symbols.sort_by(|a, b| a.0.cmp(&b.0));
+    let return_history = Arc::new(distributions::load_return_history(&symbols, &mut seed.stream("var.return_history")));
+    // Store the latest VaR result and its history
+    let (store, history_file) = history::load_var_store();
+    let latest_var = Arc::new(Mutex::new(store));
+
+    // Bootstrap the yield curve the rates positions are valued off
+    let curve: SharedCurve = Arc::new(Mutex::new(load_curve()));
+    let rates: RatesPositions = Arc::new(load_rates_positions());
+    let factors = CurveFactors::from_env();
+    println!("Curve factors: {:?}", factors);
+    let sampling = VarSampling::from_env();
+    println!("Monte Carlo sampling: {:?}", sampling);
+
+    // Spawn the background task that rebuilds the curve from the rates feed
+    let (curve_clone, feed_rng) = (curve.clone(), seed.stream("var.curve_feed"));
+    tokio::spawn(async move {
+        listen_for_curve_quotes(curve_clone, feed_rng).await;
+    });
+
+    let model = VaRModel {
+        portfolio,
+        return_history,
+        curve: curve.clone(),
+        rates: rates.clone(),
+        factors,
+        sampling,
+        convergence_tolerance: diagnostics::convergence_tolerance_from_env(),
+        rng: Arc::new(Mutex::new(seed.stream("var.incremental"))),
+    };
 
     // Spawn the background calculation task
-    let portfolio_clone = portfolio.clone();
+    let (model_clone, latest_var_clone) = (model.clone(), latest_var.clone());
+    let rng = seed.stream("var.monte_carlo");
+    tokio::spawn(async move {
+        run_var_calculations(model_clone, latest_var_clone, history_file, rng).await;
+    });
+
+    // Spawn the backtesting task
+    let backtest = Arc::new(Mutex::new(VecDeque::with_capacity(BACKTEST_WINDOW)));
     let latest_var_clone = latest_var.clone();
+    let backtest_clone = backtest.clone();
     tokio::spawn(async move {
-        run_var_calculations(portfolio_clone, latest_var_clone).await;
+        run_backtest(latest_var_clone, backtest_clone).await;
     });
 
     // --- API Endpoint to get the latest VaR ---
-    let get_var = warp::path("var")
+    let get_var = warp::path!("var")
         .and(warp::get())
-        .and(with_state(latest_var))
+        .and(with_state(latest_var.clone()))
         .and_then(handler_get_latest_var);
+    // --- API Endpoint to chart VaR over time ---
+    let get_history = warp::path!("var" / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .and(with_state(latest_var))
+        .and_then(handler_get_var_history);
+    // --- API Endpoint for model validation ---
+    let get_backtest = warp::path!("var" / "backtest")
+        .and(warp::get())
+        .and(with_state(backtest))
+        .and_then(handler_get_backtest);
+    // --- API Endpoint to price hypothetical changes ---
+    let post_incremental = warp::path!("var" / "incremental")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_state(model))
+        .and_then(handler_post_incremental_var);
+
+    // --- API Endpoints for the yield curve and its stress scenarios ---
+    let get_curve = warp::path!("curve")
+        .and(warp::get())
+        .and(with_state(curve.clone()))
+        .and_then(handler_get_curve);
+    let get_curve_scenarios = warp::path!("var" / "curve-scenarios")
+        .and(warp::get())
+        .and(with_state(curve))
+        .and(with_state(rates))
+        .and_then(handler_get_curve_scenarios);
+
+    println!("API server running at http://127.0.0.1:3031/var and /curve");
+    let routes = get_var
+        .or(get_history)
+        .or(get_backtest)
+        .or(post_incremental)
+        .or(get_curve)
+        .or(get_curve_scenarios)
+        .or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3031)).await;
+}
 
-    println!("API server running at http://127.0.0.1:3031/var");
-    warp::serve(get_var).run(([127, 0, 0, 1], 3031)).await;
+/// The OpenAPI document of the VaR and curve endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("VaR Calculator", "Monte Carlo Value at Risk, its history and backtests, and the yield curve.")
+        .errors::<ErrorBody>()
+        .get::<VaRResult>("/var", "The latest VaR")
+        .operation(Operation::get("/var/history", "VaR over time").query::<HistoryQuery>().response::<VaRHistory>())
+        .get::<BacktestReport>("/var/backtest", "Kupiec POF and Basel traffic light backtests")
+        .post::<IncrementalVaRRequest, IncrementalVaRResult>("/var/incremental", "VaR before and after changes")
+        .get::<CurveState>("/curve", "The curve quotes and the bootstrapped zero curve")
+        .get::<Vec<CurveScenarioResult>>("/var/curve-scenarios", "The rates book's P&L under curve stress moves")
 }
 
 /// Warp filter to inject state into the handler.
@@ -92,81 +312,370 @@ fn with_state<T: Clone + Send>(
 }
 
 /// Handler for the /var API endpoint.
-async fn handler_get_latest_var(state: VaRHistory) -> Result<impl warp::Reply, warp::Rejection> {
-    let result = state.lock().unwrap().clone();
+async fn handler_get_latest_var(state: SharedVaRStore) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = state.lock().unwrap().latest().cloned();
     match result {
-        Some(var_result) => Ok(warp::reply::json(&var_result)),
-        None => Ok(warp::reply::json(&serde_json::json!({ "error": "VaR not calculated yet." }))),
+        Some(var_result) => Ok(warp::reply::with_status(warp::reply::json(&var_result), warp::http::StatusCode::OK)),
+        None => {
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemNotReady, "VaR not calculated yet."));
+            Ok(warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::SERVICE_UNAVAILABLE))
+        }
+    }
+}
+
+/// Handler for GET /var/history.
+async fn handler_get_var_history(query: HistoryQuery, state: SharedVaRStore) -> Result<impl warp::Reply, warp::Rejection> {
+    let retention_secs = state.lock().unwrap().retention_secs();
+    let window_secs = match query.window.as_deref().map(history::parse_window) {
+        None => history::DEFAULT_HISTORY_WINDOW_SECS.min(retention_secs),
+        Some(Some(secs)) if secs <= retention_secs => secs,
+        Some(parsed) => {
+            let message = match parsed {
+                Some(_) => format!("window may be at most {}s, the history's retention.", retention_secs),
+                None => "window must look like 90s, 30m, 24h or 7d.".to_string(),
+            };
+            let body = ErrorBody::from(Rejection::new(RejectCode::SystemInvalidRequest, message));
+            return Ok(warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::BAD_REQUEST));
+        }
+    };
+    let max_points = query.points.unwrap_or(history::DEFAULT_HISTORY_POINTS).max(1);
+
+    // Out of range only for a retention beyond chrono's calendar.
+    let since = chrono::Duration::try_seconds(window_secs)
+        .and_then(|window| Utc::now().checked_sub_signed(window))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let store = state.lock().unwrap();
+    let in_window: Vec<&VaRResult> = store.history.iter().filter(|r| r.timestamp_utc >= since).collect();
+    let points = history::downsample(&in_window, since, window_secs, max_points);
+
+    let response = VaRHistory { window_secs, points };
+    Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK))
+}
+
+/// Handler for GET /var/backtest.
+async fn handler_get_backtest(state: SharedBacktest) -> Result<impl warp::Reply, warp::Rejection> {
+    let observations: Vec<BacktestObservation> = state.lock().unwrap().iter().cloned().collect();
+    Ok(warp::reply::json(&backtest::evaluate(&observations)))
+}
+
+/// Handler for GET /curve: the quotes and the bootstrapped zero curve.
+async fn handler_get_curve(curve: SharedCurve) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = curve.lock().unwrap().clone();
+    Ok(warp::reply::json(&state))
+}
+
+/// Handler for GET /var/curve-scenarios: the rates book's P&L under each
+/// stress move of the curve.
+async fn handler_get_curve_scenarios(
+    curve: SharedCurve,
+    rates: RatesPositions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let base = curve.lock().unwrap().curve.clone();
+    let results: Vec<CurveScenarioResult> = curves::SCENARIOS
+        .iter()
+        .map(|(scenario, shock)| {
+            let shocked = base.shocked(*shock);
+            let by_position: HashMap<String, f64> = rates
+                .iter()
+                .map(|position| (position.symbol.clone(), position.value(&shocked) - position.value(&base)))
+                .collect();
+            CurveScenarioResult {
+                scenario,
+                parallel_bp: shock.parallel_bp,
+                slope_bp: shock.slope_bp,
+                pnl: by_position.values().sum(),
+                by_position,
+            }
+        })
+        .collect();
+    Ok(warp::reply::json(&results))
+}
+
+/// Handler for POST /var/incremental: VaR before and after the changes,
+/// from the same Monte Carlo paths.
+async fn handler_post_incremental_var(
+    request: IncrementalVaRRequest,
+    model: VaRModel,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut positions = model.portfolio.lock().unwrap().clone();
+    let mut changes: HashMap<String, f64> = HashMap::new();
+    for change in &request.changes {
+        if !positions.contains_key(&change.symbol) {
+            let Some(price) = change.price.filter(|p| *p > 0.0) else {
+                let body = ErrorBody::from(Rejection::new(
+                    RejectCode::SystemInvalidRequest,
+                    format!("{} is not held: its change needs a price.", change.symbol),
+                ));
+                return Ok(warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::BAD_REQUEST));
+            };
+            positions.insert(change.symbol.clone(), Position {
+                symbol: change.symbol.clone(),
+                quantity: 0,
+                current_price: price,
+                daily_return_volatility: DEFAULT_DAILY_VOLATILITY,
+                distribution: DistributionKind::Normal,
+            });
+        }
+        *changes.entry(change.symbol.clone()).or_default() += change.quantity as f64;
+    }
+
+    let samplers = fit_samplers(&positions, &model.return_history);
+    let assets = simulated_assets(&samplers);
+    let quantity_changes: Vec<f64> =
+        samplers.iter().map(|(position, _)| changes.get(&position.symbol).copied().unwrap_or(0.0)).collect();
+    let yield_curve = model.curve.lock().unwrap().curve.clone();
+    let rates_book = RatesBook { curve: &yield_curve, positions: &model.rates, factors: model.factors };
+    let mut rng = model.rng.lock().unwrap();
+    let (var_before, var_after) = monte_carlo::simulate_var_change(
+        &assets,
+        &quantity_changes,
+        Some(&rates_book),
+        NUM_SIMULATIONS,
+        CONFIDENCE_LEVEL,
+        &mut *rng,
+    );
+    let result = IncrementalVaRResult {
+        confidence_level: CONFIDENCE_LEVEL,
+        var_before,
+        var_after,
+        incremental_var: var_after - var_before,
+        timestamp_utc: Utc::now(),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&result), warp::http::StatusCode::OK))
+}
+
+/// Background task that pairs each trading day closed by the portfolio manager
+/// with the VaR forecast in force when the day started.
+async fn run_backtest(latest_var: SharedVaRStore, backtest: SharedBacktest) {
+    let http_client = quantumarb_tenancy::firm_client();
+    let mut last_evaluated: Option<DateTime<Utc>> = None;
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let days = match http_client.get(PORTFOLIO_DAILY_PNL_URL).send().await {
+            Ok(response) => match response.json::<Vec<DailyPnl>>().await {
+                Ok(days) => days,
+                Err(_) => continue,
+            },
+            Err(_) => continue,
+        };
+
+        let observations = {
+            let store = latest_var.lock().unwrap();
+            history::pair_with_forecasts(days, &store.history, &mut last_evaluated)
+        };
+        for observation in observations {
+            println!(
+                "\nBacktest: day P&L ${:.2} vs VaR ${:.2}{}",
+                observation.realized_pnl,
+                observation.predicted_var,
+                if observation.exception { " -> EXCEPTION" } else { "" }
+            );
+            let mut observations = backtest.lock().unwrap();
+            if observations.len() == BACKTEST_WINDOW {
+                observations.pop_front();
+            }
+            observations.push_back(observation);
+        }
+    }
+}
+
+/// Bootstraps the curve from the configured quotes, or the built-in ones if
+/// they don't bootstrap.
+fn load_curve() -> CurveState {
+    let quotes = curves::load_quotes_from_env();
+    let (quotes, curve) = match YieldCurve::bootstrap(&quotes) {
+        Ok(curve) => (quotes, curve),
+        Err(e) => {
+            println!("Yield curve quotes don't bootstrap ({}). Using the built-in curve.", e);
+            let quotes = curves::default_quotes();
+            let curve = YieldCurve::bootstrap(&quotes).expect("the built-in curve bootstraps");
+            (quotes, curve)
+        }
+    };
+    for point in curve.points() {
+        println!("  -> Curve {:>6.2}Y: zero {:.4}%", point.tenor_years, point.zero_rate * 100.0);
+    }
+    CurveState::new(quotes, curve)
+}
+
+/// Simulates receiving curve quotes from the 'market_data.rates' topic and
+/// rebuilds the curve from each set.
+async fn listen_for_curve_quotes(curve: SharedCurve, mut rng: SimRng) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe("market_data.rates").await.unwrap();
+    // while let Some(message) = subscriber.next().await { ... }
+    let move_bp = Normal::new(0.0, 1.0).unwrap();
+    let mut interval = time::interval(Duration::from_secs(60));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let mut quotes = curve.lock().unwrap().quotes.clone();
+        for quote in &mut quotes {
+            quote.rate += move_bp.sample(&mut rng) * curves::BASIS_POINT;
+        }
+        match YieldCurve::bootstrap(&quotes) {
+            Ok(rebuilt) => *curve.lock().unwrap() = CurveState::new(quotes, rebuilt),
+            Err(e) => println!("  -> Curve quotes rejected, keeping the previous curve: {}", e),
+        }
     }
 }
 
 /// Background task to periodically run the Monte Carlo VaR simulation.
-async fn run_var_calculations(portfolio: PortfolioState, latest_var: VaRHistory) {
-    let mut interval = time::interval(Duration::from_secs(15)); // Recalculate every 15 seconds
+async fn run_var_calculations(
+    model: VaRModel,
+    latest_var: SharedVaRStore,
+    mut history_file: Option<HistoryFile>,
+    mut rng: SimRng,
+) {
+    let mut interval = time::interval(Duration::from_secs(history::RESULT_INTERVAL_SECS as u64));
     loop {
         interval.tick().await;
         println!("\nRunning new Monte Carlo VaR simulation...");
 
-        let portfolio_snapshot = portfolio.lock().unwrap().clone();
-        let num_simulations = 10000;
-        let confidence_level = 0.99;
-        let time_horizon_days = 1;
+        let portfolio_snapshot = model.portfolio.lock().unwrap().clone();
 
-        let mut final_values = Vec::with_capacity(num_simulations);
+        // The rates positions are futures: they add risk but, margined
+        // daily, no value of their own.
+        let yield_curve = model.curve.lock().unwrap().curve.clone();
         let initial_portfolio_value: f64 = portfolio_snapshot
             .values()
             .map(|p| p.quantity as f64 * p.current_price)
             .sum();
-
-        for _ in 0..num_simulations {
-            let mut simulated_portfolio_value = 0.0;
-            for position in portfolio_snapshot.values() {
-                // Assume returns are normally distributed (a simplification)
-                let normal = Normal::new(0.0, position.daily_return_volatility).unwrap();
-                let random_return = normal.sample(&mut thread_rng());
-                
-                let simulated_price = position.current_price * (1.0 + random_return);
-                simulated_portfolio_value += position.quantity as f64 * simulated_price;
-            }
-            final_values.push(simulated_portfolio_value);
+        for position in model.rates.iter() {
+            println!(
+                "  -> {}: {:.3} on the curve, DV01 ${:.2}",
+                position.symbol,
+                position.instrument.price(&yield_curve),
+                position.dv01(&yield_curve)
+            );
         }
 
-        // Calculate VaR by finding the appropriate percentile in the simulated losses
-        let mut losses: Vec<f64> = final_values
-            .into_iter()
-            .map(|final_value| initial_portfolio_value - final_value)
-            .collect();
-        losses.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // Fit each asset's return distribution once per run.
+        let samplers = fit_samplers(&portfolio_snapshot, &model.return_history);
+        for (position, sampler) in &samplers {
+            println!("  -> {}: {}", position.symbol, sampler.describe());
+        }
+        let assets = simulated_assets(&samplers);
 
-        let var_index = (num_simulations as f64 * confidence_level) as usize;
-        let var_amount = losses[var_index];
+        let rates_book = RatesBook { curve: &yield_curve, positions: &model.rates, factors: model.factors };
+        let (estimate, diagnosed) = monte_carlo::simulate_var_with_diagnostics(
+            model.sampling,
+            &assets,
+            Some(&rates_book),
+            NUM_SIMULATIONS,
+            CONFIDENCE_LEVEL,
+            model.convergence_tolerance,
+            &mut rng,
+        );
 
+        let interval = |bounds: Interval| ConfidenceInterval {
+            level: diagnostics::INTERVAL_LEVEL,
+            lower: bounds.lower,
+            upper: bounds.upper,
+        };
         let result = VaRResult {
-            confidence_level,
-            var_amount,
+            confidence_level: CONFIDENCE_LEVEL,
+            var_amount: estimate.var,
+            standard_error: estimate.standard_error,
+            expected_shortfall: diagnosed.expected_shortfall,
+            var_interval: Some(interval(diagnosed.var_interval)),
+            es_interval: Some(interval(diagnosed.es_interval)),
+            converged: diagnosed.converged,
             portfolio_value: initial_portfolio_value,
-            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+            timestamp_utc: Utc::now(),
         };
         
-        println!("  -> Simulation Complete. 99% VaR: ${:.2}", result.var_amount);
-        *latest_var.lock().unwrap() = Some(result);
+        println!(
+            "  -> Simulation Complete. 99% VaR: ${:.2} (standard error ${:.2}, 95% CI ${:.2} to ${:.2}), ES ${:.2}",
+            result.var_amount,
+            result.standard_error,
+            diagnosed.var_interval.lower,
+            diagnosed.var_interval.upper,
+            result.expected_shortfall
+        );
+        if !result.converged {
+            println!(
+                "  -> Not converged: VaR on half the paths was ${:.2} (tolerance {:.0}%)",
+                diagnosed.half_paths_var,
+                model.convergence_tolerance * 100.0
+            );
+        }
+        if let Some(file) = &mut history_file {
+            if let Err(e) = file.append(&result) {
+                println!("  -> Failed to persist VaR result: {}", e);
+            }
+        }
+        latest_var.lock().unwrap().push(result);
     }
 }
 
+/// Fits each position's return distribution, in symbol order so a seeded
+/// run draws the same paths.
+fn fit_samplers<'a>(
+    positions: &'a HashMap<String, Position>,
+    return_history: &HashMap<String, Vec<f64>>,
+) -> Vec<(&'a Position, ReturnSampler)> {
+    let mut samplers: Vec<(&Position, ReturnSampler)> = positions
+        .values()
+        .map(|position| {
+            let history = return_history.get(&position.symbol).map_or(&[][..], |h| h.as_slice());
+            (position, ReturnSampler::fit(position.distribution, history, position.daily_return_volatility))
+        })
+        .collect();
+    samplers.sort_by(|a, b| a.0.symbol.cmp(&b.0.symbol));
+    samplers
+}
+
+fn simulated_assets<'a>(samplers: &'a [(&Position, ReturnSampler)]) -> Vec<SimulatedAsset<'a>> {
+    samplers
+        .iter()
+        .map(|(position, sampler)| SimulatedAsset {
+            quantity: position.quantity as f64,
+            price: position.current_price,
+            sampler,
+        })
+        .collect()
+}
+
 /// Loads a mock portfolio for the simulation.
+/// Distributions default to normal unless overridden by QA_VAR_DISTRIBUTIONS.
 fn load_initial_portfolio() -> HashMap<String, Position> {
+    let overrides = distributions::overrides_from_env();
+    let distribution = |symbol: &str| overrides.get(symbol).copied().unwrap_or(DistributionKind::Normal);
     let mut portfolio = HashMap::new();
     portfolio.insert("BTC".to_string(), Position {
         symbol: "BTC".to_string(),
         quantity: 10,
         current_price: 60000.0,
         daily_return_volatility: 0.02, // 2% daily volatility
+        distribution: distribution("BTC"),
     });
     portfolio.insert("ETH".to_string(), Position {
         symbol: "ETH".to_string(),
         quantity: 50,
         current_price: 3000.0,
         daily_return_volatility: 0.03, // 3% daily volatility
+        distribution: distribution("ETH"),
     });
     portfolio
 }
+
+/// Loads a mock rates book: a long 10-year note future and a short strip of
+/// 3-month SOFR futures.
+fn load_rates_positions() -> Vec<RatePosition> {
+    vec![
+        RatePosition {
+            symbol: "ZNZ5".to_string(),
+            instrument: RateInstrument::Bond { coupon: 0.04375, maturity_years: 10.0, frequency: 2 },
+            quantity: 20.0,
+            point_value: 1_000.0,
+        },
+        RatePosition {
+            symbol: "SR3H6".to_string(),
+            instrument: RateInstrument::RateFuture { start_years: 0.25, end_years: 0.5 },
+            quantity: -40.0,
+            point_value: 2_500.0,
+        },
+    ]
+}
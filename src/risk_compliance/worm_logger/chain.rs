@@ -0,0 +1,204 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Checksum-Chained Batches
+ *
+ * File: src/risk_compliance/worm_logger/chain.rs
+ *
+ * Description:
+ * Records are exported in batches, and each batch is sealed with a SHA-256
+ * hash over its sequence number, the previous batch's hash, its creation
+ * time and its records. The first batch links to GENESIS_HASH. Changing,
+ * removing or reordering any stored batch breaks the link from the batch
+ * after it, so the chain shows tampering even where the storage's own
+ * retention lock is bypassed.
+ *
+ * `verify` walks the stored chain in sequence order and reports the first
+ * break: a missing sequence number, a batch that does not link to its
+ * predecessor, or a batch whose contents no longer match its hash.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// What the first batch of a chain links to.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RecordKind {
+    OrderEvent,
+    Alert,
+    Audit,
+}
+
+/// One exported record: a bus message as received, with where it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ComplianceRecord {
+    pub kind: RecordKind,
+    pub topic: String,
+    pub received_utc: String,
+    /// The message exactly as published.
+    pub payload: String,
+}
+
+/// A sealed batch, stored as one object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Batch {
+    pub sequence: u64,
+    pub previous_hash: String,
+    pub created_utc: String,
+    pub records: Vec<ComplianceRecord>,
+    /// SHA-256 of the fields above, hex-encoded.
+    pub hash: String,
+}
+
+/// The end of the chain, which the next batch links to.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct ChainHead {
+    /// Sequence number of the next batch.
+    pub next_sequence: u64,
+    pub hash: String,
+}
+
+impl Default for ChainHead {
+    fn default() -> Self {
+        ChainHead { next_sequence: 0, hash: GENESIS_HASH.to_string() }
+    }
+}
+
+/// Where and why a chain stops verifying.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct ChainBreak {
+    pub sequence: u64,
+    pub reason: String,
+}
+
+/// Body of a GET /worm/verify response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ChainReport {
+    pub intact: bool,
+    pub batches: usize,
+    pub records: usize,
+    /// Hash of the last batch verified before any break.
+    pub head_hash: String,
+    pub first_break: Option<ChainBreak>,
+    pub verified_utc: String,
+}
+
+// --- Sealing ---
+
+impl Batch {
+    /// Seals `records` as the batch after `head`, and advances the head.
+    pub fn seal(head: &mut ChainHead, records: Vec<ComplianceRecord>, created_utc: String) -> Batch {
+        let sequence = head.next_sequence;
+        let hash = batch_hash(sequence, &head.hash, &created_utc, &records);
+        let batch = Batch { sequence, previous_hash: head.hash.clone(), created_utc, records, hash };
+        *head = batch.head();
+        batch
+    }
+
+    /// The head after this batch.
+    pub fn head(&self) -> ChainHead {
+        ChainHead { next_sequence: self.sequence + 1, hash: self.hash.clone() }
+    }
+
+    fn computed_hash(&self) -> String {
+        batch_hash(self.sequence, &self.previous_hash, &self.created_utc, &self.records)
+    }
+}
+
+fn batch_hash(sequence: u64, previous_hash: &str, created_utc: &str, records: &[ComplianceRecord]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(sequence.to_be_bytes());
+    hasher.update(previous_hash.as_bytes());
+    hasher.update(created_utc.as_bytes());
+    hasher.update(serde_json::to_vec(records).expect("records serialize"));
+    hex::encode(hasher.finalize())
+}
+
+// --- Verification ---
+
+/// Verifies `batches`, which must be sorted by sequence number, from the
+/// genesis batch on.
+pub fn verify(batches: &[Batch]) -> ChainReport {
+    let mut head = ChainHead::default();
+    let mut records = 0;
+    let mut first_break = None;
+    for batch in batches {
+        let reason = if batch.sequence != head.next_sequence {
+            Some(format!("expected batch {}, found {}", head.next_sequence, batch.sequence))
+        } else if batch.previous_hash != head.hash {
+            Some(format!("links to {} instead of {}", batch.previous_hash, head.hash))
+        } else if batch.computed_hash() != batch.hash {
+            Some("contents do not match the batch hash".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            first_break = Some(ChainBreak { sequence: batch.sequence, reason });
+            break;
+        }
+        records += batch.records.len();
+        head = batch.head();
+    }
+    ChainReport {
+        intact: first_break.is_none(),
+        batches: batches.len(),
+        records,
+        head_hash: head.hash,
+        first_break,
+        verified_utc: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(payload: &str) -> ComplianceRecord {
+        ComplianceRecord {
+            kind: RecordKind::OrderEvent,
+            topic: "orders.requests".to_string(),
+            received_utc: "2025-07-31T12:00:00Z".to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    fn chain(batches: usize) -> Vec<Batch> {
+        let mut head = ChainHead::default();
+        (0..batches)
+            .map(|i| Batch::seal(&mut head, vec![record(&format!("{{\"n\":{}}}", i))], "2025-07-31T12:00:00Z".into()))
+            .collect()
+    }
+
+    #[test]
+    fn an_untouched_chain_verifies_to_its_head() {
+        let batches = chain(3);
+        assert_eq!(batches[0].previous_hash, GENESIS_HASH);
+        let report = verify(&batches);
+        assert!(report.intact);
+        assert_eq!((report.batches, report.records), (3, 3));
+        assert_eq!(report.head_hash, batches[2].hash);
+    }
+
+    #[test]
+    fn reports_the_first_edited_missing_or_relinked_batch() {
+        let mut edited = chain(3);
+        edited[1].records[0].payload = "{\"n\":99}".to_string();
+        assert_eq!(verify(&edited).first_break.unwrap().sequence, 1);
+
+        let mut missing = chain(3);
+        missing.remove(1);
+        let report = verify(&missing);
+        assert_eq!(report.first_break.unwrap().reason, "expected batch 1, found 2");
+        assert_eq!(report.head_hash, missing[0].hash);
+
+        // Re-sealing an edited batch does not help: the next batch no longer links to it.
+        let mut resealed = chain(3);
+        let mut head = ChainHead { next_sequence: 1, hash: resealed[0].hash.clone() };
+        resealed[1] = Batch::seal(&mut head, vec![record("{\"n\":99}")], "2025-07-31T12:00:00Z".into());
+        assert_eq!(verify(&resealed).first_break.unwrap().sequence, 2);
+    }
+}
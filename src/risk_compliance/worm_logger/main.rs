@@ -9,39 +9,83 @@
  * critical requirement for regulatory compliance (e.g., SEC Rule 17a-4),
  * as it ensures that audit trails are immutable and cannot be tampered with.
  *
- * In a real system, this service would:
- * - Listen on a dedicated message queue (e.g., NATS) for audit events.
- * - Batch events together to optimize writes.
- * - Write the batched logs to a pre-configured S3 bucket with Object Lock enabled.
- * - Handle write failures and retries gracefully.
+ * It exports continuously:
+ * - Order events ('orders.requests', 'execution_reports' and package
+ * reports), every alert ('alerts.*') and audit events ('audit.events') are
+ * taken off the bus as published.
+ * - Records are batched, up to QA_WORM_BATCH_SIZE (500) or every
+ * QA_WORM_FLUSH_SECS (10), and each batch is sealed with a SHA-256 hash
+ * linking it to the previous batch (see `chain.rs`).
+ * - Batches are written create-only to an S3 bucket with Object Lock enabled
+ * (see `store.rs`). A batch that fails to write is retried at the next flush
+ * with the same sequence number, so the chain has no gaps.
+ * - An API on port 3041 serves the chain head (GET /worm/head) and re-reads
+ * and verifies the whole stored chain on demand (GET /worm/verify).
+ * - GET /worm/records?topic=T&start_utc=..&end_utc=.. reads the stored records
+ * of one topic received in a time range back out, in the order they were
+ * exported; the risk-replay tool records the risk gateway's decisions this way.
+ * - GET /openapi.json serves the API's OpenAPI document, and GET /docs
+ * browses it in Swagger UI.
  *
- * This POC simulates receiving audit records and the process of writing them.
- *
- * To run (with a Cargo.toml file):
- * [dependencies]
- * tokio = { version = "1", features = ["full"] }
- * serde = { version = "1.0", features = ["derive"] }
- * serde_json = "1.0"
- * chrono = { version = "0.4", features = ["serde"] }
- * uuid = { version = "1", features = ["v4"] }
+ * The bus subscription is simulated until the services hold a real bus
+ * connection.
  */
 
+mod chain;
+mod store;
+
+use chain::{Batch, ChainHead, ChainReport, ComplianceRecord, RecordKind};
 use chrono::{DateTime, Utc};
+use quantumarb_bus::topics;
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use quantumarb_openapi::{ApiDoc, ApiSchema, Operation};
+use quantumarb_types::{AuditEvent, RiskDecisionAudit, RiskOutcome};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use store::WormStore;
+use tokio::sync::{mpsc, Mutex};
 use tokio::time::{self, Duration};
 use uuid::Uuid;
+use warp::Filter;
 
 // --- Data Structures ---
 
-/// A generic structure for any audit log event in the system.
-#[derive(Debug, Serialize, Deserialize)]
-struct AuditEvent {
-    event_id: Uuid,
-    service_name: String, // e.g., "strategy-engine", "risk-gateway"
-    event_type: String,   // e.g., "ORDER_SENT", "RISK_REJECTED"
-    timestamp: DateTime<Utc>,
-    // The actual event payload, serialized as a JSON string.
-    payload: String,
+#[derive(Debug, Clone)]
+struct ExportConfig {
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+impl ExportConfig {
+    fn from_env() -> ExportConfig {
+        let var = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        ExportConfig {
+            batch_size: var("QA_WORM_BATCH_SIZE", 500).max(1) as usize,
+            flush_interval: Duration::from_secs(var("QA_WORM_FLUSH_SECS", 10).max(1)),
+        }
+    }
+}
+
+/// Body of a GET /worm/head response.
+#[derive(Debug, Clone, Default, Serialize, ApiSchema)]
+struct ExportStatus {
+    head: ChainHead,
+    batches_written: u64,
+    records_written: u64,
+    pending_records: usize,
+    failed_writes: u64,
+    last_error: Option<String>,
+}
+
+/// The chain head and counters, shared between the exporter and the API.
+type SharedStatus = Arc<Mutex<ExportStatus>>;
+
+/// Query of a GET /worm/records request.
+#[derive(Debug, Deserialize, ApiSchema)]
+struct RecordsQuery {
+    topic: String,
+    start_utc: DateTime<Utc>,
+    end_utc: DateTime<Utc>,
 }
 
 // --- Main Application Logic ---
@@ -49,67 +93,237 @@ struct AuditEvent {
 #[tokio::main]
 async fn main() {
     println!("--- Starting QuantumArb 2.0 WORM Logger Service ---");
+    let config = ExportConfig::from_env();
+    let store = WormStore::from_env().expect("Failed to open WORM storage");
 
-    // In a real app, this would be a long-running task subscribing to NATS/Kafka.
-    let mut interval = time::interval(Duration::from_secs(3));
+    // Resume the chain where the last run left it.
+    let head = store.head().await.expect("Failed to read the stored chain");
+    println!("Chain head: batch {} ({})", head.next_sequence, head.hash);
+    let status: SharedStatus = Arc::new(Mutex::new(ExportStatus { head, ..Default::default() }));
+
+    let (records_tx, records_rx) = mpsc::channel(10_000);
+    tokio::spawn(async move {
+        subscribe_to_compliance_topics(records_tx).await;
+    });
+
+    let (api_store, api_status) = (store.clone(), status.clone());
+    tokio::spawn(async move {
+        run_api(api_store, api_status).await;
+    });
+
+    export(records_rx, store, status, config).await;
+}
+
+/// Batches incoming records and writes each batch as the next link of the chain.
+async fn export(
+    mut records: mpsc::Receiver<ComplianceRecord>,
+    store: WormStore,
+    status: SharedStatus,
+    config: ExportConfig,
+) {
+    let mut pending: Vec<ComplianceRecord> = Vec::new();
+    let mut interval = time::interval(config.flush_interval);
     loop {
-        interval.tick().await;
+        let (flush, closed) = tokio::select! {
+            record = records.recv() => match record {
+                Some(record) => {
+                    pending.push(record);
+                    (pending.len() >= config.batch_size, false)
+                }
+                None => (true, true),
+            },
+            _ = interval.tick() => (true, false),
+        };
+        status.lock().await.pending_records = pending.len();
+        if flush && !pending.is_empty() {
+            flush_batch(&mut pending, &store, &status).await;
+        }
+        if closed {
+            return;
+        }
+    }
+}
 
-        // 1. Simulate receiving an audit event from another service.
-        let event = generate_simulated_audit_event();
-        println!("\nReceived Audit Event: {:?}", event.event_type);
+/// Seals and writes the pending records. The head only advances once the
+/// write has succeeded; on failure the records stay pending.
+async fn flush_batch(pending: &mut Vec<ComplianceRecord>, store: &WormStore, status: &SharedStatus) {
+    let mut head = status.lock().await.head.clone();
+    let batch = Batch::seal(&mut head, pending.clone(), Utc::now().to_rfc3339());
+    let result = store.put(&batch).await;
 
-        // 2. Serialize the event for storage.
-        let serialized_event = serde_json::to_string(&event).unwrap();
+    let mut status = status.lock().await;
+    match result {
+        Ok(path) => {
+            let (sequence, count) = (batch.sequence, batch.records.len());
+            println!("  -> Wrote batch {} ({} records) to {}: {}", sequence, count, path, batch.hash);
+            status.head = head;
+            status.batches_written += 1;
+            status.records_written += batch.records.len() as u64;
+            pending.clear();
+        }
+        Err(e) => {
+            println!("  -> Failed to write batch {}: {}. Retrying at the next flush.", batch.sequence, e);
+            status.failed_writes += 1;
+            status.last_error = Some(e.to_string());
+        }
+    }
+    status.pending_records = pending.len();
+}
+
+/// Simulates the subscription to the order, alert and audit topics.
+async fn subscribe_to_compliance_topics(records: mpsc::Sender<ComplianceRecord>) {
+    // In a real system:
+    // let mut subscriber = nats_client.subscribe("alerts.>").await.unwrap();
+    // ...and likewise for the order, execution report and audit topics.
+    let simulated_messages = [
+        (
+            topics::ORDER_REQUESTS,
+            r#"{"order_id":"a1b2c3d4-e5f6-7890-1234-567890abcdef","account_id":101,"instrument_id":1,
+                "side":"Buy","price":6015000,"size":40,"venue_id":1,"mode":"sandbox"}"#,
+        ),
+        (
+            topics::EXECUTION_REPORTS,
+            r#"{"exchange_order_id":"EXCH-1","exec_id":"E1","internal_order_id":"a1b2c3d4-e5f6-7890-1234-567890abcdef",
+                "status":"Filled","filled_size":40,"filled_price":6015000}"#,
+        ),
+        (
+            topics::DATA_QUALITY_ALERTS,
+            r#"{"instrument_id":1,"status":"Suspect","issue":"OutlierPrice","detail":"Mid moved 487.3 bps (9.1 sigma).",
+                "timestamp_utc":"2025-07-31T12:00:00Z"}"#,
+        ),
+    ];
+    let mut interval = time::interval(Duration::from_secs(3));
+    for (topic, payload) in simulated_messages.iter().cycle() {
+        interval.tick().await;
+        let audit = generate_simulated_audit_event();
+        let messages = [(*topic, payload.to_string()), (topics::AUDIT_EVENTS, serde_json::to_string(&audit).unwrap())];
+        for (topic, payload) in messages {
+            let record = ComplianceRecord {
+                kind: kind_of(topic),
+                topic: topic.to_string(),
+                received_utc: Utc::now().to_rfc3339(),
+                payload,
+            };
+            if records.send(record).await.is_err() {
+                return;
+            }
+        }
+    }
+}
 
-        // 3. Write the event to the WORM-compliant storage.
-        write_to_worm_storage(&event.event_id, &serialized_event).await;
+fn kind_of(topic: &str) -> RecordKind {
+    if topic.starts_with("alerts.") {
+        RecordKind::Alert
+    } else if topic == topics::ORDER_REQUESTS || topic.starts_with(topics::EXECUTION_REPORTS) {
+        RecordKind::OrderEvent
+    } else {
+        RecordKind::Audit
     }
 }
 
 /// Simulates an audit event generated by the risk_gateway service.
 fn generate_simulated_audit_event() -> AuditEvent {
-    let payload = serde_json::json!({
-        "order_id": "a1b2c3d4-e5f6-7890-1234-567890abcdef",
-        "reason": "Order size 150 exceeds max limit 100",
-        "checked_by": "risk-gateway-instance-1"
+    let request = serde_json::json!({
+        "type": "Order", "order_id": "a1b2c3d4-e5f6-7890-1234-567890abcdef", "account_id": 101,
+        "instrument_id": 1, "side": "Buy", "price": 6015000, "size": 150, "venue_id": 1, "mode": "SANDBOX"
     });
+    let decision = RiskDecisionAudit {
+        outcome: RiskOutcome::Rejected,
+        code: Some(RejectCode::RiskOrderSizeLimit),
+        reason: Some("Order size 150 exceeds max limit 100".to_string()),
+        checked_by: "risk-gateway-instance-1".to_string(),
+        request: serde_json::from_value(request).unwrap(),
+    };
 
     AuditEvent {
         event_id: Uuid::new_v4(),
         service_name: "risk-gateway".to_string(),
-        event_type: "RISK_REJECTED".to_string(),
+        event_type: decision.outcome.event_type().to_string(),
         timestamp: Utc::now(),
-        payload: payload.to_string(),
+        payload: serde_json::to_string(&decision).unwrap(),
+    }
+}
+
+// --- API ---
+
+async fn run_api(store: WormStore, status: SharedStatus) {
+    let head = warp::path!("worm" / "head")
+        .and(warp::get())
+        .and(with_state(status))
+        .and_then(handler_get_head);
+    let verify = warp::path!("worm" / "verify")
+        .and(warp::get())
+        .and(with_state(store.clone()))
+        .and_then(handler_verify);
+    let records = warp::path!("worm" / "records")
+        .and(warp::get())
+        .and(warp::query::<RecordsQuery>())
+        .and(with_state(store))
+        .and_then(handler_get_records);
+    println!("WORM API running at http://127.0.0.1:3041 (/worm/head, /worm/verify, /worm/records)");
+    let routes = head.or(verify).or(records).or(quantumarb_openapi::routes(api_doc()));
+    warp::serve(routes).run(([127, 0, 0, 1], 3041)).await;
+}
+
+/// The OpenAPI document of the chain endpoints.
+fn api_doc() -> ApiDoc {
+    ApiDoc::new("WORM Logger", "The sealed compliance record chain.")
+        .errors::<ErrorBody>()
+        .get::<ExportStatus>("/worm/head", "The chain head and export counters")
+        .get::<ChainReport>("/worm/verify", "Re-read and verify the stored chain")
+        .operation(
+            Operation::get("/worm/records", "One topic's records received in a time range")
+                .query::<RecordsQuery>()
+                .response::<Vec<ComplianceRecord>>(),
+        )
+}
+
+/// Warp filter to inject state into the handler.
+fn with_state<T: Clone + Send>(state: T) -> impl Filter<Extract = (T,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Handler for GET /worm/head.
+async fn handler_get_head(status: SharedStatus) -> Result<impl warp::Reply, warp::Rejection> {
+    let status = status.lock().await.clone();
+    Ok(warp::reply::json(&status))
+}
+
+/// Handler for GET /worm/verify: reads every stored batch and checks the chain.
+async fn handler_verify(store: WormStore) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.load_all().await {
+        Ok(batches) => {
+            let report = chain::verify(&batches);
+            Ok(warp::reply::with_status(warp::reply::json(&report), warp::http::StatusCode::OK))
+        }
+        Err(e) => Ok(store_unavailable(e)),
+    }
+}
+
+/// Handler for GET /worm/records: the stored records of one topic received
+/// in [start_utc, end_utc), oldest batch first.
+async fn handler_get_records(query: RecordsQuery, store: WormStore) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.load_all().await {
+        Ok(batches) => {
+            let in_range = |record: &ComplianceRecord| {
+                DateTime::parse_from_rfc3339(&record.received_utc)
+                    .is_ok_and(|received| received >= query.start_utc && received < query.end_utc)
+            };
+            let records: Vec<&ComplianceRecord> = batches
+                .iter()
+                .flat_map(|batch| &batch.records)
+                .filter(|record| record.topic == query.topic && in_range(record))
+                .collect();
+            Ok(warp::reply::with_status(warp::reply::json(&records), warp::http::StatusCode::OK))
+        }
+        Err(e) => Ok(store_unavailable(e)),
     }
 }
 
-/// Simulates writing a log entry to a WORM-compliant storage like S3 with Object Lock.
-async fn write_to_worm_storage(event_id: &Uuid, data: &str) {
-    // The object key would typically include a timestamp to ensure uniqueness and allow for efficient querying.
-    let object_key = format!(
-        "audit_logs/{}/{}.json",
-        Utc::now().format("%Y/%m/%d"),
-        event_id
-    );
-
-    println!(
-        "  -> Writing to WORM storage (e.g., S3 with Object Lock)..."
-    );
-    println!("     Bucket: quantum-arb-audit-logs");
-    println!("     Key: {}", object_key);
-    println!("     Data: {}", data);
-
-    // In a real implementation using the AWS SDK for Rust:
-    // let client = s3_client::Client::new(&aws_config);
-    // client.put_object()
-    //     .bucket("quantum-arb-audit-logs")
-    //     .key(object_key)
-    //     .body(data.as_bytes().to_owned().into())
-    //     .object_lock_mode("COMPLIANCE") // This is the key WORM setting
-    //     .send()
-    //     .await
-    //     .expect("Failed to write to S3");
-
-    println!("  -> Write successful.");
+fn store_unavailable(e: object_store::Error) -> warp::reply::WithStatus<warp::reply::Json> {
+    let message = format!("Failed to read the stored chain: {}", e);
+    let rejection = Rejection::new(RejectCode::SystemStateUnavailable, message);
+    let status = warp::http::StatusCode::from_u16(rejection.code.http_status())
+        .unwrap_or(warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+    warp::reply::with_status(warp::reply::json(&ErrorBody::from(rejection)), status)
 }
@@ -0,0 +1,128 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: WORM Batch Store
+ *
+ * File: src/risk_compliance/worm_logger/store.rs
+ *
+ * Description:
+ * Writes sealed batches as JSON objects, one per batch, and reads the chain
+ * back for verification:
+ *
+ *   <prefix>/date=2025-07-31/batch-00000000000000000042.json
+ *
+ * Batches are written create-only: a key that already exists is never
+ * overwritten (If-None-Match on S3), so a restarted exporter cannot replace
+ * what an earlier one wrote. Immutability itself comes from the bucket: it
+ * must have S3 Object Lock enabled with a default retention in COMPLIANCE
+ * mode, which then applies to every batch written.
+ *
+ * On start-up the exporter resumes the chain from the highest batch stored.
+ *
+ * Storage (environment):
+ *   QA_WORM_BUCKET      S3 bucket (default "quantum-arb-audit-logs");
+ *                       credentials, region and AWS_ENDPOINT come from the
+ *                       standard AWS_* variables
+ *   QA_WORM_PREFIX      key prefix inside the bucket (default "compliance")
+ *   QA_WORM_LOCAL_DIR   use a local directory instead of S3 (development)
+ */
+
+use futures::TryStreamExt;
+use object_store::aws::{AmazonS3Builder, S3ConditionalPut};
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutMode, PutOptions};
+use std::sync::Arc;
+
+use crate::chain::{Batch, ChainHead};
+
+#[derive(Clone)]
+pub struct WormStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl WormStore {
+    /// Opens a local directory if QA_WORM_LOCAL_DIR is set, otherwise the
+    /// S3 bucket in QA_WORM_BUCKET.
+    pub fn from_env() -> Result<WormStore, object_store::Error> {
+        let prefix = std::env::var("QA_WORM_PREFIX").unwrap_or_else(|_| "compliance".to_string());
+        let prefix = prefix.trim_matches('/').to_string();
+        if let Ok(dir) = std::env::var("QA_WORM_LOCAL_DIR") {
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| object_store::Error::Generic { store: "local", source: Box::new(e) })?;
+            return Ok(WormStore::new(Arc::new(LocalFileSystem::new_with_prefix(dir)?), &prefix));
+        }
+        let bucket = std::env::var("QA_WORM_BUCKET").unwrap_or_else(|_| "quantum-arb-audit-logs".to_string());
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_conditional_put(S3ConditionalPut::ETagMatch)
+            .build()?;
+        Ok(WormStore::new(Arc::new(store), &prefix))
+    }
+
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> WormStore {
+        WormStore { store, prefix: prefix.to_string() }
+    }
+
+    /// Writes `batch`, failing if its key already exists.
+    pub async fn put(&self, batch: &Batch) -> Result<Path, object_store::Error> {
+        let date = batch.created_utc.get(..10).unwrap_or("unknown");
+        let path = Path::from(format!("{}/date={}/batch-{:020}.json", self.prefix, date, batch.sequence));
+        let bytes = serde_json::to_vec(batch).expect("batch serializes");
+        let options = PutOptions { mode: PutMode::Create, ..Default::default() };
+        self.store.put_opts(&path, bytes.into(), options).await?;
+        Ok(path)
+    }
+
+    /// Every stored batch, in sequence order. A stored object that does not
+    /// parse as a batch is an error: it cannot be skipped without hiding it.
+    pub async fn load_all(&self) -> Result<Vec<Batch>, object_store::Error> {
+        let prefix = Path::from(self.prefix.as_str());
+        let objects: Vec<_> = self.store.list(Some(&prefix)).try_collect().await?;
+        let mut batches = Vec::with_capacity(objects.len());
+        for object in objects.iter().filter(|o| o.location.as_ref().ends_with(".json")) {
+            let bytes = self.store.get(&object.location).await?.bytes().await?;
+            let batch: Batch = serde_json::from_slice(&bytes).map_err(|e| object_store::Error::Generic {
+                store: "worm",
+                source: format!("{} is not a batch: {}", object.location, e).into(),
+            })?;
+            batches.push(batch);
+        }
+        batches.sort_by_key(|b| b.sequence);
+        Ok(batches)
+    }
+
+    /// The head after the highest stored batch; the genesis head for an
+    /// empty store.
+    pub async fn head(&self) -> Result<ChainHead, object_store::Error> {
+        Ok(self.load_all().await?.last().map(Batch::head).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::{self, ComplianceRecord, RecordKind};
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn batches_are_written_once_and_read_back_in_sequence() {
+        let store = WormStore::new(Arc::new(InMemory::new()), "compliance");
+        let mut head = ChainHead::default();
+        let record = ComplianceRecord {
+            kind: RecordKind::Alert,
+            topic: "alerts.margin".to_string(),
+            received_utc: "2025-07-31T23:59:59Z".to_string(),
+            payload: "{}".to_string(),
+        };
+        let first = Batch::seal(&mut head, vec![record.clone()], "2025-07-31T23:59:59Z".into());
+        let second = Batch::seal(&mut head, vec![record], "2025-08-01T00:00:01Z".into());
+        store.put(&second).await.unwrap();
+        store.put(&first).await.unwrap();
+        assert!(matches!(store.put(&first).await, Err(object_store::Error::AlreadyExists { .. })));
+
+        let batches = store.load_all().await.unwrap();
+        assert_eq!(batches, vec![first, second]);
+        assert!(chain::verify(&batches).intact);
+        assert_eq!(store.head().await.unwrap(), head);
+    }
+}
@@ -0,0 +1,436 @@
+/*
+ * QuantumArb 2.0 - Shared: Bus Archive Format
+ *
+ * File: src/shared/archive/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-archive`) defines how bus traffic is stored
+ * in object storage, so the archiver (writer) and the market replay service
+ * (reader) agree on layout and schema.
+ *
+ * Layout: one dataset per topic family, Hive-style partitions by UTC date
+ * and (with hourly partitioning) hour, zstd-compressed Parquet files:
+ *
+ *   <prefix>/market_data/date=2025-06-11/hour=14/part-<uuid>.parquet
+ *   <prefix>/orders/...  <prefix>/executions/...  <prefix>/alt_data/...
+ *
+ * Every file shares one schema. The payload column holds the bus message
+ * exactly as published (binary `quantumarb-wire` or JSON, per content_type),
+ * so a replay republishes byte-identical messages; the other columns exist
+ * for filtering:
+ *
+ *   topic: Utf8 | event_time: Timestamp(ns, UTC) | instrument_id: UInt32 (nullable)
+ *   | content_type: Utf8 | payload: Binary
+ *
+ * Storage (environment):
+ *   QA_ARCHIVE_BUCKET      S3 bucket; credentials, region and AWS_ENDPOINT
+ *                          (for S3-compatible stores such as MinIO) come from
+ *                          the standard AWS_* variables
+ *   QA_ARCHIVE_PREFIX      key prefix inside the bucket (default "archive")
+ *   QA_ARCHIVE_LOCAL_DIR   use a local directory instead of S3 (development)
+ *
+ * Reading: `ArchiveReader` lists only the partitions overlapping a query's
+ * time range and streams their files a record batch at a time, merging the
+ * datasets asked for into one event-time-ordered sequence. Nothing is
+ * downloaded up front, so a multi-day replay holds one batch per dataset in
+ * memory rather than the whole range.
+ */
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{TimestampNanosecondType, UInt32Type};
+use arrow_array::{Array, ArrayRef, BinaryArray, RecordBatch, StringArray, TimestampNanosecondArray, UInt32Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::{StreamExt, TryStreamExt};
+use object_store::aws::AmazonS3Builder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+use parquet::arrow::async_reader::{ParquetObjectReader, ParquetRecordBatchStream};
+use parquet::arrow::{ArrowWriter, ParquetRecordBatchStreamBuilder};
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+// --- Data Structures ---
+
+/// One archived bus message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveRecord {
+    pub topic: String,
+    /// When the message was seen on the bus, in nanoseconds since the Unix epoch.
+    pub event_time_ns: i64,
+    pub instrument_id: Option<u32>,
+    pub content_type: String,
+    pub payload: Vec<u8>,
+}
+
+/// Failure reading or writing the archive.
+#[derive(Debug)]
+pub enum ArchiveError {
+    Store(object_store::Error),
+    Parquet(ParquetError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Store(e) => write!(f, "archive store: {}", e),
+            ArchiveError::Parquet(e) => write!(f, "archive file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<object_store::Error> for ArchiveError {
+    fn from(e: object_store::Error) -> Self {
+        ArchiveError::Store(e)
+    }
+}
+
+impl From<ParquetError> for ArchiveError {
+    fn from(e: ParquetError) -> Self {
+        ArchiveError::Parquet(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioning {
+    Hourly,
+    Daily,
+}
+
+impl Partitioning {
+    /// Reads QA_ARCHIVE_PARTITIONING ("hourly" or "daily"); hourly is the default.
+    pub fn from_env() -> Partitioning {
+        match std::env::var("QA_ARCHIVE_PARTITIONING").as_deref() {
+            Ok("daily") => Partitioning::Daily,
+            _ => Partitioning::Hourly,
+        }
+    }
+}
+
+// --- Layout ---
+
+/// The dataset a topic is archived under, or None for topics not archived.
+pub fn dataset_for_topic(topic: &str) -> Option<&'static str> {
+    match topic {
+        t if t.starts_with("market_data.") => Some("market_data"),
+        "orders.requests" => Some("orders"),
+        "execution_reports" => Some("executions"),
+        "alt_data.normalized" => Some("alt_data"),
+        _ => None,
+    }
+}
+
+/// Directory of the partition holding data for `time`, e.g.
+/// "archive/market_data/date=2025-06-11/hour=14".
+pub fn partition_path(prefix: &str, dataset: &str, time: DateTime<Utc>, partitioning: Partitioning) -> String {
+    let date = time.format("%Y-%m-%d");
+    match partitioning {
+        Partitioning::Hourly => format!("{}/{}/date={}/hour={}", prefix, dataset, date, time.format("%H")),
+        Partitioning::Daily => format!("{}/{}/date={}", prefix, dataset, date),
+    }
+}
+
+/// Key prefix from QA_ARCHIVE_PREFIX (default "archive").
+pub fn prefix_from_env() -> String {
+    std::env::var("QA_ARCHIVE_PREFIX").unwrap_or_else(|_| "archive".to_string()).trim_matches('/').to_string()
+}
+
+// --- Schema and Encoding ---
+
+pub fn schema() -> SchemaRef {
+    static SCHEMA: OnceLock<SchemaRef> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| {
+            Arc::new(Schema::new(vec![
+                Field::new("topic", DataType::Utf8, false),
+                Field::new("event_time", DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())), false),
+                Field::new("instrument_id", DataType::UInt32, true),
+                Field::new("content_type", DataType::Utf8, false),
+                Field::new("payload", DataType::Binary, false),
+            ]))
+        })
+        .clone()
+}
+
+/// Encodes records (in the order given) as one zstd-compressed Parquet file.
+pub fn encode_parquet(records: &[ArchiveRecord]) -> Result<Vec<u8>, ParquetError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(records.iter().map(|r| r.topic.as_str()))),
+        Arc::new(TimestampNanosecondArray::from_iter_values(records.iter().map(|r| r.event_time_ns)).with_timezone("UTC")),
+        Arc::new(UInt32Array::from_iter(records.iter().map(|r| r.instrument_id))),
+        Arc::new(StringArray::from_iter_values(records.iter().map(|r| r.content_type.as_str()))),
+        Arc::new(BinaryArray::from_iter_values(records.iter().map(|r| r.payload.as_slice()))),
+    ];
+    let batch = RecordBatch::try_new(schema(), columns)?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::try_new(3)?))
+        .build();
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+// --- Storage ---
+
+/// Opens the archive store: a local directory if QA_ARCHIVE_LOCAL_DIR is set,
+/// otherwise the S3 bucket in QA_ARCHIVE_BUCKET.
+pub fn open_store() -> Result<Arc<dyn ObjectStore>, object_store::Error> {
+    if let Ok(dir) = std::env::var("QA_ARCHIVE_LOCAL_DIR") {
+        std::fs::create_dir_all(&dir).map_err(|e| object_store::Error::Generic { store: "local", source: Box::new(e) })?;
+        return Ok(Arc::new(LocalFileSystem::new_with_prefix(dir)?));
+    }
+    let bucket = std::env::var("QA_ARCHIVE_BUCKET").unwrap_or_else(|_| "quantumarb-archive".to_string());
+    Ok(Arc::new(AmazonS3Builder::from_env().with_bucket_name(bucket).build()?))
+}
+
+/// Writes an encoded file under `partition` with a unique name and returns its path.
+pub async fn put_file(store: &dyn ObjectStore, partition: &str, file_id: &str, bytes: Vec<u8>) -> Result<Path, object_store::Error> {
+    let path = Path::from(format!("{}/part-{}.parquet", partition, file_id));
+    store.put(&path, bytes.into()).await?;
+    Ok(path)
+}
+
+// --- Reading ---
+
+/// Rows decoded per read; each dataset being merged holds at most one batch.
+const READ_BATCH_ROWS: usize = 8192;
+
+/// Which archived messages to read.
+#[derive(Debug, Clone)]
+pub struct ArchiveQuery {
+    /// Inclusive start of the event time range.
+    pub start: DateTime<Utc>,
+    /// Exclusive end of the event time range.
+    pub end: DateTime<Utc>,
+    /// Instruments to keep; empty keeps all. Messages without an instrument
+    /// (alternative data) are only kept when this is empty.
+    pub instrument_ids: Vec<u32>,
+    /// Topics to keep, exact or NATS-style with a trailing ">" wildcard
+    /// ("market_data.>"). Empty means market data only.
+    pub topics: Vec<String>,
+}
+
+impl ArchiveQuery {
+    fn topic_patterns(&self) -> Vec<&str> {
+        if self.topics.is_empty() {
+            vec!["market_data.>"]
+        } else {
+            self.topics.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// The datasets holding the queried topics.
+    fn datasets(&self) -> Vec<&'static str> {
+        let mut datasets: Vec<&'static str> =
+            self.topic_patterns().into_iter().filter_map(dataset_for_topic).collect();
+        datasets.sort_unstable();
+        datasets.dedup();
+        datasets
+    }
+
+    pub fn matches(&self, record: &ArchiveRecord) -> bool {
+        let start = self.start.timestamp_nanos_opt().unwrap_or(i64::MIN);
+        let end = self.end.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        (start..end).contains(&record.event_time_ns)
+            && (self.instrument_ids.is_empty()
+                || record.instrument_id.is_some_and(|id| self.instrument_ids.contains(&id)))
+            && self.topic_patterns().into_iter().any(|pattern| match pattern.strip_suffix('>') {
+                Some(prefix) => record.topic.starts_with(prefix),
+                None => record.topic == pattern,
+            })
+    }
+}
+
+/// Whether a file's partition ("date=YYYY-MM-DD[/hour=HH]" in its path) can
+/// hold events in [start, end). Paths without a date are kept.
+fn partition_overlaps(path: &Path, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    let mut date = None;
+    let mut hour = None;
+    for part in path.parts() {
+        if let Some(value) = part.as_ref().strip_prefix("date=") {
+            date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+        } else if let Some(value) = part.as_ref().strip_prefix("hour=") {
+            hour = value.parse::<u32>().ok();
+        }
+    }
+    let Some(date) = date else {
+        return true;
+    };
+    let (first, length) = match hour {
+        Some(hour) => (date.and_hms_opt(hour, 0, 0), Duration::hours(1)),
+        None => (date.and_hms_opt(0, 0, 0), Duration::days(1)),
+    };
+    match first {
+        Some(first) => {
+            let first = first.and_utc();
+            first < end && first + length > start
+        }
+        None => true,
+    }
+}
+
+/// The files of one dataset overlapping the query's time range, in the order
+/// the archiver wrote them.
+pub async fn list_files(
+    store: &dyn ObjectStore,
+    prefix: &str,
+    dataset: &str,
+    query: &ArchiveQuery,
+) -> Result<Vec<ObjectMeta>, ArchiveError> {
+    let mut files = Vec::new();
+    if query.end <= query.start {
+        return Ok(files);
+    }
+    let last_day = (query.end - Duration::nanoseconds(1)).date_naive();
+    let mut day = query.start.date_naive();
+    while day <= last_day {
+        let directory = Path::from(format!("{}/{}/date={}", prefix, dataset, day.format("%Y-%m-%d")));
+        let listed: Vec<ObjectMeta> = store.list(Some(&directory)).try_collect().await?;
+        files.extend(listed.into_iter().filter(|meta| {
+            meta.location.extension() == Some("parquet") && partition_overlaps(&meta.location, query.start, query.end)
+        }));
+        day = match day.succ_opt() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    // File names are random; within a partition, write order is upload order.
+    files.sort_by(|a, b| (a.last_modified, &a.location).cmp(&(b.last_modified, &b.location)));
+    Ok(files)
+}
+
+/// How far a reader has got through the files it selected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadProgress {
+    pub files_total: usize,
+    pub files_read: usize,
+    pub bytes_total: u64,
+    pub bytes_read: u64,
+}
+
+/// Streams one dataset's files in order, a record batch at a time.
+struct DatasetCursor {
+    files: VecDeque<ObjectMeta>,
+    current: Option<(ParquetRecordBatchStream<ParquetObjectReader>, u64)>,
+    buffered: VecDeque<ArchiveRecord>,
+}
+
+impl DatasetCursor {
+    /// Makes sure the next matching record (if any) is buffered.
+    async fn fill(
+        &mut self,
+        store: &Arc<dyn ObjectStore>,
+        query: &ArchiveQuery,
+        progress: &mut ReadProgress,
+    ) -> Result<(), ArchiveError> {
+        while self.buffered.is_empty() {
+            if self.current.is_none() {
+                let Some(meta) = self.files.pop_front() else {
+                    return Ok(());
+                };
+                let size = meta.size as u64;
+                let reader = ParquetObjectReader::new(store.clone(), meta);
+                let stream = ParquetRecordBatchStreamBuilder::new(reader)
+                    .await?
+                    .with_batch_size(READ_BATCH_ROWS)
+                    .build()?;
+                self.current = Some((stream, size));
+            }
+            let Some((stream, size)) = self.current.as_mut() else {
+                continue;
+            };
+            match stream.next().await {
+                Some(batch) => self.buffered.extend(records_from_batch(&batch?, query)),
+                None => {
+                    progress.files_read += 1;
+                    progress.bytes_read += *size;
+                    self.current = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn peek_time(&self) -> Option<i64> {
+        self.buffered.front().map(|r| r.event_time_ns)
+    }
+}
+
+/// The records of a batch that match the query. Columns are read by position:
+/// every archive file has the layout of `schema()`.
+fn records_from_batch(batch: &RecordBatch, query: &ArchiveQuery) -> Vec<ArchiveRecord> {
+    let topics = batch.column(0).as_string::<i32>();
+    let times = batch.column(1).as_primitive::<TimestampNanosecondType>();
+    let instruments = batch.column(2).as_primitive::<UInt32Type>();
+    let content_types = batch.column(3).as_string::<i32>();
+    let payloads = batch.column(4).as_binary::<i32>();
+    (0..batch.num_rows())
+        .map(|row| ArchiveRecord {
+            topic: topics.value(row).to_string(),
+            event_time_ns: times.value(row),
+            instrument_id: (!instruments.is_null(row)).then(|| instruments.value(row)),
+            content_type: content_types.value(row).to_string(),
+            payload: payloads.value(row).to_vec(),
+        })
+        .filter(|record| query.matches(record))
+        .collect()
+}
+
+/// Reads the records matching a query from every dataset it covers, merged
+/// into event time order.
+pub struct ArchiveReader {
+    store: Arc<dyn ObjectStore>,
+    query: ArchiveQuery,
+    cursors: Vec<DatasetCursor>,
+    progress: ReadProgress,
+}
+
+impl ArchiveReader {
+    /// Lists the files to read; no data is fetched until `next_chunk`.
+    pub async fn open(store: Arc<dyn ObjectStore>, prefix: &str, query: ArchiveQuery) -> Result<Self, ArchiveError> {
+        let mut cursors = Vec::new();
+        let mut progress = ReadProgress::default();
+        for dataset in query.datasets() {
+            let files = list_files(&*store, prefix, dataset, &query).await?;
+            progress.files_total += files.len();
+            progress.bytes_total += files.iter().map(|meta| meta.size as u64).sum::<u64>();
+            cursors.push(DatasetCursor { files: files.into(), current: None, buffered: VecDeque::new() });
+        }
+        Ok(ArchiveReader { store, query, cursors, progress })
+    }
+
+    pub fn progress(&self) -> ReadProgress {
+        self.progress
+    }
+
+    /// The next `max` (or fewer) matching records in event time order. An
+    /// empty chunk means the archive has been read to the end of the range.
+    pub async fn next_chunk(&mut self, max: usize) -> Result<Vec<ArchiveRecord>, ArchiveError> {
+        let mut chunk = Vec::new();
+        while chunk.len() < max {
+            for cursor in self.cursors.iter_mut() {
+                cursor.fill(&self.store, &self.query, &mut self.progress).await?;
+            }
+            let earliest = self
+                .cursors
+                .iter_mut()
+                .filter(|cursor| cursor.peek_time().is_some())
+                .min_by_key(|cursor| cursor.peek_time());
+            match earliest.and_then(|cursor| cursor.buffered.pop_front()) {
+                Some(record) => chunk.push(record),
+                None => break,
+            }
+        }
+        Ok(chunk)
+    }
+}
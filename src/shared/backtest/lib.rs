@@ -0,0 +1,517 @@
+/*
+ * QuantumArb 2.0 - Shared: Backtest Harness
+ *
+ * File: src/shared/backtest/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-backtest`) runs a strategy over recorded
+ * top of book history and reports how it would have done. It is what the
+ * Python bindings (src/tools/pyquantumarb) drive, so quants can prototype
+ * against the platform's own data, fill rules and fee schedules.
+ *
+ *   ticks      venue top of book updates (`BboUpdate`), from a CSV file,
+ *              from the archive's market data, or added one at a time;
+ *              replayed in time order
+ *   strategy   after every tick, sees a `MarketView` of the instrument's
+ *              venue books (the strategy plugin SDK's messages) and returns
+ *              `OrderIntent`s; a plugin's `Strategy` runs unchanged
+ *   fills      an order crossing its venue's touch fills at once, as a taker,
+ *              at the touch price and up to the size quoted there; the rest is
+ *              cancelled. There is no resting order, queue position or
+ *              latency, and the size taken is gone until the venue's next tick
+ *   fees       from the fee schedules (`quantumarb-fees`, QA_FEE_SCHEDULES_PATH)
+ *   marks      positions are marked at the mid of the best bid and offer
+ *              across the instrument's venues
+ *
+ * Instruments come from the reference data (QA_REFDATA_PATH); ticks for an
+ * instrument it does not define are skipped.
+ *
+ * The `Report` is column-oriented: its equity curve and trade log are
+ * records of equal-length arrays, which load straight into a DataFrame.
+ *
+ * CSV files have a header row and one tick per line, prices at the
+ * instrument's wire price scale:
+ *
+ *   timestamp_ns,instrument_id,venue_id,bid,bid_size,ask,ask_size
+ *   1749427200000000000,1,1,6010000,3,6010050,2
+ */
+
+use quantumarb_archive::{ArchiveQuery, ArchiveReader};
+use quantumarb_bus::topics;
+use quantumarb_fees::{FeeEngine, Liquidity};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_risk::concentration::venue_name;
+use quantumarb_strategy_sdk::{MarketView, OrderIntent, Side, Strategy, VenueBook};
+use quantumarb_wire::{BboUpdate, Encoding};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/// Archived records read at a time.
+const ARCHIVE_CHUNK_RECORDS: usize = 10_000;
+const MILLIS_PER_YEAR: f64 = 365.25 * 24.0 * 3600.0 * 1000.0;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    pub initial_capital: f64,
+    /// How often the equity curve is sampled, in tick time.
+    pub equity_interval_ms: u64,
+    pub instruments: Vec<InstrumentDefinition>,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        BacktestConfig {
+            initial_capital: 100_000.0,
+            equity_interval_ms: 60_000,
+            instruments: quantumarb_refdata::load_from_env(),
+        }
+    }
+}
+
+/// Why a backtest could not load its data or finish.
+#[derive(Debug)]
+pub enum BacktestError {
+    Io(std::io::Error),
+    Csv { line: usize, message: String },
+    Archive(String),
+    /// The strategy raised an error; the run stops there.
+    Strategy(String),
+}
+
+impl fmt::Display for BacktestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BacktestError::Io(e) => write!(f, "reading ticks: {}", e),
+            BacktestError::Csv { line, message } => write!(f, "line {}: {}", line, message),
+            BacktestError::Archive(e) => write!(f, "reading the archive: {}", e),
+            BacktestError::Strategy(e) => write!(f, "strategy failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BacktestError {}
+
+impl From<std::io::Error> for BacktestError {
+    fn from(e: std::io::Error) -> Self {
+        BacktestError::Io(e)
+    }
+}
+
+// --- Report ---
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub initial_capital: f64,
+    pub final_equity: f64,
+    pub pnl: f64,
+    /// Fraction of the initial capital (0.02 = 2%).
+    pub total_return: f64,
+    /// Annualized from the equity curve's returns; 0 with fewer than two.
+    pub sharpe: f64,
+    /// Largest fall from a peak of the equity curve, as a fraction of it.
+    pub max_drawdown: f64,
+    pub ticks: u64,
+    /// Ticks for instruments the reference data does not define.
+    pub skipped_ticks: u64,
+    pub orders: u64,
+    pub fills: u64,
+    /// Orders that did not cross the touch, or found no size at it.
+    pub unfilled: u64,
+    /// Notional traded.
+    pub volume: f64,
+    pub fees: f64,
+}
+
+/// The equity curve, one entry per sample.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EquityCurve {
+    pub time_ns: Vec<u64>,
+    pub equity: Vec<f64>,
+    /// Fraction below the running peak.
+    pub drawdown: Vec<f64>,
+}
+
+/// Every fill, one entry per fill.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TradeLog {
+    pub time_ns: Vec<u64>,
+    pub instrument_id: Vec<u32>,
+    pub symbol: Vec<String>,
+    pub venue_id: Vec<u32>,
+    pub side: Vec<Side>,
+    pub price: Vec<f64>,
+    pub size: Vec<u32>,
+    pub fee: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub summary: Summary,
+    pub equity: EquityCurve,
+    pub trades: TradeLog,
+    /// Net position per instrument at the end.
+    pub positions: BTreeMap<u32, i64>,
+}
+
+// --- Harness ---
+
+pub struct Backtest {
+    config: BacktestConfig,
+    ticks: Vec<BboUpdate>,
+}
+
+impl Backtest {
+    pub fn new(config: BacktestConfig) -> Backtest {
+        Backtest { config, ticks: Vec::new() }
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn add(&mut self, tick: BboUpdate) {
+        self.ticks.push(tick);
+    }
+
+    /// Loads the ticks of a CSV file (see the file header); returns how many.
+    pub fn load_csv(&mut self, path: &Path) -> Result<usize, BacktestError> {
+        let text = std::fs::read_to_string(path)?;
+        let before = self.ticks.len();
+        for (index, line) in text.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let tick = parse_csv_line(line).map_err(|message| BacktestError::Csv { line: index + 1, message })?;
+            self.ticks.push(tick);
+        }
+        Ok(self.ticks.len() - before)
+    }
+
+    /// Loads the archived top of book updates the query selects (its topics
+    /// are replaced by the market data topics); returns how many.
+    pub async fn load_archive(&mut self, mut query: ArchiveQuery) -> Result<usize, BacktestError> {
+        query.topics = vec![format!("{}>", topics::MARKET_DATA_PREFIX)];
+        let store = quantumarb_archive::open_store().map_err(|e| BacktestError::Archive(e.to_string()))?;
+        let mut reader = ArchiveReader::open(store, &quantumarb_archive::prefix_from_env(), query)
+            .await
+            .map_err(|e| BacktestError::Archive(e.to_string()))?;
+        let before = self.ticks.len();
+        loop {
+            let chunk = reader
+                .next_chunk(ARCHIVE_CHUNK_RECORDS)
+                .await
+                .map_err(|e| BacktestError::Archive(e.to_string()))?;
+            if chunk.is_empty() {
+                return Ok(self.ticks.len() - before);
+            }
+            for record in chunk {
+                let body = quantumarb_bus::envelope::payload_of(&record.payload);
+                if let Ok(tick) = Encoding::decode_any::<BboUpdate>(body) {
+                    self.ticks.push(tick);
+                }
+            }
+        }
+    }
+
+    /// Runs a strategy plugin's `Strategy` over the ticks.
+    pub fn run_strategy<S: Strategy>(&self, strategy: &mut S) -> Result<Report, BacktestError> {
+        self.run(|view| Ok(strategy.on_market(view)))
+    }
+
+    /// Runs `on_market` over the ticks in time order; an error from it stops
+    /// the run.
+    pub fn run<F>(&self, mut on_market: F) -> Result<Report, BacktestError>
+    where
+        F: FnMut(&MarketView) -> Result<Vec<OrderIntent>, String>,
+    {
+        let mut ticks = self.ticks.clone();
+        ticks.sort_by_key(|tick| tick.timestamp_ns);
+        let mut run = Run::new(&self.config);
+        for tick in &ticks {
+            run.tick(tick, &mut on_market)?;
+        }
+        Ok(run.finish())
+    }
+}
+
+fn parse_csv_line(line: &str) -> Result<BboUpdate, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() != 7 {
+        return Err(format!("expected 7 fields, found {}", fields.len()));
+    }
+    fn field<T: std::str::FromStr>(fields: &[&str], index: usize, name: &str) -> Result<T, String> {
+        fields[index].parse().map_err(|_| format!("invalid {} '{}'", name, fields[index]))
+    }
+    Ok(BboUpdate {
+        timestamp_ns: field(&fields, 0, "timestamp_ns")?,
+        instrument_id: field(&fields, 1, "instrument_id")?,
+        venue_id: field(&fields, 2, "venue_id")?,
+        best_bid_price: field(&fields, 3, "bid")?,
+        best_bid_size: field(&fields, 4, "bid_size")?,
+        best_ask_price: field(&fields, 5, "ask")?,
+        best_ask_size: field(&fields, 6, "ask_size")?,
+    })
+}
+
+/// The state of one run.
+struct Run<'a> {
+    config: &'a BacktestConfig,
+    refdata: ReferenceData,
+    fees: FeeEngine,
+    books: BTreeMap<u32, BTreeMap<u32, VenueBook>>,
+    marks: HashMap<u32, Price>,
+    positions: BTreeMap<u32, i64>,
+    cash: Money,
+    peak: f64,
+    next_sample_ns: u64,
+    last_tick_ns: Option<u64>,
+    summary: Summary,
+    equity: EquityCurve,
+    trades: TradeLog,
+}
+
+impl<'a> Run<'a> {
+    fn new(config: &'a BacktestConfig) -> Run<'a> {
+        Run {
+            config,
+            refdata: ReferenceData::new(config.instruments.clone()),
+            fees: FeeEngine::from_env(),
+            books: BTreeMap::new(),
+            marks: HashMap::new(),
+            positions: BTreeMap::new(),
+            cash: Money::from_f64(config.initial_capital),
+            peak: config.initial_capital,
+            next_sample_ns: 0,
+            last_tick_ns: None,
+            summary: Summary {
+                initial_capital: config.initial_capital,
+                final_equity: config.initial_capital,
+                pnl: 0.0,
+                total_return: 0.0,
+                sharpe: 0.0,
+                max_drawdown: 0.0,
+                ticks: 0,
+                skipped_ticks: 0,
+                orders: 0,
+                fills: 0,
+                unfilled: 0,
+                volume: 0.0,
+                fees: 0.0,
+            },
+            equity: EquityCurve::default(),
+            trades: TradeLog::default(),
+        }
+    }
+
+    fn tick<F>(&mut self, tick: &BboUpdate, on_market: &mut F) -> Result<(), BacktestError>
+    where
+        F: FnMut(&MarketView) -> Result<Vec<OrderIntent>, String>,
+    {
+        let Some(definition) = self.refdata.get(tick.instrument_id).cloned() else {
+            self.summary.skipped_ticks += 1;
+            return Ok(());
+        };
+        self.summary.ticks += 1;
+        self.last_tick_ns = Some(tick.timestamp_ns);
+        let book = VenueBook {
+            venue_id: tick.venue_id,
+            best_bid: tick.best_bid_price,
+            bid_size: tick.best_bid_size,
+            best_ask: tick.best_ask_price,
+            ask_size: tick.best_ask_size,
+        };
+        let books = self.books.entry(tick.instrument_id).or_default();
+        books.insert(tick.venue_id, book);
+        self.mark(&definition);
+
+        let view = MarketView {
+            instrument_id: tick.instrument_id,
+            symbol: definition.symbol.clone(),
+            price_decimals: definition.price_decimals,
+            venues: self.books[&tick.instrument_id].values().copied().collect(),
+            time_ns: i64::try_from(tick.timestamp_ns).unwrap_or(i64::MAX),
+        };
+        for order in on_market(&view).map_err(BacktestError::Strategy)? {
+            self.summary.orders += 1;
+            self.execute(&definition, tick.timestamp_ns, order);
+        }
+
+        if tick.timestamp_ns >= self.next_sample_ns {
+            self.sample(tick.timestamp_ns);
+            let interval_ns = self.config.equity_interval_ms.max(1) * 1_000_000;
+            self.next_sample_ns = tick.timestamp_ns - tick.timestamp_ns % interval_ns + interval_ns;
+        }
+        Ok(())
+    }
+
+    /// Marks the instrument at the mid of its best bid and offer across venues.
+    fn mark(&mut self, definition: &InstrumentDefinition) {
+        let books = self.books[&definition.instrument_id].values();
+        let bid = books.clone().filter(|b| b.bid_size > 0).map(|b| b.best_bid).max();
+        let ask = books.filter(|b| b.ask_size > 0 && b.best_ask > 0).map(|b| b.best_ask).min();
+        if let (Some(bid), Some(ask)) = (bid, ask) {
+            let mid = definition.price(bid).scaled(0.5) + definition.price(ask).scaled(0.5);
+            self.marks.insert(definition.instrument_id, mid);
+        }
+    }
+
+    /// Fills an order against its venue's touch, if it crosses it.
+    fn execute(&mut self, definition: &InstrumentDefinition, time_ns: u64, order: OrderIntent) {
+        let book = self.books.get_mut(&definition.instrument_id).and_then(|books| books.get_mut(&order.venue_id));
+        let filled = book.and_then(|book| {
+            let (touch, available) = match order.side {
+                Side::Buy => (book.best_ask, &mut book.ask_size),
+                Side::Sell => (book.best_bid, &mut book.bid_size),
+            };
+            let crosses = match order.side {
+                Side::Buy => order.price >= touch,
+                Side::Sell => order.price <= touch,
+            };
+            let size = order.size.min(*available);
+            if touch == 0 || !crosses || size == 0 {
+                return None;
+            }
+            *available -= size;
+            Some((touch, size))
+        });
+        let Some((touch, size)) = filled else {
+            self.summary.unfilled += 1;
+            return;
+        };
+
+        let price = definition.price(touch);
+        let quantity = match order.side {
+            Side::Buy => Quantity(i64::from(size)),
+            Side::Sell => -Quantity(i64::from(size)),
+        };
+        let venue = venue_name(order.venue_id).unwrap_or("UNROUTED");
+        let fee = Money::from_f64(self.fees.apply(venue, Liquidity::Taker, quantity.0, price.to_f64()).total);
+        let notional = definition.notional(price, quantity);
+        self.cash -= notional + fee;
+        *self.positions.entry(definition.instrument_id).or_insert(0) += quantity.0;
+
+        self.summary.fills += 1;
+        self.summary.volume += notional.abs().to_f64();
+        self.summary.fees += fee.to_f64();
+        self.trades.time_ns.push(time_ns);
+        self.trades.instrument_id.push(definition.instrument_id);
+        self.trades.symbol.push(definition.symbol.clone());
+        self.trades.venue_id.push(order.venue_id);
+        self.trades.side.push(order.side);
+        self.trades.price.push(price.to_f64());
+        self.trades.size.push(size);
+        self.trades.fee.push(fee.to_f64());
+    }
+
+    /// Cash plus positions at their marks.
+    fn equity(&self) -> f64 {
+        let marked: Money = self
+            .positions
+            .iter()
+            .filter_map(|(id, &quantity)| {
+                let definition = self.refdata.get(*id)?;
+                Some(definition.notional(*self.marks.get(id)?, Quantity(quantity)))
+            })
+            .sum();
+        (self.cash + marked).to_f64()
+    }
+
+    fn sample(&mut self, time_ns: u64) {
+        let equity = self.equity();
+        self.peak = self.peak.max(equity);
+        let drawdown = if self.peak > 0.0 { (self.peak - equity) / self.peak } else { 0.0 };
+        self.equity.time_ns.push(time_ns);
+        self.equity.equity.push(equity);
+        self.equity.drawdown.push(drawdown);
+    }
+
+    fn finish(mut self) -> Report {
+        if let Some(time_ns) = self.last_tick_ns.filter(|last| self.equity.time_ns.last() != Some(last)) {
+            self.sample(time_ns);
+        }
+        let summary = &mut self.summary;
+        summary.final_equity = self.equity.equity.last().copied().unwrap_or(self.config.initial_capital);
+        summary.pnl = summary.final_equity - self.config.initial_capital;
+        if self.config.initial_capital > 0.0 {
+            summary.total_return = summary.pnl / self.config.initial_capital;
+        }
+        summary.sharpe = sharpe(&self.equity.equity, self.config.equity_interval_ms);
+        summary.max_drawdown = self.equity.drawdown.iter().copied().fold(0.0, f64::max);
+        Report { summary: self.summary, equity: self.equity, trades: self.trades, positions: self.positions }
+    }
+}
+
+/// Annualized Sharpe ratio of an equity curve sampled every `interval_ms`.
+fn sharpe(equity: &[f64], interval_ms: u64) -> f64 {
+    let returns: Vec<f64> =
+        equity.windows(2).filter(|pair| pair[0] > 0.0).map(|pair| pair[1] / pair[0] - 1.0).collect();
+    if returns.len() < 2 {
+        return 0.0;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if variance <= 0.0 {
+        return 0.0;
+    }
+    mean / variance.sqrt() * (MILLIS_PER_YEAR / interval_ms.max(1) as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_crossing_the_touch_fill_at_it_and_equity_follows_the_mid() {
+        let path = std::env::temp_dir().join(format!("qa-backtest-{}.csv", std::process::id()));
+        let csv = "timestamp_ns,instrument_id,venue_id,bid,bid_size,ask,ask_size\n\
+                   60000000000,1,1,6000000,5,6000100,2\n\
+                   120000000000,1,1,6010000,5,6010100,2\n\
+                   180000000000,99,1,100,1,101,1\n";
+        std::fs::write(&path, csv).unwrap();
+        let config = BacktestConfig {
+            initial_capital: 100_000.0,
+            equity_interval_ms: 60_000,
+            instruments: quantumarb_refdata::default_instruments(),
+        };
+        let mut backtest = Backtest::new(config);
+        assert_eq!(backtest.load_csv(&path).unwrap(), 3);
+        let _ = std::fs::remove_file(&path);
+
+        // Buys 3 on the first tick (only 2 are offered), then sells 2 at the bid.
+        let mut calls = 0;
+        let report = backtest
+            .run(|view| {
+                calls += 1;
+                let book = view.venues[0];
+                Ok(match calls {
+                    1 => vec![OrderIntent { venue_id: 1, side: Side::Buy, price: book.best_ask, size: 3 }],
+                    2 => vec![
+                        OrderIntent { venue_id: 1, side: Side::Sell, price: book.best_bid, size: 2 },
+                        OrderIntent { venue_id: 1, side: Side::Sell, price: book.best_ask, size: 1 },
+                    ],
+                    _ => Vec::new(),
+                })
+            })
+            .unwrap();
+
+        let summary = &report.summary;
+        assert_eq!((summary.ticks, summary.skipped_ticks), (2, 1));
+        assert_eq!((summary.orders, summary.fills, summary.unfilled), (3, 2, 1));
+        assert_eq!(report.trades.size, [2, 2]);
+        assert_eq!(report.trades.price, [60001.0, 60100.0]);
+        assert_eq!(report.positions[&1], 0);
+        // Bought at 60001, sold at 60100: 198 less VENUE_A's taker fees.
+        let gross = 2.0 * (60100.0 - 60001.0);
+        assert!((summary.pnl - (gross - summary.fees)).abs() < 1e-6);
+        assert!(summary.fees > 0.0);
+        assert_eq!(report.equity.time_ns, [60000000000, 120000000000]);
+        let columns = serde_json::to_value(&report.trades).unwrap();
+        assert_eq!(columns["side"], serde_json::json!(["buy", "sell"]));
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * QuantumArb 2.0 - Shared: Conflation
+ *
+ * File: src/shared/bus/conflation.rs
+ *
+ * Description:
+ * Protects slow consumers (dashboards, the VaR calculator) from bursts of
+ * top of book updates. A `Conflator` passes at most `max_rate_hz` messages
+ * a second per topic; a message arriving sooner is held, replacing any
+ * message already held for its topic, and released once the topic is due.
+ * Each market data topic is one instrument, so a consumer sees every
+ * instrument's latest state, no later than one interval after it changed,
+ * however fast the feed ticks.
+ *
+ * A consumer can conflate its own subscription, or subscribe to the
+ * conflated topic variant (`topics::conflated`), published at the rate in
+ * QA_CONFLATION_MAX_HZ (default DEFAULT_MAX_RATE_HZ).
+ *
+ * The conflator does no I/O and keeps no timer: the caller passes the time
+ * in, and waits until `next_due` for held messages to come due.
+ */
+
+use crate::BusMessage;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Messages per second per topic when QA_CONFLATION_MAX_HZ is not set.
+pub const DEFAULT_MAX_RATE_HZ: f64 = 4.0;
+
+#[derive(Debug, Default)]
+struct TopicState {
+    last_delivered: Option<Instant>,
+    held: Option<BusMessage>,
+}
+
+/// Coalesces messages per topic to a maximum rate; see the module
+/// description.
+#[derive(Debug)]
+pub struct Conflator {
+    min_interval: Duration,
+    topics: HashMap<String, TopicState>,
+    coalesced: u64,
+}
+
+impl Conflator {
+    pub fn new(max_rate_hz: f64) -> Conflator {
+        let max_rate_hz = if max_rate_hz > 0.0 { max_rate_hz } else { DEFAULT_MAX_RATE_HZ };
+        Conflator { min_interval: Duration::from_secs_f64(1.0 / max_rate_hz), topics: HashMap::new(), coalesced: 0 }
+    }
+
+    /// A conflator at QA_CONFLATION_MAX_HZ messages a second per topic.
+    pub fn from_env() -> Conflator {
+        let max_rate_hz = std::env::var("QA_CONFLATION_MAX_HZ").ok().and_then(|v| v.parse().ok());
+        Conflator::new(max_rate_hz.unwrap_or(DEFAULT_MAX_RATE_HZ))
+    }
+
+    /// Returns `message` if its topic is due, and otherwise holds it in
+    /// place of any message held for the topic.
+    pub fn offer(&mut self, message: BusMessage, now: Instant) -> Option<BusMessage> {
+        let min_interval = self.min_interval;
+        let state = self.topics.entry(message.topic.clone()).or_default();
+        if state.last_delivered.is_none_or(|last| now >= last + min_interval) {
+            state.last_delivered = Some(now);
+            // A newer message supersedes one still held.
+            self.coalesced += state.held.take().is_some() as u64;
+            return Some(message);
+        }
+        self.coalesced += state.held.replace(message).is_some() as u64;
+        None
+    }
+
+    /// Releases the held messages whose topics are now due, in arrival order.
+    pub fn due(&mut self, now: Instant) -> Vec<BusMessage> {
+        let min_interval = self.min_interval;
+        let mut due: Vec<BusMessage> = self
+            .topics
+            .values_mut()
+            .filter(|state| state.held.is_some() && state.last_delivered.is_none_or(|last| now >= last + min_interval))
+            .filter_map(|state| {
+                state.last_delivered = Some(now);
+                state.held.take()
+            })
+            .collect();
+        due.sort_by_key(|message| message.seq);
+        due
+    }
+
+    /// When the first held message comes due; None if none is held.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.topics
+            .values()
+            .filter(|state| state.held.is_some())
+            .filter_map(|state| state.last_delivered.map(|last| last + self.min_interval))
+            .min()
+    }
+
+    /// Releases every held message regardless of rate, as when the stream
+    /// ends.
+    pub fn flush(&mut self) -> Vec<BusMessage> {
+        let mut held: Vec<BusMessage> = self.topics.values_mut().filter_map(|state| state.held.take()).collect();
+        held.sort_by_key(|message| message.seq);
+        held
+    }
+
+    /// Messages dropped for a newer one on the same topic so far.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbo(seq: u64, instrument_id: u32) -> BusMessage {
+        BusMessage { seq, topic: crate::topics::market_data(instrument_id), payload: seq.to_string().into_bytes() }
+    }
+
+    #[test]
+    fn bursts_are_coalesced_to_the_latest_state_per_instrument() {
+        let mut conflator = Conflator::new(10.0);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(conflator.offer(bbo(1, 1), at(0)).map(|m| m.seq), Some(1));
+        assert_eq!(conflator.offer(bbo(2, 2), at(1)).map(|m| m.seq), Some(2), "topics are paced apart");
+        // A burst on instrument 1 within the 100ms interval is held, newest wins.
+        for seq in 3..=9 {
+            assert!(conflator.offer(bbo(seq, 1), at(seq)).is_none());
+        }
+        assert!(conflator.offer(bbo(10, 2), at(20)).is_none());
+        assert_eq!(conflator.next_due(), Some(at(100)));
+        assert!(conflator.due(at(99)).is_empty());
+        let released: Vec<u64> = conflator.due(at(101)).iter().map(|m| m.seq).collect();
+        assert_eq!(released, vec![9, 10]);
+        assert_eq!(conflator.coalesced(), 6);
+        assert_eq!(conflator.next_due(), None);
+
+        // Nothing is lost at the end of a stream.
+        assert!(conflator.offer(bbo(11, 1), at(150)).is_none());
+        assert_eq!(conflator.flush().iter().map(|m| m.seq).collect::<Vec<_>>(), vec![11]);
+    }
+}
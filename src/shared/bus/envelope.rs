@@ -0,0 +1,286 @@
+/*
+ * QuantumArb 2.0 - Shared: Message Envelope
+ *
+ * File: src/shared/bus/envelope.rs
+ *
+ * Description:
+ * Every message published through this crate travels in an `Envelope`:
+ * the payload (JSON or a `quantumarb-wire` binary encoding, untouched)
+ * behind a short header block naming the message, its payload's schema
+ * version, the service that produced it, the request it belongs to, and
+ * when it was produced:
+ *
+ *   QA-ENVELOPE/1
+ *   Message-Id: 6f1c...            the bus dedup key when there is one
+ *   Schema-Version: 1
+ *   Source-Service: risk-gateway
+ *   Correlation-Id: 42f0...        only when set
+ *   Produced-At: 2026-10-17T09:30:00.000000000Z
+ *
+ *   <payload>
+ *
+ * Lines end in CRLF, as in NATS headers. The header is text so archived
+ * messages stay readable; the payload after the blank line is binary-safe.
+ *
+ * A consumer opens a message with `Envelope::decode` (or `decode_json` for
+ * a JSON payload) and can refuse payloads newer than it understands with
+ * `require_schema`. Messages from before the envelope (older archives,
+ * feeds that publish bare) have no header block; `payload_of` returns the
+ * payload of either kind.
+ *
+ * The source service is QA_SERVICE_NAME, or else the executable's name,
+ * which for every service binary is the service's name.
+ */
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// First line of every enveloped message.
+const PREAMBLE: &[u8] = b"QA-ENVELOPE/1\r\n";
+/// Schema version of payloads that do not set one.
+pub const DEFAULT_SCHEMA_VERSION: u16 = 1;
+
+// --- Data Structures ---
+
+/// A payload with the metadata needed to trace, replay and version it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope<T = Vec<u8>> {
+    /// Unique per message; resends of the same message keep it.
+    pub message_id: String,
+    /// Version of the payload's schema, for consumers to check.
+    pub schema_version: u16,
+    pub source_service: String,
+    /// Ties the message to the request or order it results from.
+    pub correlation_id: Option<String>,
+    pub produced_at: DateTime<Utc>,
+    pub payload: T,
+}
+
+impl<T> Envelope<T> {
+    /// Wraps `payload` under a new message id, produced now by this service.
+    pub fn new(payload: T) -> Envelope<T> {
+        Envelope {
+            message_id: Uuid::new_v4().to_string(),
+            schema_version: DEFAULT_SCHEMA_VERSION,
+            source_service: source_service().to_string(),
+            correlation_id: None,
+            produced_at: Utc::now(),
+            payload,
+        }
+    }
+
+    pub fn with_message_id(mut self, message_id: impl Into<String>) -> Envelope<T> {
+        self.message_id = message_id.into();
+        self
+    }
+
+    pub fn with_schema_version(mut self, schema_version: u16) -> Envelope<T> {
+        self.schema_version = schema_version;
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Envelope<T> {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// The same envelope around a different payload.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Envelope<U> {
+        Envelope {
+            message_id: self.message_id,
+            schema_version: self.schema_version,
+            source_service: self.source_service,
+            correlation_id: self.correlation_id,
+            produced_at: self.produced_at,
+            payload: f(self.payload),
+        }
+    }
+
+    /// Fails if the payload's schema is newer than `supported`.
+    pub fn require_schema(self, supported: u16) -> Result<Envelope<T>, EnvelopeError> {
+        if self.schema_version > supported {
+            return Err(EnvelopeError::UnsupportedSchema { found: self.schema_version, supported });
+        }
+        Ok(self)
+    }
+
+    fn encode_with(&self, payload: &[u8]) -> Vec<u8> {
+        let mut header = String::new();
+        let mut field = |name: &str, value: &str| {
+            // A line break in a value would end the header early.
+            header.push_str(&format!("{}: {}\r\n", name, value.replace(['\r', '\n'], " ")));
+        };
+        field("Message-Id", &self.message_id);
+        field("Schema-Version", &self.schema_version.to_string());
+        field("Source-Service", &self.source_service);
+        if let Some(correlation_id) = &self.correlation_id {
+            field("Correlation-Id", correlation_id);
+        }
+        field("Produced-At", &self.produced_at.to_rfc3339_opts(SecondsFormat::Nanos, true));
+
+        let mut bytes = Vec::with_capacity(PREAMBLE.len() + header.len() + 2 + payload.len());
+        bytes.extend_from_slice(PREAMBLE);
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+}
+
+impl Envelope<Vec<u8>> {
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_with(&self.payload)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Envelope<Vec<u8>>, EnvelopeError> {
+        let (header, payload) = split(bytes).ok_or(EnvelopeError::NotEnveloped)?;
+        let header = std::str::from_utf8(header).map_err(|_| EnvelopeError::Malformed("header is not UTF-8".into()))?;
+        let (mut message_id, mut schema_version, mut source_service, mut correlation_id, mut produced_at) =
+            (None, None, None, None, None);
+        for line in header.split("\r\n").filter(|line| !line.is_empty()) {
+            let (name, value) =
+                line.split_once(": ").ok_or_else(|| EnvelopeError::Malformed(format!("header line '{}'", line)))?;
+            match name {
+                "Message-Id" => message_id = Some(value.to_string()),
+                "Schema-Version" => {
+                    let version = value.parse().map_err(|_| EnvelopeError::Malformed("Schema-Version".into()))?;
+                    schema_version = Some(version);
+                }
+                "Source-Service" => source_service = Some(value.to_string()),
+                "Correlation-Id" => correlation_id = Some(value.to_string()),
+                "Produced-At" => {
+                    let at = DateTime::parse_from_rfc3339(value)
+                        .map_err(|_| EnvelopeError::Malformed("Produced-At".into()))?;
+                    produced_at = Some(at.with_timezone(&Utc));
+                }
+                // Headers added by later versions are skipped.
+                _ => {}
+            }
+        }
+        let missing = |name: &'static str| EnvelopeError::Missing(name);
+        Ok(Envelope {
+            message_id: message_id.ok_or_else(|| missing("Message-Id"))?,
+            schema_version: schema_version.ok_or_else(|| missing("Schema-Version"))?,
+            source_service: source_service.ok_or_else(|| missing("Source-Service"))?,
+            correlation_id,
+            produced_at: produced_at.ok_or_else(|| missing("Produced-At"))?,
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+impl<T: Serialize> Envelope<T> {
+    /// Encodes the envelope with `payload` as JSON.
+    pub fn encode_json(&self) -> Result<Vec<u8>, EnvelopeError> {
+        let payload = serde_json::to_vec(&self.payload).map_err(|e| EnvelopeError::Payload(e.to_string()))?;
+        Ok(self.encode_with(&payload))
+    }
+}
+
+impl<T: DeserializeOwned> Envelope<T> {
+    /// Decodes an envelope with a JSON payload.
+    pub fn decode_json(bytes: &[u8]) -> Result<Envelope<T>, EnvelopeError> {
+        let envelope = Envelope::decode(bytes)?;
+        let payload = serde_json::from_slice(&envelope.payload).map_err(|e| EnvelopeError::Payload(e.to_string()))?;
+        Ok(envelope.map(|_| payload))
+    }
+}
+
+/// The header block and the payload of an enveloped message.
+fn split(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let rest = bytes.strip_prefix(PREAMBLE)?;
+    if let Some(payload) = rest.strip_prefix(b"\r\n") {
+        return Some((&[], payload));
+    }
+    let end = rest.windows(4).position(|w| w == b"\r\n\r\n")?;
+    Some((&rest[..end + 2], &rest[end + 4..]))
+}
+
+/// The payload of `bytes`, whether enveloped or published bare.
+pub fn payload_of(bytes: &[u8]) -> &[u8] {
+    split(bytes).map_or(bytes, |(_, payload)| payload)
+}
+
+/// Name this process publishes under.
+pub fn source_service() -> &'static str {
+    static SOURCE: OnceLock<String> = OnceLock::new();
+    SOURCE.get_or_init(|| {
+        std::env::var("QA_SERVICE_NAME")
+            .ok()
+            .filter(|name| !name.trim().is_empty())
+            .or_else(|| {
+                let exe = std::env::current_exe().ok()?;
+                exe.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "unknown".to_string())
+    })
+}
+
+// --- Errors ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvelopeError {
+    /// The bytes do not start with an envelope header block.
+    NotEnveloped,
+    Malformed(String),
+    Missing(&'static str),
+    UnsupportedSchema { found: u16, supported: u16 },
+    Payload(String),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::NotEnveloped => write!(f, "message has no envelope"),
+            EnvelopeError::Malformed(what) => write!(f, "malformed envelope: {}", what),
+            EnvelopeError::Missing(name) => write!(f, "envelope has no {} header", name),
+            EnvelopeError::UnsupportedSchema { found, supported } => {
+                write!(f, "payload schema version {} is newer than the supported {}", found, supported)
+            }
+            EnvelopeError::Payload(reason) => write!(f, "invalid payload: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Alert {
+        instrument_id: u32,
+        reason: String,
+    }
+
+    #[test]
+    fn envelopes_round_trip_with_binary_and_json_payloads() {
+        let binary = Envelope::new(vec![0u8, 13, 10, 13, 10, 255])
+            .with_message_id("order-7")
+            .with_correlation_id("trace\r\n42")
+            .with_schema_version(3);
+        let decoded = Envelope::decode(&binary.encode()).unwrap();
+        assert_eq!(decoded.payload, binary.payload, "the payload is binary-safe");
+        assert_eq!(decoded.message_id, "order-7");
+        assert_eq!(decoded.correlation_id.as_deref(), Some("trace  42"), "line breaks cannot end the header");
+        assert_eq!(decoded.produced_at, binary.produced_at);
+        assert_eq!(decoded.source_service, source_service());
+        assert_eq!(decoded.clone().require_schema(2), Err(EnvelopeError::UnsupportedSchema { found: 3, supported: 2 }));
+        assert!(decoded.require_schema(3).is_ok());
+
+        let alert = Envelope::new(Alert { instrument_id: 1, reason: "crossed".into() });
+        let bytes = alert.encode_json().unwrap();
+        assert_eq!(Envelope::<Alert>::decode_json(&bytes).unwrap(), alert);
+        assert_eq!(payload_of(&bytes), br#"{"instrument_id":1,"reason":"crossed"}"#);
+
+        // Messages published before the envelope pass through whole.
+        assert_eq!(payload_of(b"{\"bare\":true}"), b"{\"bare\":true}");
+        assert_eq!(Envelope::decode(b"{\"bare\":true}"), Err(EnvelopeError::NotEnveloped));
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * QuantumArb 2.0 - Shared: Kafka Transport
+ *
+ * File: src/shared/bus/kafka.rs
+ *
+ * Description:
+ * Carries the bus over Kafka (rdkafka), with QA_BUS_TRANSPORT=kafka in a
+ * build with the `kafka` feature. The topic mapping below is compiled into
+ * every build so it can be checked without a broker.
+ *
+ * Topics: each platform topic is carried on the Kafka topic `subjects.rs`
+ * maps it to, so a per-instrument family is one Kafka topic keyed by
+ * instrument and one instrument's ticks stay in order on one partition.
+ * Every other topic is keyed by the `account_id` or else the
+ * `instrument_id` its JSON body names; a message with neither (or a binary
+ * body) has no key and is spread over the partitions.
+ *
+ *   market_data.instrument.7   ->  market_data.instrument   key instrument:7
+ *   orders.requests            ->  orders.requests          key account:101
+ *
+ * The value is the encoded envelope; the platform topic travels in the
+ * `qa-subject` header and the envelope's message id in `qa-message-id`.
+ *
+ * Delivery is at least once. The producer waits for every in-sync replica
+ * (acks=all) and is idempotent, so its own retries never duplicate a
+ * message. A subscriber joins a consumer group (QA_KAFKA_GROUP_ID, by
+ * default the service's name) whose members share the partitions, with
+ * cooperative rebalancing; only the offsets of messages it has acked are
+ * committed, so a message being handled when a member dies or loses its
+ * partition is delivered again, to whoever has the partition next. Acks
+ * must follow the order messages were received in. Consumers dedupe by
+ * `Delivery::message_id` where a redelivery matters.
+ *
+ * Subscriptions take NATS subjects, wildcards included ('*' one token, '>'
+ * the rest): `market_data.instrument.*` subscribes to the Kafka topic
+ * `market_data.instrument`, `alerts.>` to every Kafka topic under
+ * `alerts.`, and messages whose subject the subscription does not match
+ * are skipped.
+ *
+ * Configuration (environment):
+ *   QA_KAFKA_BROKERS=localhost:9092    bootstrap servers
+ *   QA_KAFKA_CLIENT_ID                 by default the service's name
+ *   QA_KAFKA_GROUP_ID                  consumer group, by default the
+ *                                      service's name
+ *   QA_KAFKA_LINGER_MS=1               producer batching delay
+ *   QA_KAFKA_SESSION_TIMEOUT_MS=10000  a silent member leaves the group
+ *   QA_KAFKA_AUTO_OFFSET_RESET=earliest
+ *                                      where a new group starts
+ */
+
+use crate::subjects::{self, carrier};
+
+/// Header carrying the platform topic.
+pub const SUBJECT_HEADER: &str = "qa-subject";
+/// Header carrying the envelope's message id.
+pub const MESSAGE_ID_HEADER: &str = "qa-message-id";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    pub brokers: String,
+    pub client_id: String,
+    pub group_id: String,
+    pub linger_ms: u64,
+    pub session_timeout_ms: u64,
+    pub auto_offset_reset: String,
+}
+
+impl KafkaConfig {
+    pub fn from_env() -> KafkaConfig {
+        fn var(name: &str, default: &str) -> String {
+            std::env::var(name).unwrap_or_else(|_| default.to_string())
+        }
+        let service = crate::envelope::source_service();
+        KafkaConfig {
+            brokers: var("QA_KAFKA_BROKERS", "localhost:9092"),
+            client_id: var("QA_KAFKA_CLIENT_ID", service),
+            group_id: var("QA_KAFKA_GROUP_ID", service),
+            linger_ms: var("QA_KAFKA_LINGER_MS", "1").parse().unwrap_or(1),
+            session_timeout_ms: var("QA_KAFKA_SESSION_TIMEOUT_MS", "10000").parse().unwrap_or(10_000),
+            auto_offset_reset: var("QA_KAFKA_AUTO_OFFSET_RESET", "earliest"),
+        }
+    }
+}
+
+// --- Topic Mapping ---
+
+/// The key a message is partitioned by: its instrument on a per-instrument
+/// topic, or else the account or instrument its JSON body names.
+pub fn partition_key(topic: &str, body: &[u8]) -> Option<String> {
+    if let (_, Some(instrument)) = carrier(topic) {
+        return Some(format!("instrument:{}", instrument));
+    }
+    let body: serde_json::Value = serde_json::from_slice(body).ok()?;
+    [("account_id", "account"), ("instrument_id", "instrument")].into_iter().find_map(|(field, name)| {
+        let value = body.get(field)?;
+        let value = value.as_u64().map(|id| id.to_string()).or_else(|| value.as_str().map(str::to_string))?;
+        Some(format!("{}:{}", name, value))
+    })
+}
+
+/// The Kafka subscription for a subject: a topic name, or a regex (leading
+/// '^', as librdkafka takes them) for a wildcard outside an instrument family.
+pub fn kafka_subscription(subject: &str) -> String {
+    if !subjects::needs_discovery(subject) {
+        return carrier(subject).0.to_string();
+    }
+    let tokens: Vec<&str> = subject
+        .split('.')
+        .map(|token| match token {
+            "*" => "[^.]+",
+            ">" => ".+",
+            literal => literal,
+        })
+        .collect();
+    format!("^{}$", tokens.join("\\."))
+}
+
+#[cfg(feature = "kafka")]
+pub use client::{Delivery, KafkaSubscriber, KafkaTransport};
+
+#[cfg(feature = "kafka")]
+mod client {
+    use rdkafka::consumer::{Consumer, ConsumerContext, Rebalance, StreamConsumer};
+    use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
+    use rdkafka::message::{Header, Headers, Message, OwnedHeaders};
+    use rdkafka::producer::{BaseRecord, DeliveryResult, ProducerContext, ThreadedProducer};
+    use rdkafka::{ClientConfig, ClientContext, TopicPartitionList};
+    use std::time::Duration;
+
+    use super::{kafka_subscription, partition_key, KafkaConfig};
+    use super::{MESSAGE_ID_HEADER, SUBJECT_HEADER};
+    use crate::subjects::{carrier, subject_matches};
+    use crate::transport::Transport;
+    use crate::{BusMessage, Envelope};
+
+    /// How long a publish waits for room in a full producer queue.
+    const QUEUE_FULL_WAIT: Duration = Duration::from_millis(5);
+
+    // --- Producer ---
+
+    /// Reports messages Kafka could not deliver after its retries.
+    pub struct DeliveryReports;
+
+    impl ClientContext for DeliveryReports {}
+
+    impl ProducerContext for DeliveryReports {
+        type DeliveryOpaque = ();
+
+        fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+            if let Err((e, message)) = result {
+                println!("  -> Kafka delivery to '{}' failed: {}", message.topic(), e);
+            }
+        }
+    }
+
+    /// Publishes to Kafka from a background thread of librdkafka's.
+    pub struct KafkaTransport {
+        producer: ThreadedProducer<DeliveryReports>,
+    }
+
+    impl KafkaTransport {
+        pub fn connect(config: &KafkaConfig) -> KafkaResult<KafkaTransport> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", &config.brokers)
+                .set("client.id", &config.client_id)
+                .set("acks", "all")
+                .set("enable.idempotence", "true")
+                .set("linger.ms", config.linger_ms.to_string())
+                .create_with_context(DeliveryReports)?;
+            println!("Publishing to Kafka at {} as '{}'", config.brokers, config.client_id);
+            Ok(KafkaTransport { producer })
+        }
+    }
+
+    impl Transport for KafkaTransport {
+        fn name(&self) -> &'static str {
+            "kafka"
+        }
+
+        fn publish(&self, topic: &str, envelope: &Envelope) {
+            let (kafka_topic, _) = carrier(topic);
+            let key = partition_key(topic, &envelope.payload);
+            let payload = envelope.encode();
+            let headers = OwnedHeaders::new()
+                .insert(Header { key: SUBJECT_HEADER, value: Some(topic) })
+                .insert(Header { key: MESSAGE_ID_HEADER, value: Some(&envelope.message_id) });
+            let mut record = BaseRecord::to(kafka_topic).payload(&payload).headers(headers);
+            if let Some(key) = &key {
+                record = record.key(key);
+            }
+            // A full queue means the brokers are behind: wait rather than drop.
+            while let Err((e, returned)) = self.producer.send(record) {
+                if e != KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull) {
+                    println!("  -> Failed to publish to Kafka topic '{}': {}", kafka_topic, e);
+                    return;
+                }
+                std::thread::sleep(QUEUE_FULL_WAIT);
+                record = returned;
+            }
+        }
+    }
+
+    // --- Consumer ---
+
+    /// Logs the group's partition assignments and failed commits.
+    pub struct GroupEvents {
+        group_id: String,
+    }
+
+    impl ClientContext for GroupEvents {}
+
+    impl ConsumerContext for GroupEvents {
+        fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
+            let describe = |partitions: &TopicPartitionList| {
+                let list: Vec<String> =
+                    partitions.elements().iter().map(|p| format!("{}/{}", p.topic(), p.partition())).collect();
+                list.join(", ")
+            };
+            match rebalance {
+                Rebalance::Assign(partitions) => {
+                    println!("Kafka group '{}' assigned [{}]", self.group_id, describe(partitions))
+                }
+                Rebalance::Revoke(partitions) => {
+                    println!("Kafka group '{}' revoked [{}]", self.group_id, describe(partitions))
+                }
+                Rebalance::Error(e) => println!("Kafka group '{}' failed to rebalance: {}", self.group_id, e),
+            }
+        }
+
+        fn commit_callback(&self, result: KafkaResult<()>, _: &TopicPartitionList) {
+            if let Err(e) = result {
+                println!("  -> Kafka group '{}' failed to commit offsets: {}", self.group_id, e);
+            }
+        }
+    }
+
+    /// A message received from Kafka, to be acked once handled.
+    pub struct Delivery {
+        pub message: BusMessage,
+        /// The envelope's message id, to recognise a redelivery.
+        pub message_id: Option<String>,
+        kafka_topic: String,
+        partition: i32,
+        offset: i64,
+    }
+
+    /// A consumer group member subscribed to platform subjects.
+    pub struct KafkaSubscriber {
+        consumer: StreamConsumer<GroupEvents>,
+        subjects: Vec<String>,
+        seq: u64,
+    }
+
+    impl KafkaSubscriber {
+        /// Joins the configured consumer group on the Kafka topics carrying
+        /// `subjects`.
+        pub fn subscribe(config: &KafkaConfig, subjects: &[&str]) -> KafkaResult<KafkaSubscriber> {
+            let consumer: StreamConsumer<GroupEvents> = ClientConfig::new()
+                .set("bootstrap.servers", &config.brokers)
+                .set("client.id", &config.client_id)
+                .set("group.id", &config.group_id)
+                .set("partition.assignment.strategy", "cooperative-sticky")
+                .set("session.timeout.ms", config.session_timeout_ms.to_string())
+                .set("auto.offset.reset", &config.auto_offset_reset)
+                // Offsets are committed in the background, but only once acked.
+                .set("enable.auto.commit", "true")
+                .set("enable.auto.offset.store", "false")
+                .create_with_context(GroupEvents { group_id: config.group_id.clone() })?;
+            let mut kafka_topics: Vec<String> = subjects.iter().map(|subject| kafka_subscription(subject)).collect();
+            kafka_topics.sort();
+            kafka_topics.dedup();
+            consumer.subscribe(&kafka_topics.iter().map(String::as_str).collect::<Vec<_>>())?;
+            println!("Kafka group '{}' subscribed to {:?}", config.group_id, kafka_topics);
+            Ok(KafkaSubscriber {
+                consumer,
+                subjects: subjects.iter().map(|subject| subject.to_string()).collect(),
+                seq: 0,
+            })
+        }
+
+        /// The next message on a subscribed subject. Until it is acked, it
+        /// is delivered again after a restart or rebalance.
+        pub async fn recv(&mut self) -> KafkaResult<Delivery> {
+            loop {
+                let message = self.consumer.recv().await?;
+                let header = |name: &str| {
+                    let headers = message.headers()?;
+                    (0..headers.count()).map(|i| headers.get(i)).find(|h| h.key == name).and_then(|h| {
+                        h.value.and_then(|value| std::str::from_utf8(value).ok()).map(str::to_string)
+                    })
+                };
+                let subject = header(SUBJECT_HEADER).unwrap_or_else(|| message.topic().to_string());
+                if !self.subjects.iter().any(|pattern| subject_matches(pattern, &subject)) {
+                    self.consumer.store_offset(message.topic(), message.partition(), message.offset())?;
+                    continue;
+                }
+                self.seq += 1;
+                return Ok(Delivery {
+                    message: BusMessage {
+                        seq: self.seq,
+                        topic: subject,
+                        payload: message.payload().unwrap_or_default().to_vec(),
+                    },
+                    message_id: header(MESSAGE_ID_HEADER),
+                    kafka_topic: message.topic().to_string(),
+                    partition: message.partition(),
+                    offset: message.offset(),
+                });
+            }
+        }
+
+        /// Marks a delivery handled; its offset goes out with the next commit.
+        pub fn ack(&self, delivery: &Delivery) -> KafkaResult<()> {
+            self.consumer.store_offset(&delivery.kafka_topic, delivery.partition, delivery.offset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_keyed_and_subjects_subscribed_as_kafka_topics() {
+        assert_eq!(partition_key("market_data.conflated.instrument.7", b"\x01\x02"), Some("instrument:7".into()));
+        let order = br#"{"order_id":"a","account_id":101,"instrument_id":1}"#;
+        assert_eq!(partition_key("orders.requests", order), Some("account:101".into()));
+        let alert = br#"{"instrument_id":4,"kind":"SUSPECT"}"#;
+        assert_eq!(partition_key("alerts.data_quality", alert), Some("instrument:4".into()));
+        assert_eq!(partition_key("heartbeats", br#"{"source":"market_data"}"#), None);
+        assert_eq!(partition_key("execution_reports", b"\x00binary"), None);
+
+        assert_eq!(kafka_subscription("market_data.instrument.*"), "market_data.instrument");
+        assert_eq!(kafka_subscription("orders.requests"), "orders.requests");
+        assert_eq!(kafka_subscription("alerts.>"), "^alerts\\..+$");
+        assert_eq!(kafka_subscription("portfolio.*.pnl"), "^portfolio\\.[^.]+\\.pnl$");
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * QuantumArb 2.0 - Shared: Message Bus
+ *
+ * File: src/shared/bus/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-bus`) is the services' side of the
+ * message bus (NATS JetStream or Kafka in production): the platform-wide
+ * topic names, the message a subscription delivers, the envelope every
+ * message travels in (see `envelope.rs`), and publishing.
+ *
+ * Every publish wraps its payload in an `Envelope` stamped with a message
+ * id, the payload's schema version, this service's name and the time, so
+ * tracing, replay and compatibility checks work the same on every topic.
+ * The message goes out on the process's transport (see `transport.rs`):
+ * NATS, which until the services hold a real bus connection logs the
+ * message it would send, in the same form everywhere:
+ *
+ *   -> Publishing to topic 'alerts.data_quality' [6f1c...]: {...}
+ *
+ * Kafka (`kafka.rs`) or Redis Streams (`redis_streams.rs`), so swapping in
+ * a broker client is a change to this crate only. Market data can instead
+ * go out over UDP multicast (`multicast.rs`), sequenced and with snapshots
+ * for receivers that join late or miss a datagram.
+ * Consumers of the order flow can resume it from a checkpoint after a
+ * restart, catching up on what they missed before going live (`replay.rs`).
+ * `publish_deduplicated` uses its dedup key as the message id (JetStream's
+ * Nats-Msg-Id): the bus drops a message whose id it has seen within its
+ * duplicate window, so a publisher that cannot tell whether a send went
+ * through (see `quantumarb-outbox`) can simply send it again.
+ *
+ * A desk's own messages (its accounts' P&L, its strategies' heartbeats)
+ * travel under the desk's namespace, `desk.<desk>.<topic>` (see
+ * `topics::for_desk`), so a desk's consumers subscribe to its namespace
+ * only; firm-wide consumers subscribe to the plain topic and to
+ * `desk.*.<topic>`, and read the topic a message came on with
+ * `topics::split_desk`. Messages of no desk keep the plain topic.
+ *
+ * Topics one service both owns and documents (the strategy engine's model
+ * topics, the reference data updates) keep their constants next to that
+ * service's code.
+ */
+
+pub mod conflation;
+pub mod envelope;
+pub mod kafka;
+pub mod multicast;
+pub mod redis_streams;
+pub mod replay;
+pub mod subjects;
+pub mod transport;
+
+pub use conflation::Conflator;
+pub use envelope::{Envelope, EnvelopeError};
+pub use transport::Transport;
+use serde::Serialize;
+
+/// Topics shared across services.
+pub mod topics {
+    /// Liveness of strategies and the market data feed (`quantumarb-types::Heartbeat`).
+    pub const HEARTBEATS: &str = "heartbeats";
+    pub const DATA_QUALITY_ALERTS: &str = "alerts.data_quality";
+    pub const ALT_DATA_ALERTS: &str = "alerts.alt_data";
+    pub const ORDER_REQUESTS: &str = "orders.requests";
+    pub const EXECUTION_REPORTS: &str = "execution_reports";
+    pub const PACKAGE_REPORTS: &str = "execution_reports.packages";
+    /// Top of book, one topic per instrument: `market_data.instrument.<id>`.
+    pub const MARKET_DATA_PREFIX: &str = "market_data.instrument.";
+    /// The same top of book at a capped rate per instrument (see `conflation`).
+    pub const CONFLATED_MARKET_DATA_PREFIX: &str = "market_data.conflated.instrument.";
+    /// Best bid and offer across venues, one topic per instrument
+    /// (`quantumarb-wire::ConsolidatedBbo`).
+    pub const CONSOLIDATED_MARKET_DATA_PREFIX: &str = "market_data.consolidated.instrument.";
+    /// Imbalance, microprice and momentum, one topic per instrument:
+    /// `signals.instrument.<id>` (`quantumarb-signals::BookSignals`).
+    pub const SIGNALS_PREFIX: &str = "signals.instrument.";
+    pub const ALT_DATA_NORMALIZED: &str = "alt_data.normalized";
+    pub const CORPORATE_ACTIONS: &str = "reference.corporate_actions";
+    /// Precipitation forecasts along the microwave links (`quantumarb-types::LinkWeatherForecast`).
+    pub const WEATHER_FORECASTS: &str = "alt_data.weather";
+    /// Upcoming economic releases and their event-risk windows (`quantumarb-types::EventWindow`).
+    pub const EVENT_WINDOWS: &str = "calendar.event_windows";
+    /// Venue session state changes (`quantumarb-types::VenueConnectivity`).
+    pub const VENUE_CONNECTIVITY: &str = "venues.connectivity";
+    /// Position reductions for the strategy engine (`quantumarb-types::StrategyInstruction`).
+    pub const STRATEGY_INSTRUCTIONS: &str = "strategy.instructions";
+    /// Session P&L per account (`quantumarb-types::AccountPnl`).
+    pub const ACCOUNT_PNL: &str = "portfolio.account_pnl";
+    /// Audit trail events kept by the WORM logger (`quantumarb-types::AuditEvent`).
+    pub const AUDIT_EVENTS: &str = "audit.events";
+    /// Hosts' clock offsets and the fleet's skew, every poll of the clock monitor.
+    pub const CLOCK_SKEW: &str = "clock.skew";
+    /// Changes of a host's or the fleet's clock level.
+    pub const CLOCK_SKEW_ALERTS: &str = "alerts.clock_skew";
+
+    /// Namespace of desk-owned topics: `desk.<desk>.<topic>`.
+    pub const DESK_PREFIX: &str = "desk.";
+
+    /// `topic` in the namespace of `desk`; unchanged for no desk.
+    pub fn for_desk(desk: Option<&str>, topic: &str) -> String {
+        match desk {
+            Some(desk) => format!("{}{}.{}", DESK_PREFIX, desk, topic),
+            None => topic.to_string(),
+        }
+    }
+
+    /// The subject pattern of `topic` in every desk's namespace.
+    pub fn all_desks(topic: &str) -> String {
+        format!("{}*.{}", DESK_PREFIX, topic)
+    }
+
+    /// The desk of a namespaced topic and the topic within the namespace.
+    pub fn split_desk(topic: &str) -> (Option<&str>, &str) {
+        topic
+            .strip_prefix(DESK_PREFIX)
+            .and_then(|rest| rest.split_once('.'))
+            .map_or((None, topic), |(desk, topic)| (Some(desk), topic))
+    }
+
+    pub fn market_data(instrument_id: u32) -> String {
+        format!("{}{}", MARKET_DATA_PREFIX, instrument_id)
+    }
+
+    pub fn market_data_conflated(instrument_id: u32) -> String {
+        format!("{}{}", CONFLATED_MARKET_DATA_PREFIX, instrument_id)
+    }
+
+    pub fn consolidated_market_data(instrument_id: u32) -> String {
+        format!("{}{}", CONSOLIDATED_MARKET_DATA_PREFIX, instrument_id)
+    }
+
+    pub fn signals(instrument_id: u32) -> String {
+        format!("{}{}", SIGNALS_PREFIX, instrument_id)
+    }
+
+    /// The conflated variant of a market data topic; None for other topics.
+    pub fn conflated(topic: &str) -> Option<String> {
+        let instrument = topic.strip_prefix(MARKET_DATA_PREFIX)?;
+        Some(format!("{}{}", CONFLATED_MARKET_DATA_PREFIX, instrument))
+    }
+}
+
+/// A raw message as delivered by a subscription.
+#[derive(Debug, Clone)]
+pub struct BusMessage {
+    /// Arrival order across all topics of the subscription.
+    pub seq: u64,
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+impl BusMessage {
+    /// The payload inside the message's envelope (the whole message if it
+    /// was published bare).
+    pub fn body(&self) -> &[u8] {
+        envelope::payload_of(&self.payload)
+    }
+}
+
+/// Publishes an enveloped payload on `topic`.
+pub fn publish_envelope(topic: &str, envelope: &Envelope) {
+    if multicast::carries(topic) {
+        if let Some(publisher) = multicast::publisher() {
+            publisher.publish(topic, envelope);
+            return;
+        }
+    }
+    transport::transport().publish(topic, envelope);
+}
+
+/// Publishes a text payload on `topic`.
+pub fn publish(topic: &str, payload: &str) {
+    publish_envelope(topic, &Envelope::new(payload.as_bytes().to_vec()));
+}
+
+/// Publishes `payload` on `topic` under a dedup key; a resend with the same
+/// key is dropped by the bus.
+pub fn publish_deduplicated(topic: &str, payload: &[u8], key: &str) {
+    publish_envelope(topic, &Envelope::new(payload.to_vec()).with_message_id(key));
+}
+
+/// Publishes `message` on `topic` as JSON.
+pub fn publish_json<T: Serialize>(topic: &str, message: &T) {
+    match serde_json::to_vec(message) {
+        Ok(payload) => publish_envelope(topic, &Envelope::new(payload)),
+        Err(e) => println!("  -> Failed to encode a message for topic '{}': {}", topic, e),
+    }
+}
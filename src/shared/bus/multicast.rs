@@ -0,0 +1,481 @@
+/*
+ * QuantumArb 2.0 - Shared: Market Data Multicast
+ *
+ * File: src/shared/bus/multicast.rs
+ *
+ * Description:
+ * Fans market data out over UDP multicast rather than the bus. With
+ * QA_MD_MULTICAST_GROUP set, every publish on a `market_data.` topic is
+ * one datagram to the group, reaching every subscriber on the segment
+ * without a broker hop or a copy per subscriber; other topics stay on the
+ * bus transport.
+ *
+ *   datagram   "QAMD" | session u64 | seq u64 | topic length u16 | topic
+ *              | envelope
+ *
+ * (big endian). The session is drawn when the publisher starts; seq counts
+ * its datagrams from 1 across all topics.
+ *
+ * UDP drops and reorders, so receivers sequence: a datagram after the next
+ * expected one is a gap, one before it a duplicate or straggler and
+ * dropped. A gap is not replayed. Market data is last-value, so the
+ * receiver takes a snapshot instead: the publisher keeps the latest message
+ * of every topic and serves them on its TCP snapshot channel
+ * (QA_MD_SNAPSHOT_BIND):
+ *
+ *   snapshot   "QAMS" | session u64 | seq u64 | count u32
+ *              | count x (seq u64 | topic length u16 | topic
+ *                         | envelope length u32 | envelope)
+ *
+ * where seq is the last datagram the snapshot covers. A receiver takes one
+ * when it joins (at its first datagram, so a late joiner starts from the
+ * current book), after a gap, and when the session changes (the publisher
+ * restarted). It delivers the snapshot's messages, then the datagrams after
+ * the snapshot's seq, dropping those it covers. If the snapshot channel is
+ * unreachable it carries on from the datagram in hand and counts the gap.
+ *
+ * Configuration (environment):
+ *   QA_MD_MULTICAST_GROUP          group and port, e.g. 239.192.0.1:30001;
+ *                                  market data goes on the bus if unset
+ *   QA_MD_MULTICAST_IFACE=0.0.0.0  interface to send and join on
+ *   QA_MD_MULTICAST_TTL=1          hops a datagram may take
+ *   QA_MD_SNAPSHOT_BIND=0.0.0.0:30002
+ *                                  publisher's snapshot channel
+ *   QA_MD_SNAPSHOT_ADDR=127.0.0.1:30002
+ *                                  where receivers ask for snapshots
+ *   QA_MD_SNAPSHOT_TIMEOUT_MS=500
+ */
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::{BusMessage, Envelope};
+
+const DATAGRAM_MAGIC: &[u8; 4] = b"QAMD";
+const SNAPSHOT_MAGIC: &[u8; 4] = b"QAMS";
+/// The largest UDP payload over IPv4.
+const MAX_DATAGRAM: usize = 65_507;
+/// Topics carried by multicast when it is configured.
+const MARKET_DATA_TOPICS: &str = "market_data.";
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct MulticastConfig {
+    pub group: SocketAddrV4,
+    pub interface: Ipv4Addr,
+    pub ttl: u32,
+    pub snapshot_bind: String,
+    pub snapshot_addr: String,
+    pub snapshot_timeout: Duration,
+}
+
+impl MulticastConfig {
+    /// None unless QA_MD_MULTICAST_GROUP is set.
+    pub fn from_env() -> Option<MulticastConfig> {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let group = std::env::var("QA_MD_MULTICAST_GROUP").ok()?;
+        let group = match group.parse() {
+            Ok(group) => group,
+            Err(_) => panic!("QA_MD_MULTICAST_GROUP={}: expected an IPv4 group and port", group),
+        };
+        Some(MulticastConfig {
+            group,
+            interface: var("QA_MD_MULTICAST_IFACE", Ipv4Addr::UNSPECIFIED),
+            ttl: var("QA_MD_MULTICAST_TTL", 1),
+            snapshot_bind: var("QA_MD_SNAPSHOT_BIND", "0.0.0.0:30002".to_string()),
+            snapshot_addr: var("QA_MD_SNAPSHOT_ADDR", "127.0.0.1:30002".to_string()),
+            snapshot_timeout: Duration::from_millis(var("QA_MD_SNAPSHOT_TIMEOUT_MS", 500)),
+        })
+    }
+}
+
+/// Whether multicast carries `topic` when it is configured.
+pub fn carries(topic: &str) -> bool {
+    topic.starts_with(MARKET_DATA_TOPICS)
+}
+
+/// The process's multicast publisher, if QA_MD_MULTICAST_GROUP is set.
+pub fn publisher() -> Option<&'static MulticastPublisher> {
+    static PUBLISHER: OnceLock<Option<MulticastPublisher>> = OnceLock::new();
+    PUBLISHER
+        .get_or_init(|| {
+            let config = MulticastConfig::from_env()?;
+            match MulticastPublisher::start(&config) {
+                Ok(publisher) => Some(publisher),
+                Err(e) => panic!("Could not start multicast to {}: {}", config.group, e),
+            }
+        })
+        .as_ref()
+}
+
+// --- Framing ---
+
+/// A sequenced message on the multicast group or in a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequenced {
+    pub seq: u64,
+    pub topic: String,
+    pub envelope: Vec<u8>,
+}
+
+pub fn encode_datagram(session: u64, message: &Sequenced) -> Vec<u8> {
+    let mut out = Vec::with_capacity(22 + message.topic.len() + message.envelope.len());
+    out.extend_from_slice(DATAGRAM_MAGIC);
+    out.extend(session.to_be_bytes());
+    out.extend(message.seq.to_be_bytes());
+    out.extend((message.topic.len() as u16).to_be_bytes());
+    out.extend_from_slice(message.topic.as_bytes());
+    out.extend_from_slice(&message.envelope);
+    out
+}
+
+/// The session and message of a datagram; None for anything else.
+pub fn decode_datagram(bytes: &[u8]) -> Option<(u64, Sequenced)> {
+    let mut reader = Cursor(bytes);
+    if reader.take(4)? != DATAGRAM_MAGIC {
+        return None;
+    }
+    let session = reader.u64()?;
+    let seq = reader.u64()?;
+    let topic_len = reader.u16()? as usize;
+    let topic = String::from_utf8(reader.take(topic_len)?.to_vec()).ok()?;
+    Some((session, Sequenced { seq, topic, envelope: reader.0.to_vec() }))
+}
+
+pub fn encode_snapshot(session: u64, seq: u64, messages: &[Sequenced]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(SNAPSHOT_MAGIC);
+    out.extend(session.to_be_bytes());
+    out.extend(seq.to_be_bytes());
+    out.extend((messages.len() as u32).to_be_bytes());
+    for message in messages {
+        out.extend(message.seq.to_be_bytes());
+        out.extend((message.topic.len() as u16).to_be_bytes());
+        out.extend_from_slice(message.topic.as_bytes());
+        out.extend((message.envelope.len() as u32).to_be_bytes());
+        out.extend_from_slice(&message.envelope);
+    }
+    out
+}
+
+/// The session, the seq covered and the messages of a snapshot.
+pub fn decode_snapshot(bytes: &[u8]) -> Option<(u64, u64, Vec<Sequenced>)> {
+    let mut reader = Cursor(bytes);
+    if reader.take(4)? != SNAPSHOT_MAGIC {
+        return None;
+    }
+    let session = reader.u64()?;
+    let seq = reader.u64()?;
+    let count = reader.u32()?;
+    let mut messages = Vec::with_capacity(count.min(10_000) as usize);
+    for _ in 0..count {
+        let message_seq = reader.u64()?;
+        let topic_len = reader.u16()? as usize;
+        let topic = String::from_utf8(reader.take(topic_len)?.to_vec()).ok()?;
+        let envelope_len = reader.u32()? as usize;
+        messages.push(Sequenced { seq: message_seq, topic, envelope: reader.take(envelope_len)?.to_vec() });
+    }
+    Some((session, seq, messages))
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+// --- Publishing ---
+
+struct PublisherState {
+    seq: u64,
+    /// The latest message of every topic: the snapshot.
+    latest: BTreeMap<String, Sequenced>,
+}
+
+/// Sends market data to the group and serves snapshots of it.
+pub struct MulticastPublisher {
+    socket: UdpSocket,
+    group: SocketAddrV4,
+    session: u64,
+    state: Arc<Mutex<PublisherState>>,
+    snapshot_addr: SocketAddr,
+}
+
+impl MulticastPublisher {
+    pub fn start(config: &MulticastConfig) -> io::Result<MulticastPublisher> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_if_v4(&config.interface)?;
+        socket.set_multicast_ttl_v4(config.ttl)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.bind(&SocketAddr::from((config.interface, 0)).into())?;
+
+        let state = Arc::new(Mutex::new(PublisherState { seq: 0, latest: BTreeMap::new() }));
+        let session = uuid::Uuid::new_v4().as_u64_pair().0;
+        let listener = TcpListener::bind(&config.snapshot_bind)?;
+        let snapshot_addr = listener.local_addr()?;
+        let snapshot_state = state.clone();
+        std::thread::spawn(move || serve_snapshots(listener, session, snapshot_state));
+        println!(
+            "Multicasting market data to {} (session {:016x}), snapshots on {}",
+            config.group, session, snapshot_addr
+        );
+        Ok(MulticastPublisher { socket: socket.into(), group: config.group, session, state, snapshot_addr })
+    }
+
+    /// Where the snapshot channel listens.
+    pub fn snapshot_addr(&self) -> SocketAddr {
+        self.snapshot_addr
+    }
+
+    pub fn publish(&self, topic: &str, envelope: &Envelope) {
+        let mut state = self.state.lock().unwrap();
+        let message = Sequenced { seq: state.seq + 1, topic: topic.to_string(), envelope: envelope.encode() };
+        let datagram = encode_datagram(self.session, &message);
+        if datagram.len() > MAX_DATAGRAM {
+            println!("  -> Dropping a {} byte message on '{}': too large for a datagram", datagram.len(), topic);
+            return;
+        }
+        // Sent under the lock, so a snapshot's seq never runs ahead of the wire.
+        if let Err(e) = self.socket.send_to(&datagram, self.group) {
+            println!("  -> Failed to multicast on '{}': {}", topic, e);
+        }
+        state.seq = message.seq;
+        state.latest.insert(message.topic.clone(), message);
+    }
+}
+
+/// Answers each connection to the snapshot channel with a snapshot.
+fn serve_snapshots(listener: TcpListener, session: u64, state: Arc<Mutex<PublisherState>>) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let snapshot = {
+            let state = state.lock().unwrap();
+            encode_snapshot(session, state.seq, &state.latest.values().cloned().collect::<Vec<_>>())
+        };
+        let _ = stream.write_all(&(snapshot.len() as u32).to_be_bytes()).and_then(|_| stream.write_all(&snapshot));
+    }
+}
+
+// --- Receiving ---
+
+/// What to do with a datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Deliver,
+    /// A duplicate, or older than what has been delivered.
+    Drop,
+    /// A gap, a new session or the first datagram: take a snapshot.
+    Recover,
+}
+
+/// Tracks a publisher's sequence.
+#[derive(Debug, Default)]
+pub struct Sequencer {
+    session: Option<u64>,
+    next: u64,
+    pub gaps: u64,
+    pub dropped: u64,
+}
+
+impl Sequencer {
+    pub fn on_datagram(&mut self, session: u64, seq: u64) -> Verdict {
+        if self.session != Some(session) {
+            return Verdict::Recover;
+        }
+        if seq < self.next {
+            self.dropped += 1;
+            return Verdict::Drop;
+        }
+        if seq > self.next {
+            self.gaps += 1;
+            return Verdict::Recover;
+        }
+        self.next += 1;
+        Verdict::Deliver
+    }
+
+    /// Continues the sequence after everything up to `seq`.
+    pub fn resync(&mut self, session: u64, seq: u64) {
+        self.session = Some(session);
+        self.next = seq + 1;
+    }
+}
+
+/// Market data counters of a receiver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReceiverStats {
+    pub received: u64,
+    pub gaps: u64,
+    pub dropped: u64,
+    pub snapshots: u64,
+    pub failed_snapshots: u64,
+}
+
+/// A member of the multicast group, delivering market data in order.
+pub struct MulticastReceiver {
+    socket: UdpSocket,
+    snapshot_addr: String,
+    snapshot_timeout: Duration,
+    sequencer: Sequencer,
+    ready: VecDeque<BusMessage>,
+    seq: u64,
+    stats: ReceiverStats,
+    buffer: Vec<u8>,
+}
+
+impl MulticastReceiver {
+    /// Joins the group; several receivers on one host can share it.
+    pub fn join(config: &MulticastConfig) -> io::Result<MulticastReceiver> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, config.group.port())).into())?;
+        if config.group.ip().is_multicast() {
+            socket.join_multicast_v4(config.group.ip(), &config.interface)?;
+        }
+        Ok(MulticastReceiver {
+            socket: socket.into(),
+            snapshot_addr: config.snapshot_addr.clone(),
+            snapshot_timeout: config.snapshot_timeout,
+            sequencer: Sequencer::default(),
+            ready: VecDeque::new(),
+            seq: 0,
+            stats: ReceiverStats::default(),
+            buffer: vec![0; MAX_DATAGRAM],
+        })
+    }
+
+    /// The next message, blocking until there is one.
+    pub fn recv(&mut self) -> io::Result<BusMessage> {
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Ok(message);
+            }
+            let len = self.socket.recv(&mut self.buffer)?;
+            let Some((session, message)) = decode_datagram(&self.buffer[..len]) else {
+                continue;
+            };
+            self.stats.received += 1;
+            match self.sequencer.on_datagram(session, message.seq) {
+                Verdict::Deliver => self.deliver(message),
+                Verdict::Drop => {}
+                Verdict::Recover => {
+                    self.recover(session, message.seq);
+                    // The snapshot covers the datagram unless it was taken
+                    // before the datagram was sent, or not at all.
+                    if self.sequencer.on_datagram(session, message.seq) != Verdict::Drop {
+                        self.sequencer.resync(session, message.seq);
+                        self.deliver(message);
+                    }
+                }
+            }
+            self.stats.gaps = self.sequencer.gaps;
+            self.stats.dropped = self.sequencer.dropped;
+        }
+    }
+
+    pub fn stats(&self) -> ReceiverStats {
+        self.stats
+    }
+
+    fn deliver(&mut self, message: Sequenced) {
+        self.seq += 1;
+        self.ready.push_back(BusMessage { seq: self.seq, topic: message.topic, payload: message.envelope });
+    }
+
+    /// Takes a snapshot and queues its messages; on failure, carries on from
+    /// the datagram that needed it.
+    fn recover(&mut self, session: u64, seq: u64) {
+        match self.fetch_snapshot() {
+            Ok((snapshot_session, snapshot_seq, messages)) if snapshot_session == session => {
+                self.stats.snapshots += 1;
+                self.sequencer.resync(session, snapshot_seq);
+                for message in messages {
+                    self.deliver(message);
+                }
+            }
+            Ok(_) => {
+                self.stats.failed_snapshots += 1;
+                println!("  -> Market data snapshot is from another session; resuming at seq {}", seq);
+            }
+            Err(e) => {
+                self.stats.failed_snapshots += 1;
+                println!(
+                    "  -> Market data snapshot from {} failed, resuming at seq {}: {}",
+                    self.snapshot_addr, seq, e
+                );
+            }
+        }
+    }
+
+    fn fetch_snapshot(&self) -> io::Result<(u64, u64, Vec<Sequenced>)> {
+        let addr = self
+            .snapshot_addr
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "bad snapshot address"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.snapshot_timeout)?;
+        stream.set_read_timeout(Some(self.snapshot_timeout))?;
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let mut snapshot = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut snapshot)?;
+        decode_snapshot(&snapshot).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed snapshot"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_joiners_and_gaps_recover_from_a_snapshot() {
+        let message = |seq, topic: &str| Sequenced { seq, topic: topic.to_string(), envelope: vec![seq as u8] };
+        let datagram = encode_datagram(7, &message(42, "market_data.instrument.1"));
+        assert_eq!(decode_datagram(&datagram), Some((7, message(42, "market_data.instrument.1"))));
+        assert_eq!(decode_datagram(b"QAMX"), None);
+        let latest = vec![message(40, "market_data.instrument.1"), message(42, "market_data.instrument.2")];
+        assert_eq!(decode_snapshot(&encode_snapshot(7, 42, &latest)), Some((7, 42, latest)));
+
+        // A late joiner's first datagram asks for a snapshot; the datagrams
+        // the snapshot covers are dropped and the next one delivered.
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.on_datagram(7, 41), Verdict::Recover);
+        sequencer.resync(7, 42);
+        assert_eq!(sequencer.on_datagram(7, 41), Verdict::Drop);
+        assert_eq!(sequencer.on_datagram(7, 42), Verdict::Drop);
+        assert_eq!(sequencer.on_datagram(7, 43), Verdict::Deliver);
+        // A missed datagram is a gap; a restarted publisher a new session.
+        assert_eq!(sequencer.on_datagram(7, 45), Verdict::Recover);
+        sequencer.resync(7, 45);
+        assert_eq!(sequencer.on_datagram(7, 46), Verdict::Deliver);
+        assert_eq!(sequencer.on_datagram(8, 1), Verdict::Recover);
+        assert_eq!((sequencer.gaps, sequencer.dropped), (1, 2));
+    }
+}
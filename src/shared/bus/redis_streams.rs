@@ -0,0 +1,381 @@
+/*
+ * QuantumArb 2.0 - Shared: Redis Streams Transport
+ *
+ * File: src/shared/bus/redis_streams.rs
+ *
+ * Description:
+ * Carries the bus over Redis Streams (QA_BUS_TRANSPORT=redis), so a
+ * single-box deployment needs no broker besides the Redis the risk gateway
+ * and the feature store already use.
+ *
+ * Each carrier (`subjects.rs`) is one stream, `<prefix><carrier>`: a
+ * per-instrument family shares a stream, any other topic has its own. A
+ * publish is an XADD of three fields (the platform topic, the envelope's
+ * message id and the encoded envelope), trimmed to about
+ * QA_REDIS_STREAM_MAXLEN entries. Publishing blocks on the round trip to
+ * Redis, which on one box is tens of microseconds.
+ *
+ * A subscriber reads as one consumer of a consumer group (QA_REDIS_GROUP,
+ * by default the service's name) with XREADGROUP, so the group's members
+ * share each stream's entries, and acks with XACK. Delivery is at least
+ * once: an entry stays in the group's pending list until acked.
+ *
+ * - On start a consumer first rereads its own pending entries, those it
+ *   received before a restart and never acked (its name, QA_REDIS_CONSUMER,
+ *   must then outlive the process: the pod's HOSTNAME by default).
+ * - Every QA_REDIS_CLAIM_IDLE_MS it claims (XCLAIM) the entries that have
+ *   been pending that long, whoever holds them, so the entries of a member
+ *   that died are handled by the others.
+ * - An entry delivered QA_REDIS_MAX_DELIVERIES times without an ack is
+ *   moved to the `<prefix>dead_letter` stream instead, so one message that
+ *   crashes its handler does not come back forever.
+ *
+ * A group created on a stream starts at its first entry. Streams behind a
+ * wildcard subscription outside an instrument family (`alerts.>`) are found
+ * by SCAN, again every few seconds for streams created since. Entries whose
+ * topic the subscription does not match are acked and skipped.
+ *
+ * Configuration (environment):
+ *   QA_BUS_REDIS_URL             by default QA_REDIS_URL, else
+ *                                redis://127.0.0.1/
+ *   QA_REDIS_STREAM_PREFIX=qa:bus:
+ *   QA_REDIS_STREAM_MAXLEN=100000
+ *   QA_REDIS_GROUP               by default the service's name
+ *   QA_REDIS_CONSUMER            by default HOSTNAME, else the process id
+ *   QA_REDIS_CLAIM_IDLE_MS=30000
+ *   QA_REDIS_MAX_DELIVERIES=10
+ */
+
+use redis::aio::MultiplexedConnection;
+use redis::streams::{
+    StreamClaimReply, StreamId, StreamMaxlen, StreamPendingCountReply, StreamReadOptions, StreamReadReply,
+};
+use redis::{AsyncCommands, Commands, RedisResult};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::subjects::{self, carrier, subject_matches};
+use crate::transport::Transport;
+use crate::{BusMessage, Envelope};
+
+const SUBJECT_FIELD: &str = "subject";
+const MESSAGE_ID_FIELD: &str = "message_id";
+const ENVELOPE_FIELD: &str = "envelope";
+/// Entries read, or pending entries claimed, per stream at a time.
+const BATCH: usize = 100;
+/// How long a read waits for new entries.
+const BLOCK_MS: usize = 1_000;
+/// How often wildcard subscriptions look for new streams.
+const DISCOVER_EVERY: Duration = Duration::from_secs(5);
+
+// --- Configuration ---
+
+#[derive(Debug, Clone)]
+pub struct RedisStreamsConfig {
+    pub url: String,
+    pub prefix: String,
+    pub max_len: usize,
+    pub group: String,
+    pub consumer: String,
+    pub claim_idle_ms: usize,
+    pub max_deliveries: usize,
+}
+
+impl RedisStreamsConfig {
+    pub fn from_env() -> RedisStreamsConfig {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name).ok()
+        }
+        let service = crate::envelope::source_service();
+        RedisStreamsConfig {
+            url: var("QA_BUS_REDIS_URL")
+                .or_else(|| var("QA_REDIS_URL"))
+                .unwrap_or_else(|| "redis://127.0.0.1/".to_string()),
+            prefix: var("QA_REDIS_STREAM_PREFIX").unwrap_or_else(|| "qa:bus:".to_string()),
+            max_len: var("QA_REDIS_STREAM_MAXLEN").and_then(|v| v.parse().ok()).unwrap_or(100_000),
+            group: var("QA_REDIS_GROUP").unwrap_or_else(|| service.to_string()),
+            consumer: var("QA_REDIS_CONSUMER")
+                .or_else(|| var("HOSTNAME"))
+                .unwrap_or_else(|| format!("{}-{}", service, std::process::id())),
+            claim_idle_ms: var("QA_REDIS_CLAIM_IDLE_MS").and_then(|v| v.parse().ok()).unwrap_or(30_000),
+            max_deliveries: var("QA_REDIS_MAX_DELIVERIES").and_then(|v| v.parse().ok()).unwrap_or(10).max(1),
+        }
+    }
+
+    /// The stream a platform topic is carried on.
+    pub fn stream(&self, topic: &str) -> String {
+        format!("{}{}", self.prefix, carrier(topic).0)
+    }
+
+    /// The SCAN pattern for the streams a wildcard subject may be carried
+    /// on; every match still has to pass `subject_matches`.
+    pub fn discovery_pattern(&self, subject: &str) -> String {
+        let tokens: Vec<&str> = subject.split('.').map(|token| if token == ">" { "*" } else { token }).collect();
+        format!("{}{}", self.prefix, tokens.join("."))
+    }
+
+    fn dead_letter(&self) -> String {
+        format!("{}dead_letter", self.prefix)
+    }
+}
+
+// --- Publishing ---
+
+/// Publishes with XADD over one connection, reopened after an error.
+pub struct RedisStreamsTransport {
+    client: redis::Client,
+    connection: Mutex<Option<redis::Connection>>,
+    config: RedisStreamsConfig,
+}
+
+impl RedisStreamsTransport {
+    pub fn connect(config: &RedisStreamsConfig) -> RedisResult<RedisStreamsTransport> {
+        let client = redis::Client::open(config.url.as_str())?;
+        println!("Publishing to Redis Streams at {} under '{}'", config.url, config.prefix);
+        Ok(RedisStreamsTransport { client, connection: Mutex::new(None), config: config.clone() })
+    }
+}
+
+impl Transport for RedisStreamsTransport {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn publish(&self, topic: &str, envelope: &Envelope) {
+        let stream = self.config.stream(topic);
+        let fields: [(&str, Vec<u8>); 3] = [
+            (SUBJECT_FIELD, topic.as_bytes().to_vec()),
+            (MESSAGE_ID_FIELD, envelope.message_id.as_bytes().to_vec()),
+            (ENVELOPE_FIELD, envelope.encode()),
+        ];
+        let mut connection = self.connection.lock().unwrap();
+        // One retry on a fresh connection, for a connection Redis has dropped.
+        for attempt in 0..2 {
+            let sent = connection.take().map_or_else(|| self.client.get_connection(), Ok).and_then(|mut open| {
+                let max_len = StreamMaxlen::Approx(self.config.max_len);
+                open.xadd_maxlen::<_, _, _, _, String>(&stream, max_len, "*", &fields)?;
+                Ok(open)
+            });
+            match sent {
+                Ok(open) => {
+                    *connection = Some(open);
+                    return;
+                }
+                Err(e) if attempt == 1 => println!("  -> Failed to publish to Redis stream '{}': {}", stream, e),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+// --- Subscribing ---
+
+/// An entry received from a stream, to be acked once handled.
+pub struct Delivery {
+    pub message: BusMessage,
+    /// The envelope's message id, to recognise a redelivery.
+    pub message_id: Option<String>,
+    /// How many times the group has delivered the entry, this time included.
+    pub deliveries: usize,
+    stream: String,
+    entry_id: String,
+}
+
+/// A consumer group member subscribed to platform subjects.
+pub struct RedisSubscriber {
+    config: RedisStreamsConfig,
+    /// Blocks in XREADGROUP; everything else goes on `control`, so acks
+    /// never wait behind a read.
+    reader: MultiplexedConnection,
+    control: MultiplexedConnection,
+    subjects: Vec<String>,
+    streams: Vec<String>,
+    discovered: Instant,
+    claimed: Instant,
+    /// Whether this consumer's own pending entries have been reread.
+    resumed: bool,
+    ready: VecDeque<Delivery>,
+    seq: u64,
+}
+
+impl RedisSubscriber {
+    /// Joins the configured consumer group on the streams carrying `subjects`.
+    pub async fn subscribe(config: &RedisStreamsConfig, subjects: &[&str]) -> RedisResult<RedisSubscriber> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let mut subscriber = RedisSubscriber {
+            config: config.clone(),
+            reader: client.get_multiplexed_tokio_connection().await?,
+            control: client.get_multiplexed_tokio_connection().await?,
+            subjects: subjects.iter().map(|subject| subject.to_string()).collect(),
+            streams: Vec::new(),
+            discovered: Instant::now(),
+            claimed: Instant::now(),
+            resumed: false,
+            ready: VecDeque::new(),
+            seq: 0,
+        };
+        subscriber.discover().await?;
+        println!(
+            "Redis group '{}' (consumer '{}') subscribed to {:?}",
+            config.group, config.consumer, subscriber.streams
+        );
+        Ok(subscriber)
+    }
+
+    /// The next entry on a subscribed subject. Until it is acked, it is
+    /// delivered again: to this consumer after a restart, or to whichever
+    /// member claims it once it has been pending QA_REDIS_CLAIM_IDLE_MS.
+    pub async fn recv(&mut self) -> RedisResult<Delivery> {
+        loop {
+            if let Some(delivery) = self.ready.pop_front() {
+                return Ok(delivery);
+            }
+            if self.discovered.elapsed() >= DISCOVER_EVERY {
+                self.discover().await?;
+            }
+            if self.streams.is_empty() {
+                tokio::time::sleep(Duration::from_millis(BLOCK_MS as u64)).await;
+                continue;
+            }
+            if !self.resumed {
+                self.resumed = true;
+                self.read("0", None).await?;
+            } else if self.claimed.elapsed() >= Duration::from_millis(self.config.claim_idle_ms as u64) {
+                self.claimed = Instant::now();
+                self.claim().await?;
+            } else {
+                self.read(">", Some(BLOCK_MS)).await?;
+            }
+        }
+    }
+
+    /// Marks a delivery handled.
+    pub async fn ack(&mut self, delivery: &Delivery) -> RedisResult<()> {
+        self.control.xack(&delivery.stream, &self.config.group, &[&delivery.entry_id]).await
+    }
+
+    /// Finds the streams of the subscription, creating the group on each.
+    async fn discover(&mut self) -> RedisResult<()> {
+        self.discovered = Instant::now();
+        let mut streams = Vec::new();
+        for subject in &self.subjects {
+            if !subjects::needs_discovery(subject) {
+                streams.push(self.config.stream(subject));
+                continue;
+            }
+            let mut found: Vec<String> = Vec::new();
+            let mut keys = self.control.scan_match::<_, String>(self.config.discovery_pattern(subject)).await?;
+            while let Some(key) = keys.next_item().await {
+                found.push(key);
+            }
+            streams.extend(found.into_iter().filter(|key| {
+                key.strip_prefix(&self.config.prefix).is_some_and(|carrier| subject_matches(subject, carrier))
+            }));
+        }
+        streams.sort();
+        streams.dedup();
+        for stream in streams.iter().filter(|stream| !self.streams.contains(stream)) {
+            let created: RedisResult<()> = self.control.xgroup_create_mkstream(stream, &self.config.group, "0").await;
+            match created {
+                Err(e) if e.code() != Some("BUSYGROUP") => return Err(e),
+                _ => {}
+            }
+        }
+        self.streams = streams;
+        Ok(())
+    }
+
+    /// Reads entries after `id` on every stream: "0" for this consumer's own
+    /// pending entries, ">" for new ones.
+    async fn read(&mut self, id: &str, block_ms: Option<usize>) -> RedisResult<()> {
+        let mut options = StreamReadOptions::default().group(&self.config.group, &self.config.consumer).count(BATCH);
+        if let Some(block_ms) = block_ms {
+            options = options.block(block_ms);
+        }
+        let ids = vec![id; self.streams.len()];
+        let reply: Option<StreamReadReply> = self.reader.xread_options(&self.streams, &ids, &options).await?;
+        for key in reply.map(|reply| reply.keys).unwrap_or_default() {
+            for entry in key.ids {
+                // A reread pending entry has been delivered before.
+                let deliveries = if id == "0" { 2 } else { 1 };
+                self.accept(&key.key, entry, deliveries).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Claims the entries pending longer than the claim timeout, and moves
+    /// those delivered too often to the dead-letter stream.
+    async fn claim(&mut self) -> RedisResult<()> {
+        let group = self.config.group.clone();
+        for stream in self.streams.clone() {
+            let pending: StreamPendingCountReply =
+                self.control.xpending_count(&stream, &group, "-", "+", BATCH).await?;
+            let idle: Vec<_> =
+                pending.ids.into_iter().filter(|p| p.last_delivered_ms >= self.config.claim_idle_ms).collect();
+            if idle.is_empty() {
+                continue;
+            }
+            let ids: Vec<&str> = idle.iter().map(|p| p.id.as_str()).collect();
+            let claimed: StreamClaimReply = self
+                .control
+                .xclaim(&stream, &group, &self.config.consumer, self.config.claim_idle_ms, &ids)
+                .await?;
+            for entry in claimed.ids {
+                let previous = idle.iter().find(|p| p.id == entry.id).map_or(1, |p| p.times_delivered);
+                self.accept(&stream, entry, previous + 1).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues an entry for delivery, unless its subject is not subscribed
+    /// or it has been delivered too often.
+    async fn accept(&mut self, stream: &str, entry: StreamId, deliveries: usize) -> RedisResult<()> {
+        let subject: String = entry.get(SUBJECT_FIELD).unwrap_or_default();
+        let group = self.config.group.clone();
+        if !self.subjects.iter().any(|pattern| subject_matches(pattern, &subject)) {
+            return self.control.xack(stream, &group, &[&entry.id]).await;
+        }
+        let payload: Vec<u8> = entry.get(ENVELOPE_FIELD).unwrap_or_default();
+        if deliveries > self.config.max_deliveries {
+            println!(
+                "  -> Redis entry {} on '{}' was delivered {} times without an ack; moving it to the dead letters",
+                entry.id,
+                subject,
+                deliveries - 1
+            );
+            let fields =
+                [("stream", stream.as_bytes()), (SUBJECT_FIELD, subject.as_bytes()), (ENVELOPE_FIELD, &payload)];
+            let _: String = self.control.xadd(self.config.dead_letter(), "*", &fields).await?;
+            return self.control.xack(stream, &group, &[&entry.id]).await;
+        }
+        self.seq += 1;
+        self.ready.push_back(Delivery {
+            message: BusMessage { seq: self.seq, topic: subject, payload },
+            message_id: entry.get(MESSAGE_ID_FIELD),
+            deliveries,
+            stream: stream.to_string(),
+            entry_id: entry.id,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics_map_to_prefixed_streams_and_wildcards_to_scan_patterns() {
+        let mut config = RedisStreamsConfig::from_env();
+        config.prefix = "qa:bus:".to_string();
+        assert_eq!(config.stream("market_data.instrument.7"), "qa:bus:market_data.instrument");
+        assert_eq!(config.stream("market_data.instrument.*"), "qa:bus:market_data.instrument");
+        assert_eq!(config.stream("execution_reports"), "qa:bus:execution_reports");
+        assert_eq!(config.discovery_pattern("alerts.>"), "qa:bus:alerts.*");
+        assert_eq!(config.discovery_pattern("portfolio.*.pnl"), "qa:bus:portfolio.*.pnl");
+        assert_eq!(config.dead_letter(), "qa:bus:dead_letter");
+    }
+}
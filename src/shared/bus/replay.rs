@@ -0,0 +1,304 @@
+/*
+ * QuantumArb 2.0 - Shared: Persistent Subscriptions
+ *
+ * File: src/shared/bus/replay.rs
+ *
+ * Description:
+ * Lets a consumer resume a replayable topic where it left off instead of
+ * wherever the bus happens to be when it starts. A consumer that restarts
+ * (the portfolio manager after a deploy) would otherwise miss every fill
+ * published while it was down.
+ *
+ * The consumer keeps a checkpoint: the time of the last message it
+ * acknowledged, once that message's effects were durable, and the ids of
+ * the last RECENT_IDS messages acknowledged. On start it reads the topic's
+ * history from the checkpoint (less QA_REPLAY_GRACE_SECS, so messages
+ * stamped a little out of order are not missed), then goes live:
+ *
+ *   archive   history comes from the archiver's files (`quantumarb-archive`),
+ *             read from the checkpoint up to the moment catch-up started
+ *   kafka     the consumer group's committed offsets are the position; the
+ *             group's subscriber resumes from them by itself, so there is no
+ *             separate history to read
+ *
+ * While history is read, live messages are held back and delivered after
+ * it, so the consumer sees one stream in publish order. History and live
+ * traffic overlap (the grace period, messages archived and live at once),
+ * and `CatchUp` delivers each message id once, the checkpoint's recent ids
+ * included; a consumer still dedupes what it books, as a redelivery can
+ * outlive RECENT_IDS.
+ *
+ * Checkpoints are one JSON file per consumer, replaced atomically:
+ *
+ *   <QA_REPLAY_DIR>/<consumer>.checkpoint.json
+ *
+ * Configuration (environment):
+ *   QA_REPLAY_SOURCE        archive | kafka; kafka when QA_BUS_TRANSPORT is
+ *                           kafka, archive otherwise
+ *   QA_REPLAY_DIR=.         where checkpoints are kept
+ *   QA_REPLAY_GRACE_SECS=5
+ */
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::topics;
+
+/// Topics a consumer can resume: the order flow, which books depend on.
+pub const REPLAYABLE: [&str; 2] = [topics::ORDER_REQUESTS, topics::EXECUTION_REPORTS];
+/// Message ids a checkpoint remembers.
+pub const RECENT_IDS: usize = 1024;
+
+pub fn is_replayable(topic: &str) -> bool {
+    REPLAYABLE.contains(&topic)
+}
+
+// --- Configuration ---
+
+/// Where a consumer's history comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySource {
+    Archive,
+    Kafka,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    pub source: ReplaySource,
+    pub dir: PathBuf,
+    pub grace: Duration,
+}
+
+impl ReplayConfig {
+    pub fn from_env() -> ReplayConfig {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        let kafka = std::env::var("QA_BUS_TRANSPORT").as_deref() == Ok("kafka");
+        let source = match std::env::var("QA_REPLAY_SOURCE").as_deref() {
+            Ok("archive") => ReplaySource::Archive,
+            Ok("kafka") => ReplaySource::Kafka,
+            Ok(other) => panic!("QA_REPLAY_SOURCE={}: expected 'archive' or 'kafka'", other),
+            Err(_) if kafka => ReplaySource::Kafka,
+            Err(_) => ReplaySource::Archive,
+        };
+        ReplayConfig {
+            source,
+            dir: PathBuf::from(var("QA_REPLAY_DIR", ".".to_string())),
+            grace: Duration::from_secs(var("QA_REPLAY_GRACE_SECS", 5)),
+        }
+    }
+}
+
+// --- Checkpoints ---
+
+/// How far a consumer has acknowledged its replayable topics.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub consumer: String,
+    /// Time of the last message acknowledged, in nanoseconds since the Unix
+    /// epoch; None before the first.
+    pub acked_ns: Option<i64>,
+    /// Ids of the last RECENT_IDS messages acknowledged, oldest first.
+    pub recent: VecDeque<String>,
+}
+
+/// A consumer's checkpoint and its file.
+#[derive(Debug)]
+pub struct CheckpointStore {
+    path: PathBuf,
+    grace: Duration,
+    checkpoint: Checkpoint,
+}
+
+impl CheckpointStore {
+    /// Loads `consumer`'s checkpoint; a consumer without one starts empty.
+    pub fn open(config: &ReplayConfig, consumer: &str) -> io::Result<CheckpointStore> {
+        let path = config.dir.join(format!("{}.checkpoint.json", consumer));
+        let checkpoint = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Checkpoint { consumer: consumer.to_string(), ..Checkpoint::default() }
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(CheckpointStore { path, grace: config.grace, checkpoint })
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    /// Where history should be read from: the checkpoint less the grace
+    /// period. None if nothing was ever acknowledged.
+    pub fn resume_from_ns(&self) -> Option<i64> {
+        let grace = i64::try_from(self.grace.as_nanos()).unwrap_or(i64::MAX);
+        self.checkpoint.acked_ns.map(|acked| acked.saturating_sub(grace))
+    }
+
+    /// Records a message as handled and writes the checkpoint. Acknowledge
+    /// only once the message's effects are durable.
+    pub fn ack(&mut self, message_id: Option<&str>, time_ns: i64) -> io::Result<()> {
+        let checkpoint = &mut self.checkpoint;
+        checkpoint.acked_ns = Some(checkpoint.acked_ns.map_or(time_ns, |acked| acked.max(time_ns)));
+        if let Some(id) = message_id {
+            checkpoint.recent.push_back(id.to_string());
+            while checkpoint.recent.len() > RECENT_IDS {
+                checkpoint.recent.pop_front();
+            }
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&self.checkpoint).map_err(io::Error::other)?)?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+// --- Catching up ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    CatchingUp,
+    Live,
+}
+
+/// Counters of a catch-up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CatchUpStats {
+    /// History delivered.
+    pub replayed: u64,
+    /// Live messages held back while history was read.
+    pub held: u64,
+    /// Messages not delivered because their id already was.
+    pub duplicates: u64,
+}
+
+/// Merges a consumer's history with its live messages: history first, then
+/// the live messages held meanwhile, each message id once.
+#[derive(Debug)]
+pub struct CatchUp<T> {
+    phase: Phase,
+    seen: HashSet<String>,
+    order: VecDeque<String>,
+    held: Vec<(Option<String>, T)>,
+    stats: CatchUpStats,
+}
+
+impl<T> CatchUp<T> {
+    /// Starts catching up from `checkpoint`, whose recent ids count as
+    /// delivered.
+    pub fn new(checkpoint: &Checkpoint) -> CatchUp<T> {
+        let mut catch_up = CatchUp {
+            phase: Phase::CatchingUp,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            held: Vec::new(),
+            stats: CatchUpStats::default(),
+        };
+        for id in &checkpoint.recent {
+            catch_up.first_sight(Some(id));
+        }
+        catch_up
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn stats(&self) -> CatchUpStats {
+        self.stats
+    }
+
+    /// A message read from history: returned unless it was delivered before.
+    pub fn replayed(&mut self, message_id: Option<&str>, message: T) -> Option<T> {
+        if !self.first_sight(message_id) {
+            self.stats.duplicates += 1;
+            return None;
+        }
+        self.stats.replayed += 1;
+        Some(message)
+    }
+
+    /// A live message: held while catching up, returned once live unless it
+    /// was delivered before.
+    pub fn live(&mut self, message_id: Option<&str>, message: T) -> Option<T> {
+        if self.phase == Phase::CatchingUp {
+            self.stats.held += 1;
+            self.held.push((message_id.map(str::to_string), message));
+            return None;
+        }
+        if !self.first_sight(message_id) {
+            self.stats.duplicates += 1;
+            return None;
+        }
+        Some(message)
+    }
+
+    /// Ends the history; returns the live messages held meanwhile that it
+    /// did not already hold.
+    pub fn go_live(&mut self) -> Vec<T> {
+        self.phase = Phase::Live;
+        let held = std::mem::take(&mut self.held);
+        held.into_iter().filter_map(|(id, message)| self.live(id.as_deref(), message)).collect()
+    }
+
+    /// Whether `message_id` is new, remembering it; messages without an id
+    /// always are.
+    fn first_sight(&mut self, message_id: Option<&str>) -> bool {
+        let Some(id) = message_id else {
+            return true;
+        };
+        if !self.seen.insert(id.to_string()) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        if self.order.len() > RECENT_IDS * 4 {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_comes_before_held_live_messages_and_each_id_is_delivered_once() {
+        let dir = std::env::temp_dir().join(format!("qa-replay-{}", uuid::Uuid::new_v4()));
+        let config = ReplayConfig { source: ReplaySource::Archive, dir: dir.clone(), grace: Duration::from_secs(5) };
+        let mut store = CheckpointStore::open(&config, "portfolio_manager").unwrap();
+        assert_eq!(store.resume_from_ns(), None);
+        store.ack(Some("fill-1"), 10_000_000_000).unwrap();
+        store.ack(Some("fill-2"), 9_000_000_000).unwrap();
+        let store = CheckpointStore::open(&config, "portfolio_manager").unwrap();
+        assert_eq!(store.resume_from_ns(), Some(5_000_000_000));
+        assert_eq!(store.checkpoint().recent, ["fill-1", "fill-2"]);
+
+        let mut catch_up = CatchUp::new(store.checkpoint());
+        assert_eq!(catch_up.live(Some("fill-4"), 4), None);
+        assert_eq!(catch_up.replayed(Some("fill-2"), 2), None);
+        assert_eq!(catch_up.replayed(Some("fill-3"), 3), Some(3));
+        assert_eq!(catch_up.replayed(Some("fill-4"), 4), Some(4));
+        assert_eq!(catch_up.live(Some("fill-5"), 5), None);
+        assert_eq!(catch_up.phase(), Phase::CatchingUp);
+        assert_eq!(catch_up.go_live(), [5]);
+        assert_eq!(catch_up.live(Some("fill-5"), 5), None);
+        assert_eq!(catch_up.live(None, 6), Some(6));
+        assert_eq!(catch_up.stats(), CatchUpStats { replayed: 2, held: 2, duplicates: 3 });
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * QuantumArb 2.0 - Shared: Subject Mapping
+ *
+ * File: src/shared/bus/subjects.rs
+ *
+ * Description:
+ * How platform topics, which are NATS subjects, map onto brokers without a
+ * subject hierarchy (Kafka, Redis Streams). A per-instrument topic family
+ * (market data, conflated and consolidated market data, signals) travels
+ * as one broker topic or stream named after its prefix, with the
+ * instrument as its key, so a broker does not need one topic per
+ * instrument. Every other platform topic is carried under its own name:
+ *
+ *   market_data.instrument.7   ->  market_data.instrument   instrument 7
+ *   orders.requests            ->  orders.requests
+ *
+ * The platform topic travels with each message, and subscribers skip the
+ * messages of a carrier whose subject they did not subscribe to. Subjects
+ * follow NATS: '*' matches one token, '>' every token after it.
+ */
+
+use crate::topics;
+
+/// Topic families carried as one broker topic keyed by instrument.
+const INSTRUMENT_FAMILIES: [&str; 4] = [
+    topics::MARKET_DATA_PREFIX,
+    topics::CONFLATED_MARKET_DATA_PREFIX,
+    topics::CONSOLIDATED_MARKET_DATA_PREFIX,
+    topics::SIGNALS_PREFIX,
+];
+
+/// The broker topic a platform topic (or subject pattern) is carried on,
+/// with the instrument of a per-instrument topic.
+pub fn carrier(topic: &str) -> (&str, Option<&str>) {
+    for family in INSTRUMENT_FAMILIES {
+        if let Some(instrument) = topic.strip_prefix(family) {
+            return (family.trim_end_matches('.'), Some(instrument));
+        }
+    }
+    (topic, None)
+}
+
+/// Whether a subject pattern has wildcards outside an instrument family's
+/// instrument, so its carriers have to be discovered.
+pub fn needs_discovery(pattern: &str) -> bool {
+    let (carrier, instrument) = carrier(pattern);
+    instrument.is_none() && carrier.split('.').any(|token| token == "*" || token == ">")
+}
+
+/// Whether `subject` matches a NATS subject pattern.
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for token in pattern.split('.') {
+        match (token, subject_tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (literal, Some(actual)) if literal == actual => {}
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instrument_families_share_a_carrier_and_subjects_match_like_nats() {
+        assert_eq!(carrier("market_data.instrument.7"), ("market_data.instrument", Some("7")));
+        assert_eq!(carrier("signals.instrument.*"), ("signals.instrument", Some("*")));
+        assert_eq!(carrier("orders.requests"), ("orders.requests", None));
+        assert!(!needs_discovery("market_data.instrument.*"));
+        assert!(needs_discovery("alerts.>"));
+        assert!(!needs_discovery("orders.requests"));
+
+        assert!(subject_matches("market_data.instrument.*", "market_data.instrument.7"));
+        assert!(!subject_matches("market_data.instrument.7", "market_data.instrument.8"));
+        assert!(subject_matches("alerts.>", "alerts.clock_skew"));
+        assert!(!subject_matches("alerts.>", "alerts"));
+        assert!(!subject_matches("orders.*", "orders.requests.extra"));
+    }
+
+    #[test]
+    fn desk_topics_are_namespaced_and_matched_by_firm_wide_patterns() {
+        let topic = topics::for_desk(Some("crypto"), topics::ACCOUNT_PNL);
+        assert_eq!(topic, "desk.crypto.portfolio.account_pnl");
+        assert_eq!(topics::split_desk(&topic), (Some("crypto"), topics::ACCOUNT_PNL));
+        assert_eq!(topics::split_desk(topics::ACCOUNT_PNL), (None, topics::ACCOUNT_PNL));
+        assert_eq!(topics::for_desk(None, topics::HEARTBEATS), topics::HEARTBEATS);
+        assert!(subject_matches(&topics::all_desks(topics::ACCOUNT_PNL), &topic));
+        assert!(!subject_matches(&topics::all_desks(topics::HEARTBEATS), &topic));
+        assert_eq!(carrier(&topic), (topic.as_str(), None));
+    }
+}
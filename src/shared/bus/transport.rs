@@ -0,0 +1,95 @@
+/*
+ * QuantumArb 2.0 - Shared: Bus Transports
+ *
+ * File: src/shared/bus/transport.rs
+ *
+ * Description:
+ * What carries a published message to the bus. A process picks its
+ * transport once, from QA_BUS_TRANSPORT, the first time it publishes:
+ *
+ *   nats    NATS JetStream (the default). Until the services hold a real
+ *           connection it logs the message it would send.
+ *   kafka   Kafka, for deployments that already run it (see `kafka.rs`);
+ *           only in builds with this crate's `kafka` feature
+ *   redis   Redis Streams, for a single box with no broker besides Redis
+ *           (see `redis_streams.rs`)
+ *
+ * Every transport is given the enveloped message and the platform topic
+ * (the NATS subject); how it maps topics, keys and headers onto its broker
+ * is its own business, so services publish the same way on any of them.
+ * Market data bypasses the transport when multicast is configured (see
+ * `multicast.rs`).
+ */
+
+use std::sync::OnceLock;
+
+use crate::Envelope;
+
+/// A message bus backend.
+pub trait Transport: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Sends an enveloped message on `topic`. A send that fails after it has
+    /// been accepted is reported by the transport itself.
+    fn publish(&self, topic: &str, envelope: &Envelope);
+}
+
+/// The process's transport, chosen on first use.
+pub fn transport() -> &'static dyn Transport {
+    static TRANSPORT: OnceLock<Box<dyn Transport>> = OnceLock::new();
+    TRANSPORT.get_or_init(from_env).as_ref()
+}
+
+fn from_env() -> Box<dyn Transport> {
+    match std::env::var("QA_BUS_TRANSPORT").as_deref() {
+        Ok("kafka") => kafka(),
+        Ok("redis") => {
+            let config = crate::redis_streams::RedisStreamsConfig::from_env();
+            match crate::redis_streams::RedisStreamsTransport::connect(&config) {
+                Ok(transport) => Box::new(transport),
+                Err(e) => panic!("Invalid Redis URL {} for the bus: {}", config.url, e),
+            }
+        }
+        Ok("nats") | Err(_) => Box::new(NatsTransport),
+        Ok(other) => panic!("QA_BUS_TRANSPORT={}: expected 'nats', 'kafka' or 'redis'", other),
+    }
+}
+
+#[cfg(feature = "kafka")]
+fn kafka() -> Box<dyn Transport> {
+    let config = crate::kafka::KafkaConfig::from_env();
+    match crate::kafka::KafkaTransport::connect(&config) {
+        Ok(transport) => Box::new(transport),
+        Err(e) => panic!("Could not create the Kafka producer for {}: {}", config.brokers, e),
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka() -> Box<dyn Transport> {
+    panic!("QA_BUS_TRANSPORT=kafka, but this build has no Kafka transport: it needs the bus's `kafka` feature.");
+}
+
+// --- NATS ---
+
+/// NATS JetStream, logging each message until there is a connection.
+pub struct NatsTransport;
+
+impl Transport for NatsTransport {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn publish(&self, topic: &str, envelope: &Envelope) {
+        let id = match &envelope.correlation_id {
+            Some(correlation_id) => format!("{}, correlation {}", envelope.message_id, correlation_id),
+            None => envelope.message_id.clone(),
+        };
+        match std::str::from_utf8(&envelope.payload) {
+            Ok(text) => println!("  -> Publishing to topic '{}' [{}]: {}", topic, id, text),
+            Err(_) => println!("  -> Publishing to topic '{}' [{}]: {} bytes", topic, id, envelope.payload.len()),
+        }
+        // In a real system:
+        // let headers = HeaderMap::from_iter([("Nats-Msg-Id", envelope.message_id.as_str())]);
+        // jetstream.publish_with_headers(topic, headers, envelope.encode().into()).await.unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+/*
+ * QuantumArb 2.0 - Shared: Corporate Actions
+ *
+ * File: src/shared/corporate_actions/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-corporate-actions`) defines the normalized
+ * corporate-action event published by the data bus connector on the
+ * 'reference.corporate_actions' topic, and the arithmetic every consumer
+ * needs to apply it consistently:
+ *
+ * - Split { new_shares, old_shares }: a 4-for-1 split is new 4 / old 1, a
+ *   1-for-10 reverse split is new 1 / old 10. Quantities scale by
+ *   new/old and prices by old/new, so notional is unchanged.
+ * - CashDividend: paid per share held at the ex-date, on the pay date.
+ * - SymbolChange: the instrument keeps its id but trades under a new ticker.
+ *
+ * The portfolio manager adjusts live positions; the market replay service
+ * back-adjusts historical prices so backtests don't see splits as price jumps.
+ *
+ * Events can also be loaded from a JSON-lines file (QA_CORPORATE_ACTIONS_PATH),
+ * one `CorporateAction` per line.
+ */
+
+use chrono::{NaiveDate, NaiveTime};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "type")]
+pub enum CorporateActionKind {
+    Split { new_shares: u32, old_shares: u32 },
+    CashDividend { amount_per_share: f64, currency: String, pay_date: NaiveDate },
+    SymbolChange { new_symbol: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct CorporateAction {
+    /// Stable id from the source, used to apply each action exactly once.
+    pub action_id: String,
+    pub instrument_id: u32,
+    /// Ticker before the action takes effect.
+    pub symbol: String,
+    /// First day the instrument trades with the action applied.
+    pub ex_date: NaiveDate,
+    #[serde(flatten)]
+    pub kind: CorporateActionKind,
+}
+
+impl CorporateAction {
+    /// Quantity multiplier of a split (1.0 for any other action).
+    pub fn quantity_factor(&self) -> f64 {
+        match self.kind {
+            CorporateActionKind::Split { new_shares, old_shares } if new_shares > 0 && old_shares > 0 => {
+                new_shares as f64 / old_shares as f64
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Price multiplier of a split (1.0 for any other action).
+    pub fn price_factor(&self) -> f64 {
+        1.0 / self.quantity_factor()
+    }
+
+    /// Start of the ex-date (UTC) in nanoseconds since the Unix epoch.
+    pub fn ex_date_ns(&self) -> u64 {
+        let start = self.ex_date.and_time(NaiveTime::MIN).and_utc();
+        start.timestamp_nanos_opt().unwrap_or(0).max(0) as u64
+    }
+}
+
+// --- Loading ---
+
+/// Reads actions from a JSON-lines file. Unparseable lines are reported and skipped.
+pub fn load_from_file(path: &str) -> std::io::Result<Vec<CorporateAction>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut actions = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<CorporateAction>(line) {
+            Ok(action) => actions.push(action),
+            Err(e) => println!("  -> Skipping corporate action at {}:{} ({})", path, number + 1, e),
+        }
+    }
+    Ok(actions)
+}
+
+/// Loads actions from QA_CORPORATE_ACTIONS_PATH, or none if it is unset.
+pub fn load_from_env() -> Vec<CorporateAction> {
+    let Ok(path) = std::env::var("QA_CORPORATE_ACTIONS_PATH") else {
+        return Vec::new();
+    };
+    match load_from_file(&path) {
+        Ok(actions) => actions,
+        Err(e) => {
+            println!("Failed to read corporate actions from {} ({}).", path, e);
+            Vec::new()
+        }
+    }
+}
+
+// --- Historical Adjustment ---
+
+/// Back-adjustment factors for an instrument's history: for data observed at
+/// `timestamp_ns`, the product of the factors of every split on the
+/// instrument going ex after that time. Returns (price factor, quantity factor).
+pub fn back_adjustment(actions: &[CorporateAction], instrument_id: u32, timestamp_ns: u64) -> (f64, f64) {
+    actions
+        .iter()
+        .filter(|a| a.instrument_id == instrument_id && a.ex_date_ns() > timestamp_ns)
+        .fold((1.0, 1.0), |(price, quantity), a| (price * a.price_factor(), quantity * a.quantity_factor()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(id: &str, instrument_id: u32, ex_date: NaiveDate, new_shares: u32, old_shares: u32) -> CorporateAction {
+        CorporateAction {
+            action_id: id.to_string(),
+            instrument_id,
+            symbol: "ACME".to_string(),
+            ex_date,
+            kind: CorporateActionKind::Split { new_shares, old_shares },
+        }
+    }
+
+    fn june(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    #[test]
+    fn split_factors_keep_notional_unchanged() {
+        let forward = split("CA-1", 7, june(2), 4, 1);
+        assert_eq!(forward.quantity_factor(), 4.0);
+        assert_eq!(forward.price_factor(), 0.25);
+
+        let reverse = split("CA-2", 7, june(2), 1, 10);
+        assert_eq!(reverse.quantity_factor(), 0.1);
+        assert!((reverse.price_factor() - 10.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn degenerate_splits_and_other_actions_leave_quantities_alone() {
+        assert_eq!(split("CA-1", 7, june(2), 0, 1).quantity_factor(), 1.0);
+        assert_eq!(split("CA-2", 7, june(2), 3, 0).price_factor(), 1.0);
+
+        let rename = CorporateAction {
+            kind: CorporateActionKind::SymbolChange { new_symbol: "ACMX".to_string() },
+            ..split("CA-3", 7, june(2), 2, 1)
+        };
+        assert_eq!(rename.quantity_factor(), 1.0);
+        assert_eq!(rename.price_factor(), 1.0);
+    }
+
+    #[test]
+    fn back_adjustment_compounds_later_splits_of_the_instrument_only() {
+        let actions = vec![
+            split("CA-1", 7, june(2), 2, 1),
+            split("CA-2", 7, june(10), 3, 1),
+            split("CA-3", 8, june(10), 5, 1),
+        ];
+        let at = |day: u32| split("", 7, june(day), 1, 1).ex_date_ns();
+
+        assert_eq!(back_adjustment(&actions, 7, at(1)), (1.0 / 6.0, 6.0));
+        // Data from the ex-date itself already trades split-adjusted.
+        assert_eq!(back_adjustment(&actions, 7, at(2)), (1.0 / 3.0, 3.0));
+        assert_eq!(back_adjustment(&actions, 7, at(10)), (1.0, 1.0));
+        assert_eq!(back_adjustment(&actions, 9, at(1)), (1.0, 1.0));
+    }
+
+    #[test]
+    fn ex_date_is_midnight_utc() {
+        let second_day = NaiveDate::from_ymd_opt(1970, 1, 2).unwrap();
+        assert_eq!(split("CA-1", 7, second_day, 2, 1).ex_date_ns(), 86_400_000_000_000);
+    }
+
+    #[test]
+    fn actions_round_trip_through_the_tagged_json_form() {
+        let line = concat!(
+            r#"{"action_id":"CA-9","instrument_id":7,"symbol":"ACME","ex_date":"2025-06-02","#,
+            r#""type":"CashDividend","amount_per_share":0.25,"currency":"USD","pay_date":"2025-06-20"}"#,
+        );
+        let action: CorporateAction = serde_json::from_str(line).unwrap();
+        assert_eq!(
+            action.kind,
+            CorporateActionKind::CashDividend {
+                amount_per_share: 0.25,
+                currency: "USD".to_string(),
+                pay_date: june(20),
+            }
+        );
+        assert_eq!(serde_json::from_str::<CorporateAction>(&serde_json::to_string(&action).unwrap()).unwrap(), action);
+    }
+
+    #[test]
+    fn loading_skips_unparseable_and_blank_lines() {
+        let path = std::env::temp_dir().join(format!("qa-corporate-actions-{}.jsonl", std::process::id()));
+        let valid = serde_json::to_string(&split("CA-1", 7, june(2), 2, 1)).unwrap();
+        let renamed = concat!(
+            r#"{"action_id":"CA-2","instrument_id":7,"symbol":"ACME","ex_date":"2025-06-03","#,
+            r#""type":"SymbolChange","new_symbol":"ACMX"}"#,
+        );
+        let contents = [valid.as_str(), "", "{\"action_id\":\"CA-X\"", r#"{"type":"Merger"}"#, renamed].join("\n");
+        std::fs::write(&path, contents).unwrap();
+
+        let actions = load_from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let ids: Vec<&str> = actions.iter().map(|a| a.action_id.as_str()).collect();
+        assert_eq!(ids, ["CA-1", "CA-2"]);
+        assert!(load_from_file(path.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+/*
+ * QuantumArb 2.0 - Shared: Error and Reject-Code Taxonomy
+ *
+ * File: src/shared/errors/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-errors`) defines the single set of machine-
+ * readable codes used by every service when something is rejected or fails.
+ * Downstream systems (strategies, dashboards, surveillance) branch on the
+ * `code` instead of parsing English sentences.
+ *
+ * Codes are grouped into three categories:
+ * - Risk:   pre-trade rejections from the risk gateway.
+ * - Venue:  rejections reported by an exchange.
+ * - System: infrastructure failures and invalid API requests.
+ *
+ * On the wire a rejection always has the same shape, in HTTP bodies and in
+ * bus messages alike:
+ *   { "code": "RISK_ORDER_SIZE_LIMIT", "category": "RISK", "message": "..." }
+ * HTTP error responses wrap it as { "error": { ... } } (see `ErrorBody`).
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// --- Reject Codes ---
+
+/// Top-level grouping of a reject code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCategory {
+    Risk,
+    Venue,
+    System,
+}
+
+/// Every reason an order or request can be refused. Serialized as a stable
+/// SCREAMING_SNAKE_CASE string; new variants may be added but existing ones
+/// are never renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RejectCode {
+    // Risk gateway (pre-trade)
+    RiskKillSwitchEngaged,
+    RiskAccountNotFound,
+    RiskOrderSizeLimit,
+    RiskExposureLimit,
+    RiskConcentrationLimit,
+    RiskCounterpartyLimit,
+    RiskUnknownInstrument,
+    RiskInvalidTickSize,
+    RiskInvalidLotSize,
+    /// The order is routed to a venue whose session is down.
+    RiskVenueUnavailable,
+    /// VaR is over its absolute limit and the order would add to exposure.
+    RiskVarLimitBreached,
+    /// The account hit its daily loss limit and the order would add to exposure.
+    RiskDailyLossLimit,
+    /// The order would take its strategy past its capital allocation.
+    RiskCapitalAllocation,
+    /// The option order would take the account past its vega or gamma limit.
+    RiskGreeksLimit,
+    /// The large order would take the account's stressed loss past its limit.
+    RiskStressLimit,
+    /// As RiskStressLimit, but an operator may approve the order.
+    RiskStressApprovalRequired,
+    /// Not final: the order is held for a supervisor and its verdict follows.
+    RiskPendingApproval,
+
+    // Exchange / venue
+    VenueUnknownInstrument,
+    VenueInvalidPrice,
+    VenueInvalidQuantity,
+    VenueMarketClosed,
+    VenueDuplicateOrder,
+    VenueRateLimited,
+    VenueOther,
+
+    // System
+    SystemStateUnavailable,
+    SystemNotReady,
+    SystemInvalidRequest,
+    SystemConflict,
+    SystemTimeout,
+    /// A sandbox message reached a live service, or the other way round.
+    SystemModeMismatch,
+    /// Shed to keep the service inside its latency budget.
+    SystemOverloaded,
+    /// Sent to a risk gateway instance that does not own the account.
+    SystemWrongShard,
+    /// The API request carried no valid credentials.
+    SystemUnauthorized,
+    /// The credentials do not reach what the request names (another desk's
+    /// state, or a firm-wide setting).
+    SystemForbidden,
+    SystemInternal,
+}
+
+impl RejectCode {
+    pub fn category(self) -> ErrorCategory {
+        use RejectCode::*;
+        match self {
+            RiskKillSwitchEngaged | RiskAccountNotFound | RiskOrderSizeLimit | RiskExposureLimit
+            | RiskConcentrationLimit | RiskCounterpartyLimit | RiskUnknownInstrument | RiskInvalidTickSize
+            | RiskInvalidLotSize | RiskVenueUnavailable | RiskVarLimitBreached | RiskDailyLossLimit
+            | RiskCapitalAllocation | RiskGreeksLimit | RiskStressLimit | RiskStressApprovalRequired
+            | RiskPendingApproval => {
+                ErrorCategory::Risk
+            }
+            VenueUnknownInstrument | VenueInvalidPrice | VenueInvalidQuantity | VenueMarketClosed
+            | VenueDuplicateOrder | VenueRateLimited | VenueOther => ErrorCategory::Venue,
+            SystemStateUnavailable | SystemNotReady | SystemInvalidRequest | SystemConflict | SystemTimeout
+            | SystemModeMismatch | SystemOverloaded | SystemWrongShard | SystemUnauthorized | SystemForbidden
+            | SystemInternal => {
+                ErrorCategory::System
+            }
+        }
+    }
+
+    /// The wire name of the code (identical to its serde representation).
+    pub fn as_str(self) -> &'static str {
+        use RejectCode::*;
+        match self {
+            RiskKillSwitchEngaged => "RISK_KILL_SWITCH_ENGAGED",
+            RiskAccountNotFound => "RISK_ACCOUNT_NOT_FOUND",
+            RiskOrderSizeLimit => "RISK_ORDER_SIZE_LIMIT",
+            RiskExposureLimit => "RISK_EXPOSURE_LIMIT",
+            RiskConcentrationLimit => "RISK_CONCENTRATION_LIMIT",
+            RiskCounterpartyLimit => "RISK_COUNTERPARTY_LIMIT",
+            RiskUnknownInstrument => "RISK_UNKNOWN_INSTRUMENT",
+            RiskInvalidTickSize => "RISK_INVALID_TICK_SIZE",
+            RiskInvalidLotSize => "RISK_INVALID_LOT_SIZE",
+            RiskVenueUnavailable => "RISK_VENUE_UNAVAILABLE",
+            RiskVarLimitBreached => "RISK_VAR_LIMIT_BREACHED",
+            RiskDailyLossLimit => "RISK_DAILY_LOSS_LIMIT",
+            RiskCapitalAllocation => "RISK_CAPITAL_ALLOCATION",
+            RiskGreeksLimit => "RISK_GREEKS_LIMIT",
+            RiskStressLimit => "RISK_STRESS_LIMIT",
+            RiskStressApprovalRequired => "RISK_STRESS_APPROVAL_REQUIRED",
+            RiskPendingApproval => "RISK_PENDING_APPROVAL",
+            VenueUnknownInstrument => "VENUE_UNKNOWN_INSTRUMENT",
+            VenueInvalidPrice => "VENUE_INVALID_PRICE",
+            VenueInvalidQuantity => "VENUE_INVALID_QUANTITY",
+            VenueMarketClosed => "VENUE_MARKET_CLOSED",
+            VenueDuplicateOrder => "VENUE_DUPLICATE_ORDER",
+            VenueRateLimited => "VENUE_RATE_LIMITED",
+            VenueOther => "VENUE_OTHER",
+            SystemStateUnavailable => "SYSTEM_STATE_UNAVAILABLE",
+            SystemNotReady => "SYSTEM_NOT_READY",
+            SystemInvalidRequest => "SYSTEM_INVALID_REQUEST",
+            SystemConflict => "SYSTEM_CONFLICT",
+            SystemTimeout => "SYSTEM_TIMEOUT",
+            SystemModeMismatch => "SYSTEM_MODE_MISMATCH",
+            SystemOverloaded => "SYSTEM_OVERLOADED",
+            SystemWrongShard => "SYSTEM_WRONG_SHARD",
+            SystemUnauthorized => "SYSTEM_UNAUTHORIZED",
+            SystemForbidden => "SYSTEM_FORBIDDEN",
+            SystemInternal => "SYSTEM_INTERNAL",
+        }
+    }
+
+    /// Stable numeric id used by binary encodings (0 is reserved for "none").
+    /// Risk codes are 1xx, venue codes 2xx and system codes 9xx.
+    pub fn wire_id(self) -> u16 {
+        use RejectCode::*;
+        match self {
+            RiskKillSwitchEngaged => 101,
+            RiskAccountNotFound => 102,
+            RiskOrderSizeLimit => 103,
+            RiskExposureLimit => 104,
+            RiskConcentrationLimit => 105,
+            RiskCounterpartyLimit => 106,
+            RiskUnknownInstrument => 107,
+            RiskInvalidTickSize => 108,
+            RiskInvalidLotSize => 109,
+            RiskVenueUnavailable => 110,
+            RiskVarLimitBreached => 111,
+            RiskDailyLossLimit => 112,
+            RiskCapitalAllocation => 113,
+            RiskGreeksLimit => 114,
+            RiskStressLimit => 115,
+            RiskStressApprovalRequired => 116,
+            RiskPendingApproval => 117,
+            VenueUnknownInstrument => 201,
+            VenueInvalidPrice => 202,
+            VenueInvalidQuantity => 203,
+            VenueMarketClosed => 204,
+            VenueDuplicateOrder => 205,
+            VenueRateLimited => 206,
+            VenueOther => 299,
+            SystemStateUnavailable => 901,
+            SystemNotReady => 902,
+            SystemInvalidRequest => 903,
+            SystemConflict => 904,
+            SystemTimeout => 905,
+            SystemModeMismatch => 906,
+            SystemOverloaded => 907,
+            SystemWrongShard => 908,
+            SystemUnauthorized => 909,
+            SystemForbidden => 910,
+            SystemInternal => 999,
+        }
+    }
+
+    pub fn from_wire_id(id: u16) -> Option<RejectCode> {
+        use RejectCode::*;
+        let code = match id {
+            101 => RiskKillSwitchEngaged,
+            102 => RiskAccountNotFound,
+            103 => RiskOrderSizeLimit,
+            104 => RiskExposureLimit,
+            105 => RiskConcentrationLimit,
+            106 => RiskCounterpartyLimit,
+            107 => RiskUnknownInstrument,
+            108 => RiskInvalidTickSize,
+            109 => RiskInvalidLotSize,
+            110 => RiskVenueUnavailable,
+            111 => RiskVarLimitBreached,
+            112 => RiskDailyLossLimit,
+            113 => RiskCapitalAllocation,
+            114 => RiskGreeksLimit,
+            115 => RiskStressLimit,
+            116 => RiskStressApprovalRequired,
+            117 => RiskPendingApproval,
+            201 => VenueUnknownInstrument,
+            202 => VenueInvalidPrice,
+            203 => VenueInvalidQuantity,
+            204 => VenueMarketClosed,
+            205 => VenueDuplicateOrder,
+            206 => VenueRateLimited,
+            299 => VenueOther,
+            901 => SystemStateUnavailable,
+            902 => SystemNotReady,
+            903 => SystemInvalidRequest,
+            904 => SystemConflict,
+            905 => SystemTimeout,
+            906 => SystemModeMismatch,
+            907 => SystemOverloaded,
+            908 => SystemWrongShard,
+            909 => SystemUnauthorized,
+            910 => SystemForbidden,
+            999 => SystemInternal,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// The HTTP status an API should answer with when returning this code.
+    pub fn http_status(self) -> u16 {
+        use RejectCode::*;
+        match self {
+            RiskAccountNotFound => 404,
+            SystemInvalidRequest => 400,
+            SystemConflict => 409,
+            SystemWrongShard => 421,
+            SystemUnauthorized => 401,
+            SystemForbidden => 403,
+            SystemNotReady | SystemStateUnavailable | SystemOverloaded => 503,
+            SystemTimeout => 504,
+            SystemInternal => 500,
+            // Business rejections are a valid answer, not a server failure.
+            _ => 422,
+        }
+    }
+}
+
+impl fmt::Display for RejectCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// --- Rejection ---
+
+/// A reject code plus human-readable context. This is the unified error type
+/// carried in risk decisions, execution reports and API error responses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct Rejection {
+    pub code: RejectCode,
+    pub category: ErrorCategory,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(code: RejectCode, message: impl Into<String>) -> Self {
+        Rejection {
+            code,
+            category: code.category(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Standard body for HTTP error responses: { "error": { code, category, message } }.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct ErrorBody {
+    pub error: Rejection,
+}
+
+impl From<Rejection> for ErrorBody {
+    fn from(error: Rejection) -> Self {
+        ErrorBody { error }
+    }
+}
@@ -0,0 +1,362 @@
+/*
+ * QuantumArb 2.0 - Shared: Feature Store
+ *
+ * File: src/shared/features/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-features`) defines the rolling per-symbol
+ * features the ML models consume, how they are computed from normalized
+ * events, and where they are stored, so the live strategy engine and the
+ * offline training pipeline read exactly the same values.
+ *
+ * Features (`FeatureSnapshot`), computed by `FeatureBuilder`:
+ * - sentiment_1m / sentiment_5m / sentiment_1h: decay-weighted news
+ *   sentiment over the `SentimentWindows` (see below);
+ * - news_count: news events for the symbol in the last hour;
+ * - mid and mavg_spread: the mid sampled once per snapshot interval, and the
+ *   mean of the last 5 samples minus the mean of the last 20 (the training
+ *   set's short/long moving-average spread on 1-minute bars);
+ * - spread_mean_bps / spread_std_bps: quoted bid/ask spread over the quotes
+ *   of the last 20 intervals;
+ * - volatility_bps: standard deviation of the sampled mid's log returns over
+ *   the last 20 intervals;
+ * - book_imbalance / microprice_edge_bps / momentum_bps: the latest top of
+ *   book signals (`quantumarb-signals`) of the interval.
+ *
+ * Sentiment windows (`SentimentWindows`): the news sentiment scores of a
+ * symbol aggregated over the last 1m, 5m and 1h, each with the event count,
+ * the plain mean and a decay-weighted score. In the decay-weighted score an
+ * event's weight halves every quarter of the window, so the newest news
+ * dominates while the window still bounds how far back it looks. The data
+ * bus connector publishes the same windows on 'alt_data.sentiment'.
+ *
+ * Storage:
+ * - Redis (live): `features:{symbol}:latest` holds the newest snapshot as
+ *   JSON; `features:{symbol}:history` is a sorted set of snapshots scored by
+ *   timestamp (ms), trimmed to the retention period, for point-in-time reads.
+ * - Postgres (offline): one row per snapshot in `feature_snapshots`
+ *   (`POSTGRES_SCHEMA`), keyed by (symbol, ts), for training sets.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use quantumarb_signals::BookSignals;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Mid samples behind the short and long moving averages.
+const SHORT_WINDOW: usize = 5;
+const LONG_WINDOW: usize = 20;
+
+/// Sentiment aggregation windows: (name, length in ms), shortest first.
+pub const SENTIMENT_WINDOWS: [(&str, i64); 3] = [("1m", 60_000), ("5m", 300_000), ("1h", 3_600_000)];
+/// An event's weight in the decay-weighted score halves every
+/// `window / SENTIMENT_HALF_LIVES_PER_WINDOW`.
+const SENTIMENT_HALF_LIVES_PER_WINDOW: f64 = 4.0;
+/// Cap on the news events held per symbol, so a flood of news cannot grow
+/// the windows without bound; the oldest events go first.
+const MAX_SENTIMENT_EVENTS: usize = 10_000;
+
+/// DDL for the offline feature table.
+pub const POSTGRES_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS feature_snapshots (
+    symbol              TEXT             NOT NULL,
+    ts                  TIMESTAMPTZ      NOT NULL,
+    sentiment_1m        DOUBLE PRECISION,
+    sentiment_5m        DOUBLE PRECISION,
+    sentiment_1h        DOUBLE PRECISION,
+    news_count          INTEGER          NOT NULL,
+    mid                 DOUBLE PRECISION,
+    mavg_spread         DOUBLE PRECISION,
+    spread_mean_bps     DOUBLE PRECISION,
+    spread_std_bps      DOUBLE PRECISION,
+    volatility_bps      DOUBLE PRECISION,
+    book_imbalance      DOUBLE PRECISION,
+    microprice_edge_bps DOUBLE PRECISION,
+    momentum_bps        DOUBLE PRECISION,
+    PRIMARY KEY (symbol, ts)
+);
+-- Tables created before the sentiment windows had EMA columns instead.
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS sentiment_1m DOUBLE PRECISION;
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS sentiment_5m DOUBLE PRECISION;
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS sentiment_1h DOUBLE PRECISION;
+-- And before the book signals.
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS book_imbalance DOUBLE PRECISION;
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS microprice_edge_bps DOUBLE PRECISION;
+ALTER TABLE feature_snapshots ADD COLUMN IF NOT EXISTS momentum_bps DOUBLE PRECISION;
+";
+
+// --- Data Structures ---
+
+/// The features of one symbol at one point in time. Fields are None until
+/// there is enough data to compute them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct FeatureSnapshot {
+    pub symbol: String,
+    pub timestamp_ms: i64,
+    /// Decay-weighted sentiment per window (None without news in the window).
+    pub sentiment_1m: Option<f64>,
+    pub sentiment_5m: Option<f64>,
+    pub sentiment_1h: Option<f64>,
+    pub news_count: u32,
+    pub mid: Option<f64>,
+    pub mavg_spread: Option<f64>,
+    pub spread_mean_bps: Option<f64>,
+    pub spread_std_bps: Option<f64>,
+    pub volatility_bps: Option<f64>,
+    /// Book signals; absent from snapshots stored before they were added.
+    #[serde(default)]
+    pub book_imbalance: Option<f64>,
+    #[serde(default)]
+    pub microprice_edge_bps: Option<f64>,
+    #[serde(default)]
+    pub momentum_bps: Option<f64>,
+}
+
+/// News sentiment aggregated over one window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct SentimentWindow {
+    pub window: String,
+    pub count: u32,
+    pub mean: Option<f64>,
+    pub decay_weighted: Option<f64>,
+}
+
+/// The recent news sentiment scores of one symbol, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct SentimentWindows {
+    events: VecDeque<(i64, f64)>,
+}
+
+impl SentimentWindows {
+    /// Records a news sentiment score (-1 to 1).
+    pub fn record(&mut self, sentiment: f64, at_ms: i64) {
+        if self.events.len() == MAX_SENTIMENT_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back((at_ms, sentiment));
+    }
+
+    /// True once no news is left in the longest window.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Aggregates each window as of `now_ms`, dropping news older than the
+    /// longest one.
+    pub fn windows(&mut self, now_ms: i64) -> Vec<SentimentWindow> {
+        let longest = SENTIMENT_WINDOWS[SENTIMENT_WINDOWS.len() - 1].1;
+        while self.events.front().is_some_and(|(t, _)| now_ms - t > longest) {
+            self.events.pop_front();
+        }
+        SENTIMENT_WINDOWS
+            .iter()
+            .map(|(name, length_ms)| {
+                let half_life_ms = *length_ms as f64 / SENTIMENT_HALF_LIVES_PER_WINDOW;
+                let (mut count, mut sum, mut weighted, mut weights) = (0u32, 0.0, 0.0, 0.0);
+                for (at_ms, sentiment) in self.events.iter().rev().take_while(|(t, _)| now_ms - t <= *length_ms) {
+                    let weight = 0.5f64.powf((now_ms - at_ms).max(0) as f64 / half_life_ms);
+                    count += 1;
+                    sum += sentiment;
+                    weighted += weight * sentiment;
+                    weights += weight;
+                }
+                SentimentWindow {
+                    window: name.to_string(),
+                    count,
+                    mean: (count > 0).then(|| sum / count as f64),
+                    decay_weighted: (count > 0).then(|| weighted / weights),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Accumulates one symbol's events between snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureBuilder {
+    sentiment: SentimentWindows,
+    last_mid: Option<f64>,
+    /// Mid sampled at each snapshot, newest last.
+    mids: VecDeque<f64>,
+    /// Quoted spreads (bps) per interval, newest last.
+    spreads: VecDeque<Vec<f64>>,
+    /// The latest book signals of the current interval.
+    signals: Option<BookSignals>,
+}
+
+// --- Feature Computation ---
+
+impl FeatureBuilder {
+    /// Records a news sentiment score (-1 to 1).
+    pub fn on_news(&mut self, sentiment: f64, at_ms: i64) {
+        self.sentiment.record(sentiment, at_ms);
+    }
+
+    /// Records a top-of-book quote (prices in dollars).
+    pub fn on_quote(&mut self, bid: f64, ask: f64) {
+        if bid <= 0.0 || ask < bid {
+            return;
+        }
+        let mid = (bid + ask) / 2.0;
+        self.last_mid = Some(mid);
+        if self.spreads.is_empty() {
+            self.spreads.push_back(Vec::new());
+        }
+        if let Some(current) = self.spreads.back_mut() {
+            current.push((ask - bid) / mid * 10_000.0);
+        }
+    }
+
+    /// Records the instrument's latest book signals.
+    pub fn on_signals(&mut self, signals: BookSignals) {
+        self.signals = Some(signals);
+    }
+
+    /// Samples the interval's mid, closes the interval and returns the
+    /// features as of `at_ms`.
+    pub fn snapshot(&mut self, symbol: &str, at_ms: i64) -> FeatureSnapshot {
+        let sentiment = self.sentiment.windows(at_ms);
+        if let Some(mid) = self.last_mid {
+            if self.mids.len() == LONG_WINDOW + 1 {
+                self.mids.pop_front();
+            }
+            self.mids.push_back(mid);
+        }
+
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let std_dev = |values: &[f64]| {
+            let m = mean(values);
+            (values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64).sqrt()
+        };
+
+        let mids: Vec<f64> = self.mids.iter().copied().collect();
+        let long: Vec<f64> = mids.iter().rev().take(LONG_WINDOW).copied().collect();
+        let mavg_spread = (long.len() == LONG_WINDOW).then(|| mean(&long[..SHORT_WINDOW]) - mean(&long));
+        let returns: Vec<f64> = mids.windows(2).map(|w| (w[1] / w[0]).ln() * 10_000.0).collect();
+        let volatility_bps = (returns.len() >= 2).then(|| std_dev(&returns));
+        let spreads: Vec<f64> = self.spreads.iter().flatten().copied().collect();
+
+        let snapshot = FeatureSnapshot {
+            symbol: symbol.to_string(),
+            timestamp_ms: at_ms,
+            sentiment_1m: sentiment[0].decay_weighted,
+            sentiment_5m: sentiment[1].decay_weighted,
+            sentiment_1h: sentiment[2].decay_weighted,
+            news_count: sentiment[2].count,
+            mid: self.last_mid,
+            mavg_spread,
+            spread_mean_bps: (!spreads.is_empty()).then(|| mean(&spreads)),
+            spread_std_bps: (spreads.len() >= 2).then(|| std_dev(&spreads)),
+            volatility_bps,
+            book_imbalance: self.signals.as_ref().map(|signals| signals.imbalance),
+            microprice_edge_bps: self.signals.as_ref().map(|signals| signals.microprice_edge_bps),
+            momentum_bps: self.signals.take().and_then(|signals| signals.momentum_bps),
+        };
+
+        if self.spreads.len() == LONG_WINDOW {
+            self.spreads.pop_front();
+        }
+        self.spreads.push_back(Vec::new());
+        snapshot
+    }
+}
+
+// --- Redis (live) ---
+
+pub fn latest_key(symbol: &str) -> String {
+    format!("features:{}:latest", symbol)
+}
+
+pub fn history_key(symbol: &str) -> String {
+    format!("features:{}:history", symbol)
+}
+
+/// Stores the snapshot as the symbol's latest and adds it to the history
+/// (replacing any snapshot with the same timestamp, so replicated writers
+/// leave one entry per interval), dropping history older than `retention_ms`.
+pub async fn write_redis<C: redis::aio::ConnectionLike>(
+    con: &mut C,
+    snapshot: &FeatureSnapshot,
+    retention_ms: i64,
+) -> redis::RedisResult<()> {
+    let json = serde_json::to_string(snapshot).expect("feature snapshots always serialize");
+    let history = history_key(&snapshot.symbol);
+    redis::pipe()
+        .atomic()
+        .set(latest_key(&snapshot.symbol), &json)
+        .ignore()
+        .zrembyscore(&history, snapshot.timestamp_ms, snapshot.timestamp_ms)
+        .ignore()
+        .zadd(&history, &json, snapshot.timestamp_ms)
+        .ignore()
+        .zrembyscore(&history, "-inf", snapshot.timestamp_ms - retention_ms)
+        .ignore()
+        .query_async(con)
+        .await
+}
+
+/// The newest snapshot of a symbol, if any.
+pub async fn read_latest<C: redis::aio::ConnectionLike>(
+    con: &mut C,
+    symbol: &str,
+) -> redis::RedisResult<Option<FeatureSnapshot>> {
+    let json: Option<String> = redis::cmd("GET").arg(latest_key(symbol)).query_async(con).await?;
+    Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+}
+
+/// The newest snapshot taken at or before `at_ms` (a point-in-time read, so
+/// backtests never see features from the future).
+pub async fn read_as_of<C: redis::aio::ConnectionLike>(
+    con: &mut C,
+    symbol: &str,
+    at_ms: i64,
+) -> redis::RedisResult<Option<FeatureSnapshot>> {
+    let found: Vec<String> = redis::cmd("ZRANGE")
+        .arg(history_key(symbol))
+        .arg(at_ms)
+        .arg("-inf")
+        .arg("BYSCORE")
+        .arg("REV")
+        .arg("LIMIT")
+        .arg(0)
+        .arg(1)
+        .query_async(con)
+        .await?;
+    Ok(found.first().and_then(|j| serde_json::from_str(j).ok()))
+}
+
+// --- Postgres (offline) ---
+
+/// Inserts the snapshot into `feature_snapshots` (idempotent on (symbol, ts)).
+pub async fn write_postgres(
+    client: &tokio_postgres::Client,
+    snapshot: &FeatureSnapshot,
+) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute(
+            "INSERT INTO feature_snapshots (symbol, ts, sentiment_1m, sentiment_5m, sentiment_1h, news_count, mid,
+                 mavg_spread, spread_mean_bps, spread_std_bps, volatility_bps, book_imbalance, microprice_edge_bps,
+                 momentum_bps)
+             VALUES ($1, to_timestamp($2::DOUBLE PRECISION / 1000), $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13,
+                 $14)
+             ON CONFLICT (symbol, ts) DO NOTHING",
+            &[
+                &snapshot.symbol,
+                &(snapshot.timestamp_ms as f64),
+                &snapshot.sentiment_1m,
+                &snapshot.sentiment_5m,
+                &snapshot.sentiment_1h,
+                &(snapshot.news_count as i32),
+                &snapshot.mid,
+                &snapshot.mavg_spread,
+                &snapshot.spread_mean_bps,
+                &snapshot.spread_std_bps,
+                &snapshot.volatility_bps,
+                &snapshot.book_imbalance,
+                &snapshot.microprice_edge_bps,
+                &snapshot.momentum_bps,
+            ],
+        )
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,278 @@
+/*
+ * QuantumArb 2.0 - Shared: Fee and Commission Engine
+ *
+ * File: src/shared/fees/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-fees`) prices the explicit cost of a trade
+ * from per-venue fee schedules. A fee is made up of:
+ *
+ *   exchange fee     maker or taker rate (bps of notional) from the volume
+ *                    tier the venue currently puts us in; negative = rebate
+ *   per-contract fee fixed amount per unit traded (futures venues)
+ *   regulatory fee   per-contract levies (e.g. NFA) plus a rate on sell
+ *                    notional (e.g. SEC Section 31)
+ *
+ * The portfolio manager applies the engine to every fill, accruing traded
+ * volume so tiers step down as the month goes on; a fee the venue reported on
+ * the execution report takes precedence over the schedule's. The paper venue
+ * charges its simulated fills from the same schedules. The strategy engine uses
+ * `estimate` (no accrual) to compare venues on an all-in cost basis.
+ *
+ * Schedules are built in, or loaded from a JSON array of `FeeSchedule`s at
+ * QA_FEE_SCHEDULES_PATH.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The liquidity indicator execution reports carry.
+pub use quantumarb_wire::Liquidity;
+
+// --- Data Structures ---
+
+/// One volume tier of a venue's schedule. Applies once month-to-date traded
+/// notional at the venue reaches `min_monthly_volume`.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct FeeTier {
+    pub min_monthly_volume: f64,
+    pub maker_bps: f64,
+    pub taker_bps: f64,
+    #[serde(default)]
+    pub per_contract: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct FeeSchedule {
+    pub venue: String,
+    /// Sorted by `min_monthly_volume`, ascending; the first tier starts at 0.
+    pub tiers: Vec<FeeTier>,
+    #[serde(default)]
+    pub regulatory_per_contract: f64,
+    /// Fraction of sell notional (e.g. 0.0000278 for $27.80 per million).
+    #[serde(default)]
+    pub sell_regulatory_rate: f64,
+}
+
+impl FeeSchedule {
+    /// Index of the tier that applies at `monthly_volume`.
+    fn tier_index(&self, monthly_volume: f64) -> usize {
+        self.tiers
+            .iter()
+            .rposition(|tier| monthly_volume >= tier.min_monthly_volume)
+            .unwrap_or(0)
+    }
+}
+
+/// The fees charged on one fill, in the instrument's currency.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct FeeBreakdown {
+    pub venue: String,
+    pub liquidity: Liquidity,
+    pub tier: usize,
+    pub exchange_fee: f64,
+    pub per_contract_fee: f64,
+    pub regulatory_fee: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeeEngine {
+    schedules: HashMap<String, FeeSchedule>,
+    /// Applied to venues without a schedule of their own.
+    fallback: FeeSchedule,
+    monthly_volume: HashMap<String, f64>,
+}
+
+// --- Fee Engine ---
+
+impl FeeEngine {
+    pub fn new(schedules: Vec<FeeSchedule>) -> FeeEngine {
+        FeeEngine {
+            schedules: schedules.into_iter().map(|s| (s.venue.clone(), s)).collect(),
+            fallback: FeeSchedule {
+                venue: "DEFAULT".to_string(),
+                tiers: vec![FeeTier { min_monthly_volume: 0.0, maker_bps: 20.0, taker_bps: 30.0, per_contract: 0.0 }],
+                regulatory_per_contract: 0.0,
+                sell_regulatory_rate: 0.0,
+            },
+            monthly_volume: HashMap::new(),
+        }
+    }
+
+    /// Loads schedules from QA_FEE_SCHEDULES_PATH, falling back to the
+    /// built-in schedules if it is unset or unreadable.
+    pub fn from_env() -> FeeEngine {
+        let Ok(path) = std::env::var("QA_FEE_SCHEDULES_PATH") else {
+            return FeeEngine::new(default_schedules());
+        };
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<Vec<FeeSchedule>>(&json).map_err(|e| e.to_string()));
+        match loaded {
+            Ok(schedules) if schedules.iter().all(|s| !s.tiers.is_empty()) => {
+                println!("Loaded {} fee schedule(s) from {}", schedules.len(), path);
+                FeeEngine::new(schedules)
+            }
+            Ok(_) => {
+                println!("Fee schedules in {} must each have at least one tier; using built-in schedules.", path);
+                FeeEngine::new(default_schedules())
+            }
+            Err(e) => {
+                println!("Failed to load fee schedules from {} ({}); using built-in schedules.", path, e);
+                FeeEngine::new(default_schedules())
+            }
+        }
+    }
+
+    /// Fees for a prospective trade at the current tier, without accruing volume.
+    /// `quantity` is signed: positive for buys, negative for sells.
+    pub fn estimate(&self, venue: &str, liquidity: Liquidity, quantity: i64, price: f64) -> FeeBreakdown {
+        let schedule = self.schedules.get(venue).unwrap_or(&self.fallback);
+        let volume = self.monthly_volume.get(venue).copied().unwrap_or(0.0);
+        let tier_index = schedule.tier_index(volume);
+        let tier = &schedule.tiers[tier_index];
+
+        let units = quantity.unsigned_abs() as f64;
+        let notional = units * price;
+        let rate_bps = match liquidity {
+            Liquidity::Maker => tier.maker_bps,
+            Liquidity::Taker => tier.taker_bps,
+        };
+        let exchange_fee = notional * rate_bps / 10_000.0;
+        let per_contract_fee = units * tier.per_contract;
+        let mut regulatory_fee = units * schedule.regulatory_per_contract;
+        if quantity < 0 {
+            regulatory_fee += notional * schedule.sell_regulatory_rate;
+        }
+
+        FeeBreakdown {
+            venue: venue.to_string(),
+            liquidity,
+            tier: tier_index,
+            exchange_fee,
+            per_contract_fee,
+            regulatory_fee,
+            total: exchange_fee + per_contract_fee + regulatory_fee,
+        }
+    }
+
+    /// Fees for an executed fill. The fill's notional counts towards the
+    /// venue's monthly volume, so it can move later fills into a cheaper tier.
+    pub fn apply(&mut self, venue: &str, liquidity: Liquidity, quantity: i64, price: f64) -> FeeBreakdown {
+        let fees = self.estimate(venue, liquidity, quantity, price);
+        *self.monthly_volume.entry(venue.to_string()).or_insert(0.0) += quantity.unsigned_abs() as f64 * price;
+        fees
+    }
+
+    /// Starts a new tier period; call when the calendar month rolls.
+    pub fn reset_monthly_volume(&mut self) {
+        self.monthly_volume.clear();
+    }
+}
+
+/// Built-in schedules for the venues we trade.
+pub fn default_schedules() -> Vec<FeeSchedule> {
+    vec![
+        FeeSchedule {
+            venue: "VENUE_A".to_string(),
+            tiers: vec![
+                FeeTier { min_monthly_volume: 0.0, maker_bps: 10.0, taker_bps: 20.0, per_contract: 0.0 },
+                FeeTier { min_monthly_volume: 1_000_000.0, maker_bps: 8.0, taker_bps: 15.0, per_contract: 0.0 },
+                FeeTier { min_monthly_volume: 10_000_000.0, maker_bps: 4.0, taker_bps: 10.0, per_contract: 0.0 },
+            ],
+            regulatory_per_contract: 0.0,
+            sell_regulatory_rate: 0.0,
+        },
+        FeeSchedule {
+            venue: "VENUE_B".to_string(),
+            tiers: vec![
+                FeeTier { min_monthly_volume: 0.0, maker_bps: 0.0, taker_bps: 25.0, per_contract: 0.0 },
+                FeeTier { min_monthly_volume: 5_000_000.0, maker_bps: -1.0, taker_bps: 18.0, per_contract: 0.0 },
+            ],
+            regulatory_per_contract: 0.0,
+            sell_regulatory_rate: 0.0,
+        },
+        FeeSchedule {
+            venue: "CME".to_string(),
+            tiers: vec![
+                FeeTier { min_monthly_volume: 0.0, maker_bps: 0.0, taker_bps: 0.0, per_contract: 1.18 },
+                FeeTier { min_monthly_volume: 50_000_000.0, maker_bps: 0.0, taker_bps: 0.0, per_contract: 0.85 },
+            ],
+            regulatory_per_contract: 0.02, // NFA assessment fee
+            sell_regulatory_rate: 0.0,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn tiers_step_down_as_monthly_volume_accrues() {
+        let mut engine = FeeEngine::new(default_schedules());
+        // 900,000 of notional at the 20 bps taker rate of the first tier.
+        let first = engine.apply("VENUE_A", Liquidity::Taker, 15, 60_000.0);
+        assert_eq!(first.tier, 0);
+        assert!(close(first.exchange_fee, 1_800.0));
+        // 1,200,000 traded: the second tier from here on.
+        engine.apply("VENUE_A", Liquidity::Maker, -5, 60_000.0);
+        let second = engine.estimate("VENUE_A", Liquidity::Maker, 1, 60_000.0);
+        assert_eq!(second.tier, 1);
+        assert!(close(second.exchange_fee, 48.0));
+        assert_eq!(engine.estimate("VENUE_B", Liquidity::Taker, 1, 60_000.0).tier, 0, "volume is per venue");
+
+        engine.reset_monthly_volume();
+        assert_eq!(engine.estimate("VENUE_A", Liquidity::Maker, 1, 60_000.0).tier, 0);
+    }
+
+    #[test]
+    fn estimates_do_not_accrue_and_rebates_are_negative() {
+        let mut engine = FeeEngine::new(default_schedules());
+        for _ in 0..3 {
+            engine.estimate("VENUE_B", Liquidity::Maker, 100, 60_000.0);
+        }
+        assert_eq!(engine.estimate("VENUE_B", Liquidity::Maker, 1, 60_000.0).tier, 0);
+        engine.apply("VENUE_B", Liquidity::Taker, 100, 60_000.0);
+        let rebate = engine.estimate("VENUE_B", Liquidity::Maker, 10, 2_000.0);
+        assert_eq!(rebate.tier, 1);
+        assert!(close(rebate.total, -2.0), "1 bp back on 20,000");
+    }
+
+    #[test]
+    fn futures_pay_per_contract_on_either_side() {
+        let engine = FeeEngine::new(default_schedules());
+        let buy = engine.estimate("CME", Liquidity::Taker, 10, 5_000.0);
+        assert!(close(buy.exchange_fee, 0.0) && close(buy.per_contract_fee, 11.8) && close(buy.regulatory_fee, 0.2));
+        let sell = engine.estimate("CME", Liquidity::Maker, -10, 5_000.0);
+        assert!(close(sell.total, buy.total));
+    }
+
+    #[test]
+    fn the_sell_side_regulatory_rate_applies_to_sells_only() {
+        let equities = FeeSchedule {
+            venue: "NASDAQ".to_string(),
+            tiers: vec![FeeTier { min_monthly_volume: 0.0, maker_bps: 0.0, taker_bps: 3.0, per_contract: 0.0 }],
+            regulatory_per_contract: 0.0,
+            sell_regulatory_rate: 0.0000278,
+        };
+        let engine = FeeEngine::new(vec![equities]);
+        let buy = engine.estimate("NASDAQ", Liquidity::Taker, 10_000, 100.0);
+        assert!(close(buy.regulatory_fee, 0.0) && close(buy.total, 300.0));
+        let sell = engine.estimate("NASDAQ", Liquidity::Taker, -10_000, 100.0);
+        assert!(close(sell.regulatory_fee, 27.8) && close(sell.total, 327.8), "{:?}", sell);
+    }
+
+    #[test]
+    fn venues_without_a_schedule_use_the_fallback() {
+        let fees = FeeEngine::new(default_schedules()).estimate("ELSEWHERE", Liquidity::Taker, 1, 10_000.0);
+        assert_eq!(fees.tier, 0);
+        assert!(close(fees.total, 30.0));
+    }
+}
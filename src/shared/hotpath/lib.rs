@@ -0,0 +1,156 @@
+/*
+ * QuantumArb 2.0 - Shared: Low-Latency Runtime Mode
+ *
+ * File: src/shared/hotpath/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-hotpath`) provides the optional low-latency
+ * runtime used by latency-sensitive deployments. Instead of running the
+ * market-data consumer and order-submission paths as tokio tasks woken by
+ * timers, each path gets a dedicated OS thread pinned to an isolated core,
+ * busy-polling a bounded lock-free queue. This trades a fully burnt core per
+ * path for the removal of scheduler wake-up jitter.
+ *
+ * Configuration (environment):
+ *   QA_RUNTIME_MODE=standard|low-latency   (default: standard)
+ *   QA_PINNED_CORES=2,3                    cores for the hot-path threads, in
+ *                                          the order the service spawns them
+ *   QA_HOTPATH_QUEUE_CAPACITY=1024         bound of each hot-path queue
+ *
+ * The cores should be excluded from the general scheduler (isolcpus /
+ * Kubernetes static CPU manager with integer CPU requests).
+ */
+
+use crossbeam_queue::ArrayQueue;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LowLatencyConfig {
+    pub cores: Vec<usize>,
+    pub queue_capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeMode {
+    /// Everything runs on the tokio runtime.
+    Standard,
+    /// Hot paths run on pinned, busy-polling threads.
+    LowLatency(LowLatencyConfig),
+}
+
+impl RuntimeMode {
+    pub fn from_env() -> RuntimeMode {
+        if std::env::var("QA_RUNTIME_MODE").as_deref() != Ok("low-latency") {
+            return RuntimeMode::Standard;
+        }
+        let cores = std::env::var("QA_PINNED_CORES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|c| c.trim().parse().ok())
+            .collect();
+        let queue_capacity = std::env::var("QA_HOTPATH_QUEUE_CAPACITY")
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .filter(|c| *c > 0)
+            .unwrap_or(DEFAULT_QUEUE_CAPACITY);
+        RuntimeMode::LowLatency(LowLatencyConfig { cores, queue_capacity })
+    }
+}
+
+impl LowLatencyConfig {
+    /// The core for the n-th hot-path thread, if one was configured.
+    pub fn core(&self, index: usize) -> Option<usize> {
+        self.cores.get(index).copied()
+    }
+}
+
+// --- Bounded Queue ---
+
+/// A bounded, lock-free MPMC queue shared between a producer (often a tokio
+/// task) and a busy-polling hot-path thread. Pushing never blocks: a full
+/// queue hands the item back so the caller picks the overflow behaviour.
+pub struct HotQueue<T> {
+    inner: Arc<ArrayQueue<T>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<T> Clone for HotQueue<T> {
+    fn clone(&self) -> Self {
+        HotQueue { inner: self.inner.clone(), dropped: self.dropped.clone() }
+    }
+}
+
+impl<T> HotQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        HotQueue { inner: Arc::new(ArrayQueue::new(capacity)), dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    pub fn push(&self, item: T) -> Result<(), T> {
+        self.inner.push(item).inspect_err(|_| {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.inner.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Number of pushes refused because the queue was full.
+    pub fn rejected_pushes(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// --- Pinned Threads ---
+
+/// Spawns a named OS thread, pinning it to `core` when given. Pinning
+/// failures are reported but not fatal, so the same build runs on a laptop.
+pub fn spawn_pinned<F>(name: &str, core: Option<usize>, f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let thread_name = name.to_string();
+    std::thread::Builder::new()
+        .name(thread_name.clone())
+        .spawn(move || {
+            if let Some(core) = core {
+                if core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+                    println!("[{}] pinned to core {}", thread_name, core);
+                } else {
+                    println!("[{}] WARNING: could not pin to core {}", thread_name, core);
+                }
+            }
+            f();
+        })
+        .expect("failed to spawn hot-path thread")
+}
+
+/// Drains `queue` forever on the current thread, spinning when it is empty.
+/// Returns once `running` is cleared and the queue has been drained.
+pub fn busy_poll<T>(queue: &HotQueue<T>, running: &AtomicBool, mut handler: impl FnMut(T)) {
+    loop {
+        match queue.pop() {
+            Some(item) => handler(item),
+            None => {
+                if !running.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
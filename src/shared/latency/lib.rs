@@ -0,0 +1,139 @@
+/*
+ * QuantumArb 2.0 - Shared: Tick-to-Trade Latency Histograms
+ *
+ * File: src/shared/latency/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-latency`) aggregates the `HopStamps`
+ * carried by order-path messages (see `quantumarb-wire`) into HDR histograms,
+ * one per hop-to-hop segment plus the end-to-end tick-to-trade segment:
+ *
+ *   market_data_receive -> strategy_decision -> risk_decision
+ *                       -> gateway_send -> execution_receive
+ *
+ * Services that see completed messages call `LatencyRecorder::record` and
+ * serve `LatencyRecorder::summary` on their /latency endpoint.
+ */
+
+use hdrhistogram::Histogram;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::{Hop, HopStamps};
+use serde::Serialize;
+
+/// Largest latency tracked (60s); anything slower is clamped to it.
+const MAX_TRACKABLE_NS: u64 = 60_000_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+// --- Data Structures ---
+
+/// Percentiles for one segment, in nanoseconds.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct SegmentSummary {
+    pub from: Hop,
+    pub to: Hop,
+    pub count: u64,
+    pub min_ns: u64,
+    pub mean_ns: f64,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+}
+
+/// Body of a /latency response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct LatencySummary {
+    pub segments: Vec<SegmentSummary>,
+    pub tick_to_trade: SegmentSummary,
+    /// Messages with a hop stamped earlier than its predecessor (clock skew).
+    pub discarded: u64,
+}
+
+struct Segment {
+    from: Hop,
+    to: Hop,
+    histogram: Histogram<u64>,
+}
+
+impl Segment {
+    fn new(from: Hop, to: Hop) -> Segment {
+        Segment {
+            from,
+            to,
+            histogram: Histogram::new_with_bounds(1, MAX_TRACKABLE_NS, SIGNIFICANT_DIGITS)
+                .expect("histogram bounds are valid"),
+        }
+    }
+
+    /// Returns false if both hops are stamped but out of order.
+    fn record(&mut self, stamps: &HopStamps) -> bool {
+        let (Some(from_ns), Some(to_ns)) = (stamps.get(self.from), stamps.get(self.to)) else {
+            return true;
+        };
+        if to_ns < from_ns {
+            return false;
+        }
+        self.histogram.saturating_record((to_ns - from_ns).max(1));
+        true
+    }
+
+    fn summary(&self) -> SegmentSummary {
+        let h = &self.histogram;
+        SegmentSummary {
+            from: self.from,
+            to: self.to,
+            count: h.len(),
+            min_ns: h.min(),
+            mean_ns: h.mean(),
+            p50_ns: h.value_at_quantile(0.50),
+            p90_ns: h.value_at_quantile(0.90),
+            p99_ns: h.value_at_quantile(0.99),
+            p999_ns: h.value_at_quantile(0.999),
+            max_ns: h.max(),
+        }
+    }
+}
+
+// --- Recorder ---
+
+pub struct LatencyRecorder {
+    segments: Vec<Segment>,
+    tick_to_trade: Segment,
+    discarded: u64,
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        LatencyRecorder::new()
+    }
+}
+
+impl LatencyRecorder {
+    pub fn new() -> LatencyRecorder {
+        LatencyRecorder {
+            segments: Hop::ALL.windows(2).map(|pair| Segment::new(pair[0], pair[1])).collect(),
+            tick_to_trade: Segment::new(Hop::MarketDataReceive, Hop::ExecutionReceive),
+            discarded: 0,
+        }
+    }
+
+    /// Adds every segment whose two hops are stamped in `stamps`.
+    pub fn record(&mut self, stamps: &HopStamps) {
+        let mut in_order = self.tick_to_trade.record(stamps);
+        for segment in &mut self.segments {
+            in_order &= segment.record(stamps);
+        }
+        if !in_order {
+            self.discarded += 1;
+        }
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            segments: self.segments.iter().map(Segment::summary).collect(),
+            tick_to_trade: self.tick_to_trade.summary(),
+            discarded: self.discarded,
+        }
+    }
+}
@@ -0,0 +1,491 @@
+/*
+ * QuantumArb 2.0 - Shared: Fixed-Point Prices and Money
+ *
+ * File: src/shared/money/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-money`) defines the decimal types every
+ * order, fill, P&L and risk calculation is done in, so amounts never pass
+ * through binary floating point:
+ *
+ *   Price      a price in points, to six decimal places
+ *   Quantity   a signed whole number of units (positive = long or buy)
+ *   Money      a currency amount, to six decimal places
+ *
+ * On the wire a price is an integer in its instrument's own scale:
+ * `price_decimals` in the reference data (2 for every built-in instrument,
+ * so 60150_00 is 60,150.00). `Price::from_wire` and `Price::to_wire` convert
+ * at that scale; nothing else should multiply or divide by 100. A wire
+ * price too large for a `Price`, or a scale finer than six decimals, is a
+ * `WireError`.
+ *
+ * Price x Quantity is exact. A notional is price x quantity x the
+ * instrument's multiplier (the Money value of a one-point move per unit),
+ * rounded half away from zero to the micro-unit only when the multiplier has
+ * a fractional part. `Money::from_f64` and `Price::from_f64` are for the edges
+ * only: configuration, statistical models and JSON.
+ *
+ * In JSON both types are plain decimal numbers, so HTTP bodies, persisted
+ * state and reference data files read the same as they did with f64 fields.
+ */
+
+use quantumarb_openapi::{schema, ApiSchema, Components, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// Decimal places held by `Price` and `Money`; the finest an instrument's
+/// price scale may be.
+pub const DECIMALS: u8 = 6;
+const ONE: i64 = 1_000_000;
+
+/// Why a wire price has no `Price`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    /// The scale is finer than DECIMALS.
+    ScaleTooFine { decimals: u8 },
+    /// The price is beyond the largest `Price`.
+    OutOfRange { raw: u64, decimals: u8 },
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::ScaleTooFine { decimals } => {
+                write!(f, "price scale {} is finer than {} decimals", decimals, DECIMALS)
+            }
+            WireError::OutOfRange { raw, decimals } => {
+                write!(f, "wire price {} at {} decimals is out of range", raw, decimals)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+/// 10^(DECIMALS - decimals): the micro-units in one wire unit at `decimals`.
+fn wire_unit(decimals: u8) -> Result<i64, WireError> {
+    if decimals > DECIMALS {
+        return Err(WireError::ScaleTooFine { decimals });
+    }
+    Ok(10i64.pow((DECIMALS - decimals) as u32))
+}
+
+/// n / d, rounded half away from zero.
+fn div_round(n: i128, d: i128) -> i128 {
+    let (q, r) = (n / d, n % d);
+    if 2 * r.abs() >= d.abs() {
+        q + (n.signum() * d.signum())
+    } else {
+        q
+    }
+}
+
+fn micros_from_f64(value: f64) -> i64 {
+    (value * ONE as f64).round() as i64
+}
+
+// --- Price ---
+
+/// A price in points, held in millionths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(i64);
+
+impl Price {
+    pub const ZERO: Price = Price(0);
+    pub const MAX: Price = Price(i64::MAX);
+
+    pub const fn from_micros(micros: i64) -> Price {
+        Price(micros)
+    }
+
+    pub fn micros(self) -> i64 {
+        self.0
+    }
+
+    /// A wire price: an integer count of 10^-decimals.
+    pub fn from_wire(raw: u64, decimals: u8) -> Result<Price, WireError> {
+        let unit = wire_unit(decimals)?;
+        i64::try_from(raw)
+            .ok()
+            .and_then(|raw| raw.checked_mul(unit))
+            .map(Price)
+            .ok_or(WireError::OutOfRange { raw, decimals })
+    }
+
+    /// The wire form at `decimals`, or None if the price is negative or
+    /// finer than that scale.
+    pub fn to_wire(self, decimals: u8) -> Option<u64> {
+        let unit = wire_unit(decimals).ok()?;
+        if self.0 < 0 || self.0 % unit != 0 {
+            return None;
+        }
+        Some((self.0 / unit) as u64)
+    }
+
+    /// Rounds to the nearest millionth.
+    pub fn from_f64(value: f64) -> Price {
+        Price(micros_from_f64(value))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / ONE as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether the price lies on the grid of `tick`.
+    pub fn is_multiple_of(self, tick: Price) -> bool {
+        tick.0 != 0 && self.0 % tick.0 == 0
+    }
+
+    /// The nearest multiple of `tick`, halves away from zero.
+    pub fn round_to(self, tick: Price) -> Price {
+        Price(div_round(self.0 as i128, tick.0 as i128) as i64 * tick.0)
+    }
+
+    /// The price scaled by a ratio (a split's price factor), rounded to the
+    /// nearest millionth.
+    pub fn scaled(self, factor: f64) -> Price {
+        Price((self.0 as f64 * factor).round() as i64)
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+    fn add(self, other: Price) -> Price {
+        Price(self.0 + other.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+    fn sub(self, other: Price) -> Price {
+        Price(self.0 - other.0)
+    }
+}
+
+impl Neg for Price {
+    type Output = Price;
+    fn neg(self) -> Price {
+        Price(-self.0)
+    }
+}
+
+/// Value at one currency unit per point; exact.
+impl Mul<Quantity> for Price {
+    type Output = Money;
+    fn mul(self, quantity: Quantity) -> Money {
+        Money(self.0 * quantity.0)
+    }
+}
+
+// --- Quantity ---
+
+/// A signed whole number of units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(transparent)]
+pub struct Quantity(pub i64);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0);
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn signum(self) -> i64 {
+        self.0.signum()
+    }
+
+    pub fn abs(self) -> Quantity {
+        Quantity(self.0.abs())
+    }
+}
+
+impl From<i64> for Quantity {
+    fn from(units: i64) -> Quantity {
+        Quantity(units)
+    }
+}
+
+impl From<u32> for Quantity {
+    fn from(units: u32) -> Quantity {
+        Quantity(units as i64)
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity(self.0 + other.0)
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity(self.0 - other.0)
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Quantity;
+    fn neg(self) -> Quantity {
+        Quantity(-self.0)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// --- Money ---
+
+/// A currency amount, held in millionths of the currency unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_micros(micros: i64) -> Money {
+        Money(micros)
+    }
+
+    pub fn micros(self) -> i64 {
+        self.0
+    }
+
+    /// Rounds to the nearest millionth.
+    pub fn from_f64(value: f64) -> Money {
+        Money(micros_from_f64(value))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / ONE as f64
+    }
+
+    /// price x quantity x multiplier, where `multiplier` is the value of a
+    /// one-point move for one unit.
+    pub fn notional(price: Price, quantity: Quantity, multiplier: Money) -> Money {
+        (price * quantity).at_multiplier(multiplier)
+    }
+
+    /// A per-point amount (a P&L in points x quantity) in currency, at
+    /// `multiplier` per point.
+    pub fn at_multiplier(self, multiplier: Money) -> Money {
+        Money(div_round(self.0 as i128 * multiplier.0 as i128, ONE as i128) as i64)
+    }
+
+    /// The share `part / whole` of this amount, rounded to the nearest
+    /// millionth.
+    pub fn pro_rata(self, part: Quantity, whole: Quantity) -> Money {
+        Money(div_round(self.0 as i128 * part.0 as i128, whole.0 as i128) as i64)
+    }
+
+    /// The price at which `quantity` units are worth this amount, rounded to
+    /// the nearest millionth; zero for no quantity.
+    pub fn per_unit(self, quantity: Quantity) -> Price {
+        if quantity.is_zero() {
+            return Price::ZERO;
+        }
+        Price(div_round(self.0 as i128, quantity.0 as i128) as i64)
+    }
+
+    /// The amount scaled by a ratio (a limit multiplier, a fraction of a
+    /// position), rounded to the nearest millionth.
+    pub fn scaled(self, factor: f64) -> Money {
+        Money((self.0 as f64 * factor).round() as i64)
+    }
+
+    /// This amount as a fraction of `total`.
+    pub fn ratio(self, total: Money) -> f64 {
+        self.0 as f64 / total.0 as f64
+    }
+
+    pub fn abs(self) -> Money {
+        Money(self.0.abs())
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.0 -= other.0;
+    }
+}
+
+/// An amount per unit, times a number of units; exact.
+impl Mul<Quantity> for Money {
+    type Output = Money;
+    fn mul(self, quantity: Quantity) -> Money {
+        Money(self.0 * quantity.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+// --- Formatting and JSON ---
+
+// Display goes through f64 so format precision works as it always has
+// ({:.2} for dollars); the six stored decimals survive the round trip.
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Price, D::Error> {
+        f64::deserialize(deserializer).map(Price::from_f64)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        f64::deserialize(deserializer).map(Money::from_f64)
+    }
+}
+
+impl ApiSchema for Price {
+    fn schema(components: &mut Components) -> Value {
+        schema::describe(f64::schema(components), Some("A price in points, to six decimal places"))
+    }
+}
+
+impl ApiSchema for Money {
+    fn schema(components: &mut Components) -> Value {
+        schema::describe(f64::schema(components), Some("A currency amount, to six decimal places"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_round_takes_halves_away_from_zero() {
+        assert_eq!([div_round(7, 2), div_round(-7, 2), div_round(7, -2), div_round(-7, -2)], [4, -4, -4, 4]);
+        assert_eq!([div_round(5, 3), div_round(-5, 3), div_round(4, 3), div_round(-4, 3)], [2, -2, 1, -1]);
+        assert_eq!([div_round(6, 2), div_round(-6, 2), div_round(0, 5)], [3, -3, 0]);
+    }
+
+    #[test]
+    fn round_to_snaps_to_the_nearest_tick() {
+        let tick = Price::from_f64(0.25);
+        assert_eq!(Price::from_f64(100.12).round_to(tick), Price::from_f64(100.0));
+        assert_eq!(Price::from_f64(100.125).round_to(tick), Price::from_f64(100.25), "a half goes up");
+        assert_eq!(Price::from_f64(-100.125).round_to(tick), Price::from_f64(-100.25), "and away from zero");
+        assert_eq!(Price::from_f64(100.5).round_to(tick), Price::from_f64(100.5));
+    }
+
+    #[test]
+    fn wire_prices_round_trip_at_every_scale() {
+        for decimals in 0..=DECIMALS {
+            let price = Price::from_wire(6_015_000, decimals).unwrap();
+            assert_eq!(price.micros(), 6_015_000 * 10i64.pow((DECIMALS - decimals) as u32));
+            assert_eq!(price.to_wire(decimals), Some(6_015_000), "at {} decimals", decimals);
+        }
+        assert_eq!(Price::from_f64(60_150.5).to_wire(0), None, "finer than the scale");
+        assert_eq!(Price::from_f64(-1.0).to_wire(2), None, "negative");
+        assert_eq!(Price::from_f64(1.0).to_wire(7), None);
+    }
+
+    #[test]
+    fn from_wire_rejects_what_a_price_cannot_hold() {
+        assert_eq!(Price::from_wire(1, 7), Err(WireError::ScaleTooFine { decimals: 7 }));
+        assert_eq!(Price::from_wire(u64::MAX, 6), Err(WireError::OutOfRange { raw: u64::MAX, decimals: 6 }));
+        let largest = i64::MAX as u64 / 10_000;
+        assert_eq!(Price::from_wire(largest, 2).map(Price::micros), Ok(largest as i64 * 10_000));
+        assert_eq!(Price::from_wire(largest + 1, 2), Err(WireError::OutOfRange { raw: largest + 1, decimals: 2 }));
+    }
+
+    #[test]
+    fn at_multiplier_rounds_to_the_micro_unit() {
+        let pnl = Price::from_f64(1.5) * Quantity(3);
+        assert_eq!(pnl.at_multiplier(Money::from_f64(50.0)), Money::from_f64(225.0));
+        assert_eq!(Money::from_micros(3).at_multiplier(Money::from_f64(0.5)), Money::from_micros(2));
+        assert_eq!(Money::from_micros(-3).at_multiplier(Money::from_f64(0.5)), Money::from_micros(-2));
+        let notional = Money::notional(Price::from_f64(4_500.25), Quantity(-2), Money::from_f64(50.0));
+        assert_eq!(notional, Money::from_f64(-450_025.0));
+    }
+
+    #[test]
+    fn pro_rata_and_per_unit_round_half_away_from_zero() {
+        let fee = Money::from_micros(10);
+        assert_eq!(fee.pro_rata(Quantity(1), Quantity(4)), Money::from_micros(3), "2.5 rounds up");
+        assert_eq!((-fee).pro_rata(Quantity(1), Quantity(4)), Money::from_micros(-3));
+        assert_eq!(fee.pro_rata(Quantity(3), Quantity(3)), fee);
+
+        let cost = Money::from_f64(301.0);
+        assert_eq!(cost.per_unit(Quantity(3)), Price::from_micros(100_333_333));
+        assert_eq!(Money::from_micros(5).per_unit(Quantity(2)), Price::from_micros(3));
+        assert_eq!(Money::from_micros(-5).per_unit(Quantity(2)), Price::from_micros(-3));
+        assert_eq!(cost.per_unit(Quantity::ZERO), Price::ZERO);
+    }
+}
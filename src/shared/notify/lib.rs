@@ -0,0 +1,423 @@
+/*
+ * QuantumArb 2.0 - Shared: Notifications
+ *
+ * File: src/shared/notify/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-notify`) tells people about the events
+ * that need one: the kill switch being engaged, a VaR breach, an account
+ * locked out by its daily loss limit, a compliance alert, the market data
+ * feed going down, host clocks drifting apart. Services raise a `Notification`
+ * with the event's details; the `Notifier` renders it through the class's
+ * template and delivers it to every route configured for the class:
+ *
+ *   webhook  POST of the rendered notification as JSON
+ *   slack    POST of {"text": ...} to a Slack incoming webhook
+ *   email    POST of {from, to, subject, body} to an HTTP mail relay
+ *
+ * Templates are text with {field} placeholders, filled from the
+ * notification's fields plus {class}, {source} and {timestamp_utc}; unknown
+ * placeholders are left as they are. Each class has a built-in template,
+ * which the configuration can replace for all routes or for one.
+ *
+ * Delivery runs in the background and is retried with exponential backoff
+ * on connection errors, 5xx and 429 responses; other 4xx responses are not
+ * retried. A notification is never allowed to hold up the event that raised
+ * it.
+ *
+ * Configuration: QA_NOTIFY_CONFIG_PATH names a JSON file such as
+ *
+ *   {
+ *     "routes": [
+ *       { "classes": ["KILL_SWITCH_ENGAGED", "VAR_BREACH"],
+ *         "channel": { "type": "slack", "webhook_url": "https://hooks.slack.com/..." } },
+ *       { "classes": ["COMPLIANCE_ALERT"],
+ *         "channel": { "type": "email", "relay_url": "http://mail-relay/send",
+ *                      "from": "surveillance@quantumarb", "to": ["compliance@quantumarb"] } },
+ *       { "classes": [], "channel": { "type": "webhook", "url": "http://incidents/hook" } }
+ *     ],
+ *     "templates": { "FEED_DOWN": { "subject": "Feed down", "body": "{detail}" } },
+ *     "retry": { "max_attempts": 5, "initial_backoff_ms": 500 }
+ *   }
+ *
+ * A route with no classes receives every class. Without a configuration
+ * notifications are only logged.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EventClass {
+    KillSwitchEngaged,
+    VarBreach,
+    DailyLossLockout,
+    ComplianceAlert,
+    FeedDown,
+    ClockSkew,
+}
+
+/// An event to notify about, with the details its template refers to.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct Notification {
+    pub class: EventClass,
+    /// The service that raised it.
+    pub source: String,
+    pub fields: BTreeMap<String, String>,
+    pub timestamp_utc: String,
+}
+
+impl Notification {
+    pub fn new(class: EventClass, source: &str) -> Notification {
+        Notification {
+            class,
+            source: source.to_string(),
+            fields: BTreeMap::new(),
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn field(mut self, name: &str, value: impl Display) -> Notification {
+        self.fields.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Channel {
+    Webhook {
+        url: String,
+    },
+    Slack {
+        webhook_url: String,
+        #[serde(default)]
+        channel: Option<String>,
+    },
+    Email {
+        relay_url: String,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct Route {
+    /// Classes delivered on this route; empty for all of them.
+    #[serde(default)]
+    pub classes: Vec<EventClass>,
+    pub channel: Channel,
+    /// Replaces the class's template on this route.
+    #[serde(default)]
+    pub template: Option<Template>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ApiSchema)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 5, initial_backoff_ms: 500 }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (1 for the first retry).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(1 << (retry - 1).min(16)))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Replaces the built-in template of a class on every route.
+    #[serde(default)]
+    pub templates: HashMap<EventClass, Template>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+impl NotifyConfig {
+    /// Reads QA_NOTIFY_CONFIG_PATH; no routes if it is unset or unreadable.
+    pub fn from_env() -> NotifyConfig {
+        let Ok(path) = std::env::var("QA_NOTIFY_CONFIG_PATH") else {
+            return NotifyConfig::default();
+        };
+        match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("  -> Failed to load notification routes from {}: {}", path, e);
+                NotifyConfig::default()
+            }
+        }
+    }
+
+    /// The routes for `class`, each with the template to render it with.
+    pub fn routes_for(&self, class: EventClass) -> Vec<(&Route, Template)> {
+        self.routes
+            .iter()
+            .filter(|route| route.classes.is_empty() || route.classes.contains(&class))
+            .map(|route| {
+                let template = route.template.clone().or_else(|| self.templates.get(&class).cloned());
+                (route, template.unwrap_or_else(|| default_template(class)))
+            })
+            .collect()
+    }
+}
+
+/// Delivery counters, for the services' stats endpoints.
+#[derive(Debug, Clone, Copy, Default, Serialize, ApiSchema)]
+pub struct NotifierStats {
+    pub raised: u64,
+    pub delivered: u64,
+    pub retries: u64,
+    pub failed: u64,
+}
+
+// --- Templates ---
+
+pub fn default_template(class: EventClass) -> Template {
+    let (subject, body) = match class {
+        EventClass::KillSwitchEngaged => (
+            "Kill switch engaged",
+            "Trading was halted by the kill switch at {timestamp_utc}. Reason: {reason}",
+        ),
+        EventClass::VarBreach => (
+            "VaR breach: {var_amount}",
+            "99% VaR of {var_amount} is over its limit ({limit}) on a portfolio of {portfolio_value}. Action: {action}",
+        ),
+        EventClass::DailyLossLockout => (
+            "Account {account_id} locked out: daily loss {daily_pnl}",
+            "Account {account_id} lost {daily_pnl} today, past its daily loss limit of {limit}. \
+             Orders that add to its positions are blocked until {until}.",
+        ),
+        EventClass::ComplianceAlert => (
+            "Compliance alert: {pattern} ({severity})",
+            "{description} Strategy {strategy_id}, {occurrences} occurrence(s). Alert {alert_id}.",
+        ),
+        EventClass::FeedDown => ("Market data feed down", "{detail}"),
+        EventClass::ClockSkew => ("Clock skew: {scope} is {level}", "{reason}"),
+    };
+    Template { subject: subject.to_string(), body: body.to_string() }
+}
+
+/// Fills `{name}` placeholders from the notification.
+pub fn render(text: &str, notification: &Notification) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        let value = match name {
+            "class" => Some(class_name(notification.class)),
+            "source" => Some(notification.source.clone()),
+            "timestamp_utc" => Some(notification.timestamp_utc.clone()),
+            _ => notification.fields.get(name).cloned(),
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn class_name(class: EventClass) -> String {
+    serde_json::to_value(class).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+// --- Notifier ---
+
+/// Renders notifications and delivers them on the configured routes.
+#[derive(Clone)]
+pub struct Notifier {
+    source: String,
+    config: Arc<NotifyConfig>,
+    http_client: reqwest::Client,
+    stats: Arc<Mutex<NotifierStats>>,
+}
+
+impl Notifier {
+    pub fn new(source: &str, config: NotifyConfig) -> Notifier {
+        Notifier {
+            source: source.to_string(),
+            config: Arc::new(config),
+            http_client: reqwest::Client::new(),
+            stats: Arc::new(Mutex::new(NotifierStats::default())),
+        }
+    }
+
+    /// A notifier for service `source`, routed by QA_NOTIFY_CONFIG_PATH.
+    pub fn from_env(source: &str) -> Notifier {
+        Notifier::new(source, NotifyConfig::from_env())
+    }
+
+    /// A notification of `class` from this service.
+    pub fn event(&self, class: EventClass) -> Notification {
+        Notification::new(class, &self.source)
+    }
+
+    /// Delivers the notification on every route for its class, in the
+    /// background. Must be called from within a Tokio runtime.
+    pub fn notify(&self, notification: Notification) {
+        self.stats.lock().unwrap().raised += 1;
+        let routes = self.config.routes_for(notification.class);
+        let template = self.config.templates.get(&notification.class).cloned();
+        let subject = render(&template.unwrap_or_else(|| default_template(notification.class)).subject, &notification);
+        println!("  -> Notification {:?}: {} ({} route(s))", notification.class, subject, routes.len());
+        for (route, template) in routes {
+            let request = Delivery {
+                channel: route.channel.clone(),
+                subject: render(&template.subject, &notification),
+                body: render(&template.body, &notification),
+                notification: notification.clone(),
+            };
+            let (client, retry, stats) = (self.http_client.clone(), self.config.retry, self.stats.clone());
+            tokio::spawn(async move {
+                request.send_with_retry(&client, retry, &stats).await;
+            });
+        }
+    }
+
+    pub fn stats(&self) -> NotifierStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+/// One rendered notification on its way to one channel.
+struct Delivery {
+    channel: Channel,
+    subject: String,
+    body: String,
+    notification: Notification,
+}
+
+impl Delivery {
+    async fn send_with_retry(&self, client: &reqwest::Client, retry: RetryPolicy, stats: &Mutex<NotifierStats>) {
+        let (url, payload) = self.request();
+        for attempt in 1..=retry.max_attempts.max(1) {
+            if attempt > 1 {
+                stats.lock().unwrap().retries += 1;
+                tokio::time::sleep(retry.backoff(attempt - 1)).await;
+            }
+            let error = match client.post(url).json(&payload).send().await {
+                Ok(response) if response.status().is_success() => {
+                    stats.lock().unwrap().delivered += 1;
+                    return;
+                }
+                Ok(response) if !is_retryable(response.status().as_u16()) => {
+                    println!("  -> Notification to {} refused: {}", url, response.status());
+                    break;
+                }
+                Ok(response) => response.status().to_string(),
+                Err(e) => e.to_string(),
+            };
+            println!("  -> Notification to {} failed (attempt {}): {}", url, attempt, error);
+        }
+        stats.lock().unwrap().failed += 1;
+    }
+
+    /// Where the channel is posted to, and the body it expects.
+    fn request(&self) -> (&str, serde_json::Value) {
+        match &self.channel {
+            Channel::Webhook { url } => (
+                url,
+                serde_json::json!({
+                    "class": self.notification.class,
+                    "source": self.notification.source,
+                    "subject": self.subject,
+                    "body": self.body,
+                    "fields": self.notification.fields,
+                    "timestamp_utc": self.notification.timestamp_utc,
+                }),
+            ),
+            Channel::Slack { webhook_url, channel } => {
+                let mut payload = serde_json::json!({ "text": format!("*{}*\n{}", self.subject, self.body) });
+                if let Some(channel) = channel {
+                    payload["channel"] = serde_json::json!(channel);
+                }
+                (webhook_url, payload)
+            }
+            Channel::Email { relay_url, from, to } => (
+                relay_url,
+                serde_json::json!({ "from": from, "to": to, "subject": self.subject, "body": self.body }),
+            ),
+        }
+    }
+}
+
+/// Whether a failed delivery is worth repeating: server errors and rate
+/// limiting, not a request the receiver rejected.
+fn is_retryable(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_fields_and_leaves_unknown_placeholders() {
+        let notification = Notification::new(EventClass::KillSwitchEngaged, "risk-gateway").field("reason", "fat finger");
+        let text = render("{class} from {source}: {reason} {missing} {", &notification);
+        assert_eq!(text, "KILL_SWITCH_ENGAGED from risk-gateway: fat finger {missing} {");
+    }
+
+    #[test]
+    fn routes_by_class_with_the_most_specific_template() {
+        let config: NotifyConfig = serde_json::from_str(
+            r#"{
+                "routes": [
+                    { "classes": ["VAR_BREACH"], "channel": { "type": "slack", "webhook_url": "http://slack" } },
+                    { "classes": [], "channel": { "type": "webhook", "url": "http://hook" },
+                      "template": { "subject": "[{class}]", "body": "{source}" } }
+                ],
+                "templates": { "VAR_BREACH": { "subject": "VaR {var_amount}", "body": "" } }
+            }"#,
+        )
+        .unwrap();
+        let routes = config.routes_for(EventClass::VarBreach);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].1.subject, "VaR {var_amount}");
+        assert_eq!(routes[1].1.subject, "[{class}]");
+        let routes = config.routes_for(EventClass::FeedDown);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].0.channel, Channel::Webhook { url: "http://hook".to_string() });
+        assert_eq!(config.retry.max_attempts, 5);
+    }
+
+    #[test]
+    fn backs_off_exponentially_and_retries_only_transient_failures() {
+        let retry = RetryPolicy { max_attempts: 4, initial_backoff_ms: 100 };
+        assert_eq!(retry.backoff(1), Duration::from_millis(100));
+        assert_eq!(retry.backoff(3), Duration::from_millis(400));
+        assert!(is_retryable(503) && is_retryable(429));
+        assert!(!is_retryable(400) && !is_retryable(404));
+    }
+}
@@ -0,0 +1,523 @@
+/*
+ * QuantumArb 2.0 - Shared: OpenAPI Documents
+ *
+ * File: src/shared/openapi/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-openapi`) lets every HTTP service describe
+ * its API as an OpenAPI 3.1 document generated from the types it actually
+ * serves. Request and response types derive `ApiSchema`, which reads their
+ * serde attributes (see `quantumarb-openapi-derive`), so the documented JSON
+ * is the serialized JSON; a service lists its routes once, next to its warp
+ * filters:
+ *
+ *   let doc = ApiDoc::new("VaR Calculator", "...")
+ *       .get::<VaRResult>("/var", "The latest VaR")
+ *       .operation(Operation::get("/var/history", "VaR over time").query::<HistoryQuery>().response::<VaRHistory>());
+ *   warp::serve(routes.or(quantumarb_openapi::routes(doc)))
+ *
+ * and serves GET /openapi.json (the document) and GET /docs (Swagger UI).
+ *
+ * Named types become components referenced by name; generic types are
+ * inlined. Two types with the same name in one document are told apart by
+ * their module path.
+ */
+
+pub mod schema;
+
+// The derive names paths through the crate, here as anywhere else.
+extern crate self as quantumarb_openapi;
+
+pub use quantumarb_openapi_derive::ApiSchema;
+pub use serde_json::Value;
+
+use serde_json::{json, Map};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use warp::Filter;
+
+/// Swagger UI, loaded from the public CDN, pointed at the document next to it.
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>QuantumArb API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
+
+// --- Schemas ---
+
+/// A type with a JSON schema. Derive it with `#[derive(ApiSchema)]` next to
+/// `Serialize`/`Deserialize`.
+pub trait ApiSchema {
+    /// The type's schema; named types register themselves in `components`
+    /// and return a reference to it.
+    fn schema(components: &mut Components) -> Value;
+}
+
+/// The named schemas of one document.
+#[derive(Debug, Default)]
+pub struct Components {
+    schemas: BTreeMap<String, Value>,
+    /// The Rust type behind each name.
+    owners: BTreeMap<String, &'static str>,
+}
+
+impl Components {
+    /// A reference to the component `name`, building it on first use.
+    pub fn reference(&mut self, name: &str, type_name: &'static str, build: impl FnOnce(&mut Self) -> Value) -> Value {
+        let key = match self.owners.get(name) {
+            Some(owner) if *owner != type_name => type_name.replace("::", "."),
+            _ => name.to_string(),
+        };
+        if !self.schemas.contains_key(&key) {
+            self.owners.insert(key.clone(), type_name);
+            // Placeholder, so a recursive type refers to itself instead of recursing.
+            self.schemas.insert(key.clone(), Value::Null);
+            let schema = build(self);
+            self.schemas.insert(key.clone(), schema);
+        }
+        json!({ "$ref": format!("#/components/schemas/{}", key) })
+    }
+
+    /// The schema behind a reference (or the schema itself).
+    pub fn resolve<'a>(&'a self, schema: &'a Value) -> &'a Value {
+        schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix("#/components/schemas/"))
+            .and_then(|name| self.schemas.get(name))
+            .unwrap_or(schema)
+    }
+}
+
+// --- Operations ---
+
+/// One documented route.
+pub struct Operation {
+    method: &'static str,
+    path: String,
+    summary: String,
+    parameters: Vec<Parameter>,
+    query: Option<fn(&mut Components) -> Value>,
+    body: Option<fn(&mut Components) -> Value>,
+    response: Option<fn(&mut Components) -> Value>,
+    status: u16,
+}
+
+struct Parameter {
+    name: String,
+    schema: fn(&mut Components) -> Value,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &str, summary: &str) -> Operation {
+        Operation {
+            method,
+            path: path.to_string(),
+            summary: summary.to_string(),
+            parameters: Vec::new(),
+            query: None,
+            body: None,
+            response: None,
+            status: 200,
+        }
+    }
+
+    pub fn get(path: &str, summary: &str) -> Operation {
+        Operation::new("get", path, summary)
+    }
+
+    pub fn post(path: &str, summary: &str) -> Operation {
+        Operation::new("post", path, summary)
+    }
+
+    pub fn put(path: &str, summary: &str) -> Operation {
+        Operation::new("put", path, summary)
+    }
+
+    pub fn delete(path: &str, summary: &str) -> Operation {
+        Operation::new("delete", path, summary)
+    }
+
+    /// The type of a `{name}` path segment (a string unless given).
+    pub fn path_param<T: ApiSchema>(mut self, name: &str) -> Operation {
+        self.parameters.push(Parameter { name: name.to_string(), schema: T::schema });
+        self
+    }
+
+    /// The query string, one parameter per field of `T`.
+    pub fn query<T: ApiSchema>(mut self) -> Operation {
+        self.query = Some(T::schema);
+        self
+    }
+
+    /// The JSON request body.
+    pub fn body<T: ApiSchema>(mut self) -> Operation {
+        self.body = Some(T::schema);
+        self
+    }
+
+    /// The JSON body of a successful response.
+    pub fn response<T: ApiSchema>(mut self) -> Operation {
+        self.response = Some(T::schema);
+        self
+    }
+
+    /// The status of a successful response (200 unless given).
+    pub fn status(mut self, status: u16) -> Operation {
+        self.status = status;
+        self
+    }
+
+    fn document(&self, components: &mut Components, error: Option<&Value>) -> Value {
+        let mut parameters = Vec::new();
+        for segment in self.path.split('/') {
+            let Some(name) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) else {
+                continue;
+            };
+            let schema = match self.parameters.iter().find(|p| p.name == name) {
+                Some(parameter) => (parameter.schema)(components),
+                None => json!({ "type": "string" }),
+            };
+            parameters.push(json!({ "name": name, "in": "path", "required": true, "schema": schema }));
+        }
+        if let Some(query) = self.query {
+            let schema = query(components);
+            let resolved = components.resolve(&schema);
+            let required: Vec<&str> = resolved
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|r| r.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            if let Some(properties) = resolved.get("properties").and_then(Value::as_object) {
+                for (name, schema) in properties {
+                    parameters.push(json!({
+                        "name": name,
+                        "in": "query",
+                        "required": required.contains(&name.as_str()),
+                        "schema": schema,
+                    }));
+                }
+            }
+        }
+
+        let mut operation = Map::new();
+        operation.insert("summary".to_string(), json!(self.summary));
+        if !parameters.is_empty() {
+            operation.insert("parameters".to_string(), Value::Array(parameters));
+        }
+        if let Some(body) = self.body {
+            let schema = body(components);
+            operation.insert(
+                "requestBody".to_string(),
+                json!({ "required": true, "content": { "application/json": { "schema": schema } } }),
+            );
+        }
+        let mut responses = Map::new();
+        let success = match self.response {
+            Some(response) => json!({
+                "description": "Success",
+                "content": { "application/json": { "schema": response(components) } },
+            }),
+            None => json!({ "description": "Success" }),
+        };
+        responses.insert(self.status.to_string(), success);
+        if let Some(error) = error {
+            responses.insert(
+                "default".to_string(),
+                json!({ "description": "Error", "content": { "application/json": { "schema": error } } }),
+            );
+        }
+        operation.insert("responses".to_string(), Value::Object(responses));
+        Value::Object(operation)
+    }
+}
+
+// --- Documents ---
+
+/// A service's OpenAPI document.
+pub struct ApiDoc {
+    title: String,
+    description: String,
+    operations: Vec<Operation>,
+    error: Option<fn(&mut Components) -> Value>,
+}
+
+impl ApiDoc {
+    pub fn new(title: &str, description: &str) -> ApiDoc {
+        ApiDoc { title: title.to_string(), description: description.to_string(), operations: Vec::new(), error: None }
+    }
+
+    /// The body of the service's error responses.
+    pub fn errors<T: ApiSchema>(mut self) -> ApiDoc {
+        self.error = Some(T::schema);
+        self
+    }
+
+    pub fn operation(mut self, operation: Operation) -> ApiDoc {
+        self.operations.push(operation);
+        self
+    }
+
+    /// GET `path`, answering `R`.
+    pub fn get<R: ApiSchema>(self, path: &str, summary: &str) -> ApiDoc {
+        self.operation(Operation::get(path, summary).response::<R>())
+    }
+
+    /// POST `path` with a `B` body, answering `R`.
+    pub fn post<B: ApiSchema, R: ApiSchema>(self, path: &str, summary: &str) -> ApiDoc {
+        self.operation(Operation::post(path, summary).body::<B>().response::<R>())
+    }
+
+    /// PUT `path` with a `B` body, answering `R`.
+    pub fn put<B: ApiSchema, R: ApiSchema>(self, path: &str, summary: &str) -> ApiDoc {
+        self.operation(Operation::put(path, summary).body::<B>().response::<R>())
+    }
+
+    /// The OpenAPI document.
+    pub fn json(&self) -> Value {
+        let mut components = Components::default();
+        let error = self.error.map(|error| error(&mut components));
+        let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+        for operation in &self.operations {
+            let document = operation.document(&mut components, error.as_ref());
+            paths.entry(operation.path.clone()).or_default().insert(operation.method.to_string(), document);
+        }
+        json!({
+            "openapi": "3.1.0",
+            "info": { "title": self.title, "description": self.description, "version": env!("CARGO_PKG_VERSION") },
+            "paths": paths,
+            "components": { "schemas": components.schemas },
+        })
+    }
+}
+
+/// GET /openapi.json and GET /docs for `doc`.
+pub fn routes(doc: ApiDoc) -> warp::filters::BoxedFilter<(Box<dyn warp::Reply>,)> {
+    let document = Arc::new(doc.json());
+    let openapi = warp::path!("openapi.json")
+        .and(warp::get())
+        .map(move || Box::new(warp::reply::json(&*document)) as Box<dyn warp::Reply>);
+    let docs = warp::path!("docs")
+        .and(warp::get())
+        .map(|| Box::new(warp::reply::html(SWAGGER_UI)) as Box<dyn warp::Reply>);
+    openapi.or(docs).unify().boxed()
+}
+
+// --- Schemas of Standard Types ---
+
+macro_rules! primitive {
+    ($($ty:ty => $schema:tt),* $(,)?) => {
+        $(impl ApiSchema for $ty {
+            fn schema(_: &mut Components) -> Value {
+                json!($schema)
+            }
+        })*
+    };
+}
+
+primitive! {
+    bool => { "type": "boolean" },
+    i8 => { "type": "integer", "format": "int8" },
+    i16 => { "type": "integer", "format": "int16" },
+    i32 => { "type": "integer", "format": "int32" },
+    i64 => { "type": "integer", "format": "int64" },
+    isize => { "type": "integer", "format": "int64" },
+    u8 => { "type": "integer", "format": "uint8", "minimum": 0 },
+    u16 => { "type": "integer", "format": "uint16", "minimum": 0 },
+    u32 => { "type": "integer", "format": "uint32", "minimum": 0 },
+    u64 => { "type": "integer", "format": "uint64", "minimum": 0 },
+    usize => { "type": "integer", "format": "uint64", "minimum": 0 },
+    f32 => { "type": "number", "format": "float" },
+    f64 => { "type": "number", "format": "double" },
+    char => { "type": "string", "minLength": 1, "maxLength": 1 },
+    String => { "type": "string" },
+    str => { "type": "string" },
+    () => { "type": "null" },
+    Value => {},
+    uuid::Uuid => { "type": "string", "format": "uuid" },
+    chrono::NaiveDate => { "type": "string", "format": "date" },
+    chrono::NaiveTime => { "type": "string", "format": "time" },
+    chrono::NaiveDateTime => { "type": "string", "format": "date-time" },
+    std::time::Duration => {
+        "type": "object",
+        "properties": { "secs": { "type": "integer" }, "nanos": { "type": "integer" } },
+        "required": ["secs", "nanos"]
+    },
+}
+
+impl<Tz: chrono::TimeZone> ApiSchema for chrono::DateTime<Tz> {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string", "format": "date-time" })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "anyOf": [T::schema(components), { "type": "null" }] })
+    }
+}
+
+macro_rules! sequence {
+    ($($ty:ident),*) => {
+        $(impl<T: ApiSchema> ApiSchema for std::collections::$ty<T> {
+            fn schema(components: &mut Components) -> Value {
+                json!({ "type": "array", "items": T::schema(components) })
+            }
+        })*
+    };
+}
+
+sequence!(VecDeque);
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components) })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for [T] {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components) })
+    }
+}
+
+impl<T: ApiSchema, const N: usize> ApiSchema for [T; N] {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components), "minItems": N, "maxItems": N })
+    }
+}
+
+impl<T: ApiSchema, S> ApiSchema for std::collections::HashSet<T, S> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components), "uniqueItems": true })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for std::collections::BTreeSet<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components), "uniqueItems": true })
+    }
+}
+
+// Map keys serialize as JSON object keys whatever their type.
+impl<K, V: ApiSchema, S> ApiSchema for std::collections::HashMap<K, V, S> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "object", "additionalProperties": V::schema(components) })
+    }
+}
+
+impl<K, V: ApiSchema> ApiSchema for BTreeMap<K, V> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "object", "additionalProperties": V::schema(components) })
+    }
+}
+
+impl<T: ApiSchema + ?Sized> ApiSchema for Box<T> {
+    fn schema(components: &mut Components) -> Value {
+        T::schema(components)
+    }
+}
+
+impl<T: ApiSchema + ?Sized> ApiSchema for Arc<T> {
+    fn schema(components: &mut Components) -> Value {
+        T::schema(components)
+    }
+}
+
+impl<T: ApiSchema + ?Sized> ApiSchema for &T {
+    fn schema(components: &mut Components) -> Value {
+        T::schema(components)
+    }
+}
+
+macro_rules! tuple {
+    ($($name:ident),+) => {
+        impl<$($name: ApiSchema),+> ApiSchema for ($($name,)+) {
+            fn schema(components: &mut Components) -> Value {
+                schema::tuple(vec![$($name::schema(components)),+])
+            }
+        }
+    };
+}
+
+tuple!(A, B);
+tuple!(A, B, C);
+tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    /// An order.
+    #[derive(Serialize, ApiSchema)]
+    #[allow(dead_code)]
+    struct Order {
+        order_id: uuid::Uuid,
+        /// Signed: negative sells.
+        quantity: i64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        limit: Option<f64>,
+        #[serde(rename = "venue")]
+        venue_id: u32,
+        #[serde(skip)]
+        internal: bool,
+        side: Side,
+        instruction: Instruction,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    #[allow(dead_code)]
+    enum Side {
+        Buy,
+        SellShort,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    #[allow(dead_code)]
+    enum Instruction {
+        Hold,
+        Reduce { fraction: f64 },
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[allow(dead_code)]
+    struct Query {
+        window: String,
+        points: Option<usize>,
+    }
+
+    #[test]
+    fn documents_routes_with_schemas_derived_from_serde_attributes() {
+        let doc = ApiDoc::new("Test", "")
+            .get::<Vec<Order>>("/orders", "Open orders")
+            .operation(Operation::get("/orders/{id}", "One order").path_param::<u64>("id").response::<Order>())
+            .operation(Operation::get("/history", "History").query::<Query>())
+            .json();
+        let order = &doc["components"]["schemas"]["Order"];
+        assert_eq!(order["required"], json!(["order_id", "quantity", "venue", "side", "instruction"]));
+        assert_eq!(order["properties"]["quantity"]["description"], "Signed: negative sells.");
+        assert!(order["properties"].get("internal").is_none());
+        assert_eq!(doc["components"]["schemas"]["Side"]["enum"], json!(["BUY", "SELL_SHORT"]));
+        let reduce = &doc["components"]["schemas"]["Instruction"]["oneOf"][1];
+        assert_eq!(reduce["properties"]["type"]["const"], "reduce");
+        assert_eq!(reduce["required"], json!(["type", "fraction"]));
+
+        let by_id = &doc["paths"]["/orders/{id}"]["get"];
+        assert_eq!(by_id["parameters"][0]["schema"]["type"], "integer");
+        let history = &doc["paths"]["/history"]["get"]["parameters"];
+        assert_eq!((history[0]["name"].as_str(), history[0]["required"].as_bool()), (Some("points"), Some(false)));
+        assert_eq!((history[1]["name"].as_str(), history[1]["required"].as_bool()), (Some("window"), Some(true)));
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * QuantumArb 2.0 - Shared: Schema Building Blocks
+ *
+ * File: src/shared/openapi/schema.rs
+ *
+ * Description:
+ * The JSON schema shapes `#[derive(ApiSchema)]` expands to: objects with
+ * their required properties, tuples, and enums in each of serde's tagging
+ * styles.
+ */
+
+use serde_json::{json, Map, Value};
+
+/// One field of an object.
+pub struct Property {
+    pub name: &'static str,
+    pub description: Option<&'static str>,
+    pub required: bool,
+    pub schema: Value,
+}
+
+/// One variant of an enum.
+pub struct Variant {
+    pub name: &'static str,
+    pub description: Option<&'static str>,
+    pub unit: bool,
+    /// A single unnamed field, serialized as the field itself.
+    pub newtype: bool,
+    pub schema: Value,
+}
+
+/// How serde writes which variant a value is.
+pub enum Tagging {
+    /// { "Variant": ... }, or "Variant" for a unit variant.
+    External,
+    /// { "tag": "Variant", ...fields }.
+    Internal { tag: &'static str },
+    /// { "tag": "Variant", "content": ... }.
+    Adjacent { tag: &'static str, content: &'static str },
+    /// The variant's content only.
+    Untagged,
+}
+
+pub fn any() -> Value {
+    json!({})
+}
+
+pub fn null() -> Value {
+    json!({ "type": "null" })
+}
+
+pub fn tuple(items: Vec<Value>) -> Value {
+    let len = items.len();
+    json!({ "type": "array", "prefixItems": items, "minItems": len, "maxItems": len })
+}
+
+/// Adds a description, keeping a reference a reference.
+pub fn describe(schema: Value, description: Option<&str>) -> Value {
+    let Some(description) = description else {
+        return schema;
+    };
+    match schema {
+        Value::Object(mut object) if !object.contains_key("$ref") => {
+            object.insert("description".to_string(), json!(description));
+            Value::Object(object)
+        }
+        other => json!({ "allOf": [other], "description": description }),
+    }
+}
+
+/// An object with `properties`, merged with the `flattened` schemas.
+pub fn object(properties: Vec<Property>, flattened: Vec<Value>) -> Value {
+    let required: Vec<&str> = properties.iter().filter(|p| p.required).map(|p| p.name).collect();
+    let properties: Map<String, Value> =
+        properties.into_iter().map(|p| (p.name.to_string(), describe(p.schema, p.description))).collect();
+    let object = json!({ "type": "object", "properties": properties, "required": required });
+    if flattened.is_empty() {
+        object
+    } else {
+        json!({ "allOf": std::iter::once(object).chain(flattened).collect::<Vec<_>>() })
+    }
+}
+
+/// An enum written in the `tagging` style.
+pub fn enumeration(tagging: Tagging, variants: Vec<Variant>) -> Value {
+    if matches!(tagging, Tagging::External) && variants.iter().all(|v| v.unit) {
+        let names: Vec<&str> = variants.iter().map(|v| v.name).collect();
+        return json!({ "type": "string", "enum": names });
+    }
+    let one_of: Vec<Value> = variants
+        .into_iter()
+        .map(|variant| {
+            let schema = match &tagging {
+                Tagging::External if variant.unit => json!({ "const": variant.name }),
+                Tagging::External => tagged(variant.name, variant.schema),
+                Tagging::Internal { tag } => internally_tagged(tag, &variant),
+                Tagging::Adjacent { tag, content } => {
+                    let mut properties = Map::new();
+                    properties.insert(tag.to_string(), json!({ "const": variant.name }));
+                    let mut required = vec![tag.to_string()];
+                    if !variant.unit {
+                        properties.insert(content.to_string(), variant.schema);
+                        required.push(content.to_string());
+                    }
+                    json!({ "type": "object", "properties": properties, "required": required })
+                }
+                Tagging::Untagged => variant.schema,
+            };
+            describe(schema, variant.description)
+        })
+        .collect();
+    json!({ "oneOf": one_of })
+}
+
+fn tagged(name: &str, schema: Value) -> Value {
+    let mut properties = Map::new();
+    properties.insert(name.to_string(), schema);
+    json!({ "type": "object", "properties": properties, "required": [name] })
+}
+
+/// The variant's fields with the tag added; a newtype variant's content is
+/// merged with the tag.
+fn internally_tagged(tag: &str, variant: &Variant) -> Value {
+    let mut properties = Map::new();
+    properties.insert(tag.to_string(), json!({ "const": variant.name }));
+    let tag_only = json!({ "type": "object", "properties": properties, "required": [tag] });
+    if variant.unit {
+        return tag_only;
+    }
+    match (&variant.schema, variant.newtype) {
+        (Value::Object(object), false) if object.get("type") == Some(&json!("object")) => {
+            let mut merged = object.clone();
+            let mut fields = object.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+            let mut all = Map::new();
+            all.insert(tag.to_string(), json!({ "const": variant.name }));
+            all.append(&mut fields);
+            let mut required = vec![json!(tag)];
+            required.extend(object.get("required").and_then(Value::as_array).cloned().unwrap_or_default());
+            merged.insert("properties".to_string(), Value::Object(all));
+            merged.insert("required".to_string(), Value::Array(required));
+            Value::Object(merged)
+        }
+        (schema, _) => json!({ "allOf": [tag_only, schema] }),
+    }
+}
@@ -0,0 +1,303 @@
+/*
+ * QuantumArb 2.0 - Shared: OpenAPI Schema Derive
+ *
+ * File: src/shared/openapi_derive/lib.rs
+ *
+ * Description:
+ * `#[derive(ApiSchema)]` for `quantumarb-openapi`: generates a type's JSON
+ * schema from its definition, reading the same serde attributes serde does,
+ * so the documented shape is the one on the wire:
+ *
+ *   container: rename_all, tag, content, untagged, transparent
+ *   variant:   rename, rename_all, skip
+ *   field:     rename, skip, skip_serializing, default, skip_serializing_if,
+ *              flatten, with (documented as any value)
+ *
+ * Doc comments become descriptions. A field is required unless it is an
+ * Option, has a default or may be skipped when serializing.
+ */
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, GenericParam, LitStr, Type};
+
+#[proc_macro_derive(ApiSchema)]
+pub fn derive_api_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// --- Serde Attributes ---
+
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+    skip: bool,
+    optional: bool,
+    flatten: bool,
+    opaque: bool,
+}
+
+fn serde_attrs(attrs: &[Attribute]) -> syn::Result<SerdeAttrs> {
+    let mut parsed = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            let key = meta.path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+            match key.as_str() {
+                "rename" | "rename_all" | "tag" | "content" => {
+                    // rename(serialize = "..") is not used in the tree; take the plain form.
+                    let value: LitStr = meta.value()?.parse()?;
+                    let slot = match key.as_str() {
+                        "rename" => &mut parsed.rename,
+                        "rename_all" => &mut parsed.rename_all,
+                        "tag" => &mut parsed.tag,
+                        _ => &mut parsed.content,
+                    };
+                    *slot = Some(value.value());
+                }
+                "untagged" => parsed.untagged = true,
+                "transparent" => parsed.transparent = true,
+                "skip" | "skip_serializing" => parsed.skip = true,
+                "default" | "skip_serializing_if" => {
+                    parsed.optional = true;
+                    if meta.input.peek(syn::Token![=]) {
+                        let _: LitStr = meta.value()?.parse()?;
+                    }
+                }
+                "flatten" => parsed.flatten = true,
+                "with" | "serialize_with" => {
+                    parsed.opaque = true;
+                    let _: LitStr = meta.value()?.parse()?;
+                }
+                _ => {
+                    // Attributes that do not change the schema (alias, bound, ...).
+                    if meta.input.peek(syn::Token![=]) {
+                        let _: syn::Expr = meta.value()?.parse()?;
+                    } else if meta.input.peek(syn::token::Paren) {
+                        let _content;
+                        syn::parenthesized!(_content in meta.input);
+                        let _: Tokens = _content.parse()?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+fn docs(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let text = lines.join(" ").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Applies a serde rename_all rule to a Rust identifier.
+fn rename(ident: &str, rule: Option<&str>) -> String {
+    let Some(rule) = rule else {
+        return ident.to_string();
+    };
+    // Split on underscores (fields) or case boundaries (variants).
+    let mut words: Vec<String> = Vec::new();
+    for part in ident.split('_').filter(|p| !p.is_empty()) {
+        let mut word = String::new();
+        for c in part.chars() {
+            if c.is_uppercase() && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    let capitalized = |w: &String| {
+        let mut chars = w.chars();
+        chars.next().map(|c| c.to_uppercase().collect::<String>() + chars.as_str()).unwrap_or_default()
+    };
+    match rule {
+        "lowercase" => ident.to_lowercase(),
+        "UPPERCASE" => ident.to_uppercase(),
+        "snake_case" => lower.join("_"),
+        "SCREAMING_SNAKE_CASE" => lower.join("_").to_uppercase(),
+        "kebab-case" => lower.join("-"),
+        "SCREAMING-KEBAB-CASE" => lower.join("-").to_uppercase(),
+        "camelCase" => {
+            let mut out = lower.first().cloned().unwrap_or_default();
+            out.extend(lower.iter().skip(1).map(capitalized));
+            out
+        }
+        "PascalCase" => lower.iter().map(capitalized).collect(),
+        _ => ident.to_string(),
+    }
+}
+
+// --- Expansion ---
+
+fn expand(mut input: DeriveInput) -> syn::Result<Tokens> {
+    let attrs = serde_attrs(&input.attrs)?;
+    let description = option(docs(&input.attrs));
+    let body = match &input.data {
+        Data::Struct(data) => struct_schema(&data.fields, &attrs)?,
+        Data::Enum(data) => {
+            let mut variants = Vec::new();
+            for variant in &data.variants {
+                let variant_attrs = serde_attrs(&variant.attrs)?;
+                if variant_attrs.skip {
+                    continue;
+                }
+                let name = variant_attrs
+                    .rename
+                    .clone()
+                    .unwrap_or_else(|| rename(&variant.ident.to_string(), attrs.rename_all.as_deref()));
+                let fields_attrs = SerdeAttrs { rename_all: variant_attrs.rename_all.clone(), ..Default::default() };
+                let schema = struct_schema(&variant.fields, &fields_attrs)?;
+                let unit = matches!(variant.fields, Fields::Unit);
+                let newtype = matches!(&variant.fields, Fields::Unnamed(f) if f.unnamed.len() == 1);
+                let doc = option(docs(&variant.attrs));
+                variants.push(quote! {
+                    ::quantumarb_openapi::schema::Variant {
+                        name: #name,
+                        description: #doc,
+                        unit: #unit,
+                        newtype: #newtype,
+                        schema: #schema,
+                    }
+                });
+            }
+            let tagging = if attrs.untagged {
+                quote!(::quantumarb_openapi::schema::Tagging::Untagged)
+            } else {
+                match (&attrs.tag, &attrs.content) {
+                    (Some(tag), Some(content)) => {
+                        quote!(::quantumarb_openapi::schema::Tagging::Adjacent { tag: #tag, content: #content })
+                    }
+                    (Some(tag), None) => quote!(::quantumarb_openapi::schema::Tagging::Internal { tag: #tag }),
+                    _ => quote!(::quantumarb_openapi::schema::Tagging::External),
+                }
+            };
+            quote!(::quantumarb_openapi::schema::enumeration(#tagging, ::std::vec![#(#variants),*]))
+        }
+        Data::Union(_) => return Err(syn::Error::new_spanned(&input.ident, "ApiSchema does not support unions")),
+    };
+
+    let name = &input.ident;
+    let generic = input.generics.params.iter().any(|p| matches!(p, GenericParam::Type(_)));
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(t) = param {
+            t.bounds.push(syn::parse_quote!(::quantumarb_openapi::ApiSchema));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // Generic types are inlined: one component name cannot stand for every instantiation.
+    let schema = if generic {
+        quote!(::quantumarb_openapi::schema::describe(#body, #description))
+    } else {
+        let short = name.to_string();
+        quote! {
+            components.reference(#short, ::std::any::type_name::<Self>(), |components| {
+                ::quantumarb_openapi::schema::describe(#body, #description)
+            })
+        }
+    };
+    Ok(quote! {
+        impl #impl_generics ::quantumarb_openapi::ApiSchema for #name #ty_generics #where_clause {
+            fn schema(components: &mut ::quantumarb_openapi::Components) -> ::quantumarb_openapi::Value {
+                #schema
+            }
+        }
+    })
+}
+
+fn option(value: Option<String>) -> Tokens {
+    match value {
+        Some(v) => quote!(::std::option::Option::Some(#v)),
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+/// The schema of a struct's (or variant's) fields.
+fn struct_schema(fields: &Fields, attrs: &SerdeAttrs) -> syn::Result<Tokens> {
+    match fields {
+        Fields::Unit => Ok(quote!(::quantumarb_openapi::schema::null())),
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            let ty = &unnamed.unnamed[0].ty;
+            Ok(quote!(<#ty as ::quantumarb_openapi::ApiSchema>::schema(components)))
+        }
+        Fields::Unnamed(unnamed) => {
+            let items = unnamed.unnamed.iter().map(|f| {
+                let ty = &f.ty;
+                quote!(<#ty as ::quantumarb_openapi::ApiSchema>::schema(components))
+            });
+            Ok(quote!(::quantumarb_openapi::schema::tuple(::std::vec![#(#items),*])))
+        }
+        Fields::Named(named) => {
+            if attrs.transparent {
+                let field = named.named.iter().next().expect("a transparent struct has one field");
+                let ty = &field.ty;
+                return Ok(quote!(<#ty as ::quantumarb_openapi::ApiSchema>::schema(components)));
+            }
+            let mut properties = Vec::new();
+            let mut flattened = Vec::new();
+            for field in &named.named {
+                let field_attrs = serde_attrs(&field.attrs)?;
+                if field_attrs.skip {
+                    continue;
+                }
+                let ty = &field.ty;
+                let schema = if field_attrs.opaque {
+                    quote!(::quantumarb_openapi::schema::any())
+                } else {
+                    quote!(<#ty as ::quantumarb_openapi::ApiSchema>::schema(components))
+                };
+                if field_attrs.flatten {
+                    flattened.push(schema);
+                    continue;
+                }
+                let ident = field.ident.as_ref().expect("named field").to_string();
+                let ident = ident.strip_prefix("r#").unwrap_or(&ident).to_string();
+                let name = field_attrs.rename.clone().unwrap_or_else(|| rename(&ident, attrs.rename_all.as_deref()));
+                let required = !field_attrs.optional && !is_option(ty);
+                let doc = option(docs(&field.attrs));
+                properties.push(quote! {
+                    ::quantumarb_openapi::schema::Property {
+                        name: #name,
+                        description: #doc,
+                        required: #required,
+                        schema: #schema,
+                    }
+                });
+            }
+            Ok(quote! {
+                ::quantumarb_openapi::schema::object(::std::vec![#(#properties),*], ::std::vec![#(#flattened),*])
+            })
+        }
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|s| s.ident == "Option"),
+        _ => false,
+    }
+}
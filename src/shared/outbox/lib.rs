@@ -0,0 +1,297 @@
+/*
+ * QuantumArb 2.0 - Shared: Transactional Outbox
+ *
+ * File: src/shared/outbox/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-outbox`) keeps a service's state changes
+ * and the bus messages they cause in one local, append-only journal, so that
+ * a crash can never leave one without the other.
+ *
+ * A commit is one JSON line holding the events (the state changes, in the
+ * service's own event type) and the messages they cause, written and fsynced
+ * in a single write. A crash mid-write leaves a torn last line, which is
+ * discarded on open: that commit never happened, neither its state change
+ * nor its messages. On open the service gets back every committed event and
+ * replays them to rebuild its state.
+ *
+ * A publisher drains the outbox (`drain`): the pending messages are
+ * published in commit order, each under its dedup key, and then a marker
+ * records how far publishing got. A crash between the two republishes the
+ * same messages under the same keys after the restart, and the bus drops
+ * them as duplicates (see `quantumarb_bus::publish_deduplicated`).
+ * Consumers therefore see each message once: never a fill whose state
+ * change was lost (phantom), never a state change whose fill was not
+ * announced (missing).
+ *
+ * The journal grows with every commit. `compact` rewrites it as the events
+ * that rebuild the current state (typically one snapshot event) followed by
+ * the messages still pending, and swaps it in with a rename.
+ *
+ * Journal lines:
+ *   {"commit":{"seq":7,"events":[...],"messages":[{"topic","key","payload"}]}}
+ *   {"published":{"through":7}}
+ * Payloads are hex, so binary bus encodings survive the JSON journal.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+// --- Data Structures ---
+
+/// A bus message waiting in the outbox.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub struct Message {
+    pub topic: String,
+    /// The bus drops a second message with the same key.
+    pub key: String,
+    #[serde(with = "hex_payload")]
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(topic: &str, key: impl Into<String>, payload: Vec<u8>) -> Message {
+        Message { topic: topic.to_string(), key: key.into(), payload }
+    }
+
+    /// A message with `body` as its JSON payload.
+    pub fn json<T: Serialize>(topic: &str, key: impl Into<String>, body: &T) -> Message {
+        Message::new(topic, key, serde_json::to_vec(body).expect("bus messages always serialize"))
+    }
+}
+
+#[derive(Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+enum Line<E> {
+    Commit { seq: u64, events: Vec<E>, messages: Vec<Message> },
+    Published { through: u64 },
+}
+
+/// Counters served on the services' outbox endpoints.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct OutboxStats {
+    pub path: String,
+    /// Sequence number of the last commit.
+    pub last_commit: u64,
+    /// Commits whose messages have all been published.
+    pub published_through: u64,
+    pub pending_messages: usize,
+    /// Lines in the journal since it was last compacted.
+    pub journal_lines: usize,
+    pub messages_published: u64,
+    pub compactions: u64,
+}
+
+// --- Outbox ---
+
+pub struct Outbox<E> {
+    path: PathBuf,
+    file: File,
+    last_commit: u64,
+    published_through: u64,
+    /// Committed messages not yet published, with their commit's sequence number.
+    pending: VecDeque<(u64, Message)>,
+    journal_lines: usize,
+    messages_published: u64,
+    compactions: u64,
+    events: PhantomData<E>,
+}
+
+impl<E: Serialize + DeserializeOwned> Outbox<E> {
+    /// Opens the journal at `path`, creating it if needed. Returns the outbox
+    /// and every event committed so far, in order, for the caller to replay.
+    /// A torn last line, left by a crash mid-commit, is cut off.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Outbox<E>, Vec<E>)> {
+        let path = path.as_ref().to_path_buf();
+        let mut outbox = Outbox {
+            file: OpenOptions::new().create(true).append(true).open(&path)?,
+            path,
+            last_commit: 0,
+            published_through: 0,
+            pending: VecDeque::new(),
+            journal_lines: 0,
+            messages_published: 0,
+            compactions: 0,
+            events: PhantomData,
+        };
+        let mut events = Vec::new();
+        let mut valid_len = 0u64;
+        let mut reader = BufReader::new(File::open(&outbox.path)?);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let bytes = reader.read_line(&mut line)?;
+            if bytes == 0 {
+                break;
+            }
+            let parsed = line.ends_with('\n').then(|| serde_json::from_str::<Line<E>>(&line).ok()).flatten();
+            let Some(parsed) = parsed else {
+                // Only the last line can be torn; anything after it means the
+                // file was damaged some other way.
+                if reader.read_line(&mut String::new())? > 0 {
+                    let at = format!("{} at byte {}", outbox.path.display(), valid_len);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("corrupt outbox journal {}", at)));
+                }
+                println!("Outbox {}: discarding a torn last commit.", outbox.path.display());
+                outbox.file.set_len(valid_len)?;
+                break;
+            };
+            valid_len += bytes as u64;
+            outbox.journal_lines += 1;
+            match parsed {
+                Line::Commit { seq, events: committed, messages } => {
+                    events.extend(committed);
+                    outbox.last_commit = outbox.last_commit.max(seq);
+                    outbox.pending.extend(messages.into_iter().map(|message| (seq, message)));
+                }
+                Line::Published { through } => outbox.published_through = outbox.published_through.max(through),
+            }
+        }
+        let through = outbox.published_through;
+        outbox.pending.retain(|(seq, _)| *seq > through);
+        Ok((outbox, events))
+    }
+
+    /// Commits state changes and the messages they cause as one durable
+    /// write. Returns the commit's sequence number.
+    pub fn commit(&mut self, events: Vec<E>, messages: Vec<Message>) -> io::Result<u64> {
+        let seq = self.last_commit + 1;
+        self.append(&Line::Commit { seq, events, messages: messages.clone() })?;
+        self.last_commit = seq;
+        self.pending.extend(messages.into_iter().map(|message| (seq, message)));
+        Ok(seq)
+    }
+
+    /// Committed messages not yet published, oldest first.
+    pub fn pending(&self) -> impl Iterator<Item = &Message> {
+        self.pending.iter().map(|(_, message)| message)
+    }
+
+    /// Records that every message up to and including commit `through` has
+    /// been published.
+    pub fn mark_published(&mut self, through: u64) -> io::Result<()> {
+        if through <= self.published_through {
+            return Ok(());
+        }
+        self.append::<()>(&Line::Published { through })?;
+        self.published_through = through;
+        let before = self.pending.len();
+        self.pending.retain(|(seq, _)| *seq > through);
+        self.messages_published += (before - self.pending.len()) as u64;
+        Ok(())
+    }
+
+    /// Rewrites the journal as `events`, which must rebuild the current
+    /// state on their own, followed by the pending messages.
+    pub fn compact(&mut self, events: Vec<E>) -> io::Result<()> {
+        let temp = self.path.with_extension("compacting");
+        let mut lines = Vec::with_capacity(self.pending.len() + 2);
+        lines.push(serde_json::to_string(&Line::<E>::Published { through: self.published_through })?);
+        lines.push(serde_json::to_string(&Line::Commit { seq: self.last_commit, events, messages: Vec::new() })?);
+        for (seq, message) in &self.pending {
+            let line = Line::<E>::Commit { seq: *seq, events: Vec::new(), messages: vec![message.clone()] };
+            lines.push(serde_json::to_string(&line)?);
+        }
+        let mut file = File::create(&temp)?;
+        file.write_all(format!("{}\n", lines.join("\n")).as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.journal_lines = lines.len();
+        self.compactions += 1;
+        Ok(())
+    }
+
+    pub fn journal_lines(&self) -> usize {
+        self.journal_lines
+    }
+
+    pub fn stats(&self) -> OutboxStats {
+        OutboxStats {
+            path: self.path.display().to_string(),
+            last_commit: self.last_commit,
+            published_through: self.published_through,
+            pending_messages: self.pending.len(),
+            journal_lines: self.journal_lines,
+            messages_published: self.messages_published,
+            compactions: self.compactions,
+        }
+    }
+
+    fn append<T: Serialize>(&mut self, line: &Line<T>) -> io::Result<()> {
+        let mut bytes = serde_json::to_vec(line)?;
+        bytes.push(b'\n');
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        self.journal_lines += 1;
+        Ok(())
+    }
+}
+
+/// Publishes every pending message under its dedup key, in commit order,
+/// then records it. Returns the number published.
+pub fn drain<E: Serialize + DeserializeOwned>(outbox: &mut Outbox<E>) -> io::Result<usize> {
+    let Some((through, _)) = outbox.pending.back() else {
+        return Ok(0);
+    };
+    let through = *through;
+    let count = outbox.pending.len();
+    for message in outbox.pending() {
+        quantumarb_bus::publish_deduplicated(&message.topic, &message.payload, &message.key);
+    }
+    outbox.mark_published(through)?;
+    Ok(count)
+}
+
+mod hex_payload {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payload: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(payload))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_torn_commit_is_dropped_and_unpublished_messages_come_back() {
+        let path = std::env::temp_dir().join(format!("quantumarb-outbox-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let fill = |key: &str| Message::new("execution_reports", key, key.as_bytes().to_vec());
+
+        let (mut outbox, replayed) = Outbox::<String>::open(&path).unwrap();
+        assert!(replayed.is_empty());
+        outbox.commit(vec!["filled 5".to_string()], vec![fill("exec-1")]).unwrap();
+        assert_eq!(drain(&mut outbox).unwrap(), 1);
+        outbox.commit(vec!["filled 3".to_string()], vec![fill("exec-2")]).unwrap();
+        drop(outbox);
+        // A crash in the middle of the third commit.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"commit\":{\"seq\":3,\"events\":[\"filled").unwrap();
+
+        let (mut outbox, replayed) = Outbox::<String>::open(&path).unwrap();
+        assert_eq!(replayed, vec!["filled 5", "filled 3"]);
+        assert_eq!(outbox.pending().cloned().collect::<Vec<_>>(), vec![fill("exec-2")]);
+        outbox.commit(vec!["canceled".to_string()], Vec::new()).unwrap();
+
+        outbox.compact(vec!["snapshot".to_string()]).unwrap();
+        let (outbox, replayed) = Outbox::<String>::open(&path).unwrap();
+        assert_eq!(replayed, vec!["snapshot"]);
+        assert_eq!(outbox.pending().count(), 1);
+        assert_eq!(outbox.stats().last_commit, 3);
+        let _ = std::fs::remove_file(&path);
+    }
+}
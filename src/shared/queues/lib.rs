@@ -0,0 +1,447 @@
+/*
+ * QuantumArb 2.0 - Shared: Bounded Event Queues
+ *
+ * File: src/shared/queues/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-queues`) provides the bounded
+ * single-consumer queues that join the stages of the services' event
+ * pipelines. Every queue has a capacity and an explicit policy for what
+ * happens when a producer outruns the consumer:
+ *
+ *   DropOldest   the oldest queued item is discarded to make room. For
+ *                market data, where only the latest state matters and a
+ *                stale quote is worth less than a fresh one.
+ *   Block        the producer waits for free space. For orders, fills and
+ *                execution reports, which must not be lost; the wait pushes
+ *                back on the bus consumer (JetStream flow control).
+ *   SpillToDisk  items beyond the capacity are appended to a JSON-lines file
+ *                and read back, in order, once the queue has drained. For
+ *                alerts, which must not be lost but must not stall the
+ *                producer either. Items spilled by a previous run are
+ *                delivered first after a restart (at least once: items
+ *                already read back before the restart come again).
+ *
+ * Each queue keeps counters (depth, enqueued, dropped, spilled, blocked
+ * sends) that services expose on their stats endpoints.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OverflowPolicy {
+    DropOldest,
+    Block,
+    SpillToDisk,
+}
+
+/// Counters of one queue.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct QueueStats {
+    pub name: String,
+    pub policy: OverflowPolicy,
+    pub capacity: usize,
+    /// Items waiting in memory.
+    pub depth: usize,
+    /// Items waiting on disk.
+    pub spilled_depth: usize,
+    pub enqueued: u64,
+    /// Items discarded by DropOldest (or a failed spill).
+    pub dropped: u64,
+    /// Items written to disk by SpillToDisk.
+    pub spilled: u64,
+    /// Sends that had to wait for space under Block.
+    pub blocked_sends: u64,
+}
+
+/// Returned by `send` once the receiver is gone, with the unsent item.
+#[derive(Debug)]
+pub struct SendError<T>(pub T);
+
+/// The overflow file of a SpillToDisk queue.
+struct Spill<T> {
+    path: PathBuf,
+    encode: fn(&T) -> serde_json::Result<String>,
+    decode: fn(&str) -> serde_json::Result<T>,
+    /// Byte offset of the first line not yet read back.
+    read_offset: u64,
+    /// Lines written but not yet read back.
+    pending: usize,
+    /// Held open in append mode so a spill under the state lock is one
+    /// write, not an open and a write; reopened after a failed write.
+    writer: Option<File>,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    spill: Option<Spill<T>>,
+    stats: QueueStats,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// --- Constructors ---
+
+/// A queue that discards its oldest item when full.
+pub fn drop_oldest<T>(name: &str, capacity: usize) -> (Sender<T>, Receiver<T>) {
+    queue(name, OverflowPolicy::DropOldest, capacity, None)
+}
+
+/// A queue whose senders wait while it is full.
+pub fn blocking<T>(name: &str, capacity: usize) -> (Sender<T>, Receiver<T>) {
+    queue(name, OverflowPolicy::Block, capacity, None)
+}
+
+/// A queue that overflows to `path` (JSON lines). Lines left in the file by a
+/// previous run are queued ahead of anything sent now.
+pub fn spill_to_disk<T: Serialize + DeserializeOwned>(
+    name: &str,
+    capacity: usize,
+    path: impl AsRef<Path>,
+) -> (Sender<T>, Receiver<T>) {
+    let path = path.as_ref().to_path_buf();
+    let pending = match File::open(&path) {
+        Ok(file) => BufReader::new(file).lines().map_while(Result::ok).filter(|l| !l.is_empty()).count(),
+        Err(_) => 0,
+    };
+    if pending > 0 {
+        println!("Queue '{}': {} items left on disk at {} will be delivered first.", name, pending, path.display());
+    }
+    let spill = Spill {
+        path,
+        encode: |item: &T| serde_json::to_string(item),
+        decode: |line: &str| serde_json::from_str(line),
+        read_offset: 0,
+        pending,
+        writer: None,
+    };
+    queue(name, OverflowPolicy::SpillToDisk, capacity, Some(spill))
+}
+
+fn queue<T>(name: &str, policy: OverflowPolicy, capacity: usize, spill: Option<Spill<T>>) -> (Sender<T>, Receiver<T>) {
+    let capacity = capacity.max(1);
+    let stats = QueueStats {
+        name: name.to_string(),
+        policy,
+        capacity,
+        depth: 0,
+        spilled_depth: spill.as_ref().map_or(0, |s| s.pending),
+        enqueued: 0,
+        dropped: 0,
+        spilled: 0,
+        blocked_sends: 0,
+    };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            items: VecDeque::with_capacity(capacity),
+            spill,
+            stats,
+            senders: 1,
+            receiver_alive: true,
+        }),
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+// --- Sending ---
+
+impl<T> Sender<T> {
+    /// Queues an item, applying the overflow policy if the queue is full.
+    /// Only Block ever waits.
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut item = Some(item);
+        let mut counted_block = false;
+        loop {
+            let space = self.shared.not_full.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if !state.receiver_alive {
+                    return Err(SendError(item.take().unwrap()));
+                }
+                let full = state.items.len() >= state.stats.capacity;
+                match state.stats.policy {
+                    OverflowPolicy::Block if full => {
+                        if !counted_block {
+                            state.stats.blocked_sends += 1;
+                            counted_block = true;
+                        }
+                    }
+                    OverflowPolicy::DropOldest if full => {
+                        state.items.pop_front();
+                        state.stats.dropped += 1;
+                        state.push(item.take().unwrap());
+                    }
+                    OverflowPolicy::SpillToDisk if full || state.spill.as_ref().is_some_and(|s| s.pending > 0) => {
+                        state.spill_item(item.take().unwrap());
+                    }
+                    _ => state.push(item.take().unwrap()),
+                }
+            }
+            if item.is_none() {
+                self.shared.not_empty.notify_one();
+                return Ok(());
+            }
+            space.await;
+        }
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.shared.state.lock().unwrap().stats()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().senders -= 1;
+        self.shared.not_empty.notify_one();
+    }
+}
+
+impl<T> State<T> {
+    fn push(&mut self, item: T) {
+        self.items.push_back(item);
+        self.stats.enqueued += 1;
+    }
+
+    /// Appends the item to the spill file; it is dropped if that fails.
+    fn spill_item(&mut self, item: T) {
+        let spill = self.spill.as_mut().expect("SpillToDisk queues have a spill file");
+        let written = (spill.encode)(&item).map_err(|e| e.to_string()).and_then(|mut line| {
+            line.push('\n');
+            let file = match spill.writer.as_mut() {
+                Some(file) => file,
+                None => spill.writer.insert(
+                    OpenOptions::new().create(true).append(true).open(&spill.path).map_err(|e| e.to_string())?,
+                ),
+            };
+            file.write_all(line.as_bytes()).map_err(|e| e.to_string())
+        });
+        match written {
+            Ok(()) => {
+                spill.pending += 1;
+                self.stats.enqueued += 1;
+                self.stats.spilled += 1;
+            }
+            Err(e) => {
+                spill.writer = None;
+                self.stats.dropped += 1;
+                println!("Queue '{}': failed to spill to {} ({}); item dropped.", self.stats.name, spill.path.display(), e);
+            }
+        }
+    }
+
+    /// Reads up to a capacity's worth of spilled items back into memory,
+    /// truncating the file once everything has been read.
+    fn refill(&mut self) {
+        let capacity = self.stats.capacity;
+        let Some(spill) = self.spill.as_mut().filter(|s| s.pending > 0) else {
+            return;
+        };
+        let read = File::open(&spill.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(spill.read_offset))?;
+            let mut reader = BufReader::new(file);
+            let mut lines = Vec::new();
+            let mut line = String::new();
+            while lines.len() < capacity {
+                line.clear();
+                let bytes = reader.read_line(&mut line)?;
+                if bytes == 0 {
+                    break;
+                }
+                spill.read_offset += bytes as u64;
+                if !line.trim().is_empty() {
+                    lines.push(line.trim_end().to_string());
+                }
+            }
+            Ok(lines)
+        });
+        let lines = match read {
+            Ok(lines) => lines,
+            Err(e) => {
+                println!("Queue '{}': failed to read back {} ({}).", self.stats.name, spill.path.display(), e);
+                Vec::new()
+            }
+        };
+        if lines.is_empty() {
+            // Unreadable or truncated underneath us: nothing more to deliver.
+            spill.pending = 0;
+        }
+        for line in lines {
+            spill.pending = spill.pending.saturating_sub(1);
+            match (spill.decode)(&line) {
+                Ok(item) => self.items.push_back(item),
+                Err(e) => {
+                    self.stats.dropped += 1;
+                    println!("Queue '{}': dropping unreadable spilled item ({}).", self.stats.name, e);
+                }
+            }
+        }
+        if spill.pending == 0 {
+            spill.read_offset = 0;
+            if let Err(e) = OpenOptions::new().write(true).open(&spill.path).and_then(|f| f.set_len(0)) {
+                println!("Queue '{}': failed to truncate {} ({}).", self.stats.name, spill.path.display(), e);
+            }
+        }
+    }
+
+    fn stats(&self) -> QueueStats {
+        QueueStats {
+            depth: self.items.len(),
+            spilled_depth: self.spill.as_ref().map_or(0, |s| s.pending),
+            ..self.stats.clone()
+        }
+    }
+}
+
+// --- Receiving ---
+
+impl<T> Receiver<T> {
+    /// The next item, oldest first; None once every sender is gone and the
+    /// queue is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let available = self.shared.not_empty.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if state.items.is_empty() {
+                    state.refill();
+                }
+                if let Some(item) = state.items.pop_front() {
+                    drop(state);
+                    self.shared.not_full.notify_one();
+                    return Some(item);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            available.await;
+        }
+    }
+
+    /// The next item if one is queued, without waiting.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.items.is_empty() {
+            state.refill();
+        }
+        let item = state.items.pop_front();
+        drop(state);
+        if item.is_some() {
+            self.shared.not_full.notify_one();
+        }
+        item
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.shared.state.lock().unwrap().stats()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_alive = false;
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn spill_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("quantumarb-queues-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_discards_and_counts_the_oldest() {
+        let (tx, mut rx) = drop_oldest("quotes", 2);
+        for quote in 1..=5 {
+            tx.send(quote).await.unwrap();
+        }
+        let stats = rx.stats();
+        assert_eq!((stats.depth, stats.enqueued, stats.dropped), (2, 5, 3));
+        assert_eq!((rx.try_recv(), rx.try_recv(), rx.try_recv()), (Some(4), Some(5), None));
+    }
+
+    #[tokio::test]
+    async fn a_blocked_sender_wakes_once_the_receiver_takes_an_item() {
+        let (tx, mut rx) = blocking("orders", 1);
+        tx.send(1).await.unwrap();
+        let sender = tokio::spawn(async move { tx.send(2).await.map(|_| tx.stats()) });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!sender.is_finished(), "the queue is full");
+        assert_eq!(rx.stats().blocked_sends, 1);
+
+        assert_eq!(rx.recv().await, Some(1));
+        let stats = tokio::time::timeout(Duration::from_secs(1), sender).await.unwrap().unwrap().unwrap();
+        assert_eq!((stats.enqueued, stats.blocked_sends), (2, 1));
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(rx.recv().await, None, "the sender is gone");
+    }
+
+    #[tokio::test]
+    async fn spilled_items_come_back_in_order_and_survive_a_restart() {
+        let path = spill_path("alerts");
+        let (tx, mut rx) = spill_to_disk::<u32>("alerts", 2, &path);
+        for alert in 1..=5 {
+            tx.send(alert).await.unwrap();
+        }
+        assert_eq!((rx.stats().depth, rx.stats().spilled_depth, rx.stats().spilled), (2, 3, 3));
+        // Sent after a spill, an item goes behind the spilled ones.
+        assert_eq!(rx.recv().await, Some(1));
+        tx.send(6).await.unwrap();
+        let delivered: Vec<_> = std::iter::from_fn(|| rx.try_recv()).collect();
+        assert_eq!(delivered, [2, 3, 4, 5, 6]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0, "truncated once everything is read back");
+
+        // Items still on disk when the queue goes away come first after a restart.
+        for alert in 7..=10 {
+            tx.send(alert).await.unwrap();
+        }
+        drop((tx, rx));
+        let (tx, mut rx) = spill_to_disk::<u32>("alerts", 2, &path);
+        assert_eq!(rx.stats().spilled_depth, 2);
+        tx.send(11).await.unwrap();
+        let delivered: Vec<_> = std::iter::from_fn(|| rx.try_recv()).collect();
+        assert_eq!(delivered, [9, 10, 11], "7 and 8 were in memory and are lost");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,287 @@
+/*
+ * QuantumArb 2.0 - Shared: Instrument Reference Data
+ *
+ * File: src/shared/reference_data/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-refdata`) defines the instrument
+ * definition served by the reference data service and published on the
+ * 'reference.instruments' topic whenever one changes, plus the cache every
+ * consumer keeps of them.
+ *
+ * A definition carries what the rest of the platform needs to interpret the
+ * bare instrument ids and symbols on the wire:
+ * - tick_size and lot_size: the smallest price and quantity increments,
+ *   validated by the risk gateway before an order leaves the firm;
+ * - multiplier: currency value of a one-point move per contract (50 for an
+ *   E-mini S&P future), applied by the portfolio manager to P&L;
+ * - price_decimals: the scale of the instrument's integer wire prices
+ *   (2 = hundredths, the default); see `quantumarb-money`;
+ * - venue, currency and asset class;
+ * - for options, the contract terms (`OptionTerms`): underlying symbol,
+ *   call or put, strike and expiry, from which the risk gateway prices the
+ *   option's greeks.
+ *
+ * Each definition has a version, bumped by the service on every change, so
+ * a consumer that sees updates out of order keeps the newest.
+ *
+ * Definitions can be loaded from a JSON file (QA_REFDATA_PATH) holding an
+ * array of `InstrumentDefinition`; otherwise the built-in set is used.
+ *
+ * The `symbology` module maps each venue's own symbols and ids for an
+ * instrument to its canonical id.
+ */
+
+pub mod symbology;
+
+use chrono::{DateTime, Utc};
+use quantumarb_money::{Money, Price, Quantity};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub use symbology::{Symbology, VenueMapping};
+
+/// Topic the reference data service publishes changed definitions on.
+pub const UPDATES_TOPIC: &str = "reference.instruments";
+/// Wire price scale of definitions that don't give one: hundredths.
+pub const DEFAULT_PRICE_DECIMALS: u8 = 2;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AssetClass {
+    Crypto,
+    Future,
+    Equity,
+    Option,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+/// The contract terms of a European option.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct OptionTerms {
+    /// Symbol of the underlying instrument.
+    pub underlying: String,
+    pub right: OptionRight,
+    /// In points of the underlying's price.
+    pub strike: Price,
+    pub expiry_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct InstrumentDefinition {
+    pub instrument_id: u32,
+    pub symbol: String,
+    /// Primary listing venue.
+    pub venue: String,
+    pub asset_class: AssetClass,
+    pub currency: String,
+    /// Minimum price increment, in points (0.25 = a quarter point).
+    pub tick_size: Price,
+    /// Minimum quantity increment.
+    pub lot_size: u32,
+    /// Currency value of a one-point price move for one unit.
+    pub multiplier: Money,
+    /// Decimal places of the instrument's wire prices.
+    #[serde(default = "default_price_decimals")]
+    pub price_decimals: u8,
+    /// Contract terms; present exactly when asset_class is OPTION.
+    #[serde(default)]
+    pub option: Option<OptionTerms>,
+    #[serde(default)]
+    pub version: u64,
+}
+
+fn default_price_decimals() -> u8 {
+    DEFAULT_PRICE_DECIMALS
+}
+
+impl InstrumentDefinition {
+    /// A wire price for this instrument. One too large for a `Price` reads
+    /// as `Price::MAX`; the pre-trade checks reject orders at such a price.
+    pub fn price(&self, wire: u64) -> Price {
+        Price::from_wire(wire, self.price_decimals).unwrap_or(Price::MAX)
+    }
+
+    /// The wire form of `price`, rounded to the nearest tick.
+    pub fn wire_price(&self, price: Price) -> u64 {
+        price.round_to(self.tick_size).to_wire(self.price_decimals).unwrap_or(0)
+    }
+
+    /// Value of `quantity` units at `price`, with the multiplier applied.
+    pub fn notional(&self, price: Price, quantity: Quantity) -> Money {
+        Money::notional(price, quantity, self.multiplier)
+    }
+
+    /// Checks that a price is on the tick grid.
+    pub fn check_price(&self, price: Price) -> Result<(), String> {
+        if !price.is_multiple_of(self.tick_size) {
+            return Err(format!(
+                "Price {} is not a multiple of the {} tick size {}",
+                price, self.symbol, self.tick_size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that a quantity is a whole number of lots.
+    pub fn check_size(&self, size: u32) -> Result<(), String> {
+        if self.lot_size > 1 && !size.is_multiple_of(self.lot_size) {
+            return Err(format!("Size {} is not a multiple of the {} lot size {}", size, self.symbol, self.lot_size));
+        }
+        Ok(())
+    }
+
+    /// Reasons the definition can't be accepted, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.symbol.trim().is_empty() {
+            return Err("symbol must not be empty".to_string());
+        }
+        if self.tick_size <= Price::ZERO || self.multiplier <= Money::ZERO {
+            return Err("tick_size and multiplier must be positive".to_string());
+        }
+        if self.price_decimals > quantumarb_money::DECIMALS {
+            return Err(format!("price_decimals must be at most {}", quantumarb_money::DECIMALS));
+        }
+        if self.tick_size.to_wire(self.price_decimals).is_none() {
+            return Err(format!("tick_size {} is finer than {} decimal places", self.tick_size, self.price_decimals));
+        }
+        if self.lot_size == 0 {
+            return Err("lot_size must be at least 1".to_string());
+        }
+        match (&self.option, self.asset_class) {
+            (None, AssetClass::Option) => return Err("an option needs its contract terms".to_string()),
+            (Some(_), class) if class != AssetClass::Option => {
+                return Err("only options have contract terms".to_string());
+            }
+            (Some(terms), _) if terms.strike <= Price::ZERO || terms.underlying.trim().is_empty() => {
+                return Err("an option needs an underlying and a positive strike".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// --- Cache ---
+
+/// A consumer's copy of the instrument definitions, indexed by id and symbol.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceData {
+    by_id: HashMap<u32, InstrumentDefinition>,
+    ids_by_symbol: HashMap<String, u32>,
+}
+
+impl ReferenceData {
+    pub fn new(definitions: Vec<InstrumentDefinition>) -> Self {
+        let mut data = ReferenceData::default();
+        for definition in definitions {
+            data.apply(definition);
+        }
+        data
+    }
+
+    /// The built-in definitions; what consumers use until the service answers.
+    pub fn seeded() -> Self {
+        ReferenceData::new(default_instruments())
+    }
+
+    pub fn get(&self, instrument_id: u32) -> Option<&InstrumentDefinition> {
+        self.by_id.get(&instrument_id)
+    }
+
+    pub fn by_symbol(&self, symbol: &str) -> Option<&InstrumentDefinition> {
+        self.ids_by_symbol.get(symbol).and_then(|id| self.by_id.get(id))
+    }
+
+    /// Every definition, by instrument id.
+    pub fn all(&self) -> Vec<InstrumentDefinition> {
+        let mut all: Vec<InstrumentDefinition> = self.by_id.values().cloned().collect();
+        all.sort_by_key(|d| d.instrument_id);
+        all
+    }
+
+    /// Stores a definition unless a newer version is already held. Returns
+    /// whether it was stored.
+    pub fn apply(&mut self, definition: InstrumentDefinition) -> bool {
+        if let Some(existing) = self.by_id.get(&definition.instrument_id) {
+            if existing.version > definition.version {
+                return false;
+            }
+            if existing.symbol != definition.symbol {
+                self.ids_by_symbol.remove(&existing.symbol);
+            }
+        }
+        self.ids_by_symbol.insert(definition.symbol.clone(), definition.instrument_id);
+        self.by_id.insert(definition.instrument_id, definition);
+        true
+    }
+}
+
+// --- Loading ---
+
+pub fn default_instruments() -> Vec<InstrumentDefinition> {
+    let definition = |instrument_id, symbol: &str, venue: &str, asset_class, tick_size, multiplier| InstrumentDefinition {
+        instrument_id,
+        symbol: symbol.to_string(),
+        venue: venue.to_string(),
+        asset_class,
+        currency: "USD".to_string(),
+        tick_size: Price::from_f64(tick_size),
+        lot_size: 1,
+        multiplier: Money::from_f64(multiplier),
+        price_decimals: DEFAULT_PRICE_DECIMALS,
+        option: None,
+        version: 1,
+    };
+    let mut btc_call = definition(5, "BTC-26DEC26-70000-C", "DERIBIT", AssetClass::Option, 0.5, 1.0);
+    btc_call.option = Some(OptionTerms {
+        underlying: "BTC".to_string(),
+        right: OptionRight::Call,
+        strike: Price::from_f64(70_000.0),
+        expiry_utc: DateTime::from_timestamp(1_798_272_000, 0).unwrap_or_default(),
+    });
+    vec![
+        definition(1, "BTC", "VENUE_A", AssetClass::Crypto, 0.01, 1.0),
+        definition(2, "ETH", "VENUE_B", AssetClass::Crypto, 0.01, 1.0),
+        definition(3, "ESZ25", "CME", AssetClass::Future, 0.25, 50.0),
+        definition(4, "INVT", "NASDAQ", AssetClass::Equity, 0.01, 1.0),
+        btc_call,
+    ]
+}
+
+/// Reads a JSON array of definitions; every one must pass `validate`.
+pub fn load_from_file(path: &str) -> std::io::Result<Vec<InstrumentDefinition>> {
+    let contents = std::fs::read_to_string(path)?;
+    let definitions: Vec<InstrumentDefinition> =
+        serde_json::from_str(&contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    for definition in &definitions {
+        definition.validate().map_err(|reason| {
+            let reason = format!("instrument {} ({}): {}", definition.instrument_id, definition.symbol, reason);
+            std::io::Error::new(std::io::ErrorKind::InvalidData, reason)
+        })?;
+    }
+    Ok(definitions)
+}
+
+/// Loads definitions from QA_REFDATA_PATH, or the built-in set if it is unset.
+pub fn load_from_env() -> Vec<InstrumentDefinition> {
+    let Ok(path) = std::env::var("QA_REFDATA_PATH") else {
+        return default_instruments();
+    };
+    match load_from_file(&path) {
+        Ok(definitions) => definitions,
+        Err(e) => {
+            println!("Failed to read instrument definitions from {} ({}); using the built-in set.", path, e);
+            default_instruments()
+        }
+    }
+}
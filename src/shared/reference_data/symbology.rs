@@ -0,0 +1,264 @@
+/*
+ * QuantumArb 2.0 - Shared: Symbology
+ *
+ * File: src/shared/reference_data/symbology.rs
+ *
+ * Description:
+ * Each venue names the same asset its own way: BTC is "BTC-USD" on VENUE_A
+ * and "XBTUSD" on VENUE_B, and the ESZ25 future is "ESZ5", security id 118,
+ * on CME Globex. A `VenueMapping` ties one venue's symbol (and its own
+ * instrument id, where it has one) to the canonical instrument id used on
+ * the bus, and `Symbology` translates both ways:
+ *
+ *   resolve    venue symbol, venue instrument id or alias -> canonical id,
+ *              for feed handlers, the consolidator and the graph engine
+ *   to_venue   canonical id -> the venue's symbol, for the gateway's orders
+ *
+ * Each mapping is valid from `valid_from` until `valid_until` (either open),
+ * so a venue renaming a symbol, or a contract rolling, is a new mapping
+ * rather than an edit, and historical data still resolves as of its time.
+ *
+ * Aliases are the other names a venue's symbol turns up under (in files,
+ * drop copies, an operator's request). They match fuzzily: case and
+ * separators are ignored, so "btc/usd", "BTC_USD" and "BTC-USD" are the same
+ * name. An exact symbol or venue id always wins; a fuzzy name matching more
+ * than one instrument resolves to none.
+ *
+ * Mappings are loaded from QA_SYMBOLOGY_PATH (a JSON array of
+ * `VenueMapping`); otherwise the built-in set is used.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+
+/// Topic the reference data service publishes added mappings on.
+pub const SYMBOLOGY_TOPIC: &str = "reference.symbology";
+
+// --- Data Structures ---
+
+/// One venue's name for a canonical instrument over a period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct VenueMapping {
+    pub instrument_id: u32,
+    pub venue: String,
+    pub venue_symbol: String,
+    /// The venue's own id for the instrument (CME security id, ...).
+    #[serde(default)]
+    pub venue_instrument_id: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    #[serde(default)]
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl VenueMapping {
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from.is_none_or(|from| at >= from) && self.valid_until.is_none_or(|until| at < until)
+    }
+
+    /// Reasons the mapping can't be accepted, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.venue.trim().is_empty() || self.venue_symbol.trim().is_empty() {
+            return Err("venue and venue_symbol must not be empty".to_string());
+        }
+        if let (Some(from), Some(until)) = (self.valid_from, self.valid_until) {
+            if until <= from {
+                return Err("valid_until must be after valid_from".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn overlaps(&self, other: &VenueMapping) -> bool {
+        let starts_before_other_ends = match (self.valid_from, other.valid_until) {
+            (Some(from), Some(until)) => from < until,
+            _ => true,
+        };
+        let ends_after_other_starts = match (self.valid_until, other.valid_from) {
+            (Some(until), Some(from)) => until > from,
+            _ => true,
+        };
+        starts_before_other_ends && ends_after_other_starts
+    }
+
+    fn names(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.venue_symbol).chain(&self.aliases)
+    }
+}
+
+/// A name with case and separators dropped, for fuzzy matching.
+pub fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+// --- Translation ---
+
+/// Every venue mapping; see the module description.
+#[derive(Debug, Clone, Default)]
+pub struct Symbology {
+    mappings: Vec<VenueMapping>,
+}
+
+impl Symbology {
+    /// The mappings that validate and do not conflict with an earlier one;
+    /// the rest are reported and dropped.
+    pub fn new(mappings: Vec<VenueMapping>) -> Self {
+        let mut symbology = Symbology::default();
+        for mapping in mappings {
+            if let Err(reason) = symbology.add(mapping.clone()) {
+                println!("Dropping the {} mapping for {}: {}", mapping.venue, mapping.venue_symbol, reason);
+            }
+        }
+        symbology
+    }
+
+    /// The built-in mappings.
+    pub fn seeded() -> Self {
+        Symbology::new(default_mappings())
+    }
+
+    pub fn mappings(&self) -> &[VenueMapping] {
+        &self.mappings
+    }
+
+    /// Adds a mapping unless, over an overlapping period, the venue already
+    /// maps the instrument to another symbol or the symbol to another
+    /// instrument.
+    pub fn add(&mut self, mapping: VenueMapping) -> Result<(), String> {
+        mapping.validate()?;
+        let conflict = self.mappings.iter().find(|held| {
+            held.venue == mapping.venue
+                && held.overlaps(&mapping)
+                && (held.instrument_id == mapping.instrument_id) != (held.venue_symbol == mapping.venue_symbol)
+        });
+        if let Some(held) = conflict {
+            return Err(format!(
+                "{} already maps {} to instrument {} over an overlapping period",
+                held.venue, held.venue_symbol, held.instrument_id
+            ));
+        }
+        self.mappings.retain(|held| {
+            !(held.venue == mapping.venue
+                && held.venue_symbol == mapping.venue_symbol
+                && held.valid_from == mapping.valid_from)
+        });
+        self.mappings.push(mapping);
+        Ok(())
+    }
+
+    /// The mapping a venue's symbol, instrument id or alias names at `at`.
+    pub fn resolve(&self, venue: &str, name: &str, at: DateTime<Utc>) -> Option<&VenueMapping> {
+        let mut current = self.mappings.iter().filter(|m| m.venue == venue && m.is_valid_at(at));
+        let exact = current
+            .clone()
+            .find(|m| m.venue_symbol == name || m.venue_instrument_id.as_deref() == Some(name));
+        if exact.is_some() {
+            return exact;
+        }
+        let wanted = normalize(name);
+        let first = current.find(|m| m.names().any(|n| normalize(n) == wanted))?;
+        let ambiguous = self.mappings.iter().any(|m| {
+            m.venue == venue
+                && m.is_valid_at(at)
+                && m.instrument_id != first.instrument_id
+                && m.names().any(|n| normalize(n) == wanted)
+        });
+        (!ambiguous).then_some(first)
+    }
+
+    /// The canonical instrument id for a venue's name at `at`.
+    pub fn canonical_id(&self, venue: &str, name: &str, at: DateTime<Utc>) -> Option<u32> {
+        self.resolve(venue, name, at).map(|m| m.instrument_id)
+    }
+
+    /// How `venue` names the canonical instrument at `at`.
+    pub fn to_venue(&self, instrument_id: u32, venue: &str, at: DateTime<Utc>) -> Option<&VenueMapping> {
+        self.mappings.iter().find(|m| m.instrument_id == instrument_id && m.venue == venue && m.is_valid_at(at))
+    }
+}
+
+// --- Loading ---
+
+pub fn default_mappings() -> Vec<VenueMapping> {
+    let mapping = |instrument_id, venue: &str, venue_symbol: &str, aliases: &[&str]| VenueMapping {
+        instrument_id,
+        venue: venue.to_string(),
+        venue_symbol: venue_symbol.to_string(),
+        venue_instrument_id: None,
+        aliases: aliases.iter().map(|a| a.to_string()).collect(),
+        valid_from: None,
+        valid_until: None,
+    };
+    // VENUE_B listed BTC as XBTUSD until it renamed it on 1 June 2025.
+    let renamed = DateTime::from_timestamp(1_748_736_000, 0).unwrap_or_default();
+    vec![
+        mapping(1, "VENUE_A", "BTC-USD", &["XBT-USD"]),
+        VenueMapping { valid_until: Some(renamed), ..mapping(1, "VENUE_B", "XBTUSD", &[]) },
+        VenueMapping { valid_from: Some(renamed), ..mapping(1, "VENUE_B", "BTCUSD", &["XBTUSD"]) },
+        mapping(2, "VENUE_A", "ETH-USD", &[]),
+        mapping(2, "VENUE_B", "ETHUSD", &[]),
+        mapping(1, "BINANCE", "BTCUSDT", &[]),
+        mapping(2, "BINANCE", "ETHUSDT", &[]),
+        VenueMapping {
+            venue_instrument_id: Some("118".to_string()),
+            ..mapping(3, "CME", "ESZ5", &["ESZ25", "ES DEC25"])
+        },
+        mapping(4, "NASDAQ", "INVT", &[]),
+        mapping(5, "DERIBIT", "BTC-26DEC26-70000-C", &[]),
+    ]
+}
+
+pub fn load_mappings_from_file(path: &str) -> std::io::Result<Vec<VenueMapping>> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Loads mappings from QA_SYMBOLOGY_PATH, or the built-in set if it is unset.
+pub fn load_mappings_from_env() -> Vec<VenueMapping> {
+    let Ok(path) = std::env::var("QA_SYMBOLOGY_PATH") else {
+        return default_mappings();
+    };
+    match load_mappings_from_file(&path) {
+        Ok(mappings) => mappings,
+        Err(e) => {
+            println!("Failed to read venue mappings from {} ({}); using the built-in set.", path, e);
+            default_mappings()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn venue_names_resolve_to_canonical_ids_as_of_their_time() {
+        let symbology = Symbology::seeded();
+        let now = Utc::now();
+        let before_rename = DateTime::from_timestamp(1_735_689_600, 0).unwrap();
+
+        assert_eq!(symbology.canonical_id("VENUE_A", "BTC-USD", now), Some(1));
+        assert_eq!(symbology.canonical_id("VENUE_A", "xbt/usd", now), Some(1), "fuzzy alias");
+        assert_eq!(symbology.canonical_id("CME", "118", now), Some(3), "venue instrument id");
+        assert_eq!(symbology.canonical_id("CME", "es-dec25", now), Some(3));
+        assert_eq!(symbology.canonical_id("VENUE_A", "ETHUSD", now), Some(2));
+        assert_eq!(symbology.canonical_id("VENUE_A", "ESZ5", now), None, "another venue's symbol");
+
+        // VENUE_B's rename: the old symbol until then, the new one after.
+        assert_eq!(symbology.to_venue(1, "VENUE_B", before_rename).unwrap().venue_symbol, "XBTUSD");
+        assert_eq!(symbology.to_venue(1, "VENUE_B", now).unwrap().venue_symbol, "BTCUSD");
+        assert_eq!(symbology.canonical_id("VENUE_B", "XBTUSD", now), Some(1), "kept as an alias");
+
+        let mut symbology = symbology;
+        let clash = VenueMapping { instrument_id: 2, ..symbology.to_venue(1, "VENUE_A", now).unwrap().clone() };
+        assert!(symbology.add(clash).is_err());
+        let xbt = VenueMapping { instrument_id: 4, venue_symbol: "XBT_USD".to_string(), ..default_mappings()[0].clone() };
+        symbology.add(xbt).unwrap();
+        assert_eq!(symbology.canonical_id("VENUE_A", "xbt usd", now), None, "two instruments answer to it");
+        assert_eq!(symbology.canonical_id("VENUE_A", "XBT_USD", now), Some(4), "the exact symbol still wins");
+    }
+}
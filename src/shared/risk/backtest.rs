@@ -0,0 +1,226 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: VaR Model Backtesting
+ *
+ * File: src/shared/risk/backtest.rs
+ *
+ * Description:
+ * Statistical validation of the VaR model. Each trading day the VaR forecast
+ * made at the start of the day is compared with the P&L actually realized;
+ * a loss larger than the forecast is an "exception". Over a window of days:
+ *
+ * - Kupiec's proportion-of-failures (POF) test checks whether the exception
+ *   rate is consistent with the model's confidence level. Its likelihood-ratio
+ *   statistic is chi-squared with one degree of freedom under the null.
+ * - The Basel traffic light classifies the exception count by its cumulative
+ *   binomial probability: green below 95%, yellow below 99.99%, red above.
+ *   For 250 days at 99% this gives the familiar 0-4 / 5-9 / 10+ zones.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_openapi::ApiSchema;
+use serde::Serialize;
+
+/// Basel backtesting window in trading days.
+pub const BACKTEST_WINDOW: usize = 250;
+/// Significance level for rejecting the model in the Kupiec test.
+const KUPIEC_SIGNIFICANCE: f64 = 0.05;
+
+// --- Data Structures ---
+
+/// One trading day: the forecast made at its start and what happened.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BacktestObservation {
+    pub period_start_utc: DateTime<Utc>,
+    pub period_end_utc: DateTime<Utc>,
+    pub confidence_level: f64,
+    pub predicted_var: f64,
+    pub realized_pnl: f64,
+    pub exception: bool,
+}
+
+impl BacktestObservation {
+    pub fn new(
+        period_start_utc: DateTime<Utc>,
+        period_end_utc: DateTime<Utc>,
+        confidence_level: f64,
+        predicted_var: f64,
+        realized_pnl: f64,
+    ) -> Self {
+        BacktestObservation {
+            period_start_utc,
+            period_end_utc,
+            confidence_level,
+            predicted_var,
+            realized_pnl,
+            exception: -realized_pnl > predicted_var,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, ApiSchema)]
+pub enum TrafficLight {
+    Green,
+    Yellow,
+    Red,
+}
+
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct KupiecResult {
+    pub lr_statistic: f64,
+    pub p_value: f64,
+    /// True if the exception rate is inconsistent with the confidence level.
+    pub reject_model: bool,
+}
+
+/// Body of a GET /var/backtest response.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct BacktestReport {
+    pub observations: usize,
+    pub exceptions: usize,
+    pub expected_exceptions: f64,
+    pub confidence_level: f64,
+    pub kupiec: Option<KupiecResult>,
+    pub traffic_light: Option<TrafficLight>,
+    pub days: Vec<BacktestObservation>,
+}
+
+// --- Tests ---
+
+/// Runs both tests over the observations (oldest first). The confidence level
+/// of the most recent forecast is taken as the model's.
+pub fn evaluate(observations: &[BacktestObservation]) -> BacktestReport {
+    let n = observations.len();
+    let exceptions = observations.iter().filter(|o| o.exception).count();
+    let confidence_level = observations.last().map_or(0.99, |o| o.confidence_level);
+    let p = 1.0 - confidence_level;
+
+    BacktestReport {
+        observations: n,
+        exceptions,
+        expected_exceptions: n as f64 * p,
+        confidence_level,
+        kupiec: (n > 0).then(|| kupiec_pof(n, exceptions, p)),
+        traffic_light: (n > 0).then(|| traffic_light(n, exceptions, p)),
+        days: observations.to_vec(),
+    }
+}
+
+/// Kupiec's proportion-of-failures likelihood-ratio test.
+pub fn kupiec_pof(n: usize, x: usize, p: f64) -> KupiecResult {
+    let (n_f, x_f) = (n as f64, x as f64);
+    let observed = x_f / n_f;
+    let log_likelihood = |rate: f64| xlogy(n_f - x_f, 1.0 - rate) + xlogy(x_f, rate);
+    let lr_statistic = (-2.0 * (log_likelihood(p) - log_likelihood(observed))).max(0.0);
+    let p_value = chi_squared_1_sf(lr_statistic);
+    KupiecResult { lr_statistic, p_value, reject_model: p_value < KUPIEC_SIGNIFICANCE }
+}
+
+/// Basel zone for `x` exceptions out of `n` days at exception probability `p`.
+pub fn traffic_light(n: usize, x: usize, p: f64) -> TrafficLight {
+    let cumulative = binomial_cdf(n, x, p);
+    if cumulative < 0.95 {
+        TrafficLight::Green
+    } else if cumulative < 0.9999 {
+        TrafficLight::Yellow
+    } else {
+        TrafficLight::Red
+    }
+}
+
+// --- Numerics ---
+
+/// x * ln(y), with the 0 * ln(0) = 0 convention used by likelihoods.
+fn xlogy(x: f64, y: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x * y.ln()
+    }
+}
+
+/// P(X <= x) for X ~ Binomial(n, p), summed in log space to stay stable.
+fn binomial_cdf(n: usize, x: usize, p: f64) -> f64 {
+    if p <= 0.0 {
+        return 1.0;
+    }
+    let mut log_choose = 0.0; // ln C(n, k), built up incrementally
+    let mut total = 0.0;
+    for k in 0..=x.min(n) {
+        if k > 0 {
+            log_choose += ((n - k + 1) as f64).ln() - (k as f64).ln();
+        }
+        total += (log_choose + xlogy(k as f64, p) + xlogy((n - k) as f64, 1.0 - p)).exp();
+    }
+    total.min(1.0)
+}
+
+/// Survival function of the chi-squared distribution with one degree of freedom.
+fn chi_squared_1_sf(statistic: f64) -> f64 {
+    erfc((statistic / 2.0).sqrt())
+}
+
+/// Complementary error function (Numerical Recipes `erfcc`, |error| < 1.2e-7).
+pub(crate) fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binomial_cdf_matches_the_basel_table() {
+        // 250 days at 99%: the cumulative probabilities behind the zones.
+        let cases = [(0, 0.0811), (4, 0.8922), (5, 0.9588), (9, 0.9997), (10, 0.99995)];
+        for (exceptions, expected) in cases {
+            let cumulative = binomial_cdf(BACKTEST_WINDOW, exceptions, 0.01);
+            assert!((cumulative - expected).abs() < 1e-4, "{} exceptions: {}", exceptions, cumulative);
+        }
+        assert!(binomial_cdf(BACKTEST_WINDOW, BACKTEST_WINDOW, 0.01) > 1.0 - 1e-12);
+        assert_eq!(binomial_cdf(10, 0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn traffic_light_zones_at_250_days_and_99_percent() {
+        let zone = |exceptions| traffic_light(BACKTEST_WINDOW, exceptions, 0.01);
+        assert_eq!((zone(0), zone(4)), (TrafficLight::Green, TrafficLight::Green));
+        assert_eq!((zone(5), zone(9)), (TrafficLight::Yellow, TrafficLight::Yellow));
+        assert_eq!(zone(10), TrafficLight::Red);
+    }
+
+    #[test]
+    fn kupiec_accepts_rates_near_the_expected_one_and_rejects_the_rest() {
+        let at = |exceptions| kupiec_pof(BACKTEST_WINDOW, exceptions, 0.01);
+        let expected = at(2);
+        assert!((expected.lr_statistic - 0.1084).abs() < 1e-3 && !expected.reject_model, "{:?}", expected);
+        assert!(!at(6).reject_model, "p = 0.059");
+        let seven = at(7);
+        assert!((seven.lr_statistic - 5.497).abs() < 1e-3 && (seven.p_value - 0.0190).abs() < 1e-3, "{:?}", seven);
+        assert!(seven.reject_model);
+        assert!(at(0).reject_model, "no exceptions in 250 days is too few at 99%");
+        assert!(at(10).p_value < 0.001);
+    }
+
+    #[test]
+    fn evaluate_counts_losses_beyond_the_forecast() {
+        let day = |pnl| BacktestObservation::new(Utc::now(), Utc::now(), 0.99, 1_000.0, pnl);
+        let report = evaluate(&[day(-500.0), day(-1_500.0), day(200.0), day(-1_000.0)]);
+        assert_eq!((report.observations, report.exceptions), (4, 1));
+        assert!((report.expected_exceptions - 0.04).abs() < 1e-9);
+        let empty = evaluate(&[]);
+        assert!(empty.kupiec.is_none() && empty.traffic_light.is_none());
+    }
+}
@@ -0,0 +1,136 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Order Limit Checks
+ *
+ * File: src/shared/risk/checks.rs
+ *
+ * Description:
+ * The in-memory part of the pre-trade check. The gateway loads an order's
+ * limits and exposures first (from Redis, the reference data service and
+ * the portfolio manager's reports); the checks here then run without any
+ * I/O, in order: instrument tick and lot size, the account's dynamic order
+ * size limit, concentration caps, and counterparty credit. The first failing
+ * check rejects the order. Notionals are taken in the instrument's own
+ * price scale and multiplier (`quantumarb-money`).
+ *
+ * benches/pre_trade_risk.rs times these checks on their own.
+ */
+
+use crate::concentration::{self, ConcentrationLimits, Exposures};
+use crate::counterparty::{self, CounterpartyLimits};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Price;
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_types::CounterpartyExposure;
+use quantumarb_wire::OrderRequest;
+use std::collections::HashMap;
+
+/// Everything the checks read for one order.
+pub struct OrderLimits<'a> {
+    pub instruments: &'a ReferenceData,
+    /// The account's current order size limit, after the sandbox multiplier.
+    pub max_order_size: u32,
+    pub concentration: &'a ConcentrationLimits,
+    /// None until the first portfolio snapshot arrives; concentration caps
+    /// are skipped until then rather than halting trading.
+    pub exposures: Option<&'a Exposures>,
+    /// Credit limits per counterparty, after the sandbox multiplier.
+    pub counterparty: &'a CounterpartyLimits,
+    pub counterparty_exposures: &'a HashMap<String, CounterpartyExposure>,
+}
+
+/// Runs the limit checks against one order.
+pub fn check_order(limits: &OrderLimits, order: &OrderRequest) -> Result<(), Rejection> {
+    let instrument = check_instrument(limits.instruments, order)?;
+
+    if order.size > limits.max_order_size {
+        return Err(Rejection::new(
+            RejectCode::RiskOrderSizeLimit,
+            format!("Order size {} exceeds current dynamic limit {}", order.size, limits.max_order_size),
+        ));
+    }
+
+    if let Some(exposures) = limits.exposures {
+        concentration::check(limits.concentration, exposures, instrument, order)?;
+    }
+
+    // Exchange orders face the venue they are routed to.
+    if let Some(venue) = concentration::venue_name(order.venue_id) {
+        counterparty::check(limits.counterparty, limits.counterparty_exposures, venue, instrument, order)?;
+    }
+    Ok(())
+}
+
+/// Rejects orders for unknown instruments or off their tick or lot size.
+/// Returns the instrument's definition for the checks that follow.
+fn check_instrument<'a>(
+    instruments: &'a ReferenceData,
+    order: &OrderRequest,
+) -> Result<&'a InstrumentDefinition, Rejection> {
+    let definition = instruments.get(order.instrument_id).ok_or_else(|| {
+        Rejection::new(RejectCode::RiskUnknownInstrument, format!("Unknown instrument {}", order.instrument_id))
+    })?;
+    let price = Price::from_wire(order.price, definition.price_decimals)
+        .map_err(|e| Rejection::new(RejectCode::RiskInvalidTickSize, format!("Price {}", e)))?;
+    definition.check_price(price).map_err(|reason| Rejection::new(RejectCode::RiskInvalidTickSize, reason))?;
+    definition.check_size(order.size).map_err(|reason| Rejection::new(RejectCode::RiskInvalidLotSize, reason))?;
+    Ok(definition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_wire::{OrderPriority, OrderSide, TradingMode};
+    use uuid::Uuid;
+
+    fn order(instrument_id: u32, price: u64, size: u32) -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::new_v4(),
+            account_id: 101,
+            instrument_id,
+            side: OrderSide::Buy,
+            price,
+            size,
+            stamps: Default::default(),
+            venue_id: 0,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    fn check(order: &OrderRequest) -> Result<(), RejectCode> {
+        let instruments = ReferenceData::seeded();
+        let limits = OrderLimits {
+            instruments: &instruments,
+            max_order_size: 100,
+            concentration: &ConcentrationLimits::default(),
+            exposures: None,
+            counterparty: &CounterpartyLimits::new(),
+            counterparty_exposures: &HashMap::new(),
+        };
+        check_order(&limits, order).map_err(|rejection| rejection.code)
+    }
+
+    #[test]
+    fn approves_an_order_within_limits() {
+        assert_eq!(check(&order(1, 60150_00, 10)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unknown_instruments() {
+        assert_eq!(check(&order(99, 60150_00, 10)), Err(RejectCode::RiskUnknownInstrument));
+    }
+
+    #[test]
+    fn rejects_prices_off_the_tick() {
+        // ESZ25 trades in quarter points.
+        assert_eq!(check(&order(3, 5000_10, 1)), Err(RejectCode::RiskInvalidTickSize));
+        assert_eq!(check(&order(3, 5000_25, 1)), Ok(()));
+        assert_eq!(check(&order(1, u64::MAX, 1)), Err(RejectCode::RiskInvalidTickSize), "beyond any price");
+    }
+
+    #[test]
+    fn rejects_orders_over_the_size_limit() {
+        assert_eq!(check(&order(1, 60150_00, 101)), Err(RejectCode::RiskOrderSizeLimit));
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Concentration Limits
+ *
+ * File: src/shared/risk/concentration.rs
+ *
+ * Description:
+ * Caps on how much of the portfolio's gross exposure may sit in a single
+ * symbol, sector or venue. Exposures come from the portfolio manager's live
+ * snapshot; a new order is rejected if, after the fill, its symbol, sector
+ * or venue would exceed the configured share. Orders that reduce a bucket's
+ * exposure are always allowed, and the checks only apply once the portfolio
+ * is large enough for percentages to be meaningful.
+ *
+ * Exposures are notionals in Money: quantity x price x the instrument's
+ * contract multiplier. `Exposures::metrics` reports each bucket's share of
+ * gross exposure, for the portfolio manager's what-if API.
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::InstrumentDefinition;
+use quantumarb_types::PortfolioSnapshot;
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// --- Configuration ---
+
+/// Firm-wide caps, as fractions of gross exposure (0.4 = 40%).
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct ConcentrationLimits {
+    pub max_symbol_pct: f64,
+    pub max_sector_pct: f64,
+    pub max_venue_pct: f64,
+    /// Gross exposure below which the caps are not enforced.
+    pub min_portfolio_exposure: Money,
+}
+
+impl Default for ConcentrationLimits {
+    fn default() -> Self {
+        ConcentrationLimits {
+            max_symbol_pct: 0.40,
+            max_sector_pct: 0.60,
+            max_venue_pct: 0.70,
+            min_portfolio_exposure: Money::from_f64(100_000.0),
+        }
+    }
+}
+
+/// Body of a PUT /concentration-limits request; omitted fields are unchanged.
+#[derive(Debug, Deserialize, ApiSchema)]
+pub struct ConcentrationLimitsUpdate {
+    pub max_symbol_pct: Option<f64>,
+    pub max_sector_pct: Option<f64>,
+    pub max_venue_pct: Option<f64>,
+    pub min_portfolio_exposure: Option<Money>,
+}
+
+impl ConcentrationLimits {
+    pub fn apply(&mut self, update: ConcentrationLimitsUpdate) {
+        if let Some(v) = update.max_symbol_pct {
+            self.max_symbol_pct = v;
+        }
+        if let Some(v) = update.max_sector_pct {
+            self.max_sector_pct = v;
+        }
+        if let Some(v) = update.max_venue_pct {
+            self.max_venue_pct = v;
+        }
+        if let Some(v) = update.min_portfolio_exposure {
+            self.min_portfolio_exposure = v;
+        }
+    }
+}
+
+// --- Reference Data ---
+
+// Static instrument and venue reference data used to bucket exposures.
+
+pub fn instrument_symbol(instrument_id: u32) -> Option<&'static str> {
+    match instrument_id {
+        1 => Some("BTC"),
+        2 => Some("ETH"),
+        3 => Some("ESZ25"),
+        _ => None,
+    }
+}
+
+pub fn sector_of(symbol: &str) -> &'static str {
+    match symbol {
+        "BTC" | "ETH" => "Crypto",
+        "ESZ25" => "Equity Index Futures",
+        _ => "Other",
+    }
+}
+
+pub fn venue_name(venue_id: u32) -> Option<&'static str> {
+    match venue_id {
+        1 => Some("VENUE_A"),
+        2 => Some("VENUE_B"),
+        3 => Some("CME"),
+        _ => None,
+    }
+}
+
+// --- Portfolio Exposure ---
+
+/// Each bucket's share of gross exposure (0.4 = 40%).
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ConcentrationMetrics {
+    pub gross_exposure: Money,
+    pub by_symbol: HashMap<String, f64>,
+    pub by_sector: HashMap<String, f64>,
+    pub by_venue: HashMap<String, f64>,
+    /// Largest single-symbol share.
+    pub max_symbol_pct: f64,
+    /// Herfindahl index of the symbol shares: 1 for a single-symbol book,
+    /// 1/n for n equal positions.
+    pub symbol_herfindahl: f64,
+}
+
+/// Signed notional exposure bucketed by symbol, sector and venue.
+#[derive(Debug, Clone, Default)]
+pub struct Exposures {
+    by_symbol: HashMap<String, Money>,
+    by_sector: HashMap<String, Money>,
+    by_venue: HashMap<String, Money>,
+}
+
+impl Exposures {
+    pub fn from_snapshot(snapshot: &PortfolioSnapshot) -> Exposures {
+        let mut exposures = Exposures::default();
+        for position in snapshot.positions.values() {
+            let notional =
+                |quantity: i64| Money::notional(position.current_market_price, Quantity(quantity), position.multiplier);
+            exposures.add(&position.symbol, None, notional(position.quantity));
+            for (venue, quantity) in &position.venue_quantities {
+                *exposures.by_venue.entry(venue.clone()).or_default() += notional(*quantity);
+            }
+        }
+        exposures
+    }
+
+    fn add(&mut self, symbol: &str, venue: Option<&str>, notional: Money) {
+        *self.by_symbol.entry(symbol.to_string()).or_default() += notional;
+        *self.by_sector.entry(sector_of(symbol).to_string()).or_default() += notional;
+        if let Some(venue) = venue {
+            *self.by_venue.entry(venue.to_string()).or_default() += notional;
+        }
+    }
+
+    /// Signed notional held in `symbol`.
+    pub fn symbol(&self, symbol: &str) -> Money {
+        self.by_symbol.get(symbol).copied().unwrap_or_default()
+    }
+
+    fn gross(&self) -> Money {
+        self.by_symbol.values().map(|v| v.abs()).sum()
+    }
+
+    pub fn metrics(&self) -> ConcentrationMetrics {
+        let gross = self.gross();
+        let shares = |buckets: &HashMap<String, Money>| -> HashMap<String, f64> {
+            buckets
+                .iter()
+                .filter(|(_, v)| !v.is_zero())
+                .map(|(k, v)| (k.clone(), v.abs().ratio(gross)))
+                .collect()
+        };
+        let by_symbol = shares(&self.by_symbol);
+        ConcentrationMetrics {
+            gross_exposure: gross,
+            max_symbol_pct: by_symbol.values().copied().fold(0.0, f64::max),
+            symbol_herfindahl: by_symbol.values().map(|s| s * s).sum(),
+            by_symbol,
+            by_sector: shares(&self.by_sector),
+            by_venue: shares(&self.by_venue),
+        }
+    }
+}
+
+// --- Check ---
+
+/// Rejects the order if it would push any of its buckets over its cap.
+pub fn check(
+    limits: &ConcentrationLimits,
+    current: &Exposures,
+    instrument: &InstrumentDefinition,
+    order: &OrderRequest,
+) -> Result<(), Rejection> {
+    let Some(symbol) = instrument_symbol(order.instrument_id) else {
+        return Ok(());
+    };
+    let sector = sector_of(symbol);
+    let venue = venue_name(order.venue_id);
+    let quantity = match order.side {
+        OrderSide::Buy => Quantity::from(order.size),
+        OrderSide::Sell => -Quantity::from(order.size),
+    };
+    let notional = instrument.notional(instrument.price(order.price), quantity);
+
+    let mut after = current.clone();
+    after.add(symbol, venue, notional);
+    let gross = after.gross();
+    if gross < limits.min_portfolio_exposure {
+        return Ok(());
+    }
+
+    let buckets = [
+        ("symbol", symbol, &current.by_symbol, &after.by_symbol, limits.max_symbol_pct),
+        ("sector", sector, &current.by_sector, &after.by_sector, limits.max_sector_pct),
+        ("venue", venue.unwrap_or(""), &current.by_venue, &after.by_venue, limits.max_venue_pct),
+    ];
+    for (dimension, key, before, after, cap) in buckets {
+        let Some(new_exposure) = after.get(key).map(|v| v.abs()) else {
+            continue;
+        };
+        let old_exposure = before.get(key).map_or(Money::ZERO, |v| v.abs());
+        let share = new_exposure.ratio(gross);
+        if new_exposure > old_exposure && share > cap {
+            return Err(Rejection::new(
+                RejectCode::RiskConcentrationLimit,
+                format!(
+                    "Order would put {:.1}% of gross exposure in {} {} (cap {:.1}%)",
+                    share * 100.0,
+                    dimension,
+                    key,
+                    cap * 100.0
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_refdata::ReferenceData;
+    use quantumarb_wire::{OrderPriority, TradingMode};
+    use uuid::Uuid;
+
+    /// `size` of `instrument_id` at `price` whole points, routed to `venue_id`.
+    fn order(instrument_id: u32, price: u64, size: u32, side: OrderSide, venue_id: u32) -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::new_v4(),
+            account_id: 101,
+            instrument_id,
+            side,
+            price: price * 100,
+            size,
+            stamps: Default::default(),
+            venue_id,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    /// Exposures of (symbol, venue, notional) rows.
+    fn exposures(rows: &[(&str, &str, f64)]) -> Exposures {
+        let mut exposures = Exposures::default();
+        for (symbol, venue, notional) in rows {
+            exposures.add(symbol, Some(venue), Money::from_f64(*notional));
+        }
+        exposures
+    }
+
+    fn check_with(limits: &ConcentrationLimits, current: &Exposures, order: &OrderRequest) -> Result<(), String> {
+        let instruments = ReferenceData::seeded();
+        let instrument = instruments.get(order.instrument_id).unwrap();
+        check(limits, current, instrument, order).map_err(|rejection| rejection.message)
+    }
+
+    #[test]
+    fn caps_a_symbol_share_but_not_orders_that_reduce_it() {
+        let current =
+            exposures(&[("BTC", "VENUE_A", 50_000.0), ("ETH", "VENUE_B", 50_000.0), ("ESZ25", "CME", 100_000.0)]);
+        let limits = ConcentrationLimits::default();
+        // BTC would be 110,000 of 260,000: 42%.
+        let error = check_with(&limits, &current, &order(1, 60_000, 1, OrderSide::Buy, 1)).unwrap_err();
+        assert!(error.contains("symbol BTC (cap 40.0%)"), "{}", error);
+        assert_eq!(check_with(&limits, &current, &order(1, 20_000, 1, OrderSide::Buy, 1)), Ok(()));
+
+        let heavy = exposures(&[("BTC", "VENUE_A", 150_000.0), ("ESZ25", "CME", 100_000.0)]);
+        assert_eq!(check_with(&limits, &heavy, &order(1, 60_000, 1, OrderSide::Sell, 1)), Ok(()), "reduces BTC");
+    }
+
+    #[test]
+    fn caps_a_sector_share() {
+        // Crypto would be 150,000 of 240,000 (62.5%); ETH alone 33%.
+        let current =
+            exposures(&[("BTC", "VENUE_A", 70_000.0), ("ETH", "VENUE_B", 60_000.0), ("ESZ25", "CME", 90_000.0)]);
+        let error = check_with(&ConcentrationLimits::default(), &current, &order(2, 2_000, 10, OrderSide::Buy, 2));
+        assert!(error.unwrap_err().contains("sector Crypto"));
+    }
+
+    #[test]
+    fn caps_a_venue_share() {
+        let limits =
+            ConcentrationLimits { max_symbol_pct: 1.0, max_sector_pct: 1.0, max_venue_pct: 0.5, ..Default::default() };
+        // VENUE_A would hold 140,000 of 260,000: 54%.
+        let current =
+            exposures(&[("BTC", "VENUE_A", 80_000.0), ("ETH", "VENUE_B", 40_000.0), ("ESZ25", "CME", 80_000.0)]);
+        let error = check_with(&limits, &current, &order(1, 60_000, 1, OrderSide::Buy, 1)).unwrap_err();
+        assert!(error.contains("venue VENUE_A"), "{}", error);
+        let elsewhere = order(1, 60_000, 1, OrderSide::Buy, 2);
+        assert_eq!(check_with(&limits, &current, &elsewhere), Ok(()), "routed to VENUE_B");
+    }
+
+    #[test]
+    fn small_portfolios_are_not_capped() {
+        let current = exposures(&[("BTC", "VENUE_A", 20_000.0)]);
+        let order = order(1, 60_000, 1, OrderSide::Buy, 1);
+        assert_eq!(check_with(&ConcentrationLimits::default(), &current, &order), Ok(()));
+    }
+}
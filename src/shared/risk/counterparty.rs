@@ -0,0 +1,145 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Counterparty Credit Limits
+ *
+ * File: src/shared/risk/counterparty.rs
+ *
+ * Description:
+ * Per-counterparty credit limits. Current exposure (positions held at the
+ * counterparty plus unsettled non-DVP trades) comes from the portfolio
+ * manager's /portfolio/counterparties report. A new order adds its notional:
+ * on a non-DVP counterparty every trade is unsettled exposure until it
+ * settles, on a DVP counterparty only purchases add to what is held there.
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::InstrumentDefinition;
+use quantumarb_types::{CounterpartyExposure, SettlementModel};
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Credit limit per counterparty. Counterparties without a limit are not checked.
+pub type CounterpartyLimits = HashMap<String, Money>;
+
+/// Body of a PUT /counterparty-limits/{counterparty} request.
+#[derive(Debug, Deserialize, Serialize, ApiSchema)]
+pub struct CounterpartyLimitUpdate {
+    pub max_exposure: Money,
+}
+
+/// Rejects the order if it would take `counterparty` over its credit limit.
+pub fn check(
+    limits: &CounterpartyLimits,
+    exposures: &HashMap<String, CounterpartyExposure>,
+    counterparty: &str,
+    instrument: &InstrumentDefinition,
+    order: &OrderRequest,
+) -> Result<(), Rejection> {
+    let Some(limit) = limits.get(counterparty) else {
+        return Ok(());
+    };
+    let current = exposures.get(counterparty);
+    let current_exposure = current.map_or(Money::ZERO, |e| e.total_exposure);
+    // With no report for the counterparty yet, assume the riskier non-DVP case.
+    let dvp = current.is_some_and(|e| e.settlement_model == SettlementModel::Dvp);
+
+    let notional = instrument.notional(instrument.price(order.price), Quantity::from(order.size));
+    let added = match (dvp, order.side) {
+        (true, OrderSide::Sell) => Money::ZERO,
+        _ => notional,
+    };
+    if current_exposure + added > *limit {
+        return Err(Rejection::new(
+            RejectCode::RiskCounterpartyLimit,
+            format!(
+                "Exposure to {} would reach {:.2} (limit {:.2})",
+                counterparty,
+                current_exposure + added,
+                limit
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_refdata::ReferenceData;
+    use uuid::Uuid;
+
+    /// An order for `size` of instrument 1 (BTC) at `price` whole points.
+    fn order(side: OrderSide, size: u32, price: u64) -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::new_v4(),
+            account_id: 101,
+            instrument_id: 1,
+            side,
+            price: price * 100,
+            size,
+            stamps: Default::default(),
+            venue_id: 0,
+            mode: Default::default(),
+            priority: Default::default(),
+            strategy_id: String::new(),
+        }
+    }
+
+    fn exposure(counterparty: &str, settlement_model: SettlementModel, total: f64) -> CounterpartyExposure {
+        CounterpartyExposure {
+            counterparty: counterparty.to_string(),
+            settlement_model,
+            position_exposure: Money::from_f64(total),
+            unsettled_exposure: Money::ZERO,
+            total_exposure: Money::from_f64(total),
+        }
+    }
+
+    fn limits() -> CounterpartyLimits {
+        HashMap::from([("VENUE_A".to_string(), Money::from_f64(100_000.0))])
+    }
+
+    fn btc() -> InstrumentDefinition {
+        ReferenceData::seeded().get(1).unwrap().clone()
+    }
+
+    #[test]
+    fn counterparties_without_a_limit_are_not_checked() {
+        let exposures = HashMap::from([("VENUE_B".to_string(), exposure("VENUE_B", SettlementModel::Dvp, 1e9))]);
+        assert_eq!(check(&limits(), &exposures, "VENUE_B", &btc(), &order(OrderSide::Buy, 100, 60_000)), Ok(()));
+    }
+
+    #[test]
+    fn non_dvp_orders_add_their_notional_on_either_side() {
+        let model = SettlementModel::NonDvp { settlement_days: 1 };
+        let exposures = HashMap::from([("VENUE_A".to_string(), exposure("VENUE_A", model, 40_000.0))]);
+        let instrument = btc();
+
+        assert_eq!(check(&limits(), &exposures, "VENUE_A", &instrument, &order(OrderSide::Buy, 1, 60_000)), Ok(()));
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let rejection = check(&limits(), &exposures, "VENUE_A", &instrument, &order(side, 1, 60_001)).unwrap_err();
+            assert_eq!(rejection.code, RejectCode::RiskCounterpartyLimit);
+            assert_eq!(rejection.message, "Exposure to VENUE_A would reach 100001.00 (limit 100000.00)");
+        }
+    }
+
+    #[test]
+    fn dvp_sales_add_nothing() {
+        let exposures = HashMap::from([("VENUE_A".to_string(), exposure("VENUE_A", SettlementModel::Dvp, 99_000.0))]);
+        let instrument = btc();
+
+        assert_eq!(check(&limits(), &exposures, "VENUE_A", &instrument, &order(OrderSide::Sell, 5, 60_000)), Ok(()));
+        assert!(check(&limits(), &exposures, "VENUE_A", &instrument, &order(OrderSide::Buy, 1, 60_000)).is_err());
+    }
+
+    #[test]
+    fn unreported_counterparties_are_treated_as_non_dvp_with_no_exposure() {
+        let instrument = btc();
+        let at_limit = order(OrderSide::Sell, 1, 100_000);
+        assert_eq!(check(&limits(), &HashMap::new(), "VENUE_A", &instrument, &at_limit), Ok(()));
+        let over = order(OrderSide::Sell, 2, 60_000);
+        assert!(check(&limits(), &HashMap::new(), "VENUE_A", &instrument, &over).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Yield Curves
+ *
+ * File: src/shared/risk/curves.rs
+ *
+ * Description:
+ * A zero-coupon yield curve bootstrapped from market quotes, the rates
+ * instruments valued off it, and the curve shocks the VaR engine applies.
+ *
+ * Quotes are deposits (simple interest, up to a year) and par swaps (annual
+ * fixed coupons). Deposits give their discount factor directly; each swap's
+ * zero rate is then solved, shortest first, so the swap prices at par on the
+ * curve built so far. Zero rates are continuously compounded and linearly
+ * interpolated between tenors, flat beyond the ends.
+ *
+ * Rates positions are valued off the curve:
+ * - Bond: a fixed-coupon bond, priced per 100 face from its discounted cash
+ *   flows;
+ * - RateFuture: a short-term interest rate future (SOFR style), priced 100
+ *   less the forward rate over its reference period, in percent.
+ *
+ * Curve shocks move every tenor's zero rate: a parallel shift moves them all
+ * alike, and a slope shock pivots the curve about its middle, lowering the
+ * short end by half the shock and raising the long end by half (a steepener;
+ * negative for a flattener). The Monte Carlo engine draws a daily parallel
+ * and slope move per path; `SCENARIOS` are the fixed stress moves.
+ *
+ * Quotes come from a JSON file holding an array of `CurveQuote`
+ * (QA_YIELD_CURVE_PATH), or from the rates feed; otherwise the built-in USD
+ * curve is used.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use rand::Rng;
+use rand_distr::{Distribution, StandardNormal};
+use serde::{Deserialize, Serialize};
+
+/// One basis point, as a decimal rate.
+pub const BASIS_POINT: f64 = 0.0001;
+
+// --- Quotes ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QuoteKind {
+    /// Simple-interest deposit to the tenor.
+    Deposit,
+    /// Par swap paying an annual fixed coupon.
+    Swap,
+}
+
+/// A market rate at one tenor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct CurveQuote {
+    /// "1M", "3M", "2Y", ...
+    pub tenor: String,
+    pub kind: QuoteKind,
+    /// Decimal rate (0.0525 is 5.25%).
+    pub rate: f64,
+}
+
+impl CurveQuote {
+    pub fn new(tenor: &str, kind: QuoteKind, rate: f64) -> CurveQuote {
+        CurveQuote { tenor: tenor.to_string(), kind, rate }
+    }
+}
+
+/// Years in a tenor such as "6M" or "10Y".
+pub fn tenor_years(tenor: &str) -> Option<f64> {
+    let tenor = tenor.trim().to_ascii_uppercase();
+    let (value, unit) = tenor.split_at(tenor.len().checked_sub(1)?);
+    let value: f64 = value.parse().ok().filter(|v: &f64| *v > 0.0)?;
+    match unit {
+        "D" => Some(value / 365.0),
+        "W" => Some(value * 7.0 / 365.0),
+        "M" => Some(value / 12.0),
+        "Y" => Some(value),
+        _ => None,
+    }
+}
+
+/// Quotes from QA_YIELD_CURVE_PATH, or the built-in USD curve.
+pub fn load_quotes_from_env() -> Vec<CurveQuote> {
+    let Ok(path) = std::env::var("QA_YIELD_CURVE_PATH") else {
+        return default_quotes();
+    };
+    let parsed = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str::<Vec<CurveQuote>>(&contents).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(quotes) => {
+            println!("Loaded {} yield curve quotes from {}", quotes.len(), path);
+            quotes
+        }
+        Err(e) => {
+            println!("Failed to read yield curve {}: {}. Using the built-in curve.", path, e);
+            default_quotes()
+        }
+    }
+}
+
+/// A USD curve: SOFR deposits to a year, swaps beyond.
+pub fn default_quotes() -> Vec<CurveQuote> {
+    use QuoteKind::{Deposit, Swap};
+    vec![
+        CurveQuote::new("1M", Deposit, 0.0531),
+        CurveQuote::new("3M", Deposit, 0.0528),
+        CurveQuote::new("6M", Deposit, 0.0519),
+        CurveQuote::new("1Y", Deposit, 0.0497),
+        CurveQuote::new("2Y", Swap, 0.0462),
+        CurveQuote::new("3Y", Swap, 0.0440),
+        CurveQuote::new("5Y", Swap, 0.0418),
+        CurveQuote::new("7Y", Swap, 0.0410),
+        CurveQuote::new("10Y", Swap, 0.0406),
+        CurveQuote::new("30Y", Swap, 0.0391),
+    ]
+}
+
+// --- Curve ---
+
+/// A continuously compounded zero curve.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct YieldCurve {
+    /// (tenor in years, zero rate), shortest first.
+    nodes: Vec<(f64, f64)>,
+}
+
+/// One tenor of GET /curve.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct CurvePoint {
+    pub tenor_years: f64,
+    pub zero_rate: f64,
+    pub discount_factor: f64,
+}
+
+impl YieldCurve {
+    /// Bootstraps the curve from deposits and par swaps.
+    pub fn bootstrap(quotes: &[CurveQuote]) -> Result<YieldCurve, String> {
+        let mut quotes: Vec<(f64, &CurveQuote)> = quotes
+            .iter()
+            .map(|q| tenor_years(&q.tenor).map(|t| (t, q)).ok_or_else(|| format!("invalid tenor '{}'", q.tenor)))
+            .collect::<Result<_, _>>()?;
+        if quotes.is_empty() {
+            return Err("no quotes".to_string());
+        }
+        quotes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if quotes.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err("two quotes for the same tenor".to_string());
+        }
+
+        let mut curve = YieldCurve { nodes: Vec::with_capacity(quotes.len()) };
+        for (t, quote) in quotes {
+            let zero = match quote.kind {
+                QuoteKind::Deposit => (1.0 + quote.rate * t).ln() / t,
+                QuoteKind::Swap => curve.solve_par_swap(t, quote.rate)?,
+            };
+            curve.nodes.push((t, zero));
+        }
+        Ok(curve)
+    }
+
+    /// The zero rate at `t` years that prices a swap to `t` at par.
+    fn solve_par_swap(&self, t: f64, par_rate: f64) -> Result<f64, String> {
+        let mismatch = |zero: f64| {
+            let mut trial = self.clone();
+            trial.nodes.push((t, zero));
+            trial.par_swap_pv(t, par_rate)
+        };
+        // The fixed leg's value falls as the zero rate rises: bisect.
+        let (mut low, mut high) = (-0.10, 1.0);
+        if mismatch(low) < 0.0 || mismatch(high) > 0.0 {
+            return Err(format!("no zero rate reprices the {:.2}Y swap at {}", t, par_rate));
+        }
+        for _ in 0..100 {
+            let mid = 0.5 * (low + high);
+            if mismatch(mid) > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(0.5 * (low + high))
+    }
+
+    /// Value of receiving `par_rate` annually to `t` plus the notional at
+    /// `t`, less par: zero for a swap at its par rate.
+    fn par_swap_pv(&self, t: f64, par_rate: f64) -> f64 {
+        let mut pv = 0.0;
+        let mut previous = 0.0;
+        for date in coupon_dates(t, 1) {
+            pv += par_rate * (date - previous) * self.discount_factor(date);
+            previous = date;
+        }
+        pv + self.discount_factor(t) - 1.0
+    }
+
+    pub fn zero_rate(&self, t: f64) -> f64 {
+        let nodes = &self.nodes;
+        let (first, last) = (nodes[0], nodes[nodes.len() - 1]);
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        let upper = nodes.partition_point(|(tenor, _)| *tenor < t);
+        let ((t0, z0), (t1, z1)) = (nodes[upper - 1], nodes[upper]);
+        z0 + (z1 - z0) * (t - t0) / (t1 - t0)
+    }
+
+    pub fn discount_factor(&self, t: f64) -> f64 {
+        (-self.zero_rate(t) * t).exp()
+    }
+
+    /// Simple forward rate between `start` and `end` years.
+    pub fn forward_rate(&self, start: f64, end: f64) -> f64 {
+        (self.discount_factor(start) / self.discount_factor(end) - 1.0) / (end - start)
+    }
+
+    pub fn points(&self) -> Vec<CurvePoint> {
+        self.nodes
+            .iter()
+            .map(|(t, zero)| CurvePoint { tenor_years: *t, zero_rate: *zero, discount_factor: self.discount_factor(*t) })
+            .collect()
+    }
+
+    /// The curve with every tenor's zero rate moved by `shock`.
+    pub fn shocked(&self, shock: CurveShock) -> YieldCurve {
+        let (short, long) = (self.nodes[0].0, self.nodes[self.nodes.len() - 1].0);
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(t, zero)| {
+                // -0.5 at the short end to +0.5 at the long end.
+                let tilt = if long > short { (t - short) / (long - short) - 0.5 } else { 0.0 };
+                (*t, zero + (shock.parallel_bp + shock.slope_bp * tilt) * BASIS_POINT)
+            })
+            .collect();
+        YieldCurve { nodes }
+    }
+}
+
+/// Coupon dates, in years, of a bond maturing at `maturity` paying
+/// `frequency` times a year, counted back from maturity.
+fn coupon_dates(maturity: f64, frequency: u32) -> Vec<f64> {
+    let period = 1.0 / frequency.max(1) as f64;
+    let mut dates = Vec::new();
+    let mut date = maturity;
+    while date > 1e-9 {
+        dates.push(date);
+        date -= period;
+    }
+    dates.reverse();
+    dates
+}
+
+// --- Shocks ---
+
+/// A move of the whole curve, in basis points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ApiSchema)]
+pub struct CurveShock {
+    pub parallel_bp: f64,
+    /// Long end minus short end; positive steepens.
+    pub slope_bp: f64,
+}
+
+/// The fixed curve stress scenarios.
+pub const SCENARIOS: [(&str, CurveShock); 6] = [
+    ("parallel_up_100bp", CurveShock { parallel_bp: 100.0, slope_bp: 0.0 }),
+    ("parallel_down_100bp", CurveShock { parallel_bp: -100.0, slope_bp: 0.0 }),
+    ("parallel_up_25bp", CurveShock { parallel_bp: 25.0, slope_bp: 0.0 }),
+    ("parallel_down_25bp", CurveShock { parallel_bp: -25.0, slope_bp: 0.0 }),
+    ("steepener_50bp", CurveShock { parallel_bp: 0.0, slope_bp: 50.0 }),
+    ("flattener_50bp", CurveShock { parallel_bp: 0.0, slope_bp: -50.0 }),
+];
+
+/// Daily volatility of the curve's moves, in basis points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct CurveFactors {
+    pub parallel_vol_bp: f64,
+    pub slope_vol_bp: f64,
+}
+
+impl CurveFactors {
+    /// QA_CURVE_PARALLEL_VOL_BP (default 6) and QA_CURVE_SLOPE_VOL_BP (default 3).
+    pub fn from_env() -> CurveFactors {
+        let var = |name: &str, default: f64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        CurveFactors {
+            parallel_vol_bp: var("QA_CURVE_PARALLEL_VOL_BP", 6.0),
+            slope_vol_bp: var("QA_CURVE_SLOPE_VOL_BP", 3.0),
+        }
+    }
+
+    /// One day's simulated move.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> CurveShock {
+        let parallel: f64 = StandardNormal.sample(rng);
+        let slope: f64 = StandardNormal.sample(rng);
+        CurveShock { parallel_bp: parallel * self.parallel_vol_bp, slope_bp: slope * self.slope_vol_bp }
+    }
+}
+
+// --- Rates Instruments ---
+
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateInstrument {
+    /// Fixed-coupon bond; `coupon` is the decimal annual rate.
+    Bond { coupon: f64, maturity_years: f64, frequency: u32 },
+    /// Interest rate future on the rate between `start_years` and `end_years`.
+    RateFuture { start_years: f64, end_years: f64 },
+}
+
+impl RateInstrument {
+    /// Price off `curve`: per 100 face for a bond, 100 less the rate in
+    /// percent for a future.
+    pub fn price(&self, curve: &YieldCurve) -> f64 {
+        match *self {
+            RateInstrument::Bond { coupon, maturity_years, frequency } => {
+                let per_period = 100.0 * coupon / frequency.max(1) as f64;
+                let coupons: f64 = coupon_dates(maturity_years, frequency).iter().map(|t| per_period * curve.discount_factor(*t)).sum();
+                coupons + 100.0 * curve.discount_factor(maturity_years)
+            }
+            RateInstrument::RateFuture { start_years, end_years } => {
+                100.0 - 100.0 * curve.forward_rate(start_years, end_years)
+            }
+        }
+    }
+}
+
+/// A position in a rates instrument.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct RatePosition {
+    pub symbol: String,
+    pub instrument: RateInstrument,
+    /// Signed; negative for short positions.
+    pub quantity: f64,
+    /// Currency value of one point of price per unit (1,000 for a $100,000
+    /// face bond future, 2,500 for a 3-month SOFR future).
+    pub point_value: f64,
+}
+
+impl RatePosition {
+    /// Quantity times price times point value; for futures a notional, of
+    /// use for the change between two curves.
+    pub fn value(&self, curve: &YieldCurve) -> f64 {
+        self.quantity * self.instrument.price(curve) * self.point_value
+    }
+
+    /// Change in value for a one basis point parallel rise.
+    pub fn dv01(&self, curve: &YieldCurve) -> f64 {
+        self.value(&curve.shocked(CurveShock { parallel_bp: 1.0, slope_bp: 0.0 })) - self.value(curve)
+    }
+}
+
+/// Value of the positions off `curve`.
+pub fn book_value(positions: &[RatePosition], curve: &YieldCurve) -> f64 {
+    positions.iter().map(|position| position.value(curve)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootstrapped_curve_reprices_its_quotes() {
+        let quotes = default_quotes();
+        let curve = YieldCurve::bootstrap(&quotes).unwrap();
+        for quote in &quotes {
+            let t = tenor_years(&quote.tenor).unwrap();
+            let error = match quote.kind {
+                QuoteKind::Deposit => 1.0 / (1.0 + quote.rate * t) - curve.discount_factor(t),
+                QuoteKind::Swap => curve.par_swap_pv(t, quote.rate),
+            };
+            assert!(error.abs() < 1e-9, "{} off by {}", quote.tenor, error);
+        }
+        // A 10Y bond with the 10Y swap coupon prices near par.
+        let bond = RateInstrument::Bond { coupon: 0.0406, maturity_years: 10.0, frequency: 1 };
+        assert!((bond.price(&curve) - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shocks_move_bonds_and_futures_the_expected_way() {
+        let curve = YieldCurve::bootstrap(&default_quotes()).unwrap();
+        let note = RatePosition {
+            symbol: "ZN".to_string(),
+            instrument: RateInstrument::Bond { coupon: 0.04, maturity_years: 10.0, frequency: 2 },
+            quantity: 1.0,
+            point_value: 1_000.0,
+        };
+        let sofr = RatePosition {
+            symbol: "SR3".to_string(),
+            instrument: RateInstrument::RateFuture { start_years: 0.25, end_years: 0.5 },
+            quantity: 1.0,
+            point_value: 2_500.0,
+        };
+        // Long bonds and long rate futures both lose when rates rise.
+        assert!(note.dv01(&curve) < 0.0);
+        assert!((sofr.dv01(&curve) + 25.0).abs() < 0.5);
+
+        // A steepener raises the long end and lowers the short end.
+        let steeper = curve.shocked(SCENARIOS[4].1);
+        assert!(steeper.zero_rate(30.0) > curve.zero_rate(30.0));
+        assert!(steeper.zero_rate(1.0 / 12.0) < curve.zero_rate(1.0 / 12.0));
+        let bond = RatePosition {
+            instrument: RateInstrument::Bond { coupon: 0.04, maturity_years: 30.0, frequency: 2 },
+            ..note
+        };
+        assert!(bond.value(&steeper) < bond.value(&curve));
+        assert!(sofr.value(&steeper) > sofr.value(&curve));
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: VaR Estimation Diagnostics
+ *
+ * File: src/shared/risk/diagnostics.rs
+ *
+ * Description:
+ * How far a Monte Carlo VaR figure can be trusted, from the losses of the
+ * run that produced it:
+ *
+ * - Expected shortfall: the mean loss at or beyond the VaR.
+ * - Bootstrap confidence intervals for the VaR and the expected shortfall:
+ *   the losses are resampled with replacement BOOTSTRAP_RESAMPLES times and
+ *   the interval is the middle INTERVAL_LEVEL of the resampled figures
+ *   (percentile method). The bootstrap treats the paths as independent, so
+ *   for Sobol runs the intervals are conservative; with a control variate
+ *   the VaR interval is moved by the control's correction.
+ * - Convergence: the VaR on the first half of the paths against the VaR on
+ *   all of them. If doubling the paths moved the figure by more than the
+ *   tolerance (QA_VAR_CONVERGENCE_TOLERANCE, relative, default 5%), more
+ *   paths would likely move it again and the run has not converged.
+ */
+
+use crate::monte_carlo::{percentile, var_index, VarEstimate};
+use quantumarb_openapi::ApiSchema;
+use rand::Rng;
+use serde::Serialize;
+
+/// Resamples behind each bootstrap interval.
+pub const BOOTSTRAP_RESAMPLES: usize = 200;
+/// Coverage of the bootstrap intervals.
+pub const INTERVAL_LEVEL: f64 = 0.95;
+pub const DEFAULT_CONVERGENCE_TOLERANCE: f64 = 0.05;
+
+/// QA_VAR_CONVERGENCE_TOLERANCE, the relative change in VaR between half
+/// and all of the paths that still counts as converged.
+pub fn convergence_tolerance_from_env() -> f64 {
+    std::env::var("QA_VAR_CONVERGENCE_TOLERANCE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|tolerance: &f64| *tolerance > 0.0)
+        .unwrap_or(DEFAULT_CONVERGENCE_TOLERANCE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct Interval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct VarDiagnostics {
+    pub expected_shortfall: f64,
+    pub var_interval: Interval,
+    pub es_interval: Interval,
+    /// The VaR on the first half of the paths.
+    pub half_paths_var: f64,
+    pub converged: bool,
+}
+
+/// The VaR and expected shortfall of `losses`.
+pub fn tail(losses: &mut [f64], confidence_level: f64) -> (f64, f64) {
+    let var = percentile(losses, confidence_level);
+    // Selecting the VaR left every larger loss after it.
+    let beyond = &losses[var_index(losses.len(), confidence_level)..];
+    (var, beyond.iter().sum::<f64>() / beyond.len() as f64)
+}
+
+/// Diagnoses a run from its losses, in path order, and the estimate made
+/// from them. Reorders `losses`.
+pub fn diagnose<R: Rng + ?Sized>(
+    losses: &mut [f64],
+    estimate: &VarEstimate,
+    confidence_level: f64,
+    tolerance: f64,
+    rng: &mut R,
+) -> VarDiagnostics {
+    let half = losses.len() / 2;
+    let half_paths_var = if half > 0 { percentile(&mut losses[..half], confidence_level) } else { f64::NAN };
+    let (var, expected_shortfall) = tail(losses, confidence_level);
+    let converged = (var - half_paths_var).abs() <= tolerance * var.abs();
+
+    let mut resample = vec![0.0; losses.len()];
+    let mut vars = Vec::with_capacity(BOOTSTRAP_RESAMPLES);
+    let mut shortfalls = Vec::with_capacity(BOOTSTRAP_RESAMPLES);
+    for _ in 0..BOOTSTRAP_RESAMPLES {
+        resample.iter_mut().for_each(|loss| *loss = losses[rng.gen_range(0..losses.len())]);
+        let (var, shortfall) = tail(&mut resample, confidence_level);
+        vars.push(var);
+        shortfalls.push(shortfall);
+    }
+    let correction = estimate.var - var;
+    VarDiagnostics {
+        expected_shortfall,
+        var_interval: interval(&mut vars, correction),
+        es_interval: interval(&mut shortfalls, 0.0),
+        half_paths_var,
+        converged,
+    }
+}
+
+fn interval(resampled: &mut [f64], shift: f64) -> Interval {
+    let outside = (1.0 - INTERVAL_LEVEL) / 2.0;
+    Interval {
+        lower: percentile(resampled, outside) + shift,
+        upper: percentile(resampled, 1.0 - outside) + shift,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributions::{DistributionKind, ReturnSampler};
+    use crate::monte_carlo::{simulate_var_with_diagnostics, SimulatedAsset, VarEngine, VarSampling};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn intervals_cover_the_exact_figures_and_few_paths_do_not_converge() {
+        // A normal book: loss ~ N(0, 12,000), VaR 2.326 and ES 2.665 sigma.
+        let btc = ReturnSampler::fit(DistributionKind::Normal, &[], 0.02);
+        let book = [SimulatedAsset { quantity: 10.0, price: 60_000.0, sampler: &btc }];
+        let (exact_var, exact_es) = (2.326348 * 12_000.0, 2.665214 * 12_000.0);
+        let run = |num_paths, tolerance| {
+            let sampling = VarSampling::plain(VarEngine::Scalar);
+            let mut rng = StdRng::seed_from_u64(11);
+            simulate_var_with_diagnostics(sampling, &book, None, num_paths, 0.99, tolerance, &mut rng)
+        };
+
+        let (estimate, diagnostics) = run(10_000, DEFAULT_CONVERGENCE_TOLERANCE);
+        let Interval { lower, upper } = diagnostics.var_interval;
+        assert!(lower < estimate.var && estimate.var < upper);
+        assert!(lower < exact_var && exact_var < upper, "{:?}", diagnostics);
+        let Interval { lower, upper } = diagnostics.es_interval;
+        assert!(lower < exact_es && exact_es < upper, "{:?}", diagnostics);
+        assert!(diagnostics.expected_shortfall > estimate.var);
+        assert!(diagnostics.converged);
+
+        let (_, noisy) = run(1_000, 0.001);
+        assert!(!noisy.converged, "{:?}", noisy);
+    }
+}
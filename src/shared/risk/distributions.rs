@@ -0,0 +1,321 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Return Distributions
+ *
+ * File: src/shared/risk/distributions.rs
+ *
+ * Description:
+ * Marginal daily-return distributions for the Monte Carlo engine. Normal
+ * returns understate tail risk, so each asset can instead use:
+ *
+ * - Student-t: degrees of freedom fitted from the sample excess kurtosis
+ *   (method of moments, nu = 4 + 6 / kurtosis) and scaled so the variance
+ *   matches the sample volatility.
+ * - Empirical: a bootstrap that resamples the asset's historical returns.
+ *
+ * Historical returns come from the return store, a CSV file of
+ * `symbol,date,return` rows (QA_RETURN_HISTORY_PATH). Without one a
+ * simulated fat-tailed history is used so the service still runs standalone.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use rand::Rng;
+use rand_distr::{Distribution, Normal, StudentT};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Below this many observations a fit falls back to the normal distribution.
+const MIN_FIT_OBSERVATIONS: usize = 30;
+/// Fitted degrees of freedom are clamped to this range; 30+ is close to normal.
+const MIN_DEGREES_OF_FREEDOM: f64 = 2.5;
+const MAX_DEGREES_OF_FREEDOM: f64 = 30.0;
+/// Used for Student-t assets without enough history to fit.
+const DEFAULT_DEGREES_OF_FREEDOM: f64 = 5.0;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum DistributionKind {
+    Normal,
+    StudentT,
+    Empirical,
+}
+
+impl DistributionKind {
+    pub fn parse(value: &str) -> Option<DistributionKind> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(DistributionKind::Normal),
+            "student-t" | "t" => Some(DistributionKind::StudentT),
+            "empirical" | "bootstrap" => Some(DistributionKind::Empirical),
+            _ => None,
+        }
+    }
+}
+
+/// Per-asset overrides from QA_VAR_DISTRIBUTIONS, e.g. "BTC:student-t,ETH:empirical".
+pub fn overrides_from_env() -> HashMap<String, DistributionKind> {
+    let mut overrides = HashMap::new();
+    let Ok(spec) = std::env::var("QA_VAR_DISTRIBUTIONS") else {
+        return overrides;
+    };
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        match entry.split_once(':').and_then(|(symbol, kind)| Some((symbol, DistributionKind::parse(kind)?))) {
+            Some((symbol, kind)) => {
+                overrides.insert(symbol.trim().to_string(), kind);
+            }
+            None => println!("Ignoring invalid QA_VAR_DISTRIBUTIONS entry '{}'", entry),
+        }
+    }
+    overrides
+}
+
+// --- Samplers ---
+
+/// A fitted distribution ready to draw daily returns from.
+pub enum ReturnSampler {
+    Normal(Normal<f64>),
+    StudentT { dist: StudentT<f64>, nu: f64, scale: f64 },
+    Empirical(Vec<f64>),
+}
+
+impl ReturnSampler {
+    /// Fits `kind` to the asset's history. `volatility` is used when there is
+    /// too little history to estimate it.
+    pub fn fit(kind: DistributionKind, history: &[f64], volatility: f64) -> ReturnSampler {
+        let enough_history = history.len() >= MIN_FIT_OBSERVATIONS;
+        match kind {
+            DistributionKind::Empirical if enough_history => ReturnSampler::Empirical(history.to_vec()),
+            DistributionKind::StudentT => {
+                let (sigma, nu) = if enough_history {
+                    let moments = Moments::of(history);
+                    (moments.std_dev, degrees_of_freedom(moments.excess_kurtosis))
+                } else {
+                    (volatility, DEFAULT_DEGREES_OF_FREEDOM)
+                };
+                // Var(t_nu) = nu / (nu - 2); rescale so the sampled volatility is sigma.
+                let scale = sigma * ((nu - 2.0) / nu).sqrt();
+                ReturnSampler::StudentT { dist: StudentT::new(nu).unwrap(), nu, scale }
+            }
+            _ => ReturnSampler::Normal(Normal::new(0.0, volatility).unwrap()),
+        }
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            ReturnSampler::Normal(dist) => dist.sample(rng),
+            ReturnSampler::StudentT { dist, scale, .. } => dist.sample(rng) * scale,
+            ReturnSampler::Empirical(returns) => returns[rng.gen_range(0..returns.len())],
+        }
+    }
+
+    /// Standard deviation of the returns drawn.
+    pub fn std_dev(&self) -> f64 {
+        match self {
+            ReturnSampler::Normal(dist) => dist.std_dev(),
+            ReturnSampler::StudentT { nu, scale, .. } => scale * (nu / (nu - 2.0)).sqrt(),
+            ReturnSampler::Empirical(returns) => Moments::of(returns).std_dev,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ReturnSampler::Normal(dist) => format!("normal(sigma={:.4})", dist.std_dev()),
+            ReturnSampler::StudentT { nu, scale, .. } => format!("student-t(nu={:.2}, scale={:.4})", nu, scale),
+            ReturnSampler::Empirical(returns) => format!("empirical({} days)", returns.len()),
+        }
+    }
+}
+
+struct Moments {
+    std_dev: f64,
+    excess_kurtosis: f64,
+}
+
+impl Moments {
+    fn of(returns: &[f64]) -> Moments {
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let m2 = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n;
+        let m4 = returns.iter().map(|r| (r - mean).powi(4)).sum::<f64>() / n;
+        let excess_kurtosis = if m2 > 0.0 { m4 / (m2 * m2) - 3.0 } else { 0.0 };
+        Moments { std_dev: m2.sqrt(), excess_kurtosis }
+    }
+}
+
+/// The standard normal quantile of `p` in (0, 1), by Acklam's rational
+/// approximation (relative error below 1.2e-9).
+pub fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00, 3.754408661907416e+00];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Method-of-moments estimate: a Student-t with nu > 4 has excess kurtosis 6 / (nu - 4).
+fn degrees_of_freedom(excess_kurtosis: f64) -> f64 {
+    if excess_kurtosis <= 0.0 {
+        return MAX_DEGREES_OF_FREEDOM;
+    }
+    (4.0 + 6.0 / excess_kurtosis).clamp(MIN_DEGREES_OF_FREEDOM, MAX_DEGREES_OF_FREEDOM)
+}
+
+// --- Historical Return Store ---
+
+/// Loads daily returns per symbol, oldest first.
+/// `rng` draws the simulated history used when there is no return store.
+pub fn load_return_history<R: Rng + ?Sized>(symbols: &[(String, f64)], rng: &mut R) -> HashMap<String, Vec<f64>> {
+    match std::env::var("QA_RETURN_HISTORY_PATH") {
+        Ok(path) => match load_return_csv(&path) {
+            Ok(history) => {
+                println!("Loaded historical returns for {} symbol(s) from {}", history.len(), path);
+                history
+            }
+            Err(e) => {
+                println!("Failed to read return history {}: {}. Using simulated history.", path, e);
+                load_mock_return_history(symbols, rng)
+            }
+        },
+        Err(_) => load_mock_return_history(symbols, rng),
+    }
+}
+
+/// Reads `symbol,date,return` rows (a header row is skipped). Rows are
+/// expected in date order per symbol.
+fn load_return_csv(path: &str) -> std::io::Result<HashMap<String, Vec<f64>>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut history: HashMap<String, Vec<f64>> = HashMap::new();
+    for line in contents.lines() {
+        let mut fields = line.split(',');
+        let (Some(symbol), Some(_date), Some(value)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(value) = value.trim().parse::<f64>() {
+            history.entry(symbol.trim().to_string()).or_default().push(value);
+        }
+    }
+    Ok(history)
+}
+
+/// Two years of simulated fat-tailed (t with 4 d.o.f.) daily returns per symbol.
+fn load_mock_return_history<R: Rng + ?Sized>(symbols: &[(String, f64)], rng: &mut R) -> HashMap<String, Vec<f64>> {
+    let t = StudentT::new(4.0).unwrap();
+    symbols
+        .iter()
+        .map(|(symbol, volatility)| {
+            let scale = volatility * (0.5f64).sqrt(); // Var(t_4) = 2
+            let returns = (0..504).map(|_| t.sample(rng) * scale).collect();
+            (symbol.clone(), returns)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn student_t_params(sampler: &ReturnSampler) -> (f64, f64) {
+        match sampler {
+            ReturnSampler::StudentT { nu, scale, .. } => (*nu, *scale),
+            _ => panic!("expected a student-t sampler, got {}", sampler.describe()),
+        }
+    }
+
+    #[test]
+    fn fitting_a_student_t_sample_recovers_its_tail_and_volatility() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let t = StudentT::new(10.0).unwrap();
+        // t_10 scaled to a 2% daily volatility: Var(t_10) = 10 / 8.
+        let scale = 0.02 / (10.0f64 / 8.0).sqrt();
+        let history: Vec<f64> = (0..200_000).map(|_| t.sample(&mut rng) * scale).collect();
+
+        let sampler = ReturnSampler::fit(DistributionKind::StudentT, &history, 0.5);
+        let (nu, _) = student_t_params(&sampler);
+        assert!((7.0..=15.0).contains(&nu), "nu = {}", nu);
+        assert!((sampler.std_dev() - 0.02).abs() < 0.0005, "std dev = {}", sampler.std_dev());
+
+        let draws: Vec<f64> = (0..200_000).map(|_| sampler.sample(&mut rng)).collect();
+        assert!((Moments::of(&draws).std_dev - 0.02).abs() < 0.0005);
+    }
+
+    #[test]
+    fn degrees_of_freedom_are_clamped_to_the_supported_range() {
+        // The moment estimate only approaches 4 from above, so it never reaches the lower bound.
+        let heaviest = degrees_of_freedom(1e9);
+        assert!((MIN_DEGREES_OF_FREEDOM..4.0 + 1e-6).contains(&heaviest), "nu = {}", heaviest);
+        assert_eq!(degrees_of_freedom(0.01), MAX_DEGREES_OF_FREEDOM);
+        assert_eq!(degrees_of_freedom(-1.0), MAX_DEGREES_OF_FREEDOM);
+        assert_eq!(degrees_of_freedom(1.0), 10.0);
+
+        // A near-normal sample fits the loosest tail, a spiky one the heaviest.
+        let mut history = [0.01, -0.01].repeat(MIN_FIT_OBSERVATIONS);
+        assert_eq!(student_t_params(&ReturnSampler::fit(DistributionKind::StudentT, &history, 0.5)).0, 30.0);
+        history[0] = 0.5;
+        let (nu, _) = student_t_params(&ReturnSampler::fit(DistributionKind::StudentT, &history, 0.5));
+        assert!((MIN_DEGREES_OF_FREEDOM..4.5).contains(&nu), "nu = {}", nu);
+    }
+
+    #[test]
+    fn too_little_history_falls_back_to_the_given_volatility() {
+        let history = [0.01, -0.02, 0.03];
+        let (nu, _) = student_t_params(&ReturnSampler::fit(DistributionKind::StudentT, &history, 0.04));
+        assert_eq!(nu, DEFAULT_DEGREES_OF_FREEDOM);
+        for kind in [DistributionKind::StudentT, DistributionKind::Empirical, DistributionKind::Normal] {
+            assert!((ReturnSampler::fit(kind, &history, 0.04).std_dev() - 0.04).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn the_empirical_sampler_draws_only_observed_returns() {
+        let history: Vec<f64> = (0..MIN_FIT_OBSERVATIONS).map(|n| n as f64 / 1000.0 - 0.015).collect();
+        let sampler = ReturnSampler::fit(DistributionKind::Empirical, &history, 0.5);
+        assert_eq!(sampler.describe(), format!("empirical({} days)", MIN_FIT_OBSERVATIONS));
+        assert!((sampler.std_dev() - Moments::of(&history).std_dev).abs() < 1e-15);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut seen = vec![false; history.len()];
+        for _ in 0..10_000 {
+            let draw = sampler.sample(&mut rng);
+            let index = history.iter().position(|r| *r == draw).expect("drew a return not in the history");
+            seen[index] = true;
+        }
+        assert!(seen.iter().all(|s| *s), "every observed day is drawn");
+    }
+}
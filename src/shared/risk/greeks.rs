@@ -0,0 +1,239 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Option Greeks Limits
+ *
+ * File: src/shared/risk/greeks.rs
+ *
+ * Description:
+ * Pre-trade checks for options, which the notional of an order says little
+ * about. An option order is priced (see `pricing`) off its underlying's
+ * price and volatility and measured by its greeks, in currency:
+ *
+ *   delta_notional   delta x underlying price x multiplier x quantity: the
+ *                    underlying exposure the option amounts to
+ *   gamma            dollar gamma: the change in delta_notional for a 1%
+ *                    move in the underlying
+ *   vega             the change in value for a one-point move in volatility
+ *
+ * The account's option positions are priced the same way and the order is
+ * rejected with RISK_GREEKS_LIMIT if it would take the account's net vega or
+ * gamma past its limit. An order that brings a greek back towards zero
+ * passes even while the account is over the limit.
+ */
+
+use crate::curves::YieldCurve;
+use crate::pricing::{self, ImpliedVols, OptionInputs};
+use chrono::{DateTime, Utc};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{InstrumentDefinition, ReferenceData};
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Add;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+// --- Data Structures ---
+
+/// An account's vega and gamma limits; no limit when absent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct GreeksLimits {
+    pub max_vega: Option<Money>,
+    pub max_gamma: Option<Money>,
+}
+
+/// Greeks of a position or order, in currency; see the module description.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ApiSchema)]
+pub struct GreekExposure {
+    pub delta_notional: Money,
+    pub gamma: Money,
+    pub vega: Money,
+}
+
+impl Add for GreekExposure {
+    type Output = GreekExposure;
+
+    fn add(self, other: GreekExposure) -> GreekExposure {
+        GreekExposure {
+            delta_notional: self.delta_notional + other.delta_notional,
+            gamma: self.gamma + other.gamma,
+            vega: self.vega + other.vega,
+        }
+    }
+}
+
+/// What options are priced off: underlying prices, volatilities and rates.
+#[derive(Debug, Clone)]
+pub struct OptionMarket {
+    /// Underlying price by symbol, in points.
+    pub spots: HashMap<String, f64>,
+    pub vols: ImpliedVols,
+    pub curve: Option<YieldCurve>,
+}
+
+impl OptionMarket {
+    /// Greeks of `quantity` (signed, positive for long) of an option. None
+    /// if the instrument is not an option; an error if its underlying has no
+    /// price.
+    pub fn exposure(
+        &self,
+        instrument: &InstrumentDefinition,
+        quantity: i64,
+        now: DateTime<Utc>,
+    ) -> Result<Option<GreekExposure>, Rejection> {
+        let Some(inputs) = self.inputs(instrument, now)? else {
+            return Ok(None);
+        };
+        let greeks = pricing::black_scholes(&inputs);
+        let (spot, units) = (inputs.spot, quantity as f64 * instrument.multiplier.to_f64());
+        Ok(Some(GreekExposure {
+            delta_notional: Money::from_f64(greeks.delta * spot * units),
+            gamma: Money::from_f64(greeks.gamma * spot * spot * 0.01 * units),
+            vega: Money::from_f64(greeks.vega * units),
+        }))
+    }
+
+    /// What an option is priced off now. None if the instrument is not an
+    /// option; an error if its underlying has no price.
+    pub fn inputs(
+        &self,
+        instrument: &InstrumentDefinition,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OptionInputs>, Rejection> {
+        let Some(terms) = &instrument.option else {
+            return Ok(None);
+        };
+        let Some(spot) = self.spots.get(&terms.underlying).copied() else {
+            return Err(Rejection::new(
+                RejectCode::SystemStateUnavailable,
+                format!("No price for {}, the underlying of {}", terms.underlying, instrument.symbol),
+            ));
+        };
+        let years = ((terms.expiry_utc - now).num_seconds() as f64 / SECONDS_PER_YEAR).max(0.0);
+        Ok(Some(OptionInputs {
+            right: terms.right,
+            spot,
+            strike: terms.strike.to_f64(),
+            years,
+            rate: self.curve.as_ref().map_or(0.0, |curve| curve.zero_rate(years)),
+            volatility: self.vols.get(&terms.underlying),
+        }))
+    }
+
+    /// Net greeks of the options among `positions` (quantity by symbol).
+    /// Options whose underlying has no price are left out.
+    pub fn book_exposure(
+        &self,
+        instruments: &ReferenceData,
+        positions: &HashMap<String, i64>,
+        now: DateTime<Utc>,
+    ) -> GreekExposure {
+        positions
+            .iter()
+            .filter_map(|(symbol, quantity)| {
+                let instrument = instruments.by_symbol(symbol)?;
+                self.exposure(instrument, *quantity, now).ok().flatten()
+            })
+            .fold(GreekExposure::default(), |total, exposure| total + exposure)
+    }
+}
+
+// --- Pre-Trade Check ---
+
+/// Checks an option order against the account's vega and gamma limits,
+/// given the account's positions. Returns the order's greeks; None for an
+/// order that is not for an option.
+pub fn check_order(
+    limits: &GreeksLimits,
+    market: &OptionMarket,
+    instrument: &InstrumentDefinition,
+    instruments: &ReferenceData,
+    positions: &HashMap<String, i64>,
+    order: &OrderRequest,
+    now: DateTime<Utc>,
+) -> Result<Option<GreekExposure>, Rejection> {
+    let quantity = match order.side {
+        OrderSide::Buy => order.size as i64,
+        OrderSide::Sell => -(order.size as i64),
+    };
+    let Some(order_greeks) = market.exposure(instrument, quantity, now)? else {
+        return Ok(None);
+    };
+    let before = market.book_exposure(instruments, positions, now);
+    let after = before + order_greeks;
+    for (greek, limit, before, after) in [
+        ("vega", limits.max_vega, before.vega, after.vega),
+        ("gamma", limits.max_gamma, before.gamma, after.gamma),
+    ] {
+        let Some(limit) = limit else {
+            continue;
+        };
+        if after.abs() > limit && after.abs() > before.abs() {
+            return Err(Rejection::new(
+                RejectCode::RiskGreeksLimit,
+                format!(
+                    "Order for {} takes account {}'s net {} from {:.2} to {:.2}, past its limit of {:.2}",
+                    instrument.symbol, order.account_id, greek, before, after, limit
+                ),
+            ));
+        }
+    }
+    Ok(Some(order_greeks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_wire::{HopStamps, OrderPriority, TradingMode};
+
+    fn order(side: OrderSide, size: u32) -> OrderRequest {
+        OrderRequest {
+            order_id: uuid::Uuid::nil(),
+            account_id: 101,
+            instrument_id: 5,
+            side,
+            price: 3_000_00,
+            size,
+            stamps: HopStamps::default(),
+            venue_id: 0,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn option_orders_are_held_to_vega_and_gamma_but_may_reduce() {
+        let instruments = ReferenceData::seeded();
+        let call = instruments.get(5).unwrap();
+        let expiry = call.option.as_ref().unwrap().expiry_utc;
+        let now = expiry - chrono::Duration::days(73);
+        let market = OptionMarket {
+            spots: HashMap::from([("BTC".to_string(), 70_000.0)]),
+            vols: ImpliedVols { by_underlying: HashMap::new(), default: 0.6 },
+            curve: None,
+        };
+        // At the money with a fifth of a year left: vega per contract is
+        // 70,000 x sqrt(0.2) x n(0.134) / 100, about 124.
+        let one = market.exposure(call, 1, now).unwrap().unwrap();
+        assert!(one.vega > Money::from_f64(123.0) && one.vega < Money::from_f64(125.0));
+        assert!(one.delta_notional > Money::from_f64(35_000.0));
+
+        let limits = GreeksLimits { max_vega: Some(Money::from_f64(1_000.0)), max_gamma: None };
+        let check = |positions: &HashMap<String, i64>, order: &OrderRequest| {
+            check_order(&limits, &market, call, &instruments, positions, order, now)
+        };
+        let flat = HashMap::new();
+        assert!(check(&flat, &order(OrderSide::Buy, 8)).is_ok());
+        let rejection = check(&flat, &order(OrderSide::Buy, 9)).unwrap_err();
+        assert_eq!(rejection.code, RejectCode::RiskGreeksLimit);
+        // Already over the limit: selling some back is allowed.
+        let long = HashMap::from([(call.symbol.clone(), 12)]);
+        assert!(check(&long, &order(OrderSide::Sell, 2)).is_ok());
+        assert!(check(&long, &order(OrderSide::Buy, 1)).is_err());
+        // Instruments other than options carry no greeks.
+        let btc = instruments.get(1).unwrap();
+        assert_eq!(check_order(&limits, &market, btc, &instruments, &long, &order(OrderSide::Buy, 1), now), Ok(None));
+    }
+}
@@ -0,0 +1,51 @@
+/*
+ * QuantumArb 2.0 - Shared: Risk Models
+ *
+ * File: src/shared/risk/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-risk`) holds the risk logic that has no
+ * I/O of its own, so it can be unit tested and benchmarked apart from the
+ * services that load its inputs:
+ *
+ *   Pre-trade checks (used by the risk gateway)
+ *     checks          per-order checks, in order: instrument, size,
+ *                     concentration, counterparty
+ *     concentration   symbol, sector and venue caps on gross exposure
+ *     counterparty    per-counterparty credit limits
+ *     package         multi-leg package validation and net exposure
+ *     greeks          vega and gamma limits on option orders
+ *     stress          stress-scenario limits on large orders
+ *     sharding        the consistent-hash ring that spreads accounts over
+ *                     gateway instances
+ *
+ *   Value at Risk (used by the VaR calculator)
+ *     curves          bootstrapped yield curves, rates instruments and
+ *                     curve shocks
+ *     distributions   fitted daily-return distributions
+ *     monte_carlo     the Monte Carlo VaR engine
+ *     simd            its vectorized path kernel
+ *     qmc             Sobol sequences and variance reduction for it
+ *     diagnostics     expected shortfall, bootstrap intervals and
+ *                     convergence of its estimates
+ *     backtest        Kupiec and Basel traffic-light backtests of the model
+ *
+ *   Pricing
+ *     pricing         Black-Scholes option prices and greeks
+ */
+
+pub mod backtest;
+pub mod checks;
+pub mod concentration;
+pub mod counterparty;
+pub mod curves;
+pub mod diagnostics;
+pub mod distributions;
+pub mod greeks;
+pub mod monte_carlo;
+pub mod package;
+pub mod pricing;
+pub mod qmc;
+pub mod sharding;
+pub mod simd;
+pub mod stress;
@@ -0,0 +1,385 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Monte Carlo VaR Engine
+ *
+ * File: src/shared/risk/monte_carlo.rs
+ *
+ * Description:
+ * Generates the Monte Carlo paths behind the VaR figure. Each path draws one
+ * daily return per asset from its fitted distribution (see distributions.rs)
+ * and revalues the portfolio; VaR is the loss at the confidence level's
+ * percentile of the simulated losses.
+ *
+ * Rates positions are simulated through the curve rather than a return: each
+ * path also draws a parallel and a slope move of the yield curve (see
+ * curves.rs) and revalues the rates book on the shocked curve. The curve
+ * move is drawn after the assets' returns, and only when there is a rates
+ * book, so portfolios without one draw the same paths as before.
+ *
+ * Assets are drawn in the order given, so a seeded generator and the same
+ * asset order reproduce the same paths. benches/monte_carlo.rs times a full
+ * run.
+ *
+ * Two engines generate the paths, picked with QA_VAR_ENGINE:
+ *
+ *   simd     the default: four paths at a time in vector lanes (see simd.rs)
+ *   scalar   one path at a time, each asset's return from its sampler
+ *
+ * They agree in distribution but draw differently, so a seed repeats its
+ * figures only on the same engine.
+ *
+ * For a tighter figure on the same paths, the VaR can instead be estimated
+ * with quasi-random (Sobol) points, antithetic variates and a control
+ * variate, each switched on separately (see qmc.rs):
+ *
+ *   QA_VAR_SEQUENCE=sobol        or pseudo, the default
+ *   QA_VAR_ANTITHETIC=true
+ *   QA_VAR_CONTROL_VARIATE=true
+ *
+ * Either way the estimate carries its standard error, from the spread of
+ * the VaR over BATCHES independent batches of the paths. With a control
+ * variate the VaR is corrected by the control's error: the same percentile
+ * of a control with a known VaR (a normal loss on the same draws), weighted
+ * by the regression of the batches' VaRs on the control's.
+ *
+ * `simulate_var_with_diagnostics` adds the expected shortfall, bootstrap
+ * confidence intervals and a convergence check to the figure.
+ *
+ * `simulate_var_change` prices a change in quantities (incremental VaR): it
+ * revalues the portfolio before and after the change on the same paths, so
+ * the difference between the two figures is the change's and not sampling
+ * noise. It always runs on the scalar engine.
+ */
+
+use crate::curves::{self, CurveFactors, RatePosition, YieldCurve};
+use crate::diagnostics::{self, VarDiagnostics};
+use crate::distributions::ReturnSampler;
+use crate::{qmc, simd};
+use quantumarb_openapi::ApiSchema;
+use rand::Rng;
+use serde::Serialize;
+
+/// Batches the paths are split into to measure the estimate's standard error.
+pub const BATCHES: usize = 16;
+
+/// How the paths are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum VarEngine {
+    Scalar,
+    Simd,
+}
+
+impl VarEngine {
+    pub fn parse(value: &str) -> Option<VarEngine> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "scalar" => Some(VarEngine::Scalar),
+            "simd" => Some(VarEngine::Simd),
+            _ => None,
+        }
+    }
+
+    /// QA_VAR_ENGINE, defaulting to `simd`.
+    pub fn from_env() -> VarEngine {
+        match std::env::var("QA_VAR_ENGINE") {
+            Ok(value) => VarEngine::parse(&value).unwrap_or_else(|| {
+                println!("Ignoring invalid QA_VAR_ENGINE '{}', using simd", value);
+                VarEngine::Simd
+            }),
+            Err(_) => VarEngine::Simd,
+        }
+    }
+}
+
+/// Where the uniforms behind the paths come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sequence {
+    Pseudo,
+    Sobol,
+}
+
+/// How a VaR run samples its paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ApiSchema)]
+pub struct VarSampling {
+    /// Used for plain sampling; the variance reduction techniques run on
+    /// the scalar path generator in qmc.rs.
+    pub engine: VarEngine,
+    pub sequence: Sequence,
+    pub antithetic: bool,
+    pub control_variate: bool,
+}
+
+impl VarSampling {
+    /// Pseudo-random paths on `engine`, without variance reduction.
+    pub fn plain(engine: VarEngine) -> VarSampling {
+        VarSampling { engine, sequence: Sequence::Pseudo, antithetic: false, control_variate: false }
+    }
+
+    /// QA_VAR_ENGINE, QA_VAR_SEQUENCE, QA_VAR_ANTITHETIC and QA_VAR_CONTROL_VARIATE.
+    pub fn from_env() -> VarSampling {
+        let flag = |name: &str| std::env::var(name).is_ok_and(|v| matches!(v.trim(), "1" | "true" | "yes"));
+        let sequence = match std::env::var("QA_VAR_SEQUENCE").as_deref().map(str::trim) {
+            Ok("sobol") => Sequence::Sobol,
+            Ok("pseudo") | Err(_) => Sequence::Pseudo,
+            Ok(other) => {
+                println!("Ignoring invalid QA_VAR_SEQUENCE '{}', using pseudo", other);
+                Sequence::Pseudo
+            }
+        };
+        VarSampling {
+            engine: VarEngine::from_env(),
+            sequence,
+            antithetic: flag("QA_VAR_ANTITHETIC"),
+            control_variate: flag("QA_VAR_CONTROL_VARIATE"),
+        }
+    }
+
+    fn reduces_variance(&self) -> bool {
+        self.sequence == Sequence::Sobol || self.antithetic || self.control_variate
+    }
+}
+
+/// A VaR figure and its Monte Carlo standard error.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ApiSchema)]
+pub struct VarEstimate {
+    pub var: f64,
+    /// Zero when there are too few paths to tell.
+    pub standard_error: f64,
+}
+
+/// One position to simulate.
+pub struct SimulatedAsset<'a> {
+    /// Signed; negative for short positions.
+    pub quantity: f64,
+    pub price: f64,
+    pub sampler: &'a ReturnSampler,
+}
+
+/// Rates positions and the curve they are valued off.
+pub struct RatesBook<'a> {
+    pub curve: &'a YieldCurve,
+    pub positions: &'a [RatePosition],
+    pub factors: CurveFactors,
+}
+
+/// Runs `num_paths` one-day paths on the scalar engine and returns the VaR
+/// at `confidence_level`.
+pub fn simulate_var<R: Rng + ?Sized>(
+    assets: &[SimulatedAsset],
+    num_paths: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> VarEstimate {
+    simulate_var_with_rates(VarSampling::plain(VarEngine::Scalar), assets, None, num_paths, confidence_level, rng)
+}
+
+/// As `simulate_var` with `sampling`, and a rates book revalued on each
+/// path's curve. The simd engine draws the curve moves after all the returns.
+pub fn simulate_var_with_rates<R: Rng + ?Sized>(
+    sampling: VarSampling,
+    assets: &[SimulatedAsset],
+    rates: Option<&RatesBook>,
+    num_paths: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> VarEstimate {
+    let mut paths = simulate_losses(sampling, assets, rates, num_paths, confidence_level, rng);
+    paths.estimate(confidence_level)
+}
+
+/// As `simulate_var_with_rates`, with the run's diagnostics: expected
+/// shortfall, bootstrap intervals and whether the VaR has converged to
+/// within `tolerance` (see diagnostics.rs). The bootstrap draws from `rng`
+/// after the paths.
+pub fn simulate_var_with_diagnostics<R: Rng + ?Sized>(
+    sampling: VarSampling,
+    assets: &[SimulatedAsset],
+    rates: Option<&RatesBook>,
+    num_paths: usize,
+    confidence_level: f64,
+    tolerance: f64,
+    rng: &mut R,
+) -> (VarEstimate, VarDiagnostics) {
+    let mut paths = simulate_losses(sampling, assets, rates, num_paths, confidence_level, rng);
+    let mut losses = paths.losses.clone();
+    let estimate = paths.estimate(confidence_level);
+    (estimate, diagnostics::diagnose(&mut losses, &estimate, confidence_level, tolerance, rng))
+}
+
+/// The losses of one run, in path order, with the control's on the same
+/// paths and its known VaR when there is one.
+struct SimulatedLosses {
+    losses: Vec<f64>,
+    control: Option<(Vec<f64>, f64)>,
+    batch_len: usize,
+}
+
+impl SimulatedLosses {
+    fn estimate(&mut self, confidence_level: f64) -> VarEstimate {
+        let control = self.control.as_mut().map(|(controls, known)| (&mut controls[..], *known));
+        estimate(&mut self.losses, control, self.batch_len, confidence_level)
+    }
+}
+
+fn simulate_losses<R: Rng + ?Sized>(
+    sampling: VarSampling,
+    assets: &[SimulatedAsset],
+    rates: Option<&RatesBook>,
+    num_paths: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> SimulatedLosses {
+    let base_value = rates.map_or(0.0, |book| curves::book_value(book.positions, book.curve));
+    let rates_loss = |rng: &mut R| match rates {
+        Some(book) if !book.positions.is_empty() => {
+            let curve = book.curve.shocked(book.factors.sample(rng));
+            base_value - curves::book_value(book.positions, &curve)
+        }
+        _ => 0.0,
+    };
+    if sampling.reduces_variance() {
+        let paths = qmc::simulate_paths(sampling, assets, num_paths, BATCHES, rng, rates_loss);
+        let known = qmc::control_var(assets, confidence_level);
+        let control = sampling.control_variate.then_some((paths.controls, known));
+        return SimulatedLosses { losses: paths.losses, control, batch_len: paths.batch_len };
+    }
+    let losses: Vec<f64> = match sampling.engine {
+        VarEngine::Scalar => (0..num_paths).map(|_| simulate_loss(assets, rng) + rates_loss(rng)).collect(),
+        VarEngine::Simd => {
+            let mut losses = simd::portfolio_losses(assets, num_paths, rng);
+            losses.iter_mut().for_each(|loss| *loss += rates_loss(rng));
+            losses
+        }
+    };
+    SimulatedLosses { losses, control: None, batch_len: num_paths.div_ceil(BATCHES).max(1) }
+}
+
+/// VaR before and after changing each asset's quantity by `changes` (in the
+/// order of `assets`; missing entries are no change). Assets the portfolio
+/// does not hold yet are passed with a zero quantity.
+pub fn simulate_var_change<R: Rng + ?Sized>(
+    assets: &[SimulatedAsset],
+    changes: &[f64],
+    rates: Option<&RatesBook>,
+    num_paths: usize,
+    confidence_level: f64,
+    rng: &mut R,
+) -> (f64, f64) {
+    let base_value = rates.map_or(0.0, |book| curves::book_value(book.positions, book.curve));
+    let mut before = Vec::with_capacity(num_paths);
+    let mut after = Vec::with_capacity(num_paths);
+    for _ in 0..num_paths {
+        let (mut loss, mut changed_loss) = (0.0, 0.0);
+        for (index, asset) in assets.iter().enumerate() {
+            let loss_per_unit = -asset.price * asset.sampler.sample(rng);
+            loss += asset.quantity * loss_per_unit;
+            changed_loss += (asset.quantity + changes.get(index).copied().unwrap_or(0.0)) * loss_per_unit;
+        }
+        if let Some(book) = rates.filter(|book| !book.positions.is_empty()) {
+            let curve = book.curve.shocked(book.factors.sample(rng));
+            let rates_loss = base_value - curves::book_value(book.positions, &curve);
+            loss += rates_loss;
+            changed_loss += rates_loss;
+        }
+        before.push(loss);
+        after.push(changed_loss);
+    }
+    (percentile(&mut before, confidence_level), percentile(&mut after, confidence_level))
+}
+
+/// The loss at the confidence level's percentile. Selects rather than sorts:
+/// on a small book sorting the losses costs more than drawing them.
+pub(crate) fn percentile(losses: &mut [f64], confidence_level: f64) -> f64 {
+    let index = var_index(losses.len(), confidence_level);
+    *losses.select_nth_unstable_by(index, |a, b| a.partial_cmp(b).unwrap()).1
+}
+
+/// Where the percentile falls among `len` losses in ascending order.
+pub(crate) fn var_index(len: usize, confidence_level: f64) -> usize {
+    ((len as f64 * confidence_level) as usize).min(len - 1)
+}
+
+/// The VaR of `losses` and its standard error over batches of `batch_len`
+/// paths, corrected by `control`'s simulated and known VaR when given.
+fn estimate(
+    losses: &mut [f64],
+    control: Option<(&mut [f64], f64)>,
+    batch_len: usize,
+    confidence_level: f64,
+) -> VarEstimate {
+    // The batches first: selecting the pooled percentile reorders the paths.
+    let batch_vars: Vec<f64> = losses.chunks_mut(batch_len).map(|batch| percentile(batch, confidence_level)).collect();
+    let var = percentile(losses, confidence_level);
+    let Some((controls, known)) = control else {
+        return VarEstimate { var, standard_error: standard_error(&batch_vars, 1) };
+    };
+    let control_vars: Vec<f64> =
+        controls.chunks_mut(batch_len).map(|batch| percentile(batch, confidence_level)).collect();
+    let control_var = percentile(controls, confidence_level);
+    let beta = regression_slope(&control_vars, &batch_vars);
+    let corrected: Vec<f64> =
+        batch_vars.iter().zip(&control_vars).map(|(var, control)| var - beta * (control - known)).collect();
+    // The slope was fitted to these batches: one more degree of freedom spent.
+    VarEstimate { var: var - beta * (control_var - known), standard_error: standard_error(&corrected, 2) }
+}
+
+/// The standard error of the mean of independent batch estimates, of which
+/// `fitted` degrees of freedom went into estimating them.
+fn standard_error(estimates: &[f64], fitted: usize) -> f64 {
+    let n = estimates.len() as f64;
+    if estimates.len() <= fitted {
+        return 0.0;
+    }
+    let mean = estimates.iter().sum::<f64>() / n;
+    let variance = estimates.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (n - fitted as f64);
+    (variance / n).sqrt()
+}
+
+/// The least-squares slope of `y` on `x`; zero if `x` does not vary.
+fn regression_slope(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let (mean_x, mean_y) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let covariance: f64 = x.iter().zip(y).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = x.iter().map(|x| (x - mean_x).powi(2)).sum();
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// The portfolio's loss on one simulated day.
+fn simulate_loss<R: Rng + ?Sized>(assets: &[SimulatedAsset], rng: &mut R) -> f64 {
+    assets
+        .iter()
+        .map(|asset| {
+            let random_return = asset.sampler.sample(rng);
+            -asset.quantity * asset.price * random_return
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributions::{DistributionKind, ReturnSampler};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn a_hedge_lowers_var_and_an_unchanged_book_keeps_it() {
+        let btc = ReturnSampler::fit(DistributionKind::Normal, &[], 0.02);
+        let eth = ReturnSampler::fit(DistributionKind::Normal, &[], 0.03);
+        let assets = [
+            SimulatedAsset { quantity: 10.0, price: 60_000.0, sampler: &btc },
+            SimulatedAsset { quantity: 0.0, price: 3_000.0, sampler: &eth },
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        let (before, unchanged) = simulate_var_change(&assets, &[], None, 5_000, 0.99, &mut rng);
+        assert_eq!(before, unchanged);
+        let (before, hedged) = simulate_var_change(&assets, &[-5.0], None, 5_000, 0.99, &mut rng);
+        assert!((hedged - before / 2.0).abs() < 1e-6 * before);
+        let (_, added) = simulate_var_change(&assets, &[0.0, 100.0], None, 5_000, 0.99, &mut rng);
+        assert!(added > before);
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Multi-Leg Package Checks
+ *
+ * File: src/shared/risk/package.rs
+ *
+ * Description:
+ * Package-level checks for multi-leg orders. A package is approved or
+ * rejected as a whole: every leg must pass the single-order checks on its
+ * own (size, instrument, concentration, counterparty), and the package's net
+ * notional - buys minus sells across all legs - must fit under the account's
+ * exposure limit. An arbitrage that buys on one venue and sells on another
+ * therefore uses only its residual exposure, not the sum of its legs.
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::{Money, Quantity};
+use quantumarb_refdata::ReferenceData;
+use quantumarb_wire::{MultiLegOrder, OrderSide};
+
+// --- Checks ---
+
+/// Rejects packages that are malformed regardless of limits.
+pub fn validate(package: &MultiLegOrder) -> Result<(), Rejection> {
+    let invalid = |reason: String| Err(Rejection::new(RejectCode::SystemInvalidRequest, reason));
+    if package.legs.len() < 2 || package.legs.len() > MultiLegOrder::MAX_LEGS {
+        return invalid(format!(
+            "Package has {} legs; expected 2 to {}",
+            package.legs.len(),
+            MultiLegOrder::MAX_LEGS
+        ));
+    }
+    if package.quantity == 0 {
+        return invalid("Package quantity must be positive".to_string());
+    }
+    if let Some(index) = package.legs.iter().position(|leg| leg.ratio == 0) {
+        return invalid(format!("Leg {} has a zero ratio", index + 1));
+    }
+    Ok(())
+}
+
+/// Signed notional of the whole package: buys positive, sells negative.
+/// Legs on unknown instruments are rejected by the per-leg checks first.
+pub fn net_notional(package: &MultiLegOrder, instruments: &ReferenceData) -> Money {
+    (0..package.legs.len())
+        .filter_map(|index| {
+            let order = package.leg_order(index);
+            let instrument = instruments.get(order.instrument_id)?;
+            let quantity = match order.side {
+                OrderSide::Buy => Quantity::from(order.size),
+                OrderSide::Sell => -Quantity::from(order.size),
+            };
+            Some(instrument.notional(instrument.price(order.price), quantity))
+        })
+        .sum()
+}
+
+/// Rejects the package if its net notional would take the account over its
+/// exposure limit.
+pub fn check_net_exposure(
+    package: &MultiLegOrder,
+    instruments: &ReferenceData,
+    current_exposure: Money,
+    max_exposure: Money,
+) -> Result<(), Rejection> {
+    let net = net_notional(package, instruments);
+    if current_exposure + net.abs() > max_exposure {
+        return Err(Rejection::new(
+            RejectCode::RiskExposureLimit,
+            format!(
+                "Package net notional {:.2} on top of exposure {:.2} exceeds limit {:.2}",
+                net, current_exposure, max_exposure
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Prefixes a leg's rejection with the leg number, so the strategy can tell
+/// which leg failed.
+pub fn leg_rejection(index: usize, rejection: Rejection) -> Rejection {
+    Rejection::new(rejection.code, format!("Leg {}: {}", index + 1, rejection.message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_errors::RejectCode;
+    use quantumarb_wire::OrderLeg;
+    use uuid::Uuid;
+
+    /// A leg of `instrument_id` at `price` whole points.
+    fn leg(instrument_id: u32, side: OrderSide, ratio: u32, price: u64) -> OrderLeg {
+        OrderLeg { instrument_id, side, ratio, price: price * 100, venue_id: 0 }
+    }
+
+    fn package(quantity: u32, legs: Vec<OrderLeg>) -> MultiLegOrder {
+        MultiLegOrder {
+            package_id: Uuid::new_v4(),
+            account_id: 101,
+            quantity,
+            legs,
+            stamps: Default::default(),
+            mode: Default::default(),
+            priority: Default::default(),
+            strategy_id: String::new(),
+        }
+    }
+
+    fn spread() -> MultiLegOrder {
+        package(2, vec![leg(1, OrderSide::Buy, 1, 60_000), leg(1, OrderSide::Sell, 1, 60_010)])
+    }
+
+    #[test]
+    fn malformed_packages_are_invalid() {
+        assert_eq!(validate(&spread()), Ok(()));
+        let invalid = |package: MultiLegOrder| validate(&package).unwrap_err();
+
+        let one_leg = invalid(package(1, vec![leg(1, OrderSide::Buy, 1, 60_000)]));
+        assert_eq!(one_leg.code, RejectCode::SystemInvalidRequest);
+        assert!(one_leg.message.contains("has 1 legs"), "{}", one_leg.message);
+        let too_many = package(1, vec![leg(1, OrderSide::Buy, 1, 60_000); MultiLegOrder::MAX_LEGS + 1]);
+        assert!(invalid(too_many).message.contains("expected 2 to 8"));
+        assert!(invalid(MultiLegOrder { quantity: 0, ..spread() }).message.contains("quantity must be positive"));
+        let zero_ratio = package(1, vec![leg(1, OrderSide::Buy, 1, 60_000), leg(2, OrderSide::Sell, 0, 2_000)]);
+        assert_eq!(invalid(zero_ratio).message, "Leg 2 has a zero ratio");
+    }
+
+    #[test]
+    fn net_notional_nets_buys_against_sells_by_ratio_and_multiplier() {
+        let instruments = ReferenceData::seeded();
+        // 2 x 60,000 bought, 2 x 60,010 sold.
+        assert_eq!(net_notional(&spread(), &instruments), Money::from_f64(-20.0));
+
+        // 1 ESZ25 (multiplier 50) bought at 4,500 against 3 x 75,000 of BTC sold.
+        let hedge = package(1, vec![leg(3, OrderSide::Buy, 1, 4_500), leg(1, OrderSide::Sell, 3, 75_000)]);
+        assert_eq!(net_notional(&hedge, &instruments), Money::from_f64(0.0));
+
+        let unknown = package(1, vec![leg(1, OrderSide::Buy, 1, 60_000), leg(99, OrderSide::Sell, 1, 60_000)]);
+        assert_eq!(net_notional(&unknown, &instruments), Money::from_f64(60_000.0), "unknown legs are skipped");
+    }
+
+    #[test]
+    fn net_exposure_counts_only_the_residual() {
+        let instruments = ReferenceData::seeded();
+        let (limit, current) = (Money::from_f64(100_000.0), Money::from_f64(99_980.0));
+        assert_eq!(check_net_exposure(&spread(), &instruments, current, limit), Ok(()));
+
+        let error = check_net_exposure(&spread(), &instruments, Money::from_f64(99_990.0), limit).unwrap_err();
+        assert_eq!(error.code, RejectCode::RiskExposureLimit);
+        assert!(error.message.contains("net notional -20"), "{}", error.message);
+    }
+
+    #[test]
+    fn leg_rejections_name_the_leg() {
+        let rejection = leg_rejection(2, Rejection::new(RejectCode::RiskExposureLimit, "Too big".to_string()));
+        assert_eq!((rejection.code, rejection.message.as_str()), (RejectCode::RiskExposureLimit, "Leg 3: Too big"));
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Option Pricing
+ *
+ * File: src/shared/risk/pricing.rs
+ *
+ * Description:
+ * Black-Scholes prices and greeks of European options, per unit of the
+ * underlying. Rates are continuously compounded (the yield curve's zero
+ * rate to expiry) and the underlying pays no dividends.
+ *
+ *   delta   change in price for a one-point move in the underlying
+ *   gamma   change in delta for a one-point move in the underlying
+ *   vega    change in price for a one-point (0.01) move in volatility
+ *
+ * An expired option, or one priced at zero volatility, is worth its
+ * discounted intrinsic value and has no gamma or vega.
+ *
+ * Implied volatility per underlying is configured until a volatility
+ * surface is sourced: QA_OPTION_VOLS="BTC:0.65,ETH:0.80", with
+ * QA_OPTION_DEFAULT_VOL (default 0.60) for the rest.
+ */
+
+use crate::backtest::erfc;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::OptionRight;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::f64::consts::{PI, SQRT_2};
+
+/// Implied volatility of underlyings without a configured one.
+pub const DEFAULT_VOLATILITY: f64 = 0.60;
+
+// --- Pricing ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptionInputs {
+    pub right: OptionRight,
+    pub spot: f64,
+    pub strike: f64,
+    /// Time to expiry in years.
+    pub years: f64,
+    pub rate: f64,
+    /// Annualized volatility (0.65 is 65%).
+    pub volatility: f64,
+}
+
+/// Price and greeks per unit of the underlying.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, ApiSchema)]
+pub struct Greeks {
+    pub price: f64,
+    pub delta: f64,
+    pub gamma: f64,
+    pub vega: f64,
+}
+
+pub fn black_scholes(inputs: &OptionInputs) -> Greeks {
+    let OptionInputs { right, spot, strike, years, rate, volatility } = *inputs;
+    let discount = (-rate * years.max(0.0)).exp();
+    let std_dev = volatility * years.max(0.0).sqrt();
+    if std_dev <= 0.0 || spot <= 0.0 || strike <= 0.0 {
+        let forward_value = spot - strike * discount;
+        return match right {
+            OptionRight::Call => Greeks {
+                price: forward_value.max(0.0),
+                delta: if forward_value > 0.0 { 1.0 } else { 0.0 },
+                ..Greeks::default()
+            },
+            OptionRight::Put => Greeks {
+                price: (-forward_value).max(0.0),
+                delta: if forward_value < 0.0 { -1.0 } else { 0.0 },
+                ..Greeks::default()
+            },
+        };
+    }
+    let d1 = ((spot / strike).ln() + (rate + volatility * volatility / 2.0) * years) / std_dev;
+    let d2 = d1 - std_dev;
+    let density = (-d1 * d1 / 2.0).exp() / (2.0 * PI).sqrt();
+    let gamma = density / (spot * std_dev);
+    let vega = spot * density * years.sqrt() / 100.0;
+    match right {
+        OptionRight::Call => Greeks {
+            price: spot * normal_cdf(d1) - strike * discount * normal_cdf(d2),
+            delta: normal_cdf(d1),
+            gamma,
+            vega,
+        },
+        OptionRight::Put => Greeks {
+            price: strike * discount * normal_cdf(-d2) - spot * normal_cdf(-d1),
+            delta: normal_cdf(d1) - 1.0,
+            gamma,
+            vega,
+        },
+    }
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / SQRT_2)
+}
+
+// --- Volatility ---
+
+/// Implied volatility per underlying, from QA_OPTION_VOLS and
+/// QA_OPTION_DEFAULT_VOL.
+#[derive(Debug, Clone, Serialize, ApiSchema)]
+pub struct ImpliedVols {
+    pub by_underlying: HashMap<String, f64>,
+    pub default: f64,
+}
+
+impl ImpliedVols {
+    pub fn from_env() -> ImpliedVols {
+        let by_underlying = std::env::var("QA_OPTION_VOLS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (symbol, vol) = entry.split_once(':')?;
+                Some((symbol.trim().to_string(), vol.trim().parse().ok().filter(|v: &f64| *v > 0.0)?))
+            })
+            .collect();
+        let default = std::env::var("QA_OPTION_DEFAULT_VOL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &f64| *v > 0.0)
+            .unwrap_or(DEFAULT_VOLATILITY);
+        ImpliedVols { by_underlying, default }
+    }
+
+    pub fn get(&self, underlying: &str) -> f64 {
+        self.by_underlying.get(underlying).copied().unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_textbook_values_and_put_call_parity() {
+        // Hull's example: S=42, K=40, r=10%, sigma=20%, six months.
+        let call = OptionInputs { right: OptionRight::Call, spot: 42.0, strike: 40.0, years: 0.5, rate: 0.1, volatility: 0.2 };
+        let put = OptionInputs { right: OptionRight::Put, ..call };
+        let (c, p) = (black_scholes(&call), black_scholes(&put));
+        assert!((c.price - 4.76).abs() < 0.005 && (p.price - 0.81).abs() < 0.005);
+        assert!((c.price - p.price - (42.0 - 40.0 * (-0.05f64).exp())).abs() < 1e-6);
+        assert!((c.delta - p.delta - 1.0).abs() < 1e-9 && c.gamma == p.gamma && c.vega == p.vega);
+
+        let expired = black_scholes(&OptionInputs { years: 0.0, ..call });
+        assert_eq!(expired, Greeks { price: 2.0, delta: 1.0, gamma: 0.0, vega: 0.0 });
+    }
+}
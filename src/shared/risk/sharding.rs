@@ -0,0 +1,112 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Account Sharding
+ *
+ * File: src/shared/risk/sharding.rs
+ *
+ * Description:
+ * Spreads accounts over risk gateway instances with a consistent-hash ring.
+ * Each instance is placed on the ring at `vnodes` points (hashes of
+ * "{instance}#{n}"); an account belongs to the first point at or after the
+ * hash of its id, wrapping around. When an instance joins or leaves only the
+ * accounts on the arcs it gains or loses move, about 1/N of them, and every
+ * other account keeps its gateway.
+ *
+ * The coordinator gateway publishes the `ShardMap` (the live instances and
+ * an epoch that increases with every change); gateways and order routers
+ * build the same ring from it and so agree on every account's owner.
+ * Hashes are 64-bit FNV-1a, which is stable across processes and builds.
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+
+/// Ring points per instance unless the shard map says otherwise.
+pub const DEFAULT_VNODES: u32 = 64;
+
+/// A risk gateway instance taking part in the ring.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub struct GatewayInstance {
+    pub id: String,
+    /// Base URL orders for the instance's accounts are routed to.
+    pub url: String,
+}
+
+/// The assignment the coordinator publishes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ShardMap {
+    pub epoch: u64,
+    pub vnodes: u32,
+    /// Sorted by id.
+    pub instances: Vec<GatewayInstance>,
+    pub updated_utc: String,
+}
+
+impl ShardMap {
+    pub fn ring(&self) -> HashRing {
+        HashRing::new(self.instances.clone(), self.vnodes)
+    }
+}
+
+/// The consistent-hash ring over a set of instances.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    /// (hash, index into `instances`), sorted by hash.
+    points: Vec<(u64, usize)>,
+    instances: Vec<GatewayInstance>,
+}
+
+impl HashRing {
+    pub fn new(instances: Vec<GatewayInstance>, vnodes: u32) -> HashRing {
+        let mut points: Vec<(u64, usize)> = instances
+            .iter()
+            .enumerate()
+            .flat_map(|(index, instance)| {
+                (0..vnodes.max(1)).map(move |n| (fnv1a(format!("{}#{}", instance.id, n).as_bytes()), index))
+            })
+            .collect();
+        points.sort_unstable();
+        HashRing { points, instances }
+    }
+
+    /// The instance that owns the account; None on an empty ring.
+    pub fn owner(&self, account_id: u32) -> Option<&GatewayInstance> {
+        let hash = fnv1a(&account_id.to_le_bytes());
+        let at = self.points.partition_point(|(point, _)| *point < hash);
+        let (_, index) = self.points.get(at).or_else(|| self.points.first())?;
+        Some(&self.instances[*index])
+    }
+
+    pub fn instances(&self) -> &[GatewayInstance] {
+        &self.instances
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances(ids: &[&str]) -> Vec<GatewayInstance> {
+        ids.iter().map(|id| GatewayInstance { id: id.to_string(), url: format!("http://{}:3034", id) }).collect()
+    }
+
+    #[test]
+    fn a_joining_instance_takes_accounts_only_from_the_others() {
+        let before = HashRing::new(instances(&["gw-a", "gw-b", "gw-c"]), DEFAULT_VNODES);
+        let after = HashRing::new(instances(&["gw-a", "gw-b", "gw-c", "gw-d"]), DEFAULT_VNODES);
+        let mut moved = 0;
+        for account_id in 0..10_000 {
+            let (old, new) = (before.owner(account_id).unwrap(), after.owner(account_id).unwrap());
+            if old != new {
+                assert_eq!(new.id, "gw-d", "account {} moved between existing instances", account_id);
+                moved += 1;
+            }
+        }
+        // About a quarter of the accounts move to the new instance.
+        assert!((1_500..3_500).contains(&moved), "{} accounts moved", moved);
+        assert!(HashRing::new(Vec::new(), DEFAULT_VNODES).owner(101).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Vectorized Path Kernel
+ *
+ * File: src/shared/risk/simd.rs
+ *
+ * Description:
+ * The path generation behind the Monte Carlo engine's `simd` engine. Paths
+ * are simulated LANES at a time in 4-wide vectors (`wide`, which compiles to
+ * SSE2/AVX or NEON on stable Rust): one vector holds an asset's return on
+ * four paths, and the portfolio's loss on them accumulates with one fused
+ * multiply-add per asset.
+ *
+ * Normal returns, the common case, are generated in the lanes too: each
+ * lane runs its own xoshiro256+ generator, seeded from the caller's, and the
+ * Box-Muller transform turns each pair of uniform vectors into two vectors
+ * of normals, the second kept for the next normal asset. Student-t and
+ * empirical returns are drawn from the caller's generator one path at a
+ * time, as in the scalar engine, and only their revaluation is vectorized.
+ *
+ * The vectors are as wide as the target allows: the default x86-64 target
+ * has only SSE2, two lanes an instruction, so build the VaR calculator with
+ * RUSTFLAGS="-C target-cpu=x86-64-v3" (AVX2 and FMA) to get the speed-up.
+ *
+ * The draws are not the scalar engine's, so a seed gives different paths per
+ * engine; the two agree in distribution, which the tests check. A seeded run
+ * of one engine repeats.
+ */
+
+use crate::distributions::ReturnSampler;
+use crate::monte_carlo::SimulatedAsset;
+use rand::Rng;
+use std::f64::consts::TAU;
+use wide::{f64x4, u64x4};
+
+/// Paths simulated per vector.
+pub const LANES: usize = 4;
+
+/// Standard normals, a vector at a time, from a xoshiro256+ generator per
+/// lane.
+struct NormalLanes {
+    state: [u64x4; 4],
+    spare: Option<f64x4>,
+}
+
+impl NormalLanes {
+    fn seeded<R: Rng + ?Sized>(rng: &mut R) -> NormalLanes {
+        // An all-zero state would stay zero; a seeded rng will not draw one.
+        let state = [(); 4].map(|_| u64x4::from([(); LANES].map(|_| rng.gen::<u64>())));
+        NormalLanes { state, spare: None }
+    }
+
+    /// Uniforms in [0, 1), from the top 52 bits of each lane's next output.
+    fn uniforms(&mut self) -> f64x4 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let output = *s0 + *s3;
+        let shifted = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= shifted;
+        *s3 = (*s3 << 45) | (*s3 >> 19);
+        // Under the exponent of 1.0 the bits are a double in [1, 2).
+        let bits: u64x4 = (output >> 12) | u64x4::splat(1.0f64.to_bits());
+        f64x4::from(bits.to_array().map(f64::from_bits)) - f64x4::ONE
+    }
+
+    fn next(&mut self) -> f64x4 {
+        if let Some(normals) = self.spare.take() {
+            return normals;
+        }
+        // 1 - [0, 1) keeps the logarithm's argument away from zero.
+        let u1 = f64x4::ONE - self.uniforms();
+        let u2 = self.uniforms();
+        let radius = (f64x4::splat(-2.0) * u1.ln()).sqrt();
+        let (sin, cos) = (u2 * f64x4::splat(TAU)).sin_cos();
+        self.spare = Some(radius * sin);
+        radius * cos
+    }
+}
+
+/// The portfolio's loss on each of `num_paths` simulated days.
+pub fn portfolio_losses<R: Rng + ?Sized>(assets: &[SimulatedAsset], num_paths: usize, rng: &mut R) -> Vec<f64> {
+    let weights: Vec<f64x4> = assets.iter().map(|asset| f64x4::splat(-asset.quantity * asset.price)).collect();
+    let mut normals = NormalLanes::seeded(rng);
+    let mut losses = Vec::with_capacity(num_paths.next_multiple_of(LANES));
+    for _ in 0..num_paths.div_ceil(LANES) {
+        let mut loss = f64x4::ZERO;
+        for (asset, weight) in assets.iter().zip(&weights) {
+            let returns = match asset.sampler {
+                ReturnSampler::Normal(dist) => {
+                    normals.next().mul_add(f64x4::splat(dist.std_dev()), f64x4::splat(dist.mean()))
+                }
+                sampler => f64x4::from([(); LANES].map(|_| sampler.sample(rng))),
+            };
+            loss = returns.mul_add(*weight, loss);
+        }
+        losses.extend_from_slice(&loss.to_array());
+    }
+    losses.truncate(num_paths);
+    losses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributions::DistributionKind;
+    use crate::monte_carlo::{simulate_var_with_rates, VarEngine, VarSampling};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, Normal};
+
+    #[test]
+    fn the_kernel_agrees_with_the_scalar_engine() {
+        let mut history_rng = StdRng::seed_from_u64(3);
+        let daily = Normal::new(0.0, 0.025).unwrap();
+        let history: Vec<f64> = (0..500).map(|_| daily.sample(&mut history_rng)).collect();
+        let num_paths = 100_001;
+        for kind in [DistributionKind::Normal, DistributionKind::StudentT, DistributionKind::Empirical] {
+            let btc = ReturnSampler::fit(kind, &history, 0.02);
+            let eth = ReturnSampler::fit(DistributionKind::Normal, &[], 0.03);
+            let assets = [
+                SimulatedAsset { quantity: 10.0, price: 60_000.0, sampler: &btc },
+                SimulatedAsset { quantity: -50.0, price: 3_000.0, sampler: &eth },
+            ];
+            let var = |engine, seed| {
+                let sampling = VarSampling::plain(engine);
+                simulate_var_with_rates(sampling, &assets, None, num_paths, 0.99, &mut StdRng::seed_from_u64(seed)).var
+            };
+            let (scalar, simd) = (var(VarEngine::Scalar, 1), var(VarEngine::Simd, 2));
+            // The 99% quantile's standard error on 100,000 paths is about 0.5%.
+            assert!((simd - scalar).abs() < 0.03 * scalar, "{:?}: simd {} scalar {}", kind, simd, scalar);
+            assert_eq!(simd, var(VarEngine::Simd, 2));
+        }
+
+        let btc = ReturnSampler::fit(DistributionKind::Normal, &[], 0.02);
+        let flat = [SimulatedAsset { quantity: 1.0, price: 100.0, sampler: &btc }];
+        let losses = portfolio_losses(&flat, 10_003, &mut StdRng::seed_from_u64(4));
+        assert_eq!(losses.len(), 10_003);
+        let mean = losses.iter().sum::<f64>() / losses.len() as f64;
+        let std_dev = (losses.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / losses.len() as f64).sqrt();
+        assert!(mean.abs() < 0.1 && (std_dev - 2.0).abs() < 0.1, "mean {} std_dev {}", mean, std_dev);
+    }
+}
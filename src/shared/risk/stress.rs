@@ -0,0 +1,274 @@
+/*
+ * QuantumArb 2.0 - Risk & Compliance: Stress Limits
+ *
+ * File: src/shared/risk/stress.rs
+ *
+ * Description:
+ * What-if checks of large orders against predefined stress scenarios. Each
+ * asset class has its own scenario set; a scenario moves the price of every
+ * instrument of that class (an option's underlying, plus its implied
+ * volatility) and the account's positions in the class, with the order
+ * filled, are revalued:
+ *
+ *   linear        quantity x multiplier x mark x price_move
+ *   options       repriced (see `pricing`) at the moved underlying and
+ *                 volatility, less their price now
+ *
+ * The account's stressed loss is its loss in the worst scenario. An order
+ * of at least `min_order_notional` that would take it past the account's
+ * stress limit is, per the configured action, rejected with
+ * RISK_STRESS_LIMIT or held for an operator with
+ * RISK_STRESS_APPROVAL_REQUIRED. As for the greeks limits, an order that
+ * brings the stressed loss down passes even while it is over the limit.
+ * Positions without a mark are left out; positions in other asset classes
+ * are not moved, so hedges across classes are not netted.
+ */
+
+use crate::greeks::OptionMarket;
+use crate::pricing::{self, OptionInputs};
+use chrono::{DateTime, Utc};
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use quantumarb_refdata::{AssetClass, InstrumentDefinition, ReferenceData};
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// --- Data Structures ---
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct StressScenario {
+    pub name: String,
+    /// Price move as a fraction (-0.10 is down 10%); for an option, of its
+    /// underlying.
+    pub price_move: f64,
+    /// Move in implied volatility (0.10 is ten points up); options only.
+    #[serde(default)]
+    pub vol_move: f64,
+}
+
+/// What happens to an order that breaches the stress limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StressAction {
+    Reject,
+    RequireApproval,
+}
+
+/// The stress check's settings; stored in Redis and shared by every
+/// gateway instance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct StressConfig {
+    /// Orders below this notional (delta-adjusted for options) are not stressed.
+    pub min_order_notional: Money,
+    pub action: StressAction,
+    pub scenarios: HashMap<AssetClass, Vec<StressScenario>>,
+}
+
+impl Default for StressConfig {
+    fn default() -> StressConfig {
+        let scenario = |name: &str, price_move, vol_move| StressScenario { name: name.to_string(), price_move, vol_move };
+        StressConfig {
+            min_order_notional: Money::from_f64(250_000.0),
+            action: StressAction::RequireApproval,
+            scenarios: HashMap::from([
+                (AssetClass::Crypto, vec![scenario("down_10", -0.10, 0.0), scenario("up_10", 0.10, 0.0)]),
+                (AssetClass::Future, vec![scenario("down_5", -0.05, 0.0), scenario("up_5", 0.05, 0.0)]),
+                (AssetClass::Equity, vec![scenario("down_10", -0.10, 0.0), scenario("up_10", 0.10, 0.0)]),
+                (
+                    AssetClass::Option,
+                    vec![
+                        scenario("crash", -0.10, 0.15),
+                        scenario("rally", 0.10, 0.05),
+                        scenario("vol_crush", 0.0, -0.15),
+                    ],
+                ),
+            ]),
+        }
+    }
+}
+
+/// Prices positions are revalued from.
+#[derive(Debug, Clone, Copy)]
+pub struct StressMarket<'a> {
+    pub instruments: &'a ReferenceData,
+    /// Last price by symbol, in points.
+    pub marks: &'a HashMap<String, f64>,
+    pub options: &'a OptionMarket,
+    pub now: DateTime<Utc>,
+}
+
+/// The loss of a set of positions in their worst scenario.
+#[derive(Debug, Clone, PartialEq, Serialize, ApiSchema)]
+pub struct StressedLoss {
+    pub scenario: String,
+    /// Positive for a loss.
+    pub loss: Money,
+}
+
+// --- Evaluation ---
+
+/// The positions' loss in the worst of `scenarios`, which move instruments
+/// of `asset_class`; None without scenarios.
+pub fn stressed_loss(
+    scenarios: &[StressScenario],
+    asset_class: AssetClass,
+    market: &StressMarket,
+    positions: &HashMap<String, i64>,
+) -> Option<StressedLoss> {
+    let moved: Vec<(&InstrumentDefinition, i64)> = positions
+        .iter()
+        .filter_map(|(symbol, quantity)| Some((market.instruments.by_symbol(symbol)?, *quantity)))
+        .filter(|(instrument, quantity)| instrument.asset_class == asset_class && *quantity != 0)
+        .collect();
+    scenarios
+        .iter()
+        .map(|scenario| {
+            let pnl: Money = moved
+                .iter()
+                .filter_map(|(instrument, quantity)| scenario_pnl(scenario, instrument, *quantity, market))
+                .sum();
+            StressedLoss { scenario: scenario.name.clone(), loss: -pnl }
+        })
+        .max_by_key(|stressed| stressed.loss)
+}
+
+fn scenario_pnl(
+    scenario: &StressScenario,
+    instrument: &InstrumentDefinition,
+    quantity: i64,
+    market: &StressMarket,
+) -> Option<Money> {
+    let units = quantity as f64 * instrument.multiplier.to_f64();
+    if instrument.option.is_some() {
+        let now = market.options.inputs(instrument, market.now).ok().flatten()?;
+        let moved = OptionInputs {
+            spot: now.spot * (1.0 + scenario.price_move),
+            volatility: (now.volatility + scenario.vol_move).max(0.0),
+            ..now
+        };
+        let change = pricing::black_scholes(&moved).price - pricing::black_scholes(&now).price;
+        return Some(Money::from_f64(change * units));
+    }
+    let mark = market.marks.get(&instrument.symbol)?;
+    Some(Money::from_f64(mark * scenario.price_move * units))
+}
+
+// --- Pre-Trade Check ---
+
+/// Stresses an order of `notional` on `instrument` given the account's
+/// positions, against the account's stress limit.
+pub fn check_order(
+    config: &StressConfig,
+    max_loss: Option<Money>,
+    market: &StressMarket,
+    positions: &HashMap<String, i64>,
+    instrument: &InstrumentDefinition,
+    order: &OrderRequest,
+    notional: Money,
+) -> Result<(), Rejection> {
+    let Some(limit) = max_loss else {
+        return Ok(());
+    };
+    let Some(scenarios) = config.scenarios.get(&instrument.asset_class) else {
+        return Ok(());
+    };
+    if notional < config.min_order_notional {
+        return Ok(());
+    }
+    // The order's own price marks an instrument not yet marked.
+    let mut marks = Cow::Borrowed(market.marks);
+    if !marks.contains_key(&instrument.symbol) {
+        marks.to_mut().insert(instrument.symbol.clone(), instrument.price(order.price).to_f64());
+    }
+    let market = StressMarket { marks: &marks, ..*market };
+    let mut after = positions.clone();
+    *after.entry(instrument.symbol.clone()).or_insert(0) += match order.side {
+        OrderSide::Buy => order.size as i64,
+        OrderSide::Sell => -(order.size as i64),
+    };
+    let loss = |positions| stressed_loss(scenarios, instrument.asset_class, &market, positions);
+    let (Some(before), Some(after)) = (loss(positions), loss(&after)) else {
+        return Ok(());
+    };
+    if after.loss <= limit || after.loss <= before.loss {
+        return Ok(());
+    }
+    let code = match config.action {
+        StressAction::Reject => RejectCode::RiskStressLimit,
+        StressAction::RequireApproval => RejectCode::RiskStressApprovalRequired,
+    };
+    Err(Rejection::new(
+        code,
+        format!(
+            "Order for {} takes account {}'s stressed loss ({}) from {:.2} to {:.2}, past its limit of {:.2}",
+            instrument.symbol, order.account_id, after.scenario, before.loss, after.loss, limit
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ImpliedVols;
+    use quantumarb_wire::{HopStamps, OrderPriority, TradingMode};
+
+    fn order(instrument_id: u32, side: OrderSide, size: u32, price: u64) -> OrderRequest {
+        OrderRequest {
+            order_id: uuid::Uuid::nil(),
+            account_id: 101,
+            instrument_id,
+            side,
+            price,
+            size,
+            stamps: HopStamps::default(),
+            venue_id: 0,
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Opportunistic,
+            strategy_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn large_orders_are_held_to_the_worst_scenario_loss() {
+        let instruments = ReferenceData::seeded();
+        let options = OptionMarket {
+            spots: HashMap::from([("BTC".to_string(), 60_000.0)]),
+            vols: ImpliedVols { by_underlying: HashMap::new(), default: 0.6 },
+            curve: None,
+        };
+        let marks = HashMap::from([("BTC".to_string(), 60_000.0)]);
+        let market = StressMarket { instruments: &instruments, marks: &marks, options: &options, now: Utc::now() };
+        let config = StressConfig { min_order_notional: Money::from_f64(100_000.0), ..StressConfig::default() };
+        let limit = Some(Money::from_f64(50_000.0));
+        let btc = instruments.get(1).unwrap();
+        let check = |positions: &HashMap<String, i64>, side, size| {
+            let notional = Money::from_f64(60_000.0 * size as f64);
+            check_order(&config, limit, &market, positions, btc, &order(1, side, size, 60_000_00), notional)
+        };
+
+        // Long 5 BTC loses 30,000 if it falls 10%; 9 would lose 54,000.
+        let long = HashMap::from([("BTC".to_string(), 3)]);
+        assert!(check(&long, OrderSide::Buy, 2).is_ok());
+        let rejection = check(&long, OrderSide::Buy, 6).unwrap_err();
+        assert_eq!(rejection.code, RejectCode::RiskStressApprovalRequired);
+        // Small orders are not stressed; orders that reduce the loss pass.
+        assert!(check(&HashMap::from([("BTC".to_string(), 12)]), OrderSide::Buy, 1).is_ok());
+        assert!(check(&HashMap::from([("BTC".to_string(), 12)]), OrderSide::Sell, 2).is_ok());
+
+        let reject = StressConfig { action: StressAction::Reject, ..config.clone() };
+        // Selling 12 leaves short 9, which loses 54,000 if it rises 10%.
+        let sell = order(1, OrderSide::Sell, 12, 60_000_00);
+        let rejection = check_order(&reject, limit, &market, &long, btc, &sell, Money::from_f64(720_000.0)).unwrap_err();
+        assert_eq!(rejection.code, RejectCode::RiskStressLimit);
+
+        // A long call loses in the vol crush even with the underlying unmoved.
+        let call = instruments.get(5).unwrap();
+        let calls = HashMap::from([(call.symbol.clone(), 10)]);
+        let worst = stressed_loss(&config.scenarios[&AssetClass::Option], AssetClass::Option, &market, &calls).unwrap();
+        assert!(worst.loss > Money::ZERO);
+        assert!(["crash", "vol_crush"].contains(&worst.scenario.as_str()));
+    }
+}
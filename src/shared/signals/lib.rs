@@ -0,0 +1,199 @@
+/*
+ * QuantumArb 2.0 - Shared: Book Signals
+ *
+ * File: src/shared/signals/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-signals`) turns an instrument's top of
+ * book into short-horizon signals:
+ *
+ *   imbalance            (bid size - ask size) / (bid size + ask size), from
+ *                        -1 (all the size on the ask) to 1 (all on the bid)
+ *   microprice           the mid weighted towards the thinner side (bid x
+ *                        ask size + ask x bid size, over both sizes), where
+ *                        the price is likelier to move next
+ *   microprice_edge_bps  microprice less the mid, in basis points of the mid
+ *   momentum_bps         change in the microprice over the momentum horizon,
+ *                        in basis points; None until the book has been
+ *                        followed that long
+ *
+ * The data bus connector computes them from the top of book it follows on
+ * 'market_data.instrument.*' and publishes them on 'signals.instrument.<id>'.
+ * The feature store keeps the latest per interval as ML features, and a
+ * strategy can act on a signal's `lean` directly.
+ *
+ * The momentum horizon is QA_SIGNAL_MOMENTUM_HORIZON_MS (default
+ * DEFAULT_MOMENTUM_HORIZON_MS).
+ */
+
+use quantumarb_openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub const DEFAULT_MOMENTUM_HORIZON_MS: u64 = 1_000;
+/// Cap on the microprices held per instrument, so a burst of updates within
+/// the horizon cannot grow the history without bound.
+const MAX_SAMPLES: usize = 10_000;
+
+// --- Data Structures ---
+
+/// Best bid and ask, prices in points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopOfBook {
+    pub bid: f64,
+    pub bid_size: f64,
+    pub ask: f64,
+    pub ask_size: f64,
+}
+
+impl TopOfBook {
+    /// Both sides quoted with size, and not crossed.
+    pub fn is_valid(&self) -> bool {
+        self.bid > 0.0 && self.ask >= self.bid && self.bid_size > 0.0 && self.ask_size > 0.0
+    }
+
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+
+    pub fn imbalance(&self) -> f64 {
+        (self.bid_size - self.ask_size) / (self.bid_size + self.ask_size)
+    }
+
+    pub fn microprice(&self) -> f64 {
+        (self.bid * self.ask_size + self.ask * self.bid_size) / (self.bid_size + self.ask_size)
+    }
+}
+
+/// The signals of one instrument at one top of book; see the crate
+/// description.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct BookSignals {
+    pub instrument_id: u32,
+    pub symbol: String,
+    pub timestamp_ns: u64,
+    pub mid: f64,
+    pub imbalance: f64,
+    pub microprice: f64,
+    pub microprice_edge_bps: f64,
+    pub momentum_bps: Option<f64>,
+}
+
+/// Which way the book points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Lean {
+    Up,
+    Down,
+    Flat,
+}
+
+impl BookSignals {
+    /// Up when the imbalance is at least `min_imbalance` towards the bid and
+    /// the microprice has not fallen over the horizon, Down for the mirror
+    /// image, and Flat otherwise (or before there is any momentum).
+    pub fn lean(&self, min_imbalance: f64) -> Lean {
+        match self.momentum_bps {
+            Some(momentum) if self.imbalance >= min_imbalance && momentum >= 0.0 => Lean::Up,
+            Some(momentum) if self.imbalance <= -min_imbalance && momentum <= 0.0 => Lean::Down,
+            _ => Lean::Flat,
+        }
+    }
+}
+
+// --- Signal Computation ---
+
+/// Follows one instrument's top of book.
+#[derive(Debug, Clone)]
+pub struct SignalBuilder {
+    horizon_ns: u64,
+    /// (timestamp_ns, microprice), oldest first.
+    microprices: VecDeque<(u64, f64)>,
+}
+
+impl SignalBuilder {
+    pub fn new(horizon_ms: u64) -> SignalBuilder {
+        SignalBuilder { horizon_ns: horizon_ms * 1_000_000, microprices: VecDeque::new() }
+    }
+
+    /// A builder with the QA_SIGNAL_MOMENTUM_HORIZON_MS horizon.
+    pub fn from_env() -> SignalBuilder {
+        let horizon_ms = std::env::var("QA_SIGNAL_MOMENTUM_HORIZON_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(DEFAULT_MOMENTUM_HORIZON_MS);
+        SignalBuilder::new(horizon_ms)
+    }
+
+    /// The signals at a new top of book; None for a one-sided or crossed
+    /// book, which is left out of the momentum too.
+    pub fn on_book(
+        &mut self,
+        instrument_id: u32,
+        symbol: &str,
+        book: TopOfBook,
+        timestamp_ns: u64,
+    ) -> Option<BookSignals> {
+        if !book.is_valid() {
+            return None;
+        }
+        let (mid, microprice) = (book.mid(), book.microprice());
+        // Keep the newest sample at or before the horizon to measure from.
+        let start = timestamp_ns.checked_sub(self.horizon_ns);
+        let measured_from = |t: u64| start.is_some_and(|start| t <= start);
+        while self.microprices.get(1).is_some_and(|(t, _)| measured_from(*t)) {
+            self.microprices.pop_front();
+        }
+        let momentum_bps = self
+            .microprices
+            .front()
+            .filter(|(t, _)| measured_from(*t))
+            .map(|(_, then)| (microprice / then - 1.0) * 10_000.0);
+        if self.microprices.len() == MAX_SAMPLES {
+            self.microprices.pop_front();
+        }
+        self.microprices.push_back((timestamp_ns, microprice));
+        Some(BookSignals {
+            instrument_id,
+            symbol: symbol.to_string(),
+            timestamp_ns,
+            mid,
+            imbalance: book.imbalance(),
+            microprice,
+            microprice_edge_bps: (microprice - mid) / mid * 10_000.0,
+            momentum_bps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_on_the_bid_pulls_the_microprice_up_and_momentum_follows_it() {
+        let mut builder = SignalBuilder::new(1_000);
+        let book = |bid_size, ask_size| TopOfBook { bid: 99.0, bid_size, ask: 101.0, ask_size };
+        let ms = 1_000_000;
+
+        let even = builder.on_book(1, "BTC", book(10.0, 10.0), 0).unwrap();
+        assert_eq!((even.imbalance, even.microprice, even.momentum_bps), (0.0, 100.0, None));
+        // Three times the size on the bid: the next trade likelier lifts the ask.
+        let bid_heavy = builder.on_book(1, "BTC", book(30.0, 10.0), 600 * ms).unwrap();
+        assert_eq!((bid_heavy.imbalance, bid_heavy.microprice), (0.5, 100.5));
+        assert!((bid_heavy.microprice_edge_bps - 50.0).abs() < 1e-9);
+        assert_eq!(bid_heavy.lean(0.3), Lean::Flat, "no momentum before the horizon");
+
+        // Measured from the last book at or before one second ago.
+        let later = builder.on_book(1, "BTC", book(30.0, 10.0), 1_700 * ms).unwrap();
+        assert!((later.momentum_bps.unwrap() - 0.0).abs() < 1e-9);
+        assert_eq!(later.lean(0.3), Lean::Up);
+        assert_eq!(later.lean(0.6), Lean::Flat);
+        let ask_heavy = builder.on_book(1, "BTC", book(10.0, 30.0), 1_800 * ms).unwrap();
+        assert!((ask_heavy.momentum_bps.unwrap() - (99.5 / 100.5 - 1.0) * 10_000.0).abs() < 1e-9);
+        assert_eq!(ask_heavy.lean(0.3), Lean::Down);
+
+        assert!(builder.on_book(1, "BTC", TopOfBook { bid: 101.5, ..book(1.0, 1.0) }, 1_900 * ms).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+/*
+ * QuantumArb 2.0 - Shared: Deterministic Simulation
+ *
+ * File: src/shared/sim/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-sim`) is the single source of randomness
+ * for everything the platform simulates: Monte Carlo VaR paths, the paper
+ * venue's rejections, the simulated feeds and order flow, and the latency
+ * oracle's path jitter. A service reads its `Seed` once at startup and takes
+ * one named stream per component:
+ *
+ *   let seed = quantumarb_sim::Seed::from_args();
+ *   let mut rng = seed.stream("var.monte_carlo");
+ *
+ * The seed comes from a `--seed N` command-line flag, or QA_SEED when the
+ * flag is absent. With a seed, each stream is a ChaCha8 generator keyed by
+ * the seed and the stream's name, so the same seed gives the same draws on
+ * every run and every machine. Streams are independent: a component that
+ * draws more numbers does not shift any other component's sequence. Without
+ * a seed, streams are seeded from OS entropy.
+ *
+ * A seed fixes what is drawn, not when: tick intervals and task scheduling
+ * still follow the wall clock, so each component repeats its own sequence
+ * of simulated values.
+ */
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use uuid::Uuid;
+
+/// The generator behind every simulation stream.
+pub type SimRng = ChaCha8Rng;
+
+// --- Seed ---
+
+/// The run's seed, or none for a non-reproducible run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seed(Option<u64>);
+
+impl Seed {
+    pub fn fixed(seed: u64) -> Seed {
+        Seed(Some(seed))
+    }
+
+    pub fn entropy() -> Seed {
+        Seed(None)
+    }
+
+    /// Reads `--seed N` (or `--seed=N`) from the command line, then QA_SEED.
+    /// Panics on a seed that is not a u64, rather than silently running
+    /// unseeded.
+    pub fn from_args() -> Seed {
+        let from_flag = parse_seed_flag(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+        let seed = from_flag.or_else(|| {
+            std::env::var("QA_SEED")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse().unwrap_or_else(|_| panic!("QA_SEED must be an unsigned integer, got '{}'", v)))
+        });
+        Seed(seed)
+    }
+
+    pub fn value(&self) -> Option<u64> {
+        self.0
+    }
+
+    /// The generator for one component. Each name gets its own sequence.
+    pub fn stream(&self, name: &str) -> SimRng {
+        match self.0 {
+            Some(seed) => SimRng::seed_from_u64(splitmix64(seed ^ fnv1a(name))),
+            None => SimRng::from_entropy(),
+        }
+    }
+
+    /// For startup logs.
+    pub fn describe(&self) -> String {
+        match self.0 {
+            Some(seed) => format!("deterministic (seed {})", seed),
+            None => "unseeded".to_string(),
+        }
+    }
+}
+
+/// A version-4 UUID drawn from `rng`, so ids repeat with the seed.
+pub fn uuid(rng: &mut SimRng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.gen()).into_uuid()
+}
+
+// --- Helpers ---
+
+fn parse_seed_flag(mut args: impl Iterator<Item = String>) -> Result<Option<u64>, String> {
+    while let Some(arg) = args.next() {
+        let value = if arg == "--seed" {
+            args.next().ok_or("--seed needs a value")?
+        } else if let Some(value) = arg.strip_prefix("--seed=") {
+            value.to_string()
+        } else {
+            continue;
+        };
+        return value.parse().map(Some).map_err(|_| format!("--seed must be an unsigned integer, got '{}'", value));
+    }
+    Ok(None)
+}
+
+/// Stable across Rust versions, unlike the standard library's hasher.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// Spreads nearby seeds (1, 2, 3...) across the key space.
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,236 @@
+/*
+ * QuantumArb 2.0 - Shared: Strategy Plugin SDK
+ *
+ * File: src/shared/strategy_sdk/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-strategy-sdk`) is how a strategy is
+ * written as a plugin for the strategy engine, shipped without rebuilding
+ * the engine: a WebAssembly module the engine loads, runs in a sandbox and
+ * can swap while it trades (see the engine's `plugins.rs`).
+ *
+ * A plugin implements `Strategy` and exports it:
+ *
+ *   struct Skew { edge: u64 }
+ *
+ *   impl Strategy for Skew {
+ *       fn init(config: &serde_json::Value) -> Result<Self, String> { ... }
+ *       fn on_market(&mut self, view: &MarketView) -> Vec<OrderIntent> { ... }
+ *   }
+ *
+ *   quantumarb_strategy_sdk::export_strategy!(Skew);
+ *
+ * and is built as a `cdylib` for wasm32-unknown-unknown:
+ *
+ *   cargo build --release --target wasm32-unknown-unknown
+ *
+ * The engine calls `on_market` with every pass's venue books and routes the
+ * orders returned through its pre-trade risk checks, tagged with the
+ * plugin's name. `init` gets the plugin's configuration (the JSON file next
+ * to the module), and `log` writes to the engine's log.
+ *
+ * The ABI underneath, for plugins written in other languages: the module
+ * exports its `memory` and
+ *
+ *   qa_abi_version() -> i32           ABI_VERSION
+ *   qa_alloc(len: i32) -> i32         a buffer for the engine to write into
+ *   qa_free(ptr: i32, len: i32)       releases a buffer either side made
+ *   qa_init(ptr: i32, len: i32) -> i32
+ *                                     the configuration JSON; 0 if accepted
+ *   qa_on_market(ptr: i32, len: i32) -> i64
+ *                                     a `MarketView` as JSON; returns the
+ *                                     JSON array of `OrderIntent`s as
+ *                                     pointer << 32 | length, 0 for none
+ *
+ * and may import `qa.log(ptr: i32, len: i32)`, UTF-8 text. Input buffers
+ * belong to the plugin once passed; the engine frees each output buffer
+ * after reading it.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// The plugin ABI this crate implements.
+pub const ABI_VERSION: i32 = 1;
+
+// --- Messages ---
+
+/// One venue's top of book; prices at the instrument's wire price scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VenueBook {
+    pub venue_id: u32,
+    pub best_bid: u64,
+    pub bid_size: u32,
+    pub best_ask: u64,
+    pub ask_size: u32,
+}
+
+/// What a plugin sees on each pass of the engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketView {
+    pub instrument_id: u32,
+    pub symbol: String,
+    /// Decimal places of the wire prices (2 means 6010050 is 60100.50).
+    pub price_decimals: u8,
+    pub venues: Vec<VenueBook>,
+    /// Wall-clock time of the pass, in nanoseconds since the Unix epoch.
+    pub time_ns: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// An order a plugin wants sent; it is risk checked like any other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderIntent {
+    pub venue_id: u32,
+    pub side: Side,
+    pub price: u64,
+    pub size: u32,
+}
+
+// --- Writing a plugin ---
+
+/// A strategy run by the engine as a plugin.
+pub trait Strategy: Sized {
+    /// Builds the strategy from its configuration; an error refuses the load.
+    fn init(config: &serde_json::Value) -> Result<Self, String>;
+
+    /// The orders to send on this pass, if any.
+    fn on_market(&mut self, view: &MarketView) -> Vec<OrderIntent>;
+}
+
+/// Writes a line to the engine's log, prefixed with the plugin's name.
+pub fn log(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        #[link(wasm_import_module = "qa")]
+        extern "C" {
+            #[link_name = "log"]
+            fn host_log(ptr: i32, len: i32);
+        }
+        // SAFETY: the engine only reads the bytes passed.
+        unsafe { host_log(message.as_ptr() as i32, message.len() as i32) };
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    println!("{}", message);
+}
+
+/// Exports a `Strategy` through the plugin ABI.
+#[macro_export]
+macro_rules! export_strategy {
+    ($strategy:ty) => {
+        thread_local! {
+            static QA_STRATEGY: ::std::cell::RefCell<Option<$strategy>> = const { ::std::cell::RefCell::new(None) };
+        }
+
+        #[no_mangle]
+        pub extern "C" fn qa_abi_version() -> i32 {
+            $crate::ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn qa_alloc(len: i32) -> i32 {
+            $crate::guest::alloc(len)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn qa_free(ptr: i32, len: i32) {
+            drop($crate::guest::take(ptr, len))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn qa_init(ptr: i32, len: i32) -> i32 {
+            QA_STRATEGY.with(|slot| $crate::guest::init(slot, $crate::guest::take(ptr, len)))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn qa_on_market(ptr: i32, len: i32) -> i64 {
+            QA_STRATEGY.with(|slot| $crate::guest::on_market(slot, $crate::guest::take(ptr, len)))
+        }
+    };
+}
+
+/// The plugin side of the ABI, used by `export_strategy!`. Pointers are
+/// offsets into the module's 32-bit memory.
+#[doc(hidden)]
+pub mod guest {
+    use super::{MarketView, Strategy};
+    use std::cell::RefCell;
+
+    pub fn alloc(len: i32) -> i32 {
+        leak(vec![0; len.max(0) as usize])
+    }
+
+    /// Hands a buffer to the engine; its capacity is exactly its length.
+    fn leak(buffer: Vec<u8>) -> i32 {
+        Box::into_raw(buffer.into_boxed_slice()) as *mut u8 as usize as i32
+    }
+
+    /// Takes back a buffer made by `alloc` (or returned by `on_market`).
+    pub fn take(ptr: i32, len: i32) -> Vec<u8> {
+        if ptr == 0 || len <= 0 {
+            return Vec::new();
+        }
+        // SAFETY: the buffer was leaked by `leak` with exactly `len` bytes.
+        unsafe { Vec::from_raw_parts(ptr as u32 as usize as *mut u8, len as usize, len as usize) }
+    }
+
+    pub fn init<S: Strategy>(slot: &RefCell<Option<S>>, config: Vec<u8>) -> i32 {
+        let config = match serde_json::from_slice(&config) {
+            Ok(config) => config,
+            Err(e) => {
+                super::log(&format!("invalid configuration: {}", e));
+                return 1;
+            }
+        };
+        match S::init(&config) {
+            Ok(strategy) => {
+                *slot.borrow_mut() = Some(strategy);
+                0
+            }
+            Err(e) => {
+                super::log(&format!("refused to start: {}", e));
+                2
+            }
+        }
+    }
+
+    pub fn on_market<S: Strategy>(slot: &RefCell<Option<S>>, view: Vec<u8>) -> i64 {
+        let Ok(view) = serde_json::from_slice::<MarketView>(&view) else {
+            return 0;
+        };
+        let orders = match slot.borrow_mut().as_mut() {
+            Some(strategy) => strategy.on_market(&view),
+            None => return 0,
+        };
+        if orders.is_empty() {
+            return 0;
+        }
+        let output = serde_json::to_vec(&orders).unwrap_or_default();
+        let len = output.len() as u32;
+        ((leak(output) as u32 as i64) << 32) | len as i64
+    }
+}
+
+/// Splits an `on_market` result into its pointer and length.
+pub fn unpack(packed: i64) -> (u32, u32) {
+    ((packed as u64 >> 32) as u32, packed as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_keep_the_abi_json_shape() {
+        let order = OrderIntent { venue_id: 2, side: Side::Sell, price: 6010050, size: 3 };
+        let json = serde_json::to_string(&[order]).unwrap();
+        assert_eq!(json, r#"[{"venue_id":2,"side":"sell","price":6010050,"size":3}]"#);
+        assert_eq!(serde_json::from_str::<Vec<OrderIntent>>(&json).unwrap(), [order]);
+        assert_eq!(unpack((4096 << 32) | 57), (4096, 57));
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * QuantumArb 2.0 - Shared: Desks
+ *
+ * File: src/shared/tenancy/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-tenancy`) is the desk (tenant) dimension
+ * of the platform. Several desks trade on one deployment; each owns a set of
+ * accounts and strategies, and its users and strategies see and change only
+ * those. Firm users (risk, compliance, operations) see every desk and are
+ * the only ones who can change firm-wide settings.
+ *
+ * Configuration: QA_DESKS_PATH names a JSON file such as
+ *
+ *   {
+ *     "desks": [
+ *       { "id": "crypto", "accounts": [101], "strategies": ["momentum", "stat_arb"],
+ *         "tokens": ["..."] },
+ *       { "id": "rates", "accounts": [205], "strategies": ["basis"], "tokens": ["..."] }
+ *     ],
+ *     "firm_tokens": ["..."]
+ *   }
+ *
+ * An account or strategy belongs to at most one desk. API callers present a
+ * desk's or a firm token as Authorization: Bearer <token>; a request with no
+ * valid token is answered 401 with SYSTEM_UNAUTHORIZED, and one that names
+ * another desk's account or strategy is answered as if it did not exist.
+ * Firm-wide endpoints answer desk callers 403 with SYSTEM_FORBIDDEN.
+ *
+ * Without QA_DESKS_PATH the platform is a single desk and every caller has
+ * firm scope, as before desks existed. A file that cannot be read or is
+ * invalid is an error: a service must not start open to every desk.
+ *
+ * Desk-owned messages travel on topics namespaced by desk (see
+ * `quantumarb_bus::topics::for_desk`).
+ *
+ * The services add `scope`, `firm_only` or `desk_only` to their routes and
+ * `.recover(recover)` to the assembled API, which answers the rejections of
+ * all three with the standard error body. A service reading another's
+ * desk-scoped API (the portfolio manager's book, the surveillance alerts)
+ * does so with `firm_client`, which presents the first firm token.
+ */
+
+use quantumarb_errors::{ErrorBody, RejectCode, Rejection};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use warp::Filter;
+
+// --- Configuration ---
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Desk {
+    pub id: String,
+    #[serde(default)]
+    pub accounts: BTreeSet<u32>,
+    #[serde(default)]
+    pub strategies: BTreeSet<String>,
+    /// Bearer tokens of the desk's users and strategies.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Desks {
+    #[serde(default)]
+    pub desks: Vec<Desk>,
+    /// Bearer tokens with firm scope.
+    #[serde(default)]
+    pub firm_tokens: Vec<String>,
+}
+
+impl Desks {
+    /// Reads QA_DESKS_PATH; a single desk if it is unset.
+    pub fn from_env() -> Result<Desks, String> {
+        let Ok(path) = std::env::var("QA_DESKS_PATH") else {
+            return Ok(Desks::default());
+        };
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let desks: Desks = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        desks.validate().map_err(|reason| format!("{}: {}", path, reason))?;
+        Ok(desks)
+    }
+
+    /// Errs with the reason the configuration cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut tokens: Vec<&str> = self.firm_tokens.iter().map(String::as_str).collect();
+        for (i, desk) in self.desks.iter().enumerate() {
+            let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-';
+            if desk.id.is_empty() || !desk.id.chars().all(valid) {
+                return Err(format!("Desk id '{}' must be lowercase letters, digits, '_' or '-'.", desk.id));
+            }
+            let earlier = &self.desks[..i];
+            if earlier.iter().any(|d| d.id == desk.id) {
+                return Err(format!("Desk '{}' is configured twice.", desk.id));
+            }
+            if let Some(account) = desk.accounts.iter().find(|a| earlier.iter().any(|d| d.accounts.contains(a))) {
+                return Err(format!("Account {} belongs to more than one desk.", account));
+            }
+            if let Some(strategy) = desk.strategies.iter().find(|s| earlier.iter().any(|d| d.strategies.contains(*s))) {
+                return Err(format!("Strategy '{}' belongs to more than one desk.", strategy));
+            }
+            tokens.extend(desk.tokens.iter().map(String::as_str));
+        }
+        if tokens.iter().any(|t| t.trim().is_empty()) {
+            return Err("Tokens must not be empty.".to_string());
+        }
+        if tokens.iter().enumerate().any(|(i, t)| tokens[..i].contains(t)) {
+            return Err("A token is given more than once.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether desks are configured, so callers need a token.
+    pub fn enforced(&self) -> bool {
+        !self.desks.is_empty()
+    }
+
+    pub fn desk_of_account(&self, account_id: u32) -> Option<&str> {
+        self.desks.iter().find(|d| d.accounts.contains(&account_id)).map(|d| d.id.as_str())
+    }
+
+    pub fn desk_of_strategy(&self, strategy_id: &str) -> Option<&str> {
+        self.desks.iter().find(|d| d.strategies.contains(strategy_id)).map(|d| d.id.as_str())
+    }
+
+    /// The token services present to each other's APIs.
+    pub fn firm_token(&self) -> Option<&str> {
+        self.firm_tokens.first().map(String::as_str)
+    }
+
+    /// The scope of an Authorization header.
+    pub fn scope(&self, authorization: Option<&str>) -> Result<Scope, Rejection> {
+        if !self.enforced() {
+            return Ok(Scope::Firm);
+        }
+        let unauthorized = || Rejection::new(RejectCode::SystemUnauthorized, "a valid bearer token is required");
+        let token = authorization.and_then(|h| h.strip_prefix("Bearer ")).ok_or_else(unauthorized)?.trim();
+        let matches = |tokens: &[String]| {
+            // Every token is compared, so response times do not reveal which matched.
+            tokens.iter().fold(false, |found, t| constant_time_eq(t.as_bytes(), token.as_bytes()) | found)
+        };
+        if matches(&self.firm_tokens) {
+            return Ok(Scope::Firm);
+        }
+        let desk = self.desks.iter().fold(None, |found, d| if matches(&d.tokens) { Some(d) } else { found });
+        desk.map(|d| Scope::Desk(d.id.clone())).ok_or_else(unauthorized)
+    }
+}
+
+/// Compares without returning early, so response times do not reveal how
+/// much of a token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// --- Service clients ---
+
+/// An HTTP client builder that presents the firm token of QA_DESKS_PATH, if
+/// desks are configured; a service refuses to start on an invalid file, so
+/// this reads it leniently.
+pub fn firm_client_builder() -> reqwest::ClientBuilder {
+    let desks = Desks::from_env().unwrap_or_default();
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = desks.firm_token() {
+        if let Ok(value) = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token)) {
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+    }
+    reqwest::Client::builder().default_headers(headers)
+}
+
+/// `firm_client_builder` with the default settings.
+pub fn firm_client() -> reqwest::Client {
+    firm_client_builder().build().unwrap()
+}
+
+// --- Scope ---
+
+/// What an API caller may see and change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Every desk, and firm-wide settings.
+    Firm,
+    /// One desk's accounts and strategies.
+    Desk(String),
+}
+
+impl Scope {
+    pub fn is_firm(&self) -> bool {
+        matches!(self, Scope::Firm)
+    }
+
+    /// The desk, for a desk caller.
+    pub fn desk(&self) -> Option<&str> {
+        match self {
+            Scope::Firm => None,
+            Scope::Desk(desk) => Some(desk),
+        }
+    }
+
+    pub fn sees_account(&self, desks: &Desks, account_id: u32) -> bool {
+        self.sees_desk(desks.desk_of_account(account_id))
+    }
+
+    pub fn sees_strategy(&self, desks: &Desks, strategy_id: &str) -> bool {
+        self.sees_desk(desks.desk_of_strategy(strategy_id))
+    }
+
+    /// Whether the caller sees what belongs to `desk` (None: to no desk,
+    /// which only the firm sees).
+    pub fn sees_desk(&self, desk: Option<&str>) -> bool {
+        match self {
+            Scope::Firm => true,
+            Scope::Desk(own) => desk == Some(own.as_str()),
+        }
+    }
+}
+
+// --- Filters ---
+
+/// A request refused by `scope`, `firm_only` or `desk_only`.
+#[derive(Debug)]
+struct Denied(Rejection);
+
+impl warp::reject::Reject for Denied {}
+
+/// Extracts the caller's scope from the Authorization header.
+pub fn scope(desks: Arc<Desks>) -> impl Filter<Extract = (Scope,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization").and_then(move |authorization: Option<String>| {
+        let scope = desks.scope(authorization.as_deref());
+        async move { scope.map_err(|rejection| warp::reject::custom(Denied(rejection))) }
+    })
+}
+
+/// Passes firm callers only.
+pub fn firm_only(desks: Arc<Desks>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    desk_only(desks, None)
+}
+
+/// Passes firm callers and those of `desk`, for a service that runs one
+/// desk's strategy; firm callers only for None.
+pub fn desk_only(desks: Arc<Desks>, desk: Option<&str>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    let desk = desk.map(str::to_string);
+    scope(desks)
+        .and_then(move |scope: Scope| {
+            let refused = match (&scope, &desk) {
+                (Scope::Desk(own), None) => Some(format!("desk '{}' cannot use a firm-wide endpoint", own)),
+                (Scope::Desk(own), Some(desk)) if own != desk => {
+                    Some(format!("desk '{}' cannot use an endpoint of desk '{}'", own, desk))
+                }
+                _ => None,
+            };
+            async move {
+                match refused {
+                    None => Ok(()),
+                    Some(reason) => {
+                        Err(warp::reject::custom(Denied(Rejection::new(RejectCode::SystemForbidden, reason))))
+                    }
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Answers the rejections of `scope`, `firm_only` and `desk_only` with the
+/// standard error body; passes others on.
+pub async fn recover(rejection: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    let Some(Denied(denied)) = rejection.find::<Denied>() else {
+        return Err(rejection);
+    };
+    let status = warp::http::StatusCode::from_u16(denied.code.http_status())
+        .unwrap_or(warp::http::StatusCode::FORBIDDEN);
+    Ok(warp::reply::with_status(warp::reply::json(&ErrorBody::from(denied.clone())), status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desks() -> Desks {
+        serde_json::from_str(
+            r#"{"desks": [
+                {"id": "crypto", "accounts": [101], "strategies": ["momentum"], "tokens": ["c-token"]},
+                {"id": "rates", "accounts": [205], "strategies": ["basis"], "tokens": ["r-token"]}
+            ], "firm_tokens": ["f-token"]}"#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn desks_see_only_their_own_and_firm_endpoints_refuse_them() {
+        let desks = desks();
+        assert!(desks.validate().is_ok());
+        let crypto = desks.scope(Some("Bearer c-token")).unwrap();
+        assert_eq!(crypto, Scope::Desk("crypto".to_string()));
+        assert!(crypto.sees_account(&desks, 101) && !crypto.sees_account(&desks, 205));
+        assert!(crypto.sees_strategy(&desks, "momentum") && !crypto.sees_strategy(&desks, "basis"));
+        assert!(!crypto.sees_account(&desks, 999));
+        assert!(desks.scope(Some("Bearer f-token")).unwrap().sees_account(&desks, 205));
+        assert_eq!(desks.scope(Some("Bearer nope")).unwrap_err().code, RejectCode::SystemUnauthorized);
+        assert_eq!(desks.scope(None).unwrap_err().code, RejectCode::SystemUnauthorized);
+        assert_eq!(Desks::default().scope(None), Ok(Scope::Firm));
+
+        let route = warp::path!("kill-switch").and(firm_only(Arc::new(desks))).map(|| "ok").recover(recover);
+        let call = |token: &str| warp::test::request().path("/kill-switch").header("authorization", token);
+        assert_eq!(call("Bearer f-token").reply(&route).await.status(), 200);
+        assert_eq!(call("Bearer r-token").reply(&route).await.status(), 403);
+        assert_eq!(call("Bearer nope").reply(&route).await.status(), 401);
+
+        let mut shared = self::desks();
+        shared.desks[1].accounts.insert(101);
+        assert!(shared.validate().is_err());
+    }
+}
@@ -0,0 +1,503 @@
+/*
+ * QuantumArb 2.0 - Shared: Service Message Types
+ *
+ * File: src/shared/types/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-types`) defines the JSON messages that
+ * pass between services over HTTP and the bus. The service that produces a
+ * message and every service that consumes it compile against the one
+ * definition here, instead of each keeping its own copy of the fields it
+ * happens to read:
+ *
+ *   Heartbeat               'heartbeats': strategies and the data quality
+ *                           monitor -> the risk gateway's watchdog
+ *   DataQualityAlert        'alerts.data_quality': data quality monitor ->
+ *                           strategy engine
+ *   AltDataAnomaly          'alerts.alt_data': data bus connector ->
+ *                           strategy engine
+ *   PortfolioSnapshot       GET /portfolio: portfolio manager -> risk gateway
+ *   CounterpartyExposure    GET /portfolio/counterparties: portfolio manager
+ *                           -> risk gateway
+ *   DailyPnl                GET /portfolio/pnl/daily: portfolio manager ->
+ *                           VaR calculator
+ *   AccountPnl              'portfolio.account_pnl' and GET
+ *                           /portfolio/accounts: portfolio manager -> risk
+ *                           gateway's daily loss limits
+ *   VaRResult, VaRHistory   GET /var and /var/history: VaR calculator ->
+ *                           risk gateway
+ *   CancelRequest,          POST /orders/cancel and /orders/flatten: risk
+ *   FlattenRequest          gateway's watchdog -> exchange gateway
+ *   VenueConnectivity       'venues.connectivity': exchange gateway's session
+ *                           supervisor -> risk gateway
+ *   StrategyInstruction     'strategy.instructions': portfolio manager's
+ *                           margin monitor, risk gateway's VaR escalation
+ *                           and portfolio optimizer -> strategy engine
+ *   LinkWeatherForecast     'alt_data.weather': data bus connector ->
+ *                           latency oracle
+ *   EventWindow             'calendar.event_windows': data bus connector ->
+ *                           strategy engine and risk gateway
+ *   AuditEvent              'audit.events': any service -> WORM logger; the
+ *                           risk gateway's carry a RiskDecisionAudit, read
+ *                           back by the risk-replay tool
+ *
+ * The hot-path messages (quotes, orders, execution reports) and their
+ * binary codec stay in `quantumarb-wire`.
+ */
+
+use chrono::{DateTime, Utc};
+use quantumarb_errors::RejectCode;
+use quantumarb_money::{Money, Price};
+use quantumarb_openapi::ApiSchema;
+use quantumarb_wire::{MultiLegOrder, OrderRequest, OrderSide};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// --- Heartbeats ---
+
+/// Published on 'heartbeats' by anything the watchdog keeps alive: a
+/// strategy's trading loop ("strategy:<name>") or the market data feed
+/// ("market_data"). Liveness is judged by arrival time, so `sent_utc` is
+/// informational only.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct Heartbeat {
+    pub source: String,
+    pub sequence: u64,
+    #[serde(default)]
+    pub sent_utc: String,
+}
+
+impl Heartbeat {
+    pub fn new(source: &str) -> Heartbeat {
+        Heartbeat { source: source.to_string(), sequence: 0, sent_utc: String::new() }
+    }
+
+    /// Advances to the next beat, stamped now.
+    pub fn beat(&mut self) -> &Heartbeat {
+        self.sequence += 1;
+        self.sent_utc = Utc::now().to_rfc3339();
+        self
+    }
+}
+
+// --- Venue Connectivity ---
+
+/// The state of the exchange gateway's connection to a venue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConnectivityState {
+    /// Connected on the primary session.
+    Up,
+    /// Connected on a backup session after the primary failed.
+    FailedOver,
+    /// Heartbeats stopped; logging on again.
+    Reconnecting,
+    /// Every session failed to log on. Retries continue.
+    Down,
+}
+
+impl ConnectivityState {
+    /// Whether orders can be routed to the venue.
+    pub fn is_routable(self) -> bool {
+        matches!(self, ConnectivityState::Up | ConnectivityState::FailedOver)
+    }
+}
+
+/// Published on 'venues.connectivity' whenever a venue's state or active
+/// session changes. `venue` is the venue name the risk checks use (VENUE_A,
+/// CME, ...).
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct VenueConnectivity {
+    pub venue: String,
+    pub state: ConnectivityState,
+    /// The session in use, or being logged on to.
+    pub session: String,
+    pub detail: String,
+    pub timestamp_utc: String,
+}
+
+// --- Alerts ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ApiSchema)]
+pub enum Issue {
+    CrossedQuote,
+    StaleTick,
+    OutlierPrice,
+    MissingHeartbeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum QualityStatus {
+    Suspect,
+    Cleared,
+}
+
+/// Published on 'alerts.data_quality'. For a Cleared alert, `issue` is the
+/// last issue the instrument was suspect for.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct DataQualityAlert {
+    pub instrument_id: u32,
+    pub status: QualityStatus,
+    pub issue: Issue,
+    pub detail: String,
+    pub timestamp_utc: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnomalyKind {
+    NewsBurst,
+    SentimentShift,
+}
+
+/// Published on 'alerts.alt_data'.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct AltDataAnomaly {
+    pub symbol: String,
+    pub kind: AnomalyKind,
+    /// Standard deviations from the baseline (signed for sentiment shifts).
+    pub score: f64,
+    pub detail: String,
+    /// How long consumers should treat the symbol as abnormal.
+    pub hold_secs: u64,
+    pub timestamp_utc: String,
+}
+
+// --- Portfolio ---
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct Position {
+    pub symbol: String,
+    pub quantity: i64,
+    /// Entry value of the open quantity per point.
+    #[serde(default)]
+    pub cost_basis: Money,
+    pub average_entry_price: Price,
+    pub current_market_price: Price,
+    pub unrealized_pnl: Money,
+    /// Currency value of a one-point move per unit, from reference data.
+    pub multiplier: Money,
+    /// Signed quantity held at each venue; sums to `quantity`.
+    #[serde(default)]
+    pub venue_quantities: HashMap<String, i64>,
+    /// Signed quantity facing each counterparty; sums to `quantity`.
+    #[serde(default)]
+    pub counterparty_quantities: HashMap<String, i64>,
+}
+
+/// The portfolio manager's GET /portfolio body.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ApiSchema)]
+pub struct PortfolioSnapshot {
+    pub positions: HashMap<String, Position>,
+    pub realized_pnl: Money,
+    pub total_unrealized_pnl: Money,
+    pub total_portfolio_value: Money,
+    pub total_fees: Money,
+    /// Realized + unrealized P&L, less fees.
+    pub net_pnl: Money,
+    pub timestamp_utc: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SettlementModel {
+    Dvp,
+    NonDvp { settlement_days: u32 },
+}
+
+/// One row of GET /portfolio/counterparties.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct CounterpartyExposure {
+    pub counterparty: String,
+    pub settlement_model: SettlementModel,
+    pub position_exposure: Money,
+    pub unsettled_exposure: Money,
+    pub total_exposure: Money,
+}
+
+/// Total P&L (realized + change in unrealized) over one closed trading day.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct DailyPnl {
+    pub period_start_utc: DateTime<Utc>,
+    pub period_end_utc: DateTime<Utc>,
+    pub pnl: Money,
+}
+
+/// One account's P&L in the current session (trading day), booked from the
+/// fills tagged with the account.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct AccountPnl {
+    pub account_id: u32,
+    /// When the session began; a later value means a new session.
+    pub session_start_utc: DateTime<Utc>,
+    pub realized_pnl: Money,
+    pub unrealized_pnl: Money,
+    /// Realized + unrealized P&L since the session began, before fees.
+    pub daily_pnl: Money,
+    /// Net quantity per symbol, positive long.
+    pub positions: HashMap<String, i64>,
+    /// Maintenance margin required for the positions at their marks.
+    #[serde(default)]
+    pub margin_requirement: Money,
+    pub timestamp_utc: DateTime<Utc>,
+}
+
+// --- VaR ---
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct VaRResult {
+    pub confidence_level: f64,
+    /// The calculated Value at Risk.
+    pub var_amount: f64,
+    /// Monte Carlo standard error of `var_amount`.
+    #[serde(default)]
+    pub standard_error: f64,
+    /// The mean loss at or beyond the VaR.
+    #[serde(default)]
+    pub expected_shortfall: f64,
+    /// Bootstrap confidence intervals for `var_amount` and `expected_shortfall`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var_interval: Option<ConfidenceInterval>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub es_interval: Option<ConfidenceInterval>,
+    /// False when more paths would likely still move the figure materially;
+    /// consumers may skip such an update. Results without the flag count as
+    /// converged.
+    #[serde(default = "converged_by_default")]
+    pub converged: bool,
+    pub portfolio_value: f64,
+    pub timestamp_utc: DateTime<Utc>,
+}
+
+fn converged_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ConfidenceInterval {
+    /// Coverage, e.g. 0.95.
+    pub level: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// One point of the downsampled GET /var/history series. Each point covers a
+/// time bucket and reports the worst (largest) VaR seen in it.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct VaRHistoryPoint {
+    pub timestamp_utc: DateTime<Utc>,
+    pub var_amount: f64,
+    pub portfolio_value: f64,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct VaRHistory {
+    pub window_secs: i64,
+    pub points: Vec<VaRHistoryPoint>,
+}
+
+// --- Emergency Orders ---
+
+/// Body of the exchange gateway's POST /orders/cancel. No symbols means
+/// every open order.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct CancelRequest {
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    pub reason: String,
+}
+
+/// An offsetting order for one position.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct FlattenOrder {
+    pub instrument_symbol: String,
+    pub side: OrderSide,
+    pub size: u32,
+    pub price: u64,
+}
+
+/// Body of the exchange gateway's POST /orders/flatten.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct FlattenRequest {
+    pub orders: Vec<FlattenOrder>,
+    pub reason: String,
+}
+
+// --- Strategy Instructions ---
+
+/// Instruction to the strategy engine, published on 'strategy.instructions'.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "type")]
+pub enum StrategyInstruction {
+    ReducePositions { reason: String, fraction: f64, orders: Vec<ReduceOrder> },
+    /// The portfolio optimizer's target weights and the orders that would
+    /// reach them. A suggestion: nothing is sent without a strategy acting
+    /// on it.
+    Rebalance { reason: String, targets: Vec<TargetWeight>, orders: Vec<ReduceOrder> },
+}
+
+/// A symbol's weight in the book, as a fraction of capital (negative when
+/// short), now and as suggested.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct TargetWeight {
+    pub symbol: String,
+    pub current_weight: f64,
+    pub target_weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct ReduceOrder {
+    pub symbol: String,
+    pub side: String, // "Buy" or "Sell"
+    pub quantity: u64,
+}
+
+/// Orders that cut every open position by `fraction` (rounded up to whole units).
+pub fn reduction_orders(positions: &HashMap<String, Position>, fraction: f64) -> Vec<ReduceOrder> {
+    positions
+        .values()
+        .filter(|p| p.quantity != 0)
+        .map(|p| ReduceOrder {
+            symbol: p.symbol.clone(),
+            side: if p.quantity > 0 { "Sell" } else { "Buy" }.to_string(),
+            quantity: ((p.quantity.unsigned_abs() as f64 * fraction).ceil() as u64).min(p.quantity.unsigned_abs()),
+        })
+        .filter(|o| o.quantity > 0)
+        .collect()
+}
+
+// --- Audit Trail ---
+
+/// Published on 'audit.events' and kept in the WORM log.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct AuditEvent {
+    pub event_id: Uuid,
+    pub service_name: String, // e.g., "strategy-engine", "risk-gateway"
+    pub event_type: String,   // e.g., "ORDER_SENT", "RISK_REJECTED"
+    pub timestamp: DateTime<Utc>,
+    /// The actual event payload, serialized as a JSON string.
+    pub payload: String,
+}
+
+/// What the risk gateway decided about a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RiskOutcome {
+    Approved,
+    Rejected,
+    /// Parked for a supervisor; a second decision for the order follows.
+    Held,
+}
+
+impl RiskOutcome {
+    /// The audit event type the risk gateway records the outcome under.
+    pub fn event_type(self) -> &'static str {
+        match self {
+            RiskOutcome::Approved => "RISK_APPROVED",
+            RiskOutcome::Rejected => "RISK_REJECTED",
+            RiskOutcome::Held => "RISK_HELD",
+        }
+    }
+}
+
+/// A request the risk gateway checks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+#[serde(tag = "type")]
+pub enum CheckedRequest {
+    Order(OrderRequest),
+    Package(MultiLegOrder),
+}
+
+impl CheckedRequest {
+    /// The order id, or the package id of a package.
+    pub fn id(&self) -> Uuid {
+        match self {
+            CheckedRequest::Order(order) => order.order_id,
+            CheckedRequest::Package(package) => package.package_id,
+        }
+    }
+}
+
+/// Payload of the risk gateway's RISK_* audit events: the request as it was
+/// checked and the verdict, so the request stream can be replayed against
+/// another build of the gateway and the verdicts compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct RiskDecisionAudit {
+    pub outcome: RiskOutcome,
+    /// Why the request was rejected or held.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<RejectCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The gateway instance that decided.
+    pub checked_by: String,
+    pub request: CheckedRequest,
+}
+
+// --- Weather ---
+
+/// Published on 'alt_data.weather': the hourly precipitation forecast at
+/// points along a microwave link's path.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct LinkWeatherForecast {
+    pub link: String,
+    pub waypoints: Vec<WaypointForecast>,
+    pub issued_utc: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct WaypointForecast {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Consecutive hours, earliest first.
+    pub hours: Vec<PrecipitationHour>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct PrecipitationHour {
+    pub starts_utc: DateTime<Utc>,
+    pub precipitation_mm_per_hr: f64,
+    /// Probability of precipitation, 0.0 to 1.0.
+    pub probability: f64,
+}
+
+// --- Economic Calendar ---
+
+/// Scheduled releases that move the markets traded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EconomicEventKind {
+    /// FOMC rate decision.
+    Fomc,
+    /// US nonfarm payrolls.
+    Nfp,
+    /// US consumer price index.
+    Cpi,
+}
+
+/// Published on 'calendar.event_windows': an upcoming release and the window
+/// around it in which its prices are treated as event risk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct EventWindow {
+    /// The calendar's id of the release; later publications replace earlier ones.
+    pub event_id: String,
+    pub kind: EconomicEventKind,
+    pub name: String,
+    pub scheduled_utc: DateTime<Utc>,
+    pub starts_utc: DateTime<Utc>,
+    pub ends_utc: DateTime<Utc>,
+    /// Symbols the release moves; every symbol when empty.
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+impl EventWindow {
+    /// Whether the window is open for `symbol` at `at`.
+    pub fn covers(&self, symbol: &str, at: DateTime<Utc>) -> bool {
+        let moves = self.symbols.is_empty() || self.symbols.iter().any(|s| s == symbol);
+        moves && self.starts_utc <= at && at < self.ends_utc
+    }
+}
@@ -0,0 +1,1270 @@
+/*
+ * QuantumArb 2.0 - Shared: Hot-Path Wire Format
+ *
+ * File: src/shared/wire/lib.rs
+ *
+ * Description:
+ * This library crate (`quantumarb-wire`) defines the canonical bus messages for
+ * the latency-critical paths (BboUpdate, OrderRequest, ExecutionReport,
+ * RiskVerdict, MultiLegOrder, ConsolidatedBbo) and a
+ * compact SBE-style binary encoding for them. JSON costs several microseconds
+ * per message on the market-data and order paths; the fixed-layout binary
+ * form is a handful of little-endian copies.
+ *
+ * Every binary message starts with an 8-byte header:
+ *   block_length: u16 | template_id: u16 | schema_id: u16 | version: u16
+ * followed by the fixed-length block and then any variable-length fields
+ * (u16 length prefix + UTF-8 bytes, or a u16 count + fixed-size entries for
+ * repeating groups).
+ *
+ * Schema evolution follows the SBE rules: new fields are only ever appended to
+ * the end of a block and the schema version is bumped. Decoders skip any block
+ * bytes they don't understand, so an old consumer can read a newer producer,
+ * and fill appended fields with defaults when reading an older producer.
+ *
+ * Order-path messages carry `HopStamps`: CLOCK_MONOTONIC nanosecond
+ * timestamps taken at each hop (market data receive, strategy decision, risk
+ * decision, gateway send, execution receive) for tick-to-trade measurement.
+ * Monotonic clocks are only comparable between processes on the same host.
+ *
+ * Orders and execution reports also carry the `TradingMode` (sandbox or
+ * live) of the service that produced them, so a sandbox order can never be
+ * mistaken for a live one downstream. Messages from producers that predate
+ * the field decode as sandbox.
+ *
+ * Orders and packages carry an `OrderPriority` class (hedge > arbitrage >
+ * opportunistic) that the risk gateway's request queue and the exchange
+ * gateway's send queue serve highest first, so a risk-reducing hedge is not
+ * stuck behind a burst of opportunistic orders. Producers that predate the
+ * field decode as opportunistic.
+ *
+ * A `MultiLegOrder` is a package of legs (instrument, side, ratio, price,
+ * venue) traded together; its legs are a repeating group in the var data.
+ *
+ * Orders and packages name the strategy that sent them (`strategy_id`, empty
+ * when none did), which the risk gateway charges against the strategy's
+ * capital allocation. It is var data, cut to MAX_STRATEGY_ID_LEN bytes so a
+ * full package still fits a risk ring slot.
+ *
+ * Execution reports follow the FIX fill fields: `filled_size`/`filled_price`
+ * are this report's fill (LastQty/LastPx), alongside the order's cumulative
+ * and remaining quantity (CumQty/LeavesQty), the liquidity indicator, the
+ * fee the venue charged and the venue's transact time. Each report carries
+ * the venue's ExecID, unique per order, so a report delivered twice can be
+ * recognised.
+ *
+ * A venue can bust or correct a fill after the fact (FIX ExecType H and G).
+ * A TradeBusted report names the busted fill's ExecID in `exec_ref_id` and
+ * repeats its quantity and price; a TradeCorrected report names the fill it
+ * corrects and carries the corrected quantity, price and fee. Both carry the
+ * order's cumulative and remaining quantity after the change.
+ *
+ * A `BboUpdate` names the venue it was quoted on (0 from feeds that predate
+ * the field). The market data consolidator merges the venues' updates for an
+ * instrument into a `ConsolidatedBbo`: the best bid and offer across venues,
+ * each with the venue quoting the most at that price.
+ *
+ * The messages also derive serde so JSON remains available for debugging
+ * endpoints and logs; `Encoding` selects which form goes on the bus.
+ */
+
+use quantumarb_errors::{RejectCode, Rejection};
+use quantumarb_money::Money;
+use quantumarb_openapi::ApiSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use uuid::Uuid;
+
+pub const SCHEMA_ID: u16 = 1;
+pub const SCHEMA_VERSION: u16 = 12;
+pub const HEADER_LENGTH: usize = 8;
+/// Longest strategy id an order carries on the wire, in bytes.
+pub const MAX_STRATEGY_ID_LEN: usize = 32;
+
+// --- Hop Timestamps ---
+
+/// The points on the order path where a message is timestamped, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Hop {
+    MarketDataReceive,
+    StrategyDecision,
+    RiskDecision,
+    GatewaySend,
+    ExecutionReceive,
+}
+
+impl Hop {
+    pub const ALL: [Hop; 5] =
+        [Hop::MarketDataReceive, Hop::StrategyDecision, Hop::RiskDecision, Hop::GatewaySend, Hop::ExecutionReceive];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Hop::MarketDataReceive => "market_data_receive",
+            Hop::StrategyDecision => "strategy_decision",
+            Hop::RiskDecision => "risk_decision",
+            Hop::GatewaySend => "gateway_send",
+            Hop::ExecutionReceive => "execution_receive",
+        }
+    }
+}
+
+/// Monotonic nanosecond timestamps for each hop a message has passed. Zero
+/// means the hop has not been stamped (yet).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub struct HopStamps {
+    pub market_data_receive_ns: u64,
+    pub strategy_decision_ns: u64,
+    pub risk_decision_ns: u64,
+    pub gateway_send_ns: u64,
+    pub execution_receive_ns: u64,
+}
+
+impl HopStamps {
+    /// Encoded size: five u64s.
+    pub const LENGTH: u16 = 40;
+
+    pub fn get(&self, hop: Hop) -> Option<u64> {
+        let ns = match hop {
+            Hop::MarketDataReceive => self.market_data_receive_ns,
+            Hop::StrategyDecision => self.strategy_decision_ns,
+            Hop::RiskDecision => self.risk_decision_ns,
+            Hop::GatewaySend => self.gateway_send_ns,
+            Hop::ExecutionReceive => self.execution_receive_ns,
+        };
+        (ns != 0).then_some(ns)
+    }
+
+    pub fn set(&mut self, hop: Hop, ns: u64) {
+        let slot = match hop {
+            Hop::MarketDataReceive => &mut self.market_data_receive_ns,
+            Hop::StrategyDecision => &mut self.strategy_decision_ns,
+            Hop::RiskDecision => &mut self.risk_decision_ns,
+            Hop::GatewaySend => &mut self.gateway_send_ns,
+            Hop::ExecutionReceive => &mut self.execution_receive_ns,
+        };
+        *slot = ns;
+    }
+
+    /// Stamps `hop` with the current monotonic time.
+    pub fn stamp(&mut self, hop: Hop) {
+        self.set(hop, monotonic_ns());
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        for hop in Hop::ALL {
+            out.extend_from_slice(&self.get(hop).unwrap_or(0).to_le_bytes());
+        }
+    }
+
+    /// Reads the stamps if the producer's schema version included them.
+    fn read_optional(block: &mut Reader<'_>) -> Result<HopStamps, DecodeError> {
+        let mut stamps = HopStamps::default();
+        if block.remaining() < Self::LENGTH as usize {
+            return Ok(stamps);
+        }
+        for hop in Hop::ALL {
+            stamps.set(hop, block.u64()?);
+        }
+        Ok(stamps)
+    }
+}
+
+/// CLOCK_MONOTONIC in nanoseconds. Unlike `std::time::Instant`, the value is
+/// meaningful to other processes on the same host.
+pub fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `ts` is a valid, writable timespec and CLOCK_MONOTONIC is always supported.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Wall-clock UTC time in nanoseconds since the Unix epoch, the scale of
+/// venue transact times.
+pub fn utc_now_ns() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+// --- Trading Mode ---
+
+/// Whether a service trades against real venues (Live) or the paper-trading
+/// simulator (Sandbox). Sandbox is the default everywhere: live trading must
+/// be asked for explicitly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TradingMode {
+    #[default]
+    Sandbox,
+    Live,
+}
+
+impl TradingMode {
+    /// Reads `QA_TRADING_MODE` ("sandbox" or "live"); anything but "live"
+    /// means sandbox.
+    pub fn from_env() -> TradingMode {
+        match std::env::var("QA_TRADING_MODE") {
+            Ok(mode) if mode.eq_ignore_ascii_case("live") => TradingMode::Live,
+            _ => TradingMode::Sandbox,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradingMode::Sandbox => "SANDBOX",
+            TradingMode::Live => "LIVE",
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            TradingMode::Sandbox => 1,
+            TradingMode::Live => 2,
+        }
+    }
+
+    /// Reads the mode if the producer's schema version included it.
+    fn read_optional(block: &mut Reader<'_>) -> Result<TradingMode, DecodeError> {
+        if block.remaining() < 1 {
+            return Ok(TradingMode::Sandbox);
+        }
+        match block.u8()? {
+            1 => Ok(TradingMode::Sandbox),
+            2 => Ok(TradingMode::Live),
+            _ => Err(DecodeError::InvalidField("mode")),
+        }
+    }
+}
+
+impl fmt::Display for TradingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// --- Order Priority ---
+
+/// How urgently an order must reach the venue, lowest first. Queues on the
+/// order path serve the highest class first and keep arrival order within it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderPriority {
+    /// Discretionary orders that can wait.
+    #[default]
+    Opportunistic,
+    /// Legs of an arbitrage, whose edge decays in microseconds.
+    Arbitrage,
+    /// Orders that reduce risk: hedges, flattens, VaR cuts.
+    Hedge,
+}
+
+impl OrderPriority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderPriority::Opportunistic => "OPPORTUNISTIC",
+            OrderPriority::Arbitrage => "ARBITRAGE",
+            OrderPriority::Hedge => "HEDGE",
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            OrderPriority::Opportunistic => 1,
+            OrderPriority::Arbitrage => 2,
+            OrderPriority::Hedge => 3,
+        }
+    }
+
+    /// Reads the priority if the producer's schema version included it.
+    fn read_optional(block: &mut Reader<'_>) -> Result<OrderPriority, DecodeError> {
+        if block.remaining() < 1 {
+            return Ok(OrderPriority::Opportunistic);
+        }
+        match block.u8()? {
+            1 => Ok(OrderPriority::Opportunistic),
+            2 => Ok(OrderPriority::Arbitrage),
+            3 => Ok(OrderPriority::Hedge),
+            _ => Err(DecodeError::InvalidField("priority")),
+        }
+    }
+}
+
+impl fmt::Display for OrderPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Orders waiting on the order path: the oldest of the highest priority
+/// class is served first.
+#[derive(Debug)]
+pub struct OrderQueue<T> {
+    /// One FIFO per class, lowest first.
+    classes: [VecDeque<T>; 3],
+}
+
+impl<T> Default for OrderQueue<T> {
+    fn default() -> Self {
+        OrderQueue { classes: [VecDeque::new(), VecDeque::new(), VecDeque::new()] }
+    }
+}
+
+impl<T> OrderQueue<T> {
+    pub fn push(&mut self, priority: OrderPriority, item: T) {
+        self.classes[priority.to_u8() as usize - 1].push_back(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.classes.iter_mut().rev().find_map(VecDeque::pop_front)
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.iter().all(VecDeque::is_empty)
+    }
+}
+
+// --- Canonical Messages ---
+
+/// Top-of-book update for one instrument on one venue. Prices are integers
+/// at the instrument's `price_decimals` scale in the reference data
+/// (hundredths for every built-in instrument), like every wire price.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct BboUpdate {
+    pub instrument_id: u32,
+    pub best_bid_price: u64,
+    pub best_bid_size: u32,
+    pub best_ask_price: u64,
+    pub best_ask_size: u32,
+    pub timestamp_ns: u64,
+    /// The venue quoting it (see `quantumarb-risk::concentration::venue_name`);
+    /// 0 when the feed does not say.
+    #[serde(default)]
+    pub venue_id: u32,
+}
+
+/// The best bid and offer for one instrument across venues. Each side has
+/// the best price any venue quotes, the size quoted at it across venues, and
+/// the venue quoting the most of it (the lowest venue id on a tie). A side
+/// no venue quotes has price, size and venue 0.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ConsolidatedBbo {
+    pub instrument_id: u32,
+    pub best_bid_price: u64,
+    pub best_bid_size: u32,
+    pub best_bid_venue_id: u32,
+    pub best_ask_price: u64,
+    pub best_ask_size: u32,
+    pub best_ask_venue_id: u32,
+    /// Venues with a live quote behind it.
+    pub venue_count: u8,
+    /// The newest venue quote's timestamp.
+    pub timestamp_ns: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+/// An order as submitted by a strategy to the risk gateway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct OrderRequest {
+    pub order_id: Uuid,
+    pub account_id: u32,
+    pub instrument_id: u32,
+    pub side: OrderSide,
+    pub price: u64,
+    pub size: u32,
+    #[serde(default)]
+    pub stamps: HopStamps,
+    /// Venue the order is routed to (0 = not yet routed).
+    #[serde(default)]
+    pub venue_id: u32,
+    #[serde(default)]
+    pub mode: TradingMode,
+    #[serde(default)]
+    pub priority: OrderPriority,
+    /// The strategy that sent the order; empty if none did.
+    #[serde(default)]
+    pub strategy_id: String,
+}
+
+/// One leg of a MultiLegOrder. The leg trades `ratio` times the package
+/// quantity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct OrderLeg {
+    pub instrument_id: u32,
+    pub side: OrderSide,
+    pub ratio: u32,
+    pub price: u64,
+    /// Venue the leg is routed to (0 = not yet routed).
+    #[serde(default)]
+    pub venue_id: u32,
+}
+
+impl OrderLeg {
+    /// Encoded size: instrument_id u32 | side u8 | ratio u32 | price u64 | venue_id u32.
+    pub const LENGTH: usize = 21;
+}
+
+/// An atomic package of legs (a spread or an arbitrage), submitted by a
+/// strategy to the risk gateway, which checks it as a whole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct MultiLegOrder {
+    pub package_id: Uuid,
+    pub account_id: u32,
+    /// Package quantity; each leg trades `ratio` times this.
+    pub quantity: u32,
+    pub legs: Vec<OrderLeg>,
+    #[serde(default)]
+    pub stamps: HopStamps,
+    #[serde(default)]
+    pub mode: TradingMode,
+    #[serde(default)]
+    pub priority: OrderPriority,
+    /// The strategy that sent the package; empty if none did.
+    #[serde(default)]
+    pub strategy_id: String,
+}
+
+impl MultiLegOrder {
+    /// Most legs in one package (keeps the message inside a risk ring slot).
+    pub const MAX_LEGS: usize = 8;
+
+    /// Leg `index` as a single order. Leg order ids are the package id plus
+    /// the leg number (1-based), so they can be traced back to the package.
+    pub fn leg_order(&self, index: usize) -> OrderRequest {
+        let leg = &self.legs[index];
+        OrderRequest {
+            order_id: Uuid::from_u128(self.package_id.as_u128().wrapping_add(index as u128 + 1)),
+            account_id: self.account_id,
+            instrument_id: leg.instrument_id,
+            side: leg.side,
+            price: leg.price,
+            size: leg.ratio.saturating_mul(self.quantity),
+            stamps: self.stamps,
+            venue_id: leg.venue_id,
+            mode: self.mode,
+            priority: self.priority,
+            strategy_id: self.strategy_id.clone(),
+        }
+    }
+}
+
+/// Whether our order added liquidity to the book or took it (FIX tag 851).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl Liquidity {
+    fn to_u8(liquidity: Option<Liquidity>) -> u8 {
+        match liquidity {
+            None => 0,
+            Some(Liquidity::Maker) => 1,
+            Some(Liquidity::Taker) => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<Option<Liquidity>, DecodeError> {
+        match value {
+            0 => Ok(None),
+            1 => Ok(Some(Liquidity::Maker)),
+            2 => Ok(Some(Liquidity::Taker)),
+            _ => Err(DecodeError::InvalidField("liquidity")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub enum OrderStatus {
+    New,
+    SentToExchange,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    RejectedByExchange,
+    /// The venue accepted an amendment of the order's quantity (since
+    /// version 7); `leaves_size` is the quantity now working.
+    Replaced,
+    /// The venue canceled the fill named in `exec_ref_id` (FIX ExecType H,
+    /// since version 12); `filled_size` and `filled_price` are the busted fill's.
+    TradeBusted,
+    /// The venue corrected the fill named in `exec_ref_id` (FIX ExecType G,
+    /// since version 12) to this report's quantity, price and fee.
+    TradeCorrected,
+}
+
+impl OrderStatus {
+    /// Whether the report busts or corrects an earlier fill.
+    pub fn is_trade_correction(self) -> bool {
+        matches!(self, OrderStatus::TradeBusted | OrderStatus::TradeCorrected)
+    }
+}
+
+/// A venue's answer to an order, published by the exchange gateway. Reports
+/// from producers before schema version 6 decode with zero cumulative and
+/// remaining quantity, no liquidity indicator, no fee and no transact time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct ExecutionReport {
+    pub exchange_order_id: String,
+    /// The venue's id for this report (FIX ExecID), unique per order; empty
+    /// from producers before version 8.
+    #[serde(default)]
+    pub exec_id: String,
+    pub internal_order_id: Uuid,
+    pub status: OrderStatus,
+    /// Quantity filled by this report (FIX LastQty); 0 if it is not a fill.
+    pub filled_size: u32,
+    /// Price of this report's fill (FIX LastPx).
+    pub filled_price: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject: Option<Rejection>,
+    #[serde(default)]
+    pub stamps: HopStamps,
+    #[serde(default)]
+    pub mode: TradingMode,
+    /// Quantity filled over the order's life, this fill included (FIX CumQty).
+    #[serde(default)]
+    pub cumulative_size: u32,
+    /// Quantity still working at the venue (FIX LeavesQty); 0 once the order
+    /// is filled, canceled or rejected.
+    #[serde(default)]
+    pub leaves_size: u32,
+    /// Whether this report's fill made or took liquidity (FIX LastLiquidityInd).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub liquidity: Option<Liquidity>,
+    /// Fees the venue charged on this fill, in the instrument's currency;
+    /// negative for a rebate.
+    #[serde(default)]
+    pub fee: Money,
+    /// When the venue executed the report (FIX TransactTime), UTC nanoseconds
+    /// since the epoch; 0 if the venue did not say.
+    #[serde(default)]
+    pub transact_time_ns: u64,
+    /// The ExecID of the fill a TradeBusted or TradeCorrected report refers to
+    /// (FIX ExecRefID); empty on other reports and from producers before
+    /// version 12.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub exec_ref_id: String,
+}
+
+impl ExecutionReport {
+    /// Encoded size of the fields added in schema version 6:
+    /// cumulative_size u32 | leaves_size u32 | liquidity u8 | fee i64 | transact_time_ns u64.
+    const FILL_DETAIL_LENGTH: u16 = 25;
+}
+
+/// The risk gateway's answer to an OrderRequest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct RiskVerdict {
+    pub order_id: Uuid,
+    pub approved: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject: Option<Rejection>,
+    #[serde(default)]
+    pub stamps: HopStamps,
+}
+
+// --- Encoding Selection ---
+
+/// Which representation a publisher puts on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Binary,
+    Json,
+}
+
+impl Encoding {
+    /// Reads `QA_BUS_ENCODING` ("binary" or "json"); binary is the default.
+    pub fn from_env() -> Encoding {
+        match std::env::var("QA_BUS_ENCODING").as_deref() {
+            Ok("json") => Encoding::Json,
+            _ => Encoding::Binary,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Binary => "application/x-quantumarb-sbe",
+            Encoding::Json => "application/json",
+        }
+    }
+
+    pub fn encode<M: WireMessage + Serialize>(self, msg: &M) -> Vec<u8> {
+        match self {
+            Encoding::Binary => encode(msg),
+            Encoding::Json => serde_json::to_vec(msg).expect("bus messages always serialize"),
+        }
+    }
+
+    /// Decodes a bus payload in either form. Consumers can't know which
+    /// encoding each producer was started with, so JSON is recognised by its
+    /// leading '{' (a binary header never starts with one: block lengths are small).
+    pub fn decode_any<M: WireMessage + DeserializeOwned>(bytes: &[u8]) -> Result<M, DecodeError> {
+        if bytes.first() == Some(&b'{') {
+            serde_json::from_slice(bytes).map_err(|e| DecodeError::InvalidJson(e.to_string()))
+        } else {
+            decode(bytes)
+        }
+    }
+}
+
+// --- Errors ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    Truncated { needed: usize, available: usize },
+    UnknownSchema(u16),
+    UnsupportedVersion(u16),
+    WrongTemplate { expected: u16, found: u16 },
+    BlockTooShort { minimum: u16, found: u16 },
+    InvalidField(&'static str),
+    InvalidJson(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated { needed, available } => {
+                write!(f, "message truncated: needed {} bytes, have {}", needed, available)
+            }
+            DecodeError::UnknownSchema(id) => write!(f, "unknown schema id {}", id),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported schema version {}", v),
+            DecodeError::WrongTemplate { expected, found } => {
+                write!(f, "expected template {} but found {}", expected, found)
+            }
+            DecodeError::BlockTooShort { minimum, found } => {
+                write!(f, "block length {} is shorter than the minimum {}", found, minimum)
+            }
+            DecodeError::InvalidField(name) => write!(f, "invalid value in field '{}'", name),
+            DecodeError::InvalidJson(reason) => write!(f, "invalid JSON message: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// --- Header ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub block_length: u16,
+    pub template_id: u16,
+    pub schema_id: u16,
+    pub version: u16,
+}
+
+/// Reads the header without decoding the body, so a consumer on a shared
+/// topic can dispatch on `template_id`.
+pub fn peek_header(bytes: &[u8]) -> Result<MessageHeader, DecodeError> {
+    let mut reader = Reader::new(bytes);
+    let header = MessageHeader {
+        block_length: reader.u16()?,
+        template_id: reader.u16()?,
+        schema_id: reader.u16()?,
+        version: reader.u16()?,
+    };
+    if header.schema_id != SCHEMA_ID {
+        return Err(DecodeError::UnknownSchema(header.schema_id));
+    }
+    if header.version == 0 {
+        return Err(DecodeError::UnsupportedVersion(header.version));
+    }
+    Ok(header)
+}
+
+// --- Codec ---
+
+/// A message with a fixed binary layout.
+pub trait WireMessage: Sized {
+    const TEMPLATE_ID: u16;
+    /// Length of the fixed block in the current schema version.
+    const BLOCK_LENGTH: u16;
+    /// Shortest block an older producer may send; fields beyond it are optional.
+    const MIN_BLOCK_LENGTH: u16 = Self::BLOCK_LENGTH;
+
+    fn write_block(&self, out: &mut Vec<u8>);
+    fn write_var_data(&self, _out: &mut Vec<u8>) {}
+    fn read(block: &mut Reader<'_>, var_data: &mut Reader<'_>) -> Result<Self, DecodeError>;
+}
+
+pub fn encode<M: WireMessage>(msg: &M) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LENGTH + M::BLOCK_LENGTH as usize);
+    out.extend_from_slice(&M::BLOCK_LENGTH.to_le_bytes());
+    out.extend_from_slice(&M::TEMPLATE_ID.to_le_bytes());
+    out.extend_from_slice(&SCHEMA_ID.to_le_bytes());
+    out.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    msg.write_block(&mut out);
+    debug_assert_eq!(out.len(), HEADER_LENGTH + M::BLOCK_LENGTH as usize);
+    msg.write_var_data(&mut out);
+    out
+}
+
+pub fn decode<M: WireMessage>(bytes: &[u8]) -> Result<M, DecodeError> {
+    let header = peek_header(bytes)?;
+    if header.template_id != M::TEMPLATE_ID {
+        return Err(DecodeError::WrongTemplate { expected: M::TEMPLATE_ID, found: header.template_id });
+    }
+    if header.block_length < M::MIN_BLOCK_LENGTH {
+        return Err(DecodeError::BlockTooShort { minimum: M::MIN_BLOCK_LENGTH, found: header.block_length });
+    }
+    let block_end = HEADER_LENGTH + header.block_length as usize;
+    if bytes.len() < block_end {
+        return Err(DecodeError::Truncated { needed: block_end, available: bytes.len() });
+    }
+    // Fields appended by newer producers sit between BLOCK_LENGTH and
+    // block_length and are skipped by only handing over the known prefix.
+    let known = header.block_length.min(M::BLOCK_LENGTH) as usize;
+    let mut block = Reader::new(&bytes[HEADER_LENGTH..HEADER_LENGTH + known]);
+    let mut var_data = Reader::new(&bytes[block_end..]);
+    M::read(&mut block, &mut var_data)
+}
+
+/// Little-endian cursor over a byte slice.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.pos + n > self.bytes.len() {
+            return Err(DecodeError::Truncated { needed: self.pos + n, available: self.bytes.len() });
+        }
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, DecodeError> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn uuid(&mut self) -> Result<Uuid, DecodeError> {
+        Ok(Uuid::from_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    pub fn var_string(&mut self, field: &'static str) -> Result<String, DecodeError> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidField(field))
+    }
+}
+
+/// Writes a length-prefixed string, cut to u16::MAX bytes on a character
+/// boundary.
+fn write_var_string(out: &mut Vec<u8>, value: &str) {
+    let mut len = value.len().min(u16::MAX as usize);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    out.extend_from_slice(&(len as u16).to_le_bytes());
+    out.extend_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Writes a strategy id, cut to MAX_STRATEGY_ID_LEN bytes on a character
+/// boundary.
+fn write_strategy_id(out: &mut Vec<u8>, strategy_id: &str) {
+    let mut len = strategy_id.len().min(MAX_STRATEGY_ID_LEN);
+    while !strategy_id.is_char_boundary(len) {
+        len -= 1;
+    }
+    write_var_string(out, &strategy_id[..len]);
+}
+
+fn side_to_u8(side: OrderSide) -> u8 {
+    match side {
+        OrderSide::Buy => 1,
+        OrderSide::Sell => 2,
+    }
+}
+
+fn side_from_u8(value: u8) -> Result<OrderSide, DecodeError> {
+    match value {
+        1 => Ok(OrderSide::Buy),
+        2 => Ok(OrderSide::Sell),
+        _ => Err(DecodeError::InvalidField("side")),
+    }
+}
+
+fn status_to_u8(status: OrderStatus) -> u8 {
+    match status {
+        OrderStatus::New => 0,
+        OrderStatus::SentToExchange => 1,
+        OrderStatus::PartiallyFilled => 2,
+        OrderStatus::Filled => 3,
+        OrderStatus::Canceled => 4,
+        OrderStatus::RejectedByExchange => 5,
+        OrderStatus::Replaced => 6,
+        OrderStatus::TradeBusted => 7,
+        OrderStatus::TradeCorrected => 8,
+    }
+}
+
+fn status_from_u8(value: u8) -> Result<OrderStatus, DecodeError> {
+    match value {
+        0 => Ok(OrderStatus::New),
+        1 => Ok(OrderStatus::SentToExchange),
+        2 => Ok(OrderStatus::PartiallyFilled),
+        3 => Ok(OrderStatus::Filled),
+        4 => Ok(OrderStatus::Canceled),
+        5 => Ok(OrderStatus::RejectedByExchange),
+        6 => Ok(OrderStatus::Replaced),
+        7 => Ok(OrderStatus::TradeBusted),
+        8 => Ok(OrderStatus::TradeCorrected),
+        _ => Err(DecodeError::InvalidField("status")),
+    }
+}
+
+// --- Message Layouts ---
+
+/// Template 1. Block: instrument_id u32 | bid_px u64 | bid_sz u32 | ask_px u64 | ask_sz u32 | ts u64
+/// | venue_id u32 (since version 11)
+impl WireMessage for BboUpdate {
+    const TEMPLATE_ID: u16 = 1;
+    const BLOCK_LENGTH: u16 = 36 + 4;
+    const MIN_BLOCK_LENGTH: u16 = 36;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.instrument_id.to_le_bytes());
+        out.extend_from_slice(&self.best_bid_price.to_le_bytes());
+        out.extend_from_slice(&self.best_bid_size.to_le_bytes());
+        out.extend_from_slice(&self.best_ask_price.to_le_bytes());
+        out.extend_from_slice(&self.best_ask_size.to_le_bytes());
+        out.extend_from_slice(&self.timestamp_ns.to_le_bytes());
+        out.extend_from_slice(&self.venue_id.to_le_bytes());
+    }
+
+    fn read(block: &mut Reader<'_>, _var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(BboUpdate {
+            instrument_id: block.u32()?,
+            best_bid_price: block.u64()?,
+            best_bid_size: block.u32()?,
+            best_ask_price: block.u64()?,
+            best_ask_size: block.u32()?,
+            timestamp_ns: block.u64()?,
+            venue_id: if block.remaining() >= 4 { block.u32()? } else { 0 },
+        })
+    }
+}
+
+/// Template 2. Block: order_id [16] | account_id u32 | instrument_id u32 | side u8 | price u64 | size u32
+/// | stamps [5 x u64] (since version 2) | venue_id u32 (since version 3) | mode u8 (since version 4)
+/// | priority u8 (since version 9)
+/// Var data: strategy_id (since version 10).
+impl WireMessage for OrderRequest {
+    const TEMPLATE_ID: u16 = 2;
+    const BLOCK_LENGTH: u16 = 37 + HopStamps::LENGTH + 4 + 1 + 1;
+    const MIN_BLOCK_LENGTH: u16 = 37;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.order_id.as_bytes());
+        out.extend_from_slice(&self.account_id.to_le_bytes());
+        out.extend_from_slice(&self.instrument_id.to_le_bytes());
+        out.push(side_to_u8(self.side));
+        out.extend_from_slice(&self.price.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        self.stamps.write(out);
+        out.extend_from_slice(&self.venue_id.to_le_bytes());
+        out.push(self.mode.to_u8());
+        out.push(self.priority.to_u8());
+    }
+
+    fn write_var_data(&self, out: &mut Vec<u8>) {
+        write_strategy_id(out, &self.strategy_id);
+    }
+
+    fn read(block: &mut Reader<'_>, var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(OrderRequest {
+            order_id: block.uuid()?,
+            account_id: block.u32()?,
+            instrument_id: block.u32()?,
+            side: side_from_u8(block.u8()?)?,
+            price: block.u64()?,
+            size: block.u32()?,
+            stamps: HopStamps::read_optional(block)?,
+            venue_id: if block.remaining() >= 4 { block.u32()? } else { 0 },
+            mode: TradingMode::read_optional(block)?,
+            priority: OrderPriority::read_optional(block)?,
+            strategy_id: if var_data.remaining() > 0 { var_data.var_string("strategy_id")? } else { String::new() },
+        })
+    }
+}
+
+/// Template 3. Block: internal_order_id [16] | status u8 | filled_size u32 | filled_price u64 | reject_code u16
+/// | stamps [5 x u64] (since version 2) | mode u8 (since version 4) | cumulative_size u32 | leaves_size u32
+/// | liquidity u8 | fee i64 (micros) | transact_time_ns u64 (since version 6)
+/// Var data: exchange_order_id, reject_message, exec_id (since version 8), exec_ref_id (since version 12).
+impl WireMessage for ExecutionReport {
+    const TEMPLATE_ID: u16 = 3;
+    const BLOCK_LENGTH: u16 = 31 + HopStamps::LENGTH + 1 + ExecutionReport::FILL_DETAIL_LENGTH;
+    const MIN_BLOCK_LENGTH: u16 = 31;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.internal_order_id.as_bytes());
+        out.push(status_to_u8(self.status));
+        out.extend_from_slice(&self.filled_size.to_le_bytes());
+        out.extend_from_slice(&self.filled_price.to_le_bytes());
+        let reject_code = self.reject.as_ref().map_or(0, |r| r.code.wire_id());
+        out.extend_from_slice(&reject_code.to_le_bytes());
+        self.stamps.write(out);
+        out.push(self.mode.to_u8());
+        out.extend_from_slice(&self.cumulative_size.to_le_bytes());
+        out.extend_from_slice(&self.leaves_size.to_le_bytes());
+        out.push(Liquidity::to_u8(self.liquidity));
+        out.extend_from_slice(&self.fee.micros().to_le_bytes());
+        out.extend_from_slice(&self.transact_time_ns.to_le_bytes());
+    }
+
+    fn write_var_data(&self, out: &mut Vec<u8>) {
+        write_var_string(out, &self.exchange_order_id);
+        write_var_string(out, self.reject.as_ref().map_or("", |r| r.message.as_str()));
+        write_var_string(out, &self.exec_id);
+        write_var_string(out, &self.exec_ref_id);
+    }
+
+    fn read(block: &mut Reader<'_>, var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let internal_order_id = block.uuid()?;
+        let status = status_from_u8(block.u8()?)?;
+        let filled_size = block.u32()?;
+        let filled_price = block.u64()?;
+        let reject_code = block.u16()?;
+        let stamps = HopStamps::read_optional(block)?;
+        let mode = TradingMode::read_optional(block)?;
+        let (mut cumulative_size, mut leaves_size, mut liquidity, mut fee, mut transact_time_ns) =
+            (0, 0, None, Money::ZERO, 0);
+        if block.remaining() >= ExecutionReport::FILL_DETAIL_LENGTH as usize {
+            cumulative_size = block.u32()?;
+            leaves_size = block.u32()?;
+            liquidity = Liquidity::from_u8(block.u8()?)?;
+            fee = Money::from_micros(block.i64()?);
+            transact_time_ns = block.u64()?;
+        }
+        let exchange_order_id = var_data.var_string("exchange_order_id")?;
+        let reject_message = var_data.var_string("reject_message")?;
+        let exec_id = if var_data.remaining() > 0 { var_data.var_string("exec_id")? } else { String::new() };
+        let exec_ref_id = if var_data.remaining() > 0 { var_data.var_string("exec_ref_id")? } else { String::new() };
+        let reject = match reject_code {
+            0 => None,
+            id => {
+                let code = RejectCode::from_wire_id(id).ok_or(DecodeError::InvalidField("reject_code"))?;
+                Some(Rejection::new(code, reject_message))
+            }
+        };
+        Ok(ExecutionReport {
+            exchange_order_id,
+            exec_id,
+            internal_order_id,
+            status,
+            filled_size,
+            filled_price,
+            reject,
+            stamps,
+            mode,
+            cumulative_size,
+            leaves_size,
+            liquidity,
+            fee,
+            transact_time_ns,
+            exec_ref_id,
+        })
+    }
+}
+
+/// Template 4. Block: order_id [16] | approved u8 | reject_code u16 | stamps [5 x u64] (since version 2)
+/// Var data: reject_message.
+impl WireMessage for RiskVerdict {
+    const TEMPLATE_ID: u16 = 4;
+    const BLOCK_LENGTH: u16 = 19 + HopStamps::LENGTH;
+    const MIN_BLOCK_LENGTH: u16 = 19;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.order_id.as_bytes());
+        out.push(self.approved as u8);
+        let reject_code = self.reject.as_ref().map_or(0, |r| r.code.wire_id());
+        out.extend_from_slice(&reject_code.to_le_bytes());
+        self.stamps.write(out);
+    }
+
+    fn write_var_data(&self, out: &mut Vec<u8>) {
+        write_var_string(out, self.reject.as_ref().map_or("", |r| r.message.as_str()));
+    }
+
+    fn read(block: &mut Reader<'_>, var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let order_id = block.uuid()?;
+        let approved = block.u8()? != 0;
+        let reject_code = block.u16()?;
+        let stamps = HopStamps::read_optional(block)?;
+        let reject_message = var_data.var_string("reject_message")?;
+        let reject = match reject_code {
+            0 => None,
+            id => {
+                let code = RejectCode::from_wire_id(id).ok_or(DecodeError::InvalidField("reject_code"))?;
+                Some(Rejection::new(code, reject_message))
+            }
+        };
+        Ok(RiskVerdict { order_id, approved, reject, stamps })
+    }
+}
+
+/// Template 5 (since version 5). Block: package_id [16] | account_id u32 | quantity u32 | stamps [5 x u64]
+/// | mode u8 | priority u8 (since version 9)
+/// Var data: legs (u16 count + OrderLeg::LENGTH bytes each), strategy_id (since version 10).
+impl WireMessage for MultiLegOrder {
+    const TEMPLATE_ID: u16 = 5;
+    const BLOCK_LENGTH: u16 = 24 + HopStamps::LENGTH + 1 + 1;
+    const MIN_BLOCK_LENGTH: u16 = 24 + HopStamps::LENGTH + 1;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.package_id.as_bytes());
+        out.extend_from_slice(&self.account_id.to_le_bytes());
+        out.extend_from_slice(&self.quantity.to_le_bytes());
+        self.stamps.write(out);
+        out.push(self.mode.to_u8());
+        out.push(self.priority.to_u8());
+    }
+
+    fn write_var_data(&self, out: &mut Vec<u8>) {
+        let count = self.legs.len().min(u16::MAX as usize);
+        out.extend_from_slice(&(count as u16).to_le_bytes());
+        for leg in &self.legs[..count] {
+            out.extend_from_slice(&leg.instrument_id.to_le_bytes());
+            out.push(side_to_u8(leg.side));
+            out.extend_from_slice(&leg.ratio.to_le_bytes());
+            out.extend_from_slice(&leg.price.to_le_bytes());
+            out.extend_from_slice(&leg.venue_id.to_le_bytes());
+        }
+        write_strategy_id(out, &self.strategy_id);
+    }
+
+    fn read(block: &mut Reader<'_>, var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let package_id = block.uuid()?;
+        let account_id = block.u32()?;
+        let quantity = block.u32()?;
+        let stamps = HopStamps::read_optional(block)?;
+        let mode = TradingMode::read_optional(block)?;
+        let priority = OrderPriority::read_optional(block)?;
+        let count = var_data.u16()? as usize;
+        let mut legs = Vec::with_capacity(count.min(MultiLegOrder::MAX_LEGS));
+        for _ in 0..count {
+            legs.push(OrderLeg {
+                instrument_id: var_data.u32()?,
+                side: side_from_u8(var_data.u8()?)?,
+                ratio: var_data.u32()?,
+                price: var_data.u64()?,
+                venue_id: var_data.u32()?,
+            });
+        }
+        let strategy_id = if var_data.remaining() > 0 { var_data.var_string("strategy_id")? } else { String::new() };
+        Ok(MultiLegOrder { package_id, account_id, quantity, legs, stamps, mode, priority, strategy_id })
+    }
+}
+
+/// Template 6 (since version 11). Block: instrument_id u32 | bid_px u64 | bid_sz u32 | bid_venue u32
+/// | ask_px u64 | ask_sz u32 | ask_venue u32 | venue_count u8 | ts u64
+impl WireMessage for ConsolidatedBbo {
+    const TEMPLATE_ID: u16 = 6;
+    const BLOCK_LENGTH: u16 = 45;
+
+    fn write_block(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.instrument_id.to_le_bytes());
+        out.extend_from_slice(&self.best_bid_price.to_le_bytes());
+        out.extend_from_slice(&self.best_bid_size.to_le_bytes());
+        out.extend_from_slice(&self.best_bid_venue_id.to_le_bytes());
+        out.extend_from_slice(&self.best_ask_price.to_le_bytes());
+        out.extend_from_slice(&self.best_ask_size.to_le_bytes());
+        out.extend_from_slice(&self.best_ask_venue_id.to_le_bytes());
+        out.push(self.venue_count);
+        out.extend_from_slice(&self.timestamp_ns.to_le_bytes());
+    }
+
+    fn read(block: &mut Reader<'_>, _var_data: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(ConsolidatedBbo {
+            instrument_id: block.u32()?,
+            best_bid_price: block.u64()?,
+            best_bid_size: block.u32()?,
+            best_bid_venue_id: block.u32()?,
+            best_ask_price: block.u64()?,
+            best_ask_size: block.u32()?,
+            best_ask_venue_id: block.u32()?,
+            venue_count: block.u8()?,
+            timestamp_ns: block.u64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(priority: OrderPriority) -> OrderRequest {
+        OrderRequest {
+            order_id: Uuid::from_u128(7),
+            account_id: 101,
+            instrument_id: 1,
+            side: OrderSide::Buy,
+            price: 60_000_000_000,
+            size: 3,
+            stamps: HopStamps::default(),
+            venue_id: 2,
+            mode: TradingMode::Live,
+            priority,
+            strategy_id: "stat_arb".to_string(),
+        }
+    }
+
+    fn stamps() -> HopStamps {
+        HopStamps {
+            market_data_receive_ns: 1,
+            strategy_decision_ns: 2,
+            risk_decision_ns: 3,
+            gateway_send_ns: 4,
+            execution_receive_ns: 5,
+        }
+    }
+
+    fn verdict(reject: Option<Rejection>) -> RiskVerdict {
+        RiskVerdict { order_id: Uuid::from_u128(7), approved: reject.is_none(), reject, stamps: stamps() }
+    }
+
+    fn round_trip<M: WireMessage + PartialEq + fmt::Debug>(message: &M) {
+        assert_eq!(&decode::<M>(&encode(message)).unwrap(), message);
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let bbo = BboUpdate {
+            instrument_id: 1,
+            best_bid_price: 60_150_00,
+            best_bid_size: 3,
+            best_ask_price: 60_151_00,
+            best_ask_size: 2,
+            timestamp_ns: 9,
+            venue_id: 2,
+        };
+        round_trip(&bbo);
+        round_trip(&OrderRequest { stamps: stamps(), ..order(OrderPriority::Arbitrage) });
+        round_trip(&ExecutionReport {
+            exchange_order_id: "EXCH-1".to_string(),
+            exec_id: "E-2".to_string(),
+            internal_order_id: Uuid::from_u128(7),
+            status: OrderStatus::PartiallyFilled,
+            filled_size: 1,
+            filled_price: 60_150_00,
+            reject: None,
+            stamps: stamps(),
+            mode: TradingMode::Live,
+            cumulative_size: 1,
+            leaves_size: 2,
+            liquidity: Some(Liquidity::Maker),
+            fee: Money::from_micros(-1_250),
+            transact_time_ns: 11,
+            exec_ref_id: "E-1".to_string(),
+        });
+        let reject = Rejection::new(RejectCode::RiskOrderSizeLimit, "Size 101 over 100");
+        round_trip(&verdict(Some(reject)));
+        let leg = |instrument_id, side| OrderLeg { instrument_id, side, ratio: 1, price: 60_150_00, venue_id: 1 };
+        round_trip(&MultiLegOrder {
+            package_id: Uuid::from_u128(8),
+            account_id: 101,
+            quantity: 2,
+            legs: vec![leg(1, OrderSide::Buy), leg(2, OrderSide::Sell)],
+            stamps: stamps(),
+            mode: TradingMode::Sandbox,
+            priority: OrderPriority::Hedge,
+            strategy_id: "stat_arb".to_string(),
+        });
+        round_trip(&ConsolidatedBbo {
+            instrument_id: 1,
+            best_bid_price: 60_150_00,
+            best_bid_size: 5,
+            best_bid_venue_id: 1,
+            best_ask_price: 60_151_00,
+            best_ask_size: 4,
+            best_ask_venue_id: 2,
+            venue_count: 2,
+            timestamp_ns: 9,
+        });
+    }
+
+    #[test]
+    fn long_strings_are_cut_on_a_char_boundary() {
+        // Two bytes a character: u16::MAX falls inside one.
+        let message = "é".repeat(u16::MAX as usize / 2 + 1);
+        let reject = Rejection::new(RejectCode::VenueOther, message.clone());
+        let decoded = decode::<RiskVerdict>(&encode(&verdict(Some(reject)))).unwrap().reject.unwrap().message;
+        assert_eq!(decoded.len(), u16::MAX as usize - 1);
+        assert!(message.starts_with(&decoded));
+
+        let long_id = format!("{}é", "s".repeat(MAX_STRATEGY_ID_LEN - 1));
+        let long = OrderRequest { strategy_id: long_id, ..order(OrderPriority::Hedge) };
+        assert_eq!(decode::<OrderRequest>(&encode(&long)).unwrap().strategy_id, "s".repeat(MAX_STRATEGY_ID_LEN - 1));
+    }
+
+    #[test]
+    fn order_queue_serves_the_highest_class_first_and_fifo_within_it() {
+        let mut queue = OrderQueue::default();
+        queue.push(OrderPriority::Opportunistic, "opportunistic 1");
+        queue.push(OrderPriority::Arbitrage, "arbitrage 1");
+        queue.push(OrderPriority::Hedge, "hedge 1");
+        queue.push(OrderPriority::Opportunistic, "opportunistic 2");
+        queue.push(OrderPriority::Hedge, "hedge 2");
+        queue.push(OrderPriority::Arbitrage, "arbitrage 2");
+        assert_eq!(queue.len(), 6);
+        let served: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(
+            served,
+            ["hedge 1", "hedge 2", "arbitrage 1", "arbitrage 2", "opportunistic 1", "opportunistic 2"]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn a_version_8_order_decodes_as_opportunistic() {
+        let current = encode(&order(OrderPriority::Hedge));
+        assert_eq!(decode::<OrderRequest>(&current).unwrap(), order(OrderPriority::Hedge));
+
+        // Version 8 had neither the priority byte nor the strategy id.
+        let block_length = OrderRequest::BLOCK_LENGTH - 1;
+        let mut old = current[..HEADER_LENGTH + block_length as usize].to_vec();
+        old[0..2].copy_from_slice(&block_length.to_le_bytes());
+        old[6..8].copy_from_slice(&8u16.to_le_bytes());
+        let decoded = decode::<OrderRequest>(&old).unwrap();
+        assert_eq!((decoded.priority, decoded.strategy_id.as_str()), (OrderPriority::Opportunistic, ""));
+        assert_eq!((decoded.venue_id, decoded.mode), (2, TradingMode::Live));
+    }
+}
@@ -0,0 +1,224 @@
+/*
+ * QuantumArb 2.0 - Tools: Python Bindings
+ *
+ * File: src/tools/pyquantumarb/lib.rs
+ *
+ * Description:
+ * `pyquantumarb` is a Python extension module over the backtest harness
+ * (`quantumarb-backtest`), so quants can prototype a strategy in Python
+ * against the platform's data, fill rules and fee schedules. The wheel is
+ * built with maturin from this directory (see pyproject.toml).
+ *
+ *   import pandas as pd
+ *   import pyquantumarb
+ *
+ *   bt = pyquantumarb.Backtest(initial_capital=100_000.0)
+ *   bt.load_csv("ticks.csv")            # or bt.load_archive(start, end, [1])
+ *
+ *   @bt.strategy
+ *   def on_market(view):
+ *       book = view["venues"][0]
+ *       return [{"venue_id": book["venue_id"], "side": "buy",
+ *                "price": book["best_ask"], "size": 1}]
+ *
+ *   report = bt.run()
+ *   equity = pd.DataFrame(report["equity"])
+ *   trades = pd.DataFrame(report["trades"])
+ *
+ * The callback gets each `MarketView` as a dict and returns a list of
+ * `OrderIntent` dicts, or None: the same JSON a WebAssembly strategy plugin
+ * exchanges with the engine, so a strategy prototyped here ports directly.
+ * An exception raised by the callback stops the run and is raised by
+ * `run()`.
+ *
+ * `run()` returns the `Report` as plain dicts and lists: "summary" (return,
+ * Sharpe, drawdown, fills, fees), "equity" and "trades" (columns of equal
+ * length) and "positions".
+ *
+ * Instruments come from the reference data, QA_REFDATA_PATH or the built-in
+ * set, unless `refdata_path` is given; the archive is read through the
+ * QA_ARCHIVE_* variables.
+ */
+
+use chrono::{DateTime, Utc};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use quantumarb_archive::ArchiveQuery;
+use quantumarb_backtest::{Backtest as Harness, BacktestConfig, BacktestError};
+use quantumarb_strategy_sdk::OrderIntent;
+use quantumarb_wire::BboUpdate;
+
+/// A strategy run over recorded top of book history.
+#[pyclass(module = "pyquantumarb")]
+struct Backtest {
+    harness: Harness,
+    strategy: Option<Py<PyAny>>,
+}
+
+#[pymethods]
+impl Backtest {
+    #[new]
+    #[pyo3(signature = (initial_capital=100_000.0, equity_interval_ms=60_000, refdata_path=None))]
+    fn new(initial_capital: f64, equity_interval_ms: u64, refdata_path: Option<&str>) -> PyResult<Self> {
+        let instruments = match refdata_path {
+            Some(path) => quantumarb_refdata::load_from_file(path).map_err(|e| PyIOError::new_err(e.to_string()))?,
+            None => quantumarb_refdata::load_from_env(),
+        };
+        let config = BacktestConfig { initial_capital, equity_interval_ms, instruments };
+        Ok(Backtest { harness: Harness::new(config), strategy: None })
+    }
+
+    /// Ticks loaded so far.
+    #[getter]
+    fn ticks(&self) -> usize {
+        self.harness.ticks()
+    }
+
+    /// Loads the ticks of a CSV file; returns how many.
+    fn load_csv(&mut self, path: &str) -> PyResult<usize> {
+        self.harness.load_csv(path.as_ref()).map_err(to_py)
+    }
+
+    /// Loads the archived top of book updates between two RFC 3339 times,
+    /// for the listed instruments (all if none); returns how many.
+    #[pyo3(signature = (start_utc, end_utc, instruments=None))]
+    fn load_archive(&mut self, start_utc: &str, end_utc: &str, instruments: Option<Vec<u32>>) -> PyResult<usize> {
+        let query = ArchiveQuery {
+            start: parse_time(start_utc)?,
+            end: parse_time(end_utc)?,
+            instrument_ids: instruments.unwrap_or_default(),
+            topics: Vec::new(),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        runtime.block_on(self.harness.load_archive(query)).map_err(to_py)
+    }
+
+    /// Adds one tick, prices at the instrument's wire price scale.
+    #[allow(clippy::too_many_arguments)]
+    fn add_tick(
+        &mut self,
+        timestamp_ns: u64,
+        instrument_id: u32,
+        venue_id: u32,
+        bid: u64,
+        bid_size: u32,
+        ask: u64,
+        ask_size: u32,
+    ) {
+        self.harness.add(BboUpdate {
+            instrument_id,
+            best_bid_price: bid,
+            best_bid_size: bid_size,
+            best_ask_price: ask,
+            best_ask_size: ask_size,
+            timestamp_ns,
+            venue_id,
+        });
+    }
+
+    /// Registers the callback run on every tick; returns it, so this can be
+    /// used as a decorator.
+    fn strategy<'py>(&mut self, callback: Bound<'py, PyAny>) -> Bound<'py, PyAny> {
+        self.strategy = Some(callback.clone().unbind());
+        callback
+    }
+
+    /// Runs the registered strategy over the ticks; returns the report.
+    fn run(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let Some(callback) = &self.strategy else {
+            return Err(PyValueError::new_err("no strategy registered; call strategy(callback) first"));
+        };
+        let json = py.import("json")?;
+        let mut raised: Option<PyErr> = None;
+        let result = self.harness.run(|view| {
+            let call = || -> PyResult<String> {
+                let view = json.call_method1("loads", (to_json(view)?,))?;
+                let orders = callback.bind(py).call1((view,))?;
+                if orders.is_none() {
+                    return Ok("[]".to_string());
+                }
+                json.call_method1("dumps", (orders,))?.extract()
+            };
+            let orders = call().map_err(|e| {
+                let message = e.to_string();
+                raised = Some(e);
+                message
+            })?;
+            parse_orders(&orders)
+        });
+        let report = match (result, raised) {
+            (Err(BacktestError::Strategy(_)), Some(e)) => return Err(e),
+            (result, _) => result.map_err(to_py)?,
+        };
+        Ok(json.call_method1("loads", (to_json(&report)?,))?.unbind())
+    }
+}
+
+fn parse_orders(orders: &str) -> Result<Vec<OrderIntent>, String> {
+    serde_json::from_str(orders).map_err(|e| format!("invalid orders {}: {}", orders, e))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> PyResult<String> {
+    serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn parse_time(text: &str) -> PyResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| PyValueError::new_err(format!("{}: {}", text, e)))
+}
+
+fn to_py(e: BacktestError) -> PyErr {
+    match e {
+        BacktestError::Io(e) => PyIOError::new_err(e.to_string()),
+        e @ BacktestError::Csv { .. } => PyValueError::new_err(e.to_string()),
+        e => PyRuntimeError::new_err(e.to_string()),
+    }
+}
+
+#[pymodule]
+fn pyquantumarb(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Backtest>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn a_python_callback_trades_and_the_report_comes_back_as_columns() {
+        Python::initialize();
+        Python::attach(|py| {
+            let code = CString::new(
+                "def on_market(view):\n    \
+                     book = view['venues'][0]\n    \
+                     if view['time_ns'] == 60_000_000_000:\n        \
+                         return [{'venue_id': 1, 'side': 'buy', 'price': book['best_ask'], 'size': 1}]\n\
+                 def broken(view):\n    \
+                     raise KeyError('spread')\n",
+            )
+            .unwrap();
+            let module = PyModule::from_code(py, &code, c"strategy.py", c"strategy").unwrap();
+            let mut backtest = Backtest::new(100_000.0, 60_000, None).unwrap();
+            backtest.add_tick(120_000_000_000, 1, 1, 60100_00, 5, 60101_00, 5);
+            backtest.add_tick(60_000_000_000, 1, 1, 60000_00, 5, 60001_00, 5);
+            assert!(backtest.run(py).is_err());
+
+            backtest.strategy(module.getattr("on_market").unwrap());
+            let report = backtest.run(py).unwrap().into_bound(py);
+            let trades = report.get_item("trades").unwrap();
+            assert_eq!(trades.get_item("side").unwrap().extract::<Vec<String>>().unwrap(), ["buy"]);
+            assert_eq!(trades.get_item("price").unwrap().extract::<Vec<f64>>().unwrap(), [60001.0]);
+            let equity = report.get_item("equity").unwrap().get_item("equity").unwrap();
+            assert_eq!(equity.extract::<Vec<f64>>().unwrap().len(), 2);
+
+            backtest.strategy(module.getattr("broken").unwrap());
+            let error = backtest.run(py).unwrap_err();
+            assert!(error.is_instance_of::<pyo3::exceptions::PyKeyError>(py));
+        });
+    }
+}
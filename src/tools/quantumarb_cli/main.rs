@@ -0,0 +1,592 @@
+/*
+ * QuantumArb 2.0 - Tools: Operator CLI
+ *
+ * File: src/tools/quantumarb_cli/main.rs
+ *
+ * Description:
+ * `quantumarb-cli` is the command-line admin tool for the platform. It wraps
+ * the HTTP APIs exposed by the individual services so operators can manage the
+ * system without hand-crafting curl requests and reading raw JSON.
+ *
+ * Supported commands:
+ *   positions list                         -> portfolio_manager  GET  /portfolio
+ *   positions flatten [--symbol SYM]       -> portfolio_manager  POST /portfolio/flatten
+ *   var                                    -> var_calculator     GET  /var
+ *   limits get <ACCOUNT>                   -> risk_gateway       GET  /limits/{account}
+ *   limits set <ACCOUNT> [--max-order-size N] [--max-exposure X]
+ *                                          -> risk_gateway       PUT  /limits/{account}
+ *   kill-switch status|engage --reason R|release
+ *                                          -> risk_gateway       /kill-switch
+ *   snapshot list|create [--label L]|restore <ID>
+ *          |export <ID> --file F|import --file F
+ *                                          -> risk_gateway       /snapshots
+ *   replay start [--speed X] [--from UTC --to UTC [--instrument ID]... [--topic T]...]
+ *                [--synthetic F [--seed N]]
+ *          |stop|status                    -> market_replay      /replay/{start,stop,status}
+ *                                             (--from/--to replay the bus archive,
+ *                                             --synthetic a generated market)
+ *   alerts tail [--interval SECS]          -> trade_surveillance GET  /alerts (polled)
+ *   models status|promote|feedback         -> strategy_engine    /models, /models/{promote,feedback}
+ *
+ * Service base URLs default to the local development ports and can be
+ * overridden with flags or QA_*_URL environment variables. Where desks are
+ * configured, --token (or QA_API_TOKEN) is sent as the bearer token: a firm
+ * token for the whole platform, a desk's for its own accounts and strategies.
+ */
+
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::time::{self, Duration};
+
+// --- Command-Line Definition ---
+
+#[derive(Debug, Parser)]
+#[command(name = "quantumarb-cli", about = "Operator tool for the QuantumArb 2.0 platform")]
+struct Cli {
+    #[arg(long, env = "QA_PORTFOLIO_URL", default_value = "http://127.0.0.1:3032")]
+    portfolio_url: String,
+    #[arg(long, env = "QA_VAR_URL", default_value = "http://127.0.0.1:3031")]
+    var_url: String,
+    #[arg(long, env = "QA_RISK_URL", default_value = "http://127.0.0.1:3034")]
+    risk_url: String,
+    #[arg(long, env = "QA_REPLAY_URL", default_value = "http://127.0.0.1:3035")]
+    replay_url: String,
+    #[arg(long, env = "QA_SURVEILLANCE_URL", default_value = "http://127.0.0.1:3033")]
+    surveillance_url: String,
+    #[arg(long, env = "QA_STRATEGY_URL", default_value = "http://127.0.0.1:3040")]
+    strategy_url: String,
+    #[arg(long, env = "QA_API_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect or flatten positions.
+    Positions {
+        #[command(subcommand)]
+        action: PositionsAction,
+    },
+    /// Show the latest Value at Risk.
+    Var,
+    /// Inspect or change account risk limits.
+    Limits {
+        #[command(subcommand)]
+        action: LimitsAction,
+    },
+    /// Inspect, engage or release the firm-wide kill switch.
+    KillSwitch {
+        #[command(subcommand)]
+        action: KillSwitchAction,
+    },
+    /// Archive, restore or copy coordinated snapshots of the platform state.
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Control market replay sessions.
+    Replay {
+        #[command(subcommand)]
+        action: ReplayAction,
+    },
+    /// Follow compliance alerts.
+    Alerts {
+        #[command(subcommand)]
+        action: AlertsAction,
+    },
+    /// Compare or promote the champion and challenger ML models.
+    Models {
+        #[command(subcommand)]
+        action: ModelsAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum PositionsAction {
+    List,
+    Flatten {
+        #[arg(long)]
+        symbol: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LimitsAction {
+    Get {
+        account_id: u32,
+    },
+    Set {
+        account_id: u32,
+        #[arg(long)]
+        max_order_size: Option<u32>,
+        #[arg(long)]
+        max_exposure: Option<f64>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KillSwitchAction {
+    Status,
+    Engage {
+        #[arg(long)]
+        reason: String,
+    },
+    Release,
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotAction {
+    List,
+    /// Briefly halts trading while the state is captured.
+    Create {
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Restores a snapshot; trading stays halted until the kill switch is released.
+    Restore {
+        snapshot_id: String,
+    },
+    /// Saves an archive to a file, e.g. to import it into another environment.
+    Export {
+        snapshot_id: String,
+        #[arg(long)]
+        file: String,
+    },
+    /// Uploads an archive saved with `export`.
+    Import {
+        #[arg(long)]
+        file: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ReplayAction {
+    Start {
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Replay the archive from this time (RFC 3339) instead of the sample data.
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+        /// End of the archive range (exclusive, RFC 3339).
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+        /// Archived instruments to replay (repeatable; default all).
+        #[arg(long = "instrument")]
+        instruments: Vec<u32>,
+        /// Archived topics to replay (repeatable; default "market_data.>").
+        #[arg(long = "topic")]
+        topics: Vec<String>,
+        /// Replay a generated market described by this JSON file instead.
+        #[arg(long, conflicts_with = "from")]
+        synthetic: Option<String>,
+        /// Seed for the generated market (default the service's).
+        #[arg(long, requires = "synthetic")]
+        seed: Option<u64>,
+    },
+    Stop,
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+enum AlertsAction {
+    Tail {
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ModelsAction {
+    Status,
+    /// Make the challenger the champion (the champion becomes the challenger).
+    Promote,
+    /// Show prediction accuracy, precision and calibration.
+    Feedback,
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = &cli.token {
+        let value = format!("Bearer {}", token).parse().expect("the token is not a valid header value");
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    let client = reqwest::Client::builder().default_headers(headers).build().unwrap();
+
+    let result = match &cli.command {
+        Command::Positions { action: PositionsAction::List } => list_positions(&client, &cli).await,
+        Command::Positions { action: PositionsAction::Flatten { symbol } } => {
+            flatten_positions(&client, &cli, symbol.clone()).await
+        }
+        Command::Var => show_var(&client, &cli).await,
+        Command::Limits { action: LimitsAction::Get { account_id } } => {
+            let url = format!("{}/limits/{}", cli.risk_url, account_id);
+            get_json(&client, &url).await.map(|limits| print_limits(&limits))
+        }
+        Command::Limits { action: LimitsAction::Set { account_id, max_order_size, max_exposure } } => {
+            let url = format!("{}/limits/{}", cli.risk_url, account_id);
+            let body = json!({ "max_order_size": max_order_size, "max_exposure": max_exposure });
+            send_json(client.put(&url).json(&body)).await.map(|limits| print_limits(&limits))
+        }
+        Command::KillSwitch { action } => kill_switch(&client, &cli, action).await,
+        Command::Snapshot { action } => snapshot(&client, &cli, action).await,
+        Command::Replay { action } => replay(&client, &cli, action).await,
+        Command::Alerts { action: AlertsAction::Tail { interval } } => tail_alerts(&client, &cli, *interval).await,
+        Command::Models { action } => models(&client, &cli, action).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// --- Command Implementations ---
+
+async fn list_positions(client: &reqwest::Client, cli: &Cli) -> Result<(), String> {
+    let portfolio = get_json(client, &format!("{}/portfolio", cli.portfolio_url)).await?;
+    println!("{:<10} {:>12} {:>14} {:>14} {:>14}", "SYMBOL", "QUANTITY", "AVG ENTRY", "MARK", "UNREAL P&L");
+    if let Some(positions) = portfolio["positions"].as_object() {
+        for position in positions.values() {
+            println!(
+                "{:<10} {:>12} {:>14.2} {:>14.2} {:>14.2}",
+                position["symbol"].as_str().unwrap_or("?"),
+                position["quantity"].as_i64().unwrap_or(0),
+                position["average_entry_price"].as_f64().unwrap_or(0.0),
+                position["current_market_price"].as_f64().unwrap_or(0.0),
+                position["unrealized_pnl"].as_f64().unwrap_or(0.0),
+            );
+        }
+    }
+    println!();
+    println!("Realized P&L:   {:>14.2}", portfolio["realized_pnl"].as_f64().unwrap_or(0.0));
+    println!("Unrealized P&L: {:>14.2}", portfolio["total_unrealized_pnl"].as_f64().unwrap_or(0.0));
+    println!("Gross value:    {:>14.2}", portfolio["total_portfolio_value"].as_f64().unwrap_or(0.0));
+    Ok(())
+}
+
+async fn flatten_positions(client: &reqwest::Client, cli: &Cli, symbol: Option<String>) -> Result<(), String> {
+    let url = format!("{}/portfolio/flatten", cli.portfolio_url);
+    let orders = send_json(client.post(&url).json(&json!({ "symbol": symbol }))).await?;
+    let orders = orders.as_array().cloned().unwrap_or_default();
+    if orders.is_empty() {
+        println!("Nothing to flatten.");
+        return Ok(());
+    }
+    println!("Submitted {} flattening order(s):", orders.len());
+    for order in orders {
+        println!(
+            "  {} {} {} @ ~{:.2}",
+            order["side"].as_str().unwrap_or("?"),
+            order["quantity"].as_u64().unwrap_or(0),
+            order["symbol"].as_str().unwrap_or("?"),
+            order["reference_price"].as_f64().unwrap_or(0.0),
+        );
+    }
+    Ok(())
+}
+
+async fn show_var(client: &reqwest::Client, cli: &Cli) -> Result<(), String> {
+    let var = get_json(client, &format!("{}/var", cli.var_url)).await?;
+    let var_amount = var["var_amount"].as_f64().unwrap_or(0.0);
+    let portfolio_value = var["portfolio_value"].as_f64().unwrap_or(0.0);
+    println!("Confidence:      {:.1}%", var["confidence_level"].as_f64().unwrap_or(0.0) * 100.0);
+    println!("VaR:             {:.2}", var_amount);
+    println!("Standard error:  {:.2}", var["standard_error"].as_f64().unwrap_or(0.0));
+    if let Some(interval) = var["var_interval"].as_object() {
+        println!(
+            "VaR interval:    {:.2} to {:.2}",
+            interval["lower"].as_f64().unwrap_or(0.0),
+            interval["upper"].as_f64().unwrap_or(0.0)
+        );
+    }
+    println!("Exp. shortfall:  {:.2}", var["expected_shortfall"].as_f64().unwrap_or(0.0));
+    if let Some(interval) = var["es_interval"].as_object() {
+        println!(
+            "ES interval:     {:.2} to {:.2}",
+            interval["lower"].as_f64().unwrap_or(0.0),
+            interval["upper"].as_f64().unwrap_or(0.0)
+        );
+    }
+    println!("Converged:       {}", var["converged"].as_bool().unwrap_or(true));
+    println!("Portfolio value: {:.2}", portfolio_value);
+    if portfolio_value != 0.0 {
+        println!("VaR / value:     {:.2}%", var_amount / portfolio_value * 100.0);
+    }
+    println!("As of:           {}", var["timestamp_utc"].as_str().unwrap_or("?"));
+    Ok(())
+}
+
+async fn kill_switch(client: &reqwest::Client, cli: &Cli, action: &KillSwitchAction) -> Result<(), String> {
+    let url = format!("{}/kill-switch", cli.risk_url);
+    let state = match action {
+        KillSwitchAction::Status => get_json(client, &url).await?,
+        KillSwitchAction::Engage { reason } => {
+            send_json(client.post(&url).json(&json!({ "engaged": true, "reason": reason }))).await?
+        }
+        KillSwitchAction::Release => send_json(client.post(&url).json(&json!({ "engaged": false }))).await?,
+    };
+    if state["engaged"].as_bool().unwrap_or(false) {
+        println!("Kill switch: ENGAGED ({})", state["reason"].as_str().unwrap_or(""));
+    } else {
+        println!("Kill switch: released");
+    }
+    println!("Last change: {}", state["updated_utc"].as_str().unwrap_or("?"));
+    Ok(())
+}
+
+async fn snapshot(client: &reqwest::Client, cli: &Cli, action: &SnapshotAction) -> Result<(), String> {
+    let url = format!("{}/snapshots", cli.risk_url);
+    let summaries = match action {
+        SnapshotAction::List => get_json(client, &url).await?,
+        SnapshotAction::Create { label } => {
+            println!("Halting trading to capture a consistent snapshot...");
+            json!([send_json(client.post(&url).json(&json!({ "label": label }))).await?])
+        }
+        SnapshotAction::Restore { snapshot_id } => {
+            let report = send_json(client.post(format!("{}/{}/restore", url, snapshot_id))).await?;
+            print_restore_report(&report);
+            return Ok(());
+        }
+        SnapshotAction::Export { snapshot_id, file } => {
+            let archive = get_json(client, &format!("{}/{}", url, snapshot_id)).await?;
+            let json = serde_json::to_string_pretty(&archive).map_err(|e| e.to_string())?;
+            std::fs::write(file, json).map_err(|e| format!("cannot write {}: {}", file, e))?;
+            println!("Snapshot {} saved to {}.", snapshot_id, file);
+            return Ok(());
+        }
+        SnapshotAction::Import { file } => {
+            let json = std::fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", file, e))?;
+            let archive: Value = serde_json::from_str(&json).map_err(|e| format!("{} is not JSON: {}", file, e))?;
+            let snapshot_id = archive["snapshot_id"].as_str().ok_or_else(|| format!("{} has no snapshot_id", file))?;
+            json!([send_json(client.put(format!("{}/{}", url, snapshot_id)).json(&archive)).await?])
+        }
+    };
+    println!("{:<22} {:<20} {:>8} {:>12}  LABEL", "SNAPSHOT", "CREATED", "ACCOUNTS", "OPEN ORDERS");
+    for summary in summaries.as_array().cloned().unwrap_or_default() {
+        println!(
+            "{:<22} {:<20} {:>8} {:>12}  {}",
+            summary["snapshot_id"].as_str().unwrap_or("?"),
+            summary["created_utc"].as_str().unwrap_or("?").get(..19).unwrap_or("?"),
+            summary["accounts"].as_u64().unwrap_or(0),
+            summary["open_orders"].as_u64().unwrap_or(0),
+            summary["label"].as_str().unwrap_or(""),
+        );
+    }
+    Ok(())
+}
+
+fn print_restore_report(report: &Value) {
+    println!("Restored snapshot {}:", report["snapshot_id"].as_str().unwrap_or("?"));
+    for section in report["sections"].as_array().cloned().unwrap_or_default() {
+        println!(
+            "  {:<12} {:<7} {}",
+            section["section"].as_str().unwrap_or("?"),
+            if section["restored"].as_bool().unwrap_or(false) { "ok" } else { "FAILED" },
+            section["detail"].as_str().unwrap_or(""),
+        );
+    }
+    if report["kill_switch_engaged"].as_bool().unwrap_or(false) {
+        println!("Trading is halted. Review the restored state, then run `kill-switch release`.");
+    }
+}
+
+async fn replay(client: &reqwest::Client, cli: &Cli, action: &ReplayAction) -> Result<(), String> {
+    let session = match action {
+        ReplayAction::Start { speed, from, to, instruments, topics, synthetic, seed } => {
+            let url = format!("{}/replay/start", cli.replay_url);
+            let mut body = json!({ "speed": speed });
+            if let (Some(from), Some(to)) = (from, to) {
+                body["archive"] = json!({ "start_utc": from, "end_utc": to, "instruments": instruments, "topics": topics });
+            }
+            if let Some(file) = synthetic {
+                let json = std::fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", file, e))?;
+                body["synthetic"] = serde_json::from_str(&json).map_err(|e| format!("{} is not JSON: {}", file, e))?;
+                if let Some(seed) = seed {
+                    body["synthetic"]["seed"] = json!(seed);
+                }
+            }
+            send_json(client.post(&url).json(&body)).await?
+        }
+        ReplayAction::Stop => send_json(client.post(format!("{}/replay/stop", cli.replay_url))).await?,
+        ReplayAction::Status => get_json(client, &format!("{}/replay/status", cli.replay_url)).await?,
+    };
+    println!("Session:  {}", session["session_id"].as_str().unwrap_or("-"));
+    println!("Status:   {}", session["status"].as_str().unwrap_or("?"));
+    println!("Speed:    {}x", session["speed"].as_f64().unwrap_or(1.0));
+    match session["total_events"].as_u64() {
+        Some(total) => {
+            println!("Progress: {}/{} events", session["events_published"].as_u64().unwrap_or(0), total)
+        }
+        None => println!("Progress: {} events", session["events_published"].as_u64().unwrap_or(0)),
+    }
+    if let Some(seed) = session["seed"].as_u64() {
+        println!("Seed:     {}", seed);
+    }
+    let archive = &session["archive"];
+    if !archive.is_null() {
+        println!(
+            "Archive:  {}/{} files, {:.1}% read, replay clock at {}",
+            archive["files_read"].as_u64().unwrap_or(0),
+            archive["files_total"].as_u64().unwrap_or(0),
+            archive["percent_read"].as_f64().unwrap_or(0.0),
+            archive["replay_time_utc"].as_str().unwrap_or("-")
+        );
+    }
+    if let Some(error) = session["error"].as_str() {
+        println!("Error:    {}", error);
+    }
+    Ok(())
+}
+
+async fn models(client: &reqwest::Client, cli: &Cli, action: &ModelsAction) -> Result<(), String> {
+    let report = match action {
+        ModelsAction::Status => get_json(client, &format!("{}/models", cli.strategy_url)).await?,
+        ModelsAction::Promote => send_json(client.post(format!("{}/models/promote", cli.strategy_url))).await?,
+        ModelsAction::Feedback => {
+            let report = get_json(client, &format!("{}/models/feedback", cli.strategy_url)).await?;
+            print_model_feedback(&report);
+            return Ok(());
+        }
+    };
+    println!(
+        "{:<11} {:<40} {:>8} {:>8} {:>6} {:>10} {:>14}",
+        "ROLE", "ENDPOINT", "PREDS", "FAILS", "LAST", "PAPER POS", "HYPO P&L"
+    );
+    for role in ["champion", "challenger"] {
+        let model = &report[role];
+        if model.is_null() {
+            println!("{:<11} -", role);
+            continue;
+        }
+        println!(
+            "{:<11} {:<40} {:>8} {:>8} {:>6} {:>10} {:>14.2}",
+            role,
+            model["url"].as_str().unwrap_or("?"),
+            model["predictions"].as_u64().unwrap_or(0),
+            model["failures"].as_u64().unwrap_or(0),
+            model["last_signal"].as_str().unwrap_or("-"),
+            model["paper_position"].as_i64().unwrap_or(0),
+            model["hypothetical_pnl"].as_f64().unwrap_or(0.0),
+        );
+    }
+    println!();
+    match report["agreement_rate"].as_f64() {
+        Some(rate) => {
+            println!("Agreement: {:.1}% over {} cycles", rate * 100.0, report["compared"].as_u64().unwrap_or(0))
+        }
+        None => println!("Agreement: -"),
+    }
+    println!(
+        "Promotions: {} (last {})",
+        report["promotions"].as_u64().unwrap_or(0),
+        report["last_promotion_utc"].as_str().unwrap_or("never")
+    );
+    Ok(())
+}
+
+fn print_model_feedback(report: &Value) {
+    let percent = |value: &Value| value.as_f64().map_or("-".to_string(), |v| format!("{:.1}%", v * 100.0));
+    println!(
+        "{:<11} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "ROLE", "SAMPLES", "PENDING", "ACCURACY", "BUY PREC", "SELL PREC", "BRIER"
+    );
+    for role in ["champion", "challenger"] {
+        let metrics = &report[role]["metrics"];
+        if metrics.is_null() {
+            println!("{:<11} -", role);
+            continue;
+        }
+        println!(
+            "{:<11} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            role,
+            metrics["samples"].as_u64().unwrap_or(0),
+            metrics["pending"].as_u64().unwrap_or(0),
+            percent(&metrics["accuracy"]),
+            percent(&metrics["buy_precision"]),
+            percent(&metrics["sell_precision"]),
+            metrics["brier_score"].as_f64().map_or("-".to_string(), |b| format!("{:.3}", b)),
+        );
+    }
+    println!();
+    if report["ml_filter_enabled"].as_bool().unwrap_or(false) {
+        println!("ML filter: enabled");
+    } else {
+        println!("ML filter: disabled ({})", report["ml_filter_disabled_reason"].as_str().unwrap_or("no champion"));
+    }
+}
+
+/// Polls /alerts and prints alerts that are new or have escalated (more
+/// occurrences) since the last poll.
+async fn tail_alerts(client: &reqwest::Client, cli: &Cli, interval_secs: u64) -> Result<(), String> {
+    let url = format!("{}/alerts", cli.surveillance_url);
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    let mut interval = time::interval(Duration::from_secs(interval_secs.max(1)));
+    println!("Tailing compliance alerts from {} (Ctrl-C to stop)...", url);
+    loop {
+        interval.tick().await;
+        let alerts = get_json(client, &url).await?;
+        for alert in alerts.as_array().cloned().unwrap_or_default() {
+            let alert_id = alert["alert_id"].as_str().unwrap_or("").to_string();
+            let occurrences = alert["occurrences"].as_u64().unwrap_or(1);
+            if seen.insert(alert_id.clone(), occurrences) == Some(occurrences) {
+                continue;
+            }
+            println!(
+                "[{}] {} {} {} (x{}, score {:.0}) - {}: {}",
+                alert["last_seen_utc"].as_str().unwrap_or("?"),
+                alert_id,
+                alert["severity"].as_str().unwrap_or("?"),
+                alert["strategy_id"].as_str().unwrap_or("?"),
+                occurrences,
+                alert["severity_score"].as_f64().unwrap_or(0.0),
+                alert["pattern_detected"].as_str().unwrap_or("?"),
+                alert["description"].as_str().unwrap_or(""),
+            );
+        }
+    }
+}
+
+// --- HTTP Helpers ---
+
+fn print_limits(limits: &Value) {
+    println!("Account:        {}", limits["account_id"]);
+    println!(
+        "Max order size: {} (base {})",
+        limits["current_max_order_size"], limits["base_max_order_size"]
+    );
+    println!(
+        "Max exposure:   {:.2} (base {:.2})",
+        limits["current_max_exposure"].as_f64().unwrap_or(0.0),
+        limits["base_max_exposure"].as_f64().unwrap_or(0.0)
+    );
+    println!("Exposure:       {:.2}", limits["current_exposure"].as_f64().unwrap_or(0.0));
+}
+
+async fn get_json(client: &reqwest::Client, url: &str) -> Result<Value, String> {
+    send_json(client.get(url)).await
+}
+
+/// Sends a request and decodes the JSON body, turning non-2xx responses
+/// (a standard `{ "error": { code, message } }` body) into an error message.
+async fn send_json(request: reqwest::RequestBuilder) -> Result<Value, String> {
+    let response = request.send().await.map_err(|e| format!("request failed: {}", e))?;
+    let status = response.status();
+    let body: Value = response.json().await.map_err(|e| format!("invalid response: {}", e))?;
+    if !status.is_success() {
+        let error = &body["error"];
+        return Err(format!(
+            "{}: {} ({})",
+            error["code"].as_str().unwrap_or("UNKNOWN"),
+            error["message"].as_str().unwrap_or("request rejected"),
+            status
+        ));
+    }
+    Ok(body)
+}
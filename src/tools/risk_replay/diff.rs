@@ -0,0 +1,156 @@
+/*
+ * QuantumArb 2.0 - Tools: Risk Decision Diff
+ *
+ * File: src/tools/risk_replay/diff.rs
+ *
+ * Description:
+ * Compares two recordings of risk gateway decisions over the same requests,
+ * matched by order (or package) id. A request counts as changed when its
+ * outcome differs, or when both builds rejected it for different codes.
+ * Held requests are compared by outcome alone: a held verdict on the ring
+ * carries RISK_PENDING_APPROVAL rather than the check that held it.
+ */
+
+use quantumarb_errors::RejectCode;
+use quantumarb_types::{RiskDecisionAudit, RiskOutcome};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    NowApproved,
+    NowRejected,
+    NowHeld,
+    /// Rejected by both, for different reasons.
+    CodeChanged,
+    /// The candidate has no decision for the request.
+    Missing,
+}
+
+/// One decision as a column of the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub outcome: RiskOutcome,
+    pub code: Option<RejectCode>,
+}
+
+impl Decision {
+    fn of(audit: &RiskDecisionAudit) -> Decision {
+        Decision { outcome: audit.outcome, code: audit.code }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.code.filter(|_| self.outcome != RiskOutcome::Approved) {
+            Some(code) => format!("{} {}", self.outcome.event_type(), code.as_str()),
+            None => self.outcome.event_type().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub id: Uuid,
+    pub kind: ChangeKind,
+    pub baseline: Decision,
+    pub candidate: Option<Decision>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffReport {
+    pub compared: usize,
+    pub unchanged: usize,
+    pub changes: Vec<Change>,
+}
+
+impl DiffReport {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|change| change.kind == kind).count()
+    }
+}
+
+/// Diffs `candidate` against `baseline`, in the baseline's order. Requests
+/// only the candidate decided are ignored.
+pub fn compare(baseline: &[RiskDecisionAudit], candidate: &[RiskDecisionAudit]) -> DiffReport {
+    let candidate: HashMap<Uuid, &RiskDecisionAudit> = candidate.iter().map(|c| (c.request.id(), c)).collect();
+    let mut report = DiffReport::default();
+    for recorded in baseline {
+        report.compared += 1;
+        let id = recorded.request.id();
+        let before = Decision::of(recorded);
+        let Some(after) = candidate.get(&id).map(|c| Decision::of(c)) else {
+            report.changes.push(Change { id, kind: ChangeKind::Missing, baseline: before, candidate: None });
+            continue;
+        };
+        let kind = match (before.outcome, after.outcome) {
+            (RiskOutcome::Rejected, RiskOutcome::Rejected) if before.code != after.code => ChangeKind::CodeChanged,
+            (previous, now) if previous == now => {
+                report.unchanged += 1;
+                continue;
+            }
+            (_, RiskOutcome::Approved) => ChangeKind::NowApproved,
+            (_, RiskOutcome::Rejected) => ChangeKind::NowRejected,
+            (_, RiskOutcome::Held) => ChangeKind::NowHeld,
+        };
+        report.changes.push(Change { id, kind, baseline: before, candidate: Some(after) });
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantumarb_types::CheckedRequest;
+    use quantumarb_wire::{OrderRequest, OrderSide};
+
+    fn decision(id: u128, outcome: RiskOutcome, code: Option<RejectCode>) -> RiskDecisionAudit {
+        let order = OrderRequest {
+            order_id: Uuid::from_u128(id),
+            account_id: 101,
+            instrument_id: 1,
+            side: OrderSide::Buy,
+            price: 60150_00,
+            size: 120,
+            stamps: Default::default(),
+            venue_id: 1,
+            mode: Default::default(),
+            priority: Default::default(),
+            strategy_id: String::new(),
+        };
+        let checked_by = "risk-gateway-1".to_string();
+        RiskDecisionAudit { outcome, code, reason: None, checked_by, request: CheckedRequest::Order(order) }
+    }
+
+    #[test]
+    fn changed_outcomes_and_codes_are_reported_in_baseline_order() {
+        use RiskOutcome::*;
+        let size = Some(RejectCode::RiskOrderSizeLimit);
+        let baseline = [
+            decision(1, Approved, None),
+            decision(2, Rejected, size),
+            decision(3, Rejected, size),
+            decision(4, Held, Some(RejectCode::RiskStressLimit)),
+            decision(5, Approved, None),
+            decision(6, Approved, None),
+        ];
+        let candidate = [
+            decision(6, Rejected, Some(RejectCode::RiskCapitalAllocation)),
+            decision(4, Held, Some(RejectCode::RiskPendingApproval)),
+            decision(3, Rejected, Some(RejectCode::RiskExposureLimit)),
+            decision(2, Approved, None),
+            decision(1, Approved, None),
+            decision(7, Rejected, size),
+        ];
+
+        let report = compare(&baseline, &candidate);
+        assert_eq!((report.compared, report.unchanged), (6, 2));
+        let kinds: Vec<_> = report.changes.iter().map(|c| (c.id.as_u128(), c.kind)).collect();
+        let expected = [
+            (2, ChangeKind::NowApproved),
+            (3, ChangeKind::CodeChanged),
+            (5, ChangeKind::Missing),
+            (6, ChangeKind::NowRejected),
+        ];
+        assert_eq!(kinds, expected);
+        assert_eq!(report.changes[1].candidate.as_ref().unwrap().describe(), "RISK_REJECTED RISK_EXPOSURE_LIMIT");
+    }
+}
@@ -0,0 +1,321 @@
+/*
+ * QuantumArb 2.0 - Tools: Risk Gateway Replay
+ *
+ * File: src/tools/risk_replay/main.rs
+ *
+ * Description:
+ * `risk-replay` validates a change to the risk gateway's limit logic before
+ * it is deployed. It records the order flow the gateway decided in
+ * production, replays it against a new build, and diffs the two builds'
+ * approve/reject decisions:
+ *
+ *   record --from UTC --to UTC --output F [--account ID]
+ *                          -> worm_logger  GET /worm/records?topic=audit.events
+ *   replay --recording F [--output F] [--mode sandbox|live] [--timeout-ms N]
+ *                          -> risk_gateway over the shared-memory rings
+ *   diff BASELINE CANDIDATE
+ *
+ * A recording is JSON Lines, one `RiskDecisionAudit` per request: the request
+ * exactly as the gateway checked it and the gateway's first decision on it
+ * (for a held order, the hold). `record` takes them from the risk gateway's
+ * RISK_* audit events in the WORM log.
+ *
+ * `replay` acts as the strategy engine on the shared-memory rings, so the
+ * gateway under test must run with QA_RISK_TRANSPORT=shm on this host. It
+ * sends the recorded requests one at a time, in recorded order, waiting for
+ * each verdict before the next, so account state evolves as it did when the
+ * recording was made. Decisions also depend on that state: restore the
+ * platform snapshot taken at the start of the recorded window into the test
+ * Redis first (`quantumarb-cli snapshot import/restore`). --mode restamps
+ * every request, e.g. to replay live flow against a sandbox gateway.
+ *
+ * `replay` and `diff` print every request whose decision changed and exit
+ * with status 2 if any did, so the comparison can gate a deployment.
+ * Replaying the same recording against two builds and diffing their outputs
+ * compares the builds with each other.
+ */
+
+mod diff;
+
+use clap::{Parser, Subcommand};
+use diff::{ChangeKind, DiffReport};
+use quantumarb_bus::topics;
+use quantumarb_errors::RejectCode;
+use quantumarb_shm::{risk_channel, PushError, ShmConsumer, ShmProducer};
+use quantumarb_types::{AuditEvent, CheckedRequest, RiskDecisionAudit, RiskOutcome};
+use quantumarb_wire::{RiskVerdict, TradingMode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Name the decisions of a replay are recorded as checked by.
+const REPLAY_CHECKED_BY: &str = "risk-replay";
+
+// --- Command-Line Definition ---
+
+#[derive(Debug, Parser)]
+#[command(name = "risk-replay", about = "Replay recorded order flow against a risk gateway build")]
+struct Cli {
+    #[arg(long, env = "QA_WORM_URL", default_value = "http://127.0.0.1:3041")]
+    worm_url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Record the risk gateway's decisions in a time range from the WORM log.
+    Record {
+        /// Start of the range (RFC 3339).
+        #[arg(long)]
+        from: String,
+        /// End of the range (exclusive, RFC 3339).
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        output: String,
+        /// Only this account's requests.
+        #[arg(long)]
+        account: Option<u32>,
+    },
+    /// Replay a recording against the risk gateway and diff its decisions.
+    Replay {
+        #[arg(long)]
+        recording: String,
+        /// Write the new decisions here, as a recording.
+        #[arg(long)]
+        output: Option<String>,
+        /// Restamp every request with this trading mode.
+        #[arg(long, value_parser = ["sandbox", "live"])]
+        mode: Option<String>,
+        /// How long to wait for each verdict.
+        #[arg(long, default_value_t = 5_000)]
+        timeout_ms: u64,
+    },
+    /// Diff two recordings of the same requests.
+    Diff { baseline: String, candidate: String },
+}
+
+/// A record of GET /worm/records; only the message is needed.
+#[derive(Debug, Deserialize)]
+struct StoredRecord {
+    payload: String,
+}
+
+// --- Main Application Logic ---
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        Command::Record { from, to, output, account } => record(&cli, from, to, output, *account).await,
+        Command::Replay { recording, output, mode, timeout_ms } => {
+            let mode = mode.as_deref().map(|m| if m == "live" { TradingMode::Live } else { TradingMode::Sandbox });
+            let timeout = Duration::from_millis(*timeout_ms);
+            replay(recording, output.as_deref(), mode, timeout).map(|report| report.changes.is_empty())
+        }
+        Command::Diff { baseline, candidate } => diff_recordings(baseline, candidate).map(|r| r.changes.is_empty()),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(2),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// --- Command Implementations ---
+
+async fn record(cli: &Cli, from: &str, to: &str, output: &str, account: Option<u32>) -> Result<bool, String> {
+    let url = format!("{}/worm/records", cli.worm_url);
+    let query = [("topic", topics::AUDIT_EVENTS), ("start_utc", from), ("end_utc", to)];
+    let response = reqwest::Client::new().get(&url).query(&query).send().await;
+    let response = response.map_err(|e| format!("request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("the WORM logger answered {}", response.status()));
+    }
+    let records: Vec<StoredRecord> = response.json().await.map_err(|e| format!("invalid response: {}", e))?;
+
+    let (decisions, skipped) = decisions_from_audit(&records, account);
+    write_recording(output, &decisions)?;
+    println!("Recorded {} request(s) from {} audit event(s) to {}.", decisions.len(), records.len(), output);
+    if skipped > 0 {
+        println!("Skipped {} risk gateway event(s) without a readable request.", skipped);
+    }
+    Ok(true)
+}
+
+/// The first decision on each request in the risk gateway's audit events, and
+/// how many of its RISK_* events could not be read.
+fn decisions_from_audit(records: &[StoredRecord], account: Option<u32>) -> (Vec<RiskDecisionAudit>, usize) {
+    let (mut decisions, mut seen, mut skipped) = (Vec::new(), HashSet::new(), 0);
+    for record in records {
+        let Ok(event) = serde_json::from_str::<AuditEvent>(&record.payload) else {
+            continue;
+        };
+        if event.service_name != "risk-gateway" || !event.event_type.starts_with("RISK_") {
+            continue;
+        }
+        let Ok(decision) = serde_json::from_str::<RiskDecisionAudit>(&event.payload) else {
+            skipped += 1;
+            continue;
+        };
+        let account_id = match &decision.request {
+            CheckedRequest::Order(order) => order.account_id,
+            CheckedRequest::Package(package) => package.account_id,
+        };
+        // A held order's settlement is a second decision on the same request.
+        if account.is_none_or(|a| a == account_id) && seen.insert(decision.request.id()) {
+            decisions.push(decision);
+        }
+    }
+    (decisions, skipped)
+}
+
+fn replay(
+    recording: &str,
+    output: Option<&str>,
+    mode: Option<TradingMode>,
+    timeout: Duration,
+) -> Result<DiffReport, String> {
+    let baseline = read_recording(recording)?;
+    let (capacity, slot_size) = (risk_channel::CAPACITY, risk_channel::SLOT_SIZE);
+    let map_error = |e: std::io::Error| format!("cannot map the risk rings: {}", e);
+    let mut requests = ShmProducer::open(risk_channel::REQUESTS_PATH, capacity, slot_size).map_err(map_error)?;
+    let mut verdicts = ShmConsumer::open(risk_channel::VERDICTS_PATH, capacity, slot_size).map_err(map_error)?;
+    let mut buf = Vec::with_capacity(slot_size);
+    // Verdicts left from earlier traffic are not ours.
+    while verdicts.try_pop(&mut buf) {}
+
+    println!("Replaying {} request(s) from {} over {}", baseline.len(), recording, risk_channel::REQUESTS_PATH);
+    let mut candidate = Vec::with_capacity(baseline.len());
+    for recorded in &baseline {
+        let mut request = recorded.request.clone();
+        let payload = match &mut request {
+            CheckedRequest::Order(order) => {
+                order.mode = mode.unwrap_or(order.mode);
+                quantumarb_wire::encode(order)
+            }
+            CheckedRequest::Package(package) => {
+                package.mode = mode.unwrap_or(package.mode);
+                quantumarb_wire::encode(package)
+            }
+        };
+        loop {
+            match requests.try_push(&payload) {
+                Ok(()) => break,
+                Err(PushError::Full) => std::thread::yield_now(),
+                Err(PushError::TooLarge { max }) => {
+                    return Err(format!("request {} exceeds the ring slot size ({} bytes)", request.id(), max))
+                }
+            }
+        }
+        let verdict = await_verdict(&mut verdicts, &mut buf, request.id(), timeout)?;
+        candidate.push(decision_from_verdict(verdict, request));
+    }
+
+    if let Some(output) = output {
+        write_recording(output, &candidate)?;
+        println!("Wrote the new decisions to {}.", output);
+    }
+    let report = diff::compare(&baseline, &candidate);
+    print_report(&report);
+    Ok(report)
+}
+
+/// Waits for the first verdict on `id`. Verdicts on other requests (the
+/// settlement of an order held earlier) are passed over.
+fn await_verdict(
+    verdicts: &mut ShmConsumer,
+    buf: &mut Vec<u8>,
+    id: Uuid,
+    timeout: Duration,
+) -> Result<RiskVerdict, String> {
+    loop {
+        if !verdicts.pop_spin(buf, timeout) {
+            return Err(format!("no verdict on {} within {:?}; is the gateway serving over shm?", id, timeout));
+        }
+        match quantumarb_wire::decode::<RiskVerdict>(buf) {
+            Ok(verdict) if verdict.order_id == id => return Ok(verdict),
+            Ok(_) => {}
+            Err(e) => return Err(format!("undecodable verdict: {}", e)),
+        }
+    }
+}
+
+fn decision_from_verdict(verdict: RiskVerdict, request: CheckedRequest) -> RiskDecisionAudit {
+    let outcome = match &verdict.reject {
+        None if verdict.approved => RiskOutcome::Approved,
+        Some(reject) if reject.code == RejectCode::RiskPendingApproval => RiskOutcome::Held,
+        _ => RiskOutcome::Rejected,
+    };
+    RiskDecisionAudit {
+        outcome,
+        code: verdict.reject.as_ref().map(|r| r.code),
+        reason: verdict.reject.map(|r| r.message),
+        checked_by: REPLAY_CHECKED_BY.to_string(),
+        request,
+    }
+}
+
+fn diff_recordings(baseline: &str, candidate: &str) -> Result<DiffReport, String> {
+    let report = diff::compare(&read_recording(baseline)?, &read_recording(candidate)?);
+    print_report(&report);
+    Ok(report)
+}
+
+fn print_report(report: &DiffReport) {
+    if !report.changes.is_empty() {
+        println!("{:<38} {:<14} {:<40} CANDIDATE", "REQUEST", "CHANGE", "BASELINE");
+    }
+    for change in &report.changes {
+        println!(
+            "{:<38} {:<14} {:<40} {}",
+            change.id,
+            format!("{:?}", change.kind),
+            change.baseline.describe(),
+            change.candidate.as_ref().map_or("-".to_string(), |d| d.describe())
+        );
+    }
+    println!(
+        "Compared {} request(s): {} unchanged, {} now approved, {} now rejected, {} now held, {} rejected for a \
+         different reason, {} without a decision.",
+        report.compared,
+        report.unchanged,
+        report.count(ChangeKind::NowApproved),
+        report.count(ChangeKind::NowRejected),
+        report.count(ChangeKind::NowHeld),
+        report.count(ChangeKind::CodeChanged),
+        report.count(ChangeKind::Missing),
+    );
+}
+
+// --- Recordings ---
+
+fn read_recording(path: &str) -> Result<Vec<RiskDecisionAudit>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{} line {} is not a decision: {}", path, index + 1, e))
+        })
+        .collect()
+}
+
+fn write_recording(path: &str, decisions: &[RiskDecisionAudit]) -> Result<(), String> {
+    let write_error = |e: std::io::Error| format!("cannot write {}: {}", path, e);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path).map_err(write_error)?);
+    for decision in decisions {
+        let line = serde_json::to_string(decision).expect("decisions serialize");
+        writeln!(file, "{}", line).map_err(write_error)?;
+    }
+    file.flush().map_err(write_error)
+}
@@ -0,0 +1,288 @@
+/*
+ * QuantumArb 2.0 - Integration Tests: Platform Harness
+ *
+ * File: tests/integration/harness.rs
+ *
+ * Description:
+ * Starts a small copy of the platform on the local machine: Redis, the risk
+ * gateway, the portfolio manager and the VaR calculator as separate
+ * processes, plus the in-process mock exchange. Orders are submitted the way
+ * a colocated strategy engine sends them, over the risk gateway's
+ * shared-memory rings; approved orders are filled by the mock exchange.
+ *
+ * Services run from prebuilt binaries and listen on their usual ports (3031,
+ * 3032, 3034), so only one harness can run on a machine at a time. Each
+ * service's output goes to a log file under the run's working directory,
+ * printed when the harness starts.
+ *
+ * Services run seeded (QA_SEED), so the VaR calculator's Monte Carlo draws
+ * and everything else they simulate repeat from run to run.
+ *
+ * Configuration (environment):
+ *   QA_IT_BIN_DIR=target/debug    where the service binaries are
+ *   QA_IT_REDIS_URL=              a Redis to use instead of starting one in
+ *                                 a container; it is FLUSHED on start
+ *   QA_IT_SEED=42                 the seed the services run with
+ */
+
+use quantumarb_shm::{risk_channel, ShmConsumer, ShmProducer};
+use quantumarb_wire::{OrderRequest, RiskVerdict};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::ContainerAsync;
+use testcontainers_modules::redis::{Redis, REDIS_PORT};
+
+use crate::mock_exchange::MockExchange;
+
+pub const VAR_CALCULATOR_URL: &str = "http://127.0.0.1:3031";
+pub const PORTFOLIO_MANAGER_URL: &str = "http://127.0.0.1:3032";
+pub const RISK_GATEWAY_URL: &str = "http://127.0.0.1:3034";
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const VERDICT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// --- Data Structures ---
+
+/// A running service, killed when dropped.
+struct Service {
+    name: &'static str,
+    child: Child,
+    log_path: PathBuf,
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The risk gateway's shared-memory order entry, as seen by a strategy.
+struct RiskClient {
+    requests: ShmProducer,
+    verdicts: ShmConsumer,
+}
+
+pub struct Platform {
+    pub http: reqwest::Client,
+    pub exchange: MockExchange,
+    risk: RiskClient,
+    // Dropped in declaration order: services stop before Redis goes away.
+    services: Vec<Service>,
+    _redis: Option<ContainerAsync<Redis>>,
+}
+
+// --- Startup ---
+
+impl Platform {
+    /// Starts Redis and the services and waits until each one answers.
+    /// `var_history` is written to the VaR calculator's history file before
+    /// it starts, to give it a past.
+    pub async fn start(var_history: &[serde_json::Value]) -> Platform {
+        let work_dir = std::env::temp_dir().join(format!("quantumarb-it-{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).expect("Failed to create the harness directory");
+        println!("Harness logs in {}", work_dir.display());
+
+        for port in [3031, 3032, 3034] {
+            assert!(
+                std::net::TcpListener::bind(("127.0.0.1", port)).is_ok(),
+                "port {} is in use; stop the local services before running the harness",
+                port
+            );
+        }
+
+        let (redis_url, container) = start_redis().await;
+
+        // Fresh rings, so no verdicts from an earlier run are waiting.
+        let _ = std::fs::remove_file(risk_channel::REQUESTS_PATH);
+        let _ = std::fs::remove_file(risk_channel::VERDICTS_PATH);
+        let risk = RiskClient {
+            requests: ShmProducer::open(risk_channel::REQUESTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE)
+                .expect("Failed to map risk request ring"),
+            verdicts: ShmConsumer::open(risk_channel::VERDICTS_PATH, risk_channel::CAPACITY, risk_channel::SLOT_SIZE)
+                .expect("Failed to map risk verdict ring"),
+        };
+
+        let history_path = work_dir.join("var_history.jsonl");
+        let history: String = var_history.iter().map(|point| format!("{}\n", point)).collect();
+        std::fs::write(&history_path, history).expect("Failed to write the VaR history");
+        // A fresh journal, so no fills from an earlier run are replayed.
+        let journal_path = work_dir.join("portfolio_manager.journal.jsonl");
+        let _ = std::fs::remove_file(&journal_path);
+
+        let bin_dir = PathBuf::from(std::env::var("QA_IT_BIN_DIR").unwrap_or_else(|_| "target/debug".to_string()));
+        let seed = std::env::var("QA_IT_SEED").unwrap_or_else(|_| "42".to_string());
+        println!("Services seeded with {}", seed);
+        let services = vec![
+            spawn(
+                &bin_dir,
+                &work_dir,
+                "var-calculator",
+                &[("QA_SEED", seed.clone()), ("QA_VAR_HISTORY_PATH", path_str(&history_path))],
+            ),
+            spawn(
+                &bin_dir,
+                &work_dir,
+                "portfolio-manager",
+                &[
+                    ("QA_SEED", seed.clone()),
+                    ("QA_PM_FEEDS", "http".to_string()),
+                    ("QA_PM_JOURNAL_PATH", path_str(&journal_path)),
+                ],
+            ),
+            spawn(
+                &bin_dir,
+                &work_dir,
+                "risk-gateway",
+                &[
+                    ("QA_SEED", seed),
+                    ("QA_REDIS_URL", redis_url),
+                    ("QA_VAR_CALCULATOR_URL", VAR_CALCULATOR_URL.to_string()),
+                    ("QA_PORTFOLIO_MANAGER_URL", PORTFOLIO_MANAGER_URL.to_string()),
+                    ("QA_RISK_TRANSPORT", "shm".to_string()),
+                    ("QA_TRADING_MODE", "sandbox".to_string()),
+                    // Production-sized limits, so the scripted limits apply as written.
+                    ("QA_SANDBOX_LIMIT_MULTIPLIER", "1".to_string()),
+                ],
+            ),
+        ];
+
+        let http = reqwest::Client::new();
+        let mut platform = Platform {
+            exchange: MockExchange::new(http.clone(), PORTFOLIO_MANAGER_URL),
+            http,
+            risk,
+            services,
+            _redis: container,
+        };
+        platform.wait_until_up("var-calculator", &format!("{}/var/history", VAR_CALCULATOR_URL)).await;
+        platform.wait_until_up("portfolio-manager", &format!("{}/portfolio", PORTFOLIO_MANAGER_URL)).await;
+        platform.wait_until_up("risk-gateway", &format!("{}/mode", RISK_GATEWAY_URL)).await;
+        platform
+    }
+
+    async fn wait_until_up(&mut self, name: &str, url: &str) {
+        let started = Instant::now();
+        loop {
+            if let Ok(response) = self.http.get(url).send().await {
+                if response.status().is_success() {
+                    return;
+                }
+            }
+            let service = self.services.iter_mut().find(|s| s.name == name).expect("unknown service");
+            if let Ok(Some(status)) = service.child.try_wait() {
+                panic!("{} exited during startup ({}); see {}", name, status, service.log_path.display());
+            }
+            let log = service.log_path.display();
+            assert!(started.elapsed() < STARTUP_TIMEOUT, "{} did not come up; see {}", name, log);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+async fn start_redis() -> (String, Option<ContainerAsync<Redis>>) {
+    if let Ok(url) = std::env::var("QA_IT_REDIS_URL") {
+        let client = redis::Client::open(url.as_str()).expect("Invalid QA_IT_REDIS_URL");
+        let mut con = client.get_multiplexed_async_connection().await.expect("Failed to connect to QA_IT_REDIS_URL");
+        let _: () = redis::cmd("FLUSHDB").query_async(&mut con).await.expect("Failed to flush Redis");
+        return (url, None);
+    }
+    let container = Redis::default().start().await.expect("Failed to start Redis (is Docker running?)");
+    let port = container.get_host_port_ipv4(REDIS_PORT).await.expect("Redis port not mapped");
+    (format!("redis://127.0.0.1:{}/", port), Some(container))
+}
+
+fn spawn(bin_dir: &Path, work_dir: &Path, name: &'static str, env: &[(&str, String)]) -> Service {
+    let log_path = work_dir.join(format!("{}.log", name));
+    let log = std::fs::File::create(&log_path).expect("Failed to create service log");
+    let child = Command::new(bin_dir.join(name))
+        .current_dir(work_dir)
+        .envs(env.iter().map(|(key, value)| (*key, value.as_str())))
+        .stdout(log.try_clone().expect("Failed to share service log"))
+        .stderr(log)
+        .stdin(Stdio::null())
+        .spawn()
+        .unwrap_or_else(|e| {
+            panic!("Failed to start {} from {} ({}); build the services first", name, bin_dir.display(), e)
+        });
+    Service { name, child, log_path }
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+// --- Order Flow ---
+
+impl Platform {
+    /// Sends an order to the risk gateway and waits for its verdict. Approved
+    /// orders are executed by the mock exchange, which reports the fill to
+    /// the portfolio manager.
+    pub async fn submit(&mut self, order: &OrderRequest) -> RiskVerdict {
+        let payload = quantumarb_wire::encode(order);
+        self.risk.requests.try_push(&payload).expect("risk request ring is full");
+
+        let started = Instant::now();
+        let mut buf = Vec::with_capacity(risk_channel::SLOT_SIZE);
+        let verdict = loop {
+            if self.risk.verdicts.try_pop(&mut buf) {
+                let verdict: RiskVerdict = quantumarb_wire::decode(&buf).expect("undecodable verdict");
+                if verdict.order_id == order.order_id {
+                    break verdict;
+                }
+                continue;
+            }
+            assert!(started.elapsed() < VERDICT_TIMEOUT, "no verdict for order {}", order.order_id);
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        };
+
+        if verdict.approved {
+            self.exchange.execute(order).await;
+        }
+        verdict
+    }
+}
+
+// --- HTTP Helpers ---
+
+impl Platform {
+    pub async fn get<T: DeserializeOwned>(&self, url: &str) -> T {
+        let response = self.http.get(url).send().await.unwrap_or_else(|e| panic!("GET {} failed: {}", url, e));
+        assert!(response.status().is_success(), "GET {}: HTTP {}", url, response.status());
+        response.json().await.unwrap_or_else(|e| panic!("GET {}: bad body: {}", url, e))
+    }
+
+    pub async fn put<B: Serialize>(&self, url: &str, body: &B) {
+        let response =
+            self.http.put(url).json(body).send().await.unwrap_or_else(|e| panic!("PUT {} failed: {}", url, e));
+        assert!(response.status().is_success(), "PUT {}: HTTP {}", url, response.status());
+    }
+
+    pub async fn post<B: Serialize>(&self, url: &str, body: &B) {
+        let response =
+            self.http.post(url).json(body).send().await.unwrap_or_else(|e| panic!("POST {} failed: {}", url, e));
+        assert!(response.status().is_success(), "POST {}: HTTP {}", url, response.status());
+    }
+}
+
+/// Polls `probe` until it returns a value, for effects that cross a
+/// service's refresh interval.
+pub async fn eventually<T, F, Fut>(what: &str, timeout: Duration, mut probe: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let started = Instant::now();
+    loop {
+        if let Some(value) = probe().await {
+            return value;
+        }
+        assert!(started.elapsed() < timeout, "timed out waiting for {}", what);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
@@ -0,0 +1,183 @@
+/*
+ * QuantumArb 2.0 - Integration Tests: Scripted Order Flow
+ *
+ * File: tests/integration/main.rs
+ *
+ * Description:
+ * End-to-end test across the risk gateway, portfolio manager, VaR calculator
+ * and a mock exchange (see harness.rs). A scripted sequence of orders checks
+ * the behavior that only shows up when the services run together:
+ *
+ *   - the VaR calculator's output moves the risk gateway's dynamic limits,
+ *     and the next risk decision uses the adjusted limit;
+ *   - rejections carry the right reject code (size, kill switch, unknown
+ *     instrument, tick size, concentration);
+ *   - approved orders fill, and the portfolio manager's positions, fees and
+ *     P&L follow the fills and marks;
+ *   - the portfolio the manager reports feeds back into the gateway's
+ *     concentration check.
+ *
+ * The test is ignored by default: it needs the service binaries and either
+ * Docker (for the Redis container) or QA_IT_REDIS_URL. To run it:
+ *
+ *   cargo build --workspace --bins
+ *   cargo test --test integration -- --ignored --nocapture
+ */
+
+mod harness;
+mod mock_exchange;
+
+use harness::{eventually, Platform, PORTFOLIO_MANAGER_URL, RISK_GATEWAY_URL, VAR_CALCULATOR_URL};
+use quantumarb_wire::{HopStamps, OrderPriority, OrderRequest, OrderSide, RiskVerdict, TradingMode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use uuid::Uuid;
+
+const ACCOUNT_ID: u32 = 101;
+const BTC: u32 = 1;
+const ESZ25: u32 = 3;
+const VENUE_A: u32 = 1;
+/// Base order size limit for the run.
+const MAX_ORDER_SIZE: u32 = 40;
+/// The risk gateway re-reads VaR every 15s.
+const VAR_ADJUSTMENT_TIMEOUT: Duration = Duration::from_secs(45);
+/// The risk gateway refreshes portfolio exposures every 2s.
+const EXPOSURE_REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn order(instrument_id: u32, side: OrderSide, price: u64, size: u32) -> OrderRequest {
+    OrderRequest {
+        order_id: Uuid::new_v4(),
+        account_id: ACCOUNT_ID,
+        instrument_id,
+        side,
+        price,
+        size,
+        stamps: HopStamps::default(),
+        venue_id: VENUE_A,
+        mode: TradingMode::Sandbox,
+        priority: OrderPriority::Opportunistic,
+        strategy_id: String::new(),
+    }
+}
+
+fn reject_code(verdict: &RiskVerdict) -> String {
+    assert!(!verdict.approved, "expected a rejection, got an approval");
+    let reject = verdict.reject.as_ref().expect("rejection without a reason");
+    serde_json::to_value(reject.code).unwrap().as_str().unwrap().to_string()
+}
+
+fn assert_close(actual: f64, expected: f64, what: &str) {
+    assert!((actual - expected).abs() < 1e-6, "{}: expected {}, got {}", what, expected, actual);
+}
+
+#[tokio::test]
+#[ignore = "needs the service binaries and Docker or QA_IT_REDIS_URL"]
+async fn scripted_order_flow() {
+    // A small VaR 50 minutes ago: today's VaR is far above it, so the trend
+    // rule tightens limits even while VaR is under 5% of the portfolio.
+    let past = json!({
+        "confidence_level": 0.99,
+        "var_amount": 1000.0,
+        "portfolio_value": 750000.0,
+        "timestamp_utc": (chrono::Utc::now() - chrono::Duration::minutes(50)).to_rfc3339(),
+    });
+    let mut platform = Platform::start(&[past]).await;
+    let limits_url = format!("{}/limits/{}", RISK_GATEWAY_URL, ACCOUNT_ID);
+    let concentration_url = format!("{}/concentration-limits", RISK_GATEWAY_URL);
+    let portfolio_url = format!("{}/portfolio", PORTFOLIO_MANAGER_URL);
+
+    platform.put(&limits_url, &json!({ "max_order_size": MAX_ORDER_SIZE, "max_exposure": 5_000_000.0 })).await;
+    // Caps only bite later in the script, once there is a position to measure.
+    let loose_caps = json!({ "min_portfolio_exposure": 1.0e12 });
+    platform.put(&concentration_url, &loose_caps).await;
+
+    // --- VaR adjustment ---
+    let limits: Value = eventually("a VaR-driven limit adjustment", VAR_ADJUSTMENT_TIMEOUT, || async {
+        let limits: Value = platform.get(&limits_url).await;
+        (limits["current_max_order_size"].as_u64() != Some(MAX_ORDER_SIZE as u64)).then_some(limits)
+    })
+    .await;
+    let var: Value = platform.get(&format!("{}/var", VAR_CALCULATOR_URL)).await;
+    let var_ratio = var["var_amount"].as_f64().unwrap() / var["portfolio_value"].as_f64().unwrap();
+    let factor = if var_ratio > 0.05 { 0.75 } else { 0.9 };
+    let max_order_size = limits["current_max_order_size"].as_u64().unwrap() as u32;
+    assert_eq!(max_order_size, (MAX_ORDER_SIZE as f32 * factor as f32) as u32, "VaR ratio {:.4}", var_ratio);
+    assert_close(limits["current_max_exposure"].as_f64().unwrap(), 5_000_000.0 * factor, "current_max_exposure");
+
+    // --- Risk decisions ---
+    let verdict = platform.submit(&order(BTC, OrderSide::Buy, 60150_00, max_order_size + 1)).await;
+    assert_eq!(reject_code(&verdict), "RISK_ORDER_SIZE_LIMIT");
+    assert!(platform.exchange.fills.is_empty(), "a rejected order reached the exchange");
+
+    let verdict = platform.submit(&order(99, OrderSide::Buy, 100_00, 1)).await;
+    assert_eq!(reject_code(&verdict), "RISK_UNKNOWN_INSTRUMENT");
+
+    // ESZ25 trades in quarter points.
+    let verdict = platform.submit(&order(ESZ25, OrderSide::Buy, 5000_10, 1)).await;
+    assert_eq!(reject_code(&verdict), "RISK_INVALID_TICK_SIZE");
+
+    platform
+        .post(&format!("{}/kill-switch", RISK_GATEWAY_URL), &json!({ "engaged": true, "reason": "integration test" }))
+        .await;
+    let verdict = platform.submit(&order(BTC, OrderSide::Buy, 60150_00, 1)).await;
+    assert_eq!(reject_code(&verdict), "RISK_KILL_SWITCH_ENGAGED");
+    platform.post(&format!("{}/kill-switch", RISK_GATEWAY_URL), &json!({ "engaged": false })).await;
+
+    // --- Fill, position and fees ---
+    let verdict = platform.submit(&order(BTC, OrderSide::Buy, 60150_00, 10)).await;
+    assert!(verdict.approved, "buy rejected: {:?}", verdict.reject);
+    assert_eq!(platform.exchange.fills.len(), 1);
+
+    let portfolio: Value = eventually("the buy fill to be booked", EXPOSURE_REFRESH_TIMEOUT, || async {
+        let portfolio: Value = platform.get(&portfolio_url).await;
+        (portfolio["positions"]["BTC"]["quantity"].as_i64() == Some(10)).then_some(portfolio)
+    })
+    .await;
+    assert_close(portfolio["positions"]["BTC"]["average_entry_price"].as_f64().unwrap(), 60150.0, "entry price");
+    assert!(portfolio["total_fees"].as_f64().unwrap() > 0.0, "no fees charged on the fill");
+
+    // --- Mark to market ---
+    let mark = json!({ "symbol": "BTC", "price": 60250.0 });
+    platform.post(&format!("{}/portfolio/prices", PORTFOLIO_MANAGER_URL), &mark).await;
+    let portfolio: Value = eventually("the BTC mark", EXPOSURE_REFRESH_TIMEOUT, || async {
+        let portfolio: Value = platform.get(&portfolio_url).await;
+        (portfolio["positions"]["BTC"]["current_market_price"].as_f64() == Some(60250.0)).then_some(portfolio)
+    })
+    .await;
+    assert_close(portfolio["positions"]["BTC"]["unrealized_pnl"].as_f64().unwrap(), 1000.0, "unrealized P&L");
+    assert_close(portfolio["total_portfolio_value"].as_f64().unwrap(), 602_500.0, "portfolio value");
+    let fees = portfolio["total_fees"].as_f64().unwrap();
+    assert_close(portfolio["net_pnl"].as_f64().unwrap(), 1000.0 - fees, "net P&L");
+
+    // --- Concentration, from the portfolio the manager reports ---
+    platform.put(&concentration_url, &json!({ "min_portfolio_exposure": 100_000.0, "max_symbol_pct": 0.4 })).await;
+    let started = std::time::Instant::now();
+    loop {
+        let verdict = platform.submit(&order(BTC, OrderSide::Buy, 60250_00, 1)).await;
+        if !verdict.approved && reject_code(&verdict) == "RISK_CONCENTRATION_LIMIT" {
+            break;
+        }
+        // Until the gateway's next exposure refresh the probe may fill; keep
+        // the expected position in step.
+        assert!(started.elapsed() < EXPOSURE_REFRESH_TIMEOUT, "concentration cap never applied: {:?}", verdict);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    platform.put(&concentration_url, &loose_caps).await;
+    let probe_fills: i64 = platform.exchange.fills[1..].iter().map(|fill| fill.quantity).sum();
+
+    // --- Realized P&L on a sell through zero ---
+    let long = 10 + probe_fills;
+    let sell_size = (long + 5) as u32;
+    assert!(sell_size <= max_order_size, "probe fills pushed the flip past the size limit");
+    let verdict = platform.submit(&order(BTC, OrderSide::Sell, 60350_00, sell_size)).await;
+    assert!(verdict.approved, "sell rejected: {:?}", verdict.reject);
+    let portfolio: Value = eventually("the sell fill to be booked", EXPOSURE_REFRESH_TIMEOUT, || async {
+        let portfolio: Value = platform.get(&portfolio_url).await;
+        (portfolio["positions"]["BTC"]["quantity"].as_i64() == Some(-5)).then_some(portfolio)
+    })
+    .await;
+    // Realized on the long closed out: bought 10 at 60150 and any probes at 60250.
+    let entry_cost = 10.0 * 60150.0 + probe_fills as f64 * 60250.0;
+    let expected_realized = long as f64 * 60350.0 - entry_cost;
+    assert_close(portfolio["realized_pnl"].as_f64().unwrap(), expected_realized, "realized P&L");
+}
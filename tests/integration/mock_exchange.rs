@@ -0,0 +1,78 @@
+/*
+ * QuantumArb 2.0 - Integration Tests: Mock Exchange
+ *
+ * File: tests/integration/mock_exchange.rs
+ *
+ * Description:
+ * Stands in for the exchange gateway and the venue behind it. Every order the
+ * risk gateway approves fills in full at its limit price, taking liquidity,
+ * and the fill is sent to the portfolio manager as the exchange gateway's
+ * execution report would be. Fills are kept so tests can check what traded.
+ */
+
+use quantumarb_money::Price;
+use quantumarb_refdata::ReferenceData;
+use quantumarb_wire::{OrderRequest, OrderSide};
+use serde::Serialize;
+
+/// A fill as the portfolio manager consumes it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Fill {
+    pub symbol: String,
+    /// Positive for buys, negative for sells.
+    pub quantity: i64,
+    pub price: Price,
+    pub venue: String,
+    pub counterparty: String,
+    pub liquidity: &'static str,
+}
+
+pub struct MockExchange {
+    http: reqwest::Client,
+    fills_url: String,
+    instruments: ReferenceData,
+    pub fills: Vec<Fill>,
+}
+
+impl MockExchange {
+    pub fn new(http: reqwest::Client, portfolio_manager_url: &str) -> MockExchange {
+        MockExchange {
+            http,
+            fills_url: format!("{}/portfolio/fills", portfolio_manager_url),
+            instruments: ReferenceData::seeded(),
+            fills: Vec::new(),
+        }
+    }
+
+    /// Fills `order` and reports the fill to the portfolio manager.
+    pub async fn execute(&mut self, order: &OrderRequest) -> Fill {
+        let definition = self.instruments.get(order.instrument_id).expect("approved order for an unknown instrument");
+        let symbol = definition.symbol.clone();
+        let venue = venue_name(order.venue_id).to_string();
+        let fill = Fill {
+            symbol,
+            quantity: match order.side {
+                OrderSide::Buy => order.size as i64,
+                OrderSide::Sell => -(order.size as i64),
+            },
+            price: definition.price(order.price),
+            counterparty: venue.clone(),
+            venue,
+            liquidity: "Taker",
+        };
+        let response = self.http.post(&self.fills_url).json(&fill).send().await.expect("portfolio manager unreachable");
+        assert!(response.status().is_success(), "fill rejected by the portfolio manager: HTTP {}", response.status());
+        self.fills.push(fill.clone());
+        fill
+    }
+}
+
+/// Venue names as the risk gateway knows them.
+fn venue_name(venue_id: u32) -> &'static str {
+    match venue_id {
+        1 => "VENUE_A",
+        2 => "VENUE_B",
+        3 => "CME",
+        _ => "UNKNOWN",
+    }
+}